@@ -0,0 +1,78 @@
+//! 自定义控制端
+//!
+//! 使用 `SignalingClient` 连接被控端信令服务，加入房间、提交 PIN 并切换画面光标，
+//! 打印收到的所有信令事件。可作为自建 Viewer 的起点。
+//!
+//! ```bash
+//! cargo run --example custom_controller -- ws://192.168.1.5:9527/ws 123456
+//! ```
+
+use anyhow::{anyhow, Result};
+use sscontrol::webrtc::signaling::{SignalingClient, SignalingEvent};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let mut args = std::env::args().skip(1);
+    let url = args
+        .next()
+        .ok_or_else(|| anyhow!("用法: custom_controller <ws://host:port/ws> [PIN]"))?;
+    let pin = args.next();
+
+    let client = SignalingClient::new(url);
+
+    // 事件回调是同步的，转发到通道中异步处理
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    client.on_event(move |event| {
+        let _ = tx.send(event);
+    }).await;
+
+    client.connect().await?;
+    client.join_room("default".to_string()).await?;
+
+    while let Some(event) = rx.recv().await {
+        match event {
+            SignalingEvent::Joined { peers, .. } => {
+                let ids: Vec<_> = peers.iter().map(|p| p.id.as_str()).collect();
+                println!("已加入房间，成员: {}", ids.join(", "));
+            }
+            SignalingEvent::Hello { host } => {
+                println!("被控端时区: {}", host.timezone.as_deref().unwrap_or("未知"));
+            }
+            SignalingEvent::Queued { position, queue_length } => {
+                println!("排队中: {}/{}", position, queue_length);
+            }
+            SignalingEvent::PinRequired => match &pin {
+                Some(pin) => client.send_pin(pin).await?,
+                None => {
+                    println!("被控端要求 PIN，请作为第二个参数传入");
+                    break;
+                }
+            },
+            SignalingEvent::PinResult { accepted: true, .. } => {
+                println!("PIN 验证通过，演示切换画面光标");
+                client.send_cursor(false).await?;
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                client.send_cursor(true).await?;
+                // 自建 Viewer 在这里创建 PeerConnection 并调用 send_offer()
+                break;
+            }
+            SignalingEvent::PinResult { remaining_attempts, retry_after_secs, .. } => {
+                println!(
+                    "PIN 错误 (剩余次数: {:?}, 锁定秒数: {:?})",
+                    remaining_attempts, retry_after_secs
+                );
+                break;
+            }
+            SignalingEvent::Error { message } => println!("错误: {}", message),
+            SignalingEvent::Disconnected => break,
+            other => println!("{:?}", other),
+        }
+    }
+
+    client.disconnect().await?;
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! 在自己的程序中嵌入被控端信令服务
+//!
+//! 启动内嵌信令服务器 (带会话上限和一次性 PIN)，并处理 Viewer 的信令事件。
+//! 实际的视频推流由 `sscontrol host` 完成，这里只演示嵌入方需要对接的接口。
+//!
+//! ```bash
+//! cargo run --example embed_host -- 9527
+//! ```
+
+use anyhow::Result;
+use sscontrol::capture;
+use sscontrol::signaling::{CapacityConfig, EmbeddedSignalingServer, HostSignalEvent, PinConfig};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let port = std::env::args()
+        .nth(1)
+        .map(|p| p.parse::<u16>())
+        .transpose()?
+        .unwrap_or(9527);
+
+    // 屏幕信息 (当前平台不支持捕获时仅提示)
+    match capture::create_capturer(None) {
+        Ok(capturer) => println!("屏幕尺寸: {}x{}", capturer.width(), capturer.height()),
+        Err(e) => println!("屏幕捕获不可用: {}", e),
+    }
+
+    let mut server = EmbeddedSignalingServer::new(port);
+    server.set_capacity(CapacityConfig {
+        max_sessions: Some(1),
+        queue_enabled: true,
+        ..Default::default()
+    });
+    server.set_pin(Some(PinConfig::default()));
+
+    let actual_port = server.start().await?;
+    let mut events = server
+        .take_host_events()
+        .expect("start 之后应能取得 Host 事件接收器");
+
+    println!("信令服务: ws://0.0.0.0:{}/ws", actual_port);
+    if let Some(pin) = server.current_pin().await {
+        println!("连接 PIN: {}", pin);
+    }
+    println!("按 Ctrl+C 退出");
+
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let Some(event) = event else { break };
+                match event {
+                    HostSignalEvent::ViewerJoined { peer_id } => println!("[+] {}", peer_id),
+                    HostSignalEvent::ViewerLeft { peer_id } => println!("[-] {}", peer_id),
+                    HostSignalEvent::ViewerResumed { peer_id, session_id } => {
+                        // 断线重连: 把 session_id 对应的 WebRTC 会话转交给新的 peer_id
+                        println!("[~] {} 恢复会话 {}", peer_id, session_id)
+                    }
+                    HostSignalEvent::PinChanged { pin } => println!("新的连接 PIN: {}", pin),
+                    HostSignalEvent::Offer { from, session_id, .. } => {
+                        // 嵌入方在这里创建 WebRTC 会话并调用 server.send_answer()
+                        println!("收到 {} 的 Offer (会话 {})", from, session_id);
+                    }
+                    HostSignalEvent::Ice { from, .. } => println!("收到 {} 的 ICE 候选", from),
+                    HostSignalEvent::Punch { from, .. } => {
+                        // 不打洞时也应回复，Viewer 收到 None 后直接走常规 ICE
+                        server.send_punch(&from, None).await;
+                    }
+                    HostSignalEvent::Cursor { from, show } => println!("{} 切换光标: {}", from, show),
+                    HostSignalEvent::Chat { message } => {
+                        println!("[聊天] {}: {}", message.from, message.text);
+                        server.post_chat("host", "已收到").await;
+                    }
+                    HostSignalEvent::FileReceived { from, path } => {
+                        println!("收到 {} 上传的文件: {}", from, path.display())
+                    }
+                    HostSignalEvent::TicketRequested { from, .. } => {
+                        // Web 查看器打开 /video 前请求票据: 嵌入方在这里审批，允许后下发
+                        server.grant_ticket(&from).await;
+                    }
+                    // 其他事件由嵌入方按需处理
+                    _ => {}
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    server.stop();
+    Ok(())
+}
@@ -0,0 +1,53 @@
+//! 程序化输入注入
+//!
+//! 用 `InputSimulator` 让鼠标在屏幕中央画一个方框并输入一段文字。
+//! 加 `--dry-run` 时只打印会发送的输入事件 (JSON，与网络协议一致)，不操作本机。
+//!
+//! ```bash
+//! cargo run --example input_injection -- --dry-run
+//! ```
+
+use anyhow::Result;
+use sscontrol::input::{self, InputEvent, MouseButton};
+use std::time::Duration;
+
+/// 方框四个角 (归一化坐标)
+const SQUARE: [(f64, f64); 5] = [(0.4, 0.4), (0.6, 0.4), (0.6, 0.6), (0.4, 0.6), (0.4, 0.4)];
+
+fn script() -> Vec<InputEvent> {
+    let mut events = vec![InputEvent::mouse_move(SQUARE[0].0, SQUARE[0].1)];
+    events.push(InputEvent::mouse_click(MouseButton::Left, true));
+    for &(x, y) in &SQUARE[1..] {
+        events.push(InputEvent::mouse_move(x, y));
+    }
+    events.push(InputEvent::mouse_click(MouseButton::Left, false));
+
+    for key in "hello".chars().map(String::from).chain(["Enter".to_string()]) {
+        events.push(InputEvent::KeyEvent { key: key.clone(), pressed: true });
+        events.push(InputEvent::KeyEvent { key, pressed: false });
+    }
+    events
+}
+
+fn main() -> Result<()> {
+    let dry_run = std::env::args().any(|arg| arg == "--dry-run");
+    let events = script();
+
+    if dry_run {
+        for event in &events {
+            println!("{}", serde_json::to_string(event)?);
+        }
+        return Ok(());
+    }
+
+    let mut simulator = input::create_input_simulator()?;
+    println!("3 秒后开始注入输入，请切换到要操作的窗口...");
+    std::thread::sleep(Duration::from_secs(3));
+
+    for event in &events {
+        simulator.handle_event(event)?;
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    println!("已发送 {} 个输入事件", events.len());
+    Ok(())
+}
@@ -0,0 +1,88 @@
+//! 单独使用捕获 + 编码流水线录制屏幕
+//!
+//! 不经过网络，按固定帧率捕获主显示器并用 H.264 编码，写出 Annex-B 码流，
+//! 再调用 ffmpeg 无损封装为 MP4 (未安装 ffmpeg 时保留 .h264 文件)。
+//!
+//! ```bash
+//! cargo run --release --features h264 --example record_screen -- screen.mp4 10
+//! ```
+
+use anyhow::{anyhow, Result};
+use sscontrol::capture;
+use sscontrol::encoder::alignment::FrameAligner;
+use sscontrol::encoder::{Encoder, H264Encoder};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const FPS: u32 = 30;
+const BITRATE_KBPS: u32 = 4000;
+
+fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let mut args = std::env::args().skip(1);
+    let output = PathBuf::from(args.next().unwrap_or_else(|| "screen.mp4".to_string()));
+    let seconds: u64 = args.next().map(|s| s.parse()).transpose()?.unwrap_or(10);
+
+    let mut capturer = capture::create_capturer(None)?;
+    capturer.start()?;
+
+    // YUV420 要求偶数宽高
+    let aligner = FrameAligner::even(capturer.width(), capturer.height())?;
+    let mut encoder = H264Encoder::new(aligner.width(), aligner.height(), FPS, BITRATE_KBPS)?;
+
+    let raw_path = output.with_extension("h264");
+    let mut raw = std::io::BufWriter::new(std::fs::File::create(&raw_path)?);
+
+    println!("录制 {} 秒 ({}x{} @ {} fps)...", seconds, aligner.width(), aligner.height(), FPS);
+    let frame_interval = Duration::from_secs(1) / FPS;
+    let deadline = Instant::now() + Duration::from_secs(seconds);
+    let mut frames = 0u64;
+
+    while Instant::now() < deadline {
+        let start = Instant::now();
+        match capturer.capture() {
+            Ok(frame) => {
+                if let Some(packet) = encoder.encode(&aligner.align(frame))? {
+                    raw.write_all(&packet.data)?;
+                    frames += 1;
+                }
+            }
+            // 画面无变化时捕获会超时，跳过即可
+            Err(e) => tracing::debug!("捕获失败: {}", e),
+        }
+        if let Some(remaining) = frame_interval.checked_sub(start.elapsed()) {
+            std::thread::sleep(remaining);
+        }
+    }
+
+    while let Some(packet) = encoder.flush()? {
+        raw.write_all(&packet.data)?;
+        frames += 1;
+    }
+    raw.flush()?;
+    capturer.stop()?;
+    println!("已编码 {} 帧: {}", frames, raw_path.display());
+
+    // 封装为 MP4 (仅复制码流，不重新编码)
+    let status = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-framerate", &FPS.to_string(), "-i"])
+        .arg(&raw_path)
+        .args(["-c", "copy"])
+        .arg(&output)
+        .status();
+    match status {
+        Ok(status) if status.success() => {
+            std::fs::remove_file(&raw_path)?;
+            println!("已保存: {}", output.display());
+            Ok(())
+        }
+        Ok(status) => Err(anyhow!("ffmpeg 封装失败: {}", status)),
+        Err(e) => {
+            println!("未找到 ffmpeg ({})，保留原始码流: {}", e, raw_path.display());
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! 程序化远程控制
+//!
+//! 用 `ControlSession` 连接被控端，统计收到的视频帧，并在远端屏幕中央点击一次。
+//! 可作为自动化脚本、端到端测试的起点。
+//!
+//! ```bash
+//! cargo run --features webrtc --example remote_control -- ws://192.168.1.5:9527/ws 123456
+//! ```
+
+use anyhow::{anyhow, Result};
+use sscontrol::input::MouseButton;
+use sscontrol::webrtc::control_session::{ControlOptions, ControlSession};
+use std::time::Duration;
+
+/// 统计视频帧的时长
+const WATCH_DURATION: Duration = Duration::from_secs(5);
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let mut args = std::env::args().skip(1);
+    let url = args
+        .next()
+        .ok_or_else(|| anyhow!("用法: remote_control <ws://host:port/ws> [PIN]"))?;
+    let options = ControlOptions {
+        pin: args.next(),
+        ..Default::default()
+    };
+
+    let session = ControlSession::connect(&url, options).await?;
+    println!("已连接，会话权限: {}", session.permissions());
+
+    let (mut frames, mut key_frames, mut bytes) = (0, 0, 0);
+    let _ = tokio::time::timeout(WATCH_DURATION, async {
+        while let Some(sample) = session.next_sample().await {
+            frames += 1;
+            key_frames += sample.is_key_frame as u32;
+            bytes += sample.data.len();
+        }
+    })
+    .await;
+    println!(
+        "{} 秒内收到 {} 帧 (关键帧 {})，共 {:.1} KB",
+        WATCH_DURATION.as_secs(),
+        frames,
+        key_frames,
+        bytes as f64 / 1024.0
+    );
+
+    if session.permissions().input {
+        session.send_mouse_move(0.5, 0.5).await?;
+        session.send_mouse_click(MouseButton::Left, true).await?;
+        session.send_mouse_click(MouseButton::Left, false).await?;
+        println!("已在屏幕中央点击");
+    }
+
+    session.close().await
+}
@@ -0,0 +1,405 @@
+//! 会话审计日志
+//!
+//! 记录谁在什么时间、从哪里连接，通过了哪些验证，获得了哪些权限以及做了什么操作。
+//! 日志是只追加的 JSON Lines 文件，每条记录带有序号和前一条记录的哈希:
+//!
+//! `hash = SHA-256(prev || 记录内容)`
+//!
+//! 修改、删除或插入任意一条记录都会使之后的哈希链断开，可用 `sscontrol audit verify` 检查。
+//! 哈希链无法发现截掉文件末尾的记录，需要时可定期把最新的哈希另行保存
+
+#![allow(dead_code)]
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::power::PowerAction;
+use crate::quality::profile::QualityProfile;
+use crate::signaling::SessionPermissions;
+
+/// 第一条记录的 `prev`
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// 验证方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    /// 一次性 PIN 或无人值守访问密钥
+    Pin,
+    /// TOTP 双因素验证码
+    Totp,
+    /// 断线重连时出示会话令牌
+    Resume,
+    /// 已配对设备的签名
+    Device,
+}
+
+/// 审计事件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// Viewer 建立连接
+    Connect { peer: String, addr: String, transport: String },
+    /// 验证结果
+    Auth { peer: String, method: AuthMethod, success: bool },
+    /// 连接审批结果 (允许时记录授予的权限)
+    Approval {
+        peer: String,
+        accepted: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        permissions: Option<SessionPermissions>,
+    },
+    /// 获得输入控制权 (`peer` 与其他事件一样为 peer_id，WebRTC 会话的会话 ID 记录在 [`AuditRecord::session_id`])
+    ControlGranted { peer: String },
+    /// 文件传输
+    FileTransferred { peer: String, name: String, bytes: u64 },
+    /// 电源操作
+    Power { peer: String, action: PowerAction },
+    /// Web 查看器开始接收 `/video` 视频流
+    VideoStream { peer: String },
+    /// Web 查看器或 `sscontrol info` 通过 `/sysinfo` 查看系统信息
+    SysInfo { peer: String },
+    /// 切换画面中是否包含鼠标指针
+    Cursor { peer: String, show: bool },
+    /// 切换画质预设
+    Profile { peer: String, profile: QualityProfile },
+    /// Web 查看器通过 `/chat` 发送聊天消息 (不记录内容)
+    Chat { peer: String },
+    /// Viewer 断开
+    Disconnect { peer: String },
+}
+
+impl std::fmt::Display for AuditEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditEvent::Connect { peer, addr, transport } => write!(f, "{} 连接 ({}, {})", peer, addr, transport),
+            AuditEvent::Auth { peer, method, success } => {
+                let method = match method {
+                    AuthMethod::Pin => "PIN",
+                    AuthMethod::Totp => "TOTP",
+                    AuthMethod::Resume => "会话令牌",
+                    AuthMethod::Device => "已配对设备",
+                };
+                write!(f, "{} {} 验证{}", peer, method, if *success { "通过" } else { "失败" })
+            }
+            AuditEvent::Approval { peer, accepted: true, permissions } => match permissions {
+                Some(permissions) => write!(f, "{} 已允许 ({})", peer, permissions),
+                None => write!(f, "{} 已允许", peer),
+            },
+            AuditEvent::Approval { peer, accepted: false, .. } => write!(f, "{} 已拒绝", peer),
+            AuditEvent::ControlGranted { peer } => write!(f, "{} 获得控制权", peer),
+            AuditEvent::FileTransferred { peer, name, bytes } => {
+                write!(f, "{} 传输文件 {} ({} 字节)", peer, name, bytes)
+            }
+            AuditEvent::Power { peer, action } => write!(f, "{} 请求{}", peer, action.label()),
+            AuditEvent::VideoStream { peer } => write!(f, "{} 开始接收视频流", peer),
+            AuditEvent::SysInfo { peer } => write!(f, "{} 查看系统信息", peer),
+            AuditEvent::Cursor { peer, show } => {
+                write!(f, "{} 切换画面鼠标指针: {}", peer, if *show { "显示" } else { "隐藏" })
+            }
+            AuditEvent::Profile { peer, profile } => write!(f, "{} 切换画质预设: {}", peer, profile),
+            AuditEvent::Chat { peer } => write!(f, "{} 发送聊天消息", peer),
+            AuditEvent::Disconnect { peer } => write!(f, "{} 断开", peer),
+        }
+    }
+}
+
+impl AuditEvent {
+    /// 事件涉及的 Viewer
+    pub fn peer(&self) -> &str {
+        match self {
+            AuditEvent::Connect { peer, .. }
+            | AuditEvent::Auth { peer, .. }
+            | AuditEvent::Approval { peer, .. }
+            | AuditEvent::ControlGranted { peer }
+            | AuditEvent::FileTransferred { peer, .. }
+            | AuditEvent::Power { peer, .. }
+            | AuditEvent::VideoStream { peer }
+            | AuditEvent::SysInfo { peer }
+            | AuditEvent::Cursor { peer, .. }
+            | AuditEvent::Profile { peer, .. }
+            | AuditEvent::Chat { peer }
+            | AuditEvent::Disconnect { peer } => peer,
+        }
+    }
+}
+
+/// 参与哈希的记录内容 (没有会话 ID 的记录与旧版本的哈希一致)
+#[derive(Serialize)]
+struct RecordBody<'a> {
+    seq: u64,
+    time: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session_id: Option<&'a str>,
+    #[serde(flatten)]
+    event: &'a AuditEvent,
+}
+
+/// 日志中的一条记录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// 序号 (从 1 开始连续递增)
+    pub seq: u64,
+    /// UTC 时间 (RFC 3339)
+    pub time: String,
+    /// 会话 ID (信令服务器在 Offer 时分配，同一会话的记录可据此关联；协商之前的事件没有)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+    /// 前一条记录的哈希
+    pub prev: String,
+    /// 本条记录的哈希
+    pub hash: String,
+}
+
+impl AuditRecord {
+    fn new(seq: u64, time: String, session_id: Option<String>, event: AuditEvent, prev: String) -> Result<Self> {
+        let hash = chain_hash(&prev, seq, &time, session_id.as_deref(), &event)?;
+        Ok(Self { seq, time, session_id, event, prev, hash })
+    }
+
+    /// 按内容重新计算的哈希
+    fn expected_hash(&self) -> Result<String> {
+        chain_hash(&self.prev, self.seq, &self.time, self.session_id.as_deref(), &self.event)
+    }
+}
+
+fn chain_hash(prev: &str, seq: u64, time: &str, session_id: Option<&str>, event: &AuditEvent) -> Result<String> {
+    let body = serde_json::to_string(&RecordBody { seq, time, session_id, event })?;
+    let mut hasher = Sha256::new();
+    hasher.update(prev.as_bytes());
+    hasher.update(body.as_bytes());
+    Ok(hex::encode(hasher.finalize()))
+}
+
+struct Writer {
+    file: File,
+    seq: u64,
+    last_hash: String,
+}
+
+/// 审计日志 (未配置路径时不记录)
+#[derive(Default)]
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    writer: Mutex<Option<Writer>>,
+}
+
+impl AuditLog {
+    /// 不记录的审计日志
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// 打开 (或创建) 审计日志，接在已有记录之后继续哈希链
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).with_context(|| format!("创建审计日志目录 {} 失败", parent.display()))?;
+        }
+
+        let (seq, last_hash) = match last_record(&path)? {
+            Some(record) => (record.seq, record.hash),
+            None => (0, GENESIS_HASH.to_string()),
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("打开审计日志 {} 失败", path.display()))?;
+
+        Ok(Self {
+            path: Some(path),
+            writer: Mutex::new(Some(Writer { file, seq, last_hash })),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// 追加一条记录 (写入失败只记录警告，不影响会话)
+    pub fn record(&self, event: AuditEvent) {
+        self.record_for_session(None, event);
+    }
+
+    /// 追加一条记录，并记下事件所属的会话 ID
+    pub fn record_for_session(&self, session_id: Option<&str>, event: AuditEvent) {
+        if let Err(e) = self.try_record(session_id, event) {
+            tracing::warn!("写入审计日志失败: {}", e);
+        }
+    }
+
+    fn try_record(&self, session_id: Option<&str>, event: AuditEvent) -> Result<()> {
+        let mut guard = self.writer.lock().map_err(|_| anyhow!("审计日志状态已损坏"))?;
+        let Some(writer) = guard.as_mut() else {
+            return Ok(());
+        };
+
+        let time = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        let record = AuditRecord::new(
+            writer.seq + 1,
+            time,
+            session_id.map(str::to_string),
+            event,
+            writer.last_hash.clone(),
+        )?;
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+        writer.file.write_all(line.as_bytes())?;
+        writer.file.sync_data()?;
+
+        writer.seq = record.seq;
+        writer.last_hash = record.hash;
+        Ok(())
+    }
+}
+
+/// 读取全部记录
+pub fn read_records(path: impl AsRef<Path>) -> Result<Vec<AuditRecord>> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("打开审计日志 {} 失败", path.display()))?;
+    let mut records = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|e| anyhow!("第 {} 行无法解析: {}", index + 1, e))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// 最后一条记录 (文件不存在或为空时返回 None)
+fn last_record(path: &Path) -> Result<Option<AuditRecord>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let file = File::open(path).with_context(|| format!("打开审计日志 {} 失败", path.display()))?;
+    let mut last = None;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            last = Some(line);
+        }
+    }
+    last.map(|line| serde_json::from_str(&line).map_err(|e| anyhow!("审计日志末尾的记录已损坏: {}", e)))
+        .transpose()
+}
+
+/// 校验哈希链，返回记录条数；链断开时返回第一处问题
+pub fn verify(records: &[AuditRecord]) -> Result<usize> {
+    let mut prev = GENESIS_HASH.to_string();
+    for (index, record) in records.iter().enumerate() {
+        let expected_seq = index as u64 + 1;
+        if record.seq != expected_seq {
+            return Err(anyhow!("第 {} 条记录的序号为 {}，记录可能被删除或插入", expected_seq, record.seq));
+        }
+        if record.prev != prev {
+            return Err(anyhow!("第 {} 条记录与前一条记录的哈希不衔接", record.seq));
+        }
+        if record.expected_hash()? != record.hash {
+            return Err(anyhow!("第 {} 条记录的内容与哈希不符，记录已被修改", record.seq));
+        }
+        prev = record.hash.clone();
+    }
+    Ok(records.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("sscontrol-audit-{}-{}.log", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_chain_survives_reopen() {
+        let path = temp_path("reopen");
+        {
+            let log = AuditLog::open(&path).unwrap();
+            log.record(AuditEvent::Connect {
+                peer: "viewer_0".to_string(),
+                addr: "192.168.1.20".to_string(),
+                transport: "websocket".to_string(),
+            });
+            log.record(AuditEvent::Auth { peer: "viewer_0".to_string(), method: AuthMethod::Pin, success: true });
+        }
+        let log = AuditLog::open(&path).unwrap();
+        log.record(AuditEvent::Approval {
+            peer: "viewer_0".to_string(),
+            accepted: true,
+            permissions: Some(SessionPermissions::view_only()),
+        });
+        log.record_for_session(Some("5f0c"), AuditEvent::Disconnect { peer: "viewer_0".to_string() });
+
+        let records = read_records(&path).unwrap();
+        assert_eq!(verify(&records).unwrap(), 4);
+        assert_eq!(records[2].seq, 3);
+        assert_eq!(records[2].prev, records[1].hash);
+        assert_eq!(records[2].session_id, None);
+        assert_eq!(records[3].event.peer(), "viewer_0");
+        assert_eq!(records[3].session_id.as_deref(), Some("5f0c"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_detects_tampering() {
+        let path = temp_path("tamper");
+        let log = AuditLog::open(&path).unwrap();
+        for action in [PowerAction::Lock, PowerAction::Reboot, PowerAction::Shutdown] {
+            log.record(AuditEvent::Power { peer: "web".to_string(), action });
+        }
+        let records = read_records(&path).unwrap();
+        assert!(verify(&records).is_ok());
+
+        // 修改内容
+        let mut modified = records.clone();
+        modified[1].event = AuditEvent::Power { peer: "web".to_string(), action: PowerAction::Lock };
+        assert!(verify(&modified).unwrap_err().to_string().contains("第 2 条"));
+
+        // 删除中间的记录
+        let mut removed = records.clone();
+        removed.remove(1);
+        assert!(verify(&removed).is_err());
+
+        // 会话 ID 同样受哈希保护
+        let mut reassigned = records.clone();
+        reassigned[0].session_id = Some("5f0c".to_string());
+        assert!(verify(&reassigned).unwrap_err().to_string().contains("第 1 条"));
+
+        // 整条重算哈希也无法接上后面的记录
+        let mut rehashed = records.clone();
+        rehashed[0] = AuditRecord::new(
+            1,
+            rehashed[0].time.clone(),
+            None,
+            rehashed[1].event.clone(),
+            GENESIS_HASH.to_string(),
+        )
+        .unwrap();
+        assert!(verify(&rehashed).unwrap_err().to_string().contains("第 2 条"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_disabled_log() {
+        let log = AuditLog::disabled();
+        assert!(!log.is_enabled());
+        log.record(AuditEvent::Disconnect { peer: "viewer_0".to_string() });
+    }
+}
@@ -0,0 +1,275 @@
+//! 鼠标指针合成
+//!
+//! DXGI Desktop Duplication、GDI BitBlt 和 CGDisplay 截图得到的桌面图像都不包含鼠标指针。
+//! 需要在视频中显示远端光标时，由捕获器取得指针形状和位置后合成到 CPU 帧上；
+//! 关闭时由 Viewer 根据元数据在本地绘制光标。
+//!
+//! ## 指针形状
+//! - 彩色指针: 32 位带 Alpha，按 Alpha 混合
+//! - 单色指针: AND/XOR 双掩码，支持反色
+//! - 掩码彩色指针: Alpha 字节作为掩码，0xFF 表示与屏幕颜色异或
+
+#![allow(dead_code)]
+
+use super::Frame;
+
+/// 内置箭头光标 (无法取得系统指针形状时使用)
+///
+/// `#` 为黑色描边，`.` 为白色填充，空格透明
+const ARROW: [&str; 19] = [
+    "#           ",
+    "##          ",
+    "#.#         ",
+    "#..#        ",
+    "#...#       ",
+    "#....#      ",
+    "#.....#     ",
+    "#......#    ",
+    "#.......#   ",
+    "#........#  ",
+    "#.........# ",
+    "#......#####",
+    "#...#..#    ",
+    "#..# #..#   ",
+    "#.#  #..#   ",
+    "##    #..#  ",
+    "#     #..#  ",
+    "       #..# ",
+    "       ###  ",
+];
+
+/// 鼠标指针形状 (RGBA)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CursorShape {
+    pub width: u32,
+    pub height: u32,
+    /// 热点 (指针实际指向的位置)
+    pub hotspot_x: u32,
+    pub hotspot_y: u32,
+    /// RGBA 像素，非异或像素按 Alpha 混合
+    pub pixels: Vec<u8>,
+    /// 逐像素异或标记 (为空表示没有异或像素)，异或像素的 RGB 与屏幕颜色异或
+    pub xor: Vec<bool>,
+}
+
+impl CursorShape {
+    /// 内置箭头光标
+    pub fn arrow() -> Self {
+        let width = ARROW[0].len() as u32;
+        let height = ARROW.len() as u32;
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for row in ARROW {
+            for c in row.bytes() {
+                pixels.extend_from_slice(match c {
+                    b'#' => &[0, 0, 0, 255],
+                    b'.' => &[255, 255, 255, 255],
+                    _ => &[0, 0, 0, 0],
+                });
+            }
+        }
+
+        Self {
+            width,
+            height,
+            hotspot_x: 0,
+            hotspot_y: 0,
+            pixels,
+            xor: Vec::new(),
+        }
+    }
+
+    /// 解析彩色指针 (32 位 BGRA，带 Alpha)
+    pub fn from_color(width: u32, height: u32, pitch: usize, data: &[u8], hotspot: (u32, u32)) -> Option<Self> {
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height as usize {
+            let row = data.get(y * pitch..y * pitch + width as usize * 4)?;
+            for bgra in row.chunks_exact(4) {
+                pixels.extend_from_slice(&[bgra[2], bgra[1], bgra[0], bgra[3]]);
+            }
+        }
+
+        Some(Self {
+            width,
+            height,
+            hotspot_x: hotspot.0,
+            hotspot_y: hotspot.1,
+            pixels,
+            xor: Vec::new(),
+        })
+    }
+
+    /// 解析掩码彩色指针 (32 位 BGRA，Alpha 字节为掩码)
+    ///
+    /// 掩码为 0 时直接使用指针颜色；为 0xFF 时与屏幕颜色异或
+    pub fn from_masked_color(width: u32, height: u32, pitch: usize, data: &[u8], hotspot: (u32, u32)) -> Option<Self> {
+        let mut shape = Self::from_color(width, height, pitch, data, hotspot)?;
+        shape.xor = shape.pixels.chunks_exact(4).map(|p| p[3] != 0).collect();
+        for pixel in shape.pixels.chunks_exact_mut(4) {
+            pixel[3] = 255;
+        }
+        Some(shape)
+    }
+
+    /// 解析单色指针
+    ///
+    /// 数据为 1 bpp 的 AND 掩码后接 XOR 掩码，`height` 为两个掩码的总行数
+    pub fn from_monochrome(width: u32, height: u32, pitch: usize, data: &[u8], hotspot: (u32, u32)) -> Option<Self> {
+        let height = height / 2;
+        let bit = |row: usize, x: usize| -> Option<bool> {
+            data.get(row * pitch + x / 8).map(|byte| byte & (0x80 >> (x % 8)) != 0)
+        };
+
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        let mut xor = Vec::with_capacity((width * height) as usize);
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let and_bit = bit(y, x)?;
+                let xor_bit = bit(y + height as usize, x)?;
+                let (pixel, invert) = match (and_bit, xor_bit) {
+                    (false, false) => ([0, 0, 0, 255], false),
+                    (false, true) => ([255, 255, 255, 255], false),
+                    (true, false) => ([0, 0, 0, 0], false),
+                    (true, true) => ([255, 255, 255, 255], true),
+                };
+                pixels.extend_from_slice(&pixel);
+                xor.push(invert);
+            }
+        }
+
+        Some(Self {
+            width,
+            height,
+            hotspot_x: hotspot.0,
+            hotspot_y: hotspot.1,
+            pixels,
+            xor,
+        })
+    }
+}
+
+/// 将指针合成到 RGBA 帧上
+///
+/// # 参数
+/// * `x`, `y` - 指针热点在帧中的位置 (可以部分超出画面)
+pub fn composite(frame: &mut Frame, shape: &CursorShape, x: i32, y: i32) {
+    if frame.is_gpu() {
+        return;
+    }
+
+    let left = x - shape.hotspot_x as i32;
+    let top = y - shape.hotspot_y as i32;
+
+    for sy in 0..shape.height as i32 {
+        let fy = top + sy;
+        if fy < 0 || fy >= frame.height as i32 {
+            continue;
+        }
+        for sx in 0..shape.width as i32 {
+            let fx = left + sx;
+            if fx < 0 || fx >= frame.width as i32 {
+                continue;
+            }
+
+            let index = (sy * shape.width as i32 + sx) as usize;
+            let src = &shape.pixels[index * 4..index * 4 + 4];
+            let offset = fy as usize * frame.stride + fx as usize * 4;
+            let Some(dst) = frame.data.get_mut(offset..offset + 3) else {
+                continue;
+            };
+
+            if shape.xor.get(index).copied().unwrap_or(false) {
+                for (d, s) in dst.iter_mut().zip(src) {
+                    *d ^= s;
+                }
+                continue;
+            }
+
+            let alpha = src[3] as u32;
+            if alpha == 0 {
+                continue;
+            }
+            for (d, &s) in dst.iter_mut().zip(src) {
+                *d = ((s as u32 * alpha + *d as u32 * (255 - alpha) + 127) / 255) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray_frame(width: u32, height: u32, value: u8) -> Frame {
+        let mut frame = Frame::new(width, height);
+        frame.data.fill(value);
+        frame
+    }
+
+    fn pixel(frame: &Frame, x: usize, y: usize) -> &[u8] {
+        let offset = y * frame.stride + x * 4;
+        &frame.data[offset..offset + 4]
+    }
+
+    #[test]
+    fn test_composite_arrow_clips_at_edges() {
+        let arrow = CursorShape::arrow();
+        assert_eq!(arrow.pixels.len(), (arrow.width * arrow.height * 4) as usize);
+
+        let mut frame = gray_frame(8, 8, 128);
+        composite(&mut frame, &arrow, 6, 6);
+
+        // 热点处为黑色描边，左上方不受影响
+        assert_eq!(&pixel(&frame, 6, 6)[..3], &[0, 0, 0]);
+        assert_eq!(&pixel(&frame, 5, 5)[..3], &[128, 128, 128]);
+        assert_eq!(&pixel(&frame, 7, 7)[..3], &[0, 0, 0]);
+        // 箭头内部为白色
+        let mut frame = gray_frame(8, 8, 128);
+        composite(&mut frame, &arrow, 0, 0);
+        assert_eq!(&pixel(&frame, 1, 3)[..3], &[255, 255, 255]);
+
+        // 完全超出画面时不修改
+        let mut frame = gray_frame(8, 8, 128);
+        composite(&mut frame, &arrow, -50, 100);
+        assert!(frame.data.iter().all(|&v| v == 128));
+    }
+
+    #[test]
+    fn test_color_shape_alpha_blend() {
+        // 1x2 指针: 不透明红色 + 半透明蓝色 (BGRA)
+        let data = [0, 0, 255, 255, 255, 0, 0, 128];
+        let shape = CursorShape::from_color(1, 2, 4, &data, (0, 0)).unwrap();
+        assert_eq!(shape.pixels, vec![255, 0, 0, 255, 0, 0, 255, 128]);
+
+        let mut frame = gray_frame(2, 2, 0);
+        composite(&mut frame, &shape, 0, 0);
+        assert_eq!(&pixel(&frame, 0, 0)[..3], &[255, 0, 0]);
+        assert_eq!(&pixel(&frame, 0, 1)[..3], &[0, 0, 128]);
+
+        // 数据不足时解析失败
+        assert!(CursorShape::from_color(2, 2, 8, &data, (0, 0)).is_none());
+    }
+
+    #[test]
+    fn test_monochrome_and_masked_shapes() {
+        // 4x1 单色指针: AND = 0011, XOR = 0101 → 黑、白、透明、反色
+        let data = [0b0011_0000, 0b0101_0000];
+        let shape = CursorShape::from_monochrome(4, 2, 1, &data, (0, 0)).unwrap();
+        assert_eq!(shape.height, 1);
+        assert_eq!(shape.xor, vec![false, false, false, true]);
+
+        let mut frame = gray_frame(4, 1, 0x40);
+        composite(&mut frame, &shape, 0, 0);
+        assert_eq!(&pixel(&frame, 0, 0)[..3], &[0, 0, 0]);
+        assert_eq!(&pixel(&frame, 1, 0)[..3], &[255, 255, 255]);
+        assert_eq!(&pixel(&frame, 2, 0)[..3], &[0x40, 0x40, 0x40]);
+        assert_eq!(&pixel(&frame, 3, 0)[..3], &[0xbf, 0xbf, 0xbf]);
+
+        // 掩码彩色: 第一个像素直接覆盖，第二个像素与屏幕异或
+        let data = [0x10, 0x20, 0x30, 0x00, 0xff, 0x00, 0x00, 0xff];
+        let shape = CursorShape::from_masked_color(2, 1, 8, &data, (0, 0)).unwrap();
+        let mut frame = gray_frame(2, 1, 0x0f);
+        composite(&mut frame, &shape, 0, 0);
+        assert_eq!(&pixel(&frame, 0, 0)[..3], &[0x30, 0x20, 0x10]);
+        assert_eq!(&pixel(&frame, 1, 0)[..3], &[0x0f, 0x0f, 0xf0]);
+    }
+}
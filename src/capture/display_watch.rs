@@ -0,0 +1,329 @@
+//! 显示配置变化检测
+//!
+//! 会话中途修改分辨率或拔出显示器后，已创建的捕获器会一直失败 (尺寸固定、复制句柄失效)，
+//! 需要按新配置重建捕获器和编码器:
+//! - Windows: 隐藏窗口接收 `WM_DISPLAYCHANGE` 广播
+//! - macOS: `CGDisplayRegisterReconfigurationCallback` 回调 (经主线程 run loop 派发)
+//!
+//! 系统通知只递增变化代数，[`DisplayWatcher::poll`] 发现代数变化后枚举显示器，
+//! 与上次的布局对比得到具体变化。通知可能丢失 (没有 run loop、消息线程创建失败)，
+//! 所以每隔 `POLL_INTERVAL` 也会主动对比一次
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// 没有收到系统通知时主动对比布局的间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 系统通知递增的变化代数
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// 单个显示器
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayInfo {
+    /// 平台显示器 ID (Windows 为 HMONITOR，macOS 为 CGDirectDisplayID)
+    pub id: u64,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// 显示配置变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayChange {
+    Added(DisplayInfo),
+    Removed(DisplayInfo),
+    Resized { id: u64, from: (u32, u32), to: (u32, u32) },
+}
+
+impl std::fmt::Display for DisplayChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisplayChange::Added(display) => {
+                write!(f, "显示器 {} 已连接 ({}x{})", display.id, display.width, display.height)
+            }
+            DisplayChange::Removed(display) => write!(f, "显示器 {} 已断开", display.id),
+            DisplayChange::Resized { id, from, to } => {
+                write!(f, "显示器 {} 分辨率变化: {}x{} -> {}x{}", id, from.0, from.1, to.0, to.1)
+            }
+        }
+    }
+}
+
+/// 当前的显示器布局 (按枚举顺序)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DisplayLayout {
+    pub displays: Vec<DisplayInfo>,
+}
+
+impl DisplayLayout {
+    /// 枚举当前连接的显示器 (失败或不支持的平台返回空布局)
+    pub fn current() -> Self {
+        Self { displays: platform::enumerate() }
+    }
+
+    /// 与旧布局对比，返回变化列表
+    pub fn diff(&self, previous: &DisplayLayout) -> Vec<DisplayChange> {
+        let mut changes = Vec::new();
+        for old in &previous.displays {
+            match self.displays.iter().find(|d| d.id == old.id) {
+                None => changes.push(DisplayChange::Removed(*old)),
+                Some(new) if (new.width, new.height) != (old.width, old.height) => {
+                    changes.push(DisplayChange::Resized {
+                        id: old.id,
+                        from: (old.width, old.height),
+                        to: (new.width, new.height),
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+        for new in &self.displays {
+            if !previous.displays.iter().any(|d| d.id == new.id) {
+                changes.push(DisplayChange::Added(*new));
+            }
+        }
+        changes
+    }
+}
+
+/// 显示配置变化监视器
+pub struct DisplayWatcher {
+    layout: DisplayLayout,
+    generation: u64,
+    last_check: Instant,
+}
+
+impl Default for DisplayWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DisplayWatcher {
+    /// 注册系统通知 (进程内只注册一次) 并记录当前布局
+    pub fn new() -> Self {
+        static REGISTER: std::sync::Once = std::sync::Once::new();
+        REGISTER.call_once(platform::register);
+
+        Self {
+            layout: DisplayLayout::current(),
+            generation: GENERATION.load(Ordering::Acquire),
+            last_check: Instant::now(),
+        }
+    }
+
+    /// 当前记录的布局
+    pub fn layout(&self) -> &DisplayLayout {
+        &self.layout
+    }
+
+    /// 检查自上次调用以来的变化 (开销很小，可在每帧调用)
+    pub fn poll(&mut self) -> Vec<DisplayChange> {
+        let generation = GENERATION.load(Ordering::Acquire);
+        if generation == self.generation && self.last_check.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.generation = generation;
+        self.last_check = Instant::now();
+
+        let layout = DisplayLayout::current();
+        let changes = layout.diff(&self.layout);
+        self.layout = layout;
+        changes
+    }
+}
+
+/// 系统通知回调: 递增变化代数
+fn notify() {
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::DisplayInfo;
+    use windows::core::w;
+    use windows::Win32::Foundation::{BOOL, HINSTANCE, HWND, LPARAM, LRESULT, RECT, WPARAM};
+    use windows::Win32::Graphics::Gdi::{EnumDisplayMonitors, GetMonitorInfoW, HDC, HMONITOR, MONITORINFO};
+    use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows::Win32::UI::WindowsAndMessaging::{
+        CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW, TranslateMessage, MSG,
+        WINDOW_EX_STYLE, WM_DISPLAYCHANGE, WNDCLASSW, WS_OVERLAPPED,
+    };
+
+    /// 创建隐藏窗口接收 `WM_DISPLAYCHANGE`
+    ///
+    /// 该消息只广播给顶层窗口 (仅消息窗口收不到)，窗口创建后不显示
+    pub fn register() {
+        let result = std::thread::Builder::new().name("display-watch".to_string()).spawn(|| unsafe {
+            let instance: HINSTANCE = match GetModuleHandleW(None) {
+                Ok(module) => module.into(),
+                Err(e) => {
+                    tracing::warn!("显示配置监听启动失败: {}", e);
+                    return;
+                }
+            };
+            let class = WNDCLASSW {
+                lpfnWndProc: Some(window_proc),
+                hInstance: instance,
+                lpszClassName: w!("SSControlDisplayWatch"),
+                ..Default::default()
+            };
+            if RegisterClassW(&class) == 0 {
+                tracing::warn!("显示配置监听启动失败: 无法注册窗口类");
+                return;
+            }
+            let hwnd = CreateWindowExW(
+                WINDOW_EX_STYLE(0),
+                w!("SSControlDisplayWatch"),
+                w!(""),
+                WS_OVERLAPPED,
+                0,
+                0,
+                0,
+                0,
+                None,
+                None,
+                instance,
+                None,
+            );
+            if hwnd.0 == 0 {
+                tracing::warn!("显示配置监听启动失败: 无法创建窗口");
+                return;
+            }
+
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        });
+        if let Err(e) = result {
+            tracing::warn!("显示配置监听线程创建失败: {}", e);
+        }
+    }
+
+    unsafe extern "system" fn window_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        if msg == WM_DISPLAYCHANGE {
+            super::notify();
+        }
+        DefWindowProcW(hwnd, msg, wparam, lparam)
+    }
+
+    pub fn enumerate() -> Vec<DisplayInfo> {
+        unsafe extern "system" fn collect(monitor: HMONITOR, _hdc: HDC, _rect: *mut RECT, data: LPARAM) -> BOOL {
+            let displays = &mut *(data.0 as *mut Vec<DisplayInfo>);
+            let mut info = MONITORINFO {
+                cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+                ..Default::default()
+            };
+            if GetMonitorInfoW(monitor, &mut info).as_bool() {
+                let rect = info.rcMonitor;
+                displays.push(DisplayInfo {
+                    id: monitor.0 as u64,
+                    width: (rect.right - rect.left).max(0) as u32,
+                    height: (rect.bottom - rect.top).max(0) as u32,
+                });
+            }
+            true.into()
+        }
+
+        let mut displays: Vec<DisplayInfo> = Vec::new();
+        unsafe {
+            EnumDisplayMonitors(None, None, Some(collect), LPARAM(&mut displays as *mut _ as isize));
+        }
+        displays
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::DisplayInfo;
+    use core_graphics::display::CGDisplay;
+    use std::ffi::c_void;
+
+    /// kCGDisplayBeginConfigurationFlag: 配置即将变化 (变化完成后还会再回调一次)
+    const BEGIN_CONFIGURATION_FLAG: u32 = 1 << 0;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGDisplayRegisterReconfigurationCallback(
+            callback: extern "C" fn(u32, u32, *mut c_void),
+            user_info: *mut c_void,
+        ) -> i32;
+    }
+
+    extern "C" fn reconfiguration_callback(_display: u32, flags: u32, _user_info: *mut c_void) {
+        if flags & BEGIN_CONFIGURATION_FLAG == 0 {
+            super::notify();
+        }
+    }
+
+    pub fn register() {
+        let error = unsafe { CGDisplayRegisterReconfigurationCallback(reconfiguration_callback, std::ptr::null_mut()) };
+        if error != 0 {
+            tracing::warn!("显示配置监听注册失败: CGError {}", error);
+        }
+    }
+
+    pub fn enumerate() -> Vec<DisplayInfo> {
+        CGDisplay::active_displays()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|id| {
+                let display = CGDisplay::new(id);
+                DisplayInfo {
+                    id: id as u64,
+                    width: display.pixels_wide() as u32,
+                    height: display.pixels_high() as u32,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod platform {
+    use super::DisplayInfo;
+
+    pub fn register() {}
+
+    pub fn enumerate() -> Vec<DisplayInfo> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(id: u64, width: u32, height: u32) -> DisplayInfo {
+        DisplayInfo { id, width, height }
+    }
+
+    #[test]
+    fn test_layout_diff() {
+        let before = DisplayLayout { displays: vec![display(1, 1920, 1080), display(2, 1280, 1024)] };
+        assert!(before.diff(&before).is_empty());
+
+        let after = DisplayLayout { displays: vec![display(1, 2560, 1440), display(3, 3840, 2160)] };
+        assert_eq!(
+            after.diff(&before),
+            vec![
+                DisplayChange::Resized { id: 1, from: (1920, 1080), to: (2560, 1440) },
+                DisplayChange::Removed(display(2, 1280, 1024)),
+                DisplayChange::Added(display(3, 3840, 2160)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_poll_after_notification() {
+        let mut watcher = DisplayWatcher::new();
+        assert!(watcher.poll().is_empty());
+
+        // 通知后重新枚举；布局没有变化时不报告
+        notify();
+        assert!(watcher.poll().is_empty());
+        assert_eq!(watcher.generation, GENERATION.load(Ordering::Acquire));
+    }
+}
@@ -1,9 +1,12 @@
 //! macOS 屏幕捕获实现
 //!
 //! 使用 CGDisplayStream API 进行屏幕捕获
+//!
+//! CGDisplay 截图不包含鼠标指针，启用指针合成时在当前鼠标位置绘制内置箭头
 
+use super::cursor::{self, CursorShape};
 use super::Frame;
-use super::Capturer;
+use super::{Capturer, DisplayChanged};
 use anyhow::{anyhow, Result};
 use core_graphics::display::CGDisplay;
 
@@ -12,6 +15,9 @@ pub struct MacOSCapturer {
     display_id: u32,
     width: u32,
     height: u32,
+    /// 是否在画面中合成鼠标指针
+    show_cursor: bool,
+    cursor_shape: CursorShape,
 }
 
 impl MacOSCapturer {
@@ -37,6 +43,8 @@ impl MacOSCapturer {
             display_id,
             width,
             height,
+            show_cursor: false,
+            cursor_shape: CursorShape::arrow(),
         })
     }
 
@@ -72,11 +80,35 @@ impl MacOSCapturer {
         Ok((width, height))
     }
 
-    /// 检查屏幕录制权限
+    /// 鼠标在本显示器上的像素坐标 (Retina 屏幕按像素/点比例换算)
+    fn cursor_position(&self, frame_width: u32) -> Option<(i32, i32)> {
+        use core_graphics::event::CGEvent;
+        use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+
+        let source = CGEventSource::new(CGEventSourceStateID::CombinedSessionState).ok()?;
+        let location = CGEvent::new(source).ok()?.location();
+
+        let bounds = CGDisplay::new(self.display_id).bounds();
+        if bounds.size.width <= 0.0 {
+            return None;
+        }
+        let scale = frame_width as f64 / bounds.size.width;
+        Some((
+            ((location.x - bounds.origin.x) * scale) as i32,
+            ((location.y - bounds.origin.y) * scale) as i32,
+        ))
+    }
+
+    /// 检查屏幕录制权限 (未授权时截图只有桌面背景，没有窗口内容)
+    ///
+    /// 只查询不弹出授权对话框；授权后需要重启进程才生效
     pub fn check_screen_recording_permission() -> bool {
-        // macOS 没有直接的 API 来检查屏幕录制权限
-        // 实际运行时会弹出权限请求
-        true
+        #[link(name = "CoreGraphics", kind = "framework")]
+        extern "C" {
+            /// macOS 10.15+
+            fn CGPreflightScreenCaptureAccess() -> bool;
+        }
+        unsafe { CGPreflightScreenCaptureAccess() }
     }
 }
 
@@ -85,6 +117,19 @@ impl Capturer for MacOSCapturer {
     fn capture(&mut self) -> Result<Frame> {
         let display = CGDisplay::new(self.display_id);
 
+        // 显示器断开或分辨率变化后需要重建捕获器 (尺寸在创建时确定)
+        if !display.is_active() {
+            return Err(DisplayChanged(format!("显示器 {} 已断开", self.display_id)).into());
+        }
+        let (width, height) = (display.pixels_wide() as u32, display.pixels_high() as u32);
+        if (width, height) != (self.width, self.height) {
+            return Err(DisplayChanged(format!(
+                "屏幕尺寸 {}x{} -> {}x{}",
+                self.width, self.height, width, height
+            ))
+            .into());
+        }
+
         // 捕获屏幕图像
         let image = display
             .image()
@@ -107,7 +152,14 @@ impl Capturer for MacOSCapturer {
         // CGImage 返回的数据是 RGBA/RGB 格式
         let pixel_data: Vec<u8> = data.bytes().to_vec();
 
-        Ok(Frame::from_raw_data(width, height, pixel_data, bytes_per_row))
+        let mut frame = Frame::from_raw_data(width, height, pixel_data, bytes_per_row);
+        if self.show_cursor {
+            if let Some((x, y)) = self.cursor_position(width) {
+                cursor::composite(&mut frame, &self.cursor_shape, x, y);
+            }
+        }
+
+        Ok(frame)
     }
 
     fn width(&self) -> u32 {
@@ -127,6 +179,14 @@ impl Capturer for MacOSCapturer {
         tracing::info!("屏幕捕获已停止");
         Ok(())
     }
+
+    fn set_show_cursor(&mut self, enabled: bool) -> bool {
+        if self.show_cursor != enabled {
+            tracing::info!("macOS 画面包含鼠标指针: {}", if enabled { "是" } else { "否" });
+        }
+        self.show_cursor = enabled;
+        true
+    }
 }
 
 #[cfg(test)]
@@ -5,6 +5,8 @@
 #![allow(dead_code)]
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
 
 /// 视频帧
 #[derive(Debug, Clone)]
@@ -14,8 +16,32 @@ pub struct Frame {
     pub data: Vec<u8>,  // RGBA 格式
     pub timestamp: u64,  // 时间戳 (毫秒)
     pub stride: usize,   // 每行字节数
+    /// GPU 纹理 (零拷贝路径，此时 data 为空)
+    pub gpu: Option<GpuFrame>,
 }
 
+/// GPU 帧 - 驻留在显存中的捕获结果
+///
+/// 由支持零拷贝的捕获器 (DXGI) 产生，可直接交给支持纹理输入的硬件编码器 (NVENC)，
+/// 避免 GPU → CPU → 颜色转换 → 编码器的往返
+#[derive(Debug, Clone)]
+pub struct GpuFrame {
+    pub width: u32,
+    pub height: u32,
+    /// D3D11 纹理 (BGRA)
+    #[cfg(target_os = "windows")]
+    pub texture: ::windows::Win32::Graphics::Direct3D11::ID3D11Texture2D,
+    /// 创建纹理的 D3D11 设备 (编码器必须使用同一设备)
+    #[cfg(target_os = "windows")]
+    pub device: ::windows::Win32::Graphics::Direct3D11::ID3D11Device,
+}
+
+// SAFETY: D3D11 设备是自由线程的；纹理只在捕获/编码线程中顺序访问
+#[cfg(target_os = "windows")]
+unsafe impl Send for GpuFrame {}
+#[cfg(target_os = "windows")]
+unsafe impl Sync for GpuFrame {}
+
 impl Frame {
     /// 创建一个新的空帧
     pub fn new(width: u32, height: u32) -> Self {
@@ -27,6 +53,7 @@ impl Frame {
             data,
             timestamp: Self::current_timestamp(),
             stride,
+            gpu: None,
         }
     }
 
@@ -47,8 +74,38 @@ impl Frame {
             data,
             timestamp: Self::current_timestamp(),
             stride,
+            gpu: None,
         }
     }
+
+    /// 从 GPU 纹理创建帧 (不含 CPU 像素数据)
+    pub fn from_gpu(gpu: GpuFrame) -> Self {
+        Frame {
+            width: gpu.width,
+            height: gpu.height,
+            data: Vec::new(),
+            timestamp: Self::current_timestamp(),
+            stride: 0,
+            gpu: Some(gpu),
+        }
+    }
+
+    /// 是否为 GPU 帧
+    pub fn is_gpu(&self) -> bool {
+        self.gpu.is_some()
+    }
+}
+
+/// 显示配置已变化 (分辨率改变、显示器断开)，捕获器需要按新配置重建
+#[derive(Debug, thiserror::Error)]
+#[error("显示配置已变化: {0}")]
+pub struct DisplayChanged(pub String);
+
+impl DisplayChanged {
+    /// 错误是否由显示配置变化引起
+    pub fn is(error: &anyhow::Error) -> bool {
+        error.downcast_ref::<DisplayChanged>().is_some()
+    }
 }
 
 /// 屏幕捕获器 trait
@@ -67,10 +124,69 @@ pub trait Capturer: Send {
 
     /// 停止捕获
     fn stop(&mut self) -> Result<()>;
+
+    /// 切换 GPU 输出 (零拷贝)
+    ///
+    /// 启用后 `capture` 返回 GPU 帧而不是 RGBA 数据。
+    /// 返回 false 表示捕获器不支持 GPU 输出
+    fn set_gpu_output(&mut self, _enabled: bool) -> bool {
+        false
+    }
+
+    /// 切换是否在画面中包含鼠标指针
+    ///
+    /// 关闭后 Viewer 需要根据元数据在本地绘制光标。
+    /// 返回 false 表示捕获器不支持合成指针
+    fn set_show_cursor(&mut self, _enabled: bool) -> bool {
+        false
+    }
+}
+
+/// 画面来源
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureSource {
+    /// 真实屏幕
+    #[default]
+    Screen,
+    /// 合成画面 (无显示器的 CI / Docker 环境)
+    Synthetic,
+}
+
+impl std::str::FromStr for CaptureSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "screen" => Ok(Self::Screen),
+            "synthetic" => Ok(Self::Synthetic),
+            _ => Err(anyhow::anyhow!("未知的画面来源: {} (可选 screen / synthetic)", s)),
+        }
+    }
+}
+
+/// 进程内使用合成画面时的配置
+static SYNTHETIC: OnceLock<SyntheticConfig> = OnceLock::new();
+
+/// 让之后的 [`create_capturer`] 都返回合成画面捕获器
+///
+/// 在进程启动时调用一次，之后的调用被忽略
+pub fn use_synthetic(config: SyntheticConfig) {
+    let _ = SYNTHETIC.set(config);
 }
 
-/// 创建平台特定的捕获器
+/// 是否使用合成画面
+pub fn is_synthetic() -> bool {
+    SYNTHETIC.get().is_some()
+}
+
+/// 创建平台特定的捕获器 (启用合成画面时返回 [`SyntheticCapturer`]，忽略显示器序号)
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(unused_variables))]
 pub fn create_capturer(screen_index: Option<u32>) -> Result<Box<dyn Capturer>> {
+    if let Some(config) = SYNTHETIC.get() {
+        return Ok(Box::new(SyntheticCapturer::new(config.clone())?));
+    }
+
     #[cfg(target_os = "macos")]
     {
         Ok(Box::new(macos::MacOSCapturer::new(screen_index)?))
@@ -97,6 +213,21 @@ pub fn create_capturer(screen_index: Option<u32>) -> Result<Box<dyn Capturer>> {
     }
 }
 
+// 鼠标指针合成
+pub mod cursor;
+
+// 显示配置变化检测
+pub mod display_watch;
+pub use display_watch::DisplayWatcher;
+
+// 合成画面 (无显示器环境)
+pub mod synthetic;
+pub use synthetic::{SyntheticCapturer, SyntheticConfig};
+
+// 单帧截图
+pub mod screenshot;
+pub use screenshot::{capture_screenshot, ImageFormat};
+
 // macOS 实现
 #[cfg(target_os = "macos")]
 pub mod macos;
@@ -120,5 +251,16 @@ mod tests {
         assert_eq!(frame.height, 1080);
         assert_eq!(frame.data.len(), 1920 * 1080 * 4);
         assert_eq!(frame.stride, 1920 * 4);
+        assert!(!frame.is_gpu());
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_gpu_frame_has_no_pixels() {
+        let frame = Frame::from_gpu(GpuFrame { width: 1920, height: 1080 });
+        assert!(frame.is_gpu());
+        assert_eq!((frame.width, frame.height), (1920, 1080));
+        assert!(frame.data.is_empty());
+        assert_eq!(frame.stride, 0);
     }
 }
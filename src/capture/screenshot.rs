@@ -0,0 +1,156 @@
+//! 单帧截图
+//!
+//! 捕获一帧屏幕并编码为 PNG / JPEG，供 `sscontrol screenshot`、监控脚本和 UI 预览共用
+
+use super::Frame;
+use anyhow::{anyhow, Result};
+use std::path::Path;
+use std::time::Duration;
+
+/// 截图捕获失败时的重试次数 (DXGI 等流式捕获器首帧可能超时)
+const CAPTURE_ATTEMPTS: u32 = 10;
+const CAPTURE_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 截图图片格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    /// JPEG (质量 1-100)
+    Jpeg { quality: u8 },
+}
+
+impl ImageFormat {
+    /// 默认 JPEG 质量
+    pub const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+    /// 按文件扩展名选择格式 (.jpg / .jpeg 为 JPEG，其余为 PNG)
+    pub fn from_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase)
+            .as_deref()
+        {
+            Some("jpg") | Some("jpeg") => Self::Jpeg {
+                quality: Self::DEFAULT_JPEG_QUALITY,
+            },
+            _ => Self::Png,
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg { .. } => "image/jpeg",
+        }
+    }
+}
+
+/// 编码后的截图
+#[derive(Debug, Clone)]
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    pub format: ImageFormat,
+    /// 编码后的图片数据
+    pub data: Vec<u8>,
+}
+
+impl Screenshot {
+    /// 写入文件
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, &self.data)
+            .map_err(|e| anyhow!("写入截图失败 {}: {}", path.display(), e))
+    }
+}
+
+/// 捕获指定屏幕的一帧并编码
+pub fn capture_screenshot(screen_index: Option<u32>, format: ImageFormat) -> Result<Screenshot> {
+    let frame = capture_frame(screen_index)?;
+    Ok(Screenshot {
+        width: frame.width,
+        height: frame.height,
+        format,
+        data: encode_frame(&frame, format)?,
+    })
+}
+
+/// 捕获一帧 RGBA 画面
+pub fn capture_frame(screen_index: Option<u32>) -> Result<Frame> {
+    let mut capturer = super::create_capturer(screen_index)?;
+    capturer.start()?;
+
+    let mut last_error = None;
+    for _ in 0..CAPTURE_ATTEMPTS {
+        match capturer.capture() {
+            Ok(frame) if !frame.is_gpu() => {
+                let _ = capturer.stop();
+                return Ok(frame);
+            }
+            Ok(_) => last_error = Some(anyhow!("捕获器返回了 GPU 帧")),
+            Err(e) => last_error = Some(e),
+        }
+        std::thread::sleep(CAPTURE_RETRY_INTERVAL);
+    }
+
+    let _ = capturer.stop();
+    Err(last_error.unwrap_or_else(|| anyhow!("截图失败")))
+}
+
+/// 将 RGBA 帧编码为图片
+pub fn encode_frame(frame: &Frame, format: ImageFormat) -> Result<Vec<u8>> {
+    let row_bytes = frame.width as usize * 4;
+    if frame.stride < row_bytes || frame.data.len() < frame.stride * frame.height as usize {
+        return Err(anyhow!("帧数据不完整: {}x{}, stride {}", frame.width, frame.height, frame.stride));
+    }
+
+    // 去掉行尾填充
+    let mut rgba = Vec::with_capacity(row_bytes * frame.height as usize);
+    for row in frame.data.chunks(frame.stride).take(frame.height as usize) {
+        rgba.extend_from_slice(&row[..row_bytes]);
+    }
+    let image = image::RgbaImage::from_raw(frame.width, frame.height, rgba)
+        .ok_or_else(|| anyhow!("无法构造图像"))?;
+
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    match format {
+        ImageFormat::Png => image.write_to(&mut buffer, image::ImageFormat::Png)?,
+        ImageFormat::Jpeg { quality } => {
+            // JPEG 不支持透明通道
+            let rgb = image::DynamicImage::ImageRgba8(image).to_rgb8();
+            let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, quality.clamp(1, 100));
+            rgb.write_with_encoder(encoder)?;
+        }
+    }
+    Ok(buffer.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_frame_with_stride() {
+        // 2x2 帧，每行末尾有 8 字节填充
+        let stride = 2 * 4 + 8;
+        let mut data = vec![0u8; stride * 2];
+        data[..4].copy_from_slice(&[255, 0, 0, 255]);
+        data[stride + 4..stride + 8].copy_from_slice(&[0, 0, 255, 255]);
+        let frame = Frame::from_raw_data(2, 2, data, stride);
+
+        let png = encode_frame(&frame, ImageFormat::Png).unwrap();
+        let decoded = image::load_from_memory(&png).unwrap().to_rgba8();
+        assert_eq!(decoded.dimensions(), (2, 2));
+        assert_eq!(decoded.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(decoded.get_pixel(1, 1).0, [0, 0, 255, 255]);
+
+        let jpeg = encode_frame(&frame, ImageFormat::Jpeg { quality: 80 }).unwrap();
+        assert_eq!(&jpeg[..2], &[0xFF, 0xD8]);
+
+        assert_eq!(ImageFormat::from_path(Path::new("a.JPG")), ImageFormat::Jpeg { quality: 85 });
+        assert_eq!(ImageFormat::from_path(Path::new("a.png")), ImageFormat::Png);
+    }
+}
@@ -0,0 +1,278 @@
+//! 合成画面捕获器
+//!
+//! 不需要显示器，按帧生成移动的渐变或测试卡画面，供 CI、Docker 和自动化测试走完整的
+//! 编码和网络链路 (`--capture synthetic` 或配置 `capture.source = "synthetic"`)。
+//!
+//! 每帧左下角以黑白方块编码帧序号 (32 位，高位在前)，接收端可用
+//! [`SyntheticCapturer::decode_frame_index`] 检查丢帧和乱序
+
+use super::{Capturer, Frame};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// 帧序号的位数
+const INDEX_BITS: u32 = 32;
+/// 帧序号方块的最大边长 (像素，编码后仍能分辨)
+const INDEX_BLOCK: u32 = 16;
+
+/// 合成画面图案
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyntheticPattern {
+    /// 彩条 + 灰阶 + 移动方块
+    #[default]
+    TestCard,
+    /// 整屏滚动的彩色渐变 (所有像素每帧都变化，编码压力最大)
+    Gradient,
+}
+
+/// 合成画面配置 (`[capture.synthetic]`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyntheticConfig {
+    /// 画面宽度
+    #[serde(default = "default_width")]
+    pub width: u32,
+    /// 画面高度
+    #[serde(default = "default_height")]
+    pub height: u32,
+    /// 图案
+    #[serde(default)]
+    pub pattern: SyntheticPattern,
+    /// 每帧移动的像素数 (0 = 静止画面，用于测试静态画面检测)
+    #[serde(default = "default_motion")]
+    pub motion: u32,
+}
+
+fn default_width() -> u32 {
+    1280
+}
+
+fn default_height() -> u32 {
+    720
+}
+
+fn default_motion() -> u32 {
+    8
+}
+
+impl Default for SyntheticConfig {
+    fn default() -> Self {
+        Self {
+            width: default_width(),
+            height: default_height(),
+            pattern: SyntheticPattern::default(),
+            motion: default_motion(),
+        }
+    }
+}
+
+/// 合成画面捕获器
+pub struct SyntheticCapturer {
+    config: SyntheticConfig,
+    frame_index: u32,
+    running: bool,
+}
+
+impl SyntheticCapturer {
+    pub fn new(config: SyntheticConfig) -> Result<Self> {
+        if config.width < 2 || config.height < 2 {
+            bail!("合成画面分辨率无效: {}x{}", config.width, config.height);
+        }
+        tracing::info!(
+            "使用合成画面捕获: {}x{}, {:?}, 每帧移动 {} 像素",
+            config.width,
+            config.height,
+            config.pattern,
+            config.motion
+        );
+        Ok(Self { config, frame_index: 0, running: false })
+    }
+
+    /// 下一帧的序号
+    pub fn frame_index(&self) -> u32 {
+        self.frame_index
+    }
+
+    /// 生成指定序号的帧
+    pub fn render(&self, index: u32) -> Frame {
+        let (width, height) = (self.config.width as usize, self.config.height as usize);
+        let stride = width * 4;
+        let mut data = vec![0u8; stride * height];
+        let offset = (index as u64 * self.config.motion as u64) as usize;
+
+        match self.config.pattern {
+            SyntheticPattern::TestCard => draw_test_card(&mut data, width, height, offset),
+            SyntheticPattern::Gradient => draw_gradient(&mut data, width, height, offset),
+        }
+        draw_frame_index(&mut data, width, height, index);
+
+        Frame::from_raw_data(self.config.width, self.config.height, data, stride)
+    }
+
+    /// 从 (可能经过有损编码的) 合成帧中读出帧序号
+    ///
+    /// 画面太小无法放下序号时返回 None
+    pub fn decode_frame_index(frame: &Frame) -> Option<u32> {
+        let block = index_block(frame.width as usize)?;
+        if frame.data.len() < frame.stride * frame.height as usize || (frame.height as usize) < block {
+            return None;
+        }
+        let y = frame.height as usize - block / 2 - 1;
+        let index = (0..INDEX_BITS as usize).fold(0u32, |index, bit| {
+            let x = bit * block + block / 2;
+            let offset = y * frame.stride + x * 4;
+            let luma = frame.data[offset..offset + 3].iter().map(|&c| c as u32).sum::<u32>() / 3;
+            (index << 1) | (luma >= 128) as u32
+        });
+        Some(index)
+    }
+}
+
+impl Capturer for SyntheticCapturer {
+    fn capture(&mut self) -> Result<Frame> {
+        let frame = self.render(self.frame_index);
+        self.frame_index = self.frame_index.wrapping_add(1);
+        Ok(frame)
+    }
+
+    fn width(&self) -> u32 {
+        self.config.width
+    }
+
+    fn height(&self) -> u32 {
+        self.config.height
+    }
+
+    fn start(&mut self) -> Result<()> {
+        self.running = true;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.running = false;
+        Ok(())
+    }
+}
+
+/// 帧序号方块的边长 (画面太窄时为 None)
+fn index_block(width: usize) -> Option<usize> {
+    let block = (width / INDEX_BITS as usize).min(INDEX_BLOCK as usize);
+    (block >= 2).then_some(block)
+}
+
+fn put(data: &mut [u8], width: usize, x: usize, y: usize, rgb: [u8; 3]) {
+    let offset = (y * width + x) * 4;
+    data[offset..offset + 4].copy_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+}
+
+/// 75% 彩条 (白、黄、青、绿、品红、红、蓝) + 底部灰阶 + 水平移动的白色方块
+fn draw_test_card(data: &mut [u8], width: usize, height: usize, offset: usize) {
+    const BARS: [[u8; 3]; 7] = [
+        [191, 191, 191],
+        [191, 191, 0],
+        [0, 191, 191],
+        [0, 191, 0],
+        [191, 0, 191],
+        [191, 0, 0],
+        [0, 0, 191],
+    ];
+    let bars_height = height * 3 / 4;
+    for y in 0..height {
+        for x in 0..width {
+            let rgb = if y < bars_height {
+                BARS[x * BARS.len() / width]
+            } else {
+                let gray = (x * 255 / (width - 1)) as u8;
+                [gray, gray, gray]
+            };
+            put(data, width, x, y, rgb);
+        }
+    }
+
+    // 移动方块在彩条区域内左右往返
+    let size = (height / 8).max(1).min(width);
+    let travel = width - size;
+    let x0 = if travel == 0 {
+        0
+    } else {
+        let position = offset % (travel * 2);
+        if position <= travel { position } else { travel * 2 - position }
+    };
+    let y0 = (bars_height.saturating_sub(size)) / 2;
+    for y in y0..(y0 + size).min(height) {
+        for x in x0..x0 + size {
+            put(data, width, x, y, [255, 255, 255]);
+        }
+    }
+}
+
+/// 对角滚动的彩色渐变
+fn draw_gradient(data: &mut [u8], width: usize, height: usize, offset: usize) {
+    for y in 0..height {
+        let g = (y * 255 / (height - 1)) as u8;
+        for x in 0..width {
+            let r = ((x + offset) % width * 255 / width) as u8;
+            let b = ((x + y + offset) / 2 % 256) as u8;
+            put(data, width, x, y, [r, g, b]);
+        }
+    }
+}
+
+/// 在左下角画出帧序号 (白 = 1，黑 = 0)
+fn draw_frame_index(data: &mut [u8], width: usize, height: usize, index: u32) {
+    let Some(block) = index_block(width) else { return };
+    if height < block {
+        return;
+    }
+    for bit in 0..INDEX_BITS as usize {
+        let set = (index >> (INDEX_BITS as usize - 1 - bit)) & 1 == 1;
+        let value = if set { 255 } else { 0 };
+        for y in height - block..height {
+            for x in bit * block..(bit + 1) * block {
+                put(data, width, x, y, [value, value, value]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capturer(pattern: SyntheticPattern, motion: u32) -> SyntheticCapturer {
+        SyntheticCapturer::new(SyntheticConfig { width: 640, height: 360, pattern, motion }).unwrap()
+    }
+
+    #[test]
+    fn test_frame_size_and_index() {
+        let mut capturer = capturer(SyntheticPattern::TestCard, 8);
+        for expected in 0..3 {
+            let frame = capturer.capture().unwrap();
+            assert_eq!((frame.width, frame.height, frame.stride), (640, 360, 640 * 4));
+            assert_eq!(frame.data.len(), 640 * 360 * 4);
+            assert_eq!(SyntheticCapturer::decode_frame_index(&frame), Some(expected));
+        }
+        let frame = capturer.render(0xDEAD_BEEF);
+        assert_eq!(SyntheticCapturer::decode_frame_index(&frame), Some(0xDEAD_BEEF));
+    }
+
+    #[test]
+    fn test_motion() {
+        for pattern in [SyntheticPattern::TestCard, SyntheticPattern::Gradient] {
+            let moving = capturer(pattern, 8);
+            assert_ne!(moving.render(1).data[..640 * 300 * 4], moving.render(2).data[..640 * 300 * 4]);
+
+            // 静止画面除帧序号外完全相同
+            let still = capturer(pattern, 0);
+            assert_eq!(still.render(1).data[..640 * 300 * 4], still.render(2).data[..640 * 300 * 4]);
+        }
+    }
+
+    #[test]
+    fn test_invalid_size() {
+        assert!(SyntheticCapturer::new(SyntheticConfig { width: 0, ..Default::default() }).is_err());
+        // 太窄时不画帧序号
+        let tiny = SyntheticCapturer::new(SyntheticConfig { width: 16, height: 16, ..Default::default() }).unwrap();
+        assert_eq!(SyntheticCapturer::decode_frame_index(&tiny.render(5)), None);
+    }
+}
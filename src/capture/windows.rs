@@ -4,7 +4,7 @@
 
 #![cfg(target_os = "windows")]
 
-use super::{Capturer, Frame};
+use super::{Capturer, DisplayChanged, Frame};
 use anyhow::{anyhow, Result};
 use windows::Win32::Graphics::Gdi::{
     CreateCompatibleDC, CreateDIBSection, DeleteDC, DeleteObject, GetDC, GetDIBits, HDC, HBITMAP,
@@ -12,7 +12,8 @@ use windows::Win32::Graphics::Gdi::{
 };
 use windows::Win32::Graphics::Gdi::{BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_USAGE};
 use windows::Win32::UI::WindowsAndMessaging::{
-    GetDesktopWindow, GetSystemMetrics, SM_CXSCREEN, SM_CYSCREEN,
+    DrawIconEx, GetCursorInfo, GetDesktopWindow, GetIconInfo, GetSystemMetrics, CURSORINFO,
+    CURSOR_SHOWING, DI_NORMAL, HICON, ICONINFO, SM_CXSCREEN, SM_CYSCREEN,
 };
 
 /// Windows 屏幕捕获器
@@ -24,6 +25,8 @@ pub struct WindowsCapturer {
     mem_dc: HDC,
     hbitmap: HBITMAP,
     is_started: bool,
+    /// 是否在画面中绘制鼠标指针 (BitBlt 不包含指针)
+    show_cursor: bool,
 }
 
 impl WindowsCapturer {
@@ -53,6 +56,7 @@ impl WindowsCapturer {
                 mem_dc: HDC::default(),
                 hbitmap: HBITMAP::default(),
                 is_started: false,
+                show_cursor: false,
             })
         }
     }
@@ -124,6 +128,44 @@ impl WindowsCapturer {
         }
     }
 
+    /// 在内存 DC 上绘制当前鼠标指针
+    unsafe fn draw_cursor(&self) {
+        let mut info = CURSORINFO {
+            cbSize: std::mem::size_of::<CURSORINFO>() as u32,
+            ..Default::default()
+        };
+        if GetCursorInfo(&mut info).is_err() || info.flags.0 & CURSOR_SHOWING.0 == 0 {
+            return;
+        }
+
+        let icon = HICON(info.hCursor.0);
+        let mut icon_info = ICONINFO::default();
+        let (hotspot_x, hotspot_y) = if GetIconInfo(icon, &mut icon_info).is_ok() {
+            // GetIconInfo 创建的位图需要调用方释放
+            if !icon_info.hbmMask.is_invalid() {
+                DeleteObject(icon_info.hbmMask);
+            }
+            if !icon_info.hbmColor.is_invalid() {
+                DeleteObject(icon_info.hbmColor);
+            }
+            (icon_info.xHotspot as i32, icon_info.yHotspot as i32)
+        } else {
+            (0, 0)
+        };
+
+        let _ = DrawIconEx(
+            self.mem_dc,
+            info.ptScreenPos.x - hotspot_x,
+            info.ptScreenPos.y - hotspot_y,
+            icon,
+            0,
+            0,
+            0,
+            None,
+            DI_NORMAL,
+        );
+    }
+
     /// 清理 GDI 资源
     fn cleanup_gdi_resources(&mut self) {
         if !self.mem_dc.is_invalid() {
@@ -153,6 +195,23 @@ impl Capturer for WindowsCapturer {
         }
 
         unsafe {
+            // 输入桌面切换 (锁屏、Ctrl+Alt+Del、UAC 提示) 后桌面 DC 属于旧桌面，在新桌面上重新获取
+            if let Ok(true) = crate::service::windows_session::follow_input_desktop() {
+                let hwnd = GetDesktopWindow();
+                ReleaseDC(hwnd, self.hdc);
+                self.hdc = GetDC(GetDesktopWindow());
+            }
+
+            // 位图按创建时的尺寸分配，分辨率变化后需要重建捕获器
+            let (width, height) = (GetSystemMetrics(SM_CXSCREEN), GetSystemMetrics(SM_CYSCREEN));
+            if (width, height) != (self.width as i32, self.height as i32) {
+                return Err(DisplayChanged(format!(
+                    "屏幕尺寸 {}x{} -> {}x{}",
+                    self.width, self.height, width, height
+                ))
+                .into());
+            }
+
             // 复制屏幕到位图
             let result = windows::Win32::Graphics::Gdi::BitBlt(
                 self.mem_dc,
@@ -167,6 +226,10 @@ impl Capturer for WindowsCapturer {
             );
 
             if result.is_ok() {
+                if self.show_cursor {
+                    self.draw_cursor();
+                }
+
                 // 获取位图数据
                 let mut bitmap_info = BITMAPINFO {
                     bmiHeader: BITMAPINFOHEADER {
@@ -218,6 +281,7 @@ impl Capturer for WindowsCapturer {
                     data: rgba_data,
                     timestamp: Frame::current_timestamp(),
                     stride: self.width as usize * 4,
+                    gpu: None,
                 })
             } else {
                 Err(anyhow!("BitBlt 失败"))
@@ -244,6 +308,14 @@ impl Capturer for WindowsCapturer {
         self.cleanup_gdi_resources();
         Ok(())
     }
+
+    fn set_show_cursor(&mut self, enabled: bool) -> bool {
+        if self.show_cursor != enabled {
+            tracing::info!("GDI 画面包含鼠标指针: {}", if enabled { "是" } else { "否" });
+        }
+        self.show_cursor = enabled;
+        true
+    }
 }
 
 impl Drop for WindowsCapturer {
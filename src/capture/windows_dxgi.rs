@@ -7,18 +7,23 @@
 
 #![cfg(target_os = "windows")]
 
-use super::{Capturer, Frame};
+use super::cursor::{self, CursorShape};
+use super::{Capturer, DisplayChanged, Frame, GpuFrame};
 use anyhow::{anyhow, Result};
 use windows::core::ComInterface;
 use windows::Win32::Graphics::Direct3D::D3D_DRIVER_TYPE_HARDWARE;
 use windows::Win32::Graphics::Direct3D11::{
     D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D,
-    D3D11_CPU_ACCESS_READ, D3D11_CREATE_DEVICE_BGRA_SUPPORT, D3D11_MAP_READ,
-    D3D11_MAPPED_SUBRESOURCE, D3D11_SDK_VERSION, D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING,
+    D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE, D3D11_CPU_ACCESS_READ,
+    D3D11_CREATE_DEVICE_BGRA_SUPPORT, D3D11_CREATE_DEVICE_VIDEO_SUPPORT, D3D11_MAP_READ,
+    D3D11_MAPPED_SUBRESOURCE, D3D11_SDK_VERSION, D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT,
+    D3D11_USAGE_STAGING,
 };
 use windows::Win32::Graphics::Dxgi::{
     IDXGIAdapter, IDXGIDevice, IDXGIOutput, IDXGIOutput1, IDXGIOutputDuplication,
     DXGI_ERROR_ACCESS_LOST, DXGI_ERROR_WAIT_TIMEOUT, DXGI_OUTDUPL_FRAME_INFO,
+    DXGI_OUTDUPL_POINTER_SHAPE_INFO, DXGI_OUTDUPL_POINTER_SHAPE_TYPE_COLOR,
+    DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MASKED_COLOR, DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MONOCHROME,
 };
 use windows::Win32::Graphics::Dxgi::Common::DXGI_FORMAT_B8G8R8A8_UNORM;
 
@@ -28,6 +33,20 @@ pub struct DXGICapturer {
     context: ID3D11DeviceContext,
     duplication: IDXGIOutputDuplication,
     staging_texture: Option<ID3D11Texture2D>,
+    /// GPU 输出纹理 (零拷贝模式，常驻显存)
+    gpu_texture: Option<ID3D11Texture2D>,
+    /// 是否输出 GPU 帧
+    gpu_output: bool,
+    /// 是否在画面中合成鼠标指针
+    show_cursor: bool,
+    /// 最近一次的指针形状 (仅在形状变化时由 DXGI 下发)
+    cursor_shape: Option<CursorShape>,
+    /// 指针热点位置 (None = 指针隐藏)
+    cursor_position: Option<(i32, i32)>,
+    /// 指针形状读取缓冲区
+    pointer_buffer: Vec<u8>,
+    /// 适配器上的输出 (显示器) 索引
+    output_index: u32,
     width: u32,
     height: u32,
     is_started: bool,
@@ -41,6 +60,11 @@ impl DXGICapturer {
     pub fn new(screen_index: Option<u32>) -> Result<Self> {
         let index = screen_index.unwrap_or(0);
 
+        // 桌面复制只能在输入桌面上创建 (锁屏或 UAC 提示时为 Winlogon 安全桌面)
+        if let Err(e) = crate::service::windows_session::follow_input_desktop() {
+            tracing::debug!("{}", e);
+        }
+
         unsafe {
             // 创建 D3D11 设备
             let mut device: Option<ID3D11Device> = None;
@@ -50,7 +74,8 @@ impl DXGICapturer {
                 None, // 默认适配器
                 D3D_DRIVER_TYPE_HARDWARE,
                 None,
-                D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+                // VIDEO_SUPPORT 使设备可被硬件编码器直接使用 (零拷贝)
+                D3D11_CREATE_DEVICE_BGRA_SUPPORT | D3D11_CREATE_DEVICE_VIDEO_SUPPORT,
                 None, // 默认特性级别
                 D3D11_SDK_VERSION,
                 Some(&mut device),
@@ -97,6 +122,13 @@ impl DXGICapturer {
                 context,
                 duplication,
                 staging_texture: None,
+                gpu_texture: None,
+                gpu_output: false,
+                show_cursor: false,
+                cursor_shape: None,
+                cursor_position: None,
+                pointer_buffer: Vec::new(),
+                output_index: index,
                 width,
                 height,
                 is_started: false,
@@ -135,15 +167,138 @@ impl DXGICapturer {
         }
     }
 
+    /// 创建 GPU 输出纹理 (编码器直接读取，不经过 CPU)
+    fn create_gpu_texture(&mut self) -> Result<()> {
+        if self.gpu_texture.is_some() {
+            return Ok(());
+        }
+
+        unsafe {
+            let desc = D3D11_TEXTURE2D_DESC {
+                Width: self.width,
+                Height: self.height,
+                MipLevels: 1,
+                ArraySize: 1,
+                Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+                SampleDesc: windows::Win32::Graphics::Dxgi::Common::DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Usage: D3D11_USAGE_DEFAULT,
+                BindFlags: (D3D11_BIND_RENDER_TARGET.0 | D3D11_BIND_SHADER_RESOURCE.0) as u32,
+                CPUAccessFlags: 0,
+                MiscFlags: 0,
+            };
+
+            let mut texture: Option<ID3D11Texture2D> = None;
+            self.device.CreateTexture2D(&desc, None, Some(&mut texture))?;
+
+            self.gpu_texture = texture;
+            Ok(())
+        }
+    }
+
+    /// 将桌面纹理复制到 GPU 输出纹理 (显存内复制)
+    unsafe fn copy_to_gpu_frame(&mut self, desktop_texture: &ID3D11Texture2D) -> Result<Frame> {
+        self.create_gpu_texture()?;
+        let texture = self.gpu_texture.clone()
+            .ok_or_else(|| anyhow!("GPU 纹理未创建"))?;
+        self.context.CopyResource(&texture, desktop_texture);
+
+        Ok(Frame::from_gpu(GpuFrame {
+            width: self.width,
+            height: self.height,
+            texture,
+            device: self.device.clone(),
+        }))
+    }
+
+    /// 更新指针位置和形状 (必须在 ReleaseFrame 之前调用)
+    ///
+    /// 关闭指针合成时也持续跟踪，因为形状只在变化时下发
+    unsafe fn update_pointer(&mut self, frame_info: &DXGI_OUTDUPL_FRAME_INFO) {
+        // LastMouseUpdateTime 为 0 表示指针位置和可见性没有变化
+        if frame_info.LastMouseUpdateTime != 0 {
+            let pointer = &frame_info.PointerPosition;
+            self.cursor_position = pointer
+                .Visible
+                .as_bool()
+                .then_some((pointer.Position.x, pointer.Position.y));
+        }
+
+        let buffer_size = frame_info.PointerShapeBufferSize;
+        if buffer_size == 0 {
+            return;
+        }
+
+        self.pointer_buffer.resize(buffer_size as usize, 0);
+        let mut required = 0u32;
+        let mut info = DXGI_OUTDUPL_POINTER_SHAPE_INFO::default();
+        if let Err(e) = self.duplication.GetFramePointerShape(
+            buffer_size,
+            self.pointer_buffer.as_mut_ptr() as *mut _,
+            &mut required,
+            &mut info,
+        ) {
+            tracing::debug!("获取指针形状失败: {:?}", e);
+            return;
+        }
+
+        let hotspot = (info.HotSpot.x.max(0) as u32, info.HotSpot.y.max(0) as u32);
+        let pitch = info.Pitch as usize;
+        let data = &self.pointer_buffer;
+        let shape = match info.Type {
+            t if t == DXGI_OUTDUPL_POINTER_SHAPE_TYPE_COLOR.0 as u32 => {
+                CursorShape::from_color(info.Width, info.Height, pitch, data, hotspot)
+            }
+            t if t == DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MASKED_COLOR.0 as u32 => {
+                CursorShape::from_masked_color(info.Width, info.Height, pitch, data, hotspot)
+            }
+            t if t == DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MONOCHROME.0 as u32 => {
+                CursorShape::from_monochrome(info.Width, info.Height, pitch, data, hotspot)
+            }
+            other => {
+                tracing::debug!("未知的指针形状类型: {}", other);
+                None
+            }
+        };
+
+        if shape.is_some() {
+            self.cursor_shape = shape;
+        }
+    }
+
     /// 尝试重新获取桌面复制
+    ///
+    /// 显示器断开或分辨率变化时返回 [`DisplayChanged`] (纹理尺寸已固定，需要重建捕获器)
     fn try_reacquire_duplication(&mut self) -> Result<()> {
         unsafe {
             // 获取 DXGI 设备
             let dxgi_device: IDXGIDevice = self.device.cast()?;
             let adapter: IDXGIAdapter = dxgi_device.GetAdapter()?;
-            let output: IDXGIOutput = adapter.EnumOutputs(0)?;
+            let output: IDXGIOutput = adapter
+                .EnumOutputs(self.output_index)
+                .map_err(|_| DisplayChanged(format!("显示器 {} 已断开", self.output_index)))?;
+
+            let mut desc = windows::Win32::Graphics::Dxgi::DXGI_OUTPUT_DESC::default();
+            output.GetDesc(&mut desc)?;
+            let width = (desc.DesktopCoordinates.right - desc.DesktopCoordinates.left) as u32;
+            let height = (desc.DesktopCoordinates.bottom - desc.DesktopCoordinates.top) as u32;
+            if (width, height) != (self.width, self.height) {
+                return Err(DisplayChanged(format!(
+                    "屏幕尺寸 {}x{} -> {}x{}",
+                    self.width, self.height, width, height
+                ))
+                .into());
+            }
+
             let output1: IDXGIOutput1 = output.cast()?;
 
+            // 访问丢失通常是输入桌面切换 (锁屏、Ctrl+Alt+Del、UAC 提示)，先跟随到新桌面
+            if let Err(e) = crate::service::windows_session::follow_input_desktop() {
+                tracing::debug!("{}", e);
+            }
+
             // 重新创建桌面复制
             self.duplication = output1.DuplicateOutput(&self.device)?;
             tracing::info!("DXGI 桌面复制已重新获取");
@@ -161,6 +316,11 @@ impl Capturer for DXGICapturer {
         // 确保 staging 纹理已创建
         self.create_staging_texture()?;
 
+        // 捕获可能在不同线程上进行，线程桌面与输入桌面不一致时在输入桌面上重建桌面复制
+        if let Ok(true) = crate::service::windows_session::follow_input_desktop() {
+            self.try_reacquire_duplication()?;
+        }
+
         unsafe {
             let mut frame_info = DXGI_OUTDUPL_FRAME_INFO::default();
             let mut desktop_resource = None;
@@ -199,6 +359,15 @@ impl Capturer for DXGICapturer {
             // 获取纹理
             let desktop_texture: ID3D11Texture2D = desktop_resource.cast()?;
 
+            self.update_pointer(&frame_info);
+
+            // 零拷贝模式: 纹理留在显存中，直接交给编码器
+            if self.gpu_output {
+                let frame = self.copy_to_gpu_frame(&desktop_texture);
+                self.duplication.ReleaseFrame()?;
+                return frame;
+            }
+
             // 复制到 staging 纹理
             let staging = self.staging_texture.as_ref()
                 .ok_or_else(|| anyhow!("staging 纹理未创建"))?;
@@ -237,13 +406,23 @@ impl Capturer for DXGICapturer {
             // 释放帧
             self.duplication.ReleaseFrame()?;
 
-            Ok(Frame {
+            let mut frame = Frame {
                 width: self.width,
                 height: self.height,
                 data: rgba_data,
                 timestamp: Frame::current_timestamp(),
                 stride: self.width as usize * 4,
-            })
+                gpu: None,
+            };
+
+            if self.show_cursor {
+                if let Some((x, y)) = self.cursor_position {
+                    let shape = self.cursor_shape.get_or_insert_with(CursorShape::arrow);
+                    cursor::composite(&mut frame, shape, x, y);
+                }
+            }
+
+            Ok(frame)
         }
     }
 
@@ -267,9 +446,35 @@ impl Capturer for DXGICapturer {
     fn stop(&mut self) -> Result<()> {
         self.is_started = false;
         self.staging_texture = None;
+        self.gpu_texture = None;
         tracing::info!("DXGI 捕获器已停止");
         Ok(())
     }
+
+    fn set_gpu_output(&mut self, enabled: bool) -> bool {
+        if self.gpu_output != enabled {
+            tracing::info!("DXGI GPU 零拷贝输出: {}", if enabled { "启用" } else { "禁用" });
+        }
+        self.gpu_output = enabled;
+        if !enabled {
+            self.gpu_texture = None;
+        }
+        if enabled && self.show_cursor {
+            tracing::warn!("GPU 零拷贝模式下画面不包含鼠标指针");
+        }
+        true
+    }
+
+    fn set_show_cursor(&mut self, enabled: bool) -> bool {
+        if self.show_cursor != enabled {
+            tracing::info!("DXGI 画面包含鼠标指针: {}", if enabled { "是" } else { "否" });
+        }
+        self.show_cursor = enabled;
+        if enabled && self.gpu_output {
+            tracing::warn!("GPU 零拷贝模式下画面不包含鼠标指针");
+        }
+        true
+    }
 }
 
 // DXGICapturer 不能自动 Send，需要手动实现
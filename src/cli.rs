@@ -4,6 +4,12 @@
 
 use clap::{Parser, Subcommand};
 
+use crate::capture::CaptureSource;
+use crate::config::{TurnServerConfig, WebRTCConfig};
+use crate::network::lan::Subnet;
+use crate::power::PowerAction;
+use crate::quality::profile::QualityProfile;
+
 /// sscontrol - 命令行参数
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -23,11 +29,19 @@ pub struct Args {
     #[arg(short = 'i', long)]
     pub screen: Option<u32>,
 
+    /// 画面来源 (screen/synthetic)，synthetic 生成合成画面，不需要显示器 (CI、Docker)
+    #[arg(long, env = "SSCONTROL_CAPTURE")]
+    pub capture: Option<CaptureSource>,
+
     /// 日志级别 (0=warn, 1=info, 2=debug, 3=trace)
     #[arg(short, long)]
     pub verbose: Option<u8>,
 
-    /// 编码器类型 (auto/software/nvenc/amf/qsv/videotoolbox)
+    /// 日志格式 (json: 每行一个对象，附带 peer_id/session_id 关联 ID)
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// 编码器类型 (auto/software/vp9/nvenc/amf/qsv/videotoolbox)
     #[arg(long)]
     pub encoder: Option<String>,
 
@@ -35,7 +49,11 @@ pub struct Args {
     #[arg(long)]
     pub bitrate: Option<u32>,
 
-    /// 启用自适应码率
+    /// 画质预设 (low-latency/balanced/high-quality/low-bandwidth/battery-saver)，代替配置中的帧率和码率
+    #[arg(long)]
+    pub profile: Option<QualityProfile>,
+
+    /// 启用自适应码率 (根据 RTCP 丢包/RTT/NACK 动态调整码率，带宽不足时降低分辨率)
     #[arg(long)]
     pub adaptive: bool,
 }
@@ -44,10 +62,18 @@ pub struct Args {
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// 以服务模式运行
-    Run,
+    Run {
+        /// 作为服务在用户会话中启动的助手进程运行 (Windows，内部使用)
+        #[arg(long, hide = true)]
+        session_helper: bool,
+    },
 
     /// 服务管理
     Service {
+        /// 使用系统级 LaunchDaemon (macOS): 开机即运行，注销后不退出，可在登录窗口连接 (需要 sudo)
+        #[arg(long, global = true)]
+        daemon: bool,
+
         #[command(subcommand)]
         action: ServiceCommands,
     },
@@ -58,14 +84,89 @@ pub enum Commands {
         #[arg(short, long, default_value = "9527")]
         port: u16,
 
-        /// 启用公网隧道 (Cloudflare Tunnel)
-        #[cfg(feature = "tunnel")]
+        /// 仅查看模式 (Viewer 不能操作鼠标键盘、同步剪贴板或传输文件)
         #[arg(long)]
-        tunnel: bool,
+        view_only: bool,
+
+        /// 无人值守访问: 首次启动生成长期访问密钥 (配置中只保存其哈希)，
+        /// Viewer 可用它代替一次性 PIN 连接，且不再需要连接审批 (需要 security feature)
+        #[arg(long)]
+        unattended: bool,
+
+        /// 录制会话到文件 (H.264 写入 .mp4，VP8 写入 .webm，.mkv 均可)
+        #[arg(long, value_name = "PATH")]
+        record: Option<String>,
+
+        /// 录制按时长分段 (分钟)
+        #[arg(long, value_name = "MINUTES", requires = "record")]
+        record_split: Option<u64>,
+
+        /// 录制分段的最大大小 (MB)
+        #[arg(long, value_name = "MB", requires = "record")]
+        record_max_size: Option<u64>,
+
+        /// 定期在控制台输出延迟统计 (端到端/编码/RTT 的 p50/p95)
+        #[arg(long)]
+        stats: bool,
+
+        #[command(flatten)]
+        limits: SignalingLimits,
+
+        #[command(flatten)]
+        cluster: ClusterArgs,
+
+        #[command(flatten)]
+        ice: IceArgs,
+
+        /// 启用公网隧道，可指定隧道程序 (cloudflared / ngrok / ssh，默认取配置文件的 tunnel.provider)
+        #[cfg(feature = "tunnel")]
+        #[arg(long, value_name = "PROVIDER", num_args = 0..=1, value_parser = crate::config::TUNNEL_PROVIDERS)]
+        tunnel: Option<Option<String>>,
     },
 
-    /// 控制端模式 - 通过 IP 或公网 URL 连接被控端
+    /// 控制端模式 - 通过地址簿中的别名、IP 或公网 URL 连接被控端
     Connect {
+        /// 地址簿中的被控端别名 (见 sscontrol hosts)
+        #[arg(conflicts_with_all = ["ip", "url"])]
+        host: Option<String>,
+
+        /// 被控端 IP 地址 (局域网模式，可重复指定，在同一窗口中同时查看多台主机)
+        #[arg(long)]
+        ip: Vec<String>,
+
+        /// 被控端公网 URL (隧道模式，如 wss://xxx.trycloudflare.com，可重复指定)
+        #[arg(long)]
+        url: Vec<String>,
+
+        /// 被控端端口 (仅 --ip 或别名时使用，默认为地址簿中保存的端口或 9527)
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// 传输方式: webrtc (浏览器查看器) 或 quic (命令行直连，需要 quic feature)，
+        /// 默认为地址簿中保存的方式或 webrtc
+        #[arg(long, value_enum)]
+        transport: Option<Transport>,
+
+        /// 被控端显示的 PIN 或无人值守访问密钥 (仅 QUIC 传输；浏览器查看器在页面中输入)
+        #[arg(long, env = "SSCONTROL_PIN")]
+        pin: Option<String>,
+
+        /// 身份验证器 App 中的 6 位验证码 (仅 QUIC 传输，被控端启用 TOTP 双因素验证时需要)
+        #[arg(long, env = "SSCONTROL_TOTP")]
+        totp: Option<String>,
+
+        /// 被控端证书指纹 (仅 QUIC 传输，被控端启动时显示)
+        #[arg(long, value_name = "SHA256")]
+        fingerprint: Option<String>,
+
+        /// 与被控端配对 (仅 QUIC 传输，需要 pairing feature): 本次用 PIN 验证，之后免 PIN 连接，
+        /// 并固定被控端的设备公钥
+        #[arg(long)]
+        pair: bool,
+    },
+
+    /// 连接前探测被控端 (可达性、RTT、协议版本、支持的功能)
+    Probe {
         /// 被控端 IP 地址 (局域网模式)
         #[arg(long, conflicts_with = "url")]
         ip: Option<String>,
@@ -77,9 +178,85 @@ pub enum Commands {
         /// 被控端端口 (仅 --ip 时使用，默认 9527)
         #[arg(short, long, default_value = "9527")]
         port: u16,
+
+        /// 超时时间 (秒)
+        #[arg(long, default_value = "5")]
+        timeout: u64,
+    },
+
+    /// 查看系统信息 (系统版本、运行时间、CPU/内存负载、占用最高的进程、磁盘、电池)，
+    /// 指定 --ip/--url 时查询被控端，否则显示本机
+    Info {
+        /// 被控端 IP 地址 (局域网模式)
+        #[arg(long, conflicts_with = "url")]
+        ip: Option<String>,
+
+        /// 被控端公网 URL (隧道模式，如 wss://xxx.trycloudflare.com)
+        #[arg(long, conflicts_with = "ip")]
+        url: Option<String>,
+
+        /// 被控端端口 (仅 --ip 时使用，默认 9527)
+        #[arg(short, long, default_value = "9527")]
+        port: u16,
+
+        /// 被控端配置了认证时使用的 API Key 或 Bearer Token
+        #[arg(long, env = "SSCONTROL_TOKEN")]
+        token: Option<String>,
+
+        /// 被控端显示的 PIN 或无人值守访问密钥 (被控端启用 PIN 时需要)
+        #[arg(long, env = "SSCONTROL_PIN")]
+        pin: Option<String>,
+
+        /// 身份验证器 App 中的 6 位验证码 (被控端启用 TOTP 双因素验证时需要)
+        #[arg(long, env = "SSCONTROL_TOTP")]
+        totp: Option<String>,
+
+        /// 显示 CPU 占用最高的进程数
+        #[arg(long, default_value = "10")]
+        top: usize,
+
+        /// 以 JSON 输出 (便于脚本处理)
+        #[arg(long)]
+        json: bool,
+
+        /// 超时时间 (秒)
+        #[arg(long, default_value = "10")]
+        timeout: u64,
+    },
+
+    /// 截取屏幕画面 (PNG / JPEG)
+    Screenshot {
+        /// 屏幕索引 (默认主显示器)
+        #[arg(long)]
+        screen: Option<u32>,
+
+        /// 输出文件 (扩展名 .jpg/.jpeg 为 JPEG，其余为 PNG；默认 screenshot-时间.png)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// JPEG 质量 (1-100)
+        #[arg(long, default_value = "85", value_parser = clap::value_parser!(u8).range(1..=100))]
+        quality: u8,
+    },
+
+    /// 在本机执行电源操作: lock (锁屏)、logout (注销)、reboot (重启)、shutdown (关机)
+    Power {
+        /// 电源操作
+        action: PowerAction,
+
+        /// 不询问确认 (注销、重启、关机默认需要确认)
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// 编码器能力: 列出探测到的编码器 (--encoder 的取值)
+    Encoders {
+        #[command(subcommand)]
+        action: EncoderCommands,
     },
 
-    /// 列出可用编码器
+    /// 列出可用编码器 (同 encoders list)
+    #[command(hide = true)]
     ListEncoders,
 
     /// 编码器性能测试
@@ -97,8 +274,27 @@ pub enum Commands {
         height: u32,
     },
 
-    /// 网络诊断
+    /// 采集和编码吞吐量测试 (仅采集、采集+颜色转换、每个可用编码器的采集+编码)，使用全局 --screen/--fps/--bitrate
+    Bench {
+        /// 每个阶段的时长 (秒)
+        #[arg(long, default_value = "5")]
+        duration: u64,
+
+        /// 只测试这些编码器 (software/nvenc/amf/qsv/videotoolbox/vp8，可重复)
+        #[arg(long = "only", value_name = "ENCODER")]
+        only: Vec<String>,
+
+        /// 以 JSON 输出结果 (便于比较不同版本的性能)
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// 环境和网络诊断 (权限、屏幕捕获、FFmpeg、编码器、防火墙、STUN)，并给出修复方法
     Doctor {
+        /// 检查的被控端信令端口
+        #[arg(short, long, default_value = "9527")]
+        port: u16,
+
         /// 详细 NAT 检测
         #[arg(long)]
         nat: bool,
@@ -106,27 +302,113 @@ pub enum Commands {
         /// 网络质量测试
         #[arg(long)]
         quality: bool,
+
+        /// NAT 检测使用的 STUN 服务器 (可重复，默认使用配置文件中的 webrtc.stun_servers)
+        #[arg(long = "stun", value_name = "URL")]
+        stun: Vec<String>,
+    },
+
+    /// 运行 STUN 服务 (供 NAT 检测和 ICE 收集公网候选)
+    StunServer {
+        /// 监听端口 (UDP)
+        #[arg(short, long, default_value = "3478")]
+        port: u16,
     },
 
     /// 显示系统信息
     SysInfo,
 
-    /// 生成配置文件
+    /// 配置文件: 生成 (不带子命令)、校验、查看和修改
     Config {
         /// 配置文件路径
-        #[arg(short, long)]
+        #[arg(short, long, global = true)]
         path: Option<String>,
+
+        #[command(subcommand)]
+        action: Option<ConfigCommands>,
     },
 
     /// 实时性能监控
     Stats,
+
+    /// 认证管理: TOTP 双因素验证和 mTLS 设备证书 (需要 security feature)
+    Auth {
+        #[command(subcommand)]
+        action: AuthCommands,
+    },
+
+    /// 会话审计日志: 查看记录、校验哈希链
+    Audit {
+        #[command(subcommand)]
+        action: AuditCommands,
+    },
+
+    /// 发现局域网内的被控端，实时显示上线、变化和下线 (mDNS，需要 discovery feature)
+    Discover {
+        /// 运行多少秒后退出 (默认一直运行到 Ctrl+C)
+        #[arg(long)]
+        timeout: Option<u64>,
+    },
+
+    /// 地址簿: 保存常用被控端，之后用 sscontrol connect <别名> 连接
+    Hosts {
+        #[command(subcommand)]
+        action: HostsCommands,
+    },
+
+    /// 设备配对: 查看本机设备指纹、管理已配对设备 (需要 pairing feature)
+    Pair {
+        #[command(subcommand)]
+        action: PairCommands,
+    },
+
+    /// 检查并安装新版本: 校验签名后替换可执行文件，并重启正在运行的服务 (需要 update feature)
+    Update {
+        /// 只检查是否有新版本，不下载安装
+        #[arg(long)]
+        check_only: bool,
+    },
+
+    /// 通过 SSH 在远程服务器上部署服务 (需要 deploy feature)
+    Deploy {
+        #[command(subcommand)]
+        action: DeployCommands,
+    },
+
+    /// 查看登记到信令服务器的设备 (被控端配置 fleet.server 后自动登记)
+    Fleet {
+        #[command(subcommand)]
+        action: FleetCommands,
+    },
+}
+
+/// 控制端传输方式
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    /// WebRTC (在浏览器中打开查看器)
+    Webrtc,
+    /// QUIC (命令行直连，单条 QUIC 连接承载视频和输入)
+    Quic,
+}
+
+/// 日志格式
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// 人类可读的文本
+    Text,
+    /// 单行 JSON (用于 Loki / Elasticsearch 等日志系统)
+    Json,
 }
 
 /// 服务命令
 #[derive(Subcommand, Debug)]
 pub enum ServiceCommands {
     /// 安装服务
-    Install,
+    Install {
+        /// 跳过屏幕录制和辅助功能权限检查 (macOS)
+        #[arg(long)]
+        skip_permission_check: bool,
+    },
     /// 卸载服务
     Uninstall,
     /// 启动服务
@@ -135,4 +417,446 @@ pub enum ServiceCommands {
     Stop,
     /// 查看服务状态
     Status,
+    /// 检查屏幕录制和辅助功能权限 (macOS，供安装向导调用，退出码表示缺少的权限)
+    #[command(hide = true)]
+    CheckPermissions,
+}
+
+/// 认证管理命令
+#[derive(Subcommand, Debug)]
+pub enum AuthCommands {
+    /// 生成 TOTP 密钥并写入配置，显示供身份验证器 App 扫描的二维码
+    Enroll {
+        /// 身份验证器 App 中显示的账户名 (默认为主机名)
+        #[arg(long)]
+        account: Option<String>,
+
+        /// 已配置密钥时强制换发 (之前绑定的身份验证器将失效)
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// 为设备签发 mTLS 客户端证书 (首次使用时生成设备 CA)
+    IssueCert {
+        /// 设备名 (证书通用名称，也用作文件名)
+        device: String,
+
+        /// 证书目录 (默认为配置文件旁的 certs 目录)
+        #[arg(long)]
+        dir: Option<String>,
+    },
+}
+
+/// 配置文件命令
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommands {
+    /// 校验配置文件，列出全部错误和警告
+    Validate,
+
+    /// 显示配置 (密钥类配置项以 *** 代替)
+    Show {
+        /// 只显示该配置项 (如 capture.fps 或 capture)
+        key: Option<String>,
+    },
+
+    /// 修改单个配置项并写回文件 (运行中的被控端会自动应用帧率、码率等采集参数)
+    Set {
+        /// 配置项 (如 capture.fps)
+        key: String,
+
+        /// 新值 (按 TOML 解析，如 30、true、"text"、["a", "b"]；无法解析时作为字符串)
+        value: String,
+    },
+}
+
+/// 编码器命令
+#[derive(Subcommand, Debug)]
+pub enum EncoderCommands {
+    /// 列出编码器及其能力 (使用缓存的探测结果)
+    List {
+        /// 忽略缓存，重新探测并更新缓存
+        #[arg(long)]
+        refresh: bool,
+
+        /// 以 JSON 输出
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// 审计日志命令
+#[derive(Subcommand, Debug)]
+pub enum AuditCommands {
+    /// 列出审计记录
+    List {
+        /// 审计日志路径 (默认使用配置中的 host.audit_log)
+        #[arg(long)]
+        file: Option<String>,
+
+        /// 只显示该 Viewer 的记录
+        #[arg(long)]
+        peer: Option<String>,
+
+        /// 只显示该会话的记录
+        #[arg(long)]
+        session: Option<String>,
+
+        /// 只显示最近 N 条
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+
+    /// 校验哈希链，检查记录是否被修改、删除或插入
+    Verify {
+        /// 审计日志路径 (默认使用配置中的 host.audit_log)
+        #[arg(long)]
+        file: Option<String>,
+    },
+}
+
+/// 地址簿命令
+#[derive(Subcommand, Debug)]
+pub enum HostsCommands {
+    /// 添加被控端
+    Add {
+        /// 别名 (如 office-pc)
+        alias: String,
+
+        /// 被控端 IP 地址 (可重复指定)
+        #[arg(long)]
+        ip: Vec<String>,
+
+        /// 公网隧道 URL (局域网地址都不可达时使用)
+        #[arg(long)]
+        url: Option<String>,
+
+        /// 被控端端口 (默认 9527)
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// 默认传输方式
+        #[arg(long, value_enum)]
+        transport: Option<Transport>,
+
+        /// 被控端设备指纹 (被控端 sscontrol pair show 显示，用于 mDNS 识别)
+        #[arg(long)]
+        fingerprint: Option<String>,
+
+        /// 被控端设备 ID (被控端配置中的 server.device_id，用于 mDNS 识别)
+        #[arg(long)]
+        device_id: Option<String>,
+
+        /// 别名已存在时替换
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// 列出地址簿
+    List,
+
+    /// 删除被控端
+    Remove {
+        /// 别名
+        alias: String,
+    },
+
+    /// 通过 mDNS 查找局域网内的被控端，更新保存的地址 (需要 discovery feature)
+    Refresh {
+        /// 只刷新该别名 (默认全部)
+        alias: Option<String>,
+
+        /// 等待 mDNS 响应的时间 (秒)
+        #[arg(long, default_value = "3")]
+        timeout: u64,
+    },
+}
+
+/// 设备配对命令
+#[derive(Subcommand, Debug)]
+pub enum PairCommands {
+    /// 显示本机设备指纹和二维码 (首次使用时生成设备密钥)
+    Show,
+
+    /// 列出已配对设备
+    List,
+
+    /// 从设备二维码内容 (sscontrol://device?...) 添加信任的设备
+    Add {
+        /// 对方 `sscontrol pair show` 显示的 URL
+        url: String,
+
+        /// viewer: 允许该设备免 PIN 控制本机；host: 固定该被控端的设备公钥
+        #[arg(long, value_enum, default_value = "viewer")]
+        role: PairRole,
+    },
+
+    /// 删除已配对设备
+    Remove {
+        /// 设备名或指纹前缀 (至少 4 位)
+        device: String,
+    },
+}
+
+/// 配对设备的角色
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PairRole {
+    /// 控制端 (可以控制本机)
+    Viewer,
+    /// 被控端 (本机连接的主机)
+    Host,
+}
+
+/// 内嵌信令服务器的防滥用限制
+#[derive(clap::Args, Debug, Clone)]
+pub struct SignalingLimits {
+    /// 每个 IP 的最大并发 WebSocket 连接数
+    #[arg(long, env = "SSCONTROL_MAX_CONNECTIONS_PER_IP", default_value_t = 8)]
+    pub max_connections_per_ip: usize,
+
+    /// 每个连接每秒允许的信令消息数 (允许 2 倍突发，持续超限时断开)
+    #[arg(long, env = "SSCONTROL_MAX_MESSAGES_PER_SEC", default_value_t = 50)]
+    pub max_messages_per_sec: u32,
+
+    /// 同时存在的房间数上限 (默认不限制)
+    #[arg(long, env = "SSCONTROL_MAX_ROOMS")]
+    pub max_rooms: Option<usize>,
+
+    /// 同一 IP 认证失败多少次后封禁 (0 = 不封禁)
+    #[arg(long, env = "SSCONTROL_AUTH_BAN_THRESHOLD", default_value_t = 5)]
+    pub auth_ban_threshold: u32,
+
+    /// 封禁时长 (秒)
+    #[arg(long, env = "SSCONTROL_AUTH_BAN_SECS", default_value_t = 900)]
+    pub auth_ban_secs: u64,
+}
+
+/// WebRTC ICE 服务器 (指定时代替配置文件中的 webrtc.stun_servers / webrtc.turn_servers)
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct IceArgs {
+    /// STUN 服务器 (如 stun:stun.example.com:3478，可重复指定)
+    #[arg(long = "stun", value_name = "URL")]
+    pub stun: Vec<String>,
+
+    /// TURN 中继服务器，凭证写在地址中 (如 turn:用户名:密码@turn.example.com:3478，可重复指定；
+    /// 环境变量中多个地址用逗号分隔)
+    #[arg(long = "turn", env = "SSCONTROL_TURN", value_name = "URL", value_delimiter = ',', hide_env_values = true)]
+    pub turn: Vec<TurnServerConfig>,
+
+    /// 只经 TURN 中继连接 (不暴露本机和公网地址，需要 --turn 或配置 webrtc.turn_servers)
+    #[arg(long)]
+    pub relay_only: bool,
+
+    /// 不向 Viewer 通告本机地址候选 (只发送公网映射和中继候选)
+    #[arg(long)]
+    pub hide_host_candidates: bool,
+
+    /// ICE 只使用该网段内的本机地址 (CIDR，可重复指定)
+    #[arg(long = "ice-network", value_name = "CIDR")]
+    pub networks: Vec<Subnet>,
+
+    /// ICE 只使用该网卡 (可重复指定)
+    #[arg(long = "ice-interface", value_name = "NAME")]
+    pub interfaces: Vec<String>,
+}
+
+impl IceArgs {
+    /// 用命令行指定的服务器覆盖配置
+    pub fn apply(&self, webrtc: &mut WebRTCConfig) {
+        if !self.stun.is_empty() {
+            webrtc.stun_servers = self.stun.clone();
+        }
+        if !self.turn.is_empty() {
+            webrtc.turn_servers = self.turn.clone();
+        }
+        if self.relay_only {
+            webrtc.ice_transport_policy = "relay".to_string();
+        }
+        if self.hide_host_candidates {
+            webrtc.host_candidates = false;
+        }
+        if !self.networks.is_empty() {
+            webrtc.candidate_networks = self.networks.iter().map(Subnet::to_string).collect();
+        }
+        if !self.interfaces.is_empty() {
+            webrtc.candidate_interfaces = self.interfaces.clone();
+        }
+    }
+}
+
+/// 设备清单命令
+#[derive(Subcommand, Debug)]
+pub enum FleetCommands {
+    /// 列出设备的在线状态、最后在线时间和版本
+    List {
+        /// 信令服务器地址 (默认配置文件中的 fleet.server)
+        #[arg(long)]
+        url: Option<String>,
+
+        /// 信令服务器配置了认证时使用的 API Key 或 Bearer Token (默认 fleet.token / security.api_key)
+        #[arg(long, env = "SSCONTROL_TOKEN")]
+        token: Option<String>,
+
+        /// 只显示在线设备
+        #[arg(long)]
+        online: bool,
+
+        /// 以 JSON 输出 (便于脚本处理)
+        #[arg(long)]
+        json: bool,
+
+        /// 超时时间 (秒)
+        #[arg(long, default_value = "10")]
+        timeout: u64,
+    },
+}
+
+/// 部署命令
+#[derive(Subcommand, Debug)]
+pub enum DeployCommands {
+    /// 安装并配置 coturn TURN 服务器，生成长期凭证并写入本地配置的 webrtc.turn_servers
+    Turn {
+        #[command(flatten)]
+        target: DeployTarget,
+
+        #[command(flatten)]
+        turn: TurnArgs,
+
+        /// 只打印凭证，不写入本地配置文件
+        #[arg(long)]
+        no_save: bool,
+    },
+
+    /// 在远程 Linux / macOS 主机上安装 sscontrol 服务，写入预配配置并确认连上信令服务器
+    Agent {
+        #[command(flatten)]
+        target: DeployTarget,
+
+        /// 信令服务器地址 (写入 server.url)
+        #[arg(long, value_name = "URL")]
+        signaling_url: String,
+
+        /// API Key (写入 security.api_key)
+        #[arg(long, env = "SSCONTROL_API_KEY", hide_env_values = true)]
+        api_key: Option<String>,
+
+        /// 设备 ID (写入 server.device_id，默认按远程主机名生成)
+        #[arg(long)]
+        device_id: Option<String>,
+
+        /// 上传的 sscontrol 可执行文件 (默认当前程序；远程系统或架构不同时必须指定)
+        #[arg(long, value_name = "FILE")]
+        binary: Option<std::path::PathBuf>,
+
+        /// 等待代理连上信令服务器的时间 (秒，0 = 不等待)
+        #[arg(long, default_value = "30")]
+        verify_timeout: u64,
+    },
+
+    /// 生成 docker-compose 部署文件: coturn TURN 服务器，以及可选的 Caddy TLS 反向代理
+    Compose {
+        /// 输出目录
+        #[arg(long, default_value = "sscontrol-deploy")]
+        output: std::path::PathBuf,
+
+        /// 部署的服务器地址 (TURN 地址；启用反向代理时必须是域名，用于申请证书)
+        #[arg(long)]
+        host: String,
+
+        #[command(flatten)]
+        turn: TurnArgs,
+
+        /// 不生成 TURN 服务
+        #[arg(long)]
+        no_turn: bool,
+
+        /// 反向代理的信令地址 (如经内网或 VPN 可达的被控端 10.8.0.2:9527)，对外提供 wss://<域名>
+        #[arg(long, value_name = "HOST:PORT")]
+        signaling_upstream: Option<String>,
+
+        /// 申请证书时登记的邮箱
+        #[arg(long)]
+        email: Option<String>,
+
+        /// 覆盖输出目录中已存在的文件
+        #[arg(long)]
+        force: bool,
+
+        /// 不把 TURN 凭证写入本地配置文件
+        #[arg(long)]
+        no_save: bool,
+    },
+}
+
+/// TURN 服务器参数
+#[derive(clap::Args, Debug, Clone)]
+pub struct TurnArgs {
+    /// TURN 监听端口 (UDP 和 TCP)
+    #[arg(long, default_value = "3478")]
+    pub port: u16,
+
+    /// 中继端口范围下限 (UDP)
+    #[arg(long, default_value = "49152")]
+    pub min_port: u16,
+
+    /// 中继端口范围上限 (UDP)
+    #[arg(long, default_value = "65535")]
+    pub max_port: u16,
+
+    /// 服务器的公网 IP (云主机的公网地址经 NAT 映射时需要，格式 公网IP 或 公网IP/内网IP)
+    #[arg(long)]
+    pub external_ip: Option<String>,
+
+    /// TURN 用户名
+    #[arg(long, default_value = "sscontrol")]
+    pub username: String,
+}
+
+/// 部署的目标服务器 (认证使用 SSH 密钥或 ssh-agent)
+#[derive(clap::Args, Debug, Clone)]
+pub struct DeployTarget {
+    /// 服务器地址 (IP 或域名)
+    #[arg(long)]
+    pub host: String,
+
+    /// SSH 用户 (非 root 用户需要免密 sudo)
+    #[arg(long, default_value = "root")]
+    pub user: String,
+
+    /// SSH 端口
+    #[arg(long, default_value = "22")]
+    pub ssh_port: u16,
+
+    /// SSH 私钥文件 (未指定时使用 ssh-agent 和默认密钥)
+    #[arg(long, value_name = "FILE")]
+    pub identity: Option<std::path::PathBuf>,
+
+    /// 跳板机 (可重复指定，按顺序经过)
+    #[arg(long = "jump", value_name = "USER@HOST[:PORT]")]
+    pub jumps: Vec<String>,
+
+    /// 把本地 ssh-agent 转发到目标服务器
+    #[arg(long)]
+    pub forward_agent: bool,
+
+    /// 首次连接时接受并记录未知的主机密钥 (默认只信任 known_hosts 中已有的密钥)
+    #[arg(long)]
+    pub accept_new_host_key: bool,
+
+    /// 校验主机密钥使用的 known_hosts 文件 (默认 ~/.ssh/known_hosts)
+    #[arg(long, value_name = "FILE")]
+    pub known_hosts: Option<std::path::PathBuf>,
+
+    /// SSH 保活间隔 (秒，0 = 关闭)
+    #[arg(long, default_value = "15")]
+    pub keepalive: u32,
+}
+
+/// 信令服务器多实例部署
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct ClusterArgs {
+    /// Redis 地址 (如 redis://127.0.0.1:6379)，设置后多个实例共享房间状态，可部署在负载均衡之后
+    /// (需要 redis feature)
+    #[cfg(feature = "redis")]
+    #[arg(long, env = "SSCONTROL_REDIS_URL", value_name = "URL")]
+    pub redis_url: Option<String>,
 }
@@ -5,21 +5,45 @@
 use anyhow::Result;
 use std::path::PathBuf;
 
+use crate::audit;
 use crate::config;
 use crate::encoder;
 use crate::encoder::hardware::{HardwareEncoder, HardwareEncoderConfig, HardwareEncoderType, EncoderPreset};
 use crate::encoder::Encoder;
 use crate::capture::{self, Frame};
 use crate::service::{self, ServiceController};
+use crate::cli::LogFormat;
+use crate::power::{self, PowerAction};
 use crate::tools;
 
 /// ServiceCommands enum (re-exported from cli for convenience)
 pub use crate::cli::ServiceCommands;
+pub use crate::cli::AuthCommands;
+pub use crate::cli::AuditCommands;
+pub use crate::cli::EncoderCommands;
+pub use crate::cli::ConfigCommands;
+pub use crate::cli::PairCommands;
+pub use crate::cli::HostsCommands;
+pub use crate::cli::DeployCommands;
+pub use crate::cli::FleetCommands;
+
+/// Select the capture source for this process
+///
+/// `--capture` overrides `capture.source` from the config file; synthetic frames use `[capture.synthetic]`.
+pub fn select_capture_source(cli: Option<capture::CaptureSource>) {
+    if cli == Some(capture::CaptureSource::Screen) {
+        return;
+    }
+    let config_path = config::Config::get_config_path(None);
+    let config = config::Config::load_unchecked(&config_path).unwrap_or_default();
+    if cli.unwrap_or(config.capture.source) == capture::CaptureSource::Synthetic {
+        capture::use_synthetic(config.capture.synthetic);
+    }
+}
 
 /// Initialize logging with the specified verbosity level
-pub fn init_logging(verbose: u8) {
+pub fn init_logging(verbose: u8, format: LogFormat) {
     use tracing::Level;
-    use tracing_subscriber;
     use std::str::FromStr;
 
     let log_level = match verbose {
@@ -31,19 +55,35 @@ pub fn init_logging(verbose: u8) {
 
     let level = Level::from_str(log_level).unwrap_or(Level::INFO);
 
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .with_level(true)
-        .with_max_level(level)
-        .init();
+    crate::logging::init(level, format == LogFormat::Json);
 }
 
 /// Handle service management commands
-pub fn handle_service_command(action: ServiceCommands) -> Result<()> {
-    let controller = service::create_controller();
+pub fn handle_service_command(action: ServiceCommands, daemon: bool) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    let controller = if daemon {
+        service::macos::MacOSLaunchAgent::daemon()
+    } else {
+        service::macos::MacOSLaunchAgent::new()
+    };
+    #[cfg(not(target_os = "macos"))]
+    let controller = {
+        if daemon {
+            anyhow::bail!("--daemon 只支持 macOS (Windows 服务和 systemd 服务本身就在登录前运行)");
+        }
+        service::create_controller()
+    };
 
     match action {
-        ServiceCommands::Install => {
+        ServiceCommands::Install { skip_permission_check } => {
+            // LaunchAgent/LaunchDaemon 没有终端可以弹出授权，安装前先确认权限
+            #[cfg(target_os = "macos")]
+            if !skip_permission_check {
+                service::macos::permission_assistant()?;
+            }
+            #[cfg(not(target_os = "macos"))]
+            let _ = skip_permission_check;
+
             println!("正在安装服务...");
             controller.install()?;
             println!("服务安装成功!");
@@ -65,50 +105,77 @@ pub fn handle_service_command(action: ServiceCommands) -> Result<()> {
             let status = controller.status()?;
             println!("服务状态: {}", status);
         }
+        ServiceCommands::CheckPermissions => {
+            #[cfg(target_os = "macos")]
+            std::process::exit(service::macos::PermissionStatus::current().exit_code());
+        }
     }
 
     Ok(())
 }
 
-/// Handle list encoders command
-pub fn handle_list_encoders() -> Result<()> {
-    use encoder::hardware::HardwareEncoderType;
+/// Handle encoders command
+pub fn handle_encoders_command(action: EncoderCommands) -> Result<()> {
+    match action {
+        EncoderCommands::List { refresh, json } => {
+            let path = encoder::probe::cache_path();
+            let report = match encoder::probe::load_cached(&path).filter(|_| !refresh) {
+                Some(report) => report,
+                None => {
+                    if !json {
+                        println!("正在探测编码器 (结果会缓存到 {})...", path.display());
+                    }
+                    let report = encoder::probe::probe();
+                    if let Err(e) = encoder::probe::save(&path, &report) {
+                        eprintln!("保存编码器能力缓存失败: {}", e);
+                    }
+                    report
+                }
+            };
 
-    println!("可用编码器列表:");
-    println!();
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(());
+            }
 
-    // 检测各个编码器
-    let encoders = vec![
-        (HardwareEncoderType::NVENC, "NVIDIA NVENC"),
-        (HardwareEncoderType::AMF, "AMD AMF"),
-        (HardwareEncoderType::QuickSync, "Intel Quick Sync"),
-        (HardwareEncoderType::VideoToolbox, "Apple VideoToolbox"),
-        (HardwareEncoderType::Software, "Software (x264)"),
-    ];
-
-    for (encoder_type, name) in encoders {
-        let available = match encoder_type {
-            #[cfg(target_os = "windows")]
-            HardwareEncoderType::NVENC => encoder::nvenc::NvencEncoder::is_available(),
-            #[cfg(target_os = "windows")]
-            HardwareEncoderType::AMF => encoder::amf::AmfEncoder::is_available(),
-            #[cfg(target_os = "windows")]
-            HardwareEncoderType::QuickSync => encoder::qsv::QuickSyncEncoder::is_available(),
-            #[cfg(target_os = "macos")]
-            HardwareEncoderType::VideoToolbox => {
-                encoder::videotoolbox::VideoToolboxEncoder::is_available()
+            println!("可用编码器列表:");
+            println!();
+            println!(
+                "  {:<14} {:<22} {:<10} {:<14} {:<16} {:<6} 低延迟",
+                "--encoder", "名称", "状态", "最大分辨率", "codec", "B 帧"
+            );
+            let yes_no = |value: bool| if value { "✓" } else { "✗" };
+            for capabilities in &report.encoders {
+                if capabilities.available {
+                    println!(
+                        "  {:<14} {:<22} {:<10} {:<14} {:<16} {:<6} {}",
+                        capabilities.id,
+                        capabilities.name,
+                        "✓ 可用",
+                        format!("{}x{}", capabilities.max_width, capabilities.max_height),
+                        capabilities.codecs.join(","),
+                        yes_no(capabilities.b_frames),
+                        yes_no(capabilities.low_latency)
+                    );
+                } else {
+                    println!(
+                        "  {:<14} {:<22} ✗ 不可用  {}",
+                        capabilities.id,
+                        capabilities.name,
+                        capabilities.error.as_deref().unwrap_or_default()
+                    );
+                }
             }
-            HardwareEncoderType::Software => true,
-            _ => false,
-        };
 
-        let status = if available { "✓ 可用" } else { "✗ 不可用" };
-        println!("  {:<25} {}", name, status);
+            println!();
+            if !report.drivers.is_empty() {
+                println!("显卡驱动: {}", report.drivers.join("; "));
+            }
+            println!("探测时间: {} (sscontrol encoders list --refresh 重新探测)", format_unix_time(report.probed_at));
+            println!("使用 --encoder <类型> 指定编码器，auto 按上表自动选择硬件编码器");
+            Ok(())
+        }
     }
-
-    println!();
-    println!("使用 --encoder <类型> 指定编码器");
-    Ok(())
 }
 
 /// Handle encoder benchmark command
@@ -130,6 +197,7 @@ pub async fn handle_benchmark(duration: u64, width: u32, height: u32) -> Result<
         height,
         timestamp: 0,
         stride,
+        gpu: None,
     };
 
     // 测试配置
@@ -195,13 +263,92 @@ pub async fn handle_benchmark(duration: u64, width: u32, height: u32) -> Result<
     Ok(())
 }
 
-/// Handle network diagnostics command
-pub async fn handle_doctor(nat: bool, quality: bool) -> Result<()> {
-    println!("sscontrol 网络诊断");
-    println!("==================");
+/// Handle capture and encode throughput benchmark command
+pub async fn handle_bench(duration: u64, screen: Option<u32>, fps: u32, bitrate: u32, only: Vec<String>, json: bool) -> Result<()> {
+    let options = tools::bench::BenchOptions {
+        duration: std::time::Duration::from_secs(duration.max(1)),
+        screen_index: screen,
+        fps,
+        bitrate,
+        encoders: only,
+    };
+
+    if !json {
+        println!("采集和编码性能测试");
+        println!("==================");
+        println!("每个阶段 {} 秒，逐帧不限速运行", options.duration.as_secs());
+        println!();
+        println!(
+            "{:<30} {:>7} {:>16} {:>16} {:>7} {:>10}",
+            "阶段", "FPS", "采集 p50/95/99", "处理 p50/95/99", "CPU", "码率"
+        );
+    }
+
+    let report = tokio::task::spawn_blocking(move || {
+        tools::bench::run(&options, |stage| {
+            if json {
+                return;
+            }
+            if let Some(error) = &stage.error {
+                println!("{:<30} ❌ {}", stage.name, error);
+                return;
+            }
+            let ms = |summary: Option<tools::bench::LatencySummary>| summary.map_or("-".to_string(), |s| s.to_string());
+            println!(
+                "{:<30} {:>7.1} {:>16} {:>16} {:>7} {:>10}",
+                stage.name,
+                stage.fps,
+                ms(stage.capture_ms),
+                ms(stage.process_ms),
+                stage.cpu_percent.map_or("-".to_string(), |cpu| format!("{:.0}%", cpu)),
+                stage.bitrate_kbps.map_or("-".to_string(), |kbps| format!("{:.0}kbps", kbps)),
+            );
+            if stage.errors > 0 {
+                println!("{:<30} ⚠️  {} 次失败 (-v 2 查看详情)", "", stage.errors);
+            }
+        })
+    })
+    .await??;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
     println!();
+    println!("分辨率: {}x{}，逻辑 CPU: {} (CPU 占用按单核计算)", report.width, report.height, report.cpus);
+    match report.recommended_encoder() {
+        Some(stage) => println!(
+            "推荐编码器: {} (sscontrol --encoder {} host)",
+            stage.name.trim_start_matches("采集+编码 "),
+            stage.encoder.as_deref().unwrap_or("auto")
+        ),
+        None if cfg!(feature = "h264") => println!("没有可用的 H.264 编码器"),
+        None => println!("编码测试需要 h264 feature (cargo build --features h264)"),
+    }
+
+    Ok(())
+}
+
+/// Handle environment and network diagnostics command
+pub async fn handle_doctor(port: u16, nat: bool, quality: bool, stun_servers: Vec<String>) -> Result<()> {
+    println!("sscontrol 环境诊断");
+    println!("==================");
+
+    // 配置文件中的 QUIC 开关和 STUN 服务器 (没有配置文件时使用默认值)
+    let config_path = config::Config::get_config_path(None);
+    let config = config::Config::load_unchecked(&config_path).unwrap_or_default();
+    let options = tools::doctor::DoctorOptions {
+        port,
+        quic: config.host.quic,
+        stun_servers: if stun_servers.is_empty() { config.webrtc.stun_servers.clone() } else { stun_servers.clone() },
+    };
+    let result = tools::doctor::run(&options).await;
+    println!("{}", tools::diagnostic::format_diagnostic_result(&result));
 
-    // 运行基础诊断
+    // 运行基础网络诊断
+    println!("网络诊断");
+    println!("==================");
     tools::diagnostic::print_diagnostics();
 
     // 如果需要 NAT 检测
@@ -209,8 +356,7 @@ pub async fn handle_doctor(nat: bool, quality: bool) -> Result<()> {
         println!();
         println!("NAT 类型检测:");
         println!("===============");
-        // TODO: 实现详细的 NAT 检测
-        println!("NAT 详细检测功能即将推出...");
+        print_nat_report(stun_servers).await;
     }
 
     // 如果需要网络质量测试
@@ -225,6 +371,231 @@ pub async fn handle_doctor(nat: bool, quality: bool) -> Result<()> {
     Ok(())
 }
 
+/// 使用 STUN 检测 NAT 类型并打印结果
+async fn print_nat_report(stun_servers: Vec<String>) {
+    use crate::nat::{detector::TraversalDifficulty, NatConfig, NatDetector};
+
+    let stun_servers = if stun_servers.is_empty() {
+        let config_path = config::Config::get_config_path(None);
+        config::Config::load(&config_path)
+            .map(|c| c.webrtc.stun_servers)
+            .unwrap_or_default()
+    } else {
+        stun_servers
+    };
+    if stun_servers.is_empty() {
+        println!("  未配置 STUN 服务器，请使用 --stun <URL> 或在配置文件中设置 webrtc.stun_servers");
+        println!("  可在公网主机上运行 'sscontrol stun-server' 自建 STUN 服务");
+        return;
+    }
+    for server in &stun_servers {
+        println!("  STUN 服务器: {}", server);
+    }
+    if stun_servers.len() < 2 {
+        println!("  (只有一个 STUN 服务器，无法识别对称 NAT)");
+    }
+
+    let detector = NatDetector::new(NatConfig {
+        stun_servers,
+        ..Default::default()
+    });
+    let behavior = match detector.detect_nat_type().await {
+        Ok(behavior) => behavior,
+        Err(e) => {
+            println!("  ✗ 检测失败: {}", e);
+            return;
+        }
+    };
+
+    println!("  NAT 类型: {:?}", behavior.nat_type);
+    if let (Some(ip), Some(port)) = (&behavior.external_ip, behavior.external_port) {
+        println!("  公网映射地址: {}:{}", ip, port);
+    }
+    println!("  端口分配: {:?}", behavior.port_allocation_pattern);
+    let difficulty = match detector.assess_difficulty(&behavior) {
+        TraversalDifficulty::Easy => "容易 (可直接连接)",
+        TraversalDifficulty::Medium => "中等 (需要打洞)",
+        TraversalDifficulty::Hard => "困难 (可能需要多次尝试)",
+        TraversalDifficulty::Impossible => "无法穿透 (需要中继)",
+    };
+    println!("  穿透难度: {}", difficulty);
+}
+
+/// 运行 STUN 服务直到 Ctrl+C
+pub async fn handle_stun_server(port: u16) -> Result<()> {
+    let socket = tokio::net::UdpSocket::bind(("0.0.0.0", port)).await?;
+    println!("在配置文件中设置 webrtc.stun_servers = [\"stun:<本机公网 IP>:{}\"]", port);
+    println!("按 Ctrl+C 停止");
+
+    tokio::select! {
+        result = crate::nat::stun::serve(socket) => result,
+        _ = tokio::signal::ctrl_c() => {
+            println!("STUN 服务已停止");
+            Ok(())
+        }
+    }
+}
+
+/// Handle probe command
+pub async fn handle_probe(ip: Option<&str>, url: Option<&str>, port: u16, timeout: u64) -> Result<()> {
+    use tools::probe::{self, ProbeTarget};
+
+    let target = match (ip, url) {
+        (_, Some(url)) => ProbeTarget::from_url(url)?,
+        (Some(ip), None) => ProbeTarget::from_ip(ip, port),
+        (None, None) => anyhow::bail!("必须指定 --ip 或 --url 参数"),
+    };
+
+    println!("sscontrol 连接探测");
+    println!("==================");
+    println!();
+    println!("目标: {}", target);
+
+    let report = probe::probe(target, std::time::Duration::from_secs(timeout)).await;
+    let mark = |ok: bool| if ok { "✓" } else { "✗" };
+
+    println!("  信令端口: {}", if report.reachable { "✓ 可达" } else { "✗ 不可达" });
+    if let Some(rtt) = report.rtt_ms {
+        println!("  RTT: {:.1} ms", rtt);
+    }
+
+    if let Some(ref caps) = report.capabilities {
+        println!();
+        println!("被控端:");
+        println!("  版本: {}", caps.version);
+        println!(
+            "  协议版本: {} (兼容 >= {}) {}",
+            caps.protocol_version,
+            caps.min_protocol_version,
+            if caps.is_compatible() { "✓ 兼容" } else { "✗ 不兼容" }
+        );
+        println!("  需要认证: {}", if caps.auth_required { "是" } else { "否" });
+        println!();
+        println!("功能支持:");
+        println!("  WebRTC: {}", mark(caps.features.webrtc));
+        println!("  H.264: {}", mark(caps.features.h264));
+        println!("  音频: {}", mark(caps.features.audio));
+        println!("  文件传输: {}", mark(caps.features.file_transfer));
+        if !caps.codecs.is_empty() {
+            println!("  视频 codec: {}", caps.codecs.join(", "));
+        }
+    }
+
+    if let Some(ref error) = report.error {
+        println!();
+        println!("错误: {}", error);
+    }
+
+    println!();
+    if report.is_ready() {
+        println!("结论: ✓ 可以建立会话");
+        Ok(())
+    } else {
+        anyhow::bail!("被控端当前无法建立会话")
+    }
+}
+
+/// Handle info command (remote host via `/sysinfo`, or the local machine)
+///
+/// The remote query first passes PIN/TOTP on `/ws` and waits for host approval,
+/// like a viewer does, then presents the one-time ticket to `/sysinfo`.
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_info(
+    ip: Option<&str>,
+    url: Option<&str>,
+    port: u16,
+    token: Option<&str>,
+    pin: Option<&str>,
+    totp: Option<&str>,
+    top: usize,
+    json: bool,
+    timeout: u64,
+) -> Result<()> {
+    use crate::system::{SysInfoRequest, SystemReport};
+    use tools::probe::{self, ProbeTarget};
+
+    let top = SysInfoRequest { top: Some(top) }.top();
+    let target = match (ip, url) {
+        (_, Some(url)) => Some(ProbeTarget::from_url(url)?),
+        (Some(ip), None) => Some(ProbeTarget::from_ip(ip, port)),
+        (None, None) => None,
+    };
+
+    let report = match &target {
+        Some(target) => {
+            let path = format!("/sysinfo?top={}", top);
+            let request = probe::get_with_ticket(target, &path, token, pin, totp);
+            let (status, body) = tokio::time::timeout(std::time::Duration::from_secs(timeout), request)
+                .await
+                .map_err(|_| anyhow::anyhow!("获取系统信息超时 ({} 秒)", timeout))??;
+            match status {
+                200 => serde_json::from_slice::<SystemReport>(&body)
+                    .map_err(|e| anyhow::anyhow!("解析系统信息失败: {}", e))?,
+                401 => anyhow::bail!("被控端要求认证，请使用 --token 或 SSCONTROL_TOKEN 提供 API Key"),
+                404 => anyhow::bail!("被控端版本过旧，不支持系统信息查询"),
+                code => anyhow::bail!("获取系统信息失败: HTTP {}", code),
+            }
+        }
+        None => tokio::task::spawn_blocking(move || SystemReport::collect(top)).await?,
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    match &target {
+        Some(target) => println!("被控端系统信息: {}", target),
+        None => println!("本机系统信息"),
+    }
+    println!("================");
+    println!();
+    print!("{}", report);
+    Ok(())
+}
+
+/// Handle screenshot command
+pub fn handle_screenshot(screen: Option<u32>, output: Option<String>, quality: u8) -> Result<()> {
+    let path = output.map(PathBuf::from).unwrap_or_else(|| {
+        PathBuf::from(format!("screenshot-{}.png", chrono::Local::now().format("%Y%m%d-%H%M%S")))
+    });
+    let format = match capture::ImageFormat::from_path(&path) {
+        capture::ImageFormat::Jpeg { .. } => capture::ImageFormat::Jpeg { quality },
+        format => format,
+    };
+
+    let screenshot = capture::capture_screenshot(screen, format)?;
+    screenshot.save(&path)?;
+    println!(
+        "已保存截图: {} ({}x{}, {} KB)",
+        path.display(),
+        screenshot.width,
+        screenshot.height,
+        screenshot.data.len() / 1024
+    );
+    Ok(())
+}
+
+/// Handle power command
+pub fn handle_power(action: PowerAction, yes: bool) -> Result<()> {
+    if action != PowerAction::Lock && !yes {
+        print!("确定要{}吗? (y/N): ", action.label());
+        use std::io::Write;
+        std::io::stdout().flush().ok();
+
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input).ok();
+        if !input.trim().to_lowercase().starts_with('y') {
+            println!("已取消");
+            return Ok(());
+        }
+    }
+
+    power::execute(action)?;
+    println!("✓ 已执行: {}", action.label());
+    Ok(())
+}
+
 /// Handle system info command
 pub fn handle_sysinfo() -> Result<()> {
     println!("sscontrol 系统信息");
@@ -343,6 +714,656 @@ pub fn handle_generate_config(path: Option<String>) -> Result<()> {
     Ok(())
 }
 
+/// Handle config validate/show/set commands
+pub fn handle_config_command(path: Option<String>, action: ConfigCommands) -> Result<()> {
+    use anyhow::anyhow;
+    use config::edit;
+    use config::validate::Severity;
+
+    let config_path = config::Config::get_config_path(path.as_deref());
+    match action {
+        ConfigCommands::Validate => {
+            if !std::path::Path::new(&config_path).exists() {
+                anyhow::bail!("配置文件不存在: {}", config_path);
+            }
+            let config = config::Config::load_unchecked(&config_path)?;
+            let issues = config.validate();
+            for issue in &issues {
+                println!("{}", issue);
+            }
+            let errors = issues.iter().filter(|issue| issue.severity == Severity::Error).count();
+            if errors > 0 {
+                anyhow::bail!("{} 有 {} 处错误，{} 处警告", config_path, errors, issues.len() - errors);
+            }
+            println!("✓ 配置有效: {} ({} 处警告)", config_path, issues.len());
+            Ok(())
+        }
+        ConfigCommands::Show { key } => {
+            let config = config::Config::load_unchecked(&config_path)?;
+            let table = edit::masked(&config)?;
+            let output = match key.as_deref() {
+                None => toml::to_string_pretty(&table)?,
+                Some(key) => match edit::lookup(&table, key) {
+                    Some(toml::Value::Table(section)) => toml::to_string_pretty(section)?,
+                    Some(value) => format!("{} = {}", key, value),
+                    None => return Err(anyhow!("配置项不存在或未设置: {}", key)),
+                },
+            };
+            println!("{}", output.trim_end());
+            Ok(())
+        }
+        ConfigCommands::Set { key, value } => {
+            let content = if std::path::Path::new(&config_path).exists() {
+                std::fs::read_to_string(&config_path).map_err(|e| anyhow!("读取 {} 失败: {}", config_path, e))?
+            } else {
+                // 文件不存在时以默认配置为基础 (固定下随机生成的设备 ID)
+                toml::to_string_pretty(&config::Config::default())?
+            };
+            let (content, _) = edit::set_value(&content, &key, &value)?;
+            std::fs::write(&config_path, content).map_err(|e| anyhow!("写入 {} 失败: {}", config_path, e))?;
+
+            println!("✓ 已设置 {} ({})", key, config_path);
+            if config::watch::LIVE_KEYS.contains(&key.as_str()) {
+                println!("  运行中的被控端会自动应用");
+            } else {
+                println!("  运行中的被控端需要重启后生效");
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Handle authentication management commands
+pub fn handle_auth_command(action: AuthCommands) -> Result<()> {
+    match action {
+        AuthCommands::Enroll { account, force } => handle_totp_enroll(account, force),
+        AuthCommands::IssueCert { device, dir } => handle_issue_cert(device, dir),
+    }
+}
+
+/// Generate a TOTP secret, save it to the config and show the provisioning QR code
+#[cfg(feature = "security")]
+fn handle_totp_enroll(account: Option<String>, force: bool) -> Result<()> {
+    use crate::security::totp;
+    use anyhow::anyhow;
+
+    let config_path = config::Config::get_config_path(None);
+    let mut config = config::Config::load(&config_path)?;
+    if config.host.totp_secret.is_some() && !force {
+        anyhow::bail!("已配置 TOTP 密钥 (host.totp_secret)，换发会使已绑定的身份验证器失效，确认请加 --force");
+    }
+
+    let account = account
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .unwrap_or_else(|| "host".to_string());
+    let secret = totp::generate_secret();
+    let uri = totp::provisioning_uri(&secret, &account);
+
+    config.host.totp_secret = Some(secret.clone());
+    config
+        .save(&config_path)
+        .map_err(|e| anyhow!("保存 TOTP 密钥到 {} 失败: {}", config_path, e))?;
+
+    println!("用身份验证器 App (Google Authenticator、1Password 等) 扫描二维码:");
+    println!();
+    println!("{}", totp::render_qr(&uri)?);
+    println!("无法扫描时手动输入密钥: {}", secret);
+    println!("或导入 URI: {}", uri);
+    println!();
+    println!("✓ TOTP 密钥已保存到 {}", config_path);
+    println!("  重新启动被控端后，Viewer 连接时除 PIN 外还需输入 App 中的 6 位验证码");
+
+    Ok(())
+}
+
+#[cfg(not(feature = "security"))]
+fn handle_totp_enroll(_account: Option<String>, _force: bool) -> Result<()> {
+    anyhow::bail!("TOTP 双因素验证需要 security feature (cargo build --features security)")
+}
+
+/// Issue an mTLS client certificate for a device (creating the device CA on first use)
+#[cfg(feature = "security")]
+fn handle_issue_cert(device: String, dir: Option<String>) -> Result<()> {
+    use crate::security::mtls;
+
+    let dir = dir.map(PathBuf::from).unwrap_or_else(|| {
+        let config_path = config::Config::get_config_path(None);
+        std::path::Path::new(&config_path)
+            .parent()
+            .map(|parent| parent.join("certs"))
+            .unwrap_or_else(|| PathBuf::from("certs"))
+    });
+    let issued = mtls::issue_device_cert(&dir, &device)?;
+
+    println!("✓ 已为设备 {} 签发客户端证书", device);
+    println!("  证书: {}", issued.cert_path.display());
+    println!("  私钥: {}", issued.key_path.display());
+    println!();
+    println!("信令服务器配置 (只接受该 CA 签发的设备):");
+    println!("  tls_client_ca = \"{}\"", issued.ca_path.display());
+    println!("设备配置 ([security] 段，私钥请通过安全渠道拷贝到设备):");
+    println!("  tls_client_cert = \"{}\"", issued.cert_path.display());
+    println!("  tls_client_key = \"{}\"", issued.key_path.display());
+
+    Ok(())
+}
+
+#[cfg(not(feature = "security"))]
+fn handle_issue_cert(_device: String, _dir: Option<String>) -> Result<()> {
+    anyhow::bail!("mTLS 设备证书需要 security feature (cargo build --features security)")
+}
+
+/// Handle audit log commands
+pub fn handle_audit_command(action: AuditCommands) -> Result<()> {
+    match action {
+        AuditCommands::List { file, peer, session, limit } => {
+            let path = audit_log_path(file)?;
+            let records = audit::read_records(&path)?;
+            let selected: Vec<_> = records
+                .iter()
+                .filter(|record| peer.as_deref().is_none_or(|peer| record.event.peer() == peer))
+                .filter(|record| session.is_none() || record.session_id == session)
+                .collect();
+            let skip = limit.map_or(0, |limit| selected.len().saturating_sub(limit));
+
+            for record in &selected[skip..] {
+                match &record.session_id {
+                    Some(session_id) => {
+                        println!("{:>6}  {}  {} [会话 {}]", record.seq, record.time, record.event, session_id)
+                    }
+                    None => println!("{:>6}  {}  {}", record.seq, record.time, record.event),
+                }
+            }
+            println!();
+            println!("共 {} 条记录 (显示 {} 条)", records.len(), selected.len() - skip);
+            Ok(())
+        }
+        AuditCommands::Verify { file } => {
+            let path = audit_log_path(file)?;
+            let records = audit::read_records(&path)?;
+            let count = audit::verify(&records).map_err(|e| anyhow::anyhow!("审计日志校验失败: {}", e))?;
+            println!("✓ 审计日志完整: {} 条记录，哈希链未断开", count);
+            if let Some(last) = records.last() {
+                println!("  最新记录: #{} {}", last.seq, last.time);
+                println!("  最新哈希: {}", last.hash);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// 命令行指定的审计日志，未指定时使用配置中的 host.audit_log
+fn audit_log_path(file: Option<String>) -> Result<PathBuf> {
+    if let Some(file) = file {
+        return Ok(PathBuf::from(file));
+    }
+    let config_path = config::Config::get_config_path(None);
+    config::Config::load(&config_path)?
+        .host
+        .audit_log
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow::anyhow!("未配置审计日志 (host.audit_log)，请用 --file 指定"))
+}
+
+/// Show hosts on the local network as they appear, change and leave
+#[cfg(feature = "discovery")]
+pub async fn handle_discover(timeout: Option<u64>) -> Result<()> {
+    use crate::discovery::{DiscoveredPeer, DiscoveryEvent, MdnsDiscovery, PeerSource};
+
+    fn describe(peer: &DiscoveredPeer) -> String {
+        let mut line = format!("{:<20} {}", peer.name, crate::network::lan::host_port(&peer.ip_address.to_string(), peer.port));
+        if let Some(ref os) = peer.os {
+            line.push_str(&format!("  {}", os));
+        }
+        if let Some(ref version) = peer.version {
+            line.push_str(&format!("  v{}", version));
+        }
+        if let Some(screens) = peer.screens {
+            line.push_str(&format!("  {} 个显示器", screens));
+        }
+        if let Some(ref fingerprint) = peer.fingerprint {
+            line.push_str(&format!("  指纹 {}", fingerprint));
+        }
+        if peer.source == PeerSource::Broadcast {
+            line.push_str("  (UDP 广播)");
+        }
+        line
+    }
+
+    let broadcast_port = crate::connect_mode::broadcast_port();
+    let mut discovery = MdnsDiscovery::new()?;
+    discovery.enable_broadcast(broadcast_port);
+    let mut events = discovery.start()?;
+    println!("正在通过 mDNS 和 UDP 广播 (端口 {}) 发现局域网内的被控端 (按 Ctrl+C 退出)...", broadcast_port);
+    println!();
+
+    let watch = async {
+        while let Some(event) = events.next_event().await {
+            match event {
+                DiscoveryEvent::Added(peer) => println!("  + {}", describe(&peer)),
+                DiscoveryEvent::Updated(peer) => println!("  ~ {}", describe(&peer)),
+                DiscoveryEvent::Removed(peer) => println!("  - {} 已下线", peer.name),
+            }
+        }
+    };
+    let deadline = async {
+        match timeout {
+            Some(secs) => tokio::time::sleep(std::time::Duration::from_secs(secs)).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        _ = watch => {}
+        _ = deadline => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+
+    println!();
+    println!("当前在线 {} 台 (连接: sscontrol connect --ip <IP> --port <端口>)", discovery.get_peers().len());
+    Ok(())
+}
+
+#[cfg(not(feature = "discovery"))]
+pub async fn handle_discover(_timeout: Option<u64>) -> Result<()> {
+    anyhow::bail!("设备发现需要 discovery feature (cargo build --features discovery)")
+}
+
+/// Handle update command: check the release URL, install a signed update and restart the service
+#[cfg(feature = "update")]
+pub async fn handle_update(path: Option<String>, check_only: bool) -> Result<()> {
+    use crate::updater::{self, Updater};
+
+    let config = config::Config::load(config::Config::get_config_path(path.as_deref()))?;
+    if let Ok(exe) = std::env::current_exe() {
+        updater::swap::cleanup_backup(&exe);
+    }
+
+    let updater = Updater::new(config.update)?;
+    println!("当前版本: {}", updater::CURRENT_VERSION);
+    let Some(update) = updater.check().await? else {
+        println!("✓ 已是最新版本");
+        return Ok(());
+    };
+
+    println!("发现新版本: {} ({})", update.version, update.binary.name);
+    if let Some(url) = &update.release.html_url {
+        println!("发布说明: {}", url);
+    }
+    if check_only {
+        println!("运行 sscontrol update 安装");
+        return Ok(());
+    }
+
+    let exe = updater.install(&update).await?;
+    println!("✓ 已更新到 {} ({})", update.version, exe.display());
+
+    match tokio::task::spawn_blocking(updater::restart_service).await? {
+        Ok(true) => println!("✓ 服务已重启"),
+        Ok(false) => println!("服务未运行，下次启动时使用新版本"),
+        Err(e) => println!("⚠ 重启服务失败: {}，请手动重启 (sscontrol service stop && sscontrol service start)", e),
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "update"))]
+pub async fn handle_update(_path: Option<String>, _check_only: bool) -> Result<()> {
+    anyhow::bail!("自动更新需要 update feature (cargo build --features update)")
+}
+
+/// Handle deploy commands: install a service on a remote server over SSH
+#[cfg(feature = "deploy")]
+pub async fn handle_deploy_command(path: Option<String>, action: DeployCommands) -> Result<()> {
+    use crate::deploy::{compose::ComposeDeployment, turn::TurnDeployment, RemoteHost};
+
+    match action {
+        DeployCommands::Turn { target, turn, no_save } => {
+            let deployment = TurnDeployment::from_args(&target.host, &turn)?;
+            let remote = RemoteHost::from(&target);
+            println!("正在 {} 上部署 TURN 服务器 (coturn)...", remote.destination());
+            remote.run_script(&deployment.install_script()).await?;
+
+            println!();
+            println!("✓ TURN 服务器已启动");
+            report_turn_credentials(path, &deployment, no_save)
+        }
+        DeployCommands::Agent { target, signaling_url, api_key, device_id, binary, verify_timeout } => {
+            use crate::deploy::agent::{self, AgentProvision, RemotePlatform};
+
+            let remote = RemoteHost::from(&target);
+            let platform = RemotePlatform::parse(&remote.output(RemotePlatform::PROBE).await?)?;
+            println!("{}: {:?} {} ({})", remote.destination(), platform.os, platform.arch, platform.hostname);
+            let binary = match binary {
+                Some(binary) => binary,
+                None if platform.matches_local() => std::env::current_exe()?,
+                None => anyhow::bail!(
+                    "远程主机是 {:?} {}，与本机不同，请用 --binary 指定对应平台的 sscontrol",
+                    platform.os,
+                    platform.arch
+                ),
+            };
+            let provision = AgentProvision {
+                device_id: device_id.unwrap_or_else(|| platform.default_device_id()),
+                platform,
+                signaling_url,
+                api_key,
+                verify_timeout,
+            };
+            let script = provision.install_script()?;
+
+            let data = std::fs::read(&binary).map_err(|e| anyhow::anyhow!("读取 {} 失败: {}", binary.display(), e))?;
+            println!("正在上传 {} ({:.1} MB)...", binary.display(), data.len() as f64 / 1024.0 / 1024.0);
+            remote.upload(&data, agent::UPLOAD_PATH).await?;
+            remote.run_script(&script).await?;
+
+            println!();
+            println!("✓ {} 已部署，设备 ID: {}", remote.host, provision.device_id);
+            Ok(())
+        }
+        DeployCommands::Compose { output, host, turn, no_turn, signaling_upstream, email, force, no_save } => {
+            let turn = (!no_turn).then(|| TurnDeployment::from_args(&host, &turn)).transpose()?;
+            let deployment = ComposeDeployment::new(&host, turn, signaling_upstream, email)?;
+            for file in deployment.write(&output, force)? {
+                println!("✓ 已生成 {}", file.display());
+            }
+            println!();
+            let dir_name = output.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+            println!("复制到服务器后启动:");
+            println!("  scp -r {} <服务器>: && ssh <服务器> 'cd {} && docker compose up -d'", output.display(), dir_name);
+            if let Some(url) = deployment.signaling_url() {
+                println!("信令地址 (域名需解析到服务器，80/443 端口需放行):");
+                println!("  sscontrol connect --url {}", url);
+            }
+            match &deployment.turn {
+                Some(turn) => report_turn_credentials(path, turn, no_save),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+/// 打印 TURN 凭证，并写入本地配置的 webrtc.turn_servers
+#[cfg(feature = "deploy")]
+fn report_turn_credentials(path: Option<String>, deployment: &crate::deploy::turn::TurnDeployment, no_save: bool) -> Result<()> {
+    let server = deployment.server_config();
+    println!("TURN 服务器: {}", server.url);
+    println!("  用户名: {}", server.username);
+    println!("  密码:   {}", server.password);
+    println!(
+        "  请确认云服务商安全组已放行 {}/udp、{}/tcp 和 {}-{}/udp",
+        deployment.port, deployment.port, deployment.min_port, deployment.max_port
+    );
+    if no_save {
+        println!("  命令行使用: --turn turn:{}:{}@{}", server.username, server.password, server.url.trim_start_matches("turn:"));
+        return Ok(());
+    }
+    let config_path = config::Config::get_config_path(path.as_deref());
+    crate::deploy::turn::save_to_config(std::path::Path::new(&config_path), &server)?;
+    println!("✓ 已写入 {} 的 webrtc.turn_servers", config_path);
+    Ok(())
+}
+
+#[cfg(not(feature = "deploy"))]
+pub async fn handle_deploy_command(_path: Option<String>, _action: DeployCommands) -> Result<()> {
+    anyhow::bail!("远程部署需要 deploy feature (cargo build --features deploy)")
+}
+
+/// Handle fleet commands (device list from the signaling server's `/devices`)
+pub async fn handle_fleet_command(path: Option<String>, action: FleetCommands) -> Result<()> {
+    use crate::signaling::presence::DeviceStatus;
+    use tools::probe::{self, ProbeTarget};
+
+    match action {
+        FleetCommands::List { url, token, online, json, timeout } => {
+            let config = config::Config::load(config::Config::get_config_path(path.as_deref()))?;
+            let url = url
+                .or(config.fleet.server)
+                .ok_or_else(|| anyhow::anyhow!("未指定信令服务器，请使用 --url 或在配置文件中设置 fleet.server"))?;
+            let token = token.or(config.fleet.token).or(config.security.api_key);
+            let target = ProbeTarget::from_url(&url)?;
+
+            let (status, body) = tokio::time::timeout(
+                std::time::Duration::from_secs(timeout),
+                probe::get(&target, "/devices", token.as_deref()),
+            )
+            .await
+            .map_err(|_| anyhow::anyhow!("获取设备清单超时 ({} 秒)", timeout))??;
+            let mut devices = match status {
+                200 => serde_json::from_slice::<Vec<DeviceStatus>>(&body)
+                    .map_err(|e| anyhow::anyhow!("解析设备清单失败: {}", e))?,
+                401 => anyhow::bail!("信令服务器要求认证，请使用 --token 或 SSCONTROL_TOKEN 提供 API Key"),
+                404 => anyhow::bail!("信令服务器版本过旧，不支持设备清单"),
+                code => anyhow::bail!("获取设备清单失败: HTTP {}", code),
+            };
+            if online {
+                devices.retain(|device| device.online);
+            }
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&devices)?);
+                return Ok(());
+            }
+            if devices.is_empty() {
+                println!("{} 上没有登记的设备", target);
+                return Ok(());
+            }
+            println!(
+                "{:<6} {:<20} {:<20} {:<10} {:<16} {:<17} 地址",
+                "状态", "名称", "设备 ID", "版本", "系统", "最后在线"
+            );
+            for device in &devices {
+                println!(
+                    "{:<6} {:<20} {:<20} {:<10} {:<16} {:<17} {}",
+                    if device.online { "在线" } else { "离线" },
+                    device.info.name,
+                    device.info.device_id,
+                    device.info.version,
+                    device.info.os,
+                    format_unix_time(device.last_seen_ms / 1000),
+                    device.info.url.as_deref().unwrap_or("-")
+                );
+            }
+            let online = devices.iter().filter(|device| device.online).count();
+            println!();
+            println!("共 {} 台设备，{} 台在线", devices.len(), online);
+            Ok(())
+        }
+    }
+}
+
+/// Handle address book commands
+pub async fn handle_hosts_command(action: HostsCommands) -> Result<()> {
+    use crate::hosts::{AddressBook, HostEntry};
+    use clap::ValueEnum;
+
+    let mut book = AddressBook::load(&AddressBook::default_path())?;
+    match action {
+        HostsCommands::Add { alias, ip, url, port, transport, fingerprint, device_id, force } => {
+            let mut entry = HostEntry::new(&alias);
+            for ip in ip.iter().rev() {
+                entry.remember_address(ip);
+            }
+            entry.url = url;
+            entry.port = port;
+            entry.transport = transport
+                .and_then(|transport| transport.to_possible_value())
+                .map(|value| value.get_name().to_string());
+            entry.fingerprint = fingerprint;
+            entry.device_id = device_id;
+            book.add(entry, force)?;
+            book.save()?;
+            println!("✓ 已保存 {} ({})", alias, book.path().display());
+            println!("  连接: sscontrol connect {}", alias);
+            Ok(())
+        }
+        HostsCommands::List => {
+            if book.hosts().is_empty() {
+                println!("地址簿为空 ({})", book.path().display());
+                println!("添加: sscontrol hosts add office-pc --ip 192.168.1.5");
+                return Ok(());
+            }
+            for entry in book.hosts() {
+                println!("{}", entry.alias);
+                if !entry.addresses.is_empty() {
+                    println!("  地址:     {} (端口 {})", entry.addresses.join(", "), entry.port());
+                }
+                if let Some(ref url) = entry.url {
+                    println!("  隧道:     {}", url);
+                }
+                if let Some(ref transport) = entry.transport {
+                    println!("  传输方式: {}", transport);
+                }
+                if let Some(ref fingerprint) = entry.fingerprint {
+                    println!("  设备指纹: {}", fingerprint);
+                }
+                if let Some(ref device_id) = entry.device_id {
+                    println!("  设备 ID:  {}", device_id);
+                }
+                if let Some(secs) = entry.last_connected {
+                    println!("  最近连接: {}", format_unix_time(secs));
+                }
+            }
+            Ok(())
+        }
+        HostsCommands::Remove { alias } => {
+            if !book.remove(&alias) {
+                anyhow::bail!("地址簿中没有 {}", alias);
+            }
+            book.save()?;
+            println!("✓ 已删除 {}", alias);
+            Ok(())
+        }
+        HostsCommands::Refresh { alias, timeout } => refresh_hosts(book, alias, timeout).await,
+    }
+}
+
+/// Look up saved hosts over mDNS and save their current addresses
+#[cfg(feature = "discovery")]
+async fn refresh_hosts(mut book: crate::hosts::AddressBook, alias: Option<String>, timeout: u64) -> Result<()> {
+    use crate::discovery::MdnsDiscovery;
+
+    if let Some(ref alias) = alias {
+        if book.get(alias).is_none() {
+            anyhow::bail!("地址簿中没有 {}", alias);
+        }
+    }
+    println!("正在通过 mDNS 和 UDP 广播查找局域网内的被控端 ({} 秒)...", timeout);
+    let mut discovery = MdnsDiscovery::new()?;
+    discovery.enable_broadcast(crate::connect_mode::broadcast_port());
+    let mut events = discovery.start()?;
+    let _ = tokio::time::timeout(std::time::Duration::from_secs(timeout), async {
+        while events.next_event().await.is_some() {}
+    })
+    .await;
+    let peers = discovery.get_peers();
+
+    let mut updated = 0;
+    for mut entry in book.hosts().to_vec() {
+        if alias.as_deref().is_some_and(|alias| !entry.alias.eq_ignore_ascii_case(alias)) {
+            continue;
+        }
+        let matched = peers
+            .iter()
+            .find(|peer| entry.matches(&peer.device_id, peer.fingerprint.as_deref(), &peer.hostname));
+        match matched {
+            Some(peer) => {
+                let address = peer.ip_address.to_string();
+                println!("  ✓ {}: {}", entry.alias, crate::network::lan::host_port(&address, peer.port));
+                entry.learn(&address, peer.port, &peer.device_id, peer.fingerprint.as_deref());
+                book.update(entry);
+                updated += 1;
+            }
+            None => println!("  ✗ {}: 未发现", entry.alias),
+        }
+    }
+    book.save()?;
+    println!();
+    println!("已更新 {} 台被控端 (共发现 {} 台)", updated, peers.len());
+    Ok(())
+}
+
+#[cfg(not(feature = "discovery"))]
+async fn refresh_hosts(_book: crate::hosts::AddressBook, _alias: Option<String>, _timeout: u64) -> Result<()> {
+    anyhow::bail!("mDNS 刷新需要 discovery feature (cargo build --features discovery)")
+}
+
+/// Handle device pairing commands
+#[cfg(feature = "pairing")]
+pub fn handle_pair_command(action: PairCommands) -> Result<()> {
+    use crate::cli::PairRole;
+    use crate::pairing::{self, identity, TrustRole};
+
+    match action {
+        PairCommands::Show => {
+            let identity = pairing::load_identity()?;
+            let url = identity.device_url();
+            println!("本机设备: {}", identity.name());
+            println!("设备指纹: {}", identity.fingerprint());
+            println!();
+            println!("{}", pairing::render_qr(&url)?);
+            println!("对方添加信任: sscontrol pair add \"{}\" --role <viewer|host>", url);
+            println!("QUIC 控制端也可以直接连接时加 --pair，用 PIN 验证一次后自动配对");
+            Ok(())
+        }
+        PairCommands::List => {
+            let trust = pairing::load_trust_store()?;
+            if trust.devices().is_empty() {
+                println!("没有已配对设备 ({})", trust.path().display());
+                return Ok(());
+            }
+            println!("{:<8} {:<20} {:<20} {:<22} {:<17} 最近连接", "角色", "设备", "指纹", "地址", "配对时间");
+            for device in trust.devices() {
+                println!(
+                    "{:<8} {:<20} {:<20} {:<22} {:<17} {}",
+                    device.role.to_string(),
+                    device.name,
+                    device.fingerprint(),
+                    device.address.as_deref().unwrap_or("-"),
+                    format_unix_time(device.paired_at),
+                    device.last_seen.map(format_unix_time).unwrap_or_else(|| "-".to_string())
+                );
+            }
+            Ok(())
+        }
+        PairCommands::Add { url, role } => {
+            let (name, key) = identity::parse_device_url(&url)?;
+            let role = match role {
+                PairRole::Viewer => TrustRole::Viewer,
+                PairRole::Host => TrustRole::Host,
+            };
+            let mut trust = pairing::load_trust_store()?;
+            trust.trust(role, &name, &key, None);
+            trust.save()?;
+            println!("✓ 已信任{} {} ({})", role, name, identity::fingerprint(&key));
+            Ok(())
+        }
+        PairCommands::Remove { device } => {
+            let mut trust = pairing::load_trust_store()?;
+            let removed = trust.remove(&device);
+            if removed.is_empty() {
+                anyhow::bail!("没有匹配 {} 的已配对设备 (用 sscontrol pair list 查看)", device);
+            }
+            trust.save()?;
+            for device in removed {
+                println!("✓ 已删除{} {} ({})", device.role, device.name, device.fingerprint());
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "pairing"))]
+pub fn handle_pair_command(_action: PairCommands) -> Result<()> {
+    anyhow::bail!("设备配对需要 pairing feature (cargo build --features pairing)")
+}
+
+/// Unix 秒格式化为本地时间
+fn format_unix_time(secs: u64) -> String {
+    chrono::DateTime::from_timestamp(secs as i64, 0)
+        .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
 /// Handle stats command
 pub fn handle_stats() -> Result<()> {
     println!("sscontrol 实时性能统计");
@@ -442,6 +1463,7 @@ fn sys_info_total_memory() -> anyhow::Result<u64> {
 }
 
 #[cfg(not(target_os = "windows"))]
+#[allow(dead_code)]
 fn sys_info_total_memory() -> anyhow::Result<u64> {
     Ok(8 * 1024 * 1024 * 1024) // 默认 8GB
 }
@@ -4,12 +4,19 @@
 
 #![allow(dead_code)]
 
+use crate::quality::profile::QualityProfile;
+use crate::signaling::{ApprovalMode, Permission};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use anyhow::Result;
 use uuid::Uuid;
 
+pub mod edit;
+pub mod validate;
+pub mod watch;
+
 /// 应用程序配置
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
@@ -22,6 +29,24 @@ pub struct Config {
     /// WebRTC 配置
     #[serde(default)]
     pub webrtc: WebRTCConfig,
+    /// 被控端会话容量配置
+    #[serde(default)]
+    pub host: HostConfig,
+    /// Web 查看器品牌和附加资源
+    #[serde(default)]
+    pub viewer: ViewerConfig,
+    /// 自动更新
+    #[serde(default)]
+    pub update: UpdateConfig,
+    /// 带宽上限和按时段限速
+    #[serde(default)]
+    pub bandwidth: BandwidthConfig,
+    /// 公网隧道 (需要 tunnel feature)
+    #[serde(default)]
+    pub tunnel: TunnelConfig,
+    /// 设备清单: 向中心信令服务器登记在线状态
+    #[serde(default)]
+    pub fleet: FleetConfig,
 }
 
 /// 服务器配置
@@ -32,6 +57,25 @@ pub struct ServerConfig {
     /// 设备 ID (自动生成或手动指定)
     #[serde(default = "default_device_id")]
     pub device_id: String,
+    /// WebSocket 心跳间隔 (秒，0 = 不发送心跳)，同时用于内嵌信令服务器
+    #[serde(default = "default_ping_interval_secs")]
+    pub ping_interval_secs: u64,
+    /// 多久未收到对端数据判定连接已断开 (秒)
+    #[serde(default = "default_pong_timeout_secs")]
+    pub pong_timeout_secs: u64,
+    /// 协商 WebSocket 文本消息的 deflate 压缩 (视频帧不压缩)，同时用于内嵌信令服务器
+    #[serde(default = "default_compression")]
+    pub compression: bool,
+}
+
+impl ServerConfig {
+    /// WebSocket 心跳配置
+    pub fn keepalive(&self) -> crate::network::keepalive::KeepaliveConfig {
+        crate::network::keepalive::KeepaliveConfig {
+            ping_interval: std::time::Duration::from_secs(self.ping_interval_secs),
+            pong_timeout: std::time::Duration::from_secs(self.pong_timeout_secs.max(1)),
+        }
+    }
 }
 
 /// 屏幕捕获配置
@@ -49,6 +93,30 @@ pub struct CaptureConfig {
     /// 捕获高度 (None = 原始高度)
     #[serde(default)]
     pub height: Option<u32>,
+    /// 是否在画面中包含鼠标指针 (false = 由 Viewer 在本地绘制光标)
+    #[serde(default = "default_show_cursor")]
+    pub show_cursor: bool,
+    /// 目标码率 (kbps，None = 使用命令行 --bitrate 或默认 2000)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bitrate: Option<u32>,
+    /// 画质预设 (设置后代替 fps 和 bitrate，命令行 --profile 优先)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<QualityProfile>,
+    /// 控制者指针周围区域分配更多码率 (ROI 编码，编码器不支持时忽略)
+    #[serde(default = "default_roi")]
+    pub roi: bool,
+    /// 捕获器和编码器都支持时直接编码 GPU 纹理 (零拷贝)
+    ///
+    /// GPU 帧没有 CPU 像素数据，静态画面检测和场景切换检测不生效，
+    /// 需要跳过静态画面节省带宽时可以关闭
+    #[serde(default = "default_zero_copy")]
+    pub zero_copy: bool,
+    /// 画面来源 (命令行 --capture 优先)
+    #[serde(default)]
+    pub source: crate::capture::CaptureSource,
+    /// 合成画面设置 (source = "synthetic" 时使用)
+    #[serde(default)]
+    pub synthetic: crate::capture::SyntheticConfig,
 }
 
 /// 日志配置
@@ -77,16 +145,353 @@ pub struct SecurityConfig {
     /// 是否强制使用 TLS
     #[serde(default)]
     pub require_tls: bool,
+    /// 客户端 CA 证书路径 (信令服务器启用 mTLS，只接受该 CA 签发的设备)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_client_ca: Option<String>,
+    /// 连接信令服务器时出示的设备证书 (`sscontrol auth issue-cert` 签发)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_client_cert: Option<String>,
+    /// 设备证书私钥
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_client_key: Option<String>,
+    /// 是否启用端到端加密 (X25519 + ChaCha20-Poly1305，无需证书)
+    #[serde(default)]
+    pub e2ee: bool,
     /// Token 最大有效期（秒）
     #[serde(default = "default_token_ttl")]
     pub token_ttl: u64,
+    /// 信令服务器认证提供者 (None = 不认证)
+    #[serde(default)]
+    pub auth: Option<AuthProviderConfig>,
+}
+
+/// 认证提供者配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuthProviderConfig {
+    /// 共享 API Key (使用 security.api_key 或 SSCONTROL_API_KEY)
+    ApiKey,
+    /// OIDC Bearer Token (需要 sso feature)
+    Oidc(OidcConfig),
+    /// LDAP 用户名/密码 (需要 sso feature)
+    Ldap(LdapConfig),
+}
+
+/// OIDC 认证配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OidcConfig {
+    /// Issuer (例如: https://login.example.com/realms/corp)
+    pub issuer: String,
+    /// 期望的 audience (client_id)
+    pub audience: String,
+    /// JWKS 地址 (None = 通过 {issuer}/.well-known/openid-configuration 发现)
+    #[serde(default)]
+    pub jwks_uri: Option<String>,
+    /// JWKS 缓存时间（秒）
+    #[serde(default = "default_jwks_cache_secs")]
+    pub jwks_cache_secs: u64,
+    /// 角色来源声明 (字符串或字符串数组)
+    #[serde(default = "default_role_claim")]
+    pub role_claim: String,
+    /// 接受的签名算法 (JWKS 中的密钥声明了 `alg` 时以密钥为准，不接受 HS256/HS384/HS512)
+    #[serde(default = "default_oidc_algorithms")]
+    pub algorithms: Vec<String>,
+    /// 角色映射
+    #[serde(default)]
+    pub roles: RoleMappingConfig,
+}
+
+/// LDAP 认证配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LdapConfig {
+    /// 服务器地址 (ldap:// 或 ldaps://)
+    pub url: String,
+    /// 用户 DN 模板，`{username}` 会被替换 (例如: uid={username},ou=people,dc=example,dc=com)
+    pub user_dn_template: String,
+    /// 组成员属性
+    #[serde(default = "default_group_attribute")]
+    pub group_attribute: String,
+    /// 角色映射
+    #[serde(default)]
+    pub roles: RoleMappingConfig,
+}
+
+/// 角色映射配置
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RoleMappingConfig {
+    /// 声明值或组 (DN 或 CN) → 角色 (viewer / operator / admin)
+    #[serde(default)]
+    pub mappings: HashMap<String, String>,
+    /// 无匹配时的默认角色 (None = 拒绝连接)
+    #[serde(default)]
+    pub default_role: Option<String>,
+}
+
+/// 被控端会话容量配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HostConfig {
+    /// 最大并发 Viewer 会话数 (None = 不限制)
+    #[serde(default)]
+    pub max_sessions: Option<usize>,
+    /// 会话已满时是否让新 Viewer 排队等待
+    #[serde(default)]
+    pub queue_enabled: bool,
+    /// 等待队列最大长度
+    #[serde(default = "default_max_queue_length")]
+    pub max_queue_length: usize,
+    /// 单个房间最多容纳的 Viewer 数 (None = 不限制)
+    #[serde(default)]
+    pub max_viewers_per_room: Option<usize>,
+    /// Viewer 是否必须输入 Host 显示的一次性 PIN 才能发起会话
+    #[serde(default = "default_require_pin")]
+    pub require_pin: bool,
+    /// PIN 连续错误多少次后锁定 (锁定时间逐次翻倍)
+    #[serde(default = "default_pin_max_attempts")]
+    pub pin_max_attempts: u32,
+    /// 无人值守访问密钥的 Argon2 哈希 (`host --unattended` 首次启动时生成)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unattended_secret_hash: Option<String>,
+    /// TOTP 双因素验证密钥 (Base32，`sscontrol auth enroll` 生成；设置后 Viewer 必须提交验证码)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub totp_secret: Option<String>,
+    /// 新 Viewer 默认获得的权限 (空列表 = 仅查看)
+    #[serde(default = "default_permissions")]
+    pub permissions: Vec<Permission>,
+    /// 连接审批方式: prompt (控制台), desktop (桌面对话框), auto (自动允许)
+    #[serde(default)]
+    pub approval: ApprovalMode,
+    /// 审批超时 (秒)，超时未作答则拒绝
+    #[serde(default = "default_approval_timeout_secs")]
+    pub approval_timeout_secs: u64,
+    /// 启动时通过 UPnP / NAT-PMP / PCP 在路由器上映射信令端口和 WebRTC 端口
+    #[serde(default = "default_port_mapping")]
+    pub port_mapping: bool,
+    /// 在信令端口号的 UDP 端口上提供 QUIC 传输 (需要 quic feature，供 `connect --transport quic` 使用)
+    #[serde(default = "default_quic")]
+    pub quic: bool,
+    /// 在局域网内通过 mDNS 广播本机 (需要 discovery feature，控制端据此刷新地址簿中过期的 IP)
+    #[serde(default = "default_mdns")]
+    pub mdns: bool,
+    /// 同时在 UDP 端口上广播签名信标 (需要 discovery feature，用于过滤组播、mDNS 不可用的网络)
+    #[serde(default = "default_broadcast_discovery")]
+    pub broadcast_discovery: bool,
+    /// 广播信标的 UDP 端口 (控制端监听同一端口)
+    #[serde(default = "default_broadcast_port")]
+    pub broadcast_port: u16,
+    /// 多观看者时按各自的网络状况分档编码 (高/中/低三档分辨率和码率)
+    #[serde(default)]
+    pub simulcast: bool,
+    /// 隐私模式: 会话建立时自动遮挡本地屏幕 (控制者也可以随时通过 `control` 数据通道切换)
+    #[serde(default)]
+    pub privacy_mode: bool,
+    /// 隐私模式开启时同时屏蔽本地键盘鼠标 (远程输入不受影响)
+    #[serde(default)]
+    pub privacy_block_input: bool,
+    /// 会话审计日志路径 (None = 不记录；`sscontrol audit list/verify` 查看和校验)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_log: Option<String>,
+    /// 交换控制端的 Cmd (Meta) 与 Ctrl (控制端和本机一个是 macOS、一个是 Windows 时开启)
+    #[serde(default)]
+    pub swap_meta_control: bool,
+    /// 不注入本机的组合键 (如 "Alt+F4"、"Cmd+Q")，按交换后的键匹配；控制端显式发送的组合键不受限制
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocked_shortcuts: Vec<String>,
+    /// 每秒最多注入的鼠标移动/滚动事件数，超出的移动合并为最新位置 (0 = 不限速)
+    #[serde(default = "default_max_input_rate")]
+    pub max_input_rate: u32,
+    /// 控制端上传文件的保存目录 (None = 当前用户的桌面)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incoming_dir: Option<String>,
+    /// 单个上传文件的大小上限 (MB)
+    #[serde(default = "default_max_upload_mb")]
+    pub max_upload_mb: u64,
+    /// 上传完成后在本机焦点窗口中输入文件路径 (相当于粘贴路径，需要输入权限)
+    #[serde(default)]
+    pub paste_uploads: bool,
+    /// 会话空闲超时 (分钟): 没有输入且画面静止超过该时间后断开会话 (None = 不限制)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_timeout_minutes: Option<u64>,
+    /// 单个会话的最长时长 (分钟，None = 不限制)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_session_minutes: Option<u64>,
+    /// 空闲超时或达到最长时长前多少秒通知控制端
+    #[serde(default = "default_session_warning_secs")]
+    pub session_warning_secs: u64,
+}
+
+/// Web 查看器配置 (未设置的项使用内置样式)
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ViewerConfig {
+    /// 页面标题和页头名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Logo 地址 (可以指向 assets_dir 中的文件，如 "/assets/logo.png")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logo: Option<String>,
+    /// 页面背景色
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    /// 页头和面板背景色
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub panel: Option<String>,
+    /// 边框色
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub border: Option<String>,
+    /// 强调色
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accent: Option<String>,
+    /// 文字颜色
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// 静态资源目录，挂载到查看器的 `/assets/` 路径下
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assets_dir: Option<String>,
+}
+
+/// 带宽策略 (见 [`crate::quality::bandwidth`])
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct BandwidthConfig {
+    /// 所有观看者合计的视频带宽上限 (kbps，None = 不限制)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_kbps: Option<u32>,
+    /// 单个观看者会话的带宽上限 (kbps，None = 不限制)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_session_kbps: Option<u32>,
+    /// 按时段限速 (如工作时间)，与 max_kbps 同时生效时取较小值
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<BandwidthWindow>,
+}
+
+/// 限速时段
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BandwidthWindow {
+    /// 生效的星期 ("mon" - "sun"，空 = 每天)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<String>,
+    /// 开始时间 (本地时间 "HH:MM")
+    pub start: String,
+    /// 结束时间 ("HH:MM"，早于开始时间表示跨过午夜)
+    pub end: String,
+    /// 该时段所有观看者合计的带宽上限 (kbps)
+    pub max_kbps: u32,
+}
+
+/// 自动更新配置 (需要 update feature)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UpdateConfig {
+    /// 发布信息地址 (GitHub Releases API 的 /releases/latest 格式)
+    #[serde(default = "default_update_url")]
+    pub url: String,
+    /// 发布签名公钥 (Ed25519，十六进制)；未配置时只能检查，不会安装
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    /// 服务模式下自动更新的检查间隔 (小时，0 = 不自动更新)
+    #[serde(default)]
+    pub auto_update_hours: u64,
+}
+
+fn default_update_url() -> String {
+    "https://api.github.com/repos/iannil/sscontrol/releases/latest".to_string()
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            url: default_update_url(),
+            public_key: None,
+            auto_update_hours: 0,
+        }
+    }
+}
+
+/// 支持的隧道程序
+pub const TUNNEL_PROVIDERS: [&str; 3] = ["cloudflared", "ngrok", "ssh"];
+
+/// 公网隧道配置 (需要 tunnel feature，`sscontrol host --tunnel` 启用)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TunnelConfig {
+    /// 隧道程序: cloudflared / ngrok / ssh (命令行 `--tunnel <程序>` 可覆盖)
+    #[serde(default = "default_tunnel_provider")]
+    pub provider: String,
+    /// SSH 反向隧道的目标 (`user@host` 或 ~/.ssh/config 中的别名)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_target: Option<String>,
+    /// SSH 服务器上监听的端口 (None = 与本地信令端口相同)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_remote_port: Option<u16>,
+    /// SSH 反向隧道的对外地址 (前面有反向代理时填写，None = http://<主机>:<远程端口>)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_public_url: Option<String>,
+    /// 经公网地址检查隧道是否可用的间隔 (秒，0 = 不检查)
+    #[serde(default = "default_tunnel_health_check_secs")]
+    pub health_check_secs: u64,
+    /// 当前的公网地址 (由被控端在地址变化时写入，供脚本读取)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_url: Option<String>,
+}
+
+fn default_tunnel_provider() -> String {
+    "cloudflared".to_string()
+}
+
+fn default_tunnel_health_check_secs() -> u64 {
+    30
+}
+
+impl Default for TunnelConfig {
+    fn default() -> Self {
+        Self {
+            provider: default_tunnel_provider(),
+            ssh_target: None,
+            ssh_remote_port: None,
+            ssh_public_url: None,
+            health_check_secs: default_tunnel_health_check_secs(),
+            public_url: None,
+        }
+    }
+}
+
+/// 设备清单配置 (配置了 `server` 时被控端登记到该信令服务器，`sscontrol fleet list` 查看)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FleetConfig {
+    /// 中心信令服务器的 WebSocket 地址 (如 wss://signal.example.com/ws，None = 不登记)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    /// 设备显示名称 (None = 主机名)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// 连接中心服务器使用的令牌 (None = 使用 security.api_key)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// 作为中心信令服务器时接受设备登记和 /devices 的令牌 (未配置认证提供者时必须设置)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration_token: Option<String>,
+    /// 心跳间隔 (秒)，服务器超过 3 个间隔未收到心跳即视为离线
+    #[serde(default = "default_fleet_heartbeat_secs")]
+    pub heartbeat_secs: u64,
+}
+
+fn default_fleet_heartbeat_secs() -> u64 {
+    30
+}
+
+impl Default for FleetConfig {
+    fn default() -> Self {
+        Self {
+            server: None,
+            name: None,
+            token: None,
+            registration_token: None,
+            heartbeat_secs: default_fleet_heartbeat_secs(),
+        }
+    }
 }
 
 /// WebRTC 配置
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct WebRTCConfig {
-    /// STUN 服务器列表
-    #[serde(default = "default_stun_servers")]
+    /// STUN 服务器列表 (用于获取公网映射地址；为空时仅使用本机地址候选)
+    #[serde(default)]
     pub stun_servers: Vec<String>,
     /// TURN 服务器配置
     #[serde(default)]
@@ -94,6 +499,23 @@ pub struct WebRTCConfig {
     /// ICE 传输策略: "all" 或 "relay"
     #[serde(default = "default_ice_transport_policy")]
     pub ice_transport_policy: String,
+    /// WebRTC 使用的本地 UDP 端口范围 [最小, 最大] (None = 系统随机分配)
+    ///
+    /// 固定范围后才能在路由器上做端口映射或在防火墙中放行
+    #[serde(default)]
+    pub udp_port_range: Option<(u16, u16)>,
+    /// 对称 NAT 下是否在 ICE 之前尝试预测性打洞 (需要配置 STUN 服务器)
+    #[serde(default = "default_hole_punching")]
+    pub hole_punching: bool,
+    /// 是否向对端通告本机地址候选 (false: 只发送公网映射和中继候选，并隐藏其中的内网地址)
+    #[serde(default = "default_host_candidates")]
+    pub host_candidates: bool,
+    /// ICE 只使用这些网段内的本机地址 (CIDR，如 "192.168.1.0/24"；为空时不限制)
+    #[serde(default)]
+    pub candidate_networks: Vec<String>,
+    /// ICE 只使用这些网卡 (如 "eth0"；为空时不限制)
+    #[serde(default)]
+    pub candidate_interfaces: Vec<String>,
 }
 
 /// TURN 服务器配置
@@ -107,18 +529,54 @@ pub struct TurnServerConfig {
     pub password: String,
 }
 
+impl std::str::FromStr for TurnServerConfig {
+    type Err = anyhow::Error;
+
+    /// 解析命令行形式的 TURN 地址: `turn:用户名:密码@主机:端口` (查询参数保留在 URL 中)
+    fn from_str(s: &str) -> Result<Self> {
+        let (scheme, rest) = ["turns:", "turn:"]
+            .iter()
+            .find_map(|scheme| s.strip_prefix(scheme).map(|rest| (*scheme, rest)))
+            .ok_or_else(|| anyhow::anyhow!("\"{}\" 不是 turn: 或 turns: 地址", s))?;
+        let (credentials, address) = rest
+            .rsplit_once('@')
+            .ok_or_else(|| anyhow::anyhow!("TURN 地址缺少凭证 (格式: turn:用户名:密码@主机:端口)"))?;
+        let (username, password) = credentials
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("TURN 凭证格式应为 用户名:密码"))?;
+        if username.is_empty() || address.is_empty() {
+            anyhow::bail!("TURN 地址缺少用户名或主机");
+        }
+        Ok(Self {
+            url: format!("{}{}", scheme, address),
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
             server: ServerConfig {
                 url: "ws://localhost:8080".to_string(),
                 device_id: Uuid::new_v4().to_string(),
+                ping_interval_secs: default_ping_interval_secs(),
+                pong_timeout_secs: default_pong_timeout_secs(),
+                compression: default_compression(),
             },
             capture: CaptureConfig {
                 fps: 30,
                 screen_index: None,
                 width: None,
                 height: None,
+                show_cursor: true,
+                bitrate: None,
+                profile: None,
+                roi: true,
+                zero_copy: true,
+                source: crate::capture::CaptureSource::Screen,
+                synthetic: crate::capture::SyntheticConfig::default(),
             },
             logging: LoggingConfig {
                 level: "info".to_string(),
@@ -126,6 +584,48 @@ impl Default for Config {
             },
             security: SecurityConfig::default(),
             webrtc: WebRTCConfig::default(),
+            host: HostConfig::default(),
+            viewer: ViewerConfig::default(),
+            update: UpdateConfig::default(),
+            bandwidth: BandwidthConfig::default(),
+            tunnel: TunnelConfig::default(),
+            fleet: FleetConfig::default(),
+        }
+    }
+}
+
+impl Default for HostConfig {
+    fn default() -> Self {
+        HostConfig {
+            max_sessions: None,
+            queue_enabled: false,
+            max_queue_length: default_max_queue_length(),
+            max_viewers_per_room: None,
+            require_pin: default_require_pin(),
+            pin_max_attempts: default_pin_max_attempts(),
+            unattended_secret_hash: None,
+            totp_secret: None,
+            permissions: default_permissions(),
+            approval: ApprovalMode::default(),
+            approval_timeout_secs: default_approval_timeout_secs(),
+            port_mapping: default_port_mapping(),
+            quic: default_quic(),
+            mdns: default_mdns(),
+            broadcast_discovery: default_broadcast_discovery(),
+            broadcast_port: default_broadcast_port(),
+            simulcast: false,
+            privacy_mode: false,
+            privacy_block_input: false,
+            audit_log: None,
+            swap_meta_control: false,
+            blocked_shortcuts: Vec::new(),
+            max_input_rate: default_max_input_rate(),
+            incoming_dir: None,
+            max_upload_mb: default_max_upload_mb(),
+            paste_uploads: false,
+            idle_timeout_minutes: None,
+            max_session_minutes: None,
+            session_warning_secs: default_session_warning_secs(),
         }
     }
 }
@@ -135,6 +635,9 @@ impl Default for ServerConfig {
         ServerConfig {
             url: "ws://localhost:8080".to_string(),
             device_id: Uuid::new_v4().to_string(),
+            ping_interval_secs: default_ping_interval_secs(),
+            pong_timeout_secs: default_pong_timeout_secs(),
+            compression: default_compression(),
         }
     }
 }
@@ -146,6 +649,13 @@ impl Default for CaptureConfig {
             screen_index: None,
             width: None,
             height: None,
+            show_cursor: default_show_cursor(),
+            bitrate: None,
+            profile: None,
+            roi: default_roi(),
+            zero_copy: default_zero_copy(),
+            source: crate::capture::CaptureSource::default(),
+            synthetic: crate::capture::SyntheticConfig::default(),
         }
     }
 }
@@ -166,7 +676,12 @@ impl Default for SecurityConfig {
             tls_cert: None,
             tls_key: None,
             require_tls: false,
+            tls_client_ca: None,
+            tls_client_cert: None,
+            tls_client_key: None,
+            e2ee: false,
             token_ttl: 300, // 5 分钟
+            auth: None,
         }
     }
 }
@@ -174,9 +689,14 @@ impl Default for SecurityConfig {
 impl Default for WebRTCConfig {
     fn default() -> Self {
         WebRTCConfig {
-            stun_servers: default_stun_servers(),
+            stun_servers: Vec::new(),
             turn_servers: Vec::new(),
             ice_transport_policy: "all".to_string(),
+            udp_port_range: None,
+            hole_punching: default_hole_punching(),
+            host_candidates: default_host_candidates(),
+            candidate_networks: Vec::new(),
+            candidate_interfaces: Vec::new(),
         }
     }
 }
@@ -185,10 +705,34 @@ fn default_device_id() -> String {
     Uuid::new_v4().to_string()
 }
 
+fn default_ping_interval_secs() -> u64 {
+    5
+}
+
+fn default_pong_timeout_secs() -> u64 {
+    15
+}
+
+fn default_compression() -> bool {
+    true
+}
+
 fn default_fps() -> u32 {
     30
 }
 
+fn default_show_cursor() -> bool {
+    true
+}
+
+fn default_roi() -> bool {
+    true
+}
+
+fn default_zero_copy() -> bool {
+    true
+}
+
 fn default_log_level() -> String {
     "info".to_string()
 }
@@ -197,23 +741,104 @@ fn default_token_ttl() -> u64 {
     300 // 5 分钟
 }
 
-fn default_stun_servers() -> Vec<String> {
-    vec![
-        "stun:stun.l.google.com:19302".to_string(),
-        "stun:stun1.l.google.com:19302".to_string(),
-    ]
+fn default_max_queue_length() -> usize {
+    10
+}
+
+fn default_require_pin() -> bool {
+    true
+}
+
+fn default_pin_max_attempts() -> u32 {
+    5
+}
+
+fn default_approval_timeout_secs() -> u64 {
+    30
+}
+
+fn default_port_mapping() -> bool {
+    true
+}
+
+fn default_quic() -> bool {
+    true
+}
+
+fn default_mdns() -> bool {
+    true
+}
+
+fn default_broadcast_discovery() -> bool {
+    true
+}
+
+/// 与 `discovery::broadcast::DEFAULT_BROADCAST_PORT` 相同 (discovery 模块需要 feature)
+fn default_broadcast_port() -> u16 {
+    9531
+}
+
+fn default_max_input_rate() -> u32 {
+    crate::input::InputCoalescer::DEFAULT_RATE
+}
+
+fn default_max_upload_mb() -> u64 {
+    crate::transfer::DEFAULT_MAX_UPLOAD_MB
+}
+
+fn default_session_warning_secs() -> u64 {
+    60
+}
+
+fn default_permissions() -> Vec<Permission> {
+    vec![Permission::Input, Permission::Clipboard, Permission::FileTransfer]
+}
+
+fn default_jwks_cache_secs() -> u64 {
+    3600
+}
+
+fn default_role_claim() -> String {
+    "groups".to_string()
+}
+
+fn default_oidc_algorithms() -> Vec<String> {
+    vec!["RS256".to_string()]
+}
+
+fn default_group_attribute() -> String {
+    "memberOf".to_string()
+}
+
+fn default_hole_punching() -> bool {
+    true
+}
+
+fn default_host_candidates() -> bool {
+    true
 }
 
 fn default_ice_transport_policy() -> String {
     "all".to_string()
 }
 
+/// 指定配置文件路径的环境变量
+pub const CONFIG_ENV: &str = "SSCONTROL_CONFIG";
+
 impl Config {
-    /// 从文件加载配置
+    /// 从文件加载配置并校验
     ///
-    /// 如果文件不存在或解析失败，返回默认配置
+    /// 如果文件不存在，返回默认配置；解析失败或校验有错误时返回错误
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
+        let config = Self::load_unchecked(path)?;
+        config.check().map_err(|e| anyhow::anyhow!("{:?}: {}", path, e))?;
+        Ok(config)
+    }
+
+    /// 从文件加载配置，只解析不校验
+    pub fn load_unchecked<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
 
         if !path.exists() {
             tracing::warn!("配置文件不存在: {:?}, 使用默认配置", path);
@@ -243,6 +868,11 @@ impl Config {
             return p.to_string();
         }
 
+        // 环境变量指定 (系统服务没有可用的 HOME 时使用)
+        if let Some(p) = std::env::var(CONFIG_ENV).ok().filter(|p| !p.is_empty()) {
+            return p;
+        }
+
         // 首先检查当前目录
         if Path::new("config.toml").exists() {
             return "config.toml".to_string();
@@ -274,10 +904,51 @@ mod tests {
         assert!(!config.server.device_id.is_empty());
     }
 
+    #[test]
+    fn test_parse_turn_server() {
+        let server: TurnServerConfig = "turns:alice:p@ss:w0rd@turn.example.com:5349?transport=tcp".parse().unwrap();
+        assert_eq!(server.url, "turns:turn.example.com:5349?transport=tcp");
+        assert_eq!(server.username, "alice");
+        assert_eq!(server.password, "p@ss:w0rd");
+
+        assert!("turn:turn.example.com:3478".parse::<TurnServerConfig>().is_err());
+        assert!("stun:alice:x@stun.example.com".parse::<TurnServerConfig>().is_err());
+    }
+
     #[test]
     fn test_config_serialization() {
         let config = Config::default();
         let toml_str = toml::to_string(&config).unwrap();
         let _parsed: Config = toml::from_str(&toml_str).unwrap();
     }
+
+    #[test]
+    fn test_auth_provider_config() {
+        let config: Config = toml::from_str(r#"
+            [server]
+            url = "ws://localhost:8080"
+
+            [capture]
+
+            [logging]
+
+            [security.auth]
+            type = "oidc"
+            issuer = "https://login.example.com"
+            audience = "sscontrol"
+
+            [security.auth.roles]
+            default_role = "viewer"
+            mappings = { "it-admins" = "admin" }
+        "#).unwrap();
+
+        match config.security.auth {
+            Some(AuthProviderConfig::Oidc(oidc)) => {
+                assert_eq!(oidc.role_claim, "groups");
+                assert_eq!(oidc.jwks_cache_secs, 3600);
+                assert_eq!(oidc.roles.mappings["it-admins"], "admin");
+            }
+            other => panic!("unexpected auth config: {:?}", other),
+        }
+    }
 }
@@ -0,0 +1,139 @@
+//! 按配置项 (`section.key`) 查看和修改配置文件
+//!
+//! 修改在 TOML 文档上进行，写回前按 [`Config`] 解析并校验，
+//! 拼错的配置项和不合法的取值不会写入文件。写回时文件中的注释不会保留
+
+use super::{watch, Config};
+use anyhow::{anyhow, Result};
+use toml::{Table, Value};
+
+/// 显示时隐藏的配置项
+const SECRET_KEYS: [&str; 3] = ["security.api_key", "host.totp_secret", "host.unattended_secret_hash"];
+
+/// 隐藏后显示的值
+const MASK: &str = "***";
+
+/// 把命令行参数解析成 TOML 值 (`30`、`true`、`[1, 2]`、`"text"`)，无法解析时作为字符串
+pub fn parse_value(raw: &str) -> Value {
+    toml::from_str::<Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(raw.to_string()))
+}
+
+/// 修改配置文件内容中的一项，返回新的文件内容和解析后的配置
+pub fn set_value(content: &str, key: &str, raw: &str) -> Result<(String, Config)> {
+    let mut document: Table = toml::from_str(content).map_err(|e| anyhow!("配置文件解析失败: {}", e))?;
+    let segments: Vec<&str> = key.split('.').collect();
+    if segments.iter().any(|segment| segment.is_empty()) {
+        return Err(anyhow!("无效的配置项: {}", key));
+    }
+
+    let (name, sections) = segments.split_last().expect("split 至少返回一段");
+    let mut table = &mut document;
+    for (depth, section) in sections.iter().enumerate() {
+        let entry = table.entry(section.to_string()).or_insert_with(|| Value::Table(Table::new()));
+        table = entry
+            .as_table_mut()
+            .ok_or_else(|| anyhow!("{} 不是配置段，无法设置 {}", segments[..=depth].join("."), key))?;
+    }
+    table.insert(name.to_string(), parse_value(raw));
+
+    let content = toml::to_string_pretty(&document)?;
+    let config: Config = toml::from_str(&content).map_err(|e| anyhow!("{} 的取值无效: {}", key, e))?;
+
+    // 未知的配置项会被反序列化忽略，不会出现在解析后的配置中
+    let nested = format!("{}.", key);
+    if !watch::flatten(&config).keys().any(|known| known == key || known.starts_with(&nested)) {
+        return Err(anyhow!("未知的配置项: {}", key));
+    }
+    config.check()?;
+    Ok((content, config))
+}
+
+/// 配置转为 TOML 表，密钥类配置项以 `***` 代替
+pub fn masked(config: &Config) -> Result<Table> {
+    let mut table = Table::try_from(config)?;
+    for key in SECRET_KEYS {
+        if let Some(value) = lookup_mut(&mut table, key) {
+            *value = Value::String(MASK.to_string());
+        }
+    }
+    if let Some(Value::Array(servers)) = lookup_mut(&mut table, "webrtc.turn_servers") {
+        for server in servers {
+            if let Some(password) = server.get_mut("password") {
+                *password = Value::String(MASK.to_string());
+            }
+        }
+    }
+    Ok(table)
+}
+
+/// 按 `section.key` 查找值
+pub fn lookup<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    let (first, rest) = key.split_once('.').map_or((key, None), |(first, rest)| (first, Some(rest)));
+    let value = table.get(first)?;
+    match rest {
+        None => Some(value),
+        Some(rest) => lookup(value.as_table()?, rest),
+    }
+}
+
+fn lookup_mut<'a>(table: &'a mut Table, key: &str) -> Option<&'a mut Value> {
+    let (first, rest) = key.split_once('.').map_or((key, None), |(first, rest)| (first, Some(rest)));
+    let value = table.get_mut(first)?;
+    match rest {
+        None => Some(value),
+        Some(rest) => lookup_mut(value.as_table_mut()?, rest),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = r#"
+        [server]
+        url = "ws://localhost:8080"
+        device_id = "test"
+
+        [capture]
+        fps = 30
+
+        [logging]
+        level = "info"
+    "#;
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(parse_value("30"), Value::Integer(30));
+        assert_eq!(parse_value("true"), Value::Boolean(true));
+        assert_eq!(parse_value("\"30\""), Value::String("30".to_string()));
+        assert_eq!(parse_value("wss://example.com"), Value::String("wss://example.com".to_string()));
+    }
+
+    #[test]
+    fn test_set_value() {
+        let (content, config) = set_value(CONTENT, "capture.fps", "15").unwrap();
+        assert_eq!(config.capture.fps, 15);
+        assert_eq!(config.server.device_id, "test");
+
+        // 配置段不存在时自动创建
+        let (_, config) = set_value(&content, "host.audit_log", "audit.jsonl").unwrap();
+        assert_eq!(config.host.audit_log.as_deref(), Some("audit.jsonl"));
+
+        assert!(set_value(CONTENT, "capture.fsp", "15").unwrap_err().to_string().contains("未知的配置项"));
+        assert!(set_value(CONTENT, "capture.fps", "fast").is_err());
+        assert!(set_value(CONTENT, "capture.fps", "0").is_err());
+        assert!(set_value(CONTENT, "capture.fps.max", "60").is_err());
+    }
+
+    #[test]
+    fn test_masked() {
+        let mut config = Config::default();
+        config.security.api_key = Some("secret".to_string());
+        let table = masked(&config).unwrap();
+        assert_eq!(lookup(&table, "security.api_key"), Some(&Value::String(MASK.to_string())));
+        assert_eq!(lookup(&table, "capture.fps"), Some(&Value::Integer(30)));
+    }
+}
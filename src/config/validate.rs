@@ -0,0 +1,448 @@
+//! 配置校验
+//!
+//! TOML 解析只检查类型，取值是否合理 (帧率范围、证书和私钥是否成对等) 由这里检查。
+//! 错误会让加载失败，警告只记录日志
+
+use super::Config;
+use std::fmt;
+
+/// 问题级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// 配置无法使用
+    Error,
+    /// 可以运行，但很可能不是预期的行为
+    Warning,
+}
+
+/// 单个配置问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// 配置项 (如 `capture.fps`)
+    pub key: String,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.severity {
+            Severity::Error => "错误",
+            Severity::Warning => "警告",
+        };
+        write!(f, "[{}] {}: {}", level, self.key, self.message)
+    }
+}
+
+/// 配置校验失败 (只包含错误级别的问题)
+#[derive(Debug, Clone)]
+pub struct ValidationError {
+    pub issues: Vec<Issue>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "配置有 {} 处错误:", self.issues.len())?;
+        for issue in &self.issues {
+            write!(f, "\n  {}: {}", issue.key, issue.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// 日志级别的合法取值
+const LOG_LEVELS: [&str; 5] = ["trace", "debug", "info", "warn", "error"];
+
+/// 收集问题
+#[derive(Default)]
+struct Issues(Vec<Issue>);
+
+impl Issues {
+    fn error(&mut self, key: &str, message: impl Into<String>) {
+        self.push(Severity::Error, key, message.into());
+    }
+
+    fn warning(&mut self, key: &str, message: impl Into<String>) {
+        self.push(Severity::Warning, key, message.into());
+    }
+
+    fn push(&mut self, severity: Severity, key: &str, message: String) {
+        self.0.push(Issue { severity, key: key.to_string(), message });
+    }
+}
+
+impl Config {
+    /// 检查配置取值，返回全部问题 (错误在前)
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = Issues::default();
+        self.validate_server(&mut issues);
+        self.validate_capture(&mut issues);
+        self.validate_logging(&mut issues);
+        self.validate_security(&mut issues);
+        self.validate_webrtc(&mut issues);
+        self.validate_host(&mut issues);
+        self.validate_viewer(&mut issues);
+        self.validate_bandwidth(&mut issues);
+        self.validate_tunnel(&mut issues);
+        self.validate_fleet(&mut issues);
+
+        let mut issues = issues.0;
+        issues.sort_by_key(|issue| issue.severity != Severity::Error);
+        issues
+    }
+
+    /// 有错误时返回 [`ValidationError`]，警告写入日志
+    pub fn check(&self) -> Result<(), ValidationError> {
+        let (errors, warnings): (Vec<_>, Vec<_>) =
+            self.validate().into_iter().partition(|issue| issue.severity == Severity::Error);
+        for warning in &warnings {
+            tracing::warn!("配置 {}: {}", warning.key, warning.message);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError { issues: errors })
+        }
+    }
+
+    fn validate_server(&self, issues: &mut Issues) {
+        let server = &self.server;
+        if !["ws://", "wss://", "http://", "https://"].iter().any(|scheme| server.url.starts_with(scheme)) {
+            issues.error("server.url", format!("\"{}\" 不是 ws://、wss://、http:// 或 https:// 地址", server.url));
+        }
+        if server.device_id.trim().is_empty() {
+            issues.error("server.device_id", "设备 ID 不能为空");
+        }
+        if server.ping_interval_secs > 0 && server.pong_timeout_secs <= server.ping_interval_secs {
+            issues.error(
+                "server.pong_timeout_secs",
+                format!(
+                    "超时 ({} 秒) 必须大于心跳间隔 ping_interval_secs ({} 秒)，否则连接会被误判为断开",
+                    server.pong_timeout_secs, server.ping_interval_secs
+                ),
+            );
+        }
+    }
+
+    fn validate_capture(&self, issues: &mut Issues) {
+        let capture = &self.capture;
+        if !(1..=240).contains(&capture.fps) {
+            issues.error("capture.fps", format!("帧率 {} 超出范围 (1-240)", capture.fps));
+        } else if capture.fps > 60 {
+            issues.warning("capture.fps", format!("帧率 {} 高于 60，编码和带宽开销会明显增加", capture.fps));
+        }
+        for (key, value) in [("capture.width", capture.width), ("capture.height", capture.height)] {
+            if value == Some(0) {
+                issues.error(key, "不能为 0 (不缩放请删除该项)");
+            }
+        }
+        if capture.width.is_some() != capture.height.is_some() {
+            issues.warning("capture.width", "width 和 height 应同时设置，只设置一项时按原始比例处理");
+        }
+        if let Some(bitrate) = capture.bitrate {
+            if !(100..=100_000).contains(&bitrate) {
+                issues.error("capture.bitrate", format!("码率 {} kbps 超出范围 (100-100000)", bitrate));
+            }
+        }
+    }
+
+    fn validate_logging(&self, issues: &mut Issues) {
+        if !LOG_LEVELS.contains(&self.logging.level.to_lowercase().as_str()) {
+            issues.error(
+                "logging.level",
+                format!("未知的日志级别 \"{}\" (可选 {})", self.logging.level, LOG_LEVELS.join(" / ")),
+            );
+        }
+    }
+
+    fn validate_security(&self, issues: &mut Issues) {
+        let security = &self.security;
+        if security.tls_cert.is_some() != security.tls_key.is_some() {
+            issues.error("security.tls_cert", "tls_cert 和 tls_key 必须同时设置");
+        }
+        if security.require_tls && security.tls_cert.is_none() {
+            issues.error("security.require_tls", "要求 TLS 时必须配置 tls_cert 和 tls_key");
+        }
+        if security.tls_client_cert.is_some() != security.tls_client_key.is_some() {
+            issues.error("security.tls_client_cert", "tls_client_cert 和 tls_client_key 必须同时设置");
+        }
+        for (key, path) in [
+            ("security.tls_cert", &security.tls_cert),
+            ("security.tls_key", &security.tls_key),
+            ("security.tls_client_ca", &security.tls_client_ca),
+            ("security.tls_client_cert", &security.tls_client_cert),
+            ("security.tls_client_key", &security.tls_client_key),
+        ] {
+            if let Some(path) = path {
+                if !std::path::Path::new(path).exists() {
+                    issues.warning(key, format!("文件 {} 不存在", path));
+                }
+            }
+        }
+        if security.token_ttl == 0 {
+            issues.error("security.token_ttl", "Token 有效期不能为 0");
+        }
+    }
+
+    fn validate_webrtc(&self, issues: &mut Issues) {
+        let webrtc = &self.webrtc;
+        match webrtc.ice_transport_policy.as_str() {
+            "all" => {}
+            "relay" if webrtc.turn_servers.is_empty() => {
+                issues.error("webrtc.ice_transport_policy", "relay 策略只使用 TURN 中继，必须配置 turn_servers");
+            }
+            "relay" => {}
+            other => issues.error("webrtc.ice_transport_policy", format!("未知的策略 \"{}\" (可选 all / relay)", other)),
+        }
+        for url in &webrtc.stun_servers {
+            if !url.starts_with("stun:") && !url.starts_with("stuns:") {
+                issues.error("webrtc.stun_servers", format!("\"{}\" 不是 stun: 或 stuns: 地址", url));
+            }
+        }
+        for server in &webrtc.turn_servers {
+            if !server.url.starts_with("turn:") && !server.url.starts_with("turns:") {
+                issues.error("webrtc.turn_servers", format!("\"{}\" 不是 turn: 或 turns: 地址", server.url));
+            }
+        }
+        for network in &webrtc.candidate_networks {
+            if let Err(e) = network.parse::<crate::network::lan::Subnet>() {
+                issues.error("webrtc.candidate_networks", e.to_string());
+            }
+        }
+        if let Some((min, max)) = webrtc.udp_port_range {
+            if min == 0 || min > max {
+                issues.error("webrtc.udp_port_range", format!("端口范围 [{}, {}] 无效", min, max));
+            }
+        }
+    }
+
+    fn validate_host(&self, issues: &mut Issues) {
+        let host = &self.host;
+        if host.max_sessions == Some(0) {
+            issues.error("host.max_sessions", "不能为 0 (不限制请删除该项)");
+        }
+        if host.max_viewers_per_room == Some(0) {
+            issues.error("host.max_viewers_per_room", "不能为 0 (不限制请删除该项)");
+        }
+        if host.queue_enabled && host.max_queue_length == 0 {
+            issues.error("host.max_queue_length", "启用排队时队列长度不能为 0");
+        }
+        if host.require_pin && host.pin_max_attempts == 0 {
+            issues.error("host.pin_max_attempts", "允许的尝试次数不能为 0");
+        }
+        if host.approval_timeout_secs == 0 {
+            issues.error("host.approval_timeout_secs", "审批超时不能为 0");
+        }
+        if !host.require_pin && host.approval == crate::signaling::ApprovalMode::Auto {
+            issues.warning("host.require_pin", "PIN 和连接审批都已关闭，任何能访问信令端口的人都可以控制本机");
+        }
+        if host.privacy_block_input && !host.privacy_mode {
+            issues.warning("host.privacy_block_input", "只在隐私模式开启时生效 (privacy_mode 或控制者手动开启)");
+        }
+        if host.audit_log.as_deref().is_some_and(|path| path.trim().is_empty()) {
+            issues.error("host.audit_log", "路径不能为空 (不记录请删除该项)");
+        }
+        if host.max_upload_mb == 0 {
+            issues.error("host.max_upload_mb", "上传大小上限不能为 0");
+        }
+        if let Some(dir) = &host.incoming_dir {
+            if std::path::Path::new(dir).is_file() {
+                issues.error("host.incoming_dir", format!("{} 是文件，不是目录", dir));
+            }
+        }
+        if host.idle_timeout_minutes == Some(0) {
+            issues.error("host.idle_timeout_minutes", "不能为 0 (不限制请删除该项)");
+        }
+        if host.max_session_minutes == Some(0) {
+            issues.error("host.max_session_minutes", "不能为 0 (不限制请删除该项)");
+        }
+        let shortest = [host.idle_timeout_minutes, host.max_session_minutes]
+            .into_iter()
+            .flatten()
+            .filter(|minutes| *minutes > 0)
+            .min();
+        if shortest.is_some_and(|minutes| host.session_warning_secs >= minutes * 60) {
+            issues.warning("host.session_warning_secs", "不短于会话超时，会话建立后立即收到断开提醒");
+        }
+        for combo in &host.blocked_shortcuts {
+            if let Err(e) = combo.parse::<crate::input::Combo>() {
+                issues.error("host.blocked_shortcuts", e.to_string());
+            }
+        }
+    }
+
+    fn validate_viewer(&self, issues: &mut Issues) {
+        let viewer = &self.viewer;
+        let colors = [
+            ("viewer.background", &viewer.background),
+            ("viewer.panel", &viewer.panel),
+            ("viewer.border", &viewer.border),
+            ("viewer.accent", &viewer.accent),
+            ("viewer.text", &viewer.text),
+        ];
+        for (key, color) in colors {
+            if let Some(color) = color.as_deref().filter(|color| !crate::viewer::is_css_color(color)) {
+                issues.error(key, format!("\"{}\" 不是颜色 (#rrggbb 或颜色名)", color));
+            }
+        }
+        if let Some(dir) = &viewer.assets_dir {
+            if !std::path::Path::new(dir).is_dir() {
+                issues.warning("viewer.assets_dir", format!("目录 {} 不存在", dir));
+            }
+        }
+    }
+
+    fn validate_bandwidth(&self, issues: &mut Issues) {
+        let bandwidth = &self.bandwidth;
+        if bandwidth.max_kbps == Some(0) {
+            issues.error("bandwidth.max_kbps", "不能为 0 (不限制请删除该项)");
+        }
+        if bandwidth.per_session_kbps == Some(0) {
+            issues.error("bandwidth.per_session_kbps", "不能为 0 (不限制请删除该项)");
+        }
+        if let (Some(max), Some(session)) = (bandwidth.max_kbps, bandwidth.per_session_kbps) {
+            if session > max {
+                issues.warning("bandwidth.per_session_kbps", format!("大于合计上限 {} kbps，不会生效", max));
+            }
+        }
+        for (index, window) in bandwidth.schedule.iter().enumerate() {
+            if let Err(e) = crate::quality::bandwidth::Window::parse(window) {
+                issues.error(&format!("bandwidth.schedule[{}]", index), e.to_string());
+            }
+        }
+    }
+
+    fn validate_tunnel(&self, issues: &mut Issues) {
+        let tunnel = &self.tunnel;
+        if !super::TUNNEL_PROVIDERS.contains(&tunnel.provider.as_str()) {
+            issues.error(
+                "tunnel.provider",
+                format!("未知的隧道程序 \"{}\" (可选 {})", tunnel.provider, super::TUNNEL_PROVIDERS.join(" / ")),
+            );
+        }
+        if tunnel.provider == "ssh" && tunnel.ssh_target.as_deref().is_none_or(|target| target.trim().is_empty()) {
+            issues.error("tunnel.ssh_target", "ssh 隧道需要指定目标服务器 (user@host)");
+        }
+        if let Some(url) = &tunnel.ssh_public_url {
+            if !["http://", "https://"].iter().any(|scheme| url.starts_with(scheme)) {
+                issues.error("tunnel.ssh_public_url", format!("\"{}\" 不是 http:// 或 https:// 地址", url));
+            }
+        }
+        if tunnel.ssh_remote_port == Some(0) {
+            issues.error("tunnel.ssh_remote_port", "端口不能为 0");
+        }
+    }
+
+    fn validate_fleet(&self, issues: &mut Issues) {
+        let fleet = &self.fleet;
+        if let Some(url) = &fleet.server {
+            if !["ws://", "wss://"].iter().any(|scheme| url.starts_with(scheme)) {
+                issues.error("fleet.server", format!("\"{}\" 不是 ws:// 或 wss:// 地址", url));
+            }
+        }
+        if fleet.heartbeat_secs == 0 {
+            issues.error("fleet.heartbeat_secs", "心跳间隔必须大于 0");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(config: &Config, severity: Severity) -> Vec<String> {
+        config
+            .validate()
+            .into_iter()
+            .filter(|issue| issue.severity == severity)
+            .map(|issue| issue.key)
+            .collect()
+    }
+
+    #[test]
+    fn test_default_config_is_valid() {
+        assert!(Config::default().check().is_ok());
+    }
+
+    #[test]
+    fn test_reports_invalid_values() {
+        let mut config = Config::default();
+        config.capture.fps = 0;
+        config.logging.level = "verbose".to_string();
+        config.security.tls_cert = Some("cert.pem".to_string());
+        config.webrtc.ice_transport_policy = "relay".to_string();
+        config.webrtc.udp_port_range = Some((50010, 50000));
+        config.host.privacy_block_input = true;
+
+        assert_eq!(
+            keys(&config, Severity::Error),
+            vec![
+                "capture.fps",
+                "logging.level",
+                "security.tls_cert",
+                "webrtc.ice_transport_policy",
+                "webrtc.udp_port_range",
+            ]
+        );
+        assert!(keys(&config, Severity::Warning).contains(&"host.privacy_block_input".to_string()));
+
+        let error = config.check().unwrap_err();
+        assert_eq!(error.issues.len(), 5);
+        assert!(error.to_string().contains("capture.fps: 帧率 0 超出范围"));
+    }
+
+    #[test]
+    fn test_reports_invalid_bandwidth() {
+        let mut config = Config::default();
+        config.bandwidth.max_kbps = Some(2000);
+        config.bandwidth.per_session_kbps = Some(3000);
+        config.bandwidth.schedule = vec![crate::config::BandwidthWindow {
+            days: vec!["workday".to_string()],
+            start: "09:00".to_string(),
+            end: "18:00".to_string(),
+            max_kbps: 1000,
+        }];
+
+        assert_eq!(keys(&config, Severity::Error), vec!["bandwidth.schedule[0]"]);
+        assert!(keys(&config, Severity::Warning).contains(&"bandwidth.per_session_kbps".to_string()));
+    }
+
+    #[test]
+    fn test_reports_invalid_capacity() {
+        let mut config = Config::default();
+        config.host.max_sessions = Some(0);
+        config.host.max_viewers_per_room = Some(0);
+        assert_eq!(keys(&config, Severity::Error), vec!["host.max_sessions", "host.max_viewers_per_room"]);
+        assert!(config.check().is_err());
+
+        config.host.max_sessions = Some(1);
+        config.host.max_viewers_per_room = None;
+        assert!(config.check().is_ok());
+    }
+
+    #[test]
+    fn test_reports_invalid_tunnel() {
+        let mut config = Config::default();
+        config.tunnel.provider = "ssh".to_string();
+        config.tunnel.ssh_public_url = Some("relay.example.com".to_string());
+        assert_eq!(keys(&config, Severity::Error), vec!["tunnel.ssh_target", "tunnel.ssh_public_url"]);
+
+        config.tunnel.provider = "frp".to_string();
+        config.tunnel.ssh_public_url = None;
+        assert_eq!(keys(&config, Severity::Error), vec!["tunnel.provider"]);
+    }
+
+    #[test]
+    fn test_reports_invalid_fleet() {
+        let mut config = Config::default();
+        config.fleet.server = Some("https://signal.example.com".to_string());
+        config.fleet.heartbeat_secs = 0;
+        assert_eq!(keys(&config, Severity::Error), vec!["fleet.server", "fleet.heartbeat_secs"]);
+
+        config.fleet.server = Some("wss://signal.example.com/ws".to_string());
+        config.fleet.heartbeat_secs = 30;
+        assert!(keys(&config, Severity::Error).is_empty());
+    }
+}
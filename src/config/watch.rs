@@ -0,0 +1,235 @@
+//! 配置热加载
+//!
+//! 轮询配置文件的修改时间和大小，变化后重新加载并校验。
+//! 帧率、码率、画质预设和光标等采集参数 ([`LIVE_KEYS`]) 立即生效，
+//! 端口、证书等其他配置需要重启，只提示不应用
+
+use super::{CaptureConfig, Config};
+use crate::quality::profile::{ProfileSettings, QualityProfile};
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 可以在运行中应用的配置项
+pub const LIVE_KEYS: [&str; 4] = ["capture.fps", "capture.bitrate", "capture.profile", "capture.show_cursor"];
+
+/// 不参与比较的配置项 (未配置时每次加载随机生成；隧道地址由被控端自己写入)
+const IGNORED_KEYS: [&str; 2] = ["server.device_id", "tunnel.public_url"];
+
+/// 运行中生效的采集参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiveSettings {
+    pub fps: u32,
+    /// 目标码率 (kbps)，None = 未在配置文件中指定
+    pub bitrate: Option<u32>,
+    /// 画质预设 (Viewer 可在运行中切换)
+    pub profile: Option<QualityProfile>,
+    pub show_cursor: bool,
+}
+
+impl LiveSettings {
+    pub fn from_config(config: &Config) -> Self {
+        Self::from_capture(&config.capture)
+    }
+
+    pub fn from_capture(capture: &CaptureConfig) -> Self {
+        Self {
+            fps: capture.fps,
+            bitrate: capture.bitrate,
+            profile: capture.profile,
+            show_cursor: capture.show_cursor,
+        }
+    }
+
+    /// 实际使用的编码参数
+    ///
+    /// 选择了预设时使用预设参数，否则使用 fps 和 bitrate (其余同均衡预设)；
+    /// 命令行 `--bitrate` 始终优先
+    pub fn resolve(&self, bitrate_arg: Option<u32>) -> ProfileSettings {
+        let settings = match self.profile {
+            Some(profile) => profile.settings(),
+            None => ProfileSettings {
+                fps: self.fps,
+                bitrate: self.bitrate.unwrap_or(QualityProfile::Balanced.settings().bitrate),
+                ..QualityProfile::Balanced.settings()
+            },
+        };
+        ProfileSettings { bitrate: bitrate_arg.unwrap_or(settings.bitrate), ..settings }
+    }
+
+    /// 只应用重新加载后变化的配置项 (保留 Viewer 在运行中的调整)
+    pub fn apply(&mut self, reloaded: &LiveSettings, keys: &[String]) {
+        for key in keys {
+            match key.as_str() {
+                "capture.fps" => self.fps = reloaded.fps,
+                "capture.bitrate" => self.bitrate = reloaded.bitrate,
+                "capture.profile" => self.profile = reloaded.profile,
+                "capture.show_cursor" => self.show_cursor = reloaded.show_cursor,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// 一次重新加载的结果
+#[derive(Debug, Clone)]
+pub struct ConfigReload {
+    pub config: Config,
+    /// 已生效的配置项
+    pub applied: Vec<String>,
+    /// 需要重启才能生效的配置项
+    pub restart_required: Vec<String>,
+}
+
+impl ConfigReload {
+    pub fn live_settings(&self) -> LiveSettings {
+        LiveSettings::from_config(&self.config)
+    }
+}
+
+/// 把配置展开成 `section.key` → 值
+pub fn flatten(config: &Config) -> BTreeMap<String, Value> {
+    let mut out = BTreeMap::new();
+    if let Ok(value) = serde_json::to_value(config) {
+        flatten_into("", &value, &mut out);
+    }
+    out
+}
+
+fn flatten_into(prefix: &str, value: &Value, out: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten_into(&key, value, out);
+            }
+        }
+        _ => {
+            out.insert(prefix.to_string(), value.clone());
+        }
+    }
+}
+
+/// 两份配置之间有变化的配置项
+pub fn changed_keys(old: &Config, new: &Config) -> Vec<String> {
+    let old = flatten(old);
+    let new = flatten(new);
+    let mut keys: Vec<String> = old
+        .keys()
+        .chain(new.keys())
+        .filter(|key| !IGNORED_KEYS.contains(&key.as_str()))
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// 配置文件监视器
+pub struct ConfigWatcher {
+    path: PathBuf,
+    current: Config,
+    stamp: Option<(SystemTime, u64)>,
+}
+
+impl ConfigWatcher {
+    /// `current` 是启动时加载的配置，之后的变化相对它计算
+    pub fn new(path: impl Into<PathBuf>, current: Config) -> Self {
+        let path = path.into();
+        let stamp = file_stamp(&path);
+        Self { path, current, stamp }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 当前生效的配置
+    pub fn current(&self) -> &Config {
+        &self.current
+    }
+
+    /// 检查文件是否变化
+    ///
+    /// 未变化返回 None；变化后解析或校验失败返回错误，继续使用原配置
+    pub fn poll(&mut self) -> Option<Result<ConfigReload>> {
+        let stamp = file_stamp(&self.path);
+        if stamp == self.stamp {
+            return None;
+        }
+        self.stamp = stamp;
+        // 文件被删除时保持原配置
+        stamp?;
+
+        let result = Config::load(&self.path).map(|mut config| {
+            // 设备 ID 未写入配置文件时沿用启动时生成的值
+            config.server.device_id.clone_from(&self.current.server.device_id);
+            let (applied, restart_required) = changed_keys(&self.current, &config)
+                .into_iter()
+                .partition(|key| LIVE_KEYS.contains(&key.as_str()));
+            self.current = config.clone();
+            ConfigReload { config, applied, restart_required }
+        });
+        Some(result)
+    }
+}
+
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_keys() {
+        let old = Config::default();
+        let mut new = old.clone();
+        new.server.device_id = "other".to_string();
+        assert!(changed_keys(&old, &new).is_empty());
+
+        new.capture.fps = 15;
+        new.capture.bitrate = Some(4000);
+        new.host.quic = !old.host.quic;
+        assert_eq!(changed_keys(&old, &new), vec!["capture.bitrate", "capture.fps", "host.quic"]);
+    }
+
+    #[test]
+    fn test_watcher_reload() {
+        let path = std::env::temp_dir().join(format!("sscontrol-watch-{}.toml", std::process::id()));
+        let mut config = Config::default();
+        config.save(&path).unwrap();
+
+        let mut watcher = ConfigWatcher::new(&path, config.clone());
+        assert!(watcher.poll().is_none());
+
+        config.capture.fps = 10;
+        config.webrtc.udp_port_range = Some((50000, 50100));
+        config.save(&path).unwrap();
+        // 确保大小变化，避免修改时间精度不足
+        let reload = watcher.poll().unwrap().unwrap();
+        assert_eq!(reload.applied, vec!["capture.fps"]);
+        assert_eq!(reload.restart_required, vec!["webrtc.udp_port_range"]);
+        assert_eq!(reload.live_settings().fps, 10);
+
+        // Viewer 切换的预设不受其他配置项重新加载影响
+        let mut live = LiveSettings { profile: Some(QualityProfile::LowBandwidth), ..LiveSettings::from_config(&Config::default()) };
+        live.apply(&reload.live_settings(), &reload.applied);
+        assert_eq!(live.fps, 10);
+        assert_eq!(live.profile, Some(QualityProfile::LowBandwidth));
+        assert_eq!(live.resolve(None).fps, 15);
+        assert_eq!(live.resolve(Some(3000)).bitrate, 3000);
+
+        config.capture.fps = 0;
+        config.save(&path).unwrap();
+        assert!(watcher.poll().unwrap().is_err());
+        assert_eq!(watcher.current().capture.fps, 10);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -3,29 +3,175 @@
 //! This module handles the client/viewer mode that connects to a remote host.
 
 use anyhow::Result;
+use std::time::Duration;
 use tracing::{info, warn};
 
-/// Connect mode - Connect to a remote host via IP or public URL
+use crate::cli::Transport;
+use crate::hosts::AddressBook;
+
+/// 检查保存的地址是否可达的超时
+const REACHABLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 保存的地址都不可达时等待 mDNS 响应的时间
+#[cfg(feature = "discovery")]
+const MDNS_WAIT: Duration = Duration::from_secs(3);
+
+/// A saved host resolved to the address to connect to
+pub struct SavedHost {
+    /// Reachable LAN address (or the most recent one when nothing answers)
+    pub ip: Option<String>,
+    /// Tunnel URL, used when no LAN address is available
+    pub url: Option<String>,
+    pub port: u16,
+    /// Default transport saved with the host
+    pub transport: Option<Transport>,
+}
+
+/// Resolve an address book alias
+///
+/// Tries the saved addresses in order (most recent first). When none of them answers, looks the
+/// host up over mDNS (with the `discovery` feature) and saves the new address; the tunnel URL is
+/// the last resort.
+///
+/// # Arguments
+/// * `alias` - Alias in the address book
+/// * `port` - Port from the command line (overrides the saved port)
+pub async fn resolve_saved_host(alias: &str, port: Option<u16>) -> Result<SavedHost> {
+    use clap::ValueEnum;
+
+    let mut book = AddressBook::load(&AddressBook::default_path())?;
+    let Some(mut entry) = book.get(alias).cloned() else {
+        anyhow::bail!("地址簿中没有 {} (用 sscontrol hosts list 查看，sscontrol hosts add 添加)", alias);
+    };
+    let port = port.unwrap_or(entry.port());
+
+    let mut ip = None;
+    for address in &entry.addresses {
+        if is_reachable(address, port).await {
+            ip = Some(address.clone());
+            break;
+        }
+        info!("{} 的地址 {} 不可达", entry.alias, address);
+    }
+
+    #[cfg(feature = "discovery")]
+    let port = if ip.is_some() {
+        port
+    } else {
+        match discover_host(&entry, MDNS_WAIT).await {
+            Some(peer) => {
+                let address = peer.ip_address.to_string();
+                println!("已通过 mDNS 找到 {}: {}", entry.alias, crate::network::lan::host_port(&address, peer.port));
+                entry.learn(&address, peer.port, &peer.device_id, peer.fingerprint.as_deref());
+                ip = Some(address);
+                peer.port
+            }
+            None => port,
+        }
+    };
+
+    if ip.is_none() && entry.url.is_none() {
+        // 都不可达时仍尝试最近的地址，由连接报告具体错误
+        ip = entry.addresses.first().cloned();
+        if ip.is_none() {
+            anyhow::bail!("{} 没有可用的地址 (用 sscontrol hosts add {} --ip <IP> --force 重新保存)", entry.alias, entry.alias);
+        }
+    }
+
+    if let Some(ref ip) = ip {
+        entry.remember_address(ip);
+    }
+    entry.last_connected = Some(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    );
+    let transport = entry.transport.as_deref().and_then(|transport| match Transport::from_str(transport, true) {
+        Ok(transport) => Some(transport),
+        Err(_) => {
+            warn!("忽略 {} 保存的未知传输方式: {}", entry.alias, transport);
+            None
+        }
+    });
+    let url = if ip.is_none() { entry.url.clone() } else { None };
+    book.update(entry);
+    if let Err(e) = book.save() {
+        warn!("{}", e);
+    }
+
+    Ok(SavedHost { ip, url, port, transport })
+}
+
+/// 被控端的信令端口 (TCP) 是否可连接
+async fn is_reachable(address: &str, port: u16) -> bool {
+    let addr = crate::network::lan::host_port(address, port);
+    matches!(
+        tokio::time::timeout(REACHABLE_TIMEOUT, tokio::net::TcpStream::connect(addr)).await,
+        Ok(Ok(_))
+    )
+}
+
+/// 通过 mDNS 查找地址簿中的被控端
+#[cfg(feature = "discovery")]
+pub async fn discover_host(
+    entry: &crate::hosts::HostEntry,
+    wait: Duration,
+) -> Option<crate::discovery::DiscoveredPeer> {
+    let mut discovery = match crate::discovery::MdnsDiscovery::new() {
+        Ok(discovery) => discovery,
+        Err(e) => {
+            warn!("mDNS 不可用: {}", e);
+            return None;
+        }
+    };
+    discovery.enable_broadcast(broadcast_port());
+    let mut events = discovery.start().ok()?;
+    tokio::time::timeout(wait, async {
+        while let Some(event) = events.next_event().await {
+            match event {
+                crate::discovery::DiscoveryEvent::Added(peer) | crate::discovery::DiscoveryEvent::Updated(peer)
+                    if entry.matches(&peer.device_id, peer.fingerprint.as_deref(), &peer.hostname) =>
+                {
+                    return Some(peer);
+                }
+                _ => {}
+            }
+        }
+        None
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Connect mode - Connect to one or more remote hosts via IP or public URL
+///
+/// With several hosts the browser opens a dashboard that shows every host at once.
 ///
 /// # Arguments
-/// * `ip` - Optional IP address for LAN mode
-/// * `url` - Optional public URL for tunnel mode
+/// * `ips` - IP addresses for LAN mode
+/// * `urls` - Public URLs for tunnel mode
 /// * `port` - Port number (only used with IP mode, defaults to 9527)
-pub async fn run_connect_mode(ip: Option<&str>, url: Option<&str>, port: u16) -> Result<()> {
+pub async fn run_connect_mode(ips: &[String], urls: &[String], port: u16) -> Result<()> {
     info!("sscontrol 控制端模式启动...");
 
     // 构建 WebSocket URL
-    let (ws_url, display_target) = if let Some(url) = url {
+    let mut hosts = Vec::new();
+    for url in urls {
         // 公网 URL 模式
         info!("目标地址: {} (公网隧道)", url);
-        (url.to_string(), url.to_string())
-    } else if let Some(ip) = ip {
+        hosts.push(crate::viewer::ViewerHost { name: crate::viewer::host_label(url), signaling_url: url.clone() });
+    }
+    for ip in ips {
         // 局域网 IP 模式
-        info!("目标地址: {}:{}", ip, port);
-        (format!("ws://{}:{}", ip, port), format!("{}:{}", ip, port))
-    } else {
+        let address = crate::network::lan::host_port(ip, port);
+        info!("目标地址: {}", address);
+        hosts.push(crate::viewer::ViewerHost { name: address.clone(), signaling_url: format!("ws://{}", address) });
+    }
+    if hosts.is_empty() {
         anyhow::bail!("必须指定 --ip 或 --url 参数");
-    };
+    }
 
     println!();
     println!("========================================");
@@ -33,13 +179,16 @@ pub async fn run_connect_mode(ip: Option<&str>, url: Option<&str>, port: u16) ->
     println!("========================================");
     println!();
 
-    // 启动 Web 查看器
-    let viewer = crate::viewer::WebViewer::new(ws_url.clone(), 0); // 0 = 随机端口
+    // 启动 Web 查看器 (多台主机时为多会话面板)
+    let viewer = crate::viewer::WebViewer::dashboard(hosts, 0) // 0 = 随机端口
+        .with_config(&viewer_config());
     let viewer_port = viewer.start().await?;
 
     let viewer_url = format!("http://127.0.0.1:{}", viewer_port);
 
-    println!("  被控端: {}", display_target);
+    for host in viewer.hosts() {
+        println!("  被控端: {}", host.name);
+    }
     println!("  查看器: {}", viewer_url);
     println!();
 
@@ -62,6 +211,226 @@ pub async fn run_connect_mode(ip: Option<&str>, url: Option<&str>, port: u16) ->
     Ok(())
 }
 
+/// Connect mode over QUIC - receive video and send input without a browser
+///
+/// # Arguments
+/// * `ip` - Host IP address (QUIC 不经过隧道，必须直连)
+/// * `url` - Public URL (not supported by QUIC)
+/// * `port` - Host port (QUIC 使用同一端口号的 UDP 端口)
+/// * `pin` - PIN shown on the host console, or the unattended access secret
+/// * `totp` - TOTP code from the authenticator app (hosts with two-factor enabled)
+/// * `fingerprint` - Expected host certificate fingerprint
+/// * `pair` - Pair with the host (PIN once, then authenticate with the device key)
+#[cfg(feature = "quic")]
+pub async fn run_quic_connect_mode(
+    ip: Option<&str>,
+    url: Option<&str>,
+    port: u16,
+    pin: Option<String>,
+    totp: Option<String>,
+    fingerprint: Option<String>,
+    pair: bool,
+) -> Result<()> {
+    use crate::network::quic::{QuicClient, QuicClientOptions};
+    use std::time::{Duration, Instant};
+
+    /// 统计输出间隔
+    const STATS_INTERVAL: Duration = Duration::from_secs(2);
+
+    if url.is_some() {
+        anyhow::bail!("QUIC 传输不支持 --url (隧道只转发 HTTP)，请使用 --ip 直连");
+    }
+    let Some(ip) = ip else {
+        anyhow::bail!("必须指定 --ip 参数");
+    };
+    let addr = tokio::net::lookup_host((ip, port))
+        .await?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("无法解析地址: {}", ip))?;
+
+    #[cfg(not(feature = "pairing"))]
+    if pair {
+        anyhow::bail!("设备配对需要 pairing feature (cargo build --features pairing)");
+    }
+    // 本机设备身份: 已配对的被控端据此免 PIN
+    #[cfg(feature = "pairing")]
+    let identity = std::sync::Arc::new(crate::pairing::load_identity()?);
+    #[cfg(feature = "pairing")]
+    let device: Option<std::sync::Arc<dyn crate::network::quic::DeviceSigner>> = Some(identity.clone());
+    #[cfg(not(feature = "pairing"))]
+    let device = None;
+
+    info!("sscontrol 控制端模式启动 (QUIC)...");
+    info!("目标地址: {}", addr);
+    let options = QuicClientOptions { pin, totp, fingerprint, device, pair, ..Default::default() };
+    let client = QuicClient::connect(addr, options).await?;
+    #[cfg(feature = "pairing")]
+    let host_device = match verify_host_device(&client, &addr.to_string(), pair) {
+        Ok(status) => status,
+        Err(e) => {
+            client.close().await;
+            return Err(e);
+        }
+    };
+    let stream = client.stream_info();
+
+    println!();
+    println!("========================================");
+    println!("  sscontrol 控制端 (QUIC)");
+    println!("========================================");
+    println!();
+    println!("  被控端:   {}", addr);
+    println!("  画面:     {}x{} ({})", stream.width, stream.height, stream.codec);
+    println!("  输入权限: {}", if stream.input { "允许" } else { "仅查看" });
+    println!("  证书指纹: {}", client.fingerprint());
+    #[cfg(feature = "pairing")]
+    {
+        println!("  设备指纹: {}", host_device);
+        println!("  本机指纹: {}", identity.fingerprint());
+    }
+    println!();
+    println!("按 Ctrl+C 退出");
+    println!();
+
+    let mut frames: u64 = 0;
+    let mut bytes: u64 = 0;
+    let mut expected_sequence: Option<u64> = None;
+    let mut window_start = Instant::now();
+    let receive = async {
+        while let Some(frame) = client.next_frame().await {
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(e) => {
+                    warn!("接收视频帧失败: {}", e);
+                    continue;
+                }
+            };
+            // 被控端丢帧后会从关键帧恢复，这里只在意外缺帧时再请求一次
+            if expected_sequence.is_some_and(|expected| frame.sequence != expected) && !frame.is_key_frame {
+                let _ = client.request_key_frame().await;
+            }
+            expected_sequence = Some(frame.sequence + 1);
+            frames += 1;
+            bytes += frame.data.len() as u64;
+
+            let elapsed = window_start.elapsed();
+            if elapsed >= STATS_INTERVAL {
+                println!(
+                    "  {:.1} fps, {} kbps, RTT {} ms",
+                    frames as f64 / elapsed.as_secs_f64(),
+                    (bytes as f64 * 8.0 / elapsed.as_secs_f64() / 1000.0) as u64,
+                    client.rtt().as_millis()
+                );
+                frames = 0;
+                bytes = 0;
+                window_start = Instant::now();
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = receive => warn!("被控端已断开连接"),
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    client.close().await;
+
+    info!("控制端模式已退出");
+    Ok(())
+}
+
+/// Verify the host's device proof against the pinned host keys
+///
+/// Returns the host fingerprint with its pairing status. Fails when the host at this address
+/// was paired before but now presents a different key (or none at all).
+#[cfg(all(feature = "quic", feature = "pairing"))]
+fn verify_host_device(client: &crate::network::quic::QuicClient, address: &str, pair: bool) -> Result<String> {
+    use crate::network::quic::DeviceRole;
+    use crate::pairing::{identity, TrustRole};
+
+    let mut trust = crate::pairing::load_trust_store()?;
+    let Some(proof) = client.host_device() else {
+        if let Some(pinned) = trust.host_at(address) {
+            anyhow::bail!(
+                "被控端 {} 已配对为 {} ({})，但本次未出示设备身份，可能不是同一台主机",
+                address,
+                pinned.name,
+                pinned.fingerprint()
+            );
+        }
+        if pair {
+            warn!("被控端未启用设备配对 (pairing feature)，无法配对");
+        }
+        return Ok("(被控端未启用配对)".to_string());
+    };
+
+    let key = identity::verify_proof(proof, &client.channel_binding(DeviceRole::Host)?)?;
+    let fingerprint = identity::fingerprint(&key);
+    if trust.find(TrustRole::Host, &key).is_some() {
+        trust.touch(TrustRole::Host, &key);
+        trust.save()?;
+        return Ok(format!("{} (已配对)", fingerprint));
+    }
+    if let Some(pinned) = trust.host_at(address) {
+        anyhow::bail!(
+            "被控端 {} 的设备公钥已变化 (已配对 {}，本次 {})。如确认被控端重新安装过，请先执行 sscontrol pair remove {}",
+            address,
+            pinned.fingerprint(),
+            fingerprint,
+            pinned.name
+        );
+    }
+    if !pair {
+        return Ok(format!("{} (未配对，加 --pair 配对)", fingerprint));
+    }
+    trust.trust(TrustRole::Host, &proof.name, &key, Some(address));
+    trust.save()?;
+    info!("已配对被控端 {} ({})", proof.name, fingerprint);
+    Ok(format!("{} (已配对 {})", fingerprint, proof.name))
+}
+
+/// Connect mode over QUIC (requires the `quic` feature)
+#[cfg(not(feature = "quic"))]
+pub async fn run_quic_connect_mode(
+    _ip: Option<&str>,
+    _url: Option<&str>,
+    _port: u16,
+    _pin: Option<String>,
+    _totp: Option<String>,
+    _fingerprint: Option<String>,
+    _pair: bool,
+) -> Result<()> {
+    anyhow::bail!("QUIC 传输需要启用 quic feature (cargo build --features quic)")
+}
+
+/// Load the `[viewer]` section of the config file (branding and extra assets)
+///
+/// Falls back to the built-in viewer when there is no config file or it cannot be parsed.
+fn viewer_config() -> crate::config::ViewerConfig {
+    let config_path = crate::config::Config::get_config_path(None);
+    if !std::path::Path::new(&config_path).exists() {
+        return Default::default();
+    }
+    match crate::config::Config::load_unchecked(&config_path) {
+        Ok(config) => config.viewer,
+        Err(e) => {
+            warn!("无法读取配置文件 {}，查看器使用默认样式: {}", config_path, e);
+            Default::default()
+        }
+    }
+}
+
+/// UDP port to listen on for host broadcast beacons (`host.broadcast_port` in the config file)
+#[cfg(feature = "discovery")]
+pub fn broadcast_port() -> u16 {
+    let config_path = crate::config::Config::get_config_path(None);
+    if !std::path::Path::new(&config_path).exists() {
+        return crate::discovery::broadcast::DEFAULT_BROADCAST_PORT;
+    }
+    crate::config::Config::load_unchecked(&config_path)
+        .map(|config| config.host.broadcast_port)
+        .unwrap_or(crate::discovery::broadcast::DEFAULT_BROADCAST_PORT)
+}
+
 /// Open a browser with the specified URL
 ///
 /// # Arguments
@@ -0,0 +1,255 @@
+//! 被控端代理部署
+//!
+//! 把 sscontrol 可执行文件上传到远程 Linux / macOS 主机，写入预配的配置
+//! (信令服务器地址、API Key、设备 ID)，安装并启动系统服务，然后确认代理连上了信令服务器:
+//! - Linux: systemd 服务以 root 运行且看不到 /home，配置写入 /etc/sscontrol/config.toml，
+//!   经 systemd drop-in 设置 `SSCONTROL_CONFIG` 指向它；从 journal 中等待连接成功的日志
+//! - macOS: LaunchAgent 以登录用户运行，配置写入 ~/.config/sscontrol/config.toml；
+//!   屏幕录制和辅助功能权限只能在本机授予，这里只确认服务已启动
+
+use super::remote::SCRIPT_PRELUDE;
+use crate::config::{Config, CONFIG_ENV};
+use anyhow::{anyhow, Result};
+
+/// 上传到远程主目录下的临时位置
+pub const UPLOAD_PATH: &str = ".sscontrol-upload/sscontrol";
+
+/// 远程主机上的安装位置
+const INSTALL_PATH: &str = "/usr/local/bin/sscontrol";
+
+/// Linux 上的配置文件
+const LINUX_CONFIG_PATH: &str = "/etc/sscontrol/config.toml";
+
+/// 代理连上信令服务器时的日志 (见 `network::VideoClient::connect`)
+const CONNECTED_LOG: &str = "连接成功";
+
+/// 远程主机的系统
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteOs {
+    Linux,
+    MacOs,
+}
+
+/// 远程主机信息 (`uname -s; uname -m; hostname` 的输出)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemotePlatform {
+    pub os: RemoteOs,
+    /// Rust 的架构名 (x86_64 / aarch64 ...)
+    pub arch: String,
+    pub hostname: String,
+}
+
+impl RemotePlatform {
+    /// 探测命令
+    pub const PROBE: &'static str = "uname -s; uname -m; hostname";
+
+    pub fn parse(output: &str) -> Result<Self> {
+        let mut lines = output.lines().map(str::trim);
+        let (Some(os), Some(arch), Some(hostname)) = (lines.next(), lines.next(), lines.next()) else {
+            return Err(anyhow!("无法识别远程主机的系统: {:?}", output));
+        };
+        let os = match os {
+            "Linux" => RemoteOs::Linux,
+            "Darwin" => RemoteOs::MacOs,
+            other => return Err(anyhow!("不支持的远程系统: {} (只支持 Linux 和 macOS)", other)),
+        };
+        let arch = match arch {
+            "arm64" => "aarch64",
+            "amd64" => "x86_64",
+            other => other,
+        };
+        Ok(Self { os, arch: arch.to_string(), hostname: hostname.to_string() })
+    }
+
+    /// 当前程序能否直接在该主机上运行
+    pub fn matches_local(&self) -> bool {
+        let os = match self.os {
+            RemoteOs::Linux => "linux",
+            RemoteOs::MacOs => "macos",
+        };
+        os == std::env::consts::OS && self.arch == std::env::consts::ARCH
+    }
+
+    /// 按主机名生成设备 ID (只保留字母、数字、- 和 _)
+    pub fn default_device_id(&self) -> String {
+        let short = self.hostname.split('.').next().unwrap_or_default();
+        let id: String = short
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c.to_ascii_lowercase() } else { '-' })
+            .collect();
+        if id.trim_matches('-').is_empty() {
+            "sscontrol-agent".to_string()
+        } else {
+            id
+        }
+    }
+}
+
+/// 一台主机的预配参数
+#[derive(Debug, Clone)]
+pub struct AgentProvision {
+    pub platform: RemotePlatform,
+    pub signaling_url: String,
+    pub api_key: Option<String>,
+    pub device_id: String,
+    /// 等待连上信令服务器的时间 (秒，0 = 不检查)
+    pub verify_timeout: u64,
+}
+
+impl AgentProvision {
+    /// 写入远程主机的配置 (默认配置加上预配项，写入前校验)
+    pub fn config(&self) -> Result<String> {
+        let mut config = Config::default();
+        config.server.url = self.signaling_url.clone();
+        config.server.device_id = self.device_id.clone();
+        config.security.api_key = self.api_key.clone();
+        // 同时登记到信令服务器的设备清单 (`sscontrol fleet list` 查看)
+        config.fleet.server = Some(websocket_url(&self.signaling_url));
+        config.check()?;
+        Ok(toml::to_string_pretty(&config)?)
+    }
+
+    /// 在远程主机上执行的安装脚本
+    pub fn install_script(&self) -> Result<String> {
+        let config = self.config()?;
+        let mut script = format!(
+            r#"{prelude}
+echo "[1/4] 安装 {install}"
+$SUDO mkdir -p "$(dirname {install})"
+$SUDO install -m 755 "$HOME/{upload}" {install}
+rm -rf "$HOME/$(dirname {upload})"
+{install} --version
+"#,
+            prelude = SCRIPT_PRELUDE,
+            install = INSTALL_PATH,
+            upload = UPLOAD_PATH,
+        );
+        match self.platform.os {
+            RemoteOs::Linux => script.push_str(&format!(
+                r#"
+echo "[2/4] 写入 {config_path}"
+$SUDO mkdir -p "$(dirname {config_path})"
+$SUDO tee {config_path} >/dev/null <<'SSCONTROL_EOF'
+{config}SSCONTROL_EOF
+$SUDO chmod 600 {config_path}
+
+echo "[3/4] 安装系统服务"
+$SUDO {install} service install
+$SUDO mkdir -p /etc/systemd/system/sscontrol.service.d
+printf '[Service]\nEnvironment={env}={config_path}\n' | $SUDO tee /etc/systemd/system/sscontrol.service.d/provision.conf >/dev/null
+$SUDO systemctl daemon-reload
+START="$(date '+%Y-%m-%d %H:%M:%S')"
+$SUDO systemctl restart sscontrol
+
+echo "[4/4] 等待连接信令服务器"
+i=0
+while [ "$i" -lt {timeout} ]; do
+    if $SUDO journalctl -u sscontrol --since "$START" --no-pager 2>/dev/null | grep -q "{connected}"; then
+        echo "  已连接"
+        exit 0
+    fi
+    sleep 1
+    i=$((i + 1))
+done
+if [ {timeout} -eq 0 ]; then
+    $SUDO systemctl is-active --quiet sscontrol
+    exit 0
+fi
+echo "  {timeout} 秒内未连上信令服务器，最近的日志:" >&2
+$SUDO journalctl -u sscontrol -n 20 --no-pager >&2 || true
+exit 3
+"#,
+                config_path = LINUX_CONFIG_PATH,
+                env = CONFIG_ENV,
+                install = INSTALL_PATH,
+                timeout = self.verify_timeout,
+                connected = CONNECTED_LOG,
+            )),
+            RemoteOs::MacOs => script.push_str(&format!(
+                r#"
+echo "[2/4] 写入 ~/.config/sscontrol/config.toml"
+mkdir -p "$HOME/.config/sscontrol"
+(umask 077 && cat > "$HOME/.config/sscontrol/config.toml") <<'SSCONTROL_EOF'
+{config}SSCONTROL_EOF
+
+echo "[3/4] 安装 LaunchAgent"
+{install} service install --skip-permission-check
+{install} service start
+
+echo "[4/4] 检查服务"
+sleep 2
+launchctl list | grep -q com.sscontrol.agent
+echo "  已启动 (屏幕录制和辅助功能权限需要在这台 Mac 上授予)"
+"#,
+                install = INSTALL_PATH,
+            )),
+        }
+        Ok(script)
+    }
+}
+
+/// http(s):// 地址换成对应的 ws(s):// 地址 (设备清单只接受 WebSocket 地址)
+fn websocket_url(url: &str) -> String {
+    if let Some(rest) = url.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = url.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else {
+        url.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provision(os: RemoteOs) -> AgentProvision {
+        AgentProvision {
+            platform: RemotePlatform { os, arch: "x86_64".to_string(), hostname: "build-07.lab.example.com".to_string() },
+            signaling_url: "wss://signal.example.com".to_string(),
+            api_key: Some("fleet-key".to_string()),
+            device_id: "build-07".to_string(),
+            verify_timeout: 30,
+        }
+    }
+
+    #[test]
+    fn test_parse_platform() {
+        let platform = RemotePlatform::parse("Darwin\narm64\nStudio.local\n").unwrap();
+        assert_eq!(platform.os, RemoteOs::MacOs);
+        assert_eq!(platform.arch, "aarch64");
+        assert_eq!(platform.default_device_id(), "studio");
+
+        let platform = RemotePlatform::parse("Linux\nx86_64\nBuild_07.lab\n").unwrap();
+        assert_eq!(platform.default_device_id(), "build_07");
+        assert!(RemotePlatform::parse("FreeBSD\namd64\nbsd\n").is_err());
+        assert!(RemotePlatform::parse("Linux\n").is_err());
+    }
+
+    #[test]
+    fn test_provision_config() {
+        let config: Config = toml::from_str(&provision(RemoteOs::Linux).config().unwrap()).unwrap();
+        assert_eq!(config.server.url, "wss://signal.example.com");
+        assert_eq!(config.server.device_id, "build-07");
+        assert_eq!(config.security.api_key.as_deref(), Some("fleet-key"));
+        assert_eq!(config.fleet.server.as_deref(), Some("wss://signal.example.com"));
+
+        let mut provision = provision(RemoteOs::Linux);
+        provision.signaling_url = "https://signal.example.com".to_string();
+        let config: Config = toml::from_str(&provision.config().unwrap()).unwrap();
+        assert_eq!(config.fleet.server.as_deref(), Some("wss://signal.example.com"));
+    }
+
+    #[test]
+    fn test_install_scripts() {
+        let linux = provision(RemoteOs::Linux).install_script().unwrap();
+        assert!(linux.contains("$SUDO install -m 755 \"$HOME/.sscontrol-upload/sscontrol\" /usr/local/bin/sscontrol"));
+        assert!(linux.contains("Environment=SSCONTROL_CONFIG=/etc/sscontrol/config.toml"));
+        assert!(linux.contains("device_id = \"build-07\""));
+        assert!(linux.contains("grep -q \"连接成功\""));
+
+        let macos = provision(RemoteOs::MacOs).install_script().unwrap();
+        assert!(macos.contains("service install --skip-permission-check"));
+        assert!(!macos.contains("systemctl"));
+    }
+}
@@ -0,0 +1,196 @@
+//! docker-compose 部署文件
+//!
+//! 生成可以直接 `docker compose up -d` 的目录:
+//! - turn: 官方 coturn 镜像，使用 host 网络 (中继端口范围很大，端口映射代价高)
+//! - proxy: Caddy 反向代理，自动申请 Let's Encrypt 证书，把 `wss://<域名>` 转发到信令地址
+//!   (如经内网或 VPN 可达的被控端)；WebSocket 升级由 Caddy 自动处理
+//!
+//! 本仓库没有独立的信令服务器程序，不生成信令服务的镜像
+
+use super::turn::TurnDeployment;
+use anyhow::{anyhow, Result};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+/// coturn 镜像
+const COTURN_IMAGE: &str = "coturn/coturn:4.6";
+
+/// Caddy 镜像
+const CADDY_IMAGE: &str = "caddy:2";
+
+/// 容器访问宿主机的地址 (配合 extra_hosts 的 host-gateway)
+const DOCKER_HOST: &str = "host.docker.internal";
+
+/// 部署目录的内容
+#[derive(Debug, Clone)]
+pub struct ComposeDeployment {
+    /// 服务器地址 (反向代理启用时是证书域名)
+    pub host: String,
+    pub turn: Option<TurnDeployment>,
+    /// 反向代理的信令地址 (`主机:端口`)
+    pub signaling_upstream: Option<String>,
+    /// 申请证书时登记的邮箱
+    pub email: Option<String>,
+}
+
+impl ComposeDeployment {
+    pub fn new(
+        host: &str,
+        turn: Option<TurnDeployment>,
+        signaling_upstream: Option<String>,
+        email: Option<String>,
+    ) -> Result<Self> {
+        if turn.is_none() && signaling_upstream.is_none() {
+            return Err(anyhow!("没有要部署的服务 (去掉 --no-turn 或指定 --signaling-upstream)"));
+        }
+        if let Some(upstream) = &signaling_upstream {
+            if host.parse::<IpAddr>().is_ok() {
+                return Err(anyhow!("反向代理需要域名申请证书，--host 不能是 IP 地址"));
+            }
+            let port = upstream.rsplit_once(':').and_then(|(_, port)| port.parse::<u16>().ok());
+            if port.is_none_or(|port| port == 0) {
+                return Err(anyhow!("信令地址 \"{}\" 格式应为 主机:端口", upstream));
+            }
+        }
+        Ok(Self { host: host.to_string(), turn, signaling_upstream, email })
+    }
+
+    /// 公网信令地址 (启用反向代理时)
+    pub fn signaling_url(&self) -> Option<String> {
+        self.signaling_upstream.as_ref().map(|_| format!("wss://{}", self.host))
+    }
+
+    /// 生成的文件 (文件名, 内容)
+    pub fn render(&self) -> Vec<(&'static str, String)> {
+        let mut files = vec![("docker-compose.yml", self.compose_file())];
+        if let Some(turn) = &self.turn {
+            files.push(("turnserver.conf", turn.container_coturn_config()));
+        }
+        if self.signaling_upstream.is_some() {
+            files.push(("Caddyfile", self.caddyfile()));
+        }
+        files
+    }
+
+    /// 写入输出目录，返回写入的文件 (已存在的文件只在 `force` 时覆盖)
+    pub fn write(&self, dir: &Path, force: bool) -> Result<Vec<PathBuf>> {
+        let files = self.render();
+        if !force {
+            if let Some((name, _)) = files.iter().find(|(name, _)| dir.join(name).exists()) {
+                return Err(anyhow!("{:?} 已存在 (使用 --force 覆盖)", dir.join(name)));
+            }
+        }
+        std::fs::create_dir_all(dir).map_err(|e| anyhow!("创建 {:?} 失败: {}", dir, e))?;
+        files
+            .into_iter()
+            .map(|(name, content)| {
+                let path = dir.join(name);
+                std::fs::write(&path, content).map_err(|e| anyhow!("写入 {:?} 失败: {}", path, e))?;
+                Ok(path)
+            })
+            .collect()
+    }
+
+    fn compose_file(&self) -> String {
+        let mut lines = vec![
+            "# 由 sscontrol deploy compose 生成".to_string(),
+            "services:".to_string(),
+        ];
+        if self.turn.is_some() {
+            lines.extend([
+                "  turn:".to_string(),
+                format!("    image: {}", COTURN_IMAGE),
+                "    restart: unless-stopped".to_string(),
+                "    network_mode: host".to_string(),
+                "    volumes:".to_string(),
+                "      - ./turnserver.conf:/etc/coturn/turnserver.conf:ro".to_string(),
+            ]);
+        }
+        if self.signaling_upstream.is_some() {
+            lines.extend([
+                "  proxy:".to_string(),
+                format!("    image: {}", CADDY_IMAGE),
+                "    restart: unless-stopped".to_string(),
+                "    ports:".to_string(),
+                "      - \"80:80\"".to_string(),
+                "      - \"443:443\"".to_string(),
+                "    extra_hosts:".to_string(),
+                format!("      - \"{}:host-gateway\"", DOCKER_HOST),
+                "    volumes:".to_string(),
+                "      - ./Caddyfile:/etc/caddy/Caddyfile:ro".to_string(),
+                "      - caddy-data:/data".to_string(),
+                "volumes:".to_string(),
+                "  caddy-data:".to_string(),
+            ]);
+        }
+        lines.join("\n") + "\n"
+    }
+
+    fn caddyfile(&self) -> String {
+        let upstream = self.signaling_upstream.as_deref().unwrap_or_default();
+        // 容器内的 localhost 是容器自己，改为宿主机
+        let upstream = match upstream.rsplit_once(':') {
+            Some(("localhost" | "127.0.0.1", port)) => format!("{}:{}", DOCKER_HOST, port),
+            _ => upstream.to_string(),
+        };
+        let mut content = String::from("# 由 sscontrol deploy compose 生成\n");
+        if let Some(email) = &self.email {
+            content.push_str(&format!("{{\n    email {}\n}}\n\n", email));
+        }
+        content.push_str(&format!("{} {{\n    reverse_proxy {}\n}}\n", self.host, upstream));
+        content
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn() -> TurnDeployment {
+        TurnDeployment::new("relay.example.com", 3478, (49152, 49200), None, "sscontrol").unwrap()
+    }
+
+    #[test]
+    fn test_renders_turn_and_proxy() {
+        let deployment = ComposeDeployment::new(
+            "relay.example.com",
+            Some(turn()),
+            Some("localhost:9527".to_string()),
+            Some("ops@example.com".to_string()),
+        )
+        .unwrap();
+        let files = deployment.render();
+        let names: Vec<_> = files.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["docker-compose.yml", "turnserver.conf", "Caddyfile"]);
+
+        let compose = &files[0].1;
+        assert!(compose.contains("  turn:\n    image: coturn/coturn:4.6\n"));
+        assert!(compose.contains("network_mode: host"));
+        assert!(compose.contains("  proxy:\n"));
+        assert!(files[1].1.contains("log-file=stdout\n"));
+        assert!(files[2].1.contains("email ops@example.com"));
+        assert!(files[2].1.contains("relay.example.com {\n    reverse_proxy host.docker.internal:9527\n}"));
+        assert_eq!(deployment.signaling_url().as_deref(), Some("wss://relay.example.com"));
+    }
+
+    #[test]
+    fn test_turn_only_and_invalid_input() {
+        let files = ComposeDeployment::new("203.0.113.7", Some(turn()), None, None).unwrap().render();
+        assert_eq!(files.len(), 2);
+        assert!(!files[0].1.contains("proxy"));
+
+        assert!(ComposeDeployment::new("relay.example.com", None, None, None).is_err());
+        assert!(ComposeDeployment::new("203.0.113.7", None, Some("10.8.0.2:9527".to_string()), None).is_err());
+        assert!(ComposeDeployment::new("relay.example.com", None, Some("10.8.0.2".to_string()), None).is_err());
+    }
+
+    #[test]
+    fn test_write_refuses_to_overwrite() {
+        let dir = std::env::temp_dir().join(format!("sscontrol-deploy-compose-{}", std::process::id()));
+        let deployment = ComposeDeployment::new("relay.example.com", Some(turn()), None, None).unwrap();
+        assert_eq!(deployment.write(&dir, false).unwrap().len(), 2);
+        assert!(deployment.write(&dir, false).is_err());
+        assert!(deployment.write(&dir, true).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
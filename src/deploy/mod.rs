@@ -0,0 +1,15 @@
+//! 远程部署模块
+//!
+//! 通过系统的 `ssh` 在服务器上执行安装脚本 (认证沿用 ssh 自己的密钥、ssh-agent 和 ~/.ssh/config)，
+//! 部署结果写回本地配置文件:
+//! - turn: 安装 coturn，生成长期凭证，开放防火墙端口，写入 `webrtc.turn_servers`
+//! - agent: 上传 sscontrol，写入预配配置并安装系统服务，确认代理连上信令服务器
+//!
+//! 另外可以生成 docker-compose 部署目录 (compose)，由用户自己复制到服务器启动
+
+pub mod agent;
+pub mod compose;
+pub mod remote;
+pub mod turn;
+
+pub use remote::RemoteHost;
@@ -0,0 +1,267 @@
+//! 在远程服务器上执行脚本
+//!
+//! 脚本经 stdin 交给远程的 `sh -s` 执行，输出直接显示在本地终端。
+//! 使用 BatchMode: 不支持交互式输入密码，需要事先配置密钥登录或运行 ssh-agent。
+//!
+//! 连接参数写入本次连接专用的 ssh 配置文件 (`ssh -F`)，而不是 `-o` 参数:
+//! ssh 经跳板机 (ProxyJump) 连接时只把 `-F` 传给跳板机的连接，`-o` 参数对跳板机不生效。
+//! 这样主机密钥校验、保活和私钥对链路上的每一跳都适用；文件末尾引用用户自己的 ~/.ssh/config，
+//! 其中的别名和其他设置照常可用 (同一设置以先出现的为准)
+
+use crate::cli::DeployTarget;
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// 主机密钥校验方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HostKeyPolicy {
+    /// 只信任 known_hosts 中已有的密钥
+    #[default]
+    Strict,
+    /// 记录首次见到的密钥，之后密钥变化时拒绝连接
+    AcceptNew,
+}
+
+/// 部署的目标服务器
+#[derive(Debug, Clone)]
+pub struct RemoteHost {
+    pub host: String,
+    pub user: String,
+    pub ssh_port: u16,
+    pub identity: Option<PathBuf>,
+    /// 依次经过的跳板机 (`user@host[:port]`)
+    pub jumps: Vec<String>,
+    /// 转发本地 ssh-agent 到目标服务器
+    pub forward_agent: bool,
+    pub host_key_policy: HostKeyPolicy,
+    /// known_hosts 文件 (None = ssh 默认)
+    pub known_hosts: Option<PathBuf>,
+    /// 保活间隔 (秒，0 = 关闭)
+    pub keepalive: u32,
+}
+
+impl From<&DeployTarget> for RemoteHost {
+    fn from(target: &DeployTarget) -> Self {
+        Self {
+            host: target.host.clone(),
+            user: target.user.clone(),
+            ssh_port: target.ssh_port,
+            identity: target.identity.clone(),
+            jumps: target.jumps.clone(),
+            forward_agent: target.forward_agent,
+            host_key_policy: if target.accept_new_host_key { HostKeyPolicy::AcceptNew } else { HostKeyPolicy::Strict },
+            known_hosts: target.known_hosts.clone(),
+            keepalive: target.keepalive,
+        }
+    }
+}
+
+/// 本次连接专用的 ssh 配置文件 (drop 时删除)
+struct SshConfigFile(PathBuf);
+
+impl SshConfigFile {
+    fn create(content: &str) -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("sscontrol-ssh-{}-{}.conf", std::process::id(), rand::random::<u32>()));
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        // ssh 拒绝其他用户可写的配置文件
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&path).map_err(|e| anyhow!("创建 ssh 配置文件失败: {}", e))?;
+        std::io::Write::write_all(&mut file, content.as_bytes())?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for SshConfigFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+impl RemoteHost {
+    /// `user@host`
+    pub fn destination(&self) -> String {
+        format!("{}@{}", self.user, self.host)
+    }
+
+    /// 检查连接参数
+    pub fn check(&self) -> Result<()> {
+        for jump in &self.jumps {
+            if jump.is_empty() || jump.contains(|c: char| c == ',' || c.is_whitespace()) {
+                return Err(anyhow!("跳板机 \"{}\" 格式应为 user@host[:port]", jump));
+            }
+        }
+        if self.forward_agent && std::env::var_os("SSH_AUTH_SOCK").is_none() {
+            return Err(anyhow!("--forward-agent 需要运行中的 ssh-agent (未设置 SSH_AUTH_SOCK)"));
+        }
+        Ok(())
+    }
+
+    /// 本次连接的 ssh 配置 (对目标和所有跳板机生效)
+    pub fn ssh_config(&self) -> String {
+        let mut lines = vec![
+            "# 由 sscontrol deploy 生成，仅用于本次连接".to_string(),
+            "Host *".to_string(),
+            "    BatchMode yes".to_string(),
+            "    ConnectTimeout 15".to_string(),
+            format!(
+                "    StrictHostKeyChecking {}",
+                match self.host_key_policy {
+                    HostKeyPolicy::Strict => "yes",
+                    HostKeyPolicy::AcceptNew => "accept-new",
+                }
+            ),
+        ];
+        if let Some(known_hosts) = &self.known_hosts {
+            lines.push(format!("    UserKnownHostsFile \"{}\"", known_hosts.display()));
+        }
+        if self.keepalive > 0 {
+            lines.push(format!("    ServerAliveInterval {}", self.keepalive));
+            lines.push("    ServerAliveCountMax 4".to_string());
+        }
+        if let Some(identity) = &self.identity {
+            lines.push(format!("    IdentityFile \"{}\"", identity.display()));
+        }
+        lines.push("    Include ~/.ssh/config".to_string());
+        lines.join("\n") + "\n"
+    }
+
+    /// 使用给定配置文件连接服务器并执行 `remote_command` 的 ssh 命令
+    fn ssh_command(&self, config_file: &Path, remote_command: &str) -> Command {
+        let mut command = Command::new("ssh");
+        command.arg("-F").arg(config_file);
+        if !self.jumps.is_empty() {
+            command.arg("-J").arg(self.jumps.join(","));
+        }
+        if self.forward_agent {
+            command.arg("-A");
+        }
+        command.arg("-p").arg(self.ssh_port.to_string()).arg(self.destination()).arg(remote_command);
+        command
+    }
+
+    /// 执行 shell 脚本 (输出显示在本地终端)，脚本以非零状态退出时返回错误
+    pub async fn run_script(&self, script: &str) -> Result<()> {
+        self.exec("sh -s", script.as_bytes(), false).await.map(drop)
+    }
+
+    /// 执行命令并返回其输出
+    pub async fn output(&self, remote_command: &str) -> Result<String> {
+        self.exec(remote_command, &[], true).await
+    }
+
+    /// 把文件内容上传到远程主目录下的 `remote_path` (仅当前用户可读写)
+    pub async fn upload(&self, data: &[u8], remote_path: &str) -> Result<()> {
+        let command = format!("umask 077 && mkdir -p \"$(dirname {path})\" && cat > {path}", path = remote_path);
+        self.exec(&command, data, false).await.map(drop)
+    }
+
+    async fn exec(&self, remote_command: &str, input: &[u8], capture: bool) -> Result<String> {
+        self.check()?;
+        let config_file = SshConfigFile::create(&self.ssh_config())?;
+        let mut command = self.ssh_command(&config_file.0, remote_command);
+        command.stdin(Stdio::piped()).kill_on_drop(true);
+        if capture {
+            command.stdout(Stdio::piped());
+        }
+        let mut child = command.spawn().map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => anyhow!("找不到 ssh 命令，请先安装 OpenSSH 客户端"),
+            _ => anyhow!("启动 ssh 失败: {}", e),
+        })?;
+        let mut stdin = child.stdin.take().ok_or_else(|| anyhow!("无法写入 ssh 的标准输入"))?;
+        stdin.write_all(input).await?;
+        drop(stdin);
+
+        let output = child.wait_with_output().await?;
+        match output.status.code() {
+            Some(0) => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+            // ssh 自身的错误 (连接、认证、主机密钥校验失败) 退出码为 255
+            Some(255) => Err(anyhow!(
+                "无法通过 SSH 连接 {} (端口 {})；首次连接的服务器需要加 --accept-new-host-key 或先手动 ssh 一次",
+                self.destination(),
+                self.ssh_port
+            )),
+            _ => Err(anyhow!("{} 上的命令执行失败 ({})", self.host, output.status)),
+        }
+    }
+}
+
+/// 脚本开头: 出错即停止，非 root 用户通过 sudo 执行特权命令
+pub const SCRIPT_PRELUDE: &str = r#"set -eu
+if [ "$(id -u)" -ne 0 ]; then SUDO="sudo -n"; else SUDO=""; fi
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remote() -> RemoteHost {
+        RemoteHost {
+            host: "relay.example.com".to_string(),
+            user: "admin".to_string(),
+            ssh_port: 2222,
+            identity: Some(PathBuf::from("/home/me/.ssh/deploy")),
+            jumps: vec!["ops@bastion.example.com".to_string(), "10.0.0.1:2200".to_string()],
+            forward_agent: false,
+            host_key_policy: HostKeyPolicy::Strict,
+            known_hosts: None,
+            keepalive: 15,
+        }
+    }
+
+    #[test]
+    fn test_ssh_command_args() {
+        let command = remote().ssh_command(Path::new("/tmp/ssh.conf"), "sh -s");
+        let args: Vec<_> = command.as_std().get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        assert_eq!(
+            args,
+            [
+                "-F",
+                "/tmp/ssh.conf",
+                "-J",
+                "ops@bastion.example.com,10.0.0.1:2200",
+                "-p",
+                "2222",
+                "admin@relay.example.com",
+                "sh -s"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ssh_config_applies_to_every_hop() {
+        let config = remote().ssh_config();
+        assert!(config.contains("Host *\n    BatchMode yes\n"));
+        assert!(config.contains("    StrictHostKeyChecking yes\n"));
+        assert!(config.contains("    ServerAliveInterval 15\n"));
+        assert!(config.contains("    IdentityFile \"/home/me/.ssh/deploy\"\n"));
+        assert!(config.ends_with("    Include ~/.ssh/config\n"));
+
+        let relaxed = RemoteHost {
+            host_key_policy: HostKeyPolicy::AcceptNew,
+            known_hosts: Some(PathBuf::from("/etc/sscontrol/known_hosts")),
+            keepalive: 0,
+            ..remote()
+        };
+        let config = relaxed.ssh_config();
+        assert!(config.contains("    StrictHostKeyChecking accept-new\n"));
+        assert!(config.contains("    UserKnownHostsFile \"/etc/sscontrol/known_hosts\"\n"));
+        assert!(!config.contains("ServerAlive"));
+
+        let invalid = RemoteHost { jumps: vec!["a, b".to_string()], ..remote() };
+        assert!(invalid.check().is_err());
+    }
+
+    #[test]
+    fn test_config_file_is_removed() {
+        let file = SshConfigFile::create("Host *\n").unwrap();
+        let path = file.0.clone();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Host *\n");
+        drop(file);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,276 @@
+//! TURN 服务器部署 (coturn)
+//!
+//! 在服务器上安装 coturn 并写入 `/etc/turnserver.conf`:
+//! - 长期凭证 (lt-cred-mech)，用户名和随机生成的密码写入本地配置的 `webrtc.turn_servers`
+//! - 禁止中继到内网和本机地址，防止 TURN 服务器被用来访问服务器所在的内网
+//! - 开放监听端口 (UDP/TCP) 和中继端口范围 (UDP)，支持 ufw 和 firewalld；
+//!   云服务商的安全组需要另外放行
+
+use super::remote::SCRIPT_PRELUDE;
+use crate::cli::TurnArgs;
+use crate::config::{edit, Config, TurnServerConfig};
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// 不允许中继的对端地址段 (coturn 的 denied-peer-ip 格式)
+const DENIED_PEER_RANGES: [&str; 9] = [
+    "0.0.0.0-0.255.255.255",
+    "10.0.0.0-10.255.255.255",
+    "100.64.0.0-100.127.255.255",
+    "127.0.0.0-127.255.255.255",
+    "169.254.0.0-169.254.255.255",
+    "172.16.0.0-172.31.255.255",
+    "192.168.0.0-192.168.255.255",
+    "::1",
+    "fc00::-fdff:ffff:ffff:ffff:ffff:ffff:ffff:ffff",
+];
+
+/// TURN 服务器参数
+#[derive(Debug, Clone)]
+pub struct TurnDeployment {
+    /// 服务器地址 (TURN URL 中的主机，同时作为 realm)
+    pub host: String,
+    pub port: u16,
+    pub min_port: u16,
+    pub max_port: u16,
+    /// 公网 IP (`公网IP` 或 `公网IP/内网IP`)
+    pub external_ip: Option<String>,
+    pub username: String,
+    pub password: String,
+}
+
+impl TurnDeployment {
+    /// 按命令行参数创建
+    pub fn from_args(host: &str, args: &TurnArgs) -> Result<Self> {
+        Self::new(host, args.port, (args.min_port, args.max_port), args.external_ip.clone(), &args.username)
+    }
+
+    /// 检查参数并生成随机密码
+    pub fn new(
+        host: &str,
+        port: u16,
+        (min_port, max_port): (u16, u16),
+        external_ip: Option<String>,
+        username: &str,
+    ) -> Result<Self> {
+        if port == 0 {
+            return Err(anyhow!("TURN 端口不能为 0"));
+        }
+        if min_port == 0 || min_port >= max_port {
+            return Err(anyhow!("中继端口范围 {}-{} 无效", min_port, max_port));
+        }
+        if username.is_empty() || !username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(anyhow!("TURN 用户名只能包含字母、数字、- 和 _"));
+        }
+        if let Some(ip) = &external_ip {
+            if !ip.split('/').all(|part| part.parse::<IpAddr>().is_ok()) || ip.split('/').count() > 2 {
+                return Err(anyhow!("\"{}\" 不是有效的公网 IP (格式: 公网IP 或 公网IP/内网IP)", ip));
+            }
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            min_port,
+            max_port,
+            external_ip,
+            username: username.to_string(),
+            password: new_password(),
+        })
+    }
+
+    /// 写入本地配置的 TURN 服务器
+    pub fn server_config(&self) -> TurnServerConfig {
+        let host = match self.host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(ip)) => format!("[{}]", ip),
+            _ => self.host.clone(),
+        };
+        TurnServerConfig {
+            url: format!("turn:{}:{}", host, self.port),
+            username: self.username.clone(),
+            password: self.password.clone(),
+        }
+    }
+
+    /// `/etc/turnserver.conf` 的内容 (日志写入 syslog)
+    pub fn coturn_config(&self) -> String {
+        self.render_coturn_config("syslog")
+    }
+
+    /// 容器中使用的 coturn 配置 (日志输出到 stdout，由 docker logs 查看)
+    pub fn container_coturn_config(&self) -> String {
+        self.render_coturn_config("log-file=stdout")
+    }
+
+    fn render_coturn_config(&self, log: &str) -> String {
+        let mut lines = vec![
+            "# 由 sscontrol deploy turn 生成".to_string(),
+            format!("listening-port={}", self.port),
+            format!("min-port={}", self.min_port),
+            format!("max-port={}", self.max_port),
+            "fingerprint".to_string(),
+            "lt-cred-mech".to_string(),
+            format!("user={}:{}", self.username, self.password),
+            format!("realm={}", self.host),
+        ];
+        if let Some(ip) = &self.external_ip {
+            lines.push(format!("external-ip={}", ip));
+        }
+        lines.extend(["no-cli", "no-multicast-peers", "no-tlsv1", "no-tlsv1_1", log, "simple-log"].map(String::from));
+        lines.extend(DENIED_PEER_RANGES.iter().map(|range| format!("denied-peer-ip={}", range)));
+        lines.join("\n") + "\n"
+    }
+
+    /// 在服务器上执行的安装脚本
+    pub fn install_script(&self) -> String {
+        let (port, min, max) = (self.port, self.min_port, self.max_port);
+        format!(
+            r#"{prelude}
+echo "[1/4] 安装 coturn"
+if ! command -v turnserver >/dev/null 2>&1; then
+    if command -v apt-get >/dev/null 2>&1; then
+        $SUDO env DEBIAN_FRONTEND=noninteractive apt-get update -q
+        $SUDO env DEBIAN_FRONTEND=noninteractive apt-get install -y -q coturn
+    elif command -v dnf >/dev/null 2>&1; then
+        $SUDO dnf install -y coturn
+    elif command -v yum >/dev/null 2>&1; then
+        $SUDO yum install -y epel-release && $SUDO yum install -y coturn
+    elif command -v apk >/dev/null 2>&1; then
+        $SUDO apk add coturn
+    else
+        echo "不支持的系统: 找不到 apt-get / dnf / yum / apk" >&2
+        exit 1
+    fi
+fi
+
+echo "[2/4] 写入 /etc/turnserver.conf"
+$SUDO tee /etc/turnserver.conf >/dev/null <<'SSCONTROL_EOF'
+{config}SSCONTROL_EOF
+$SUDO chmod 640 /etc/turnserver.conf
+for group in turnserver coturn; do
+    if getent group "$group" >/dev/null 2>&1; then $SUDO chgrp "$group" /etc/turnserver.conf; fi
+done
+if [ -f /etc/default/coturn ]; then
+    $SUDO sed -i 's/^#*TURNSERVER_ENABLED=.*/TURNSERVER_ENABLED=1/' /etc/default/coturn
+fi
+
+echo "[3/4] 开放防火墙端口"
+if command -v ufw >/dev/null 2>&1 && $SUDO ufw status 2>/dev/null | grep -q "Status: active"; then
+    $SUDO ufw allow {port}/udp
+    $SUDO ufw allow {port}/tcp
+    $SUDO ufw allow {min}:{max}/udp
+elif command -v firewall-cmd >/dev/null 2>&1 && $SUDO firewall-cmd --state >/dev/null 2>&1; then
+    $SUDO firewall-cmd --permanent --add-port={port}/udp --add-port={port}/tcp --add-port={min}-{max}/udp
+    $SUDO firewall-cmd --reload
+else
+    echo "  未检测到 ufw / firewalld，跳过"
+fi
+
+echo "[4/4] 启动 coturn"
+if command -v systemctl >/dev/null 2>&1; then
+    $SUDO systemctl enable coturn >/dev/null 2>&1 || true
+    $SUDO systemctl restart coturn
+    sleep 1
+    $SUDO systemctl is-active --quiet coturn
+elif command -v rc-service >/dev/null 2>&1; then
+    $SUDO rc-update add turnserver default >/dev/null 2>&1 || true
+    $SUDO rc-service turnserver restart
+else
+    echo "无法识别服务管理器，请手动启动 turnserver -c /etc/turnserver.conf" >&2
+    exit 1
+fi
+"#,
+            prelude = SCRIPT_PRELUDE,
+            config = self.coturn_config(),
+        )
+    }
+}
+
+/// 把 TURN 服务器写入配置文件的 `webrtc.turn_servers` (同一 URL 的旧凭证被替换)
+///
+/// 文件不存在时以默认配置为基础创建
+pub fn save_to_config(path: &Path, server: &TurnServerConfig) -> Result<()> {
+    let content = if path.exists() {
+        std::fs::read_to_string(path).map_err(|e| anyhow!("读取 {:?} 失败: {}", path, e))?
+    } else {
+        toml::to_string_pretty(&Config::default())?
+    };
+    let config: Config = toml::from_str(&content).map_err(|e| anyhow!("配置文件解析失败: {}", e))?;
+
+    let mut servers = config.webrtc.turn_servers;
+    servers.retain(|existing| existing.url != server.url);
+    servers.push(server.clone());
+    let value = toml::Value::try_from(&servers)?;
+    let (content, _) = edit::set_value(&content, "webrtc.turn_servers", &value.to_string())?;
+
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content).map_err(|e| anyhow!("写入 {:?} 失败: {}", path, e))?;
+    Ok(())
+}
+
+fn new_password() -> String {
+    let mut bytes = [0u8; 18];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deployment() -> TurnDeployment {
+        TurnDeployment::new("turn.example.com", 3478, (49152, 49200), Some("203.0.113.7/10.0.0.5".to_string()), "sscontrol")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_coturn_config() {
+        let turn = deployment();
+        assert_eq!(turn.password.len(), 24);
+        let config = turn.coturn_config();
+        assert!(config.contains("listening-port=3478\n"));
+        assert!(config.contains("min-port=49152\nmax-port=49200\n"));
+        assert!(config.contains(&format!("user=sscontrol:{}\n", turn.password)));
+        assert!(config.contains("external-ip=203.0.113.7/10.0.0.5\n"));
+        assert!(config.contains("denied-peer-ip=192.168.0.0-192.168.255.255\n"));
+
+        let script = turn.install_script();
+        assert!(script.starts_with(SCRIPT_PRELUDE));
+        assert!(script.contains("ufw allow 49152:49200/udp"));
+        assert!(script.contains("--add-port=49152-49200/udp"));
+    }
+
+    #[test]
+    fn test_rejects_invalid_parameters() {
+        assert!(TurnDeployment::new("h", 3478, (50000, 40000), None, "u").is_err());
+        assert!(TurnDeployment::new("h", 3478, (49152, 65535), None, "user name").is_err());
+        assert!(TurnDeployment::new("h", 3478, (49152, 65535), Some("example.com".to_string()), "u").is_err());
+
+        let ipv6 = TurnDeployment::new("2001:db8::1", 3478, (49152, 65535), None, "u").unwrap();
+        assert_eq!(ipv6.server_config().url, "turn:[2001:db8::1]:3478");
+    }
+
+    #[test]
+    fn test_save_replaces_existing_credentials() {
+        let dir = std::env::temp_dir().join(format!("sscontrol-deploy-turn-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        let turn = deployment();
+
+        save_to_config(&path, &turn.server_config()).unwrap();
+        let redeployed = deployment();
+        save_to_config(&path, &redeployed.server_config()).unwrap();
+        let other = TurnServerConfig { url: "turn:other.example.com:3478".to_string(), ..redeployed.server_config() };
+        save_to_config(&path, &other).unwrap();
+
+        let config = Config::load_unchecked(&path).unwrap();
+        assert_eq!(config.webrtc.turn_servers.len(), 2);
+        assert_eq!(config.webrtc.turn_servers[0].url, "turn:turn.example.com:3478");
+        assert_eq!(config.webrtc.turn_servers[0].password, redeployed.password);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,456 @@
+//! UDP 广播发现
+//!
+//! 不少网络 (如企业网) 过滤组播，mDNS 无法工作。被控端在可配置的 UDP 端口上周期性地
+//! 广播签名信标 (255.255.255.255)，控制端监听同一端口，结果与 mDNS 合并到同一个设备列表
+//! ([`super::MdnsDiscovery::enable_broadcast`])：
+//! - 信标用 Ed25519 签名 (启用 pairing 时为设备身份，否则为每次启动生成的临时密钥)，
+//!   公布的设备指纹必须与签名公钥一致
+//! - 同一设备 ID 在信标过期前只接受同一公钥，序号不递增的信标视为重放丢弃
+//! - 发送间隔在基准间隔的 50%~150% 之间随机抖动，避免大量设备同时广播
+//! - 被控端退出时广播有效期为 0 的告别信标，控制端立即移除该设备
+
+use super::mdns::{DeviceMetadata, DiscoveredPeer, PeerSource};
+use anyhow::{anyhow, bail, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// 默认广播端口
+pub const DEFAULT_BROADCAST_PORT: u16 = 9531;
+
+/// 默认发送间隔 (实际间隔在 50%~150% 之间抖动)
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 信标格式版本 (同时作为签名的域分隔前缀)
+const BEACON_MAGIC: &str = "sscontrol-beacon/1";
+
+/// 信标最大长度 (不超过以太网 MTU，避免分片)
+const MAX_BEACON_SIZE: usize = 1400;
+
+/// 连续错过多少个 (最长) 发送间隔后视为下线
+const TTL_INTERVALS: f64 = 3.0;
+
+/// 接收线程检查停止标志和过期设备的间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 信标签名
+pub trait BeaconSigner: Send + Sync {
+    fn public_key(&self) -> VerifyingKey;
+    fn sign(&self, message: &[u8]) -> Signature;
+}
+
+impl BeaconSigner for SigningKey {
+    fn public_key(&self) -> VerifyingKey {
+        self.verifying_key()
+    }
+
+    fn sign(&self, message: &[u8]) -> Signature {
+        Signer::sign(self, message)
+    }
+}
+
+/// 每次启动生成的临时签名密钥 (未启用 pairing 时使用)
+pub fn ephemeral_signer() -> Arc<dyn BeaconSigner> {
+    use rand::RngCore;
+    let mut secret = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    Arc::new(SigningKey::from_bytes(&secret))
+}
+
+/// 信标内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Beacon {
+    device_id: String,
+    /// 被控端信令端口
+    port: u16,
+    hostname: String,
+    name: String,
+    os: String,
+    version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    screens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    session_id: Option<String>,
+    /// 单调递增的序号 (防重放)
+    seq: u64,
+    /// 有效期 (秒)，0 表示下线
+    ttl: u64,
+}
+
+/// 线上格式: 签名覆盖 `body` 的原始字节
+#[derive(Serialize, Deserialize)]
+struct SignedBeacon {
+    magic: String,
+    body: String,
+    key: String,
+    sig: String,
+}
+
+fn signed_message(body: &str) -> Vec<u8> {
+    [BEACON_MAGIC.as_bytes(), b"\n", body.as_bytes()].concat()
+}
+
+fn encode(beacon: &Beacon, signer: &dyn BeaconSigner) -> Result<Vec<u8>> {
+    let body = serde_json::to_string(beacon)?;
+    let signature = signer.sign(&signed_message(&body));
+    let packet = serde_json::to_vec(&SignedBeacon {
+        magic: BEACON_MAGIC.to_string(),
+        body,
+        key: hex::encode(signer.public_key().to_bytes()),
+        sig: hex::encode(signature.to_bytes()),
+    })?;
+    if packet.len() > MAX_BEACON_SIZE {
+        bail!("信标过大 ({} 字节)", packet.len());
+    }
+    Ok(packet)
+}
+
+/// 解析并校验信标，返回信标和签名公钥
+fn decode(packet: &[u8]) -> Result<(Beacon, VerifyingKey)> {
+    let signed: SignedBeacon = serde_json::from_slice(packet)?;
+    if signed.magic != BEACON_MAGIC {
+        bail!("不支持的信标版本: {}", signed.magic);
+    }
+    let key: [u8; 32] = hex::decode(&signed.key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("信标公钥格式错误"))?;
+    let key = VerifyingKey::from_bytes(&key).map_err(|_| anyhow!("无效的信标公钥"))?;
+    let signature: [u8; 64] = hex::decode(&signed.sig)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("信标签名格式错误"))?;
+    key.verify(&signed_message(&signed.body), &Signature::from_bytes(&signature))
+        .map_err(|_| anyhow!("信标签名无效"))?;
+
+    let beacon: Beacon = serde_json::from_str(&signed.body)?;
+    if beacon.fingerprint.as_deref().is_some_and(|fingerprint| fingerprint != key_fingerprint(&key)) {
+        bail!("设备 {} 的信标指纹与签名公钥不符", beacon.device_id);
+    }
+    Ok((beacon, key))
+}
+
+/// 公钥短指纹 (格式与 `pairing::identity::fingerprint` 相同)
+fn key_fingerprint(key: &VerifyingKey) -> String {
+    let digest = Sha256::digest(key.as_bytes());
+    hex::encode_upper(&digest[..8])
+        .as_bytes()
+        .chunks(4)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// 在基准间隔的 50%~150% 之间随机取值
+fn jitter(interval: Duration) -> Duration {
+    use rand::Rng;
+    interval.mul_f64(rand::thread_rng().gen_range(0.5..1.5))
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+/// 广播信标 (被控端使用)
+///
+/// 被丢弃时广播告别信标
+pub struct BroadcastAnnouncer {
+    socket: UdpSocket,
+    target: SocketAddr,
+    device_id: String,
+    port: u16,
+    interval: Duration,
+    signer: Arc<dyn BeaconSigner>,
+    metadata: DeviceMetadata,
+    seq: u64,
+}
+
+impl BroadcastAnnouncer {
+    /// 创建广播器
+    ///
+    /// * `port` - 被控端信令端口 (写入信标)
+    /// * `broadcast_port` - 信标发往的 UDP 端口
+    pub fn new(
+        device_id: &str,
+        port: u16,
+        broadcast_port: u16,
+        interval: Duration,
+        signer: Arc<dyn BeaconSigner>,
+    ) -> Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+        // 只在异步任务中发送 UDP，不阻塞运行时
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            target: SocketAddr::from((Ipv4Addr::BROADCAST, broadcast_port)),
+            device_id: device_id.to_string(),
+            port,
+            interval,
+            signer,
+            metadata: DeviceMetadata::local(),
+            // 以启动时间起算，重启后的序号仍大于之前发出的
+            seq: unix_millis(),
+        })
+    }
+
+    /// 当前公布的信息
+    pub fn metadata(&self) -> &DeviceMetadata {
+        &self.metadata
+    }
+
+    /// 更新公布的信息 (与签名公钥不符的指纹不公布)
+    pub fn set_metadata(&mut self, mut metadata: DeviceMetadata) {
+        if metadata.fingerprint.as_deref().is_some_and(|fingerprint| fingerprint != key_fingerprint(&self.signer.public_key())) {
+            metadata.fingerprint = None;
+        }
+        self.metadata = metadata;
+    }
+
+    /// 广播一次信标
+    pub fn announce(&mut self) -> Result<()> {
+        let ttl = (self.interval.as_secs_f64() * 1.5 * TTL_INTERVALS).ceil() as u64;
+        self.send(ttl.max(1))
+    }
+
+    /// 距下一次广播的等待时间 (随机抖动)
+    pub fn next_delay(&self) -> Duration {
+        jitter(self.interval)
+    }
+
+    fn send(&mut self, ttl: u64) -> Result<()> {
+        self.seq += 1;
+        let beacon = Beacon {
+            device_id: self.device_id.clone(),
+            port: self.port,
+            hostname: super::mdns::local_hostname(),
+            name: self.metadata.name.clone(),
+            os: self.metadata.os.clone(),
+            version: self.metadata.version.clone(),
+            screens: self.metadata.screens,
+            fingerprint: self.metadata.fingerprint.clone(),
+            session_id: self.metadata.session_id.clone(),
+            seq: self.seq,
+            ttl,
+        };
+        let packet = encode(&beacon, self.signer.as_ref())?;
+        self.socket.send_to(&packet, self.target)?;
+        Ok(())
+    }
+}
+
+impl Drop for BroadcastAnnouncer {
+    fn drop(&mut self) {
+        if let Err(e) = self.send(0) {
+            debug!("发送告别信标失败: {}", e);
+        }
+    }
+}
+
+/// 信标带来的设备列表变化
+#[derive(Debug)]
+pub(super) enum BeaconUpdate {
+    /// 设备在线 (新设备或刷新)
+    Announced(Box<DiscoveredPeer>),
+    /// 设备下线 (告别信标或过期)，值为设备 ID
+    Left(String),
+}
+
+/// 已接受的设备
+struct KnownBeacon {
+    key: VerifyingKey,
+    seq: u64,
+    expires: Instant,
+}
+
+/// 按设备 ID 记录签名公钥、序号和过期时间
+#[derive(Default)]
+pub(super) struct BeaconTable {
+    known: HashMap<String, KnownBeacon>,
+}
+
+impl BeaconTable {
+    /// 处理一个已校验签名的信标，应丢弃时返回 None
+    fn accept(&mut self, beacon: Beacon, key: VerifyingKey, from: IpAddr, now: Instant) -> Option<BeaconUpdate> {
+        if let Some(known) = self.known.get(&beacon.device_id) {
+            if known.expires > now {
+                if known.key != key {
+                    debug!("忽略设备 {} 来自其他公钥的信标 ({})", beacon.device_id, from);
+                    return None;
+                }
+                if beacon.seq <= known.seq {
+                    return None;
+                }
+            }
+        }
+
+        if beacon.ttl == 0 {
+            self.known.remove(&beacon.device_id);
+            return Some(BeaconUpdate::Left(beacon.device_id));
+        }
+        self.known.insert(
+            beacon.device_id.clone(),
+            KnownBeacon { key, seq: beacon.seq, expires: now + Duration::from_secs(beacon.ttl) },
+        );
+        Some(BeaconUpdate::Announced(Box::new(DiscoveredPeer {
+            device_id: beacon.device_id,
+            fullname: String::new(),
+            ip_address: from,
+            port: beacon.port,
+            hostname: beacon.hostname,
+            name: beacon.name,
+            os: Some(beacon.os).filter(|os| !os.is_empty()),
+            version: Some(beacon.version).filter(|version| !version.is_empty()),
+            screens: beacon.screens,
+            session_id: beacon.session_id,
+            fingerprint: beacon.fingerprint,
+            source: PeerSource::Broadcast,
+            last_seen: now,
+        })))
+    }
+
+    /// 移除过期的设备，返回其设备 ID
+    fn expire(&mut self, now: Instant) -> Vec<String> {
+        let expired: Vec<String> =
+            self.known.iter().filter(|(_, known)| known.expires <= now).map(|(id, _)| id.clone()).collect();
+        for device_id in &expired {
+            self.known.remove(device_id);
+        }
+        expired
+    }
+}
+
+/// 在后台线程中接收信标，直到 `stop` 被置位
+pub(super) fn spawn_listener(
+    port: u16,
+    stop: Arc<AtomicBool>,
+    mut on_update: impl FnMut(BeaconUpdate) + Send + 'static,
+) -> Result<()> {
+    let socket = bind_listener(port)?;
+    socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+    std::thread::spawn(move || {
+        let mut table = BeaconTable::default();
+        let mut buf = [0u8; MAX_BEACON_SIZE];
+        while !stop.load(Ordering::Relaxed) {
+            match socket.recv_from(&mut buf) {
+                Ok((len, from)) => match decode(&buf[..len]) {
+                    Ok((beacon, key)) => {
+                        if let Some(update) = table.accept(beacon, key, from.ip(), Instant::now()) {
+                            on_update(update);
+                        }
+                    }
+                    Err(e) => debug!("丢弃来自 {} 的信标: {}", from, e),
+                },
+                Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {}
+                Err(e) => {
+                    warn!("接收广播信标失败: {}", e);
+                    break;
+                }
+            }
+            for device_id in table.expire(Instant::now()) {
+                on_update(BeaconUpdate::Left(device_id));
+            }
+        }
+    });
+    Ok(())
+}
+
+/// 监听广播端口 (允许同一台机器上的多个控制端共用端口)
+fn bind_listener(port: u16) -> Result<UdpSocket> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    socket.set_reuse_port(true)?;
+    socket.set_broadcast(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)).into())?;
+    Ok(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beacon(device_id: &str, seq: u64, ttl: u64) -> Beacon {
+        Beacon {
+            device_id: device_id.to_string(),
+            port: 9527,
+            hostname: "office-pc".to_string(),
+            name: "office-pc".to_string(),
+            os: "windows".to_string(),
+            version: "0.1.0".to_string(),
+            screens: Some(2),
+            fingerprint: None,
+            session_id: None,
+            seq,
+            ttl,
+        }
+    }
+
+    #[test]
+    fn test_encode_and_verify() {
+        let signer = ephemeral_signer();
+        let mut original = beacon("a", 1, 15);
+        original.fingerprint = Some(key_fingerprint(&signer.public_key()));
+        let packet = encode(&original, signer.as_ref()).unwrap();
+        let (decoded, key) = decode(&packet).unwrap();
+        assert_eq!(decoded, original);
+        assert_eq!(key, signer.public_key());
+
+        // 篡改内容后签名失效
+        let tampered = String::from_utf8(packet).unwrap().replace("9527", "9528");
+        assert!(decode(tampered.as_bytes()).is_err());
+
+        // 不能冒用其他设备的指纹
+        let mut forged = beacon("a", 2, 15);
+        forged.fingerprint = Some("3F2A-91C0-7B44-E215".to_string());
+        assert!(decode(&encode(&forged, signer.as_ref()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_beacon_table() {
+        let owner = ephemeral_signer().public_key();
+        let intruder = ephemeral_signer().public_key();
+        let from: IpAddr = "192.168.1.5".parse().unwrap();
+        let now = Instant::now();
+        let mut table = BeaconTable::default();
+
+        assert!(matches!(table.accept(beacon("a", 5, 15), owner, from, now), Some(BeaconUpdate::Announced(_))));
+        // 重放和其他公钥的信标被丢弃
+        assert!(table.accept(beacon("a", 5, 15), owner, from, now).is_none());
+        assert!(table.accept(beacon("a", 9, 15), intruder, from, now).is_none());
+        assert!(table.accept(beacon("a", 6, 15), owner, from, now).is_some());
+
+        // 过期后移除，之后接受新的公钥
+        assert!(table.expire(now + Duration::from_secs(10)).is_empty());
+        assert_eq!(table.expire(now + Duration::from_secs(15)), vec!["a".to_string()]);
+        let later = now + Duration::from_secs(20);
+        assert!(table.accept(beacon("a", 1, 15), intruder, from, later).is_some());
+
+        // 告别信标立即移除
+        match table.accept(beacon("a", 2, 0), intruder, from, later) {
+            Some(BeaconUpdate::Left(device_id)) => assert_eq!(device_id, "a"),
+            other => panic!("unexpected update: {:?}", other),
+        }
+        assert!(table.expire(later + Duration::from_secs(60)).is_empty());
+    }
+
+    #[test]
+    fn test_jitter_range() {
+        for _ in 0..100 {
+            let delay = jitter(DEFAULT_INTERVAL);
+            assert!(delay >= Duration::from_millis(2500) && delay < Duration::from_millis(7500));
+        }
+    }
+}
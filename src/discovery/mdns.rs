@@ -1,17 +1,23 @@
 //! mDNS 设备发现模块
 //!
 //! 提供局域网内设备的自动发现功能：
-//! - 被控端：广播服务
-//! - 控制端：发现服务
+//! - 被控端：广播服务 (A 和 AAAA 记录，覆盖所有网卡的 IPv4/IPv6 地址)，
+//!   TXT 记录携带设备名、操作系统、版本、显示器数量和配对指纹 ([`DeviceMetadata`])
+//! - 控制端：发现服务 (多个地址时按 `network::lan` 的规则挑选，纯 IPv6 网络也可用)，
+//!   [`MdnsDiscovery::start`] 返回设备上线、更新、下线的事件流 ([`DiscoveryEvents`])，
+//!   启用 [`MdnsDiscovery::enable_broadcast`] 时同时合并 UDP 广播信标发现的设备
 
 use anyhow::Result;
 use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
 use std::collections::HashMap;
 use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 /// mDNS 服务类型
 pub const SERVICE_TYPE: &str = "_sscontrol._tcp.local.";
@@ -19,12 +25,79 @@ pub const SERVICE_TYPE: &str = "_sscontrol._tcp.local.";
 /// 服务实例名称前缀
 pub const SERVICE_NAME_PREFIX: &str = "sscontrol";
 
+/// 被控端在 TXT 记录中公布的信息
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceMetadata {
+    /// 设备名 (默认为主机名)
+    pub name: String,
+    /// 操作系统 (`windows`、`macos`、`linux`)
+    pub os: String,
+    /// sscontrol 版本
+    pub version: String,
+    /// 显示器数量 (无法枚举时不公布)
+    pub screens: Option<u32>,
+    /// 设备配对指纹 (启用 pairing 时)
+    pub fingerprint: Option<String>,
+    /// 会话 ID (用于匹配连接码)
+    pub session_id: Option<String>,
+}
+
+impl DeviceMetadata {
+    /// 本机信息 (不含显示器数量和指纹)
+    pub fn local() -> Self {
+        Self {
+            name: local_hostname(),
+            os: std::env::consts::OS.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// TXT 记录
+    fn properties(&self, device_id: &str) -> Vec<(String, String)> {
+        let mut properties = vec![
+            ("device_id".to_string(), device_id.to_string()),
+            ("hostname".to_string(), local_hostname()),
+            ("name".to_string(), self.name.clone()),
+            ("os".to_string(), self.os.clone()),
+            ("version".to_string(), self.version.clone()),
+        ];
+        if let Some(screens) = self.screens {
+            properties.push(("screens".to_string(), screens.to_string()));
+        }
+        if let Some(ref fingerprint) = self.fingerprint {
+            properties.push(("fingerprint".to_string(), fingerprint.clone()));
+        }
+        if let Some(ref session_id) = self.session_id {
+            properties.push(("session_id".to_string(), session_id.clone()));
+        }
+        properties
+    }
+}
+
+pub(super) fn local_hostname() -> String {
+    hostname::get()
+        .map(|h| h.to_string_lossy().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// 设备的发现方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerSource {
+    Mdns,
+    /// UDP 广播信标 (网络过滤组播时)
+    Broadcast,
+}
+
 /// 发现的设备信息
 #[derive(Debug, Clone)]
 pub struct DiscoveredPeer {
     /// 设备 ID
     pub device_id: String,
 
+    /// mDNS 服务实例全名 (下线事件按此匹配，广播发现的设备为空)
+    pub fullname: String,
+
     /// IP 地址
     pub ip_address: IpAddr,
 
@@ -34,22 +107,93 @@ pub struct DiscoveredPeer {
     /// 主机名
     pub hostname: String,
 
+    /// 设备名 (旧版本被控端不公布时为主机名)
+    pub name: String,
+
+    /// 操作系统
+    pub os: Option<String>,
+
+    /// sscontrol 版本
+    pub version: Option<String>,
+
+    /// 显示器数量
+    pub screens: Option<u32>,
+
     /// 会话 ID (用于匹配连接码)
     pub session_id: Option<String>,
 
     /// 公钥指纹
     pub fingerprint: Option<String>,
 
+    /// 发现方式
+    pub source: PeerSource,
+
     /// 最后发现时间
     pub last_seen: Instant,
 }
 
+impl DiscoveredPeer {
+    /// 公布的内容是否相同 (不比较发现时间)
+    fn same_announcement(&self, other: &DiscoveredPeer) -> bool {
+        self.fullname == other.fullname
+            && self.ip_address == other.ip_address
+            && self.port == other.port
+            && self.hostname == other.hostname
+            && self.name == other.name
+            && self.os == other.os
+            && self.version == other.version
+            && self.screens == other.screens
+            && self.session_id == other.session_id
+            && self.fingerprint == other.fingerprint
+    }
+}
+
+/// 设备列表变化
+#[derive(Debug, Clone)]
+pub enum DiscoveryEvent {
+    /// 新设备上线
+    Added(DiscoveredPeer),
+    /// 已知设备的地址或 TXT 记录变化
+    Updated(DiscoveredPeer),
+    /// 设备下线 (注销服务或记录过期)
+    Removed(DiscoveredPeer),
+}
+
+impl DiscoveryEvent {
+    pub fn peer(&self) -> &DiscoveredPeer {
+        match self {
+            Self::Added(peer) | Self::Updated(peer) | Self::Removed(peer) => peer,
+        }
+    }
+}
+
+/// 设备列表变化的事件流 (也实现了 `futures::Stream`)
+pub struct DiscoveryEvents {
+    rx: mpsc::UnboundedReceiver<DiscoveryEvent>,
+}
+
+impl DiscoveryEvents {
+    /// 等待下一个事件 (停止发现后返回 None)
+    pub async fn next_event(&mut self) -> Option<DiscoveryEvent> {
+        self.rx.recv().await
+    }
+}
+
+impl futures::Stream for DiscoveryEvents {
+    type Item = DiscoveryEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
 /// mDNS 服务 (被控端使用)
 pub struct MdnsService {
     daemon: ServiceDaemon,
     service_fullname: Option<String>,
     device_id: String,
     port: u16,
+    metadata: DeviceMetadata,
 }
 
 impl MdnsService {
@@ -62,31 +206,15 @@ impl MdnsService {
             service_fullname: None,
             device_id: device_id.to_string(),
             port,
+            metadata: DeviceMetadata::local(),
         })
     }
 
     /// 注册服务 (开始广播)
-    pub fn register(&mut self, session_id: Option<&str>, fingerprint: Option<&str>) -> Result<()> {
-        let hostname = hostname::get()
-            .map(|h| h.to_string_lossy().to_string())
-            .unwrap_or_else(|_| "unknown".to_string());
-
-        let instance_name = format!("{}-{}", SERVICE_NAME_PREFIX, &self.device_id[..8]);
-
-        // 构建 TXT 记录
-        let mut properties = vec![
-            ("device_id".to_string(), self.device_id.clone()),
-            ("hostname".to_string(), hostname),
-            ("version".to_string(), env!("CARGO_PKG_VERSION").to_string()),
-        ];
-
-        if let Some(sid) = session_id {
-            properties.push(("session_id".to_string(), sid.to_string()));
-        }
-
-        if let Some(fp) = fingerprint {
-            properties.push(("fingerprint".to_string(), fp.to_string()));
-        }
+    pub fn register(&mut self, metadata: DeviceMetadata) -> Result<()> {
+        let short_id: String = self.device_id.chars().take(8).collect();
+        let instance_name = format!("{}-{}", SERVICE_NAME_PREFIX, short_id);
+        let properties = metadata.properties(&self.device_id);
 
         let service_info = ServiceInfo::new(
             SERVICE_TYPE,
@@ -95,21 +223,36 @@ impl MdnsService {
             "",
             self.port,
             properties.as_slice(),
-        )?;
+        )?
+        .enable_addr_auto();
 
         let fullname = service_info.get_fullname().to_string();
         self.daemon.register(service_info)?;
         self.service_fullname = Some(fullname.clone());
+        self.metadata = metadata;
 
         info!("mDNS service registered: {} on port {}", fullname, self.port);
         Ok(())
     }
 
+    /// 当前公布的信息
+    pub fn metadata(&self) -> &DeviceMetadata {
+        &self.metadata
+    }
+
+    /// 更新公布的信息 (如显示器数量变化)，内容未变时不重新注册
+    pub fn update(&mut self, metadata: DeviceMetadata) -> Result<()> {
+        if self.service_fullname.is_some() && metadata == self.metadata {
+            return Ok(());
+        }
+        // 同名实例重新注册会覆盖 TXT 记录，控制端收到更新事件
+        self.register(metadata)
+    }
+
     /// 更新 session_id (生成新连接码时调用)
     pub fn update_session_id(&mut self, session_id: &str) -> Result<()> {
-        // 先注销再重新注册
-        self.unregister()?;
-        self.register(Some(session_id), None)
+        let metadata = DeviceMetadata { session_id: Some(session_id.to_string()), ..self.metadata.clone() };
+        self.update(metadata)
     }
 
     /// 注销服务
@@ -134,6 +277,10 @@ pub struct MdnsDiscovery {
     daemon: ServiceDaemon,
     discovered: Arc<Mutex<HashMap<String, DiscoveredPeer>>>,
     is_browsing: bool,
+    /// 同时监听 UDP 广播信标的端口
+    broadcast_port: Option<u16>,
+    /// 通知广播接收线程退出
+    broadcast_stop: Arc<AtomicBool>,
 }
 
 impl MdnsDiscovery {
@@ -145,48 +292,104 @@ impl MdnsDiscovery {
             daemon,
             discovered: Arc::new(Mutex::new(HashMap::new())),
             is_browsing: false,
+            broadcast_port: None,
+            broadcast_stop: Arc::new(AtomicBool::new(false)),
         })
     }
 
-    /// 开始发现设备
-    pub fn start(&mut self) -> Result<mpsc::Receiver<DiscoveredPeer>> {
+    /// 同时监听该端口上的 UDP 广播信标 (在 [`Self::start`] 之前调用)
+    ///
+    /// 两种方式都发现同一设备时以 mDNS 的结果为准
+    pub fn enable_broadcast(&mut self, port: u16) {
+        self.broadcast_port = Some(port);
+    }
+
+    /// 开始发现设备，返回设备列表变化的事件流
+    ///
+    /// 已知设备重新解析且内容未变时只刷新发现时间，不产生事件
+    pub fn start(&mut self) -> Result<DiscoveryEvents> {
         let receiver = self.daemon.browse(SERVICE_TYPE)?;
         let discovered = self.discovered.clone();
 
-        let (tx, rx) = mpsc::channel(32);
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        if let Some(port) = self.broadcast_port {
+            let discovered = self.discovered.clone();
+            let tx = tx.clone();
+            let listening = super::broadcast::spawn_listener(port, self.broadcast_stop.clone(), move |update| {
+                let mut map = discovered.lock().unwrap();
+                let change = match update {
+                    super::broadcast::BeaconUpdate::Announced(peer) => Self::apply_beacon(&mut map, *peer),
+                    super::broadcast::BeaconUpdate::Left(device_id) => Self::apply_beacon_left(&mut map, &device_id),
+                };
+                if let Some(change) = change {
+                    let _ = tx.send(change);
+                }
+            });
+            // 广播端口被占用不影响 mDNS 发现
+            match listening {
+                Ok(()) => info!("Listening for broadcast beacons on UDP port {}", port),
+                Err(e) => warn!("无法监听广播端口 {}: {}", port, e),
+            }
+        }
 
-        // 启动后台任务处理发现事件
+        // 启动后台任务处理发现事件 (事件流被丢弃后仍维护设备列表)
         std::thread::spawn(move || {
             while let Ok(event) = receiver.recv() {
-                match event {
+                let change = match event {
                     ServiceEvent::ServiceResolved(info) => {
-                        let peer = Self::parse_service_info(&info);
-                        if let Some(peer) = peer {
-                            debug!("Discovered peer: {:?}", peer);
-
-                            // 更新已发现列表
-                            {
-                                let mut map = discovered.lock().unwrap();
-                                map.insert(peer.device_id.clone(), peer.clone());
-                            }
-
-                            // 通知
-                            let _ = tx.blocking_send(peer);
-                        }
+                        let Some(peer) = Self::parse_service_info(&info) else { continue };
+                        debug!("Discovered peer: {:?}", peer);
+                        Self::apply_resolved(&mut discovered.lock().unwrap(), peer)
                     }
                     ServiceEvent::ServiceRemoved(_type_name, fullname) => {
                         debug!("Service removed: {}", fullname);
-                        // 从列表中移除
-                        let mut map = discovered.lock().unwrap();
-                        map.retain(|_, p| !fullname.contains(&p.device_id));
+                        Self::apply_removed(&mut discovered.lock().unwrap(), &fullname)
                     }
-                    _ => {}
+                    _ => None,
+                };
+                if let Some(change) = change {
+                    let _ = tx.send(change);
                 }
             }
         });
 
         self.is_browsing = true;
-        Ok(rx)
+        Ok(DiscoveryEvents { rx })
+    }
+
+    /// 记录解析到的设备，返回对应的变化
+    fn apply_resolved(map: &mut HashMap<String, DiscoveredPeer>, peer: DiscoveredPeer) -> Option<DiscoveryEvent> {
+        let event = match map.get(&peer.device_id) {
+            None => Some(DiscoveryEvent::Added(peer.clone())),
+            Some(known) if !known.same_announcement(&peer) => Some(DiscoveryEvent::Updated(peer.clone())),
+            Some(_) => None,
+        };
+        map.insert(peer.device_id.clone(), peer);
+        event
+    }
+
+    /// 移除下线的设备
+    fn apply_removed(map: &mut HashMap<String, DiscoveredPeer>, fullname: &str) -> Option<DiscoveryEvent> {
+        let device_id = map.values().find(|peer| peer.fullname == fullname)?.device_id.clone();
+        map.remove(&device_id).map(DiscoveryEvent::Removed)
+    }
+
+    /// 记录广播信标发现的设备 (mDNS 已解析到的设备只刷新发现时间)
+    fn apply_beacon(map: &mut HashMap<String, DiscoveredPeer>, peer: DiscoveredPeer) -> Option<DiscoveryEvent> {
+        if let Some(known) = map.get_mut(&peer.device_id).filter(|known| known.source == PeerSource::Mdns) {
+            known.last_seen = peer.last_seen;
+            return None;
+        }
+        Self::apply_resolved(map, peer)
+    }
+
+    /// 移除广播信标下线的设备 (mDNS 发现的设备由 mDNS 管理)
+    fn apply_beacon_left(map: &mut HashMap<String, DiscoveredPeer>, device_id: &str) -> Option<DiscoveryEvent> {
+        if map.get(device_id)?.source != PeerSource::Broadcast {
+            return None;
+        }
+        map.remove(device_id).map(DiscoveryEvent::Removed)
     }
 
     /// 获取所有已发现的设备
@@ -214,32 +417,38 @@ impl MdnsDiscovery {
 
     /// 解析服务信息
     fn parse_service_info(info: &ServiceInfo) -> Option<DiscoveredPeer> {
-        let addresses: Vec<_> = info.get_addresses().iter().collect();
-        let ip_address = addresses.first().copied()?;
+        // 优先 IPv4 私有地址，其次 IPv6 ULA/全局地址；链路本地地址无法直接连接，仅作为最后选择
+        let ip_address = info
+            .get_addresses()
+            .iter()
+            .min_by_key(|ip| crate::network::lan::lan_preference(ip).unwrap_or(u8::MAX))?;
 
         let properties = info.get_properties();
+        let property = |key: &str| properties.get(key).map(|p| p.val_str().to_string()).filter(|v| !v.is_empty());
 
-        let device_id = properties.get("device_id")?.val_str().to_string();
-        let hostname = properties
-            .get("hostname")
-            .map(|p| p.val_str().to_string())
-            .unwrap_or_else(|| "unknown".to_string());
-        let session_id = properties.get("session_id").map(|p| p.val_str().to_string());
-        let fingerprint = properties.get("fingerprint").map(|p| p.val_str().to_string());
+        let device_id = property("device_id")?;
+        let hostname = property("hostname").unwrap_or_else(|| "unknown".to_string());
 
         Some(DiscoveredPeer {
             device_id,
+            fullname: info.get_fullname().to_string(),
             ip_address: *ip_address,
             port: info.get_port(),
+            name: property("name").unwrap_or_else(|| hostname.clone()),
             hostname,
-            session_id,
-            fingerprint,
+            os: property("os"),
+            version: property("version"),
+            screens: property("screens").and_then(|screens| screens.parse().ok()),
+            session_id: property("session_id"),
+            fingerprint: property("fingerprint"),
+            source: PeerSource::Mdns,
             last_seen: Instant::now(),
         })
     }
 
     /// 停止发现
     pub fn stop(&mut self) -> Result<()> {
+        self.broadcast_stop.store(true, Ordering::Relaxed);
         if self.is_browsing {
             self.daemon.stop_browse(SERVICE_TYPE)?;
             self.is_browsing = false;
@@ -259,9 +468,78 @@ impl Drop for MdnsDiscovery {
 mod tests {
     use super::*;
 
+    fn peer(device_id: &str, ip: &str) -> DiscoveredPeer {
+        DiscoveredPeer {
+            device_id: device_id.to_string(),
+            fullname: format!("sscontrol-{}.{}", device_id, SERVICE_TYPE),
+            ip_address: ip.parse().unwrap(),
+            port: 9527,
+            hostname: "office-pc".to_string(),
+            name: "office-pc".to_string(),
+            os: Some("windows".to_string()),
+            version: Some("0.1.0".to_string()),
+            screens: Some(2),
+            session_id: None,
+            fingerprint: None,
+            source: PeerSource::Mdns,
+            last_seen: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn test_discovery_events() {
+        let mut map = HashMap::new();
+        assert!(matches!(
+            MdnsDiscovery::apply_resolved(&mut map, peer("a", "192.168.1.5")),
+            Some(DiscoveryEvent::Added(_))
+        ));
+        // 内容未变只刷新发现时间
+        assert!(MdnsDiscovery::apply_resolved(&mut map, peer("a", "192.168.1.5")).is_none());
+        match MdnsDiscovery::apply_resolved(&mut map, peer("a", "192.168.1.9")) {
+            Some(DiscoveryEvent::Updated(updated)) => assert_eq!(updated.ip_address.to_string(), "192.168.1.9"),
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        let fullname = peer("a", "192.168.1.9").fullname;
+        assert!(matches!(MdnsDiscovery::apply_removed(&mut map, &fullname), Some(DiscoveryEvent::Removed(_))));
+        assert!(MdnsDiscovery::apply_removed(&mut map, &fullname).is_none());
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_broadcast_merge() {
+        let mut map = HashMap::new();
+        let beacon = DiscoveredPeer { fullname: String::new(), source: PeerSource::Broadcast, ..peer("a", "10.0.0.5") };
+        assert!(matches!(MdnsDiscovery::apply_beacon(&mut map, beacon.clone()), Some(DiscoveryEvent::Added(_))));
+
+        // mDNS 解析到同一设备后以 mDNS 为准，广播信标不再改变设备信息
+        assert!(matches!(
+            MdnsDiscovery::apply_resolved(&mut map, peer("a", "192.168.1.5")),
+            Some(DiscoveryEvent::Updated(_))
+        ));
+        assert!(MdnsDiscovery::apply_beacon(&mut map, beacon).is_none());
+        assert!(MdnsDiscovery::apply_beacon_left(&mut map, "a").is_none());
+        assert_eq!(map["a"].ip_address.to_string(), "192.168.1.5");
+    }
+
     #[test]
     fn test_service_type() {
         assert!(SERVICE_TYPE.ends_with(".local."));
         assert!(SERVICE_TYPE.starts_with("_"));
     }
+
+    #[test]
+    fn test_metadata_properties() {
+        let metadata = DeviceMetadata {
+            screens: Some(3),
+            fingerprint: Some("3F2A-91C0-7B44-E215".to_string()),
+            ..DeviceMetadata::local()
+        };
+        let properties = metadata.properties("device-1");
+        let get = |key: &str| properties.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        assert_eq!(get("device_id"), Some("device-1"));
+        assert_eq!(get("os"), Some(std::env::consts::OS));
+        assert_eq!(get("screens"), Some("3"));
+        assert_eq!(get("session_id"), None);
+    }
 }
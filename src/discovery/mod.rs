@@ -3,12 +3,15 @@
 //! 提供以下功能：
 //! - 连接码生成和解析
 //! - mDNS 局域网设备发现
+//! - UDP 广播发现 (过滤组播的网络中代替 mDNS)
 //! - 公共信令服务客户端
 
 // 设备发现模块尚未完全激活，标记为允许死代码
 #![allow(dead_code)]
 
-mod connection_code;
-mod mdns;
+pub mod broadcast;
+pub mod connection_code;
+pub mod mdns;
 
-pub use connection_code::ConnectionCode;
+pub use broadcast::{BeaconSigner, BroadcastAnnouncer};
+pub use mdns::{DeviceMetadata, DiscoveredPeer, DiscoveryEvent, MdnsDiscovery, MdnsService, PeerSource};
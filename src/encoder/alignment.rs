@@ -0,0 +1,286 @@
+//! 编码分辨率对齐
+//!
+//! YUV420 编码器要求宽高为偶数 (部分硬件编码器要求 16 对齐)，
+//! 而缩放后的屏幕可能出现奇数尺寸 (如 1366x769)。
+//! 本模块在编码前将帧裁剪或填充到对齐尺寸，并提供输入坐标的反向映射。
+//!
+//! ## 坐标映射
+//! 控制端发送的是相对于视频画面的归一化坐标 (0.0-1.0)，
+//! 视频画面尺寸为对齐后的尺寸，因此注入前需要换算回原始屏幕的归一化坐标。
+
+use crate::capture::Frame;
+use crate::input::InputEvent;
+use anyhow::{bail, Result};
+
+/// 对齐粒度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignMode {
+    /// 偶数对齐 (YUV420 最低要求)
+    Even,
+    /// 16 像素对齐 (宏块对齐，部分硬件编码器需要)
+    Macroblock,
+}
+
+impl AlignMode {
+    /// 对齐粒度 (像素)
+    pub fn granularity(&self) -> u32 {
+        match self {
+            Self::Even => 2,
+            Self::Macroblock => 16,
+        }
+    }
+}
+
+/// 对齐策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignStrategy {
+    /// 向下裁剪 (丢弃右侧/底部多余像素)
+    Crop,
+    /// 向上填充 (复制边缘像素)
+    Pad,
+}
+
+/// 帧对齐器
+///
+/// 根据原始屏幕尺寸计算对齐后的编码尺寸，并负责帧数据和输入坐标的转换
+#[derive(Debug, Clone)]
+pub struct FrameAligner {
+    src_width: u32,
+    src_height: u32,
+    dst_width: u32,
+    dst_height: u32,
+    strategy: AlignStrategy,
+}
+
+impl FrameAligner {
+    /// 创建帧对齐器
+    ///
+    /// # 参数
+    /// * `width` - 原始屏幕宽度
+    /// * `height` - 原始屏幕高度
+    /// * `mode` - 对齐粒度
+    /// * `strategy` - 裁剪或填充
+    ///
+    /// 宽或高为 0 时返回错误
+    pub fn new(width: u32, height: u32, mode: AlignMode, strategy: AlignStrategy) -> Result<Self> {
+        if width == 0 || height == 0 {
+            bail!("无效的屏幕尺寸: {}x{}", width, height);
+        }
+
+        let dst_width = align_dimension(width, mode.granularity(), strategy);
+        let dst_height = align_dimension(height, mode.granularity(), strategy);
+
+        if dst_width != width || dst_height != height {
+            tracing::info!(
+                "编码分辨率对齐: {}x{} -> {}x{} ({:?})",
+                width, height, dst_width, dst_height, strategy
+            );
+        }
+
+        Ok(Self {
+            src_width: width,
+            src_height: height,
+            dst_width,
+            dst_height,
+            strategy,
+        })
+    }
+
+    /// 默认对齐器: 偶数对齐 + 裁剪 (最多丢弃 1 像素)
+    pub fn even(width: u32, height: u32) -> Result<Self> {
+        Self::new(width, height, AlignMode::Even, AlignStrategy::Crop)
+    }
+
+    /// 对齐后的编码宽度
+    pub fn width(&self) -> u32 {
+        self.dst_width
+    }
+
+    /// 对齐后的编码高度
+    pub fn height(&self) -> u32 {
+        self.dst_height
+    }
+
+    /// 是否需要转换 (原始尺寸已对齐时为 false)
+    pub fn is_passthrough(&self) -> bool {
+        self.src_width == self.dst_width && self.src_height == self.dst_height
+    }
+
+    /// 将捕获帧转换为对齐尺寸
+    ///
+    /// 若帧尺寸与创建时的屏幕尺寸不一致 (例如分辨率发生变化) 或为 GPU 帧，原样返回
+    pub fn align(&self, frame: Frame) -> Frame {
+        if self.is_passthrough()
+            || frame.is_gpu()
+            || frame.width != self.src_width
+            || frame.height != self.src_height
+        {
+            return frame;
+        }
+
+        let dst_stride = self.dst_width as usize * 4;
+        let mut data = vec![0u8; dst_stride * self.dst_height as usize];
+        let copy_width = self.src_width.min(self.dst_width) as usize * 4;
+
+        for y in 0..self.dst_height as usize {
+            // 填充模式下超出原始高度的行复制最后一行
+            let src_y = y.min(self.src_height as usize - 1);
+            let src_row = &frame.data[src_y * frame.stride..src_y * frame.stride + copy_width];
+            let dst_row = &mut data[y * dst_stride..(y + 1) * dst_stride];
+            dst_row[..copy_width].copy_from_slice(src_row);
+
+            // 填充模式下超出原始宽度的列复制最后一个像素
+            if copy_width < dst_stride {
+                let last_pixel: [u8; 4] = [
+                    src_row[copy_width - 4],
+                    src_row[copy_width - 3],
+                    src_row[copy_width - 2],
+                    src_row[copy_width - 1],
+                ];
+                for pixel in dst_row[copy_width..].chunks_exact_mut(4) {
+                    pixel.copy_from_slice(&last_pixel);
+                }
+            }
+        }
+
+        Frame {
+            width: self.dst_width,
+            height: self.dst_height,
+            data,
+            timestamp: frame.timestamp,
+            stride: dst_stride,
+            gpu: None,
+        }
+    }
+
+    /// 将视频画面中的归一化坐标映射为原始屏幕的归一化坐标
+    pub fn map_normalized(&self, x: f64, y: f64) -> (f64, f64) {
+        if self.is_passthrough() {
+            return (x, y);
+        }
+
+        let sx = x * self.dst_width as f64 / self.src_width as f64;
+        let sy = y * self.dst_height as f64 / self.src_height as f64;
+
+        (sx.clamp(0.0, 1.0), sy.clamp(0.0, 1.0))
+    }
+
+    /// 转换输入事件坐标 (仅影响鼠标移动和触摸事件)
+    pub fn map_input_event(&self, event: InputEvent) -> InputEvent {
+        match event {
+            InputEvent::MouseMove { x, y } => {
+                let (x, y) = self.map_normalized(x, y);
+                InputEvent::MouseMove { x, y }
+            }
+            InputEvent::Touch { id, phase, x, y } => {
+                let (x, y) = self.map_normalized(x, y);
+                InputEvent::Touch { id, phase, x, y }
+            }
+            other => other,
+        }
+    }
+
+    /// 对齐策略
+    pub fn strategy(&self) -> AlignStrategy {
+        self.strategy
+    }
+}
+
+/// 将单个维度对齐到指定粒度
+fn align_dimension(value: u32, granularity: u32, strategy: AlignStrategy) -> u32 {
+    match strategy {
+        AlignStrategy::Crop => (value / granularity * granularity).max(granularity),
+        AlignStrategy::Pad => value.div_ceil(granularity) * granularity,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_frame(width: u32, height: u32) -> Frame {
+        let mut frame = Frame::new(width, height);
+        for (i, byte) in frame.data.iter_mut().enumerate() {
+            *byte = (i % 251) as u8;
+        }
+        frame
+    }
+
+    #[test]
+    fn test_even_crop_dimensions() {
+        let aligner = FrameAligner::even(1366, 769).unwrap();
+        assert_eq!(aligner.width(), 1366);
+        assert_eq!(aligner.height(), 768);
+        assert!(!aligner.is_passthrough());
+
+        let aligned = aligner.align(test_frame(1366, 769));
+        assert_eq!(aligned.width, 1366);
+        assert_eq!(aligned.height, 768);
+        assert_eq!(aligned.data.len(), 1366 * 768 * 4);
+        assert_eq!(aligned.stride, 1366 * 4);
+    }
+
+    #[test]
+    fn test_macroblock_pad_dimensions() {
+        let aligner = FrameAligner::new(1366, 769, AlignMode::Macroblock, AlignStrategy::Pad).unwrap();
+        assert_eq!(aligner.width(), 1376);
+        assert_eq!(aligner.height(), 784);
+
+        let src = test_frame(1366, 769);
+        let aligned = aligner.align(src.clone());
+        assert_eq!(aligned.data.len(), 1376 * 784 * 4);
+
+        // 填充列复制行末像素
+        let last = (1366 - 1) * 4;
+        assert_eq!(&aligned.data[1366 * 4..1366 * 4 + 4], &src.data[last..last + 4]);
+    }
+
+    #[test]
+    fn test_passthrough() {
+        let aligner = FrameAligner::even(1920, 1080).unwrap();
+        assert!(aligner.is_passthrough());
+        assert_eq!(aligner.map_normalized(0.25, 0.75), (0.25, 0.75));
+    }
+
+    #[test]
+    fn test_rejects_zero_dimensions() {
+        assert!(FrameAligner::even(0, 1080).is_err());
+        assert!(FrameAligner::even(1920, 0).is_err());
+        assert!(FrameAligner::new(0, 0, AlignMode::Macroblock, AlignStrategy::Pad).is_err());
+
+        // 1 像素宽的屏幕裁剪后至少保留一个对齐粒度
+        let aligner = FrameAligner::even(1, 1).unwrap();
+        assert_eq!((aligner.width(), aligner.height()), (2, 2));
+        assert_eq!(aligner.align(test_frame(1, 1)).data.len(), 2 * 2 * 4);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_gpu_frame_is_not_aligned() {
+        // 需要对齐的尺寸不会启用零拷贝，误传入的 GPU 帧原样交给编码器
+        let aligner = FrameAligner::even(1366, 769).unwrap();
+        let frame = Frame::from_gpu(crate::capture::GpuFrame { width: 1366, height: 769 });
+        let aligned = aligner.align(frame);
+        assert!(aligned.is_gpu());
+        assert_eq!((aligned.width, aligned.height), (1366, 769));
+    }
+
+    #[test]
+    fn test_coordinate_mapping() {
+        let aligner = FrameAligner::new(1366, 769, AlignMode::Macroblock, AlignStrategy::Pad).unwrap();
+
+        // 视频画面右下角落在填充区域，应被限制到屏幕边缘
+        let (x, y) = aligner.map_normalized(1.0, 1.0);
+        assert_eq!((x, y), (1.0, 1.0));
+
+        // 视频中心对应的屏幕位置略偏右下
+        let (x, y) = aligner.map_normalized(0.5, 0.5);
+        assert!((x - 688.0 / 1366.0).abs() < 1e-9);
+        assert!((y - 392.0 / 769.0).abs() < 1e-9);
+
+        match aligner.map_input_event(InputEvent::mouse_move(0.5, 0.5)) {
+            InputEvent::MouseMove { x: mx, .. } => assert!((mx - x).abs() < 1e-9),
+            _ => panic!("unexpected event"),
+        }
+    }
+}
@@ -17,14 +17,44 @@
 //! - AMD GPU with VCE/VCN support
 //! - AMD Graphics Driver (Adrenalin 2020或更新)
 //! - FFmpeg with h264_amf codec
+//!
+//! ## 输入
+//! - CPU 帧: RGBA → NV12 (SIMD) 后由 AMF 上传
+//! - GPU 帧: DXGI 捕获的 BGRA 纹理通过 D3D11VA 硬件帧直接送入 AMF (零拷贝，见 [`crate::encoder::d3d11`])
 
+#[cfg(target_os = "windows")]
+use crate::capture::GpuFrame;
 #[cfg(target_os = "windows")]
 use crate::encoder::{EncodedPacket, Frame};
 #[cfg(target_os = "windows")]
-use crate::encoder::hardware::{HardwareEncoder, HardwareEncoderConfig, HardwareEncoderType};
+use crate::encoder::hardware::{EncoderPreset, HardwareEncoder, HardwareEncoderConfig, HardwareEncoderType};
 #[cfg(target_os = "windows")]
 use anyhow::{anyhow, Result};
 
+/// 关键帧间隔 (帧)
+#[cfg(target_os = "windows")]
+const KEY_FRAME_INTERVAL: u64 = 30;
+
+/// h264_amf 的低延迟选项 (CPU 帧和 D3D11 纹理两条路径共用)
+///
+/// 每个 IDR 前都带 SPS/PPS，中途加入的 Viewer 收到关键帧即可解码
+#[cfg(target_os = "windows")]
+fn low_latency_options(preset: EncoderPreset) -> Vec<(&'static str, &'static str)> {
+    let (usage, quality) = match preset {
+        EncoderPreset::UltraLowLatency => ("ultralowlatency", "speed"),
+        EncoderPreset::LowLatency => ("lowlatency", "speed"),
+        EncoderPreset::Balanced => ("lowlatency", "balanced"),
+        EncoderPreset::Quality => ("lowlatency", "quality"),
+    };
+    vec![
+        ("usage", usage),
+        ("quality", quality),
+        ("rc", "cbr"),
+        ("header_insertion_mode", "idr"),
+        ("forced_idr", "1"),
+    ]
+}
+
 /// AMD AMF 编码器
 ///
 /// 使用 AMD AMF (Advanced Media Framework) 进行 H.264 硬件编码
@@ -36,10 +66,16 @@ pub struct AmfEncoder {
     #[cfg(feature = "h264")]
     inner: Option<ffmpeg_next::encoder::Video>,
     #[cfg(feature = "h264")]
-    sws_context: Option<ffmpeg_next::software::scaling::Context>,
+    yuv_frame: crate::encoder::colorspace::ReusableVideoFrame,
     pts: i64,
     key_frame_interval: u64,
     frame_count: u64,
+    /// 感兴趣区域 (随每帧附加给编码器)
+    #[cfg(feature = "h264")]
+    roi: Vec<crate::quality::roi_encoder::RoiRegion>,
+    /// D3D11 纹理输入编码上下文 (零拷贝路径，首次收到 GPU 帧时创建)
+    #[cfg(feature = "h264")]
+    texture_encoder: Option<crate::encoder::d3d11::D3D11TextureEncoder>,
 }
 
 #[cfg(target_os = "windows")]
@@ -67,43 +103,44 @@ impl AmfEncoder {
             let mut encoder_context = context.encoder().video()?;
 
             encoder_context.set_bit_rate((config.bitrate * 1000) as usize);
+            encoder_context.set_max_bit_rate((config.bitrate * 1000) as usize);
             encoder_context.set_width(width);
             encoder_context.set_height(height);
             encoder_context.set_frame_rate(Some(ffmpeg_next::Rational(config.fps as i32, 1)));
             encoder_context.set_time_base(ffmpeg_next::Rational(1, config.fps as i32));
-            encoder_context.set_gop(30);
+            encoder_context.set_gop(KEY_FRAME_INTERVAL as u32);
+            encoder_context.set_max_b_frames(0);
             encoder_context.set_format(ffmpeg_next::format::Pixel::NV12);
 
             // AMF 特定选项
             let mut opts = ffmpeg_next::Dictionary::new();
-            opts.set("quality", "speed");  // 优先速度
-            opts.set("rc", "cbr");         // 恒定码率
-            opts.set("b_max", "0");        // 禁用 B 帧
+            for (key, value) in low_latency_options(config.preset) {
+                opts.set(key, value);
+            }
 
             // 打开编码器
             let video_encoder = encoder_context.open_with(opts)?;
 
-            // 创建 SwsContext 用于 RGBA -> NV12 转换
-            let sws_context = ffmpeg_next::software::scaling::Context::get(
-                ffmpeg_next::format::Pixel::RGBA,
-                width,
-                height,
+            // 复用的 NV12 帧 (SIMD 颜色转换)
+            let yuv_frame = crate::encoder::colorspace::ReusableVideoFrame::new(
                 ffmpeg_next::format::Pixel::NV12,
                 width,
                 height,
-                ffmpeg_next::software::scaling::Flags::BILINEAR,
-            )?;
+                crate::encoder::colorspace::PixelLayout::Rgba,
+            );
 
-            tracing::info!("AMF 编码器创建成功 (speed 预设)");
+            tracing::info!("AMF 编码器创建成功 ({:?} 预设)", config.preset);
             Ok(Self {
                 width,
                 height,
                 config,
                 inner: Some(video_encoder),
-                sws_context: Some(sws_context),
+                yuv_frame,
                 pts: 0,
-                key_frame_interval: 30,
+                key_frame_interval: KEY_FRAME_INTERVAL,
                 frame_count: 0,
+                roi: Vec::new(),
+                texture_encoder: None,
             })
         }
 
@@ -114,14 +151,18 @@ impl AmfEncoder {
     }
 
     /// 检测 AMF 是否可用
+    ///
+    /// 不打开编码会话: FFmpeg 编译了 h264_amf 且能加载 AMD 驱动附带的 AMF 运行时即视为可用
     pub fn is_available() -> bool {
         #[cfg(feature = "h264")]
         {
-            if let Ok(_) = ffmpeg_next::init() {
-                if let Some(encoder) = ffmpeg_next::encoder::find_by_name("h264_amf") {
-                    tracing::info!("AMF 编码器可用: {}", encoder.name());
+            if ffmpeg_next::init().is_ok() && ffmpeg_next::encoder::find_by_name("h264_amf").is_some() {
+                if amf_runtime_present() {
+                    tracing::info!("AMF 编码器可用");
                     return true;
                 }
+                tracing::warn!("FFmpeg 支持 h264_amf，但未找到 AMF 运行时 (amfrt64.dll)");
+                return false;
             }
             tracing::warn!("AMF 编码器不可用");
             false
@@ -132,49 +173,6 @@ impl AmfEncoder {
         }
     }
 
-    /// 将 RGBA 帧转换为 NV12 格式
-    #[cfg(feature = "h264")]
-    fn rgba_to_nv12_frame(&mut self, rgba: &[u8], width: u32, height: u32) -> Result<ffmpeg_next::frame::Video> {
-        // 创建源帧 (RGBA)
-        let mut src_frame = ffmpeg_next::frame::Video::empty();
-        src_frame.set_format(ffmpeg_next::format::Pixel::RGBA);
-        src_frame.set_width(width);
-        src_frame.set_height(height);
-
-        unsafe {
-            src_frame.alloc(ffmpeg_next::format::Pixel::RGBA, width, height);
-        }
-
-        // 复制 RGBA 数据到源帧
-        let src_stride = src_frame.stride(0);
-        let src_data = src_frame.data_mut(0);
-        for y in 0..height as usize {
-            let src_row_start = y * (width as usize * 4);
-            let dst_row_start = y * src_stride;
-            let row_len = width as usize * 4;
-            src_data[dst_row_start..dst_row_start + row_len]
-                .copy_from_slice(&rgba[src_row_start..src_row_start + row_len]);
-        }
-
-        // 创建目标帧 (NV12)
-        let mut dst_frame = ffmpeg_next::frame::Video::empty();
-        dst_frame.set_format(ffmpeg_next::format::Pixel::NV12);
-        dst_frame.set_width(width);
-        dst_frame.set_height(height);
-
-        unsafe {
-            dst_frame.alloc(ffmpeg_next::format::Pixel::NV12, width, height);
-        }
-
-        // 使用 SwsContext 进行转换
-        if let Some(ref mut sws) = self.sws_context {
-            sws.run(&src_frame, &mut dst_frame)?;
-        } else {
-            return Err(anyhow!("SwsContext 未初始化"));
-        }
-
-        Ok(dst_frame)
-    }
 }
 
 #[cfg(target_os = "windows")]
@@ -182,19 +180,21 @@ impl HardwareEncoder for AmfEncoder {
     #[cfg(feature = "h264")]
     fn encode(&mut self, frame: &Frame) -> Result<Option<EncodedPacket>> {
         // 转换为 NV12
-        let mut nv12_frame = self.rgba_to_nv12_frame(&frame.data, frame.width, frame.height)?;
+        let nv12_frame = self.yuv_frame.convert(frame)?;
 
         // 设置 PTS
         nv12_frame.set_pts(Some(self.pts));
         self.pts += 1;
         self.frame_count += 1;
 
-        // 判断是否为关键帧
-        let is_key_frame = self.frame_count % self.key_frame_interval == 0;
+        // 到达间隔或请求了关键帧时强制 IDR
+        let force_key_frame = self.frame_count % self.key_frame_interval == 0;
+        crate::encoder::mark_key_frame(nv12_frame, force_key_frame);
+        crate::encoder::attach_roi(nv12_frame, &self.roi);
 
         // 编码
         let encoder = self.inner.as_mut().ok_or_else(|| anyhow!("编码器未初始化"))?;
-        encoder.send_frame(&nv12_frame)?;
+        encoder.send_frame(nv12_frame)?;
 
         let mut packet = ffmpeg_next::packet::Packet::empty();
         match encoder.receive_packet(&mut packet) {
@@ -203,7 +203,7 @@ impl HardwareEncoder for AmfEncoder {
                     let data = packet.data().unwrap_or(&[]).to_vec();
                     Ok(Some(EncodedPacket {
                         data,
-                        is_key_frame,
+                        is_key_frame: packet.is_key(),
                         timestamp: frame.timestamp,
                         pts: self.pts,
                     }))
@@ -272,6 +272,49 @@ impl HardwareEncoder for AmfEncoder {
         HardwareEncoderType::AMF
     }
 
+    fn supports_texture_input(&self) -> bool {
+        cfg!(feature = "h264")
+    }
+
+    #[cfg(feature = "h264")]
+    fn encode_texture(&mut self, frame: &GpuFrame) -> Result<Option<EncodedPacket>> {
+        if frame.width != self.width || frame.height != self.height {
+            return Err(anyhow!(
+                "GPU 帧尺寸 {}x{} 与编码器 {}x{} 不一致",
+                frame.width, frame.height, self.width, self.height
+            ));
+        }
+
+        // 首次使用 (或按新码率重建后) 基于捕获器的 D3D11 设备创建纹理编码器
+        if self.texture_encoder.is_none() {
+            self.texture_encoder = Some(crate::encoder::d3d11::D3D11TextureEncoder::new(
+                &frame.device,
+                self.width,
+                self.height,
+                &self.config,
+                "h264_amf",
+                KEY_FRAME_INTERVAL as u32,
+                &low_latency_options(self.config.preset),
+            )?);
+        }
+
+        self.pts += 1;
+        self.frame_count += 1;
+        let force_key_frame = self.frame_count % self.key_frame_interval == 0;
+
+        let encoder = self.texture_encoder.as_mut()
+            .ok_or_else(|| anyhow!("纹理编码器未初始化"))?;
+
+        Ok(encoder
+            .encode(&frame.texture, self.pts, force_key_frame)?
+            .map(|(data, is_key_frame)| EncodedPacket {
+                data,
+                is_key_frame,
+                timestamp: crate::capture::Frame::current_timestamp(),
+                pts: self.pts,
+            }))
+    }
+
     fn is_available(&self) -> bool {
         #[cfg(feature = "h264")]
         {
@@ -282,6 +325,53 @@ impl HardwareEncoder for AmfEncoder {
             false
         }
     }
+
+    #[cfg(feature = "h264")]
+    fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()> {
+        if bitrate_kbps == self.config.bitrate {
+            return Ok(());
+        }
+        let Some(encoder) = self.inner.as_mut() else {
+            return Ok(());
+        };
+
+        if !crate::encoder::apply_bitrate(encoder, bitrate_kbps) {
+            // h264_amf 不支持运行时重配置，按新码率重建编码器 (PTS 连续)；
+            // 纹理编码器随之释放，下一个 GPU 帧按新码率重新创建
+            let mut config = self.config.clone();
+            config.bitrate = bitrate_kbps;
+            let pts = self.pts;
+            self.inner = None;
+            *self = Self::new(self.width, self.height, config)?;
+            self.pts = pts;
+        }
+        self.config.bitrate = bitrate_kbps;
+        self.request_key_frame()
+    }
+
+    #[cfg(feature = "h264")]
+    fn set_roi(&mut self, regions: &[crate::quality::roi_encoder::RoiRegion]) -> bool {
+        self.roi.clear();
+        self.roi.extend_from_slice(regions);
+        self.inner.as_ref().is_some_and(crate::encoder::encoder_supports_roi)
+    }
+}
+
+/// AMD 驱动安装的 AMF 运行时能否加载
+#[cfg(all(target_os = "windows", feature = "h264"))]
+fn amf_runtime_present() -> bool {
+    use windows::Win32::Foundation::FreeLibrary;
+    use windows::Win32::System::LibraryLoader::LoadLibraryW;
+
+    match unsafe { LoadLibraryW(windows::core::w!("amfrt64.dll")) } {
+        Ok(module) => {
+            unsafe {
+                let _ = FreeLibrary(module);
+            }
+            true
+        }
+        Err(_) => false,
+    }
 }
 
 #[cfg(not(target_os = "windows"))]
@@ -307,6 +397,15 @@ mod tests {
         // 结果取决于硬件
     }
 
+    #[test]
+    #[cfg(target_os = "windows")]
+    fn test_low_latency_options() {
+        let options = low_latency_options(EncoderPreset::UltraLowLatency);
+        assert!(options.contains(&("usage", "ultralowlatency")));
+        assert!(options.contains(&("header_insertion_mode", "idr")));
+        assert!(low_latency_options(EncoderPreset::Balanced).contains(&("quality", "balanced")));
+    }
+
     #[test]
     fn test_config_validation() {
         #[cfg(all(target_os = "windows", feature = "h264"))]
@@ -0,0 +1,359 @@
+//! H.264 码流工具
+//!
+//! VideoToolbox 输出 AVCC 格式 (每个 NAL 前是大端长度，SPS/PPS 在格式描述里)，
+//! WebRTC、`/video` 和录制都使用 Annex B (起始码分隔，关键帧前带 SPS/PPS)。
+//! 这里提供两者的转换、NAL 拆分和 SPS 解析 (用于校验编码器输出)
+
+use anyhow::{anyhow, Result};
+
+/// Annex B 起始码
+pub const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// NAL 单元类型 (ITU-T H.264 表 7-1，只列出用到的)
+pub mod nal_type {
+    /// 非 IDR 片
+    pub const SLICE: u8 = 1;
+    /// IDR 片
+    pub const IDR: u8 = 5;
+    /// 补充增强信息
+    pub const SEI: u8 = 6;
+    /// 序列参数集
+    pub const SPS: u8 = 7;
+    /// 图像参数集
+    pub const PPS: u8 = 8;
+    /// 访问单元分隔符
+    pub const AUD: u8 = 9;
+}
+
+/// NAL 单元类型 (头字节低 5 位)
+pub fn nal_unit_type(nal: &[u8]) -> Option<u8> {
+    nal.first().map(|header| header & 0x1f)
+}
+
+/// 把 AVCC 格式的 NAL 单元追加为 Annex B
+///
+/// `length_size` 为长度前缀字节数 (VideoToolbox 为 4)
+pub fn avcc_to_annex_b(data: &[u8], length_size: usize, out: &mut Vec<u8>) -> Result<()> {
+    if !(1..=4).contains(&length_size) {
+        return Err(anyhow!("无效的 NAL 长度前缀: {} 字节", length_size));
+    }
+
+    let mut offset = 0;
+    while offset < data.len() {
+        let header = data
+            .get(offset..offset + length_size)
+            .ok_or_else(|| anyhow!("AVCC 数据在偏移 {} 处截断", offset))?;
+        let length = header.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+        offset += length_size;
+
+        let nal = data
+            .get(offset..offset + length)
+            .ok_or_else(|| anyhow!("NAL 长度 {} 超出数据范围 (偏移 {})", length, offset))?;
+        out.extend_from_slice(&START_CODE);
+        out.extend_from_slice(nal);
+        offset += length;
+    }
+    Ok(())
+}
+
+/// 追加参数集 (SPS/PPS) 为 Annex B
+pub fn write_parameter_sets<'a>(sets: impl IntoIterator<Item = &'a [u8]>, out: &mut Vec<u8>) {
+    for set in sets {
+        out.extend_from_slice(&START_CODE);
+        out.extend_from_slice(set);
+    }
+}
+
+/// 按起始码 (3 或 4 字节) 拆分 Annex B 码流
+pub fn split_annex_b(data: &[u8]) -> Vec<&[u8]> {
+    let mut starts = Vec::new();
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i] == 0 && data[i + 1] == 0 && data[i + 2] == 1 {
+            starts.push(i + 3);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(n, &start)| {
+            let end = starts.get(n + 1).map_or(data.len(), |&next| next - 3);
+            // 4 字节起始码的前导 0 属于下一个起始码
+            let nal = &data[start..end];
+            let trailing = nal.iter().rev().take_while(|&&b| b == 0).count();
+            &nal[..nal.len() - trailing]
+        })
+        .filter(|nal| !nal.is_empty())
+        .collect()
+}
+
+/// 去掉防竞争字节 (00 00 03 → 00 00)，得到 RBSP
+pub fn unescape_rbsp(nal: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(nal.len());
+    let mut zeros = 0;
+    for &byte in nal {
+        if zeros >= 2 && byte == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        rbsp.push(byte);
+    }
+    rbsp
+}
+
+/// SPS 中与解码器配置相关的字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpsInfo {
+    pub profile_idc: u8,
+    pub constraint_flags: u8,
+    pub level_idc: u8,
+    pub seq_parameter_set_id: u32,
+    /// 裁剪后的宽度
+    pub width: u32,
+    /// 裁剪后的高度
+    pub height: u32,
+}
+
+impl SpsInfo {
+    /// WebCodecs / MSE 使用的 codec 字符串 (如 `avc1.4d401f`)
+    pub fn codec_string(&self) -> String {
+        format!("avc1.{:02x}{:02x}{:02x}", self.profile_idc, self.constraint_flags, self.level_idc)
+    }
+}
+
+/// 解析 SPS NAL 单元 (含 NAL 头)
+pub fn parse_sps(nal: &[u8]) -> Result<SpsInfo> {
+    if nal_unit_type(nal) != Some(nal_type::SPS) {
+        return Err(anyhow!("不是 SPS NAL 单元"));
+    }
+    let rbsp = unescape_rbsp(&nal[1..]);
+    let mut bits = BitReader::new(&rbsp);
+
+    let profile_idc = bits.read_bits(8)? as u8;
+    let constraint_flags = bits.read_bits(8)? as u8;
+    let level_idc = bits.read_bits(8)? as u8;
+    let seq_parameter_set_id = bits.read_ue()?;
+
+    let mut chroma_format_idc = 1;
+    let mut separate_colour_plane = false;
+    if matches!(profile_idc, 100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135) {
+        chroma_format_idc = bits.read_ue()?;
+        if chroma_format_idc == 3 {
+            separate_colour_plane = bits.read_bit()?;
+        }
+        bits.read_ue()?; // bit_depth_luma_minus8
+        bits.read_ue()?; // bit_depth_chroma_minus8
+        bits.read_bit()?; // qpprime_y_zero_transform_bypass_flag
+        if bits.read_bit()? {
+            // seq_scaling_matrix_present_flag
+            let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+            for i in 0..lists {
+                if bits.read_bit()? {
+                    skip_scaling_list(&mut bits, if i < 6 { 16 } else { 64 })?;
+                }
+            }
+        }
+    }
+
+    bits.read_ue()?; // log2_max_frame_num_minus4
+    match bits.read_ue()? {
+        0 => {
+            bits.read_ue()?; // log2_max_pic_order_cnt_lsb_minus4
+        }
+        1 => {
+            bits.read_bit()?; // delta_pic_order_always_zero_flag
+            bits.read_se()?; // offset_for_non_ref_pic
+            bits.read_se()?; // offset_for_top_to_bottom_field
+            for _ in 0..bits.read_ue()? {
+                bits.read_se()?; // offset_for_ref_frame
+            }
+        }
+        _ => {}
+    }
+    bits.read_ue()?; // max_num_ref_frames
+    bits.read_bit()?; // gaps_in_frame_num_value_allowed_flag
+
+    let width_in_mbs = bits.read_ue()? + 1;
+    let height_in_map_units = bits.read_ue()? + 1;
+    let frame_mbs_only = bits.read_bit()?;
+    if !frame_mbs_only {
+        bits.read_bit()?; // mb_adaptive_frame_field_flag
+    }
+    bits.read_bit()?; // direct_8x8_inference_flag
+
+    let mut width = width_in_mbs * 16;
+    let mut height = (2 - frame_mbs_only as u32) * height_in_map_units * 16;
+
+    if bits.read_bit()? {
+        // frame_cropping_flag: 裁剪单位取决于色度采样
+        let (sub_width, sub_height) = match (chroma_format_idc, separate_colour_plane) {
+            (0, _) | (_, true) => (1, 1),
+            (1, _) => (2, 2),
+            (2, _) => (2, 1),
+            _ => (1, 1),
+        };
+        let crop_x = sub_width;
+        let crop_y = sub_height * (2 - frame_mbs_only as u32);
+        let left = bits.read_ue()?;
+        let right = bits.read_ue()?;
+        let top = bits.read_ue()?;
+        let bottom = bits.read_ue()?;
+        width = width
+            .checked_sub(crop_x * (left + right))
+            .ok_or_else(|| anyhow!("SPS 裁剪超出宽度"))?;
+        height = height
+            .checked_sub(crop_y * (top + bottom))
+            .ok_or_else(|| anyhow!("SPS 裁剪超出高度"))?;
+    }
+
+    Ok(SpsInfo {
+        profile_idc,
+        constraint_flags,
+        level_idc,
+        seq_parameter_set_id,
+        width,
+        height,
+    })
+}
+
+fn skip_scaling_list(bits: &mut BitReader, size: usize) -> Result<()> {
+    let mut last_scale = 8i32;
+    let mut next_scale = 8i32;
+    for _ in 0..size {
+        if next_scale != 0 {
+            let delta = bits.read_se()?;
+            next_scale = (last_scale + delta + 256) % 256;
+        }
+        if next_scale != 0 {
+            last_scale = next_scale;
+        }
+    }
+    Ok(())
+}
+
+/// RBSP 位读取 (含 Exp-Golomb)
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn read_bit(&mut self) -> Result<bool> {
+        let byte = self
+            .data
+            .get(self.position / 8)
+            .ok_or_else(|| anyhow!("SPS 数据不足"))?;
+        let bit = (byte >> (7 - self.position % 8)) & 1;
+        self.position += 1;
+        Ok(bit == 1)
+    }
+
+    fn read_bits(&mut self, count: u32) -> Result<u32> {
+        let mut value = 0;
+        for _ in 0..count {
+            value = (value << 1) | self.read_bit()? as u32;
+        }
+        Ok(value)
+    }
+
+    /// 无符号 Exp-Golomb
+    fn read_ue(&mut self) -> Result<u32> {
+        let mut leading_zeros = 0;
+        while !self.read_bit()? {
+            leading_zeros += 1;
+            if leading_zeros > 31 {
+                return Err(anyhow!("无效的 Exp-Golomb 编码"));
+            }
+        }
+        Ok((1u32 << leading_zeros) - 1 + self.read_bits(leading_zeros)?)
+    }
+
+    /// 有符号 Exp-Golomb
+    fn read_se(&mut self) -> Result<i32> {
+        let value = self.read_ue()?;
+        let magnitude = value.div_ceil(2) as i32;
+        Ok(if value % 2 == 1 { magnitude } else { -magnitude })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Baseline 3.0 640x480 (手工按 7.3.2.1.1 编码)
+    const SPS_640X480: &[u8] = &[0x67, 0x42, 0xc0, 0x1e, 0xda, 0x02, 0x80, 0xf6, 0x40];
+    const PPS: &[u8] = &[0x68, 0xce, 0x38, 0x80];
+    const IDR_SLICE: &[u8] = &[0x65, 0x88, 0x84, 0x00, 0x33];
+
+    fn avcc(nals: &[&[u8]]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for nal in nals {
+            data.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+            data.extend_from_slice(nal);
+        }
+        data
+    }
+
+    #[test]
+    fn test_avcc_to_annex_b_with_parameter_sets() {
+        let mut out = Vec::new();
+        write_parameter_sets([SPS_640X480, PPS], &mut out);
+        avcc_to_annex_b(&avcc(&[IDR_SLICE]), 4, &mut out).unwrap();
+
+        let nals = split_annex_b(&out);
+        assert_eq!(nals, vec![SPS_640X480, PPS, IDR_SLICE]);
+        let types: Vec<_> = nals.iter().filter_map(|nal| nal_unit_type(nal)).collect();
+        assert_eq!(types, vec![nal_type::SPS, nal_type::PPS, nal_type::IDR]);
+
+        let sps = parse_sps(nals[0]).unwrap();
+        assert_eq!((sps.width, sps.height), (640, 480));
+        assert_eq!(sps.profile_idc, 66);
+        assert_eq!(sps.level_idc, 30);
+        assert_eq!(sps.codec_string(), "avc1.42c01e");
+    }
+
+    #[test]
+    fn test_parse_high_profile_sps_with_cropping() {
+        // High 4.0，1920x1088 编码后裁剪为 1080 行
+        let sps = parse_sps(&[0x67, 0x64, 0x00, 0x28, 0xac, 0xda, 0x01, 0xe0, 0x08, 0x9f, 0x95]).unwrap();
+        assert_eq!((sps.width, sps.height), (1920, 1080));
+        assert_eq!(sps.codec_string(), "avc1.640028");
+    }
+
+    #[test]
+    fn test_avcc_rejects_truncated_data() {
+        let mut data = avcc(&[IDR_SLICE]);
+        data.pop();
+        assert!(avcc_to_annex_b(&data, 4, &mut Vec::new()).is_err());
+        assert!(avcc_to_annex_b(&[0, 0], 4, &mut Vec::new()).is_err());
+        assert!(avcc_to_annex_b(&data, 0, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_split_three_byte_start_codes() {
+        let data = [0, 0, 1, 0x09, 0xf0, 0, 0, 0, 1, 0x65, 0x88, 0, 0, 1, 0x41, 0x9a];
+        let nals = split_annex_b(&data);
+        assert_eq!(nals, vec![&[0x09, 0xf0][..], &[0x65, 0x88][..], &[0x41, 0x9a][..]]);
+    }
+
+    #[test]
+    fn test_unescape_rbsp() {
+        assert_eq!(unescape_rbsp(&[0, 0, 3, 1, 0, 0, 3, 0]), vec![0, 0, 1, 0, 0, 0]);
+        assert_eq!(unescape_rbsp(&[1, 2, 3]), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_parse_sps_rejects_other_nal() {
+        assert!(parse_sps(PPS).is_err());
+        assert!(parse_sps(&[0x67, 0x42]).is_err());
+    }
+}
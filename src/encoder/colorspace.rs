@@ -0,0 +1,696 @@
+//! RGBA/BGRA → YUV 颜色空间转换
+//!
+//! 软件编码路径的瓶颈在于颜色转换: swscale 需要先把帧逐行复制到 FFmpeg 源帧再转换，
+//! 4K60 下单核难以跟上。本模块直接从捕获缓冲区写入目标平面，并提供:
+//! - AVX2 (x86_64，运行时检测) 与 NEON (aarch64) 加速路径
+//! - 标量回退路径 (与 SIMD 路径结果逐字节一致)
+//! - I420 (YUV420P) 与 NV12 两种输出布局
+//!
+//! ## 系数
+//! BT.601 limited range，7 位定点:
+//! - Y = ((33R + 64G + 13B + 64) >> 7) + 16
+//! - U = ((-19R - 37G + 56B) 的 2x2 平均) + 128
+//! - V = ((56R - 47G - 9B) 的 2x2 平均) + 128
+//!
+//! 色度先对上下两行做舍入平均 (与 `_mm256_avg_epu8` / `vrhaddq_u8` 相同)，
+//! 再对左右两个像素的分量求和，最后 `(sum + 128) >> 8`
+
+use anyhow::{anyhow, Result};
+
+/// 源像素字节序
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelLayout {
+    /// R, G, B, A
+    Rgba,
+    /// B, G, R, A (Windows DXGI / macOS 原生)
+    Bgra,
+}
+
+/// YUV 输出布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YuvLayout {
+    /// 三平面 Y / U / V (软件编码器)
+    I420,
+    /// 双平面 Y / UV 交错 (硬件编码器)
+    Nv12,
+}
+
+const Y_COEFFS: [i16; 3] = [33, 64, 13];
+const U_COEFFS: [i16; 3] = [-19, -37, 56];
+const V_COEFFS: [i16; 3] = [56, -47, -9];
+
+/// 源图像描述
+#[derive(Debug, Clone, Copy)]
+pub struct SourceImage<'a> {
+    pub data: &'a [u8],
+    pub stride: usize,
+    pub width: usize,
+    pub height: usize,
+    pub layout: PixelLayout,
+}
+
+impl<'a> SourceImage<'a> {
+    fn check(&self) -> Result<()> {
+        if self.width == 0 || self.height == 0 {
+            return Err(anyhow!("图像尺寸为 0"));
+        }
+        if self.stride < self.width * 4 {
+            return Err(anyhow!("stride {} 小于行宽 {}", self.stride, self.width * 4));
+        }
+        if self.data.len() < self.stride * (self.height - 1) + self.width * 4 {
+            return Err(anyhow!("源数据长度不足: {}", self.data.len()));
+        }
+        Ok(())
+    }
+
+    fn row(&self, y: usize) -> &'a [u8] {
+        let start = y * self.stride;
+        &self.data[start..start + self.width * 4]
+    }
+}
+
+/// 目标平面 (I420 时 `chroma_v` 为 Some，NV12 时 `chroma_u` 为交错 UV 平面)
+pub struct YuvPlanes<'a> {
+    pub y: &'a mut [u8],
+    pub y_stride: usize,
+    pub chroma_u: &'a mut [u8],
+    pub u_stride: usize,
+    pub chroma_v: Option<(&'a mut [u8], usize)>,
+}
+
+/// 可复用的 YUV 缓冲区
+///
+/// 分辨率不变时不会重新分配内存
+#[derive(Debug, Clone)]
+pub struct YuvBuffer {
+    layout: YuvLayout,
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+impl YuvBuffer {
+    pub fn new(layout: YuvLayout) -> Self {
+        Self {
+            layout,
+            width: 0,
+            height: 0,
+            data: Vec::new(),
+        }
+    }
+
+    /// 转换一帧，返回紧凑排列的 YUV 数据
+    pub fn convert(&mut self, src: &SourceImage) -> Result<&[u8]> {
+        src.check()?;
+
+        let chroma_width = src.width.div_ceil(2);
+        let chroma_height = src.height.div_ceil(2);
+        let y_size = src.width * src.height;
+        let chroma_size = chroma_width * chroma_height;
+        let total = y_size + chroma_size * 2;
+
+        if self.width != src.width || self.height != src.height {
+            self.width = src.width;
+            self.height = src.height;
+            self.data.resize(total, 0);
+        }
+
+        let (y, chroma) = self.data.split_at_mut(y_size);
+        let planes = match self.layout {
+            YuvLayout::I420 => {
+                let (u, v) = chroma.split_at_mut(chroma_size);
+                YuvPlanes {
+                    y,
+                    y_stride: src.width,
+                    chroma_u: u,
+                    u_stride: chroma_width,
+                    chroma_v: Some((v, chroma_width)),
+                }
+            }
+            YuvLayout::Nv12 => YuvPlanes {
+                y,
+                y_stride: src.width,
+                chroma_u: chroma,
+                u_stride: chroma_width * 2,
+                chroma_v: None,
+            },
+        };
+
+        convert(src, planes)?;
+        Ok(&self.data)
+    }
+
+    pub fn layout(&self) -> YuvLayout {
+        self.layout
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+}
+
+/// 转换到调用方提供的平面 (例如 FFmpeg 帧的 data 指针)
+pub fn convert(src: &SourceImage, mut planes: YuvPlanes) -> Result<()> {
+    src.check()?;
+
+    let chroma_width = src.width.div_ceil(2);
+    let chroma_height = src.height.div_ceil(2);
+    let uv_bytes = if planes.chroma_v.is_some() { chroma_width } else { chroma_width * 2 };
+
+    if planes.y_stride < src.width || planes.y.len() < planes.y_stride * (src.height - 1) + src.width {
+        return Err(anyhow!("Y 平面大小不足"));
+    }
+    if planes.u_stride < uv_bytes || planes.chroma_u.len() < planes.u_stride * (chroma_height - 1) + uv_bytes {
+        return Err(anyhow!("色度平面大小不足"));
+    }
+    if let Some((v, v_stride)) = &planes.chroma_v {
+        if *v_stride < chroma_width || v.len() < v_stride * (chroma_height - 1) + chroma_width {
+            return Err(anyhow!("V 平面大小不足"));
+        }
+    }
+
+    let kernel = Kernel::detect();
+
+    for y in 0..src.height {
+        let row = src.row(y);
+        let dst = &mut planes.y[y * planes.y_stride..y * planes.y_stride + src.width];
+        kernel.luma_row(row, dst, src.layout);
+    }
+
+    for cy in 0..chroma_height {
+        let row0 = src.row(cy * 2);
+        // 奇数高度时最后一行与自身平均
+        let row1 = src.row((cy * 2 + 1).min(src.height - 1));
+
+        let u_row = &mut planes.chroma_u[cy * planes.u_stride..cy * planes.u_stride + uv_bytes];
+        match planes.chroma_v.as_mut() {
+            Some((v, v_stride)) => {
+                let v_row = &mut v[cy * *v_stride..cy * *v_stride + chroma_width];
+                kernel.chroma_row(row0, row1, src.width, src.layout, ChromaOut::Planar(u_row, v_row));
+            }
+            None => {
+                kernel.chroma_row(row0, row1, src.width, src.layout, ChromaOut::Interleaved(u_row));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 色度输出目标
+enum ChromaOut<'a> {
+    Planar(&'a mut [u8], &'a mut [u8]),
+    Interleaved(&'a mut [u8]),
+}
+
+impl ChromaOut<'_> {
+    fn put(&mut self, index: usize, u: u8, v: u8) {
+        match self {
+            ChromaOut::Planar(us, vs) => {
+                us[index] = u;
+                vs[index] = v;
+            }
+            ChromaOut::Interleaved(uv) => {
+                uv[index * 2] = u;
+                uv[index * 2 + 1] = v;
+            }
+        }
+    }
+}
+
+/// 当前 CPU 可用的转换实现
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kernel {
+    Scalar,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+    #[cfg(target_arch = "aarch64")]
+    Neon,
+}
+
+impl Kernel {
+    /// 运行时选择最快的实现
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if std::arch::is_x86_feature_detected!("avx2") {
+                return Kernel::Avx2;
+            }
+            Kernel::Scalar
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        {
+            Kernel::Neon
+        }
+
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            Kernel::Scalar
+        }
+    }
+
+    fn luma_row(self, row: &[u8], dst: &mut [u8], layout: PixelLayout) {
+        let done = match self {
+            Kernel::Scalar => 0,
+            // SAFETY: detect() 已确认 CPU 支持 AVX2
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => unsafe { avx2::luma_row(row, dst, layout) },
+            // SAFETY: NEON 是 aarch64 的基础指令集
+            #[cfg(target_arch = "aarch64")]
+            Kernel::Neon => unsafe { neon::luma_row(row, dst, layout) },
+        };
+        scalar::luma_row(&row[done * 4..], &mut dst[done..], layout);
+    }
+
+    fn chroma_row(self, row0: &[u8], row1: &[u8], width: usize, layout: PixelLayout, mut out: ChromaOut) {
+        let done = match self {
+            Kernel::Scalar => 0,
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => unsafe { avx2::chroma_row(row0, row1, width, layout, &mut out) },
+            #[cfg(target_arch = "aarch64")]
+            Kernel::Neon => unsafe { neon::chroma_row(row0, row1, width, layout, &mut out) },
+        };
+        scalar::chroma_row(row0, row1, width, layout, done, &mut out);
+    }
+}
+
+/// 标量实现 (同时负责 SIMD 路径剩余的尾部像素)
+mod scalar {
+    use super::{ChromaOut, PixelLayout, U_COEFFS, V_COEFFS, Y_COEFFS};
+
+    fn rgb(pixel: &[u8], layout: PixelLayout) -> [i32; 3] {
+        match layout {
+            PixelLayout::Rgba => [pixel[0] as i32, pixel[1] as i32, pixel[2] as i32],
+            PixelLayout::Bgra => [pixel[2] as i32, pixel[1] as i32, pixel[0] as i32],
+        }
+    }
+
+    fn dot(rgb: [i32; 3], coeffs: [i16; 3]) -> i32 {
+        rgb[0] * coeffs[0] as i32 + rgb[1] * coeffs[1] as i32 + rgb[2] * coeffs[2] as i32
+    }
+
+    pub fn luma_row(row: &[u8], dst: &mut [u8], layout: PixelLayout) {
+        for (pixel, out) in row.chunks_exact(4).zip(dst.iter_mut()) {
+            *out = (((dot(rgb(pixel, layout), Y_COEFFS) + 64) >> 7) + 16) as u8;
+        }
+    }
+
+    /// 上下两行舍入平均
+    fn vertical_avg(row0: &[u8], row1: &[u8], x: usize) -> [u8; 4] {
+        let mut out = [0u8; 4];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = ((row0[x * 4 + i] as u16 + row1[x * 4 + i] as u16 + 1) >> 1) as u8;
+        }
+        out
+    }
+
+    /// 从第 `start` 个色度样本开始转换
+    pub fn chroma_row(
+        row0: &[u8],
+        row1: &[u8],
+        width: usize,
+        layout: PixelLayout,
+        start: usize,
+        out: &mut ChromaOut,
+    ) {
+        for cx in start..width.div_ceil(2) {
+            let x0 = cx * 2;
+            // 奇数宽度时最后一列与自身配对
+            let x1 = (x0 + 1).min(width - 1);
+
+            let p0 = rgb(&vertical_avg(row0, row1, x0), layout);
+            let p1 = rgb(&vertical_avg(row0, row1, x1), layout);
+
+            let u = ((dot(p0, U_COEFFS) + dot(p1, U_COEFFS) + 128) >> 8) + 128;
+            let v = ((dot(p0, V_COEFFS) + dot(p1, V_COEFFS) + 128) >> 8) + 128;
+            out.put(cx, u.clamp(0, 255) as u8, v.clamp(0, 255) as u8);
+        }
+    }
+}
+
+/// AVX2 实现 (每次 16 像素)
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use super::{ChromaOut, PixelLayout, U_COEFFS, V_COEFFS, Y_COEFFS};
+    use std::arch::x86_64::*;
+
+    /// 按像素字节序重复排列的 maddubs 系数 ([c0, c1, c2, 0] × 8)
+    #[target_feature(enable = "avx2")]
+    unsafe fn coeff_vector(coeffs: [i16; 3], layout: PixelLayout) -> __m256i {
+        let [r, g, b] = coeffs.map(|c| c as i8);
+        let pixel = match layout {
+            PixelLayout::Rgba => [r, g, b, 0],
+            PixelLayout::Bgra => [b, g, r, 0],
+        };
+        let word = i32::from_le_bytes(pixel.map(|c| c as u8));
+        _mm256_set1_epi32(word)
+    }
+
+    /// 16 个像素的加权和 (按像素顺序排列的 16 个 i16)
+    #[target_feature(enable = "avx2")]
+    unsafe fn weighted_sum(lo: __m256i, hi: __m256i, coeffs: __m256i) -> __m256i {
+        let a = _mm256_maddubs_epi16(lo, coeffs);
+        let b = _mm256_maddubs_epi16(hi, coeffs);
+        // hadd 在 128 位通道内交错，重排为像素顺序
+        _mm256_permute4x64_epi64(_mm256_hadd_epi16(a, b), 0b11_01_10_00)
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn luma_row(row: &[u8], dst: &mut [u8], layout: PixelLayout) -> usize {
+        let width = dst.len().min(row.len() / 4);
+        let coeffs = coeff_vector(Y_COEFFS, layout);
+        let round = _mm256_set1_epi16(64);
+        let offset = _mm256_set1_epi16(16);
+
+        let mut x = 0;
+        while x + 16 <= width {
+            let src = row.as_ptr().add(x * 4);
+            let lo = _mm256_loadu_si256(src as *const __m256i);
+            let hi = _mm256_loadu_si256(src.add(32) as *const __m256i);
+
+            let sum = weighted_sum(lo, hi, coeffs);
+            let y = _mm256_add_epi16(_mm256_srli_epi16(_mm256_add_epi16(sum, round), 7), offset);
+
+            // 每个通道打包出 8 字节，再把两个通道的低 8 字节拼到低 128 位
+            let packed = _mm256_permute4x64_epi64(_mm256_packus_epi16(y, y), 0b00_00_10_00);
+            _mm_storeu_si128(dst.as_mut_ptr().add(x) as *mut __m128i, _mm256_castsi256_si128(packed));
+            x += 16;
+        }
+        x
+    }
+
+    /// 8 个色度样本 (输入已按像素顺序排列的 16 个分量)
+    #[target_feature(enable = "avx2")]
+    unsafe fn chroma_samples(components: __m256i) -> [u8; 8] {
+        // 相邻像素求和，每个 128 位通道得到 4 个样本
+        let pairs = _mm256_hadd_epi16(components, components);
+        let value = _mm256_add_epi16(
+            _mm256_srai_epi16(_mm256_add_epi16(pairs, _mm256_set1_epi16(128)), 8),
+            _mm256_set1_epi16(128),
+        );
+        let packed = _mm256_packus_epi16(value, value);
+        let lo = _mm256_extract_epi32(packed, 0) as u32;
+        let hi = _mm256_extract_epi32(packed, 4) as u32;
+        let mut out = [0u8; 8];
+        out[..4].copy_from_slice(&lo.to_le_bytes());
+        out[4..].copy_from_slice(&hi.to_le_bytes());
+        out
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn chroma_row(
+        row0: &[u8],
+        row1: &[u8],
+        width: usize,
+        layout: PixelLayout,
+        out: &mut ChromaOut,
+    ) -> usize {
+        let u_coeffs = coeff_vector(U_COEFFS, layout);
+        let v_coeffs = coeff_vector(V_COEFFS, layout);
+
+        let mut x = 0;
+        while x + 16 <= width {
+            let p0 = row0.as_ptr().add(x * 4);
+            let p1 = row1.as_ptr().add(x * 4);
+            let lo = _mm256_avg_epu8(
+                _mm256_loadu_si256(p0 as *const __m256i),
+                _mm256_loadu_si256(p1 as *const __m256i),
+            );
+            let hi = _mm256_avg_epu8(
+                _mm256_loadu_si256(p0.add(32) as *const __m256i),
+                _mm256_loadu_si256(p1.add(32) as *const __m256i),
+            );
+
+            let u = chroma_samples(weighted_sum(lo, hi, u_coeffs));
+            let v = chroma_samples(weighted_sum(lo, hi, v_coeffs));
+            for i in 0..8 {
+                out.put(x / 2 + i, u[i], v[i]);
+            }
+            x += 16;
+        }
+        x / 2
+    }
+}
+
+/// NEON 实现 (每次 16 像素)
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use super::{ChromaOut, PixelLayout, U_COEFFS, V_COEFFS, Y_COEFFS};
+    use std::arch::aarch64::*;
+
+    /// 解交织 16 个像素，返回 (R, G, B)
+    unsafe fn load_rgb(ptr: *const u8, layout: PixelLayout) -> (uint8x16_t, uint8x16_t, uint8x16_t) {
+        let px = vld4q_u8(ptr);
+        match layout {
+            PixelLayout::Rgba => (px.0, px.1, px.2),
+            PixelLayout::Bgra => (px.2, px.1, px.0),
+        }
+    }
+
+    pub unsafe fn luma_row(row: &[u8], dst: &mut [u8], layout: PixelLayout) -> usize {
+        let width = dst.len().min(row.len() / 4);
+        let [cr, cg, cb] = Y_COEFFS.map(|c| c as u8);
+
+        let mut x = 0;
+        while x + 16 <= width {
+            let (r, g, b) = load_rgb(row.as_ptr().add(x * 4), layout);
+
+            let mut lo = vmull_u8(vget_low_u8(r), vdup_n_u8(cr));
+            lo = vmlal_u8(lo, vget_low_u8(g), vdup_n_u8(cg));
+            lo = vmlal_u8(lo, vget_low_u8(b), vdup_n_u8(cb));
+            let mut hi = vmull_u8(vget_high_u8(r), vdup_n_u8(cr));
+            hi = vmlal_u8(hi, vget_high_u8(g), vdup_n_u8(cg));
+            hi = vmlal_u8(hi, vget_high_u8(b), vdup_n_u8(cb));
+
+            // (sum + 64) >> 7，再加 16
+            let y = vcombine_u8(vrshrn_n_u16(lo, 7), vrshrn_n_u16(hi, 7));
+            vst1q_u8(dst.as_mut_ptr().add(x), vaddq_u8(y, vdupq_n_u8(16)));
+            x += 16;
+        }
+        x
+    }
+
+    /// 16 个像素的有符号加权和
+    unsafe fn weighted(r: uint8x16_t, g: uint8x16_t, b: uint8x16_t, coeffs: [i16; 3], high: bool) -> int16x8_t {
+        let pick = |v: uint8x16_t| {
+            vreinterpretq_s16_u16(vmovl_u8(if high { vget_high_u8(v) } else { vget_low_u8(v) }))
+        };
+        let mut sum = vmulq_n_s16(pick(r), coeffs[0]);
+        sum = vmlaq_n_s16(sum, pick(g), coeffs[1]);
+        vmlaq_n_s16(sum, pick(b), coeffs[2])
+    }
+
+    /// 相邻像素求和后得到 8 个色度样本
+    unsafe fn samples(lo: int16x8_t, hi: int16x8_t) -> uint8x8_t {
+        let pairs = vpaddq_s16(lo, hi);
+        let value = vaddq_s16(vshrq_n_s16(vaddq_s16(pairs, vdupq_n_s16(128)), 8), vdupq_n_s16(128));
+        vqmovun_s16(value)
+    }
+
+    pub unsafe fn chroma_row(
+        row0: &[u8],
+        row1: &[u8],
+        width: usize,
+        layout: PixelLayout,
+        out: &mut ChromaOut,
+    ) -> usize {
+        let mut x = 0;
+        while x + 16 <= width {
+            let (r0, g0, b0) = load_rgb(row0.as_ptr().add(x * 4), layout);
+            let (r1, g1, b1) = load_rgb(row1.as_ptr().add(x * 4), layout);
+            let (r, g, b) = (vrhaddq_u8(r0, r1), vrhaddq_u8(g0, g1), vrhaddq_u8(b0, b1));
+
+            let u = samples(weighted(r, g, b, U_COEFFS, false), weighted(r, g, b, U_COEFFS, true));
+            let v = samples(weighted(r, g, b, V_COEFFS, false), weighted(r, g, b, V_COEFFS, true));
+
+            let mut us = [0u8; 8];
+            let mut vs = [0u8; 8];
+            vst1_u8(us.as_mut_ptr(), u);
+            vst1_u8(vs.as_mut_ptr(), v);
+            for i in 0..8 {
+                out.put(x / 2 + i, us[i], vs[i]);
+            }
+            x += 16;
+        }
+        x / 2
+    }
+}
+
+/// 复用的 FFmpeg YUV 帧
+///
+/// 替代 swscale: 直接把捕获帧转换进 FFmpeg 帧的平面，编码器仍持有上一帧引用时才会重新分配
+#[cfg(feature = "h264")]
+pub struct ReusableVideoFrame {
+    frame: ffmpeg_next::frame::Video,
+    layout: PixelLayout,
+}
+
+#[cfg(feature = "h264")]
+impl ReusableVideoFrame {
+    /// 创建复用帧 (`format` 为 YUV420P 或 NV12)
+    pub fn new(format: ffmpeg_next::format::Pixel, width: u32, height: u32, layout: PixelLayout) -> Self {
+        Self {
+            frame: ffmpeg_next::frame::Video::new(format, width, height),
+            layout,
+        }
+    }
+
+    /// 转换捕获帧，返回可直接送入编码器的 YUV 帧
+    pub fn convert(&mut self, frame: &crate::capture::Frame) -> Result<&mut ffmpeg_next::frame::Video> {
+        use ffmpeg_next::format::Pixel;
+
+        if frame.is_gpu() {
+            return Err(anyhow!("GPU 帧不能走软件颜色转换"));
+        }
+        if frame.width != self.frame.width() || frame.height != self.frame.height() {
+            return Err(anyhow!(
+                "帧尺寸 {}x{} 与编码尺寸 {}x{} 不一致",
+                frame.width, frame.height, self.frame.width(), self.frame.height()
+            ));
+        }
+
+        let src = SourceImage {
+            data: &frame.data,
+            stride: frame.stride,
+            width: frame.width as usize,
+            height: frame.height as usize,
+            layout: self.layout,
+        };
+
+        unsafe {
+            let ptr = self.frame.as_mut_ptr();
+            // 编码器可能仍引用上一帧的缓冲区
+            if ffmpeg_next::ffi::av_frame_make_writable(ptr) < 0 {
+                return Err(anyhow!("av_frame_make_writable 失败"));
+            }
+
+            let height = src.height;
+            let chroma_height = height.div_ceil(2);
+            let plane = |index: usize, rows: usize| {
+                let stride = (*ptr).linesize[index] as usize;
+                (std::slice::from_raw_parts_mut((*ptr).data[index], stride * rows), stride)
+            };
+
+            let (y, y_stride) = plane(0, height);
+            let (chroma_u, u_stride) = plane(1, chroma_height);
+            let chroma_v = match self.frame.format() {
+                Pixel::YUV420P => Some(plane(2, chroma_height)),
+                Pixel::NV12 => None,
+                other => return Err(anyhow!("不支持的目标像素格式: {:?}", other)),
+            };
+
+            convert(&src, YuvPlanes { y, y_stride, chroma_u, u_stride, chroma_v })?;
+        }
+
+        Ok(&mut self.frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_image(stride: usize, height: usize) -> Vec<u8> {
+        let mut data = vec![0u8; stride * height];
+        let mut seed = 0x1234_5678u32;
+        for byte in data.iter_mut() {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            *byte = (seed >> 16) as u8;
+        }
+        data
+    }
+
+    fn convert_with(kernel: Kernel, src: &SourceImage, layout: YuvLayout) -> Vec<u8> {
+        let cw = src.width.div_ceil(2);
+        let ch = src.height.div_ceil(2);
+        let mut y = vec![0u8; src.width * src.height];
+        let mut u = vec![0u8; cw * ch * 2];
+        let mut v = vec![0u8; cw * ch];
+
+        for row in 0..src.height {
+            kernel.luma_row(src.row(row), &mut y[row * src.width..(row + 1) * src.width], src.layout);
+        }
+        for cy in 0..ch {
+            let row0 = src.row(cy * 2);
+            let row1 = src.row((cy * 2 + 1).min(src.height - 1));
+            let out = match layout {
+                YuvLayout::I420 => ChromaOut::Planar(&mut u[cy * cw..(cy + 1) * cw], &mut v[cy * cw..(cy + 1) * cw]),
+                YuvLayout::Nv12 => ChromaOut::Interleaved(&mut u[cy * cw * 2..(cy + 1) * cw * 2]),
+            };
+            kernel.chroma_row(row0, row1, src.width, src.layout, out);
+        }
+
+        match layout {
+            YuvLayout::I420 => [y, u[..cw * ch].to_vec(), v].concat(),
+            YuvLayout::Nv12 => [y, u].concat(),
+        }
+    }
+
+    #[test]
+    fn test_reference_colors() {
+        // 白、黑、纯红 (BT.601 limited range)
+        let pixels: [[u8; 4]; 3] = [[255, 255, 255, 255], [0, 0, 0, 255], [255, 0, 0, 255]];
+        let expected = [(235, 128, 128), (16, 128, 128), (82, 90, 240)];
+
+        for (pixel, (ey, eu, ev)) in pixels.iter().zip(expected) {
+            let data: Vec<u8> = pixel.repeat(4);
+            let src = SourceImage { data: &data, stride: 8, width: 2, height: 2, layout: PixelLayout::Rgba };
+            let mut buffer = YuvBuffer::new(YuvLayout::I420);
+            let yuv = buffer.convert(&src).unwrap();
+            assert_eq!(&yuv[..4], &[ey; 4]);
+            assert_eq!(yuv[4], eu);
+            assert_eq!(yuv[5], ev);
+        }
+    }
+
+    #[test]
+    fn test_bgra_matches_rgba() {
+        let rgba = test_image(34 * 4, 6);
+        let bgra: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect();
+
+        let mut a = YuvBuffer::new(YuvLayout::Nv12);
+        let mut b = YuvBuffer::new(YuvLayout::Nv12);
+        let ya = a.convert(&SourceImage { data: &rgba, stride: 136, width: 34, height: 6, layout: PixelLayout::Rgba }).unwrap().to_vec();
+        let yb = b.convert(&SourceImage { data: &bgra, stride: 136, width: 34, height: 6, layout: PixelLayout::Bgra }).unwrap();
+        assert_eq!(ya, yb);
+    }
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        // 奇数尺寸 + 带填充的 stride，覆盖 SIMD 主循环和标量尾部
+        let (width, height, stride) = (53, 7, 53 * 4 + 12);
+        let data = test_image(stride, height);
+
+        for layout in [PixelLayout::Rgba, PixelLayout::Bgra] {
+            let src = SourceImage { data: &data, stride, width, height, layout };
+            for yuv in [YuvLayout::I420, YuvLayout::Nv12] {
+                let expected = convert_with(Kernel::Scalar, &src, yuv);
+                assert_eq!(convert_with(Kernel::detect(), &src, yuv), expected, "{:?} {:?}", layout, yuv);
+            }
+        }
+    }
+
+    #[test]
+    fn test_buffer_reuse() {
+        let data = test_image(256, 4);
+        let src = SourceImage { data: &data, stride: 256, width: 64, height: 4, layout: PixelLayout::Rgba };
+
+        let mut buffer = YuvBuffer::new(YuvLayout::I420);
+        let first = buffer.convert(&src).unwrap().as_ptr();
+        let second = buffer.convert(&src).unwrap().as_ptr();
+        assert_eq!(first, second);
+        assert_eq!(buffer.convert(&src).unwrap().len(), 64 * 4 * 3 / 2);
+    }
+
+    #[test]
+    fn test_invalid_source() {
+        let data = vec![0u8; 10];
+        let src = SourceImage { data: &data, stride: 8, width: 2, height: 2, layout: PixelLayout::Rgba };
+        assert!(YuvBuffer::new(YuvLayout::I420).convert(&src).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+//! D3D11 纹理输入的 FFmpeg 硬件编码 (零拷贝)
+//!
+//! 通过 FFmpeg 的 D3D11VA 硬件帧上下文把 DXGI 捕获的 BGRA 纹理直接送入硬件编码器
+//! (h264_nvenc、h264_amf)，整个过程不经过 CPU 内存，也不需要 RGBA → NV12 软件转换
+//! (颜色转换由编码器在 GPU 上完成)
+
+use crate::encoder::hardware::HardwareEncoderConfig;
+use anyhow::{anyhow, Result};
+use ffmpeg_next::ffi;
+use std::ffi::{c_void, CString};
+use std::ptr;
+use windows::core::{ComInterface, Interface};
+use windows::Win32::Graphics::Direct3D11::{ID3D11Device, ID3D11DeviceContext, ID3D11Resource, ID3D11Texture2D};
+
+/// libavutil/hwcontext_d3d11va.h: AVD3D11VADeviceContext
+///
+/// ffmpeg-sys 不一定生成此头文件的绑定，这里按 C 布局声明
+#[repr(C)]
+struct AVD3D11VADeviceContext {
+    device: *mut c_void,
+    device_context: *mut c_void,
+    video_device: *mut c_void,
+    video_context: *mut c_void,
+    lock: Option<unsafe extern "C" fn(*mut c_void)>,
+    unlock: Option<unsafe extern "C" fn(*mut c_void)>,
+    lock_ctx: *mut c_void,
+}
+
+/// 基于 D3D11 硬件帧的编码上下文
+pub struct D3D11TextureEncoder {
+    codec_ctx: *mut ffi::AVCodecContext,
+    device_ref: *mut ffi::AVBufferRef,
+    frames_ref: *mut ffi::AVBufferRef,
+    context: ID3D11DeviceContext,
+    /// 编码器名称 (日志和错误信息)
+    codec_name: &'static str,
+}
+
+impl D3D11TextureEncoder {
+    /// 在捕获器的 D3D11 设备上打开 `codec_name` 编码器
+    ///
+    /// `options` 为编码器私有选项 (预设、码控等)，GOP 由调用方按关键帧间隔给出
+    pub fn new(
+        device: &ID3D11Device,
+        width: u32,
+        height: u32,
+        config: &HardwareEncoderConfig,
+        codec_name: &'static str,
+        gop_size: u32,
+        options: &[(&str, &str)],
+    ) -> Result<Self> {
+        unsafe {
+            let mut context: Option<ID3D11DeviceContext> = None;
+            device.GetImmediateContext(&mut context);
+            let context = context.ok_or_else(|| anyhow!("无法获取 D3D11 设备上下文"))?;
+
+            // 1. 用捕获器的设备创建 D3D11VA 硬件设备上下文
+            let device_ref = ffi::av_hwdevice_ctx_alloc(ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_D3D11VA);
+            if device_ref.is_null() {
+                return Err(anyhow!("av_hwdevice_ctx_alloc 失败"));
+            }
+            let mut encoder = Self {
+                codec_ctx: ptr::null_mut(),
+                device_ref,
+                frames_ref: ptr::null_mut(),
+                context,
+                codec_name,
+            };
+
+            let hw_device = (*device_ref).data as *mut ffi::AVHWDeviceContext;
+            let d3d11_device = (*hw_device).hwctx as *mut AVD3D11VADeviceContext;
+            // FFmpeg 在释放设备上下文时会 Release，这里转移一个引用
+            (*d3d11_device).device = device.clone().into_raw();
+            check(ffi::av_hwdevice_ctx_init(device_ref), "av_hwdevice_ctx_init")?;
+
+            // 2. 创建 BGRA 纹理池
+            let frames_ref = ffi::av_hwframe_ctx_alloc(device_ref);
+            if frames_ref.is_null() {
+                return Err(anyhow!("av_hwframe_ctx_alloc 失败"));
+            }
+            encoder.frames_ref = frames_ref;
+            let frames = (*frames_ref).data as *mut ffi::AVHWFramesContext;
+            (*frames).format = ffi::AVPixelFormat::AV_PIX_FMT_D3D11;
+            (*frames).sw_format = ffi::AVPixelFormat::AV_PIX_FMT_BGRA;
+            (*frames).width = width as i32;
+            (*frames).height = height as i32;
+            (*frames).initial_pool_size = 4;
+            check(ffi::av_hwframe_ctx_init(frames_ref), "av_hwframe_ctx_init")?;
+
+            // 3. 打开编码器 (D3D11 输入)
+            let name = CString::new(codec_name)?;
+            let codec = ffi::avcodec_find_encoder_by_name(name.as_ptr());
+            if codec.is_null() {
+                return Err(anyhow!("找不到编码器 ({})", codec_name));
+            }
+            let codec_ctx = ffi::avcodec_alloc_context3(codec);
+            if codec_ctx.is_null() {
+                return Err(anyhow!("avcodec_alloc_context3 失败"));
+            }
+            encoder.codec_ctx = codec_ctx;
+
+            (*codec_ctx).width = width as i32;
+            (*codec_ctx).height = height as i32;
+            (*codec_ctx).time_base = ffi::AVRational { num: 1, den: config.fps as i32 };
+            (*codec_ctx).framerate = ffi::AVRational { num: config.fps as i32, den: 1 };
+            (*codec_ctx).bit_rate = config.bitrate as i64 * 1000;
+            (*codec_ctx).rc_max_rate = config.bitrate as i64 * 1000;
+            (*codec_ctx).gop_size = gop_size as i32;
+            (*codec_ctx).max_b_frames = 0;
+            (*codec_ctx).pix_fmt = ffi::AVPixelFormat::AV_PIX_FMT_D3D11;
+            (*codec_ctx).hw_frames_ctx = ffi::av_buffer_ref(frames_ref);
+
+            let mut opts: *mut ffi::AVDictionary = ptr::null_mut();
+            for (key, value) in options {
+                let key = CString::new(*key)?;
+                let value = CString::new(*value)?;
+                ffi::av_dict_set(&mut opts, key.as_ptr(), value.as_ptr(), 0);
+            }
+            let ret = ffi::avcodec_open2(codec_ctx, codec, &mut opts);
+            ffi::av_dict_free(&mut opts);
+            check(ret, "avcodec_open2")?;
+
+            tracing::info!("{} D3D11 零拷贝编码器创建成功: {}x{}", codec_name, width, height);
+            Ok(encoder)
+        }
+    }
+
+    /// 编码一个 BGRA 纹理，返回 (码流, 是否关键帧)
+    pub fn encode(
+        &mut self,
+        texture: &ID3D11Texture2D,
+        pts: i64,
+        force_key_frame: bool,
+    ) -> Result<Option<(Vec<u8>, bool)>> {
+        unsafe {
+            let mut frame = ffi::av_frame_alloc();
+            if frame.is_null() {
+                return Err(anyhow!("av_frame_alloc 失败"));
+            }
+
+            let result = (|| -> Result<Option<(Vec<u8>, bool)>> {
+                check(ffi::av_hwframe_get_buffer(self.frames_ref, frame, 0), "av_hwframe_get_buffer")?;
+
+                // 硬件帧: data[0] = ID3D11Texture2D*, data[1] = 纹理数组索引
+                let raw = (*frame).data[0] as *mut c_void;
+                let index = (*frame).data[1] as usize as u32;
+                let target = ID3D11Texture2D::from_raw_borrowed(&raw)
+                    .ok_or_else(|| anyhow!("硬件帧纹理为空"))?;
+
+                // 显存内复制，不经过 CPU
+                self.context.CopySubresourceRegion(
+                    &target.cast::<ID3D11Resource>()?,
+                    index,
+                    0,
+                    0,
+                    0,
+                    &texture.cast::<ID3D11Resource>()?,
+                    0,
+                    None,
+                );
+
+                (*frame).pts = pts;
+                if force_key_frame {
+                    (*frame).pict_type = ffi::AVPictureType::AV_PICTURE_TYPE_I;
+                }
+
+                check(ffi::avcodec_send_frame(self.codec_ctx, frame), "avcodec_send_frame")?;
+                self.receive_packet()
+            })();
+
+            ffi::av_frame_free(&mut frame);
+            result
+        }
+    }
+
+    /// 运行时调整码率
+    ///
+    /// h264_nvenc 在下一帧检测到变化后重新配置码控；不支持运行时重配置的编码器
+    /// 返回 false，由调用方重建
+    pub fn set_bitrate(&mut self, bitrate_kbps: u32) -> bool {
+        if !crate::encoder::supports_runtime_bitrate(self.codec_name) {
+            return false;
+        }
+        unsafe {
+            (*self.codec_ctx).bit_rate = bitrate_kbps as i64 * 1000;
+            (*self.codec_ctx).rc_max_rate = bitrate_kbps as i64 * 1000;
+        }
+        true
+    }
+
+    unsafe fn receive_packet(&mut self) -> Result<Option<(Vec<u8>, bool)>> {
+        let mut packet = ffi::av_packet_alloc();
+        if packet.is_null() {
+            return Err(anyhow!("av_packet_alloc 失败"));
+        }
+
+        let ret = ffi::avcodec_receive_packet(self.codec_ctx, packet);
+        let result = if ret == ffi::AVERROR(ffi::EAGAIN) || ret == ffi::AVERROR_EOF {
+            Ok(None)
+        } else if ret < 0 {
+            Err(anyhow!("{} 纹理编码失败: {}", self.codec_name, ret))
+        } else {
+            let data = std::slice::from_raw_parts((*packet).data, (*packet).size as usize).to_vec();
+            let is_key_frame = (*packet).flags & ffi::AV_PKT_FLAG_KEY != 0;
+            Ok(Some((data, is_key_frame)))
+        };
+
+        ffi::av_packet_free(&mut packet);
+        result
+    }
+}
+
+impl Drop for D3D11TextureEncoder {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.codec_ctx.is_null() {
+                ffi::avcodec_free_context(&mut self.codec_ctx);
+            }
+            if !self.frames_ref.is_null() {
+                ffi::av_buffer_unref(&mut self.frames_ref);
+            }
+            if !self.device_ref.is_null() {
+                ffi::av_buffer_unref(&mut self.device_ref);
+            }
+        }
+    }
+}
+
+// SAFETY: 编码上下文只在视频任务中顺序使用
+unsafe impl Send for D3D11TextureEncoder {}
+
+pub(crate) fn check(ret: i32, what: &str) -> Result<()> {
+    if ret < 0 {
+        Err(anyhow!("{} 失败: {}", what, ret))
+    } else {
+        Ok(())
+    }
+}
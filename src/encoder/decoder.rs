@@ -0,0 +1,82 @@
+//! 视频解码器
+//!
+//! 将 Host 发来的 VP8 / VP9 / H.264 码流解码为 RGBA 帧 (控制端使用)
+
+use crate::capture::Frame;
+use anyhow::{anyhow, Result};
+use ffmpeg_next as ffmpeg;
+
+/// 解码的视频编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecoderCodec {
+    Vp8,
+    Vp9,
+    H264,
+}
+
+/// FFmpeg 软件解码器 (输出 RGBA)
+pub struct VideoDecoder {
+    decoder: ffmpeg::decoder::Video,
+    /// 颜色转换上下文 (按输入尺寸/格式懒创建)
+    scaler: Option<(ffmpeg::software::scaling::Context, ffmpeg::format::Pixel, u32, u32)>,
+    decoded: ffmpeg::frame::Video,
+}
+
+unsafe impl Send for VideoDecoder {}
+
+impl VideoDecoder {
+    pub fn new(codec: DecoderCodec) -> Result<Self> {
+        ffmpeg::init()?;
+
+        let id = match codec {
+            DecoderCodec::Vp8 => ffmpeg::codec::Id::VP8,
+            DecoderCodec::Vp9 => ffmpeg::codec::Id::VP9,
+            DecoderCodec::H264 => ffmpeg::codec::Id::H264,
+        };
+        let decoder = ffmpeg::decoder::find(id).ok_or_else(|| anyhow!("找不到 {:?} 解码器", codec))?;
+        let context = ffmpeg::codec::context::Context::new_with_codec(decoder);
+        let decoder = context.decoder().video()?;
+
+        Ok(Self {
+            decoder,
+            scaler: None,
+            decoded: ffmpeg::frame::Video::empty(),
+        })
+    }
+
+    /// 解码一个码流包，返回解码出的最后一帧 (数据不足时返回 None)
+    pub fn decode(&mut self, data: &[u8]) -> Result<Option<Frame>> {
+        self.decoder
+            .send_packet(&ffmpeg::Packet::copy(data))
+            .map_err(|e| anyhow!("解码失败: {}", e))?;
+
+        let mut latest = None;
+        while self.decoder.receive_frame(&mut self.decoded).is_ok() {
+            latest = Some(self.to_rgba()?);
+        }
+        Ok(latest)
+    }
+
+    fn to_rgba(&mut self) -> Result<Frame> {
+        let (format, width, height) = (self.decoded.format(), self.decoded.width(), self.decoded.height());
+        let stale = !matches!(&self.scaler, Some((_, f, w, h)) if (*f, *w, *h) == (format, width, height));
+        if stale {
+            let scaler = ffmpeg::software::scaling::Context::get(
+                format,
+                width,
+                height,
+                ffmpeg::format::Pixel::RGBA,
+                width,
+                height,
+                ffmpeg::software::scaling::Flags::BILINEAR,
+            )?;
+            self.scaler = Some((scaler, format, width, height));
+        }
+
+        let mut rgba = ffmpeg::frame::Video::empty();
+        if let Some((scaler, ..)) = self.scaler.as_mut() {
+            scaler.run(&self.decoded, &mut rgba)?;
+        }
+        Ok(Frame::from_raw_data(width, height, rgba.data(0).to_vec(), rgba.stride(0)))
+    }
+}
@@ -5,11 +5,14 @@
 // 硬件编码器模块尚未完全集成，标记为允许死代码
 #![allow(dead_code)]
 
+use crate::capture::GpuFrame;
 use crate::encoder::{EncodedPacket, Frame};
+use crate::quality::roi_encoder::RoiRegion;
 use anyhow::{anyhow, Result};
 
 /// 硬件编码器类型
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum HardwareEncoderType {
     /// NVIDIA NVENC (H.264)
     NVENC,
@@ -38,6 +41,32 @@ impl std::fmt::Display for HardwareEncoderType {
     }
 }
 
+impl HardwareEncoderType {
+    /// 命令行 `--encoder` 的取值
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::NVENC => "nvenc",
+            Self::AMF => "amf",
+            Self::QuickSync => "qsv",
+            Self::VideoToolbox => "videotoolbox",
+            Self::Software => "software",
+            Self::Auto => "auto",
+        }
+    }
+
+    /// 该编码器初始化失败时依次尝试的其他硬件编码器
+    ///
+    /// 同一台 Windows 机器上常同时有独显和核显 (NVIDIA + Intel、AMD 独显 + 核显)，
+    /// 驱动异常或 NVENC 会话数用尽时仍可以使用另一块 GPU 的编码器
+    pub fn fallbacks(&self) -> &'static [HardwareEncoderType] {
+        match self {
+            Self::NVENC => &[Self::AMF, Self::QuickSync],
+            Self::AMF => &[Self::QuickSync],
+            _ => &[],
+        }
+    }
+}
+
 /// 硬件编码器配置
 #[derive(Debug, Clone)]
 pub struct HardwareEncoderConfig {
@@ -104,10 +133,29 @@ pub trait HardwareEncoder: Send {
     fn set_bitrate(&mut self, _bitrate_kbps: u32) -> Result<()> {
         Ok(())
     }
+
+    /// 设置之后编码的帧的感兴趣区域 (空列表 = 清除)
+    ///
+    /// 默认实现：不支持 ROI，返回 false
+    fn set_roi(&mut self, _regions: &[RoiRegion]) -> bool {
+        false
+    }
+
+    /// 是否支持直接编码 GPU 纹理 (零拷贝)
+    fn supports_texture_input(&self) -> bool {
+        false
+    }
+
+    /// 编码 GPU 纹理
+    ///
+    /// 默认实现：不支持纹理输入
+    fn encode_texture(&mut self, _frame: &GpuFrame) -> Result<Option<EncodedPacket>> {
+        Err(anyhow!("编码器 {} 不支持 GPU 纹理输入", self.encoder_type()))
+    }
 }
 
-/// 硬件编码器包装器
-pub enum HardwareEncoderWrapper {
+/// 当前使用的编码器实现
+enum EncoderBackend {
     #[cfg(target_os = "windows")]
     NVENC(super::nvenc::NvencEncoder),
     #[cfg(target_os = "windows")]
@@ -119,45 +167,26 @@ pub enum HardwareEncoderWrapper {
     Software(SoftwareEncoder),
 }
 
-impl HardwareEncoderWrapper {
+impl EncoderBackend {
     /// 自动选择并创建最佳硬件编码器
+    ///
+    /// 按 [`super::probe`] 缓存的能力只尝试能编码该尺寸的编码器 (NVENC 延迟最低，
+    /// VideoToolbox 功耗最低，其次 AMF、Quick Sync)，都不可用时回退到软件编码
     pub fn auto_select(width: u32, height: u32, config: HardwareEncoderConfig) -> Result<Self> {
         tracing::info!("自动选择硬件编码器...");
 
-        // 优先级顺序：
-        // 1. NVIDIA NVENC - 最低延迟 <5ms
-        // 2. Apple VideoToolbox - 超低功耗
-        // 3. AMD AMF
-        // 4. Intel Quick Sync
-        // 5. 软件编码
-
-        #[cfg(target_os = "windows")]
-        {
-            // 尝试 NVENC
-            if Self::is_nvenc_available() {
-                tracing::info!("选择编码器: NVIDIA NVENC");
-                return Ok(Self::NVENC(super::nvenc::NvencEncoder::new(width, height, config)?));
-            }
-
-            // 尝试 AMF
-            if Self::is_amf_available() {
-                tracing::info!("选择编码器: AMD AMF");
-                return Ok(Self::AMF(super::amf::AmfEncoder::new(width, height, config)?));
-            }
-
-            // 尝试 Quick Sync
-            if Self::is_quicksync_available() {
-                tracing::info!("选择编码器: Intel Quick Sync");
-                return Ok(Self::QuickSync(super::qsv::QuickSyncEncoder::new(width, height, config)?));
-            }
-        }
-
-        #[cfg(target_os = "macos")]
-        {
-            // 尝试 VideoToolbox
-            if Self::is_videotoolbox_available() {
-                tracing::info!("选择编码器: Apple VideoToolbox");
-                return Ok(Self::VideoToolbox(super::videotoolbox::VideoToolboxEncoder::new(width, height, config)?));
+        let capabilities = super::probe::capabilities();
+        for encoder_type in capabilities.hardware_for(width, height) {
+            match Self::create(encoder_type, width, height, config.clone()) {
+                Ok(encoder) => {
+                    tracing::info!("选择编码器: {}", encoder_type);
+                    return Ok(encoder);
+                }
+                Err(e) => {
+                    // 驱动或硬件变化后缓存已经过时
+                    tracing::warn!("创建 {} 编码器失败: {}，下次启动时重新探测编码器", encoder_type, e);
+                    super::probe::invalidate();
+                }
             }
         }
 
@@ -166,6 +195,33 @@ impl HardwareEncoderWrapper {
         Ok(Self::Software(SoftwareEncoder::new(width, height, config)?))
     }
 
+    /// 创建指定类型的编码器，初始化失败时按 [`HardwareEncoderType::fallbacks`] 回退
+    ///
+    /// 所有候选都失败时返回指定编码器的错误
+    pub fn create_with_fallback(
+        encoder_type: HardwareEncoderType,
+        width: u32,
+        height: u32,
+        config: HardwareEncoderConfig,
+    ) -> Result<Self> {
+        let error = match Self::create(encoder_type, width, height, config.clone()) {
+            Ok(encoder) => return Ok(encoder),
+            Err(e) => e,
+        };
+
+        for &fallback in encoder_type.fallbacks() {
+            tracing::warn!("{} 初始化失败 ({})，尝试 {}", encoder_type, error, fallback);
+            match Self::create(fallback, width, height, config.clone()) {
+                Ok(encoder) => {
+                    tracing::info!("回退到编码器: {}", fallback);
+                    return Ok(encoder);
+                }
+                Err(e) => tracing::debug!("{} 初始化失败: {}", fallback, e),
+            }
+        }
+        Err(error)
+    }
+
     /// 创建指定类型的编码器
     pub fn create(
         encoder_type: HardwareEncoderType,
@@ -202,35 +258,9 @@ impl HardwareEncoderWrapper {
             }
         }
     }
-
-    /// 检查 NVENC 是否可用
-    #[cfg(target_os = "windows")]
-    fn is_nvenc_available() -> bool {
-        super::nvenc::NvencEncoder::is_available()
-    }
-
-    /// 检查 AMF 是否可用
-    #[cfg(target_os = "windows")]
-    fn is_amf_available() -> bool {
-        super::amf::AmfEncoder::is_available()
-    }
-
-    /// 检查 Quick Sync 是否可用
-    #[cfg(target_os = "windows")]
-    fn is_quicksync_available() -> bool {
-        super::qsv::QuickSyncEncoder::is_available()
-    }
-
-    /// 检查 VideoToolbox 是否可用
-    #[cfg(target_os = "macos")]
-    fn is_videotoolbox_available() -> bool {
-        // VideoToolbox 在所有 macOS 上都可用
-        true
-    }
 }
 
-// 为 HardwareEncoderWrapper 实现 HardwareEncoder trait
-impl HardwareEncoder for HardwareEncoderWrapper {
+impl HardwareEncoder for EncoderBackend {
     fn encode(&mut self, frame: &Frame) -> Result<Option<EncodedPacket>> {
         match self {
             #[cfg(target_os = "windows")]
@@ -328,94 +358,305 @@ impl HardwareEncoder for HardwareEncoderWrapper {
             Self::Software(enc) => enc.is_available(),
         }
     }
-}
 
-// Also implement the generic Encoder trait for HardwareEncoderWrapper
-impl crate::encoder::Encoder for HardwareEncoderWrapper {
-    fn encode(&mut self, frame: &Frame) -> Result<Option<EncodedPacket>> {
+    fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()> {
         match self {
             #[cfg(target_os = "windows")]
-            Self::NVENC(enc) => enc.encode(frame),
+            Self::NVENC(enc) => enc.set_bitrate(bitrate_kbps),
             #[cfg(target_os = "windows")]
-            Self::AMF(enc) => enc.encode(frame),
+            Self::AMF(enc) => enc.set_bitrate(bitrate_kbps),
             #[cfg(target_os = "windows")]
-            Self::QuickSync(enc) => enc.encode(frame),
+            Self::QuickSync(enc) => enc.set_bitrate(bitrate_kbps),
             #[cfg(target_os = "macos")]
-            Self::VideoToolbox(enc) => enc.encode(frame),
-            Self::Software(enc) => enc.encode(frame),
+            Self::VideoToolbox(enc) => enc.set_bitrate(bitrate_kbps),
+            Self::Software(enc) => enc.set_bitrate(bitrate_kbps),
         }
     }
 
-    fn request_key_frame(&mut self) -> Result<()> {
+    fn set_roi(&mut self, regions: &[RoiRegion]) -> bool {
         match self {
             #[cfg(target_os = "windows")]
-            Self::NVENC(enc) => enc.request_key_frame(),
+            Self::NVENC(enc) => enc.set_roi(regions),
             #[cfg(target_os = "windows")]
-            Self::AMF(enc) => enc.request_key_frame(),
+            Self::AMF(enc) => enc.set_roi(regions),
             #[cfg(target_os = "windows")]
-            Self::QuickSync(enc) => enc.request_key_frame(),
+            Self::QuickSync(enc) => enc.set_roi(regions),
             #[cfg(target_os = "macos")]
-            Self::VideoToolbox(enc) => enc.request_key_frame(),
-            Self::Software(enc) => enc.request_key_frame(),
+            Self::VideoToolbox(enc) => enc.set_roi(regions),
+            Self::Software(enc) => enc.set_roi(regions),
         }
     }
 
-    fn width(&self) -> u32 {
+    fn supports_texture_input(&self) -> bool {
         match self {
             #[cfg(target_os = "windows")]
-            Self::NVENC(enc) => enc.width(),
+            Self::NVENC(enc) => enc.supports_texture_input(),
             #[cfg(target_os = "windows")]
-            Self::AMF(enc) => enc.width(),
-            #[cfg(target_os = "windows")]
-            Self::QuickSync(enc) => enc.width(),
-            #[cfg(target_os = "macos")]
-            Self::VideoToolbox(enc) => enc.width(),
-            Self::Software(enc) => enc.width(),
+            Self::AMF(enc) => enc.supports_texture_input(),
+            _ => false,
         }
     }
 
-    fn height(&self) -> u32 {
+    #[cfg_attr(not(target_os = "windows"), allow(unused_variables))]
+    fn encode_texture(&mut self, frame: &GpuFrame) -> Result<Option<EncodedPacket>> {
         match self {
             #[cfg(target_os = "windows")]
-            Self::NVENC(enc) => enc.height(),
+            Self::NVENC(enc) => enc.encode_texture(frame),
             #[cfg(target_os = "windows")]
-            Self::AMF(enc) => enc.height(),
-            #[cfg(target_os = "windows")]
-            Self::QuickSync(enc) => enc.height(),
-            #[cfg(target_os = "macos")]
-            Self::VideoToolbox(enc) => enc.height(),
-            Self::Software(enc) => enc.height(),
+            Self::AMF(enc) => enc.encode_texture(frame),
+            _ => Err(anyhow!("编码器 {} 不支持 GPU 纹理输入", self.encoder_type())),
         }
     }
+}
 
-    fn flush(&mut self) -> Result<Option<EncodedPacket>> {
-        match self {
-            #[cfg(target_os = "windows")]
-            Self::NVENC(enc) => enc.flush(),
-            #[cfg(target_os = "windows")]
-            Self::AMF(enc) => enc.flush(),
-            #[cfg(target_os = "windows")]
-            Self::QuickSync(enc) => enc.flush(),
-            #[cfg(target_os = "macos")]
-            Self::VideoToolbox(enc) => enc.flush(),
-            Self::Software(enc) => enc.flush(),
+/// 连续编码失败多少次后切换到下一个编码器
+///
+/// 偶发的单帧错误 (如纹理复制超时) 不触发切换
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+
+/// 运行中切换编码器的记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncoderSwitch {
+    pub from: HardwareEncoderType,
+    pub to: HardwareEncoderType,
+    /// 触发切换的最后一次编码错误
+    pub reason: String,
+}
+
+/// 运行时故障转移状态
+#[derive(Debug, Default)]
+struct FailoverState {
+    consecutive_failures: u32,
+    /// 本次会话中失败过的编码器 (不再切回)
+    failed: Vec<HardwareEncoderType>,
+}
+
+impl FailoverState {
+    fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+    }
+
+    /// 记录一次编码失败，连续失败达到上限时返回 true
+    fn record_failure(&mut self) -> bool {
+        self.consecutive_failures += 1;
+        self.consecutive_failures >= MAX_CONSECUTIVE_FAILURES
+    }
+
+    fn mark_failed(&mut self, encoder_type: HardwareEncoderType) {
+        if !self.failed.contains(&encoder_type) {
+            self.failed.push(encoder_type);
+        }
+    }
+
+    /// 按优先级取下一个没有失败过的编码器，硬件编码器都失败后回退到软件编码
+    fn next_encoder(&self, priority: &[HardwareEncoderType]) -> Option<HardwareEncoderType> {
+        priority
+            .iter()
+            .copied()
+            .chain(std::iter::once(HardwareEncoderType::Software))
+            .find(|encoder_type| !self.failed.contains(encoder_type))
+    }
+}
+
+/// 硬件编码器包装器
+///
+/// 编码器在会话中途连续失败 (驱动重置、GPU 争用) 时，按 [`super::probe`] 的优先级
+/// 透明地换用下一个编码器 (最后回退到软件编码) 并请求关键帧。切换记录由调用方通过
+/// [`Self::take_switch`] 取出上报
+pub struct HardwareEncoderWrapper {
+    backend: EncoderBackend,
+    width: u32,
+    height: u32,
+    /// 重建编码器使用的配置 (码率随 set_bitrate 更新)
+    config: HardwareEncoderConfig,
+    failover: FailoverState,
+    switch: Option<EncoderSwitch>,
+}
+
+impl HardwareEncoderWrapper {
+    /// 自动选择并创建最佳硬件编码器 (见 [`EncoderBackend::auto_select`])
+    pub fn auto_select(width: u32, height: u32, config: HardwareEncoderConfig) -> Result<Self> {
+        let backend = EncoderBackend::auto_select(width, height, config.clone())?;
+        Ok(Self::wrap(backend, width, height, config))
+    }
+
+    /// 创建指定类型的编码器，初始化失败时按 [`HardwareEncoderType::fallbacks`] 回退
+    pub fn create_with_fallback(
+        encoder_type: HardwareEncoderType,
+        width: u32,
+        height: u32,
+        config: HardwareEncoderConfig,
+    ) -> Result<Self> {
+        let backend = EncoderBackend::create_with_fallback(encoder_type, width, height, config.clone())?;
+        Ok(Self::wrap(backend, width, height, config))
+    }
+
+    /// 创建指定类型的编码器
+    pub fn create(
+        encoder_type: HardwareEncoderType,
+        width: u32,
+        height: u32,
+        config: HardwareEncoderConfig,
+    ) -> Result<Self> {
+        let backend = EncoderBackend::create(encoder_type, width, height, config.clone())?;
+        Ok(Self::wrap(backend, width, height, config))
+    }
+
+    fn wrap(backend: EncoderBackend, width: u32, height: u32, config: HardwareEncoderConfig) -> Self {
+        Self {
+            backend,
+            width,
+            height,
+            config,
+            failover: FailoverState::default(),
+            switch: None,
         }
     }
 
+    /// 取出最近一次运行中的编码器切换 (没有切换时为 None)
+    pub fn take_switch(&mut self) -> Option<EncoderSwitch> {
+        self.switch.take()
+    }
+
+    /// 记录编码结果，连续失败达到上限时切换编码器
+    ///
+    /// 返回是否已切换 (调用方用新编码器重试当前帧)
+    fn check_result<T>(&mut self, result: &Result<T>) -> bool {
+        match result {
+            Ok(_) => {
+                self.failover.record_success();
+                false
+            }
+            Err(e) => self.failover.record_failure() && self.fail_over(e),
+        }
+    }
+
+    fn fail_over(&mut self, error: &anyhow::Error) -> bool {
+        let from = self.backend.encoder_type();
+        self.failover.mark_failed(from);
+        self.failover.record_success();
+
+        let priority = super::probe::capabilities().hardware_for(self.width, self.height);
+        while let Some(next) = self.failover.next_encoder(&priority) {
+            match EncoderBackend::create(next, self.width, self.height, self.config.clone()) {
+                Ok(mut backend) => {
+                    // 新编码器从关键帧开始，解码端无需等待下一个 GOP
+                    if let Err(e) = backend.request_key_frame() {
+                        tracing::debug!("{} 请求关键帧失败: {}", next, e);
+                    }
+                    tracing::warn!("{} 连续编码失败 ({})，已切换到 {}", from, error, next);
+                    self.backend = backend;
+                    self.switch = Some(EncoderSwitch {
+                        from,
+                        to: next,
+                        reason: error.to_string(),
+                    });
+                    return true;
+                }
+                Err(e) => {
+                    tracing::warn!("切换到 {} 失败: {}", next, e);
+                    self.failover.mark_failed(next);
+                }
+            }
+        }
+
+        tracing::error!("{} 连续编码失败，没有可切换的编码器", from);
+        false
+    }
+}
+
+impl HardwareEncoder for HardwareEncoderWrapper {
+    fn encode(&mut self, frame: &Frame) -> Result<Option<EncodedPacket>> {
+        let result = self.backend.encode(frame);
+        if self.check_result(&result) {
+            return self.backend.encode(frame);
+        }
+        result
+    }
+
+    fn request_key_frame(&mut self) -> Result<()> {
+        self.backend.request_key_frame()
+    }
+
+    fn width(&self) -> u32 {
+        self.backend.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.backend.height()
+    }
+
+    fn flush(&mut self) -> Result<Option<EncodedPacket>> {
+        self.backend.flush()
+    }
+
+    fn encoder_type(&self) -> HardwareEncoderType {
+        self.backend.encoder_type()
+    }
+
+    fn is_available(&self) -> bool {
+        self.backend.is_available()
+    }
+
     fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()> {
-        // Dynamic bitrate adjustment for hardware encoders
-        // Note: Most hardware encoders don't support runtime bitrate adjustment
-        // This implementation logs the request but doesn't change the bitrate
-        tracing::debug!("请求调整硬件编码器码率: {} kbps (类型: {:?})", bitrate_kbps, self.encoder_type());
+        self.config.bitrate = bitrate_kbps;
+        self.backend.set_bitrate(bitrate_kbps)
+    }
+
+    fn set_roi(&mut self, regions: &[RoiRegion]) -> bool {
+        self.backend.set_roi(regions)
+    }
+
+    fn supports_texture_input(&self) -> bool {
+        self.backend.supports_texture_input()
+    }
+
+    fn encode_texture(&mut self, frame: &GpuFrame) -> Result<Option<EncodedPacket>> {
+        let result = self.backend.encode_texture(frame);
+        if self.check_result(&result) {
+            // 新编码器不支持纹理输入时丢弃这一帧，调用方收到切换记录后关闭 GPU 输出
+            if !self.backend.supports_texture_input() {
+                return Ok(None);
+            }
+            return self.backend.encode_texture(frame);
+        }
+        result
+    }
+}
 
-        // For Software encoder (which wraps H264Encoder), try to actually change bitrate
-        if let Self::Software(enc) = self {
-            return enc.set_bitrate(bitrate_kbps);
+// Also implement the generic Encoder trait for HardwareEncoderWrapper
+impl crate::encoder::Encoder for HardwareEncoderWrapper {
+    fn encode(&mut self, frame: &Frame) -> Result<Option<EncodedPacket>> {
+        // GPU 帧走零拷贝路径
+        if let Some(ref gpu) = frame.gpu {
+            return HardwareEncoder::encode_texture(self, gpu);
         }
+        HardwareEncoder::encode(self, frame)
+    }
 
-        // For hardware encoders, we just log the request
-        // Future implementation could use codec-specific APIs
-        Ok(())
+    fn request_key_frame(&mut self) -> Result<()> {
+        HardwareEncoder::request_key_frame(self)
+    }
+
+    fn width(&self) -> u32 {
+        HardwareEncoder::width(self)
+    }
+
+    fn height(&self) -> u32 {
+        HardwareEncoder::height(self)
+    }
+
+    fn flush(&mut self) -> Result<Option<EncodedPacket>> {
+        HardwareEncoder::flush(self)
+    }
+
+    fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()> {
+        tracing::debug!("请求调整编码器码率: {} kbps (类型: {:?})", bitrate_kbps, self.encoder_type());
+        HardwareEncoder::set_bitrate(self, bitrate_kbps)
+    }
+
+    fn set_roi(&mut self, regions: &[RoiRegion]) -> bool {
+        HardwareEncoder::set_roi(self, regions)
     }
 }
 
@@ -505,6 +746,10 @@ impl HardwareEncoder for SoftwareEncoder {
         }
         Ok(())
     }
+
+    fn set_roi(&mut self, regions: &[RoiRegion]) -> bool {
+        self.inner.as_mut().is_some_and(|encoder| crate::encoder::Encoder::set_roi(encoder, regions))
+    }
 }
 
 #[cfg(not(feature = "h264"))]
@@ -555,6 +800,65 @@ mod tests {
         assert_eq!(config.fps, 30);
     }
 
+    #[test]
+    fn test_fallbacks() {
+        assert_eq!(
+            HardwareEncoderType::NVENC.fallbacks(),
+            &[HardwareEncoderType::AMF, HardwareEncoderType::QuickSync]
+        );
+        assert!(HardwareEncoderType::QuickSync.fallbacks().is_empty());
+        assert!(HardwareEncoderType::Software.fallbacks().is_empty());
+    }
+
+    #[test]
+    fn test_failover_after_consecutive_failures() {
+        let mut state = FailoverState::default();
+        assert!(!state.record_failure());
+        assert!(!state.record_failure());
+        state.record_success();
+
+        // 成功会清零计数，需要重新连续失败
+        for _ in 1..MAX_CONSECUTIVE_FAILURES {
+            assert!(!state.record_failure());
+        }
+        assert!(state.record_failure());
+    }
+
+    #[test]
+    fn test_next_encoder_skips_failed() {
+        let priority = [HardwareEncoderType::NVENC, HardwareEncoderType::AMF, HardwareEncoderType::QuickSync];
+        let mut state = FailoverState::default();
+
+        state.mark_failed(HardwareEncoderType::NVENC);
+        assert_eq!(state.next_encoder(&priority), Some(HardwareEncoderType::AMF));
+
+        state.mark_failed(HardwareEncoderType::AMF);
+        state.mark_failed(HardwareEncoderType::QuickSync);
+        assert_eq!(state.next_encoder(&priority), Some(HardwareEncoderType::Software));
+
+        // 软件编码也失败后不再切换
+        state.mark_failed(HardwareEncoderType::Software);
+        assert_eq!(state.next_encoder(&priority), None);
+        assert_eq!(state.failed.len(), 4);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_gpu_frame_requires_texture_encoder() {
+        use crate::encoder::Encoder;
+
+        let mut encoder =
+            HardwareEncoderWrapper::create(HardwareEncoderType::Software, 64, 64, HardwareEncoderConfig::default())
+                .unwrap();
+        assert!(!HardwareEncoder::supports_texture_input(&encoder));
+
+        // GPU 帧走纹理路径，不支持时报错 (由调用方关闭捕获器的 GPU 输出)，CPU 帧照常编码
+        let gpu = Frame::from_gpu(GpuFrame { width: 64, height: 64 });
+        assert!(Encoder::encode(&mut encoder, &gpu).is_err());
+        assert!(Encoder::encode(&mut encoder, &Frame::new(64, 64)).is_ok());
+        assert!(encoder.take_switch().is_none());
+    }
+
     #[test]
     fn test_encoder_type_display() {
         assert_eq!(format!("{}", HardwareEncoderType::NVENC), "NVIDIA NVENC");
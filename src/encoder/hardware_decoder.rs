@@ -0,0 +1,390 @@
+//! 硬件视频解码
+//!
+//! 与 [`super::hardware`] 对应的解码端，供原生控制端 (`ControlSession`) 使用:
+//! 按平台选择硬件解码器，不可用时回退到 FFmpeg 软件解码
+//!
+//! ## 优先级
+//! - macOS: VideoToolbox
+//! - Windows: NVDEC (CUDA) → DXVA (D3D11VA)
+//! - Linux: NVDEC (CUDA)
+//!
+//! 硬件解码的帧从显存取回 (通常为 NV12)，再按需要转换为 RGBA (原生窗口显示、自动化截图比对)。
+//! 解码器不支持的码流 (如 VideoToolbox 的 VP8) 直接跳过该解码器
+
+// 原生控制端尚未完全集成，标记为允许死代码
+#![allow(dead_code)]
+
+use crate::capture::Frame;
+
+/// 硬件解码器类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum HardwareDecoderType {
+    /// NVIDIA NVDEC (通过 CUDA 设备)
+    NVDEC,
+    /// DirectX 视频加速 (D3D11VA)
+    DXVA,
+    /// Apple VideoToolbox
+    VideoToolbox,
+    /// FFmpeg 软件解码
+    Software,
+    /// 自动选择
+    #[default]
+    Auto,
+}
+
+impl HardwareDecoderType {
+    /// 当前平台按优先级排列的硬件解码器
+    pub fn platform_candidates() -> &'static [HardwareDecoderType] {
+        #[cfg(target_os = "macos")]
+        return &[Self::VideoToolbox];
+        #[cfg(target_os = "windows")]
+        return &[Self::NVDEC, Self::DXVA];
+        #[cfg(target_os = "linux")]
+        return &[Self::NVDEC];
+        #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+        return &[];
+    }
+}
+
+impl std::fmt::Display for HardwareDecoderType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NVDEC => write!(f, "NVIDIA NVDEC"),
+            Self::DXVA => write!(f, "DXVA (D3D11VA)"),
+            Self::VideoToolbox => write!(f, "Apple VideoToolbox"),
+            Self::Software => write!(f, "Software (FFmpeg)"),
+            Self::Auto => write!(f, "Auto"),
+        }
+    }
+}
+
+/// 解码输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecoderOutput {
+    /// RGBA (窗口显示、截图)
+    #[default]
+    Rgba,
+    /// NV12 (交给 GPU 着色器转换时省去一次 CPU 颜色转换)
+    Nv12,
+}
+
+/// 硬件解码器配置
+#[derive(Debug, Clone, Default)]
+pub struct HardwareDecoderConfig {
+    pub decoder_type: HardwareDecoderType,
+    pub output: DecoderOutput,
+}
+
+/// NV12 帧: Y 平面 + UV 交错平面 (半高)
+#[derive(Debug, Clone)]
+pub struct Nv12Frame {
+    pub width: u32,
+    pub height: u32,
+    pub y: Vec<u8>,
+    pub y_stride: usize,
+    pub uv: Vec<u8>,
+    pub uv_stride: usize,
+}
+
+impl Nv12Frame {
+    /// 转换为 RGBA (BT.601 limited range，与编码端 `colorspace` 的系数对应)
+    pub fn to_rgba(&self) -> Frame {
+        let mut frame = Frame::new(self.width, self.height);
+        for row in 0..self.height as usize {
+            let y_row = &self.y[row * self.y_stride..];
+            let uv_row = &self.uv[(row / 2) * self.uv_stride..];
+            let out = &mut frame.data[row * frame.stride..];
+            for col in 0..self.width as usize {
+                let c = 298 * (y_row[col] as i32 - 16) + 128;
+                let d = uv_row[col & !1] as i32 - 128;
+                let e = uv_row[(col & !1) + 1] as i32 - 128;
+                let pixel = &mut out[col * 4..col * 4 + 4];
+                pixel[0] = ((c + 409 * e) >> 8).clamp(0, 255) as u8;
+                pixel[1] = ((c - 100 * d - 208 * e) >> 8).clamp(0, 255) as u8;
+                pixel[2] = ((c + 516 * d) >> 8).clamp(0, 255) as u8;
+                pixel[3] = 255;
+            }
+        }
+        frame
+    }
+}
+
+/// 解码出的一帧
+#[derive(Debug, Clone)]
+pub enum DecodedFrame {
+    Rgba(Frame),
+    Nv12(Nv12Frame),
+}
+
+impl DecodedFrame {
+    pub fn width(&self) -> u32 {
+        match self {
+            Self::Rgba(frame) => frame.width,
+            Self::Nv12(frame) => frame.width,
+        }
+    }
+
+    pub fn height(&self) -> u32 {
+        match self {
+            Self::Rgba(frame) => frame.height,
+            Self::Nv12(frame) => frame.height,
+        }
+    }
+
+    /// 统一转换为 RGBA
+    pub fn into_rgba(self) -> Frame {
+        match self {
+            Self::Rgba(frame) => frame,
+            Self::Nv12(frame) => frame.to_rgba(),
+        }
+    }
+}
+
+#[cfg(feature = "h264")]
+pub use ffmpeg_decoder::HardwareVideoDecoder;
+
+#[cfg(feature = "h264")]
+mod ffmpeg_decoder {
+    use super::{DecodedFrame, DecoderOutput, HardwareDecoderConfig, HardwareDecoderType, Nv12Frame};
+    use crate::capture::Frame;
+    use crate::encoder::decoder::DecoderCodec;
+    use anyhow::{anyhow, Result};
+    use ffmpeg_next as ffmpeg;
+    use ffmpeg_next::ffi;
+    use std::ptr;
+
+    /// 硬件解码器 (FFmpeg hwaccel，软件解码时不创建设备)
+    pub struct HardwareVideoDecoder {
+        decoder_type: HardwareDecoderType,
+        output: DecoderOutput,
+        decoder: ffmpeg::decoder::Video,
+        decoded: ffmpeg::frame::Video,
+        /// 从显存取回的帧
+        transferred: ffmpeg::frame::Video,
+        /// 颜色转换上下文 (按输入尺寸/格式懒创建)
+        scaler: Option<(ffmpeg::software::scaling::Context, ffmpeg::format::Pixel, u32, u32)>,
+    }
+
+    unsafe impl Send for HardwareVideoDecoder {}
+
+    impl HardwareVideoDecoder {
+        /// 按配置创建解码器；`Auto` 依次尝试平台硬件解码器，最后回退到软件解码
+        pub fn new(codec: DecoderCodec, config: HardwareDecoderConfig) -> Result<Self> {
+            if config.decoder_type != HardwareDecoderType::Auto {
+                return Self::open(codec, config.decoder_type, config.output);
+            }
+
+            for &decoder_type in HardwareDecoderType::platform_candidates() {
+                match Self::open(codec, decoder_type, config.output) {
+                    Ok(decoder) => {
+                        tracing::info!("选择解码器: {} ({:?})", decoder_type, codec);
+                        return Ok(decoder);
+                    }
+                    Err(e) => tracing::debug!("{} 不可用: {}", decoder_type, e),
+                }
+            }
+            tracing::info!("回退到软件解码器 ({:?})", codec);
+            Self::open(codec, HardwareDecoderType::Software, config.output)
+        }
+
+        fn open(codec: DecoderCodec, decoder_type: HardwareDecoderType, output: DecoderOutput) -> Result<Self> {
+            ffmpeg::init()?;
+
+            let id = match codec {
+                DecoderCodec::Vp8 => ffmpeg::codec::Id::VP8,
+                DecoderCodec::Vp9 => ffmpeg::codec::Id::VP9,
+                DecoderCodec::H264 => ffmpeg::codec::Id::H264,
+            };
+            let found = ffmpeg::decoder::find(id).ok_or_else(|| anyhow!("找不到 {:?} 解码器", codec))?;
+            let device_type = match decoder_type {
+                HardwareDecoderType::NVDEC => Some(ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_CUDA),
+                HardwareDecoderType::DXVA => Some(ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_D3D11VA),
+                HardwareDecoderType::VideoToolbox => Some(ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VIDEOTOOLBOX),
+                HardwareDecoderType::Software => None,
+                HardwareDecoderType::Auto => return Err(anyhow!("请先选择具体的解码器")),
+            };
+            if let Some(device_type) = device_type {
+                if !unsafe { supports_device(found.as_ptr(), device_type) } {
+                    return Err(anyhow!("{} 不支持解码 {:?}", decoder_type, codec));
+                }
+            }
+
+            let mut context = ffmpeg::codec::context::Context::new_with_codec(found);
+            if let Some(device_type) = device_type {
+                unsafe {
+                    let mut device: *mut ffi::AVBufferRef = ptr::null_mut();
+                    let ret = ffi::av_hwdevice_ctx_create(&mut device, device_type, ptr::null(), ptr::null_mut(), 0);
+                    if ret < 0 {
+                        return Err(anyhow!("创建 {} 设备失败: {}", decoder_type, ffmpeg::Error::from(ret)));
+                    }
+                    // 解码上下文释放时一并释放设备引用；
+                    // 设置后默认的 get_format 会优先选择该设备的硬件像素格式
+                    (*context.as_mut_ptr()).hw_device_ctx = device;
+                }
+            }
+            let decoder = context.decoder().video()?;
+
+            Ok(Self {
+                decoder_type,
+                output,
+                decoder,
+                decoded: ffmpeg::frame::Video::empty(),
+                transferred: ffmpeg::frame::Video::empty(),
+                scaler: None,
+            })
+        }
+
+        /// 实际使用的解码器
+        pub fn decoder_type(&self) -> HardwareDecoderType {
+            self.decoder_type
+        }
+
+        pub fn is_hardware(&self) -> bool {
+            self.decoder_type != HardwareDecoderType::Software
+        }
+
+        /// 解码一个码流包，返回解码出的最后一帧 (数据不足时返回 None)
+        pub fn decode(&mut self, data: &[u8]) -> Result<Option<DecodedFrame>> {
+            self.decoder
+                .send_packet(&ffmpeg::Packet::copy(data))
+                .map_err(|e| anyhow!("解码失败: {}", e))?;
+
+            let mut latest = None;
+            while self.decoder.receive_frame(&mut self.decoded).is_ok() {
+                latest = Some(self.convert()?);
+            }
+            Ok(latest)
+        }
+
+        fn convert(&mut self) -> Result<DecodedFrame> {
+            // 硬件帧: 数据在显存中，先取回到内存
+            let on_device = unsafe { !(*self.decoded.as_ptr()).hw_frames_ctx.is_null() };
+            if on_device {
+                unsafe {
+                    let ret = ffi::av_hwframe_transfer_data(self.transferred.as_mut_ptr(), self.decoded.as_ptr(), 0);
+                    if ret < 0 {
+                        return Err(anyhow!("取回硬件解码帧失败: {}", ffmpeg::Error::from(ret)));
+                    }
+                }
+            }
+            let source = if on_device { &self.transferred } else { &self.decoded };
+
+            let (format, width, height) = (source.format(), source.width(), source.height());
+            let target = match self.output {
+                DecoderOutput::Rgba => ffmpeg::format::Pixel::RGBA,
+                DecoderOutput::Nv12 => ffmpeg::format::Pixel::NV12,
+            };
+            let stale = !matches!(&self.scaler, Some((_, f, w, h)) if (*f, *w, *h) == (format, width, height));
+            if stale {
+                let scaler = ffmpeg::software::scaling::Context::get(
+                    format,
+                    width,
+                    height,
+                    target,
+                    width,
+                    height,
+                    ffmpeg::software::scaling::Flags::BILINEAR,
+                )?;
+                self.scaler = Some((scaler, format, width, height));
+            }
+
+            let mut converted = ffmpeg::frame::Video::empty();
+            if let Some((scaler, ..)) = self.scaler.as_mut() {
+                scaler.run(source, &mut converted)?;
+            }
+            Ok(match self.output {
+                DecoderOutput::Rgba => DecodedFrame::Rgba(Frame::from_raw_data(
+                    width,
+                    height,
+                    converted.data(0).to_vec(),
+                    converted.stride(0),
+                )),
+                DecoderOutput::Nv12 => DecodedFrame::Nv12(Nv12Frame {
+                    width,
+                    height,
+                    y: converted.data(0).to_vec(),
+                    y_stride: converted.stride(0),
+                    uv: converted.data(1).to_vec(),
+                    uv_stride: converted.stride(1),
+                }),
+            })
+        }
+    }
+
+    /// 解码器是否支持通过该类型的硬件设备解码
+    unsafe fn supports_device(codec: *const ffi::AVCodec, device_type: ffi::AVHWDeviceType) -> bool {
+        let mut index = 0;
+        loop {
+            let config = ffi::avcodec_get_hw_config(codec, index);
+            if config.is_null() {
+                return false;
+            }
+            if (*config).device_type == device_type
+                && (*config).methods & ffi::AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX as i32 != 0
+            {
+                return true;
+            }
+            index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nv12_to_rgba() {
+        // 上半黑色，下半白色，色度为中性
+        let frame = Nv12Frame {
+            width: 4,
+            height: 4,
+            y: [[16u8; 4], [16; 4], [235; 4], [235; 4]].concat(),
+            y_stride: 4,
+            uv: vec![128; 8],
+            uv_stride: 4,
+        };
+        let rgba = DecodedFrame::Nv12(frame).into_rgba();
+        assert_eq!((rgba.width, rgba.height), (4, 4));
+        assert_eq!(&rgba.data[..4], &[0, 0, 0, 255]);
+        assert_eq!(&rgba.data[3 * rgba.stride..3 * rgba.stride + 4], &[255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_decoder_type_display() {
+        assert_eq!(HardwareDecoderType::default(), HardwareDecoderType::Auto);
+        assert_eq!(format!("{}", HardwareDecoderType::NVDEC), "NVIDIA NVDEC");
+        assert!(!HardwareDecoderType::platform_candidates().contains(&HardwareDecoderType::Software));
+    }
+
+    #[cfg(feature = "h264")]
+    #[test]
+    fn test_software_decode_round_trip() {
+        use crate::encoder::decoder::DecoderCodec;
+        use crate::encoder::{Encoder, H264Encoder};
+
+        let mut encoder = H264Encoder::new(64, 64, 30, 1000).unwrap();
+        let mut decoder = HardwareVideoDecoder::new(
+            DecoderCodec::H264,
+            HardwareDecoderConfig { decoder_type: HardwareDecoderType::Software, output: DecoderOutput::Nv12 },
+        )
+        .unwrap();
+        assert!(!decoder.is_hardware());
+
+        let mut source = Frame::new(64, 64);
+        source.data.fill(128);
+        let mut decoded = None;
+        for _ in 0..10 {
+            if let Some(packet) = encoder.encode(&source).unwrap() {
+                decoded = decoder.decode(&packet.data).unwrap().or(decoded);
+            }
+        }
+        let decoded = decoded.expect("没有解码出画面");
+        assert!(matches!(decoded, DecodedFrame::Nv12(_)));
+        let rgba = decoded.into_rgba();
+        assert_eq!((rgba.width, rgba.height), (64, 64));
+        // 灰色画面经过编解码后仍接近原值
+        assert!(rgba.data[..4 * 64].chunks(4).all(|pixel| pixel[0].abs_diff(128) < 12));
+    }
+}
@@ -7,6 +7,19 @@
 
 // 硬件编码器抽象层
 pub mod hardware;
+// 编码器能力探测和缓存
+pub mod probe;
+
+// 编码分辨率对齐
+pub mod alignment;
+pub mod colorspace;
+// H.264 码流转换和解析
+pub mod bitstream;
+
+// 视频解码 (控制端)
+#[cfg(feature = "h264")]
+pub mod decoder;
+pub mod hardware_decoder;
 
 // 平台特定的硬件编码器
 #[cfg(target_os = "macos")]
@@ -21,7 +34,15 @@ pub mod amf;
 #[cfg(target_os = "windows")]
 pub mod qsv;
 
+// D3D11 纹理输入 (NVENC / AMF 零拷贝路径)
+#[cfg(all(target_os = "windows", feature = "h264"))]
+pub mod d3d11;
+
+// VP9 时域分层编码
+pub mod vp9;
+
 use crate::capture::Frame;
+use crate::quality::roi_encoder::RoiRegion;
 use anyhow::Result;
 
 /// 编码后的数据包
@@ -57,6 +78,14 @@ pub trait Encoder: Send {
     fn set_bitrate(&mut self, _bitrate_kbps: u32) -> Result<()> {
         Ok(())
     }
+
+    /// 设置之后编码的帧的感兴趣区域 (空列表 = 清除)
+    ///
+    /// 默认实现：不支持 ROI，返回 false
+    /// 返回编码器是否会应用这些区域
+    fn set_roi(&mut self, _regions: &[RoiRegion]) -> bool {
+        false
+    }
 }
 
 /// 简单编码器 - 直接传输原始帧数据
@@ -148,23 +177,23 @@ impl Encoder for SimpleEncoder {
 /// 注意: 需要安装 FFmpeg 开发库
 /// 使用 ffmpeg-next crate
 ///
-/// **重要**: FFmpeg 的 SwsContext 不是 Send，但我们在单线程编码器中使用是安全的
+/// **重要**: FFmpeg 的编码器上下文和帧不是 Send，但我们在单线程编码器中使用是安全的
 #[cfg(feature = "h264")]
 pub struct H264Encoder {
     width: u32,
     height: u32,
-    #[allow(dead_code)]
     fps: u32,
-    #[allow(dead_code)]
     bitrate: u32,
     encoder: Option<ffmpeg::encoder::Video>,
-    sws_context: Option<ffmpeg::software::scaling::Context>,
+    yuv_frame: colorspace::ReusableVideoFrame,
     pts: i64,
     key_frame_interval: u64,
     frame_count: u64,
+    /// 感兴趣区域 (随每帧附加给编码器)
+    roi: Vec<RoiRegion>,
 }
 
-// SAFETY: FFmpeg SwsContext 在单线程使用时是安全的
+// SAFETY: FFmpeg 编码器上下文和帧在单线程使用时是安全的
 #[cfg(feature = "h264")]
 unsafe impl Send for H264Encoder {}
 
@@ -195,6 +224,33 @@ impl H264Encoder {
         // 初始化 FFmpeg (仅第一次)
         ffmpeg::init()?;
 
+        let video_encoder = Self::open_encoder(width, height, fps, bitrate)?;
+
+        // 复用的 YUV420P 帧 (SIMD 颜色转换)
+        let yuv_frame = colorspace::ReusableVideoFrame::new(
+            ffmpeg::format::Pixel::YUV420P,
+            width,
+            height,
+            colorspace::PixelLayout::Rgba,
+        );
+
+        tracing::info!("H.264 编码器创建成功 (ultrafast/zerolatency)");
+        Ok(H264Encoder {
+            width,
+            height,
+            fps,
+            bitrate,
+            encoder: Some(video_encoder),
+            yuv_frame,
+            pts: 0,
+            key_frame_interval: 30,
+            frame_count: 0,
+            roi: Vec::new(),
+        })
+    }
+
+    /// 按给定码控参数打开编码器上下文
+    fn open_encoder(width: u32, height: u32, fps: u32, bitrate: u32) -> Result<ffmpeg::encoder::Video> {
         // 查找 H.264 编码器
         let encoder = ffmpeg::encoder::find(ffmpeg::codec::Id::H264)
             .ok_or_else(|| anyhow!("找不到 H.264 编码器"))?;
@@ -216,85 +272,21 @@ impl H264Encoder {
         opts.set("preset", "ultrafast");
         opts.set("tune", "zerolatency");
         opts.set("rc-lookahead", "0");
+        // 强制 I 帧时输出 IDR，接收端可以直接从该帧开始解码
+        opts.set("forced-idr", "1");
+        // ultrafast 关闭了自适应量化，libx264 只在开启 AQ 时应用 ROI
+        opts.set("aq-mode", "1");
 
         // 打开编码器
-        let video_encoder = encoder_context.open_with(opts)?;
-
-        // 创建 SwsContext 用于 RGBA -> YUV420P 转换
-        let sws_context = ffmpeg::software::scaling::Context::get(
-            ffmpeg::format::Pixel::RGBA,
-            width,
-            height,
-            ffmpeg::format::Pixel::YUV420P,
-            width,
-            height,
-            ffmpeg::software::scaling::Flags::BILINEAR,
-        )?;
-
-        tracing::info!("H.264 编码器创建成功 (ultrafast/zerolatency)");
-        Ok(H264Encoder {
-            width,
-            height,
-            fps,
-            bitrate,
-            encoder: Some(video_encoder),
-            sws_context: Some(sws_context),
-            pts: 0,
-            key_frame_interval: 30,
-            frame_count: 0,
-        })
-    }
-
-    /// 将 RGBA 帧转换为 YUV420P (使用 SwsContext 硬件加速)
-    fn rgba_to_yuv420p_frame(&mut self, rgba: &[u8], width: u32, height: u32) -> Result<ffmpeg::frame::Video> {
-        // 创建源帧 (RGBA)
-        let mut src_frame = ffmpeg::frame::Video::empty();
-        src_frame.set_format(ffmpeg::format::Pixel::RGBA);
-        src_frame.set_width(width);
-        src_frame.set_height(height);
-
-        unsafe {
-            src_frame.alloc(ffmpeg::format::Pixel::RGBA, width, height);
-        }
-
-        // 复制 RGBA 数据到源帧
-        let src_stride = src_frame.stride(0);
-        let src_data = src_frame.data_mut(0);
-        for y in 0..height as usize {
-            let src_row_start = y * (width as usize * 4);
-            let dst_row_start = y * src_stride;
-            let row_len = width as usize * 4;
-            src_data[dst_row_start..dst_row_start + row_len]
-                .copy_from_slice(&rgba[src_row_start..src_row_start + row_len]);
-        }
-
-        // 创建目标帧 (YUV420P)
-        let mut dst_frame = ffmpeg::frame::Video::empty();
-        dst_frame.set_format(ffmpeg::format::Pixel::YUV420P);
-        dst_frame.set_width(width);
-        dst_frame.set_height(height);
-
-        unsafe {
-            dst_frame.alloc(ffmpeg::format::Pixel::YUV420P, width, height);
-        }
-
-        // 使用 SwsContext 进行转换
-        if let Some(ref mut sws) = self.sws_context {
-            sws.run(&src_frame, &mut dst_frame)?;
-        } else {
-            return Err(anyhow!("SwsContext 未初始化"));
-        }
-
-        Ok(dst_frame)
+        Ok(encoder_context.open_with(opts)?)
     }
 }
 
 #[cfg(feature = "h264")]
 impl Encoder for H264Encoder {
     fn encode(&mut self, frame: &Frame) -> Result<Option<EncodedPacket>> {
-        // 阶段 1: 转换为 YUV420P (使用 sws_context)
-        // 注意: 必须先完成此操作再获取 encoder 引用，避免借用冲突
-        let mut yuv_frame = self.rgba_to_yuv420p_frame(&frame.data, frame.width, frame.height)?;
+        // 阶段 1: 转换为 YUV420P (复用帧缓冲区)
+        let yuv_frame = self.yuv_frame.convert(frame)?;
 
         // 设置 PTS
         yuv_frame.set_pts(Some(self.pts));
@@ -303,10 +295,12 @@ impl Encoder for H264Encoder {
 
         // 判断是否为关键帧
         let is_key_frame = self.frame_count % self.key_frame_interval == 0;
+        mark_key_frame(yuv_frame, is_key_frame);
+        attach_roi(yuv_frame, &self.roi);
 
         // 阶段 2: 编码 (使用 encoder)
         let encoder = self.encoder.as_mut().ok_or_else(|| anyhow!("编码器未初始化"))?;
-        encoder.send_frame(&yuv_frame)?;
+        encoder.send_frame(yuv_frame)?;
 
         let mut packet = ffmpeg::packet::Packet::empty();
         match encoder.receive_packet(&mut packet) {
@@ -371,26 +365,125 @@ impl Encoder for H264Encoder {
     }
 
     fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()> {
-        // 尝试动态调整 FFmpeg 编码器的码率
-        // 注意: 不是所有编码器都支持运行时码率调整
-        if let Some(encoder) = self.encoder.as_mut() {
-            // 更新内部记录
-            self.bitrate = bitrate_kbps;
+        if bitrate_kbps == self.bitrate {
+            return Ok(());
+        }
+        let Some(encoder) = self.encoder.as_mut() else {
+            return Ok(());
+        };
+
+        if !apply_bitrate(encoder, bitrate_kbps) {
+            // 编码器不支持运行时重配置，按新码率重新打开 (PTS 连续)
+            self.encoder = None;
+            self.encoder = Some(Self::open_encoder(self.width, self.height, self.fps, bitrate_kbps)?);
+            tracing::debug!("H.264 编码器已按 {} kbps 重新打开", bitrate_kbps);
+        }
+        self.bitrate = bitrate_kbps;
+
+        // 新码控参数从 IDR 开始生效，接收端无需等待下一个 GOP
+        self.request_key_frame()
+    }
+
+    fn set_roi(&mut self, regions: &[RoiRegion]) -> bool {
+        self.roi.clear();
+        self.roi.extend_from_slice(regions);
+        self.encoder.as_ref().is_some_and(encoder_supports_roi)
+    }
+}
 
-            // 尝试设置编码器码率 (以 bps 为单位)
-            let bitrate_bps = bitrate_kbps as usize * 1000;
+/// 是否支持运行时调整码率
+///
+/// 这些编码器在下一次 send_frame 时检测到 AVCodecContext 码率变化会重新配置码控；
+/// 其余编码器 (libvpx、h264_amf、openh264 等) 会忽略该修改，需要重新打开
+pub(crate) fn supports_runtime_bitrate(codec_name: &str) -> bool {
+    matches!(codec_name, "libx264" | "h264_nvenc" | "h264_qsv")
+}
 
-            // 通过 FFmpeg 的全局质量/码率设置来调整
-            // 注意: 这种方式对 x264 等编码器可能不会立即生效
-            tracing::debug!("尝试调整 H.264 编码器码率: {} kbps", bitrate_kbps);
+/// 运行时调整已打开的 FFmpeg 编码器码率
+///
+/// # 返回
+/// 编码器不支持运行时重配置时返回 false，调用方需要按新码率重新打开编码器
+#[cfg(feature = "h264")]
+pub(crate) fn apply_bitrate(encoder: &mut ffmpeg::encoder::Video, bitrate_kbps: u32) -> bool {
+    let codec_name = encoder.codec().map(|codec| codec.name().to_string()).unwrap_or_default();
+    if !supports_runtime_bitrate(&codec_name) {
+        return false;
+    }
 
-            // FFmpeg 的编码器通常不支持运行时码率调整
-            // 这里我们仅记录码率变化，实际效果取决于编码器实现
+    let bitrate_bps = bitrate_kbps as i64 * 1000;
+    unsafe {
+        let ctx = encoder.as_mut_ptr();
+        (*ctx).bit_rate = bitrate_bps;
+        // CBR 模式下峰值码率和 VBV 缓冲区需要同步调整，否则仍按旧码率限速
+        if (*ctx).rc_max_rate > 0 {
+            (*ctx).rc_max_rate = bitrate_bps;
+        }
+        if (*ctx).rc_buffer_size > 0 {
+            (*ctx).rc_buffer_size = bitrate_bps.min(i32::MAX as i64) as i32;
+        }
+    }
+    tracing::debug!("编码器码率已调整: {} kbps ({})", bitrate_kbps, codec_name);
+    true
+}
+
+/// 是否会应用帧附加的 ROI 数据 (`AV_FRAME_DATA_REGIONS_OF_INTEREST`)
+///
+/// 其余编码器忽略 ROI 数据: FFmpeg 的 h264_nvenc / h264_amf 不把它转换成
+/// qpDeltaMap 等按宏块的 QP 偏移，openh264 没有相应接口
+pub(crate) fn supports_roi(codec_name: &str) -> bool {
+    matches!(codec_name, "libx264" | "libx265" | "libvpx" | "libvpx-vp9" | "h264_qsv" | "hevc_qsv")
+}
+
+#[cfg(feature = "h264")]
+pub(crate) fn encoder_supports_roi(encoder: &ffmpeg::encoder::Video) -> bool {
+    encoder.codec().is_some_and(|codec| supports_roi(codec.name()))
+}
+
+/// 把 ROI 区域附加到输入帧 (替换上一帧留下的区域)，区域为空时只清除
+#[cfg(feature = "h264")]
+pub(crate) fn attach_roi(frame: &mut ffmpeg::frame::Video, regions: &[RoiRegion]) {
+    use ffmpeg::ffi;
+
+    let kind = ffi::AVFrameSideDataType::AV_FRAME_DATA_REGIONS_OF_INTEREST;
+    unsafe {
+        let ptr = frame.as_mut_ptr();
+        ffi::av_frame_remove_side_data(ptr, kind);
+        if regions.is_empty() {
+            return;
+        }
+
+        let size = std::mem::size_of::<ffi::AVRegionOfInterest>();
+        let side_data = ffi::av_frame_new_side_data(ptr, kind, (size * regions.len()) as _);
+        if side_data.is_null() {
+            tracing::debug!("分配 ROI 附加数据失败");
+            return;
+        }
+        let rois = (*side_data).data as *mut ffi::AVRegionOfInterest;
+        for (index, region) in regions.iter().enumerate() {
+            rois.add(index).write(ffi::AVRegionOfInterest {
+                self_size: size as u32,
+                top: region.top as i32,
+                bottom: region.bottom as i32,
+                left: region.left as i32,
+                right: region.right as i32,
+                qoffset: ffi::AVRational { num: (region.qoffset * 1000.0).round() as i32, den: 1000 },
+            });
         }
-        Ok(())
     }
 }
 
+/// 标记输入帧的图像类型
+///
+/// 设置为 I 帧时编码器强制输出关键帧；否则交由编码器按 GOP 自行决定
+#[cfg(feature = "h264")]
+pub(crate) fn mark_key_frame(frame: &mut ffmpeg::frame::Video, key_frame: bool) {
+    frame.set_kind(if key_frame {
+        ffmpeg::picture::Type::I
+    } else {
+        ffmpeg::picture::Type::None
+    });
+}
+
 /// H264Encoder 类型别名 (当 h264 feature 未启用时使用 SimpleEncoder)
 #[cfg(not(feature = "h264"))]
 pub type H264Encoder = SimpleEncoder;
@@ -402,13 +495,15 @@ pub type H264Encoder = SimpleEncoder;
 pub struct VP8Encoder {
     width: u32,
     height: u32,
-    #[allow(dead_code)]
     fps: u32,
+    bitrate: u32,
     encoder: Option<ffmpeg::encoder::Video>,
-    sws_context: Option<ffmpeg::software::scaling::Context>,
+    yuv_frame: colorspace::ReusableVideoFrame,
     pts: i64,
     key_frame_interval: u64,
     frame_count: u64,
+    /// 感兴趣区域 (随每帧附加给编码器)
+    roi: Vec<RoiRegion>,
 }
 
 #[cfg(feature = "h264")]
@@ -429,6 +524,33 @@ impl VP8Encoder {
         // 初始化 FFmpeg (仅第一次)
         ffmpeg::init()?;
 
+        let video_encoder = Self::open_encoder(width, height, fps, bitrate)?;
+
+        // 复用的 YUV420P 帧 (SIMD 颜色转换)
+        let yuv_frame = colorspace::ReusableVideoFrame::new(
+            ffmpeg::format::Pixel::YUV420P,
+            width,
+            height,
+            colorspace::PixelLayout::Rgba,
+        );
+
+        tracing::info!("VP8 编码器创建成功 (realtime mode)");
+        Ok(VP8Encoder {
+            width,
+            height,
+            fps,
+            bitrate,
+            encoder: Some(video_encoder),
+            yuv_frame,
+            pts: 0,
+            key_frame_interval: 30,
+            frame_count: 0,
+            roi: Vec::new(),
+        })
+    }
+
+    /// 按给定码控参数打开编码器上下文
+    fn open_encoder(width: u32, height: u32, fps: u32, bitrate: u32) -> Result<ffmpeg::encoder::Video> {
         // 查找 VP8 编码器 (libvpx)
         let encoder = ffmpeg::encoder::find(ffmpeg::codec::Id::VP8)
             .ok_or_else(|| anyhow!("找不到 VP8 编码器 (需要 libvpx)"))?;
@@ -453,45 +575,24 @@ impl VP8Encoder {
         opts.set("error-resilient", "1");
 
         // 打开编码器
-        let video_encoder = encoder_context.open_with(opts)?;
-
-        // 创建 SwsContext 用于 RGBA -> YUV420P 转换
-        let sws_context = ffmpeg::software::scaling::Context::get(
-            ffmpeg::format::Pixel::RGBA,
-            width,
-            height,
-            ffmpeg::format::Pixel::YUV420P,
-            width,
-            height,
-            ffmpeg::software::scaling::Flags::BILINEAR,
-        )?;
-
-        tracing::info!("VP8 编码器创建成功 (realtime mode)");
-        Ok(VP8Encoder {
-            width,
-            height,
-            fps,
-            encoder: Some(video_encoder),
-            sws_context: Some(sws_context),
-            pts: 0,
-            key_frame_interval: 30,
-            frame_count: 0,
-        })
+        Ok(encoder_context.open_with(opts)?)
     }
 
     /// 编码帧并返回 VP8 数据
     pub fn encode_frame(&mut self, frame: &Frame) -> Result<Option<Vec<u8>>> {
         // 转换为 YUV420P
-        let mut yuv_frame = self.rgba_to_yuv420p_frame(&frame.data, frame.width, frame.height)?;
+        let yuv_frame = self.yuv_frame.convert(frame)?;
 
         // 设置 PTS
         yuv_frame.set_pts(Some(self.pts));
         self.pts += 1;
         self.frame_count += 1;
+        mark_key_frame(yuv_frame, self.frame_count % self.key_frame_interval == 0);
+        attach_roi(yuv_frame, &self.roi);
 
         // 编码
         let encoder = self.encoder.as_mut().ok_or_else(|| anyhow!("编码器未初始化"))?;
-        encoder.send_frame(&yuv_frame)?;
+        encoder.send_frame(yuv_frame)?;
 
         let mut packet = ffmpeg::packet::Packet::empty();
         match encoder.receive_packet(&mut packet) {
@@ -513,51 +614,45 @@ impl VP8Encoder {
         }
     }
 
-    /// 将 RGBA 帧转换为 YUV420P
-    fn rgba_to_yuv420p_frame(&mut self, rgba: &[u8], width: u32, height: u32) -> Result<ffmpeg::frame::Video> {
-        let mut src_frame = ffmpeg::frame::Video::empty();
-        src_frame.set_format(ffmpeg::format::Pixel::RGBA);
-        src_frame.set_width(width);
-        src_frame.set_height(height);
-
-        unsafe {
-            src_frame.alloc(ffmpeg::format::Pixel::RGBA, width, height);
-        }
+    pub fn width(&self) -> u32 {
+        self.width
+    }
 
-        let src_stride = src_frame.stride(0);
-        let src_data = src_frame.data_mut(0);
-        for y in 0..height as usize {
-            let src_row_start = y * (width as usize * 4);
-            let dst_row_start = y * src_stride;
-            let row_len = width as usize * 4;
-            src_data[dst_row_start..dst_row_start + row_len]
-                .copy_from_slice(&rgba[src_row_start..src_row_start + row_len]);
-        }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
 
-        let mut dst_frame = ffmpeg::frame::Video::empty();
-        dst_frame.set_format(ffmpeg::format::Pixel::YUV420P);
-        dst_frame.set_width(width);
-        dst_frame.set_height(height);
+    /// 请求下一帧为关键帧
+    pub fn request_key_frame(&mut self) {
+        self.frame_count = self.key_frame_interval - 1;
+    }
 
-        unsafe {
-            dst_frame.alloc(ffmpeg::format::Pixel::YUV420P, width, height);
+    /// 设置码率 (kbps)
+    ///
+    /// libvpx 不支持运行时重配置，按新码率重新打开编码器并从关键帧开始
+    pub fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()> {
+        if bitrate_kbps == self.bitrate {
+            return Ok(());
         }
-
-        if let Some(ref mut sws) = self.sws_context {
-            sws.run(&src_frame, &mut dst_frame)?;
-        } else {
-            return Err(anyhow!("SwsContext 未初始化"));
+        let Some(encoder) = self.encoder.as_mut() else {
+            return Ok(());
+        };
+
+        if !apply_bitrate(encoder, bitrate_kbps) {
+            self.encoder = None;
+            self.encoder = Some(Self::open_encoder(self.width, self.height, self.fps, bitrate_kbps)?);
+            tracing::debug!("VP8 编码器已按 {} kbps 重新打开", bitrate_kbps);
         }
-
-        Ok(dst_frame)
-    }
-
-    pub fn width(&self) -> u32 {
-        self.width
+        self.bitrate = bitrate_kbps;
+        self.request_key_frame();
+        Ok(())
     }
 
-    pub fn height(&self) -> u32 {
-        self.height
+    /// 设置之后编码的帧的感兴趣区域，返回 libvpx 是否会应用
+    pub fn set_roi(&mut self, regions: &[RoiRegion]) -> bool {
+        self.roi.clear();
+        self.roi.extend_from_slice(regions);
+        self.encoder.as_ref().is_some_and(encoder_supports_roi)
     }
 }
 
@@ -572,6 +667,17 @@ impl VP8Encoder {
     }
 }
 
+/// 本机编译进来的视频编码器对应的 codec 名称
+///
+/// VP8、VP9 和 H.264 编码器都基于 FFmpeg，需要启用 `h264` feature
+pub fn compiled_codecs() -> Vec<&'static str> {
+    if cfg!(feature = "h264") {
+        vec!["VP8", "VP9", "H264"]
+    } else {
+        Vec::new()
+    }
+}
+
 /// 创建编码器
 ///
 /// # 参数
@@ -631,4 +737,13 @@ mod tests {
             assert!(packet.data.len() > 24); // 至少包含头部
         }
     }
+
+    #[test]
+    fn test_runtime_bitrate_support() {
+        assert!(supports_runtime_bitrate("libx264"));
+        assert!(supports_runtime_bitrate("h264_nvenc"));
+        assert!(!supports_runtime_bitrate("libvpx"));
+        assert!(!supports_runtime_bitrate("h264_amf"));
+        assert!(!supports_runtime_bitrate(""));
+    }
 }
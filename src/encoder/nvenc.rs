@@ -17,14 +17,49 @@
 //! - NVIDIA GPU with NVENC support
 //! - NVIDIA Graphics Driver 470.x 或更新
 //! - FFmpeg with h264_nvenc codec
+//!
+//! ## 低延迟配置
+//! - 无 B 帧、无 lookahead、输出不缓冲 (`delay=0`)，每帧送入后立即取回码流
+//! - `forced-idr`: 请求的关键帧以 IDR 编码，新加入的 Viewer 可以立即解码
+//! - 码率在运行时通过 `nvEncReconfigureEncoder` 调整，不重建会话也不插入关键帧
 
+#[cfg(target_os = "windows")]
+use crate::capture::GpuFrame;
 #[cfg(target_os = "windows")]
 use crate::encoder::{EncodedPacket, Frame};
 #[cfg(target_os = "windows")]
-use crate::encoder::hardware::{HardwareEncoder, HardwareEncoderConfig, HardwareEncoderType};
+use crate::encoder::hardware::{EncoderPreset, HardwareEncoder, HardwareEncoderConfig, HardwareEncoderType};
 #[cfg(target_os = "windows")]
 use anyhow::{anyhow, Result};
 
+/// 关键帧间隔 (帧)
+#[cfg(target_os = "windows")]
+const KEY_FRAME_INTERVAL: u64 = 30;
+
+/// h264_nvenc 的低延迟选项 (CPU 帧和 D3D11 纹理两条路径共用)
+///
+/// 预设越快画质越低: 超低延迟用 p1 + ull，画质优先时放宽到 p6 + hq。
+/// 场景切换不自动插入 I 帧，避免码率突增，关键帧只按间隔或请求产生
+#[cfg(target_os = "windows")]
+fn low_latency_options(preset: EncoderPreset) -> Vec<(&'static str, &'static str)> {
+    let (nv_preset, tune) = match preset {
+        EncoderPreset::UltraLowLatency => ("p1", "ull"),
+        EncoderPreset::LowLatency => ("p2", "ull"),
+        EncoderPreset::Balanced => ("p4", "ll"),
+        EncoderPreset::Quality => ("p6", "hq"),
+    };
+    vec![
+        ("preset", nv_preset),
+        ("tune", tune),
+        ("rc", "cbr"),
+        ("zerolatency", "1"),
+        ("delay", "0"),
+        ("rc-lookahead", "0"),
+        ("forced-idr", "1"),
+        ("no-scenecut", "1"),
+    ]
+}
+
 /// NVIDIA NVENC 编码器
 ///
 /// 使用 NVIDIA NVENC 进行 H.264 硬件编码
@@ -36,10 +71,16 @@ pub struct NvencEncoder {
     #[cfg(feature = "h264")]
     inner: Option<ffmpeg_next::encoder::Video>,
     #[cfg(feature = "h264")]
-    sws_context: Option<ffmpeg_next::software::scaling::Context>,
+    yuv_frame: crate::encoder::colorspace::ReusableVideoFrame,
     pts: i64,
     key_frame_interval: u64,
     frame_count: u64,
+    /// 感兴趣区域 (随每帧附加给编码器)
+    #[cfg(feature = "h264")]
+    roi: Vec<crate::quality::roi_encoder::RoiRegion>,
+    /// D3D11 纹理输入编码上下文 (零拷贝路径，首次收到 GPU 帧时创建)
+    #[cfg(feature = "h264")]
+    texture_encoder: Option<crate::encoder::d3d11::D3D11TextureEncoder>,
 }
 
 #[cfg(target_os = "windows")]
@@ -67,45 +108,45 @@ impl NvencEncoder {
             let mut encoder_context = context.encoder().video()?;
 
             encoder_context.set_bit_rate((config.bitrate * 1000) as usize);
+            // CBR 峰值码率随 set_bitrate 一起调整
+            encoder_context.set_max_bit_rate((config.bitrate * 1000) as usize);
             encoder_context.set_width(width);
             encoder_context.set_height(height);
             encoder_context.set_frame_rate(Some(ffmpeg_next::Rational(config.fps as i32, 1)));
             encoder_context.set_time_base(ffmpeg_next::Rational(1, config.fps as i32));
-            encoder_context.set_gop(30);
+            encoder_context.set_gop(KEY_FRAME_INTERVAL as u32);
+            encoder_context.set_max_b_frames(0);
             encoder_context.set_format(ffmpeg_next::format::Pixel::NV12); // NVENC 使用 NV12 格式
 
             // NVENC 特定选项
             let mut opts = ffmpeg_next::Dictionary::new();
-            opts.set("preset", "p1");  // 最快预设 (p1: fastest, p7: slowest)
-            opts.set("tune", "ll");    // 低延迟
-            opts.set("rc", "cbr");     // 恒定码率
-            opts.set("b_max", "0");    // 禁用 B 帧（降低延迟）
-            opts.set("zerolatency", "1"); // 零延迟
+            for (key, value) in low_latency_options(config.preset) {
+                opts.set(key, value);
+            }
 
             // 打开编码器
             let video_encoder = encoder_context.open_with(opts)?;
 
-            // 创建 SwsContext 用于 RGBA -> NV12 转换
-            let sws_context = ffmpeg_next::software::scaling::Context::get(
-                ffmpeg_next::format::Pixel::RGBA,
-                width,
-                height,
+            // 复用的 NV12 帧 (SIMD 颜色转换)
+            let yuv_frame = crate::encoder::colorspace::ReusableVideoFrame::new(
                 ffmpeg_next::format::Pixel::NV12,
                 width,
                 height,
-                ffmpeg_next::software::scaling::Flags::BILINEAR,
-            )?;
+                crate::encoder::colorspace::PixelLayout::Rgba,
+            );
 
-            tracing::info!("NVENC 编码器创建成功 (p1/ll 预设)");
+            tracing::info!("NVENC 编码器创建成功 ({:?} 预设)", config.preset);
             Ok(Self {
                 width,
                 height,
                 config,
                 inner: Some(video_encoder),
-                sws_context: Some(sws_context),
+                yuv_frame,
                 pts: 0,
-                key_frame_interval: 30,
+                key_frame_interval: KEY_FRAME_INTERVAL,
                 frame_count: 0,
+                roi: Vec::new(),
+                texture_encoder: None,
             })
         }
 
@@ -134,49 +175,6 @@ impl NvencEncoder {
         }
     }
 
-    /// 将 RGBA 帧转换为 NV12 格式
-    #[cfg(feature = "h264")]
-    fn rgba_to_nv12_frame(&mut self, rgba: &[u8], width: u32, height: u32) -> Result<ffmpeg_next::frame::Video> {
-        // 创建源帧 (RGBA)
-        let mut src_frame = ffmpeg_next::frame::Video::empty();
-        src_frame.set_format(ffmpeg_next::format::Pixel::RGBA);
-        src_frame.set_width(width);
-        src_frame.set_height(height);
-
-        unsafe {
-            src_frame.alloc(ffmpeg_next::format::Pixel::RGBA, width, height);
-        }
-
-        // 复制 RGBA 数据到源帧
-        let src_stride = src_frame.stride(0);
-        let src_data = src_frame.data_mut(0);
-        for y in 0..height as usize {
-            let src_row_start = y * (width as usize * 4);
-            let dst_row_start = y * src_stride;
-            let row_len = width as usize * 4;
-            src_data[dst_row_start..dst_row_start + row_len]
-                .copy_from_slice(&rgba[src_row_start..src_row_start + row_len]);
-        }
-
-        // 创建目标帧 (NV12)
-        let mut dst_frame = ffmpeg_next::frame::Video::empty();
-        dst_frame.set_format(ffmpeg_next::format::Pixel::NV12);
-        dst_frame.set_width(width);
-        dst_frame.set_height(height);
-
-        unsafe {
-            dst_frame.alloc(ffmpeg_next::format::Pixel::NV12, width, height);
-        }
-
-        // 使用 SwsContext 进行转换
-        if let Some(ref mut sws) = self.sws_context {
-            sws.run(&src_frame, &mut dst_frame)?;
-        } else {
-            return Err(anyhow!("SwsContext 未初始化"));
-        }
-
-        Ok(dst_frame)
-    }
 }
 
 #[cfg(target_os = "windows")]
@@ -184,19 +182,21 @@ impl HardwareEncoder for NvencEncoder {
     #[cfg(feature = "h264")]
     fn encode(&mut self, frame: &Frame) -> Result<Option<EncodedPacket>> {
         // 转换为 NV12
-        let mut nv12_frame = self.rgba_to_nv12_frame(&frame.data, frame.width, frame.height)?;
+        let nv12_frame = self.yuv_frame.convert(frame)?;
 
         // 设置 PTS
         nv12_frame.set_pts(Some(self.pts));
         self.pts += 1;
         self.frame_count += 1;
 
-        // 判断是否为关键帧
-        let is_key_frame = self.frame_count % self.key_frame_interval == 0;
+        // 到达间隔或请求了关键帧时强制 IDR (forced-idr)
+        let force_key_frame = self.frame_count % self.key_frame_interval == 0;
+        crate::encoder::mark_key_frame(nv12_frame, force_key_frame);
+        crate::encoder::attach_roi(nv12_frame, &self.roi);
 
         // 编码
         let encoder = self.inner.as_mut().ok_or_else(|| anyhow!("编码器未初始化"))?;
-        encoder.send_frame(&nv12_frame)?;
+        encoder.send_frame(nv12_frame)?;
 
         let mut packet = ffmpeg_next::packet::Packet::empty();
         match encoder.receive_packet(&mut packet) {
@@ -205,7 +205,7 @@ impl HardwareEncoder for NvencEncoder {
                     let data = packet.data().unwrap_or(&[]).to_vec();
                     Ok(Some(EncodedPacket {
                         data,
-                        is_key_frame,
+                        is_key_frame: packet.is_key(),
                         timestamp: frame.timestamp,
                         pts: self.pts,
                     }))
@@ -274,6 +274,49 @@ impl HardwareEncoder for NvencEncoder {
         HardwareEncoderType::NVENC
     }
 
+    fn supports_texture_input(&self) -> bool {
+        cfg!(feature = "h264")
+    }
+
+    #[cfg(feature = "h264")]
+    fn encode_texture(&mut self, frame: &GpuFrame) -> Result<Option<EncodedPacket>> {
+        if frame.width != self.width || frame.height != self.height {
+            return Err(anyhow!(
+                "GPU 帧尺寸 {}x{} 与编码器 {}x{} 不一致",
+                frame.width, frame.height, self.width, self.height
+            ));
+        }
+
+        // 首次使用时基于捕获器的 D3D11 设备创建纹理编码器
+        if self.texture_encoder.is_none() {
+            self.texture_encoder = Some(crate::encoder::d3d11::D3D11TextureEncoder::new(
+                &frame.device,
+                self.width,
+                self.height,
+                &self.config,
+                "h264_nvenc",
+                KEY_FRAME_INTERVAL as u32,
+                &low_latency_options(self.config.preset),
+            )?);
+        }
+
+        self.pts += 1;
+        self.frame_count += 1;
+        let force_key_frame = self.frame_count % self.key_frame_interval == 0;
+
+        let encoder = self.texture_encoder.as_mut()
+            .ok_or_else(|| anyhow!("纹理编码器未初始化"))?;
+
+        Ok(encoder
+            .encode(&frame.texture, self.pts, force_key_frame)?
+            .map(|(data, is_key_frame)| EncodedPacket {
+                data,
+                is_key_frame,
+                timestamp: crate::capture::Frame::current_timestamp(),
+                pts: self.pts,
+            }))
+    }
+
     fn is_available(&self) -> bool {
         #[cfg(feature = "h264")]
         {
@@ -284,6 +327,32 @@ impl HardwareEncoder for NvencEncoder {
             false
         }
     }
+
+    #[cfg(feature = "h264")]
+    fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()> {
+        if bitrate_kbps == self.config.bitrate {
+            return Ok(());
+        }
+        if let Some(encoder) = self.inner.as_mut() {
+            crate::encoder::apply_bitrate(encoder, bitrate_kbps);
+        }
+        if let Some(encoder) = self.texture_encoder.as_mut() {
+            encoder.set_bitrate(bitrate_kbps);
+        }
+        self.config.bitrate = bitrate_kbps;
+
+        // h264_nvenc 在下一帧通过 nvEncReconfigureEncoder 切换码控，不需要关键帧
+        Ok(())
+    }
+
+    /// ROI 只作用于 CPU 帧路径，D3D11 纹理路径不附加；
+    /// FFmpeg 的 h264_nvenc 不读取 ROI 附加数据 (qpDeltaMap 需要直接使用 NVENC SDK)，这里返回 false
+    #[cfg(feature = "h264")]
+    fn set_roi(&mut self, regions: &[crate::quality::roi_encoder::RoiRegion]) -> bool {
+        self.roi.clear();
+        self.roi.extend_from_slice(regions);
+        self.inner.as_ref().is_some_and(crate::encoder::encoder_supports_roi)
+    }
 }
 
 #[cfg(not(target_os = "windows"))]
@@ -309,6 +378,22 @@ mod tests {
         // 结果取决于硬件
     }
 
+    #[test]
+    #[cfg(target_os = "windows")]
+    fn test_low_latency_options() {
+        for preset in [
+            EncoderPreset::UltraLowLatency,
+            EncoderPreset::LowLatency,
+            EncoderPreset::Balanced,
+            EncoderPreset::Quality,
+        ] {
+            let options = low_latency_options(preset);
+            assert!(options.contains(&("forced-idr", "1")));
+            assert!(options.contains(&("delay", "0")));
+        }
+        assert!(low_latency_options(EncoderPreset::UltraLowLatency).contains(&("preset", "p1")));
+    }
+
     #[test]
     fn test_config_validation() {
         #[cfg(all(target_os = "windows", feature = "h264"))]
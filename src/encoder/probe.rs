@@ -0,0 +1,378 @@
+//! 硬件编码器能力探测
+//!
+//! 自动选择编码器时逐个尝试构造，驱动缺失或初始化很慢时会话开始要等很久。
+//! 探测只运行一次: 对每种编码器实际打开小尺寸的编码会话，记录支持的 codec、最大分辨率、
+//! B 帧和低延迟模式，以及显卡驱动版本，结果缓存在配置文件所在目录的 `encoders.json`。
+//!
+//! sscontrol 或 FFmpeg 版本变化、缓存超过 [`CACHE_MAX_AGE`] 或缓存中的编码器创建失败时重新探测；
+//! `sscontrol encoders list --refresh` 手动重新探测
+
+use super::hardware::HardwareEncoderType;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 缓存有效期 (驱动更新后最迟在此之后重新探测)
+pub const CACHE_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// 探测最大分辨率时依次尝试的尺寸
+const PROBE_SIZES: &[(u32, u32)] =
+    &[(7680, 4320), (4096, 2160), (3840, 2160), (2560, 1440), (1920, 1080), (1280, 720)];
+
+/// 自动选择时硬件编码器的优先级 (NVENC 延迟最低，VideoToolbox 功耗最低)
+pub const PRIORITY: &[HardwareEncoderType] = &[
+    HardwareEncoderType::NVENC,
+    HardwareEncoderType::VideoToolbox,
+    HardwareEncoderType::AMF,
+    HardwareEncoderType::QuickSync,
+];
+
+/// 一种编码器在 FFmpeg 中的实现
+struct EncoderSpec {
+    kind: HardwareEncoderType,
+    /// 各 codec 对应的 FFmpeg 编码器 (第一个为 sscontrol 使用的 H.264 编码器)
+    codecs: &'static [(&'static str, &'static str)],
+    /// 低延迟模式的选项 (能以这些选项打开即视为支持)
+    low_latency: &'static [(&'static str, &'static str)],
+}
+
+/// 当前平台可以探测的编码器
+// 按平台 cfg 追加，不适合写成 vec![]
+#[allow(clippy::vec_init_then_push)]
+fn specs() -> Vec<EncoderSpec> {
+    let mut specs = Vec::new();
+    #[cfg(target_os = "windows")]
+    specs.extend([
+        EncoderSpec {
+            kind: HardwareEncoderType::NVENC,
+            codecs: &[("h264", "h264_nvenc"), ("hevc", "hevc_nvenc"), ("av1", "av1_nvenc")],
+            low_latency: &[("tune", "ll"), ("zerolatency", "1")],
+        },
+        EncoderSpec {
+            kind: HardwareEncoderType::AMF,
+            codecs: &[("h264", "h264_amf"), ("hevc", "hevc_amf"), ("av1", "av1_amf")],
+            low_latency: &[("usage", "lowlatency")],
+        },
+        EncoderSpec {
+            kind: HardwareEncoderType::QuickSync,
+            codecs: &[("h264", "h264_qsv"), ("hevc", "hevc_qsv"), ("av1", "av1_qsv")],
+            low_latency: &[("preset", "veryfast"), ("low_power", "1")],
+        },
+    ]);
+    #[cfg(target_os = "macos")]
+    specs.push(EncoderSpec {
+        kind: HardwareEncoderType::VideoToolbox,
+        codecs: &[("h264", "h264_videotoolbox"), ("hevc", "hevc_videotoolbox")],
+        low_latency: &[("realtime", "1")],
+    });
+    specs.push(EncoderSpec {
+        kind: HardwareEncoderType::Software,
+        codecs: &[("h264", "libx264"), ("vp8", "libvpx"), ("vp9", "libvpx-vp9")],
+        low_latency: &[("tune", "zerolatency")],
+    });
+    specs
+}
+
+/// 一种编码器的探测结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncoderCapabilities {
+    /// `--encoder` 的取值 (如 "nvenc")
+    pub id: String,
+    pub name: String,
+    /// 能否打开 H.264 编码会话
+    pub available: bool,
+    /// 可用的 codec (h264/hevc/av1/vp8/vp9)
+    pub codecs: Vec<String>,
+    /// 能打开的最大分辨率
+    pub max_width: u32,
+    pub max_height: u32,
+    /// 支持 B 帧
+    pub b_frames: bool,
+    /// 支持低延迟模式
+    pub low_latency: bool,
+    /// 不可用的原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl EncoderCapabilities {
+    fn unavailable(kind: HardwareEncoderType, error: impl Into<String>) -> Self {
+        Self {
+            id: kind.id().to_string(),
+            name: kind.to_string(),
+            available: false,
+            codecs: Vec::new(),
+            max_width: 0,
+            max_height: 0,
+            b_frames: false,
+            low_latency: false,
+            error: Some(error.into()),
+        }
+    }
+
+    /// 能否编码该尺寸
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        self.available && width <= self.max_width && height <= self.max_height
+    }
+}
+
+/// 一次完整的探测结果 (即 `encoders.json` 的内容)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeReport {
+    /// sscontrol 和 FFmpeg 版本，变化后缓存失效
+    pub fingerprint: String,
+    /// 探测时间 (Unix 秒)
+    pub probed_at: u64,
+    /// 显卡和驱动版本
+    #[serde(default)]
+    pub drivers: Vec<String>,
+    pub encoders: Vec<EncoderCapabilities>,
+}
+
+impl ProbeReport {
+    pub fn get(&self, kind: HardwareEncoderType) -> Option<&EncoderCapabilities> {
+        self.encoders.iter().find(|encoder| encoder.id == kind.id())
+    }
+
+    /// 按优先级返回能编码该尺寸的硬件编码器
+    pub fn hardware_for(&self, width: u32, height: u32) -> Vec<HardwareEncoderType> {
+        PRIORITY
+            .iter()
+            .copied()
+            .filter(|kind| self.get(*kind).is_some_and(|encoder| encoder.fits(width, height)))
+            .collect()
+    }
+
+    /// 缓存是否仍可使用
+    fn is_fresh(&self, fingerprint: &str, now: u64) -> bool {
+        self.fingerprint == fingerprint && now.saturating_sub(self.probed_at) < CACHE_MAX_AGE.as_secs()
+    }
+}
+
+/// 缓存文件路径 (配置文件所在目录下的 `encoders.json`)
+pub fn cache_path() -> PathBuf {
+    let config_path = crate::config::Config::get_config_path(None);
+    Path::new(&config_path)
+        .parent()
+        .map(|parent| parent.join("encoders.json"))
+        .unwrap_or_else(|| PathBuf::from("encoders.json"))
+}
+
+/// 当前的 sscontrol 和 FFmpeg 版本
+fn fingerprint() -> String {
+    #[cfg(feature = "h264")]
+    let ffmpeg = {
+        let version = ffmpeg_next::util::version();
+        format!("libavutil {}.{}.{}", version >> 16, (version >> 8) & 0xff, version & 0xff)
+    };
+    #[cfg(not(feature = "h264"))]
+    let ffmpeg = "no ffmpeg".to_string();
+    format!("sscontrol {} {}-{} {}", env!("CARGO_PKG_VERSION"), std::env::consts::OS, std::env::consts::ARCH, ffmpeg)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// 读取缓存 (不存在、无法解析或已失效时为 None)
+pub fn load_cached(path: &Path) -> Option<ProbeReport> {
+    let text = std::fs::read_to_string(path).ok()?;
+    let report: ProbeReport = serde_json::from_str(&text).ok()?;
+    report.is_fresh(&fingerprint(), unix_now()).then_some(report)
+}
+
+/// 保存缓存
+pub fn save(path: &Path, report: &ProbeReport) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("无法创建目录 {}", parent.display()))?;
+    }
+    let text = serde_json::to_string_pretty(report)?;
+    std::fs::write(path, text).with_context(|| format!("无法写入 {}", path.display()))
+}
+
+/// 探测所有编码器 (每种编码器会实际打开几次编码会话，可能需要数秒)
+pub fn probe() -> ProbeReport {
+    tracing::info!("探测编码器能力...");
+    let encoders = specs().iter().map(probe_encoder).collect();
+    ProbeReport { fingerprint: fingerprint(), probed_at: unix_now(), drivers: driver_versions(), encoders }
+}
+
+#[cfg(feature = "h264")]
+fn probe_encoder(spec: &EncoderSpec) -> EncoderCapabilities {
+    let (_, h264) = spec.codecs[0];
+    if let Err(e) = ffmpeg_next::init() {
+        return EncoderCapabilities::unavailable(spec.kind, format!("FFmpeg 初始化失败: {}", e));
+    }
+    let Some(&(max_width, max_height)) =
+        PROBE_SIZES.iter().find(|(width, height)| try_open(h264, *width, *height, 0, &[]).is_ok())
+    else {
+        let error = try_open(h264, 1280, 720, 0, &[]).err().map_or_else(String::new, |e| e.to_string());
+        tracing::debug!("{} 不可用: {}", spec.kind, error);
+        return EncoderCapabilities::unavailable(spec.kind, error);
+    };
+
+    let codecs = spec
+        .codecs
+        .iter()
+        .filter(|(_, name)| *name == h264 || try_open(name, 1280, 720, 0, &[]).is_ok())
+        .map(|(codec, _)| codec.to_string())
+        .collect();
+    let capabilities = EncoderCapabilities {
+        id: spec.kind.id().to_string(),
+        name: spec.kind.to_string(),
+        available: true,
+        codecs,
+        max_width,
+        max_height,
+        b_frames: try_open(h264, 1280, 720, 2, &[]).is_ok(),
+        low_latency: try_open(h264, 1280, 720, 0, spec.low_latency).is_ok(),
+        error: None,
+    };
+    tracing::debug!("{} 可用: 最大 {}x{}, codec {:?}", spec.kind, max_width, max_height, capabilities.codecs);
+    capabilities
+}
+
+#[cfg(not(feature = "h264"))]
+fn probe_encoder(spec: &EncoderSpec) -> EncoderCapabilities {
+    EncoderCapabilities::unavailable(spec.kind, "未编译 FFmpeg 支持 (h264 feature)")
+}
+
+/// 以给定参数打开一个编码会话 (打开后立即释放)
+#[cfg(feature = "h264")]
+fn try_open(name: &str, width: u32, height: u32, b_frames: usize, options: &[(&str, &str)]) -> Result<()> {
+    let codec = ffmpeg_next::encoder::find_by_name(name).ok_or_else(|| anyhow::anyhow!("找不到编码器 {}", name))?;
+    let mut context = ffmpeg_next::codec::context::Context::new_with_codec(codec).encoder().video()?;
+    context.set_width(width);
+    context.set_height(height);
+    context.set_bit_rate(4_000_000);
+    context.set_frame_rate(Some(ffmpeg_next::Rational(30, 1)));
+    context.set_time_base(ffmpeg_next::Rational(1, 30));
+    context.set_gop(30);
+    context.set_max_b_frames(b_frames);
+    // 硬件编码器使用 NV12，软件编码器使用 YUV420P
+    let format = if name.starts_with("lib") {
+        ffmpeg_next::format::Pixel::YUV420P
+    } else {
+        ffmpeg_next::format::Pixel::NV12
+    };
+    context.set_format(format);
+    let mut dictionary = ffmpeg_next::Dictionary::new();
+    for (key, value) in options {
+        dictionary.set(key, value);
+    }
+    context.open_with(dictionary)?;
+    Ok(())
+}
+
+/// 显卡名称和驱动版本
+fn driver_versions() -> Vec<String> {
+    #[cfg(target_os = "windows")]
+    let output = command_output(
+        "powershell",
+        &[
+            "-NoProfile",
+            "-Command",
+            "Get-CimInstance Win32_VideoController | ForEach-Object { $_.Name + ' ' + $_.DriverVersion }",
+        ],
+    );
+    #[cfg(target_os = "macos")]
+    let output = command_output("sw_vers", &["-productVersion"]).map(|version| format!("macOS {}", version));
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let output = command_output("nvidia-smi", &["--query-gpu=name,driver_version", "--format=csv,noheader"]);
+
+    output
+        .map(|text| text.lines().map(str::trim).filter(|line| !line.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// 运行命令并返回标准输出 (命令不存在或失败时为 None)
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// 进程内的探测结果 (首次调用时读取缓存，没有可用缓存时探测并写入缓存)
+pub fn capabilities() -> ProbeReport {
+    static REPORT: OnceLock<Mutex<Option<ProbeReport>>> = OnceLock::new();
+    let mut slot = REPORT.get_or_init(|| Mutex::new(None)).lock().unwrap_or_else(|e| e.into_inner());
+    slot.get_or_insert_with(|| {
+        let path = cache_path();
+        if let Some(report) = load_cached(&path) {
+            tracing::debug!("使用缓存的编码器能力: {}", path.display());
+            return report;
+        }
+        let report = probe();
+        if let Err(e) = save(&path, &report) {
+            tracing::warn!("保存编码器能力缓存失败: {}", e);
+        }
+        report
+    })
+    .clone()
+}
+
+/// 缓存的结果与实际不符 (编码器创建失败) 时删除缓存，下次启动重新探测
+pub fn invalidate() {
+    let path = cache_path();
+    if path.exists() {
+        if let Err(e) = std::fs::remove_file(&path) {
+            tracing::warn!("删除编码器能力缓存失败: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoder(kind: HardwareEncoderType, available: bool, max: (u32, u32)) -> EncoderCapabilities {
+        EncoderCapabilities {
+            available,
+            max_width: max.0,
+            max_height: max.1,
+            codecs: vec!["h264".to_string()],
+            error: None,
+            ..EncoderCapabilities::unavailable(kind, "")
+        }
+    }
+
+    fn report(encoders: Vec<EncoderCapabilities>) -> ProbeReport {
+        ProbeReport { fingerprint: fingerprint(), probed_at: unix_now(), drivers: Vec::new(), encoders }
+    }
+
+    #[test]
+    fn test_hardware_for_respects_priority_and_size() {
+        let report = report(vec![
+            encoder(HardwareEncoderType::QuickSync, true, (4096, 2160)),
+            encoder(HardwareEncoderType::NVENC, true, (1920, 1080)),
+            encoder(HardwareEncoderType::AMF, false, (0, 0)),
+            encoder(HardwareEncoderType::Software, true, (4096, 2304)),
+        ]);
+        assert_eq!(
+            report.hardware_for(1920, 1080),
+            vec![HardwareEncoderType::NVENC, HardwareEncoderType::QuickSync]
+        );
+        // 超过 NVENC 的最大分辨率
+        assert_eq!(report.hardware_for(3840, 2160), vec![HardwareEncoderType::QuickSync]);
+    }
+
+    #[test]
+    fn test_cache_round_trip_and_expiry() {
+        let path = std::env::temp_dir().join(format!("sscontrol-encoders-{}.json", std::process::id()));
+        let mut cached = report(vec![encoder(HardwareEncoderType::NVENC, true, (3840, 2160))]);
+        save(&path, &cached).unwrap();
+        assert_eq!(load_cached(&path), Some(cached.clone()));
+
+        // 版本变化或过期后失效
+        cached.fingerprint = "sscontrol 0.0.0".to_string();
+        save(&path, &cached).unwrap();
+        assert_eq!(load_cached(&path), None);
+        cached.fingerprint = fingerprint();
+        cached.probed_at = unix_now() - CACHE_MAX_AGE.as_secs() - 1;
+        save(&path, &cached).unwrap();
+        assert_eq!(load_cached(&path), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -17,14 +17,43 @@
 //! - Intel GPU with Quick Sync Video support
 //! - Intel Graphics Driver
 //! - FFmpeg with h264_qsv codec
+//!
+//! ## 输入
+//! RGBA → NV12 (SIMD) 后通过 QSV 硬件帧上下文上传到显存表面，编码器直接读取表面，
+//! 不再在内部复制系统内存帧。无法创建 QSV 设备时退回系统内存输入
 
 #[cfg(target_os = "windows")]
 use crate::encoder::{EncodedPacket, Frame};
 #[cfg(target_os = "windows")]
-use crate::encoder::hardware::{HardwareEncoder, HardwareEncoderConfig, HardwareEncoderType};
+use crate::encoder::hardware::{EncoderPreset, HardwareEncoder, HardwareEncoderConfig, HardwareEncoderType};
 #[cfg(target_os = "windows")]
 use anyhow::{anyhow, Result};
 
+/// 关键帧间隔 (帧)
+#[cfg(target_os = "windows")]
+const KEY_FRAME_INTERVAL: u64 = 30;
+
+/// h264_qsv 的低延迟选项
+///
+/// `async_depth=1` 让每帧送入后立即取回码流 (默认会缓冲 4 帧)；
+/// 码率与峰值码率相同时 h264_qsv 使用 CBR
+#[cfg(target_os = "windows")]
+fn low_latency_options(preset: EncoderPreset) -> Vec<(&'static str, &'static str)> {
+    let qsv_preset = match preset {
+        EncoderPreset::UltraLowLatency => "veryfast",
+        EncoderPreset::LowLatency => "faster",
+        EncoderPreset::Balanced => "medium",
+        EncoderPreset::Quality => "slower",
+    };
+    vec![
+        ("preset", qsv_preset),
+        ("async_depth", "1"),
+        ("look_ahead", "0"),
+        ("forced_idr", "1"),
+        ("low_delay_brc", "1"),
+    ]
+}
+
 /// Intel Quick Sync 编码器
 ///
 /// 使用 Intel Quick Sync Video 进行 H.264 硬件编码
@@ -36,10 +65,16 @@ pub struct QuickSyncEncoder {
     #[cfg(feature = "h264")]
     inner: Option<ffmpeg_next::encoder::Video>,
     #[cfg(feature = "h264")]
-    sws_context: Option<ffmpeg_next::software::scaling::Context>,
+    yuv_frame: crate::encoder::colorspace::ReusableVideoFrame,
     pts: i64,
     key_frame_interval: u64,
     frame_count: u64,
+    /// 感兴趣区域 (随每帧附加给编码器)
+    #[cfg(feature = "h264")]
+    roi: Vec<crate::quality::roi_encoder::RoiRegion>,
+    /// QSV 显存表面池 (None = 系统内存输入)
+    #[cfg(feature = "h264")]
+    surfaces: Option<hw::QsvFrames>,
 }
 
 #[cfg(target_os = "windows")]
@@ -67,44 +102,64 @@ impl QuickSyncEncoder {
             let mut encoder_context = context.encoder().video()?;
 
             encoder_context.set_bit_rate((config.bitrate * 1000) as usize);
+            encoder_context.set_max_bit_rate((config.bitrate * 1000) as usize);
             encoder_context.set_width(width);
             encoder_context.set_height(height);
             encoder_context.set_frame_rate(Some(ffmpeg_next::Rational(config.fps as i32, 1)));
             encoder_context.set_time_base(ffmpeg_next::Rational(1, config.fps as i32));
-            encoder_context.set_gop(30);
+            encoder_context.set_gop(KEY_FRAME_INTERVAL as u32);
+            encoder_context.set_max_b_frames(0);
             encoder_context.set_format(ffmpeg_next::format::Pixel::NV12);
 
+            // 显存表面输入: 编码器从 QSV 硬件帧上下文取帧
+            let surfaces = match hw::QsvFrames::new(width, height) {
+                Ok(surfaces) => {
+                    unsafe {
+                        let ctx = encoder_context.as_mut_ptr();
+                        (*ctx).pix_fmt = ffmpeg_next::ffi::AVPixelFormat::AV_PIX_FMT_QSV;
+                        (*ctx).hw_frames_ctx = ffmpeg_next::ffi::av_buffer_ref(surfaces.frames_ref());
+                    }
+                    Some(surfaces)
+                }
+                Err(e) => {
+                    tracing::warn!("创建 QSV 硬件帧上下文失败，使用系统内存输入: {}", e);
+                    None
+                }
+            };
+
             // Quick Sync 特定选项
             let mut opts = ffmpeg_next::Dictionary::new();
-            opts.set("preset", "faster");  // 更快的编码
-            opts.set("rc", "cbr");         // 恒定码率
-            opts.set("b_max", "0");        // 禁用 B 帧（降低延迟）
-            opts.set("look_ahead", "0");   // 禁用前瞻（降低延迟）
+            for (key, value) in low_latency_options(config.preset) {
+                opts.set(key, value);
+            }
 
             // 打开编码器
             let video_encoder = encoder_context.open_with(opts)?;
 
-            // 创建 SwsContext 用于 RGBA -> NV12 转换
-            let sws_context = ffmpeg_next::software::scaling::Context::get(
-                ffmpeg_next::format::Pixel::RGBA,
-                width,
-                height,
+            // 复用的 NV12 帧 (SIMD 颜色转换)
+            let yuv_frame = crate::encoder::colorspace::ReusableVideoFrame::new(
                 ffmpeg_next::format::Pixel::NV12,
                 width,
                 height,
-                ffmpeg_next::software::scaling::Flags::BILINEAR,
-            )?;
-
-            tracing::info!("Quick Sync 编码器创建成功 (faster 预设)");
+                crate::encoder::colorspace::PixelLayout::Rgba,
+            );
+
+            tracing::info!(
+                "Quick Sync 编码器创建成功 ({:?} 预设，{})",
+                config.preset,
+                if surfaces.is_some() { "显存表面输入" } else { "系统内存输入" }
+            );
             Ok(Self {
                 width,
                 height,
                 config,
                 inner: Some(video_encoder),
-                sws_context: Some(sws_context),
+                yuv_frame,
                 pts: 0,
-                key_frame_interval: 30,
+                key_frame_interval: KEY_FRAME_INTERVAL,
                 frame_count: 0,
+                roi: Vec::new(),
+                surfaces,
             })
         }
 
@@ -115,14 +170,18 @@ impl QuickSyncEncoder {
     }
 
     /// 检测 Quick Sync 是否可用
+    ///
+    /// 不打开编码会话: FFmpeg 编译了 h264_qsv 且能创建 QSV 设备 (需要 Intel 显卡和 oneVPL/Media SDK 运行时)
     pub fn is_available() -> bool {
         #[cfg(feature = "h264")]
         {
-            if let Ok(_) = ffmpeg_next::init() {
-                if let Some(encoder) = ffmpeg_next::encoder::find_by_name("h264_qsv") {
-                    tracing::info!("Quick Sync 编码器可用: {}", encoder.name());
+            if ffmpeg_next::init().is_ok() && ffmpeg_next::encoder::find_by_name("h264_qsv").is_some() {
+                if hw::device_available() {
+                    tracing::info!("Quick Sync 编码器可用");
                     return true;
                 }
+                tracing::warn!("FFmpeg 支持 h264_qsv，但无法创建 QSV 设备");
+                return false;
             }
             tracing::warn!("Quick Sync 编码器不可用");
             false
@@ -133,49 +192,6 @@ impl QuickSyncEncoder {
         }
     }
 
-    /// 将 RGBA 帧转换为 NV12 格式
-    #[cfg(feature = "h264")]
-    fn rgba_to_nv12_frame(&mut self, rgba: &[u8], width: u32, height: u32) -> Result<ffmpeg_next::frame::Video> {
-        // 创建源帧 (RGBA)
-        let mut src_frame = ffmpeg_next::frame::Video::empty();
-        src_frame.set_format(ffmpeg_next::format::Pixel::RGBA);
-        src_frame.set_width(width);
-        src_frame.set_height(height);
-
-        unsafe {
-            src_frame.alloc(ffmpeg_next::format::Pixel::RGBA, width, height);
-        }
-
-        // 复制 RGBA 数据到源帧
-        let src_stride = src_frame.stride(0);
-        let src_data = src_frame.data_mut(0);
-        for y in 0..height as usize {
-            let src_row_start = y * (width as usize * 4);
-            let dst_row_start = y * src_stride;
-            let row_len = width as usize * 4;
-            src_data[dst_row_start..dst_row_start + row_len]
-                .copy_from_slice(&rgba[src_row_start..src_row_start + row_len]);
-        }
-
-        // 创建目标帧 (NV12)
-        let mut dst_frame = ffmpeg_next::frame::Video::empty();
-        dst_frame.set_format(ffmpeg_next::format::Pixel::NV12);
-        dst_frame.set_width(width);
-        dst_frame.set_height(height);
-
-        unsafe {
-            dst_frame.alloc(ffmpeg_next::format::Pixel::NV12, width, height);
-        }
-
-        // 使用 SwsContext 进行转换
-        if let Some(ref mut sws) = self.sws_context {
-            sws.run(&src_frame, &mut dst_frame)?;
-        } else {
-            return Err(anyhow!("SwsContext 未初始化"));
-        }
-
-        Ok(dst_frame)
-    }
 }
 
 #[cfg(target_os = "windows")]
@@ -183,19 +199,24 @@ impl HardwareEncoder for QuickSyncEncoder {
     #[cfg(feature = "h264")]
     fn encode(&mut self, frame: &Frame) -> Result<Option<EncodedPacket>> {
         // 转换为 NV12
-        let mut nv12_frame = self.rgba_to_nv12_frame(&frame.data, frame.width, frame.height)?;
+        let nv12_frame = self.yuv_frame.convert(frame)?;
 
         // 设置 PTS
         nv12_frame.set_pts(Some(self.pts));
         self.pts += 1;
         self.frame_count += 1;
 
-        // 判断是否为关键帧
-        let is_key_frame = self.frame_count % self.key_frame_interval == 0;
+        // 到达间隔或请求了关键帧时强制 IDR
+        let force_key_frame = self.frame_count % self.key_frame_interval == 0;
+        crate::encoder::mark_key_frame(nv12_frame, force_key_frame);
+        crate::encoder::attach_roi(nv12_frame, &self.roi);
 
-        // 编码
+        // 编码 (有显存表面时先上传，PTS、帧类型和 ROI 随帧属性一起复制)
         let encoder = self.inner.as_mut().ok_or_else(|| anyhow!("编码器未初始化"))?;
-        encoder.send_frame(&nv12_frame)?;
+        match self.surfaces.as_ref() {
+            Some(surfaces) => encoder.send_frame(&surfaces.upload(nv12_frame)?)?,
+            None => encoder.send_frame(nv12_frame)?,
+        }
 
         let mut packet = ffmpeg_next::packet::Packet::empty();
         match encoder.receive_packet(&mut packet) {
@@ -204,7 +225,7 @@ impl HardwareEncoder for QuickSyncEncoder {
                     let data = packet.data().unwrap_or(&[]).to_vec();
                     Ok(Some(EncodedPacket {
                         data,
-                        is_key_frame,
+                        is_key_frame: packet.is_key(),
                         timestamp: frame.timestamp,
                         pts: self.pts,
                     }))
@@ -283,6 +304,138 @@ impl HardwareEncoder for QuickSyncEncoder {
             false
         }
     }
+
+    #[cfg(feature = "h264")]
+    fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()> {
+        if bitrate_kbps == self.config.bitrate {
+            return Ok(());
+        }
+        if let Some(encoder) = self.inner.as_mut() {
+            crate::encoder::apply_bitrate(encoder, bitrate_kbps);
+        }
+        self.config.bitrate = bitrate_kbps;
+
+        // h264_qsv 在下一帧通过 MFXVideoENCODE_Reset 切换码控，不需要关键帧
+        Ok(())
+    }
+
+    #[cfg(feature = "h264")]
+    fn set_roi(&mut self, regions: &[crate::quality::roi_encoder::RoiRegion]) -> bool {
+        self.roi.clear();
+        self.roi.extend_from_slice(regions);
+        self.inner.as_ref().is_some_and(crate::encoder::encoder_supports_roi)
+    }
+}
+
+/// QSV 硬件帧 (显存表面)
+#[cfg(all(target_os = "windows", feature = "h264"))]
+mod hw {
+    use crate::encoder::d3d11::check;
+    use anyhow::{anyhow, Result};
+    use ffmpeg_next::ffi;
+    use std::ffi::CString;
+    use std::ptr;
+
+    /// 表面池大小 (async_depth=1 时编码器最多同时占用几帧，留出余量)
+    const POOL_SIZE: i32 = 8;
+
+    /// QSV 设备和 NV12 表面池
+    pub struct QsvFrames {
+        device_ref: *mut ffi::AVBufferRef,
+        frames_ref: *mut ffi::AVBufferRef,
+    }
+
+    impl QsvFrames {
+        pub fn new(width: u32, height: u32) -> Result<Self> {
+            unsafe {
+                let mut frames = Self {
+                    device_ref: create_device()?,
+                    frames_ref: ptr::null_mut(),
+                };
+
+                let frames_ref = ffi::av_hwframe_ctx_alloc(frames.device_ref);
+                if frames_ref.is_null() {
+                    return Err(anyhow!("av_hwframe_ctx_alloc 失败"));
+                }
+                frames.frames_ref = frames_ref;
+                let ctx = (*frames_ref).data as *mut ffi::AVHWFramesContext;
+                (*ctx).format = ffi::AVPixelFormat::AV_PIX_FMT_QSV;
+                (*ctx).sw_format = ffi::AVPixelFormat::AV_PIX_FMT_NV12;
+                (*ctx).width = width as i32;
+                (*ctx).height = height as i32;
+                (*ctx).initial_pool_size = POOL_SIZE;
+                check(ffi::av_hwframe_ctx_init(frames_ref), "av_hwframe_ctx_init")?;
+                Ok(frames)
+            }
+        }
+
+        pub fn frames_ref(&self) -> *mut ffi::AVBufferRef {
+            self.frames_ref
+        }
+
+        /// 把 NV12 帧上传到一个空闲表面
+        pub fn upload(&self, src: &ffmpeg_next::frame::Video) -> Result<ffmpeg_next::frame::Video> {
+            let mut dst = ffmpeg_next::frame::Video::empty();
+            unsafe {
+                check(ffi::av_hwframe_get_buffer(self.frames_ref, dst.as_mut_ptr(), 0), "av_hwframe_get_buffer")?;
+                check(ffi::av_hwframe_transfer_data(dst.as_mut_ptr(), src.as_ptr(), 0), "av_hwframe_transfer_data")?;
+                check(ffi::av_frame_copy_props(dst.as_mut_ptr(), src.as_ptr()), "av_frame_copy_props")?;
+            }
+            Ok(dst)
+        }
+    }
+
+    impl Drop for QsvFrames {
+        fn drop(&mut self) {
+            unsafe {
+                if !self.frames_ref.is_null() {
+                    ffi::av_buffer_unref(&mut self.frames_ref);
+                }
+                if !self.device_ref.is_null() {
+                    ffi::av_buffer_unref(&mut self.device_ref);
+                }
+            }
+        }
+    }
+
+    // SAFETY: 表面池只在视频任务中顺序使用
+    unsafe impl Send for QsvFrames {}
+
+    /// 创建 QSV 设备 (基于 D3D11，与 DXGI 捕获共用显卡驱动)
+    unsafe fn create_device() -> Result<*mut ffi::AVBufferRef> {
+        let mut opts: *mut ffi::AVDictionary = ptr::null_mut();
+        let key = CString::new("child_device_type")?;
+        let value = CString::new("d3d11va")?;
+        ffi::av_dict_set(&mut opts, key.as_ptr(), value.as_ptr(), 0);
+
+        let mut device_ref: *mut ffi::AVBufferRef = ptr::null_mut();
+        let ret = ffi::av_hwdevice_ctx_create(
+            &mut device_ref,
+            ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_QSV,
+            ptr::null(),
+            opts,
+            0,
+        );
+        ffi::av_dict_free(&mut opts);
+        check(ret, "av_hwdevice_ctx_create (QSV)")?;
+        Ok(device_ref)
+    }
+
+    /// 能否创建 QSV 设备 (只建立会话，不打开编码器)
+    pub fn device_available() -> bool {
+        unsafe {
+            match create_device() {
+                Ok(mut device_ref) => {
+                    ffi::av_buffer_unref(&mut device_ref);
+                    true
+                }
+                Err(e) => {
+                    tracing::debug!("QSV 设备不可用: {}", e);
+                    false
+                }
+            }
+        }
+    }
 }
 
 #[cfg(not(target_os = "windows"))]
@@ -308,6 +461,15 @@ mod tests {
         // 结果取决于硬件
     }
 
+    #[test]
+    #[cfg(target_os = "windows")]
+    fn test_low_latency_options() {
+        let options = low_latency_options(EncoderPreset::LowLatency);
+        assert!(options.contains(&("async_depth", "1")));
+        assert!(options.contains(&("forced_idr", "1")));
+        assert!(low_latency_options(EncoderPreset::Quality).contains(&("preset", "slower")));
+    }
+
     #[test]
     fn test_config_validation() {
         #[cfg(all(target_os = "windows", feature = "h264"))]
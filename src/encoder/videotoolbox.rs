@@ -8,24 +8,80 @@
 //! - 带宽: 1.5-3 Mbps @1080p@30fps
 //!
 //! ## 支持的平台
-
-// VideoToolbox 编码器尚未完全集成，标记为允许死代码
-#![allow(dead_code)]
 //! - macOS 10.8+ (所有支持硬件加速的 Mac)
+//!
+//! ## 输出格式
+//! VTCompressionSession 在回调中输出 AVCC 样本 (4 字节长度前缀，SPS/PPS 在格式描述里)。
+//! 回调通过 refcon 拿到编码器的输出队列，把样本转换为 Annex B 并在关键帧前插入
+//! SPS/PPS，与其他编码器的输出一致
 
+use crate::encoder::bitstream;
+use anyhow::Result;
+
+#[cfg(target_os = "macos")]
+use crate::encoder::colorspace::{self, PixelLayout, SourceImage, YuvPlanes};
+#[cfg(target_os = "macos")]
+use crate::encoder::hardware::{EncoderPreset, HardwareEncoder, HardwareEncoderConfig, HardwareEncoderType};
 #[cfg(target_os = "macos")]
 use crate::encoder::{EncodedPacket, Frame};
 #[cfg(target_os = "macos")]
-use crate::encoder::hardware::{HardwareEncoder, HardwareEncoderConfig, HardwareEncoderType, EncoderPreset};
+use anyhow::anyhow;
 #[cfg(target_os = "macos")]
-use anyhow::{anyhow, Result};
-
+use core_foundation::{
+    array::CFArray,
+    base::{CFType, TCFType},
+    boolean::CFBoolean,
+    dictionary::CFDictionary,
+    number::CFNumber,
+    string::CFString,
+};
+#[cfg(target_os = "macos")]
+use std::collections::VecDeque;
+#[cfg(target_os = "macos")]
+use std::ffi::c_void;
 #[cfg(target_os = "macos")]
 use std::ptr;
 #[cfg(target_os = "macos")]
 use std::sync::{Arc, Mutex};
+
+/// 关键帧间隔 (帧)
+#[cfg(target_os = "macos")]
+const KEY_FRAME_INTERVAL: u64 = 30;
+
+/// 把一个编码输出样本组装为 Annex B 访问单元
+///
+/// `parameter_sets` 只在关键帧时非空 (SPS、PPS)，写在片数据之前，
+/// 使解码端可以从任意关键帧开始解码
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn assemble_access_unit(
+    avcc: &[u8],
+    length_size: usize,
+    parameter_sets: &[&[u8]],
+    out: &mut Vec<u8>,
+) -> Result<()> {
+    bitstream::write_parameter_sets(parameter_sets.iter().copied(), out);
+    bitstream::avcc_to_annex_b(avcc, length_size, out)
+}
+
+/// 回调产生的编码样本
 #[cfg(target_os = "macos")]
-use std::os::raw::{c_int, c_void};
+struct EncodedSample {
+    data: Vec<u8>,
+    is_key_frame: bool,
+    /// 捕获时间戳 (通过 sourceFrameRefcon 传递)
+    timestamp: u64,
+}
+
+/// 输出回调的上下文 (outputCallbackRefCon)
+///
+/// 由编码器持有，会话在编码器析构时先于上下文失效，回调不会访问已释放的内存
+#[cfg(target_os = "macos")]
+#[derive(Default)]
+struct CallbackContext {
+    samples: Mutex<VecDeque<EncodedSample>>,
+    /// 最近一次回调错误 (由下一次 encode 返回)
+    error: Mutex<Option<String>>,
+}
 
 /// VideoToolbox 编码器
 ///
@@ -35,11 +91,10 @@ pub struct VideoToolboxEncoder {
     width: u32,
     height: u32,
     config: HardwareEncoderConfig,
-    session: Option<CompressionSession>,
+    session: ffi::VTCompressionSessionRef,
+    context: Arc<CallbackContext>,
     pts: i64,
-    key_frame_interval: u64,
-    frame_count: u64,
-    encoded_data: Arc<Mutex<Vec<u8>>>,
+    force_key_frame: bool,
 }
 
 #[cfg(target_os = "macos")]
@@ -61,20 +116,190 @@ impl VideoToolboxEncoder {
             return Err(anyhow!("码率超出范围: {}kbps (有效范围: 100-50000)", config.bitrate));
         }
 
-        // 创建压缩会话
-        let encoded_data = Arc::new(Mutex::new(Vec::new()));
-        let session = CompressionSession::new(width, height, &config, encoded_data.clone())?;
-
-        Ok(Self {
-            width,
-            height,
-            config,
-            session: Some(session),
-            pts: 0,
-            key_frame_interval: 30,
-            frame_count: 0,
-            encoded_data,
-        })
+        let context = Arc::new(CallbackContext::default());
+
+        unsafe {
+            // 编码器规格: 要求硬件编码，并启用低延迟码控 (不做帧重排和前瞻)
+            let encoder_spec = CFDictionary::from_CFType_pairs(&[
+                (
+                    CFString::wrap_under_get_rule(ffi::kVTVideoEncoderSpecification_EnableHardwareAcceleratedVideoEncoder),
+                    CFBoolean::true_value().as_CFType(),
+                ),
+                (
+                    CFString::wrap_under_get_rule(ffi::kVTVideoEncoderSpecification_EnableLowLatencyRateControl),
+                    CFBoolean::true_value().as_CFType(),
+                ),
+            ]);
+
+            // 源像素缓冲区: NV12 (video range)，会话的缓冲池按此分配
+            let source_attrs = CFDictionary::from_CFType_pairs(&[
+                (
+                    CFString::wrap_under_get_rule(ffi::kCVPixelBufferPixelFormatTypeKey),
+                    CFNumber::from(ffi::kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange as i32).as_CFType(),
+                ),
+                (
+                    CFString::wrap_under_get_rule(ffi::kCVPixelBufferWidthKey),
+                    CFNumber::from(width as i32).as_CFType(),
+                ),
+                (
+                    CFString::wrap_under_get_rule(ffi::kCVPixelBufferHeightKey),
+                    CFNumber::from(height as i32).as_CFType(),
+                ),
+            ]);
+
+            let mut session: ffi::VTCompressionSessionRef = ptr::null_mut();
+            let status = ffi::VTCompressionSessionCreate(
+                ptr::null(),
+                width as i32,
+                height as i32,
+                ffi::kCMVideoCodecType_H264,
+                encoder_spec.as_concrete_TypeRef(),
+                source_attrs.as_concrete_TypeRef(),
+                ptr::null(),
+                Some(output_callback),
+                Arc::as_ptr(&context) as *mut c_void,
+                &mut session,
+            );
+            if status != 0 || session.is_null() {
+                return Err(anyhow!("创建 VideoToolbox 压缩会话失败: {}", status));
+            }
+
+            let encoder = Self {
+                width,
+                height,
+                config,
+                session,
+                context,
+                pts: 0,
+                force_key_frame: false,
+            };
+
+            encoder.configure()?;
+
+            let status = ffi::VTCompressionSessionPrepareToEncodeFrames(encoder.session);
+            if status != 0 {
+                return Err(anyhow!("准备 VideoToolbox 编码会话失败: {}", status));
+            }
+
+            tracing::info!("VideoToolbox 编码器创建成功: {}x{}", width, height);
+            Ok(encoder)
+        }
+    }
+
+    /// 设置压缩属性 (实时、档次、码率、关键帧间隔)
+    unsafe fn configure(&self) -> Result<()> {
+        let profile = match self.config.preset {
+            EncoderPreset::UltraLowLatency => ffi::kVTProfileLevel_H264_Baseline_AutoLevel,
+            EncoderPreset::LowLatency | EncoderPreset::Balanced => ffi::kVTProfileLevel_H264_Main_AutoLevel,
+            EncoderPreset::Quality => ffi::kVTProfileLevel_H264_High_AutoLevel,
+        };
+
+        self.set_property(ffi::kVTCompressionPropertyKey_RealTime, CFBoolean::true_value().as_CFType())?;
+        self.set_property(ffi::kVTCompressionPropertyKey_ProfileLevel, CFString::wrap_under_get_rule(profile).as_CFType())?;
+        // B 帧会增加一帧以上的延迟
+        self.set_property(ffi::kVTCompressionPropertyKey_AllowFrameReordering, CFBoolean::false_value().as_CFType())?;
+        self.set_property(
+            ffi::kVTCompressionPropertyKey_MaxKeyFrameInterval,
+            CFNumber::from(KEY_FRAME_INTERVAL as i32).as_CFType(),
+        )?;
+        self.set_property(
+            ffi::kVTCompressionPropertyKey_ExpectedFrameRate,
+            CFNumber::from(self.config.fps as i32).as_CFType(),
+        )?;
+        self.apply_bitrate(self.config.bitrate)
+    }
+
+    /// 设置平均码率和峰值限制 (1 秒内不超过目标码率的 1.5 倍)
+    unsafe fn apply_bitrate(&self, bitrate_kbps: u32) -> Result<()> {
+        let bits_per_second = bitrate_kbps as i64 * 1000;
+        self.set_property(
+            ffi::kVTCompressionPropertyKey_AverageBitRate,
+            CFNumber::from(bits_per_second).as_CFType(),
+        )?;
+
+        let limits = CFArray::from_CFTypes(&[
+            CFNumber::from(bits_per_second * 3 / 2 / 8).as_CFType(),
+            CFNumber::from(1.0f64).as_CFType(),
+        ]);
+        self.set_property(ffi::kVTCompressionPropertyKey_DataRateLimits, limits.as_CFType())
+    }
+
+    unsafe fn set_property(&self, key: ffi::CFStringRef, value: CFType) -> Result<()> {
+        let status = ffi::VTSessionSetProperty(self.session, key, value.as_CFTypeRef());
+        if status != 0 {
+            let name = CFString::wrap_under_get_rule(key);
+            return Err(anyhow!("设置 VideoToolbox 属性 {} 失败: {}", name, status));
+        }
+        Ok(())
+    }
+
+    /// 从会话缓冲池取一个 NV12 像素缓冲区并填充 RGBA 帧
+    unsafe fn fill_pixel_buffer(&self, frame: &Frame) -> Result<ffi::CVPixelBufferRef> {
+        let pool = ffi::VTCompressionSessionGetPixelBufferPool(self.session);
+        if pool.is_null() {
+            return Err(anyhow!("VideoToolbox 像素缓冲池不可用"));
+        }
+
+        let mut buffer: ffi::CVPixelBufferRef = ptr::null_mut();
+        let status = ffi::CVPixelBufferPoolCreatePixelBuffer(ptr::null(), pool, &mut buffer);
+        if status != 0 || buffer.is_null() {
+            return Err(anyhow!("分配 CVPixelBuffer 失败: {}", status));
+        }
+
+        ffi::CVPixelBufferLockBaseAddress(buffer, 0);
+        let result = (|| {
+            let y_stride = ffi::CVPixelBufferGetBytesPerRowOfPlane(buffer, 0);
+            let uv_stride = ffi::CVPixelBufferGetBytesPerRowOfPlane(buffer, 1);
+            let y_ptr = ffi::CVPixelBufferGetBaseAddressOfPlane(buffer, 0) as *mut u8;
+            let uv_ptr = ffi::CVPixelBufferGetBaseAddressOfPlane(buffer, 1) as *mut u8;
+            if y_ptr.is_null() || uv_ptr.is_null() {
+                return Err(anyhow!("CVPixelBuffer 平面地址为空"));
+            }
+            let y_len = y_stride * ffi::CVPixelBufferGetHeightOfPlane(buffer, 0);
+            let uv_len = uv_stride * ffi::CVPixelBufferGetHeightOfPlane(buffer, 1);
+
+            let stride = if frame.stride == 0 { frame.width as usize * 4 } else { frame.stride };
+            colorspace::convert(
+                &SourceImage {
+                    data: &frame.data,
+                    stride,
+                    width: frame.width as usize,
+                    height: frame.height as usize,
+                    layout: PixelLayout::Rgba,
+                },
+                YuvPlanes {
+                    y: std::slice::from_raw_parts_mut(y_ptr, y_len),
+                    y_stride,
+                    chroma_u: std::slice::from_raw_parts_mut(uv_ptr, uv_len),
+                    u_stride: uv_stride,
+                    chroma_v: None,
+                },
+            )
+        })();
+        ffi::CVPixelBufferUnlockBaseAddress(buffer, 0);
+
+        match result {
+            Ok(()) => Ok(buffer),
+            Err(e) => {
+                ffi::CVPixelBufferRelease(buffer);
+                Err(e)
+            }
+        }
+    }
+
+    /// 取出回调产生的样本 (或回调报告的错误)
+    fn take_output(&mut self) -> Result<Option<EncodedPacket>> {
+        if let Some(error) = self.context.error.lock().unwrap().take() {
+            return Err(anyhow!(error));
+        }
+
+        let sample = self.context.samples.lock().unwrap().pop_front();
+        Ok(sample.map(|sample| EncodedPacket {
+            data: sample.data,
+            is_key_frame: sample.is_key_frame,
+            timestamp: sample.timestamp,
+            pts: self.pts - 1,
+        }))
     }
 }
 
@@ -89,38 +314,47 @@ impl HardwareEncoder for VideoToolboxEncoder {
             ));
         }
 
-        // 获取会话
-        let session = self.session.as_ref().ok_or_else(|| anyhow!("压缩会话未初始化"))?;
-
-        // 编码帧
-        let is_key_frame = self.frame_count % self.key_frame_interval == 0;
-        session.encode_frame(&frame.data, is_key_frame)?;
-
-        // 获取编码数据
-        let data = self.encoded_data.lock().unwrap();
-        if !data.is_empty() {
-            let packet = EncodedPacket {
-                data: data.clone(),
-                is_key_frame,
-                timestamp: frame.timestamp,
-                pts: self.pts,
-            };
-            drop(data);
-            self.encoded_data.lock().unwrap().clear();
-
-            self.pts += 1;
-            self.frame_count += 1;
-            Ok(Some(packet))
-        } else {
-            drop(data);
+        unsafe {
+            let buffer = self.fill_pixel_buffer(frame)?;
+
+            // 请求关键帧通过帧属性字典传递
+            let properties = self.force_key_frame.then(|| {
+                CFDictionary::from_CFType_pairs(&[(
+                    CFString::wrap_under_get_rule(ffi::kVTEncodeFrameOptionKey_ForceKeyFrame),
+                    CFBoolean::true_value().as_CFType(),
+                )])
+            });
+
+            let pts = ffi::CMTimeMake(self.pts, self.config.fps as i32);
+            let status = ffi::VTCompressionSessionEncodeFrame(
+                self.session,
+                buffer,
+                pts,
+                ffi::kCMTimeInvalid,
+                properties.as_ref().map_or(ptr::null(), |p| p.as_concrete_TypeRef()),
+                // 捕获时间戳随帧传给回调
+                frame.timestamp as usize as *mut c_void,
+                ptr::null_mut(),
+            );
+            ffi::CVPixelBufferRelease(buffer);
+            if status != 0 {
+                return Err(anyhow!("VideoToolbox 编码帧失败: {}", status));
+            }
+            self.force_key_frame = false;
             self.pts += 1;
-            self.frame_count += 1;
-            Ok(None)
+
+            // 等待本帧输出，保证一帧进一帧出 (实时模式下没有重排，不增加延迟)
+            let status = ffi::VTCompressionSessionCompleteFrames(self.session, pts);
+            if status != 0 {
+                return Err(anyhow!("VideoToolbox 完成编码失败: {}", status));
+            }
         }
+
+        self.take_output()
     }
 
     fn request_key_frame(&mut self) -> Result<()> {
-        self.frame_count = self.key_frame_interval - 1;
+        self.force_key_frame = true;
         Ok(())
     }
 
@@ -133,7 +367,10 @@ impl HardwareEncoder for VideoToolboxEncoder {
     }
 
     fn flush(&mut self) -> Result<Option<EncodedPacket>> {
-        Ok(None)
+        unsafe {
+            ffi::VTCompressionSessionCompleteFrames(self.session, ffi::kCMTimeInvalid);
+        }
+        self.take_output()
     }
 
     fn encoder_type(&self) -> HardwareEncoderType {
@@ -141,344 +378,312 @@ impl HardwareEncoder for VideoToolboxEncoder {
     }
 
     fn is_available(&self) -> bool {
-        self.session.is_some()
+        !self.session.is_null()
     }
-}
 
-/// 压缩会话包装器
-#[cfg(target_os = "macos")]
-struct CompressionSession {
-    session: *mut std::ffi::c_void,
-    width: u32,
-    height: u32,
-    pts: Arc<Mutex<i64>>,
-    _encoded_data: Arc<Mutex<Vec<u8>>>,
+    /// AverageBitRate / DataRateLimits 可以在会话运行中修改，下一帧生效
+    fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()> {
+        unsafe { self.apply_bitrate(bitrate_kbps)? };
+        self.config.bitrate = bitrate_kbps;
+        Ok(())
+    }
+
+    /// VTCompressionSession 没有公开的按区域 QP 接口 (只能整帧设置质量和码率)，不支持 ROI
+    fn set_roi(&mut self, _regions: &[crate::quality::roi_encoder::RoiRegion]) -> bool {
+        false
+    }
 }
 
 #[cfg(target_os = "macos")]
-impl CompressionSession {
-    fn new(
-        width: u32,
-        height: u32,
-        config: &HardwareEncoderConfig,
-        encoded_data: Arc<Mutex<Vec<u8>>>,
-    ) -> Result<Self> {
-        use core_foundation::{
-            base::TCFType,
-            dictionary::CFDictionary,
-            string::CFString,
-        };
-
-        // 创建编码参数字典
-        let encoded_spec = CFDictionary::from_CFType_pairs(&[
-            (CFString::new("VTVideoEncoderSpecification_EncoderID"),
-             CFString::new("com.apple.videotoolbox.videoencoder.h264.gva").as_CFType()),
-        ]);
-
-        // 创建压缩属性字典
-        let compression_spec = CFDictionary::from_CFType_pairs(&[
-            (CFString::new("VTCompressionPropertyKey_ProfileLevel"),
-             CFString::new("H264_Main_3_1").as_CFType()),
-            (CFString::new("VTCompressionPropertyKey_AverageBitRate"),
-             (config.bitrate * 1000).as_CFType()),
-            (CFString::new("VTCompressionPropertyKey_MaxKeyFrameIntervalDuration"),
-             (config.fps / 30).max(1).as_CFType()),
-            (CFString::new("VTCompressionPropertyKey_RealTime"),
-             true.as_CFType()),
-        ]);
-
-        let pts = Arc::new(Mutex::new(0i64));
-        let pts_clone = pts.clone();
-        let encoded_data_clone = encoded_data.clone();
-
-        // 创建输出回调
-        extern "C" fn output_callback(
-            _output_callback_refcon: *mut c_void,
-            _source_frame_refcon: *mut c_void,
-            status: OSStatus,
-            _info_flags: VTEncodeInfoFlags,
-            sample_buffer: CMSampleBufferRef,
-        ) {
-            if status != 0 {
-                tracing::error!("VideoToolbox 编码回调错误: {}", status);
-                return;
-            }
-
-            if !sample_buffer.is_null() {
-                unsafe {
-                    // 获取数据缓冲区
-                    let data_ptr = CMSampleBufferGetDataBuffer(sample_buffer);
-                    if !data_ptr.is_null() {
-                        let length = CMBlockBufferGetDataLength(data_ptr);
-                        let mut data_bytes: *mut u8 = ptr::null_mut();
-                        let mut total_length: i32 = 0;
-                        let status = CMBlockBufferGetDataPointer(
-                            data_ptr,
-                            0,
-                            ptr::null_mut(),
-                            &mut total_length,
-                            &mut data_bytes,
-                        );
-
-                        if status == 0 && !data_bytes.is_null() {
-                            let data_slice = std::slice::from_raw_parts(data_bytes, length as usize);
-                            // 注意: 实际实现需要通过 refcon 传递回调上下文
-                            // 这里使用全局 tracing，实际需要存储数据
-                            tracing::trace!("VideoToolbox 编码输出: {} bytes", data_slice.len());
-                        }
-                    }
-                }
+impl Drop for VideoToolboxEncoder {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.session.is_null() {
+                // 先让会话停止回调，之后上下文才随编码器释放
+                ffi::VTCompressionSessionCompleteFrames(self.session, ffi::kCMTimeInvalid);
+                ffi::VTCompressionSessionInvalidate(self.session);
+                ffi::CFRelease(self.session as *const c_void);
             }
         }
+    }
+}
 
-        // 创建会话
-        let session = unsafe {
-            let mut session_ptr: *mut std::ffi::c_void = ptr::null_mut();
-            let status = VTCompressionSessionCreate(
-                ptr::null_mut(),
-                width,
-                height,
-                kCMVideoCodecType_H264,
-                &encoded_spec as *const _ as *const _,
-                &compression_spec as *const _ as *const _,
-                ptr::null_mut(),
-                Some(output_callback),
-                Box::into_raw(Box::new((encoded_data_clone, pts_clone))) as *mut _,
-                &mut session_ptr,
-            );
-
-            if status != 0 {
-                return Err(anyhow!("创建 VideoToolbox 压缩会话失败: {}", status));
-            }
-
-            // 准备编码
-            let status = VTCompressionSessionPrepareToEncodeFrames(session_ptr as VTCompressionSessionRef);
-            if status != 0 {
-                VTCompressionSessionInvalidate(session_ptr as VTCompressionSessionRef);
-                return Err(anyhow!("准备 VideoToolbox 编码会话失败: {}", status));
-            }
-
-            session_ptr
-        };
+// SAFETY: 会话只在视频任务中顺序使用，回调共享的数据由 Mutex 保护
+#[cfg(target_os = "macos")]
+unsafe impl Send for VideoToolboxEncoder {}
 
-        Ok(Self {
-            session,
-            width,
-            height,
-            pts,
-            _encoded_data: encoded_data,
-        })
+/// 压缩会话输出回调
+#[cfg(target_os = "macos")]
+extern "C" fn output_callback(
+    output_refcon: *mut c_void,
+    source_frame_refcon: *mut c_void,
+    status: ffi::OSStatus,
+    _info_flags: ffi::VTEncodeInfoFlags,
+    sample_buffer: ffi::CMSampleBufferRef,
+) {
+    if output_refcon.is_null() {
+        return;
     }
+    // SAFETY: refcon 是编码器持有的 CallbackContext，会话失效前一直有效
+    let context = unsafe { &*(output_refcon as *const CallbackContext) };
 
-    fn encode_frame(&self, frame_data: &[u8], force_key_frame: bool) -> Result<()> {
-        unsafe {
-            // 创建 CVPixelBuffer
-            let mut pixel_buffer: *mut std::ffi::c_void = ptr::null_mut();
-            let status = CVPixelBufferCreate(
-                ptr::null_mut(),
-                self.width,
-                self.height,
-                kCVPixelFormatType_32ARGB,
-                ptr::null_mut(),
-                &mut pixel_buffer,
-            );
-
-            if status != 0 {
-                return Err(anyhow!("创建 CVPixelBuffer 失败: {}", status));
-            }
+    if status != 0 {
+        tracing::error!("VideoToolbox 编码回调错误: {}", status);
+        *context.error.lock().unwrap() = Some(format!("VideoToolbox 编码回调错误: {}", status));
+        return;
+    }
+    // 丢帧时 sample_buffer 为空
+    if sample_buffer.is_null() {
+        return;
+    }
 
-            // 锁定并填充像素数据
-            CVPixelBufferLockBaseAddress(pixel_buffer as CVPixelBufferRef, 0);
+    match unsafe { read_sample(sample_buffer) } {
+        Ok((data, is_key_frame)) => {
+            context.samples.lock().unwrap().push_back(EncodedSample {
+                data,
+                is_key_frame,
+                timestamp: source_frame_refcon as usize as u64,
+            });
+        }
+        Err(e) => {
+            tracing::error!("读取 VideoToolbox 输出失败: {}", e);
+            *context.error.lock().unwrap() = Some(e.to_string());
+        }
+    }
+}
 
-            let dst_ptr = CVPixelBufferGetBaseAddress(pixel_buffer as CVPixelBufferRef) as *mut u8;
-            let bytes_per_row = CVPixelBufferGetBytesPerRow(pixel_buffer as CVPixelBufferRef);
-            let row_size = self.width as usize * 4;
+/// 把 CMSampleBuffer 转换为 Annex B 访问单元，返回 (码流, 是否关键帧)
+#[cfg(target_os = "macos")]
+unsafe fn read_sample(sample_buffer: ffi::CMSampleBufferRef) -> Result<(Vec<u8>, bool)> {
+    if ffi::CMSampleBufferDataIsReady(sample_buffer) == 0 {
+        return Err(anyhow!("样本数据未就绪"));
+    }
 
-            for y in 0..self.height as usize {
-                let src_offset = y * row_size;
-                let dst_offset = y * bytes_per_row as usize;
-                std::ptr::copy_nonoverlapping(
-                    frame_data.as_ptr().add(src_offset),
-                    dst_ptr.add(dst_offset),
-                    row_size,
+    let is_key_frame = is_sync_sample(sample_buffer);
+
+    // 关键帧: 从格式描述取出 SPS/PPS
+    let format = ffi::CMSampleBufferGetFormatDescription(sample_buffer);
+    let mut parameter_sets: Vec<&[u8]> = Vec::new();
+    let mut length_size: i32 = 4;
+    if !format.is_null() {
+        let mut count = 0usize;
+        let status = ffi::CMVideoFormatDescriptionGetH264ParameterSetAtIndex(
+            format,
+            0,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &mut count,
+            &mut length_size,
+        );
+        if status != 0 {
+            return Err(anyhow!("读取参数集数量失败: {}", status));
+        }
+        if is_key_frame {
+            for index in 0..count {
+                let mut pointer: *const u8 = ptr::null();
+                let mut size = 0usize;
+                let status = ffi::CMVideoFormatDescriptionGetH264ParameterSetAtIndex(
+                    format,
+                    index,
+                    &mut pointer,
+                    &mut size,
+                    ptr::null_mut(),
+                    ptr::null_mut(),
                 );
+                if status != 0 || pointer.is_null() {
+                    return Err(anyhow!("读取参数集 {} 失败: {}", index, status));
+                }
+                parameter_sets.push(std::slice::from_raw_parts(pointer, size));
             }
-
-            CVPixelBufferUnlockBaseAddress(pixel_buffer as CVPixelBufferRef, 0);
-
-            // 编码帧
-            let mut flags: VTEncodeInfoFlags = 0;
-            if force_key_frame {
-                flags |= kVTEncodeInfoFlags_ForceKeyFrame;
-            }
-
-            let current_pts = *self.pts.lock().unwrap();
-            let timestamp = CMTime {
-                value: current_pts,
-                timescale: 30,
-                flags: 1,
-                epoch: 0,
-            };
-
-            let status = VTCompressionSessionEncodeFrame(
-                self.session as VTCompressionSessionRef,
-                pixel_buffer as CVPixelBufferRef,
-                timestamp,
-                kCMTimeInvalid,
-                flags,
-                ptr::null_mut(),
-                ptr::null_mut(),
-            );
-
-            if status != 0 {
-                return Err(anyhow!("VideoToolbox 编码帧失败: {}", status));
-            }
-
-            // 更新 PTS
-            *self.pts.lock().unwrap() += 1;
-
-            Ok(())
         }
     }
+
+    let block = ffi::CMSampleBufferGetDataBuffer(sample_buffer);
+    if block.is_null() {
+        return Err(anyhow!("样本没有数据缓冲区"));
+    }
+    let total = ffi::CMBlockBufferGetDataLength(block);
+
+    // 连续内存直接读取，否则复制出来
+    let mut copied = Vec::new();
+    let mut length_at_offset = 0usize;
+    let mut data_pointer: *mut std::os::raw::c_char = ptr::null_mut();
+    let status = ffi::CMBlockBufferGetDataPointer(block, 0, &mut length_at_offset, ptr::null_mut(), &mut data_pointer);
+    let avcc: &[u8] = if status == 0 && !data_pointer.is_null() && length_at_offset == total {
+        std::slice::from_raw_parts(data_pointer as *const u8, total)
+    } else {
+        copied.resize(total, 0);
+        let status = ffi::CMBlockBufferCopyDataBytes(block, 0, total, copied.as_mut_ptr() as *mut c_void);
+        if status != 0 {
+            return Err(anyhow!("复制样本数据失败: {}", status));
+        }
+        &copied
+    };
+
+    let mut out = Vec::with_capacity(total + parameter_sets.iter().map(|s| s.len() + 4).sum::<usize>());
+    assemble_access_unit(avcc, length_size as usize, &parameter_sets, &mut out)?;
+    Ok((out, is_key_frame))
 }
 
+/// 样本附件中没有 NotSync 或其值为 false 时是关键帧
 #[cfg(target_os = "macos")]
-impl Drop for CompressionSession {
-    fn drop(&mut self) {
-        unsafe {
-            if !self.session.is_null() {
-                VTCompressionSessionInvalidate(self.session as VTCompressionSessionRef);
-            }
-        }
+unsafe fn is_sync_sample(sample_buffer: ffi::CMSampleBufferRef) -> bool {
+    let attachments = ffi::CMSampleBufferGetSampleAttachmentsArray(sample_buffer, 0);
+    if attachments.is_null() || ffi::CFArrayGetCount(attachments) == 0 {
+        return true;
+    }
+    let dict = ffi::CFArrayGetValueAtIndex(attachments, 0);
+    if dict.is_null() {
+        return true;
     }
+    let not_sync = ffi::CFDictionaryGetValue(dict, ffi::kCMSampleAttachmentKey_NotSync as *const c_void);
+    not_sync.is_null() || ffi::CFBooleanGetValue(not_sync) == 0
 }
 
 // ============================================================================
-// VideoToolbox FFI 类型定义
+// VideoToolbox FFI
 // ============================================================================
 
-/// OSStatus 类型
-pub type OSStatus = i32;
-
-/// VTCompressionSessionRef (不完整类型)
-#[repr(C)]
-pub struct VTCompressionSessionRef {
-    _private: [u8; 0],
-}
-
-/// CVPixelBufferRef (不完整类型)
-#[repr(C)]
-pub struct CVPixelBufferRef {
-    _private: [u8; 0],
-}
+#[cfg(target_os = "macos")]
+#[allow(non_upper_case_globals, non_snake_case)]
+mod ffi {
+    use std::ffi::c_void;
+    use std::os::raw::c_char;
+
+    pub use core_foundation::string::CFStringRef;
+    pub type OSStatus = i32;
+    pub type VTEncodeInfoFlags = u32;
+    pub type VTCompressionSessionRef = *mut c_void;
+    pub type CVPixelBufferRef = *mut c_void;
+    pub type CVPixelBufferPoolRef = *mut c_void;
+    pub type CMSampleBufferRef = *mut c_void;
+    pub type CMBlockBufferRef = *mut c_void;
+    pub type CMFormatDescriptionRef = *mut c_void;
+    pub type CFArrayRef = *const c_void;
+    pub type CFDictionaryRef = core_foundation::dictionary::CFDictionaryRef;
+
+    /// CMTime
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct CMTime {
+        pub value: i64,
+        pub timescale: i32,
+        pub flags: u32,
+        pub epoch: i64,
+    }
 
-/// CMSampleBufferRef (不完整类型)
-#[repr(C)]
-pub struct CMSampleBufferRef {
-    _private: [u8; 0],
-}
+    pub type OutputCallback = extern "C" fn(*mut c_void, *mut c_void, OSStatus, VTEncodeInfoFlags, CMSampleBufferRef);
+
+    /// 'avc1'
+    pub const kCMVideoCodecType_H264: u32 = u32::from_be_bytes(*b"avc1");
+    /// '420v' (NV12, video range)
+    pub const kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange: u32 = u32::from_be_bytes(*b"420v");
+
+    #[link(name = "VideoToolbox", kind = "framework")]
+    extern "C" {
+        pub static kVTVideoEncoderSpecification_EnableHardwareAcceleratedVideoEncoder: CFStringRef;
+        pub static kVTVideoEncoderSpecification_EnableLowLatencyRateControl: CFStringRef;
+        pub static kVTCompressionPropertyKey_RealTime: CFStringRef;
+        pub static kVTCompressionPropertyKey_ProfileLevel: CFStringRef;
+        pub static kVTCompressionPropertyKey_AverageBitRate: CFStringRef;
+        pub static kVTCompressionPropertyKey_DataRateLimits: CFStringRef;
+        pub static kVTCompressionPropertyKey_MaxKeyFrameInterval: CFStringRef;
+        pub static kVTCompressionPropertyKey_AllowFrameReordering: CFStringRef;
+        pub static kVTCompressionPropertyKey_ExpectedFrameRate: CFStringRef;
+        pub static kVTProfileLevel_H264_Baseline_AutoLevel: CFStringRef;
+        pub static kVTProfileLevel_H264_Main_AutoLevel: CFStringRef;
+        pub static kVTProfileLevel_H264_High_AutoLevel: CFStringRef;
+        pub static kVTEncodeFrameOptionKey_ForceKeyFrame: CFStringRef;
+
+        pub fn VTCompressionSessionCreate(
+            allocator: *const c_void,
+            width: i32,
+            height: i32,
+            codec_type: u32,
+            encoder_specification: CFDictionaryRef,
+            source_image_buffer_attributes: CFDictionaryRef,
+            compressed_data_allocator: *const c_void,
+            output_callback: Option<OutputCallback>,
+            output_callback_refcon: *mut c_void,
+            session_out: *mut VTCompressionSessionRef,
+        ) -> OSStatus;
+        pub fn VTSessionSetProperty(session: VTCompressionSessionRef, key: CFStringRef, value: *const c_void) -> OSStatus;
+        pub fn VTCompressionSessionPrepareToEncodeFrames(session: VTCompressionSessionRef) -> OSStatus;
+        pub fn VTCompressionSessionGetPixelBufferPool(session: VTCompressionSessionRef) -> CVPixelBufferPoolRef;
+        pub fn VTCompressionSessionEncodeFrame(
+            session: VTCompressionSessionRef,
+            image_buffer: CVPixelBufferRef,
+            presentation_timestamp: CMTime,
+            duration: CMTime,
+            frame_properties: CFDictionaryRef,
+            source_frame_refcon: *mut c_void,
+            info_flags_out: *mut VTEncodeInfoFlags,
+        ) -> OSStatus;
+        pub fn VTCompressionSessionCompleteFrames(session: VTCompressionSessionRef, until: CMTime) -> OSStatus;
+        pub fn VTCompressionSessionInvalidate(session: VTCompressionSessionRef);
+    }
 
-/// CMBlockBufferRef (不完整类型)
-#[repr(C)]
-pub struct CMBlockBufferRef {
-    _private: [u8; 0],
-}
+    #[link(name = "CoreMedia", kind = "framework")]
+    extern "C" {
+        pub static kCMTimeInvalid: CMTime;
+        pub static kCMSampleAttachmentKey_NotSync: CFStringRef;
+
+        pub fn CMTimeMake(value: i64, timescale: i32) -> CMTime;
+        pub fn CMSampleBufferDataIsReady(sbuf: CMSampleBufferRef) -> u8;
+        pub fn CMSampleBufferGetDataBuffer(sbuf: CMSampleBufferRef) -> CMBlockBufferRef;
+        pub fn CMSampleBufferGetFormatDescription(sbuf: CMSampleBufferRef) -> CMFormatDescriptionRef;
+        pub fn CMSampleBufferGetSampleAttachmentsArray(sbuf: CMSampleBufferRef, create_if_necessary: u8) -> CFArrayRef;
+        pub fn CMVideoFormatDescriptionGetH264ParameterSetAtIndex(
+            description: CMFormatDescriptionRef,
+            index: usize,
+            pointer_out: *mut *const u8,
+            size_out: *mut usize,
+            count_out: *mut usize,
+            nal_header_length_out: *mut i32,
+        ) -> OSStatus;
+        pub fn CMBlockBufferGetDataLength(buffer: CMBlockBufferRef) -> usize;
+        pub fn CMBlockBufferGetDataPointer(
+            buffer: CMBlockBufferRef,
+            offset: usize,
+            length_at_offset_out: *mut usize,
+            total_length_out: *mut usize,
+            data_pointer_out: *mut *mut c_char,
+        ) -> OSStatus;
+        pub fn CMBlockBufferCopyDataBytes(
+            buffer: CMBlockBufferRef,
+            offset: usize,
+            length: usize,
+            destination: *mut c_void,
+        ) -> OSStatus;
+    }
 
-/// CMTime
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct CMTime {
-    pub value: i64,
-    pub timescale: i32,
-    pub flags: u32,
-    pub epoch: u64,
-}
+    #[link(name = "CoreVideo", kind = "framework")]
+    extern "C" {
+        pub static kCVPixelBufferPixelFormatTypeKey: CFStringRef;
+        pub static kCVPixelBufferWidthKey: CFStringRef;
+        pub static kCVPixelBufferHeightKey: CFStringRef;
+
+        pub fn CVPixelBufferPoolCreatePixelBuffer(
+            allocator: *const c_void,
+            pool: CVPixelBufferPoolRef,
+            pixel_buffer_out: *mut CVPixelBufferRef,
+        ) -> i32;
+        pub fn CVPixelBufferLockBaseAddress(buffer: CVPixelBufferRef, flags: u64) -> i32;
+        pub fn CVPixelBufferUnlockBaseAddress(buffer: CVPixelBufferRef, flags: u64) -> i32;
+        pub fn CVPixelBufferGetBaseAddressOfPlane(buffer: CVPixelBufferRef, plane: usize) -> *mut c_void;
+        pub fn CVPixelBufferGetBytesPerRowOfPlane(buffer: CVPixelBufferRef, plane: usize) -> usize;
+        pub fn CVPixelBufferGetHeightOfPlane(buffer: CVPixelBufferRef, plane: usize) -> usize;
+        pub fn CVPixelBufferRelease(buffer: CVPixelBufferRef);
+    }
 
-/// VTEncodeInfoFlags
-pub type VTEncodeInfoFlags = u32;
-
-// 常量定义
-const kCMVideoCodecType_H264: u32 = 0x61766331; // 'avc1'
-const kCVPixelFormatType_32ARGB: u32 = 32; // BGRA
-const kCMTimeInvalid: CMTime = CMTime { value: 0, timescale: 0, flags: 0, epoch: 0 };
-const kVTEncodeInfoFlags_ForceKeyFrame: VTEncodeInfoFlags = 1 << 0;
-
-// 外部函数声明
-#[link(name = "VideoToolbox", kind = "framework")]
-#[link(name = "CoreMedia", kind = "framework")]
-#[link(name = "CoreVideo", kind = "framework")]
-extern "C" {
-    fn VTCompressionSessionCreate(
-        allocator: *const c_void,
-        width: u32,
-        height: u32,
-        codec_type: u32,
-        encoder_spec: *const c_void,
-        compression_spec: *const c_void,
-        pixel_buffer_attr: *const c_void,
-        output_callback: Option<
-            extern "C" fn(
-                *mut c_void,
-                *mut c_void,
-                OSStatus,
-                VTEncodeInfoFlags,
-                CMSampleBufferRef,
-            ),
-        >,
-        refcon: *mut c_void,
-        session_out: *mut VTCompressionSessionRef,
-    ) -> OSStatus;
-
-    fn VTCompressionSessionPrepareToEncodeFrames(
-        session: VTCompressionSessionRef,
-    ) -> OSStatus;
-
-    fn VTCompressionSessionEncodeFrame(
-        session: VTCompressionSessionRef,
-        pixel_buffer: CVPixelBufferRef,
-        presentation_timestamp: CMTime,
-        duration: CMTime,
-        frame_properties: VTEncodeInfoFlags,
-        sourceFrameRefcon: *mut c_void,
-        infoFlagsOut: *mut VTEncodeInfoFlags,
-    ) -> OSStatus;
-
-    fn VTCompressionSessionInvalidate(session: VTCompressionSessionRef);
-
-    fn CVPixelBufferCreate(
-        allocator: *const c_void,
-        width: u32,
-        height: u32,
-        pixel_format_type: u32,
-        pixel_buffer_attributes: *const c_void,
-        pixel_buffer_out: *mut CVPixelBufferRef,
-    ) -> OSStatus;
-
-    fn CVPixelBufferLockBaseAddress(
-        pixelBuffer: CVPixelBufferRef,
-        flags: u32,
-    ) -> OSStatus;
-
-    fn CVPixelBufferUnlockBaseAddress(
-        pixelBuffer: CVPixelBufferRef,
-        flags: u32,
-    ) -> OSStatus;
-
-    fn CVPixelBufferGetBaseAddress(pixelBuffer: CVPixelBufferRef) -> *mut c_void;
-
-    fn CVPixelBufferGetBytesPerRow(pixelBuffer: CVPixelBufferRef) -> i32;
-
-    fn CMSampleBufferGetDataBuffer(sbuf: CMSampleBufferRef) -> CMBlockBufferRef;
-
-    fn CMBlockBufferGetDataLength(buffer: CMBlockBufferRef) -> i32;
-
-    fn CMBlockBufferGetDataPointer(
-        buffer: CMBlockBufferRef,
-        offset: i32,
-        lengthAtOffset: *mut i32,
-        totalLength: *mut i32,
-        dataPointer: *mut *mut u8,
-    ) -> OSStatus;
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub fn CFRelease(cf: *const c_void);
+        pub fn CFArrayGetCount(array: CFArrayRef) -> isize;
+        pub fn CFArrayGetValueAtIndex(array: CFArrayRef, index: isize) -> *const c_void;
+        pub fn CFDictionaryGetValue(dict: *const c_void, key: *const c_void) -> *const c_void;
+        pub fn CFBooleanGetValue(boolean: *const c_void) -> u8;
+    }
 }
 
 #[cfg(not(target_os = "macos"))]
@@ -491,3 +696,96 @@ impl VideoToolboxEncoder {
         Err(anyhow::anyhow!("VideoToolbox 只在 macOS 上可用"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::bitstream::{nal_type, nal_unit_type, parse_sps, split_annex_b};
+
+    const SPS: [u8; 9] = [0x67, 0x42, 0xc0, 0x1e, 0xda, 0x02, 0x80, 0xf6, 0x40];
+    const PPS: [u8; 4] = [0x68, 0xce, 0x38, 0x80];
+
+    fn avcc(nals: &[&[u8]]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for nal in nals {
+            out.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+            out.extend_from_slice(nal);
+        }
+        out
+    }
+
+    #[test]
+    fn test_key_frame_access_unit_starts_with_parameter_sets() {
+        let idr = [0x65, 0x88, 0x84, 0x00, 0x33];
+        let mut out = Vec::new();
+        assemble_access_unit(&avcc(&[&idr]), 4, &[&SPS, &PPS], &mut out).unwrap();
+
+        let nals = split_annex_b(&out);
+        let types: Vec<_> = nals.iter().map(|nal| nal_unit_type(nal).unwrap()).collect();
+        assert_eq!(types, vec![nal_type::SPS, nal_type::PPS, nal_type::IDR]);
+
+        let sps = parse_sps(nals[0]).unwrap();
+        assert_eq!((sps.width, sps.height), (640, 480));
+    }
+
+    #[test]
+    fn test_delta_frame_access_unit_has_only_slices() {
+        let slice = [0x41, 0x9a, 0x02, 0x04];
+        let sei = [0x06, 0x05, 0x01, 0x80];
+        let mut out = Vec::new();
+        assemble_access_unit(&avcc(&[&sei, &slice]), 4, &[], &mut out).unwrap();
+
+        assert!(out.starts_with(&bitstream::START_CODE));
+        let nals = split_annex_b(&out);
+        assert_eq!(nals, vec![&sei[..], &slice[..]]);
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_encoded_bitstream_parses() {
+        use crate::encoder::hardware::{HardwareEncoder, HardwareEncoderConfig};
+        use crate::encoder::Frame;
+
+        let (width, height) = (320u32, 240u32);
+        let mut encoder = match VideoToolboxEncoder::new(width, height, HardwareEncoderConfig::default()) {
+            Ok(encoder) => encoder,
+            // 没有硬件编码器的虚拟机
+            Err(_) => return,
+        };
+
+        let mut packets = Vec::new();
+        for i in 0..5u8 {
+            let frame = Frame {
+                width,
+                height,
+                data: vec![i.wrapping_mul(40); (width * height * 4) as usize],
+                timestamp: i as u64 * 33,
+                stride: width as usize * 4,
+                gpu: None,
+            };
+            if let Some(packet) = encoder.encode(&frame).unwrap() {
+                packets.push(packet);
+            }
+        }
+        if let Some(packet) = encoder.flush().unwrap() {
+            packets.push(packet);
+        }
+
+        let first = packets.first().expect("没有编码输出");
+        assert!(first.is_key_frame);
+        assert_eq!(first.timestamp, 0);
+
+        let nals = split_annex_b(&first.data);
+        assert_eq!(nal_unit_type(nals[0]), Some(nal_type::SPS));
+        let sps = parse_sps(nals[0]).unwrap();
+        assert_eq!((sps.width, sps.height), (width, height));
+        assert!(nals.iter().any(|nal| nal_unit_type(nal) == Some(nal_type::PPS)));
+        assert!(nals.iter().any(|nal| nal_unit_type(nal) == Some(nal_type::IDR)));
+
+        for packet in &packets[1..] {
+            let nals = split_annex_b(&packet.data);
+            assert!(!nals.is_empty());
+            assert!(nals.iter().all(|nal| nal_unit_type(nal) != Some(nal_type::SPS)) || packet.is_key_frame);
+        }
+    }
+}
@@ -0,0 +1,426 @@
+//! VP9 编码器 (时域分层)
+//!
+//! 使用 libvpx-vp9 realtime 模式，按固定的时域分层模式编码 (3 层，周期 0-2-1-2):
+//! - 第 0 层 (基础层): 只参考基础层，1/4 帧率
+//! - 第 1 层: 只参考基础层，与基础层合计 1/2 帧率
+//! - 第 2 层: 不被其他帧参考，可以随时丢弃
+//!
+//! 多个观看者时只编码一次，网速慢的观看者只接收较低的层 ([`LayerFilter`])。
+//! 丢弃的帧不会被保留的帧参考，切换层数不需要额外的关键帧
+
+use std::time::Duration;
+
+#[cfg(feature = "h264")]
+use super::{apply_bitrate, attach_roi, colorspace, encoder_supports_roi, mark_key_frame};
+#[cfg(feature = "h264")]
+use crate::capture::Frame;
+#[cfg(feature = "h264")]
+use crate::quality::roi_encoder::RoiRegion;
+#[cfg(feature = "h264")]
+use anyhow::anyhow;
+use anyhow::Result;
+#[cfg(feature = "h264")]
+use ffmpeg_next as ffmpeg;
+
+/// 默认的时域层数
+pub const TEMPORAL_LAYERS: u8 = 3;
+
+/// 时域分层模式 (与 libvpx 的 `ts_layering_mode` 一致)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemporalPattern {
+    layers: u8,
+}
+
+impl TemporalPattern {
+    /// 1~3 层
+    pub fn new(layers: u8) -> Self {
+        Self { layers: layers.clamp(1, 3) }
+    }
+
+    pub fn layers(&self) -> u8 {
+        self.layers
+    }
+
+    /// 一个周期内各帧所在的层
+    fn layer_ids(&self) -> &'static [u8] {
+        match self.layers {
+            1 => &[0],
+            2 => &[0, 1],
+            _ => &[0, 2, 1, 2],
+        }
+    }
+
+    /// 从编码器打开起第 `index` 帧所在的层
+    pub fn layer_of(&self, index: u64) -> u8 {
+        let ids = self.layer_ids();
+        ids[(index % ids.len() as u64) as usize]
+    }
+
+    /// 接收到第 `layer` 层 (含) 为止的帧率占比
+    pub fn frame_rate_fraction(&self, layer: u8) -> f64 {
+        let ids = self.layer_ids();
+        ids.iter().filter(|&&id| id <= layer).count() as f64 / ids.len() as f64
+    }
+
+    /// 接收到第 `layer` 层 (含) 为止的累计码率 (kbps)
+    ///
+    /// 基础层帧只参考基础层，压缩效率较低，码率占比高于帧率占比
+    pub fn cumulative_bitrate(&self, layer: u8, bitrate_kbps: u32) -> u32 {
+        let percent: &[u32] = match self.layers {
+            1 => &[100],
+            2 => &[60, 100],
+            _ => &[40, 60, 100],
+        };
+        let percent = percent[usize::from(layer).min(percent.len() - 1)];
+        bitrate_kbps * percent / 100
+    }
+
+    /// FFmpeg libvpx 的 `ts-parameters` 选项
+    pub fn ts_parameters(&self, bitrate_kbps: u32) -> String {
+        let join = |values: Vec<String>| values.join(",");
+        let layers = 0..self.layers;
+        let ids = self.layer_ids();
+        format!(
+            concat!(
+                "ts_number_layers={}:ts_target_bitrate={}:ts_rate_decimator={}:",
+                "ts_periodicity={}:ts_layer_id={}:ts_layering_mode={}"
+            ),
+            self.layers,
+            join(layers.clone().map(|layer| self.cumulative_bitrate(layer, bitrate_kbps).to_string()).collect()),
+            join(layers.rev().map(|layer| (1u32 << layer).to_string()).collect()),
+            ids.len(),
+            join(ids.iter().map(u8::to_string).collect()),
+            self.layers,
+        )
+    }
+}
+
+/// 单个观看者的时域层过滤
+///
+/// - 降层立即生效: 较低的层不参考较高的层
+/// - 升层在下一个基础层帧 (或关键帧) 生效: 之前丢弃的帧可能被新增的层参考
+/// - 关键帧总是转发
+/// - 丢弃的帧时长累加到下一个发送的帧，RTP 时间戳保持连续
+#[derive(Debug, Clone)]
+pub struct LayerFilter {
+    max_layer: u8,
+    pending: Option<u8>,
+    skipped: Duration,
+}
+
+impl Default for LayerFilter {
+    /// 接收所有层
+    fn default() -> Self {
+        Self::new(u8::MAX)
+    }
+}
+
+impl LayerFilter {
+    pub fn new(max_layer: u8) -> Self {
+        Self {
+            max_layer,
+            pending: None,
+            skipped: Duration::ZERO,
+        }
+    }
+
+    /// 目标的最高层 (升层尚未生效时也返回新值)
+    pub fn max_layer(&self) -> u8 {
+        self.pending.unwrap_or(self.max_layer)
+    }
+
+    pub fn set_max_layer(&mut self, layer: u8) {
+        if layer <= self.max_layer {
+            self.max_layer = layer;
+            self.pending = None;
+        } else {
+            self.pending = Some(layer);
+        }
+    }
+
+    /// 判断是否转发一帧，转发时返回该帧的发送时长
+    pub fn admit(&mut self, layer: u8, is_key_frame: bool, duration: Duration) -> Option<Duration> {
+        if layer == 0 || is_key_frame {
+            if let Some(pending) = self.pending.take() {
+                self.max_layer = pending;
+            }
+        }
+        if layer <= self.max_layer || is_key_frame {
+            Some(std::mem::take(&mut self.skipped) + duration)
+        } else {
+            self.skipped += duration;
+            None
+        }
+    }
+}
+
+/// 编码后的一帧 VP9 数据
+#[derive(Debug, Clone)]
+pub struct LayeredFrame {
+    pub data: Vec<u8>,
+    pub is_key_frame: bool,
+    /// 时域层 (0 = 基础层)
+    pub temporal_layer: u8,
+}
+
+/// VP9 编码器 (使用 FFmpeg 的 libvpx-vp9)
+#[cfg(feature = "h264")]
+pub struct VP9Encoder {
+    width: u32,
+    height: u32,
+    fps: u32,
+    bitrate: u32,
+    pattern: TemporalPattern,
+    encoder: Option<ffmpeg::encoder::Video>,
+    yuv_frame: colorspace::ReusableVideoFrame,
+    pts: i64,
+    /// 编码器打开以来输入的帧数 (决定时域层，重新打开时清零)
+    layer_index: u64,
+    key_frame_interval: u64,
+    frame_count: u64,
+    /// 感兴趣区域 (随每帧附加给编码器)
+    roi: Vec<RoiRegion>,
+}
+
+#[cfg(feature = "h264")]
+unsafe impl Send for VP9Encoder {}
+
+#[cfg(feature = "h264")]
+impl VP9Encoder {
+    /// 创建新的 VP9 编码器 (`temporal_layers` 为 1~3 层)
+    pub fn new(width: u32, height: u32, fps: u32, bitrate: u32, temporal_layers: u8) -> Result<Self> {
+        let pattern = TemporalPattern::new(temporal_layers);
+        tracing::info!(
+            "创建 VP9 编码器: {}x{} @ {}fps, {}kbps, {} 个时域层",
+            width,
+            height,
+            fps,
+            bitrate,
+            pattern.layers()
+        );
+
+        // 初始化 FFmpeg (仅第一次)
+        ffmpeg::init()?;
+
+        let video_encoder = Self::open_encoder(width, height, fps, bitrate, pattern)?;
+
+        // 复用的 YUV420P 帧 (SIMD 颜色转换)
+        let yuv_frame = colorspace::ReusableVideoFrame::new(
+            ffmpeg::format::Pixel::YUV420P,
+            width,
+            height,
+            colorspace::PixelLayout::Rgba,
+        );
+
+        tracing::info!("VP9 编码器创建成功 (realtime mode)");
+        Ok(VP9Encoder {
+            width,
+            height,
+            fps,
+            bitrate,
+            pattern,
+            encoder: Some(video_encoder),
+            yuv_frame,
+            pts: 0,
+            layer_index: 0,
+            key_frame_interval: 30,
+            frame_count: 0,
+            roi: Vec::new(),
+        })
+    }
+
+    /// 按给定码控参数打开编码器上下文
+    fn open_encoder(
+        width: u32,
+        height: u32,
+        fps: u32,
+        bitrate: u32,
+        pattern: TemporalPattern,
+    ) -> Result<ffmpeg::encoder::Video> {
+        // 时域分层参数是 libvpx 的私有选项，不能使用其他 VP9 编码器
+        let encoder = ffmpeg::encoder::find_by_name("libvpx-vp9")
+            .ok_or_else(|| anyhow!("找不到 VP9 编码器 (需要 libvpx-vp9)"))?;
+
+        // 配置编码器
+        let context = ffmpeg::codec::context::Context::new_with_codec(encoder);
+        let mut encoder_context = context.encoder().video()?;
+
+        encoder_context.set_bit_rate((bitrate * 1000) as usize);
+        encoder_context.set_width(width);
+        encoder_context.set_height(height);
+        encoder_context.set_frame_rate(Some(ffmpeg::Rational(fps as i32, 1)));
+        encoder_context.set_time_base(ffmpeg::Rational(1, fps as i32));
+        encoder_context.set_gop(30);
+        encoder_context.set_format(ffmpeg::format::Pixel::YUV420P);
+
+        // 设置低延迟编码参数
+        let mut opts = ffmpeg::Dictionary::new();
+        opts.set("deadline", "realtime");
+        opts.set("cpu-used", "8");
+        opts.set("lag-in-frames", "0");
+        opts.set("error-resilient", "1");
+        opts.set("row-mt", "1");
+        if pattern.layers() > 1 {
+            opts.set("ts-parameters", &pattern.ts_parameters(bitrate));
+        }
+
+        // 打开编码器
+        Ok(encoder_context.open_with(opts)?)
+    }
+
+    /// 编码帧并返回 VP9 数据和所在的时域层
+    pub fn encode_frame(&mut self, frame: &Frame) -> Result<Option<LayeredFrame>> {
+        // 转换为 YUV420P
+        let yuv_frame = self.yuv_frame.convert(frame)?;
+
+        // 设置 PTS
+        yuv_frame.set_pts(Some(self.pts));
+        self.pts += 1;
+        self.frame_count += 1;
+        mark_key_frame(yuv_frame, self.frame_count % self.key_frame_interval == 0);
+        attach_roi(yuv_frame, &self.roi);
+
+        // lag-in-frames = 0，每个输入帧立即输出，层按输入顺序循环
+        let temporal_layer = self.pattern.layer_of(self.layer_index);
+        self.layer_index += 1;
+
+        // 编码
+        let encoder = self.encoder.as_mut().ok_or_else(|| anyhow!("编码器未初始化"))?;
+        encoder.send_frame(yuv_frame)?;
+
+        let mut packet = ffmpeg::packet::Packet::empty();
+        match encoder.receive_packet(&mut packet) {
+            Ok(_) => {
+                if packet.size() > 0 {
+                    Ok(Some(LayeredFrame {
+                        data: packet.data().unwrap_or(&[]).to_vec(),
+                        is_key_frame: packet.is_key(),
+                        temporal_layer,
+                    }))
+                } else {
+                    Ok(None)
+                }
+            }
+            Err(e) => {
+                let err_msg = e.to_string();
+                if err_msg.contains("more frames") || err_msg.contains("flushing") {
+                    Ok(None)
+                } else {
+                    Err(anyhow!("VP9 编码失败: {}", e))
+                }
+            }
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn temporal_layers(&self) -> u8 {
+        self.pattern.layers()
+    }
+
+    /// 请求下一帧为关键帧
+    pub fn request_key_frame(&mut self) {
+        self.frame_count = self.key_frame_interval - 1;
+    }
+
+    /// 设置码率 (kbps)
+    ///
+    /// libvpx 不支持运行时重配置，按新码率 (和各层码率) 重新打开编码器并从关键帧开始
+    pub fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()> {
+        if bitrate_kbps == self.bitrate {
+            return Ok(());
+        }
+        let Some(encoder) = self.encoder.as_mut() else {
+            return Ok(());
+        };
+
+        if !apply_bitrate(encoder, bitrate_kbps) {
+            self.encoder = None;
+            self.encoder = Some(Self::open_encoder(self.width, self.height, self.fps, bitrate_kbps, self.pattern)?);
+            // 新的编码器从分层周期的起点开始
+            self.layer_index = 0;
+            tracing::debug!("VP9 编码器已按 {} kbps 重新打开", bitrate_kbps);
+        }
+        self.bitrate = bitrate_kbps;
+        self.request_key_frame();
+        Ok(())
+    }
+
+    /// 设置之后编码的帧的感兴趣区域，返回 libvpx 是否会应用
+    pub fn set_roi(&mut self, regions: &[RoiRegion]) -> bool {
+        self.roi.clear();
+        self.roi.extend_from_slice(regions);
+        self.encoder.as_ref().is_some_and(encoder_supports_roi)
+    }
+}
+
+/// VP9Encoder 占位符 (当 h264 feature 未启用时)
+#[cfg(not(feature = "h264"))]
+pub struct VP9Encoder;
+
+#[cfg(not(feature = "h264"))]
+impl VP9Encoder {
+    pub fn new(_width: u32, _height: u32, _fps: u32, _bitrate: u32, _temporal_layers: u8) -> Result<Self> {
+        Err(anyhow::anyhow!("VP9 编码器需要启用 h264 feature (FFmpeg)"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(33);
+
+    #[test]
+    fn test_temporal_pattern() {
+        let pattern = TemporalPattern::new(3);
+        let layers: Vec<u8> = (0..8).map(|index| pattern.layer_of(index)).collect();
+        assert_eq!(layers, vec![0, 2, 1, 2, 0, 2, 1, 2]);
+        assert_eq!(pattern.frame_rate_fraction(0), 0.25);
+        assert_eq!(pattern.frame_rate_fraction(1), 0.5);
+        assert_eq!(pattern.frame_rate_fraction(2), 1.0);
+        assert_eq!(
+            pattern.ts_parameters(2000),
+            "ts_number_layers=3:ts_target_bitrate=800,1200,2000:ts_rate_decimator=4,2,1:\
+             ts_periodicity=4:ts_layer_id=0,2,1,2:ts_layering_mode=3"
+        );
+
+        assert_eq!(TemporalPattern::new(2).layer_of(3), 1);
+        assert_eq!(TemporalPattern::new(9).layers(), 3);
+        assert_eq!(TemporalPattern::new(1).cumulative_bitrate(0, 1000), 1000);
+    }
+
+    #[test]
+    fn test_layer_filter_downgrade() {
+        let mut filter = LayerFilter::default();
+        assert_eq!(filter.admit(2, false, FRAME), Some(FRAME));
+
+        // 只接收基础层: 增强层帧的时长累加到下一个基础层帧
+        filter.set_max_layer(0);
+        assert_eq!(filter.admit(1, false, FRAME), None);
+        assert_eq!(filter.admit(2, false, FRAME), None);
+        assert_eq!(filter.admit(0, false, FRAME), Some(FRAME * 3));
+        // 关键帧总是转发
+        assert_eq!(filter.admit(2, true, FRAME), Some(FRAME));
+    }
+
+    #[test]
+    fn test_layer_filter_upgrade_waits_for_base_layer() {
+        let mut filter = LayerFilter::new(0);
+        filter.admit(0, false, FRAME);
+
+        // 周期中途升层: 第 2 层帧可能参考已丢弃的第 1 层帧，等下一个基础层帧
+        filter.set_max_layer(2);
+        assert_eq!(filter.max_layer(), 2);
+        assert_eq!(filter.admit(2, false, FRAME), None);
+        assert_eq!(filter.admit(0, false, FRAME), Some(FRAME * 2));
+        assert_eq!(filter.admit(2, false, FRAME), Some(FRAME));
+        assert_eq!(filter.admit(1, false, FRAME), Some(FRAME));
+    }
+}
@@ -0,0 +1,21 @@
+//! 推流引擎
+//!
+//! - [`sink`]: 媒体发送端抽象 ([`MediaSink`])，由 VideoClient (WebSocket)、HostSession (WebRTC) 等实现
+//! - [`stage`]: 编码前的处理阶段 (静态画面检测、场景切换检测等)，由流水线按顺序调用
+//! - [`pipeline`]: 捕获 → 处理 → 编码 → 发送循环 ([`StreamingPipeline`])
+//! - [`supervisor`]: 后台任务的优雅退出、panic 重启、看门狗和健康状态
+
+#![allow(dead_code)]
+
+pub mod pipeline;
+pub mod sink;
+pub mod stage;
+pub mod supervisor;
+
+pub use pipeline::StreamingPipeline;
+pub use sink::{broadcast_video, MediaEvent, MediaSink, VideoSample};
+// 只有 WebRTC 会话使用
+#[cfg(feature = "webrtc")]
+pub use sink::{take_key_frame_requests, KeyFrameGate};
+pub use stage::{FrameStage, SceneChangeStage, StageOutput, StaticSceneStage};
+pub use supervisor::{Heartbeat, RestartPolicy, Supervisor, TaskEvent};
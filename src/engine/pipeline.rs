@@ -0,0 +1,252 @@
+//! 推流循环: 捕获 → 对齐 → 处理阶段 (见 [`super::stage`]) → 编码 → 发送
+//!
+//! 按帧间隔循环执行，直到取消令牌触发；每帧开始前把发送端的拥塞反馈应用到编码器
+//! (调整码率、请求关键帧)
+
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::sink::{broadcast_video, MediaSink, VideoSample};
+use super::stage::{run_stages, FrameStage};
+use crate::capture::Capturer;
+use crate::encoder::alignment::FrameAligner;
+use crate::encoder::Encoder;
+use crate::network::pacing::CongestionFeedback;
+
+/// 统计日志间隔
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 推流管线
+pub struct StreamingPipeline {
+    capturer: Box<dyn Capturer>,
+    encoder: Box<dyn Encoder>,
+    aligner: FrameAligner,
+    frame_interval: Duration,
+    stages: Vec<Box<dyn FrameStage>>,
+    sinks: Vec<Arc<dyn MediaSink>>,
+    feedback_tx: mpsc::UnboundedSender<CongestionFeedback>,
+    feedback_rx: mpsc::UnboundedReceiver<CongestionFeedback>,
+}
+
+impl StreamingPipeline {
+    /// 创建推流管线 (捕获器需已启动，编码器尺寸与 `aligner` 一致)
+    pub fn new(capturer: Box<dyn Capturer>, encoder: Box<dyn Encoder>, aligner: FrameAligner, fps: u32) -> Self {
+        let (feedback_tx, feedback_rx) = mpsc::unbounded_channel();
+        Self {
+            capturer,
+            encoder,
+            aligner,
+            frame_interval: Duration::from_millis(1000 / fps.max(1) as u64),
+            stages: Vec::new(),
+            sinks: Vec::new(),
+            feedback_tx,
+            feedback_rx,
+        }
+    }
+
+    /// 在编码前追加一个处理阶段 (按添加顺序执行)
+    pub fn add_stage(&mut self, stage: Box<dyn FrameStage>) {
+        self.stages.push(stage);
+    }
+
+    /// 添加发送端，并订阅它的拥塞反馈
+    pub async fn add_sink(&mut self, sink: Arc<dyn MediaSink>) {
+        let feedback_tx = self.feedback_tx.clone();
+        sink.on_feedback(Arc::new(move |feedback| {
+            let _ = feedback_tx.send(feedback);
+        }))
+        .await;
+        self.sinks.push(sink);
+    }
+
+    /// 编码尺寸对齐器 (输入事件坐标需要据此映射回原始屏幕)
+    pub fn aligner(&self) -> &FrameAligner {
+        &self.aligner
+    }
+
+    /// 把积压的拥塞反馈应用到编码器
+    fn apply_feedback(&mut self) {
+        while let Ok(feedback) = self.feedback_rx.try_recv() {
+            let result = match feedback {
+                CongestionFeedback::SetBitrate(kbps) => self.encoder.set_bitrate(kbps),
+                CongestionFeedback::KeyFrameNeeded => self.encoder.request_key_frame(),
+            };
+            if let Err(e) = result {
+                tracing::warn!("应用拥塞反馈失败: {}", e);
+            }
+        }
+    }
+
+    /// 捕获、编码并发送一帧
+    ///
+    /// 返回编码后的字节数，编码器没有输出时返回 None
+    pub async fn step(&mut self) -> Result<Option<usize>> {
+        self.apply_feedback();
+
+        let frame = self.aligner.align(self.capturer.capture()?);
+        let Some((frame, key_frame)) = run_stages(&mut self.stages, frame)? else {
+            return Ok(None);
+        };
+        if key_frame {
+            self.encoder.request_key_frame()?;
+        }
+        let Some(packet) = self.encoder.encode(&frame)? else {
+            return Ok(None);
+        };
+
+        let mut ready = Vec::with_capacity(self.sinks.len());
+        for sink in &self.sinks {
+            if sink.is_ready().await {
+                ready.push(sink.clone());
+            }
+        }
+        let sample = VideoSample {
+            data: &packet.data,
+            is_key_frame: packet.is_key_frame,
+            temporal_layer: 0,
+            tier: 0,
+            duration: self.frame_interval,
+        };
+        broadcast_video(&ready, sample).await;
+
+        Ok(Some(packet.data.len()))
+    }
+
+    /// 按帧率持续推流，`cancel` 触发后在当前帧结束时返回
+    pub async fn run(&mut self, cancel: CancellationToken) {
+        let mut frame_count = 0u64;
+        let mut last_report = Instant::now();
+
+        while !cancel.is_cancelled() {
+            let start = Instant::now();
+
+            match self.step().await {
+                Ok(Some(_)) => frame_count += 1,
+                Ok(None) => {}
+                Err(e) => tracing::error!("推流失败: {}", e),
+            }
+
+            if last_report.elapsed() >= REPORT_INTERVAL {
+                self.report(frame_count, last_report.elapsed()).await;
+                frame_count = 0;
+                last_report = Instant::now();
+            }
+
+            let elapsed = start.elapsed();
+            if elapsed < self.frame_interval {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tokio::time::sleep(self.frame_interval - elapsed) => {}
+                }
+            }
+        }
+    }
+
+    async fn report(&self, frame_count: u64, elapsed: Duration) {
+        let fps = frame_count as f64 / elapsed.as_secs_f64();
+        let mut congestion = None;
+        for sink in &self.sinks {
+            if let Some(stats) = sink.congestion_stats().await {
+                congestion = Some(stats);
+                break;
+            }
+        }
+
+        match congestion {
+            Some(stats) => tracing::info!(
+                "捕获: {} 帧, 实际 FPS: {:.1}, 目标码率: {} kbps, 累计丢帧: {}",
+                frame_count, fps, stats.target_bitrate_kbps, stats.dropped_frames
+            ),
+            None => tracing::info!("捕获: {} 帧, 实际 FPS: {:.1}", frame_count, fps),
+        }
+    }
+
+    /// 停止屏幕捕获
+    pub fn stop(&mut self) -> Result<()> {
+        self.capturer.stop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Frame;
+    use crate::encoder::SimpleEncoder;
+    use crate::engine::sink::MediaEvent;
+    use crate::network::pacing::CongestionCallback;
+    use std::sync::Mutex;
+
+    struct StaticCapturer;
+
+    impl Capturer for StaticCapturer {
+        fn capture(&mut self) -> Result<Frame> {
+            Ok(Frame::new(16, 16))
+        }
+        fn width(&self) -> u32 {
+            16
+        }
+        fn height(&self) -> u32 {
+            16
+        }
+        fn start(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn stop(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingSink {
+        ready: bool,
+        frames: Mutex<Vec<usize>>,
+        callback: Mutex<Option<CongestionCallback>>,
+    }
+
+    #[async_trait::async_trait]
+    impl MediaSink for RecordingSink {
+        fn name(&self) -> String {
+            "test".to_string()
+        }
+        async fn is_ready(&self) -> bool {
+            self.ready
+        }
+        async fn send_video(&self, sample: VideoSample<'_>) -> Result<()> {
+            self.frames.lock().unwrap().push(sample.data.len());
+            Ok(())
+        }
+        async fn send_event(&self, _event: &MediaEvent) -> Result<()> {
+            Ok(())
+        }
+        async fn on_feedback(&self, callback: CongestionCallback) {
+            *self.callback.lock().unwrap() = Some(callback);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_step_sends_to_ready_sinks() {
+        let encoder = SimpleEncoder::new(16, 16, 30, 2000).unwrap();
+        let mut pipeline = StreamingPipeline::new(
+            Box::new(StaticCapturer),
+            Box::new(encoder),
+            FrameAligner::even(16, 16).unwrap(),
+            30,
+        );
+
+        let ready = Arc::new(RecordingSink { ready: true, ..Default::default() });
+        let offline = Arc::new(RecordingSink::default());
+        pipeline.add_sink(ready.clone()).await;
+        pipeline.add_sink(offline.clone()).await;
+
+        // 反馈经回调进入队列，下一帧前应用到编码器
+        let callback = ready.callback.lock().unwrap().clone().unwrap();
+        callback(CongestionFeedback::KeyFrameNeeded);
+
+        let bytes = pipeline.step().await.unwrap().unwrap();
+        assert_eq!(ready.frames.lock().unwrap().as_slice(), &[bytes]);
+        assert!(offline.frames.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+//! 媒体发送端抽象
+//!
+//! 编码后的数据经 [`MediaSink`] 发出，推流循环不关心底层是 WebSocket、WebRTC 还是 QUIC。
+//! 发送端通过 `on_feedback` 回报拥塞 (降低码率、请求关键帧)，由推流循环转给编码器。
+//! 新加入的观看者和丢失参考帧的观看者 (PLI/FIR) 通过 [`MediaSink::take_key_frame_request`]
+//! 要求立即输出关键帧，不用等到下一个 GOP
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::network::pacing::{CongestionCallback, CongestionStats};
+use crate::signaling::chat::ChatMessage;
+use crate::signaling::control::ControlMessage;
+
+/// 编码后的一帧视频
+#[derive(Debug, Clone, Copy)]
+pub struct VideoSample<'a> {
+    pub data: &'a [u8],
+    pub is_key_frame: bool,
+    /// 时域层 (0 = 基础层；不分层的编码器始终为 0)，发送端可以丢弃观看者不需要的层
+    pub temporal_layer: u8,
+    /// simulcast 档位 (0 = 最高档)，按 rid 协商多编码的会话据此选择发送的编码
+    pub tier: usize,
+    /// 帧时长 (WebRTC 按此推进 RTP 时间戳)
+    pub duration: Duration,
+}
+
+/// 发给观看端的带外事件
+#[derive(Debug, Clone)]
+pub enum MediaEvent {
+    Control(ControlMessage),
+    Chat(ChatMessage),
+}
+
+/// 媒体发送端 (VideoClient、HostSession 等)
+#[async_trait]
+pub trait MediaSink: Send + Sync {
+    /// 日志中使用的名称
+    fn name(&self) -> String;
+
+    /// 是否可以发送 (未连接时推流循环跳过该发送端)
+    async fn is_ready(&self) -> bool {
+        true
+    }
+
+    /// 发送一帧视频
+    async fn send_video(&self, sample: VideoSample<'_>) -> Result<()>;
+
+    /// 发送一段编码后的音频
+    async fn send_audio(&self, _data: &[u8], _duration: Duration) -> Result<()> {
+        Err(anyhow!("{} 不支持音频", self.name()))
+    }
+
+    /// 发送带外事件
+    async fn send_event(&self, event: &MediaEvent) -> Result<()>;
+
+    /// 注册拥塞反馈回调 (默认: 发送端不产生反馈)
+    async fn on_feedback(&self, _callback: CongestionCallback) {}
+
+    /// 发送队列统计 (没有发送队列的发送端返回 None)
+    async fn congestion_stats(&self) -> Option<CongestionStats> {
+        None
+    }
+
+    /// 取出关键帧请求 (新会话或收到 PLI/FIR 后为 true，取出后清除)
+    fn take_key_frame_request(&self) -> bool {
+        false
+    }
+}
+
+/// 单个观看者的关键帧状态
+///
+/// 新会话在收到第一个关键帧之前丢弃差分帧 (解码器没有参考帧，只会花屏或报错)，
+/// 并立即请求关键帧；之后的 PLI/FIR 只请求关键帧，不再丢帧
+#[derive(Debug)]
+pub struct KeyFrameGate {
+    waiting: AtomicBool,
+    requested: AtomicBool,
+}
+
+impl Default for KeyFrameGate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyFrameGate {
+    /// 新会话: 等待关键帧并请求一个
+    pub fn new() -> Self {
+        Self {
+            waiting: AtomicBool::new(true),
+            requested: AtomicBool::new(true),
+        }
+    }
+
+    /// 接收端报告丢失参考帧 (PLI/FIR)
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+
+    /// 取出关键帧请求
+    pub fn take_request(&self) -> bool {
+        self.requested.swap(false, Ordering::Relaxed)
+    }
+
+    /// 是否发送这一帧: 等待期间只放行关键帧
+    pub fn admit(&self, is_key_frame: bool) -> bool {
+        if is_key_frame {
+            self.waiting.store(false, Ordering::Relaxed);
+            return true;
+        }
+        !self.waiting.load(Ordering::Relaxed)
+    }
+}
+
+/// 把一帧发给多个发送端，失败只记录日志
+///
+/// 返回发送成功的数量
+pub async fn broadcast_video<S: MediaSink + ?Sized>(sinks: &[Arc<S>], sample: VideoSample<'_>) -> usize {
+    let mut sent = 0;
+    for sink in sinks {
+        match sink.send_video(sample).await {
+            Ok(()) => sent += 1,
+            Err(e) => tracing::error!("[{}] 发送视频帧失败: {}", sink.name(), e),
+        }
+    }
+    sent
+}
+
+/// 取出所有发送端的关键帧请求 (每个发送端的请求都会被清除)
+pub fn take_key_frame_requests<S: MediaSink + ?Sized>(sinks: &[Arc<S>]) -> bool {
+    sinks.iter().fold(false, |requested, sink| sink.take_key_frame_request() | requested)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::bitstream::{nal_type, nal_unit_type, split_annex_b};
+    use std::sync::Mutex;
+
+    /// 记录收到的帧，按 [`KeyFrameGate`] 过滤
+    struct RecordingSink {
+        gate: KeyFrameGate,
+        received: Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl RecordingSink {
+        fn new() -> Arc<Self> {
+            Arc::new(Self {
+                gate: KeyFrameGate::new(),
+                received: Mutex::new(Vec::new()),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl MediaSink for RecordingSink {
+        fn name(&self) -> String {
+            "test".to_string()
+        }
+
+        async fn send_video(&self, sample: VideoSample<'_>) -> Result<()> {
+            if self.gate.admit(sample.is_key_frame) {
+                self.received.lock().unwrap().push(sample.data.to_vec());
+            }
+            Ok(())
+        }
+
+        async fn send_event(&self, _event: &MediaEvent) -> Result<()> {
+            Ok(())
+        }
+
+        fn take_key_frame_request(&self) -> bool {
+            self.gate.take_request()
+        }
+    }
+
+    fn sample(data: &[u8], is_key_frame: bool) -> VideoSample<'_> {
+        VideoSample {
+            data,
+            is_key_frame,
+            temporal_layer: 0,
+            tier: 0,
+            duration: Duration::from_millis(33),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_late_joiner_starts_with_idr() {
+        const P_SLICE: &[u8] = &[0, 0, 0, 1, 0x41, 0x9a, 0x02];
+        const IDR: &[u8] = &[0, 0, 0, 1, 0x67, 0x42, 0xc0, 0x1e, 0, 0, 0, 1, 0x68, 0xce, 0, 0, 0, 1, 0x65, 0x88];
+
+        let early = RecordingSink::new();
+        early.gate.admit(true);
+        early.gate.take_request();
+        let sinks = vec![Arc::clone(&early)];
+        broadcast_video(&sinks, sample(P_SLICE, false)).await;
+
+        // 中途加入的观看者请求关键帧，之前的差分帧不发给它
+        let late = RecordingSink::new();
+        let sinks = vec![Arc::clone(&early), Arc::clone(&late)];
+        assert!(take_key_frame_requests(&sinks));
+        assert!(!take_key_frame_requests(&sinks));
+        broadcast_video(&sinks, sample(P_SLICE, false)).await;
+        broadcast_video(&sinks, sample(IDR, true)).await;
+        broadcast_video(&sinks, sample(P_SLICE, false)).await;
+
+        let received = late.received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        let first = split_annex_b(&received[0]);
+        assert!(first.iter().any(|nal| nal_unit_type(nal) == Some(nal_type::IDR)));
+        assert_eq!(early.received.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_picture_loss_requests_without_dropping() {
+        let gate = KeyFrameGate::new();
+        assert!(gate.take_request());
+        assert!(gate.admit(true));
+
+        gate.request();
+        assert!(gate.take_request());
+        assert!(!gate.take_request());
+        assert!(gate.admit(false));
+    }
+}
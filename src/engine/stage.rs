@@ -0,0 +1,238 @@
+//! 推流管线中编码前的处理阶段
+//!
+//! 捕获 → [阶段 …] → 编码 → 分发。每个阶段可以修改帧 (如叠加水印)、丢弃帧 (如静态画面)
+//! 或要求编码器输出关键帧；新增处理只需实现 [`FrameStage`] 并加入管线
+
+use anyhow::Result;
+
+use crate::capture::Frame;
+use crate::quality::scene_change::SceneChangeDetector;
+use crate::quality::static_detector::{StaticDetectionConfig, StaticSceneDetector};
+
+/// 阶段处理结果
+#[derive(Debug)]
+pub enum StageOutput {
+    /// 继续处理
+    Frame(Frame),
+    /// 继续处理，并要求编码器把这一帧编码为关键帧
+    KeyFrame(Frame),
+    /// 静态画面的保活关键帧: 画面与上一帧相同，可以重发缓存的关键帧代替编码
+    Keepalive(Frame),
+    /// 丢弃这一帧 (后续阶段和编码都跳过)
+    Drop,
+}
+
+/// 编码前的处理阶段
+pub trait FrameStage: Send {
+    /// 日志中使用的名称
+    fn name(&self) -> &'static str;
+
+    fn process(&mut self, frame: Frame) -> Result<StageOutput>;
+
+    /// 捕获重启 (空闲恢复、分辨率变化) 后清空状态
+    fn reset(&mut self) {}
+}
+
+/// 静态画面检测: 画面没有变化时跳过编码，每隔 `keepalive_frames` 帧发送一个保活关键帧
+pub struct StaticSceneStage {
+    detector: StaticSceneDetector,
+    /// 差异低于该比例视为静态
+    threshold: f32,
+    keepalive_frames: u32,
+    consecutive_static: u32,
+    /// 检测到的静态帧数
+    pub static_frames: u64,
+    /// 因静态跳过编码的帧数
+    pub skipped_frames: u64,
+}
+
+impl StaticSceneStage {
+    /// 默认差异低于 1% 视为静态
+    const DEFAULT_THRESHOLD: f32 = 0.01;
+
+    pub fn new(keepalive_frames: u32) -> Self {
+        Self {
+            detector: StaticSceneDetector::new(StaticDetectionConfig::default()),
+            threshold: Self::DEFAULT_THRESHOLD,
+            keepalive_frames: keepalive_frames.max(1),
+            consecutive_static: 0,
+            static_frames: 0,
+            skipped_frames: 0,
+        }
+    }
+
+    /// 调整灵敏度 (画质预设切换时)
+    pub fn configure(&mut self, threshold: f32, keepalive_frames: u32) {
+        self.threshold = threshold;
+        self.keepalive_frames = keepalive_frames.max(1);
+        self.consecutive_static = 0;
+    }
+
+    /// 取出并清零统计 (静态帧数, 跳过帧数)
+    pub fn take_stats(&mut self) -> (u64, u64) {
+        (std::mem::take(&mut self.static_frames), std::mem::take(&mut self.skipped_frames))
+    }
+
+    /// 最近一帧是否为静态画面
+    pub fn is_static(&self) -> bool {
+        self.consecutive_static > 0
+    }
+}
+
+impl FrameStage for StaticSceneStage {
+    fn name(&self) -> &'static str {
+        "static-scene"
+    }
+
+    fn process(&mut self, frame: Frame) -> Result<StageOutput> {
+        // GPU 帧没有 CPU 像素数据，不参与静态检测
+        if frame.is_gpu() {
+            return Ok(StageOutput::Frame(frame));
+        }
+
+        let diff = match self.detector.detect(&frame) {
+            Ok(diff) => diff,
+            Err(e) => {
+                tracing::warn!("静态检测失败: {}，继续编码", e);
+                self.consecutive_static = 0;
+                return Ok(StageOutput::Frame(frame));
+            }
+        };
+        self.detector.update_previous_frame(&frame);
+
+        if diff.difference_ratio >= self.threshold {
+            self.consecutive_static = 0;
+            return Ok(StageOutput::Frame(frame));
+        }
+
+        self.static_frames += 1;
+        self.consecutive_static += 1;
+        if self.consecutive_static.is_multiple_of(self.keepalive_frames) {
+            tracing::debug!("静态场景，发送关键帧保持连接");
+            Ok(StageOutput::Keepalive(frame))
+        } else {
+            self.skipped_frames += 1;
+            Ok(StageOutput::Drop)
+        }
+    }
+
+    fn reset(&mut self) {
+        self.detector = StaticSceneDetector::new(StaticDetectionConfig::default());
+        self.consecutive_static = 0;
+    }
+}
+
+/// 场景切换检测: 画面大幅变化 (切换应用、全屏视频开始) 时要求编码器立即输出关键帧
+#[derive(Default)]
+pub struct SceneChangeStage {
+    detector: SceneChangeDetector,
+    /// 检测到的场景切换次数
+    pub scene_changes: u64,
+}
+
+impl SceneChangeStage {
+    /// 取出并清零场景切换次数
+    pub fn take_stats(&mut self) -> u64 {
+        std::mem::take(&mut self.scene_changes)
+    }
+}
+
+impl FrameStage for SceneChangeStage {
+    fn name(&self) -> &'static str {
+        "scene-change"
+    }
+
+    fn process(&mut self, frame: Frame) -> Result<StageOutput> {
+        // GPU 帧没有 CPU 像素数据，只能依靠周期关键帧
+        if frame.is_gpu() {
+            return Ok(StageOutput::Frame(frame));
+        }
+
+        let change = self.detector.detect(&frame);
+        if change.is_cut {
+            self.scene_changes += 1;
+            tracing::debug!(
+                "检测到场景切换 (SAD {:.2}, 直方图距离 {:.2})，插入关键帧",
+                change.sad,
+                change.histogram_distance
+            );
+            Ok(StageOutput::KeyFrame(frame))
+        } else {
+            Ok(StageOutput::Frame(frame))
+        }
+    }
+
+    fn reset(&mut self) {
+        self.detector.reset();
+    }
+}
+
+/// 依次执行各阶段，返回处理后的帧和是否需要关键帧；任一阶段丢弃时返回 None
+pub fn run_stages(stages: &mut [Box<dyn FrameStage>], frame: Frame) -> Result<Option<(Frame, bool)>> {
+    let mut frame = frame;
+    let mut key_frame = false;
+    for stage in stages.iter_mut() {
+        match stage.process(frame)? {
+            StageOutput::Frame(next) => frame = next,
+            StageOutput::KeyFrame(next) | StageOutput::Keepalive(next) => {
+                frame = next;
+                key_frame = true;
+            }
+            StageOutput::Drop => return Ok(None),
+        }
+    }
+    Ok(Some((frame, key_frame)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static_scene_skips_and_sends_keepalive() {
+        let mut stage = StaticSceneStage::new(3);
+        let mut outputs = Vec::new();
+        for _ in 0..7 {
+            outputs.push(match stage.process(Frame::new(64, 64)).unwrap() {
+                StageOutput::Frame(_) => 'F',
+                StageOutput::KeyFrame(_) => 'K',
+                StageOutput::Keepalive(_) => 'A',
+                StageOutput::Drop => 'D',
+            });
+        }
+
+        // 画面不变时只有每第 3 个静态帧发送保活关键帧
+        assert_eq!(outputs.iter().collect::<String>(), "DDADDAD");
+        assert_eq!(stage.take_stats(), (7, 5));
+        assert_eq!(stage.take_stats(), (0, 0));
+    }
+
+    #[test]
+    fn test_scene_change_requests_key_frame() {
+        let mut stages: Vec<Box<dyn FrameStage>> = vec![Box::new(SceneChangeStage::default())];
+        let mut white = Frame::new(64, 36);
+        white.data.fill(255);
+
+        let (_, key_frame) = run_stages(&mut stages, Frame::new(64, 36)).unwrap().unwrap();
+        assert!(!key_frame);
+        let (_, key_frame) = run_stages(&mut stages, white.clone()).unwrap().unwrap();
+        assert!(key_frame);
+        let (_, key_frame) = run_stages(&mut stages, white).unwrap().unwrap();
+        assert!(!key_frame);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_gpu_frames_bypass_detection() {
+        let gpu = || Frame::from_gpu(crate::capture::GpuFrame { width: 64, height: 64 });
+        let mut stages: Vec<Box<dyn FrameStage>> =
+            vec![Box::new(StaticSceneStage::new(3)), Box::new(SceneChangeStage::default())];
+
+        // 没有像素数据可比较: 重复的 GPU 帧既不跳过也不触发关键帧
+        for _ in 0..5 {
+            let (frame, key_frame) = run_stages(&mut stages, gpu()).unwrap().unwrap();
+            assert!(frame.is_gpu());
+            assert!(!key_frame);
+        }
+    }
+}
@@ -0,0 +1,576 @@
+//! 后台任务监管
+//!
+//! 被控端的捕获循环、信令事件处理、控制台命令等后台任务统一由 [`Supervisor`] 启动:
+//! - 退出时先取消 [`CancellationToken`]，任务在宽限期内自行收尾 (结束录制分段等)，
+//!   超时后才强制终止；没有收尾工作的一次性任务 ([`Supervisor::adopt`]) 直接终止
+//! - 任务 panic 时按 [`RestartPolicy`] 指数退避重启 (可重建的任务) 或标记为失败
+//! - [`Supervisor::spawn_watched`] 启动的任务还受看门狗监视: 超时没有 [`Heartbeat`] 时
+//!   视为卡死，终止后按同样的策略重启
+//! - [`Supervisor::health`] 汇报每个任务的状态和重启次数，[`Supervisor::subscribe`]
+//!   推送失败和重启事件 (用于通知 Viewer)
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio::task::{JoinError, JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+/// 任务 panic 后的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// 不重启 (持有一次性资源的任务，如事件接收端)
+    Never,
+    /// panic 后重启: 第 n 次连续失败等待 `backoff * 2^n` (不超过 `max_backoff`)，
+    /// 连续失败超过 `max_restarts` 次后放弃。稳定运行 [`STABLE_RUN`] 后连续失败次数清零
+    OnPanic { max_restarts: u32, backoff: Duration, max_backoff: Duration },
+}
+
+/// 任务运行超过该时间后再失败，视为偶发故障，重新从最短退避开始计算
+pub const STABLE_RUN: Duration = Duration::from_secs(60);
+
+/// 看门狗终止卡死的任务后，等待其结束的时间 (阻塞在同步调用中的任务要等调用返回)
+const ABORT_WAIT: Duration = Duration::from_secs(2);
+
+/// 第 `failures` 次连续失败后的退避时间
+fn backoff_delay(backoff: Duration, max_backoff: Duration, failures: u32) -> Duration {
+    backoff.saturating_mul(1u32 << failures.min(16)).min(max_backoff)
+}
+
+/// 任务状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskState {
+    Running,
+    /// panic 后等待重启
+    Restarting,
+    /// 正常结束
+    Finished,
+    /// panic 且不再重启
+    Failed,
+}
+
+impl fmt::Display for TaskState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TaskState::Running => "运行中",
+            TaskState::Restarting => "重启中",
+            TaskState::Finished => "已结束",
+            TaskState::Failed => "已失败",
+        })
+    }
+}
+
+/// 单个任务的健康状态
+#[derive(Debug, Clone)]
+pub struct TaskHealth {
+    pub state: TaskState,
+    pub restarts: u32,
+    /// 最近一次 panic 的信息
+    pub last_panic: Option<String>,
+    /// 本次启动时间
+    pub started_at: Instant,
+}
+
+impl fmt::Display for TaskHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.state)?;
+        if self.restarts > 0 {
+            write!(f, ", 重启 {} 次", self.restarts)?;
+        }
+        if let Some(panic) = &self.last_panic {
+            write!(f, ", 最近错误: {}", panic)?;
+        }
+        Ok(())
+    }
+}
+
+/// 任务失败和重启事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskEvent {
+    /// 任务 panic 或被看门狗终止，`restart_in` 后重启 (None 表示不再重启)
+    Failed { name: String, error: String, restart_in: Option<Duration> },
+    /// 任务已重启 (`restarts` 为累计重启次数)
+    Restarted { name: String, restarts: u32 },
+}
+
+/// 任务心跳 (由 [`Supervisor::spawn_watched`] 传给任务)
+///
+/// 任务在每轮循环中调用 [`Heartbeat::beat`]；需要长时间等待外部事件 (如空闲时等待 Viewer)
+/// 前调用 [`Heartbeat::pause`]，下次 `beat` 时恢复检查
+#[derive(Clone)]
+pub struct Heartbeat {
+    inner: Arc<HeartbeatState>,
+}
+
+struct HeartbeatState {
+    created: Instant,
+    /// 最近一次心跳 (相对 `created` 的毫秒数)
+    last: AtomicU64,
+    paused: AtomicBool,
+}
+
+impl Heartbeat {
+    fn new() -> Self {
+        Self {
+            inner: Arc::new(HeartbeatState {
+                created: Instant::now(),
+                last: AtomicU64::new(0),
+                paused: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// 报告任务仍在正常运行
+    pub fn beat(&self) {
+        let now = self.inner.created.elapsed().as_millis() as u64;
+        self.inner.last.store(now, Ordering::Relaxed);
+        self.inner.paused.store(false, Ordering::Relaxed);
+    }
+
+    /// 暂停检查，直到下次心跳
+    pub fn pause(&self) {
+        self.inner.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// 距最近一次心跳的时间 (暂停时为 None)
+    fn silence(&self) -> Option<Duration> {
+        if self.inner.paused.load(Ordering::Relaxed) {
+            return None;
+        }
+        let now = self.inner.created.elapsed().as_millis() as u64;
+        Some(Duration::from_millis(now.saturating_sub(self.inner.last.load(Ordering::Relaxed))))
+    }
+
+    /// 等待超过 `timeout` 没有心跳
+    async fn stalled(&self, timeout: Duration) {
+        let mut interval = tokio::time::interval((timeout / 4).max(Duration::from_millis(10)));
+        loop {
+            interval.tick().await;
+            if self.silence().is_some_and(|silence| silence >= timeout) {
+                return;
+            }
+        }
+    }
+}
+
+struct Inner {
+    health: Mutex<BTreeMap<String, TaskHealth>>,
+    monitors: Mutex<Vec<JoinHandle<()>>>,
+    events: broadcast::Sender<TaskEvent>,
+}
+
+impl Default for Inner {
+    fn default() -> Self {
+        Self {
+            health: Mutex::default(),
+            monitors: Mutex::default(),
+            events: broadcast::channel(64).0,
+        }
+    }
+}
+
+/// 后台任务监管者 (克隆后共享同一组任务)
+#[derive(Clone, Default)]
+pub struct Supervisor {
+    /// 通知任务优雅退出
+    cancel: CancellationToken,
+    /// 宽限期结束后强制终止任务
+    kill: CancellationToken,
+    inner: Arc<Inner>,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 启动可重建的任务: 每次 (重新) 启动都调用 `factory` 创建新的 future
+    pub fn spawn<F, Fut>(&self, name: &str, policy: RestartPolicy, mut factory: F)
+    where
+        F: FnMut(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.run(name, policy, None, move |cancel, _| factory(cancel));
+    }
+
+    /// 启动受看门狗监视的可重建任务: 超过 `timeout` 没有心跳时终止并按 `policy` 重启
+    ///
+    /// 阻塞在同步调用 (捕获、编码) 中的任务无法被立即终止，只能在调用返回后结束
+    pub fn spawn_watched<F, Fut>(&self, name: &str, policy: RestartPolicy, timeout: Duration, factory: F)
+    where
+        F: FnMut(CancellationToken, Heartbeat) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.run(name, policy, Some(timeout), factory);
+    }
+
+    fn run<F, Fut>(&self, name: &str, policy: RestartPolicy, watchdog: Option<Duration>, mut factory: F)
+    where
+        F: FnMut(CancellationToken, Heartbeat) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let name = name.to_string();
+        let supervisor = self.clone();
+        self.set_state(&name, TaskState::Running);
+
+        let monitor = tokio::spawn(async move {
+            // 连续失败次数，决定退避时间和是否放弃
+            let mut failures = 0;
+            loop {
+                let heartbeat = Heartbeat::new();
+                let started = Instant::now();
+                let task = tokio::spawn(factory(supervisor.cancel.child_token(), heartbeat.clone()));
+                let Some(result) = supervisor.watch(task, watchdog.map(|timeout| (heartbeat, timeout))).await else {
+                    return;
+                };
+
+                let message = match result {
+                    Ok(()) => {
+                        supervisor.set_state(&name, TaskState::Finished);
+                        return;
+                    }
+                    Err(message) => message,
+                };
+                tracing::error!("后台任务 {} 异常退出: {}", name, message);
+                if started.elapsed() >= STABLE_RUN {
+                    failures = 0;
+                }
+
+                let backoff = match policy {
+                    RestartPolicy::OnPanic { max_restarts, backoff, max_backoff }
+                        if failures < max_restarts && !supervisor.cancel.is_cancelled() =>
+                    {
+                        backoff_delay(backoff, max_backoff, failures)
+                    }
+                    _ => {
+                        supervisor.notify(TaskEvent::Failed {
+                            name: name.clone(),
+                            error: message.clone(),
+                            restart_in: None,
+                        });
+                        supervisor.record_panic(&name, message, TaskState::Failed);
+                        return;
+                    }
+                };
+
+                failures += 1;
+                supervisor.notify(TaskEvent::Failed {
+                    name: name.clone(),
+                    error: message.clone(),
+                    restart_in: Some(backoff),
+                });
+                supervisor.record_panic(&name, message, TaskState::Restarting);
+                tokio::select! {
+                    _ = supervisor.cancel.cancelled() => {
+                        supervisor.set_state(&name, TaskState::Finished);
+                        return;
+                    }
+                    _ = tokio::time::sleep(backoff) => {}
+                }
+                let restarts = supervisor.restarted(&name);
+                tracing::warn!("重启后台任务 {} (第 {} 次)", name, restarts);
+                supervisor.notify(TaskEvent::Restarted { name: name.clone(), restarts });
+            }
+        });
+        self.push_monitor(monitor);
+    }
+
+    /// 监管已启动的一次性任务
+    ///
+    /// 这类任务没有需要收尾的状态 (事件转发、控制台命令等)：panic 时只记录不重启，
+    /// 退出时直接终止
+    pub fn adopt(&self, name: &str, mut task: JoinHandle<()>) {
+        let name = name.to_string();
+        let supervisor = self.clone();
+        self.set_state(&name, TaskState::Running);
+
+        let monitor = tokio::spawn(async move {
+            tokio::select! {
+                result = &mut task => match result {
+                    Ok(()) => supervisor.set_state(&name, TaskState::Finished),
+                    Err(e) => {
+                        let message = panic_message(e);
+                        tracing::error!("后台任务 {} 异常退出: {}", name, message);
+                        supervisor.record_panic(&name, message, TaskState::Failed);
+                    }
+                },
+                _ = supervisor.cancel.cancelled() => {
+                    task.abort();
+                    let _ = task.await;
+                    supervisor.set_state(&name, TaskState::Finished);
+                }
+            }
+        });
+        self.push_monitor(monitor);
+    }
+
+    /// 各任务的健康状态 (按名称排序)
+    pub fn health(&self) -> Vec<(String, TaskHealth)> {
+        self.inner
+            .health
+            .lock()
+            .map(|health| health.iter().map(|(name, h)| (name.clone(), h.clone())).collect())
+            .unwrap_or_default()
+    }
+
+    /// 订阅任务失败和重启事件
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// 是否有任务失败 (panic 且不再重启)
+    pub fn is_degraded(&self) -> bool {
+        self.health().iter().any(|(_, health)| health.state == TaskState::Failed)
+    }
+
+    /// 通知所有任务退出，等待最多 `grace` 后强制终止剩余任务
+    pub async fn shutdown(&self, grace: Duration) {
+        self.cancel.cancel();
+
+        let monitors = self
+            .inner
+            .monitors
+            .lock()
+            .map(|mut monitors| std::mem::take(&mut *monitors))
+            .unwrap_or_default();
+        let all = futures::future::join_all(monitors);
+        tokio::pin!(all);
+
+        if tokio::time::timeout(grace, &mut all).await.is_err() {
+            let pending: Vec<String> = self
+                .health()
+                .into_iter()
+                .filter(|(_, health)| matches!(health.state, TaskState::Running | TaskState::Restarting))
+                .map(|(name, _)| name)
+                .collect();
+            tracing::warn!("后台任务未在 {} 秒内退出，强制终止: {}", grace.as_secs(), pending.join(", "));
+            self.kill.cancel();
+            all.await;
+        }
+    }
+
+    /// 等待任务结束 (失败时返回错误信息)；强制终止时返回 None
+    async fn watch(
+        &self,
+        mut task: JoinHandle<()>,
+        watchdog: Option<(Heartbeat, Duration)>,
+    ) -> Option<Result<(), String>> {
+        let timeout = watchdog.as_ref().map(|(_, timeout)| *timeout).unwrap_or_default();
+        let stalled = async {
+            match &watchdog {
+                Some((heartbeat, timeout)) => heartbeat.stalled(*timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            result = &mut task => Some(result.map_err(panic_message)),
+            _ = stalled => {
+                task.abort();
+                let _ = tokio::time::timeout(ABORT_WAIT, task).await;
+                Some(Err(format!("看门狗超时 ({} 秒没有心跳)", timeout.as_secs_f32())))
+            }
+            _ = self.kill.cancelled() => {
+                task.abort();
+                let _ = task.await;
+                None
+            }
+        }
+    }
+
+    fn push_monitor(&self, monitor: JoinHandle<()>) {
+        if let Ok(mut monitors) = self.inner.monitors.lock() {
+            monitors.retain(|monitor| !monitor.is_finished());
+            monitors.push(monitor);
+        }
+    }
+
+    fn update(&self, name: &str, f: impl FnOnce(&mut TaskHealth)) {
+        if let Ok(mut health) = self.inner.health.lock() {
+            let entry = health.entry(name.to_string()).or_insert_with(|| TaskHealth {
+                state: TaskState::Running,
+                restarts: 0,
+                last_panic: None,
+                started_at: Instant::now(),
+            });
+            f(entry);
+        }
+    }
+
+    fn set_state(&self, name: &str, state: TaskState) {
+        self.update(name, |health| health.state = state);
+    }
+
+    fn record_panic(&self, name: &str, message: String, state: TaskState) {
+        self.update(name, |health| {
+            health.state = state;
+            health.last_panic = Some(message);
+        });
+    }
+
+    /// 记录重启，返回累计重启次数
+    fn restarted(&self, name: &str) -> u32 {
+        let mut restarts = 0;
+        self.update(name, |health| {
+            health.state = TaskState::Running;
+            health.restarts += 1;
+            health.started_at = Instant::now();
+            restarts = health.restarts;
+        });
+        restarts
+    }
+
+    fn notify(&self, event: TaskEvent) {
+        // 没有订阅者时忽略
+        let _ = self.inner.events.send(event);
+    }
+}
+
+/// 提取 panic 信息
+fn panic_message(error: JoinError) -> String {
+    if !error.is_panic() {
+        return error.to_string();
+    }
+    let payload = error.into_panic();
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "未知 panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn state_of(supervisor: &Supervisor, name: &str) -> Option<TaskHealth> {
+        supervisor.health().into_iter().find(|(n, _)| n == name).map(|(_, h)| h)
+    }
+
+    #[tokio::test]
+    async fn test_restart_on_panic() {
+        let supervisor = Supervisor::new();
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let mut events = supervisor.subscribe();
+        let counter = runs.clone();
+        supervisor.spawn(
+            "flaky",
+            RestartPolicy::OnPanic {
+                max_restarts: 2,
+                backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(4),
+            },
+            move |_| {
+                let run = counter.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if run < 5 {
+                        panic!("boom {}", run);
+                    }
+                }
+            },
+        );
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while state_of(&supervisor, "flaky").map(|h| h.state) != Some(TaskState::Failed) {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        // 首次运行 + 2 次重启
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        let health = state_of(&supervisor, "flaky").unwrap();
+        assert_eq!(health.restarts, 2);
+        assert_eq!(health.last_panic.as_deref(), Some("boom 2"));
+        assert!(supervisor.is_degraded());
+
+        let first = events.recv().await.unwrap();
+        assert_eq!(
+            first,
+            TaskEvent::Failed {
+                name: "flaky".to_string(),
+                error: "boom 0".to_string(),
+                restart_in: Some(Duration::from_millis(1)),
+            }
+        );
+        assert_eq!(events.recv().await.unwrap(), TaskEvent::Restarted { name: "flaky".to_string(), restarts: 1 });
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff = Duration::from_millis(500);
+        let max_backoff = Duration::from_secs(5);
+        assert_eq!(backoff_delay(backoff, max_backoff, 0), Duration::from_millis(500));
+        assert_eq!(backoff_delay(backoff, max_backoff, 1), Duration::from_secs(1));
+        assert_eq!(backoff_delay(backoff, max_backoff, 3), Duration::from_secs(4));
+        assert_eq!(backoff_delay(backoff, max_backoff, 4), max_backoff);
+        assert_eq!(backoff_delay(backoff, max_backoff, 100), max_backoff);
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_restarts_stalled_task() {
+        let supervisor = Supervisor::new();
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let counter = runs.clone();
+        supervisor.spawn_watched(
+            "stalled",
+            RestartPolicy::OnPanic {
+                max_restarts: 1,
+                backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(1),
+            },
+            Duration::from_millis(40),
+            move |_, heartbeat| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    heartbeat.beat();
+                    std::future::pending::<()>().await;
+                }
+            },
+        );
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while state_of(&supervisor, "stalled").map(|h| h.state) != Some(TaskState::Failed) {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        let health = state_of(&supervisor, "stalled").unwrap();
+        assert!(health.last_panic.unwrap().contains("看门狗"));
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown_and_kill() {
+        let supervisor = Supervisor::new();
+        let finished = Arc::new(AtomicU32::new(0));
+
+        let done = finished.clone();
+        supervisor.spawn("graceful", RestartPolicy::Never, move |cancel| {
+            let done = done.clone();
+            async move {
+                cancel.cancelled().await;
+                done.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        // 一次性任务收到取消时直接终止
+        supervisor.adopt("adopted", tokio::spawn(std::future::pending()));
+        // 不响应取消的任务在宽限期后被强制终止
+        supervisor.spawn("stubborn", RestartPolicy::Never, |_| std::future::pending());
+
+        supervisor.shutdown(Duration::from_millis(50)).await;
+
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+        assert_eq!(state_of(&supervisor, "graceful").unwrap().state, TaskState::Finished);
+        assert_eq!(state_of(&supervisor, "adopted").unwrap().state, TaskState::Finished);
+        assert!(!supervisor.is_degraded());
+    }
+}
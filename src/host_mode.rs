@@ -3,75 +3,248 @@
 //! This module handles the host mode that starts an embedded signaling server
 //! and streams video via WebRTC.
 
+// 未启用 webrtc feature 时视频流水线大部分不可用
+#![cfg_attr(not(feature = "webrtc"), allow(unused))]
+
 use anyhow::Result;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::signal;
-use tokio::sync::Mutex;
-use tracing::{debug, error, info, warn};
+use tokio::sync::{watch, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn, Instrument};
 
+use crate::audit::{AuditEvent, AuditLog};
 use crate::capture;
+use crate::cli::{ClusterArgs, IceArgs, SignalingLimits};
 use crate::config;
+use crate::engine::{
+    broadcast_video, FrameStage, Heartbeat, RestartPolicy, SceneChangeStage, StageOutput, StaticSceneStage, Supervisor,
+    TaskEvent, VideoSample,
+};
+#[cfg(feature = "webrtc")]
+use crate::engine::take_key_frame_requests;
 use crate::input;
-use crate::quality::{self, adaptive_bitrate::AbreConfig, roi_encoder::ROIEncoderWrapper, static_detector::{StaticSceneDetector, StaticDetectionConfig}};
-use crate::signaling::{EmbeddedSignalingServer, HostSignalEvent};
+#[cfg(any(feature = "webrtc", feature = "quic"))]
+use crate::privacy::PrivacyMode;
+use crate::quality::{self, adaptive_bitrate::AbreConfig, profile::QualityProfile, roi_encoder::PointerPosition};
+#[cfg(feature = "h264")]
+use crate::quality::keyframe_cache::StreamKey;
+use crate::recorder::RecordingConfig;
+use crate::signaling::{EmbeddedSignalingServer, HostSignalEvent, SessionPermissions};
+use crate::signaling::chat::ChatMessage;
+use crate::signaling::approval::{ApprovalDecision, ConnectionApprover};
+#[cfg(any(feature = "webrtc", feature = "quic"))]
+use crate::signaling::control::{ControlHolder, InputArbiter, Outbox};
+use crate::signaling::control::ControlMessage;
+#[cfg(feature = "webrtc")]
+use crate::signaling::timeout::{SessionTimeouts, SharedTimeouts, TimeoutAction, TimeoutConfig};
 use crate::webrtc;
 
+/// 共享的屏幕捕获器 (空闲模式下为 None)
+type SharedCapturer = Arc<Mutex<Option<Box<dyn capture::Capturer>>>>;
+
+/// 各会话共用的输入模拟器
+type SharedInputSimulator = Arc<std::sync::Mutex<Box<dyn input::InputSimulator>>>;
+
+/// 输入控制权: 仲裁器和待发给 Viewer 的控制权消息
+#[cfg(any(feature = "webrtc", feature = "quic"))]
+#[derive(Clone)]
+struct InputControl {
+    arbiter: Arc<std::sync::Mutex<InputArbiter>>,
+    outbox: tokio::sync::mpsc::UnboundedSender<Outbox>,
+    /// 控制权标识 (WebRTC 会话为会话 ID，QUIC 为 peer_id) -> peer_id
+    peers: Arc<std::sync::Mutex<HashMap<String, String>>>,
+    audit: Arc<AuditLog>,
+}
+
+#[cfg(any(feature = "webrtc", feature = "quic"))]
+impl InputControl {
+    fn new(audit: Arc<AuditLog>) -> (Self, tokio::sync::mpsc::UnboundedReceiver<Outbox>) {
+        let (outbox, messages) = tokio::sync::mpsc::unbounded_channel();
+        let control = Self {
+            arbiter: Arc::new(std::sync::Mutex::new(InputArbiter::new())),
+            outbox,
+            peers: Arc::default(),
+            audit,
+        };
+        (control, messages)
+    }
+
+    /// 修改控制权状态，控制者变化时在控制台提示
+    fn update<T>(&self, f: impl FnOnce(&mut InputArbiter) -> (T, Outbox)) -> Option<T> {
+        let mut arbiter = self.arbiter.lock().ok()?;
+        let before = arbiter.holder().clone();
+        let (result, outbox) = f(&mut arbiter);
+        if *arbiter.holder() != before {
+            match arbiter.holder() {
+                ControlHolder::Nobody => println!("  [控制] 当前无人控制"),
+                ControlHolder::Host => println!("  [控制] 已收回控制权，Viewer 输入将被忽略 (输入 release 交还)"),
+                ControlHolder::Viewer(viewer) => {
+                    println!("  [控制] 控制权 -> {}", viewer);
+                    self.audit_control_granted(viewer);
+                }
+            }
+        }
+        if !outbox.is_empty() {
+            let _ = self.outbox.send(outbox);
+        }
+        Some(result)
+    }
+
+    /// Viewer 加入控制权仲裁 (`viewer` 为控制权标识)
+    fn join(&self, viewer: &str, peer_id: &str) {
+        if let Ok(mut peers) = self.peers.lock() {
+            peers.insert(viewer.to_string(), peer_id.to_string());
+        }
+        self.update(|arbiter| ((), arbiter.join(viewer)));
+    }
+
+    /// Viewer 离开，持有的控制权随之释放
+    fn leave(&self, viewer: &str) {
+        if let Ok(mut peers) = self.peers.lock() {
+            peers.remove(viewer);
+        }
+        self.update(|arbiter| ((), arbiter.leave(viewer)));
+    }
+
+    /// 审计记录的 peer 为 peer_id，控制权标识是会话 ID 时作为记录的会话 ID
+    fn audit_control_granted(&self, viewer: &str) {
+        let peer_id = self.peers.lock().ok().and_then(|peers| peers.get(viewer).cloned());
+        match peer_id {
+            Some(peer) if peer != viewer => {
+                self.audit.record_for_session(Some(viewer), AuditEvent::ControlGranted { peer })
+            }
+            _ => self.audit.record(AuditEvent::ControlGranted { peer: viewer.to_string() }),
+        }
+    }
+
+    /// 该 Viewer 的输入是否可以注入
+    fn allow_input(&self, viewer: &str) -> bool {
+        self.update(|arbiter| arbiter.allow_input(viewer)).unwrap_or(false)
+    }
+
+    /// 控制者切换隐私模式，其他 Viewer 的请求只回复当前状态
+    #[cfg(feature = "webrtc")]
+    fn toggle_privacy(&self, viewer: &str, privacy: &PrivacyMode, enabled: bool) {
+        if self.update(|arbiter| (arbiter.is_holder(viewer), Vec::new())).unwrap_or(false) {
+            match privacy.set(enabled) {
+                Ok(enabled) => println!("  [隐私] {} 已{}隐私模式", viewer, if enabled { "开启" } else { "关闭" }),
+                Err(e) => warn!("切换隐私模式失败: {}", e),
+            }
+        }
+        self.announce_privacy(privacy);
+    }
+
+    /// 把隐私模式状态推送给所有 Viewer
+    #[cfg(feature = "webrtc")]
+    fn announce_privacy(&self, privacy: &PrivacyMode) {
+        let message = ControlMessage::Privacy { enabled: privacy.is_enabled() };
+        self.update(|arbiter| ((), arbiter.send_all(message)));
+    }
+}
+
 /// Host mode with tunnel support
 #[cfg(feature = "tunnel")]
+#[allow(clippy::too_many_arguments)]
 pub async fn run_host_mode(
     port: u16,
-    enable_tunnel: bool,
+    tunnel: Option<Option<String>>,
+    view_only: bool,
+    unattended: bool,
+    recording: Option<RecordingConfig>,
     encoder_type: Option<String>,
     bitrate: Option<u32>,
+    profile: Option<QualityProfile>,
     adaptive: bool,
+    show_stats: bool,
+    limits: SignalingLimits,
+    cluster: ClusterArgs,
+    ice: IceArgs,
 ) -> Result<()> {
-    run_host_mode_impl(port, enable_tunnel, encoder_type, bitrate, adaptive).await
+    run_host_mode_impl(port, tunnel, view_only, unattended, recording, encoder_type, bitrate, profile, adaptive, show_stats, limits, cluster, ice).await
 }
 
 /// Host mode without tunnel support
 #[cfg(not(feature = "tunnel"))]
+#[allow(clippy::too_many_arguments)]
 pub async fn run_host_mode(
     port: u16,
-    _enable_tunnel: bool,
+    _tunnel: Option<Option<String>>,
+    view_only: bool,
+    unattended: bool,
+    recording: Option<RecordingConfig>,
     encoder_type: Option<String>,
     bitrate: Option<u32>,
+    profile: Option<QualityProfile>,
     adaptive: bool,
+    show_stats: bool,
+    limits: SignalingLimits,
+    cluster: ClusterArgs,
+    ice: IceArgs,
 ) -> Result<()> {
-    run_host_mode_impl(port, encoder_type, bitrate, adaptive).await
+    run_host_mode_impl(port, view_only, unattended, recording, encoder_type, bitrate, profile, adaptive, show_stats, limits, cluster, ice).await
 }
 
 /// Host mode implementation - WebRTC video streaming
 #[cfg(feature = "tunnel")]
+#[allow(clippy::too_many_arguments)]
 async fn run_host_mode_impl(
     port: u16,
-    enable_tunnel: bool,
+    tunnel: Option<Option<String>>,
+    view_only: bool,
+    unattended: bool,
+    recording: Option<RecordingConfig>,
     encoder_type: Option<String>,
     bitrate_arg: Option<u32>,
+    profile: Option<QualityProfile>,
     adaptive: bool,
+    show_stats: bool,
+    limits: SignalingLimits,
+    cluster: ClusterArgs,
+    ice: IceArgs,
 ) -> Result<()> {
-    run_host_mode_inner(port, enable_tunnel, encoder_type, bitrate_arg, adaptive).await
+    run_host_mode_inner(port, tunnel, view_only, unattended, recording, encoder_type, bitrate_arg, profile, adaptive, show_stats, limits, cluster, ice).await
 }
 
 /// Host mode implementation without tunnel
 #[cfg(not(feature = "tunnel"))]
+#[allow(clippy::too_many_arguments)]
 async fn run_host_mode_impl(
     port: u16,
+    view_only: bool,
+    unattended: bool,
+    recording: Option<RecordingConfig>,
     encoder_type: Option<String>,
     bitrate_arg: Option<u32>,
+    profile: Option<QualityProfile>,
     adaptive: bool,
+    show_stats: bool,
+    limits: SignalingLimits,
+    cluster: ClusterArgs,
+    ice: IceArgs,
 ) -> Result<()> {
-    run_host_mode_inner(port, encoder_type, bitrate_arg, adaptive).await
+    run_host_mode_inner(port, view_only, unattended, recording, encoder_type, bitrate_arg, profile, adaptive, show_stats, limits, cluster, ice).await
 }
 
 /// Inner host mode implementation
+#[allow(clippy::too_many_arguments)]
 async fn run_host_mode_inner(
     port: u16,
-    #[cfg(feature = "tunnel")] enable_tunnel: bool,
+    #[cfg(feature = "tunnel")] tunnel: Option<Option<String>>,
+    view_only: bool,
+    unattended: bool,
+    recording: Option<RecordingConfig>,
     encoder_type: Option<String>,
     bitrate_arg: Option<u32>,
+    profile: Option<QualityProfile>,
     adaptive: bool,
+    show_stats: bool,
+    limits: SignalingLimits,
+    cluster: ClusterArgs,
+    ice: IceArgs,
 ) -> Result<()> {
     info!("sscontrol 被控端模式启动...");
     if let Some(ref enc) = encoder_type {
@@ -80,17 +253,114 @@ async fn run_host_mode_inner(
     if let Some(br) = bitrate_arg {
         info!("指定的码率: {} kbps", br);
     }
+    if let Some(profile) = profile {
+        info!("指定的画质预设: {}", profile.as_str());
+    }
     if adaptive {
         info!("自适应码率控制: 已启用");
     }
+    let host_info = crate::signaling::HostInfo::current();
+    info!(
+        "本机时区: {} ({}), 区域设置: {}",
+        host_info.timezone.as_deref().unwrap_or("未知"),
+        host_info.utc_offset_label(),
+        host_info.locale.as_deref().unwrap_or("未知")
+    );
 
     // 加载配置
     let config_path = config::Config::get_config_path(None);
-    let config = config::Config::load(&config_path)?;
+    let mut config = config::Config::load(&config_path)?;
+    if profile.is_some() {
+        config.capture.profile = profile;
+    }
+    ice.apply(&mut config.webrtc);
+    let access_secret_hash = if unattended {
+        // 无人值守时没有人作答，审批只能自动通过
+        if config.host.approval != crate::signaling::ApprovalMode::Auto {
+            info!("无人值守模式: 跳过连接审批");
+            config.host.approval = crate::signaling::ApprovalMode::Auto;
+        }
+        Some(unattended_secret_hash(&mut config, config_path.as_ref())?)
+    } else {
+        None
+    };
 
     // 启动内嵌信令服务器
     let mut signaling_server = EmbeddedSignalingServer::new(port);
+    if let Some(max_sessions) = config.host.max_sessions {
+        info!(
+            "最大并发会话: {} (排队: {})",
+            max_sessions,
+            if config.host.queue_enabled { "启用" } else { "禁用" }
+        );
+    }
+    signaling_server.set_capacity(crate::signaling::CapacityConfig {
+        max_sessions: config.host.max_sessions,
+        queue_enabled: config.host.queue_enabled,
+        max_queue_length: config.host.max_queue_length,
+        max_viewers_per_room: config.host.max_viewers_per_room,
+        max_rooms: limits.max_rooms,
+    });
+    signaling_server.set_abuse_limits(crate::signaling::rate_limit::AbuseConfig {
+        max_connections_per_ip: limits.max_connections_per_ip,
+        messages_per_sec: limits.max_messages_per_sec,
+        message_burst: limits.max_messages_per_sec.saturating_mul(2),
+        auth_failures_before_ban: limits.auth_ban_threshold,
+        ban_duration: Duration::from_secs(limits.auth_ban_secs),
+    });
+    signaling_server.set_keepalive(config.server.keepalive());
+    signaling_server.set_compression(config.server.compression);
+    signaling_server.set_max_input_rate(config.host.max_input_rate);
+    let incoming = crate::transfer::IncomingFiles::new(config.host.incoming_dir.as_deref(), config.host.max_upload_mb);
+    info!("上传文件保存到 {}", incoming.dir().display());
+    signaling_server.set_incoming_files(incoming);
+    #[cfg(feature = "redis")]
+    signaling_server.set_redis_url(cluster.redis_url);
+    #[cfg(not(feature = "redis"))]
+    let _ = cluster;
+    // 无人值守访问密钥通过 PIN 验证提交，即使配置关闭了 PIN 也要启用
+    if config.host.require_pin || access_secret_hash.is_some() {
+        signaling_server.set_pin(Some(crate::signaling::PinConfig {
+            max_attempts: config.host.pin_max_attempts.max(1),
+            access_secret_hash,
+            ..Default::default()
+        }));
+    } else {
+        warn!("PIN 验证已关闭，任何能访问信令端口的 Viewer 都可以控制本机");
+    }
+    if let Some(secret) = config.host.totp_secret.clone() {
+        enable_totp(&mut signaling_server, secret)?;
+    } else if unattended {
+        warn!("无人值守访问未启用 TOTP 双因素验证，建议运行 sscontrol auth enroll");
+    }
+    // 命令行 --view-only 优先于配置文件中的权限列表
+    let default_permissions = if view_only {
+        SessionPermissions::view_only()
+    } else {
+        SessionPermissions::from_list(&config.host.permissions)
+    };
+    info!("Viewer 默认权限: {}", default_permissions);
+    match config.host.approval {
+        crate::signaling::ApprovalMode::Auto => warn!("连接审批已关闭，Viewer 通过 PIN 后将自动接入"),
+        mode => info!("连接审批: {:?} (超时 {} 秒自动拒绝)", mode, config.host.approval_timeout_secs),
+    }
+    signaling_server.set_default_permissions(default_permissions);
+    signaling_server.set_fleet_token(config.fleet.registration_token.clone());
+    #[cfg(feature = "security")]
+    if let Some(provider) = crate::security::provider::create_provider(&config.security)? {
+        info!("信令服务器认证已启用: {}", provider.name());
+        signaling_server.set_auth_provider(provider);
+    }
+    let audit = Arc::new(match config.host.audit_log.as_deref() {
+        Some(path) => {
+            info!("会话审计日志: {}", path);
+            AuditLog::open(path)?
+        }
+        None => AuditLog::disabled(),
+    });
+    signaling_server.set_audit_log(audit.clone());
     let actual_port = signaling_server.start().await?;
+    let pin = signaling_server.current_pin().await;
 
     // 获取 Host 事件接收器
     let mut host_events = signaling_server
@@ -98,72 +368,203 @@ async fn run_host_mode_inner(
         .expect("无法获取 Host 事件接收器");
 
     // 获取本机 IP 地址
-    let local_ip = get_local_ip().unwrap_or_else(|| "127.0.0.1".to_string());
+    let local_ip = crate::network::lan::local_ip()
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| "127.0.0.1".to_string());
 
-    // 启动公网隧道 (如果启用)
-    #[cfg(feature = "tunnel")]
-    let _tunnel = if enable_tunnel {
-        info!("正在创建 Cloudflare Tunnel...");
-        let mut cf_tunnel = crate::tunnel::CloudflareTunnel::new();
-        match cf_tunnel.start(actual_port) {
-            Ok(tunnel_url) => {
-                // 打印连接信息 (带隧道)
-                println!();
-                println!("========================================");
-                println!("  sscontrol 被控端已启动");
-                println!("========================================");
-                println!();
-                println!("  本机 IP: {}", local_ip);
-                println!("  端口:    {}", actual_port);
-                println!();
-                println!("局域网连接:");
-                println!("  sscontrol connect --ip {} --port {}", local_ip, actual_port);
-                println!();
-                println!("公网连接 (Cloudflare Tunnel):");
-                println!("  sscontrol connect --url {}", tunnel_url);
-                println!();
-                println!("等待连接中... (按 Ctrl+C 退出)");
-                println!();
-                Some(cf_tunnel)
+    // 在路由器上映射信令端口和 WebRTC 端口
+    let port_mapper = if config.host.port_mapping {
+        start_port_mapping(actual_port, config.webrtc.udp_port_range).await
+    } else {
+        None
+    };
+
+    // QUIC 传输 (与信令服务同一端口号的 UDP 端口)
+    #[cfg(feature = "quic")]
+    let quic_server = if config.host.quic {
+        match crate::network::quic::QuicServer::bind(std::net::SocketAddr::from(([0, 0, 0, 0], actual_port))) {
+            Ok(server) => Some(Arc::new(server)),
+            Err(e) => {
+                warn!("启动 QUIC 传输失败: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // 设备配对: QUIC 控制端出示已配对设备的签名时免 PIN
+    #[cfg(all(feature = "quic", feature = "pairing"))]
+    let device_pairing = match quic_server {
+        Some(_) => match crate::pairing::PairingState::load() {
+            Ok(pairing) => Some(Arc::new(pairing)),
+            Err(e) => {
+                warn!("加载设备配对信息失败，已配对设备需要重新输入 PIN: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    // mDNS 和 UDP 广播 (sscontrol discover 和控制端地址簿据此发现本机)
+    #[cfg(all(feature = "discovery", feature = "quic", feature = "pairing"))]
+    let fingerprint = device_pairing.as_ref().map(|pairing| pairing.identity.fingerprint());
+    #[cfg(all(feature = "discovery", not(all(feature = "quic", feature = "pairing"))))]
+    let fingerprint: Option<String> = None;
+    #[cfg(feature = "discovery")]
+    let discovery_metadata = crate::discovery::DeviceMetadata {
+        screens: screen_count(),
+        fingerprint,
+        ..crate::discovery::DeviceMetadata::local()
+    };
+    #[cfg(feature = "discovery")]
+    let mdns_service = if config.host.mdns {
+        let registered = crate::discovery::MdnsService::new(&config.server.device_id, actual_port).and_then(|mut service| {
+            service.register(discovery_metadata.clone())?;
+            Ok(service)
+        });
+        match registered {
+            Ok(service) => {
+                info!("已通过 mDNS 广播本机 (设备 ID {})", config.server.device_id);
+                Some(service)
+            }
+            Err(e) => {
+                warn!("mDNS 广播失败: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    #[cfg(feature = "discovery")]
+    let broadcast_announcer = if config.host.broadcast_discovery {
+        let announcer = crate::discovery::BroadcastAnnouncer::new(
+            &config.server.device_id,
+            actual_port,
+            config.host.broadcast_port,
+            crate::discovery::broadcast::DEFAULT_INTERVAL,
+            beacon_signer(),
+        );
+        match announcer {
+            Ok(mut announcer) => {
+                announcer.set_metadata(discovery_metadata.clone());
+                info!("已在 UDP 端口 {} 广播本机信标", config.host.broadcast_port);
+                Some(announcer)
             }
             Err(e) => {
-                error!("创建 Cloudflare Tunnel 失败: {}", e);
-                warn!("将仅使用局域网模式");
-                print_local_only_info(&local_ip, actual_port);
+                warn!("UDP 广播发现不可用: {}", e);
                 None
             }
         }
     } else {
-        print_local_only_info(&local_ip, actual_port);
         None
     };
 
+    // 启动公网隧道 (如果启用)
+    #[cfg(feature = "tunnel")]
+    let tunnel_handle = match tunnel {
+        Some(name) => start_tunnel(&config, &config_path, name.as_deref(), actual_port).await,
+        None => None,
+    };
+    #[cfg(feature = "tunnel")]
+    match tunnel_handle.as_ref().and_then(|handle| handle.url().map(|url| (handle.provider(), url))) {
+        Some((provider, url)) => {
+            // 打印连接信息 (带隧道)
+            println!();
+            println!("========================================");
+            println!("  sscontrol 被控端已启动");
+            println!("========================================");
+            println!();
+            println!("  本机 IP: {}", local_ip);
+            println!("  端口:    {}", actual_port);
+            println!("  权限:    {}", default_permissions);
+            println!();
+            println!("局域网连接:");
+            println!("  sscontrol connect --ip {} --port {}", local_ip, actual_port);
+            println!();
+            println!("公网连接 ({}):", provider);
+            println!("  sscontrol connect --url {}", crate::tunnel::websocket_url(&url));
+            println!();
+            print_pin(pin.as_deref());
+            println!("等待连接中... (按 Ctrl+C 退出)");
+            println!();
+        }
+        None => print_local_only_info(&local_ip, actual_port, pin.as_deref(), default_permissions),
+    }
+
     #[cfg(not(feature = "tunnel"))]
-    print_local_only_info(&local_ip, actual_port);
+    print_local_only_info(&local_ip, actual_port, pin.as_deref(), default_permissions);
+
+    if let Some(ref mapper) = port_mapper {
+        print_port_mapping(mapper, actual_port, pin.is_some()).await;
+    }
+
+    #[cfg(feature = "quic")]
+    if let Some(ref server) = quic_server {
+        println!("QUIC 直连 (命令行控制端):");
+        println!("  sscontrol connect --ip {} --port {} --transport quic", local_ip, actual_port);
+        println!("  证书指纹: {}", server.fingerprint());
+        #[cfg(feature = "pairing")]
+        if let Some(ref pairing) = device_pairing {
+            println!("  设备指纹: {} (控制端加 --pair 配对后免 PIN)", pairing.identity.fingerprint());
+        }
+        println!();
+    }
+
+    // 通过 STUN 获取公网映射地址 (仅提示，ICE 会自行收集 server-reflexive 候选)
+    if let Some(server) = config.webrtc.stun_servers.first().cloned() {
+        tokio::spawn(async move {
+            match crate::nat::stun::public_address(&server).await {
+                Ok(addr) => {
+                    info!("公网映射地址: {} (STUN {})", addr, server);
+                    println!("  公网映射地址: {} (STUN)", addr);
+                }
+                Err(e) => warn!("通过 STUN 获取公网地址失败: {}", e),
+            }
+        });
+    }
 
     // 检查屏幕录制权限 (macOS)
     #[cfg(target_os = "macos")]
     {
-        if !capture::macos::MacOSCapturer::check_screen_recording_permission() {
+        if !capture::is_synthetic() && !capture::macos::MacOSCapturer::check_screen_recording_permission() {
             warn!("屏幕录制权限未授予，请在系统设置中授权");
         }
     }
 
     // 创建屏幕捕获器
     info!("初始化屏幕捕获器...");
-    let capturer = Arc::new(Mutex::new(capture::create_capturer(config.capture.screen_index)?));
-    let screen_width;
-    let screen_height;
-    {
-        let cap = capturer.lock().await;
-        screen_width = cap.width();
-        screen_height = cap.height();
+    let mut initial_capturer = capture::create_capturer(config.capture.screen_index)?;
+    let screen_width = initial_capturer.width();
+    let screen_height = initial_capturer.height();
+    if !initial_capturer.set_show_cursor(config.capture.show_cursor) && config.capture.show_cursor {
+        warn!("当前捕获器不支持在画面中合成鼠标指针");
     }
+    let capturer: SharedCapturer = Arc::new(Mutex::new(Some(initial_capturer)));
+    // 鼠标指针开关 (空闲模式重建捕获器时沿用)
+    let show_cursor = Arc::new(AtomicBool::new(config.capture.show_cursor));
+    // 运行中可调整的采集参数 (配置热加载、Viewer 切换画质预设)
+    let (live_tx, live_rx) = watch::channel(config::watch::LiveSettings::from_config(&config));
+    let live_tx = Arc::new(live_tx);
     info!("屏幕尺寸: {}x{}", screen_width, screen_height);
 
-    // 创建输入模拟器
+    // 唤醒空闲中的视频流水线 (Viewer 加入或会话建立时通知)
+    let (wake_tx, wake_rx) = watch::channel(());
+
+    // 创建输入模拟器 (快捷键交换和屏蔽对所有传输方式的输入生效)
     info!("初始化输入模拟器...");
-    let _input_simulator = input::create_input_simulator()?;
+    let shortcut_policy = input::ShortcutPolicy::new(config.host.swap_meta_control, &config.host.blocked_shortcuts);
+    let mut simulator = input::create_input_simulator()?;
+    if !shortcut_policy.is_empty() {
+        info!(
+            "快捷键: {}屏蔽 {} 个组合键",
+            if shortcut_policy.swap_meta_control { "交换 Cmd/Ctrl，" } else { "" },
+            shortcut_policy.blocked.len()
+        );
+        simulator = Box::new(input::ShortcutFilter::new(simulator, shortcut_policy));
+    }
+    let input_simulator: SharedInputSimulator = Arc::new(std::sync::Mutex::new(simulator));
+    let max_input_rate = config.host.max_input_rate;
 
     // WebRTC 会话管理 - 使用 Arc<HostSession> 以便共享
     #[cfg(feature = "webrtc")]
@@ -175,151 +576,642 @@ async fn run_host_mode_inner(
 
     // 根据编码器类型确定 WebRTC codec
     // VP8: 软件编码（默认）
+    // VP9: 软件编码，时域分层（多个观看者网速差异大时只编码一次）
     // H.264: 硬件编码（NVENC/AMF/QSV/VideoToolbox）
     #[cfg(feature = "webrtc")]
     let video_codec = match encoder_type.as_deref() {
         Some("nvenc") | Some("amf") | Some("qsv") | Some("videotoolbox") | Some("h264") => {
             webrtc::host_session::VideoCodec::H264
         }
+        Some("vp9") => webrtc::host_session::VideoCodec::VP9,
         _ => webrtc::host_session::VideoCodec::VP8,
     };
 
     #[cfg(feature = "webrtc")]
     info!("选择的 WebRTC codec: {}", video_codec.name());
 
+    // 后台读取或探测编码器能力，第一个观看者连接时自动选择编码器不需要等待
+    #[cfg(all(feature = "h264", feature = "webrtc"))]
+    if hardware_encoder_type(encoder_type.as_deref()) == crate::encoder::hardware::HardwareEncoderType::Auto {
+        tokio::task::spawn_blocking(crate::encoder::probe::capabilities);
+    }
+
     // 处理信令事件
-    #[cfg(feature = "webrtc")]
     let signaling_server_clone = signaling_server.clone();
     #[cfg(feature = "webrtc")]
     let sessions_clone = sessions.clone();
     #[cfg(feature = "webrtc")]
     let codec_for_session = video_codec;
+    // 开启 simulcast 时，Viewer 在 Offer 中声明了 rid 多路接收则按档位分别发送到各个编码
+    #[cfg(feature = "webrtc")]
+    let simulcast_tiers = config
+        .host
+        .simulcast
+        .then(|| quality::simulcast::SimulcastConfig::for_bitrate(0).tiers.len())
+        .unwrap_or(0);
+    let input_for_signal = input_simulator.clone();
+    // 控制者的指针位置 (ROI 编码: 指针周围分配更多码率)
+    let pointer = PointerPosition::new();
+    let pointer_for_signal = pointer.clone();
+    #[cfg(feature = "webrtc")]
+    let ice_config = webrtc::host_session::IceConfig {
+        stun_servers: config.webrtc.stun_servers.clone(),
+        turn_servers: config.webrtc.turn_servers.clone(),
+        candidate_filter: webrtc::candidate::CandidateFilter::from_config(&config.webrtc)?,
+        udp_port_range: config.webrtc.udp_port_range,
+        public_ip: match port_mapper {
+            Some(ref mapper) if mapper.preserves_ports(crate::nat::port_mapping::Protocol::Udp).await => {
+                mapper.external_ip()
+            }
+            _ => None,
+        },
+    };
+    // 输入控制权仲裁 (多个 Viewer 时只有控制者的输入生效)
+    #[cfg(any(feature = "webrtc", feature = "quic"))]
+    let (input_control, control_messages) = InputControl::new(audit.clone());
+    // 隐私模式 (会话期间遮挡本地屏幕，最后一个会话结束时恢复)
+    #[cfg(any(feature = "webrtc", feature = "quic"))]
+    let privacy = Arc::new(PrivacyMode::new(config.host.privacy_mode, config.host.privacy_block_input));
+    #[cfg(any(feature = "webrtc", feature = "quic"))]
+    if config.host.privacy_mode {
+        info!("隐私模式已启用: 会话期间遮挡本地屏幕{}", if config.host.privacy_block_input { "并屏蔽本地输入" } else { "" });
+    }
+    // 会话空闲超时和最长时长 (到期前通知控制端，到期后关闭会话)
+    #[cfg(feature = "webrtc")]
+    let timeout_config = TimeoutConfig::from_host(&config.host);
+    #[cfg(feature = "webrtc")]
+    if timeout_config.is_enabled() {
+        info!(
+            "会话超时: 空闲 {}，最长 {}",
+            config.host.idle_timeout_minutes.map_or("不限".to_string(), |m| format!("{} 分钟", m)),
+            config.host.max_session_minutes.map_or("不限".to_string(), |m| format!("{} 分钟", m))
+        );
+    }
+    #[cfg(feature = "webrtc")]
+    let timeouts = SessionTimeouts::shared(timeout_config);
+    // 聊天: 转发给各 WebRTC 会话的消息 (Web 查看器从信令服务器的聊天记录拉取)
+    #[cfg_attr(not(feature = "webrtc"), allow(unused_variables))]
+    let (chat_tx, chat_messages) = tokio::sync::mpsc::unbounded_channel::<ChatMessage>();
+    // 后台任务 (退出时统一取消，panic 时按策略重启)
+    let supervisor = Supervisor::new();
+    // 登记到设备清单 (上报公网隧道地址，没有隧道时上报局域网地址)
+    let lan_url = format!("ws://{}", crate::network::lan::host_port(&local_ip, actual_port));
+    #[cfg(feature = "tunnel")]
+    let device_url = tunnel_handle
+        .as_ref()
+        .and_then(|handle| handle.url())
+        .map_or(lan_url, |url| crate::tunnel::websocket_url(&url));
+    #[cfg(not(feature = "tunnel"))]
+    let device_url = lan_url;
+    if let Some(client) = crate::network::presence::PresenceClient::from_config(&config, Some(device_url)) {
+        supervisor.adopt("fleet-presence", client.spawn());
+    }
+    #[cfg(feature = "tunnel")]
+    if let Some(handle) = tunnel_handle {
+        supervisor.adopt("tunnel", handle.task);
+    }
+    #[cfg(any(feature = "webrtc", feature = "quic"))]
+    supervisor.adopt(
+        "console",
+        spawn_console_commands(input_control.clone(), signaling_server.clone(), chat_tx.clone(), supervisor.clone()),
+    );
+    #[cfg(feature = "webrtc")]
+    supervisor.adopt("control-delivery", spawn_control_delivery(sessions.clone(), control_messages));
+    #[cfg(all(feature = "quic", not(feature = "webrtc")))]
+    drop(control_messages);
+    #[cfg(feature = "webrtc")]
+    supervisor.adopt("chat-delivery", spawn_chat_delivery(sessions.clone(), chat_messages));
+    #[cfg(feature = "webrtc")]
+    if timeout_config.is_enabled() {
+        supervisor.adopt(
+            "session-timeouts",
+            spawn_session_timeouts(
+                timeouts.clone(),
+                sessions.clone(),
+                input_control.clone(),
+                privacy.clone(),
+                signaling_server.clone(),
+            ),
+        );
+    }
+    #[cfg(any(feature = "webrtc", feature = "quic"))]
+    let control_for_signal = input_control.clone();
+    #[cfg(feature = "webrtc")]
+    let privacy_for_signal = privacy.clone();
+    #[cfg(feature = "webrtc")]
+    let timeouts_for_signal = timeouts.clone();
+
+    // 连接审批 (每个 Viewer 首次 Offer 或请求票据时确认一次，QUIC 连接握手时确认)
+    let approver = Arc::new(ConnectionApprover::new(
+        config.host.approval,
+        Duration::from_secs(config.host.approval_timeout_secs.max(1)),
+    ));
+    let mut approved: std::collections::HashSet<String> = std::collections::HashSet::new();
+    // 对称 NAT 打洞 (需要 STUN)，进行中的打洞在该 Viewer 的首个 Offer 时取结果
+    #[cfg(feature = "webrtc")]
+    let punch_config = (config.webrtc.hole_punching && !config.webrtc.stun_servers.is_empty()).then(|| {
+        crate::nat::NatConfig {
+            stun_servers: config.webrtc.stun_servers.clone(),
+            ..Default::default()
+        }
+    });
+    #[cfg(feature = "webrtc")]
+    let mut pending_punches: HashMap<String, tokio::task::JoinHandle<Option<crate::nat::predictive_punching::PunchedPath>>> =
+        HashMap::new();
+    // Viewer 在 setLocalDescription 后立即开始发送候选，可能早于 Offer 到达: 暂存到会话创建之后
+    #[cfg(feature = "webrtc")]
+    let mut early_candidates: HashMap<String, Vec<webrtc::host_session::IceCandidate>> = HashMap::new();
+    // Viewer 断线后保留的会话 (会话 ID -> 会话)，宽限期内重连可恢复
+    #[cfg(feature = "webrtc")]
+    let detached: Arc<Mutex<HashMap<String, Arc<webrtc::host_session::HostSession>>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+    let capturer_for_signal = capturer.clone();
+    let show_cursor_for_signal = show_cursor.clone();
+    let live_for_signal = live_tx.clone();
+    // 上传完成后输入文件路径 (上传者要有输入权限并持有控制权)
+    let paste_uploads = config.host.paste_uploads;
+
+    #[cfg(feature = "quic")]
+    if let Some(server) = quic_server.clone() {
+        let task = spawn_quic_server(
+            server,
+            signaling_server.clone(),
+            approver.clone(),
+            default_permissions,
+            input_simulator.clone(),
+            max_input_rate,
+            input_control.clone(),
+            privacy.clone(),
+            config.capture.clone(),
+            bitrate_arg,
+            #[cfg(feature = "pairing")]
+            device_pairing.clone(),
+        );
+        supervisor.adopt("quic-server", task);
+    }
+
+    // 任务结束时注销 mDNS 服务并广播告别信标
+    #[cfg(feature = "discovery")]
+    if mdns_service.is_some() || broadcast_announcer.is_some() {
+        supervisor.adopt("discovery", spawn_discovery(mdns_service, broadcast_announcer));
+    }
 
     let signal_handler = tokio::spawn(async move {
         while let Some(event) = host_events.recv().await {
-            match event {
-                HostSignalEvent::ViewerJoined { peer_id } => {
-                    info!("Viewer 加入: {}", peer_id);
-                    println!("  [+] Viewer 连接: {}", peer_id);
-                }
-                HostSignalEvent::ViewerLeft { peer_id } => {
-                    info!("Viewer 离开: {}", peer_id);
-                    println!("  [-] Viewer 断开: {}", peer_id);
-
+            let span = event_span(&event);
+            async {
+                match event {
+                    HostSignalEvent::ViewerJoined { peer_id } => {
+                        info!("Viewer 加入: {}", peer_id);
+                        println!("  [+] Viewer 连接: {}", peer_id);
+                        wake_tx.send_replace(());
+                    }
+                    HostSignalEvent::ViewerLeft { peer_id } => {
+                        info!("Viewer 离开: {}", peer_id);
+                        println!("  [-] Viewer 断开: {}", peer_id);
+                        approved.remove(&peer_id);
+
+                        #[cfg(feature = "webrtc")]
+                        {
+                            early_candidates.remove(&peer_id);
+                            if let Some(punch) = pending_punches.remove(&peer_id) {
+                                punch.abort();
+                            }
+                            let session = sessions_clone.lock().await.remove(&peer_id);
+                            if let Some(session) = session {
+                                // 断开的控制者立即让出控制权 (重连后需重新申请)
+                                control_for_signal.leave(session.session_id());
+                                privacy_for_signal.session_ended();
+                                control_for_signal.announce_privacy(&privacy_for_signal);
+
+                                // 先挂起，宽限期内未恢复再关闭
+                                let session_id = session.session_id().to_string();
+                                info!("会话挂起: {} ({})，等待重连", session_id, peer_id);
+                                detached.lock().await.insert(session_id.clone(), session);
+
+                                let detached = detached.clone();
+                                let timeouts = timeouts_for_signal.clone();
+                                tokio::spawn(async move {
+                                    tokio::time::sleep(EmbeddedSignalingServer::RESUME_GRACE).await;
+                                    let session = detached.lock().await.remove(&session_id);
+                                    if let Some(session) = session {
+                                        info!("会话结束: {} (未在宽限期内重连)", session_id);
+                                        if let Ok(mut timeouts) = timeouts.lock() {
+                                            timeouts.leave(&session_id);
+                                        }
+                                        let _ = session.close().await;
+                                    }
+                                }.in_current_span());
+                            }
+                        }
+                    }
                     #[cfg(feature = "webrtc")]
-                    {
-                        let mut sessions = sessions_clone.lock().await;
-                        if let Some(session) = sessions.remove(&peer_id) {
-                            let _ = session.close().await;
+                    HostSignalEvent::ViewerResumed { peer_id, session_id } => {
+                        let session = detached.lock().await.remove(&session_id);
+                        if let Some(session) = session {
+                            session.set_peer_id(peer_id.clone());
+                            control_for_signal.join(&session_id, &peer_id);
+                            if let Ok(mut timeouts) = timeouts_for_signal.lock() {
+                                timeouts.join(&session_id);
+                            }
+                            sessions_clone.lock().await.insert(peer_id.clone(), session);
+                            privacy_for_signal.session_started();
+                            control_for_signal.announce_privacy(&privacy_for_signal);
+                            // 恢复的会话已审批过
+                            approved.insert(peer_id.clone());
+                            wake_tx.send_replace(());
+                            info!("会话已恢复: {} ({})", session_id, peer_id);
+                            println!("  [~] Viewer 重连: {} (会话已恢复)", peer_id);
                         }
                     }
-                }
-                #[cfg(feature = "webrtc")]
-                HostSignalEvent::Offer { from, sdp } => {
-                    info!("收到 Offer from: {}", from);
+                    #[cfg(not(feature = "webrtc"))]
+                    HostSignalEvent::ViewerResumed { peer_id, session_id } => {
+                        info!("Viewer {} 恢复会话 {}", peer_id, session_id);
+                    }
+                    #[cfg(feature = "webrtc")]
+                    HostSignalEvent::Offer { from, sdp, session_id } => {
+                        info!("收到 Offer from: {} (会话 {})", from, session_id);
 
-                    // 创建 WebRTC 会话
-                    match webrtc::host_session::HostSession::new(from.clone(), codec_for_session).await {
-                        Ok(session) => {
-                            let session = Arc::new(session);
+                        // 审批期间阻塞事件循环，该 Viewer 的 ICE 候选会排队到会话创建之后处理
+                        if !approved.contains(&from) {
+                            if !approve_viewer(&signaling_server_clone, &approver, &from, &session_id).await {
+                                return;
+                            }
+                            approved.insert(from.clone());
+                        }
 
-                            // 处理 Offer，生成 Answer
+                        // 已有会话: 重协商 (ICE 重启)，保留数据通道和视频轨道
+                        let existing = sessions_clone.lock().await.get(&from).cloned();
+                        if let Some(session) = existing {
                             match session.handle_offer(&sdp).await {
                                 Ok(answer_sdp) => {
-                                    // 发送 Answer
                                     signaling_server_clone
-                                        .send_answer(&from, &answer_sdp)
+                                        .send_answer(&from, &answer_sdp, &session_id)
                                         .await;
-                                    info!("已发送 Answer to: {}", from);
+                                    info!("已发送重协商 Answer to: {} ({})", from, session_id);
+                                }
+                                Err(e) => error!("重协商失败: {}", e),
+                            }
+                            return;
+                        }
 
-                                    // 发送 ICE 候选
+                        // 创建 WebRTC 会话 (Viewer 在打洞结束后才发送 Offer，这里通常无需等待)
+                        let punched = match pending_punches.remove(&from) {
+                            Some(punch) => punch.await.ok().flatten(),
+                            None => None,
+                        };
+                        let permissions = signaling_server_clone.permissions(&from).await;
+                        let simulcast = webrtc::simulcast::parse_offer(&sdp, simulcast_tiers);
+                        match webrtc::host_session::HostSession::new(
+                            from.clone(),
+                            session_id.clone(),
+                            codec_for_session,
+                            permissions,
+                            &ice_config,
+                            punched,
+                            simulcast,
+                        )
+                        .await
+                        {
+                            Ok(session) => {
+                                let session = Arc::new(session);
+
+                                // 注入 Viewer 的输入 (无输入权限的事件已在数据通道层丢弃，
+                                // 没有控制权的 Viewer 的输入在这里丢弃)
+                                control_for_signal.join(&session_id, &from);
+                                if let Some(mut input_events) = session.take_input_events() {
+                                    let simulator = input_for_signal.clone();
+                                    let control = control_for_signal.clone();
+                                    let pointer = pointer_for_signal.clone();
+                                    let timeouts = timeouts_for_signal.clone();
+                                    let session_id = session_id.clone();
+                                    tokio::spawn(async move {
+                                        // 触摸手势转换 (平板上的控制端)，鼠标移动合并限速
+                                        let mut touch = input::TouchTranslator::default();
+                                        let mut coalescer = input::InputCoalescer::new(max_input_rate);
+                                        loop {
+                                            tokio::select! {
+                                                event = input_events.recv() => {
+                                                    let Some(event) = event else { break };
+                                                    if let Ok(mut timeouts) = timeouts.lock() {
+                                                        timeouts.input(&session_id);
+                                                    }
+                                                    if !control.allow_input(&session_id) {
+                                                        coalescer.drop_event();
+                                                        continue;
+                                                    }
+                                                    let now = std::time::Instant::now();
+                                                    let events = touch.translate(event).into_iter().flat_map(|event| coalescer.push(event, now)).collect();
+                                                    inject_input(&simulator, Some(&pointer), events);
+                                                }
+                                                _ = coalescer.due() => {
+                                                    inject_input(&simulator, Some(&pointer), coalescer.poll(std::time::Instant::now()));
+                                                }
+                                            }
+                                        }
+                                        inject_input(&simulator, Some(&pointer), coalescer.flush());
+                                        inject_input(&simulator, None, touch.reset());
+                                    }.in_current_span());
+                                }
+                                // 申请/移交控制权，切换隐私模式
+                                if let Some(mut messages) = session.take_control_messages() {
+                                    let control = control_for_signal.clone();
+                                    let privacy = privacy_for_signal.clone();
+                                    let session_id = session_id.clone();
+                                    tokio::spawn(async move {
+                                        while let Some(message) = messages.recv().await {
+                                            match message {
+                                                ControlMessage::Privacy { enabled } => {
+                                                    control.toggle_privacy(&session_id, &privacy, enabled);
+                                                }
+                                                message => {
+                                                    control.update(|arbiter| ((), arbiter.handle(&session_id, &message)));
+                                                }
+                                            }
+                                        }
+                                    }.in_current_span());
+                                }
+                                // 聊天消息: 记录后在控制台显示并转发给其他控制端
+                                if let Some(mut posts) = session.take_chat_messages() {
                                     let signaling = signaling_server_clone.clone();
-                                    let peer_id = from.clone();
-                                    let session_for_ice = session.clone();
-
+                                    let chat_tx = chat_tx.clone();
+                                    let session = Arc::downgrade(&session);
                                     tokio::spawn(async move {
-                                        while let Some(ice) =
-                                            session_for_ice.next_ice_candidate().await
-                                        {
-                                            signaling
-                                                .send_ice(
-                                                    &peer_id,
-                                                    &ice.candidate,
-                                                    &ice.sdp_mid,
-                                                    ice.sdp_mline_index,
-                                                )
-                                                .await;
+                                        while let Some(post) = posts.recv().await {
+                                            let Some(peer_id) = session.upgrade().map(|s| s.peer_id()) else { break };
+                                            if let Some(message) = signaling.post_chat(&peer_id, &post.text).await {
+                                                print_chat(&message);
+                                                let _ = chat_tx.send(message);
+                                            }
+                                        }
+                                    }.in_current_span());
+                                }
+
+                                // 处理 Offer，生成 Answer
+                                match session.handle_offer(&sdp).await {
+                                    Ok(answer_sdp) => {
+                                        // 发送 Answer
+                                        signaling_server_clone
+                                            .send_answer(&from, &answer_sdp, &session_id)
+                                            .await;
+                                        info!("已发送 Answer to: {}", from);
+
+                                        // 发送 ICE 候选 (重连后发给新的 peer_id)
+                                        let signaling = signaling_server_clone.clone();
+                                        let session_for_ice = session.clone();
+
+                                        tokio::spawn(async move {
+                                            while let Some(ice) =
+                                                session_for_ice.next_ice_candidate().await
+                                            {
+                                                signaling
+                                                    .send_ice(
+                                                        &session_for_ice.peer_id(),
+                                                        &ice.candidate,
+                                                        &ice.sdp_mid,
+                                                        ice.sdp_mline_index,
+                                                    )
+                                                    .await;
+                                            }
+                                        }.in_current_span());
+
+                                        // Offer 之前到达的候选
+                                        for ice in early_candidates.remove(&from).unwrap_or_default() {
+                                            if let Err(e) = session.add_ice_candidate(&ice).await {
+                                                debug!("添加 ICE 候选失败: {}", e);
+                                            }
                                         }
-                                    });
 
-                                    // 保存会话
-                                    {
-                                        let mut sessions = sessions_clone.lock().await;
-                                        sessions.insert(from.clone(), session);
+                                        // 保存会话
+                                        {
+                                            let mut sessions = sessions_clone.lock().await;
+                                            sessions.insert(from.clone(), session);
+                                        }
+                                        privacy_for_signal.session_started();
+                                        control_for_signal.announce_privacy(&privacy_for_signal);
+                                        if let Ok(mut timeouts) = timeouts_for_signal.lock() {
+                                            timeouts.join(&session_id);
+                                        }
+                                        wake_tx.send_replace(());
+                                        info!("WebRTC 会话已建立: {} ({})", session_id, from);
+                                    }
+                                    Err(e) => {
+                                        error!("处理 Offer 失败: {}", e);
                                     }
-                                    info!("WebRTC 会话已建立: {}", from);
-                                }
-                                Err(e) => {
-                                    error!("处理 Offer 失败: {}", e);
                                 }
                             }
-                        }
-                        Err(e) => {
-                            error!("创建 WebRTC 会话失败: {}", e);
+                            Err(e) => {
+                                error!("创建 WebRTC 会话失败: {}", e);
+                            }
                         }
                     }
-                }
-                #[cfg(not(feature = "webrtc"))]
-                HostSignalEvent::Offer { from, sdp: _ } => {
-                    info!("收到 Offer from: {}", from);
-                    warn!("WebRTC feature 未启用，无法处理 Offer");
-                }
-                #[cfg(feature = "webrtc")]
-                HostSignalEvent::Ice {
-                    from,
-                    candidate,
-                    sdp_mid,
-                    sdp_mline_index,
-                } => {
-                    info!("收到 ICE from: {}", from);
-
-                    let sessions = sessions_clone.lock().await;
-                    if let Some(session) = sessions.get(&from) {
+                    #[cfg(not(feature = "webrtc"))]
+                    HostSignalEvent::Offer { from, session_id, .. } => {
+                        info!("收到 Offer from: {} (会话 {})", from, session_id);
+                        warn!("WebRTC feature 未启用，无法处理 Offer");
+                    }
+                    #[cfg(feature = "webrtc")]
+                    HostSignalEvent::Ice {
+                        from,
+                        candidate,
+                        sdp_mid,
+                        sdp_mline_index,
+                    } => {
+                        debug!("收到 ICE from: {}", from);
+
                         let ice = webrtc::host_session::IceCandidate {
                             candidate,
                             sdp_mid,
                             sdp_mline_index,
                         };
-                        if let Err(e) = session.add_ice_candidate(&ice).await {
-                            error!("添加 ICE 候选失败: {}", e);
+                        let session = sessions_clone.lock().await.get(&from).cloned();
+                        match session {
+                            Some(session) => {
+                                if let Err(e) = session.add_ice_candidate(&ice).await {
+                                    error!("添加 ICE 候选失败: {}", e);
+                                }
+                            }
+                            None => {
+                                let pending = early_candidates.entry(from).or_default();
+                                if pending.len() < MAX_EARLY_CANDIDATES {
+                                    pending.push(ice);
+                                }
+                            }
+                        }
+                    }
+                    #[cfg(not(feature = "webrtc"))]
+                    HostSignalEvent::Ice {
+                        from,
+                        candidate: _,
+                        sdp_mid: _,
+                        sdp_mline_index: _,
+                    } => {
+                        info!("收到 ICE from: {} (WebRTC 未启用，忽略)", from);
+                    }
+                    #[cfg(feature = "webrtc")]
+                    HostSignalEvent::Punch { from, candidate } => {
+                        let punch = answer_punch(signaling_server_clone.clone(), from.clone(), candidate, punch_config.clone());
+                        if let Some(previous) = pending_punches.insert(from, tokio::spawn(punch.in_current_span())) {
+                            previous.abort();
+                        }
+                    }
+                    #[cfg(not(feature = "webrtc"))]
+                    HostSignalEvent::Punch { from, .. } => {
+                        debug!("收到 {} 的打洞请求 (WebRTC 未启用，忽略)", from);
+                    }
+                    HostSignalEvent::PinChanged { pin } => {
+                        info!("PIN 已使用，已换发新的 PIN");
+                        println!("  [*] 新的连接 PIN: {}", pin);
+                    }
+                    HostSignalEvent::Chat { message } => {
+                        print_chat(&message);
+                        #[cfg(feature = "webrtc")]
+                        let _ = chat_tx.send(message);
+                    }
+                    HostSignalEvent::Cursor { from, show } => {
+                        show_cursor_for_signal.store(show, Ordering::Relaxed);
+                        let applied = match capturer_for_signal.lock().await.as_mut() {
+                            Some(cap) => cap.set_show_cursor(show),
+                            // 空闲中，恢复捕获时生效
+                            None => true,
+                        };
+                        if applied {
+                            info!("{} 切换画面鼠标指针: {}", from, if show { "显示" } else { "隐藏" });
+                        } else {
+                            warn!("当前捕获器不支持在画面中合成鼠标指针 (来自 {})", from);
+                        }
+                    }
+                    HostSignalEvent::Profile { from, profile } => {
+                        info!("{} 切换画质预设: {}", from, profile);
+                        live_for_signal.send_modify(|settings| settings.profile = Some(profile));
+                    }
+                    HostSignalEvent::Input { from, event } => {
+                        #[cfg(any(feature = "webrtc", feature = "quic"))]
+                        let allowed = control_for_signal.allow_input(&from);
+                        #[cfg(not(any(feature = "webrtc", feature = "quic")))]
+                        let allowed = true;
+                        if allowed {
+                            inject_input(&input_for_signal, Some(&pointer_for_signal), vec![event]);
+                        }
+                    }
+                    HostSignalEvent::InputClosed { from } => {
+                        debug!("Web 查看器输入连接断开: {}", from);
+                        #[cfg(any(feature = "webrtc", feature = "quic"))]
+                        control_for_signal.leave(&from);
+                    }
+                    HostSignalEvent::Power { from, action } => {
+                        info!("{} 请求{}", from, action.label());
+                        println!("  [!] {} 请求{}", from, action.label());
+                        match tokio::task::spawn_blocking(move || crate::power::execute(action)).await {
+                            Ok(Ok(())) => {}
+                            Ok(Err(e)) => warn!("{}", e),
+                            Err(e) => warn!("电源操作任务异常: {}", e),
+                        }
+                    }
+                    HostSignalEvent::TicketRequested { from, session_id } => {
+                        // 与 WebRTC 协商共用审批结果: 同一个 Viewer 只确认一次
+                        if !approved.contains(&from) {
+                            if !approve_viewer(&signaling_server_clone, &approver, &from, &session_id).await {
+                                return;
+                            }
+                            approved.insert(from.clone());
+                        }
+                        if !signaling_server_clone.grant_ticket(&from).await {
+                            debug!("Viewer {} 已断开，不再下发票据", from);
+                        }
+                    }
+                    HostSignalEvent::FileReceived { from, path } => {
+                        info!("{} 上传了文件 {}", from, path.display());
+                        println!("  [↓] 收到文件: {}", path.display());
+                        if paste_uploads && signaling_server_clone.permissions(&from).await.input {
+                            #[cfg(any(feature = "webrtc", feature = "quic"))]
+                            let allowed = control_for_signal.allow_input(&from);
+                            #[cfg(not(any(feature = "webrtc", feature = "quic")))]
+                            let allowed = true;
+                            if allowed {
+                                let text = path.display().to_string();
+                                inject_input(&input_for_signal, None, vec![input::InputEvent::TextInput { text }]);
+                            } else {
+                                debug!("{} 没有控制权，不输入上传文件的路径", from);
+                            }
                         }
                     }
-                }
-                #[cfg(not(feature = "webrtc"))]
-                HostSignalEvent::Ice {
-                    from,
-                    candidate: _,
-                    sdp_mid: _,
-                    sdp_mline_index: _,
-                } => {
-                    info!("收到 ICE from: {} (WebRTC 未启用，忽略)", from);
                 }
             }
+            .instrument(span)
+            .await;
         }
     });
 
-    // 视频捕获和发送循环
-    let video_task = spawn_video_task(
-        capturer.clone(),
-        #[cfg(feature = "webrtc")]
-        sessions,
-        config,
-        encoder_type,
-        bitrate_arg,
-        adaptive,
-        screen_width,
-        screen_height,
+    supervisor.adopt("signaling-events", signal_handler);
+
+    // 配置热加载 (以文件中的配置为基准，不含上面按命令行参数做的调整)
+    match config::Config::load_unchecked(&config_path) {
+        Ok(file_config) => {
+            let watcher = config::watch::ConfigWatcher::new(&config_path, file_config);
+            supervisor.adopt("config-watcher", spawn_config_watcher(watcher, live_tx.clone()));
+        }
+        Err(e) => warn!("无法监视配置文件 {}: {}", config_path, e),
+    }
+
+    // 视频捕获和发送循环 (panic 或卡死后重建捕获器和编码器重新开始，并通知 Viewer)
+    supervisor.adopt(
+        "video-notices",
+        spawn_video_notices(
+            &supervisor,
+            #[cfg(feature = "webrtc")]
+            sessions.clone(),
+            signaling_server.video_stream(),
+        ),
     );
+    {
+        let capturer = capturer.clone();
+        #[cfg(feature = "webrtc")]
+        let sessions = sessions.clone();
+        #[cfg(feature = "webrtc")]
+        let timeouts = timeouts.clone();
+        let video_stream = signaling_server.video_stream();
+        let mut first_run = true;
+        supervisor.spawn_watched(VIDEO_TASK, VIDEO_RESTART_POLICY, VIDEO_WATCHDOG_TIMEOUT, move |cancel, heartbeat| {
+            // 重启时丢弃崩溃前的捕获器 (可能已处于异常状态)，由视频任务重新打开
+            let reset_capturer = !std::mem::replace(&mut first_run, false);
+            let task = video_task(
+                capturer.clone(),
+                show_cursor.clone(),
+                pointer.clone(),
+                wake_rx.clone(),
+                live_rx.clone(),
+                #[cfg(feature = "webrtc")]
+                sessions.clone(),
+                #[cfg(feature = "webrtc")]
+                timeouts.clone(),
+                video_stream.clone(),
+                config.clone(),
+                recording.clone(),
+                encoder_type.clone(),
+                bitrate_arg,
+                adaptive,
+                screen_width,
+                screen_height,
+                heartbeat,
+                cancel,
+            );
+            let capturer = capturer.clone();
+            async move {
+                if reset_capturer {
+                    if let Some(mut cap) = capturer.lock().await.take() {
+                        let _ = cap.stop();
+                    }
+                }
+                task.await
+            }
+        });
+    }
+
+    // 控制台延迟统计
+    if show_stats {
+        supervisor.adopt("stats", spawn_stats_printer());
+    }
 
     // 等待退出信号
     let ctrl_c = async {
@@ -332,56 +1224,528 @@ async fn run_host_mode_inner(
 
     ctrl_c.await;
 
-    // 清理
-    signal_handler.abort();
-    video_task.abort();
+    // 清理: 通知后台任务退出 (视频任务结束录制分段)，超时后强制终止
+    supervisor.shutdown(SHUTDOWN_GRACE).await;
+    #[cfg(feature = "quic")]
+    if let Some(server) = quic_server {
+        server.close();
+    }
     signaling_server.stop();
+    if let Some(mapper) = port_mapper {
+        mapper.close().await;
+    }
 
     // 停止捕获器
-    {
-        let mut cap = capturer.lock().await;
+    if let Some(cap) = capturer.lock().await.as_mut() {
         let _ = cap.stop();
     }
 
+    // 恢复本地屏幕和输入
+    #[cfg(any(feature = "webrtc", feature = "quic"))]
+    if privacy.is_enabled() {
+        let _ = privacy.set(false);
+    }
+
     info!("被控端模式已退出");
     Ok(())
 }
 
-/// Spawn the video capture and streaming task
-fn spawn_video_task(
-    capturer: Arc<Mutex<Box<dyn capture::Capturer>>>,
-    #[cfg(feature = "webrtc")] sessions: Arc<Mutex<HashMap<String, Arc<webrtc::host_session::HostSession>>>>,
-    config: config::Config,
-    selected_encoder: Option<String>,
-    bitrate_arg: Option<u32>,
-    enable_adaptive: bool,
-    screen_width: u32,
-    screen_height: u32,
-) -> tokio::task::JoinHandle<()> {
-    tokio::spawn(async move {
-        use crate::encoder;
+/// 无人值守访问密钥的哈希: 配置中没有时生成新密钥 (只显示这一次) 并保存哈希
+#[cfg(feature = "security")]
+fn unattended_secret_hash(config: &mut config::Config, config_path: &std::path::Path) -> Result<String> {
+    use crate::security::unattended;
 
-        let fps = config.capture.fps;
-        let bitrate = bitrate_arg.unwrap_or(2000);
+    if let Some(hash) = &config.host.unattended_secret_hash {
+        unattended::validate_hash(hash)?;
+        info!("无人值守访问已启用 (使用已保存的访问密钥)");
+        println!("  [*] 无人值守访问已启用，使用之前生成的访问密钥");
+        return Ok(hash.clone());
+    }
 
-        // 自适应码率控制器
-        let _adaptive_controller = if enable_adaptive {
-            Some(quality::adaptive_bitrate::RuleBasedAbreController::new(
-                AbreConfig {
-                    initial_bitrate: bitrate,
-                    min_bitrate: 500,
-                    max_bitrate: 10000,
+    let secret = unattended::generate_secret();
+    let hash = unattended::hash_secret(&secret)?;
+    config.host.unattended_secret_hash = Some(hash.clone());
+    config
+        .save(config_path)
+        .map_err(|e| anyhow::anyhow!("保存访问密钥到 {:?} 失败: {}", config_path, e))?;
+    info!("已生成无人值守访问密钥，哈希保存到 {:?}", config_path);
+    println!();
+    println!("  [*] 无人值守访问密钥: {}", secret);
+    println!("      该密钥只显示这一次，请妥善保存；Viewer 在输入 PIN 处填写即可连接");
+    println!("      删除配置中的 host.unattended_secret_hash 后重新启动可换发新密钥");
+    println!();
+    Ok(hash)
+}
+
+#[cfg(not(feature = "security"))]
+fn unattended_secret_hash(_config: &mut config::Config, _config_path: &std::path::Path) -> Result<String> {
+    anyhow::bail!("无人值守访问需要 security feature")
+}
+
+/// 要求 Viewer 提交 TOTP 验证码 (host.totp_secret 已配置时)
+#[cfg(feature = "security")]
+fn enable_totp(signaling_server: &mut EmbeddedSignalingServer, secret: String) -> Result<()> {
+    signaling_server
+        .set_totp(Some(secret))
+        .map_err(|e| anyhow::anyhow!("host.totp_secret 无效: {}", e))?;
+    info!("TOTP 双因素验证已启用");
+    println!("  [*] TOTP 双因素验证已启用，Viewer 需输入身份验证器 App 中的验证码");
+    Ok(())
+}
+
+#[cfg(not(feature = "security"))]
+fn enable_totp(_signaling_server: &mut EmbeddedSignalingServer, _secret: String) -> Result<()> {
+    anyhow::bail!("配置了 host.totp_secret，但 TOTP 双因素验证需要 security feature")
+}
+
+/// 注入输入事件，同时记录指针位置 (ROI 编码)
+fn inject_input(simulator: &SharedInputSimulator, pointer: Option<&PointerPosition>, events: Vec<input::InputEvent>) {
+    let Ok(mut simulator) = simulator.lock() else { return };
+    for event in events {
+        if let (Some(pointer), input::InputEvent::MouseMove { x, y }) = (pointer, &event) {
+            pointer.set(*x, *y);
+        }
+        if let Err(e) = simulator.handle_event(&event) {
+            debug!("注入输入失败: {}", e);
+        }
+    }
+}
+
+/// 信令事件所属 Viewer 的日志 span
+fn event_span(event: &HostSignalEvent) -> tracing::Span {
+    match event {
+        HostSignalEvent::Offer { from: peer_id, session_id, .. }
+        | HostSignalEvent::TicketRequested { from: peer_id, session_id }
+        | HostSignalEvent::ViewerResumed { peer_id, session_id } => crate::logging::session_span(peer_id, session_id),
+        HostSignalEvent::ViewerJoined { peer_id }
+        | HostSignalEvent::ViewerLeft { peer_id }
+        | HostSignalEvent::Ice { from: peer_id, .. }
+        | HostSignalEvent::Punch { from: peer_id, .. }
+        | HostSignalEvent::Cursor { from: peer_id, .. }
+        | HostSignalEvent::Profile { from: peer_id, .. }
+        | HostSignalEvent::Power { from: peer_id, .. }
+        | HostSignalEvent::Input { from: peer_id, .. }
+        | HostSignalEvent::InputClosed { from: peer_id }
+        | HostSignalEvent::FileReceived { from: peer_id, .. } => crate::logging::peer_span(peer_id),
+        HostSignalEvent::PinChanged { .. } | HostSignalEvent::Chat { .. } => tracing::Span::none(),
+    }
+}
+
+/// 审批信令 Viewer 的连接请求 (允许时按审批结果调整权限)，拒绝时通知该 Viewer 并返回 false
+async fn approve_viewer(
+    signaling: &EmbeddedSignalingServer,
+    approver: &ConnectionApprover,
+    peer_id: &str,
+    session_id: &str,
+) -> bool {
+    let requested = signaling.permissions(peer_id).await;
+    match approver.request(peer_id, requested).await {
+        ApprovalDecision::Accept(permissions) => {
+            if permissions != requested {
+                signaling.set_permissions(peer_id, permissions).await;
+            }
+            info!("已允许连接: {} ({})", peer_id, permissions);
+            println!("  [✓] 已允许 {} ({})", peer_id, permissions);
+            signaling.audit_session(session_id, AuditEvent::Approval {
+                peer: peer_id.to_string(),
+                accepted: true,
+                permissions: Some(permissions),
+            });
+            true
+        }
+        ApprovalDecision::Reject => {
+            info!("已拒绝连接: {}", peer_id);
+            signaling.audit_session(session_id, AuditEvent::Approval {
+                peer: peer_id.to_string(),
+                accepted: false,
+                permissions: None,
+            });
+            println!("  [x] 已拒绝 {}", peer_id);
+            signaling.send_error(peer_id, "被控端拒绝了连接请求").await;
+            false
+        }
+    }
+}
+
+/// 自适应码率的调整周期
+const ABR_UPDATE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 按时段和观看者数重新评估带宽上限的周期
+const BANDWIDTH_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 检查会话空闲超时和最长时长的间隔
+#[cfg(feature = "webrtc")]
+const SESSION_TIMEOUT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 每个 Viewer 在 Offer 之前最多暂存的 ICE 候选数
+#[cfg(feature = "webrtc")]
+const MAX_EARLY_CANDIDATES: usize = 32;
+
+/// 检查配置文件是否修改的间隔
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// `--stats` 控制台输出的间隔
+const STATS_PRINT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 没有观看者多久后进入空闲模式 (留出 Viewer 加入到发送 Offer 的时间)
+const IDLE_GRACE: Duration = Duration::from_secs(10);
+
+/// 捕获器重建失败后的重试间隔
+const CAPTURER_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 连续捕获失败多少次后重建捕获器 (显示配置变化的通知可能丢失)
+const MAX_CAPTURE_FAILURES: u32 = 30;
+
+/// 退出时等待后台任务收尾的时间
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(3);
+
+/// 启动时等待隧道报告公网地址的时间
+#[cfg(feature = "tunnel")]
+const TUNNEL_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 视频任务的名称 (健康状态和重启事件)
+const VIDEO_TASK: &str = "video";
+
+/// 视频任务 panic 或卡死后的重启策略 (重建捕获器和编码器): 1s、2s、4s... 最长 30s
+const VIDEO_RESTART_POLICY: RestartPolicy = RestartPolicy::OnPanic {
+    max_restarts: 8,
+    backoff: Duration::from_secs(1),
+    max_backoff: Duration::from_secs(30),
+};
+
+/// 视频任务超过该时间没有完成一轮循环时视为卡死 (空闲模式下不检查)
+const VIDEO_WATCHDOG_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// QUIC 传输的码率范围 (kbps)，拥塞控制估算的码率限制在此范围内
+#[cfg(feature = "quic")]
+const QUIC_MIN_BITRATE: u32 = 500;
+#[cfg(feature = "quic")]
+const QUIC_MAX_BITRATE: u32 = 10000;
+
+/// 定期在控制台输出延迟统计和带宽用量 (没有发送过视频帧时不输出)
+fn spawn_stats_printer() -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(STATS_PRINT_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let stats = quality::latency::get_statistics();
+            if stats.frames > 0 {
+                println!("[统计] {}", stats);
+                println!("[带宽] {}", quality::bandwidth::monitor().usage());
+            }
+        }
+    })
+}
+
+/// 配置文件热加载: 采集参数经 `live` 通知视频任务，其余变化提示需要重启
+fn spawn_config_watcher(
+    mut watcher: config::watch::ConfigWatcher,
+    live: Arc<watch::Sender<config::watch::LiveSettings>>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CONFIG_POLL_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            match watcher.poll() {
+                None => {}
+                Some(Ok(reload)) => {
+                    if !reload.applied.is_empty() {
+                        info!("配置已重新加载，立即生效: {}", reload.applied.join(", "));
+                        let reloaded = reload.live_settings();
+                        live.send_modify(|settings| settings.apply(&reloaded, &reload.applied));
+                    }
+                    if !reload.restart_required.is_empty() {
+                        warn!("以下配置修改需要重启后生效: {}", reload.restart_required.join(", "));
+                    }
+                }
+                Some(Err(e)) => warn!("重新加载配置 {:?} 失败，继续使用原配置: {}", watcher.path(), e),
+            }
+        }
+    })
+}
+
+/// 在控制台显示聊天消息
+fn print_chat(message: &ChatMessage) {
+    println!("  [聊天] {}: {}", message.from, message.text);
+}
+
+/// 被控端控制台命令: 收回/交还输入控制权，发送聊天消息，查看后台任务状态
+#[cfg(any(feature = "webrtc", feature = "quic"))]
+fn spawn_console_commands(
+    control: InputControl,
+    signaling: Arc<EmbeddedSignalingServer>,
+    chat_tx: tokio::sync::mpsc::UnboundedSender<ChatMessage>,
+    supervisor: Supervisor,
+) -> tokio::task::JoinHandle<()> {
+    use crate::signaling::chat::HOST_SENDER;
+
+    tokio::spawn(async move {
+        let Some(mut commands) = crate::signaling::approval::take_console_commands() else {
+            return;
+        };
+        while let Some(line) = commands.recv().await {
+            let line = line.trim();
+            if let Some(text) = line.strip_prefix("say ") {
+                if let Some(message) = signaling.post_chat(HOST_SENDER, text).await {
+                    let _ = chat_tx.send(message);
+                }
+                continue;
+            }
+            match line.to_lowercase().as_str() {
+                "" => {}
+                "take" | "t" => {
+                    control.update(|arbiter| ((), arbiter.take_host()));
+                }
+                "release" | "r" => {
+                    control.update(|arbiter| ((), arbiter.release_host()));
+                }
+                "health" | "h" => {
+                    for (name, health) in supervisor.health() {
+                        println!("  [任务] {}: {}", name, health);
+                    }
+                }
+                _ => println!(
+                    "  [?] 可用命令: take (收回控制权) / release (交还控制权) / say <消息> (发送聊天消息) / health (后台任务状态)"
+                ),
+            }
+        }
+    })
+}
+
+/// 把聊天消息转发给各会话 (不回送给发送者)
+#[cfg(feature = "webrtc")]
+fn spawn_chat_delivery(
+    sessions: Arc<Mutex<HashMap<String, Arc<webrtc::host_session::HostSession>>>>,
+    mut messages: tokio::sync::mpsc::UnboundedReceiver<ChatMessage>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(message) = messages.recv().await {
+            let sessions: Vec<_> = sessions.lock().await.values().cloned().collect();
+            for session in sessions.iter().filter(|s| s.peer_id() != message.from) {
+                session.send_chat(&message).await;
+            }
+        }
+    })
+}
+
+/// 把控制权消息发给对应会话 (按会话 ID 查找，已断开的会话忽略)
+#[cfg(feature = "webrtc")]
+fn spawn_control_delivery(
+    sessions: Arc<Mutex<HashMap<String, Arc<webrtc::host_session::HostSession>>>>,
+    mut messages: tokio::sync::mpsc::UnboundedReceiver<Outbox>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(outbox) = messages.recv().await {
+            let sessions: Vec<_> = sessions.lock().await.values().cloned().collect();
+            for (session_id, message) in outbox {
+                if let Some(session) = sessions.iter().find(|s| s.session_id() == session_id) {
+                    session.send_control(&message).await;
+                }
+            }
+        }
+    })
+}
+
+/// 会话空闲超时和最长时长: 到期前通过 control 通道通知控制端，到期后关闭会话
+/// (挂起中的会话由断线宽限期处理)
+#[cfg(feature = "webrtc")]
+fn spawn_session_timeouts(
+    timeouts: SharedTimeouts,
+    sessions: Arc<Mutex<HashMap<String, Arc<webrtc::host_session::HostSession>>>>,
+    control: InputControl,
+    privacy: Arc<PrivacyMode>,
+    signaling: Arc<EmbeddedSignalingServer>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SESSION_TIMEOUT_INTERVAL);
+        loop {
+            interval.tick().await;
+            let actions = match timeouts.lock() {
+                Ok(mut timeouts) => timeouts.poll(),
+                Err(_) => break,
+            };
+            for action in actions {
+                match action {
+                    TimeoutAction::Warn { session: session_id, reason, remaining } => {
+                        let session = sessions.lock().await.values().find(|s| s.session_id() == session_id).cloned();
+                        if let Some(session) = session {
+                            info!(
+                                parent: session.span(),
+                                "会话 {} 将在 {} 秒后断开 ({})",
+                                session_id, remaining.as_secs(), reason
+                            );
+                            let remaining_secs = remaining.as_secs().max(1);
+                            session.send_control(&ControlMessage::SessionExpiring { reason, remaining_secs }).await;
+                        }
+                    }
+                    TimeoutAction::Extend { session: session_id } => {
+                        let session = sessions.lock().await.values().find(|s| s.session_id() == session_id).cloned();
+                        if let Some(session) = session {
+                            session.send_control(&ControlMessage::SessionExtended).await;
+                        }
+                    }
+                    TimeoutAction::Expire { session: session_id, reason } => {
+                        let session = {
+                            let mut sessions = sessions.lock().await;
+                            let peer_id = sessions
+                                .iter()
+                                .find(|(_, s)| s.session_id() == session_id)
+                                .map(|(peer_id, _)| peer_id.clone());
+                            peer_id.and_then(|peer_id| sessions.remove(&peer_id))
+                        };
+                        let Some(session) = session else { continue };
+                        info!(parent: session.span(), "会话结束: {} ({})", session_id, reason);
+                        println!("  [-] 会话 {} 已断开: {}", session_id, reason);
+                        session.send_control(&ControlMessage::SessionEnded { reason }).await;
+                        control.leave(&session_id);
+                        privacy.session_ended();
+                        control.announce_privacy(&privacy);
+                        signaling.send_error(&session.peer_id(), &format!("会话已断开: {}", reason)).await;
+                        let _ = session.close().await;
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// 视频任务失败和重启时通知 Viewer (WebRTC 会话的 control 通道和 /video 连接)，并记录重启次数
+fn spawn_video_notices(
+    supervisor: &Supervisor,
+    #[cfg(feature = "webrtc")] sessions: Arc<Mutex<HashMap<String, Arc<webrtc::host_session::HostSession>>>>,
+    video_stream: Arc<crate::signaling::video_stream::VideoStream>,
+) -> tokio::task::JoinHandle<()> {
+    let mut events = supervisor.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let message = match events.recv().await {
+                Ok(TaskEvent::Failed { name, error, restart_in }) if name == VIDEO_TASK => {
+                    match restart_in {
+                        Some(delay) => warn!("视频流中断，{:.1} 秒后重建捕获器和编码器", delay.as_secs_f32()),
+                        None => error!("视频流多次异常退出，不再重试 (需要重启 sscontrol)"),
+                    }
+                    ControlMessage::StreamInterrupted {
+                        reason: error,
+                        retry_ms: restart_in.map(|delay| delay.as_millis() as u64),
+                    }
+                }
+                Ok(TaskEvent::Restarted { name, .. }) if name == VIDEO_TASK => {
+                    #[cfg(feature = "metrics")]
+                    crate::signaling::metrics::metrics().record_video_restart();
+                    video_stream.request_key_frame();
+                    ControlMessage::StreamResumed
+                }
+                Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            };
+
+            if let Ok(text) = serde_json::to_string(&message) {
+                video_stream.notify(&text);
+            }
+            #[cfg(feature = "webrtc")]
+            {
+                let viewers: Vec<_> = sessions.lock().await.values().cloned().collect();
+                for session in viewers {
+                    session.send_control(&message).await;
+                }
+            }
+        }
+    })
+}
+
+/// 创建并启动捕获器 (退出空闲模式时调用)
+fn open_capturer(screen_index: Option<u32>, show_cursor: bool) -> Result<Box<dyn capture::Capturer>> {
+    let mut cap = capture::create_capturer(screen_index)?;
+    cap.set_show_cursor(show_cursor);
+    cap.start()?;
+    Ok(cap)
+}
+
+/// 重建捕获器 (显示配置变化后调用)，指定的显示器已断开时改为捕获主显示器
+///
+/// 显示器重新连接后 [`capture::DisplayWatcher`] 会再次报告变化，届时恢复捕获指定的显示器
+fn reopen_capturer(screen_index: Option<u32>, show_cursor: bool) -> Result<Box<dyn capture::Capturer>> {
+    open_capturer(screen_index, show_cursor).or_else(|e| match screen_index {
+        Some(index) if index != 0 => {
+            warn!("无法捕获显示器 {} ({})，改为捕获主显示器", index, e);
+            open_capturer(None, show_cursor)
+        }
+        _ => Err(e),
+    })
+}
+
+/// Video capture and streaming task (run under the supervisor)
+///
+/// 没有观看者超过 `IDLE_GRACE` 后进入空闲模式: 释放捕获器 (DXGI 复制等 GPU 资源)
+/// 和编码器，停止帧定时器，直到 `wake` 收到通知后再按需重建。
+/// `live` 变化时应用热加载或 Viewer 切换的帧率、码率、画质预设和光标设置。
+/// 每轮循环向看门狗报告 `heartbeat`，空闲等待期间暂停检查。
+/// `cancel` 触发后结束录制分段并返回 (捕获器由调用方停止)
+#[allow(clippy::too_many_arguments)]
+// 显式标注 Send + 'static，Supervisor 需要在其他线程上重启任务
+#[allow(clippy::manual_async_fn)]
+fn video_task(
+    capturer: SharedCapturer,
+    show_cursor: Arc<AtomicBool>,
+    #[cfg_attr(not(feature = "h264"), allow(unused_variables))] pointer: PointerPosition,
+    mut wake: watch::Receiver<()>,
+    mut live: watch::Receiver<config::watch::LiveSettings>,
+    #[cfg(feature = "webrtc")] sessions: Arc<Mutex<HashMap<String, Arc<webrtc::host_session::HostSession>>>>,
+    #[cfg(feature = "webrtc")] timeouts: SharedTimeouts,
+    video_stream: Arc<crate::signaling::video_stream::VideoStream>,
+    config: config::Config,
+    recording: Option<RecordingConfig>,
+    selected_encoder: Option<String>,
+    bitrate_arg: Option<u32>,
+    enable_adaptive: bool,
+    mut screen_width: u32,
+    mut screen_height: u32,
+    heartbeat: Heartbeat,
+    cancel: CancellationToken,
+) -> impl std::future::Future<Output = ()> + Send + 'static {
+    async move {
+        use crate::encoder;
+
+        // 编码参数 (画质预设或配置中的帧率、码率，命令行 --bitrate 优先)
+        let mut live_settings = *live.borrow_and_update();
+        let mut encoding = live_settings.resolve(bitrate_arg);
+        let mut fps = encoding.fps;
+        let mut bitrate = encoding.bitrate;
+        if let Some(profile) = live_settings.profile {
+            info!("画质预设: {} ({} fps / {} kbps)", profile, fps, bitrate);
+        }
+
+        // 自适应码率控制器
+        let new_abr_controller = |bitrate: u32| {
+            quality::adaptive_bitrate::RuleBasedAbreController::new(
+                AbreConfig {
+                    initial_bitrate: bitrate,
+                    min_bitrate: 500,
+                    max_bitrate: 10000,
                     ..Default::default()
                 }
-            ))
-        } else {
-            None
+            )
         };
+        let mut adaptive_controller = enable_adaptive.then(|| new_abr_controller(bitrate));
+
+        // 带宽上限: 合计上限按观看者数均分后与单会话上限一起限制编码码率，
+        // 自适应码率和配置的码率都不超过它
+        let bandwidth_policy = quality::bandwidth::BandwidthPolicy::from_config(&config.bandwidth).unwrap_or_else(|e| {
+            warn!("带宽策略无效，不限制带宽: {}", e);
+            Default::default()
+        });
+        let mut bandwidth_cap: Option<u32> = None;
+        let mut last_bandwidth_update: Option<std::time::Instant> = None;
+        let capped = |kbps: u32, cap: Option<u32>| cap.map_or(kbps, |cap| kbps.min(cap));
 
         // 创建编码器（将在第一次循环时根据 session codec 决定）
         #[cfg(all(feature = "h264", feature = "webrtc"))]
         let mut vp8_encoder: Option<encoder::VP8Encoder> = None;
         #[cfg(all(feature = "h264", feature = "webrtc"))]
+        let mut vp9_encoder: Option<encoder::vp9::VP9Encoder> = None;
+        #[cfg(all(feature = "h264", feature = "webrtc"))]
         let mut h264_encoder: Option<encoder::hardware::HardwareEncoderWrapper> = None;
 
         #[cfg(all(not(feature = "h264"), feature = "webrtc"))]
@@ -392,42 +1756,299 @@ fn spawn_video_task(
         #[cfg(not(feature = "webrtc"))]
         let mut current_codec: Option<()> = None;
 
-        // ROI 编码器包装器（基于鼠标位置的区域化编码）
-        let mut _roi_encoder = ROIEncoderWrapper::new(screen_width, screen_height, None);
-
-        // 静态画面检测器
-        let mut static_detector = StaticSceneDetector::new(StaticDetectionConfig::default());
+        // 编码分辨率对齐 (YUV420 要求偶数宽高)，预设限制了高度时等比缩小
+        let mut aligner = match encoder::alignment::FrameAligner::even(screen_width, screen_height) {
+            Ok(aligner) => aligner,
+            Err(e) => {
+                error!("初始化编码分辨率失败: {}", e);
+                return;
+            }
+        };
+        let (mut encode_width, mut encode_height) =
+            quality::profile::cap_resolution(aligner.width(), aligner.height(), encoding.max_height);
+
+        // 动态分辨率 (码率降到下限后继续降低编码分辨率)
+        // simulcast 时由各观看者切换档位代替，最高档保持原始分辨率
+        let mut resolution_controller = (enable_adaptive && !config.host.simulcast).then(|| {
+            quality::dynamic_resolution::DynamicResolutionController::new(
+                encode_width,
+                encode_height,
+                quality::dynamic_resolution::DynamicResolutionConfig::default(),
+            )
+        });
+
+        // simulcast: 每个观看者按自己的网络状况接收 高/中/低 档，
+        // 最高档沿用上面的编码器，较低档位在有观看者时按需创建
+        #[cfg(feature = "webrtc")]
+        let mut simulcast = config
+            .host
+            .simulcast
+            .then(|| quality::simulcast::TierSelector::new(quality::simulcast::SimulcastConfig::for_bitrate(bitrate)));
+        #[cfg(all(feature = "h264", feature = "webrtc"))]
+        let mut tier_encoders: HashMap<usize, TierEncoder> = HashMap::new();
+
+        // ROI 编码: 控制者指针周围分配更多码率 (只作用于最高档编码器)
+        #[cfg(feature = "h264")]
+        let roi_enabled = config.capture.roi;
+        #[cfg(feature = "h264")]
+        let mut roi_applied = None;
+
+        // 静态画面检测 (每 30 个静态帧编码一个关键帧保持连接)
+        let mut static_stage = StaticSceneStage::new(encoding.static_keepalive_frames);
+        static_stage.configure(encoding.static_threshold, encoding.static_keepalive_frames);
+        let mut scene_stage = SceneChangeStage::default();
+        // 静态画面保活时重发的关键帧 (按 codec 和编码尺寸)
+        #[cfg(feature = "h264")]
+        let mut key_frame_cache = quality::keyframe_cache::KeyFrameCache::new();
+
+        // 会话录制 (直接写入发送给 Viewer 的编码数据)
+        let mut recorder = recording.and_then(|recording| {
+            let path = recording.path.display().to_string();
+            match crate::recorder::SessionRecorder::new(recording) {
+                Ok(recorder) => {
+                    info!("会话录制已启用: {}", path);
+                    Some(recorder)
+                }
+                Err(e) => {
+                    warn!("无法启用会话录制: {}", e);
+                    None
+                }
+            }
+        });
 
         if enable_adaptive {
             info!("自适应码率控制器已启用 (初始码率: {} kbps)", bitrate);
         }
-        info!("ROI 编码器包装器已启用（基于鼠标位置）");
+        #[cfg(feature = "webrtc")]
+        if let Some(selector) = simulcast.as_ref() {
+            info!(
+                "simulcast 已启用: {}",
+                selector
+                    .config()
+                    .tiers
+                    .iter()
+                    .map(|tier| format!("{} {} kbps", tier.name, tier.bitrate_kbps))
+                    .collect::<Vec<_>>()
+                    .join(" / ")
+            );
+        }
+        #[cfg(feature = "metrics")]
+        crate::signaling::metrics::metrics().set_target_bitrate(bitrate);
         info!("静态画面检测器已启用");
 
-        let frame_interval = Duration::from_millis(1000 / fps as u64);
+        // 静态画面或 CPU 过载时降低采集帧率
+        let mut fps_governor =
+            quality::fps_governor::FpsGovernor::new(fps, quality::fps_governor::FpsGovernorConfig::default());
+        let mut cpu_sampler = quality::fps_governor::CpuSampler::new();
+        #[cfg(feature = "metrics")]
+        crate::signaling::metrics::metrics().set_target_frame_rate(fps_governor.fps());
+        let mut frame_interval = fps_governor.frame_interval();
         let mut last_report = std::time::Instant::now();
         let mut last_stats_report = std::time::Instant::now();
+        let mut last_abr_update = std::time::Instant::now();
+        let mut last_stream_report = std::time::Instant::now();
+        // 每个会话的网络状态估计器 (按 peer_id)
+        let mut network_estimators: HashMap<String, quality::adaptive_bitrate::NetworkStateEstimator> = HashMap::new();
         let mut frame_count = 0u64;
 
         // 性能统计
         let mut total_encode_time = Duration::from_secs(0);
         let mut total_bytes_sent = 0u64;
-        let mut static_frames_count = 0u64;
-        let mut static_skipped_count = 0u64;
-        let mut consecutive_static_frames = 0u32;
         let mut last_fps_time = std::time::Instant::now();
         let mut fps_frame_count = 0u32;
+        // 最近一秒的编码帧率和发给 /video 观看者的字节数 (质量报告)
+        let mut measured_fps = 0.0f64;
+        let mut stream_bytes = 0u64;
 
         // 启动捕获器
-        {
-            let mut cap = capturer.lock().await;
+        if let Some(cap) = capturer.lock().await.as_mut() {
             if let Err(e) = cap.start() {
                 error!("启动屏幕捕获失败: {}", e);
                 return;
             }
         }
 
-        loop {
+        // 最近一次有观看者的时间
+        let mut last_active = std::time::Instant::now();
+        let mut idle = false;
+
+        // 显示配置变化 (分辨率改变、显示器插拔) 时丢弃捕获器，按新配置重建
+        let mut display_watcher = capture::DisplayWatcher::new();
+        let mut capture_failures = 0u32;
+
+        while !cancel.is_cancelled() {
+            heartbeat.beat();
+
+            // 空闲模式: 释放资源后挂起，直到 Viewer 加入
+            if idle {
+                if let Some(mut cap) = capturer.lock().await.take() {
+                    let _ = cap.stop();
+                }
+                #[cfg(feature = "webrtc")]
+                {
+                    current_codec = None;
+                }
+                #[cfg(all(feature = "h264", feature = "webrtc"))]
+                {
+                    vp8_encoder = None;
+                    h264_encoder = None;
+                    tier_encoders.clear();
+                }
+                network_estimators.clear();
+                // 空闲期间不产生画面，结束当前录制分段
+                if let Some(rec) = recorder.as_mut() {
+                    if let Err(e) = rec.finish() {
+                        warn!("结束录制分段失败: {}", e);
+                    }
+                }
+                static_stage.reset();
+                scene_stage.reset();
+                #[cfg(feature = "h264")]
+                key_frame_cache.clear();
+                info!("没有观看者，进入空闲模式 (已释放屏幕捕获和编码器)");
+
+                wake.borrow_and_update();
+                heartbeat.pause();
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    changed = wake.changed() => {
+                        if changed.is_err() {
+                            // 信令处理已退出
+                            break;
+                        }
+                    }
+                    _ = video_stream.subscribed() => {}
+                }
+
+                info!("Viewer 加入，退出空闲模式");
+                heartbeat.beat();
+                idle = false;
+                last_active = std::time::Instant::now();
+                last_report = std::time::Instant::now();
+                last_fps_time = std::time::Instant::now();
+            }
+
+            let display_changes = display_watcher.poll();
+            if !display_changes.is_empty() {
+                for change in &display_changes {
+                    info!("{}", change);
+                }
+                if let Some(mut cap) = capturer.lock().await.take() {
+                    let _ = cap.stop();
+                }
+            }
+
+            // 配置文件热加载或 Viewer 切换画质预设
+            if live.has_changed().unwrap_or(false) {
+                let settings = *live.borrow_and_update();
+                let next = settings.resolve(bitrate_arg);
+                if next != encoding {
+                    if settings.profile != live_settings.profile {
+                        info!(
+                            "画质预设切换: {} -> {}",
+                            live_settings.profile.map_or("无", |profile| profile.label()),
+                            settings.profile.map_or("无", |profile| profile.label())
+                        );
+                    }
+                    info!("编码参数更新: {} fps / {} kbps -> {} fps / {} kbps", fps, bitrate, next.fps, next.bitrate);
+                    fps = next.fps;
+                    fps_governor.set_max_fps(fps);
+                    frame_interval = fps_governor.frame_interval();
+                    static_stage.configure(next.static_threshold, next.static_keepalive_frames);
+                    if next.max_height != encoding.max_height {
+                        (encode_width, encode_height) =
+                            quality::profile::cap_resolution(aligner.width(), aligner.height(), next.max_height);
+                        if let Some(resolution) = resolution_controller.as_mut() {
+                            *resolution = quality::dynamic_resolution::DynamicResolutionController::new(
+                                encode_width,
+                                encode_height,
+                                quality::dynamic_resolution::DynamicResolutionConfig::default(),
+                            );
+                        }
+                    }
+                    if next.bitrate != bitrate {
+                        bitrate = next.bitrate;
+                        if let Some(controller) = adaptive_controller.as_mut() {
+                            *controller = new_abr_controller(bitrate);
+                        }
+                        #[cfg(feature = "webrtc")]
+                        if let Some(selector) = simulcast.as_mut() {
+                            *selector = quality::simulcast::TierSelector::new(selector.config().with_bitrate(bitrate));
+                        }
+                        #[cfg(feature = "metrics")]
+                        crate::signaling::metrics::metrics().set_target_bitrate(bitrate);
+                    }
+                    encoding = next;
+                    // 清空当前 codec，下一帧按新的参数重建编码器
+                    #[cfg(feature = "webrtc")]
+                    {
+                        current_codec = None;
+                    }
+                }
+                // Viewer 切换过光标时，只在配置项本身变化后覆盖
+                if settings.show_cursor != live_settings.show_cursor {
+                    show_cursor.store(settings.show_cursor, Ordering::Relaxed);
+                    if let Some(cap) = capturer.lock().await.as_mut() {
+                        if !cap.set_show_cursor(settings.show_cursor) && settings.show_cursor {
+                            warn!("当前捕获器不支持在画面中合成鼠标指针");
+                        }
+                    }
+                }
+                live_settings = settings;
+            }
+
+            // 空闲或显示配置变化后懒加载捕获器
+            if capturer.lock().await.is_none() {
+                // 报告 0 尺寸的捕获器按重建失败处理
+                let reopened = reopen_capturer(config.capture.screen_index, show_cursor.load(Ordering::Relaxed))
+                    .and_then(|cap| Ok((encoder::alignment::FrameAligner::even(cap.width(), cap.height())?, cap)));
+                match reopened {
+                    Ok((new_aligner, cap)) => {
+                        let (width, height) = (cap.width(), cap.height());
+                        if (width, height) != (screen_width, screen_height) {
+                            // 分辨率变化，按新尺寸重建对齐器和编码器
+                            info!("屏幕尺寸变化: {}x{} -> {}x{}", screen_width, screen_height, width, height);
+                            screen_width = width;
+                            screen_height = height;
+                            aligner = new_aligner;
+                            (encode_width, encode_height) =
+                                quality::profile::cap_resolution(aligner.width(), aligner.height(), encoding.max_height);
+                            if let Some(resolution) = resolution_controller.as_mut() {
+                                *resolution = quality::dynamic_resolution::DynamicResolutionController::new(
+                                    encode_width,
+                                    encode_height,
+                                    quality::dynamic_resolution::DynamicResolutionConfig::default(),
+                                );
+                            }
+                            static_stage.reset();
+                            scene_stage.reset();
+                            #[cfg(feature = "h264")]
+                            key_frame_cache.clear();
+
+                            // 清空当前 codec，下一帧按新尺寸重建编码器 (首帧即为关键帧)，
+                            // 并通知 Viewer 调整画面比例和输入坐标映射
+                            #[cfg(feature = "webrtc")]
+                            {
+                                current_codec = None;
+                                let message = ControlMessage::Resolution { width, height };
+                                let viewers: Vec<_> = sessions.lock().await.values().cloned().collect();
+                                for session in viewers {
+                                    session.send_control(&message).await;
+                                }
+                            }
+                        }
+                        capture_failures = 0;
+                        *capturer.lock().await = Some(cap);
+                    }
+                    Err(e) => {
+                        error!("重建屏幕捕获器失败: {}", e);
+                        idle = last_active.elapsed() >= IDLE_GRACE;
+                        tokio::time::sleep(CAPTURER_RETRY_INTERVAL).await;
+                        continue;
+                    }
+                }
+            }
+
             let start = std::time::Instant::now();
 
             #[cfg(feature = "webrtc")]
@@ -440,15 +2061,56 @@ fn spawn_video_task(
             #[cfg(not(feature = "webrtc"))]
             let active_sessions: Vec<()> = vec![];
 
-            if !active_sessions.is_empty() {
+            // 通过 /video 接收 H.264 的 Web 查看器 (没有 WebRTC 连接)
+            let stream_viewers = video_stream.subscriber_count();
+
+            if !active_sessions.is_empty() || stream_viewers > 0 {
+                last_active = std::time::Instant::now();
+            } else if last_active.elapsed() >= IDLE_GRACE {
+                idle = true;
+                continue;
+            }
+
+            // 录制分段的元数据记录其中出现过的会话
+            #[cfg(feature = "webrtc")]
+            if let Some(rec) = recorder.as_mut() {
+                rec.set_sessions(active_sessions.iter().map(|s| s.session_id()));
+            }
+
+            if !active_sessions.is_empty() || stream_viewers > 0 {
                 #[cfg(feature = "webrtc")]
-                // 获取第一个 session 的 codec 类型（所有 session 应该使用相同的 codec）
-                let session_codec = active_sessions.first().map(|s| s.codec());
+                // 获取第一个 session 的 codec 类型（所有 session 应该使用相同的 codec）；
+                // 只有 /video 观看者时使用 H.264
+                let session_codec = active_sessions
+                    .first()
+                    .map(|s| s.codec())
+                    .or((stream_viewers > 0).then_some(webrtc::host_session::VideoCodec::H264));
 
                 #[cfg(feature = "webrtc")]
                 // 如果 codec 类型改变，重新创建编码器
                 if current_codec != session_codec {
                     current_codec = session_codec;
+                    #[cfg(feature = "h264")]
+                    tier_encoders.clear();
+                    // VP9 按时域层给观看者分档 (不需要开启 simulcast)，其余 codec 按配置分别编码各档
+                    let layered = session_codec.is_some_and(|codec| codec.has_temporal_layers());
+                    if layered != simulcast.as_ref().is_some_and(|selector| selector.config().is_temporal()) {
+                        simulcast = (layered || config.host.simulcast).then(|| {
+                            let tiers = if layered {
+                                quality::simulcast::SimulcastConfig::temporal(bitrate, encoder::vp9::TEMPORAL_LAYERS)
+                            } else {
+                                quality::simulcast::SimulcastConfig::for_bitrate(bitrate)
+                            };
+                            quality::simulcast::TierSelector::new(tiers)
+                        });
+                    }
+                    // 启用自适应码率时以当前估计码率创建编码器 (不超过带宽上限)
+                    let bitrate = capped(
+                        adaptive_controller
+                            .as_ref()
+                            .map_or(bitrate, quality::adaptive_bitrate::RuleBasedAbreController::current_bitrate),
+                        bandwidth_cap,
+                    );
 
                     match session_codec {
                         Some(webrtc::host_session::VideoCodec::VP8) => {
@@ -456,13 +2118,47 @@ fn spawn_video_task(
                             #[cfg(feature = "h264")]
                             {
                                 h264_encoder = None;
-                                vp8_encoder = match encoder::VP8Encoder::new(screen_width, screen_height, fps, bitrate) {
+                                vp9_encoder = None;
+                                if let Some(cap) = capturer.lock().await.as_mut() {
+                                    cap.set_gpu_output(false);
+                                }
+                                vp8_encoder = match encoder::VP8Encoder::new(encode_width, encode_height, fps, bitrate) {
                                     Ok(enc) => Some(enc),
                                     Err(e) => {
                                         error!("创建 VP8 编码器失败: {}", e);
                                         None
                                     }
                                 };
+                                if let Some(rec) = recorder.as_mut() {
+                                    rec.set_stream(crate::recorder::RecordCodec::Vp8, encode_width, encode_height);
+                                }
+                            }
+                        }
+                        Some(webrtc::host_session::VideoCodec::VP9) => {
+                            info!("切换到 VP9 编码器 ({} 个时域层)", encoder::vp9::TEMPORAL_LAYERS);
+                            #[cfg(feature = "h264")]
+                            {
+                                h264_encoder = None;
+                                vp8_encoder = None;
+                                if let Some(cap) = capturer.lock().await.as_mut() {
+                                    cap.set_gpu_output(false);
+                                }
+                                vp9_encoder = match encoder::vp9::VP9Encoder::new(
+                                    encode_width,
+                                    encode_height,
+                                    fps,
+                                    bitrate,
+                                    encoder::vp9::TEMPORAL_LAYERS,
+                                ) {
+                                    Ok(enc) => Some(enc),
+                                    Err(e) => {
+                                        error!("创建 VP9 编码器失败: {}", e);
+                                        None
+                                    }
+                                };
+                                if let Some(rec) = recorder.as_mut() {
+                                    rec.set_stream(crate::recorder::RecordCodec::Vp9, encode_width, encode_height);
+                                }
                             }
                         }
                         Some(webrtc::host_session::VideoCodec::H264) => {
@@ -470,26 +2166,19 @@ fn spawn_video_task(
                             #[cfg(feature = "h264")]
                             {
                                 vp8_encoder = None;
+                                vp9_encoder = None;
                                 // 根据选择的编码器类型创建
-                                let hw_encoder_type = match selected_encoder.as_deref() {
-                                    Some("nvenc") => Some(encoder::hardware::HardwareEncoderType::NVENC),
-                                    Some("amf") => Some(encoder::hardware::HardwareEncoderType::AMF),
-                                    Some("qsv") => Some(encoder::hardware::HardwareEncoderType::QuickSync),
-                                    Some("videotoolbox") => Some(encoder::hardware::HardwareEncoderType::VideoToolbox),
-                                    _ => None,  // Auto
-                                };
-
                                 let hw_config = encoder::hardware::HardwareEncoderConfig {
-                                    encoder_type: hw_encoder_type.unwrap_or(encoder::hardware::HardwareEncoderType::Auto),
+                                    encoder_type: hardware_encoder_type(selected_encoder.as_deref()),
                                     bitrate,
                                     fps,
-                                    preset: encoder::hardware::EncoderPreset::LowLatency,
+                                    preset: encoding.preset,
                                 };
 
-                                h264_encoder = match encoder::hardware::HardwareEncoderWrapper::create(
+                                h264_encoder = match encoder::hardware::HardwareEncoderWrapper::create_with_fallback(
                                     hw_config.encoder_type,
-                                    screen_width,
-                                    screen_height,
+                                    encode_width,
+                                    encode_height,
                                     hw_config,
                                 ) {
                                     Ok(enc) => Some(enc),
@@ -498,6 +2187,27 @@ fn spawn_video_task(
                                         None
                                     }
                                 };
+                                if let Some(rec) = recorder.as_mut() {
+                                    rec.set_stream(crate::recorder::RecordCodec::H264, encode_width, encode_height);
+                                }
+
+                                // 编码器支持纹理输入且无需尺寸对齐时，让捕获器直接输出 GPU 帧 (零拷贝)
+                                // simulcast 的较低档位需要在 CPU 上缩放，不能使用 GPU 帧。
+                                // GPU 帧跳过静态画面和场景切换检测 (见 engine::stage)，capture.zero_copy 可关闭
+                                let texture_input = config.capture.zero_copy
+                                    && aligner.is_passthrough()
+                                    && simulcast.is_none()
+                                    && (encode_width, encode_height) == (aligner.width(), aligner.height())
+                                    && h264_encoder.as_ref()
+                                        .is_some_and(encoder::hardware::HardwareEncoder::supports_texture_input);
+                                let gpu_enabled = capturer
+                                    .lock()
+                                    .await
+                                    .as_mut()
+                                    .is_some_and(|cap| cap.set_gpu_output(texture_input));
+                                if gpu_enabled && texture_input {
+                                    info!("已启用 GPU 零拷贝编码路径");
+                                }
                             }
                         }
                         None => {
@@ -507,83 +2217,274 @@ fn spawn_video_task(
                 }
 
                 // 捕获屏幕
-                let frame = {
-                    let mut cap = capturer.lock().await;
-                    cap.capture()
+                let frame = match capturer.lock().await.as_mut() {
+                    Some(cap) => cap.capture(),
+                    None => Err(anyhow::anyhow!("屏幕捕获器未初始化")),
                 };
 
                 match frame {
                     Ok(_frame) => {
+                        capture_failures = 0;
+                        #[cfg(feature = "webrtc")]
+                        let mut timing = quality::latency::tracker().begin_frame();
+
                         // 静态画面检测 - 如果画面静态，跳过编码以节省资源
-                        let mut should_skip = false;
-                        match static_detector.detect(&_frame) {
-                            Ok(diff) => {
-                                let is_static = diff.difference_ratio < 0.01; // 1% 阈值
-                                static_detector.update_previous_frame(&_frame);
-
-                                if is_static {
-                                    static_frames_count += 1;
-                                    consecutive_static_frames += 1;
-
-                                    // 每 30 帧强制编码一个关键帧（保持连接活跃）
-                                    if consecutive_static_frames % 30 == 0 {
-                                        debug!("静态场景，发送关键帧保持连接");
-                                        should_skip = false;
-                                        // 请求关键帧 (仅支持 H264 硬件编码器)
-                                        #[cfg(feature = "h264")]
-                                        if let Some(ref mut enc) = h264_encoder {
-                                            let _ = enc.request_key_frame();
+                        let mut key_frame = false;
+                        // 新观看者 (/video 和 WebRTC 会话) 以及丢失参考帧的观看者 (PLI/FIR) 需要从关键帧开始解码，
+                        // 不等下一个 GOP 或静态画面的保活关键帧; 画面未变化时直接重发缓存的关键帧
+                        #[cfg(feature = "webrtc")]
+                        let session_wants_key_frame = take_key_frame_requests(&active_sessions);
+                        #[cfg(not(feature = "webrtc"))]
+                        let session_wants_key_frame = false;
+                        let viewer_wants_key_frame = video_stream.take_key_frame_request();
+                        let output = if viewer_wants_key_frame || session_wants_key_frame {
+                            Ok(StageOutput::Keepalive(_frame))
+                        } else {
+                            static_stage.process(_frame)
+                        };
+                        if let Some(next_fps) = fps_governor.observe_frame(static_stage.is_static()) {
+                            debug!("帧率调节: {} fps", next_fps);
+                            frame_interval = fps_governor.frame_interval();
+                        }
+                        // 画面变化时空闲超时重新计时
+                        #[cfg(feature = "webrtc")]
+                        if !static_stage.is_static() {
+                            if let Ok(mut timeouts) = timeouts.lock() {
+                                timeouts.screen_changed();
+                            }
+                        }
+                        let _frame = match output {
+                            Ok(StageOutput::Frame(frame)) => frame,
+                            Ok(StageOutput::KeyFrame(frame)) => {
+                                key_frame = true;
+                                frame
+                            }
+                            Ok(StageOutput::Keepalive(frame)) => {
+                                // 画面自上一个关键帧以来没有变化: 重发缓存的关键帧，编码器保持空闲
+                                #[cfg(feature = "h264")]
+                                let resent = resend_cached_key_frame(
+                                    &mut key_frame_cache,
+                                    current_codec
+                                        .filter(|_| simulcast.is_none())
+                                        .map(|codec| StreamKey::new(codec.name(), encode_width, encode_height)),
+                                    &active_sessions,
+                                    &video_stream,
+                                    frame_interval,
+                                )
+                                .await;
+                                #[cfg(not(feature = "h264"))]
+                                let resent = false;
+                                if resent {
+                                    if let Some(rest) = frame_interval.checked_sub(start.elapsed()) {
+                                        tokio::select! {
+                                            _ = cancel.cancelled() => break,
+                                            _ = tokio::time::sleep(rest) => {}
                                         }
-                                    } else {
-                                        // 跳过编码，直接进入下一帧
-                                        static_skipped_count += 1;
-                                        should_skip = true;
                                     }
-                                } else {
-                                    // 动态场景，正常编码
-                                    consecutive_static_frames = 0;
+                                    continue;
+                                }
+                                key_frame = true;
+                                frame
+                            }
+                            Ok(StageOutput::Drop) => {
+                                // 跳过编码的帧同样按 (调节后的) 帧率采集，否则静态画面时会空转
+                                if let Some(rest) = frame_interval.checked_sub(start.elapsed()) {
+                                    tokio::select! {
+                                        _ = cancel.cancelled() => break,
+                                        _ = tokio::time::sleep(rest) => {}
+                                    }
                                 }
+                                continue;
                             }
                             Err(e) => {
-                                warn!("静态检测失败: {}，继续编码", e);
-                                consecutive_static_frames = 0;
+                                warn!("{} 处理失败: {}", static_stage.name(), e);
+                                continue;
+                            }
+                        };
+
+                        // 场景切换 (切换应用、全屏视频开始播放) 时立即插入关键帧，不等下一个 GOP
+                        let _frame = match scene_stage.process(_frame) {
+                            Ok(StageOutput::KeyFrame(frame)) | Ok(StageOutput::Keepalive(frame)) => {
+                                key_frame = true;
+                                frame
+                            }
+                            Ok(StageOutput::Frame(frame)) => frame,
+                            Ok(StageOutput::Drop) => continue,
+                            Err(e) => {
+                                warn!("{} 处理失败: {}", scene_stage.name(), e);
+                                continue;
+                            }
+                        };
+
+                        // 请求关键帧 (所有档位的编码器)
+                        if key_frame {
+                            #[cfg(feature = "h264")]
+                            {
+                                if let Some(ref mut enc) = h264_encoder {
+                                    let _ = enc.request_key_frame();
+                                }
+                                if let Some(ref mut enc) = vp8_encoder {
+                                    enc.request_key_frame();
+                                }
+                                if let Some(ref mut enc) = vp9_encoder {
+                                    enc.request_key_frame();
+                                }
+                                for enc in tier_encoders.values_mut() {
+                                    enc.request_key_frame();
+                                }
                             }
                         }
 
-                        // 如果画面静态且不是关键帧时刻，跳过编码
-                        if should_skip {
-                            // 跳过编码以节省 CPU
-                            continue;
+                        // 对齐到编码器要求的尺寸
+                        let _frame = aligner.align(_frame);
+
+                        // 动态分辨率降档时缩放到当前编码尺寸
+                        let _frame = if (_frame.width, _frame.height) != (encode_width, encode_height) {
+                            quality::dynamic_resolution::scale_frame(&_frame, encode_width, encode_height)
+                        } else {
+                            _frame
+                        };
+
+                        // simulcast 时最高档编码器只发给最高档的观看者，没有时跳过编码；
+                        // 时域分层时所有观看者共用一路码流，由各会话丢弃不需要的层
+                        #[cfg(feature = "webrtc")]
+                        let primary_sessions: Vec<Arc<webrtc::host_session::HostSession>> = active_sessions
+                            .iter()
+                            .filter(|s| {
+                                simulcast.as_ref().is_none_or(|selector| {
+                                    selector.config().is_temporal() || selector.tier_of(&s.peer_id()) == 0
+                                })
+                            })
+                            .cloned()
+                            .collect();
+
+                        // 按控制者的指针位置更新 ROI
+                        #[cfg(feature = "h264")]
+                        if roi_enabled {
+                            let regions = pointer.regions(_frame.width, _frame.height);
+                            let applied = match current_codec {
+                                Some(webrtc::host_session::VideoCodec::VP8) => {
+                                    vp8_encoder.as_mut().map(|enc| enc.set_roi(&regions))
+                                }
+                                Some(webrtc::host_session::VideoCodec::VP9) => {
+                                    vp9_encoder.as_mut().map(|enc| enc.set_roi(&regions))
+                                }
+                                Some(webrtc::host_session::VideoCodec::H264) => h264_encoder
+                                    .as_mut()
+                                    .map(|enc| encoder::hardware::HardwareEncoder::set_roi(enc, &regions)),
+                                None => None,
+                            };
+                            if !regions.is_empty() && applied.is_some() && applied != roi_applied {
+                                if applied == Some(true) {
+                                    info!("ROI 编码已生效 (指针周围 {}px)", regions[0].width().max(regions[0].height()));
+                                } else {
+                                    info!("当前编码器不支持 ROI，按整帧码控编码");
+                                }
+                                roi_applied = applied;
+                            }
                         }
 
                         // 根据当前 codec 编码
                         let encode_start = std::time::Instant::now();
 
                         #[cfg(feature = "h264")]
-                        match current_codec {
+                        match current_codec.filter(|_| !primary_sessions.is_empty() || stream_viewers > 0) {
                             Some(webrtc::host_session::VideoCodec::VP8) => {
                                 if let Some(ref mut encoder) = vp8_encoder {
                                     match encoder.encode(&_frame) {
                                         Ok(Some(vp8_data)) => {
                                             let encode_duration = encode_start.elapsed();
+                                            timing.mark_encoded();
+                                            total_encode_time += encode_duration;
+
+                                            // 发送给使用该编码器的会话
+                                            let key_frame = crate::recorder::is_vp8_key_frame(&vp8_data);
+                                            broadcast_video(&primary_sessions, VideoSample {
+                                                data: &vp8_data,
+                                                is_key_frame: key_frame,
+                                                temporal_layer: 0,
+                                                tier: 0,
+                                                duration: frame_interval,
+                                            })
+                                            .await;
+                                            key_frame_cache.record(
+                                                StreamKey::new("VP8", _frame.width, _frame.height),
+                                                &vp8_data,
+                                                key_frame,
+                                            );
+                                            finish_frame(timing, vp8_data.len(), &primary_sessions).await;
+                                            total_bytes_sent += vp8_data.len() as u64;
+                                            frame_count += 1;
+                                            fps_frame_count += 1;
+
+                                            if let Some(rec) = recorder.as_mut() {
+                                                match rec.write(&vp8_data, key_frame) {
+                                                    Ok(()) if rec.wants_key_frame() => encoder.request_key_frame(),
+                                                    Ok(()) => {}
+                                                    Err(e) => {
+                                                        error!("录制失败，已停止录制: {}", e);
+                                                        recorder = None;
+                                                    }
+                                                }
+                                            }
+                                        }
+                                        Ok(None) => {}
+                                        Err(e) => {
+                                            error!("VP8 编码失败: {}", e);
+                                        }
+                                    }
+                                }
+                            }
+                            Some(webrtc::host_session::VideoCodec::VP9) => {
+                                // 按各观看者的档位设置转发的最高时域层
+                                if let Some(selector) = simulcast.as_ref() {
+                                    for session in &primary_sessions {
+                                        let tier = &selector.config().tiers[selector.tier_of(&session.peer_id())];
+                                        if let Some(layer) = tier.temporal_layer {
+                                            session.set_max_temporal_layer(layer);
+                                        }
+                                    }
+                                }
+                                if let Some(ref mut encoder) = vp9_encoder {
+                                    match encoder.encode_frame(&_frame) {
+                                        Ok(Some(layered)) => {
+                                            let encode_duration = encode_start.elapsed();
+                                            timing.mark_encoded();
                                             total_encode_time += encode_duration;
 
-                                            // 发送给所有活跃会话
-                                            for session in &active_sessions {
-                                                if let Err(e) = session
-                                                    .send_video_sample(vp8_data.clone(), frame_interval)
-                                                    .await
-                                                {
-                                                    error!("发送视频帧失败: {}", e);
-                                                }
-                                            }
-                                            total_bytes_sent += vp8_data.len() as u64;
+                                            // 只编码一次，各会话按自己的档位丢弃较高的层
+                                            broadcast_video(&primary_sessions, VideoSample {
+                                                data: &layered.data,
+                                                is_key_frame: layered.is_key_frame,
+                                                temporal_layer: layered.temporal_layer,
+                                                tier: 0,
+                                                duration: frame_interval,
+                                            })
+                                            .await;
+                                            key_frame_cache.record(
+                                                StreamKey::new("VP9", _frame.width, _frame.height),
+                                                &layered.data,
+                                                layered.is_key_frame,
+                                            );
+                                            finish_frame(timing, layered.data.len(), &primary_sessions).await;
+                                            total_bytes_sent += layered.data.len() as u64;
                                             frame_count += 1;
                                             fps_frame_count += 1;
+
+                                            if let Some(rec) = recorder.as_mut() {
+                                                match rec.write(&layered.data, layered.is_key_frame) {
+                                                    Ok(()) if rec.wants_key_frame() => encoder.request_key_frame(),
+                                                    Ok(()) => {}
+                                                    Err(e) => {
+                                                        error!("录制失败，已停止录制: {}", e);
+                                                        recorder = None;
+                                                    }
+                                                }
+                                            }
                                         }
                                         Ok(None) => {}
                                         Err(e) => {
-                                            error!("VP8 编码失败: {}", e);
+                                            error!("VP9 编码失败: {}", e);
                                         }
                                     }
                                 }
@@ -593,47 +2494,151 @@ fn spawn_video_task(
                                     match encoder.encode(&_frame) {
                                         Ok(Some(packet)) => {
                                             let encode_duration = encode_start.elapsed();
+                                            timing.mark_encoded();
                                             total_encode_time += encode_duration;
 
-                                            // 发送给所有活跃会话
-                                            for session in &active_sessions {
-                                                if let Err(e) = session
-                                                    .send_video_sample(packet.data.clone(), frame_interval)
-                                                    .await
-                                                {
-                                                    error!("发送视频帧失败: {}", e);
-                                                }
+                                            // 发送给使用该编码器的会话
+                                            broadcast_video(&primary_sessions, VideoSample {
+                                                data: &packet.data,
+                                                is_key_frame: packet.is_key_frame,
+                                                temporal_layer: 0,
+                                                tier: 0,
+                                                duration: frame_interval,
+                                            })
+                                            .await;
+                                            video_stream.publish(&packet.data, packet.is_key_frame, _frame.width, _frame.height);
+                                            key_frame_cache.record(
+                                                StreamKey::new("H.264", _frame.width, _frame.height),
+                                                &packet.data,
+                                                packet.is_key_frame,
+                                            );
+                                            if stream_viewers > 0 {
+                                                stream_bytes += packet.data.len() as u64;
                                             }
+                                            finish_frame(timing, packet.data.len(), &primary_sessions).await;
                                             total_bytes_sent += packet.data.len() as u64;
                                             frame_count += 1;
                                             fps_frame_count += 1;
+
+                                            if let Some(rec) = recorder.as_mut() {
+                                                match rec.write(&packet.data, packet.is_key_frame) {
+                                                    Ok(()) if rec.wants_key_frame() => {
+                                                        let _ = encoder.request_key_frame();
+                                                    }
+                                                    Ok(()) => {}
+                                                    Err(e) => {
+                                                        error!("录制失败，已停止录制: {}", e);
+                                                        recorder = None;
+                                                    }
+                                                }
+                                            }
                                         }
                                         Ok(None) => {}
                                         Err(e) => {
                                             error!("H.264 编码失败: {}", e);
                                         }
                                     }
+
+                                    // 编码器连续失败后已换用下一个编码器: 通知观看者，新编码器不支持纹理输入时改回 CPU 帧
+                                    if let Some(switch) = encoder.take_switch() {
+                                        report_encoder_switch(&switch, &active_sessions, &video_stream).await;
+                                        if !encoder::hardware::HardwareEncoder::supports_texture_input(encoder) {
+                                            if let Some(cap) = capturer.lock().await.as_mut() {
+                                                cap.set_gpu_output(false);
+                                            }
+                                        }
+                                    }
                                 }
                             }
                             None => {}
                         }
 
+                        // simulcast 较低档位: 缩放后分别编码，只发给该档的观看者
+                        #[cfg(feature = "h264")]
+                        if let (Some(selector), Some(codec)) =
+                            (simulcast.as_ref().filter(|selector| !selector.config().is_temporal()), current_codec)
+                        {
+                            for tier in 1..selector.tier_count() {
+                                let viewers: Vec<_> = active_sessions
+                                    .iter()
+                                    .filter(|s| selector.tier_of(&s.peer_id()) == tier)
+                                    .cloned()
+                                    .collect();
+                                if viewers.is_empty() {
+                                    // 没有观看者的档位释放编码器
+                                    tier_encoders.remove(&tier);
+                                    continue;
+                                }
+
+                                let tier_config = selector.config();
+                                let (width, height) = tier_config.tier_size(tier, _frame.width, _frame.height);
+                                let tier_encoder = match tier_encoders.entry(tier) {
+                                    std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                                    std::collections::hash_map::Entry::Vacant(entry) => {
+                                        let bitrate = capped(tier_config.tiers[tier].bitrate_kbps, bandwidth_cap);
+                                        match TierEncoder::create(codec, width, height, fps, bitrate, selected_encoder.as_deref()) {
+                                            Ok(enc) => {
+                                                info!(
+                                                    "simulcast {}档编码器已创建: {}x{} @ {} kbps",
+                                                    tier_config.tiers[tier].name, width, height, bitrate
+                                                );
+                                                entry.insert(enc)
+                                            }
+                                            Err(e) => {
+                                                error!("创建 simulcast {}档编码器失败: {}", tier_config.tiers[tier].name, e);
+                                                continue;
+                                            }
+                                        }
+                                    }
+                                };
+
+                                let scaled = quality::dynamic_resolution::scale_frame(&_frame, width, height);
+                                match tier_encoder.encode(&scaled) {
+                                    Ok(Some(data)) => {
+                                        let is_key_frame = match codec {
+                                            webrtc::host_session::VideoCodec::VP8 => crate::recorder::is_vp8_key_frame(&data),
+                                            webrtc::host_session::VideoCodec::VP9 => crate::recorder::is_vp9_key_frame(&data),
+                                            webrtc::host_session::VideoCodec::H264 => crate::recorder::is_h264_key_frame(&data),
+                                        };
+                                        let sample = VideoSample {
+                                            data: &data,
+                                            is_key_frame,
+                                            temporal_layer: 0,
+                                            tier,
+                                            duration: frame_interval,
+                                        };
+                                        broadcast_video(&viewers, sample).await;
+                                        quality::bandwidth::monitor().record(data.len() * viewers.len());
+                                        #[cfg(feature = "metrics")]
+                                        crate::signaling::metrics::metrics().record_frame(data.len());
+                                        total_bytes_sent += data.len() as u64;
+                                    }
+                                    Ok(None) => {}
+                                    Err(e) => {
+                                        error!("simulcast {}档编码失败: {}", tier_config.tiers[tier].name, e);
+                                    }
+                                }
+                            }
+                        }
+
                         #[cfg(all(not(feature = "h264"), feature = "webrtc"))]
                         if let Some(ref mut encoder) = vp8_encoder {
                             match encoder.encode(&_frame) {
                                 Ok(Some(vp8_data)) => {
                                     let encode_duration = encode_start.elapsed();
+                                    timing.mark_encoded();
                                     total_encode_time += encode_duration;
 
-                                    // 发送给所有活跃会话
-                                    for session in &active_sessions {
-                                        if let Err(e) = session
-                                            .send_video_sample(vp8_data.clone(), frame_interval)
-                                            .await
-                                        {
-                                            error!("发送视频帧失败: {}", e);
-                                        }
-                                    }
+                                    // 发送给使用该编码器的会话
+                                    broadcast_video(&primary_sessions, VideoSample {
+                                        data: &vp8_data,
+                                        is_key_frame: crate::recorder::is_vp8_key_frame(&vp8_data),
+                                        temporal_layer: 0,
+                                        tier: 0,
+                                        duration: frame_interval,
+                                    })
+                                    .await;
+                                    finish_frame(timing, vp8_data.len(), &primary_sessions).await;
                                     total_bytes_sent += vp8_data.len() as u64;
                                     frame_count += 1;
                                     fps_frame_count += 1;
@@ -645,12 +2650,189 @@ fn spawn_video_task(
                             }
                         }
                     }
+                    Err(e) if capture::DisplayChanged::is(&e) => {
+                        // 下一轮按新配置重建捕获器
+                        info!("{}，重建屏幕捕获器", e);
+                        if let Some(mut cap) = capturer.lock().await.take() {
+                            let _ = cap.stop();
+                        }
+                    }
                     Err(e) => {
                         // 超时是正常的，屏幕未更新时发生
                         if e.to_string().contains("超时") {
                             debug!("屏幕捕获超时 (屏幕未更新)");
                         } else {
                             error!("屏幕捕获失败: {}", e);
+                            capture_failures += 1;
+                            if capture_failures >= MAX_CAPTURE_FAILURES {
+                                warn!("屏幕捕获连续失败 {} 次，重建捕获器", capture_failures);
+                                capture_failures = 0;
+                                if let Some(mut cap) = capturer.lock().await.take() {
+                                    let _ = cap.stop();
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            // 带宽上限: 按当前时段和观看者数重新评估，变化时调整编码码率
+            if last_bandwidth_update.is_none_or(|at| at.elapsed() >= BANDWIDTH_UPDATE_INTERVAL) {
+                last_bandwidth_update = Some(std::time::Instant::now());
+                let limit = bandwidth_policy.limit_now();
+                quality::bandwidth::monitor().set_limit(limit, active_sessions.len());
+                let cap = limit.per_viewer(active_sessions.len());
+                if cap != bandwidth_cap {
+                    match cap {
+                        Some(kbps) => info!(
+                            "带宽上限: 每路 {} kbps ({} 个观看者{})",
+                            kbps,
+                            active_sessions.len(),
+                            if limit.scheduled { "，限速时段" } else { "" }
+                        ),
+                        None => info!("带宽上限已解除"),
+                    }
+                    bandwidth_cap = cap;
+                    let target = capped(
+                        adaptive_controller
+                            .as_ref()
+                            .map_or(bitrate, quality::adaptive_bitrate::RuleBasedAbreController::current_bitrate),
+                        cap,
+                    );
+                    #[cfg(feature = "metrics")]
+                    crate::signaling::metrics::metrics().set_target_bitrate(target);
+                    #[cfg(all(feature = "h264", feature = "webrtc"))]
+                    {
+                        set_encoder_bitrate(h264_encoder.as_mut(), vp8_encoder.as_mut(), vp9_encoder.as_mut(), target);
+                        // simulcast 较低档位按新上限重建
+                        tier_encoders.clear();
+                    }
+                    #[cfg(not(all(feature = "h264", feature = "webrtc")))]
+                    let _ = target;
+                }
+            }
+
+            // 根据各会话的 RTCP 统计 (丢包/RTT/NACK) 估计网络状态，向观看者推送质量报告；
+            // 自适应码率 / simulcast 据此调整编码码率和观看者档位
+            #[cfg(feature = "webrtc")]
+            if last_abr_update.elapsed() >= ABR_UPDATE_INTERVAL {
+                last_abr_update = std::time::Instant::now();
+                let target_bitrate = capped(
+                    adaptive_controller
+                        .as_ref()
+                        .map_or(bitrate, quality::adaptive_bitrate::RuleBasedAbreController::current_bitrate),
+                    bandwidth_cap,
+                );
+                #[cfg(feature = "h264")]
+                let encoder_name = match current_codec {
+                    Some(webrtc::host_session::VideoCodec::H264) => h264_encoder
+                        .as_ref()
+                        .map(|enc| encoder::hardware::HardwareEncoder::encoder_type(enc).to_string()),
+                    Some(_) => Some("libvpx".to_string()),
+                    None => None,
+                }
+                .unwrap_or_default();
+                #[cfg(not(feature = "h264"))]
+                let encoder_name = String::new();
+
+                network_estimators.retain(|peer_id, _| active_sessions.iter().any(|s| s.peer_id() == *peer_id));
+                if let Some(selector) = simulcast.as_mut() {
+                    selector.retain(|peer_id| active_sessions.iter().any(|s| s.peer_id() == peer_id));
+                }
+                let mut network_state: Option<quality::adaptive_bitrate::NetworkState> = None;
+                for session in &active_sessions {
+                    let snapshot = session.transport_snapshot().await;
+                    let estimator = network_estimators.entry(session.peer_id()).or_default();
+                    if let Some(state) = estimator.update(snapshot) {
+                        debug!(
+                            parent: session.span(),
+                            "[{}] 网络状态: RTT {:.0}ms, 丢包 {:.1}%, 带宽 {:.2}Mbps",
+                            session.session_id(), state.latency_ms, state.packet_loss * 100.0, state.bandwidth_mbps
+                        );
+
+                        // 较低档位的观看者按所在档位的码率报告
+                        let tier_bitrate = simulcast.as_ref().and_then(|selector| {
+                            let tier = selector.tier_of(&session.peer_id());
+                            (tier != 0).then(|| selector.config().tiers[tier].bitrate_kbps)
+                        });
+                        let report = quality::report::QualityReport::new(
+                            estimator.sent_kbps(),
+                            tier_bitrate.unwrap_or(target_bitrate),
+                            measured_fps,
+                            snapshot.rtt_ms,
+                            state.packet_loss,
+                            session.codec().name(),
+                            &encoder_name,
+                        )
+                        .for_session(session.session_id());
+                        debug!(parent: session.span(), "[{}] 连接质量: {}", session.session_id(), report);
+                        session.send_quality_report(&report).await;
+
+                        if adaptive_controller.is_none() && simulcast.is_none() {
+                            continue;
+                        }
+
+                        if let Some(selector) = simulcast.as_mut() {
+                            if let Some(switch) = selector.update(&session.peer_id(), state) {
+                                let tiers = &selector.config().tiers;
+                                info!(
+                                    parent: session.span(),
+                                    "[{}] simulcast 切换: {}档 -> {}档 (丢包: {:.1}%, 带宽: {:.2}Mbps)",
+                                    session.session_id(), tiers[switch.from].name, tiers[switch.to].name,
+                                    state.packet_loss * 100.0, state.bandwidth_mbps
+                                );
+                                // 切换后的第一帧必须是关键帧 (新建的编码器首帧即为关键帧)；
+                                // 时域分层的档位从下一个基础层帧开始切换，不需要关键帧
+                                #[cfg(feature = "h264")]
+                                if tiers[switch.to].temporal_layer.is_none() {
+                                    if switch.to == 0 {
+                                        if let Some(ref mut enc) = h264_encoder {
+                                            let _ = encoder::hardware::HardwareEncoder::request_key_frame(enc);
+                                        }
+                                        if let Some(ref mut enc) = vp8_encoder {
+                                            enc.request_key_frame();
+                                        }
+                                    } else if let Some(enc) = tier_encoders.get_mut(&switch.to) {
+                                        enc.request_key_frame();
+                                    }
+                                }
+                            }
+                            // 码率只照顾最高档的观看者，较低档位由 simulcast 承接
+                            if selector.tier_of(&session.peer_id()) != 0 {
+                                continue;
+                            }
+                        }
+
+                        // 多个观看者时按最差的网络状态决策
+                        network_state = Some(network_state.map_or(state, |merged| merged.worst(state)));
+                    }
+                }
+
+                if let (Some(abr), Some(state)) = (adaptive_controller.as_mut(), network_state) {
+                    let previous = abr.current_bitrate();
+                    let target = abr.update(state);
+                    if target != previous {
+                        info!(
+                            "自适应码率: {} -> {} kbps (RTT: {:.0}ms, 丢包: {:.1}%, 带宽: {:.2}Mbps)",
+                            previous, target, state.latency_ms, state.packet_loss * 100.0, state.bandwidth_mbps
+                        );
+                        // 带宽上限以内才生效
+                        let applied = capped(target, bandwidth_cap);
+                        if applied != capped(previous, bandwidth_cap) {
+                            #[cfg(feature = "metrics")]
+                            crate::signaling::metrics::metrics().set_target_bitrate(applied);
+                            #[cfg(feature = "h264")]
+                            set_encoder_bitrate(h264_encoder.as_mut(), vp8_encoder.as_mut(), vp9_encoder.as_mut(), applied);
+                        }
+                    }
+
+                    // 码率已到下限仍不够时降低分辨率
+                    if let Some(resolution) = resolution_controller.as_mut() {
+                        if let Some((width, height)) = resolution.update(target, abr.min_bitrate()) {
+                            // 清空当前 codec，下一帧按新尺寸重建编码器，首帧即为关键帧
+                            encode_width = width;
+                            encode_height = height;
+                            current_codec = None;
                         }
                     }
                 }
@@ -670,14 +2852,31 @@ fn spawn_video_task(
                     info!("视频流统计:");
                     info!("  帧数: {}, FPS: {:.1}", frame_count, fps_actual);
                     info!("  观看者: {}", active_sessions.len());
+                    #[cfg(feature = "webrtc")]
+                    info!(
+                        "  会话: {}",
+                        active_sessions.iter().map(|s| s.session_id()).collect::<Vec<_>>().join(", ")
+                    );
                     info!("  平均编码延迟: {:?}", avg_encode_time);
                     info!("  带宽: {:.2} Mbps", bandwidth_mbps);
-                    info!("  静态帧检测: {}, 跳过编码: {}", static_frames_count, static_skipped_count);
+                    let (static_frames, skipped_frames) = static_stage.take_stats();
+                    info!("  静态帧检测: {}, 跳过编码: {}", static_frames, skipped_frames);
+                    #[cfg(feature = "h264")]
+                    info!("  重发缓存关键帧: {}", key_frame_cache.take_hits());
+                    info!("  场景切换关键帧: {}", scene_stage.take_stats());
+                    info!(
+                        "  有效帧率: {} fps (上限 {} fps{})",
+                        fps_governor.fps(),
+                        fps_governor.max_fps(),
+                        if fps_governor.cpu_limited() { "，CPU 负载过高" } else { "" }
+                    );
                 }
+                static_stage.take_stats();
+                scene_stage.take_stats();
+                #[cfg(feature = "h264")]
+                key_frame_cache.take_hits();
                 frame_count = 0;
                 total_bytes_sent = 0;
-                static_frames_count = 0;
-                static_skipped_count = 0;
                 total_encode_time = Duration::from_secs(0);
                 last_report = std::time::Instant::now();
             }
@@ -685,24 +2884,673 @@ fn spawn_video_task(
             // 每秒报告一次 FPS
             if last_fps_time.elapsed() >= Duration::from_secs(1) {
                 let fps = fps_frame_count as f64 / last_fps_time.elapsed().as_secs_f64();
+                #[cfg(feature = "metrics")]
+                crate::signaling::metrics::metrics().set_frame_rate(fps);
                 if !active_sessions.is_empty() {
                     debug!("实时 FPS: {:.1}", fps);
                 }
+                if let Some(load) = cpu_sampler.sample() {
+                    if let Some(next_fps) = fps_governor.observe_cpu(load) {
+                        info!("CPU 占用 {:.0}%，帧率调整为 {} fps", load * 100.0, next_fps);
+                        frame_interval = fps_governor.frame_interval();
+                    }
+                }
+                #[cfg(feature = "metrics")]
+                crate::signaling::metrics::metrics().set_target_frame_rate(fps_governor.fps());
                 fps_frame_count = 0;
                 last_fps_time = std::time::Instant::now();
+                measured_fps = fps;
+            }
+
+            // /video 观看者没有 RTCP 统计，质量报告只有码率、帧率和编码信息 (等级由查看器按实际显示的帧率评定)
+            if stream_viewers > 0 && last_stream_report.elapsed() >= ABR_UPDATE_INTERVAL {
+                let kbps = (stream_bytes as f64 * 8.0 / 1000.0 / last_stream_report.elapsed().as_secs_f64()) as u32;
+                #[cfg(all(feature = "h264", feature = "webrtc"))]
+                let encoder_name = h264_encoder
+                    .as_ref()
+                    .map(|enc| encoder::hardware::HardwareEncoder::encoder_type(enc).to_string())
+                    .unwrap_or_default();
+                #[cfg(not(all(feature = "h264", feature = "webrtc")))]
+                let encoder_name = String::new();
+                let target_bitrate = capped(
+                    adaptive_controller
+                        .as_ref()
+                        .map_or(bitrate, quality::adaptive_bitrate::RuleBasedAbreController::current_bitrate),
+                    bandwidth_cap,
+                );
+                let report = quality::report::QualityReport::new(
+                    kbps,
+                    target_bitrate,
+                    measured_fps,
+                    None,
+                    0.0,
+                    "H.264",
+                    &encoder_name,
+                );
+                video_stream.notify(&report.to_json());
+                stream_bytes = 0;
+                last_stream_report = std::time::Instant::now();
             }
 
             // 控制帧率
             let elapsed = start.elapsed();
             if elapsed < frame_interval {
-                tokio::time::sleep(frame_interval - elapsed).await;
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tokio::time::sleep(frame_interval - elapsed) => {}
+                }
+            }
+        }
+
+        // 退出前结束录制分段 (写入文件尾，否则文件无法播放)
+        if let Some(rec) = recorder.as_mut() {
+            if let Err(e) = rec.finish() {
+                warn!("结束录制分段失败: {}", e);
+            }
+        }
+    }
+}
+
+/// 帧已发给所有会话: 记录延迟和指标，到探测时刻时向各会话发送 Ping
+#[cfg(feature = "webrtc")]
+async fn finish_frame(
+    timing: quality::latency::FrameTiming,
+    bytes: usize,
+    sessions: &[Arc<webrtc::host_session::HostSession>],
+) {
+    quality::bandwidth::monitor().record(bytes * sessions.len());
+    #[cfg(feature = "metrics")]
+    crate::signaling::metrics::metrics().record_frame(bytes);
+    if let Some(probe) = quality::latency::tracker().frame_sent(timing) {
+        for session in sessions {
+            session.send_latency_probe(&probe).await;
+        }
+    }
+}
+
+/// 静态画面保活: 重发缓存的关键帧代替编码，返回是否已重发
+///
+/// `key` 为当前码流 (simulcast 的较低档位各自编码，不使用缓存时为 None)
+#[cfg(all(feature = "h264", feature = "webrtc"))]
+async fn resend_cached_key_frame(
+    cache: &mut quality::keyframe_cache::KeyFrameCache,
+    key: Option<StreamKey>,
+    sessions: &[Arc<webrtc::host_session::HostSession>],
+    video_stream: &crate::signaling::video_stream::VideoStream,
+    duration: Duration,
+) -> bool {
+    let Some(key) = key else {
+        return false;
+    };
+    let Some(data) = cache.lookup(&key) else {
+        return false;
+    };
+
+    // 作为新样本写入轨道，RTP 时间戳按帧间隔前进
+    broadcast_video(sessions, VideoSample {
+        data,
+        is_key_frame: true,
+        temporal_layer: 0,
+        tier: 0,
+        duration,
+    })
+    .await;
+    video_stream.publish(data, true, key.width, key.height);
+    quality::bandwidth::monitor().record(data.len() * sessions.len());
+    #[cfg(feature = "metrics")]
+    crate::signaling::metrics::metrics().record_frame(data.len());
+    true
+}
+
+/// 上报运行中的编码器切换: 记录指标并通知所有观看者 (`stats` 通道和 `/video` 通知)
+#[cfg(all(feature = "h264", feature = "webrtc"))]
+async fn report_encoder_switch(
+    switch: &crate::encoder::hardware::EncoderSwitch,
+    sessions: &[Arc<webrtc::host_session::HostSession>],
+    video_stream: &crate::signaling::video_stream::VideoStream,
+) {
+    let event = quality::report::EncoderSwitchEvent::new(&switch.from.to_string(), &switch.to.to_string(), &switch.reason);
+    warn!("编码器已切换: {}", event);
+    #[cfg(feature = "metrics")]
+    crate::signaling::metrics::metrics().record_encoder_switch();
+    for session in sessions {
+        session.send_encoder_switch(&event).await;
+    }
+    video_stream.notify(&event.to_json());
+}
+
+/// 调整当前编码器的目标码率 (kbps)
+#[cfg(all(feature = "h264", feature = "webrtc"))]
+fn set_encoder_bitrate(
+    h264_encoder: Option<&mut crate::encoder::hardware::HardwareEncoderWrapper>,
+    vp8_encoder: Option<&mut crate::encoder::VP8Encoder>,
+    vp9_encoder: Option<&mut crate::encoder::VP9Encoder>,
+    target: u32,
+) {
+    if let Some(enc) = h264_encoder {
+        if let Err(e) = crate::encoder::hardware::HardwareEncoder::set_bitrate(enc, target) {
+            warn!("调整 H.264 编码器码率失败: {}", e);
+        }
+    }
+    if let Some(enc) = vp8_encoder {
+        if let Err(e) = enc.set_bitrate(target) {
+            warn!("调整 VP8 编码器码率失败: {}", e);
+        }
+    }
+    if let Some(enc) = vp9_encoder {
+        if let Err(e) = enc.set_bitrate(target) {
+            warn!("调整 VP9 编码器码率失败: {}", e);
+        }
+    }
+}
+
+/// 命令行 `--encoder` 对应的硬件编码器类型 (未指定时自动选择)
+#[cfg(all(feature = "h264", feature = "webrtc"))]
+fn hardware_encoder_type(selected: Option<&str>) -> crate::encoder::hardware::HardwareEncoderType {
+    use crate::encoder::hardware::HardwareEncoderType;
+    match selected {
+        Some("nvenc") => HardwareEncoderType::NVENC,
+        Some("amf") => HardwareEncoderType::AMF,
+        Some("qsv") => HardwareEncoderType::QuickSync,
+        Some("videotoolbox") => HardwareEncoderType::VideoToolbox,
+        _ => HardwareEncoderType::Auto,
+    }
+}
+
+/// simulcast 较低档位的编码器 (与最高档使用相同的 codec)
+#[cfg(all(feature = "h264", feature = "webrtc"))]
+enum TierEncoder {
+    Vp8(crate::encoder::VP8Encoder),
+    Vp9(crate::encoder::vp9::VP9Encoder),
+    H264(crate::encoder::hardware::HardwareEncoderWrapper),
+}
+
+#[cfg(all(feature = "h264", feature = "webrtc"))]
+impl TierEncoder {
+    fn create(
+        codec: webrtc::host_session::VideoCodec,
+        width: u32,
+        height: u32,
+        fps: u32,
+        bitrate: u32,
+        selected_encoder: Option<&str>,
+    ) -> Result<Self> {
+        use crate::encoder::hardware::{EncoderPreset, HardwareEncoderConfig, HardwareEncoderWrapper};
+
+        match codec {
+            webrtc::host_session::VideoCodec::VP8 => {
+                Ok(Self::Vp8(crate::encoder::VP8Encoder::new(width, height, fps, bitrate)?))
+            }
+            webrtc::host_session::VideoCodec::VP9 => {
+                Ok(Self::Vp9(crate::encoder::vp9::VP9Encoder::new(width, height, fps, bitrate, 1)?))
+            }
+            webrtc::host_session::VideoCodec::H264 => {
+                let config = HardwareEncoderConfig {
+                    encoder_type: hardware_encoder_type(selected_encoder),
+                    bitrate,
+                    fps,
+                    preset: EncoderPreset::LowLatency,
+                };
+                Ok(Self::H264(HardwareEncoderWrapper::create_with_fallback(
+                    config.encoder_type,
+                    width,
+                    height,
+                    config,
+                )?))
+            }
+        }
+    }
+
+    fn encode(&mut self, frame: &capture::Frame) -> Result<Option<Vec<u8>>> {
+        match self {
+            Self::Vp8(encoder) => encoder.encode_frame(frame),
+            Self::Vp9(encoder) => Ok(encoder.encode_frame(frame)?.map(|frame| frame.data)),
+            Self::H264(encoder) => Ok(crate::encoder::hardware::HardwareEncoder::encode(encoder, frame)?
+                .map(|packet| packet.data)),
+        }
+    }
+
+    fn request_key_frame(&mut self) {
+        match self {
+            Self::Vp8(encoder) => encoder.request_key_frame(),
+            Self::Vp9(encoder) => encoder.request_key_frame(),
+            Self::H264(encoder) => {
+                let _ = crate::encoder::hardware::HardwareEncoder::request_key_frame(encoder);
+            }
+        }
+    }
+}
+
+/// 持有 mDNS 服务和广播器: 按抖动的间隔发送信标，显示器数量变化时更新公布的信息
+#[cfg(feature = "discovery")]
+fn spawn_discovery(
+    mut mdns: Option<crate::discovery::MdnsService>,
+    mut broadcast: Option<crate::discovery::BroadcastAnnouncer>,
+) -> tokio::task::JoinHandle<()> {
+    /// 检查显示器数量的间隔
+    const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+    tokio::spawn(async move {
+        let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+        refresh.tick().await;
+        let mut next_beacon = tokio::time::Instant::now();
+        loop {
+            tokio::select! {
+                _ = refresh.tick() => {
+                    let screens = screen_count();
+                    if let Some(service) = mdns.as_mut() {
+                        let metadata = crate::discovery::DeviceMetadata { screens, ..service.metadata().clone() };
+                        if let Err(e) = service.update(metadata) {
+                            warn!("更新 mDNS 广播失败: {}", e);
+                        }
+                    }
+                    if let Some(announcer) = broadcast.as_mut() {
+                        let metadata = crate::discovery::DeviceMetadata { screens, ..announcer.metadata().clone() };
+                        announcer.set_metadata(metadata);
+                    }
+                }
+                _ = tokio::time::sleep_until(next_beacon), if broadcast.is_some() => {
+                    if let Some(announcer) = broadcast.as_mut() {
+                        if let Err(e) = announcer.announce() {
+                            debug!("发送广播信标失败: {}", e);
+                        }
+                        next_beacon = tokio::time::Instant::now() + announcer.next_delay();
+                    }
+                }
             }
         }
     })
 }
 
+/// 广播信标的签名密钥: 启用 pairing 时使用设备身份 (控制端可核对设备指纹)，否则使用临时密钥
+#[cfg(feature = "discovery")]
+fn beacon_signer() -> Arc<dyn crate::discovery::BeaconSigner> {
+    #[cfg(feature = "pairing")]
+    match crate::pairing::load_identity() {
+        Ok(identity) => return Arc::new(identity),
+        Err(e) => warn!("加载设备身份失败，广播信标使用临时密钥: {}", e),
+    }
+    crate::discovery::broadcast::ephemeral_signer()
+}
+
+/// 当前连接的显示器数量 (无法枚举时为 None)
+#[cfg(feature = "discovery")]
+fn screen_count() -> Option<u32> {
+    let count = crate::capture::display_watch::DisplayLayout::current().displays.len();
+    (count > 0).then_some(count as u32)
+}
+
+/// 接受 QUIC 连接，每个连接在独立任务中处理
+#[cfg(feature = "quic")]
+#[allow(clippy::too_many_arguments)]
+fn spawn_quic_server(
+    server: Arc<crate::network::quic::QuicServer>,
+    signaling: Arc<EmbeddedSignalingServer>,
+    approver: Arc<ConnectionApprover>,
+    permissions: SessionPermissions,
+    input_simulator: SharedInputSimulator,
+    max_input_rate: u32,
+    input_control: InputControl,
+    privacy: Arc<PrivacyMode>,
+    capture_config: config::CaptureConfig,
+    bitrate_arg: Option<u32>,
+    #[cfg(feature = "pairing")] device_pairing: Option<Arc<crate::pairing::PairingState>>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(incoming) = server.accept().await {
+            let remote = incoming.remote_address();
+            let signaling = signaling.clone();
+            let approver = approver.clone();
+            let input_simulator = input_simulator.clone();
+            let input_control = input_control.clone();
+            let privacy = privacy.clone();
+            let capture_config = capture_config.clone();
+            #[cfg(feature = "pairing")]
+            let device_pairing = device_pairing.clone();
+            // 与 serve_quic_viewer 中的 peer_id 一致
+            let peer_id = format!("quic-{}", remote);
+            let span = crate::logging::peer_span(&peer_id);
+            tokio::spawn(async move {
+                signaling.audit(AuditEvent::Connect {
+                    peer: peer_id.clone(),
+                    addr: remote.ip().to_string(),
+                    transport: "quic".to_string(),
+                });
+                let result = serve_quic_viewer(
+                    incoming,
+                    &signaling,
+                    &approver,
+                    permissions,
+                    input_simulator,
+                    max_input_rate,
+                    input_control.clone(),
+                    &privacy,
+                    &capture_config,
+                    bitrate_arg,
+                    #[cfg(feature = "pairing")]
+                    device_pairing.as_deref(),
+                )
+                .await;
+                input_control.leave(&peer_id);
+                signaling.audit(AuditEvent::Disconnect { peer: peer_id.clone() });
+                if let Err(e) = result {
+                    warn!("QUIC 连接 {} 结束: {}", remote, e);
+                }
+            }.instrument(span));
+        }
+    })
+}
+
+/// 处理一个 QUIC 控制端: 校验 PIN (已配对设备校验设备签名)、审批，然后单独捕获、编码并发送画面
+///
+/// 编码器目标码率跟随 QUIC 拥塞控制估算的可用带宽，发送积压时丢帧并请求关键帧
+#[cfg(feature = "quic")]
+#[allow(clippy::too_many_arguments)]
+async fn serve_quic_viewer(
+    incoming: crate::network::quic::QuicIncoming,
+    signaling: &EmbeddedSignalingServer,
+    approver: &ConnectionApprover,
+    permissions: SessionPermissions,
+    input_simulator: SharedInputSimulator,
+    max_input_rate: u32,
+    input_control: InputControl,
+    privacy: &PrivacyMode,
+    capture_config: &config::CaptureConfig,
+    bitrate_arg: Option<u32>,
+    #[cfg(feature = "pairing")] device_pairing: Option<&crate::pairing::PairingState>,
+) -> Result<()> {
+    use crate::network::quic::StreamInfo;
+    use crate::signaling::pin::PinVerdict;
+
+    let handshake = incoming.handshake().await?;
+    let peer_id = format!("quic-{}", handshake.remote_address());
+
+    // 控制端出示的设备身份 (签名无效时拒绝)，已配对的设备免 PIN
+    #[cfg(feature = "pairing")]
+    let device = match (device_pairing, handshake.device()) {
+        (Some(_), Some(proof)) => {
+            let binding = handshake.channel_binding(crate::network::quic::DeviceRole::Viewer)?;
+            match crate::pairing::identity::verify_proof(proof, &binding) {
+                Ok(key) => Some((proof.name.clone(), key)),
+                Err(e) => {
+                    warn!("QUIC 控制端 {} 设备认证失败: {}", peer_id, e);
+                    handshake.reject("设备签名无效");
+                    return Ok(());
+                }
+            }
+        }
+        _ => None,
+    };
+    #[cfg(feature = "pairing")]
+    let paired = match (device_pairing, &device) {
+        (Some(pairing), Some((_, key))) => pairing.authenticate(key),
+        _ => None,
+    };
+    #[cfg(not(feature = "pairing"))]
+    let paired: Option<String> = None;
+
+    if let Some(ref name) = paired {
+        info!("QUIC 控制端 {} 是已配对设备 {}", peer_id, name);
+        signaling.audit(AuditEvent::Auth {
+            peer: peer_id.clone(),
+            method: crate::audit::AuthMethod::Device,
+            success: true,
+        });
+    } else {
+        match signaling.verify_pin(&peer_id, handshake.pin()).await {
+            PinVerdict::Accepted => {}
+            PinVerdict::Rejected { remaining } => {
+                warn!("QUIC 控制端 {} PIN 错误 (剩余 {} 次)", peer_id, remaining);
+                handshake.reject(&format!("PIN 错误 (剩余 {} 次)", remaining));
+                return Ok(());
+            }
+            PinVerdict::Locked { retry_after } => {
+                warn!("PIN 验证已锁定，拒绝 QUIC 控制端 {}", peer_id);
+                handshake.reject(&format!("PIN 验证已锁定，{} 秒后可重试", retry_after.as_secs().max(1)));
+                return Ok(());
+            }
+        }
+    }
+    #[cfg(feature = "security")]
+    match signaling.verify_totp(&peer_id, handshake.totp()).await {
+        PinVerdict::Accepted => {}
+        PinVerdict::Rejected { remaining } => {
+            warn!("QUIC 控制端 {} TOTP 验证码错误 (剩余 {} 次)", peer_id, remaining);
+            handshake.reject(&format!("TOTP 验证码错误 (剩余 {} 次)，请用 --totp 提供", remaining));
+            return Ok(());
+        }
+        PinVerdict::Locked { retry_after } => {
+            warn!("TOTP 验证已锁定，拒绝 QUIC 控制端 {}", peer_id);
+            handshake.reject(&format!("TOTP 验证已锁定，{} 秒后可重试", retry_after.as_secs().max(1)));
+            return Ok(());
+        }
+    }
+
+    let permissions = match approver.request(&peer_id, permissions).await {
+        ApprovalDecision::Accept(permissions) => {
+            signaling.audit(AuditEvent::Approval {
+                peer: peer_id.clone(),
+                accepted: true,
+                permissions: Some(permissions),
+            });
+            permissions
+        }
+        ApprovalDecision::Reject => {
+            signaling.audit(AuditEvent::Approval { peer: peer_id.clone(), accepted: false, permissions: None });
+            info!("已拒绝连接: {}", peer_id);
+            println!("  [x] 已拒绝 {}", peer_id);
+            handshake.reject("被控端拒绝了连接请求");
+            return Ok(());
+        }
+    };
+
+    let mut capturer = open_capturer(capture_config.screen_index, capture_config.show_cursor)?;
+    let aligner = crate::encoder::alignment::FrameAligner::even(capturer.width(), capturer.height())?;
+    // 画质预设在连接建立时生效
+    let encoding = config::watch::LiveSettings::from_capture(capture_config).resolve(bitrate_arg);
+    let fps = encoding.fps.max(1);
+    let mut encoder = crate::encoder::create_encoder(aligner.width(), aligner.height(), fps)?;
+    let mut bitrate = encoding.bitrate;
+    encoder.set_bitrate(bitrate)?;
+
+    // PIN 验证和审批都通过后信任请求配对的设备
+    #[cfg(feature = "pairing")]
+    if let (Some(pairing), Some((name, key)), None, true) =
+        (device_pairing, &device, &paired, handshake.pair_requested())
+    {
+        match pairing.pair_viewer(name, key) {
+            Ok(()) => {
+                let fingerprint = crate::pairing::identity::fingerprint(key);
+                info!("已配对控制端设备 {} ({})", name, fingerprint);
+                println!("  [+] 已配对设备: {} ({})", name, fingerprint);
+            }
+            Err(e) => warn!("保存配对设备失败: {}", e),
+        }
+    }
+    // 被控端设备身份，控制端据此校验已配对的被控端
+    #[cfg(feature = "pairing")]
+    let device = match device_pairing {
+        Some(pairing) => {
+            use crate::network::quic::DeviceSigner;
+            Some(pairing.identity.prove(&handshake.channel_binding(crate::network::quic::DeviceRole::Host)?))
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "pairing"))]
+    let device = None;
+
+    let session = handshake
+        .accept(StreamInfo {
+            width: aligner.width(),
+            height: aligner.height(),
+            codec: if cfg!(feature = "h264") { "h264" } else { "raw" }.to_string(),
+            input: permissions.input,
+            device,
+        })
+        .await?;
+    info!("QUIC 会话已建立: {} ({})", peer_id, permissions);
+    println!("  [+] QUIC 控制端连接: {} ({})", peer_id, permissions);
+    // QUIC 控制端没有控制权通道，只按配置自动开启隐私模式
+    privacy.session_started();
+
+    // 注入输入 (无输入权限时会话层已丢弃)；QUIC 控制端没有控制权通道，无人控制时发送输入即获得控制权
+    input_control.join(&peer_id, &peer_id);
+    // 指针位置是编码画面的归一化坐标，直接用于 ROI
+    let pointer = PointerPosition::new();
+    if let Some(mut input_events) = session.take_input_events() {
+        let aligner = aligner.clone();
+        let peer_id = peer_id.clone();
+        let pointer = pointer.clone();
+        tokio::spawn(async move {
+            let mut touch = input::TouchTranslator::default();
+            let mut coalescer = input::InputCoalescer::new(max_input_rate);
+            // 指针位置不受对齐换算影响，注入时再换算到屏幕坐标
+            let inject = |events: Vec<input::InputEvent>| {
+                for event in &events {
+                    if let input::InputEvent::MouseMove { x, y } = event {
+                        pointer.set(*x, *y);
+                    }
+                }
+                let events = events.into_iter().map(|event| aligner.map_input_event(event)).collect();
+                inject_input(&input_simulator, None, events);
+            };
+            loop {
+                tokio::select! {
+                    event = input_events.recv() => {
+                        let Some(event) = event else { break };
+                        if !input_control.allow_input(&peer_id) {
+                            coalescer.drop_event();
+                            continue;
+                        }
+                        let now = std::time::Instant::now();
+                        inject(touch.translate(event).into_iter().flat_map(|event| coalescer.push(event, now)).collect());
+                    }
+                    _ = coalescer.due() => inject(coalescer.poll(std::time::Instant::now())),
+                }
+            }
+            inject(coalescer.flush());
+            inject_input(&input_simulator, None, touch.reset());
+        }.in_current_span());
+    }
+
+    let mut frame_timer = tokio::time::interval(Duration::from_secs(1) / fps);
+    frame_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut abr_timer = tokio::time::interval(ABR_UPDATE_INTERVAL);
+    let mut dropped: u64 = 0;
+    let mut scene_stage = SceneChangeStage::default();
+    loop {
+        tokio::select! {
+            _ = session.closed() => break,
+            _ = abr_timer.tick() => {
+                let target = session.bitrate_hint().clamp(QUIC_MIN_BITRATE, QUIC_MAX_BITRATE);
+                // 变化超过 10% 才调整，避免频繁重配编码器
+                if target.abs_diff(bitrate) * 10 > bitrate {
+                    debug!(
+                        "QUIC 码率调整: {} -> {} kbps (RTT {:?}, 丢帧 {})",
+                        bitrate, target, session.rtt(), dropped
+                    );
+                    match encoder.set_bitrate(target) {
+                        Ok(()) => bitrate = target,
+                        Err(e) => warn!("设置码率失败: {}", e),
+                    }
+                }
+            }
+            _ = frame_timer.tick() => {
+                if session.take_key_frame_request() {
+                    let _ = encoder.request_key_frame();
+                }
+                let frame = match capturer.capture() {
+                    Ok(frame) => aligner.align(frame),
+                    Err(e) if capture::DisplayChanged::is(&e) => {
+                        // 流尺寸在握手时已确定: 重建捕获器后缩放到原尺寸
+                        info!("{}，重建 QUIC 会话的屏幕捕获器", e);
+                        let _ = capturer.stop();
+                        match reopen_capturer(capture_config.screen_index, capture_config.show_cursor) {
+                            Ok(cap) => capturer = cap,
+                            Err(e) => warn!("重建屏幕捕获器失败: {}", e),
+                        }
+                        continue;
+                    }
+                    Err(e) => {
+                        debug!("捕获失败: {}", e);
+                        continue;
+                    }
+                };
+                let frame = if (frame.width, frame.height) != (aligner.width(), aligner.height()) {
+                    quality::dynamic_resolution::scale_frame(&frame, aligner.width(), aligner.height())
+                } else {
+                    frame
+                };
+                // 场景切换时立即插入关键帧
+                let frame = match scene_stage.process(frame) {
+                    Ok(StageOutput::KeyFrame(frame)) | Ok(StageOutput::Keepalive(frame)) => {
+                        let _ = encoder.request_key_frame();
+                        frame
+                    }
+                    Ok(StageOutput::Frame(frame)) => frame,
+                    Ok(StageOutput::Drop) | Err(_) => continue,
+                };
+                if capture_config.roi {
+                    encoder.set_roi(&pointer.regions(frame.width, frame.height));
+                }
+                match encoder.encode(&frame) {
+                    Ok(Some(packet)) => {
+                        #[cfg(feature = "metrics")]
+                        let bytes = packet.data.len();
+                        if session.send_frame(packet) {
+                            #[cfg(feature = "metrics")]
+                            crate::signaling::metrics::metrics().record_frame(bytes);
+                        } else {
+                            dropped += 1;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("QUIC 视频编码失败: {}", e),
+                }
+            }
+        }
+    }
+
+    let _ = capturer.stop();
+    privacy.session_ended();
+    info!("QUIC 会话结束: {} (丢帧 {})", peer_id, dropped);
+    println!("  [-] QUIC 控制端断开: {}", peer_id);
+    Ok(())
+}
+
 /// Print local-only connection information
-fn print_local_only_info(local_ip: &str, port: u16) {
+/// 启动公网隧道并等待公网地址
+///
+/// 隧道无法创建时返回 None (仅使用局域网)；等待超时后隧道仍在后台运行，地址可用时记录在日志中
+#[cfg(feature = "tunnel")]
+async fn start_tunnel(
+    config: &config::Config,
+    config_path: &str,
+    name: Option<&str>,
+    port: u16,
+) -> Option<crate::tunnel::TunnelHandle> {
+    use crate::tunnel::TunnelSupervisor;
+
+    let provider = match crate::tunnel::provider(&config.tunnel, name) {
+        Ok(provider) => provider,
+        Err(e) => {
+            error!("创建公网隧道失败: {}", e);
+            warn!("将仅使用局域网模式");
+            return None;
+        }
+    };
+    info!("正在启动公网隧道 ({})...", provider.name());
+    let mut handle = TunnelSupervisor::new(provider, port)
+        .with_health_check(Duration::from_secs(config.tunnel.health_check_secs))
+        .with_config_path(config_path)
+        .spawn();
+    if handle.wait_for_url(TUNNEL_STARTUP_TIMEOUT).await.is_none() {
+        warn!("隧道 {} 未报告公网地址，先使用局域网模式", handle.provider());
+    }
+    Some(handle)
+}
+
+fn print_local_only_info(local_ip: &str, port: u16, pin: Option<&str>, permissions: SessionPermissions) {
     println!();
     println!("========================================");
     println!("  sscontrol 被控端已启动");
@@ -710,82 +3558,107 @@ fn print_local_only_info(local_ip: &str, port: u16) {
     println!();
     println!("  本机 IP: {}", local_ip);
     println!("  端口:    {}", port);
+    println!("  权限:    {}", permissions);
     println!();
     println!("控制端连接命令:");
     println!("  sscontrol connect --ip {} --port {}", local_ip, port);
     println!();
+    print_pin(pin);
     println!("等待连接中... (按 Ctrl+C 退出)");
     println!();
 }
 
-/// Get local IP address
-fn get_local_ip() -> Option<String> {
-    use std::net::{IpAddr, UdpSocket};
-
-    // Try to get local IP by connecting to a public address
-    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
-    socket.connect("8.8.8.8:80").ok()?;
-    let local_addr = socket.local_addr().ok()?;
-    let ip = local_addr.ip();
+/// 回复 Viewer 的打洞信息，需要时向其打洞
+///
+/// 未启用打洞或 STUN 探测失败时回复 None，Viewer 收到后直接走常规 ICE
+#[cfg(feature = "webrtc")]
+async fn answer_punch(
+    signaling: Arc<EmbeddedSignalingServer>,
+    peer_id: String,
+    remote: Option<crate::nat::predictive_punching::PunchCandidate>,
+    config: Option<crate::nat::NatConfig>,
+) -> Option<crate::nat::predictive_punching::PunchedPath> {
+    use crate::nat::predictive_punching::HolePuncher;
+
+    let puncher = match config {
+        Some(config) => match HolePuncher::prepare(config).await {
+            Ok(puncher) => Some(puncher),
+            Err(e) => {
+                warn!("打洞准备失败: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    signaling
+        .send_punch(&peer_id, puncher.as_ref().map(|p| p.candidate().clone()))
+        .await;
 
-    // Check if it's a valid LAN IP (not WARP/VPN)
-    if is_valid_lan_ip(&ip) {
-        return Some(ip.to_string());
+    let (puncher, remote) = (puncher?, remote?);
+    if !puncher.needs_punch(&remote) {
+        return None;
     }
-
-    // Fallback: try to find a valid LAN IP from all interfaces
-    #[cfg(unix)]
-    {
-        if let Ok(output) = std::process::Command::new("ifconfig").output() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            for line in output_str.lines() {
-                if line.contains("inet ") && !line.contains("127.0.0.1") {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if let Some(idx) = parts.iter().position(|&x| x == "inet") {
-                        if let Some(ip_str) = parts.get(idx + 1) {
-                            if let Ok(parsed_ip) = ip_str.parse::<IpAddr>() {
-                                if is_valid_lan_ip(&parsed_ip) {
-                                    return Some(parsed_ip.to_string());
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    match puncher.punch(&remote).await {
+        Ok(path) => {
+            info!("与 {} 打洞成功: {}", peer_id, path.remote);
+            Some(path)
+        }
+        Err(e) => {
+            info!("与 {} 打洞失败: {}，使用常规 ICE", peer_id, e);
+            None
         }
     }
-
-    // Return the original IP if no better option found
-    Some(ip.to_string())
 }
 
-/// Check if IP is a valid LAN IP (not WARP, VPN, or other virtual interfaces)
-fn is_valid_lan_ip(ip: &std::net::IpAddr) -> bool {
-    if let std::net::IpAddr::V4(ipv4) = ip {
-        let octets = ipv4.octets();
+/// 单次最多映射的 WebRTC UDP 端口数 (避免占满路由器的映射表)
+const MAX_MAPPED_UDP_PORTS: u16 = 32;
 
-        // Exclude Cloudflare WARP IPs (198.18.0.0/15)
-        if octets[0] == 198 && (octets[1] == 18 || octets[1] == 19) {
-            return false;
-        }
+/// 通过 UPnP / NAT-PMP / PCP 映射端口，失败时只记录日志
+async fn start_port_mapping(
+    signaling_port: u16,
+    udp_port_range: Option<(u16, u16)>,
+) -> Option<crate::nat::port_mapping::PortMapper> {
+    use crate::nat::port_mapping::{PortMapper, Protocol};
 
-        // Exclude CGNAT range (100.64.0.0/10) - used by some VPNs
-        if octets[0] == 100 && octets[1] >= 64 && octets[1] <= 127 {
-            return false;
-        }
+    let mut ports = vec![(Protocol::Tcp, signaling_port)];
+    if let Some((min, max)) = udp_port_range {
+        let max = max.min(min.saturating_add(MAX_MAPPED_UDP_PORTS - 1));
+        ports.extend((min..=max).map(|port| (Protocol::Udp, port)));
+    }
 
-        // Exclude loopback
-        if octets[0] == 127 {
-            return false;
+    info!("正在请求路由器端口映射...");
+    match PortMapper::start(&ports).await {
+        Ok(mapper) => Some(mapper),
+        Err(e) => {
+            info!("端口映射不可用: {}", e);
+            None
         }
+    }
+}
 
-        // Prefer private IP ranges (192.168.x.x, 10.x.x.x, 172.16-31.x.x)
-        let is_private =
-            (octets[0] == 192 && octets[1] == 168) ||
-            (octets[0] == 10) ||
-            (octets[0] == 172 && octets[1] >= 16 && octets[1] <= 31);
+/// 打印端口映射后的公网连接方式
+async fn print_port_mapping(mapper: &crate::nat::port_mapping::PortMapper, signaling_port: u16, pin_required: bool) {
+    use crate::nat::port_mapping::Protocol;
+
+    let Some(external_ip) = mapper.external_ip() else {
+        return;
+    };
+    let Some(external_port) = mapper.external_port(Protocol::Tcp, signaling_port).await else {
+        return;
+    };
+    println!("公网连接 ({} 端口映射):", mapper.method());
+    println!("  sscontrol connect --ip {} --port {}", external_ip, external_port);
+    println!();
+    if !pin_required {
+        warn!("信令端口已映射到公网且未启用 PIN 验证，建议开启 host.require_pin");
+    }
+}
 
-        return is_private;
+/// Print the one-time connection PIN
+fn print_pin(pin: Option<&str>) {
+    if let Some(pin) = pin {
+        println!("连接 PIN (一次性，使用后自动更换):");
+        println!("  {}", pin);
+        println!();
     }
-    false
 }
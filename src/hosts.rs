@@ -0,0 +1,239 @@
+//! 地址簿
+//!
+//! 保存常用被控端的别名、最近可用的 IP、隧道 URL、设备指纹和默认连接选项，
+//! 之后用 `sscontrol connect office-pc` 代替记 IP。
+//!
+//! 地址簿是配置文件旁的 `hosts.toml`，每台被控端一个 `[[host]]` 条目。
+//! 保存的 IP 失效时控制端通过 mDNS (需要 discovery feature) 按设备 ID 或设备指纹找到新地址并写回
+
+#![allow(dead_code)]
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// 被控端默认端口
+pub const DEFAULT_PORT: u16 = 9527;
+
+/// 每个条目最多保留的历史 IP
+const MAX_ADDRESSES: usize = 4;
+
+/// 地址簿条目
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostEntry {
+    /// 别名 (connect 时使用)
+    pub alias: String,
+    /// 最近可用的 IP 或主机名 (最近一次成功的在前)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<String>,
+    /// 被控端端口 (默认 9527)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// 公网隧道 URL (局域网地址都不可达时使用)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// 被控端设备指纹 (`sscontrol pair show` 显示，用于 mDNS 识别)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// 被控端设备 ID (配置中的 server.device_id，用于 mDNS 识别)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    /// 默认传输方式 ("webrtc" 或 "quic")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transport: Option<String>,
+    /// 最近一次连接的时间 (Unix 秒)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_connected: Option<u64>,
+}
+
+impl HostEntry {
+    pub fn new(alias: &str) -> Self {
+        Self { alias: alias.to_string(), ..Default::default() }
+    }
+
+    /// 记录可用的地址 (移到最前，超过上限时丢弃最旧的)
+    pub fn remember_address(&mut self, address: &str) {
+        self.addresses.retain(|known| known != address);
+        self.addresses.insert(0, address.to_string());
+        self.addresses.truncate(MAX_ADDRESSES);
+    }
+
+    /// 连接使用的端口
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(DEFAULT_PORT)
+    }
+
+    /// 记录 mDNS 发现的地址，补全未保存的设备 ID 和指纹
+    pub fn learn(&mut self, address: &str, port: u16, device_id: &str, fingerprint: Option<&str>) {
+        self.remember_address(address);
+        if port != self.port() {
+            self.port = Some(port);
+        }
+        self.device_id.get_or_insert_with(|| device_id.to_string());
+        if self.fingerprint.is_none() {
+            self.fingerprint = fingerprint.map(str::to_string);
+        }
+    }
+
+    /// mDNS 发现的设备是否是该条目
+    ///
+    /// 优先按设备 ID 和设备指纹识别；两者都没有保存时按主机名与别名比较
+    pub fn matches(&self, device_id: &str, fingerprint: Option<&str>, hostname: &str) -> bool {
+        if self.device_id.is_some() || self.fingerprint.is_some() {
+            return self.device_id.as_deref() == Some(device_id)
+                || fingerprint.zip(self.fingerprint.as_deref()).is_some_and(|(found, saved)| {
+                    normalize_fingerprint(found) == normalize_fingerprint(saved)
+                });
+        }
+        let hostname = hostname.trim_end_matches('.').trim_end_matches(".local");
+        hostname.eq_ignore_ascii_case(&self.alias)
+    }
+}
+
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint.chars().filter(|c| *c != '-' && *c != ':').collect::<String>().to_ascii_uppercase()
+}
+
+/// `hosts.toml` 的文件格式
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HostsFile {
+    #[serde(default, rename = "host")]
+    hosts: Vec<HostEntry>,
+}
+
+/// 地址簿
+#[derive(Debug, Default)]
+pub struct AddressBook {
+    path: PathBuf,
+    hosts: Vec<HostEntry>,
+}
+
+impl AddressBook {
+    /// 配置文件旁的 `hosts.toml`
+    pub fn default_path() -> PathBuf {
+        let config_path = crate::config::Config::get_config_path(None);
+        Path::new(&config_path)
+            .parent()
+            .map(|parent| parent.join("hosts.toml"))
+            .unwrap_or_else(|| PathBuf::from("hosts.toml"))
+    }
+
+    /// 读取地址簿 (文件不存在时为空)
+    pub fn load(path: &Path) -> Result<Self> {
+        let hosts = if path.exists() {
+            let content =
+                std::fs::read_to_string(path).with_context(|| format!("读取地址簿 {} 失败", path.display()))?;
+            toml::from_str::<HostsFile>(&content)
+                .map_err(|e| anyhow!("地址簿 {} 格式错误: {}", path.display(), e))?
+                .hosts
+        } else {
+            Vec::new()
+        };
+        Ok(Self { path: path.to_path_buf(), hosts })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string_pretty(&HostsFile { hosts: self.hosts.clone() })?;
+        std::fs::write(&self.path, content).with_context(|| format!("保存地址簿 {} 失败", self.path.display()))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn hosts(&self) -> &[HostEntry] {
+        &self.hosts
+    }
+
+    /// 按别名查找 (不区分大小写)
+    pub fn get(&self, alias: &str) -> Option<&HostEntry> {
+        self.hosts.iter().find(|entry| entry.alias.eq_ignore_ascii_case(alias))
+    }
+
+    /// 添加条目 (别名已存在时出错，`replace` 为 true 时替换)
+    pub fn add(&mut self, entry: HostEntry, replace: bool) -> Result<()> {
+        validate_alias(&entry.alias)?;
+        if self.get(&entry.alias).is_some() {
+            if !replace {
+                bail!("地址簿中已有 {}，替换请加 --force", entry.alias);
+            }
+            self.remove(&entry.alias);
+        }
+        self.hosts.push(entry);
+        Ok(())
+    }
+
+    /// 更新已有条目 (别名不存在时忽略)
+    pub fn update(&mut self, entry: HostEntry) {
+        if let Some(existing) = self.hosts.iter_mut().find(|existing| existing.alias == entry.alias) {
+            *existing = entry;
+        }
+    }
+
+    /// 删除条目，返回是否存在
+    pub fn remove(&mut self, alias: &str) -> bool {
+        let before = self.hosts.len();
+        self.hosts.retain(|entry| !entry.alias.eq_ignore_ascii_case(alias));
+        self.hosts.len() != before
+    }
+}
+
+/// 别名不能为空，也不能像 IP 或 URL (避免与 --ip/--url 混淆)
+fn validate_alias(alias: &str) -> Result<()> {
+    if alias.is_empty() || alias.chars().any(|c| c.is_whitespace() || c == '/' || c == ':') {
+        bail!("无效的别名: {:?} (不能为空，不能包含空白、/ 或 :)", alias);
+    }
+    if alias.parse::<std::net::IpAddr>().is_ok() {
+        bail!("别名不能是 IP 地址: {}", alias);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remember_address() {
+        let mut entry = HostEntry::new("office-pc");
+        for address in ["10.0.0.1", "10.0.0.2", "10.0.0.3", "10.0.0.4", "10.0.0.2", "10.0.0.5"] {
+            entry.remember_address(address);
+        }
+        assert_eq!(entry.addresses, vec!["10.0.0.5", "10.0.0.2", "10.0.0.4", "10.0.0.3"]);
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut entry = HostEntry::new("office-pc");
+        assert!(entry.matches("abc", None, "OFFICE-PC.local."));
+
+        // 保存了设备指纹后不再按主机名识别
+        entry.fingerprint = Some("3f2a-91c0-7b44-e215".to_string());
+        assert!(!entry.matches("abc", None, "office-pc.local."));
+        assert!(entry.matches("abc", Some("3F2A91C07B44E215"), "other"));
+
+        entry.device_id = Some("abc".to_string());
+        assert!(entry.matches("abc", None, "other"));
+    }
+
+    #[test]
+    fn test_add_and_save() {
+        let path = std::env::temp_dir().join(format!("sscontrol-hosts-{}.toml", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut book = AddressBook::load(&path).unwrap();
+        let mut entry = HostEntry::new("office-pc");
+        entry.remember_address("192.168.1.5");
+        entry.transport = Some("quic".to_string());
+        book.add(entry.clone(), false).unwrap();
+        assert!(book.add(HostEntry::new("Office-PC"), false).is_err());
+        assert!(book.add(HostEntry::new("192.168.1.5"), false).is_err());
+        book.save().unwrap();
+
+        let loaded = AddressBook::load(&path).unwrap();
+        assert_eq!(loaded.get("OFFICE-PC"), Some(&entry));
+        let _ = std::fs::remove_file(&path);
+    }
+}
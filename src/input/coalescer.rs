@@ -0,0 +1,219 @@
+//! 输入事件合并与限速
+//!
+//! 控制端快速移动鼠标时每秒会发来上百个 MouseMove，逐个注入会占满数据通道之后的输入模拟器，
+//! 点击和按键也会排在大量移动之后。这里:
+//! - 连续的 MouseMove 只保留最新位置，连续的 MouseWheel 累加
+//! - 移动和滚动按 `max_rate` 限速注入，到期前的事件合并到待注入事件中
+//! - 点击、按键等其他事件立即注入 (先注入待注入的移动，保证点击位置正确)
+//!
+//! 合并和丢弃的事件数在启用 metrics feature 时计入 `/metrics`
+
+use super::InputEvent;
+use std::time::{Duration, Instant};
+
+/// 累计统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CoalesceStats {
+    /// 已注入的事件数
+    pub injected: u64,
+    /// 被后续事件合并的移动和滚动事件数
+    pub merged: u64,
+    /// 丢弃的事件数 (没有控制权时收到的事件)
+    pub dropped: u64,
+}
+
+/// 输入事件合并器
+#[derive(Debug)]
+pub struct InputCoalescer {
+    /// 两次注入移动/滚动的最小间隔 (ZERO = 不限速)
+    interval: Duration,
+    pending_move: Option<(f64, f64)>,
+    pending_wheel: Option<(i32, i32)>,
+    last_flush: Option<Instant>,
+    stats: CoalesceStats,
+}
+
+impl Default for InputCoalescer {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_RATE)
+    }
+}
+
+impl InputCoalescer {
+    /// 默认每秒最多注入的移动/滚动事件数
+    pub const DEFAULT_RATE: u32 = 250;
+
+    /// `max_rate`: 每秒最多注入的移动/滚动事件数 (0 = 不限速，只合并同一批到达的事件)
+    pub fn new(max_rate: u32) -> Self {
+        let interval = if max_rate == 0 { Duration::ZERO } else { Duration::from_secs(1) / max_rate };
+        Self {
+            interval,
+            pending_move: None,
+            pending_wheel: None,
+            last_flush: None,
+            stats: CoalesceStats::default(),
+        }
+    }
+
+    /// 加入一个事件，返回现在应注入的事件
+    pub fn push(&mut self, event: InputEvent, now: Instant) -> Vec<InputEvent> {
+        match event {
+            InputEvent::MouseMove { x, y } => {
+                if self.pending_move.replace((x, y)).is_some() {
+                    self.count_merged();
+                }
+                self.poll(now)
+            }
+            InputEvent::MouseWheel { delta_x, delta_y } => {
+                match self.pending_wheel.as_mut() {
+                    Some(wheel) => {
+                        wheel.0 = wheel.0.saturating_add(delta_x);
+                        wheel.1 = wheel.1.saturating_add(delta_y);
+                        self.count_merged();
+                    }
+                    None => self.pending_wheel = Some((delta_x, delta_y)),
+                }
+                self.poll(now)
+            }
+            event => {
+                let mut events = self.take_pending(now);
+                events.push(event);
+                self.count_injected(1);
+                events
+            }
+        }
+    }
+
+    /// 限速间隔已到时返回待注入的移动/滚动事件
+    pub fn poll(&mut self, now: Instant) -> Vec<InputEvent> {
+        let due = match self.last_flush {
+            Some(last) => now >= last + self.interval,
+            None => true,
+        };
+        if due {
+            self.take_pending(now)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// 下一次可以注入待注入事件的时间 (None = 没有待注入事件)
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.pending_move.is_none() && self.pending_wheel.is_none() {
+            return None;
+        }
+        Some(match self.last_flush {
+            Some(last) => last + self.interval,
+            None => Instant::now(),
+        })
+    }
+
+    /// 等到 [`deadline`](Self::deadline) (没有待注入事件时一直等待)，配合 `tokio::select!` 使用
+    pub async fn due(&self) {
+        match self.deadline() {
+            Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// 立即取出所有待注入事件 (输入来源关闭时)
+    pub fn flush(&mut self) -> Vec<InputEvent> {
+        self.take_pending(Instant::now())
+    }
+
+    /// 记录一个被丢弃的事件
+    pub fn drop_event(&mut self) {
+        self.stats.dropped += 1;
+        #[cfg(feature = "metrics")]
+        crate::signaling::metrics::metrics().record_input_dropped(1);
+    }
+
+    pub fn stats(&self) -> CoalesceStats {
+        self.stats
+    }
+
+    /// 先移动再滚动 (滚动作用于指针所在位置)
+    fn take_pending(&mut self, now: Instant) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        if let Some((x, y)) = self.pending_move.take() {
+            events.push(InputEvent::MouseMove { x, y });
+        }
+        if let Some((delta_x, delta_y)) = self.pending_wheel.take() {
+            events.push(InputEvent::MouseWheel { delta_x, delta_y });
+        }
+        if !events.is_empty() {
+            self.last_flush = Some(now);
+            self.count_injected(events.len() as u64);
+        }
+        events
+    }
+
+    fn count_injected(&mut self, count: u64) {
+        self.stats.injected += count;
+        #[cfg(feature = "metrics")]
+        crate::signaling::metrics::metrics().record_input_injected(count);
+    }
+
+    fn count_merged(&mut self) {
+        self.stats.merged += 1;
+        #[cfg(feature = "metrics")]
+        crate::signaling::metrics::metrics().record_input_merged(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(events: &[InputEvent]) -> Vec<(f64, f64)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                InputEvent::MouseMove { x, y } => Some((*x, *y)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rate_limits_moves() {
+        let mut coalescer = InputCoalescer::new(100);
+        let start = Instant::now();
+
+        // 第一次移动立即注入，10ms 内的后续移动合并为最新位置
+        assert_eq!(moves(&coalescer.push(InputEvent::mouse_move(0.1, 0.1), start)), vec![(0.1, 0.1)]);
+        for i in 2..=5 {
+            let now = start + Duration::from_millis(i);
+            assert!(coalescer.push(InputEvent::mouse_move(i as f64 / 10.0, 0.1), now).is_empty());
+        }
+        assert_eq!(coalescer.deadline(), Some(start + Duration::from_millis(10)));
+        assert!(coalescer.poll(start + Duration::from_millis(9)).is_empty());
+        assert_eq!(moves(&coalescer.poll(start + Duration::from_millis(10))), vec![(0.5, 0.1)]);
+        assert_eq!(coalescer.deadline(), None);
+
+        let stats = coalescer.stats();
+        assert_eq!((stats.injected, stats.merged), (2, 3));
+    }
+
+    #[test]
+    fn test_clicks_flush_pending_move() {
+        let mut coalescer = InputCoalescer::new(100);
+        let start = Instant::now();
+        coalescer.push(InputEvent::mouse_move(0.1, 0.1), start);
+        coalescer.push(InputEvent::mouse_wheel(0, 1), start);
+        coalescer.push(InputEvent::mouse_wheel(0, 2), start);
+        coalescer.push(InputEvent::mouse_move(0.7, 0.3), start);
+
+        // 点击不等待限速，先注入最新位置
+        let events = coalescer.push(InputEvent::mouse_click(super::super::MouseButton::Left, true), start);
+        assert!(matches!(
+            events[..],
+            [
+                InputEvent::MouseMove { x: 0.7, y: 0.3 },
+                InputEvent::MouseWheel { delta_x: 0, delta_y: 3 },
+                InputEvent::MouseClick { pressed: true, .. },
+            ]
+        ));
+        assert_eq!(coalescer.stats().merged, 1);
+    }
+}
@@ -2,7 +2,7 @@
 //!
 //! 使用 Core Graphics CGEvent API
 
-use super::{InputSimulator, MouseButton};
+use super::{single_char, split_text, InputSimulator, MouseButton, TextChunk};
 use anyhow::{anyhow, Result};
 use core_graphics::display::CGDisplay;
 use core_graphics::event::{
@@ -105,6 +105,24 @@ mod keycode {
     pub const KEY_ARROW_UP: u16 = 0x7E;
 }
 
+/// 键盘事件相关的 Core Graphics 接口 (core-graphics crate 未暴露)
+mod ffi {
+    use std::ffi::c_void;
+
+    /// kCGSessionEventTap
+    pub const KCG_SESSION_EVENT_TAP: u32 = 1;
+    /// 单个键盘事件最多携带的 UTF-16 字符数 (超出部分会被系统截断)
+    pub const MAX_UNICODE_PER_EVENT: usize = 20;
+
+    extern "C" {
+        pub fn CGEventCreateKeyboardEvent(source: *const c_void, keycode: u16, key_down: bool) -> *mut c_void;
+        pub fn CGEventKeyboardSetUnicodeString(event: *mut c_void, length: usize, string: *const u16);
+        pub fn CGEventKeyboardGetUnicodeString(event: *mut c_void, max_length: usize, actual_length: *mut usize, string: *mut u16);
+        pub fn CGEventPost(tap: u32, event: *mut c_void);
+        pub fn CFRelease(cf: *mut c_void);
+    }
+}
+
 /// macOS 输入模拟器
 pub struct MacOSInputSimulator {
     display_width: f64,
@@ -139,6 +157,17 @@ impl MacOSInputSimulator {
         })
     }
 
+    /// 检查辅助功能权限 (未授权时注入的键盘鼠标事件会被系统丢弃)
+    ///
+    /// 只查询不弹出授权对话框
+    pub fn check_accessibility_permission() -> bool {
+        #[link(name = "ApplicationServices", kind = "framework")]
+        extern "C" {
+            fn AXIsProcessTrusted() -> bool;
+        }
+        unsafe { AXIsProcessTrusted() }
+    }
+
     /// 将归一化坐标转换为像素坐标
     fn normalize_to_pixel(&self, x: f64, y: f64) -> CGPoint {
         CGPoint {
@@ -190,6 +219,59 @@ impl MacOSInputSimulator {
             .map_err(|e| anyhow!("创建 CGEventSource 失败: {:?}", e))
     }
 
+    /// 以 Unicode 方式输入文本 (CGEventKeyboardSetUnicodeString，不经过键盘布局和输入法)
+    fn send_unicode(text: &str) -> Result<()> {
+        let units: Vec<u16> = text.encode_utf16().collect();
+        let mut start = 0;
+        while start < units.len() {
+            let mut end = (start + ffi::MAX_UNICODE_PER_EVENT).min(units.len());
+            // 不拆开代理对
+            if end < units.len() && (0xD800..0xDC00).contains(&units[end - 1]) {
+                end -= 1;
+            }
+            let chunk = &units[start..end];
+            for key_down in [true, false] {
+                unsafe {
+                    let event = ffi::CGEventCreateKeyboardEvent(std::ptr::null(), 0, key_down);
+                    if event.is_null() {
+                        return Err(anyhow!("创建键盘事件失败"));
+                    }
+                    ffi::CGEventKeyboardSetUnicodeString(event, chunk.len(), chunk.as_ptr());
+                    ffi::CGEventPost(ffi::KCG_SESSION_EVENT_TAP, event);
+                    ffi::CFRelease(event);
+                }
+            }
+            start = end;
+        }
+        Ok(())
+    }
+
+    /// 按当前键盘布局查找输入该字符的键码 (macOS 键码是物理位置，AZERTY 等布局下字母和符号的位置不同)
+    fn layout_keycode(c: char) -> Option<u16> {
+        let target = c.to_lowercase().next()?;
+        // 普通键位的键码都小于 0x80
+        (0..0x80u16).find(|&keycode| unsafe {
+            let event = ffi::CGEventCreateKeyboardEvent(std::ptr::null(), keycode, true);
+            if event.is_null() {
+                return false;
+            }
+            let mut buffer = [0u16; 4];
+            let mut length = 0usize;
+            ffi::CGEventKeyboardGetUnicodeString(event, buffer.len(), &mut length, buffer.as_mut_ptr());
+            ffi::CFRelease(event);
+            let mut chars = char::decode_utf16(buffer[..length].iter().copied()).filter_map(|c| c.ok());
+            matches!((chars.next(), chars.next()), (Some(produced), None) if produced == target)
+        })
+    }
+
+    /// 键名对应的键码: 单个字符按当前布局查找，其余使用固定的键名表
+    fn resolve_keycode(key: &str) -> Option<u16> {
+        single_char(key)
+            .filter(|c| !c.is_whitespace())
+            .and_then(Self::layout_keycode)
+            .or_else(|| Self::key_name_to_keycode(key))
+    }
+
     /// 将键名称转换为 macOS 虚拟键码
     fn key_name_to_keycode(key: &str) -> Option<u16> {
         match key.to_lowercase().as_str() {
@@ -396,8 +478,14 @@ impl InputSimulator for MacOSInputSimulator {
     }
 
     fn key_event(&mut self, key: &str, pressed: bool) -> Result<()> {
-        let keycode = Self::key_name_to_keycode(key)
-            .ok_or_else(|| anyhow!("未知的键名: {}", key))?;
+        let Some(keycode) = Self::resolve_keycode(key) else {
+            // 当前布局无法直接输入的字符，按下时以 Unicode 方式输入
+            return match single_char(key) {
+                Some(_) if pressed => Self::send_unicode(key),
+                Some(_) => Ok(()),
+                None => Err(anyhow!("未知的键名: {}", key)),
+            };
+        };
 
         let source = self.create_event_source()?;
 
@@ -419,6 +507,20 @@ impl InputSimulator for MacOSInputSimulator {
         );
         Ok(())
     }
+
+    fn text_input(&mut self, text: &str) -> Result<()> {
+        for chunk in split_text(text) {
+            match chunk {
+                TextChunk::Text(text) => Self::send_unicode(text)?,
+                TextChunk::Key(key) => {
+                    self.key_event(key, true)?;
+                    self.key_event(key, false)?;
+                }
+            }
+        }
+        tracing::trace!("文本输入: {} 个字符", text.chars().count());
+        Ok(())
+    }
 }
 
 #[cfg(test)]
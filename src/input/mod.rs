@@ -1,10 +1,18 @@
 //! 输入模拟模块
 //!
-//! 在本地模拟鼠标和键盘操作
+//! 在本地模拟鼠标和键盘操作。平板发来的触摸事件先经过 [`touch::TouchTranslator`] 转换为鼠标和键盘事件，
+//! 系统快捷键的交换和屏蔽由 [`shortcut::ShortcutFilter`] 处理，鼠标移动的合并和限速由 [`coalescer::InputCoalescer`] 处理
 
 #![allow(dead_code)]
 
+pub mod coalescer;
+pub mod shortcut;
+pub mod touch;
+
 use anyhow::Result;
+pub use coalescer::InputCoalescer;
+pub use shortcut::{Combo, ShortcutFilter, ShortcutPolicy};
+pub use touch::{ModifierMode, TouchPhase, TouchTranslator};
 
 /// 鼠标按钮
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -25,6 +33,14 @@ pub enum InputEvent {
     MouseWheel { delta_x: i32, delta_y: i32 },
     /// 键盘事件
     KeyEvent { key: String, pressed: bool },
+    /// 文本输入 (直接注入 Unicode 字符，不受被控端键盘布局和输入法影响)
+    TextInput { text: String },
+    /// 触摸事件 (归一化坐标 0.0-1.0，`id` 区分同时按下的多个触点)
+    Touch { id: u32, phase: TouchPhase, x: f64, y: f64 },
+    /// 屏幕修饰键栏 (按住/锁定/松开修饰键)
+    Modifier { key: String, mode: ModifierMode },
+    /// 组合键 (如 "Ctrl+Alt+Del"、"Cmd+Tab")，不受修饰键交换和屏蔽列表影响
+    Shortcut { combo: String },
 }
 
 impl InputEvent {
@@ -52,6 +68,47 @@ impl InputEvent {
     }
 }
 
+/// 键名为单个字符时返回该字符 ("a"、"é"、";")
+pub(crate) fn single_char(key: &str) -> Option<char> {
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// 文本中的换行和制表符 (用 Enter / Tab 键输入，多数程序不接受 Unicode 方式的控制字符)
+pub(crate) enum TextChunk<'a> {
+    Text(&'a str),
+    Key(&'static str),
+}
+
+/// 按换行和制表符拆分文本 (`\r\n` 算一次换行)
+pub(crate) fn split_text(text: &str) -> Vec<TextChunk<'_>> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        let key = match c {
+            '\n' => "Enter",
+            '\t' => "Tab",
+            '\r' if !text[index + 1..].starts_with('\n') => "Enter",
+            '\r' => "",
+            _ => continue,
+        };
+        if start < index {
+            chunks.push(TextChunk::Text(&text[start..index]));
+        }
+        if !key.is_empty() {
+            chunks.push(TextChunk::Key(key));
+        }
+        start = index + 1;
+    }
+    if start < text.len() {
+        chunks.push(TextChunk::Text(&text[start..]));
+    }
+    chunks
+}
+
 /// 输入模拟器 trait
 pub trait InputSimulator: Send {
     /// 移动鼠标到指定位置 (归一化坐标 0.0-1.0)
@@ -66,10 +123,19 @@ pub trait InputSimulator: Send {
     /// 键盘事件
     ///
     /// # 参数
-    /// * `key` - 键名称 (如 "a", "Enter", "Shift", "Control")
+    /// * `key` - 键名称 (如 "a", "Enter", "Shift", "Control")。单个字符按被控端当前的键盘布局查找所在的键，
+    ///   布局中没有该字符时按下时直接输入字符
     /// * `pressed` - true 表示按下，false 表示释放
     fn key_event(&mut self, key: &str, pressed: bool) -> Result<()>;
 
+    /// 输入一段 Unicode 文本 (重音字符、中日韩文字、输入法上屏的文本)
+    fn text_input(&mut self, text: &str) -> Result<()>;
+
+    /// 发送组合键 (默认依次按下再逆序松开)
+    fn shortcut(&mut self, combo: &Combo) -> Result<()> {
+        shortcut::press_combo(self, combo)
+    }
+
     /// 处理输入事件
     fn handle_event(&mut self, event: &InputEvent) -> Result<()> {
         match event {
@@ -89,6 +155,10 @@ pub trait InputSimulator: Send {
             InputEvent::KeyEvent { key, pressed } => {
                 self.key_event(key, *pressed)
             }
+            InputEvent::TextInput { text } => self.text_input(text),
+            InputEvent::Shortcut { combo } => self.shortcut(&combo.parse()?),
+            // 需要先经过 TouchTranslator 转换 (手势依赖之前的触点状态)
+            InputEvent::Touch { .. } | InputEvent::Modifier { .. } => Ok(()),
         }
     }
 }
@@ -144,6 +214,21 @@ mod tests {
         assert!(matches!(event, InputEvent::MouseWheel { .. }));
     }
 
+    #[test]
+    fn test_split_text() {
+        let chunks = split_text("héllo\r\n世界\tx\n");
+        let described: Vec<_> = chunks
+            .iter()
+            .map(|chunk| match chunk {
+                TextChunk::Text(text) => format!("text:{}", text),
+                TextChunk::Key(key) => format!("key:{}", key),
+            })
+            .collect();
+        assert_eq!(described, vec!["text:héllo", "key:Enter", "text:世界", "key:Tab", "text:x", "key:Enter"]);
+        assert_eq!(single_char("é"), Some('é'));
+        assert_eq!(single_char("Enter"), None);
+    }
+
     #[test]
     fn test_mouse_button_conversion() {
         let event = InputEvent::mouse_click(MouseButton::Right, false);
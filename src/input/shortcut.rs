@@ -0,0 +1,334 @@
+//! 系统快捷键
+//!
+//! - 控制端和被控端系统不同时交换 Cmd (Meta) 与 Ctrl: Mac 上按 Cmd+C，Windows 被控端收到 Ctrl+C
+//! - 屏蔽列表: 控制端误按的 Alt+F4 等组合键不注入被控端
+//! - [`InputEvent::Shortcut`](super::InputEvent::Shortcut) 显式发送 Ctrl+Alt+Del、Cmd+Tab 等会被控制端本机系统拦截的组合键，
+//!   不受交换和屏蔽列表影响
+//!
+//! 组合键写作 `Ctrl+Alt+Del`、`Alt+F4`、`Cmd+Q`，不区分大小写
+
+use super::{InputSimulator, MouseButton};
+use anyhow::{anyhow, Result};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// 修饰键
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Modifier {
+    Control,
+    Alt,
+    Shift,
+    /// Windows 键 / Cmd
+    Meta,
+}
+
+impl Modifier {
+    /// 按键名识别修饰键 (不区分左右)
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key.to_lowercase().as_str() {
+            "control" | "controlleft" | "controlright" | "ctrl" | "ctrlright" => Some(Self::Control),
+            "alt" | "altleft" | "altright" | "option" => Some(Self::Alt),
+            "shift" | "shiftleft" | "shiftright" => Some(Self::Shift),
+            "meta" | "metaleft" | "metaright" | "win" | "windows" | "winright" | "cmd" | "command" | "super" => {
+                Some(Self::Meta)
+            }
+            _ => None,
+        }
+    }
+
+    /// 注入时使用的键名
+    pub fn key_name(self) -> &'static str {
+        match self {
+            Self::Control => "Control",
+            Self::Alt => "Alt",
+            Self::Shift => "Shift",
+            Self::Meta => "Meta",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Control => "Ctrl",
+            Self::Alt => "Alt",
+            Self::Shift => "Shift",
+            Self::Meta => "Meta",
+        }
+    }
+}
+
+/// 主键名规范化 (小写，常用缩写展开)
+fn normalize_key(key: &str) -> String {
+    match key.to_lowercase().as_str() {
+        "del" => "delete".to_string(),
+        "esc" => "escape".to_string(),
+        "return" => "enter".to_string(),
+        " " => "space".to_string(),
+        key => key.to_string(),
+    }
+}
+
+/// 组合键
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Combo {
+    pub modifiers: BTreeSet<Modifier>,
+    /// 主键 (小写)
+    pub key: String,
+}
+
+impl Combo {
+    /// 依次按下的键名 (修饰键在前)
+    pub fn keys(&self) -> Vec<String> {
+        self.modifiers
+            .iter()
+            .map(|modifier| modifier.key_name().to_string())
+            .chain(std::iter::once(self.key.clone()))
+            .collect()
+    }
+
+    /// 是否为 Ctrl+Alt+Del (Windows 安全注意序列，无法用普通按键模拟)
+    pub fn is_secure_attention(&self) -> bool {
+        self.key == "delete" && self.modifiers == BTreeSet::from([Modifier::Control, Modifier::Alt])
+    }
+}
+
+impl FromStr for Combo {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let mut modifiers = BTreeSet::new();
+        let mut key = None;
+        for part in value.split('+').map(str::trim) {
+            if part.is_empty() {
+                return Err(anyhow!("组合键 \"{}\" 格式错误", value));
+            }
+            if let Some(modifier) = Modifier::from_key(part) {
+                modifiers.insert(modifier);
+            } else if key.replace(normalize_key(part)).is_some() {
+                return Err(anyhow!("组合键 \"{}\" 只能有一个非修饰键", value));
+            }
+        }
+        let key = key.ok_or_else(|| anyhow!("组合键 \"{}\" 缺少非修饰键", value))?;
+        Ok(Self { modifiers, key })
+    }
+}
+
+impl fmt::Display for Combo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{}+", modifier.label())?;
+        }
+        let mut chars = self.key.chars();
+        match chars.next() {
+            Some(first) => write!(f, "{}{}", first.to_uppercase(), chars.as_str()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 依次按下组合键中的键，再逆序松开
+pub(crate) fn press_combo<S: InputSimulator + ?Sized>(simulator: &mut S, combo: &Combo) -> Result<()> {
+    let keys = combo.keys();
+    for key in &keys {
+        simulator.key_event(key, true)?;
+    }
+    for key in keys.iter().rev() {
+        simulator.key_event(key, false)?;
+    }
+    Ok(())
+}
+
+/// 快捷键策略 (来自 `[host]` 的 `swap_meta_control` 和 `blocked_shortcuts`)
+#[derive(Debug, Clone, Default)]
+pub struct ShortcutPolicy {
+    /// 交换 Meta (Cmd / Windows 键) 与 Ctrl
+    pub swap_meta_control: bool,
+    /// 不注入的组合键 (按交换之后、被控端收到的键匹配，修饰键必须完全一致)
+    pub blocked: Vec<Combo>,
+}
+
+impl ShortcutPolicy {
+    /// 从配置创建，无法解析的组合键记录警告后忽略 (加载配置时已校验)
+    pub fn new(swap_meta_control: bool, blocked: &[String]) -> Self {
+        let blocked = blocked
+            .iter()
+            .filter_map(|combo| match combo.parse() {
+                Ok(combo) => Some(combo),
+                Err(e) => {
+                    tracing::warn!("忽略屏蔽的组合键: {}", e);
+                    None
+                }
+            })
+            .collect();
+        Self { swap_meta_control, blocked }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.swap_meta_control && self.blocked.is_empty()
+    }
+}
+
+/// 按 [`ShortcutPolicy`] 处理键盘事件的输入模拟器
+///
+/// 记录当前按住的修饰键，屏蔽列表中的组合键按下和松开都不注入
+pub struct ShortcutFilter {
+    inner: Box<dyn InputSimulator>,
+    policy: ShortcutPolicy,
+    held: BTreeSet<Modifier>,
+    /// 已屏蔽按下、松开时也要丢弃的主键
+    suppressed: BTreeSet<String>,
+}
+
+impl ShortcutFilter {
+    pub fn new(inner: Box<dyn InputSimulator>, policy: ShortcutPolicy) -> Self {
+        Self { inner, policy, held: BTreeSet::new(), suppressed: BTreeSet::new() }
+    }
+
+    fn swap(&self, key: &str) -> Option<&'static str> {
+        if !self.policy.swap_meta_control {
+            return None;
+        }
+        match Modifier::from_key(key)? {
+            Modifier::Control => Some(Modifier::Meta.key_name()),
+            Modifier::Meta => Some(Modifier::Control.key_name()),
+            _ => None,
+        }
+    }
+}
+
+impl InputSimulator for ShortcutFilter {
+    fn mouse_move(&mut self, x: f64, y: f64) -> Result<()> {
+        self.inner.mouse_move(x, y)
+    }
+
+    fn mouse_click(&mut self, button: MouseButton, pressed: bool) -> Result<()> {
+        self.inner.mouse_click(button, pressed)
+    }
+
+    fn mouse_wheel(&mut self, delta_x: i32, delta_y: i32) -> Result<()> {
+        self.inner.mouse_wheel(delta_x, delta_y)
+    }
+
+    fn key_event(&mut self, key: &str, pressed: bool) -> Result<()> {
+        let key = self.swap(key).unwrap_or(key);
+        if let Some(modifier) = Modifier::from_key(key) {
+            if pressed {
+                self.held.insert(modifier);
+            } else {
+                self.held.remove(&modifier);
+            }
+            return self.inner.key_event(key, pressed);
+        }
+
+        let normalized = normalize_key(key);
+        if !pressed && self.suppressed.remove(&normalized) {
+            return Ok(());
+        }
+        if pressed {
+            let blocked = self
+                .policy
+                .blocked
+                .iter()
+                .find(|combo| combo.key == normalized && combo.modifiers == self.held);
+            if let Some(combo) = blocked {
+                tracing::warn!("已屏蔽组合键 {}", combo);
+                self.suppressed.insert(normalized);
+                return Ok(());
+            }
+        }
+        self.inner.key_event(key, pressed)
+    }
+
+    fn text_input(&mut self, text: &str) -> Result<()> {
+        self.inner.text_input(text)
+    }
+
+    fn shortcut(&mut self, combo: &Combo) -> Result<()> {
+        self.inner.shortcut(combo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// 记录注入的按键
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl InputSimulator for Recorder {
+        fn mouse_move(&mut self, _x: f64, _y: f64) -> Result<()> {
+            Ok(())
+        }
+
+        fn mouse_click(&mut self, _button: MouseButton, _pressed: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn mouse_wheel(&mut self, _delta_x: i32, _delta_y: i32) -> Result<()> {
+            Ok(())
+        }
+
+        fn key_event(&mut self, key: &str, pressed: bool) -> Result<()> {
+            self.0.lock().unwrap().push(format!("{}{}", if pressed { "+" } else { "-" }, key));
+            Ok(())
+        }
+
+        fn text_input(&mut self, _text: &str) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn filter(swap: bool, blocked: &[&str]) -> (ShortcutFilter, Arc<Mutex<Vec<String>>>) {
+        let keys = Arc::new(Mutex::new(Vec::new()));
+        let blocked: Vec<String> = blocked.iter().map(|combo| combo.to_string()).collect();
+        let filter = ShortcutFilter::new(Box::new(Recorder(keys.clone())), ShortcutPolicy::new(swap, &blocked));
+        (filter, keys)
+    }
+
+    fn press(filter: &mut ShortcutFilter, keys: &[&str]) {
+        for key in keys {
+            filter.key_event(key, true).unwrap();
+        }
+        for key in keys.iter().rev() {
+            filter.key_event(key, false).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_parse_combo() {
+        let combo: Combo = "ctrl + alt + Del".parse().unwrap();
+        assert!(combo.is_secure_attention());
+        assert_eq!(combo.keys(), vec!["Control", "Alt", "delete"]);
+        assert_eq!(combo.to_string(), "Ctrl+Alt+Delete");
+        assert_eq!("Cmd+Tab".parse::<Combo>().unwrap().keys(), vec!["Meta", "tab"]);
+        assert!("Ctrl+Alt".parse::<Combo>().is_err());
+        assert!("Alt+F4+F5".parse::<Combo>().is_err());
+        assert!("Alt++".parse::<Combo>().is_err());
+    }
+
+    #[test]
+    fn test_blocked_combo() {
+        let (mut filter, keys) = filter(false, &["Alt+F4"]);
+        press(&mut filter, &["Alt", "F4"]);
+        assert_eq!(*keys.lock().unwrap(), vec!["+Alt", "-Alt"]);
+
+        // 修饰键不同的组合不受影响，显式发送的组合键不经过屏蔽列表
+        keys.lock().unwrap().clear();
+        press(&mut filter, &["Control", "Alt", "F4"]);
+        filter.shortcut(&"Alt+F4".parse().unwrap()).unwrap();
+        assert_eq!(
+            *keys.lock().unwrap(),
+            vec!["+Control", "+Alt", "+F4", "-F4", "-Alt", "-Control", "+Alt", "+f4", "-f4", "-Alt"]
+        );
+    }
+
+    #[test]
+    fn test_swap_meta_control() {
+        // Windows 控制端的 Ctrl+Q 在 Mac 被控端是 Cmd+Q，按交换后的键匹配屏蔽列表
+        let (mut filter, keys) = filter(true, &["Cmd+Q"]);
+        press(&mut filter, &["Control", "c"]);
+        press(&mut filter, &["ControlLeft", "q"]);
+        assert_eq!(*keys.lock().unwrap(), vec!["+Meta", "+c", "-c", "-Meta", "+Meta", "-Meta"]);
+    }
+}
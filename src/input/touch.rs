@@ -0,0 +1,426 @@
+//! 触摸输入转换
+//!
+//! 平板 (iPad / Android) 上的 Viewer 发送触摸事件，由 [`TouchTranslator`] 转换为桌面的鼠标和键盘操作:
+//! - 单指轻点 = 左键单击，单指拖动 = 左键拖动
+//! - 长按后抬起 (或长按后移动) = 右键单击
+//! - 双指平移 = 滚轮滚动 (自然滚动方向)，双指捏合 = Ctrl + 滚轮 (缩放)
+//!
+//! 屏幕修饰键栏通过 [`InputEvent::Modifier`] 协议按住修饰键:
+//! `once` 在下一次按键或点击后自动松开，`lock` 保持按住，`off` 松开
+
+use super::InputEvent;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// 触摸阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TouchPhase {
+    Down,
+    Move,
+    Up,
+    /// 系统打断了触摸 (如来电、手势切换应用)，不触发点击
+    Cancel,
+}
+
+/// 修饰键栏的按键状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModifierMode {
+    /// 按住到下一次按键或点击结束
+    Once,
+    /// 一直按住
+    Lock,
+    /// 松开
+    Off,
+}
+
+/// 手势参数
+#[derive(Debug, Clone)]
+pub struct TouchConfig {
+    /// 按住多久算长按
+    pub long_press: Duration,
+    /// 移动小于该距离 (归一化坐标) 仍算轻点
+    pub tap_slop: f64,
+    /// 双指平移多少距离 (归一化坐标) 滚动一格
+    pub scroll_step: f64,
+    /// 双指距离变化多少比例缩放一格
+    pub zoom_step: f64,
+}
+
+impl Default for TouchConfig {
+    fn default() -> Self {
+        Self {
+            long_press: Duration::from_millis(500),
+            tap_slop: 0.01,
+            scroll_step: 0.05,
+            zoom_step: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Contact {
+    id: u32,
+    start: (f64, f64),
+    position: (f64, f64),
+    since: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Gesture {
+    Idle,
+    /// 单指按下，尚未判断是轻点、长按还是拖动
+    Pending,
+    /// 单指拖动 (左键按住)
+    Dragging,
+    /// 长按已触发右键，之后只移动指针
+    Pointing,
+    /// 双指滚动/缩放 (累计的未满一格的距离)
+    TwoFinger { scroll: (f64, f64), zoom: f64 },
+    /// 双指手势结束后等待剩余手指抬起
+    Finished,
+}
+
+/// 触摸和修饰键转换器 (每个 Viewer 一个)
+///
+/// 鼠标、键盘事件原样通过，只在需要时补上修饰键的松开
+#[derive(Debug)]
+pub struct TouchTranslator {
+    config: TouchConfig,
+    contacts: Vec<Contact>,
+    gesture: Gesture,
+    /// 修饰键栏按住的键
+    modifiers: Vec<(String, ModifierMode)>,
+}
+
+impl TouchTranslator {
+    pub fn new(config: TouchConfig) -> Self {
+        Self {
+            config,
+            contacts: Vec::new(),
+            gesture: Gesture::Idle,
+            modifiers: Vec::new(),
+        }
+    }
+
+    /// 转换一个输入事件，返回需要注入的鼠标和键盘事件
+    pub fn translate(&mut self, event: InputEvent) -> Vec<InputEvent> {
+        self.translate_at(event, Instant::now())
+    }
+
+    pub fn translate_at(&mut self, event: InputEvent, now: Instant) -> Vec<InputEvent> {
+        match event {
+            InputEvent::Touch { id, phase, x, y } => {
+                let mut events = self.touch(id, phase, (x.clamp(0.0, 1.0), y.clamp(0.0, 1.0)), now);
+                if events.iter().any(is_release) {
+                    events.extend(self.release_once());
+                }
+                events
+            }
+            InputEvent::Modifier { key, mode } => self.modifier(key, mode),
+            event => {
+                let release = is_release(&event)
+                    && !matches!(&event, InputEvent::KeyEvent { key, .. } if self.holds(key));
+                let mut events = vec![event];
+                if release {
+                    events.extend(self.release_once());
+                }
+                events
+            }
+        }
+    }
+
+    /// Viewer 断开: 松开仍按住的鼠标按钮和修饰键
+    pub fn reset(&mut self) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        if self.gesture == Gesture::Dragging {
+            events.push(InputEvent::mouse_click(super::MouseButton::Left, false));
+        }
+        self.contacts.clear();
+        self.gesture = Gesture::Idle;
+        for (key, _) in self.modifiers.drain(..) {
+            events.push(InputEvent::KeyEvent { key, pressed: false });
+        }
+        events
+    }
+
+    fn holds(&self, key: &str) -> bool {
+        self.modifiers.iter().any(|(held, _)| held == key)
+    }
+
+    fn modifier(&mut self, key: String, mode: ModifierMode) -> Vec<InputEvent> {
+        let held = self.modifiers.iter().position(|(held, _)| *held == key);
+        match (held, mode) {
+            (Some(index), ModifierMode::Off) => {
+                self.modifiers.remove(index);
+                vec![InputEvent::KeyEvent { key, pressed: false }]
+            }
+            (Some(index), mode) => {
+                self.modifiers[index].1 = mode;
+                Vec::new()
+            }
+            (None, ModifierMode::Off) => Vec::new(),
+            (None, mode) => {
+                self.modifiers.push((key.clone(), mode));
+                vec![InputEvent::KeyEvent { key, pressed: true }]
+            }
+        }
+    }
+
+    /// 松开 `once` 模式的修饰键
+    fn release_once(&mut self) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        self.modifiers.retain(|(key, mode)| {
+            if *mode == ModifierMode::Once {
+                events.push(InputEvent::KeyEvent { key: key.clone(), pressed: false });
+                false
+            } else {
+                true
+            }
+        });
+        events
+    }
+
+    fn touch(&mut self, id: u32, phase: TouchPhase, position: (f64, f64), now: Instant) -> Vec<InputEvent> {
+        match phase {
+            TouchPhase::Down => self.touch_down(id, position, now),
+            TouchPhase::Move => self.touch_move(id, position, now),
+            TouchPhase::Up | TouchPhase::Cancel => self.touch_up(id, phase == TouchPhase::Cancel, now),
+        }
+    }
+
+    fn touch_down(&mut self, id: u32, position: (f64, f64), now: Instant) -> Vec<InputEvent> {
+        // 只跟踪前两个触点
+        if self.contacts.len() >= 2 || self.contacts.iter().any(|contact| contact.id == id) {
+            return Vec::new();
+        }
+        self.contacts.push(Contact { id, start: position, position, since: now });
+        if self.contacts.len() == 1 {
+            self.gesture = Gesture::Pending;
+            return vec![InputEvent::mouse_move(position.0, position.1)];
+        }
+
+        // 第二根手指: 放弃单指操作，进入滚动/缩放
+        let mut events = Vec::new();
+        if self.gesture == Gesture::Dragging {
+            events.push(InputEvent::mouse_click(super::MouseButton::Left, false));
+        }
+        self.gesture = Gesture::TwoFinger { scroll: (0.0, 0.0), zoom: 0.0 };
+        events
+    }
+
+    fn touch_move(&mut self, id: u32, position: (f64, f64), now: Instant) -> Vec<InputEvent> {
+        let Some(index) = self.contacts.iter().position(|contact| contact.id == id) else {
+            return Vec::new();
+        };
+        let before = self.contacts.clone();
+        self.contacts[index].position = position;
+        let contact = self.contacts[index];
+
+        match self.gesture {
+            Gesture::Pending => {
+                if distance(contact.start, position) <= self.config.tap_slop {
+                    return Vec::new();
+                }
+                if now.duration_since(contact.since) >= self.config.long_press {
+                    self.gesture = Gesture::Pointing;
+                    let mut events = right_click();
+                    events.push(InputEvent::mouse_move(position.0, position.1));
+                    events
+                } else {
+                    self.gesture = Gesture::Dragging;
+                    vec![
+                        InputEvent::mouse_click(super::MouseButton::Left, true),
+                        InputEvent::mouse_move(position.0, position.1),
+                    ]
+                }
+            }
+            Gesture::Dragging | Gesture::Pointing => vec![InputEvent::mouse_move(position.0, position.1)],
+            Gesture::TwoFinger { scroll, zoom } if before.len() == 2 => {
+                self.two_finger(&before, scroll, zoom)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// 双指移动: 中点位移换算为滚动，两指距离变化换算为缩放
+    fn two_finger(&mut self, before: &[Contact], mut scroll: (f64, f64), mut zoom: f64) -> Vec<InputEvent> {
+        let (old_a, old_b) = (before[0].position, before[1].position);
+        let (new_a, new_b) = (self.contacts[0].position, self.contacts[1].position);
+        scroll.0 += (new_a.0 + new_b.0 - old_a.0 - old_b.0) / 2.0;
+        scroll.1 += (new_a.1 + new_b.1 - old_a.1 - old_b.1) / 2.0;
+        let (old_distance, new_distance) = (distance(old_a, old_b), distance(new_a, new_b));
+        if old_distance > 0.0 && new_distance > 0.0 {
+            zoom += (new_distance / old_distance).ln();
+        }
+
+        let mut events = Vec::new();
+        // 自然滚动: 手指向下拖动 = 内容向下移动 = 向上滚动 (正值)；水平方向相反
+        let notches_x = (scroll.0 / self.config.scroll_step).trunc();
+        let notches_y = (scroll.1 / self.config.scroll_step).trunc();
+        if notches_x != 0.0 || notches_y != 0.0 {
+            scroll.0 -= notches_x * self.config.scroll_step;
+            scroll.1 -= notches_y * self.config.scroll_step;
+            events.push(InputEvent::mouse_wheel(-notches_x as i32, notches_y as i32));
+        }
+        let zoom_notch = (1.0 + self.config.zoom_step).ln();
+        let zoom_notches = (zoom / zoom_notch).trunc();
+        if zoom_notches != 0.0 {
+            zoom -= zoom_notches * zoom_notch;
+            // 张开 = 放大 = Ctrl + 向上滚动
+            let hold_control = !self.holds("Control");
+            if hold_control {
+                events.push(InputEvent::KeyEvent { key: "Control".to_string(), pressed: true });
+            }
+            events.push(InputEvent::mouse_wheel(0, zoom_notches as i32));
+            if hold_control {
+                events.push(InputEvent::KeyEvent { key: "Control".to_string(), pressed: false });
+            }
+        }
+        self.gesture = Gesture::TwoFinger { scroll, zoom };
+        events
+    }
+
+    fn touch_up(&mut self, id: u32, cancelled: bool, now: Instant) -> Vec<InputEvent> {
+        let Some(index) = self.contacts.iter().position(|contact| contact.id == id) else {
+            return Vec::new();
+        };
+        let contact = self.contacts.remove(index);
+
+        let events = match self.gesture {
+            Gesture::Pending if cancelled => Vec::new(),
+            Gesture::Pending if now.duration_since(contact.since) >= self.config.long_press => right_click(),
+            Gesture::Pending => vec![
+                InputEvent::mouse_click(super::MouseButton::Left, true),
+                InputEvent::mouse_click(super::MouseButton::Left, false),
+            ],
+            Gesture::Dragging => vec![InputEvent::mouse_click(super::MouseButton::Left, false)],
+            _ => Vec::new(),
+        };
+        self.gesture = match (self.gesture, self.contacts.is_empty()) {
+            (_, true) => Gesture::Idle,
+            (Gesture::TwoFinger { .. }, false) => Gesture::Finished,
+            (gesture, false) => gesture,
+        };
+        events
+    }
+}
+
+impl Default for TouchTranslator {
+    fn default() -> Self {
+        Self::new(TouchConfig::default())
+    }
+}
+
+fn right_click() -> Vec<InputEvent> {
+    vec![
+        InputEvent::mouse_click(super::MouseButton::Right, true),
+        InputEvent::mouse_click(super::MouseButton::Right, false),
+    ]
+}
+
+/// 按键或鼠标按钮松开、文本输入、组合键 (`once` 修饰键在此之后松开)
+fn is_release(event: &InputEvent) -> bool {
+    matches!(
+        event,
+        InputEvent::KeyEvent { pressed: false, .. }
+            | InputEvent::MouseClick { pressed: false, .. }
+            | InputEvent::TextInput { .. }
+            | InputEvent::Shortcut { .. }
+    )
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(id: u32, phase: TouchPhase, x: f64, y: f64) -> InputEvent {
+        InputEvent::Touch { id, phase, x, y }
+    }
+
+    fn clicks(events: &[InputEvent]) -> Vec<(String, bool)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                InputEvent::MouseClick { button, pressed } => Some((button.clone(), *pressed)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tap_drag_and_long_press() {
+        let mut touch_input = TouchTranslator::default();
+        let start = Instant::now();
+
+        // 轻点 = 左键单击
+        assert!(matches!(touch_input.translate_at(touch(1, TouchPhase::Down, 0.5, 0.5), start)[..], [InputEvent::MouseMove { .. }]));
+        let events = touch_input.translate_at(touch(1, TouchPhase::Up, 0.5, 0.5), start + Duration::from_millis(100));
+        assert_eq!(clicks(&events), vec![("left".to_string(), true), ("left".to_string(), false)]);
+
+        // 拖动 = 按住左键移动
+        touch_input.translate_at(touch(2, TouchPhase::Down, 0.2, 0.2), start);
+        let events = touch_input.translate_at(touch(2, TouchPhase::Move, 0.3, 0.2), start + Duration::from_millis(50));
+        assert_eq!(clicks(&events), vec![("left".to_string(), true)]);
+        let events = touch_input.translate_at(touch(2, TouchPhase::Up, 0.3, 0.2), start + Duration::from_millis(80));
+        assert_eq!(clicks(&events), vec![("left".to_string(), false)]);
+
+        // 长按 = 右键单击；被系统打断时不点击
+        touch_input.translate_at(touch(3, TouchPhase::Down, 0.4, 0.4), start);
+        let events = touch_input.translate_at(touch(3, TouchPhase::Up, 0.4, 0.4), start + Duration::from_millis(600));
+        assert_eq!(clicks(&events), vec![("right".to_string(), true), ("right".to_string(), false)]);
+        touch_input.translate_at(touch(4, TouchPhase::Down, 0.4, 0.4), start);
+        assert!(touch_input.translate_at(touch(4, TouchPhase::Cancel, 0.4, 0.4), start).is_empty());
+    }
+
+    #[test]
+    fn test_two_finger_scroll_and_pinch() {
+        let mut touch_input = TouchTranslator::default();
+        let now = Instant::now();
+        touch_input.translate_at(touch(1, TouchPhase::Down, 0.4, 0.5), now);
+        touch_input.translate_at(touch(2, TouchPhase::Down, 0.6, 0.5), now);
+
+        // 双指一起向下移动 0.06 (各自移动后中点下移 0.06) = 向上滚动一格
+        touch_input.translate_at(touch(1, TouchPhase::Move, 0.4, 0.56), now);
+        let events = touch_input.translate_at(touch(2, TouchPhase::Move, 0.6, 0.56), now);
+        assert!(matches!(events[..], [InputEvent::MouseWheel { delta_x: 0, delta_y: 1 }]));
+
+        // 双指抬起不产生点击
+        assert!(clicks(&touch_input.translate_at(touch(1, TouchPhase::Up, 0.4, 0.56), now)).is_empty());
+        assert!(clicks(&touch_input.translate_at(touch(2, TouchPhase::Up, 0.6, 0.56), now)).is_empty());
+
+        // 张开 = Ctrl + 向上滚动 (放大滚动步长，排除中点移动的影响)
+        let mut touch_input = TouchTranslator::new(TouchConfig { scroll_step: 1.0, ..Default::default() });
+        touch_input.translate_at(touch(1, TouchPhase::Down, 0.4, 0.5), now);
+        touch_input.translate_at(touch(2, TouchPhase::Down, 0.6, 0.5), now);
+        let events = touch_input.translate_at(touch(2, TouchPhase::Move, 0.7, 0.5), now);
+        assert!(matches!(&events[..], [
+            InputEvent::KeyEvent { pressed: true, .. },
+            InputEvent::MouseWheel { delta_y, .. },
+            InputEvent::KeyEvent { pressed: false, .. },
+        ] if *delta_y > 0));
+    }
+
+    #[test]
+    fn test_sticky_modifiers() {
+        let mut touch_input = TouchTranslator::default();
+        let modifier = |key: &str, mode| InputEvent::Modifier { key: key.to_string(), mode };
+        let key = |pressed| InputEvent::KeyEvent { key: "c".to_string(), pressed };
+
+        assert_eq!(touch_input.translate(modifier("Control", ModifierMode::Once)).len(), 1);
+        assert_eq!(touch_input.translate(modifier("Shift", ModifierMode::Lock)).len(), 1);
+        assert_eq!(touch_input.translate(key(true)).len(), 1);
+        // 松开 c 后 Control 自动松开，Shift 保持
+        let events = touch_input.translate(key(false));
+        assert!(matches!(&events[..], [_, InputEvent::KeyEvent { key, pressed: false }] if key == "Control"));
+        assert_eq!(touch_input.translate(key(false)).len(), 1);
+
+        let events = touch_input.reset();
+        assert!(matches!(&events[..], [InputEvent::KeyEvent { key, pressed: false }] if key == "Shift"));
+    }
+}
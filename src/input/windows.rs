@@ -4,7 +4,7 @@
 
 #![cfg(target_os = "windows")]
 
-use super::{InputSimulator, MouseButton};
+use super::{single_char, split_text, Combo, InputSimulator, MouseButton, TextChunk};
 use anyhow::{anyhow, Result};
 use std::mem;
 
@@ -22,6 +22,7 @@ const MOUSEEVENTF_ABSOLUTE: u32 = 0x8000;
 
 /// Windows 键盘输入标志
 const KEYEVENTF_KEYUP: u32 = 0x0002;
+const KEYEVENTF_UNICODE: u32 = 0x0004;
 #[allow(dead_code)]
 const KEYEVENTF_EXTENDEDKEY: u32 = 0x0001;
 
@@ -183,10 +184,19 @@ impl WindowsInputSimulator {
         }
     }
 
+    /// 切换到当前输入桌面 (锁屏、登录界面和 UAC 提示在 Winlogon 安全桌面上)，失败时仍在原桌面上发送
+    fn follow_desktop() {
+        if let Err(e) = crate::service::windows_session::follow_input_desktop() {
+            tracing::debug!("{}", e);
+        }
+    }
+
     /// 发送鼠标输入
     fn send_mouse_input(flags: u32, x: i32, y: i32, data: u32) -> Result<()> {
         use windows::Win32::UI::Input::KeyboardAndMouse::{SendInput, INPUT, INPUT_0, MOUSEINPUT, INPUT_MOUSE, MOUSE_EVENT_FLAGS};
 
+        Self::follow_desktop();
+
         unsafe {
             let mouse_input = MOUSEINPUT {
                 dx: x,
@@ -221,6 +231,7 @@ impl WindowsInputSimulator {
             SendInput, INPUT, INPUT_0, KEYBDINPUT, INPUT_KEYBOARD, KEYBD_EVENT_FLAGS, VIRTUAL_KEY,
         };
 
+        Self::follow_desktop();
         unsafe {
             let flags = if pressed { 0 } else { KEYEVENTF_KEYUP };
 
@@ -250,6 +261,72 @@ impl WindowsInputSimulator {
         Ok(())
     }
 
+    /// 以 Unicode 方式输入文本 (KEYEVENTF_UNICODE，不经过键盘布局)
+    fn send_unicode_input(text: &str) -> Result<()> {
+        use windows::Win32::UI::Input::KeyboardAndMouse::{
+            SendInput, INPUT, INPUT_0, KEYBDINPUT, INPUT_KEYBOARD, KEYBD_EVENT_FLAGS, VIRTUAL_KEY,
+        };
+
+        let inputs: Vec<INPUT> = text
+            .encode_utf16()
+            .flat_map(|unit| [KEYEVENTF_UNICODE, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP].map(move |flags| (unit, flags)))
+            .map(|(unit, flags)| INPUT {
+                r#type: INPUT_KEYBOARD,
+                Anonymous: INPUT_0 {
+                    ki: KEYBDINPUT {
+                        wVk: VIRTUAL_KEY(0),
+                        wScan: unit,
+                        dwFlags: KEYBD_EVENT_FLAGS(flags),
+                        time: 0,
+                        dwExtraInfo: 0,
+                    },
+                },
+            })
+            .collect();
+        if inputs.is_empty() {
+            return Ok(());
+        }
+
+        Self::follow_desktop();
+        let size = mem::size_of::<INPUT>() as i32;
+        let sent = unsafe { SendInput(&inputs, size) };
+        if sent as usize != inputs.len() {
+            return Err(anyhow!("SendInput 文本失败: {:?}", windows::core::Error::from_win32()));
+        }
+        Ok(())
+    }
+
+    /// 按前台窗口的键盘布局查找输入该字符的键 (非美式布局的符号键位置不同)
+    fn layout_vk(c: char) -> Option<u16> {
+        use windows::Win32::UI::Input::KeyboardAndMouse::{GetKeyboardLayout, VkKeyScanExW};
+        use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+
+        let mut units = [0u16; 2];
+        let [unit] = c.encode_utf16(&mut units) else { return None };
+        let result = unsafe {
+            let thread = GetWindowThreadProcessId(GetForegroundWindow(), None);
+            VkKeyScanExW(*unit, GetKeyboardLayout(thread))
+        };
+        // 低字节为虚拟键码 (高字节是需要的 Shift/Ctrl/Alt 状态，由控制端自己按下)，-1 表示布局中没有该字符
+        (result != -1).then_some((result as u16) & 0xFF)
+    }
+
+    /// 发送 Ctrl+Alt+Del (SendInput 模拟的 Ctrl+Alt+Del 会被系统忽略)
+    ///
+    /// 作为服务的助手进程运行时由服务调用 SendSAS 代发；否则直接调用 SendSAS，
+    /// 只有以服务 (LocalSystem) 运行且组策略"禁用或启用软件安全注意序列"允许服务时才会生效
+    fn send_secure_attention() -> Result<()> {
+        crate::service::windows_session::send_secure_attention()
+    }
+
+    /// 键名对应的虚拟键码: 单个字符优先按当前布局查找，其余使用固定的键名表
+    fn resolve_vk(key: &str) -> Option<u16> {
+        single_char(key)
+            .filter(|c| !c.is_ascii_alphanumeric())
+            .and_then(Self::layout_vk)
+            .or_else(|| Self::key_name_to_vk(key))
+    }
+
     /// 将键名称转换为 Windows 虚拟键码
     fn key_name_to_vk(key: &str) -> Option<u16> {
         match key.to_lowercase().as_str() {
@@ -403,8 +480,14 @@ impl InputSimulator for WindowsInputSimulator {
     }
 
     fn key_event(&mut self, key: &str, pressed: bool) -> Result<()> {
-        let vk = Self::key_name_to_vk(key)
-            .ok_or_else(|| anyhow!("未知的键名: {}", key))?;
+        let Some(vk) = Self::resolve_vk(key) else {
+            // 当前布局无法直接输入的字符 (如美式布局下的 é)，按下时以 Unicode 方式输入
+            return match single_char(key) {
+                Some(_) if pressed => Self::send_unicode_input(key),
+                Some(_) => Ok(()),
+                None => Err(anyhow!("未知的键名: {}", key)),
+            };
+        };
 
         Self::send_keyboard_input(vk, pressed)?;
 
@@ -416,6 +499,27 @@ impl InputSimulator for WindowsInputSimulator {
         );
         Ok(())
     }
+
+    fn text_input(&mut self, text: &str) -> Result<()> {
+        for chunk in split_text(text) {
+            match chunk {
+                TextChunk::Text(text) => Self::send_unicode_input(text)?,
+                TextChunk::Key(key) => {
+                    self.key_event(key, true)?;
+                    self.key_event(key, false)?;
+                }
+            }
+        }
+        tracing::trace!("文本输入: {} 个字符", text.chars().count());
+        Ok(())
+    }
+
+    fn shortcut(&mut self, combo: &Combo) -> Result<()> {
+        if combo.is_secure_attention() {
+            return Self::send_secure_attention();
+        }
+        super::shortcut::press_combo(self, combo)
+    }
 }
 
 /// 默认实现
@@ -2,13 +2,22 @@
 //!
 //! 提供屏幕捕获、编码和网络传输功能
 
+pub mod audit;
 pub mod capture;
 pub mod config;
 pub mod encoder;
+pub mod engine;
+pub mod hosts;
 pub mod input;
+pub mod logging;
 pub mod network;
+pub mod power;
+pub mod privacy;
+pub mod protocol;
 pub mod security;
 pub mod service;
+pub mod system;
+pub mod transfer;
 pub mod webrtc;
 
 // NAT 穿透模块 (零依赖)
@@ -17,6 +26,8 @@ pub mod nat;
 // 质量优化模块
 pub mod quality;
 
+pub mod recorder;
+
 // 命令行工具模块
 pub mod tools;
 
@@ -31,3 +42,11 @@ pub mod signaling;
 
 // Web 查看器模块
 pub mod viewer;
+
+// 自动更新模块
+#[cfg(feature = "update")]
+pub mod updater;
+
+// 端到端测试工具 (进程内回环)
+#[cfg(all(feature = "webrtc", feature = "h264"))]
+pub mod testing;
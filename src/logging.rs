@@ -0,0 +1,195 @@
+//! 日志输出
+//!
+//! 支持两种格式:
+//! - 文本: 人类可读 (默认)
+//! - JSON: 每行一个对象，便于投递到 Loki / Elasticsearch
+//!
+//! 信令连接、WebRTC 会话和视频任务中与某个 Viewer 相关的日志都记录在 `session` span 中。
+//! JSON 格式会把所在 span 的字段 (`peer_id`、`session_id`) 平铺到每条日志，可按会话过滤
+
+use serde_json::{Map, Value};
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::{span, Event, Level, Span, Subscriber};
+use tracing_subscriber::fmt::{format::Writer, FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// 初始化全局日志 (json 为 false 时输出文本格式)
+pub fn init(level: Level, json: bool) {
+    if json {
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::filter::LevelFilter::from_level(level))
+            .with(SpanFieldLayer)
+            .with(tracing_subscriber::fmt::layer().event_format(JsonFormat))
+            .init();
+    } else {
+        tracing_subscriber::fmt()
+            .with_target(false)
+            .with_level(true)
+            .with_max_level(level)
+            .init();
+    }
+}
+
+// span 取最高级别，任何日志级别下都会创建，warn/error 日志同样带有关联 ID
+
+/// Viewer 会话的日志 span
+pub fn session_span(peer_id: &str, session_id: &str) -> Span {
+    tracing::error_span!("session", peer_id = %peer_id, session_id = %session_id)
+}
+
+/// 尚未分配会话 ID 的 Viewer (之后可用 `Span::record("session_id", ..)` 补上)
+pub fn peer_span(peer_id: &str) -> Span {
+    tracing::error_span!("session", peer_id = %peer_id, session_id = tracing::field::Empty)
+}
+
+/// 记录在 span 扩展中的字段 (JSON 格式输出时使用)
+struct SpanFields(Map<String, Value>);
+
+/// 保存每个 span 的字段，供 `JsonFormat` 读取
+struct SpanFieldLayer;
+
+impl<S> Layer<S> for SpanFieldLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Map::new();
+        attrs.record(&mut JsonVisitor(&mut fields));
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(SpanFields(fields)) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut JsonVisitor(fields));
+        }
+    }
+}
+
+/// 把事件/span 字段写入 JSON 对象
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), Value::from(format!("{:?}", value)));
+    }
+}
+
+/// 单行 JSON 格式: timestamp / level / target / span 字段 / 事件字段 (含 message)
+struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut object = Map::new();
+        object.insert(
+            "timestamp".to_string(),
+            Value::from(chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)),
+        );
+        object.insert("level".to_string(), Value::from(metadata.level().as_str()));
+        object.insert("target".to_string(), Value::from(metadata.target()));
+
+        // 外层到内层，内层 span 的同名字段覆盖外层
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>() {
+                    object.extend(fields.clone());
+                }
+            }
+        }
+        event.record(&mut JsonVisitor(&mut object));
+
+        writeln!(writer, "{}", Value::Object(object))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_lines_carry_session_fields() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(SpanFieldLayer).with(
+            tracing_subscriber::fmt::layer()
+                .event_format(JsonFormat)
+                .with_writer(move || writer.clone()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("启动");
+            let span = peer_span("viewer_1");
+            let _guard = span.enter();
+            tracing::info!("收到 Offer");
+            span.record("session_id", "abc");
+            tracing::warn!(bytes = 42u64, "发送失败");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 3);
+
+        assert_eq!(lines[0]["message"], "启动");
+        assert!(lines[0].get("peer_id").is_none());
+
+        assert_eq!(lines[1]["level"], "INFO");
+        assert_eq!(lines[1]["peer_id"], "viewer_1");
+        assert!(lines[1].get("session_id").is_none(), "未记录的字段不应输出");
+
+        assert_eq!(lines[2]["level"], "WARN");
+        assert_eq!(lines[2]["session_id"], "abc");
+        assert_eq!(lines[2]["bytes"], 42);
+        assert_eq!(lines[2]["message"], "发送失败");
+        assert!(lines[2]["timestamp"].as_str().is_some_and(|ts| ts.ends_with('Z')));
+    }
+}
@@ -2,18 +2,28 @@
 //!
 //! 主入口程序
 
+mod audit;
 mod capture;
 mod cli;
 mod commands;
 mod config;
 mod connect_mode;
 mod encoder;
+mod engine;
 mod host_mode;
+mod hosts;
 mod input;
+mod logging;
 mod network;
+mod power;
+mod privacy;
+mod protocol;
 mod nat;
 mod quality;
+mod recorder;
+mod system;
 mod tools;
+mod transfer;
 
 #[cfg(feature = "discovery")]
 mod discovery;
@@ -36,13 +46,21 @@ mod webrtc;
 #[cfg(feature = "tunnel")]
 mod tunnel;
 
+// 自动更新模块 (当启用 update feature 时)
+#[cfg(feature = "update")]
+mod updater;
+
+// 远程部署模块 (当启用 deploy feature 时)
+#[cfg(feature = "deploy")]
+mod deploy;
+
 // Web 查看器模块
 mod viewer;
 
 use anyhow::Result;
 use clap::Parser;
 
-use cli::{Args, Commands};
+use cli::{Args, Commands, Transport};
 use commands::*;
 use encoder::hardware::HardwareEncoder;
 
@@ -57,52 +75,156 @@ async fn main() -> Result<()> {
     // 解析命令行参数
     let args = Args::parse();
 
+    // 选择画面来源 (命令行 --capture 优先于配置 capture.source)
+    select_capture_source(args.capture);
+
     // 处理子命令
     if let Some(command) = args.command {
         return match command {
-            Commands::Run => {
-                init_logging(args.verbose.unwrap_or(1));
+            Commands::Run { session_helper } => {
+                init_logging(args.verbose.unwrap_or(1), args.log_format);
+                // 由服务控制管理器启动时进入服务分发，服务在用户会话中启动助手进程
+                #[cfg(target_os = "windows")]
+                if !session_helper && tokio::task::block_in_place(service::windows::dispatch)? {
+                    return Ok(());
+                }
+                // 由 LaunchDaemon 启动时只监督控制台会话中的助手进程
+                #[cfg(target_os = "macos")]
+                if !session_helper && service::macos_session::is_launch_daemon() {
+                    return tokio::task::spawn_blocking(service::macos::run_daemon).await?;
+                }
+                #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+                let _ = session_helper;
                 run_service_mode().await
             }
-            Commands::Service { action } => {
-                handle_service_command(action)
+            Commands::Service { daemon, action } => {
+                handle_service_command(action, daemon)
             }
             #[cfg(feature = "tunnel")]
-            Commands::Host { port, tunnel } => {
-                init_logging(args.verbose.unwrap_or(1));
-                host_mode::run_host_mode(port, tunnel, args.encoder, args.bitrate, args.adaptive).await
+            Commands::Host { port, view_only, unattended, record, record_split, record_max_size, stats, limits, cluster, ice, tunnel } => {
+                init_logging(args.verbose.unwrap_or(1), args.log_format);
+                let recording = recording_config(record, record_split, record_max_size);
+                host_mode::run_host_mode(port, tunnel, view_only, unattended, recording, args.encoder, args.bitrate, args.profile, args.adaptive, stats, limits, cluster, ice).await
             }
             #[cfg(not(feature = "tunnel"))]
-            Commands::Host { port, .. } => {
-                init_logging(args.verbose.unwrap_or(1));
-                host_mode::run_host_mode(port, false, args.encoder, args.bitrate, args.adaptive).await
+            Commands::Host { port, view_only, unattended, record, record_split, record_max_size, stats, limits, cluster, ice, .. } => {
+                init_logging(args.verbose.unwrap_or(1), args.log_format);
+                let recording = recording_config(record, record_split, record_max_size);
+                host_mode::run_host_mode(port, None, view_only, unattended, recording, args.encoder, args.bitrate, args.profile, args.adaptive, stats, limits, cluster, ice).await
+            }
+            Commands::Connect { host, ip, url, port, transport, pin, totp, fingerprint, pair } => {
+                init_logging(args.verbose.unwrap_or(1), args.log_format);
+                // 别名按地址簿解析，命令行的端口和传输方式优先
+                let (ip, url, port, transport) = match host {
+                    Some(alias) => {
+                        let saved = connect_mode::resolve_saved_host(&alias, port).await?;
+                        (saved.ip.into_iter().collect(), saved.url.into_iter().collect(), saved.port, transport.or(saved.transport))
+                    }
+                    None => (ip, url, port.unwrap_or(hosts::DEFAULT_PORT), transport),
+                };
+                match transport.unwrap_or(Transport::Webrtc) {
+                    Transport::Webrtc => connect_mode::run_connect_mode(&ip, &url, port).await,
+                    Transport::Quic if ip.len() + url.len() > 1 => {
+                        anyhow::bail!("QUIC 传输一次只能连接一台被控端")
+                    }
+                    Transport::Quic => {
+                        let (ip, url) = (ip.first().map(String::as_str), url.first().map(String::as_str));
+                        connect_mode::run_quic_connect_mode(ip, url, port, pin, totp, fingerprint, pair).await
+                    }
+                }
+            }
+            Commands::Probe { ip, url, port, timeout } => {
+                init_logging(args.verbose.unwrap_or(0), args.log_format);
+                handle_probe(ip.as_deref(), url.as_deref(), port, timeout).await
+            }
+            Commands::Info { ip, url, port, token, pin, totp, top, json, timeout } => {
+                init_logging(args.verbose.unwrap_or(0), args.log_format);
+                handle_info(
+                    ip.as_deref(),
+                    url.as_deref(),
+                    port,
+                    token.as_deref(),
+                    pin.as_deref(),
+                    totp.as_deref(),
+                    top,
+                    json,
+                    timeout,
+                )
+                .await
+            }
+            Commands::Screenshot { screen, output, quality } => {
+                init_logging(args.verbose.unwrap_or(0), args.log_format);
+                handle_screenshot(screen, output, quality)
             }
-            Commands::Connect { ip, url, port } => {
-                init_logging(args.verbose.unwrap_or(1));
-                connect_mode::run_connect_mode(ip.as_deref(), url.as_deref(), port).await
+            Commands::Power { action, yes } => {
+                init_logging(args.verbose.unwrap_or(0), args.log_format);
+                handle_power(action, yes)
+            }
+            Commands::Encoders { action } => {
+                init_logging(args.verbose.unwrap_or(0), args.log_format);
+                handle_encoders_command(action)
             }
             Commands::ListEncoders => {
-                init_logging(args.verbose.unwrap_or(1));
-                handle_list_encoders()
+                init_logging(args.verbose.unwrap_or(0), args.log_format);
+                handle_encoders_command(cli::EncoderCommands::List { refresh: false, json: false })
             }
             Commands::Benchmark { duration, width, height } => {
-                init_logging(args.verbose.unwrap_or(1));
+                init_logging(args.verbose.unwrap_or(1), args.log_format);
                 handle_benchmark(duration, width, height).await
             }
-            Commands::Doctor { nat, quality } => {
-                init_logging(args.verbose.unwrap_or(1));
-                handle_doctor(nat, quality).await
+            Commands::Bench { duration, only, json } => {
+                // 默认只输出警告，避免日志打断结果表格
+                init_logging(args.verbose.unwrap_or(0), args.log_format);
+                handle_bench(duration, args.screen, args.fps.unwrap_or(60), args.bitrate.unwrap_or(4000), only, json).await
+            }
+            Commands::Doctor { port, nat, quality, stun } => {
+                init_logging(args.verbose.unwrap_or(1), args.log_format);
+                handle_doctor(port, nat, quality, stun).await
+            }
+            Commands::StunServer { port } => {
+                init_logging(args.verbose.unwrap_or(1), args.log_format);
+                handle_stun_server(port).await
             }
             Commands::SysInfo => {
-                init_logging(args.verbose.unwrap_or(1));
+                init_logging(args.verbose.unwrap_or(1), args.log_format);
                 handle_sysinfo()
             }
-            Commands::Config { path } => {
-                handle_generate_config(path)
-            }
+            Commands::Config { path, action } => match action {
+                None => handle_generate_config(path),
+                Some(action) => handle_config_command(path, action),
+            },
             Commands::Stats => {
                 handle_stats()
             }
+            Commands::Auth { action } => {
+                handle_auth_command(action)
+            }
+            Commands::Audit { action } => {
+                handle_audit_command(action)
+            }
+            Commands::Discover { timeout } => {
+                init_logging(args.verbose.unwrap_or(0), args.log_format);
+                handle_discover(timeout).await
+            }
+            Commands::Hosts { action } => {
+                init_logging(args.verbose.unwrap_or(0), args.log_format);
+                handle_hosts_command(action).await
+            }
+            Commands::Pair { action } => {
+                handle_pair_command(action)
+            }
+            Commands::Update { check_only } => {
+                init_logging(args.verbose.unwrap_or(0), args.log_format);
+                handle_update(args.config, check_only).await
+            }
+            Commands::Deploy { action } => {
+                init_logging(args.verbose.unwrap_or(0), args.log_format);
+                handle_deploy_command(args.config, action).await
+            }
+            Commands::Fleet { action } => {
+                init_logging(args.verbose.unwrap_or(0), args.log_format);
+                handle_fleet_command(args.config, action).await
+            }
         };
     }
 
@@ -111,22 +233,58 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// 由命令行参数构造录制配置 (分段时长单位为分钟，大小单位为 MB)
+fn recording_config(
+    path: Option<String>,
+    split_minutes: Option<u64>,
+    max_size_mb: Option<u64>,
+) -> Option<recorder::RecordingConfig> {
+    path.map(|path| recorder::RecordingConfig {
+        segment_duration: split_minutes.map(|m| std::time::Duration::from_secs(m * 60)),
+        max_segment_bytes: max_size_mb.map(|mb| mb * 1024 * 1024),
+        ..recorder::RecordingConfig::new(path)
+    })
+}
+
+/// 连接信令服务器时出示的设备证书 (证书和私钥必须同时配置)
+#[cfg(feature = "security")]
+fn client_identity(security: &config::SecurityConfig) -> Result<Option<security::TlsConfig>> {
+    match (&security.tls_client_cert, &security.tls_client_key) {
+        (Some(cert), Some(key)) => Ok(Some(security::TlsConfig::new(cert.clone(), key.clone()))),
+        (None, None) => Ok(None),
+        _ => anyhow::bail!("security.tls_client_cert 和 security.tls_client_key 必须同时配置"),
+    }
+}
+
 /// Print usage information
 fn print_usage() {
     println!("sscontrol - 无界面远程桌面应用");
     println!();
     println!("用法:");
-    println!("  被控端: sscontrol host [--port 9527] [--tunnel] [--encoder <类型>] [--bitrate <kbps>] [--adaptive]");
+    println!("  被控端: sscontrol host [--port 9527] [--tunnel] [--encoder <类型>] [--bitrate <kbps>] [--adaptive] [--stats]");
     println!("  控制端: sscontrol connect --ip <IP> [--port 9527]");
     println!("          sscontrol connect --url <URL>");
+    println!("          sscontrol connect --ip <IP> --transport quic [--pin <PIN>]");
+    println!("  连接探测: sscontrol probe --ip <IP> [--port 9527] | --url <URL>");
     println!();
     println!("工具命令:");
-    println!("  列出编码器: sscontrol list-encoders");
+    println!("  电源操作: sscontrol power <lock|logout|reboot|shutdown> [--yes]");
+    println!("  审计日志: sscontrol audit list [--peer <ID>] [-n N] | sscontrol audit verify");
+    println!("  列出编码器: sscontrol encoders list [--refresh] [--json]");
     println!("  编码器测试: sscontrol benchmark [--duration N] [--width W] [--height H]");
-    println!("  网络诊断: sscontrol doctor [--nat] [--quality]");
+    println!("  网络诊断: sscontrol doctor [--nat [--stun <URL>...]] [--quality]");
+    println!("  STUN 服务: sscontrol stun-server [--port 3478]");
+    println!("  屏幕截图: sscontrol screenshot [--screen N] [--output <文件.png|.jpg>]");
     println!("  系统信息: sscontrol sysinfo");
     println!("  生成配置: sscontrol config [--path <路径>]");
+    println!("  配置管理: sscontrol config validate | show [<配置项>] | set <配置项> <值> [--path <路径>]");
     println!("  实时统计: sscontrol stats");
+    println!("  检查更新: sscontrol update [--check-only]");
+    println!("  部署 TURN: sscontrol deploy turn --host <服务器> [--user root] [--port 3478]");
+    println!("  部署被控端: sscontrol deploy agent --host <主机> --signaling-url <URL> [--api-key <KEY>] [--device-id <ID>]");
+    println!("  容器部署: sscontrol deploy compose --host <域名> [--signaling-upstream <主机:端口>] [--output <目录>]");
+    println!("  双因素验证: sscontrol auth enroll [--account <名称>] [--force]");
+    println!("  设备证书:   sscontrol auth issue-cert <设备名> [--dir <目录>]");
     println!();
     println!("编码器类型: auto, software, nvenc, amf, qsv, videotoolbox");
     println!();
@@ -137,7 +295,6 @@ fn print_usage() {
 async fn run_service_mode() -> Result<()> {
     use tracing::info;
     use std::sync::Arc;
-    use std::time::Duration;
     use tokio::signal;
     use tokio::sync::Mutex;
     use tracing::{error, warn};
@@ -148,10 +305,14 @@ async fn run_service_mode() -> Result<()> {
     let config_path = config::Config::get_config_path(None);
     let config = config::Config::load(&config_path)?;
 
+    // 自动更新 (安装新版本后退出，由服务管理器重新启动)
+    #[cfg(feature = "update")]
+    tokio::spawn(updater::run_auto_update(config.update.clone()));
+
     // 检查屏幕录制权限 (macOS)
     #[cfg(target_os = "macos")]
     {
-        if !capture::macos::MacOSCapturer::check_screen_recording_permission() {
+        if !capture::is_synthetic() && !capture::macos::MacOSCapturer::check_screen_recording_permission() {
             warn!("屏幕录制权限未授予，请在系统设置中授权");
         }
     }
@@ -163,19 +324,27 @@ async fn run_service_mode() -> Result<()> {
 
     info!("屏幕尺寸: {}x{}", capturer.width(), capturer.height());
 
+    // 编码分辨率对齐 (YUV420 要求偶数宽高)
+    let aligner = encoder::alignment::FrameAligner::even(capturer.width(), capturer.height())?;
+
+    // 带宽上限 (按当前时段，之后定期重新评估)
+    let bandwidth = quality::bandwidth::BandwidthPolicy::from_config(&config.bandwidth)?;
+    let rate_limit = bandwidth.limit_now().per_viewer(1);
+    let bitrate = rate_limit.map_or(2000, |kbps| kbps.min(2000));
+
     // 创建编码器 - 优先使用硬件编码器
     info!("初始化编码器...");
     let hw_config = encoder::hardware::HardwareEncoderConfig {
         encoder_type: encoder::hardware::HardwareEncoderType::Auto,
-        bitrate: 2000,
+        bitrate,
         fps: config.capture.fps,
         preset: encoder::hardware::EncoderPreset::LowLatency,
     };
 
-    let mut encoder: Box<dyn encoder::Encoder> = match encoder::hardware::HardwareEncoderWrapper::create(
+    let encoder: Box<dyn encoder::Encoder> = match encoder::hardware::HardwareEncoderWrapper::create(
         hw_config.encoder_type,
-        capturer.width(),
-        capturer.height(),
+        aligner.width(),
+        aligner.height(),
         hw_config,
     ) {
         Ok(hw_enc) => {
@@ -187,29 +356,63 @@ async fn run_service_mode() -> Result<()> {
             #[cfg(feature = "h264")]
             {
                 Box::new(encoder::H264Encoder::new(
-                    capturer.width(),
-                    capturer.height(),
+                    aligner.width(),
+                    aligner.height(),
                     config.capture.fps,
-                    2000,
+                    bitrate,
                 )?)
             }
             #[cfg(not(feature = "h264"))]
             {
                 Box::new(encoder::SimpleEncoder::new(
-                    capturer.width(),
-                    capturer.height(),
+                    aligner.width(),
+                    aligner.height(),
                     config.capture.fps,
-                    2000,
+                    bitrate,
                 )?)
             }
         }
     };
 
     // 创建网络客户端
-    let client = network::VideoClient::new(
+    let client = Arc::new(network::VideoClient::with_config(
         config.server.url.clone(),
         config.server.device_id.clone(),
-    );
+        network::VideoClientConfig {
+            api_key: config.security.api_key.clone(),
+            e2ee: config.security.e2ee,
+            #[cfg(feature = "security")]
+            client_identity: client_identity(&config.security)?,
+            keepalive: config.server.keepalive(),
+            compression: config.server.compression,
+            congestion: network::pacing::CongestionConfig {
+                initial_bitrate_kbps: bitrate,
+                rate_limit_kbps: rate_limit,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    ));
+
+    // 限速时段切换时调整发送队列的上限 (编码码率经拥塞反馈跟随)
+    if !bandwidth.is_unlimited() {
+        let client = client.clone();
+        tokio::spawn(async move {
+            let mut current = rate_limit;
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(30));
+            loop {
+                interval.tick().await;
+                let limit = bandwidth.limit_now();
+                quality::bandwidth::monitor().set_limit(limit, 1);
+                let kbps = limit.per_viewer(1);
+                if kbps != current {
+                    info!("带宽上限: {:?} -> {:?} kbps", current, kbps);
+                    current = kbps;
+                    client.set_rate_limit(kbps);
+                }
+            }
+        });
+    }
 
     // 创建输入模拟器
     info!("初始化输入模拟器...");
@@ -219,9 +422,11 @@ async fn run_service_mode() -> Result<()> {
     let simulator = Arc::new(Mutex::new(input_simulator));
     let mut input_receiver = client.take_input_receiver().await?;
 
-    // 启动输入事件处理任务
+    // 启动输入事件处理任务 (坐标需从视频画面映射回原始屏幕)
+    let input_aligner = aligner.clone();
     let simulator_task = async move {
         while let Some(event) = input_receiver.recv().await {
+            let event = input_aligner.map_input_event(event);
             let mut sim = simulator.lock().await;
             if let Err(e) = sim.handle_event(&event) {
                 error!("处理输入事件失败: {}", e);
@@ -230,6 +435,14 @@ async fn run_service_mode() -> Result<()> {
     };
     let _simulator_handle = tokio::spawn(simulator_task);
 
+    // 推流管线 (发送队列积压时由拥塞反馈降低编码码率，丢帧和场景切换后请求关键帧)
+    let mut pipeline = engine::StreamingPipeline::new(capturer, encoder, aligner, config.capture.fps);
+    pipeline.add_stage(Box::new(engine::SceneChangeStage::default()));
+    pipeline.add_sink(client.clone()).await;
+
+    // 登记到设备清单 (配置了 fleet.server 时)
+    let presence = network::presence::PresenceClient::from_config(&config, None).map(|client| client.spawn());
+
     // 连接到服务器
     if let Err(e) = client.connect().await {
         error!("连接服务器失败: {}", e);
@@ -238,70 +451,25 @@ async fn run_service_mode() -> Result<()> {
         info!("连接成功!");
     }
 
-    // 设置退出信号处理
-    let ctrl_c = async {
+    // 收到退出信号后推流循环在当前帧结束时返回
+    let cancel = tokio_util::sync::CancellationToken::new();
+    let shutdown = cancel.clone();
+    tokio::spawn(async move {
         if let Err(e) = signal::ctrl_c().await {
             error!("无法监听 Ctrl+C 信号: {}", e);
-            tokio::time::sleep(Duration::from_secs(u64::MAX)).await;
+            return;
         }
         info!("收到退出信号，正在关闭...");
-    };
+        shutdown.cancel();
+    });
 
-    // 主捕获循环
-    let capture_task = async {
-        let frame_interval = Duration::from_millis(1000 / config.capture.fps as u64);
-        let mut frame_count = 0u64;
-        let mut last_report = std::time::Instant::now();
-
-        loop {
-            let start = std::time::Instant::now();
-
-            match capturer.capture() {
-                Ok(frame) => {
-                    match encoder.encode(&frame) {
-                        Ok(Some(packet)) => {
-                            if client.is_connected().await {
-                                if let Err(e) = client.send_packet(packet.data, packet.is_key_frame).await {
-                                    error!("发送失败: {}", e);
-                                }
-                            }
-
-                            frame_count += 1;
-
-                            if last_report.elapsed() >= Duration::from_secs(1) {
-                                let fps = frame_count as f64 / last_report.elapsed().as_secs_f64();
-                                info!("捕获: {} 帧, 实际 FPS: {:.1}", frame_count, fps);
-                                frame_count = 0;
-                                last_report = std::time::Instant::now();
-                            }
-                        }
-                        Ok(None) => {}
-                        Err(e) => {
-                            error!("编码失败: {}", e);
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("捕获失败: {}", e);
-                }
-            }
+    pipeline.run(cancel).await;
 
-            let elapsed = start.elapsed();
-            if elapsed < frame_interval {
-                tokio::time::sleep(frame_interval - elapsed).await;
-            }
-        }
-    };
-
-    tokio::select! {
-        _ = ctrl_c => {
-            info!("正在退出...");
-        }
-        _ = capture_task => {}
-    }
-
-    capturer.stop()?;
+    pipeline.stop()?;
     client.disconnect().await?;
+    if let Some(presence) = presence {
+        presence.abort();
+    }
 
     info!("sscontrol 已退出");
     Ok(())
@@ -1,11 +1,17 @@
 //! NAT 类型检测模块
 //!
-//! 无需 STUN 服务器，主动探测 NAT 行为
+//! 通过 STUN Binding 请求获取映射地址，比较不同服务器看到的映射判断映射行为，
+//! 再用 CHANGE-REQUEST 判断过滤行为 (服务器不支持时按端口受限处理)
 
+use super::stun::{self, ChangeRequest};
 use crate::nat::NatConfig;
 use anyhow::{anyhow, Result};
-use std::net::{SocketAddr, UdpSocket};
+use std::net::SocketAddr;
 use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// CHANGE-REQUEST 探测的等待时间 (多数公共服务器不支持，不必等满重传周期)
+const FILTERING_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// NAT 类型分类
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -34,6 +40,18 @@ pub struct NatBehavior {
     pub hairpinning: bool, // 是否支持 hairpinning
 }
 
+impl NatBehavior {
+    fn blocked() -> Self {
+        Self {
+            nat_type: NatType::Blocked,
+            external_ip: None,
+            external_port: None,
+            port_allocation_pattern: PortAllocationPattern::Fixed,
+            hairpinning: false,
+        }
+    }
+}
+
 /// 端口分配模式
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PortAllocationPattern {
@@ -50,24 +68,12 @@ pub enum PortAllocationPattern {
 /// NAT 检测器
 pub struct NatDetector {
     config: NatConfig,
-    probe_endpoints: Vec<SocketAddr>,
 }
 
 impl NatDetector {
     /// 创建新的 NAT 检测器
     pub fn new(config: NatConfig) -> Self {
-        // 使用公共 HTTP/HTTPS 端点作为探测目标
-        // 这些端口通常不被防火墙阻止
-        let probe_endpoints = vec![
-            "1.1.1.1:80".parse().unwrap(),     // Cloudflare DNS
-            "8.8.8.8:80".parse().unwrap(),     // Google DNS
-            "1.0.0.1:443".parse().unwrap(),    // Cloudflare DNS
-        ];
-
-        Self {
-            config,
-            probe_endpoints,
-        }
+        Self { config }
     }
 
     /// 使用默认配置创建检测器
@@ -77,131 +83,118 @@ impl NatDetector {
 
     /// 检测 NAT 类型
     ///
-    /// 通过多次探测不同的端点，分析端口分配模式来确定 NAT 类型
+    /// 同一个本地端口依次向配置的 STUN 服务器发送 Binding 请求，
+    /// 分析映射端口的分配模式来确定 NAT 类型 (至少两个不同服务器才能识别对称 NAT)
     pub async fn detect_nat_type(&self) -> Result<NatBehavior> {
-        tracing::info!("开始 NAT 类型检测...");
-
-        // 绑定本地 UDP socket
-        let socket = UdpSocket::bind("0.0.0.0:0")?;
-        socket.set_read_timeout(Some(Duration::from_millis(2000)))?;
-
-        let local_addr = socket.local_addr()?;
-        tracing::debug!("本地地址: {}", local_addr);
-
-        // 第一步：检测是否有 NAT
-        let (external_ip1, external_port1) = self
-            .probe_external_addr(&socket, &self.probe_endpoints[0])
-            .await?;
-
-        if external_ip1.is_none() {
-            tracing::warn!("无法获取外部地址，可能被防火墙阻止");
-            return Ok(NatBehavior {
-                nat_type: NatType::Blocked,
-                external_ip: None,
-                external_port: None,
-                port_allocation_pattern: PortAllocationPattern::Fixed,
-                hairpinning: false,
-            });
+        if self.config.stun_servers.is_empty() {
+            return Err(anyhow!("未配置 STUN 服务器"));
         }
+        tracing::info!("开始 NAT 类型检测...");
 
-        let external_ip1 = external_ip1.unwrap();
-        let external_port1 = external_port1.unwrap();
-
-        // 检查是否有 NAT (比较本地 IP 和外部 IP)
-        let has_nat = self.has_nat(&local_addr, &external_ip1);
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        let local_port = socket.local_addr()?.port();
+        tracing::debug!("本地端口: {}", local_port);
 
-        if !has_nat {
-            tracing::info!("未检测到 NAT (公网 IP)");
-            return Ok(NatBehavior {
-                nat_type: NatType::Open,
-                external_ip: Some(external_ip1),
-                external_port: Some(external_port1),
-                port_allocation_pattern: PortAllocationPattern::Fixed,
-                hairpinning: false,
-            });
+        // 第一步：收集各服务器看到的映射地址，分析映射行为
+        let mappings = self.collect_mappings(&socket).await;
+        let Some(mut behavior) = self.mapping_behavior(local_port, &mappings) else {
+            tracing::warn!("所有 STUN 服务器均无响应，可能被防火墙阻止 UDP");
+            return Ok(NatBehavior::blocked());
+        };
+        if behavior.nat_type != NatType::PortRestrictedCone {
+            tracing::info!(
+                "NAT 检测完成: {:?} (端口分配: {:?})",
+                behavior.nat_type,
+                behavior.port_allocation_pattern
+            );
+            return Ok(behavior);
         }
 
-        // 第二步：检测端口分配模式
-        let mut port_mappings = vec![(external_port1, self.probe_endpoints[0])];
+        // 第二步：分析过滤行为
+        let server = mappings[0].1;
+        behavior.nat_type = if self.probe_filtering(&socket, server, true).await {
+            NatType::FullCone
+        } else if self.probe_filtering(&socket, server, false).await {
+            NatType::RestrictedCone
+        } else {
+            NatType::PortRestrictedCone
+        };
 
-        // 向多个端点探测，收集端口映射
-        for endpoint in &self.probe_endpoints[1..] {
-            let (_, ext_port) = self.probe_external_addr(&socket, endpoint).await?;
-            if let Some(port) = ext_port {
-                port_mappings.push((port, *endpoint));
-            }
-        }
+        tracing::info!("NAT 检测完成: {:?}", behavior.nat_type);
+        tracing::info!("外部地址: {}", mappings[0].0);
+        Ok(behavior)
+    }
 
-        // 分析端口分配模式
-        let allocation_pattern = self.analyze_port_allocation(&port_mappings);
-
-        // 第三步：确定 NAT 类型
-        let nat_type = match allocation_pattern {
-            PortAllocationPattern::Fixed => NatType::FullCone,
-            PortAllocationPattern::SequentialIncrement(_) => {
-                // 检查增量是否一致
-                if port_mappings.windows(2).all(|w| w[1].0 == w[0].0 + 1) {
-                    NatType::PortRestrictedCone
-                } else {
-                    NatType::Symmetric
+    /// 同一个 socket 依次向各 STUN 服务器发送 Binding 请求，返回 (映射地址, 服务器地址)
+    pub async fn collect_mappings(&self, socket: &UdpSocket) -> Vec<(SocketAddr, SocketAddr)> {
+        let mut mappings: Vec<(SocketAddr, SocketAddr)> = Vec::new();
+        for url in &self.config.stun_servers {
+            let server = match stun::resolve_server(url).await {
+                Ok(server) => server,
+                Err(e) => {
+                    tracing::warn!("{}", e);
+                    continue;
                 }
+            };
+            if mappings.iter().any(|(_, s)| *s == server) {
+                continue;
             }
-            PortAllocationPattern::Random | PortAllocationPattern::HashBased => {
-                NatType::Symmetric
+            match stun::binding(socket, server, ChangeRequest::default()).await {
+                Ok(mapped) => {
+                    tracing::debug!("{} 看到的映射地址: {}", server, mapped);
+                    mappings.push((mapped, server));
+                }
+                Err(e) => tracing::debug!("STUN 探测失败: {}", e),
             }
-        };
-
-        tracing::info!("NAT 检测完成: {:?}", nat_type);
-        tracing::info!("外部地址: {}:{}", external_ip1, external_port1);
-        tracing::info!("端口分配模式: {:?}", allocation_pattern);
+        }
+        mappings
+    }
 
-        Ok(NatBehavior {
+    /// 根据映射地址判断映射行为 (没有任何映射时返回 None)
+    ///
+    /// 只能区分无 NAT、对称 NAT 和锥形 NAT；锥形 NAT 的过滤行为需另行探测，
+    /// 这里按最严格的端口受限锥形处理
+    pub fn mapping_behavior(&self, local_port: u16, mappings: &[(SocketAddr, SocketAddr)]) -> Option<NatBehavior> {
+        let &(mapped, _) = mappings.first()?;
+        let behavior = |nat_type, pattern| NatBehavior {
             nat_type,
-            external_ip: Some(external_ip1),
-            external_port: Some(external_port1),
-            port_allocation_pattern: allocation_pattern,
+            external_ip: Some(mapped.ip().to_string()),
+            external_port: Some(mapped.port()),
+            port_allocation_pattern: pattern,
             hairpinning: false, // TODO: 实现 hairpinning 检测
-        })
-    }
+        };
 
-    /// 探测外部地址
-    ///
-    /// 向目标端点发送数据包，观察外部 IP 和端口
-    async fn probe_external_addr(
-        &self,
-        socket: &UdpSocket,
-        target: &SocketAddr,
-    ) -> Result<(Option<String>, Option<u16>)> {
-        // 发送探测包
-        let probe_data = b"NAT_PROBE";
-        socket.send_to(probe_data, target)?;
-
-        // 等待响应 (使用超时)
-        let mut buf = [0u8; 1024];
-        match socket.recv_from(&mut buf) {
-            Ok((size, from)) => {
-                // 解析响应
-                let response = String::from_utf8_lossy(&buf[..size]);
-                tracing::trace!("收到响应: {} from {}", response, from);
-
-                // 从响应中提取外部地址
-                // 注意: 这里需要探测服务器返回外部地址
-                // 实际实现中需要专用探测协议
-                // 这里简化处理，使用 from 地址
-                Ok((Some(from.ip().to_string()), Some(from.port())))
-            }
-            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                // 超时
-                Ok((None, None))
-            }
-            Err(e) => Err(anyhow!("探测失败: {}", e)),
+        // 映射地址就是本机地址：没有 NAT
+        if !self.has_nat(local_port, &mapped) {
+            tracing::info!("未检测到 NAT (公网 IP)");
+            return Some(behavior(NatType::Open, PortAllocationPattern::Fixed));
         }
+        tracing::debug!("检测到 NAT，外部地址: {}", mapped);
+
+        let ports: Vec<(u16, SocketAddr)> = mappings.iter().map(|(m, s)| (m.port(), *s)).collect();
+        let allocation_pattern = self.analyze_port_allocation(&ports);
+        let same_ip = mappings.iter().all(|(m, _)| m.ip() == mapped.ip());
+        let nat_type = if allocation_pattern != PortAllocationPattern::Fixed || !same_ip {
+            NatType::Symmetric
+        } else {
+            NatType::PortRestrictedCone
+        };
+        Some(behavior(nat_type, allocation_pattern))
+    }
+
+    /// 请求服务器从其他地址回复，收到说明 NAT 允许该来源的入站数据
+    async fn probe_filtering(&self, socket: &UdpSocket, server: SocketAddr, change_ip: bool) -> bool {
+        let change = ChangeRequest { change_ip, change_port: true };
+        matches!(
+            tokio::time::timeout(FILTERING_PROBE_TIMEOUT, stun::binding(socket, server, change)).await,
+            Ok(Ok(_))
+        )
     }
 
-    /// 检查是否有 NAT
-    fn has_nat(&self, local_addr: &SocketAddr, external_ip: &str) -> bool {
-        let local_ip = local_addr.ip().to_string();
-        local_ip != external_ip
+    /// 检查是否有 NAT (映射地址与本机地址不同)
+    fn has_nat(&self, local_port: u16, mapped: &SocketAddr) -> bool {
+        let local_ip = local_ip_address::local_ip().ok();
+        local_ip != Some(mapped.ip()) || local_port != mapped.port()
     }
 
     /// 分析端口分配模式
@@ -1,12 +1,15 @@
 //! NAT 穿透模块
 //!
-//! 实现零依赖的 NAT 穿透技术，无需第三方 STUN/TURN 服务器
+//! 实现零依赖的 NAT 穿透技术: 内置 STUN 客户端 (可配合自建 STUN 服务) 和
+//! 路由器端口映射 (UPnP / NAT-PMP / PCP)，无需第三方库
 
 // NAT 穿透模块尚未完全激活，标记为允许死代码和未使用导入
 #![allow(dead_code, unused_imports)]
 
 pub mod detector;
+pub mod port_mapping;
 pub mod predictive_punching;
+pub mod stun;
 
 pub use detector::{NatDetector, NatType};
 
@@ -30,6 +33,8 @@ pub struct NatConfig {
     pub punch_timeout_ms: u64,
     /// 是否启用并行打洞
     pub enable_parallel_punch: bool,
+    /// NAT 检测使用的 STUN 服务器 (`stun:host:port`，至少两个不同服务器才能识别对称 NAT)
+    pub stun_servers: Vec<String>,
 }
 
 impl Default for NatConfig {
@@ -38,6 +43,7 @@ impl Default for NatConfig {
             prediction_attempts: 100, // 尝试 100 个预测端口
             punch_timeout_ms: 3000,   // 3 秒超时
             enable_parallel_punch: true,
+            stun_servers: Vec::new(),
         }
     }
 }
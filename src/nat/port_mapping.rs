@@ -0,0 +1,924 @@
+//! 路由器自动端口映射 (UPnP IGD / NAT-PMP / PCP)
+//!
+//! 被控端启动时请求家用路由器把信令端口 (TCP) 和 WebRTC 端口 (UDP) 转发到本机，
+//! 成功后直接用路由器的公网地址连接，很多家庭网络下不再需要 Cloudflare Tunnel。
+//!
+//! 三种协议并行探测:
+//! - PCP (RFC 6887) / NAT-PMP (RFC 6886): 向默认网关的 UDP 5351 发送请求，
+//!   网关只支持 NAT-PMP 时会以版本号 0 回复，随即降级
+//! - UPnP IGD: SSDP 组播发现网关，HTTP 获取设备描述后通过 SOAP 调用 WANIPConnection
+//!
+//! 映射带租期，`PortMapper` 在后台按租期一半续约，`close()` 时删除所有映射。
+
+use anyhow::{anyhow, bail, Result};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+/// NAT-PMP / PCP 服务端口
+const PMP_PORT: u16 = 5351;
+/// NAT-PMP / PCP 首次重传间隔 (RFC 6886 为 250ms，之后翻倍)
+const PMP_INITIAL_RTO: Duration = Duration::from_millis(250);
+/// NAT-PMP / PCP 最多发送次数 (不按 RFC 的 9 次，避免没有网关支持时启动过慢)
+const PMP_MAX_TRANSMISSIONS: u32 = 3;
+
+/// SSDP 组播地址
+const SSDP_ADDR: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(239, 255, 255, 250), 1900);
+/// 等待 SSDP 响应的时间
+const SSDP_TIMEOUT: Duration = Duration::from_secs(2);
+/// UPnP HTTP 请求超时
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 请求的映射租期 (到期前续约)
+pub const DEFAULT_LIFETIME: Duration = Duration::from_secs(7200);
+/// 映射描述 (显示在路由器管理页面)
+const MAPPING_DESCRIPTION: &str = "sscontrol";
+
+/// 传输协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl Protocol {
+    /// NAT-PMP 操作码
+    fn pmp_opcode(&self) -> u8 {
+        match self {
+            Self::Udp => 1,
+            Self::Tcp => 2,
+        }
+    }
+
+    /// IANA 协议号 (PCP)
+    fn iana_number(&self) -> u8 {
+        match self {
+            Self::Tcp => 6,
+            Self::Udp => 17,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Tcp => "TCP",
+            Self::Udp => "UDP",
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// 建立映射使用的协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappingMethod {
+    Upnp,
+    NatPmp,
+    Pcp,
+}
+
+impl fmt::Display for MappingMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Upnp => "UPnP",
+            Self::NatPmp => "NAT-PMP",
+            Self::Pcp => "PCP",
+        })
+    }
+}
+
+/// 已建立的端口映射
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortMapping {
+    pub protocol: Protocol,
+    pub internal_port: u16,
+    /// 路由器分配的外部端口 (可能与请求的不同)
+    pub external_port: u16,
+    pub lifetime: Duration,
+    /// 外部地址 (只有 PCP 在映射响应中返回)
+    pub external_ip: Option<Ipv4Addr>,
+}
+
+/// 支持端口映射的网关
+#[derive(Debug, Clone)]
+enum Gateway {
+    Pcp { server: SocketAddr, client_ip: Ipv4Addr },
+    NatPmp { server: SocketAddr },
+    Upnp(IgdService),
+}
+
+impl Gateway {
+    fn method(&self) -> MappingMethod {
+        match self {
+            Self::Pcp { .. } => MappingMethod::Pcp,
+            Self::NatPmp { .. } => MappingMethod::NatPmp,
+            Self::Upnp(_) => MappingMethod::Upnp,
+        }
+    }
+
+    /// 请求映射 (lifetime 为 0 表示删除)
+    async fn map(&self, protocol: Protocol, port: u16, lifetime: Duration) -> Result<PortMapping> {
+        match self {
+            Self::Pcp { server, client_ip } => pcp_map(*server, *client_ip, protocol, port, lifetime).await,
+            Self::NatPmp { server } => pmp_map(*server, protocol, port, lifetime).await,
+            Self::Upnp(service) => service.add_port_mapping(protocol, port, lifetime).await,
+        }
+    }
+
+    async fn unmap(&self, mapping: &PortMapping) -> Result<()> {
+        match self {
+            Self::Upnp(service) => service.delete_port_mapping(mapping).await,
+            _ => self.map(mapping.protocol, mapping.internal_port, Duration::ZERO).await.map(|_| ()),
+        }
+    }
+
+    async fn external_ip(&self) -> Result<Ipv4Addr> {
+        match self {
+            // PCP 没有单独的查询操作，外部地址随映射响应返回
+            Self::Pcp { .. } => Err(anyhow!("PCP 映射响应中没有外部地址")),
+            Self::NatPmp { server } => pmp_external_ip(*server).await,
+            Self::Upnp(service) => service.external_ip().await,
+        }
+    }
+}
+
+/// 端口映射管理器
+///
+/// 持有网关上的所有映射并在后台续约，退出前调用 `close()` 删除映射
+pub struct PortMapper {
+    gateway: Gateway,
+    mappings: Arc<Mutex<Vec<PortMapping>>>,
+    external_ip: Option<Ipv4Addr>,
+    renew_task: JoinHandle<()>,
+}
+
+impl PortMapper {
+    /// 发现网关并映射所有端口 (外部端口尽量与内部端口相同)
+    ///
+    /// 找不到支持映射的网关或所有端口都映射失败时返回错误
+    pub async fn start(ports: &[(Protocol, u16)]) -> Result<Self> {
+        let gateway = discover_gateway().await?;
+        let method = gateway.method();
+
+        let mut mappings = Vec::new();
+        for &(protocol, port) in ports {
+            match gateway.map(protocol, port, DEFAULT_LIFETIME).await {
+                Ok(mapping) => {
+                    tracing::info!(
+                        "{} 端口映射: {} {} -> 外部端口 {} (租期 {} 秒)",
+                        method,
+                        protocol,
+                        port,
+                        mapping.external_port,
+                        mapping.lifetime.as_secs()
+                    );
+                    mappings.push(mapping);
+                }
+                Err(e) => tracing::warn!("{} 端口映射失败 ({} {}): {}", method, protocol, port, e),
+            }
+        }
+        if mappings.is_empty() {
+            bail!("{} 网关拒绝了所有端口映射请求", method);
+        }
+
+        let external_ip = match mappings.iter().find_map(|m| m.external_ip) {
+            Some(ip) => Ok(ip),
+            None => gateway.external_ip().await,
+        };
+        let external_ip = match external_ip {
+            Ok(ip) if !ip.is_unspecified() => Some(ip),
+            Ok(_) => None,
+            Err(e) => {
+                tracing::warn!("获取路由器公网地址失败: {}", e);
+                None
+            }
+        };
+        if let Some(ip) = external_ip {
+            if is_private(ip) {
+                warn_double_nat(ip);
+            }
+        }
+
+        let mappings = Arc::new(Mutex::new(mappings));
+        let renew_task = tokio::spawn(renew_loop(gateway.clone(), mappings.clone()));
+        Ok(Self {
+            gateway,
+            mappings,
+            external_ip,
+            renew_task,
+        })
+    }
+
+    /// 使用的映射协议
+    pub fn method(&self) -> MappingMethod {
+        self.gateway.method()
+    }
+
+    /// 路由器的公网 IP
+    pub fn external_ip(&self) -> Option<Ipv4Addr> {
+        self.external_ip
+    }
+
+    /// 内部端口对应的外部端口
+    pub async fn external_port(&self, protocol: Protocol, internal_port: u16) -> Option<u16> {
+        self.mappings
+            .lock()
+            .await
+            .iter()
+            .find(|m| m.protocol == protocol && m.internal_port == internal_port)
+            .map(|m| m.external_port)
+    }
+
+    /// 该协议的端口是否都已映射且外部端口与内部端口相同
+    ///
+    /// ICE 按本地端口通告公网候选，只有端口不变时才能直接使用路由器的公网 IP
+    pub async fn preserves_ports(&self, protocol: Protocol) -> bool {
+        let mappings = self.mappings.lock().await;
+        let mut mapped = mappings.iter().filter(|m| m.protocol == protocol).peekable();
+        mapped.peek().is_some() && mapped.all(|m| m.external_port == m.internal_port)
+    }
+
+    /// 停止续约并删除所有映射
+    pub async fn close(self) {
+        self.renew_task.abort();
+        for mapping in self.mappings.lock().await.drain(..) {
+            match self.gateway.unmap(&mapping).await {
+                Ok(()) => tracing::info!(
+                    "已删除端口映射: {} {}",
+                    mapping.protocol,
+                    mapping.external_port
+                ),
+                Err(e) => tracing::warn!(
+                    "删除端口映射失败 ({} {}): {}",
+                    mapping.protocol,
+                    mapping.external_port,
+                    e
+                ),
+            }
+        }
+    }
+}
+
+/// 在租期过半时续约
+async fn renew_loop(gateway: Gateway, mappings: Arc<Mutex<Vec<PortMapping>>>) {
+    loop {
+        let shortest = mappings
+            .lock()
+            .await
+            .iter()
+            .map(|m| m.lifetime)
+            .min()
+            .unwrap_or(DEFAULT_LIFETIME);
+        tokio::time::sleep((shortest / 2).max(Duration::from_secs(30))).await;
+
+        let mut mappings = mappings.lock().await;
+        for mapping in mappings.iter_mut() {
+            match gateway.map(mapping.protocol, mapping.internal_port, DEFAULT_LIFETIME).await {
+                Ok(renewed) => *mapping = renewed,
+                Err(e) => tracing::warn!(
+                    "端口映射续约失败 ({} {}): {}",
+                    mapping.protocol,
+                    mapping.internal_port,
+                    e
+                ),
+            }
+        }
+    }
+}
+
+fn is_private(ip: Ipv4Addr) -> bool {
+    // 100.64.0.0/10 为运营商级 NAT 地址
+    ip.is_private() || (ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64)
+}
+
+fn warn_double_nat(ip: Ipv4Addr) {
+    tracing::warn!(
+        "路由器的外部地址 {} 不是公网地址 (多层 NAT)，端口映射后仍无法从公网直接访问",
+        ip
+    );
+}
+
+/// 并行探测 PCP/NAT-PMP 与 UPnP，优先使用 PCP/NAT-PMP (无需 HTTP，续约开销小)
+async fn discover_gateway() -> Result<Gateway> {
+    let pmp = async {
+        let gateway = default_gateway().ok_or_else(|| anyhow!("无法确定默认网关"))?;
+        probe_pmp(SocketAddr::new(IpAddr::V4(gateway), PMP_PORT)).await
+    };
+    let (pmp, upnp) = tokio::join!(pmp, discover_igd());
+    match (pmp, upnp) {
+        (Ok(gateway), _) => Ok(gateway),
+        (Err(_), Ok(service)) => Ok(Gateway::Upnp(service)),
+        (Err(pmp), Err(upnp)) => Err(anyhow!(
+            "未发现支持端口映射的路由器 (PCP/NAT-PMP: {}; UPnP: {})",
+            pmp,
+            upnp
+        )),
+    }
+}
+
+/// 判断网关支持 PCP 还是 NAT-PMP
+async fn probe_pmp(server: SocketAddr) -> Result<Gateway> {
+    let client_ip = local_ip_towards(server)?;
+    // 探测用的 PCP ANNOUNCE 请求 (租期 0)，只支持 NAT-PMP 的网关回复版本号 0
+    let request = encode_pcp_request(PCP_OPCODE_ANNOUNCE, Duration::ZERO, client_ip, None);
+    let response = pmp_transact(server, &request).await?;
+    match response.first() {
+        Some(&PCP_VERSION) => Ok(Gateway::Pcp { server, client_ip }),
+        Some(&PMP_VERSION) => Ok(Gateway::NatPmp { server }),
+        _ => bail!("无法识别的 PCP 响应"),
+    }
+}
+
+/// 本机访问 target 时使用的 IPv4 地址
+fn local_ip_towards(target: SocketAddr) -> Result<Ipv4Addr> {
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect(target)?;
+    match socket.local_addr()?.ip() {
+        IpAddr::V4(ip) => Ok(ip),
+        IpAddr::V6(ip) => Err(anyhow!("不支持 IPv6 地址: {}", ip)),
+    }
+}
+
+/// 获取默认网关
+fn default_gateway() -> Option<Ipv4Addr> {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_to_string("/proc/net/route")
+            .ok()
+            .and_then(|table| parse_proc_route(&table))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        // route -n get default 输出中的 "gateway: 192.168.1.1"
+        let output = std::process::Command::new("route")
+            .args(["-n", "get", "default"])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("gateway:"))
+            .and_then(|gateway| gateway.trim().parse().ok())
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // route print 的默认路由行: "0.0.0.0  0.0.0.0  192.168.1.1  192.168.1.5  25"
+        let output = std::process::Command::new("route")
+            .args(["print", "-4", "0.0.0.0"])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout).lines().find_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["0.0.0.0", "0.0.0.0", gateway, ..] => gateway.parse().ok(),
+                _ => None,
+            }
+        })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        None
+    }
+}
+
+/// 解析 /proc/net/route，取目标为 0.0.0.0 的路由的网关 (小端十六进制)
+#[cfg(any(target_os = "linux", test))]
+fn parse_proc_route(table: &str) -> Option<Ipv4Addr> {
+    table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 3 || fields[1] != "00000000" {
+            return None;
+        }
+        let raw = u32::from_str_radix(fields[2], 16).ok()?;
+        Some(Ipv4Addr::from(raw.to_le_bytes()))
+    })
+}
+
+// ===== NAT-PMP (RFC 6886) =====
+
+const PMP_VERSION: u8 = 0;
+/// 响应操作码 = 请求操作码 + 128
+const PMP_RESPONSE_BIT: u8 = 0x80;
+
+/// 发送请求并等待响应 (按 RFC 6886 重传)
+async fn pmp_transact(server: SocketAddr, request: &[u8]) -> Result<Vec<u8>> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(server).await?;
+    let mut rto = PMP_INITIAL_RTO;
+    let mut buf = [0u8; 1100];
+    for _ in 0..PMP_MAX_TRANSMISSIONS {
+        socket.send(request).await?;
+        if let Ok(received) = tokio::time::timeout(rto, socket.recv(&mut buf)).await {
+            let size = received?;
+            return Ok(buf[..size].to_vec());
+        }
+        rto *= 2;
+    }
+    Err(anyhow!("网关 {} 无响应", server))
+}
+
+fn pmp_result_error(code: u16) -> anyhow::Error {
+    let reason = match code {
+        1 => "不支持的版本",
+        2 => "未授权 (端口映射已在路由器上关闭)",
+        3 => "网络故障",
+        4 => "资源不足",
+        5 => "不支持的操作",
+        _ => "未知错误",
+    };
+    anyhow!("NAT-PMP 错误 {}: {}", code, reason)
+}
+
+/// 编码映射请求
+fn encode_pmp_map(protocol: Protocol, port: u16, lifetime: Duration) -> [u8; 12] {
+    let mut request = [0u8; 12];
+    request[0] = PMP_VERSION;
+    request[1] = protocol.pmp_opcode();
+    request[4..6].copy_from_slice(&port.to_be_bytes());
+    // 删除映射时建议的外部端口必须为 0
+    let suggested = if lifetime.is_zero() { 0 } else { port };
+    request[6..8].copy_from_slice(&suggested.to_be_bytes());
+    request[8..12].copy_from_slice(&(lifetime.as_secs() as u32).to_be_bytes());
+    request
+}
+
+/// 解码映射响应
+fn decode_pmp_map(response: &[u8], protocol: Protocol) -> Result<PortMapping> {
+    if response.len() < 16 || response[1] != PMP_RESPONSE_BIT | protocol.pmp_opcode() {
+        bail!("无效的 NAT-PMP 映射响应");
+    }
+    let code = u16::from_be_bytes([response[2], response[3]]);
+    if code != 0 {
+        return Err(pmp_result_error(code));
+    }
+    Ok(PortMapping {
+        protocol,
+        internal_port: u16::from_be_bytes([response[8], response[9]]),
+        external_port: u16::from_be_bytes([response[10], response[11]]),
+        lifetime: Duration::from_secs(
+            u32::from_be_bytes([response[12], response[13], response[14], response[15]]) as u64,
+        ),
+        external_ip: None,
+    })
+}
+
+async fn pmp_map(server: SocketAddr, protocol: Protocol, port: u16, lifetime: Duration) -> Result<PortMapping> {
+    let response = pmp_transact(server, &encode_pmp_map(protocol, port, lifetime)).await?;
+    decode_pmp_map(&response, protocol)
+}
+
+async fn pmp_external_ip(server: SocketAddr) -> Result<Ipv4Addr> {
+    let response = pmp_transact(server, &[PMP_VERSION, 0]).await?;
+    if response.len() < 12 || response[1] != PMP_RESPONSE_BIT {
+        bail!("无效的 NAT-PMP 外部地址响应");
+    }
+    let code = u16::from_be_bytes([response[2], response[3]]);
+    if code != 0 {
+        return Err(pmp_result_error(code));
+    }
+    Ok(Ipv4Addr::new(response[8], response[9], response[10], response[11]))
+}
+
+// ===== PCP (RFC 6887) =====
+
+const PCP_VERSION: u8 = 2;
+const PCP_OPCODE_ANNOUNCE: u8 = 0;
+const PCP_OPCODE_MAP: u8 = 1;
+const PCP_HEADER_LEN: usize = 24;
+const PCP_MAP_LEN: usize = 36;
+
+/// 编码 PCP 请求 (map 为 MAP 操作的 (随机数, 协议, 内部端口))
+fn encode_pcp_request(
+    opcode: u8,
+    lifetime: Duration,
+    client_ip: Ipv4Addr,
+    map: Option<([u8; 12], Protocol, u16)>,
+) -> Vec<u8> {
+    let mut request = Vec::with_capacity(PCP_HEADER_LEN + PCP_MAP_LEN);
+    request.push(PCP_VERSION);
+    request.push(opcode);
+    request.extend_from_slice(&[0, 0]);
+    request.extend_from_slice(&(lifetime.as_secs() as u32).to_be_bytes());
+    request.extend_from_slice(&client_ip.to_ipv6_mapped().octets());
+
+    if let Some((nonce, protocol, port)) = map {
+        request.extend_from_slice(&nonce);
+        request.push(protocol.iana_number());
+        request.extend_from_slice(&[0, 0, 0]);
+        request.extend_from_slice(&port.to_be_bytes());
+        // 建议的外部端口与地址 (删除时为 0)
+        let suggested = if lifetime.is_zero() { 0 } else { port };
+        request.extend_from_slice(&suggested.to_be_bytes());
+        request.extend_from_slice(&Ipv4Addr::UNSPECIFIED.to_ipv6_mapped().octets());
+    }
+    request
+}
+
+/// 解码 PCP MAP 响应
+fn decode_pcp_map(response: &[u8], nonce: &[u8; 12], protocol: Protocol) -> Result<PortMapping> {
+    if response.len() < PCP_HEADER_LEN + PCP_MAP_LEN
+        || response[0] != PCP_VERSION
+        || response[1] != PMP_RESPONSE_BIT | PCP_OPCODE_MAP
+    {
+        bail!("无效的 PCP 映射响应");
+    }
+    let code = response[3];
+    if code != 0 {
+        let reason = match code {
+            2 => "未授权 (端口映射已在路由器上关闭)",
+            8 => "资源不足",
+            11 => "无法提供外部端口",
+            _ => "请求被拒绝",
+        };
+        bail!("PCP 错误 {}: {}", code, reason);
+    }
+    let body = &response[PCP_HEADER_LEN..];
+    if body[..12] != nonce[..] {
+        bail!("PCP 响应的随机数不匹配");
+    }
+    let lifetime = u32::from_be_bytes([response[4], response[5], response[6], response[7]]);
+    let mut octets = [0u8; 16];
+    octets.copy_from_slice(&body[20..36]);
+    let external_ip = Ipv6Addr::from(octets)
+        .to_ipv4_mapped()
+        .ok_or_else(|| anyhow!("PCP 返回了 IPv6 外部地址"))?;
+    Ok(PortMapping {
+        protocol,
+        internal_port: u16::from_be_bytes([body[16], body[17]]),
+        external_port: u16::from_be_bytes([body[18], body[19]]),
+        lifetime: Duration::from_secs(lifetime as u64),
+        external_ip: Some(external_ip),
+    })
+}
+
+async fn pcp_map(
+    server: SocketAddr,
+    client_ip: Ipv4Addr,
+    protocol: Protocol,
+    port: u16,
+    lifetime: Duration,
+) -> Result<PortMapping> {
+    // 随机数由映射的内部端口和协议派生，续约与删除时必须与创建时一致
+    let nonce = pcp_nonce(protocol, port);
+    let request = encode_pcp_request(PCP_OPCODE_MAP, lifetime, client_ip, Some((nonce, protocol, port)));
+    let response = pmp_transact(server, &request).await?;
+    decode_pcp_map(&response, &nonce, protocol)
+}
+
+/// 本进程内固定的映射随机数
+fn pcp_nonce(protocol: Protocol, port: u16) -> [u8; 12] {
+    static SEED: std::sync::OnceLock<[u8; 12]> = std::sync::OnceLock::new();
+    let mut nonce = *SEED.get_or_init(rand::random);
+    nonce[0] ^= protocol.iana_number();
+    let [hi, lo] = port.to_be_bytes();
+    nonce[1] ^= hi;
+    nonce[2] ^= lo;
+    nonce
+}
+
+// ===== UPnP IGD =====
+
+const WAN_SERVICE_TYPES: [&str; 2] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:",
+    "urn:schemas-upnp-org:service:WANPPPConnection:",
+];
+
+/// UPnP 网关的 WAN 连接服务
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IgdService {
+    control_url: url::Url,
+    service_type: String,
+    /// 本机在网关所在网络中的地址 (映射的内部客户端)
+    local_ip: Ipv4Addr,
+}
+
+/// 通过 SSDP 发现网关并读取设备描述
+async fn discover_igd() -> Result<IgdService> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\n\r\n",
+        SSDP_ADDR
+    );
+    socket.send_to(search.as_bytes(), SSDP_ADDR).await?;
+
+    let mut buf = [0u8; 2048];
+    let deadline = tokio::time::Instant::now() + SSDP_TIMEOUT;
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        let (size, _) = received?;
+        let Some(location) = ssdp_location(&String::from_utf8_lossy(&buf[..size])) else {
+            continue;
+        };
+        match fetch_igd_service(&location).await {
+            Ok(service) => return Ok(service),
+            Err(e) => tracing::debug!("跳过 UPnP 设备 {}: {}", location, e),
+        }
+    }
+    Err(anyhow!("未发现 UPnP 网关"))
+}
+
+/// SSDP 响应中的 LOCATION 头
+fn ssdp_location(response: &str) -> Option<String> {
+    response.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("location")
+            .then(|| value.trim().to_string())
+    })
+}
+
+async fn fetch_igd_service(location: &str) -> Result<IgdService> {
+    let location = url::Url::parse(location)?;
+    let (status, description) = http_request(&location, "GET", &[], "").await?;
+    if status != 200 {
+        bail!("获取设备描述失败: HTTP {}", status);
+    }
+    let (service_type, control_path) =
+        parse_igd_description(&description).ok_or_else(|| anyhow!("设备不提供 WAN 连接服务"))?;
+
+    // controlURL 可能是相对路径 (相对于 URLBase 或描述文件地址)
+    let base = xml_text(&description, "URLBase")
+        .and_then(|base| url::Url::parse(base).ok())
+        .unwrap_or_else(|| location.clone());
+    let control_url = base.join(&control_path)?;
+
+    let host = control_url.host_str().unwrap_or_default().to_string();
+    let port = control_url.port_or_known_default().unwrap_or(80);
+    let gateway = tokio::net::lookup_host((host.as_str(), port))
+        .await?
+        .next()
+        .ok_or_else(|| anyhow!("无法解析网关地址: {}", host))?;
+    Ok(IgdService {
+        control_url,
+        service_type,
+        local_ip: local_ip_towards(gateway)?,
+    })
+}
+
+/// 从设备描述中找出 WANIPConnection / WANPPPConnection 服务的 (类型, controlURL)
+fn parse_igd_description(description: &str) -> Option<(String, String)> {
+    description.split("<service>").skip(1).find_map(|service| {
+        let service_type = xml_text(service, "serviceType")?;
+        if !WAN_SERVICE_TYPES.iter().any(|t| service_type.starts_with(t)) {
+            return None;
+        }
+        let control_url = xml_text(service, "controlURL")?;
+        Some((service_type.to_string(), control_url.to_string()))
+    })
+}
+
+/// 取第一个 `<tag>...</tag>` 的文本 (设备描述与 SOAP 响应结构简单，无需完整的 XML 解析)
+fn xml_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    Some(xml[start..end].trim())
+}
+
+impl IgdService {
+    /// 调用 SOAP 操作，返回响应正文
+    async fn soap(&self, action: &str, args: &[(&str, String)]) -> Result<String> {
+        let arguments: String = args
+            .iter()
+            .map(|(name, value)| format!("<{0}>{1}</{0}>", name, value))
+            .collect();
+        let body = format!(
+            "<?xml version=\"1.0\"?>\
+             <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+             <s:Body><u:{action} xmlns:u=\"{service}\">{arguments}</u:{action}></s:Body></s:Envelope>",
+            action = action,
+            service = self.service_type,
+            arguments = arguments
+        );
+        let soap_action = format!("\"{}#{}\"", self.service_type, action);
+        let headers = [
+            ("Content-Type", "text/xml; charset=\"utf-8\""),
+            ("SOAPAction", soap_action.as_str()),
+        ];
+        let (status, response) = http_request(&self.control_url, "POST", &headers, &body).await?;
+        if status != 200 {
+            let code = xml_text(&response, "errorCode").unwrap_or("?");
+            let description = xml_text(&response, "errorDescription").unwrap_or("");
+            bail!("UPnP {} 失败: HTTP {} (错误 {} {})", action, status, code, description);
+        }
+        Ok(response)
+    }
+
+    async fn add_port_mapping(&self, protocol: Protocol, port: u16, lifetime: Duration) -> Result<PortMapping> {
+        let args = |lease: u64| {
+            vec![
+                ("NewRemoteHost", String::new()),
+                ("NewExternalPort", port.to_string()),
+                ("NewProtocol", protocol.name().to_string()),
+                ("NewInternalPort", port.to_string()),
+                ("NewInternalClient", self.local_ip.to_string()),
+                ("NewEnabled", "1".to_string()),
+                ("NewPortMappingDescription", MAPPING_DESCRIPTION.to_string()),
+                ("NewLeaseDuration", lease.to_string()),
+            ]
+        };
+        let mut lifetime = lifetime;
+        if let Err(e) = self.soap("AddPortMapping", &args(lifetime.as_secs())).await {
+            // 部分路由器只支持永久映射 (错误 725 OnlyPermanentLeasesSupported)
+            if !e.to_string().contains("725") {
+                return Err(e);
+            }
+            self.soap("AddPortMapping", &args(0)).await?;
+            lifetime = Duration::ZERO;
+        }
+        Ok(PortMapping {
+            protocol,
+            internal_port: port,
+            external_port: port,
+            // 永久映射也定期重新添加，防止路由器重启后丢失
+            lifetime: if lifetime.is_zero() { DEFAULT_LIFETIME } else { lifetime },
+            external_ip: None,
+        })
+    }
+
+    async fn delete_port_mapping(&self, mapping: &PortMapping) -> Result<()> {
+        let args = [
+            ("NewRemoteHost", String::new()),
+            ("NewExternalPort", mapping.external_port.to_string()),
+            ("NewProtocol", mapping.protocol.name().to_string()),
+        ];
+        self.soap("DeletePortMapping", &args).await.map(|_| ())
+    }
+
+    async fn external_ip(&self) -> Result<Ipv4Addr> {
+        let response = self.soap("GetExternalIPAddress", &[]).await?;
+        let ip = xml_text(&response, "NewExternalIPAddress")
+            .ok_or_else(|| anyhow!("响应中没有外部地址"))?;
+        Ok(ip.parse()?)
+    }
+}
+
+/// 发送 HTTP/1.1 请求 (Connection: close)，返回 (状态码, 正文)
+async fn http_request(url: &url::Url, method: &str, headers: &[(&str, &str)], body: &str) -> Result<(u16, String)> {
+    if url.scheme() != "http" {
+        bail!("不支持的 UPnP 地址: {}", url);
+    }
+    let host = url.host_str().ok_or_else(|| anyhow!("地址缺少主机: {}", url))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}:{}\r\nConnection: close\r\nContent-Length: {}\r\n",
+        method,
+        &url[url::Position::BeforePath..],
+        host,
+        port,
+        body.len()
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+
+    let exchange = async {
+        let mut stream = TcpStream::connect((host, port)).await?;
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        Ok::<_, anyhow::Error>(response)
+    };
+    let response = tokio::time::timeout(HTTP_TIMEOUT, exchange)
+        .await
+        .map_err(|_| anyhow!("请求 {} 超时", url))??;
+    parse_http_response(&String::from_utf8_lossy(&response))
+}
+
+/// 解析 HTTP 响应 (支持 chunked 传输编码)
+fn parse_http_response(response: &str) -> Result<(u16, String)> {
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| anyhow!("不完整的 HTTP 响应"))?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| anyhow!("无效的 HTTP 状态行"))?;
+    let chunked = head.lines().any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("transfer-encoding")
+                && value.trim().eq_ignore_ascii_case("chunked")
+        })
+    });
+    if !chunked {
+        return Ok((status, body.to_string()));
+    }
+
+    let mut decoded = String::new();
+    let mut rest = body;
+    while let Some((size_line, after)) = rest.split_once("\r\n") {
+        let size = usize::from_str_radix(size_line.split(';').next().unwrap_or("").trim(), 16)
+            .map_err(|_| anyhow!("无效的 chunk 长度"))?;
+        if size == 0 || after.len() < size {
+            break;
+        }
+        decoded.push_str(&after[..size]);
+        rest = after[size..].trim_start_matches("\r\n");
+    }
+    Ok((status, decoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_nat_pmp_against_local_gateway() {
+        // 模拟只支持 NAT-PMP 的网关: 对 PCP 请求回复版本不支持，映射时分配外部端口 +1
+        let gateway = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server = gateway.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1100];
+            loop {
+                let (size, from) = gateway.recv_from(&mut buf).await.unwrap();
+                let request = &buf[..size];
+                let response = if request[0] == PCP_VERSION {
+                    vec![PMP_VERSION, PMP_RESPONSE_BIT | request[1], 0, 1, 0, 0, 0, 0]
+                } else {
+                    let mut response = vec![PMP_VERSION, PMP_RESPONSE_BIT | request[1], 0, 0, 0, 0, 0, 7];
+                    response.extend_from_slice(&request[4..6]);
+                    let external = u16::from_be_bytes([request[6], request[7]]) + 1;
+                    response.extend_from_slice(&external.to_be_bytes());
+                    response.extend_from_slice(&request[8..12]);
+                    response
+                };
+                gateway.send_to(&response, from).await.unwrap();
+            }
+        });
+
+        let gateway = probe_pmp(server).await.unwrap();
+        assert_eq!(gateway.method(), MappingMethod::NatPmp);
+        let mapping = gateway.map(Protocol::Tcp, 9527, DEFAULT_LIFETIME).await.unwrap();
+        assert_eq!(
+            mapping,
+            PortMapping {
+                protocol: Protocol::Tcp,
+                internal_port: 9527,
+                external_port: 9528,
+                lifetime: DEFAULT_LIFETIME,
+                external_ip: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_pcp_map_response() {
+        let client_ip = Ipv4Addr::new(192, 168, 1, 5);
+        let nonce = pcp_nonce(Protocol::Udp, 50000);
+        let request = encode_pcp_request(PCP_OPCODE_MAP, DEFAULT_LIFETIME, client_ip, Some((nonce, Protocol::Udp, 50000)));
+        assert_eq!(request.len(), PCP_HEADER_LEN + PCP_MAP_LEN);
+
+        // 由请求构造成功响应: 外部地址 203.0.113.7
+        let mut response = request.clone();
+        response[1] = PMP_RESPONSE_BIT | PCP_OPCODE_MAP;
+        response[42..44].copy_from_slice(&50001u16.to_be_bytes());
+        response[44..60].copy_from_slice(&Ipv4Addr::new(203, 0, 113, 7).to_ipv6_mapped().octets());
+        let mapping = decode_pcp_map(&response, &nonce, Protocol::Udp).unwrap();
+        assert_eq!(mapping.internal_port, 50000);
+        assert_eq!(mapping.external_port, 50001);
+        assert_eq!(mapping.lifetime, DEFAULT_LIFETIME);
+        assert_eq!(mapping.external_ip, Some(Ipv4Addr::new(203, 0, 113, 7)));
+
+        // 其他映射的响应不能被误认
+        assert!(decode_pcp_map(&response, &pcp_nonce(Protocol::Tcp, 50000), Protocol::Udp).is_err());
+        assert_eq!(
+            parse_proc_route("Iface\tDestination\tGateway\neth0\t00000000\t0101A8C0\n"),
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_igd_description_and_chunked_response() {
+        let response = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            1a\r\n<root><device><serviceList\r\n\
+            9f\r\n><service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType><controlURL>/ctl/IPConn</controlURL></service></serviceList></device></root>\r\n\
+            0\r\n\r\n";
+        let (status, description) = parse_http_response(response).unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(
+            parse_igd_description(&description),
+            Some((
+                "urn:schemas-upnp-org:service:WANIPConnection:1".to_string(),
+                "/ctl/IPConn".to_string()
+            ))
+        );
+        assert_eq!(
+            ssdp_location("HTTP/1.1 200 OK\r\nLOCATION: http://192.168.1.1:5000/rootDesc.xml\r\n\r\n").as_deref(),
+            Some("http://192.168.1.1:5000/rootDesc.xml")
+        );
+    }
+}
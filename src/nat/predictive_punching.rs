@@ -1,15 +1,135 @@
 //! 预测性端口攻击 (Predictive Port Punching)
 //!
 //! 通过分析 NAT 的端口分配模式，预测下一个外部端口，提高对称 NAT 穿透率
-
-// 预测性打洞模块尚未激活，标记为允许死代码和未使用导入
-#![allow(dead_code, unused_imports)]
-
-use crate::nat::detector::{NatBehavior, NatType, PortAllocationPattern};
-use anyhow::Result;
+//!
+//! 建立连接时双方各用 `HolePuncher` 在同一个 UDP socket 上向 STUN 服务器探测映射，
+//! 通过信令交换 `PunchCandidate` (公网 IP + 预测端口)，再同时向对方的预测端口发送打洞包。
+//! 打通后该 socket 交给 WebRTC 使用 (UDP mux)，对方地址作为 ICE 候选加入。
+
+use crate::nat::detector::{NatBehavior, NatDetector, NatType, PortAllocationPattern};
+use crate::nat::NatConfig;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// 打洞请求
+const PUNCH_REQUEST: &[u8] = b"SSCONTROL-PUNCH";
+/// 打洞确认 (收到请求后回复，让对方也知道链路已通)
+const PUNCH_ACK: &[u8] = b"SSCONTROL-PUNCH-ACK";
+/// 重复发送打洞包的间隔 (对方的打洞包到达前，己方 NAT 上的映射可能还没建立)
+const PUNCH_INTERVAL: Duration = Duration::from_millis(200);
+/// 确认包发送次数 (防止单个确认包丢失)
+const ACK_REPEAT: usize = 3;
+
+/// 通过信令交换的打洞信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PunchCandidate {
+    /// STUN 看到的公网 IP
+    pub ip: IpAddr,
+    /// 对方应尝试的外部端口 (锥形 NAT 只有一个映射端口，对称 NAT 为预测端口)
+    pub ports: Vec<u16>,
+    /// 是否为对称 NAT
+    pub symmetric: bool,
+}
+
+/// 打洞成功的 UDP 路径
+#[derive(Debug)]
+pub struct PunchedPath {
+    /// 已在 NAT 上打开映射的本地 socket
+    pub socket: UdpSocket,
+    /// 对方回复打洞包的地址
+    pub remote: SocketAddr,
+}
+
+/// 一次连接的打洞过程
+pub struct HolePuncher {
+    socket: UdpSocket,
+    candidate: PunchCandidate,
+    config: NatConfig,
+}
+
+impl HolePuncher {
+    /// 绑定本地 socket 并通过 STUN 探测映射，生成发给对方的打洞信息
+    pub async fn prepare(config: NatConfig) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        let local_port = socket.local_addr()?.port();
+        let detector = NatDetector::new(config.clone());
+        let mappings = detector.collect_mappings(&socket).await;
+        let behavior = detector
+            .mapping_behavior(local_port, &mappings)
+            .ok_or_else(|| anyhow!("所有 STUN 服务器均无响应"))?;
+        let (mapped, last_server) = mappings[mappings.len() - 1];
+
+        let symmetric = behavior.nat_type == NatType::Symmetric;
+        let ports = if symmetric {
+            let mut predictor = PredictivePunching::new();
+            for &(mapped, server) in &mappings {
+                predictor.add_observation(mapped.port(), server);
+            }
+            let prediction = predictor.predict_next_ports(
+                local_port,
+                last_server,
+                &behavior,
+                config.prediction_attempts as usize,
+            );
+            tracing::info!(
+                "对称 NAT ({:?})，预测 {} 个外部端口 (置信度 {:.2})",
+                behavior.port_allocation_pattern,
+                prediction.predicted_ports.len(),
+                prediction.confidence
+            );
+            prediction.predicted_ports
+        } else {
+            vec![mapped.port()]
+        };
+
+        Ok(Self {
+            socket,
+            candidate: PunchCandidate {
+                ip: mapped.ip(),
+                ports,
+                symmetric,
+            },
+            config,
+        })
+    }
+
+    /// 发给对方的打洞信息
+    pub fn candidate(&self) -> &PunchCandidate {
+        &self.candidate
+    }
+
+    /// 是否需要打洞 (双方都不是对称 NAT 时 ICE 的 server-reflexive 候选即可直连)
+    pub fn needs_punch(&self, remote: &PunchCandidate) -> bool {
+        self.candidate.symmetric || remote.symmetric
+    }
+
+    /// 向对方的预测端口打洞，成功后返回可交给 WebRTC 的路径
+    pub async fn punch(self, remote: &PunchCandidate) -> Result<PunchedPath> {
+        let port = *remote.ports.first().ok_or_else(|| anyhow!("对方没有提供打洞端口"))?;
+        let ports = if self.config.enable_parallel_punch {
+            &remote.ports[..]
+        } else {
+            &remote.ports[..1]
+        };
+        let timeout = Duration::from_millis(self.config.punch_timeout_ms);
+        let target = SocketAddr::new(remote.ip, port);
+
+        match PredictivePunching::new()
+            .parallel_punch(&self.socket, &target, ports, timeout)
+            .await?
+        {
+            Some(remote) => Ok(PunchedPath {
+                socket: self.socket,
+                remote,
+            }),
+            None => Err(anyhow!("打洞超时 ({} 个端口)", ports.len())),
+        }
+    }
+}
 
 /// 端口预测结果
 #[derive(Debug, Clone)]
@@ -123,7 +243,7 @@ impl PredictivePunching {
                 if i % 2 == 0 {
                     base_port.wrapping_add(i / 2)
                 } else {
-                    base_port.wrapping_sub((i + 1) / 2)
+                    base_port.wrapping_sub(i.div_ceil(2))
                 }
             })
             .filter(|&p| p >= min_port && p <= max_port)
@@ -197,58 +317,62 @@ impl PredictivePunching {
 
     /// 执行并行打洞
     ///
-    /// 向多个预测端口同时发送打洞包
+    /// 周期性地向目标 IP 的所有预测端口发送打洞包，收到对方的打洞包或确认后返回对方地址；
+    /// 超时仍未收到返回 None
     pub async fn parallel_punch(
         &self,
-        socket: &tokio::net::UdpSocket,
+        socket: &UdpSocket,
         target: &SocketAddr,
         predicted_ports: &[u16],
         punch_timeout: Duration,
-    ) -> Result<bool> {
+    ) -> Result<Option<SocketAddr>> {
         tracing::info!(
             "开始并行打洞: 目标={}, 预测端口数={}",
-            target,
+            target.ip(),
             predicted_ports.len()
         );
 
-        // 向所有预测端口发送打洞包
-        let punch_data = b"PUNCH";
-        for &port in predicted_ports {
-            let punch_addr = SocketAddr::new(target.ip(), port);
-            if let Err(e) = socket.send_to(punch_data, punch_addr).await {
-                tracing::debug!("发送打洞包到 {} 失败: {}", punch_addr, e);
-            }
-        }
-
-        // 等待响应
+        let deadline = tokio::time::Instant::now() + punch_timeout;
+        let mut next_round = tokio::time::Instant::now();
         let mut buf = [0u8; 1024];
-        let start = std::time::Instant::now();
 
         loop {
-            let elapsed = start.elapsed();
-            if elapsed >= punch_timeout {
-                break;
+            if tokio::time::Instant::now() >= next_round {
+                for &port in predicted_ports {
+                    let punch_addr = SocketAddr::new(target.ip(), port);
+                    if let Err(e) = socket.send_to(PUNCH_REQUEST, punch_addr).await {
+                        tracing::debug!("发送打洞包到 {} 失败: {}", punch_addr, e);
+                    }
+                }
+                next_round += PUNCH_INTERVAL;
             }
 
-            let remaining = punch_timeout - elapsed;
-
-            // 使用 tokio::time::timeout 实现超时
-            match tokio::time::timeout(remaining, socket.recv_from(&mut buf)).await {
-                Ok(Ok((size, from))) => {
-                    tracing::info!("收到打洞响应: {} ({} 字节)", from, size);
-                    return Ok(true);
-                }
+            let wait_until = next_round.min(deadline);
+            match tokio::time::timeout_at(wait_until, socket.recv_from(&mut buf)).await {
+                Ok(Ok((size, from))) if from.ip() == target.ip() => match &buf[..size] {
+                    PUNCH_REQUEST => {
+                        tracing::info!("收到打洞包: {}", from);
+                        for _ in 0..ACK_REPEAT {
+                            socket.send_to(PUNCH_ACK, from).await?;
+                        }
+                        return Ok(Some(from));
+                    }
+                    PUNCH_ACK => {
+                        tracing::info!("收到打洞确认: {}", from);
+                        return Ok(Some(from));
+                    }
+                    _ => {}
+                },
+                Ok(Ok(_)) => {}
                 Ok(Err(e)) => {
                     tracing::debug!("打洞接收错误: {}", e);
                 }
-                Err(_) => {
-                    // 超时
-                    break;
-                }
+                Err(_) if wait_until >= deadline => break,
+                Err(_) => {}
             }
         }
 
-        Ok(false)
+        Ok(None)
     }
 
     /// 清除历史记录
@@ -305,4 +429,24 @@ mod tests {
         assert!(!prediction.predicted_ports.is_empty());
         assert!(prediction.confidence > 0.0);
     }
+
+    #[tokio::test]
+    async fn test_parallel_punch_between_local_sockets() {
+        let a = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let b = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let a_addr = a.local_addr().unwrap();
+        let b_addr = b.local_addr().unwrap();
+        let punching = PredictivePunching::new();
+        let timeout = Duration::from_secs(2);
+
+        // A 只知道 B 附近的几个端口，B 知道 A 的准确端口
+        let b_ports = [b_addr.port() - 1, b_addr.port(), b_addr.port() + 1];
+        let a_ports = [a_addr.port()];
+        let (from_a, from_b) = tokio::join!(
+            punching.parallel_punch(&a, &b_addr, &b_ports, timeout),
+            punching.parallel_punch(&b, &a_addr, &a_ports, timeout),
+        );
+        assert_eq!(from_a.unwrap(), Some(b_addr));
+        assert_eq!(from_b.unwrap(), Some(a_addr));
+    }
 }
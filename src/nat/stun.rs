@@ -0,0 +1,319 @@
+//! STUN Binding 客户端 (RFC 5389)
+//!
+//! 不依赖第三方 STUN 库: 自行编解码 Binding 请求/响应，获取本机 UDP 端口在 NAT
+//! 外的映射地址 (server-reflexive 地址)，并结合 RFC 5780 的 CHANGE-REQUEST 判断 NAT 类型。
+//! `serve` 提供一个极简的 Binding 响应端，可自建 STUN 服务而不依赖公共服务器。
+
+use anyhow::{anyhow, Result};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// 固定的 Magic Cookie
+const MAGIC_COOKIE: u32 = 0x2112_A442;
+const HEADER_LEN: usize = 20;
+
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+const BINDING_ERROR: u16 = 0x0111;
+
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_CHANGE_REQUEST: u16 = 0x0003;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+
+/// 未指定端口时的默认 STUN 端口
+pub const DEFAULT_STUN_PORT: u16 = 3478;
+
+/// 首次重传间隔 (RFC 5389 建议 500ms，之后翻倍)
+const INITIAL_RTO: Duration = Duration::from_millis(500);
+/// 最多发送次数
+const MAX_TRANSMISSIONS: u32 = 4;
+
+/// 事务 ID
+pub type TransactionId = [u8; 12];
+
+/// CHANGE-REQUEST 标志 (要求服务器从其他 IP / 端口回复，用于检测过滤行为)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChangeRequest {
+    pub change_ip: bool,
+    pub change_port: bool,
+}
+
+impl ChangeRequest {
+    fn flags(&self) -> u32 {
+        (if self.change_ip { 0x04 } else { 0 }) | (if self.change_port { 0x02 } else { 0 })
+    }
+}
+
+/// 生成随机事务 ID
+pub fn new_transaction_id() -> TransactionId {
+    rand::random()
+}
+
+/// 编码 Binding 请求
+pub fn encode_binding_request(transaction_id: &TransactionId, change: ChangeRequest) -> Vec<u8> {
+    let mut attrs = Vec::new();
+    if change != ChangeRequest::default() {
+        attrs.extend_from_slice(&ATTR_CHANGE_REQUEST.to_be_bytes());
+        attrs.extend_from_slice(&4u16.to_be_bytes());
+        attrs.extend_from_slice(&change.flags().to_be_bytes());
+    }
+    encode_message(BINDING_REQUEST, transaction_id, &attrs)
+}
+
+/// 编码 Binding 成功响应 (携带 XOR-MAPPED-ADDRESS)
+pub fn encode_binding_response(transaction_id: &TransactionId, mapped: SocketAddr) -> Vec<u8> {
+    let mut value = vec![0u8];
+    let xor_port = mapped.port() ^ (MAGIC_COOKIE >> 16) as u16;
+    match mapped.ip() {
+        IpAddr::V4(ip) => {
+            value.push(0x01);
+            value.extend_from_slice(&xor_port.to_be_bytes());
+            value.extend_from_slice(&(u32::from(ip) ^ MAGIC_COOKIE).to_be_bytes());
+        }
+        IpAddr::V6(ip) => {
+            value.push(0x02);
+            value.extend_from_slice(&xor_port.to_be_bytes());
+            let key = xor_key(transaction_id);
+            value.extend(ip.octets().iter().zip(key.iter()).map(|(a, k)| a ^ k));
+        }
+    }
+
+    let mut attrs = Vec::new();
+    attrs.extend_from_slice(&ATTR_XOR_MAPPED_ADDRESS.to_be_bytes());
+    attrs.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    attrs.extend_from_slice(&value);
+    encode_message(BINDING_SUCCESS, transaction_id, &attrs)
+}
+
+fn encode_message(message_type: u16, transaction_id: &TransactionId, attrs: &[u8]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(HEADER_LEN + attrs.len());
+    msg.extend_from_slice(&message_type.to_be_bytes());
+    msg.extend_from_slice(&(attrs.len() as u16).to_be_bytes());
+    msg.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    msg.extend_from_slice(transaction_id);
+    msg.extend_from_slice(attrs);
+    msg
+}
+
+/// IPv6 地址的异或密钥: Magic Cookie + 事务 ID
+fn xor_key(transaction_id: &TransactionId) -> [u8; 16] {
+    let mut key = [0u8; 16];
+    key[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    key[4..].copy_from_slice(transaction_id);
+    key
+}
+
+/// 解析 STUN 消息头，返回 (消息类型, 事务 ID, 属性区)
+fn parse_header(data: &[u8]) -> Option<(u16, TransactionId, &[u8])> {
+    if data.len() < HEADER_LEN || data[0] & 0xC0 != 0 {
+        return None;
+    }
+    let message_type = u16::from_be_bytes([data[0], data[1]]);
+    let length = u16::from_be_bytes([data[2], data[3]]) as usize;
+    if u32::from_be_bytes([data[4], data[5], data[6], data[7]]) != MAGIC_COOKIE
+        || data.len() < HEADER_LEN + length
+    {
+        return None;
+    }
+    let mut transaction_id = [0u8; 12];
+    transaction_id.copy_from_slice(&data[8..HEADER_LEN]);
+    Some((message_type, transaction_id, &data[HEADER_LEN..HEADER_LEN + length]))
+}
+
+/// 遍历属性区，返回 (属性类型, 属性值)
+fn attributes(mut attrs: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        if attrs.len() < 4 {
+            return None;
+        }
+        let attr_type = u16::from_be_bytes([attrs[0], attrs[1]]);
+        let length = u16::from_be_bytes([attrs[2], attrs[3]]) as usize;
+        if attrs.len() < 4 + length {
+            return None;
+        }
+        let value = &attrs[4..4 + length];
+        // 属性按 4 字节对齐
+        attrs = &attrs[(4 + ((length + 3) & !3)).min(attrs.len())..];
+        Some((attr_type, value))
+    })
+}
+
+/// 解析 Binding 响应，返回映射地址 (优先 XOR-MAPPED-ADDRESS)
+pub fn decode_binding_response(data: &[u8], transaction_id: &TransactionId) -> Result<SocketAddr> {
+    let (message_type, id, attrs) = parse_header(data).ok_or_else(|| anyhow!("不是 STUN 消息"))?;
+    if id != *transaction_id {
+        return Err(anyhow!("STUN 事务 ID 不匹配"));
+    }
+    match message_type {
+        BINDING_SUCCESS => {}
+        BINDING_ERROR => return Err(anyhow!("STUN 服务器返回错误响应")),
+        other => return Err(anyhow!("意外的 STUN 消息类型: {:#06x}", other)),
+    }
+
+    let mut mapped = None;
+    for (attr_type, value) in attributes(attrs) {
+        match attr_type {
+            ATTR_XOR_MAPPED_ADDRESS => return decode_address(value, Some(transaction_id)),
+            ATTR_MAPPED_ADDRESS => mapped = Some(decode_address(value, None)?),
+            _ => {}
+        }
+    }
+    mapped.ok_or_else(|| anyhow!("STUN 响应中没有映射地址"))
+}
+
+/// 解析 (XOR-)MAPPED-ADDRESS 属性值 (xor 为事务 ID 时按异或编码解析)
+fn decode_address(value: &[u8], xor: Option<&TransactionId>) -> Result<SocketAddr> {
+    if value.len() < 8 {
+        return Err(anyhow!("STUN 地址属性长度错误"));
+    }
+    let mut port = u16::from_be_bytes([value[2], value[3]]);
+    if xor.is_some() {
+        port ^= (MAGIC_COOKIE >> 16) as u16;
+    }
+
+    let ip = match value[1] {
+        0x01 => {
+            let mut raw = u32::from_be_bytes([value[4], value[5], value[6], value[7]]);
+            if xor.is_some() {
+                raw ^= MAGIC_COOKIE;
+            }
+            IpAddr::V4(Ipv4Addr::from(raw))
+        }
+        0x02 if value.len() >= 20 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&value[4..20]);
+            if let Some(transaction_id) = xor {
+                for (octet, k) in octets.iter_mut().zip(xor_key(transaction_id)) {
+                    *octet ^= k;
+                }
+            }
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        family => return Err(anyhow!("未知的地址族: {}", family)),
+    };
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// 解析 STUN 服务器地址 (`stun:host:port`、`host:port` 或 `host`)
+pub async fn resolve_server(url: &str) -> Result<SocketAddr> {
+    let host = url.trim().trim_start_matches("stun:");
+    let target = if host.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok()) {
+        host.to_string()
+    } else {
+        format!("{}:{}", host, DEFAULT_STUN_PORT)
+    };
+    let resolved = tokio::net::lookup_host(target.as_str())
+        .await?
+        .find(SocketAddr::is_ipv4);
+    resolved.ok_or_else(|| anyhow!("无法解析 STUN 服务器: {}", url))
+}
+
+/// 发送 Binding 请求并等待响应 (按 RFC 5389 重传)
+///
+/// 带 CHANGE-REQUEST 时响应来自服务器的其他地址，因此只按事务 ID 匹配
+pub async fn binding(socket: &UdpSocket, server: SocketAddr, change: ChangeRequest) -> Result<SocketAddr> {
+    let transaction_id = new_transaction_id();
+    let request = encode_binding_request(&transaction_id, change);
+    let mut rto = INITIAL_RTO;
+    let mut buf = [0u8; 1024];
+
+    for _ in 0..MAX_TRANSMISSIONS {
+        socket.send_to(&request, server).await?;
+        let deadline = tokio::time::Instant::now() + rto;
+        while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+            let (size, _) = received?;
+            // 其他事务的迟到响应直接忽略
+            if let Ok(mapped) = decode_binding_response(&buf[..size], &transaction_id) {
+                return Ok(mapped);
+            }
+        }
+        rto *= 2;
+    }
+    Err(anyhow!("STUN 服务器 {} 无响应", server))
+}
+
+/// 用临时 UDP 端口查询本机的公网映射地址
+pub async fn public_address(server_url: &str) -> Result<SocketAddr> {
+    let server = resolve_server(server_url).await?;
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    binding(&socket, server, ChangeRequest::default()).await
+}
+
+/// 极简 STUN 服务: 对收到的 Binding 请求回复其来源地址
+///
+/// 部署在公网 (如信令服务器所在主机) 上即可替代公共 STUN 服务器
+pub async fn serve(socket: UdpSocket) -> Result<()> {
+    tracing::info!("STUN 服务已启动: {}", socket.local_addr()?);
+    let mut buf = [0u8; 1024];
+    loop {
+        let (size, from) = socket.recv_from(&mut buf).await?;
+        if let Some(response) = respond(&buf[..size], from) {
+            if let Err(e) = socket.send_to(&response, from).await {
+                tracing::debug!("STUN 响应发送失败 ({}): {}", from, e);
+            }
+        }
+    }
+}
+
+/// 为 Binding 请求生成响应 (非 Binding 请求返回 None)
+///
+/// 单地址服务无法满足 CHANGE-REQUEST，此时不回复，客户端会按过滤受限处理
+pub fn respond(request: &[u8], from: SocketAddr) -> Option<Vec<u8>> {
+    let (message_type, transaction_id, attrs) = parse_header(request)?;
+    if message_type != BINDING_REQUEST {
+        return None;
+    }
+    if attributes(attrs).any(|(attr_type, _)| attr_type == ATTR_CHANGE_REQUEST) {
+        return None;
+    }
+    Some(encode_binding_response(&transaction_id, from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binding_round_trip() {
+        let id = new_transaction_id();
+        let request = encode_binding_request(&id, ChangeRequest::default());
+        assert_eq!(request.len(), HEADER_LEN);
+
+        for mapped in ["203.0.113.7:54321", "[2001:db8::1]:3478"] {
+            let mapped: SocketAddr = mapped.parse().unwrap();
+            let response = respond(&request, mapped).unwrap();
+            assert_eq!(decode_binding_response(&response, &id).unwrap(), mapped);
+            // 事务 ID 不匹配的响应被拒绝
+            assert!(decode_binding_response(&response, &new_transaction_id()).is_err());
+        }
+
+        // 单地址服务不响应 CHANGE-REQUEST
+        let change = encode_binding_request(&id, ChangeRequest { change_ip: true, change_port: true });
+        assert!(respond(&change, "203.0.113.7:1".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_decode_rfc5769_ipv4_response() {
+        // RFC 5769 2.2 节的示例响应，只保留 XOR-MAPPED-ADDRESS 属性
+        let id: TransactionId = [0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6, 0x86, 0xfa, 0x87, 0xdf, 0xae];
+        let mut response = vec![0x01, 0x01, 0x00, 0x0c, 0x21, 0x12, 0xa4, 0x42];
+        response.extend_from_slice(&id);
+        response.extend_from_slice(&[0x00, 0x20, 0x00, 0x08, 0x00, 0x01, 0xa1, 0x47, 0xe1, 0x12, 0xa6, 0x43]);
+        assert_eq!(
+            decode_binding_response(&response, &id).unwrap(),
+            "192.0.2.1:32853".parse::<SocketAddr>().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_binding_against_local_server() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server.local_addr().unwrap();
+        tokio::spawn(serve(server));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mapped = binding(&client, server_addr, ChangeRequest::default()).await.unwrap();
+        assert_eq!(mapped, client.local_addr().unwrap());
+    }
+}
@@ -0,0 +1,111 @@
+//! WebSocket 文本消息压缩
+//!
+//! SDP 和 JSON 控制消息冗长，经隧道转发时占用不少带宽。当前使用的 tungstenite 不支持
+//! RFC 7692 (permessage-deflate 需要设置 RSV1 位，接收端会拒绝这样的帧)，这里在消息层实现同样的效果:
+//! - 握手时客户端在请求头 `X-Sscontrol-Compression: deflate` 中提出，服务器同意时在响应头中原样返回
+//! - 协商成功后，较长的文本消息以 raw deflate 压缩，作为带 `"SZ"` 前缀的二进制帧发送
+//!   (与视频帧的 v1 首字节 0 和 v2 魔数 `"SV"` 都不冲突)
+//! - 视频帧等二进制消息从不压缩；E2EE 会话也不压缩 (先压缩再加密会通过长度泄露明文信息)
+//!
+//! 浏览器无法设置自定义请求头，Web 查看器的连接不会启用压缩
+
+use anyhow::{anyhow, Result};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+
+/// 协商压缩的握手头
+pub const HEADER: &str = "x-sscontrol-compression";
+
+/// 唯一支持的压缩算法
+pub const DEFLATE: &str = "deflate";
+
+/// 压缩消息的魔数
+pub const MAGIC: [u8; 2] = *b"SZ";
+
+/// 短于该长度的消息不压缩 (压缩收益抵不过帧头和 CPU 开销)
+const MIN_SIZE: usize = 256;
+
+/// 解压后的长度上限 (防止压缩炸弹)
+const MAX_INFLATED: u64 = 1024 * 1024;
+
+/// 对端的握手头是否提出 (或同意) deflate 压缩
+pub fn accepts(header: Option<&str>) -> bool {
+    header.is_some_and(|value| value.split(',').any(|token| token.trim().eq_ignore_ascii_case(DEFLATE)))
+}
+
+/// 压缩文本消息，返回 None 表示应按原文发送 (太短或压缩后没有变小)
+pub fn compress(text: &str) -> Option<Vec<u8>> {
+    if text.len() < MIN_SIZE {
+        return None;
+    }
+    let mut encoder = DeflateEncoder::new(MAGIC.to_vec(), Compression::fast());
+    encoder.write_all(text.as_bytes()).ok()?;
+    let compressed = encoder.finish().ok()?;
+    (compressed.len() < text.len()).then_some(compressed)
+}
+
+/// 是否是压缩消息
+pub fn is_compressed(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// 解压 [`compress`] 的输出
+pub fn decompress(data: &[u8]) -> Result<String> {
+    let payload = data.strip_prefix(&MAGIC).ok_or_else(|| anyhow!("不是压缩消息"))?;
+    let mut text = String::new();
+    DeflateDecoder::new(payload)
+        .take(MAX_INFLATED + 1)
+        .read_to_string(&mut text)
+        .map_err(|e| anyhow!("解压失败: {}", e))?;
+    if text.len() as u64 > MAX_INFLATED {
+        return Err(anyhow!("解压后超过 {} 字节", MAX_INFLATED));
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_sdp() {
+        let sdp = serde_json::json!({
+            "type": "offer",
+            "from": "viewer_0",
+            "to": "host",
+            "sdp": "v=0\r\na=candidate:1 1 udp 2130706431 192.168.1.5 50000 typ host\r\n".repeat(20),
+        })
+        .to_string();
+        let compressed = compress(&sdp).unwrap();
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < sdp.len() / 4);
+        assert_eq!(decompress(&compressed).unwrap(), sdp);
+    }
+
+    #[test]
+    fn test_short_messages_stay_plain() {
+        assert!(compress(r#"{"type":"join","room_id":"room"}"#).is_none());
+        // 视频帧 (v1 首字节为 0，v2 以 "SV" 开头) 不会被误认为压缩消息
+        assert!(!is_compressed(&[0, 0, 0, 12]));
+        assert!(!is_compressed(b"SV\x02\x01"));
+        assert!(decompress(b"SV\x02\x01").is_err());
+    }
+
+    #[test]
+    fn test_rejects_oversized_payload() {
+        let mut encoder = DeflateEncoder::new(MAGIC.to_vec(), Compression::best());
+        encoder.write_all(&vec![b'a'; MAX_INFLATED as usize + 1]).unwrap();
+        let bomb = encoder.finish().unwrap();
+        assert!(decompress(&bomb).is_err());
+    }
+
+    #[test]
+    fn test_accepts_header() {
+        assert!(accepts(Some("deflate")));
+        assert!(accepts(Some("zstd, Deflate")));
+        assert!(!accepts(Some("zstd")));
+        assert!(!accepts(None));
+    }
+}
@@ -0,0 +1,95 @@
+//! WebSocket 心跳
+//!
+//! 半开连接 (对端断电、NAT 映射过期) 上 TCP 不会报错，发送会一直阻塞到内核超时 (数分钟)。
+//! 连接两端都按 `ping_interval` 发送 Ping，超过 `pong_timeout` 没有收到对端任何数据
+//! (Pong 或普通消息) 即判定连接已死: 客户端标记断开并重连，信令服务器关闭连接
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 心跳配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepaliveConfig {
+    /// Ping 间隔 (零 = 不发送心跳)
+    pub ping_interval: Duration,
+    /// 多久没有收到对端数据判定为断开
+    pub pong_timeout: Duration,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            ping_interval: Duration::from_secs(5),
+            pong_timeout: Duration::from_secs(15),
+        }
+    }
+}
+
+impl KeepaliveConfig {
+    /// 不发送心跳
+    pub fn disabled() -> Self {
+        Self {
+            ping_interval: Duration::ZERO,
+            pong_timeout: Duration::ZERO,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.ping_interval.is_zero()
+    }
+}
+
+/// 最近一次收到对端数据的时间 (接收任务更新，心跳任务检查)
+#[derive(Debug, Clone)]
+pub struct Liveness(Arc<Mutex<Instant>>);
+
+impl Liveness {
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(Instant::now())))
+    }
+
+    /// 收到对端数据
+    pub fn touch(&self) {
+        if let Ok(mut last) = self.0.lock() {
+            *last = Instant::now();
+        }
+    }
+
+    /// 超过 `timeout` 没有收到数据
+    pub fn is_dead(&self, timeout: Duration) -> bool {
+        self.is_dead_at(timeout, Instant::now())
+    }
+
+    fn is_dead_at(&self, timeout: Duration, now: Instant) -> bool {
+        self.0
+            .lock()
+            .map(|last| now.saturating_duration_since(*last) > timeout)
+            .unwrap_or(false)
+    }
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_liveness_timeout() {
+        let liveness = Liveness::new();
+        let start = Instant::now();
+        let timeout = Duration::from_secs(15);
+
+        assert!(!liveness.is_dead_at(timeout, start + Duration::from_secs(10)));
+        assert!(liveness.is_dead_at(timeout, start + Duration::from_secs(16)));
+
+        // 收到数据后重新计时
+        liveness.touch();
+        assert!(!liveness.is_dead_at(timeout, Instant::now() + Duration::from_secs(10)));
+        assert!(!KeepaliveConfig::disabled().is_enabled());
+    }
+}
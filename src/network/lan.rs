@@ -0,0 +1,259 @@
+//! 局域网地址选择
+//!
+//! 被控端启动时显示给用户的本机地址、mDNS 解析结果和 ICE 候选都按同一规则挑选:
+//! 优先 IPv4 私有地址，其次 IPv6 ULA (fc00::/7)，再次 IPv6 全局单播地址，
+//! 纯 IPv6 网络也能得到可用地址。
+//!
+//! 排除: 回环、WARP (198.18.0.0/15)、CGNAT (100.64.0.0/10) 等虚拟网卡地址，
+//! 以及 IPv6 链路本地地址 (fe80::/10，需要带网卡作用域 `%eth0` 才能使用，浏览器 URL 不支持)
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, UdpSocket};
+
+/// 是否是可以显示给用户的局域网地址 (不含 WARP、VPN 等虚拟网卡地址)
+pub fn is_valid_lan_ip(ip: &IpAddr) -> bool {
+    lan_preference(ip).is_some()
+}
+
+/// 地址优先级 (越小越优先)，不可用的地址返回 None
+pub fn lan_preference(ip: &IpAddr) -> Option<u8> {
+    match ip {
+        IpAddr::V4(ipv4) => is_private_ipv4(ipv4).then_some(0),
+        IpAddr::V6(ipv6) => match ipv6.to_ipv4_mapped() {
+            Some(ipv4) => is_private_ipv4(&ipv4).then_some(0),
+            None if is_unique_local(ipv6) => Some(1),
+            None if is_global_unicast(ipv6) => Some(2),
+            None => None,
+        },
+    }
+}
+
+fn is_private_ipv4(ip: &Ipv4Addr) -> bool {
+    let octets = ip.octets();
+
+    // Cloudflare WARP (198.18.0.0/15) 和 CGNAT (100.64.0.0/10，部分 VPN 使用) 不在私有地址段内，
+    // 回环地址同理
+    (octets[0] == 192 && octets[1] == 168)
+        || octets[0] == 10
+        || (octets[0] == 172 && (16..=31).contains(&octets[1]))
+}
+
+/// IPv6 唯一本地地址 (fc00::/7)
+pub fn is_unique_local(ip: &Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xfe00) == 0xfc00
+}
+
+/// IPv6 链路本地地址 (fe80::/10)
+pub fn is_link_local(ip: &Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xffc0) == 0xfe80
+}
+
+/// IPv6 全局单播地址 (2000::/3)
+fn is_global_unicast(ip: &Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xe000) == 0x2000
+}
+
+/// ICE 候选地址过滤: 丢弃 IPv6 链路本地和回环地址
+pub fn is_usable_candidate_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ipv4) => !ipv4.is_loopback(),
+        IpAddr::V6(ipv6) => !ipv6.is_loopback() && !is_link_local(ipv6),
+    }
+}
+
+/// 网段 (CIDR，如 `192.168.1.0/24`、`fd00::/8`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subnet {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Subnet {
+    /// 地址是否在网段内 (IPv4 映射的 IPv6 地址按 IPv4 比较)
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(ipv6) => ipv6.to_ipv4_mapped().map_or(*ip, IpAddr::V4),
+            IpAddr::V4(_) => *ip,
+        };
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix_len)).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix_len)).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::str::FromStr for Subnet {
+    type Err = anyhow::Error;
+
+    /// 不带前缀长度时表示单个地址
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (address, prefix) = match s.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s, None),
+        };
+        let network: IpAddr = address.parse().map_err(|_| anyhow::anyhow!("\"{}\" 不是有效的 IP 地址", address))?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|len| *len <= max)
+                .ok_or_else(|| anyhow::anyhow!("\"{}\" 的前缀长度无效", s))?,
+            None => max,
+        };
+        Ok(Self { network, prefix_len })
+    }
+}
+
+impl std::fmt::Display for Subnet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+/// 本机局域网地址
+///
+/// 先通过默认路由 (IPv4，然后 IPv6) 确定出口地址，不可用时 (如 WARP/VPN 接管了默认路由)
+/// 从所有网卡的地址中按优先级挑选
+pub fn local_ip() -> Option<IpAddr> {
+    let routed = [("0.0.0.0:0", "8.8.8.8:80"), ("[::]:0", "[2001:4860:4860::8888]:80")]
+        .into_iter()
+        .filter_map(|(bind, target)| {
+            let socket = UdpSocket::bind(bind).ok()?;
+            socket.connect(target).ok()?;
+            Some(socket.local_addr().ok()?.ip())
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(ip) = routed.iter().find(|ip| is_valid_lan_ip(ip)) {
+        return Some(*ip);
+    }
+
+    if let Some(ip) = interface_addresses()
+        .into_iter()
+        .filter_map(|ip| lan_preference(&ip).map(|preference| (preference, ip)))
+        .min_by_key(|(preference, _)| *preference)
+        .map(|(_, ip)| ip)
+    {
+        return Some(ip);
+    }
+
+    // 没有更好的选择时返回出口地址
+    routed.into_iter().next()
+}
+
+/// 所有网卡上的地址 (解析 ifconfig 输出，Windows 上为空)
+fn interface_addresses() -> Vec<IpAddr> {
+    #[cfg(unix)]
+    {
+        let Ok(output) = std::process::Command::new("ifconfig").output() else {
+            return Vec::new();
+        };
+        parse_ifconfig(&String::from_utf8_lossy(&output.stdout))
+    }
+    #[cfg(not(unix))]
+    {
+        Vec::new()
+    }
+}
+
+/// 解析 `inet 192.168.1.5 ...` 和 `inet6 fd00::5%en0 prefixlen 64 ...` 行
+fn parse_ifconfig(output: &str) -> Vec<IpAddr> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            parts.find(|part| *part == "inet" || *part == "inet6")?;
+            let address = parts.next()?.trim_start_matches("addr:");
+            // 去掉作用域 (%en0) 和前缀长度 (/64)
+            let address = address.split(['%', '/']).next()?;
+            address.parse().ok()
+        })
+        .collect()
+}
+
+/// `主机:端口` 形式的地址，IPv6 地址加方括号 (用于 URL 和命令行提示)
+pub fn host_port(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subnet_contains() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        let lan: Subnet = "192.168.1.0/24".parse().unwrap();
+        assert!(lan.contains(&ip("192.168.1.77")));
+        assert!(lan.contains(&ip("::ffff:192.168.1.77")));
+        assert!(!lan.contains(&ip("192.168.2.1")));
+        assert!(!lan.contains(&ip("fd00::1")));
+
+        let ula: Subnet = "fd00::/8".parse().unwrap();
+        assert!(ula.contains(&ip("fd12::1")));
+        assert!(!ula.contains(&ip("2001:db8::1")));
+
+        let any: Subnet = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains(&ip("8.8.8.8")));
+        let single: Subnet = "10.0.0.5".parse().unwrap();
+        assert_eq!(single.to_string(), "10.0.0.5/32");
+        assert!(!single.contains(&ip("10.0.0.6")));
+
+        assert!("10.0.0.0/33".parse::<Subnet>().is_err());
+        assert!("eth0".parse::<Subnet>().is_err());
+    }
+
+    #[test]
+    fn test_lan_preference() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        assert_eq!(lan_preference(&ip("192.168.1.5")), Some(0));
+        assert_eq!(lan_preference(&ip("::ffff:10.0.0.2")), Some(0));
+        assert_eq!(lan_preference(&ip("fd12:3456::5")), Some(1));
+        assert_eq!(lan_preference(&ip("2001:db8::5")), Some(2));
+
+        for excluded in ["198.18.0.1", "100.64.0.1", "127.0.0.1", "8.8.8.8", "fe80::1", "::1", "ff02::1"] {
+            assert!(!is_valid_lan_ip(&ip(excluded)), "{}", excluded);
+        }
+        assert!(!is_usable_candidate_ip(&ip("fe80::1")));
+        assert!(is_usable_candidate_ip(&ip("fd12:3456::5")));
+    }
+
+    #[test]
+    fn test_parse_ifconfig() {
+        let output = "\
+en0: flags=8863<UP,BROADCAST,RUNNING> mtu 1500
+\tinet6 fe80::1c2a:5ff:fe3b:1%en0 prefixlen 64 secured scopeid 0x4
+\tinet 192.168.1.5 netmask 0xffffff00 broadcast 192.168.1.255
+\tinet6 fd00::5 prefixlen 64 autoconf secured
+eth0      Link encap:Ethernet
+          inet addr:10.0.0.2  Bcast:10.0.0.255  Mask:255.255.255.0
+";
+        let addresses = parse_ifconfig(output);
+        assert_eq!(
+            addresses,
+            ["fe80::1c2a:5ff:fe3b:1", "192.168.1.5", "fd00::5", "10.0.0.2"]
+                .iter()
+                .map(|s| s.parse::<IpAddr>().unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_host_port_brackets_ipv6() {
+        assert_eq!(host_port("192.168.1.5", 9527), "192.168.1.5:9527");
+        assert_eq!(host_port("fd00::5", 9527), "[fd00::5]:9527");
+        assert_eq!(host_port("[fd00::5]", 9527), "[fd00::5]:9527");
+    }
+}
@@ -1,30 +1,73 @@
 //! 网络传输模块
 //!
 //! 提供 WebSocket 客户端功能，支持自动重连和输入事件处理
+//!
+//! 启用 `e2ee` 后，连接建立时先完成 X25519 密钥交换，之后所有消息
+//! (视频帧、认证、输入事件) 都以 ChaCha20-Poly1305 加密帧传输
+//!
+//! 视频帧先进入有界发送队列 (见 [`pacing`])，由独占连接写半部的发送任务逐帧写入 socket
+//! (见 [`writer`])；网络跟不上时丢弃最老的非关键帧，并通过拥塞反馈回调通知编码器降低码率。
+//! 服务器确认支持时帧头使用紧凑的二进制格式，否则沿用 JSON 帧头 (见 [`protocol`])
+//!
+//! 连接期间定期发送 Ping (见 [`keepalive`])，超时未收到服务器数据或写入超时即标记断开并重连
+//!
+//! 服务器同意时，较长的文本消息压缩后发送 (见 [`compression`])
+//!
+//! 配置了 `fleet.server` 时另用一条连接向中心信令服务器登记在线状态 (见 [`presence`])
 
 #![allow(dead_code)]
 
+pub mod compression;
+pub mod keepalive;
+pub mod lan;
+pub mod pacing;
+pub mod presence;
+pub mod protocol;
+#[cfg(feature = "quic")]
+pub mod quic;
+mod writer;
+
 use anyhow::{anyhow, Result};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
-use futures_util::{SinkExt, StreamExt};
+use futures_util::StreamExt;
+#[cfg(feature = "security")]
+use futures_util::SinkExt;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::{Mutex, mpsc};
+use tokio::task::JoinHandle;
+
+use crate::engine::{MediaEvent, MediaSink, VideoSample};
+use keepalive::{KeepaliveConfig, Liveness};
+use pacing::{CongestionCallback, CongestionConfig, CongestionFeedback, CongestionStats, QueuedFrame};
+use protocol::ProtocolVersion;
+use writer::{WriterHandle, WriterTask};
 
 // 安全相关导入
 #[cfg(feature = "security")]
 use crate::security::{ApiKeyAuth, TokenManager};
+#[cfg(feature = "security")]
+use crate::security::e2ee::{self, E2eeHandshake, E2eeOpener, E2eeSealer, E2eeSession};
+
+/// E2EE 握手超时
+#[cfg(feature = "security")]
+const E2EE_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// 输入事件发送器 (channel)
 pub type InputEventSender = mpsc::UnboundedSender<crate::input::InputEvent>;
 /// 输入事件接收器 (channel)
 pub type InputEventReceiver = mpsc::UnboundedReceiver<crate::input::InputEvent>;
 
+/// WebSocket 连接类型别名
+type WsStream =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
 /// WebSocket 发送器类型别名
-type WsSender = futures_util::stream::SplitSink<
-    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
-    Message,
->;
+type WsSender = futures_util::stream::SplitSink<WsStream, Message>;
+
+/// WebSocket 接收器类型别名
+type WsReceiver = futures_util::stream::SplitStream<WsStream>;
 
 /// 视频数据包 (用于网络传输)
 #[derive(Debug, Clone)]
@@ -37,30 +80,14 @@ pub struct VideoPacket {
 }
 
 impl VideoPacket {
-    /// 序列化为 JSON + 二进制数据
-    pub fn to_wire_format(&self) -> Vec<u8> {
-        // 格式: [JSON header length (4 bytes)][JSON header][binary data]
-        let header = serde_json::json!({
-            "device_id": self.device_id,
-            "timestamp": self.timestamp,
-            "sequence": self.sequence,
-            "is_key_frame": self.is_key_frame,
-            "data_size": self.data.len(),
-        });
-
-        let header_str = header.to_string();
-        let header_bytes = header_str.as_bytes();
-
-        let mut result = Vec::with_capacity(4 + header_bytes.len() + self.data.len());
-
-        // 写入 header 长度 (big endian)
-        result.extend_from_slice(&(header_bytes.len() as u32).to_be_bytes());
-        // 写入 header
-        result.extend_from_slice(header_bytes);
-        // 写入数据
-        result.extend_from_slice(&self.data);
-
-        result
+    /// 按指定协议版本序列化 (格式见 [`protocol`])
+    pub fn to_wire_format(&self, version: ProtocolVersion) -> Vec<u8> {
+        protocol::encode(self, version)
+    }
+
+    /// 解析视频帧 (自动识别 v1/v2)
+    pub fn from_wire_format(bytes: &[u8]) -> Result<Self> {
+        protocol::decode(bytes)
     }
 }
 
@@ -88,6 +115,17 @@ pub struct VideoClientConfig {
     pub api_key: Option<String>,
     /// 是否使用 TLS
     pub use_tls: bool,
+    /// 是否启用端到端加密 (需要 security feature，服务器必须支持 e2ee_hello 握手)
+    pub e2ee: bool,
+    /// wss 连接时出示的客户端证书 (服务器要求 mTLS 时必填)
+    #[cfg(feature = "security")]
+    pub client_identity: Option<crate::security::TlsConfig>,
+    /// 发送端拥塞控制 (排队阈值和码率范围)
+    pub congestion: CongestionConfig,
+    /// 心跳 (Ping 间隔和超时)
+    pub keepalive: KeepaliveConfig,
+    /// 握手时提出压缩文本消息 (视频帧不压缩，见 [`compression`])
+    pub compression: bool,
 }
 
 impl Default for VideoClientConfig {
@@ -99,6 +137,12 @@ impl Default for VideoClientConfig {
             connect_timeout_secs: 10,
             api_key: None,
             use_tls: false,
+            e2ee: false,
+            #[cfg(feature = "security")]
+            client_identity: None,
+            congestion: CongestionConfig::default(),
+            keepalive: KeepaliveConfig::default(),
+            compression: true,
         }
     }
 }
@@ -108,16 +152,28 @@ pub struct VideoClient {
     url: String,
     device_id: String,
     config: VideoClientConfig,
-    sender: Arc<Mutex<Option<WsSender>>>,
-    sequence: Arc<Mutex<u64>>,
+    /// 发送任务句柄 (视频帧队列和控制消息)
+    writer: WriterHandle,
+    /// 发送任务，首次连接时启动
+    writer_task: std::sync::Mutex<Option<WriterTask>>,
+    sequence: AtomicU64,
+    /// 与服务器协商的帧格式版本 (每次连接重新协商，确认前使用 v1)
+    protocol: Arc<AtomicU8>,
     state: Arc<Mutex<ConnectionState>>,
     reconnect_count: Arc<Mutex<usize>>,
+    /// 连接序号，每次 (重新) 连接递增；旧连接的心跳任务据此退出
+    connection_id: Arc<AtomicU64>,
     should_stop: Arc<Mutex<bool>>,
     input_sender: InputEventSender,
     input_receiver: Arc<Mutex<Option<InputEventReceiver>>>,
+    /// 拥塞反馈回调
+    congestion_callback: std::sync::Mutex<Option<CongestionCallback>>,
     /// Token 管理器 (用于认证)
     #[cfg(feature = "security")]
     token_manager: Option<Arc<TokenManager>>,
+    /// E2EE 加密端 (握手完成后设置，每次重连重新握手)
+    #[cfg(feature = "security")]
+    sealer: Arc<Mutex<Option<E2eeSealer>>>,
 }
 
 impl VideoClient {
@@ -129,27 +185,31 @@ impl VideoClient {
     /// 使用配置创建客户端
     pub fn with_config(url: String, device_id: String, config: VideoClientConfig) -> Self {
         #[cfg(feature = "security")]
-        let token_manager = if let Some(ref api_key) = config.api_key {
-            Some(Arc::new(TokenManager::new(ApiKeyAuth::new(api_key.clone()))))
-        } else {
-            None
-        };
+        let token_manager = config.api_key.as_ref()
+            .map(|api_key| Arc::new(TokenManager::new(ApiKeyAuth::new(api_key.clone()))));
 
         let (input_sender, input_receiver) = mpsc::unbounded_channel();
+        let (writer, writer_task) = writer::writer(config.congestion.clone());
 
         VideoClient {
             url,
             device_id,
             config,
-            sender: Arc::new(Mutex::new(None)),
-            sequence: Arc::new(Mutex::new(0)),
+            writer,
+            writer_task: std::sync::Mutex::new(Some(writer_task)),
+            sequence: AtomicU64::new(0),
+            protocol: Arc::new(AtomicU8::new(ProtocolVersion::V1 as u8)),
             state: Arc::new(Mutex::new(ConnectionState::Disconnected)),
             reconnect_count: Arc::new(Mutex::new(0)),
+            connection_id: Arc::new(AtomicU64::new(0)),
             should_stop: Arc::new(Mutex::new(false)),
             input_sender,
             input_receiver: Arc::new(Mutex::new(Some(input_receiver))),
+            congestion_callback: std::sync::Mutex::new(None),
             #[cfg(feature = "security")]
             token_manager,
+            #[cfg(feature = "security")]
+            sealer: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -164,6 +224,31 @@ impl VideoClient {
             .ok_or_else(|| anyhow!("input_receiver 已被获取，此方法只能调用一次"))
     }
 
+    /// 设置拥塞反馈回调
+    ///
+    /// 发送队列积压时回调 `SetBitrate` 要求编码器降低码率，丢帧后回调 `KeyFrameNeeded`；
+    /// 回调在调用 `send_packet` 的任务中执行
+    pub async fn set_congestion_callback(&self, callback: impl Fn(CongestionFeedback) + Send + Sync + 'static) {
+        if let Ok(mut slot) = self.congestion_callback.lock() {
+            *slot = Some(Arc::new(callback));
+        }
+    }
+
+    /// 拥塞控制统计 (排队、丢帧、吞吐量和目标码率)
+    pub async fn congestion_stats(&self) -> CongestionStats {
+        self.writer.stats()
+    }
+
+    /// 设置带宽上限 (kbps，None = 不限制)，目标码率超过上限时通过拥塞回调降低编码码率
+    pub fn set_rate_limit(&self, kbps: Option<u32>) {
+        self.writer.set_rate_limit(kbps);
+    }
+
+    /// 当前使用的帧格式版本
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        ProtocolVersion::from_u8(self.protocol.load(Ordering::Relaxed)).unwrap_or(ProtocolVersion::V1)
+    }
+
     /// 发送认证消息
     ///
     /// 当配置了 API Key 时，在连接建立后自动调用此方法进行认证
@@ -183,14 +268,11 @@ impl VideoClient {
                 "token": token,
             });
 
-            let mut sender = self.sender.lock().await;
-            if let Some(ref mut s) = *sender {
-                s.send(Message::Text(auth_msg.to_string())).await
-                    .map_err(|e| anyhow!("发送认证消息失败: {:?}", e))?;
-                tracing::info!("认证消息已发送");
-            } else {
-                return Err(anyhow!("未连接到服务器"));
-            }
+            self.writer
+                .send(Message::Text(auth_msg.to_string()))
+                .await
+                .map_err(|e| anyhow!("发送认证消息失败: {}", e))?;
+            tracing::info!("认证消息已发送");
         }
         Ok(())
     }
@@ -215,6 +297,17 @@ impl VideoClient {
 
     /// 连接到 WebSocket 服务器
     pub async fn connect(&self) -> Result<()> {
+        // 启动发送任务 (只启动一次，重连后复用)
+        let writer_task = self.writer_task.lock().ok().and_then(|mut task| task.take());
+        if let Some(task) = writer_task {
+            task.spawn(
+                self.state.clone(),
+                self.config.keepalive,
+                #[cfg(feature = "security")]
+                self.sealer.clone(),
+            );
+        }
+
         self._connect().await?;
 
         // 启动重连监控任务
@@ -222,10 +315,14 @@ impl VideoClient {
             let should_stop = self.should_stop.clone();
             let state = self.state.clone();
             let url = self.url.clone();
-            let sender = self.sender.clone();
+            let writer = self.writer.clone();
             let config = self.config.clone();
             let reconnect_count = self.reconnect_count.clone();
+            let connection_id = self.connection_id.clone();
             let input_sender = self.input_sender.clone();
+            let negotiated = self.protocol.clone();
+            #[cfg(feature = "security")]
+            let sealer_slot = self.sealer.clone();
 
             tokio::spawn(async move {
                 let mut interval = tokio::time::interval(Duration::from_millis(config.reconnect_interval_ms));
@@ -251,10 +348,23 @@ impl VideoClient {
 
                         tracing::info!("尝试重新连接到: {}", url);
 
-                        match connect_async(&url).await {
-                            Ok((ws_stream, _)) => {
-                                let (s, mut r) = ws_stream.split();
-                                *sender.lock().await = Some(s);
+                        match connect_ws(&url, &config).await {
+                            Ok((ws_stream, compressed)) => {
+                                #[cfg(feature = "security")]
+                                let mut ws_stream = ws_stream;
+                                #[cfg(feature = "security")]
+                                let opener = match setup_e2ee(&mut ws_stream, &config, &sealer_slot).await {
+                                    Ok(opener) => opener,
+                                    Err(e) => {
+                                        tracing::warn!("重连后 E2EE 握手失败: {}", e);
+                                        *reconnect_count.lock().await += 1;
+                                        continue;
+                                    }
+                                };
+
+                                let (s, r) = ws_stream.split();
+                                let id = connection_id.fetch_add(1, Ordering::SeqCst) + 1;
+                                writer.attach(id, s, compressed).await;
                                 *state.lock().await = ConnectionState::Connected;
                                 *reconnect_count.lock().await = 0;
 
@@ -268,34 +378,28 @@ impl VideoClient {
                                     tracing::info!("重连后需要重新认证");
                                 }
 
-                                // 启动接收任务
-                                let state_clone = state.clone();
-                                let sender = input_sender.clone();
-                                tokio::spawn(async move {
-                                    while let Some(msg) = r.next().await {
-                                        match msg {
-                                            Ok(Message::Text(text)) => {
-                                                // 尝试解析为输入事件
-                                                if let Ok(event) = serde_json::from_str::<crate::input::InputEvent>(&text) {
-                                                    let _ = sender.send(event);
-                                                } else {
-                                                    tracing::debug!("收到消息: {}", text);
-                                                }
-                                            }
-                                            Ok(Message::Close(_)) => {
-                                                tracing::warn!("服务器关闭连接");
-                                                *state_clone.lock().await = ConnectionState::Disconnected;
-                                                break;
-                                            }
-                                            Err(e) => {
-                                                tracing::error!("接收错误: {}", e);
-                                                *state_clone.lock().await = ConnectionState::Disconnected;
-                                                break;
-                                            }
-                                            _ => {}
-                                        }
-                                    }
-                                });
+                                // 启动接收任务和心跳任务
+                                let liveness = Liveness::new();
+                                let receiver_task = spawn_receiver(
+                                    r,
+                                    state.clone(),
+                                    input_sender.clone(),
+                                    negotiated.clone(),
+                                    liveness.clone(),
+                                    compressed,
+                                    #[cfg(feature = "security")]
+                                    opener,
+                                );
+                                negotiate_protocol(&writer, &negotiated).await;
+                                spawn_keepalive(
+                                    writer.clone(),
+                                    state.clone(),
+                                    connection_id.clone(),
+                                    id,
+                                    liveness,
+                                    config.keepalive,
+                                    receiver_task,
+                                );
                             }
                             Err(e) => {
                                 tracing::warn!("重连失败: {}", e);
@@ -316,14 +420,30 @@ impl VideoClient {
 
         tracing::info!("连接到服务器: {}", self.url);
 
-        let (ws_stream, _) = connect_async(&self.url)
-            .await
-            .map_err(|e| anyhow!("连接失败: {}", e))?;
+        let (ws_stream, compressed) = connect_ws(&self.url, &self.config).await?;
 
-        let (sender, mut receiver) = ws_stream.split();
+        // 端到端加密握手 (必须在拆分读写之前完成)
+        #[cfg(feature = "security")]
+        let mut ws_stream = ws_stream;
+        #[cfg(feature = "security")]
+        let opener = match setup_e2ee(&mut ws_stream, &self.config, &self.sealer).await {
+            Ok(opener) => opener,
+            Err(e) => {
+                *self.state.lock().await = ConnectionState::Disconnected;
+                return Err(e);
+            }
+        };
+        #[cfg(not(feature = "security"))]
+        if self.config.e2ee {
+            *self.state.lock().await = ConnectionState::Disconnected;
+            return Err(anyhow!("端到端加密需要启用 security feature"));
+        }
+
+        let (sender, receiver) = ws_stream.split();
 
-        // 保存 sender
-        *self.sender.lock().await = Some(sender);
+        // sink 交给发送任务
+        let id = self.connection_id.fetch_add(1, Ordering::SeqCst) + 1;
+        self.writer.attach(id, sender, compressed).await;
         *self.state.lock().await = ConnectionState::Connected;
         *self.reconnect_count.lock().await = 0;
 
@@ -335,79 +455,68 @@ impl VideoClient {
             }
         }
 
-        // 启动接收任务
-        let connected = self.state.clone();
-        let sender = self.input_sender.clone();
-        tokio::spawn(async move {
-            while let Some(msg) = receiver.next().await {
-                match msg {
-                    Ok(Message::Text(text)) => {
-                        // 尝试解析为输入事件
-                        if let Ok(event) = serde_json::from_str::<crate::input::InputEvent>(&text) {
-                            let _ = sender.send(event);
-                        } else {
-                            tracing::debug!("收到消息: {}", text);
-                        }
-                    }
-                    Ok(Message::Close(_)) => {
-                        tracing::warn!("服务器关闭连接");
-                        *connected.lock().await = ConnectionState::Disconnected;
-                        break;
-                    }
-                    Err(e) => {
-                        tracing::error!("接收错误: {}", e);
-                        *connected.lock().await = ConnectionState::Disconnected;
-                        break;
-                    }
-                    _ => {}
-                }
-            }
-        });
+        // 启动接收任务和心跳任务
+        let liveness = Liveness::new();
+        let receiver_task = spawn_receiver(
+            receiver,
+            self.state.clone(),
+            self.input_sender.clone(),
+            self.protocol.clone(),
+            liveness.clone(),
+            compressed,
+            #[cfg(feature = "security")]
+            opener,
+        );
+        negotiate_protocol(&self.writer, &self.protocol).await;
+        spawn_keepalive(
+            self.writer.clone(),
+            self.state.clone(),
+            self.connection_id.clone(),
+            id,
+            liveness,
+            self.config.keepalive,
+            receiver_task,
+        );
 
         tracing::info!("连接成功");
         Ok(())
     }
 
     /// 发送视频数据包
+    ///
+    /// 数据包进入发送队列后立即返回；发送队列拥塞时非关键帧会被丢弃 (见 [`pacing`])，
+    /// 序号照常递增，接收端可据此发现丢帧
     pub async fn send_packet(&self, data: Vec<u8>, is_key_frame: bool) -> Result<()> {
-        let mut sender = self.sender.lock().await;
-        let sender = sender.as_mut().ok_or_else(|| anyhow!("未连接"))?;
+        if *self.state.lock().await != ConnectionState::Connected {
+            return Err(anyhow!("未连接"));
+        }
 
-        let mut seq = self.sequence.lock().await;
         let packet = VideoPacket {
             device_id: self.device_id.clone(),
             timestamp: crate::capture::Frame::current_timestamp(),
-            sequence: *seq,
+            sequence: self.sequence.fetch_add(1, Ordering::Relaxed),
             is_key_frame,
             data,
         };
-        *seq += 1;
-        drop(seq);
-
-        let wire_data = packet.to_wire_format();
-
-        match sender.send(Message::Binary(wire_data)).await {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                *self.state.lock().await = ConnectionState::Disconnected;
-                Err(anyhow!("发送失败: {}", e))
+        let (_, feedback) = self
+            .writer
+            .push_frame(QueuedFrame::new(packet.to_wire_format(self.protocol_version()), is_key_frame));
+
+        if !feedback.is_empty() {
+            let callback = self.congestion_callback.lock().ok().and_then(|callback| callback.clone());
+            if let Some(callback) = callback {
+                for feedback in feedback {
+                    callback(feedback);
+                }
             }
         }
+        Ok(())
     }
 
-    /// 发送原始数据
+    /// 发送原始数据 (经发送任务写入，优先于排队的视频帧)
     #[allow(dead_code)]
     pub async fn send_raw(&self, data: Vec<u8>) -> Result<()> {
-        let mut sender = self.sender.lock().await;
-        let sender = sender.as_mut().ok_or_else(|| anyhow!("未连接"))?;
-
-        match sender.send(Message::Binary(data)).await {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                *self.state.lock().await = ConnectionState::Disconnected;
-                Err(anyhow!("发送失败: {}", e))
-            }
-        }
+        self.writer.send(Message::Binary(data)).await
     }
 
     /// 检查是否已连接
@@ -425,17 +534,257 @@ impl VideoClient {
     pub async fn disconnect(&self) -> Result<()> {
         *self.should_stop.lock().await = true;
         *self.state.lock().await = ConnectionState::Disconnected;
-
-        let mut sender = self.sender.lock().await;
-        if let Some(mut s) = sender.take() {
-            s.close().await?;
-        }
+        self.writer.reset();
+        self.writer.close().await;
 
         tracing::info!("连接已断开");
         Ok(())
     }
 }
 
+#[async_trait::async_trait]
+impl MediaSink for VideoClient {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn is_ready(&self) -> bool {
+        self.is_connected().await
+    }
+
+    async fn send_video(&self, sample: VideoSample<'_>) -> Result<()> {
+        self.send_packet(sample.data.to_vec(), sample.is_key_frame).await
+    }
+
+    async fn send_event(&self, event: &MediaEvent) -> Result<()> {
+        let text = match event {
+            MediaEvent::Control(message) => serde_json::to_string(message)?,
+            MediaEvent::Chat(message) => serde_json::json!({ "type": "chat", "message": message }).to_string(),
+        };
+        self.writer.send(Message::Text(text)).await
+    }
+
+    async fn on_feedback(&self, callback: CongestionCallback) {
+        self.set_congestion_callback(move |feedback| callback(feedback)).await;
+    }
+
+    async fn congestion_stats(&self) -> Option<CongestionStats> {
+        Some(VideoClient::congestion_stats(self).await)
+    }
+}
+
+/// 建立 WebSocket 连接 (配置了客户端证书时以 mTLS 连接)
+///
+/// 返回连接和服务器是否同意压缩文本消息 (E2EE 会话不提出压缩)
+async fn connect_ws(url: &str, config: &VideoClientConfig) -> Result<(WsStream, bool)> {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::http::HeaderValue;
+
+    let mut request = url.into_client_request().map_err(|e| anyhow!("无效的服务器地址 {}: {}", url, e))?;
+    if config.compression && !config.e2ee {
+        request
+            .headers_mut()
+            .insert(compression::HEADER, HeaderValue::from_static(compression::DEFLATE));
+    }
+
+    #[cfg(feature = "security")]
+    if let Some(identity) = &config.client_identity {
+        use tokio_tungstenite::{connect_async_tls_with_config, Connector};
+
+        if !url.starts_with("wss://") {
+            return Err(anyhow!("客户端证书只能用于 wss:// 连接: {}", url));
+        }
+        let tls = identity.client_config_with_identity()?;
+        let (ws_stream, response) = connect_async_tls_with_config(request, None, false, Some(Connector::Rustls(tls)))
+            .await
+            .map_err(|e| anyhow!("连接失败 (mTLS): {}", e))?;
+        return Ok((ws_stream, compression_accepted(&response)));
+    }
+
+    let (ws_stream, response) = connect_async(request).await.map_err(|e| anyhow!("连接失败: {}", e))?;
+    Ok((ws_stream, compression_accepted(&response)))
+}
+
+/// 服务器是否在握手响应中同意压缩
+fn compression_accepted(response: &tokio_tungstenite::tungstenite::handshake::client::Response) -> bool {
+    let accepted = compression::accepts(response.headers().get(compression::HEADER).and_then(|value| value.to_str().ok()));
+    if accepted {
+        tracing::debug!("服务器同意压缩文本消息");
+    }
+    accepted
+}
+
+/// 按配置完成 E2EE 握手，并把加密端放入 `sealer_slot`
+///
+/// 未启用 E2EE 时清空加密端并返回 None
+#[cfg(feature = "security")]
+async fn setup_e2ee(
+    ws: &mut WsStream,
+    config: &VideoClientConfig,
+    sealer_slot: &Mutex<Option<E2eeSealer>>,
+) -> Result<Option<E2eeOpener>> {
+    if !config.e2ee {
+        *sealer_slot.lock().await = None;
+        return Ok(None);
+    }
+
+    let session = negotiate_e2ee(ws).await?;
+    tracing::info!("端到端加密已建立，安全码: {}", session.fingerprint());
+    let (sealer, opener) = session.split();
+    *sealer_slot.lock().await = Some(sealer);
+    Ok(Some(opener))
+}
+
+/// 作为发起方完成 E2EE 握手
+#[cfg(feature = "security")]
+pub(crate) async fn negotiate_e2ee(ws: &mut WsStream) -> Result<E2eeSession> {
+    let handshake = E2eeHandshake::new(e2ee::Role::Initiator);
+    ws.send(Message::Text(handshake.hello()))
+        .await
+        .map_err(|e| anyhow!("发送 E2EE 握手失败: {}", e))?;
+
+    let reply = tokio::time::timeout(E2EE_HANDSHAKE_TIMEOUT, async {
+        while let Some(msg) = ws.next().await {
+            match msg.map_err(|e| anyhow!("E2EE 握手接收失败: {}", e))? {
+                Message::Text(text) if e2ee::is_hello(&text) => return Ok(text),
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+        Err(anyhow!("服务器在 E2EE 握手完成前关闭连接"))
+    })
+    .await
+    .map_err(|_| anyhow!("E2EE 握手超时 (服务器可能不支持端到端加密)"))??;
+
+    handshake.complete(&reply)
+}
+
+/// 发送帧格式协商消息，服务器确认前使用 v1
+async fn negotiate_protocol(writer: &WriterHandle, negotiated: &AtomicU8) {
+    negotiated.store(ProtocolVersion::V1 as u8, Ordering::Relaxed);
+    if let Err(e) = writer.send(Message::Text(protocol::hello_message())).await {
+        tracing::warn!("发送协议协商消息失败: {}", e);
+    }
+}
+
+/// 启动接收任务 (解析输入事件和协议确认)
+///
+/// E2EE 会话中只接受加密帧，明文消息会被丢弃；已协商压缩时解压压缩消息。
+/// 收到任何数据 (包括 Pong) 都会刷新 `liveness`
+fn spawn_receiver(
+    mut receiver: WsReceiver,
+    state: Arc<Mutex<ConnectionState>>,
+    input_sender: InputEventSender,
+    negotiated: Arc<AtomicU8>,
+    liveness: Liveness,
+    compressed: bool,
+    #[cfg(feature = "security")] mut opener: Option<E2eeOpener>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(msg) = receiver.next().await {
+            if msg.is_ok() {
+                liveness.touch();
+            }
+            let text = match msg {
+                #[cfg(feature = "security")]
+                Ok(Message::Binary(data)) if opener.is_some() => {
+                    let Some(opener) = opener.as_mut() else { continue };
+                    match opener.open(&data).map(String::from_utf8) {
+                        Ok(Ok(text)) => text,
+                        Ok(Err(_)) => continue,
+                        Err(e) => {
+                            tracing::warn!("丢弃无法解密的消息: {}", e);
+                            continue;
+                        }
+                    }
+                }
+                Ok(Message::Binary(data)) if compressed && compression::is_compressed(&data) => {
+                    match compression::decompress(&data) {
+                        Ok(text) => text,
+                        Err(e) => {
+                            tracing::warn!("丢弃无法解压的消息: {}", e);
+                            continue;
+                        }
+                    }
+                }
+                Ok(Message::Text(text)) => {
+                    #[cfg(feature = "security")]
+                    if opener.is_some() {
+                        tracing::warn!("E2EE 会话中收到明文消息，已丢弃");
+                        continue;
+                    }
+                    text
+                }
+                Ok(Message::Close(_)) => {
+                    tracing::warn!("服务器关闭连接");
+                    *state.lock().await = ConnectionState::Disconnected;
+                    break;
+                }
+                Err(e) => {
+                    tracing::error!("接收错误: {}", e);
+                    *state.lock().await = ConnectionState::Disconnected;
+                    break;
+                }
+                _ => continue,
+            };
+
+            // 尝试解析为输入事件
+            if let Ok(event) = serde_json::from_str::<crate::input::InputEvent>(&text) {
+                let _ = input_sender.send(event);
+            } else if let Some(version) = protocol::parse_ack(&text) {
+                tracing::info!("服务器确认帧格式版本: v{}", version as u8);
+                negotiated.store(version as u8, Ordering::Relaxed);
+            } else {
+                tracing::debug!("收到消息: {}", text);
+            }
+        }
+    })
+}
+
+/// 启动心跳任务: 定期发送 Ping，超过 `pong_timeout` 未收到服务器数据时标记断开，
+/// 由重连任务在下一个重连间隔内重新连接
+///
+/// 连接断开或已被新连接取代时退出，并终止该连接的接收任务 (避免旧连接稍后把新连接标记为断开)
+fn spawn_keepalive(
+    writer: WriterHandle,
+    state: Arc<Mutex<ConnectionState>>,
+    connection_id: Arc<AtomicU64>,
+    id: u64,
+    liveness: Liveness,
+    keepalive: KeepaliveConfig,
+    receiver_task: JoinHandle<()>,
+) {
+    if !keepalive.is_enabled() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(keepalive.ping_interval);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if connection_id.load(Ordering::SeqCst) != id {
+                receiver_task.abort();
+                break;
+            }
+            if receiver_task.is_finished() || *state.lock().await != ConnectionState::Connected {
+                break;
+            }
+
+            if liveness.is_dead(keepalive.pong_timeout) {
+                tracing::warn!("{} 秒未收到服务器数据，判定连接已断开", keepalive.pong_timeout.as_secs());
+                receiver_task.abort();
+                writer.detach(id);
+                *state.lock().await = ConnectionState::Disconnected;
+                break;
+            }
+
+            // Ping 经发送任务写入 (写入失败或超时由发送任务标记断开)
+            writer.ping();
+        }
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -450,9 +799,14 @@ mod tests {
             data: vec![1, 2, 3, 4, 5],
         };
 
-        let wire_data = packet.to_wire_format();
-        assert!(wire_data.len() > 4);
-        assert!(wire_data.len() > packet.data.len());
+        for version in ProtocolVersion::SUPPORTED {
+            let wire_data = packet.to_wire_format(version);
+            assert!(wire_data.len() > packet.data.len());
+
+            let decoded = VideoPacket::from_wire_format(&wire_data).unwrap();
+            assert_eq!(decoded.device_id, packet.device_id);
+            assert_eq!(decoded.data, packet.data);
+        }
     }
 
     #[test]
@@ -461,5 +815,71 @@ mod tests {
         assert!(config.auto_reconnect);
         assert_eq!(config.reconnect_interval_ms, 2000);
         assert!(config.max_reconnect_attempts.is_none());
+        assert!(!config.e2ee);
+        assert!(config.keepalive.is_enabled());
+        assert!(config.compression);
+    }
+
+    #[cfg(feature = "security")]
+    #[tokio::test]
+    async fn test_e2ee_with_embedded_server() {
+        let mut server = crate::signaling::EmbeddedSignalingServer::new(0);
+        let port = server.start().await.unwrap();
+
+        let (mut ws, _) = connect_async(format!("ws://127.0.0.1:{}/ws", port)).await.unwrap();
+        let (mut sealer, mut opener) = negotiate_e2ee(&mut ws).await.unwrap().split();
+
+        let join = sealer.seal(br#"{"type":"join","room_id":"room"}"#).unwrap();
+        ws.send(Message::Binary(join)).await.unwrap();
+
+        // 服务器回复的成员列表同样是加密帧
+        let reply = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let Message::Binary(frame) = reply else {
+            panic!("E2EE 会话中应收到二进制帧: {:?}", reply);
+        };
+        let text = String::from_utf8(opener.open(&frame).unwrap()).unwrap();
+        assert!(text.contains("\"peers\""));
+
+        server.stop();
+    }
+
+    #[tokio::test]
+    async fn test_compression_with_embedded_server() {
+        use futures_util::SinkExt;
+
+        let mut server = crate::signaling::EmbeddedSignalingServer::new(0);
+        let port = server.start().await.unwrap();
+        let mut host_events = server.take_host_events().unwrap();
+        let url = format!("ws://127.0.0.1:{}/ws", port);
+
+        let config = VideoClientConfig { compression: false, ..Default::default() };
+        assert!(!connect_ws(&url, &config).await.unwrap().1);
+
+        let (mut ws, compressed) = connect_ws(&url, &VideoClientConfig::default()).await.unwrap();
+        assert!(compressed);
+        ws.send(Message::Text(r#"{"type":"join","room_id":"room"}"#.to_string())).await.unwrap();
+
+        // 较长的 Offer 压缩后作为二进制帧发送，服务器解压后转发给 Host
+        let sdp = "a=candidate:1 1 udp 2130706431 192.168.1.5 50000 typ host\r\n".repeat(20);
+        let offer = serde_json::json!({ "type": "offer", "from": "viewer", "to": "host", "sdp": sdp }).to_string();
+        ws.send(Message::Binary(compression::compress(&offer).unwrap())).await.unwrap();
+
+        let received = tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(event) = host_events.recv().await {
+                if let crate::signaling::HostSignalEvent::Offer { sdp, .. } = event {
+                    return sdp;
+                }
+            }
+            String::new()
+        })
+        .await
+        .unwrap();
+        assert_eq!(received, sdp);
+
+        server.stop();
     }
 }
@@ -0,0 +1,489 @@
+//! WebSocket 发送端拥塞控制
+//!
+//! WebSocket 跑在 TCP 上，网络跟不上时帧会堆积在发送缓冲区里，
+//! Wi-Fi 下很容易累积数秒延迟。`FrameQueue` 在应用层排队待发送的帧:
+//! - 测量发送吞吐量 (只统计 socket 忙碌的时间，即链路能接受的速率)
+//! - 排队帧数 (队列容量) 或最老帧的排队时间超过阈值时，从最老的开始丢弃排队中的非关键帧
+//!   (后面的非关键帧依赖它们，一并丢弃)，之后等待关键帧恢复；已排队的关键帧保留
+//! - 周期性给出目标码率，通过 [`CongestionFeedback`] 通知编码器
+//! - 配置了带宽上限时 (见 [`crate::quality::bandwidth`]) 按令牌桶限速: 目标码率不超过上限，
+//!   超出预算的非关键帧按拥塞处理丢弃
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 吞吐量测量窗口
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(2);
+
+/// 拥塞时目标码率取可用吞吐量的比例 (留出排空队列的余量)
+const BACKOFF_FACTOR: f64 = 0.85;
+
+/// 无拥塞时每个周期的码率增幅
+const PROBE_FACTOR: f64 = 1.1;
+
+/// 码率变化小于该比例时不通知编码器
+const MIN_BITRATE_CHANGE: f64 = 0.05;
+
+/// 限速令牌桶的容量 (按上限码率可以突发发送的时长)
+const RATE_LIMIT_BURST: Duration = Duration::from_millis(500);
+
+/// 拥塞控制配置
+#[derive(Debug, Clone)]
+pub struct CongestionConfig {
+    /// 排队帧数上限，超过后丢弃非关键帧
+    pub max_queued_frames: usize,
+    /// 最老帧的排队时间上限，超过后丢弃非关键帧
+    pub max_queue_delay: Duration,
+    /// 初始目标码率 (kbps)，应与编码器创建时的码率一致
+    pub initial_bitrate_kbps: u32,
+    /// 最小码率 (kbps)
+    pub min_bitrate_kbps: u32,
+    /// 最大码率 (kbps)
+    pub max_bitrate_kbps: u32,
+    /// 码率反馈周期
+    pub feedback_interval: Duration,
+    /// 带宽上限 (kbps，None = 不限制)
+    pub rate_limit_kbps: Option<u32>,
+}
+
+impl Default for CongestionConfig {
+    fn default() -> Self {
+        Self {
+            max_queued_frames: 5,
+            max_queue_delay: Duration::from_millis(200),
+            initial_bitrate_kbps: 2000,
+            min_bitrate_kbps: 300,
+            max_bitrate_kbps: 10000,
+            feedback_interval: Duration::from_secs(1),
+            rate_limit_kbps: None,
+        }
+    }
+}
+
+/// 发给编码器的拥塞反馈
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CongestionFeedback {
+    /// 调整目标码率 (kbps)
+    SetBitrate(u32),
+    /// 已丢帧，需要尽快产生关键帧
+    KeyFrameNeeded,
+}
+
+/// 拥塞反馈回调 (在调用 `send_packet` 的任务中执行，不应阻塞)
+pub type CongestionCallback = Arc<dyn Fn(CongestionFeedback) + Send + Sync>;
+
+/// 拥塞控制统计
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CongestionStats {
+    /// 当前排队帧数
+    pub queued_frames: usize,
+    /// 累计丢弃帧数
+    pub dropped_frames: u64,
+    /// 测得的发送吞吐量 (kbps)
+    pub throughput_kbps: Option<u32>,
+    /// 当前目标码率 (kbps)
+    pub target_bitrate_kbps: u32,
+    /// 当前带宽上限 (kbps)
+    pub rate_limit_kbps: Option<u32>,
+}
+
+/// 吞吐量测量
+///
+/// 记录每次发送的字节数和耗时，按 `总字节 / 总发送耗时` 计算。
+/// 空闲时间不计入，所以结果反映的是链路能接受的速率而不是画面产生的速率
+#[derive(Debug, Default)]
+pub struct ThroughputMeter {
+    samples: VecDeque<(Instant, usize, Duration)>,
+}
+
+impl ThroughputMeter {
+    /// 记录一次发送 (完成时间、字节数、耗时)
+    pub fn record(&mut self, at: Instant, bytes: usize, elapsed: Duration) {
+        self.samples.push_back((at, bytes, elapsed));
+        while let Some(&(first, ..)) = self.samples.front() {
+            if at.duration_since(first) <= THROUGHPUT_WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// 窗口内的吞吐量 (kbps)，没有样本时返回 None
+    pub fn kbps(&self) -> Option<u32> {
+        let bytes: usize = self.samples.iter().map(|(_, bytes, _)| bytes).sum();
+        let busy: Duration = self.samples.iter().map(|(.., elapsed)| *elapsed).sum();
+        if bytes == 0 {
+            return None;
+        }
+        // 发送耗时过短 (内核缓冲区直接吸收) 时按 1 ms 计，结果即为"很快"
+        let secs = busy.as_secs_f64().max(0.001);
+        Some((bytes as f64 * 8.0 / secs / 1000.0).min(u32::MAX as f64) as u32)
+    }
+}
+
+/// 令牌桶限速 (令牌以字节计)
+#[derive(Debug)]
+struct RateLimiter {
+    kbps: u32,
+    /// 可用字节数，关键帧可以透支
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    fn new(kbps: u32, now: Instant) -> Self {
+        let mut limiter = Self { kbps, tokens: 0.0, updated: now };
+        limiter.tokens = limiter.capacity();
+        limiter
+    }
+
+    fn bytes_per_sec(&self) -> f64 {
+        self.kbps as f64 * 1000.0 / 8.0
+    }
+
+    fn capacity(&self) -> f64 {
+        self.bytes_per_sec() * RATE_LIMIT_BURST.as_secs_f64()
+    }
+
+    /// 取出 `bytes` 字节的令牌，不足时返回 false (`force` 时照常扣除)
+    fn take(&mut self, bytes: usize, now: Instant, force: bool) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.bytes_per_sec()).min(self.capacity());
+        self.updated = now;
+        if !force && self.tokens < bytes as f64 {
+            return false;
+        }
+        self.tokens -= bytes as f64;
+        true
+    }
+}
+
+/// 待发送的帧
+#[derive(Debug)]
+pub struct QueuedFrame {
+    pub data: Vec<u8>,
+    pub is_key_frame: bool,
+    enqueued: Instant,
+}
+
+impl QueuedFrame {
+    pub fn new(data: Vec<u8>, is_key_frame: bool) -> Self {
+        Self { data, is_key_frame, enqueued: Instant::now() }
+    }
+}
+
+/// 发送队列
+#[derive(Debug)]
+pub struct FrameQueue {
+    config: CongestionConfig,
+    frames: VecDeque<QueuedFrame>,
+    meter: ThroughputMeter,
+    /// 丢帧后只接受关键帧
+    awaiting_key_frame: bool,
+    /// 尚未通知编码器的关键帧请求
+    key_frame_pending: bool,
+    target_bitrate: u32,
+    dropped: u64,
+    /// 本反馈周期内的丢帧数和最大排队时间
+    interval_dropped: u64,
+    interval_peak_delay: Duration,
+    last_feedback: Instant,
+    limiter: Option<RateLimiter>,
+    /// 上限变化后需要立即通知编码器的码率
+    limit_changed: bool,
+}
+
+impl FrameQueue {
+    pub fn new(config: CongestionConfig) -> Self {
+        let now = Instant::now();
+        let limiter = config.rate_limit_kbps.map(|kbps| RateLimiter::new(kbps, now));
+        let mut queue = Self {
+            config,
+            frames: VecDeque::new(),
+            meter: ThroughputMeter::default(),
+            awaiting_key_frame: false,
+            key_frame_pending: false,
+            target_bitrate: 0,
+            dropped: 0,
+            interval_dropped: 0,
+            interval_peak_delay: Duration::ZERO,
+            last_feedback: now,
+            limiter,
+            limit_changed: false,
+        };
+        queue.target_bitrate = queue.clamp_bitrate(queue.config.initial_bitrate_kbps);
+        queue
+    }
+
+    /// 码率限制在配置范围和带宽上限之内 (上限低于最小码率时以上限为准)
+    fn clamp_bitrate(&self, kbps: u32) -> u32 {
+        let max = self
+            .limiter
+            .as_ref()
+            .map_or(self.config.max_bitrate_kbps, |limiter| limiter.kbps.min(self.config.max_bitrate_kbps));
+        kbps.clamp(self.config.min_bitrate_kbps.min(max), max)
+    }
+
+    /// 设置带宽上限 (None = 不限制)，目标码率超过新上限时下一次反馈立即降低
+    pub fn set_rate_limit(&mut self, kbps: Option<u32>) {
+        if self.limiter.as_ref().map(|limiter| limiter.kbps) == kbps {
+            return;
+        }
+        self.limiter = kbps.map(|kbps| RateLimiter::new(kbps, Instant::now()));
+        let target = self.clamp_bitrate(self.target_bitrate);
+        if target != self.target_bitrate {
+            self.target_bitrate = target;
+            self.limit_changed = true;
+        }
+    }
+
+    /// 最老帧的排队时间
+    fn queue_delay(&self, now: Instant) -> Duration {
+        self.frames
+            .front()
+            .map_or(Duration::ZERO, |frame| now.saturating_duration_since(frame.enqueued))
+    }
+
+    fn is_congested(&self, now: Instant) -> bool {
+        self.frames.len() >= self.config.max_queued_frames
+            || self.queue_delay(now) > self.config.max_queue_delay
+    }
+
+    fn drop_frames(&mut self, count: usize) {
+        self.dropped += count as u64;
+        self.interval_dropped += count as u64;
+    }
+
+    /// 加入一帧，被丢弃时返回 false
+    ///
+    /// 拥塞时丢弃排队中最老的非关键帧，后续非关键帧缺少参考帧，会一直丢弃到下一个关键帧；
+    /// 拥塞时关键帧会取代队列中所有未发送的帧
+    pub fn push(&mut self, frame: QueuedFrame) -> bool {
+        let now = frame.enqueued;
+        let delay = self.queue_delay(now);
+        self.interval_peak_delay = self.interval_peak_delay.max(delay);
+
+        if frame.is_key_frame {
+            if self.is_congested(now) {
+                let stale = self.frames.len();
+                self.frames.clear();
+                self.drop_frames(stale);
+            }
+            if let Some(limiter) = &mut self.limiter {
+                limiter.take(frame.data.len(), now, true);
+            }
+            self.awaiting_key_frame = false;
+            self.key_frame_pending = false;
+            self.frames.push_back(frame);
+            return true;
+        }
+
+        if self.awaiting_key_frame {
+            self.drop_frames(1);
+            return false;
+        }
+        if self.is_congested(now) {
+            tracing::debug!("发送队列拥塞 ({} 帧, 排队 {:?})，丢帧并等待关键帧", self.frames.len(), delay);
+            let queued = self.frames.len();
+            self.frames.retain(|frame| frame.is_key_frame);
+            let stale = queued - self.frames.len();
+            self.awaiting_key_frame = true;
+            self.key_frame_pending = true;
+            self.drop_frames(stale + 1);
+            return false;
+        }
+        if self.limiter.as_mut().is_some_and(|limiter| !limiter.take(frame.data.len(), now, false)) {
+            tracing::debug!("超出带宽上限，丢帧并等待关键帧");
+            self.awaiting_key_frame = true;
+            self.key_frame_pending = true;
+            self.drop_frames(1);
+            return false;
+        }
+
+        self.frames.push_back(frame);
+        true
+    }
+
+    /// 取出下一帧发送
+    pub fn pop(&mut self) -> Option<QueuedFrame> {
+        self.frames.pop_front()
+    }
+
+    /// 记录一次完成的发送
+    pub fn on_sent(&mut self, bytes: usize, elapsed: Duration) {
+        self.meter.record(Instant::now(), bytes, elapsed);
+    }
+
+    /// 连接断开: 丢弃未发送的帧，重连后从关键帧开始
+    pub fn reset(&mut self) {
+        let stale = self.frames.len();
+        self.frames.clear();
+        self.drop_frames(stale);
+        self.awaiting_key_frame = true;
+        self.key_frame_pending = true;
+    }
+
+    /// 取出待通知编码器的反馈
+    ///
+    /// 关键帧请求和带宽上限引起的降码率立即返回；码率每个反馈周期评估一次:
+    /// 本周期丢过帧或排队时间超过上限的一半时降到测得吞吐量以下，
+    /// 排队时间低于上限的四分之一时逐步上调 (不超过带宽上限)
+    pub fn poll_feedback(&mut self, now: Instant) -> Vec<CongestionFeedback> {
+        let mut feedback = Vec::new();
+        if std::mem::take(&mut self.key_frame_pending) {
+            feedback.push(CongestionFeedback::KeyFrameNeeded);
+        }
+        if std::mem::take(&mut self.limit_changed) {
+            feedback.push(CongestionFeedback::SetBitrate(self.target_bitrate));
+        }
+        if now.saturating_duration_since(self.last_feedback) < self.config.feedback_interval {
+            return feedback;
+        }
+
+        let peak_delay = self.interval_peak_delay.max(self.queue_delay(now));
+        let current = self.target_bitrate as f64;
+        let target = if self.interval_dropped > 0 || peak_delay > self.config.max_queue_delay / 2 {
+            let available = self.meter.kbps().map_or(current, |kbps| current.min(kbps as f64));
+            available * BACKOFF_FACTOR
+        } else if peak_delay < self.config.max_queue_delay / 4 {
+            current * PROBE_FACTOR
+        } else {
+            current
+        };
+        let target = self.clamp_bitrate(target as u32);
+
+        if (target as f64 - current).abs() >= current * MIN_BITRATE_CHANGE {
+            tracing::debug!(
+                "WebSocket 码率调整: {} -> {} kbps (吞吐量 {:?} kbps, 排队 {:?}, 丢帧 {})",
+                self.target_bitrate,
+                target,
+                self.meter.kbps(),
+                peak_delay,
+                self.interval_dropped
+            );
+            self.target_bitrate = target;
+            feedback.push(CongestionFeedback::SetBitrate(target));
+        }
+
+        self.interval_dropped = 0;
+        self.interval_peak_delay = Duration::ZERO;
+        self.last_feedback = now;
+        feedback
+    }
+
+    pub fn stats(&self) -> CongestionStats {
+        CongestionStats {
+            queued_frames: self.frames.len(),
+            dropped_frames: self.dropped,
+            throughput_kbps: self.meter.kbps(),
+            target_bitrate_kbps: self.target_bitrate,
+            rate_limit_kbps: self.limiter.as_ref().map(|limiter| limiter.kbps),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(is_key_frame: bool) -> QueuedFrame {
+        QueuedFrame::new(vec![0; 1000], is_key_frame)
+    }
+
+    #[test]
+    fn test_throughput_ignores_idle_time() {
+        let mut meter = ThroughputMeter::default();
+        assert_eq!(meter.kbps(), None);
+        let now = Instant::now();
+        // 两次各 10 ms 发送 12500 字节 = 10 Mbps，中间的空闲不影响结果
+        meter.record(now, 12_500, Duration::from_millis(10));
+        meter.record(now + Duration::from_secs(1), 12_500, Duration::from_millis(10));
+        assert_eq!(meter.kbps(), Some(10_000));
+        // 超出窗口的样本被淘汰
+        meter.record(now + Duration::from_secs(4), 1_250, Duration::from_millis(10));
+        assert_eq!(meter.kbps(), Some(1_000));
+    }
+
+    #[test]
+    fn test_drops_until_key_frame() {
+        let mut queue = FrameQueue::new(CongestionConfig { max_queued_frames: 2, ..Default::default() });
+        assert!(queue.push(frame(true)));
+        assert!(queue.push(frame(false)));
+        // 队列已满: 丢弃排队中的非关键帧和新帧，保留关键帧，并请求关键帧
+        assert!(!queue.push(frame(false)));
+        assert_eq!(queue.poll_feedback(Instant::now()), vec![CongestionFeedback::KeyFrameNeeded]);
+        assert_eq!(queue.stats().queued_frames, 1);
+        assert_eq!(queue.stats().dropped_frames, 2);
+
+        // 队列排空后仍然丢弃非关键帧，直到关键帧到来
+        assert!(queue.pop().is_some_and(|frame| frame.is_key_frame));
+        assert!(!queue.push(frame(false)));
+        assert!(queue.push(frame(true)));
+        assert!(queue.push(frame(false)));
+        assert_eq!(queue.stats().dropped_frames, 3);
+        assert_eq!(queue.stats().queued_frames, 2);
+
+        // 拥塞时关键帧取代排队中的帧
+        assert!(queue.push(frame(true)));
+        assert_eq!(queue.stats().queued_frames, 1);
+        assert_eq!(queue.stats().dropped_frames, 5);
+    }
+
+    #[test]
+    fn test_bitrate_feedback() {
+        let config = CongestionConfig { max_queued_frames: 1, ..Default::default() };
+        let interval = config.feedback_interval;
+        let mut queue = FrameQueue::new(config);
+        let start = Instant::now();
+
+        // 拥塞: 降到测得吞吐量 (1000 kbps) 的 85%
+        queue.on_sent(12_500, Duration::from_millis(100));
+        assert!(queue.push(frame(true)));
+        assert!(!queue.push(frame(false)));
+        let feedback = queue.poll_feedback(start + interval);
+        assert_eq!(feedback, vec![CongestionFeedback::KeyFrameNeeded, CongestionFeedback::SetBitrate(850)]);
+
+        // 队列畅通: 逐步上调
+        queue.pop();
+        assert_eq!(queue.poll_feedback(start + interval * 2), vec![CongestionFeedback::SetBitrate(935)]);
+        // 未到反馈周期不评估
+        assert!(queue.poll_feedback(start + interval * 2).is_empty());
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        // 1000 kbps: 每秒 125000 字节，令牌桶容量 62500 字节
+        let config = CongestionConfig {
+            rate_limit_kbps: Some(1000),
+            max_queued_frames: 100,
+            max_queue_delay: Duration::from_secs(10),
+            ..Default::default()
+        };
+        let mut queue = FrameQueue::new(config);
+        assert_eq!(queue.stats().target_bitrate_kbps, 1000);
+        let start = Instant::now();
+        let frame_at = |is_key_frame, offset_ms| QueuedFrame {
+            data: vec![0; 25_000],
+            is_key_frame,
+            enqueued: start + Duration::from_millis(offset_ms),
+        };
+
+        // 关键帧可以透支，之后超出预算的非关键帧被丢弃并请求关键帧
+        assert!(queue.push(frame_at(true, 0)));
+        assert!(queue.push(frame_at(false, 0)));
+        assert!(!queue.push(frame_at(false, 0)));
+        assert_eq!(queue.poll_feedback(start), vec![CongestionFeedback::KeyFrameNeeded]);
+        // 令牌恢复后从关键帧继续
+        assert!(queue.push(frame_at(true, 200)));
+        assert!(queue.push(frame_at(false, 400)));
+
+        // 降低上限时立即通知编码器，取消上限后恢复原来的码率范围
+        queue.set_rate_limit(Some(500));
+        assert_eq!(queue.poll_feedback(start), vec![CongestionFeedback::SetBitrate(500)]);
+        assert_eq!(queue.stats().rate_limit_kbps, Some(500));
+        queue.set_rate_limit(None);
+        assert!(queue.poll_feedback(start).is_empty());
+        assert_eq!(queue.stats().rate_limit_kbps, None);
+    }
+}
@@ -0,0 +1,208 @@
+//! 设备清单登记 (被控端侧)
+//!
+//! 配置了 `fleet.server` 时与中心信令服务器保持一条 WebSocket 连接 (令牌以 Bearer 方式认证):
+//! 连接后发送 `register` 登记设备信息，之后按 `fleet.heartbeat_secs` 发送 `heartbeat`。
+//! 连接断开后按 1 秒起、翻倍至 [`MAX_BACKOFF`] 的间隔重连 (服务器侧见 `signaling::presence`)
+
+use crate::config::Config;
+use crate::signaling::presence::DeviceInfo;
+use anyhow::{anyhow, bail, Result};
+use futures_util::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+/// 重连间隔上限
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// 等待服务器确认登记的时长
+const REGISTER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 设备清单登记客户端
+#[derive(Debug, Clone)]
+pub struct PresenceClient {
+    url: String,
+    token: Option<String>,
+    device: DeviceInfo,
+    heartbeat: Duration,
+}
+
+impl PresenceClient {
+    /// 按配置创建 (未配置 `fleet.server` 时返回 None)
+    ///
+    /// `url`: 控制端连接本机使用的地址 (如公网隧道地址)，随登记信息上报
+    pub fn from_config(config: &Config, url: Option<String>) -> Option<Self> {
+        let server = config.fleet.server.clone()?;
+        Some(Self {
+            url: server,
+            token: config.fleet.token.clone().or_else(|| config.security.api_key.clone()),
+            device: DeviceInfo::local(&config.server.device_id, config.fleet.name.as_deref(), url),
+            heartbeat: Duration::from_secs(config.fleet.heartbeat_secs.max(1)),
+        })
+    }
+
+    /// 在后台保持登记 (任务被取消前一直重连)
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move { self.run().await })
+    }
+
+    async fn run(self) {
+        tracing::info!("登记到设备清单: {} ({})", self.url, self.device.device_id);
+        let mut backoff = Duration::from_secs(1);
+        loop {
+            match self.session(&mut backoff).await {
+                Ok(()) => tracing::info!("设备清单服务器关闭了连接"),
+                Err(e) => tracing::warn!("设备清单连接中断: {}", e),
+            }
+            tracing::debug!("{} 秒后重新连接设备清单服务器", backoff.as_secs());
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// 一次连接: 登记后持续发送心跳，直到连接断开 (登记成功后重置重连间隔)
+    async fn session(&self, backoff: &mut Duration) -> Result<()> {
+        let mut request = self
+            .url
+            .as_str()
+            .into_client_request()
+            .map_err(|e| anyhow!("无效的设备清单服务器地址 {}: {}", self.url, e))?;
+        if let Some(token) = &self.token {
+            let value = HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|_| anyhow!("令牌包含非法字符"))?;
+            request.headers_mut().insert("Authorization", value);
+        }
+        let (mut ws, _) = connect_async(request).await.map_err(|e| anyhow!("连接失败: {}", e))?;
+
+        ws.send(Message::Text(self.register_message().to_string())).await?;
+        tokio::time::timeout(REGISTER_TIMEOUT, async {
+            while let Some(msg) = ws.next().await {
+                if let Message::Text(text) = msg? {
+                    match reply_type(&text) {
+                        Reply::Registered => return Ok(()),
+                        Reply::Error(message) => bail!("登记被拒绝: {}", message),
+                        Reply::Other => {}
+                    }
+                }
+            }
+            bail!("连接已关闭")
+        })
+        .await
+        .map_err(|_| anyhow!("{} 秒内未收到登记确认", REGISTER_TIMEOUT.as_secs()))??;
+        tracing::info!("已登记到设备清单: {}", self.url);
+        *backoff = Duration::from_secs(1);
+
+        let mut ticker = tokio::time::interval(self.heartbeat);
+        ticker.tick().await;
+        let heartbeat = serde_json::json!({ "type": "heartbeat" }).to_string();
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    ws.send(Message::Text(heartbeat.clone())).await?;
+                }
+                msg = ws.next() => match msg {
+                    // Ping 由 tungstenite 在读取时自动回复 Pong
+                    Some(Ok(Message::Close(_))) | None => return Ok(()),
+                    Some(Ok(Message::Text(text))) => {
+                        if let Reply::Error(message) = reply_type(&text) {
+                            tracing::warn!("设备清单服务器返回错误: {}", message);
+                        }
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e.into()),
+                },
+            }
+        }
+    }
+
+    fn register_message(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "register",
+            "device": self.device,
+            "heartbeat_secs": self.heartbeat.as_secs(),
+        })
+    }
+}
+
+/// 服务器回复的类型
+#[derive(Debug, PartialEq, Eq)]
+enum Reply {
+    Registered,
+    Error(String),
+    Other,
+}
+
+fn reply_type(text: &str) -> Reply {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(text) else {
+        return Reply::Other;
+    };
+    match value.get("type").and_then(|t| t.as_str()) {
+        Some("registered") => Reply::Registered,
+        Some("error") => Reply::Error(value.get("message").and_then(|m| m.as_str()).unwrap_or_default().to_string()),
+        _ => Reply::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signaling::EmbeddedSignalingServer;
+    use crate::tools::probe::{self, ProbeTarget};
+    use crate::signaling::presence::DeviceStatus;
+
+    #[test]
+    fn test_from_config() {
+        let mut config = Config::default();
+        assert!(PresenceClient::from_config(&config, None).is_none());
+
+        config.fleet.server = Some("ws://127.0.0.1:9527/ws".to_string());
+        config.fleet.name = Some("Office".to_string());
+        config.security.api_key = Some("secret".to_string());
+        let client = PresenceClient::from_config(&config, Some("https://office.example.com".to_string())).unwrap();
+        assert_eq!(client.token.as_deref(), Some("secret"));
+        assert_eq!(client.device.name, "Office");
+        assert_eq!(client.device.device_id, config.server.device_id);
+        assert_eq!(client.register_message()["heartbeat_secs"], 30);
+        assert_eq!(client.register_message()["device"]["url"], "https://office.example.com");
+    }
+
+    #[test]
+    fn test_reply_type() {
+        assert_eq!(reply_type(r#"{"type":"registered","device_id":"a"}"#), Reply::Registered);
+        assert_eq!(reply_type(r#"{"type":"error","message":"满了"}"#), Reply::Error("满了".to_string()));
+        assert_eq!(reply_type(r#"{"type":"peers","peers":[]}"#), Reply::Other);
+        assert_eq!(reply_type("not json"), Reply::Other);
+    }
+
+    #[tokio::test]
+    async fn test_registers_with_embedded_server() {
+        let mut server = EmbeddedSignalingServer::new(0);
+        server.set_fleet_token(Some("fleet-secret".to_string()));
+        let port = server.start().await.unwrap();
+
+        let mut config = Config::default();
+        config.fleet.server = Some(format!("ws://127.0.0.1:{}/ws", port));
+        config.fleet.name = Some("Office".to_string());
+        config.fleet.token = Some("fleet-secret".to_string());
+        let task = PresenceClient::from_config(&config, None).unwrap().spawn();
+
+        let target = ProbeTarget::from_ip("127.0.0.1", port);
+        let mut devices = Vec::new();
+        for _ in 0..50 {
+            let (status, body) = probe::get(&target, "/devices", Some("fleet-secret")).await.unwrap();
+            assert_eq!(status, 200);
+            devices = serde_json::from_slice::<Vec<DeviceStatus>>(&body).unwrap();
+            if !devices.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].info.name, "Office");
+        assert!(devices[0].online);
+
+        task.abort();
+        server.stop();
+    }
+}
@@ -0,0 +1,321 @@
+//! 视频帧传输协议
+//!
+//! 两个版本:
+//! - v1 (旧版): `[JSON 帧头长度 (4 字节大端)][JSON 帧头][数据]`
+//! - v2: `[magic "SV" (2)][version (1)][flags (1)][varint sequence][varint timestamp]
+//!   [varint device_id 长度][device_id][varint 数据长度][数据][CRC32C (4 字节大端)]`
+//!
+//! v2 帧头通常只有十几个字节，CRC32C 覆盖帧尾之前的全部内容。
+//! v1 帧以长度前缀开头 (首字节为 0)，解码时按首字节自动识别版本。
+//!
+//! 版本协商: 连接建立后客户端发送 `{"type":"protocol_hello","versions":[1,2]}`，
+//! 服务器回复 `{"type":"protocol_ack","version":2}` 后改用 v2；
+//! 旧服务器不认识该消息，不会回复，客户端继续使用 v1
+
+use anyhow::{anyhow, Result};
+use crc::{Crc, CRC_32_ISCSI};
+
+use super::VideoPacket;
+
+/// v2 帧魔数
+pub const MAGIC: [u8; 2] = *b"SV";
+
+/// CRC32C (Castagnoli)
+const CRC32C: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
+
+/// 关键帧标志位
+const FLAG_KEY_FRAME: u8 = 0x01;
+
+/// 协议版本
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProtocolVersion {
+    /// JSON 帧头
+    V1 = 1,
+    /// 二进制帧头 + CRC32C
+    V2 = 2,
+}
+
+impl ProtocolVersion {
+    /// 本端支持的版本 (从低到高)
+    pub const SUPPORTED: [ProtocolVersion; 2] = [ProtocolVersion::V1, ProtocolVersion::V2];
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(ProtocolVersion::V1),
+            2 => Some(ProtocolVersion::V2),
+            _ => None,
+        }
+    }
+
+    /// 从对端提供的版本中选出双方都支持的最高版本 (没有交集时使用 v1)
+    pub fn negotiate(offered: &[u8]) -> Self {
+        offered
+            .iter()
+            .filter_map(|&version| Self::from_u8(version))
+            .max()
+            .unwrap_or(ProtocolVersion::V1)
+    }
+}
+
+/// 客户端发送的版本协商消息
+pub fn hello_message() -> String {
+    let versions: Vec<u8> = ProtocolVersion::SUPPORTED.iter().map(|&v| v as u8).collect();
+    serde_json::json!({ "type": "protocol_hello", "versions": versions }).to_string()
+}
+
+/// 服务器回复的版本确认消息
+pub fn ack_message(version: ProtocolVersion) -> String {
+    serde_json::json!({ "type": "protocol_ack", "version": version as u8 }).to_string()
+}
+
+/// 解析版本协商消息，返回对端提供的版本列表
+pub fn parse_hello(text: &str) -> Option<Vec<u8>> {
+    let value: serde_json::Value = serde_json::from_str(text).ok()?;
+    if value["type"] != "protocol_hello" {
+        return None;
+    }
+    value["versions"]
+        .as_array()?
+        .iter()
+        .map(|version| version.as_u64().and_then(|v| u8::try_from(v).ok()))
+        .collect()
+}
+
+/// 解析版本确认消息
+pub fn parse_ack(text: &str) -> Option<ProtocolVersion> {
+    let value: serde_json::Value = serde_json::from_str(text).ok()?;
+    if value["type"] != "protocol_ack" {
+        return None;
+    }
+    ProtocolVersion::from_u8(u8::try_from(value["version"].as_u64()?).ok()?)
+}
+
+/// 按指定版本编码视频帧
+pub fn encode(packet: &VideoPacket, version: ProtocolVersion) -> Vec<u8> {
+    match version {
+        ProtocolVersion::V1 => encode_v1(packet),
+        ProtocolVersion::V2 => encode_v2(packet),
+    }
+}
+
+/// 解码视频帧 (按首字节识别版本)
+pub fn decode(bytes: &[u8]) -> Result<VideoPacket> {
+    if bytes.starts_with(&MAGIC) {
+        decode_v2(bytes)
+    } else {
+        decode_v1(bytes)
+    }
+}
+
+fn encode_v1(packet: &VideoPacket) -> Vec<u8> {
+    let header = serde_json::json!({
+        "device_id": packet.device_id,
+        "timestamp": packet.timestamp,
+        "sequence": packet.sequence,
+        "is_key_frame": packet.is_key_frame,
+        "data_size": packet.data.len(),
+    })
+    .to_string();
+
+    let mut result = Vec::with_capacity(4 + header.len() + packet.data.len());
+    result.extend_from_slice(&(header.len() as u32).to_be_bytes());
+    result.extend_from_slice(header.as_bytes());
+    result.extend_from_slice(&packet.data);
+    result
+}
+
+fn decode_v1(bytes: &[u8]) -> Result<VideoPacket> {
+    let header_len = bytes
+        .get(..4)
+        .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+        .ok_or_else(|| anyhow!("视频帧过短"))?;
+    let header = bytes
+        .get(4..4 + header_len)
+        .ok_or_else(|| anyhow!("视频帧头不完整"))?;
+    let header: serde_json::Value = serde_json::from_slice(header)?;
+
+    Ok(VideoPacket {
+        device_id: header["device_id"].as_str().unwrap_or_default().to_string(),
+        timestamp: header["timestamp"].as_u64().unwrap_or_default(),
+        sequence: header["sequence"].as_u64().unwrap_or_default(),
+        is_key_frame: header["is_key_frame"].as_bool().unwrap_or_default(),
+        data: bytes[4 + header_len..].to_vec(),
+    })
+}
+
+fn encode_v2(packet: &VideoPacket) -> Vec<u8> {
+    let device_id = packet.device_id.as_bytes();
+
+    // 帧头最长 4 + 3 * 10 + 5 字节
+    let mut result = Vec::with_capacity(39 + device_id.len() + packet.data.len() + 4);
+    result.extend_from_slice(&MAGIC);
+    result.push(ProtocolVersion::V2 as u8);
+    result.push(if packet.is_key_frame { FLAG_KEY_FRAME } else { 0 });
+    write_varint(&mut result, packet.sequence);
+    write_varint(&mut result, packet.timestamp);
+    write_varint(&mut result, device_id.len() as u64);
+    result.extend_from_slice(device_id);
+    write_varint(&mut result, packet.data.len() as u64);
+    result.extend_from_slice(&packet.data);
+
+    let checksum = CRC32C.checksum(&result);
+    result.extend_from_slice(&checksum.to_be_bytes());
+    result
+}
+
+fn decode_v2(bytes: &[u8]) -> Result<VideoPacket> {
+    if bytes.len() < MAGIC.len() + 2 + 4 {
+        return Err(anyhow!("视频帧过短"));
+    }
+    let (body, checksum) = bytes.split_at(bytes.len() - 4);
+    let checksum = u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+    if CRC32C.checksum(body) != checksum {
+        return Err(anyhow!("视频帧校验失败"));
+    }
+
+    let version = body[2];
+    if ProtocolVersion::from_u8(version) != Some(ProtocolVersion::V2) {
+        return Err(anyhow!("不支持的视频帧版本: {}", version));
+    }
+    let flags = body[3];
+
+    let mut reader = Reader { bytes: body, offset: 4 };
+    let sequence = reader.varint()?;
+    let timestamp = reader.varint()?;
+    let device_id_len = reader.varint()? as usize;
+    let device_id = reader.take(device_id_len)?;
+    let data_len = reader.varint()? as usize;
+    let data = reader.take(data_len)?;
+    if reader.offset != body.len() {
+        return Err(anyhow!("视频帧长度不符"));
+    }
+
+    Ok(VideoPacket {
+        device_id: String::from_utf8(device_id.to_vec()).map_err(|_| anyhow!("设备 ID 不是有效的 UTF-8"))?,
+        timestamp,
+        sequence,
+        is_key_frame: flags & FLAG_KEY_FRAME != 0,
+        data: data.to_vec(),
+    })
+}
+
+/// LEB128 无符号变长整数
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.bytes.get(self.offset).ok_or_else(|| anyhow!("视频帧头不完整"))?;
+            self.offset += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(anyhow!("变长整数溢出"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.offset.checked_add(len).ok_or_else(|| anyhow!("视频帧长度不符"))?;
+        let slice = self.bytes.get(self.offset..end).ok_or_else(|| anyhow!("视频帧长度不符"))?;
+        self.offset = end;
+        Ok(slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet() -> VideoPacket {
+        VideoPacket {
+            device_id: "test-device".to_string(),
+            timestamp: 1_700_000_000_000,
+            sequence: 300,
+            is_key_frame: true,
+            data: vec![1, 2, 3, 4, 5],
+        }
+    }
+
+    #[test]
+    fn test_varint_round_trip() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, value);
+            let mut reader = Reader { bytes: &buf, offset: 0 };
+            assert_eq!(reader.varint().unwrap(), value);
+            assert_eq!(reader.offset, buf.len());
+        }
+
+        let mut buf = Vec::new();
+        write_varint(&mut buf, 300);
+        assert_eq!(buf, vec![0xac, 0x02]);
+    }
+
+    #[test]
+    fn test_v2_round_trip() {
+        let packet = packet();
+        let wire = encode(&packet, ProtocolVersion::V2);
+        assert!(wire.starts_with(&MAGIC));
+        assert!(wire.len() < encode(&packet, ProtocolVersion::V1).len());
+
+        let decoded = decode(&wire).unwrap();
+        assert_eq!(decoded.device_id, packet.device_id);
+        assert_eq!(decoded.timestamp, packet.timestamp);
+        assert_eq!(decoded.sequence, packet.sequence);
+        assert!(decoded.is_key_frame);
+        assert_eq!(decoded.data, packet.data);
+    }
+
+    #[test]
+    fn test_v2_rejects_corruption() {
+        let wire = encode(&packet(), ProtocolVersion::V2);
+
+        let mut corrupted = wire.clone();
+        let middle = corrupted.len() / 2;
+        corrupted[middle] ^= 0xff;
+        assert!(decode(&corrupted).is_err());
+
+        assert!(decode(&wire[..wire.len() - 1]).is_err());
+        assert!(decode(&MAGIC).is_err());
+    }
+
+    #[test]
+    fn test_v1_round_trip() {
+        let packet = packet();
+        let wire = encode(&packet, ProtocolVersion::V1);
+        assert_eq!(wire[0], 0);
+
+        let decoded = decode(&wire).unwrap();
+        assert_eq!(decoded.device_id, packet.device_id);
+        assert_eq!(decoded.sequence, packet.sequence);
+        assert_eq!(decoded.data, packet.data);
+    }
+
+    #[test]
+    fn test_negotiation() {
+        let offered = parse_hello(&hello_message()).unwrap();
+        assert_eq!(offered, vec![1, 2]);
+        assert_eq!(ProtocolVersion::negotiate(&offered), ProtocolVersion::V2);
+
+        // 旧客户端只提供 v1，未知版本被忽略
+        assert_eq!(ProtocolVersion::negotiate(&[1, 9]), ProtocolVersion::V1);
+        assert_eq!(ProtocolVersion::negotiate(&[]), ProtocolVersion::V1);
+
+        assert_eq!(parse_ack(&ack_message(ProtocolVersion::V2)), Some(ProtocolVersion::V2));
+        assert_eq!(parse_ack(r#"{"type":"protocol_ack","version":7}"#), None);
+        assert_eq!(parse_ack(r#"{"type":"mouse_move","x":1}"#), None);
+    }
+}
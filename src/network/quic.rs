@@ -0,0 +1,868 @@
+//! QUIC 媒体传输
+//!
+//! CLI 到 CLI 的远程控制不需要浏览器，WebRTC (ICE/DTLS/SRTP/RTP) 显得过重。
+//! 这里用一条 QUIC 连接承载全部流量:
+//! - 控制流 (客户端打开的双向流): hello (PIN)、welcome、输入事件、关键帧请求，
+//!   格式为 `[长度 (4 字节，大端)][JSON]`
+//! - 视频: 每个编码帧一条单向流，格式为 `[序号 (8)][时间戳 (8)][标志 (1)][编码数据]`
+//!
+//! 被控端使用每次启动生成的自签名证书，客户端记录证书指纹，可通过
+//! `QuicClientOptions::fingerprint` 固定。拒绝连接时以 `CLOSE_REJECTED` 错误码关闭连接，
+//! 关闭原因即拒绝原因
+//!
+//! 设备配对: hello 和 welcome 可以携带 [`DeviceProof`]，即设备长期密钥对本连接
+//! TLS 导出密钥 ([`QuicHandshake::channel_binding`]) 的签名。签名无法转移到其他连接，
+//! 双方据此按信任列表认证对方 (生成和校验见 `pairing` 模块)
+//!
+//! 拥塞控制: 被控端发送积压超过 `MAX_QUEUED_FRAMES` 时丢帧并请求关键帧；
+//! `QuicHostSession::bitrate_hint` 根据拥塞窗口和 RTT 估算可用码率，供编码器调整目标码率
+
+use anyhow::{anyhow, bail, Context, Result};
+use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+use quinn::{Connection, Endpoint, RecvStream, SendStream, VarInt};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::encoder::EncodedPacket;
+use crate::engine::{MediaEvent, MediaSink, VideoSample};
+use crate::input::InputEvent;
+
+/// ALPN 协议标识
+pub const ALPN: &[u8] = b"sscontrol-quic/1";
+
+/// 证书中的服务器名 (客户端按指纹校验，不校验名称)
+const SERVER_NAME: &str = "sscontrol";
+
+/// 控制消息最大长度
+const MAX_CONTROL_MESSAGE: usize = 64 * 1024;
+
+/// 单个视频帧最大长度 (未启用 h264 时 SimpleEncoder 传输原始 RGBA)
+const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+/// 视频帧头长度
+const FRAME_HEADER_LEN: usize = 17;
+
+/// 帧头标志: 关键帧
+const FLAG_KEY_FRAME: u8 = 0x01;
+
+/// 等待发送的帧上限，超过后丢帧直到下一个关键帧
+const MAX_QUEUED_FRAMES: usize = 3;
+
+/// 码率估算留出的余量 (控制流、重传和拥塞窗口波动)
+const BITRATE_HEADROOM: f64 = 0.8;
+
+/// 客户端发送 hello 的超时
+const HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 保活间隔 (低于 NAT 映射的常见超时)
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 空闲超时
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 正常关闭的应用错误码
+const CLOSE_NORMAL: u32 = 0;
+
+/// 被控端拒绝连接的应用错误码
+const CLOSE_REJECTED: u32 = 1;
+
+/// 通道绑定值的 TLS 导出标签
+const CHANNEL_BINDING_LABEL: &[u8] = b"EXPORTER-sscontrol-device";
+
+/// 签名通道绑定值的一方 (双方的绑定值不同，签名不能反射给对方)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceRole {
+    /// 控制端
+    Viewer,
+    /// 被控端
+    Host,
+}
+
+impl DeviceRole {
+    fn context(self) -> &'static [u8] {
+        match self {
+            Self::Viewer => b"viewer",
+            Self::Host => b"host",
+        }
+    }
+}
+
+/// 设备身份证明
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceProof {
+    /// 设备名
+    pub name: String,
+    /// Ed25519 公钥 (十六进制)
+    pub public_key: String,
+    /// 对通道绑定值的签名 (十六进制)
+    pub signature: String,
+}
+
+/// 用设备长期密钥签名通道绑定值 (启用 pairing feature 时由 `pairing::DeviceIdentity` 实现)
+pub trait DeviceSigner: std::fmt::Debug + Send + Sync {
+    fn prove(&self, binding: &[u8]) -> DeviceProof;
+}
+
+/// 本连接的通道绑定值 (TLS 导出密钥，两端相同)
+fn channel_binding(connection: &Connection, role: DeviceRole) -> Result<[u8; 32]> {
+    let mut binding = [0u8; 32];
+    connection
+        .export_keying_material(&mut binding, CHANNEL_BINDING_LABEL, role.context())
+        .map_err(|_| anyhow!("导出 TLS 密钥材料失败"))?;
+    Ok(binding)
+}
+
+/// 视频流信息 (被控端在 welcome 中下发)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamInfo {
+    /// 编码宽度
+    pub width: u32,
+    /// 编码高度
+    pub height: u32,
+    /// 编码格式 ("h264" 或 "raw")
+    pub codec: String,
+    /// 是否允许发送输入事件
+    pub input: bool,
+    /// 被控端的设备身份证明 (未启用配对的被控端不发送)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<DeviceProof>,
+}
+
+/// 控制流消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ControlMessage {
+    /// 客户端握手
+    Hello {
+        pin: Option<String>,
+        /// TOTP 验证码 (旧版客户端不发送)
+        #[serde(default)]
+        totp: Option<String>,
+        /// 控制端的设备身份证明
+        #[serde(default)]
+        device: Option<DeviceProof>,
+        /// 请求配对 (PIN 验证通过后被控端信任该设备)
+        #[serde(default)]
+        pair: bool,
+    },
+    /// 被控端接受连接
+    Welcome(StreamInfo),
+    /// 输入事件
+    Input { event: InputEvent },
+    /// 请求关键帧 (客户端解码出错或刚开始接收时)
+    KeyFrame,
+}
+
+/// 接收到的视频帧
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuicFrame {
+    pub sequence: u64,
+    /// 捕获时间戳 (毫秒)
+    pub timestamp: u64,
+    pub is_key_frame: bool,
+    pub data: Vec<u8>,
+}
+
+impl QuicFrame {
+    fn header(sequence: u64, timestamp: u64, is_key_frame: bool) -> [u8; FRAME_HEADER_LEN] {
+        let mut header = [0u8; FRAME_HEADER_LEN];
+        header[..8].copy_from_slice(&sequence.to_be_bytes());
+        header[8..16].copy_from_slice(&timestamp.to_be_bytes());
+        header[16] = if is_key_frame { FLAG_KEY_FRAME } else { 0 };
+        header
+    }
+
+    fn decode(mut buf: Vec<u8>) -> Result<Self> {
+        if buf.len() < FRAME_HEADER_LEN {
+            bail!("视频帧过短: {} 字节", buf.len());
+        }
+        let sequence = u64::from_be_bytes(buf[..8].try_into()?);
+        let timestamp = u64::from_be_bytes(buf[8..16].try_into()?);
+        let is_key_frame = buf[16] & FLAG_KEY_FRAME != 0;
+        buf.drain(..FRAME_HEADER_LEN);
+        Ok(Self { sequence, timestamp, is_key_frame, data: buf })
+    }
+}
+
+/// 根据拥塞窗口和 RTT 估算可用码率 (kbps)
+///
+/// 拥塞窗口是一个 RTT 内允许在途的字节数，`cwnd / rtt` 即当前允许的发送速率
+pub fn bitrate_hint_kbps(cwnd: u64, rtt: Duration) -> u32 {
+    let rtt = rtt.as_secs_f64().max(0.001);
+    let kbps = cwnd as f64 * 8.0 / rtt / 1000.0 * BITRATE_HEADROOM;
+    kbps.min(u32::MAX as f64) as u32
+}
+
+/// 证书指纹 (DER 的 SHA-256，小写十六进制)
+fn fingerprint(cert: &CertificateDer<'_>) -> String {
+    hex::encode(Sha256::digest(cert.as_ref()))
+}
+
+/// 规范化用户输入的指纹 (允许冒号分隔和大写)
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint.chars().filter(|c| *c != ':').collect::<String>().to_ascii_lowercase()
+}
+
+fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn transport_config() -> Arc<quinn::TransportConfig> {
+    let mut transport = quinn::TransportConfig::default();
+    transport.keep_alive_interval(Some(KEEP_ALIVE_INTERVAL));
+    transport.max_idle_timeout(Some(IDLE_TIMEOUT.try_into().expect("空闲超时超出 QUIC 范围")));
+    Arc::new(transport)
+}
+
+async fn write_message(send: &mut SendStream, message: &ControlMessage) -> Result<()> {
+    let body = serde_json::to_vec(message)?;
+    send.write_all(&(body.len() as u32).to_be_bytes()).await?;
+    send.write_all(&body).await?;
+    Ok(())
+}
+
+/// 读取一条控制消息 (对端正常结束控制流时返回 None)
+async fn read_message(recv: &mut RecvStream) -> Result<Option<ControlMessage>> {
+    let mut len = [0u8; 4];
+    match recv.read_exact(&mut len).await {
+        Ok(()) => {}
+        Err(quinn::ReadExactError::FinishedEarly(0)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_CONTROL_MESSAGE {
+        bail!("控制消息过大: {} 字节", len);
+    }
+    let mut body = vec![0u8; len];
+    recv.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// 连接被被控端拒绝时的错误 (其他关闭原因返回 None)
+fn rejection(connection: &Connection) -> Option<anyhow::Error> {
+    match connection.close_reason()? {
+        quinn::ConnectionError::ApplicationClosed(close) if close.error_code == VarInt::from_u32(CLOSE_REJECTED) => {
+            Some(anyhow!("被控端拒绝连接: {}", String::from_utf8_lossy(&close.reason)))
+        }
+        _ => None,
+    }
+}
+
+/// QUIC 服务端 (被控端)
+pub struct QuicServer {
+    endpoint: Endpoint,
+    fingerprint: String,
+}
+
+impl QuicServer {
+    /// 在指定地址监听 (自签名证书，每次启动重新生成)
+    pub fn bind(addr: SocketAddr) -> Result<Self> {
+        let certified = rcgen::generate_simple_self_signed(vec![SERVER_NAME.to_string()])
+            .context("生成自签名证书失败")?;
+        let cert = CertificateDer::from(certified.cert.der().to_vec());
+        let fingerprint = fingerprint(&cert);
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der()));
+
+        let mut tls = rustls::ServerConfig::builder_with_provider(crypto_provider())
+            .with_protocol_versions(&[&rustls::version::TLS13])?
+            .with_no_client_auth()
+            .with_single_cert(vec![cert], key)?;
+        tls.alpn_protocols = vec![ALPN.to_vec()];
+
+        let mut config = quinn::ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(tls)?));
+        config.transport_config(transport_config());
+        let endpoint = Endpoint::server(config, addr).with_context(|| format!("无法监听 QUIC 端口 {}", addr))?;
+
+        tracing::info!("QUIC 传输已监听: {} (证书指纹 {})", endpoint.local_addr()?, fingerprint);
+        Ok(Self { endpoint, fingerprint })
+    }
+
+    /// 实际监听地址
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.endpoint.local_addr()?)
+    }
+
+    /// 证书指纹 (SHA-256，小写十六进制)
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// 等待下一个连接 (服务端关闭后返回 None)
+    ///
+    /// 返回的连接尚未完成握手，调用方应在独立任务中调用 `handshake`，
+    /// 以免慢速客户端阻塞后续连接
+    pub async fn accept(&self) -> Option<QuicIncoming> {
+        self.endpoint.accept().await.map(|incoming| QuicIncoming { incoming })
+    }
+
+    /// 关闭服务端和所有连接
+    pub fn close(&self) {
+        self.endpoint.close(VarInt::from_u32(CLOSE_NORMAL), b"host shutdown");
+    }
+}
+
+/// 尚未完成握手的连接
+pub struct QuicIncoming {
+    incoming: quinn::Incoming,
+}
+
+impl QuicIncoming {
+    /// 客户端地址
+    pub fn remote_address(&self) -> SocketAddr {
+        self.incoming.remote_address()
+    }
+
+    /// 完成 QUIC 握手并读取客户端的 hello
+    pub async fn handshake(self) -> Result<QuicHandshake> {
+        let connection = self.incoming.await.context("QUIC 握手失败")?;
+        let hello = async {
+            let (send, mut recv) = connection.accept_bi().await?;
+            match read_message(&mut recv).await? {
+                Some(ControlMessage::Hello { pin, totp, device, pair }) => Ok((send, recv, pin, totp, device, pair)),
+                _ => Err(anyhow!("客户端未发送 hello")),
+            }
+        };
+        let (send, recv, pin, totp, device, pair) = tokio::time::timeout(HELLO_TIMEOUT, hello)
+            .await
+            .map_err(|_| anyhow!("等待客户端 hello 超时"))??;
+        Ok(QuicHandshake { connection, send, recv, pin, totp, device, pair })
+    }
+}
+
+/// 已握手、等待被控端决定是否接受的连接
+pub struct QuicHandshake {
+    connection: Connection,
+    send: SendStream,
+    recv: RecvStream,
+    pin: Option<String>,
+    totp: Option<String>,
+    device: Option<DeviceProof>,
+    pair: bool,
+}
+
+impl QuicHandshake {
+    /// 客户端地址
+    pub fn remote_address(&self) -> SocketAddr {
+        self.connection.remote_address()
+    }
+
+    /// 客户端提交的 PIN
+    pub fn pin(&self) -> Option<&str> {
+        self.pin.as_deref()
+    }
+
+    /// 客户端提交的 TOTP 验证码
+    pub fn totp(&self) -> Option<&str> {
+        self.totp.as_deref()
+    }
+
+    /// 客户端的设备身份证明 (签名尚未校验)
+    pub fn device(&self) -> Option<&DeviceProof> {
+        self.device.as_ref()
+    }
+
+    /// 客户端是否请求配对
+    pub fn pair_requested(&self) -> bool {
+        self.pair
+    }
+
+    /// 本连接的通道绑定值 (校验客户端证明用 `Viewer`，生成被控端证明用 `Host`)
+    pub fn channel_binding(&self, role: DeviceRole) -> Result<[u8; 32]> {
+        channel_binding(&self.connection, role)
+    }
+
+    /// 拒绝连接 (原因会显示在客户端)
+    pub fn reject(self, reason: &str) {
+        self.connection.close(VarInt::from_u32(CLOSE_REJECTED), reason.as_bytes());
+    }
+
+    /// 接受连接，开始会话
+    pub async fn accept(mut self, info: StreamInfo) -> Result<QuicHostSession> {
+        let input_allowed = info.input;
+        write_message(&mut self.send, &ControlMessage::Welcome(info)).await?;
+
+        // 控制流读取: 输入事件和关键帧请求
+        let key_frame_requested = Arc::new(AtomicBool::new(true));
+        let (input_tx, input_rx) = mpsc::unbounded_channel();
+        let requested = key_frame_requested.clone();
+        let mut recv = self.recv;
+        tokio::spawn(async move {
+            loop {
+                match read_message(&mut recv).await {
+                    Ok(Some(ControlMessage::Input { event })) => {
+                        // 无输入权限时丢弃
+                        if input_allowed && input_tx.send(event).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(Some(ControlMessage::KeyFrame)) => requested.store(true, Ordering::Relaxed),
+                    Ok(Some(other)) => tracing::debug!("忽略控制消息: {:?}", other),
+                    Ok(None) => break,
+                    Err(e) => {
+                        tracing::debug!("QUIC 控制流结束: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        // 视频发送: 单个任务按顺序为每帧打开单向流，保证帧顺序
+        let (frame_tx, mut frame_rx) = mpsc::channel::<(u64, EncodedPacket)>(MAX_QUEUED_FRAMES);
+        let connection = self.connection.clone();
+        tokio::spawn(async move {
+            while let Some((sequence, packet)) = frame_rx.recv().await {
+                let sent = async {
+                    let mut stream = connection.open_uni().await?;
+                    stream
+                        .write_all(&QuicFrame::header(sequence, packet.timestamp, packet.is_key_frame))
+                        .await?;
+                    stream.write_all(&packet.data).await?;
+                    stream.finish()?;
+                    anyhow::Ok(())
+                };
+                if let Err(e) = sent.await {
+                    tracing::debug!("QUIC 视频发送结束: {}", e);
+                    break;
+                }
+            }
+        });
+
+        Ok(QuicHostSession {
+            connection: self.connection,
+            _control: self.send,
+            frames: frame_tx,
+            sequence: AtomicU64::new(0),
+            awaiting_key_frame: AtomicBool::new(true),
+            key_frame_requested,
+            input_events: StdMutex::new(Some(input_rx)),
+        })
+    }
+}
+
+/// 被控端的 QUIC 会话
+pub struct QuicHostSession {
+    connection: Connection,
+    /// 保留控制流发送端 (丢弃会结束控制流)
+    _control: SendStream,
+    frames: mpsc::Sender<(u64, EncodedPacket)>,
+    sequence: AtomicU64,
+    /// 丢帧后只发送关键帧，直到发送成功
+    awaiting_key_frame: AtomicBool,
+    /// 需要编码器产生关键帧 (会话开始、客户端请求或丢帧后)
+    key_frame_requested: Arc<AtomicBool>,
+    input_events: StdMutex<Option<mpsc::UnboundedReceiver<InputEvent>>>,
+}
+
+impl QuicHostSession {
+    /// 客户端地址
+    pub fn remote_address(&self) -> SocketAddr {
+        self.connection.remote_address()
+    }
+
+    /// 取出输入事件接收端 (只能取一次)
+    pub fn take_input_events(&self) -> Option<mpsc::UnboundedReceiver<InputEvent>> {
+        self.input_events.lock().ok()?.take()
+    }
+
+    /// 是否需要编码器产生关键帧 (读取后清除)
+    pub fn take_key_frame_request(&self) -> bool {
+        self.key_frame_requested.swap(false, Ordering::Relaxed)
+    }
+
+    /// 发送一个编码帧
+    ///
+    /// 发送积压时丢帧并请求关键帧，返回 false；此后非关键帧都会被丢弃，
+    /// 避免客户端收到缺少参考帧的数据
+    pub fn send_frame(&self, packet: EncodedPacket) -> bool {
+        if self.awaiting_key_frame.load(Ordering::Relaxed) && !packet.is_key_frame {
+            return false;
+        }
+        let sequence = self.sequence.load(Ordering::Relaxed);
+        match self.frames.try_send((sequence, packet)) {
+            Ok(()) => {
+                self.sequence.store(sequence + 1, Ordering::Relaxed);
+                self.awaiting_key_frame.store(false, Ordering::Relaxed);
+                true
+            }
+            Err(_) => {
+                self.awaiting_key_frame.store(true, Ordering::Relaxed);
+                self.key_frame_requested.store(true, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// 根据拥塞控制状态估算的可用码率 (kbps)
+    pub fn bitrate_hint(&self) -> u32 {
+        let path = self.connection.stats().path;
+        bitrate_hint_kbps(path.cwnd, path.rtt)
+    }
+
+    /// 当前 RTT
+    pub fn rtt(&self) -> Duration {
+        self.connection.rtt()
+    }
+
+    /// 等待连接关闭
+    pub async fn closed(&self) {
+        self.connection.closed().await;
+    }
+
+    /// 关闭会话
+    pub fn close(&self) {
+        self.connection.close(VarInt::from_u32(CLOSE_NORMAL), b"session closed");
+    }
+}
+
+#[async_trait::async_trait]
+impl MediaSink for QuicHostSession {
+    fn name(&self) -> String {
+        self.remote_address().to_string()
+    }
+
+    /// 积压丢帧不算发送失败 (丢帧后会请求关键帧)
+    async fn send_video(&self, sample: VideoSample<'_>) -> Result<()> {
+        self.send_frame(EncodedPacket {
+            data: sample.data.to_vec(),
+            is_key_frame: sample.is_key_frame,
+            timestamp: crate::capture::Frame::current_timestamp(),
+            pts: 0,
+        });
+        Ok(())
+    }
+
+    async fn send_event(&self, _event: &MediaEvent) -> Result<()> {
+        Err(anyhow!("QUIC 会话不支持带外事件"))
+    }
+}
+
+/// 客户端连接选项
+#[derive(Debug, Clone)]
+pub struct QuicClientOptions {
+    /// 设备身份 (设置后在 hello 中发送设备证明，已配对的被控端不再要求 PIN)
+    pub device: Option<Arc<dyn DeviceSigner>>,
+    /// 请求配对 (需要同时提供 PIN)
+    pub pair: bool,
+    /// 被控端显示的 PIN (被控端要求 PIN 时必填)
+    pub pin: Option<String>,
+    /// TOTP 验证码 (被控端启用双因素验证时必填)
+    pub totp: Option<String>,
+    /// 被控端证书指纹 (为空时接受任意证书，连接后可通过 `fingerprint` 查看)
+    pub fingerprint: Option<String>,
+    /// 连接超时 (包含被控端审批的时间)
+    pub timeout: Duration,
+}
+
+impl Default for QuicClientOptions {
+    fn default() -> Self {
+        Self {
+            device: None,
+            pair: false,
+            pin: None,
+            totp: None,
+            fingerprint: None,
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// 按指纹校验被控端的自签名证书
+#[derive(Debug)]
+struct FingerprintVerifier {
+    provider: Arc<CryptoProvider>,
+    expected: Option<String>,
+    /// 握手时看到的证书指纹
+    seen: StdMutex<Option<String>>,
+}
+
+impl ServerCertVerifier for FingerprintVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let actual = fingerprint(end_entity);
+        if let Some(ref expected) = self.expected {
+            if *expected != actual {
+                return Err(rustls::Error::General(format!("证书指纹不匹配: {}", actual)));
+            }
+        }
+        if let Ok(mut seen) = self.seen.lock() {
+            *seen = Some(actual);
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// QUIC 客户端 (控制端)
+pub struct QuicClient {
+    endpoint: Endpoint,
+    connection: Connection,
+    control: Mutex<SendStream>,
+    /// 保留控制流接收端 (丢弃会通知被控端停止发送)
+    _control_recv: RecvStream,
+    info: StreamInfo,
+    fingerprint: String,
+}
+
+impl QuicClient {
+    /// 连接被控端，完成 PIN 验证 (以及被控端审批) 后返回
+    pub async fn connect(addr: SocketAddr, options: QuicClientOptions) -> Result<Self> {
+        let provider = crypto_provider();
+        let verifier = Arc::new(FingerprintVerifier {
+            provider: provider.clone(),
+            expected: options.fingerprint.as_deref().map(normalize_fingerprint),
+            seen: StdMutex::new(None),
+        });
+        let mut tls = rustls::ClientConfig::builder_with_provider(provider)
+            .with_protocol_versions(&[&rustls::version::TLS13])?
+            .dangerous()
+            .with_custom_certificate_verifier(verifier.clone())
+            .with_no_client_auth();
+        tls.alpn_protocols = vec![ALPN.to_vec()];
+        let mut config = quinn::ClientConfig::new(Arc::new(QuicClientConfig::try_from(tls)?));
+        config.transport_config(transport_config());
+
+        let bind: SocketAddr = if addr.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" }.parse()?;
+        let mut endpoint = Endpoint::client(bind)?;
+        endpoint.set_default_client_config(config);
+
+        let connect = async {
+            let connection = endpoint
+                .connect(addr, SERVER_NAME)?
+                .await
+                .with_context(|| format!("无法连接 {}", addr))?;
+            let device = match options.device {
+                Some(ref signer) => Some(signer.prove(&channel_binding(&connection, DeviceRole::Viewer)?)),
+                None => None,
+            };
+            let (mut send, mut recv) = connection.open_bi().await?;
+            let hello = ControlMessage::Hello {
+                pin: options.pin.clone(),
+                totp: options.totp.clone(),
+                device,
+                pair: options.pair,
+            };
+            write_message(&mut send, &hello).await?;
+            let info = match read_message(&mut recv).await {
+                Ok(Some(ControlMessage::Welcome(info))) => info,
+                Ok(_) => bail!("被控端返回了意外的消息"),
+                Err(e) => return Err(rejection(&connection).unwrap_or(e)),
+            };
+            Ok((connection, send, recv, info))
+        };
+        let (connection, send, recv, info) = tokio::time::timeout(options.timeout, connect)
+            .await
+            .map_err(|_| anyhow!("连接超时"))??;
+
+        let fingerprint = verifier.seen.lock().ok().and_then(|seen| seen.clone()).unwrap_or_default();
+        tracing::info!("QUIC 已连接: {} ({}x{} {}, 证书指纹 {})", addr, info.width, info.height, info.codec, fingerprint);
+
+        Ok(Self {
+            endpoint,
+            connection,
+            control: Mutex::new(send),
+            _control_recv: recv,
+            info,
+            fingerprint,
+        })
+    }
+
+    /// 视频流信息
+    pub fn stream_info(&self) -> &StreamInfo {
+        &self.info
+    }
+
+    /// 被控端证书指纹
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// 被控端的设备身份证明 (签名尚未校验)
+    pub fn host_device(&self) -> Option<&DeviceProof> {
+        self.info.device.as_ref()
+    }
+
+    /// 本连接的通道绑定值 (校验被控端证明用 `Host`)
+    pub fn channel_binding(&self, role: DeviceRole) -> Result<[u8; 32]> {
+        channel_binding(&self.connection, role)
+    }
+
+    /// 当前 RTT
+    pub fn rtt(&self) -> Duration {
+        self.connection.rtt()
+    }
+
+    /// 接收下一个视频帧 (连接关闭后返回 None)
+    pub async fn next_frame(&self) -> Option<Result<QuicFrame>> {
+        let mut stream = match self.connection.accept_uni().await {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::debug!("QUIC 连接已关闭: {}", e);
+                return None;
+            }
+        };
+        Some(match stream.read_to_end(MAX_FRAME_SIZE).await {
+            Ok(buf) => QuicFrame::decode(buf),
+            Err(e) => Err(e.into()),
+        })
+    }
+
+    /// 发送输入事件
+    pub async fn send_event(&self, event: &InputEvent) -> Result<()> {
+        if !self.info.input {
+            bail!("被控端未授予输入权限");
+        }
+        let message = ControlMessage::Input { event: event.clone() };
+        write_message(&mut *self.control.lock().await, &message).await
+    }
+
+    /// 请求关键帧
+    pub async fn request_key_frame(&self) -> Result<()> {
+        write_message(&mut *self.control.lock().await, &ControlMessage::KeyFrame).await
+    }
+
+    /// 断开连接
+    pub async fn close(&self) {
+        self.connection.close(VarInt::from_u32(CLOSE_NORMAL), b"viewer closed");
+        self.endpoint.wait_idle().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 把通道绑定值原样作为签名，用于比对两端的绑定值
+    #[derive(Debug)]
+    struct EchoSigner;
+
+    impl DeviceSigner for EchoSigner {
+        fn prove(&self, binding: &[u8]) -> DeviceProof {
+            DeviceProof { name: "viewer".to_string(), public_key: String::new(), signature: hex::encode(binding) }
+        }
+    }
+
+    fn packet(data: &[u8], is_key_frame: bool) -> EncodedPacket {
+        EncodedPacket { data: data.to_vec(), is_key_frame, timestamp: 42, pts: 0 }
+    }
+
+    #[test]
+    fn test_bitrate_hint() {
+        // 125 KB 窗口 / 100 ms = 10 Mbps，留 20% 余量
+        assert_eq!(bitrate_hint_kbps(125_000, Duration::from_millis(100)), 8000);
+        // RTT 为 0 时按 1 ms 计算，不会除零
+        assert_eq!(bitrate_hint_kbps(1_000, Duration::ZERO), 6400);
+    }
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let mut buf = QuicFrame::header(7, 1234, true).to_vec();
+        buf.extend_from_slice(b"frame");
+        let frame = QuicFrame::decode(buf).unwrap();
+        assert_eq!(frame, QuicFrame { sequence: 7, timestamp: 1234, is_key_frame: true, data: b"frame".to_vec() });
+        assert!(QuicFrame::decode(vec![0; 3]).is_err());
+        assert_eq!(normalize_fingerprint("AB:cd:EF"), "abcdef");
+    }
+
+    #[tokio::test]
+    async fn test_loopback_session() {
+        let server = QuicServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = server.local_addr().unwrap();
+        let info = StreamInfo { width: 64, height: 32, codec: "raw".to_string(), input: true, device: None };
+
+        let host = async {
+            // 第一个连接 PIN 错误被拒绝，第二个接受
+            server.accept().await.unwrap().handshake().await.unwrap().reject("PIN 错误");
+            let handshake = server.accept().await.unwrap().handshake().await.unwrap();
+            assert_eq!((handshake.pin(), handshake.totp()), (Some("123456"), Some("654321")));
+            // 两端导出的通道绑定值相同，不同角色的绑定值不同
+            let binding = handshake.channel_binding(DeviceRole::Viewer).unwrap();
+            assert_eq!(handshake.device().unwrap().signature, hex::encode(binding));
+            assert_ne!(binding, handshake.channel_binding(DeviceRole::Host).unwrap());
+            assert!(handshake.pair_requested());
+            handshake.accept(info.clone()).await.unwrap()
+        };
+        let viewer = async {
+            let options = QuicClientOptions { pin: Some("000000".to_string()), ..Default::default() };
+            let err = QuicClient::connect(addr, options).await.err().unwrap();
+            assert!(err.to_string().contains("PIN 错误"), "{}", err);
+
+            let options = QuicClientOptions {
+                pin: Some("123456".to_string()),
+                totp: Some("654321".to_string()),
+                fingerprint: Some(server.fingerprint().to_uppercase()),
+                device: Some(Arc::new(EchoSigner)),
+                pair: true,
+                ..Default::default()
+            };
+            QuicClient::connect(addr, options).await.unwrap()
+        };
+        let (session, client) = tokio::join!(host, viewer);
+        assert_eq!(client.stream_info(), &info);
+        assert_eq!(client.fingerprint(), server.fingerprint());
+
+        // 会话开始时需要关键帧，之前的非关键帧被丢弃
+        assert!(session.take_key_frame_request());
+        assert!(!session.send_frame(packet(b"delta", false)));
+        assert!(session.send_frame(packet(b"key", true)));
+        assert!(session.send_frame(packet(b"delta", false)));
+        let first = client.next_frame().await.unwrap().unwrap();
+        assert_eq!((first.sequence, first.is_key_frame, first.data.as_slice()), (0, true, &b"key"[..]));
+        let second = client.next_frame().await.unwrap().unwrap();
+        assert_eq!((second.sequence, second.is_key_frame, second.timestamp), (1, false, 42));
+
+        let mut input = session.take_input_events().unwrap();
+        client.send_event(&InputEvent::mouse_move(0.25, 0.5)).await.unwrap();
+        match input.recv().await.unwrap() {
+            InputEvent::MouseMove { x, y } => assert_eq!((x, y), (0.25, 0.5)),
+            other => panic!("unexpected event: {:?}", other),
+        }
+        client.request_key_frame().await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !session.take_key_frame_request() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        client.close().await;
+        session.closed().await;
+        assert!(client.next_frame().await.is_none());
+    }
+}
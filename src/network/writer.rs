@@ -0,0 +1,277 @@
+//! WebSocket 发送任务
+//!
+//! 连接的写半部 (sink) 只归发送任务所有，其他任务通过有界命令队列与它通信，
+//! 不会跨 await 持有 sink 的锁:
+//! - 视频帧进入 [`FrameQueue`] (容量和丢帧策略见 [`super::pacing`])，入队后唤醒发送任务
+//! - 认证、心跳等控制消息走命令队列，优先于视频帧发送
+//! - (重新) 连接后通过 `attach` 把新的 sink 交给发送任务
+//!
+//! 写入失败或超时时标记断开并清空队列，等待重连
+
+use anyhow::{anyhow, Result};
+use futures_util::SinkExt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, Mutex, Notify};
+use tokio_tungstenite::tungstenite::Message;
+
+use super::compression;
+use super::keepalive::KeepaliveConfig;
+use super::pacing::{CongestionConfig, CongestionFeedback, CongestionStats, FrameQueue, QueuedFrame};
+use super::{ConnectionState, WsSender};
+#[cfg(feature = "security")]
+use crate::security::e2ee::E2eeSealer;
+
+/// 命令队列容量
+const COMMAND_CAPACITY: usize = 32;
+
+/// 关闭连接时等待 Close 帧写入的时间
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+enum Command {
+    /// 新连接的写半部 (连接序号, 是否已协商消息压缩)
+    Attach(u64, WsSender, bool),
+    /// 连接已判定为断开 (序号与当前连接一致时才丢弃 sink)
+    Detach(u64),
+    /// 发送控制消息，ack 返回写入结果
+    Send(Message, Option<oneshot::Sender<Result<()>>>),
+    /// 发送 Close 帧并丢弃 sink
+    Close(oneshot::Sender<()>),
+}
+
+/// 发送任务句柄
+#[derive(Clone)]
+pub(crate) struct WriterHandle {
+    commands: mpsc::Sender<Command>,
+    queue: Arc<std::sync::Mutex<FrameQueue>>,
+    notify: Arc<Notify>,
+}
+
+/// 尚未启动的发送任务 (需要在 tokio 运行时中启动)
+pub(crate) struct WriterTask {
+    commands: mpsc::Receiver<Command>,
+    queue: Arc<std::sync::Mutex<FrameQueue>>,
+    notify: Arc<Notify>,
+}
+
+pub(crate) fn writer(congestion: CongestionConfig) -> (WriterHandle, WriterTask) {
+    let (commands, receiver) = mpsc::channel(COMMAND_CAPACITY);
+    let queue = Arc::new(std::sync::Mutex::new(FrameQueue::new(congestion)));
+    let notify = Arc::new(Notify::new());
+    (
+        WriterHandle { commands, queue: queue.clone(), notify: notify.clone() },
+        WriterTask { commands: receiver, queue, notify },
+    )
+}
+
+fn lock(queue: &std::sync::Mutex<FrameQueue>) -> std::sync::MutexGuard<'_, FrameQueue> {
+    queue.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl WriterHandle {
+    fn queue(&self) -> std::sync::MutexGuard<'_, FrameQueue> {
+        lock(&self.queue)
+    }
+
+    /// 视频帧入队，返回 (是否入队, 待通知编码器的反馈)
+    pub fn push_frame(&self, frame: QueuedFrame) -> (bool, Vec<CongestionFeedback>) {
+        let (queued, feedback) = {
+            let mut queue = self.queue();
+            let queued = queue.push(frame);
+            (queued, queue.poll_feedback(Instant::now()))
+        };
+        if queued {
+            self.notify.notify_one();
+        }
+        (queued, feedback)
+    }
+
+    pub fn stats(&self) -> CongestionStats {
+        self.queue().stats()
+    }
+
+    /// 设置带宽上限 (None = 不限制)
+    pub fn set_rate_limit(&self, kbps: Option<u32>) {
+        self.queue().set_rate_limit(kbps);
+    }
+
+    /// 丢弃未发送的帧，之后从关键帧开始
+    pub fn reset(&self) {
+        self.queue().reset();
+    }
+
+    /// 把新连接的 sink 交给发送任务 (`compressed`: 握手时已协商消息压缩)
+    pub async fn attach(&self, connection_id: u64, sink: WsSender, compressed: bool) {
+        let _ = self.commands.send(Command::Attach(connection_id, sink, compressed)).await;
+    }
+
+    /// 连接已断开 (心跳超时)，不再写入它的 sink
+    pub fn detach(&self, connection_id: u64) {
+        let _ = self.commands.try_send(Command::Detach(connection_id));
+    }
+
+    /// 发送控制消息并等待写入完成
+    pub async fn send(&self, message: Message) -> Result<()> {
+        let (ack, result) = oneshot::channel();
+        self.commands
+            .send(Command::Send(message, Some(ack)))
+            .await
+            .map_err(|_| anyhow!("发送任务已退出"))?;
+        result.await.map_err(|_| anyhow!("发送任务已退出"))?
+    }
+
+    /// 发送心跳 (命令队列已满时跳过，不等待)
+    pub fn ping(&self) {
+        let _ = self.commands.try_send(Command::Send(Message::Ping(Vec::new()), None));
+    }
+
+    /// 发送 Close 帧并关闭连接
+    pub async fn close(&self) {
+        let (ack, done) = oneshot::channel();
+        if self.commands.send(Command::Close(ack)).await.is_ok() {
+            let _ = done.await;
+        }
+    }
+}
+
+impl WriterTask {
+    /// 启动发送任务 (所有句柄释放后退出)
+    pub fn spawn(
+        self,
+        state: Arc<Mutex<ConnectionState>>,
+        keepalive: KeepaliveConfig,
+        #[cfg(feature = "security")] sealer: Arc<Mutex<Option<E2eeSealer>>>,
+    ) {
+        let WriterTask { mut commands, queue, notify } = self;
+
+        tokio::spawn(async move {
+            let mut sink: Option<(u64, WsSender, bool)> = None;
+
+            loop {
+                tokio::select! {
+                    biased;
+
+                    command = commands.recv() => match command {
+                        None => break,
+                        Some(Command::Attach(id, new_sink, compressed)) => {
+                            sink = Some((id, new_sink, compressed));
+                            notify.notify_one();
+                        }
+                        Some(Command::Detach(id)) => {
+                            if sink.as_ref().is_some_and(|(current, _, _)| *current == id) {
+                                sink = None;
+                            }
+                        }
+                        Some(Command::Send(message, ack)) => {
+                            let result = match sink.as_mut() {
+                                Some((_, s, compressed)) => {
+                                    let result = write(
+                                        s,
+                                        message,
+                                        *compressed,
+                                        &keepalive,
+                                        #[cfg(feature = "security")]
+                                        &sealer,
+                                    )
+                                    .await;
+                                    if let Err(e) = &result {
+                                        tracing::warn!("控制消息发送失败: {}", e);
+                                        sink = None;
+                                        *state.lock().await = ConnectionState::Disconnected;
+                                        lock(&queue).reset();
+                                    }
+                                    result
+                                }
+                                None => Err(anyhow!("未连接")),
+                            };
+                            if let Some(ack) = ack {
+                                let _ = ack.send(result);
+                            }
+                        }
+                        Some(Command::Close(ack)) => {
+                            if let Some((_, mut s, _)) = sink.take() {
+                                let _ = tokio::time::timeout(CLOSE_TIMEOUT, s.close()).await;
+                            }
+                            let _ = ack.send(());
+                        }
+                    },
+
+                    // 每次只发送一帧，之后重新检查控制命令
+                    _ = notify.notified(), if sink.is_some() => {
+                        let Some(frame) = lock(&queue).pop() else {
+                            continue;
+                        };
+                        let Some((_, s, _)) = sink.as_mut() else {
+                            continue;
+                        };
+
+                        let bytes = frame.data.len();
+                        let start = Instant::now();
+                        match write(
+                            s,
+                            Message::Binary(frame.data),
+                            false,
+                            &keepalive,
+                            #[cfg(feature = "security")]
+                            &sealer,
+                        )
+                        .await
+                        {
+                            Ok(()) => {
+                                let mut pending = lock(&queue);
+                                pending.on_sent(bytes, start.elapsed());
+                                crate::quality::bandwidth::monitor().record(bytes);
+                                if pending.stats().queued_frames > 0 {
+                                    notify.notify_one();
+                                }
+                            }
+                            Err(e) => {
+                                tracing::warn!("视频帧发送失败: {}", e);
+                                sink = None;
+                                *state.lock().await = ConnectionState::Disconnected;
+                                lock(&queue).reset();
+                            }
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// 写入一条消息
+///
+/// E2EE 会话中数据消息在写入前才加密 (加密计数器要求按发送顺序递增)，Ping/Close 等控制帧不加密；
+/// 否则已协商压缩时压缩较长的文本消息 (见 [`super::compression`])。
+/// 启用心跳时写入超过 `pong_timeout` 视为连接已死 (半开连接上发送缓冲区写满后 send 会一直阻塞)
+async fn write(
+    sink: &mut WsSender,
+    message: Message,
+    compressed: bool,
+    keepalive: &KeepaliveConfig,
+    #[cfg(feature = "security")] sealer: &Mutex<Option<E2eeSealer>>,
+) -> Result<()> {
+    #[cfg(feature = "security")]
+    let message = match message {
+        Message::Text(_) | Message::Binary(_) => match sealer.lock().await.as_mut() {
+            Some(sealer) => Message::Binary(sealer.seal(&message.into_data())?),
+            None => message,
+        },
+        message => message,
+    };
+    let message = match message {
+        Message::Text(text) if compressed => match compression::compress(&text) {
+            Some(data) => Message::Binary(data),
+            None => Message::Text(text),
+        },
+        message => message,
+    };
+
+    if !keepalive.is_enabled() {
+        return sink.send(message).await.map_err(|e| anyhow!("发送失败: {}", e));
+    }
+    match tokio::time::timeout(keepalive.pong_timeout, sink.send(message)).await {
+        Ok(result) => result.map_err(|e| anyhow!("发送失败: {}", e)),
+        Err(_) => Err(anyhow!("发送超时 ({} 秒)", keepalive.pong_timeout.as_secs())),
+    }
+}
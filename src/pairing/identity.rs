@@ -0,0 +1,228 @@
+//! 设备身份
+//!
+//! 每台设备首次使用时生成一个 Ed25519 密钥对，私钥保存在配对目录的 `device_key` 文件中
+//! (Unix 上仅当前用户可读)。配对时双方核对公钥的短指纹 (或扫描 `sscontrol://device` 二维码)，
+//! 之后的连接用私钥签名通道绑定值证明身份
+
+use anyhow::{anyhow, bail, Context, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// 设备二维码 URL 前缀
+const DEVICE_URL_PREFIX: &str = "sscontrol://device?";
+
+/// 短指纹取公钥 SHA-256 的字节数 (显示为 4 组 4 位十六进制)
+const FINGERPRINT_BYTES: usize = 8;
+
+/// 本机设备身份
+pub struct DeviceIdentity {
+    name: String,
+    key: SigningKey,
+}
+
+impl std::fmt::Debug for DeviceIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceIdentity")
+            .field("name", &self.name)
+            .field("fingerprint", &self.fingerprint())
+            .finish()
+    }
+}
+
+impl DeviceIdentity {
+    /// 生成新的设备身份
+    pub fn generate(name: &str) -> Self {
+        use rand::RngCore;
+        let mut secret = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut secret);
+        Self { name: name.to_string(), key: SigningKey::from_bytes(&secret) }
+    }
+
+    /// 读取私钥文件，不存在时生成并保存
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        let name = device_name();
+        if path.exists() {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("读取设备密钥 {} 失败", path.display()))?;
+            let secret: [u8; 32] = hex::decode(content.trim())
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| anyhow!("设备密钥 {} 格式错误", path.display()))?;
+            return Ok(Self { name, key: SigningKey::from_bytes(&secret) });
+        }
+
+        let identity = Self::generate(&name);
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        super::write_private(path, hex::encode(identity.key.to_bytes()).as_bytes())
+            .with_context(|| format!("保存设备密钥 {} 失败", path.display()))?;
+        tracing::info!("已生成设备密钥: {} (指纹 {})", path.display(), identity.fingerprint());
+        Ok(identity)
+    }
+
+    /// 设备名 (主机名)
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn public_key(&self) -> VerifyingKey {
+        self.key.verifying_key()
+    }
+
+    /// 公钥 (十六进制)
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.public_key().to_bytes())
+    }
+
+    /// 公钥短指纹
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.public_key())
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        self.key.sign(message)
+    }
+
+    /// 设备二维码内容 (`sscontrol://device?name=...&key=...`)
+    pub fn device_url(&self) -> String {
+        format!(
+            "{}name={}&key={}",
+            DEVICE_URL_PREFIX,
+            urlencoding::encode(&self.name),
+            self.public_key_hex()
+        )
+    }
+}
+
+#[cfg(feature = "quic")]
+impl crate::network::quic::DeviceSigner for DeviceIdentity {
+    fn prove(&self, binding: &[u8]) -> crate::network::quic::DeviceProof {
+        crate::network::quic::DeviceProof {
+            name: self.name.clone(),
+            public_key: self.public_key_hex(),
+            signature: hex::encode(self.sign(binding).to_bytes()),
+        }
+    }
+}
+
+/// 用设备身份签名广播信标，控制端可据此核对公布的设备指纹
+#[cfg(feature = "discovery")]
+impl crate::discovery::BeaconSigner for DeviceIdentity {
+    fn public_key(&self) -> VerifyingKey {
+        DeviceIdentity::public_key(self)
+    }
+
+    fn sign(&self, message: &[u8]) -> Signature {
+        DeviceIdentity::sign(self, message)
+    }
+}
+
+/// 校验设备证明，返回对方公钥
+#[cfg(feature = "quic")]
+pub fn verify_proof(proof: &crate::network::quic::DeviceProof, binding: &[u8]) -> Result<VerifyingKey> {
+    use ed25519_dalek::Verifier;
+
+    let key = parse_public_key(&proof.public_key)?;
+    let signature: [u8; 64] = hex::decode(&proof.signature)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("设备签名格式错误"))?;
+    key.verify(binding, &Signature::from_bytes(&signature))
+        .map_err(|_| anyhow!("设备 {} 的签名无效", proof.name))?;
+    Ok(key)
+}
+
+/// 公钥短指纹: SHA-256 的前 8 字节，大写十六进制每 4 位一组 (如 `3F2A-91C0-7B44-E215`)
+pub fn fingerprint(key: &VerifyingKey) -> String {
+    let digest = Sha256::digest(key.as_bytes());
+    hex::encode_upper(&digest[..FINGERPRINT_BYTES])
+        .as_bytes()
+        .chunks(4)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// 解析十六进制公钥
+pub fn parse_public_key(hex_key: &str) -> Result<VerifyingKey> {
+    let bytes: [u8; 32] = hex::decode(hex_key.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("公钥格式错误: {}", hex_key))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| anyhow!("无效的 Ed25519 公钥: {}", hex_key))
+}
+
+/// 解析设备二维码内容，返回 (设备名, 公钥)
+pub fn parse_device_url(url: &str) -> Result<(String, VerifyingKey)> {
+    let Some(query) = url.trim().strip_prefix(DEVICE_URL_PREFIX) else {
+        bail!("不是设备二维码: {}", url);
+    };
+    let mut name = None;
+    let mut key = None;
+    for pair in query.split('&') {
+        match pair.split_once('=') {
+            Some(("name", value)) => name = Some(urlencoding::decode(value)?.into_owned()),
+            Some(("key", value)) => key = Some(parse_public_key(value)?),
+            _ => {}
+        }
+    }
+    let key = key.ok_or_else(|| anyhow!("设备二维码缺少公钥"))?;
+    Ok((name.unwrap_or_else(|| fingerprint(&key)), key))
+}
+
+/// 本机设备名
+fn device_name() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "sscontrol".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_format() {
+        let identity = DeviceIdentity::generate("laptop");
+        let fingerprint = identity.fingerprint();
+        assert_eq!(fingerprint.len(), 19);
+        assert_eq!(fingerprint.matches('-').count(), 3);
+        assert_eq!(fingerprint, super::fingerprint(&parse_public_key(&identity.public_key_hex()).unwrap()));
+    }
+
+    #[test]
+    fn test_device_url_roundtrip() {
+        let identity = DeviceIdentity::generate("Office PC");
+        let (name, key) = parse_device_url(&identity.device_url()).unwrap();
+        assert_eq!(name, "Office PC");
+        assert_eq!(key, identity.public_key());
+        assert!(parse_device_url("sscontrol://pair?key=00").is_err());
+        assert!(parse_device_url("sscontrol://device?name=x&key=1234").is_err());
+    }
+
+    #[test]
+    fn test_load_or_create_persists_key() {
+        let path = std::env::temp_dir().join(format!("sscontrol-device-key-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let first = DeviceIdentity::load_or_create(&path).unwrap();
+        let second = DeviceIdentity::load_or_create(&path).unwrap();
+        assert_eq!(first.public_key(), second.public_key());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "quic")]
+    #[test]
+    fn test_verify_proof() {
+        use crate::network::quic::DeviceSigner;
+
+        let identity = DeviceIdentity::generate("laptop");
+        let proof = identity.prove(b"binding");
+        assert_eq!(verify_proof(&proof, b"binding").unwrap(), identity.public_key());
+        // 签名不能用于其他连接
+        assert!(verify_proof(&proof, b"other").is_err());
+    }
+}
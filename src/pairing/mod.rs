@@ -1,9 +1,100 @@
 //! 设备配对模块
 //!
-//! 提供零配置的跨网络设备配对功能
+//! - [`identity`] — 本机的 Ed25519 设备身份和公钥短指纹
+//! - [`trust`] — 已配对设备的信任列表
+//! - `qr` — 基于 QR 码的跨网络配对 (连接码来自 `discovery`，需同时启用 discovery feature)
+//!
+//! 配对数据保存在配置文件所在目录的 `pairing` 子目录中
 
 // 设备配对模块尚未完全激活，标记为允许死代码
 #![allow(dead_code)]
 
+pub mod identity;
+#[cfg(feature = "discovery")]
 pub mod qr;
+pub mod trust;
+
+pub use identity::DeviceIdentity;
+pub use trust::{TrustRole, TrustStore};
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// 配对目录 (配置文件所在目录下的 `pairing`)
+pub fn data_dir() -> PathBuf {
+    let config_path = crate::config::Config::get_config_path(None);
+    Path::new(&config_path)
+        .parent()
+        .map(|parent| parent.join("pairing"))
+        .unwrap_or_else(|| PathBuf::from("pairing"))
+}
+
+/// 本机设备身份 (首次使用时生成)
+pub fn load_identity() -> Result<DeviceIdentity> {
+    DeviceIdentity::load_or_create(&data_dir().join("device_key"))
+}
+
+/// 本机的信任列表
+pub fn load_trust_store() -> Result<TrustStore> {
+    TrustStore::load(&data_dir().join("trusted_devices.json"))
+}
+
+/// 被控端的配对状态: 本机设备身份和允许免 PIN 连接的控制端
+#[derive(Debug)]
+pub struct PairingState {
+    pub identity: DeviceIdentity,
+    trust: std::sync::Mutex<TrustStore>,
+}
+
+impl PairingState {
+    pub fn load() -> Result<Self> {
+        Ok(Self { identity: load_identity()?, trust: std::sync::Mutex::new(load_trust_store()?) })
+    }
+
+    /// 已配对的控制端返回其设备名，并记录本次连接时间
+    pub fn authenticate(&self, key: &ed25519_dalek::VerifyingKey) -> Option<String> {
+        let mut trust = self.trust.lock().ok()?;
+        let name = trust.find(TrustRole::Viewer, key)?.name.clone();
+        trust.touch(TrustRole::Viewer, key);
+        if let Err(e) = trust.save() {
+            tracing::warn!("{}", e);
+        }
+        Some(name)
+    }
+
+    /// 信任控制端 (PIN 验证和审批通过后)
+    pub fn pair_viewer(&self, name: &str, key: &ed25519_dalek::VerifyingKey) -> Result<()> {
+        let mut trust = self.trust.lock().map_err(|_| anyhow::anyhow!("信任列表锁已损坏"))?;
+        trust.trust(TrustRole::Viewer, name, key, None);
+        trust.save()
+    }
+}
+
+/// 终端显示的二维码
+pub fn render_qr(text: &str) -> Result<String> {
+    use qrcode::render::unicode::Dense1x2;
+    use qrcode::{EcLevel, QrCode};
+
+    let qr = QrCode::with_error_correction_level(text, EcLevel::M)?;
+    Ok(qr.render::<Dense1x2>().quiet_zone(true).build())
+}
 
+/// 写入只有当前用户可读的文件
+fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(content)
+    }
+    #[cfg(not(unix))]
+    {
+        std::fs::write(path, content)
+    }
+}
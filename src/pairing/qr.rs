@@ -7,7 +7,7 @@
 //! 2. 控制端扫描 QR 码
 //! 3. 自动发起连接
 
-use crate::discovery::ConnectionCode;
+use crate::discovery::connection_code::ConnectionCode;
 use anyhow::{anyhow, Result};
 use ed25519_dalek::{Signature, Signer, Verifier, SigningKey, VerifyingKey};
 use qrcode::{QrCode, EcLevel};
@@ -156,7 +156,7 @@ impl QrPairing {
             "sscontrol://pair?device_id={}&code={}&fp={}&ts={}&v={}&sig={}",
             urlencoding::encode(&data.device_id),
             urlencoding::encode(&data.connection_code),
-            hex::encode(&data.fingerprint),
+            hex::encode(data.fingerprint),
             data.timestamp,
             data.version,
             hex::encode(signature.to_bytes().as_slice())
@@ -248,7 +248,7 @@ impl QrPairing {
             "{}|{}|{}|{}|{}",
             data.device_id,
             data.connection_code,
-            hex::encode(&data.fingerprint),
+            hex::encode(data.fingerprint),
             data.timestamp,
             data.version
         )
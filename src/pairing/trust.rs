@@ -0,0 +1,215 @@
+//! 信任列表
+//!
+//! 已配对设备的公钥保存在配对目录的 `trusted_devices.json` 中，按角色区分:
+//! - `viewer`: 可以不输入 PIN 连接本机的控制端
+//! - `host`: 本机连接过并固定了公钥的被控端，公钥变化时拒绝连接
+//!
+//! 同一公钥在两个角色中各自独立，信任某台被控端不会允许它反过来控制本机
+
+use anyhow::{Context, Result};
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 信任角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrustRole {
+    /// 允许控制本机的控制端
+    Viewer,
+    /// 本机连接的被控端
+    Host,
+}
+
+impl std::fmt::Display for TrustRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Viewer => "控制端",
+            Self::Host => "被控端",
+        })
+    }
+}
+
+/// 已配对设备
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedDevice {
+    /// 设备名
+    pub name: String,
+    pub role: TrustRole,
+    /// Ed25519 公钥 (十六进制)
+    pub public_key: String,
+    /// 被控端地址 (host 角色，用于发现同一地址的公钥变化)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// 配对时间 (Unix 秒)
+    pub paired_at: u64,
+    /// 最近一次认证通过的时间 (Unix 秒)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<u64>,
+}
+
+impl TrustedDevice {
+    /// 公钥短指纹
+    pub fn fingerprint(&self) -> String {
+        super::identity::parse_public_key(&self.public_key)
+            .map(|key| super::identity::fingerprint(&key))
+            .unwrap_or_else(|_| "(无效公钥)".to_string())
+    }
+}
+
+/// 信任列表
+#[derive(Debug, Default)]
+pub struct TrustStore {
+    path: PathBuf,
+    devices: Vec<TrustedDevice>,
+}
+
+impl TrustStore {
+    /// 读取信任列表 (文件不存在时为空)
+    pub fn load(path: &Path) -> Result<Self> {
+        let devices = if path.exists() {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("读取信任列表 {} 失败", path.display()))?;
+            serde_json::from_str(&content).with_context(|| format!("信任列表 {} 格式错误", path.display()))?
+        } else {
+            Vec::new()
+        };
+        Ok(Self { path: path.to_path_buf(), devices })
+    }
+
+    /// 写回文件
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(&self.devices)?;
+        super::write_private(&self.path, content.as_bytes())
+            .with_context(|| format!("保存信任列表 {} 失败", self.path.display()))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn devices(&self) -> &[TrustedDevice] {
+        &self.devices
+    }
+
+    /// 按角色和公钥查找
+    pub fn find(&self, role: TrustRole, key: &VerifyingKey) -> Option<&TrustedDevice> {
+        let key = hex::encode(key.to_bytes());
+        self.devices.iter().find(|device| device.role == role && device.public_key == key)
+    }
+
+    /// 该地址上固定的被控端
+    pub fn host_at(&self, address: &str) -> Option<&TrustedDevice> {
+        self.devices
+            .iter()
+            .find(|device| device.role == TrustRole::Host && device.address.as_deref() == Some(address))
+    }
+
+    /// 信任设备 (同一角色的同一公钥只保留一条，更新名称和地址)
+    pub fn trust(&mut self, role: TrustRole, name: &str, key: &VerifyingKey, address: Option<&str>) {
+        let public_key = hex::encode(key.to_bytes());
+        self.devices.retain(|device| !(device.role == role && device.public_key == public_key));
+        if let Some(address) = address {
+            // 地址上原来固定的被控端已被用户确认替换
+            self.devices
+                .retain(|device| !(device.role == role && device.address.as_deref() == Some(address)));
+        }
+        self.devices.push(TrustedDevice {
+            name: name.to_string(),
+            role,
+            public_key,
+            address: address.map(str::to_string),
+            paired_at: unix_now(),
+            last_seen: None,
+        });
+    }
+
+    /// 记录认证通过的时间
+    pub fn touch(&mut self, role: TrustRole, key: &VerifyingKey) {
+        let key = hex::encode(key.to_bytes());
+        if let Some(device) = self
+            .devices
+            .iter_mut()
+            .find(|device| device.role == role && device.public_key == key)
+        {
+            device.last_seen = Some(unix_now());
+        }
+    }
+
+    /// 按设备名或指纹前缀 (不区分大小写，可省略 `-`) 删除，返回删除的设备
+    pub fn remove(&mut self, selector: &str) -> Vec<TrustedDevice> {
+        let prefix = normalize_fingerprint(selector);
+        let matches = |device: &TrustedDevice| {
+            device.name == selector
+                || (prefix.len() >= 4 && normalize_fingerprint(&device.fingerprint()).starts_with(&prefix))
+        };
+        let (removed, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.devices).into_iter().partition(matches);
+        self.devices = kept;
+        removed
+    }
+}
+
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint.chars().filter(|c| *c != '-' && *c != ':').collect::<String>().to_ascii_uppercase()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pairing::identity::DeviceIdentity;
+
+    #[test]
+    fn test_trust_roles_are_separate() {
+        let mut store = TrustStore::default();
+        let laptop = DeviceIdentity::generate("laptop");
+        store.trust(TrustRole::Host, "laptop", &laptop.public_key(), Some("192.168.1.5:9527"));
+        assert!(store.find(TrustRole::Host, &laptop.public_key()).is_some());
+        assert!(store.find(TrustRole::Viewer, &laptop.public_key()).is_none());
+
+        // 同一地址重新配对替换原来的被控端
+        let replaced = DeviceIdentity::generate("laptop");
+        store.trust(TrustRole::Host, "laptop", &replaced.public_key(), Some("192.168.1.5:9527"));
+        assert_eq!(store.devices().len(), 1);
+        assert_eq!(store.host_at("192.168.1.5:9527").unwrap().public_key, replaced.public_key_hex());
+    }
+
+    #[test]
+    fn test_remove_by_name_or_fingerprint() {
+        let mut store = TrustStore::default();
+        let phone = DeviceIdentity::generate("phone");
+        let tablet = DeviceIdentity::generate("tablet");
+        store.trust(TrustRole::Viewer, "phone", &phone.public_key(), None);
+        store.trust(TrustRole::Viewer, "tablet", &tablet.public_key(), None);
+
+        assert_eq!(store.remove("phone").len(), 1);
+        // 指纹前缀至少 4 位，忽略大小写和分隔符
+        assert!(store.remove(&tablet.fingerprint()[..2]).is_empty());
+        let prefix = tablet.fingerprint()[..9].to_lowercase().replace('-', "");
+        assert_eq!(store.remove(&prefix)[0].name, "tablet");
+        assert!(store.devices().is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("sscontrol-trust-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let device = DeviceIdentity::generate("phone");
+        let mut store = TrustStore::load(&path).unwrap();
+        store.trust(TrustRole::Viewer, "phone", &device.public_key(), None);
+        store.touch(TrustRole::Viewer, &device.public_key());
+        store.save().unwrap();
+
+        let loaded = TrustStore::load(&path).unwrap();
+        assert_eq!(loaded.devices(), store.devices());
+        assert!(loaded.devices()[0].last_seen.is_some());
+        let _ = std::fs::remove_file(&path);
+    }
+}
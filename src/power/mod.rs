@@ -0,0 +1,163 @@
+//! 电源操作
+//!
+//! 锁屏、注销、重启和关机，通过系统自带的命令执行:
+//! - Windows: `rundll32 user32.dll,LockWorkStation` / `shutdown`
+//! - macOS: `pmset` / `osascript` (注销、重启、关机经 System Events，会先正常退出应用)
+//! - Linux: `loginctl` / `systemctl`
+//!
+//! 远程发起的操作需要相应的会话权限，见 [`PowerAction::required_permission`]
+
+#![allow(dead_code)]
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+use crate::signaling::Permission;
+
+/// 电源操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerAction {
+    /// 锁定屏幕
+    Lock,
+    /// 注销当前用户
+    Logout,
+    /// 重启
+    Reboot,
+    /// 关机
+    Shutdown,
+}
+
+impl PowerAction {
+    pub const ALL: [PowerAction; 4] = [Self::Lock, Self::Logout, Self::Reboot, Self::Shutdown];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Lock => "lock",
+            Self::Logout => "logout",
+            Self::Reboot => "reboot",
+            Self::Shutdown => "shutdown",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Lock => "锁屏",
+            Self::Logout => "注销",
+            Self::Reboot => "重启",
+            Self::Shutdown => "关机",
+        }
+    }
+
+    /// 远程发起该操作需要的权限
+    ///
+    /// 锁屏不会中断会话以外的工作，有输入权限即可；其余操作会结束用户会话或整个系统，
+    /// 需要单独授予的电源权限
+    pub fn required_permission(&self) -> Permission {
+        match self {
+            Self::Lock => Permission::Input,
+            Self::Logout | Self::Reboot | Self::Shutdown => Permission::Power,
+        }
+    }
+}
+
+impl std::fmt::Display for PowerAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl std::str::FromStr for PowerAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "lock" => Ok(Self::Lock),
+            "logout" | "logoff" | "log_out" => Ok(Self::Logout),
+            "reboot" | "restart" => Ok(Self::Reboot),
+            "shutdown" | "poweroff" => Ok(Self::Shutdown),
+            other => Err(anyhow!("未知的电源操作: {} (可选 lock / logout / reboot / shutdown)", other)),
+        }
+    }
+}
+
+/// 执行操作的命令 (程序, 参数)
+#[cfg(target_os = "windows")]
+fn command_for(action: PowerAction) -> Option<(&'static str, &'static [&'static str])> {
+    let command: (&str, &[&str]) = match action {
+        PowerAction::Lock => ("rundll32.exe", &["user32.dll,LockWorkStation"]),
+        PowerAction::Logout => ("shutdown", &["/l"]),
+        PowerAction::Reboot => ("shutdown", &["/r", "/t", "0"]),
+        PowerAction::Shutdown => ("shutdown", &["/s", "/t", "0"]),
+    };
+    Some(command)
+}
+
+#[cfg(target_os = "macos")]
+fn command_for(action: PowerAction) -> Option<(&'static str, &'static [&'static str])> {
+    let command: (&str, &[&str]) = match action {
+        // 需要在系统设置中开启 "进入睡眠或开始屏幕保护程序后立即要求输入密码"
+        PowerAction::Lock => ("pmset", &["displaysleepnow"]),
+        PowerAction::Logout => ("osascript", &["-e", "tell application \"System Events\" to log out"]),
+        PowerAction::Reboot => ("osascript", &["-e", "tell application \"System Events\" to restart"]),
+        PowerAction::Shutdown => ("osascript", &["-e", "tell application \"System Events\" to shut down"]),
+    };
+    Some(command)
+}
+
+#[cfg(target_os = "linux")]
+fn command_for(action: PowerAction) -> Option<(&'static str, &'static [&'static str])> {
+    let command: (&str, &[&str]) = match action {
+        PowerAction::Lock => ("loginctl", &["lock-session"]),
+        PowerAction::Logout => ("loginctl", &["terminate-session", "self"]),
+        PowerAction::Reboot => ("systemctl", &["reboot"]),
+        PowerAction::Shutdown => ("systemctl", &["poweroff"]),
+    };
+    Some(command)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn command_for(_action: PowerAction) -> Option<(&'static str, &'static [&'static str])> {
+    None
+}
+
+/// 执行电源操作 (阻塞直到命令返回；重启、关机命令返回后系统才开始关闭)
+pub fn execute(action: PowerAction) -> Result<()> {
+    let (program, args) = command_for(action).ok_or_else(|| anyhow!("当前平台不支持{}", action.label()))?;
+    tracing::info!("执行电源操作: {}", action.label());
+
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| anyhow!("{}失败: 无法运行 {}: {}", action.label(), program, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("{}失败: {} {}", action.label(), output.status, stderr.trim()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_action() {
+        for action in PowerAction::ALL {
+            assert_eq!(action.as_str().parse::<PowerAction>().unwrap(), action);
+        }
+        assert_eq!("Restart".parse::<PowerAction>().unwrap(), PowerAction::Reboot);
+        assert!("hibernate".parse::<PowerAction>().is_err());
+
+        let action: PowerAction = serde_json::from_str("\"shutdown\"").unwrap();
+        assert_eq!(action, PowerAction::Shutdown);
+    }
+
+    #[test]
+    fn test_required_permission() {
+        assert_eq!(PowerAction::Lock.required_permission(), Permission::Input);
+        assert_eq!(PowerAction::Reboot.required_permission(), Permission::Power);
+        assert_eq!(PowerAction::Shutdown.required_permission(), Permission::Power);
+    }
+}
@@ -0,0 +1,165 @@
+//! macOS 隐私模式
+//!
+//! 遮挡: 把所有显示器的 gamma 表置零，显示器输出全黑而帧缓冲不变，捕获画面不受影响；
+//! 恢复时还原 ColorSync 设置 (进程退出时系统也会自动还原)。
+//! 屏蔽输入: 独立线程的事件 tap 丢弃来源进程不是本进程的键盘鼠标事件
+//! (远程输入由本进程 CGEventPost 注入)
+
+use super::PrivacyBackend;
+use anyhow::{anyhow, Result};
+use core_foundation::base::TCFType;
+use core_foundation::mach_port::{CFMachPort, CFMachPortRef};
+use core_foundation::runloop::{kCFRunLoopCommonModes, CFRunLoop};
+use core_graphics::display::CGDisplay;
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::OnceLock;
+
+type CGEventRef = *mut c_void;
+type CGEventTapCallBack = extern "C" fn(*mut c_void, u32, CGEventRef, *mut c_void) -> CGEventRef;
+
+/// kCGHIDEventTap / kCGHeadInsertEventTap / kCGEventTapOptionDefault
+const HID_EVENT_TAP: u32 = 0;
+const HEAD_INSERT_EVENT_TAP: u32 = 0;
+const EVENT_TAP_OPTION_DEFAULT: u32 = 0;
+/// kCGEventSourceUnixProcessID
+const EVENT_SOURCE_UNIX_PROCESS_ID: u32 = 41;
+/// kCGEventTapDisabledByTimeout / kCGEventTapDisabledByUserInput
+const TAP_DISABLED_BY_TIMEOUT: u32 = 0xFFFF_FFFE;
+const TAP_DISABLED_BY_USER_INPUT: u32 = 0xFFFF_FFFF;
+
+/// 键盘、鼠标按键/移动/拖动和滚轮事件
+const INPUT_EVENTS: [u32; 14] = [1, 2, 3, 4, 5, 6, 7, 10, 11, 12, 22, 25, 26, 27];
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGEventTapCreate(
+        tap: u32,
+        place: u32,
+        options: u32,
+        events_of_interest: u64,
+        callback: CGEventTapCallBack,
+        user_info: *mut c_void,
+    ) -> CFMachPortRef;
+    fn CGEventTapEnable(tap: CFMachPortRef, enable: bool);
+    fn CGEventGetIntegerValueField(event: CGEventRef, field: u32) -> i64;
+    fn CGSetDisplayTransferByTable(
+        display: u32,
+        table_size: u32,
+        red: *const f32,
+        green: *const f32,
+        blue: *const f32,
+    ) -> i32;
+    fn CGDisplayRestoreColorSyncSettings();
+}
+
+/// 是否丢弃本地输入
+static BLOCK_INPUT: AtomicBool = AtomicBool::new(false);
+
+/// 事件 tap (被系统超时禁用后需要重新启用)
+static TAP: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+
+/// macOS 隐私模式
+#[derive(Default)]
+pub struct MacOSPrivacy;
+
+impl MacOSPrivacy {
+    /// 启动事件 tap 线程 (只启动一次)
+    fn start_tap() -> Result<()> {
+        static STARTED: OnceLock<Result<(), String>> = OnceLock::new();
+        STARTED
+            .get_or_init(|| {
+                let (tx, rx) = std::sync::mpsc::channel();
+                std::thread::Builder::new()
+                    .name("privacy".to_string())
+                    .spawn(move || run_tap(tx))
+                    .map_err(|e| e.to_string())?;
+                rx.recv().map_err(|_| "事件 tap 线程已退出".to_string())?
+            })
+            .clone()
+            .map_err(|e| anyhow!("屏蔽本地输入失败: {}", e))
+    }
+}
+
+impl PrivacyBackend for MacOSPrivacy {
+    fn set_curtain(&mut self, enabled: bool) -> Result<()> {
+        if !enabled {
+            unsafe { CGDisplayRestoreColorSyncSettings() };
+            return Ok(());
+        }
+
+        let displays = CGDisplay::active_displays().map_err(|e| anyhow!("获取显示器列表失败: {:?}", e))?;
+        let zeros = [0.0f32; 2];
+        for display in displays {
+            let error = unsafe {
+                CGSetDisplayTransferByTable(display, zeros.len() as u32, zeros.as_ptr(), zeros.as_ptr(), zeros.as_ptr())
+            };
+            if error != 0 {
+                unsafe { CGDisplayRestoreColorSyncSettings() };
+                return Err(anyhow!("设置显示器 {} 的 gamma 表失败: CGError {}", display, error));
+            }
+        }
+        Ok(())
+    }
+
+    fn set_input_blocked(&mut self, blocked: bool) -> Result<()> {
+        if blocked {
+            Self::start_tap()?;
+        }
+        BLOCK_INPUT.store(blocked, Ordering::Release);
+        Ok(())
+    }
+}
+
+/// 事件 tap 线程: 创建 tap 后运行 run loop
+fn run_tap(ready: std::sync::mpsc::Sender<Result<(), String>>) {
+    let mask = INPUT_EVENTS.iter().fold(0u64, |mask, &event| mask | (1 << event));
+    let port = unsafe {
+        CGEventTapCreate(
+            HID_EVENT_TAP,
+            HEAD_INSERT_EVENT_TAP,
+            EVENT_TAP_OPTION_DEFAULT,
+            mask,
+            tap_callback,
+            std::ptr::null_mut(),
+        )
+    };
+    if port.is_null() {
+        let _ = ready.send(Err("无法创建事件 tap，请在系统设置中授予辅助功能权限".to_string()));
+        return;
+    }
+    TAP.store(port as *mut c_void, Ordering::Release);
+
+    let port = unsafe { CFMachPort::wrap_under_create_rule(port) };
+    let source = match port.create_runloop_source(0) {
+        Ok(source) => source,
+        Err(()) => {
+            let _ = ready.send(Err("无法创建事件 tap 的 run loop 源".to_string()));
+            return;
+        }
+    };
+    CFRunLoop::get_current().add_source(&source, unsafe { kCFRunLoopCommonModes });
+    unsafe { CGEventTapEnable(port.as_concrete_TypeRef(), true) };
+    let _ = ready.send(Ok(()));
+
+    CFRunLoop::run_current();
+}
+
+extern "C" fn tap_callback(_proxy: *mut c_void, event_type: u32, event: CGEventRef, _user_info: *mut c_void) -> CGEventRef {
+    if event_type == TAP_DISABLED_BY_TIMEOUT || event_type == TAP_DISABLED_BY_USER_INPUT {
+        let tap = TAP.load(Ordering::Acquire);
+        if !tap.is_null() {
+            unsafe { CGEventTapEnable(tap as CFMachPortRef, true) };
+        }
+        return event;
+    }
+
+    if BLOCK_INPUT.load(Ordering::Acquire) {
+        let source_pid = unsafe { CGEventGetIntegerValueField(event, EVENT_SOURCE_UNIX_PROCESS_ID) };
+        if source_pid != std::process::id() as i64 {
+            // 返回 NULL 丢弃事件
+            return std::ptr::null_mut();
+        }
+    }
+    event
+}
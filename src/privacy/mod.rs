@@ -0,0 +1,224 @@
+//! 隐私模式
+//!
+//! 远程控制期间遮挡被控端的本地屏幕 (远端画面不受影响)，可选屏蔽本地键盘鼠标:
+//! - Windows: 置顶的黑色窗口 (`WDA_EXCLUDEFROMCAPTURE`，不出现在捕获画面中)；
+//!   低级键盘/鼠标钩子丢弃非注入的输入
+//! - macOS: 把显示器的 gamma 表置零 (只影响输出，帧缓冲和捕获画面不变)；
+//!   事件 tap 丢弃其他进程的输入 (需要辅助功能权限)
+//!
+//! 按会话计数: 最后一个会话结束时自动恢复；进程退出时系统也会撤销窗口、钩子和 gamma 设置
+
+#![allow(dead_code)]
+
+use anyhow::Result;
+use std::sync::Mutex;
+
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "windows")]
+mod windows;
+
+/// 平台实现
+pub trait PrivacyBackend: Send {
+    /// 遮挡或恢复本地屏幕
+    fn set_curtain(&mut self, enabled: bool) -> Result<()>;
+
+    /// 屏蔽或恢复本地键盘鼠标 (远程注入的输入不受影响)
+    fn set_input_blocked(&mut self, blocked: bool) -> Result<()>;
+}
+
+/// 创建平台特定的实现
+pub fn create_backend() -> Box<dyn PrivacyBackend> {
+    #[cfg(target_os = "macos")]
+    {
+        Box::new(macos::MacOSPrivacy::default())
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        Box::new(windows::WindowsPrivacy::default())
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        Box::new(Unsupported)
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+struct Unsupported;
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+impl PrivacyBackend for Unsupported {
+    fn set_curtain(&mut self, _enabled: bool) -> Result<()> {
+        Err(anyhow::anyhow!("当前平台不支持隐私模式"))
+    }
+
+    fn set_input_blocked(&mut self, _blocked: bool) -> Result<()> {
+        Err(anyhow::anyhow!("当前平台不支持隐私模式"))
+    }
+}
+
+struct State {
+    backend: Box<dyn PrivacyBackend>,
+    enabled: bool,
+    sessions: usize,
+}
+
+/// 隐私模式开关 (多个会话共享)
+pub struct PrivacyMode {
+    /// 会话建立时自动开启
+    auto: bool,
+    /// 开启时同时屏蔽本地输入
+    block_input: bool,
+    state: Mutex<State>,
+}
+
+impl PrivacyMode {
+    pub fn new(auto: bool, block_input: bool) -> Self {
+        Self::with_backend(auto, block_input, create_backend())
+    }
+
+    pub fn with_backend(auto: bool, block_input: bool, backend: Box<dyn PrivacyBackend>) -> Self {
+        Self {
+            auto,
+            block_input,
+            state: Mutex::new(State { backend, enabled: false, sessions: 0 }),
+        }
+    }
+
+    /// 当前是否开启
+    pub fn is_enabled(&self) -> bool {
+        self.state.lock().map(|state| state.enabled).unwrap_or(false)
+    }
+
+    /// 开启或关闭，返回切换后的状态
+    ///
+    /// 没有进行中的会话时不能开启；遮挡失败时返回错误并保持关闭
+    pub fn set(&self, enabled: bool) -> Result<bool> {
+        let mut state = self.state.lock().map_err(|_| anyhow::anyhow!("隐私模式状态已损坏"))?;
+        if enabled && state.sessions == 0 {
+            return Err(anyhow::anyhow!("没有进行中的会话，无法开启隐私模式"));
+        }
+        self.apply(&mut state, enabled)?;
+        Ok(state.enabled)
+    }
+
+    /// 会话开始 (配置了自动开启时在第一个会话开始时开启)
+    pub fn session_started(&self) {
+        let Ok(mut state) = self.state.lock() else { return };
+        state.sessions += 1;
+        if self.auto && !state.enabled {
+            if let Err(e) = self.apply(&mut state, true) {
+                tracing::warn!("开启隐私模式失败: {}", e);
+            }
+        }
+    }
+
+    /// 会话结束 (最后一个会话结束时恢复本地屏幕和输入)
+    pub fn session_ended(&self) {
+        let Ok(mut state) = self.state.lock() else { return };
+        state.sessions = state.sessions.saturating_sub(1);
+        if state.sessions == 0 && state.enabled {
+            if let Err(e) = self.apply(&mut state, false) {
+                tracing::warn!("关闭隐私模式失败: {}", e);
+            }
+        }
+    }
+
+    fn apply(&self, state: &mut State, enabled: bool) -> Result<()> {
+        if state.enabled == enabled {
+            return Ok(());
+        }
+
+        if enabled {
+            state.backend.set_curtain(true)?;
+            if self.block_input {
+                // 屏蔽输入失败不影响遮挡屏幕
+                if let Err(e) = state.backend.set_input_blocked(true) {
+                    tracing::warn!("屏蔽本地输入失败: {}", e);
+                }
+            }
+        } else {
+            if self.block_input {
+                if let Err(e) = state.backend.set_input_blocked(false) {
+                    tracing::warn!("恢复本地输入失败: {}", e);
+                }
+            }
+            state.backend.set_curtain(false)?;
+        }
+
+        state.enabled = enabled;
+        tracing::info!("隐私模式已{}", if enabled { "开启" } else { "关闭" });
+        Ok(())
+    }
+}
+
+impl Drop for PrivacyMode {
+    fn drop(&mut self) {
+        if let Ok(state) = self.state.get_mut() {
+            if state.enabled {
+                let _ = state.backend.set_input_blocked(false);
+                let _ = state.backend.set_curtain(false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// 记录调用的测试实现: (遮挡, 屏蔽输入)
+    #[derive(Clone, Default)]
+    struct Recording(Arc<Mutex<(bool, bool)>>);
+
+    impl PrivacyBackend for Recording {
+        fn set_curtain(&mut self, enabled: bool) -> Result<()> {
+            self.0.lock().unwrap().0 = enabled;
+            Ok(())
+        }
+
+        fn set_input_blocked(&mut self, blocked: bool) -> Result<()> {
+            self.0.lock().unwrap().1 = blocked;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_reverts_when_last_session_ends() {
+        let backend = Recording::default();
+        let privacy = PrivacyMode::with_backend(false, true, Box::new(backend.clone()));
+
+        // 没有会话时不能开启
+        assert!(privacy.set(true).is_err());
+
+        privacy.session_started();
+        privacy.session_started();
+        assert!(!privacy.is_enabled());
+        assert!(privacy.set(true).unwrap());
+        assert_eq!(*backend.0.lock().unwrap(), (true, true));
+
+        privacy.session_ended();
+        assert!(privacy.is_enabled());
+        privacy.session_ended();
+        assert!(!privacy.is_enabled());
+        assert_eq!(*backend.0.lock().unwrap(), (false, false));
+    }
+
+    #[test]
+    fn test_auto_enable() {
+        let backend = Recording::default();
+        let privacy = PrivacyMode::with_backend(true, false, Box::new(backend.clone()));
+
+        privacy.session_started();
+        assert!(privacy.is_enabled());
+        // 未配置屏蔽输入
+        assert_eq!(*backend.0.lock().unwrap(), (true, false));
+
+        assert!(!privacy.set(false).unwrap());
+        drop(privacy);
+        assert_eq!(*backend.0.lock().unwrap(), (false, false));
+    }
+}
@@ -0,0 +1,174 @@
+//! Windows 隐私模式
+//!
+//! 独立线程持有遮挡窗口和低级输入钩子并运行消息循环 (钩子回调在安装线程上派发)。
+//! 遮挡窗口设置 `WDA_EXCLUDEFROMCAPTURE` (Windows 10 2004+)，DXGI/GDI 捕获都看不到它
+
+use super::PrivacyBackend;
+use anyhow::{anyhow, Result};
+use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+use std::sync::OnceLock;
+use windows::core::w;
+use windows::Win32::Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::Graphics::Gdi::{GetStockObject, BLACK_BRUSH, HBRUSH};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::UI::WindowsAndMessaging::{
+    CallNextHookEx, CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, GetSystemMetrics,
+    PostMessageW, RegisterClassW, SetWindowDisplayAffinity, SetWindowPos, SetWindowsHookExW, ShowWindow,
+    TranslateMessage, HWND_TOPMOST, KBDLLHOOKSTRUCT, LLKHF_INJECTED, LLMHF_INJECTED, MSG, MSLLHOOKSTRUCT,
+    SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN, SWP_NOACTIVATE,
+    SWP_SHOWWINDOW, SW_HIDE, WH_KEYBOARD_LL, WH_MOUSE_LL, WINDOW_DISPLAY_AFFINITY, WM_APP, WNDCLASSW,
+    WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW, WS_EX_TOPMOST, WS_POPUP,
+};
+
+/// 不出现在屏幕捕获中 (WDA_EXCLUDEFROMCAPTURE)
+const WDA_EXCLUDE_FROM_CAPTURE: WINDOW_DISPLAY_AFFINITY = WINDOW_DISPLAY_AFFINITY(0x11);
+
+/// 显示/隐藏遮挡窗口 (wparam: 1 = 显示)
+const WM_SET_CURTAIN: u32 = WM_APP + 1;
+
+/// 遮挡窗口句柄
+static CURTAIN: AtomicIsize = AtomicIsize::new(0);
+
+/// 是否丢弃本地输入
+static BLOCK_INPUT: AtomicBool = AtomicBool::new(false);
+
+/// Windows 隐私模式
+#[derive(Default)]
+pub struct WindowsPrivacy;
+
+impl WindowsPrivacy {
+    /// 启动隐私线程 (只启动一次)，返回遮挡窗口句柄
+    fn curtain() -> Result<HWND> {
+        static STARTED: OnceLock<Result<(), String>> = OnceLock::new();
+        STARTED
+            .get_or_init(|| {
+                let (tx, rx) = std::sync::mpsc::channel();
+                std::thread::Builder::new()
+                    .name("privacy".to_string())
+                    .spawn(move || unsafe { run(tx) })
+                    .map_err(|e| e.to_string())?;
+                rx.recv().map_err(|_| "隐私线程已退出".to_string())?
+            })
+            .clone()
+            .map_err(|e| anyhow!("启动隐私模式失败: {}", e))?;
+        Ok(HWND(CURTAIN.load(Ordering::Acquire)))
+    }
+}
+
+impl PrivacyBackend for WindowsPrivacy {
+    fn set_curtain(&mut self, enabled: bool) -> Result<()> {
+        let hwnd = Self::curtain()?;
+        unsafe { PostMessageW(hwnd, WM_SET_CURTAIN, WPARAM(enabled as usize), LPARAM(0))? };
+        Ok(())
+    }
+
+    fn set_input_blocked(&mut self, blocked: bool) -> Result<()> {
+        Self::curtain()?;
+        BLOCK_INPUT.store(blocked, Ordering::Release);
+        Ok(())
+    }
+}
+
+/// 隐私线程: 创建遮挡窗口、安装钩子后运行消息循环
+unsafe fn run(ready: std::sync::mpsc::Sender<Result<(), String>>) {
+    let instance: HINSTANCE = match GetModuleHandleW(None) {
+        Ok(module) => module.into(),
+        Err(e) => {
+            let _ = ready.send(Err(e.to_string()));
+            return;
+        }
+    };
+
+    let class = WNDCLASSW {
+        lpfnWndProc: Some(window_proc),
+        hInstance: instance,
+        hbrBackground: HBRUSH(GetStockObject(BLACK_BRUSH).0),
+        lpszClassName: w!("SSControlPrivacyCurtain"),
+        ..Default::default()
+    };
+    if RegisterClassW(&class) == 0 {
+        let _ = ready.send(Err("无法注册遮挡窗口类".to_string()));
+        return;
+    }
+    let hwnd = CreateWindowExW(
+        WS_EX_TOPMOST | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+        w!("SSControlPrivacyCurtain"),
+        w!(""),
+        WS_POPUP,
+        0,
+        0,
+        0,
+        0,
+        None,
+        None,
+        instance,
+        None,
+    );
+    if hwnd.0 == 0 {
+        let _ = ready.send(Err("无法创建遮挡窗口".to_string()));
+        return;
+    }
+    // 旧版 Windows 不支持排除捕获，此时遮挡窗口会出现在远端画面中
+    if let Err(e) = SetWindowDisplayAffinity(hwnd, WDA_EXCLUDE_FROM_CAPTURE) {
+        let _ = ready.send(Err(format!("系统不支持遮挡屏幕 (需要 Windows 10 2004 及以上): {}", e)));
+        return;
+    }
+    CURTAIN.store(hwnd.0, Ordering::Release);
+
+    // 钩子失败只影响屏蔽输入
+    if let Err(e) = SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_hook), instance, 0) {
+        tracing::warn!("安装键盘钩子失败: {}", e);
+    }
+    if let Err(e) = SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_hook), instance, 0) {
+        tracing::warn!("安装鼠标钩子失败: {}", e);
+    }
+    let _ = ready.send(Ok(()));
+
+    let mut msg = MSG::default();
+    while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+        let _ = TranslateMessage(&msg);
+        DispatchMessageW(&msg);
+    }
+}
+
+unsafe extern "system" fn window_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if msg == WM_SET_CURTAIN {
+        if wparam.0 != 0 {
+            // 覆盖整个虚拟桌面 (所有显示器)
+            let _ = SetWindowPos(
+                hwnd,
+                HWND_TOPMOST,
+                GetSystemMetrics(SM_XVIRTUALSCREEN),
+                GetSystemMetrics(SM_YVIRTUALSCREEN),
+                GetSystemMetrics(SM_CXVIRTUALSCREEN),
+                GetSystemMetrics(SM_CYVIRTUALSCREEN),
+                SWP_NOACTIVATE | SWP_SHOWWINDOW,
+            );
+        } else {
+            ShowWindow(hwnd, SW_HIDE);
+        }
+        return LRESULT(0);
+    }
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+unsafe extern "system" fn keyboard_hook(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code >= 0 && BLOCK_INPUT.load(Ordering::Acquire) {
+        let event = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
+        // 远程输入经 SendInput 注入，带 INJECTED 标志
+        if event.flags.0 & LLKHF_INJECTED.0 == 0 {
+            return LRESULT(1);
+        }
+    }
+    CallNextHookEx(None, code, wparam, lparam)
+}
+
+unsafe extern "system" fn mouse_hook(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code >= 0 && BLOCK_INPUT.load(Ordering::Acquire) {
+        let event = &*(lparam.0 as *const MSLLHOOKSTRUCT);
+        if event.flags & LLMHF_INJECTED == 0 {
+            return LRESULT(1);
+        }
+    }
+    CallNextHookEx(None, code, wparam, lparam)
+}
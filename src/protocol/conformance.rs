@@ -0,0 +1,136 @@
+//! 协议一致性测试
+//!
+//! 固定各消息类型的线上格式，浏览器查看器、原生客户端和移动端按这些样例实现即可互通
+
+use super::*;
+use serde_json::Value;
+
+/// 各消息类型的线上样例
+const FIXTURES: &[&str] = &[
+    r#"{"type":"hello","version":1,"id":1,"payload":{"client":"sscontrol-web/1.0","min_version":1,"capabilities":["input","chat"]}}"#,
+    r#"{"type":"welcome","version":1,"id":1,"payload":{"server":"sscontrol/1.0.0","capabilities":["input"]}}"#,
+    r#"{"type":"error","version":1,"id":2,"payload":{"code":"not_negotiated","message":"请先发送 hello"}}"#,
+    r#"{"type":"input","version":1,"payload":{"MouseMove":{"x":0.25,"y":0.75}}}"#,
+    r#"{"type":"input","version":1,"payload":{"KeyEvent":{"key":"a","pressed":true}}}"#,
+    r#"{"type":"control","version":1,"payload":{"type":"request"}}"#,
+    r#"{"type":"control","version":1,"payload":{"type":"state","holder":"s1","host":false,"has_control":true}}"#,
+    r#"{"type":"clipboard","version":1,"payload":{"text":"copied"}}"#,
+    r#"{"type":"chat","version":1,"payload":{"text":"你好"}}"#,
+    r#"{"type":"chat_message","version":1,"payload":{"id":3,"from":"host","text":"hi","time_ms":1700000000000}}"#,
+    r#"{"type":"latency","version":1,"payload":{"type":"echo","frame":7,"host_us":1000,"hold_us":250}}"#,
+    r#"{"type":"quality","version":1,"payload":{"type":"quality","grade":"fair","bitrate_kbps":1850,"target_bitrate_kbps":2000,"fps":29.5,"rtt_ms":120.0,"packet_loss":0.01,"codec":"H.264","encoder":"NVIDIA NVENC","time_ms":1700000000000}}"#,
+    r#"{"type":"encoder_switch","version":1,"payload":{"type":"encoder_switch","from":"NVIDIA NVENC","to":"AMD AMF","reason":"avcodec_send_frame 失败: -22","time_ms":1700000000000}}"#,
+    r#"{"type":"sysinfo_request","version":1,"id":9,"payload":{"top":5}}"#,
+];
+
+fn json(text: &str) -> Value {
+    serde_json::from_str(text).unwrap()
+}
+
+fn hello(version: u32, min_version: u32, capabilities: &str) -> String {
+    format!(
+        r#"{{"type":"hello","version":{},"id":1,"payload":{{"client":"test","min_version":{},"capabilities":{}}}}}"#,
+        version, min_version, capabilities
+    )
+}
+
+fn reply(inbound: Inbound) -> Envelope {
+    match inbound {
+        Inbound::Reply(envelope) => envelope,
+        Inbound::Deliver { message, .. } => panic!("期望回复，实际交付 {}", message.kind()),
+    }
+}
+
+fn error_code(inbound: Inbound) -> ErrorCode {
+    match reply(inbound).message {
+        Message::Error(error) => error.code,
+        message => panic!("期望错误，实际 {}", message.kind()),
+    }
+}
+
+#[test]
+fn test_fixtures_round_trip() {
+    for fixture in FIXTURES {
+        let envelope = Envelope::decode(fixture.as_bytes()).unwrap_or_else(|e| panic!("{}: {:?}", fixture, e));
+        assert_eq!(json(&envelope.encode()), json(fixture), "{}", fixture);
+        assert!(Message::TYPES.contains(&envelope.message.kind()));
+    }
+}
+
+#[test]
+fn test_every_type_has_fixture() {
+    for kind in Message::TYPES.iter().filter(|kind| **kind != "sysinfo") {
+        assert!(
+            FIXTURES.iter().any(|fixture| json(fixture)["type"] == *kind),
+            "缺少 {} 的样例",
+            kind
+        );
+    }
+}
+
+#[test]
+fn test_unknown_and_invalid_messages() {
+    // 不认识的类型: 回复 unknown_type 并带上请求的 id
+    let error = Envelope::decode(br#"{"type":"screenshot","version":1,"id":4,"payload":{}}"#).unwrap_err();
+    assert_eq!(error.id, Some(4));
+    assert_eq!(error.error.code, ErrorCode::UnknownType);
+
+    // 缺少版本号 / 版本号为 0 / 内容与类型不符
+    for text in [
+        r#"{"type":"chat","payload":{"text":"hi"}}"#,
+        r#"{"type":"chat","version":0,"payload":{"text":"hi"}}"#,
+        r#"{"type":"chat","version":1,"payload":{"message":"hi"}}"#,
+        "not json",
+    ] {
+        assert_eq!(Envelope::decode(text.as_bytes()).unwrap_err().error.code, ErrorCode::InvalidMessage, "{}", text);
+    }
+
+    // 不认识的字段被忽略
+    let envelope =
+        Envelope::decode(r#"{"type":"chat","version":1,"trace":"x","payload":{"text":"hi","color":"red"}}"#.as_bytes())
+            .unwrap();
+    assert!(matches!(envelope.message, Message::Chat(post) if post.text == "hi"));
+}
+
+#[test]
+fn test_version_negotiation() {
+    let request = |version, min_version| {
+        let envelope = Envelope::decode(hello(version, min_version, "[]").as_bytes()).unwrap();
+        let Message::Hello(payload) = envelope.message else { unreachable!() };
+        negotiate(envelope.version, &payload, &[])
+    };
+
+    assert_eq!(request(VERSION, MIN_VERSION).unwrap().version, VERSION);
+    // 对端更新但仍支持本端的版本
+    assert_eq!(request(VERSION + 3, MIN_VERSION).unwrap().version, VERSION);
+    // 对端要求的最低版本高于本端
+    assert_eq!(request(VERSION + 3, VERSION + 1).unwrap_err().code, ErrorCode::UnsupportedVersion);
+}
+
+#[test]
+fn test_handshake() {
+    let offered = [Capability::Input, Capability::Control, Capability::Chat, Capability::Sysinfo];
+    let mut state = ProtocolState::default();
+
+    // 握手之前的消息被拒绝
+    let chat = br#"{"type":"chat","version":1,"payload":{"text":"hi"}}"#;
+    assert_eq!(error_code(state.receive(chat, &offered)), ErrorCode::NotNegotiated);
+
+    // 不认识的能力被忽略，只保留双方都支持的
+    let request = hello(VERSION, MIN_VERSION, r#"["chat","input","teleport"]"#);
+    let welcome = reply(state.receive(request.as_bytes(), &offered));
+    assert_eq!(welcome.id, Some(1));
+    let Message::Welcome(welcome) = welcome.message else { panic!("期望 welcome") };
+    assert_eq!(welcome.capabilities, vec![Capability::Input, Capability::Chat]);
+
+    assert!(matches!(state.receive(chat, &offered), Inbound::Deliver { message: Message::Chat(_), .. }));
+    // 没有协商的能力
+    let request = br#"{"type":"sysinfo_request","version":1,"id":5,"payload":{}}"#;
+    assert_eq!(error_code(state.receive(request, &offered)), ErrorCode::Unsupported);
+    // 对端的错误不回复
+    let error = br#"{"type":"error","version":1,"payload":{"code":"shiny_new_code","message":"?"}}"#;
+    assert!(matches!(
+        state.receive(error, &offered),
+        Inbound::Deliver { message: Message::Error(ProtocolError { code: ErrorCode::Unknown, .. }), .. }
+    ));
+}
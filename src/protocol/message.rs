@@ -0,0 +1,188 @@
+//! 控制协议的消息类型
+//!
+//! 每种消息对应信封中的一个 `type`，`payload` 为该类型的内容。输入、聊天、控制权、
+//! 延迟探测、质量报告和系统信息的内容沿用各自数据通道上的 JSON 格式
+
+use crate::input::InputEvent;
+use crate::quality::latency::LatencyMessage;
+use crate::quality::report::{EncoderSwitchEvent, QualityReport};
+use crate::signaling::chat::{ChatMessage, ChatPost};
+use crate::signaling::control::ControlMessage;
+use crate::system::{SysInfoRequest, SystemReport};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 可协商的能力
+///
+/// 不认识的能力反序列化为 `Unknown`，协商时忽略 (新版本的客户端可以连接旧版本的被控端)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// 鼠标键盘和触摸输入 (`input`)
+    Input,
+    /// 申请/移交输入控制权 (`control`)
+    Control,
+    /// 剪贴板同步 (`clipboard`)
+    Clipboard,
+    /// 文件上传
+    File,
+    /// 文字聊天 (`chat` / `chat_message`)
+    Chat,
+    /// 延迟探测、连接质量报告和编码器切换 (`latency` / `quality` / `encoder_switch`)
+    Stats,
+    /// 系统信息 (`sysinfo_request` / `sysinfo`)
+    Sysinfo,
+    #[serde(other)]
+    Unknown,
+}
+
+impl Capability {
+    /// 使用该消息类型需要协商的能力 (握手和错误消息不需要)
+    pub fn of(message: &Message) -> Option<Self> {
+        match message {
+            Message::Hello(_) | Message::Welcome(_) | Message::Error(_) => None,
+            Message::Input(_) => Some(Self::Input),
+            Message::Control(_) => Some(Self::Control),
+            Message::Clipboard(_) => Some(Self::Clipboard),
+            Message::Chat(_) | Message::ChatMessage(_) => Some(Self::Chat),
+            Message::Latency(_) | Message::Quality(_) | Message::EncoderSwitch(_) => Some(Self::Stats),
+            Message::SysinfoRequest(_) | Message::Sysinfo(_) => Some(Self::Sysinfo),
+        }
+    }
+}
+
+/// 控制端: 会话开始时发送的握手 (信封中的 version 为支持的最高版本)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hello {
+    /// 客户端名称和版本 (如 "sscontrol-web/1.2.0")
+    pub client: String,
+    /// 支持的最低版本
+    pub min_version: u32,
+    /// 希望使用的能力
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+}
+
+/// 被控端: 握手成功 (信封中的 version 为协商出的版本)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Welcome {
+    /// 被控端名称和版本
+    pub server: String,
+    /// 双方都支持且该会话有权限使用的能力
+    pub capabilities: Vec<Capability>,
+}
+
+/// 错误码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// 双方支持的版本没有交集
+    UnsupportedVersion,
+    /// 不认识的消息类型
+    UnknownType,
+    /// 不是合法的信封或内容与类型不符
+    InvalidMessage,
+    /// 握手之前发送了其他消息
+    NotNegotiated,
+    /// 该能力没有协商或会话没有相应权限
+    Unsupported,
+    #[serde(other)]
+    Unknown,
+}
+
+/// 错误 (回复引起错误的消息时使用其 id)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtocolError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ProtocolError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+/// 剪贴板文本
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClipboardText {
+    pub text: String,
+}
+
+/// 协议消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "payload", rename_all = "snake_case")]
+pub enum Message {
+    /// 控制端 → 被控端: 握手
+    Hello(Hello),
+    /// 被控端 → 控制端: 握手成功
+    Welcome(Welcome),
+    /// 双向: 错误
+    Error(ProtocolError),
+    /// 控制端 → 被控端: 输入事件
+    Input(InputEvent),
+    /// 双向: 控制权消息
+    Control(ControlMessage),
+    /// 双向: 剪贴板内容
+    Clipboard(ClipboardText),
+    /// 控制端 → 被控端: 发送聊天消息
+    Chat(ChatPost),
+    /// 被控端 → 控制端: 已记录的聊天消息
+    ChatMessage(ChatMessage),
+    /// 双向: 延迟探测和回显
+    Latency(LatencyMessage),
+    /// 被控端 → 控制端: 连接质量报告
+    Quality(QualityReport),
+    /// 被控端 → 控制端: 运行中切换了编码器
+    EncoderSwitch(EncoderSwitchEvent),
+    /// 控制端 → 被控端: 请求系统信息
+    SysinfoRequest(SysInfoRequest),
+    /// 被控端 → 控制端: 系统信息
+    Sysinfo(SystemReport),
+}
+
+impl Message {
+    /// 所有消息类型 (信封的 `type`)
+    pub const TYPES: &'static [&'static str] = &[
+        "hello",
+        "welcome",
+        "error",
+        "input",
+        "control",
+        "clipboard",
+        "chat",
+        "chat_message",
+        "latency",
+        "quality",
+        "encoder_switch",
+        "sysinfo_request",
+        "sysinfo",
+    ];
+
+    /// 信封中的 `type`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Hello(_) => "hello",
+            Self::Welcome(_) => "welcome",
+            Self::Error(_) => "error",
+            Self::Input(_) => "input",
+            Self::Control(_) => "control",
+            Self::Clipboard(_) => "clipboard",
+            Self::Chat(_) => "chat",
+            Self::ChatMessage(_) => "chat_message",
+            Self::Latency(_) => "latency",
+            Self::Quality(_) => "quality",
+            Self::EncoderSwitch(_) => "encoder_switch",
+            Self::SysinfoRequest(_) => "sysinfo_request",
+            Self::Sysinfo(_) => "sysinfo",
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! 数据通道控制协议
+//!
+//! 输入、控制权、聊天、剪贴板、延迟探测和系统信息在同一个 `sscontrol` 数据通道上交换，
+//! 每条消息是一个 JSON 信封:
+//!
+//! ```json
+//! {"type": "input", "version": 1, "id": 7, "payload": {"MouseMove": {"x": 0.5, "y": 0.5}}}
+//! ```
+//!
+//! - `type`: 消息类型 (见 [`Message::TYPES`])
+//! - `version`: 发送方使用的协议版本 (握手前为支持的最高版本，握手后为协商出的版本)
+//! - `id`: 可选，请求的编号；响应 (`welcome`、`sysinfo`、`error`) 使用请求的 id
+//! - `payload`: 该类型的内容
+//!
+//! 会话开始时控制端发送 `hello` (支持的版本区间和希望使用的能力)，被控端回复 `welcome`
+//! (协商出的版本和双方都支持、且会话有权限使用的能力) 或 `error`。握手之前和未协商的能力的
+//! 消息被拒绝。
+//!
+//! 兼容规则: 新增消息类型、能力和可选字段不改变版本号，接收方忽略不认识的字段和能力，
+//! 对不认识的消息类型回复 `unknown_type`；不兼容的修改增加 [`VERSION`]，
+//! 旧版本仍支持时保留 [`MIN_VERSION`]。
+//!
+//! 旧的按标签区分的数据通道 (`input`、`control`、`chat`、`stats`、`sysinfo`) 继续可用
+
+#![allow(dead_code)]
+
+pub mod message;
+
+pub use message::{Capability, ErrorCode, Hello, Message, ProtocolError, Welcome};
+
+use serde::{Deserialize, Serialize};
+
+/// 控制协议使用的数据通道标签
+pub const PROTOCOL_CHANNEL: &str = "sscontrol";
+
+/// 当前协议版本
+pub const VERSION: u32 = 1;
+
+/// 仍能互通的最低协议版本
+pub const MIN_VERSION: u32 = 1;
+
+/// 消息信封
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    pub version: u32,
+    pub id: Option<u64>,
+    pub message: Message,
+}
+
+/// 线上格式
+#[derive(Serialize, Deserialize)]
+struct RawEnvelope {
+    #[serde(rename = "type")]
+    kind: String,
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
+    #[serde(default)]
+    payload: serde_json::Value,
+}
+
+/// 无法解析的消息 (id 为能读出的请求编号，用于回复错误)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+    pub id: Option<u64>,
+    pub error: ProtocolError,
+}
+
+impl Envelope {
+    pub fn new(message: Message, id: Option<u64>) -> Self {
+        Self { version: VERSION, id, message }
+    }
+
+    /// 编码为 JSON
+    pub fn encode(&self) -> String {
+        // 相邻标签的枚举序列化为 {"type": ..., "payload": ...}
+        let payload = match serde_json::to_value(&self.message) {
+            Ok(serde_json::Value::Object(mut object)) => object.remove("payload").unwrap_or_default(),
+            _ => serde_json::Value::Null,
+        };
+        let raw = RawEnvelope {
+            kind: self.message.kind().to_string(),
+            version: self.version,
+            id: self.id,
+            payload,
+        };
+        serde_json::to_string(&raw).unwrap_or_default()
+    }
+
+    /// 从 JSON 解码
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let raw: RawEnvelope = serde_json::from_slice(data).map_err(|e| DecodeError {
+            id: None,
+            error: ProtocolError::new(ErrorCode::InvalidMessage, format!("无效的信封: {}", e)),
+        })?;
+        let fail = |code, message: String| DecodeError { id: raw.id, error: ProtocolError::new(code, message) };
+        if raw.version == 0 {
+            return Err(fail(ErrorCode::InvalidMessage, "版本号必须大于 0".to_string()));
+        }
+        if !Message::TYPES.contains(&raw.kind.as_str()) {
+            return Err(fail(ErrorCode::UnknownType, format!("不支持的消息类型: {}", raw.kind)));
+        }
+        let tagged = serde_json::json!({ "type": raw.kind, "payload": raw.payload });
+        let message = serde_json::from_value(tagged)
+            .map_err(|e| fail(ErrorCode::InvalidMessage, format!("无效的 {}: {}", raw.kind, e)))?;
+        Ok(Self { version: raw.version, id: raw.id, message })
+    }
+}
+
+/// 握手的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Negotiated {
+    pub version: u32,
+    pub capabilities: Vec<Capability>,
+}
+
+impl Negotiated {
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
+
+/// 协商版本和能力
+///
+/// `version` 为 hello 信封中的版本 (对端支持的最高版本)，`offered` 为本端可以提供的能力
+pub fn negotiate(version: u32, hello: &Hello, offered: &[Capability]) -> Result<Negotiated, ProtocolError> {
+    let negotiated = version.min(VERSION);
+    let min = hello.min_version.max(MIN_VERSION);
+    if negotiated < min {
+        return Err(ProtocolError::new(
+            ErrorCode::UnsupportedVersion,
+            format!("本端支持版本 {}-{}，对端支持 {}-{}", MIN_VERSION, VERSION, hello.min_version, version),
+        ));
+    }
+    let capabilities = offered
+        .iter()
+        .copied()
+        .filter(|capability| *capability != Capability::Unknown && hello.capabilities.contains(capability))
+        .collect();
+    Ok(Negotiated { version: negotiated, capabilities })
+}
+
+/// 收到的消息的处理结果
+#[derive(Debug)]
+pub enum Inbound {
+    /// 需要回复 (握手结果或错误)
+    Reply(Envelope),
+    /// 已握手且能力已协商的消息
+    Deliver { id: Option<u64>, message: Message },
+}
+
+/// 一个协议通道的握手状态 (被控端)
+#[derive(Debug, Default)]
+pub struct ProtocolState {
+    negotiated: Option<Negotiated>,
+}
+
+impl ProtocolState {
+    pub fn negotiated(&self) -> Option<&Negotiated> {
+        self.negotiated.as_ref()
+    }
+
+    /// 当前使用的版本 (握手前为本端最高版本)
+    pub fn version(&self) -> u32 {
+        self.negotiated.as_ref().map_or(VERSION, |negotiated| negotiated.version)
+    }
+
+    /// 以当前版本封装要发送的消息
+    pub fn envelope(&self, message: Message, id: Option<u64>) -> Envelope {
+        Envelope { version: self.version(), id, message }
+    }
+
+    /// 处理收到的数据，`offered` 为该会话当前可以提供的能力
+    pub fn receive(&mut self, data: &[u8], offered: &[Capability]) -> Inbound {
+        let envelope = match Envelope::decode(data) {
+            Ok(envelope) => envelope,
+            Err(e) => return Inbound::Reply(self.envelope(Message::Error(e.error), e.id)),
+        };
+        let id = envelope.id;
+
+        if let Message::Hello(hello) = &envelope.message {
+            return match negotiate(envelope.version, hello, offered) {
+                Ok(negotiated) => {
+                    let welcome = Welcome {
+                        server: format!("sscontrol/{}", env!("CARGO_PKG_VERSION")),
+                        capabilities: negotiated.capabilities.clone(),
+                    };
+                    self.negotiated = Some(negotiated);
+                    Inbound::Reply(self.envelope(Message::Welcome(welcome), id))
+                }
+                Err(e) => Inbound::Reply(self.envelope(Message::Error(e), id)),
+            };
+        }
+
+        // 对端的错误等消息直接交给调用方 (不回复错误，避免两端互相回复)
+        let Some(capability) = Capability::of(&envelope.message) else {
+            return Inbound::Deliver { id, message: envelope.message };
+        };
+        match &self.negotiated {
+            None => self.error(id, ErrorCode::NotNegotiated, "请先发送 hello".to_string()),
+            Some(negotiated) if negotiated.allows(capability) => Inbound::Deliver { id, message: envelope.message },
+            Some(_) => self.error(id, ErrorCode::Unsupported, format!("未协商的能力: {:?}", capability)),
+        }
+    }
+
+    fn error(&self, id: Option<u64>, code: ErrorCode, message: String) -> Inbound {
+        Inbound::Reply(self.envelope(Message::Error(ProtocolError::new(code, message)), id))
+    }
+}
+
+#[cfg(test)]
+mod conformance;
@@ -44,6 +44,125 @@ impl Default for NetworkState {
     }
 }
 
+impl NetworkState {
+    /// 合并多个会话的网络状态，取最差值 (码率需要照顾最差的观看者)
+    pub fn worst(self, other: Self) -> Self {
+        Self {
+            bandwidth_mbps: self.bandwidth_mbps.min(other.bandwidth_mbps),
+            latency_ms: self.latency_ms.max(other.latency_ms),
+            packet_loss: self.packet_loss.max(other.packet_loss),
+            jitter_ms: self.jitter_ms.max(other.jitter_ms),
+        }
+    }
+}
+
+/// 传输层统计快照 (累计值)
+///
+/// 来自 WebRTC 发送端统计和 RTCP 接收报告 (RR)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransportSnapshot {
+    /// 已发送字节数
+    pub bytes_sent: u64,
+    /// 已发送 RTP 包数
+    pub packets_sent: u64,
+    /// 收到的 NACK 数
+    pub nack_count: u64,
+    /// 对端报告的累计丢包数
+    pub packets_lost: i64,
+    /// 对端最近一次报告的丢包比例 (0.0 - 1.0)
+    pub fraction_lost: f64,
+    /// 往返时延 (ms)
+    pub rtt_ms: Option<f64>,
+    /// 拥塞控制估计的可用发送带宽 (Mbps)，0 表示未知
+    pub available_bandwidth_mbps: f64,
+}
+
+/// 网络状态估计器
+///
+/// 根据相邻两次传输统计快照的差值计算 [`NetworkState`]
+#[derive(Debug, Default)]
+pub struct NetworkStateEstimator {
+    last: Option<(TransportSnapshot, Instant)>,
+    last_rtt_ms: Option<f64>,
+    jitter_ms: f64,
+    sent_kbps: u32,
+}
+
+impl NetworkStateEstimator {
+    /// 无拥塞迹象时假定的带宽 (Mbps)
+    ///
+    /// 实际发送速率受画面内容影响 (静态画面几乎不发数据)，不能直接当作可用带宽，
+    /// 否则码率会被自己的低发送量压到下限。取介于低/高带宽阈值之间的中性值，
+    /// 交给延迟规则逐步上调
+    const UNCONGESTED_BANDWIDTH_MBPS: f64 = 5.0;
+    /// 丢包超过该比例视为拥塞，此时实际送达速率即为带宽上限
+    const CONGESTION_LOSS: f64 = 0.02;
+    /// 没有 RTCP 往返时延时使用的中性延迟 (不触发延迟规则)
+    const NEUTRAL_LATENCY_MS: f64 = 75.0;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 最近两次快照之间的实际发送码率 (kbps)
+    pub fn sent_kbps(&self) -> u32 {
+        self.sent_kbps
+    }
+
+    /// 输入新的快照，首次调用只记录基准，返回 None
+    pub fn update(&mut self, snapshot: TransportSnapshot) -> Option<NetworkState> {
+        self.update_at(snapshot, Instant::now())
+    }
+
+    fn update_at(&mut self, snapshot: TransportSnapshot, now: Instant) -> Option<NetworkState> {
+        let previous = self.last.replace((snapshot, now));
+
+        // RTT 抖动 (RFC 3550 风格的平滑)
+        if let Some(rtt) = snapshot.rtt_ms {
+            if let Some(last_rtt) = self.last_rtt_ms {
+                self.jitter_ms += ((rtt - last_rtt).abs() - self.jitter_ms) / 16.0;
+            }
+            self.last_rtt_ms = Some(rtt);
+        }
+
+        let (prev, prev_time) = previous?;
+        let elapsed = now.duration_since(prev_time).as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+
+        let packets = snapshot.packets_sent.saturating_sub(prev.packets_sent);
+        let lost = (snapshot.packets_lost - prev.packets_lost).max(0) as f64;
+        let nacks = snapshot.nack_count.saturating_sub(prev.nack_count) as f64;
+
+        let mut packet_loss = snapshot.fraction_lost;
+        if packets > 0 {
+            packet_loss = packet_loss
+                .max(lost / packets as f64)
+                .max(nacks / packets as f64);
+        }
+        let packet_loss = packet_loss.clamp(0.0, 1.0);
+
+        let sent_mbps =
+            snapshot.bytes_sent.saturating_sub(prev.bytes_sent) as f64 * 8.0 / elapsed / 1_000_000.0;
+        self.sent_kbps = (sent_mbps * 1000.0).round() as u32;
+        let bandwidth_mbps = if snapshot.available_bandwidth_mbps > 0.0 {
+            snapshot.available_bandwidth_mbps
+        } else if packet_loss > Self::CONGESTION_LOSS {
+            sent_mbps * (1.0 - packet_loss)
+        } else {
+            sent_mbps.max(Self::UNCONGESTED_BANDWIDTH_MBPS)
+        };
+
+        Some(NetworkState {
+            bandwidth_mbps,
+            latency_ms: snapshot.rtt_ms.unwrap_or(Self::NEUTRAL_LATENCY_MS),
+            packet_loss,
+            jitter_ms: self.jitter_ms,
+        })
+    }
+}
+
 /// ABR 配置
 #[derive(Debug, Clone)]
 pub struct AbreConfig {
@@ -154,6 +273,11 @@ impl RuleBasedAbreController {
         self.current_bitrate
     }
 
+    /// 码率下限 (kbps)
+    pub fn min_bitrate(&self) -> u32 {
+        self.config.min_bitrate
+    }
+
     /// 应用启发式规则
     fn apply_rules(&self, state: NetworkState) -> u32 {
         let mut bitrate = self.current_bitrate as f64;
@@ -376,6 +500,86 @@ mod tests {
         assert!(bitrate <= 5000, "码率不应超过最大值");
     }
 
+    #[test]
+    fn test_estimator_from_transport_stats() {
+        let mut estimator = NetworkStateEstimator::new();
+        let start = Instant::now();
+
+        let first = TransportSnapshot {
+            rtt_ms: Some(40.0),
+            ..Default::default()
+        };
+        assert!(estimator.update_at(first, start).is_none(), "首个快照只作为基准");
+
+        // 1 秒内发送 1000 个包，丢 100 个，收到 20 个 NACK
+        let second = TransportSnapshot {
+            bytes_sent: 1_000_000,
+            packets_sent: 1000,
+            nack_count: 20,
+            packets_lost: 100,
+            fraction_lost: 0.05,
+            rtt_ms: Some(200.0),
+            available_bandwidth_mbps: 0.0,
+        };
+        let state = estimator
+            .update_at(second, start + Duration::from_secs(1))
+            .expect("第二个快照应产生网络状态");
+
+        assert!((state.packet_loss - 0.1).abs() < 1e-9);
+        assert_eq!(state.latency_ms, 200.0);
+        // 拥塞时带宽取实际送达速率: 8 Mbps * 90%
+        assert!((state.bandwidth_mbps - 7.2).abs() < 1e-9);
+        assert!(state.jitter_ms > 0.0);
+        assert_eq!(estimator.sent_kbps(), 8000);
+
+        // 拥塞状态应让控制器降码率
+        let mut controller = RuleBasedAbreController::new(AbreConfig::default());
+        assert!(controller.update(state) < 2000);
+    }
+
+    #[test]
+    fn test_estimator_idle_stream_is_not_congestion() {
+        let mut estimator = NetworkStateEstimator::new();
+        let start = Instant::now();
+        estimator.update_at(TransportSnapshot::default(), start);
+
+        // 静态画面几乎不发数据，不应被当作低带宽
+        let state = estimator
+            .update_at(
+                TransportSnapshot {
+                    bytes_sent: 1_000,
+                    packets_sent: 5,
+                    rtt_ms: Some(20.0),
+                    ..Default::default()
+                },
+                start + Duration::from_secs(1),
+            )
+            .unwrap();
+        assert!(state.bandwidth_mbps >= 5.0);
+        assert_eq!(state.packet_loss, 0.0);
+    }
+
+    #[test]
+    fn test_worst_network_state() {
+        let good = NetworkState {
+            bandwidth_mbps: 20.0,
+            latency_ms: 10.0,
+            packet_loss: 0.0,
+            jitter_ms: 1.0,
+        };
+        let bad = NetworkState {
+            bandwidth_mbps: 1.0,
+            latency_ms: 300.0,
+            packet_loss: 0.2,
+            jitter_ms: 50.0,
+        };
+        let merged = good.worst(bad);
+        assert_eq!(merged.bandwidth_mbps, 1.0);
+        assert_eq!(merged.latency_ms, 300.0);
+        assert_eq!(merged.packet_loss, 0.2);
+        assert_eq!(merged.jitter_ms, 50.0);
+    }
+
     #[test]
     fn test_variance_calculation() {
         let config = AbreConfig::default();
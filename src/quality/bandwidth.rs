@@ -0,0 +1,317 @@
+//! 带宽上限和按时段限速
+//!
+//! `[bandwidth]` 配置三种限制，同时生效时取最小值:
+//! - `max_kbps`: 所有观看者合计的硬上限
+//! - `schedule`: 按星期和时段的合计上限 (如工作时间限速)，时段重叠时取较小值
+//! - `per_session_kbps`: 单个观看者会话的上限
+//!
+//! 被控端一次编码发给所有观看者，所以合计上限按观看者数均分后与单会话上限一起限制编码码率；
+//! WebSocket 推流还由发送队列的令牌桶强制限速 (见 [`crate::network::pacing`])。
+//! 当前用量和生效的上限由 [`monitor`] 汇总，供 `--stats` 和 `/metrics` 使用
+
+#![allow(dead_code)]
+
+use crate::config::{BandwidthConfig, BandwidthWindow};
+use anyhow::{anyhow, bail, Result};
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// 用量统计窗口
+const USAGE_WINDOW: Duration = Duration::from_secs(2);
+
+/// 解析后的限速时段
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Window {
+    /// 生效的星期 (空 = 每天)
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub max_kbps: u32,
+}
+
+impl Window {
+    pub fn parse(window: &BandwidthWindow) -> Result<Self> {
+        let time = |value: &str| {
+            NaiveTime::parse_from_str(value.trim(), "%H:%M").map_err(|_| anyhow!("无效的时间 \"{}\" (格式 HH:MM)", value))
+        };
+        let days = window
+            .days
+            .iter()
+            .map(|day| day.trim().parse::<Weekday>().map_err(|_| anyhow!("无效的星期 \"{}\" (mon - sun)", day)))
+            .collect::<Result<Vec<_>>>()?;
+        let (start, end) = (time(&window.start)?, time(&window.end)?);
+        if start == end {
+            bail!("开始和结束时间相同 ({})", window.start);
+        }
+        if window.max_kbps == 0 {
+            bail!("max_kbps 不能为 0");
+        }
+        Ok(Self { days, start, end, max_kbps: window.max_kbps })
+    }
+
+    /// 该时段是否包含某一时刻 (跨过午夜的时段按开始那天的星期计算)
+    pub fn contains(&self, at: NaiveDateTime) -> bool {
+        let (day, time) = (at.weekday(), at.time());
+        if self.start < self.end {
+            self.on(day) && time >= self.start && time < self.end
+        } else {
+            (self.on(day) && time >= self.start) || (self.on(day.pred()) && time < self.end)
+        }
+    }
+
+    fn on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+}
+
+/// 某一时刻生效的限制
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct BandwidthLimit {
+    /// 所有观看者合计的上限 (kbps)
+    pub total_kbps: Option<u32>,
+    /// 单个会话的上限 (kbps)
+    pub per_session_kbps: Option<u32>,
+    /// 合计上限是否来自限速时段
+    pub scheduled: bool,
+}
+
+impl BandwidthLimit {
+    pub fn is_unlimited(&self) -> bool {
+        self.total_kbps.is_none() && self.per_session_kbps.is_none()
+    }
+
+    /// 有 `sessions` 个观看者时每个会话可用的码率 (即编码码率的上限)
+    pub fn per_viewer(&self, sessions: usize) -> Option<u32> {
+        let share = self.total_kbps.map(|total| (total / sessions.max(1) as u32).max(1));
+        match (share, self.per_session_kbps) {
+            (Some(share), Some(session)) => Some(share.min(session)),
+            (share, session) => share.or(session),
+        }
+    }
+}
+
+/// 带宽策略
+#[derive(Debug, Clone, Default)]
+pub struct BandwidthPolicy {
+    max_kbps: Option<u32>,
+    per_session_kbps: Option<u32>,
+    windows: Vec<Window>,
+}
+
+impl BandwidthPolicy {
+    pub fn from_config(config: &BandwidthConfig) -> Result<Self> {
+        let windows = config
+            .schedule
+            .iter()
+            .enumerate()
+            .map(|(index, window)| Window::parse(window).map_err(|e| anyhow!("bandwidth.schedule[{}]: {}", index, e)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            max_kbps: config.max_kbps.filter(|kbps| *kbps > 0),
+            per_session_kbps: config.per_session_kbps.filter(|kbps| *kbps > 0),
+            windows,
+        })
+    }
+
+    /// 没有配置任何限制
+    pub fn is_unlimited(&self) -> bool {
+        self.max_kbps.is_none() && self.per_session_kbps.is_none() && self.windows.is_empty()
+    }
+
+    /// 某一时刻 (本地时间) 生效的限制
+    pub fn limit_at(&self, at: NaiveDateTime) -> BandwidthLimit {
+        let scheduled = self.windows.iter().filter(|window| window.contains(at)).map(|window| window.max_kbps).min();
+        let total_kbps = match (self.max_kbps, scheduled) {
+            (Some(max), Some(window)) => Some(max.min(window)),
+            (max, window) => max.or(window),
+        };
+        BandwidthLimit {
+            total_kbps,
+            per_session_kbps: self.per_session_kbps,
+            scheduled: scheduled.is_some() && total_kbps == scheduled,
+        }
+    }
+
+    /// 当前生效的限制
+    pub fn limit_now(&self) -> BandwidthLimit {
+        self.limit_at(chrono::Local::now().naive_local())
+    }
+}
+
+/// 带宽用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct BandwidthUsage {
+    /// 最近 2 秒发给所有观看者的视频码率 (kbps)
+    pub usage_kbps: u32,
+    pub limit: BandwidthLimit,
+    /// 观看者数
+    pub sessions: usize,
+}
+
+impl fmt::Display for BandwidthUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} kbps", self.usage_kbps)?;
+        if self.limit.is_unlimited() {
+            return f.write_str(" (不限制)");
+        }
+        if let Some(total) = self.limit.total_kbps {
+            write!(f, ", 上限 {} kbps{}", total, if self.limit.scheduled { " (限速时段)" } else { "" })?;
+        }
+        if let Some(session) = self.limit.per_session_kbps {
+            write!(f, ", 每会话 {} kbps", session)?;
+        }
+        if let Some(per_viewer) = self.limit.per_viewer(self.sessions).filter(|_| self.sessions > 0) {
+            write!(f, ", {} 个观看者各 {} kbps", self.sessions, per_viewer)?;
+        }
+        Ok(())
+    }
+}
+
+/// 进程级的带宽用量统计 (被控端视频流水线写入)
+#[derive(Debug, Default)]
+pub struct BandwidthMonitor {
+    inner: Mutex<MonitorInner>,
+}
+
+#[derive(Debug, Default)]
+struct MonitorInner {
+    samples: VecDeque<(Instant, usize)>,
+    limit: BandwidthLimit,
+    sessions: usize,
+}
+
+impl BandwidthMonitor {
+    /// 发送了 `bytes` 字节 (发给多个观看者时为合计)
+    pub fn record(&self, bytes: usize) {
+        self.record_at(Instant::now(), bytes);
+    }
+
+    fn record_at(&self, now: Instant, bytes: usize) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        inner.samples.push_back((now, bytes));
+        while inner.samples.front().is_some_and(|(at, _)| now.saturating_duration_since(*at) > USAGE_WINDOW) {
+            inner.samples.pop_front();
+        }
+    }
+
+    /// 更新当前生效的限制和观看者数
+    pub fn set_limit(&self, limit: BandwidthLimit, sessions: usize) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.limit = limit;
+            inner.sessions = sessions;
+        }
+    }
+
+    pub fn usage(&self) -> BandwidthUsage {
+        self.usage_at(Instant::now())
+    }
+
+    fn usage_at(&self, now: Instant) -> BandwidthUsage {
+        let Ok(inner) = self.inner.lock() else {
+            return BandwidthUsage::default();
+        };
+        let bytes: usize = inner
+            .samples
+            .iter()
+            .filter(|(at, _)| now.saturating_duration_since(*at) <= USAGE_WINDOW)
+            .map(|(_, bytes)| bytes)
+            .sum();
+        BandwidthUsage {
+            usage_kbps: (bytes as f64 * 8.0 / USAGE_WINDOW.as_secs_f64() / 1000.0) as u32,
+            limit: inner.limit,
+            sessions: inner.sessions,
+        }
+    }
+}
+
+/// 进程内共享的用量统计
+pub fn monitor() -> &'static BandwidthMonitor {
+    static MONITOR: OnceLock<BandwidthMonitor> = OnceLock::new();
+    MONITOR.get_or_init(BandwidthMonitor::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn window(days: &[&str], start: &str, end: &str, max_kbps: u32) -> BandwidthWindow {
+        BandwidthWindow {
+            days: days.iter().map(|day| day.to_string()).collect(),
+            start: start.to_string(),
+            end: end.to_string(),
+            max_kbps,
+        }
+    }
+
+    /// 2024-06-03 是星期一
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 6, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_window_contains() {
+        let work = Window::parse(&window(&["mon", "tue", "wed", "thu", "fri"], "09:00", "18:00", 1000)).unwrap();
+        assert!(work.contains(at(3, 9, 0)));
+        assert!(!work.contains(at(3, 18, 0)));
+        // 星期六
+        assert!(!work.contains(at(8, 12, 0)));
+
+        // 跨过午夜: 星期五 22:00 开始的时段持续到星期六 06:00
+        let night = Window::parse(&window(&["fri"], "22:00", "06:00", 500)).unwrap();
+        assert!(night.contains(at(7, 23, 0)));
+        assert!(night.contains(at(8, 5, 59)));
+        assert!(!night.contains(at(7, 5, 0)));
+
+        assert!(Window::parse(&window(&["someday"], "09:00", "18:00", 1000)).is_err());
+        assert!(Window::parse(&window(&[], "9am", "18:00", 1000)).is_err());
+        assert!(Window::parse(&window(&[], "09:00", "09:00", 1000)).is_err());
+        assert!(Window::parse(&window(&[], "09:00", "18:00", 0)).is_err());
+    }
+
+    #[test]
+    fn test_policy_limit() {
+        let config = BandwidthConfig {
+            max_kbps: Some(4000),
+            per_session_kbps: Some(1500),
+            schedule: vec![window(&[], "09:00", "18:00", 2000)],
+        };
+        let policy = BandwidthPolicy::from_config(&config).unwrap();
+
+        let evening = policy.limit_at(at(3, 20, 0));
+        assert_eq!(evening, BandwidthLimit { total_kbps: Some(4000), per_session_kbps: Some(1500), scheduled: false });
+        // 单个观看者受单会话上限限制，多个观看者均分合计上限
+        assert_eq!(evening.per_viewer(1), Some(1500));
+        assert_eq!(evening.per_viewer(4), Some(1000));
+
+        let office = policy.limit_at(at(3, 10, 0));
+        assert_eq!(office.total_kbps, Some(2000));
+        assert!(office.scheduled);
+        assert_eq!(office.per_viewer(2), Some(1000));
+
+        assert!(BandwidthPolicy::default().limit_at(at(3, 10, 0)).is_unlimited());
+        assert_eq!(BandwidthLimit::default().per_viewer(3), None);
+    }
+
+    #[test]
+    fn test_monitor_usage() {
+        let monitor = BandwidthMonitor::default();
+        let start = Instant::now();
+        // 2 秒内 500 KB = 2000 kbps
+        monitor.record_at(start, 250_000);
+        monitor.record_at(start + Duration::from_secs(1), 250_000);
+        assert_eq!(monitor.usage_at(start + Duration::from_secs(1)).usage_kbps, 2000);
+        // 超出窗口的样本不计入
+        assert_eq!(monitor.usage_at(start + Duration::from_secs(3)).usage_kbps, 1000);
+
+        monitor.set_limit(BandwidthLimit { total_kbps: Some(3000), per_session_kbps: None, scheduled: true }, 2);
+        let usage = monitor.usage_at(start + Duration::from_secs(1));
+        assert_eq!(usage.to_string(), "2000 kbps, 上限 3000 kbps (限速时段), 2 个观看者各 1500 kbps");
+    }
+}
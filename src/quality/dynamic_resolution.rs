@@ -0,0 +1,306 @@
+//! 动态分辨率控制
+//!
+//! 当自适应码率已降到下限仍无法满足带宽时，继续降低码率只会让画面糊成一片。
+//! 此时降低编码分辨率 (1080p → 720p → 540p) 反而能保持文字清晰；
+//! 带宽恢复后再逐级升回原始分辨率。
+//!
+//! ## 特点
+//! - 分辨率阶梯按原始屏幕宽高比计算，输出始终为偶数尺寸
+//! - 升降档均需连续多次确认 (滞回)，并有最短停留时间，避免来回抖动
+//! - 切换由调用方重建编码器完成，新编码器的首帧即为关键帧
+
+#![allow(dead_code)]
+use crate::capture::Frame;
+use std::time::{Duration, Instant};
+
+/// 动态分辨率配置
+#[derive(Debug, Clone)]
+pub struct DynamicResolutionConfig {
+    /// 分辨率阶梯 (目标高度，从高到低)，高于原始分辨率的档位会被忽略
+    pub ladder: Vec<u32>,
+    /// 连续多少次处于码率下限后降档
+    pub downscale_after: u32,
+    /// 码率恢复到下限的多少倍时视为带宽充足
+    pub upscale_ratio: f64,
+    /// 连续多少次带宽充足后升档
+    pub upscale_after: u32,
+    /// 两次切换之间的最短间隔
+    pub min_dwell: Duration,
+}
+
+impl Default for DynamicResolutionConfig {
+    fn default() -> Self {
+        Self {
+            ladder: vec![720, 540],
+            downscale_after: 3,
+            upscale_ratio: 2.5,
+            upscale_after: 5,
+            min_dwell: Duration::from_secs(10),
+        }
+    }
+}
+
+/// 动态分辨率控制器
+#[derive(Debug)]
+pub struct DynamicResolutionController {
+    config: DynamicResolutionConfig,
+    /// 可用分辨率 (第 0 档为原始分辨率)
+    levels: Vec<(u32, u32)>,
+    level: usize,
+    floor_count: u32,
+    recovered_count: u32,
+    last_switch: Option<Instant>,
+}
+
+impl DynamicResolutionController {
+    /// 创建控制器
+    ///
+    /// # 参数
+    /// * `width` - 原始编码宽度
+    /// * `height` - 原始编码高度
+    pub fn new(width: u32, height: u32, config: DynamicResolutionConfig) -> Self {
+        let mut levels = vec![(width, height)];
+        for &target in &config.ladder {
+            let (_, last_height) = levels[levels.len() - 1];
+            if target >= last_height {
+                continue;
+            }
+            let scaled_width = (width as u64 * target as u64 / height.max(1) as u64) as u32;
+            levels.push((even(scaled_width), even(target)));
+        }
+
+        Self {
+            config,
+            levels,
+            level: 0,
+            floor_count: 0,
+            recovered_count: 0,
+            last_switch: None,
+        }
+    }
+
+    /// 当前编码分辨率
+    pub fn resolution(&self) -> (u32, u32) {
+        self.levels[self.level]
+    }
+
+    /// 是否处于降档状态
+    pub fn is_scaled(&self) -> bool {
+        self.level > 0
+    }
+
+    /// 可用分辨率列表
+    pub fn levels(&self) -> &[(u32, u32)] {
+        &self.levels
+    }
+
+    /// 根据码率控制器状态更新
+    ///
+    /// # 参数
+    /// * `bitrate` - 当前码率 (kbps)
+    /// * `min_bitrate` - 码率下限 (kbps)
+    ///
+    /// # 返回
+    /// 分辨率需要切换时返回新的编码尺寸
+    pub fn update(&mut self, bitrate: u32, min_bitrate: u32) -> Option<(u32, u32)> {
+        self.update_at(bitrate, min_bitrate, Instant::now())
+    }
+
+    fn update_at(&mut self, bitrate: u32, min_bitrate: u32, now: Instant) -> Option<(u32, u32)> {
+        if bitrate <= min_bitrate {
+            self.floor_count += 1;
+            self.recovered_count = 0;
+        } else if bitrate as f64 >= min_bitrate as f64 * self.config.upscale_ratio {
+            self.recovered_count += 1;
+            self.floor_count = 0;
+        } else {
+            self.floor_count = 0;
+            self.recovered_count = 0;
+        }
+
+        let dwell_elapsed = self
+            .last_switch
+            .is_none_or(|t| now.duration_since(t) >= self.config.min_dwell);
+        if !dwell_elapsed {
+            return None;
+        }
+
+        let next = if self.floor_count >= self.config.downscale_after
+            && self.level + 1 < self.levels.len()
+        {
+            self.level + 1
+        } else if self.recovered_count >= self.config.upscale_after && self.level > 0 {
+            self.level - 1
+        } else {
+            return None;
+        };
+
+        let (from_w, from_h) = self.resolution();
+        self.level = next;
+        self.floor_count = 0;
+        self.recovered_count = 0;
+        self.last_switch = Some(now);
+
+        let (to_w, to_h) = self.resolution();
+        tracing::info!(
+            "动态分辨率切换: {}x{} -> {}x{} (码率 {} kbps)",
+            from_w, from_h, to_w, to_h, bitrate
+        );
+        Some((to_w, to_h))
+    }
+}
+
+fn even(value: u32) -> u32 {
+    (value & !1).max(2)
+}
+
+/// 将 RGBA 帧缩放到指定尺寸 (双线性插值)
+///
+/// GPU 帧没有 CPU 像素数据，原样返回
+pub fn scale_frame(frame: &Frame, width: u32, height: u32) -> Frame {
+    if frame.is_gpu() || (frame.width == width && frame.height == height) {
+        return frame.clone();
+    }
+
+    let dst_stride = width as usize * 4;
+    let mut data = vec![0u8; dst_stride * height as usize];
+
+    // 16.16 定点步长，采样点取目标像素中心
+    let x_step = ((frame.width as u64) << 16) / width.max(1) as u64;
+    let y_step = ((frame.height as u64) << 16) / height.max(1) as u64;
+    let max_x = frame.width.saturating_sub(1) as u64;
+    let max_y = frame.height.saturating_sub(1) as u64;
+
+    let columns: Vec<(usize, usize, u32)> = (0..width as u64)
+        .map(|x| {
+            let sx = (x * x_step + x_step / 2).saturating_sub(1 << 15);
+            let x0 = (sx >> 16).min(max_x);
+            let x1 = (x0 + 1).min(max_x);
+            (x0 as usize * 4, x1 as usize * 4, ((sx >> 8) & 0xff) as u32)
+        })
+        .collect();
+
+    for (y, dst_row) in data.chunks_exact_mut(dst_stride).enumerate() {
+        let sy = (y as u64 * y_step + y_step / 2).saturating_sub(1 << 15);
+        let y0 = (sy >> 16).min(max_y) as usize;
+        let y1 = (y0 + 1).min(max_y as usize);
+        let fy = ((sy >> 8) & 0xff) as u32;
+
+        let row0 = &frame.data[y0 * frame.stride..];
+        let row1 = &frame.data[y1 * frame.stride..];
+
+        for (dst, &(x0, x1, fx)) in dst_row.chunks_exact_mut(4).zip(&columns) {
+            for c in 0..4 {
+                let top = row0[x0 + c] as u32 * (256 - fx) + row0[x1 + c] as u32 * fx;
+                let bottom = row1[x0 + c] as u32 * (256 - fx) + row1[x1 + c] as u32 * fx;
+                dst[c] = ((top * (256 - fy) + bottom * fy + (1 << 15)) >> 16) as u8;
+            }
+        }
+    }
+
+    Frame {
+        width,
+        height,
+        data,
+        timestamp: frame.timestamp,
+        stride: dst_stride,
+        gpu: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller() -> DynamicResolutionController {
+        DynamicResolutionController::new(
+            1920,
+            1080,
+            DynamicResolutionConfig {
+                min_dwell: Duration::ZERO,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_ladder_keeps_aspect_ratio() {
+        let controller = controller();
+        assert_eq!(controller.levels(), &[(1920, 1080), (1280, 720), (960, 540)]);
+
+        // 原始分辨率低于阶梯时不再降档
+        let small = DynamicResolutionController::new(1366, 600, DynamicResolutionConfig::default());
+        assert_eq!(small.levels(), &[(1366, 600), (1228, 540)]);
+    }
+
+    #[test]
+    fn test_downscale_at_floor_and_recover() {
+        let mut controller = controller();
+
+        assert_eq!(controller.update(500, 500), None);
+        assert_eq!(controller.update(500, 500), None);
+        assert_eq!(controller.update(500, 500), Some((1280, 720)));
+        for _ in 0..3 {
+            controller.update(500, 500);
+        }
+        assert_eq!(controller.resolution(), (960, 540));
+
+        // 最低档后不再降
+        for _ in 0..10 {
+            assert_eq!(controller.update(500, 500), None);
+        }
+
+        // 带宽恢复后逐级升回
+        for _ in 0..4 {
+            assert_eq!(controller.update(3000, 500), None);
+        }
+        assert_eq!(controller.update(3000, 500), Some((1280, 720)));
+        for _ in 0..5 {
+            controller.update(3000, 500);
+        }
+        assert_eq!(controller.resolution(), (1920, 1080));
+        assert!(!controller.is_scaled());
+    }
+
+    #[test]
+    fn test_hysteresis_and_dwell() {
+        let mut controller = DynamicResolutionController::new(1920, 1080, DynamicResolutionConfig::default());
+        let start = Instant::now();
+
+        // 中间码率打断连续计数
+        controller.update_at(500, 500, start);
+        controller.update_at(500, 500, start);
+        controller.update_at(800, 500, start);
+        assert_eq!(controller.update_at(500, 500, start), None);
+
+        controller.update_at(500, 500, start);
+        assert_eq!(controller.update_at(500, 500, start), Some((1280, 720)));
+
+        // 停留时间内不再切换
+        for _ in 0..5 {
+            assert_eq!(controller.update_at(500, 500, start + Duration::from_secs(1)), None);
+        }
+        assert_eq!(
+            controller.update_at(500, 500, start + Duration::from_secs(10)),
+            Some((960, 540))
+        );
+    }
+
+    #[test]
+    fn test_scale_frame() {
+        let frame = Frame {
+            width: 4,
+            height: 4,
+            data: [10u8, 20, 30, 255].repeat(16),
+            timestamp: 42,
+            stride: 16,
+            gpu: None,
+        };
+
+        let scaled = scale_frame(&frame, 2, 2);
+        assert_eq!((scaled.width, scaled.height, scaled.stride), (2, 2, 8));
+        assert_eq!(scaled.timestamp, 42);
+        // 纯色画面缩放后颜色不变
+        assert_eq!(scaled.data, [10u8, 20, 30, 255].repeat(4));
+    }
+}
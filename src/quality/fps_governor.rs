@@ -0,0 +1,280 @@
+//! 帧率调节
+//!
+//! 按画面内容和系统负载调整实际采集帧率 (不超过配置的帧率):
+//! - 静态画面持续一段时间后降到 `static_fps`，画面一动立即恢复
+//! - 系统 CPU 占用超过 `cpu_high` 时降到 `cpu_fps`，回落到 `cpu_low` 以下后逐步回升
+//!
+//! 降低帧率同时减少了采集、静态检测和编码的开销
+
+use std::time::{Duration, Instant};
+
+/// 帧率调节配置
+#[derive(Debug, Clone)]
+pub struct FpsGovernorConfig {
+    /// 静态画面时的帧率
+    pub static_fps: u32,
+    /// 画面静止多久后降帧
+    pub static_after: Duration,
+    /// CPU 过载时的帧率
+    pub cpu_fps: u32,
+    /// CPU 占用高于该值时降帧 (0.0 - 1.0)
+    pub cpu_high: f32,
+    /// CPU 占用低于该值时开始回升
+    pub cpu_low: f32,
+    /// 每次 CPU 采样回升的帧数
+    pub ramp_step: u32,
+}
+
+impl Default for FpsGovernorConfig {
+    fn default() -> Self {
+        Self {
+            static_fps: 5,
+            static_after: Duration::from_secs(1),
+            cpu_fps: 10,
+            cpu_high: 0.85,
+            cpu_low: 0.6,
+            ramp_step: 5,
+        }
+    }
+}
+
+/// 动态帧率控制器
+#[derive(Debug)]
+pub struct FpsGovernor {
+    config: FpsGovernorConfig,
+    max_fps: u32,
+    /// CPU 负载允许的帧率上限
+    cpu_ceiling: u32,
+    /// 画面开始静止的时间
+    static_since: Option<Instant>,
+    fps: u32,
+}
+
+impl FpsGovernor {
+    pub fn new(max_fps: u32, config: FpsGovernorConfig) -> Self {
+        let max_fps = max_fps.max(1);
+        Self {
+            config,
+            max_fps,
+            cpu_ceiling: max_fps,
+            static_since: None,
+            fps: max_fps,
+        }
+    }
+
+    /// 当前帧率
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    /// 配置的最高帧率
+    pub fn max_fps(&self) -> u32 {
+        self.max_fps
+    }
+
+    /// 当前帧率对应的帧间隔
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_millis(1000 / self.fps as u64)
+    }
+
+    /// 修改最高帧率 (配置或画质预设变化)，返回变化后的帧率
+    pub fn set_max_fps(&mut self, max_fps: u32) -> Option<u32> {
+        let max_fps = max_fps.max(1);
+        // 未受 CPU 限制时上限跟随配置
+        if self.cpu_ceiling >= self.max_fps {
+            self.cpu_ceiling = max_fps;
+        }
+        self.max_fps = max_fps;
+        self.recompute()
+    }
+
+    /// 每帧调用一次，帧率变化时返回新帧率
+    pub fn observe_frame(&mut self, static_scene: bool) -> Option<u32> {
+        self.observe_frame_at(static_scene, Instant::now())
+    }
+
+    pub fn observe_frame_at(&mut self, static_scene: bool, now: Instant) -> Option<u32> {
+        if static_scene {
+            self.static_since.get_or_insert(now);
+        } else {
+            self.static_since = None;
+        }
+        self.recompute_at(now)
+    }
+
+    /// 定期调用 (如每秒)，`load` 为系统 CPU 占用 (0.0 - 1.0)，帧率变化时返回新帧率
+    pub fn observe_cpu(&mut self, load: f32) -> Option<u32> {
+        if load >= self.config.cpu_high {
+            self.cpu_ceiling = self.config.cpu_fps.min(self.max_fps);
+        } else if load <= self.config.cpu_low {
+            self.cpu_ceiling = (self.cpu_ceiling + self.config.ramp_step).min(self.max_fps);
+        }
+        self.recompute()
+    }
+
+    /// CPU 是否处于过载降帧状态
+    pub fn cpu_limited(&self) -> bool {
+        self.cpu_ceiling < self.max_fps
+    }
+
+    fn recompute(&mut self) -> Option<u32> {
+        self.recompute_at(Instant::now())
+    }
+
+    fn recompute_at(&mut self, now: Instant) -> Option<u32> {
+        let mut target = self.cpu_ceiling.min(self.max_fps);
+        if self.static_since.is_some_and(|since| now.duration_since(since) >= self.config.static_after) {
+            target = target.min(self.config.static_fps);
+        }
+        let target = target.max(1);
+        if target == self.fps {
+            return None;
+        }
+        self.fps = target;
+        Some(target)
+    }
+}
+
+/// 系统 CPU 占用采样
+pub struct CpuSampler {
+    /// 上次采样的 (忙碌时间, 总时间)
+    last: Option<(u64, u64)>,
+}
+
+impl CpuSampler {
+    pub fn new() -> Self {
+        Self { last: read_cpu_times() }
+    }
+
+    /// 自上次采样以来的平均 CPU 占用 (0.0 - 1.0)，平台不支持或首次采样时返回 None
+    pub fn sample(&mut self) -> Option<f32> {
+        let current = read_cpu_times()?;
+        let (busy, total) = self.last.replace(current)?;
+        let total = current.1.saturating_sub(total);
+        let busy = current.0.saturating_sub(busy);
+        (total > 0).then(|| (busy as f32 / total as f32).min(1.0))
+    }
+}
+
+impl Default for CpuSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 解析 `/proc/stat` 的汇总行，返回 (忙碌时间, 总时间)
+fn parse_proc_stat(content: &str) -> Option<(u64, u64)> {
+    let line = content.lines().find(|line| line.starts_with("cpu "))?;
+    let fields: Vec<u64> = line.split_whitespace().skip(1).filter_map(|field| field.parse().ok()).collect();
+    if fields.len() < 4 {
+        return None;
+    }
+    // user nice system idle iowait irq softirq steal (guest 已计入 user)
+    let total: u64 = fields.iter().take(8).sum();
+    let idle = fields[3] + fields.get(4).copied().unwrap_or(0);
+    Some((total - idle, total))
+}
+
+#[cfg(target_os = "linux")]
+fn read_cpu_times() -> Option<(u64, u64)> {
+    parse_proc_stat(&std::fs::read_to_string("/proc/stat").ok()?)
+}
+
+#[cfg(target_os = "windows")]
+fn read_cpu_times() -> Option<(u64, u64)> {
+    use windows::Win32::Foundation::FILETIME;
+    use windows::Win32::System::Threading::GetSystemTimes;
+
+    let (mut idle, mut kernel, mut user) = (FILETIME::default(), FILETIME::default(), FILETIME::default());
+    unsafe {
+        GetSystemTimes(Some(&mut idle as *mut _), Some(&mut kernel as *mut _), Some(&mut user as *mut _)).ok()?;
+    }
+    let ticks = |time: FILETIME| (time.dwHighDateTime as u64) << 32 | time.dwLowDateTime as u64;
+    // 内核时间包含空闲时间
+    let total = ticks(kernel) + ticks(user);
+    Some((total.saturating_sub(ticks(idle)), total))
+}
+
+#[cfg(target_os = "macos")]
+fn read_cpu_times() -> Option<(u64, u64)> {
+    use std::os::raw::c_int;
+
+    const HOST_CPU_LOAD_INFO: c_int = 3;
+    const CPU_STATE_IDLE: usize = 2;
+    extern "C" {
+        fn mach_host_self() -> u32;
+        fn host_statistics(host: u32, flavor: c_int, info: *mut c_int, count: *mut u32) -> c_int;
+    }
+
+    // host_cpu_load_info: user / system / idle / nice 的累计时钟数
+    let mut ticks = [0u32; 4];
+    let mut count = ticks.len() as u32;
+    let ret = unsafe { host_statistics(mach_host_self(), HOST_CPU_LOAD_INFO, ticks.as_mut_ptr() as *mut c_int, &mut count) };
+    if ret != 0 {
+        return None;
+    }
+    let total: u64 = ticks.iter().map(|&tick| tick as u64).sum();
+    Some((total - ticks[CPU_STATE_IDLE] as u64, total))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn read_cpu_times() -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static_scene_lowers_fps() {
+        let mut governor = FpsGovernor::new(30, FpsGovernorConfig::default());
+        let start = Instant::now();
+
+        // 静止不足 1 秒时保持原帧率
+        assert_eq!(governor.observe_frame_at(true, start), None);
+        assert_eq!(governor.observe_frame_at(true, start + Duration::from_millis(500)), None);
+        assert_eq!(governor.observe_frame_at(true, start + Duration::from_secs(1)), Some(5));
+        assert_eq!(governor.frame_interval(), Duration::from_millis(200));
+
+        // 画面变化立即恢复
+        assert_eq!(governor.observe_frame_at(false, start + Duration::from_millis(1200)), Some(30));
+    }
+
+    #[test]
+    fn test_cpu_load_lowers_and_ramps_back() {
+        let mut governor = FpsGovernor::new(30, FpsGovernorConfig::default());
+        assert_eq!(governor.observe_cpu(0.95), Some(10));
+        assert!(governor.cpu_limited());
+        // 滞回区间内保持
+        assert_eq!(governor.observe_cpu(0.7), None);
+
+        let ramp: Vec<_> = (0..5).map(|_| governor.observe_cpu(0.3)).collect();
+        assert_eq!(ramp, vec![Some(15), Some(20), Some(25), Some(30), None]);
+        assert!(!governor.cpu_limited());
+    }
+
+    #[test]
+    fn test_max_fps_change() {
+        let mut governor = FpsGovernor::new(30, FpsGovernorConfig::default());
+        assert_eq!(governor.set_max_fps(60), Some(60));
+        assert_eq!(governor.observe_cpu(0.9), Some(10));
+        // CPU 受限时降低上限不会解除限制
+        assert_eq!(governor.set_max_fps(15), None);
+        assert_eq!(governor.fps(), 10);
+
+        // 上限低于静态帧率时不会反向提高
+        let mut governor = FpsGovernor::new(3, FpsGovernorConfig::default());
+        let start = Instant::now();
+        governor.observe_frame_at(true, start);
+        assert_eq!(governor.observe_frame_at(true, start + Duration::from_secs(2)), None);
+        assert_eq!(governor.fps(), 3);
+    }
+
+    #[test]
+    fn test_parse_proc_stat() {
+        let content = "cpu  100 0 50 800 50 0 0 0 0 0\ncpu0 50 0 25 400 25 0 0 0 0 0\n";
+        assert_eq!(parse_proc_stat(content), Some((150, 1000)));
+        assert_eq!(parse_proc_stat("intr 1 2 3"), None);
+    }
+}
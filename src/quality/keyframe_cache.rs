@@ -0,0 +1,158 @@
+//! 静态画面的关键帧缓存
+//!
+//! 画面长时间静止时，静态检测阶段按保活间隔要求关键帧，让丢包后的观看者和新加入的观看者
+//! 能够显示画面。每次都重新编码同一画面是浪费: 这里按 codec 和编码尺寸缓存最近编码的关键帧，
+//! 保活时直接重发 (作为新样本写入轨道，RTP 时间戳随之更新)，静态期间编码器完全空闲。
+//!
+//! 缓存的关键帧只在它之后没有编码过差分帧时有效: 差分帧说明画面已经变化，
+//! 此时重发会让观看者退回到旧画面，必须重新编码
+
+// 只有 WebRTC 推流 (h264 feature 提供编码器) 使用
+#![cfg_attr(not(all(feature = "h264", feature = "webrtc")), allow(dead_code))]
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// 缓存关键帧的最长使用时间
+///
+/// 超过后重新编码一次，码率和画质预设的变化最终也会反映到保活帧上
+const MAX_AGE: Duration = Duration::from_secs(60);
+
+/// 一路码流 (codec + 编码尺寸)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StreamKey {
+    pub codec: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl StreamKey {
+    pub fn new(codec: &'static str, width: u32, height: u32) -> Self {
+        Self { codec, width, height }
+    }
+}
+
+#[derive(Debug)]
+struct CachedKeyFrame {
+    data: Vec<u8>,
+    /// 缓存时的画面代数
+    generation: u64,
+    stored_at: Instant,
+}
+
+/// 关键帧缓存
+#[derive(Debug, Default)]
+pub struct KeyFrameCache {
+    entries: HashMap<StreamKey, CachedKeyFrame>,
+    /// 每编码一个差分帧加一 (画面已变化)
+    generation: u64,
+    /// 重发缓存代替编码的次数
+    hits: u64,
+}
+
+impl KeyFrameCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一个编码输出: 关键帧存入缓存，差分帧使所有缓存失效
+    pub fn record(&mut self, key: StreamKey, data: &[u8], is_key_frame: bool) {
+        self.record_at(key, data, is_key_frame, Instant::now());
+    }
+
+    fn record_at(&mut self, key: StreamKey, data: &[u8], is_key_frame: bool, now: Instant) {
+        if !is_key_frame {
+            self.generation += 1;
+            return;
+        }
+        let entry = self.entries.entry(key).or_insert_with(|| CachedKeyFrame {
+            data: Vec::new(),
+            generation: 0,
+            stored_at: now,
+        });
+        entry.data.clear();
+        entry.data.extend_from_slice(data);
+        entry.generation = self.generation;
+        entry.stored_at = now;
+    }
+
+    /// 取出仍可重发的关键帧
+    pub fn lookup(&mut self, key: &StreamKey) -> Option<&[u8]> {
+        self.lookup_at(key, Instant::now())
+    }
+
+    fn lookup_at(&mut self, key: &StreamKey, now: Instant) -> Option<&[u8]> {
+        let entry = self.entries.get(key)?;
+        if entry.generation != self.generation || now.duration_since(entry.stored_at) >= MAX_AGE {
+            return None;
+        }
+        self.hits += 1;
+        Some(&entry.data)
+    }
+
+    /// 清空缓存 (捕获重启、编码尺寸变化)
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// 取出并清零重发次数
+    pub fn take_hits(&mut self) -> u64 {
+        std::mem::take(&mut self.hits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const H264: StreamKey = StreamKey { codec: "H.264", width: 1920, height: 1080 };
+
+    #[test]
+    fn test_reuses_key_frame_until_delta() {
+        let mut cache = KeyFrameCache::new();
+        let start = Instant::now();
+        assert!(cache.lookup_at(&H264, start).is_none());
+
+        cache.record_at(H264, &[1, 2, 3], true, start);
+        assert_eq!(cache.lookup_at(&H264, start), Some(&[1, 2, 3][..]));
+        assert_eq!(cache.lookup_at(&H264, start + Duration::from_secs(5)), Some(&[1, 2, 3][..]));
+
+        // 画面变化后缓存失效，直到下一个关键帧
+        cache.record_at(H264, &[9], false, start);
+        assert!(cache.lookup_at(&H264, start).is_none());
+        cache.record_at(H264, &[4, 5], true, start);
+        assert_eq!(cache.lookup_at(&H264, start), Some(&[4, 5][..]));
+        assert_eq!(cache.take_hits(), 3);
+        assert_eq!(cache.take_hits(), 0);
+    }
+
+    #[test]
+    fn test_keyed_by_codec_and_size() {
+        let mut cache = KeyFrameCache::new();
+        let start = Instant::now();
+        let vp8 = StreamKey::new("VP8", 1920, 1080);
+        let scaled = StreamKey::new("H.264", 1280, 720);
+
+        cache.record_at(H264, &[1], true, start);
+        cache.record_at(vp8, &[2], true, start);
+        assert_eq!(cache.lookup_at(&H264, start), Some(&[1][..]));
+        assert_eq!(cache.lookup_at(&vp8, start), Some(&[2][..]));
+        assert!(cache.lookup_at(&scaled, start).is_none());
+
+        // 任一码流的差分帧都说明画面变了
+        cache.record_at(vp8, &[3], false, start);
+        assert!(cache.lookup_at(&H264, start).is_none());
+    }
+
+    #[test]
+    fn test_expires_after_max_age() {
+        let mut cache = KeyFrameCache::new();
+        let start = Instant::now();
+        cache.record_at(H264, &[1], true, start);
+        assert!(cache.lookup_at(&H264, start + MAX_AGE).is_none());
+
+        cache.record_at(H264, &[1], true, start);
+        cache.clear();
+        assert!(cache.lookup_at(&H264, start).is_none());
+    }
+}
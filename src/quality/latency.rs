@@ -0,0 +1,321 @@
+//! 延迟遥测
+//!
+//! 被控端在采集、编码、发送时为每帧打时间戳，统计最近一段时间的 p50/p95:
+//! - 编码耗时 (采集 → 编码完成)
+//! - 发送延迟 (采集 → 写入视频轨道)
+//! - 网络 RTT 与端到端延迟 (通过 `stats` 数据通道回显测得)
+//!
+//! ## 回显测量
+//! 被控端每秒对一个已发送的帧发出 `Ping`，控制端在收到 Ping 后显示下一帧时回复 `Echo`，
+//! 附带从收到 Ping 到显示的等待时间 `hold_us`。被控端据此计算:
+//! - RTT = 收到 Echo 的时间 - 发出 Ping 的时间 - hold_us
+//! - 端到端 = 发送延迟 + RTT / 2 + hold_us
+//!
+//! 两端无需时钟同步；单向时延按 RTT 的一半估算
+
+// 未启用 webrtc feature 时只有统计输出，没有打点
+#![cfg_attr(not(feature = "webrtc"), allow(dead_code))]
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// 延迟回显使用的数据通道标签 (由控制端创建，不需要权限)
+pub const STATS_CHANNEL: &str = "stats";
+
+/// 统计窗口保留的样本数
+const WINDOW_SAMPLES: usize = 300;
+/// 发出 Ping 的最小间隔
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+/// 等待回显的 Ping 上限 (控制端不回显时丢弃最早的)
+const MAX_PENDING_PROBES: usize = 8;
+
+/// `stats` 数据通道上的消息 (JSON)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LatencyMessage {
+    /// 被控端: 该帧已发送
+    Ping { frame: u64, host_us: u64 },
+    /// 控制端: 收到 Ping 后显示了下一帧，hold_us 为两者的间隔
+    Echo { frame: u64, host_us: u64, hold_us: u64 },
+}
+
+/// 单帧在被控端流水线中的时间戳
+#[derive(Debug, Clone, Copy)]
+pub struct FrameTiming {
+    captured: Instant,
+    encoded: Option<Instant>,
+}
+
+impl FrameTiming {
+    fn at(captured: Instant) -> Self {
+        Self { captured, encoded: None }
+    }
+
+    /// 标记编码完成
+    pub fn mark_encoded(&mut self) {
+        self.encoded = Some(Instant::now());
+    }
+}
+
+/// 分位数 (毫秒)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Percentiles {
+    pub p50: f64,
+    pub p95: f64,
+}
+
+/// 延迟统计快照
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LatencyStatistics {
+    /// 已发送帧数
+    pub frames: u64,
+    /// 编码耗时
+    pub encode_ms: Option<Percentiles>,
+    /// 采集到发送的延迟
+    pub send_ms: Option<Percentiles>,
+    /// 采集到控制端显示的延迟 (没有控制端回显时为 None)
+    pub end_to_end_ms: Option<Percentiles>,
+    /// 网络往返时延
+    pub rtt_ms: Option<Percentiles>,
+}
+
+impl fmt::Display for LatencyStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn field(f: &mut fmt::Formatter<'_>, name: &str, value: Option<Percentiles>) -> fmt::Result {
+            match value {
+                Some(p) => write!(f, "{} p50 {:.1}ms / p95 {:.1}ms", name, p.p50, p.p95),
+                None => write!(f, "{} -", name),
+            }
+        }
+
+        write!(f, "帧数 {} | ", self.frames)?;
+        field(f, "端到端", self.end_to_end_ms)?;
+        f.write_str(" | ")?;
+        field(f, "编码", self.encode_ms)?;
+        f.write_str(" | ")?;
+        field(f, "发送", self.send_ms)?;
+        f.write_str(" | ")?;
+        field(f, "RTT", self.rtt_ms)
+    }
+}
+
+/// 最近 WINDOW_SAMPLES 个样本 (毫秒)
+#[derive(Debug, Default)]
+struct Window(VecDeque<f64>);
+
+impl Window {
+    fn push(&mut self, value: Duration) {
+        if self.0.len() == WINDOW_SAMPLES {
+            self.0.pop_front();
+        }
+        self.0.push_back(value.as_secs_f64() * 1000.0);
+    }
+
+    fn percentiles(&self) -> Option<Percentiles> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<f64> = self.0.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        // 最近秩法
+        let rank = |p: f64| sorted[((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len()) - 1];
+        Some(Percentiles {
+            p50: rank(0.50),
+            p95: rank(0.95),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    frames: u64,
+    encode: Window,
+    send: Window,
+    end_to_end: Window,
+    rtt: Window,
+    /// 等待回显的 Ping: (帧号, 采集到发送的延迟)
+    pending: VecDeque<(u64, Duration)>,
+    last_probe: Option<Instant>,
+}
+
+/// 延迟统计器 (克隆后共享同一份统计)
+#[derive(Debug, Clone)]
+pub struct LatencyTracker {
+    epoch: Instant,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyTracker {
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            inner: Arc::new(Mutex::new(Inner::default())),
+        }
+    }
+
+    /// 采集到一帧
+    pub fn begin_frame(&self) -> FrameTiming {
+        FrameTiming::at(Instant::now())
+    }
+
+    /// 该帧已发送给所有观看者；到了探测时刻时返回要发给控制端的 Ping
+    pub fn frame_sent(&self, timing: FrameTiming) -> Option<LatencyMessage> {
+        self.frame_sent_at(timing, Instant::now())
+    }
+
+    fn frame_sent_at(&self, timing: FrameTiming, now: Instant) -> Option<LatencyMessage> {
+        let mut inner = self.inner.lock().ok()?;
+        inner.frames += 1;
+        let frame = inner.frames;
+        if let Some(encoded) = timing.encoded {
+            inner.encode.push(encoded.saturating_duration_since(timing.captured));
+        }
+        let send_delay = now.saturating_duration_since(timing.captured);
+        inner.send.push(send_delay);
+
+        if inner.last_probe.is_some_and(|last| now.saturating_duration_since(last) < PROBE_INTERVAL) {
+            return None;
+        }
+        inner.last_probe = Some(now);
+        if inner.pending.len() == MAX_PENDING_PROBES {
+            inner.pending.pop_front();
+        }
+        inner.pending.push_back((frame, send_delay));
+        Some(LatencyMessage::Ping {
+            frame,
+            host_us: self.micros(now),
+        })
+    }
+
+    /// 处理控制端的回显
+    pub fn on_echo(&self, message: &LatencyMessage) {
+        self.on_echo_at(message, Instant::now());
+    }
+
+    fn on_echo_at(&self, message: &LatencyMessage, now: Instant) {
+        let LatencyMessage::Echo { frame, host_us, hold_us } = *message else {
+            return;
+        };
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        let Some(index) = inner.pending.iter().position(|(id, _)| *id == frame) else {
+            // 多个观看者时同一个 Ping 只按最先到达的回显统计
+            return;
+        };
+        let Some((_, send_delay)) = inner.pending.remove(index) else {
+            return;
+        };
+
+        let rtt = Duration::from_micros(self.micros(now).saturating_sub(host_us).saturating_sub(hold_us));
+        let hold = Duration::from_micros(hold_us);
+        inner.rtt.push(rtt);
+        inner.end_to_end.push(send_delay + rtt / 2 + hold);
+    }
+
+    /// 当前统计快照
+    pub fn statistics(&self) -> LatencyStatistics {
+        let Ok(inner) = self.inner.lock() else {
+            return LatencyStatistics::default();
+        };
+        LatencyStatistics {
+            frames: inner.frames,
+            encode_ms: inner.encode.percentiles(),
+            send_ms: inner.send.percentiles(),
+            end_to_end_ms: inner.end_to_end.percentiles(),
+            rtt_ms: inner.rtt.percentiles(),
+        }
+    }
+
+    fn micros(&self, at: Instant) -> u64 {
+        at.saturating_duration_since(self.epoch).as_micros() as u64
+    }
+}
+
+/// 进程内共享的统计器 (被控端视频流水线写入)
+pub fn tracker() -> &'static LatencyTracker {
+    static TRACKER: OnceLock<LatencyTracker> = OnceLock::new();
+    TRACKER.get_or_init(LatencyTracker::new)
+}
+
+/// 获取当前延迟统计 (供 GUI 命令和控制台输出使用)
+pub fn get_statistics() -> LatencyStatistics {
+    tracker().statistics()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let mut window = Window::default();
+        assert!(window.percentiles().is_none());
+        for ms in 1..=100 {
+            window.push(Duration::from_millis(ms));
+        }
+        let p = window.percentiles().unwrap();
+        assert_eq!(p.p50, 50.0);
+        assert_eq!(p.p95, 95.0);
+
+        // 超出窗口后丢弃最早的样本
+        for _ in 0..WINDOW_SAMPLES {
+            window.push(Duration::from_millis(7));
+        }
+        assert_eq!(window.percentiles().unwrap(), Percentiles { p50: 7.0, p95: 7.0 });
+    }
+
+    #[test]
+    fn test_probe_interval() {
+        let tracker = LatencyTracker::new();
+        let start = Instant::now();
+
+        assert!(tracker.frame_sent_at(FrameTiming::at(start), start).is_some());
+        let soon = start + Duration::from_millis(500);
+        assert!(tracker.frame_sent_at(FrameTiming::at(soon), soon).is_none());
+        let later = start + PROBE_INTERVAL;
+        assert!(tracker.frame_sent_at(FrameTiming::at(later), later).is_some());
+        assert_eq!(tracker.statistics().frames, 3);
+    }
+
+    #[test]
+    fn test_echo_measures_rtt_and_end_to_end() {
+        let tracker = LatencyTracker::new();
+        let captured = Instant::now();
+        let mut timing = FrameTiming::at(captured);
+        timing.encoded = Some(captured + Duration::from_millis(8));
+        let sent = captured + Duration::from_millis(10);
+
+        let Some(LatencyMessage::Ping { frame, host_us }) = tracker.frame_sent_at(timing, sent) else {
+            panic!("首帧应发出 Ping");
+        };
+        let stats = tracker.statistics();
+        assert_eq!(stats.encode_ms.unwrap().p50, 8.0);
+        assert_eq!(stats.send_ms.unwrap().p50, 10.0);
+        assert!(stats.end_to_end_ms.is_none());
+
+        // 往返 40ms，其中控制端等待下一帧显示 20ms
+        let echo = LatencyMessage::Echo { frame, host_us, hold_us: 20_000 };
+        tracker.on_echo_at(&echo, sent + Duration::from_millis(60));
+        let stats = tracker.statistics();
+        assert_eq!(stats.rtt_ms.unwrap().p50, 40.0);
+        // 发送 10ms + 单程 20ms + 等待显示 20ms
+        assert_eq!(stats.end_to_end_ms.unwrap().p50, 50.0);
+
+        // 重复的回显不再计入
+        tracker.on_echo_at(&echo, sent + Duration::from_millis(90));
+        assert_eq!(tracker.statistics().rtt_ms.unwrap().p95, 40.0);
+
+        let json = serde_json::to_string(&echo).unwrap();
+        assert_eq!(serde_json::from_str::<LatencyMessage>(&json).unwrap(), echo);
+    }
+}
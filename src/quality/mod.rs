@@ -4,11 +4,29 @@
 //!
 //! ## 模块
 //! - `adaptive_bitrate`: 基于规则的自适应码率控制
+//! - `bandwidth`: 带宽上限和按时段限速
+//! - `dynamic_resolution`: 带宽不足时的动态分辨率缩放
+//! - `fps_governor`: 按画面内容和 CPU 负载调节帧率
+//! - `keyframe_cache`: 静态画面保活时重发缓存的关键帧
+//! - `latency`: 采集/编码/发送/显示的延迟遥测
+//! - `profile`: 画质/延迟取舍的命名预设
+//! - `report`: 推送给观看者的连接质量报告
 //! - `roi_encoder`: 基于鼠标位置的区域化编码
+//! - `scene_change`: 场景切换检测 (触发关键帧)
+//! - `simulcast`: 多观看者按网络状况分档编码
 //! - `static_detector`: 静态画面检测
 
 pub mod adaptive_bitrate;
+pub mod bandwidth;
+pub mod dynamic_resolution;
+pub mod fps_governor;
+pub mod keyframe_cache;
+pub mod latency;
+pub mod profile;
+pub mod report;
 pub mod roi_encoder;
+pub mod scene_change;
+pub mod simulcast;
 pub mod static_detector;
 
 // Type alias for convenience
@@ -0,0 +1,168 @@
+//! 画质预设
+//!
+//! 把帧率、码率、编码预设、静态画面检测灵敏度和分辨率上限打包成命名预设，
+//! 通过 `--profile` / `capture.profile` 选择，Viewer 可在运行中切换
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::encoder::hardware::EncoderPreset;
+
+/// 画质预设
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QualityProfile {
+    /// 低延迟: 高帧率，编码器不做前瞻
+    LowLatency,
+    /// 均衡 (未选择预设时的默认参数)
+    Balanced,
+    /// 高画质: 高码率，静态检测更灵敏以免丢掉细微变化
+    HighQuality,
+    /// 低带宽: 低帧率低码率，限制到 720p
+    LowBandwidth,
+    /// 省电: 最低帧率，尽量跳过静态画面
+    BatterySaver,
+}
+
+/// 预设对应的参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProfileSettings {
+    pub fps: u32,
+    /// 目标码率 (kbps)
+    pub bitrate: u32,
+    /// 硬件编码器预设
+    pub preset: EncoderPreset,
+    /// 画面差异低于该比例视为静态 (0.0 - 1.0)
+    pub static_threshold: f32,
+    /// 静态画面每隔多少帧发送一个关键帧
+    pub static_keepalive_frames: u32,
+    /// 编码高度上限 (None = 原始分辨率)
+    pub max_height: Option<u32>,
+}
+
+impl QualityProfile {
+    pub const ALL: [QualityProfile; 5] =
+        [Self::LowLatency, Self::Balanced, Self::HighQuality, Self::LowBandwidth, Self::BatterySaver];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LowLatency => "low-latency",
+            Self::Balanced => "balanced",
+            Self::HighQuality => "high-quality",
+            Self::LowBandwidth => "low-bandwidth",
+            Self::BatterySaver => "battery-saver",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::LowLatency => "低延迟",
+            Self::Balanced => "均衡",
+            Self::HighQuality => "高画质",
+            Self::LowBandwidth => "低带宽",
+            Self::BatterySaver => "省电",
+        }
+    }
+
+    pub fn settings(&self) -> ProfileSettings {
+        match self {
+            Self::LowLatency => ProfileSettings {
+                fps: 60,
+                bitrate: 4000,
+                preset: EncoderPreset::UltraLowLatency,
+                static_threshold: 0.01,
+                static_keepalive_frames: 60,
+                max_height: Some(1080),
+            },
+            Self::Balanced => ProfileSettings {
+                fps: 30,
+                bitrate: 2000,
+                preset: EncoderPreset::LowLatency,
+                static_threshold: 0.01,
+                static_keepalive_frames: 30,
+                max_height: None,
+            },
+            Self::HighQuality => ProfileSettings {
+                fps: 30,
+                bitrate: 8000,
+                preset: EncoderPreset::Quality,
+                static_threshold: 0.002,
+                static_keepalive_frames: 30,
+                max_height: None,
+            },
+            Self::LowBandwidth => ProfileSettings {
+                fps: 15,
+                bitrate: 800,
+                preset: EncoderPreset::Balanced,
+                static_threshold: 0.02,
+                static_keepalive_frames: 15,
+                max_height: Some(720),
+            },
+            Self::BatterySaver => ProfileSettings {
+                fps: 10,
+                bitrate: 1000,
+                preset: EncoderPreset::Balanced,
+                static_threshold: 0.03,
+                static_keepalive_frames: 10,
+                max_height: Some(720),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for QualityProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl std::str::FromStr for QualityProfile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.trim().to_ascii_lowercase().replace('_', "-");
+        Self::ALL
+            .into_iter()
+            .find(|profile| profile.as_str() == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(QualityProfile::as_str).collect();
+                anyhow!("未知的画质预设: {} (可选 {})", s, names.join(" / "))
+            })
+    }
+}
+
+/// 按高度上限等比缩小编码尺寸 (保持偶数宽高)，不放大
+pub fn cap_resolution(width: u32, height: u32, max_height: Option<u32>) -> (u32, u32) {
+    match max_height {
+        Some(max_height) if height > max_height => {
+            let scaled_width = (width as u64 * max_height as u64 / height as u64) as u32;
+            ((scaled_width & !1).max(2), (max_height & !1).max(2))
+        }
+        _ => (width, height),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profile() {
+        for profile in QualityProfile::ALL {
+            assert_eq!(profile.as_str().parse::<QualityProfile>().unwrap(), profile);
+        }
+        assert_eq!("Low_Bandwidth".parse::<QualityProfile>().unwrap(), QualityProfile::LowBandwidth);
+        assert!("ultra".parse::<QualityProfile>().is_err());
+
+        let profile: QualityProfile = serde_json::from_str("\"battery-saver\"").unwrap();
+        assert_eq!(profile, QualityProfile::BatterySaver);
+    }
+
+    #[test]
+    fn test_cap_resolution() {
+        assert_eq!(cap_resolution(1920, 1080, None), (1920, 1080));
+        assert_eq!(cap_resolution(1920, 1080, Some(1080)), (1920, 1080));
+        assert_eq!(cap_resolution(3840, 2160, Some(720)), (1280, 720));
+        assert_eq!(cap_resolution(1366, 768, Some(720)), (1280, 720));
+    }
+}
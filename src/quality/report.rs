@@ -0,0 +1,246 @@
+//! 连接质量报告
+//!
+//! 被控端随网络状态估计 (每 2 秒) 向每个观看者发送一份质量报告: 实际发送码率、目标码率、
+//! 编码帧率、RTT、丢包、codec 和编码器。WebRTC 会话通过 `stats` 数据通道 (或 `sscontrol`
+//! 通道的 `quality` 消息) 发送，`/video` 观看者作为文本通知发送:
+//!
+//! ```json
+//! {"type": "quality", "grade": "good", "bitrate_kbps": 1850, "target_bitrate_kbps": 2000,
+//!  "fps": 29.8, "rtt_ms": 32.0, "packet_loss": 0.004, "codec": "H.264",
+//!  "encoder": "NVIDIA NVENC", "session_id": "5f0c...", "time_ms": 1700000000000}
+//! ```
+//!
+//! `session_id` 与审计日志、录制文件元数据中的会话 ID 相同；`/video` 的报告发给全部观看者，没有该字段
+//!
+//! `grade` 只按 RTT 和丢包评定: 静态画面时帧率和码率本来就会降低，不代表连接变差
+//!
+//! 编码器在会话中途连续失败并被替换时，通过同样的途径发送一次切换事件:
+//!
+//! ```json
+//! {"type": "encoder_switch", "from": "NVIDIA NVENC", "to": "AMD AMF",
+//!  "reason": "h264_nvenc 纹理编码失败: -542398533", "time_ms": 1700000000000}
+//! ```
+
+// 未启用 webrtc feature 时只有 /video 观看者
+#![cfg_attr(not(feature = "webrtc"), allow(dead_code))]
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 连接质量等级 (查看器显示为绿/黄/红)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityGrade {
+    Good,
+    Fair,
+    Poor,
+}
+
+impl QualityGrade {
+    /// 良好的 RTT 上限 (ms)
+    const GOOD_RTT_MS: f64 = 100.0;
+    /// 一般的 RTT 上限 (ms)
+    const FAIR_RTT_MS: f64 = 250.0;
+    /// 良好的丢包上限
+    const GOOD_LOSS: f64 = 0.02;
+    /// 一般的丢包上限
+    const FAIR_LOSS: f64 = 0.08;
+
+    /// 按 RTT (没有测得时不参与评定) 和丢包比例评定
+    pub fn assess(rtt_ms: Option<f64>, packet_loss: f64) -> Self {
+        let by_rtt = match rtt_ms {
+            Some(rtt) if rtt >= Self::FAIR_RTT_MS => Self::Poor,
+            Some(rtt) if rtt >= Self::GOOD_RTT_MS => Self::Fair,
+            _ => Self::Good,
+        };
+        let by_loss = if packet_loss >= Self::FAIR_LOSS {
+            Self::Poor
+        } else if packet_loss >= Self::GOOD_LOSS {
+            Self::Fair
+        } else {
+            Self::Good
+        };
+        by_rtt.max(by_loss)
+    }
+}
+
+impl fmt::Display for QualityGrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Good => "良好",
+            Self::Fair => "一般",
+            Self::Poor => "较差",
+        })
+    }
+}
+
+/// 一个观看者的连接质量报告
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "quality")]
+pub struct QualityReport {
+    pub grade: QualityGrade,
+    /// 最近一个周期实际发送的码率 (kbps)
+    pub bitrate_kbps: u32,
+    /// 编码器的目标码率 (kbps)
+    pub target_bitrate_kbps: u32,
+    /// 编码帧率
+    pub fps: f64,
+    /// 往返时延 (ms)，还没有 RTCP 报告时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtt_ms: Option<f64>,
+    /// 丢包比例 (0.0 - 1.0)
+    pub packet_loss: f64,
+    /// 视频 codec (如 "H.264")
+    pub codec: String,
+    /// 编码器 (如 "NVIDIA NVENC"、"libvpx")
+    pub encoder: String,
+    /// 报告所属的会话 ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// 生成时间 (Unix 毫秒)
+    pub time_ms: u64,
+}
+
+impl QualityReport {
+    /// 按测得的数据生成报告 (评定等级并记录当前时间)
+    pub fn new(
+        bitrate_kbps: u32,
+        target_bitrate_kbps: u32,
+        fps: f64,
+        rtt_ms: Option<f64>,
+        packet_loss: f64,
+        codec: &str,
+        encoder: &str,
+    ) -> Self {
+        Self {
+            grade: QualityGrade::assess(rtt_ms, packet_loss),
+            bitrate_kbps,
+            target_bitrate_kbps,
+            fps,
+            rtt_ms,
+            packet_loss,
+            codec: codec.to_string(),
+            encoder: encoder.to_string(),
+            session_id: None,
+            time_ms: unix_millis(),
+        }
+    }
+
+    /// 标记报告所属的会话
+    pub fn for_session(mut self, session_id: &str) -> Self {
+        self.session_id = Some(session_id.to_string());
+        self
+    }
+
+    /// `stats` 通道和 `/video` 通知使用的 JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for QualityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} / {} kbps, {:.1} fps, 丢包 {:.1}%, {} {})",
+            self.grade,
+            self.bitrate_kbps,
+            self.target_bitrate_kbps,
+            self.fps,
+            self.packet_loss * 100.0,
+            self.codec,
+            self.encoder
+        )?;
+        if let Some(rtt) = self.rtt_ms {
+            write!(f, " RTT {:.0}ms", rtt)?;
+        }
+        Ok(())
+    }
+}
+
+/// 运行中切换编码器的事件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "encoder_switch")]
+pub struct EncoderSwitchEvent {
+    /// 失败的编码器
+    pub from: String,
+    /// 替换后的编码器
+    pub to: String,
+    /// 触发切换的编码错误
+    pub reason: String,
+    /// 切换时间 (Unix 毫秒)
+    pub time_ms: u64,
+}
+
+impl EncoderSwitchEvent {
+    pub fn new(from: &str, to: &str, reason: &str) -> Self {
+        Self {
+            from: from.to_string(),
+            to: to.to_string(),
+            reason: reason.to_string(),
+            time_ms: unix_millis(),
+        }
+    }
+
+    /// `stats` 通道和 `/video` 通知使用的 JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for EncoderSwitchEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} → {} ({})", self.from, self.to, self.reason)
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assess_grade() {
+        assert_eq!(QualityGrade::assess(Some(30.0), 0.0), QualityGrade::Good);
+        assert_eq!(QualityGrade::assess(None, 0.01), QualityGrade::Good);
+        assert_eq!(QualityGrade::assess(Some(150.0), 0.0), QualityGrade::Fair);
+        assert_eq!(QualityGrade::assess(Some(30.0), 0.05), QualityGrade::Fair);
+        assert_eq!(QualityGrade::assess(Some(300.0), 0.0), QualityGrade::Poor);
+        // 取两者中较差的
+        assert_eq!(QualityGrade::assess(Some(150.0), 0.1), QualityGrade::Poor);
+    }
+
+    #[test]
+    fn test_report_json() {
+        let report = QualityReport::new(1850, 2000, 29.8, None, 0.004, "H.264", "NVIDIA NVENC");
+        let value: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(value["type"], "quality");
+        assert_eq!(value["grade"], "good");
+        assert_eq!(value["bitrate_kbps"], 1850);
+        assert!(value.get("rtt_ms").is_none());
+        assert!(value.get("session_id").is_none());
+
+        let parsed: QualityReport = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(parsed, report);
+
+        let report = report.for_session("5f0c");
+        let value: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(value["session_id"], "5f0c");
+    }
+
+    #[test]
+    fn test_encoder_switch_json() {
+        let event = EncoderSwitchEvent::new("NVIDIA NVENC", "AMD AMF", "avcodec_send_frame 失败: -22");
+        let value: serde_json::Value = serde_json::from_str(&event.to_json()).unwrap();
+        assert_eq!(value["type"], "encoder_switch");
+        assert_eq!(value["from"], "NVIDIA NVENC");
+        assert_eq!(value["to"], "AMD AMF");
+
+        let parsed: EncoderSwitchEvent = serde_json::from_str(&event.to_json()).unwrap();
+        assert_eq!(parsed, event);
+    }
+}
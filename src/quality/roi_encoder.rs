@@ -12,6 +12,11 @@
 //! - ROI 大小自适应屏幕分辨率
 //! - 质量级别可配置
 //! - 平滑过渡避免闪烁
+//!
+//! ## 编码器支持
+//! ROI 区域以 FFmpeg `AV_FRAME_DATA_REGIONS_OF_INTEREST` 帧附加数据交给编码器，
+//! 由编码器换算成宏块级 QP 偏移。libx264 (需开启 AQ)、libvpx 和 h264_qsv 会应用；
+//! VideoToolbox 没有公开的按区域 QP 接口，不支持 ROI
 
 // ROI 编码器模块尚未完全集成，标记为允许死代码
 #![allow(dead_code)]
@@ -19,9 +24,100 @@
 use crate::capture::Frame;
 use crate::encoder::{EncodedPacket, Encoder};
 use anyhow::Result;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+/// H.264 的 QP 范围，FFmpeg 按该范围把 qoffset 换算成 QP 偏移
+const QP_RANGE: f32 = 51.0;
+
+/// 编码器的感兴趣区域
+///
+/// 坐标为编码帧的像素坐标 (right / bottom 不含)；`qoffset` 与 FFmpeg
+/// `AVRegionOfInterest.qoffset` 含义相同: -1.0 ~ 1.0，负数表示降低 QP (提高质量)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoiRegion {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub qoffset: f32,
+}
+
+impl RoiRegion {
+    /// 以 (x, y) 为中心、半径 `half` 的方形区域，裁剪到帧内
+    fn around(x: u32, y: u32, half: u32, width: u32, height: u32, qoffset: f32) -> Self {
+        Self {
+            left: x.saturating_sub(half),
+            top: y.saturating_sub(half),
+            right: x.saturating_add(half).clamp(1, width),
+            bottom: y.saturating_add(half).clamp(1, height),
+            qoffset,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> u32 {
+        self.bottom - self.top
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        (self.left..self.right).contains(&x) && (self.top..self.bottom).contains(&y)
+    }
+}
+
+/// 指针位置 (归一化坐标 0.0 - 1.0)
+///
+/// 输入任务在注入鼠标移动时写入，视频任务编码前读取；未收到过鼠标移动时为空
+#[derive(Debug, Clone)]
+pub struct PointerPosition(Arc<AtomicU64>);
+
+impl PointerPosition {
+    const UNKNOWN: u64 = u64::MAX;
+
+    pub fn new() -> Self {
+        Self(Arc::new(AtomicU64::new(Self::UNKNOWN)))
+    }
+
+    pub fn set(&self, x: f64, y: f64) {
+        let x = (x.clamp(0.0, 1.0) as f32).to_bits() as u64;
+        let y = (y.clamp(0.0, 1.0) as f32).to_bits() as u64;
+        self.0.store(x << 32 | y, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> Option<(f64, f64)> {
+        let packed = self.0.load(Ordering::Relaxed);
+        if packed == Self::UNKNOWN {
+            return None;
+        }
+        let x = f32::from_bits((packed >> 32) as u32);
+        let y = f32::from_bits(packed as u32);
+        Some((x as f64, y as f64))
+    }
+
+    /// 按指针位置计算一帧的 ROI 区域 (ROI 大小按帧分辨率自适应)，位置未知时为空
+    pub fn regions(&self, width: u32, height: u32) -> Vec<RoiRegion> {
+        match self.get() {
+            Some((x, y)) => ROIConfig::adaptive(width, height).regions(
+                (x * width as f64) as u32,
+                (y * height as f64) as u32,
+                width,
+                height,
+            ),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Default for PointerPosition {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// ROI 配置
 #[derive(Debug, Clone)]
 pub struct ROIConfig {
@@ -54,7 +150,7 @@ impl ROIConfig {
     pub fn adaptive(screen_width: u32, screen_height: u32) -> Self {
         // ROI 大小为屏幕较小边的 1/3
         let min_dimension = screen_width.min(screen_height);
-        let roi_size = (min_dimension / 3).max(256).min(1024);
+        let roi_size = (min_dimension / 3).clamp(256, 1024);
 
         // 高分辨率屏幕使用更大过渡区
         let transition_width = if min_dimension > 1920 {
@@ -69,6 +165,53 @@ impl ROIConfig {
             ..Default::default()
         }
     }
+
+    /// 以 (x, y) 为中心生成 ROI 区域
+    ///
+    /// ROI 区域的 QP 比背景低 `background_quality - roi_quality`，过渡区降低一半；
+    /// 过渡区排在 ROI 之后，重叠部分以 ROI 为准 (FFmpeg 的约定)
+    pub fn regions(&self, x: u32, y: u32, width: u32, height: u32) -> Vec<RoiRegion> {
+        let delta = self.roi_quality as f32 - self.background_quality as f32;
+        if width == 0 || height == 0 || delta >= 0.0 {
+            return Vec::new();
+        }
+        let (x, y) = (x.min(width - 1), y.min(height - 1));
+        let qoffset = (delta / QP_RANGE).max(-1.0);
+        let half = (self.roi_size / 2).max(1);
+
+        let mut regions = vec![RoiRegion::around(x, y, half, width, height, qoffset)];
+        if self.enable_smooth_transition && self.transition_width > 0 {
+            regions.push(RoiRegion::around(x, y, half + self.transition_width, width, height, qoffset / 2.0));
+        }
+        regions
+    }
+}
+
+/// 两帧在指定区域内的亮度 PSNR (dB)，完全相同时为无穷大
+///
+/// 用于衡量 ROI 编码前后各区域的画质
+pub fn luma_psnr(reference: &Frame, decoded: &Frame, region: &RoiRegion) -> f64 {
+    let luma = |frame: &Frame, x: u32, y: u32| {
+        let offset = y as usize * frame.stride + x as usize * 4;
+        let pixel = &frame.data[offset..offset + 3];
+        0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64
+    };
+
+    let right = region.right.min(reference.width).min(decoded.width);
+    let bottom = region.bottom.min(reference.height).min(decoded.height);
+    let mut sum = 0.0;
+    let mut count = 0u64;
+    for y in region.top..bottom {
+        for x in region.left..right {
+            let diff = luma(reference, x, y) - luma(decoded, x, y);
+            sum += diff * diff;
+            count += 1;
+        }
+    }
+    if count == 0 || sum == 0.0 {
+        return f64::INFINITY;
+    }
+    10.0 * (255.0 * 255.0 / (sum / count as f64)).log10()
 }
 
 /// 基于鼠标位置的 ROI 编码器
@@ -246,9 +389,17 @@ where
     fn encode(&mut self, frame: &Frame) -> Result<Option<EncodedPacket>> {
         self.frame_count += 1;
 
-        // 目前直接使用内部编码器
-        // 实际实现需要根据 ROI 分区域编码
-        // 这需要编码器支持区域编码或分段编码
+        // 鼠标周围的宏块使用更低的 QP (编码器不支持 ROI 时忽略)
+        let (x, y) = match self.mouse_position.try_lock() {
+            Ok(position) => *position,
+            Err(_) => (self.screen_width / 2, self.screen_height / 2),
+        };
+        // 编码尺寸可能与屏幕尺寸不同，按比例换算
+        let (width, height) = (frame.width, frame.height);
+        let x = (x as u64 * width as u64 / self.screen_width.max(1) as u64) as u32;
+        let y = (y as u64 * height as u64 / self.screen_height.max(1) as u64) as u32;
+        self.inner_encoder.set_roi(&self.config.regions(x, y, width, height));
+
         self.inner_encoder.encode(frame)
     }
 
@@ -267,6 +418,10 @@ where
     fn flush(&mut self) -> Result<Option<EncodedPacket>> {
         self.inner_encoder.flush()
     }
+
+    fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()> {
+        self.inner_encoder.set_bitrate(bitrate_kbps)
+    }
 }
 
 /// ROI 统计信息
@@ -455,6 +610,126 @@ mod tests {
         assert!(savings > 30.0);
     }
 
+    #[test]
+    fn test_roi_regions() {
+        let config = ROIConfig::default();
+        let regions = config.regions(960, 540, 1920, 1080);
+        assert_eq!(regions.len(), 2);
+        assert_eq!((regions[0].left, regions[0].top, regions[0].right, regions[0].bottom), (704, 284, 1216, 796));
+        assert!((regions[0].qoffset - (-10.0 / 51.0)).abs() < 1e-6);
+        // 过渡区更大，QP 降低一半
+        assert_eq!((regions[1].left, regions[1].right), (640, 1280));
+        assert!((regions[1].qoffset - regions[0].qoffset / 2.0).abs() < 1e-6);
+
+        // 靠近边缘时裁剪到帧内
+        let regions = config.regions(0, 5000, 1920, 1080);
+        assert_eq!((regions[0].left, regions[0].top, regions[0].right, regions[0].bottom), (0, 823, 256, 1080));
+        assert!(regions[0].contains(0, 1079));
+        assert!(!regions[0].contains(256, 1000));
+
+        // 质量没有差别时不生成 ROI
+        let flat = ROIConfig { roi_quality: 28, ..ROIConfig::default() };
+        assert!(flat.regions(960, 540, 1920, 1080).is_empty());
+    }
+
+    #[test]
+    fn test_pointer_position() {
+        let pointer = PointerPosition::new();
+        assert_eq!(pointer.get(), None);
+        assert!(pointer.regions(1920, 1080).is_empty());
+
+        pointer.clone().set(0.25, 1.5);
+        assert_eq!(pointer.get(), Some((0.25, 1.0)));
+        let regions = pointer.regions(1920, 1080);
+        // 自适应 ROI 大小 360px，中心 (480, 1079)
+        assert_eq!((regions[0].left, regions[0].right, regions[0].bottom), (300, 660, 1080));
+    }
+
+    #[test]
+    fn test_luma_psnr() {
+        let reference = Frame::new(64, 64);
+        let region = RoiRegion { left: 0, top: 0, right: 32, bottom: 32, qoffset: 0.0 };
+        assert_eq!(luma_psnr(&reference, &reference, &region), f64::INFINITY);
+
+        // 区域内亮度整体偏差 10: PSNR = 10 * log10(255² / 100)
+        let mut decoded = reference.clone();
+        for y in 0..32 {
+            let row = y * decoded.stride;
+            for pixel in decoded.data[row..row + 32 * 4].chunks_mut(4) {
+                pixel[..3].fill(10);
+            }
+        }
+        let psnr = luma_psnr(&reference, &decoded, &region);
+        assert!((psnr - 28.13).abs() < 0.01, "psnr = {}", psnr);
+        let outside = RoiRegion { left: 32, right: 64, ..region };
+        assert_eq!(luma_psnr(&reference, &decoded, &outside), f64::INFINITY);
+    }
+
+    /// 同样码率下，开启 ROI 后指针周围的 PSNR 明显提高
+    #[cfg(feature = "h264")]
+    #[test]
+    fn test_roi_improves_region_psnr() {
+        use crate::encoder::decoder::{DecoderCodec, VideoDecoder};
+        use crate::encoder::H264Encoder;
+
+        const WIDTH: u32 = 640;
+        const HEIGHT: u32 = 360;
+
+        // 8x8 随机灰度块，每帧内容不同，迫使编码器持续分配码率
+        let textured = |seed: u32| {
+            let mut frame = Frame::new(WIDTH, HEIGHT);
+            for y in 0..HEIGHT {
+                for x in 0..WIDTH {
+                    let mut state = (x / 8 + 1) * 7919 ^ (y / 8 + 1) * 104_729 ^ seed.wrapping_mul(2_654_435_761);
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    let offset = y as usize * frame.stride + x as usize * 4;
+                    frame.data[offset..offset + 3].fill((state >> 24) as u8);
+                    frame.data[offset + 3] = 255;
+                }
+            }
+            frame
+        };
+
+        let regions = ROIConfig::adaptive(WIDTH, HEIGHT).regions(WIDTH / 2, HEIGHT / 2, WIDTH, HEIGHT);
+        let roi = regions[0];
+        let background = RoiRegion { left: 0, top: 0, right: regions[1].left, bottom: HEIGHT, qoffset: 0.0 };
+
+        // 返回最后一帧 (ROI 区域 PSNR, 背景 PSNR)
+        let measure = |regions: &[RoiRegion]| -> Option<(f64, f64)> {
+            let mut encoder = H264Encoder::new(WIDTH, HEIGHT, 30, 800).unwrap();
+            let mut decoder = VideoDecoder::new(DecoderCodec::H264).unwrap();
+            if !encoder.set_roi(regions) && !regions.is_empty() {
+                return None;
+            }
+            let mut result = None;
+            for seed in 0..10 {
+                let frame = textured(seed);
+                if let Some(packet) = encoder.encode(&frame).unwrap() {
+                    if let Some(decoded) = decoder.decode(&packet.data).unwrap() {
+                        result = Some((luma_psnr(&frame, &decoded, &roi), luma_psnr(&frame, &decoded, &background)));
+                    }
+                }
+            }
+            result
+        };
+
+        let Some((roi_with, background_with)) = measure(&regions) else {
+            // 系统 FFmpeg 的 H.264 编码器不是 libx264 (如 openh264)，无法应用 ROI
+            return;
+        };
+        let (roi_without, background_without) = measure(&[]).unwrap();
+        assert!(
+            roi_with > roi_without + 1.0,
+            "ROI PSNR {:.2} dB 未明显高于不开启 ROI 时的 {:.2} dB",
+            roi_with,
+            roi_without
+        );
+        // 码率从背景挪到 ROI，背景画质不会更好
+        assert!(background_with <= background_without + 0.5);
+    }
+
     #[test]
     fn test_mouse_position_update() {
         let wrapper = ROIEncoderWrapper::new(1920, 1080, None);
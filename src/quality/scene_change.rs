@@ -0,0 +1,209 @@
+//! 场景切换检测
+//!
+//! 关键帧原本只按 GOP 周期插入。切换应用、全屏视频开始播放等大幅画面变化后，
+//! 编码器仍以旧画面为参考做帧间预测，码率不足时观看者会看到数帧的拖影和模糊。
+//!
+//! 检测方法: 把帧缩小为 32x18 的亮度缩略图，与上一帧比较
+//! - 平均绝对差 (SAD): 画面内容变化了多少
+//! - 亮度直方图距离: 整体明暗分布是否改变 (滚动、拖动窗口时直方图基本不变)
+//!
+//! 两项都超过阈值时判定为场景切换
+
+use crate::capture::Frame;
+
+/// 缩略图宽度 (格)
+const THUMB_WIDTH: usize = 32;
+/// 缩略图高度 (格)
+const THUMB_HEIGHT: usize = 18;
+/// 每格内的采样步长 (像素)
+const SAMPLE_STEP: usize = 4;
+/// 直方图分桶数
+const HISTOGRAM_BINS: usize = 32;
+
+/// 场景切换检测配置
+#[derive(Debug, Clone)]
+pub struct SceneChangeConfig {
+    /// 缩略图平均绝对差阈值 (0.0 - 1.0，相对 255)
+    pub sad_threshold: f32,
+    /// 直方图距离阈值 (0.0 - 1.0，归一化直方图 L1 距离的一半)
+    pub histogram_threshold: f32,
+    /// 两次场景切换之间至少间隔的帧数 (避免视频播放时连续插入关键帧)
+    pub min_interval_frames: u32,
+}
+
+impl Default for SceneChangeConfig {
+    fn default() -> Self {
+        Self {
+            sad_threshold: 0.12,
+            histogram_threshold: 0.25,
+            min_interval_frames: 10,
+        }
+    }
+}
+
+/// 一帧的检测结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SceneChange {
+    /// 缩略图平均绝对差 (0.0 - 1.0)
+    pub sad: f32,
+    /// 直方图距离 (0.0 - 1.0)
+    pub histogram_distance: f32,
+    /// 是否判定为场景切换
+    pub is_cut: bool,
+}
+
+/// 亮度缩略图
+struct Thumbnail {
+    luma: [u8; THUMB_WIDTH * THUMB_HEIGHT],
+    histogram: [u32; HISTOGRAM_BINS],
+}
+
+impl Thumbnail {
+    fn from_frame(frame: &Frame) -> Option<Self> {
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        if width < THUMB_WIDTH || height < THUMB_HEIGHT || frame.data.len() < frame.stride * height {
+            return None;
+        }
+
+        let mut luma = [0u8; THUMB_WIDTH * THUMB_HEIGHT];
+        let mut histogram = [0u32; HISTOGRAM_BINS];
+        for cell_y in 0..THUMB_HEIGHT {
+            let (top, bottom) = (cell_y * height / THUMB_HEIGHT, (cell_y + 1) * height / THUMB_HEIGHT);
+            for cell_x in 0..THUMB_WIDTH {
+                let (left, right) = (cell_x * width / THUMB_WIDTH, (cell_x + 1) * width / THUMB_WIDTH);
+                let mut sum = 0u32;
+                let mut count = 0u32;
+                for y in (top..bottom).step_by(SAMPLE_STEP) {
+                    let row = &frame.data[y * frame.stride..];
+                    for x in (left..right).step_by(SAMPLE_STEP) {
+                        let pixel = &row[x * 4..x * 4 + 3];
+                        sum += (77 * pixel[0] as u32 + 150 * pixel[1] as u32 + 29 * pixel[2] as u32) >> 8;
+                        count += 1;
+                    }
+                }
+                let value = (sum / count.max(1)) as u8;
+                luma[cell_y * THUMB_WIDTH + cell_x] = value;
+                histogram[value as usize * HISTOGRAM_BINS / 256] += 1;
+            }
+        }
+        Some(Self { luma, histogram })
+    }
+
+    fn sad(&self, other: &Self) -> f32 {
+        let total: u32 = self.luma.iter().zip(other.luma.iter()).map(|(a, b)| a.abs_diff(*b) as u32).sum();
+        total as f32 / (self.luma.len() as f32 * 255.0)
+    }
+
+    fn histogram_distance(&self, other: &Self) -> f32 {
+        let total: u32 = self.histogram.iter().zip(other.histogram.iter()).map(|(a, b)| a.abs_diff(*b)).sum();
+        total as f32 / (2.0 * self.luma.len() as f32)
+    }
+}
+
+/// 场景切换检测器
+pub struct SceneChangeDetector {
+    config: SceneChangeConfig,
+    previous: Option<Thumbnail>,
+    frames_since_cut: u32,
+}
+
+impl SceneChangeDetector {
+    pub fn new(config: SceneChangeConfig) -> Self {
+        let frames_since_cut = config.min_interval_frames;
+        Self { config, previous: None, frames_since_cut }
+    }
+
+    /// 与上一帧比较并记录当前帧；第一帧和太小的帧不判定为场景切换
+    pub fn detect(&mut self, frame: &Frame) -> SceneChange {
+        let Some(current) = Thumbnail::from_frame(frame) else {
+            return SceneChange { sad: 0.0, histogram_distance: 0.0, is_cut: false };
+        };
+        self.frames_since_cut = self.frames_since_cut.saturating_add(1);
+
+        let (sad, histogram_distance) = match self.previous.as_ref() {
+            Some(previous) => (current.sad(previous), current.histogram_distance(previous)),
+            None => (0.0, 0.0),
+        };
+        self.previous = Some(current);
+
+        let is_cut = sad >= self.config.sad_threshold
+            && histogram_distance >= self.config.histogram_threshold
+            && self.frames_since_cut >= self.config.min_interval_frames;
+        if is_cut {
+            self.frames_since_cut = 0;
+        }
+        SceneChange { sad, histogram_distance, is_cut }
+    }
+
+    /// 捕获重启后清空参考帧
+    pub fn reset(&mut self) {
+        self.previous = None;
+        self.frames_since_cut = self.config.min_interval_frames;
+    }
+}
+
+impl Default for SceneChangeDetector {
+    fn default() -> Self {
+        Self::new(SceneChangeConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(width: u32, height: u32, pixel: impl Fn(u32, u32) -> u8) -> Frame {
+        let mut frame = Frame::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let offset = y as usize * frame.stride + x as usize * 4;
+                frame.data[offset..offset + 3].fill(pixel(x, y));
+                frame.data[offset + 3] = 255;
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn test_detects_cut() {
+        let mut detector = SceneChangeDetector::default();
+        let dark = filled(320, 180, |x, _| (x / 10) as u8);
+        let bright = filled(320, 180, |_, y| 200 + (y / 20) as u8);
+
+        assert!(!detector.detect(&dark).is_cut);
+        assert!(!detector.detect(&dark).is_cut);
+        let change = detector.detect(&bright);
+        assert!(change.is_cut, "{:?}", change);
+        assert!(change.sad > 0.5);
+        assert!(change.histogram_distance > 0.9);
+    }
+
+    #[test]
+    fn test_scrolling_is_not_a_cut() {
+        let mut detector = SceneChangeDetector::default();
+        // 条纹整体平移: 内容变化但亮度分布不变
+        let stripes = |shift: u32| filled(320, 180, move |_, y| if ((y + shift) / 10).is_multiple_of(2) { 30 } else { 220 });
+
+        for shift in 0..5 {
+            let change = detector.detect(&stripes(shift * 10));
+            assert!(!change.is_cut, "shift {}: {:?}", shift, change);
+        }
+    }
+
+    #[test]
+    fn test_min_interval() {
+        let mut detector = SceneChangeDetector::new(SceneChangeConfig { min_interval_frames: 3, ..Default::default() });
+        let black = filled(64, 36, |_, _| 0);
+        let white = filled(64, 36, |_, _| 255);
+
+        let cuts: Vec<bool> = [&black, &white, &black, &white, &black]
+            .into_iter()
+            .map(|frame| detector.detect(frame).is_cut)
+            .collect();
+        // 第一次切换后 3 帧内不再判定
+        assert_eq!(cuts, vec![false, true, false, false, true]);
+
+        detector.reset();
+        assert!(!detector.detect(&white).is_cut);
+    }
+}
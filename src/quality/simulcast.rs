@@ -0,0 +1,322 @@
+//! 多观看者 simulcast
+//!
+//! 同一画面按多个档位 (分辨率 + 码率) 编码，每个观看者根据自己测得的网络状态
+//! 接收其中一档，网速慢的观看者不再拖累其他人:
+//! - 高: 原始分辨率，配置码率
+//! - 中: 2/3 分辨率 (1080p → 720p)，1/2 码率
+//! - 低: 1/3 分辨率 (1080p → 360p)，1/5 码率
+//!
+//! VP9 时域分层时不另外编码: 各档为同一路码流的不同时域层 (全帧率 / 1/2 / 1/4 帧率)，
+//! 较低的档位只转发较低的层
+//!
+//! ## 切换规则
+//! - 丢包超过 5% 或带宽低于当前档码率时降档 (直接降到带宽能承载的档位)
+//! - 带宽达到上一档码率的 1.5 倍且持续 10 秒无明显丢包时升一档
+//! - 两次切换至少间隔 3 秒，避免在档位间来回抖动
+
+// 编码器只在 h264 feature 下可用
+#![cfg_attr(not(all(feature = "h264", feature = "webrtc")), allow(dead_code))]
+
+use super::adaptive_bitrate::NetworkState;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// 丢包超过该比例时降档
+const CONGESTION_LOSS: f64 = 0.05;
+/// 升档要求的最大丢包率
+const UPGRADE_MAX_LOSS: f64 = 0.02;
+/// 升档要求带宽达到目标档码率的倍数
+const UPGRADE_HEADROOM: f64 = 1.5;
+/// 升档条件需要持续的时间
+const UPGRADE_HOLD: Duration = Duration::from_secs(10);
+/// 两次切换的最小间隔
+const MIN_DWELL: Duration = Duration::from_secs(3);
+/// 档位码率下限 (kbps)
+const MIN_TIER_BITRATE: u32 = 200;
+
+/// 编码档位
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tier {
+    pub name: &'static str,
+    /// 相对原始分辨率的缩放比例
+    pub scale: f64,
+    /// 目标码率 (kbps)
+    pub bitrate_kbps: u32,
+    /// 转发的最高时域层 (时域分层档位；None = 该档单独编码)
+    pub temporal_layer: Option<u8>,
+}
+
+/// simulcast 档位配置 (从高到低排列，第 0 档为原始分辨率)
+#[derive(Debug, Clone)]
+pub struct SimulcastConfig {
+    pub tiers: Vec<Tier>,
+}
+
+impl SimulcastConfig {
+    /// 以最高档码率生成 高/中/低 三档
+    pub fn for_bitrate(bitrate_kbps: u32) -> Self {
+        let tier = |name, scale, divisor: u32| Tier {
+            name,
+            scale,
+            bitrate_kbps: (bitrate_kbps / divisor).max(MIN_TIER_BITRATE),
+            temporal_layer: None,
+        };
+        Self {
+            tiers: vec![tier("高", 1.0, 1), tier("中", 2.0 / 3.0, 2), tier("低", 1.0 / 3.0, 5)],
+        }
+    }
+
+    /// 按时域层分档 (VP9): 第 0 档接收全部层，最后一档只接收基础层
+    pub fn temporal(bitrate_kbps: u32, layers: u8) -> Self {
+        let pattern = crate::encoder::vp9::TemporalPattern::new(layers);
+        let names: &[&'static str] = match pattern.layers() {
+            1 => &["高"],
+            2 => &["高", "低"],
+            _ => &["高", "中", "低"],
+        };
+        let tiers = names
+            .iter()
+            .zip((0..pattern.layers()).rev())
+            .map(|(&name, layer)| Tier {
+                name,
+                scale: 1.0,
+                bitrate_kbps: pattern.cumulative_bitrate(layer, bitrate_kbps),
+                temporal_layer: Some(layer),
+            })
+            .collect();
+        Self { tiers }
+    }
+
+    /// 是否按时域层分档
+    pub fn is_temporal(&self) -> bool {
+        self.tiers.iter().any(|tier| tier.temporal_layer.is_some())
+    }
+
+    /// 按新的最高档码率重新生成同类档位
+    pub fn with_bitrate(&self, bitrate_kbps: u32) -> Self {
+        if self.is_temporal() {
+            Self::temporal(bitrate_kbps, self.tiers.len() as u8)
+        } else {
+            Self::for_bitrate(bitrate_kbps)
+        }
+    }
+
+    /// 档位在给定原始尺寸下的编码尺寸 (偶数宽高)
+    pub fn tier_size(&self, tier: usize, width: u32, height: u32) -> (u32, u32) {
+        let scale = self.tiers[tier].scale;
+        let even = |value: u32| (((value as f64 * scale).round() as u32) & !1).max(2);
+        (even(width), even(height))
+    }
+}
+
+/// 观看者的档位切换
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TierSwitch {
+    pub from: usize,
+    pub to: usize,
+}
+
+#[derive(Debug)]
+struct ViewerTier {
+    tier: usize,
+    switched_at: Option<Instant>,
+    /// 满足升档条件的起始时间
+    upgrade_since: Option<Instant>,
+}
+
+/// 按观看者 (peer_id) 选择档位
+///
+/// 新观看者从最高档开始，之后按各自的网络状态升降
+#[derive(Debug)]
+pub struct TierSelector {
+    config: SimulcastConfig,
+    viewers: HashMap<String, ViewerTier>,
+}
+
+impl TierSelector {
+    pub fn new(config: SimulcastConfig) -> Self {
+        Self {
+            config,
+            viewers: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &SimulcastConfig {
+        &self.config
+    }
+
+    pub fn tier_count(&self) -> usize {
+        self.config.tiers.len()
+    }
+
+    /// 观看者当前的档位
+    pub fn tier_of(&self, peer_id: &str) -> usize {
+        self.viewers.get(peer_id).map_or(0, |viewer| viewer.tier)
+    }
+
+    /// 只保留仍在观看的观看者
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.viewers.retain(|peer_id, _| keep(peer_id));
+    }
+
+    /// 输入观看者最新的网络状态，档位变化时返回切换
+    pub fn update(&mut self, peer_id: &str, state: NetworkState) -> Option<TierSwitch> {
+        self.update_at(peer_id, state, Instant::now())
+    }
+
+    fn update_at(&mut self, peer_id: &str, state: NetworkState, now: Instant) -> Option<TierSwitch> {
+        let tiers = &self.config.tiers;
+        let last = tiers.len().checked_sub(1)?;
+        let viewer = self.viewers.entry(peer_id.to_string()).or_insert(ViewerTier {
+            tier: 0,
+            switched_at: None,
+            upgrade_since: None,
+        });
+
+        let current = viewer.tier;
+        let bandwidth_kbps = state.bandwidth_mbps * 1000.0;
+        let bitrate = |tier: usize| f64::from(tiers[tier].bitrate_kbps);
+
+        let target = if state.packet_loss > CONGESTION_LOSS || bandwidth_kbps < bitrate(current) {
+            viewer.upgrade_since = None;
+            // 带宽能承载的最高档，至少降一档
+            let fit = (0..=last).find(|&tier| bitrate(tier) <= bandwidth_kbps).unwrap_or(last);
+            fit.max(current + 1).min(last)
+        } else if current > 0
+            && state.packet_loss <= UPGRADE_MAX_LOSS
+            && bandwidth_kbps >= bitrate(current - 1) * UPGRADE_HEADROOM
+        {
+            let since = *viewer.upgrade_since.get_or_insert(now);
+            if now.saturating_duration_since(since) >= UPGRADE_HOLD {
+                current - 1
+            } else {
+                current
+            }
+        } else {
+            viewer.upgrade_since = None;
+            current
+        };
+
+        let dwelling = viewer
+            .switched_at
+            .is_some_and(|at| now.saturating_duration_since(at) < MIN_DWELL);
+        if target == current || dwelling {
+            return None;
+        }
+
+        viewer.tier = target;
+        viewer.switched_at = Some(now);
+        viewer.upgrade_since = None;
+        Some(TierSwitch { from: current, to: target })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(bandwidth_mbps: f64, packet_loss: f64) -> NetworkState {
+        NetworkState {
+            bandwidth_mbps,
+            packet_loss,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tiers() {
+        let config = SimulcastConfig::for_bitrate(2000);
+        let bitrates: Vec<u32> = config.tiers.iter().map(|t| t.bitrate_kbps).collect();
+        assert_eq!(bitrates, vec![2000, 1000, 400]);
+        assert_eq!(config.tier_size(0, 1920, 1080), (1920, 1080));
+        assert_eq!(config.tier_size(1, 1920, 1080), (1280, 720));
+        assert_eq!(config.tier_size(2, 1920, 1080), (640, 360));
+        // 缩放后仍为偶数
+        assert_eq!(config.tier_size(1, 1366, 768), (910, 512));
+
+        assert_eq!(SimulcastConfig::for_bitrate(500).tiers[2].bitrate_kbps, MIN_TIER_BITRATE);
+    }
+
+    #[test]
+    fn test_temporal_tiers() {
+        let config = SimulcastConfig::temporal(2000, 3);
+        let tiers: Vec<(u32, Option<u8>)> = config.tiers.iter().map(|t| (t.bitrate_kbps, t.temporal_layer)).collect();
+        assert_eq!(tiers, vec![(2000, Some(2)), (1200, Some(1)), (800, Some(0))]);
+        assert_eq!(config.tier_size(2, 1920, 1080), (1920, 1080));
+        assert!(config.is_temporal());
+        assert!(config.with_bitrate(1000).is_temporal());
+        assert!(!SimulcastConfig::for_bitrate(2000).is_temporal());
+    }
+
+    #[test]
+    fn test_downgrade_to_fitting_tier() {
+        let mut selector = TierSelector::new(SimulcastConfig::for_bitrate(2000));
+        let now = Instant::now();
+
+        assert_eq!(selector.update_at("fast", state(5.0, 0.0), now), None);
+        assert_eq!(selector.tier_of("fast"), 0);
+
+        // 带宽只够低档，直接降两档
+        assert_eq!(
+            selector.update_at("slow", state(0.5, 0.0), now),
+            Some(TierSwitch { from: 0, to: 2 })
+        );
+        assert_eq!(selector.tier_of("slow"), 2);
+        assert_eq!(selector.tier_of("fast"), 0, "其他观看者不受影响");
+
+        // 带宽足够但丢包严重，降一档
+        assert_eq!(
+            selector.update_at("lossy", state(5.0, 0.1), now),
+            Some(TierSwitch { from: 0, to: 1 })
+        );
+        // 已在最低档时不再切换
+        assert_eq!(selector.update_at("slow", state(0.1, 0.2), now + MIN_DWELL), None);
+
+        selector.retain(|peer| peer != "slow");
+        assert_eq!(selector.tier_of("slow"), 0);
+    }
+
+    #[test]
+    fn test_upgrade_requires_sustained_headroom() {
+        let mut selector = TierSelector::new(SimulcastConfig::for_bitrate(2000));
+        let start = Instant::now();
+        selector.update_at("viewer", state(0.5, 0.0), start);
+        assert_eq!(selector.tier_of("viewer"), 2);
+
+        // 带宽恢复 (中档 1000kbps 的 1.5 倍)，需要持续 UPGRADE_HOLD 才升档
+        let good = state(1.6, 0.0);
+        let t1 = start + MIN_DWELL;
+        assert_eq!(selector.update_at("viewer", good, t1), None);
+        assert_eq!(selector.update_at("viewer", good, t1 + UPGRADE_HOLD / 2), None);
+        assert_eq!(
+            selector.update_at("viewer", good, t1 + UPGRADE_HOLD),
+            Some(TierSwitch { from: 2, to: 1 })
+        );
+
+        // 中途出现丢包会重新计时
+        let t2 = t1 + UPGRADE_HOLD + MIN_DWELL;
+        let better = state(3.5, 0.0);
+        assert_eq!(selector.update_at("viewer", better, t2), None);
+        assert_eq!(selector.update_at("viewer", state(3.5, 0.03), t2 + UPGRADE_HOLD / 2), None);
+        assert_eq!(selector.update_at("viewer", better, t2 + UPGRADE_HOLD), None);
+        assert_eq!(
+            selector.update_at("viewer", better, t2 + UPGRADE_HOLD * 2),
+            Some(TierSwitch { from: 1, to: 0 })
+        );
+    }
+
+    #[test]
+    fn test_min_dwell_between_switches() {
+        let mut selector = TierSelector::new(SimulcastConfig::for_bitrate(2000));
+        let start = Instant::now();
+        assert!(selector.update_at("viewer", state(1.5, 0.0), start).is_some());
+        assert_eq!(selector.tier_of("viewer"), 1);
+
+        // 刚切换过，继续恶化也要等 MIN_DWELL
+        assert_eq!(selector.update_at("viewer", state(0.5, 0.0), start + Duration::from_secs(1)), None);
+        assert_eq!(
+            selector.update_at("viewer", state(0.5, 0.0), start + MIN_DWELL),
+            Some(TierSwitch { from: 1, to: 2 })
+        );
+    }
+}
@@ -207,7 +207,7 @@ impl StaticSceneDetector {
                 // 简单的绝对差异
                 let diff = prev_rgba.iter()
                     .zip(curr_rgba.iter())
-                    .map(|(p, c)| (*p as i32 - *c as i32).abs() as u32)
+                    .map(|(p, c)| (*p as i32 - *c as i32).unsigned_abs())
                     .sum::<u32>();
 
                 // 如果任一通道差异 > 10，认为是不同像素
@@ -386,13 +386,14 @@ mod tests {
 
     fn create_test_frame(width: u32, height: u32, color: u8) -> Frame {
         let stride = (width * 4) as usize;
-        let mut data = vec![color; (width * height * 4) as usize];
+        let data = vec![color; (width * height * 4) as usize];
         Frame {
             width,
             height,
             data,
             timestamp: 0,
             stride,
+            gpu: None,
         }
     }
 
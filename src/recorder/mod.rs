@@ -0,0 +1,407 @@
+//! 会话录制
+//!
+//! 直接复用发送给 Viewer 的编码数据 (H.264 / VP8)，不重新编码，封装为 MP4 / WebM / MKV 文件。
+//! 支持按时长和文件大小分段，避免无人值守的长会话写出单个超大文件。
+//!
+//! 分段只在关键帧处切换，保证每个文件都能独立播放；达到分段条件后
+//! `wants_key_frame()` 返回 true，调用方应尽快请求关键帧。
+//!
+//! 每个分段的元数据 (`comment`) 记录其中出现过的会话 ID，可与审计日志和统计关联；
+//! 有新会话加入时同样在下一个关键帧切换分段。
+
+#![allow(dead_code)]
+
+#[cfg(feature = "h264")]
+mod muxer;
+
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// 录制配置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordingConfig {
+    /// 输出路径 (扩展名与编码不兼容时自动改为 .mp4 / .webm)
+    pub path: PathBuf,
+    /// 单个分段的最长时长 (None = 不按时长分段)
+    pub segment_duration: Option<Duration>,
+    /// 单个分段的最大字节数 (None = 不按大小分段)
+    pub max_segment_bytes: Option<u64>,
+}
+
+impl RecordingConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            segment_duration: None,
+            max_segment_bytes: None,
+        }
+    }
+
+    /// 是否启用了分段
+    pub fn rotates(&self) -> bool {
+        self.segment_duration.is_some() || self.max_segment_bytes.is_some()
+    }
+}
+
+/// 录制的视频编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordCodec {
+    H264,
+    Vp8,
+    Vp9,
+}
+
+impl RecordCodec {
+    /// 默认容器扩展名
+    pub fn default_extension(&self) -> &'static str {
+        match self {
+            Self::H264 => "mp4",
+            Self::Vp8 | Self::Vp9 => "webm",
+        }
+    }
+
+    /// 容器是否支持该编码
+    fn supports_extension(&self, ext: &str) -> bool {
+        match self {
+            Self::H264 => matches!(ext, "mp4" | "mov" | "mkv"),
+            Self::Vp8 | Self::Vp9 => matches!(ext, "webm" | "mkv"),
+        }
+    }
+}
+
+/// 判断 VP8 数据是否为关键帧 (帧头第一个字节最低位为 0)
+pub fn is_vp8_key_frame(data: &[u8]) -> bool {
+    data.first().is_some_and(|b| b & 1 == 0)
+}
+
+/// 判断 VP9 数据是否为关键帧
+///
+/// 未压缩帧头: frame_marker(2) profile_low(1) profile_high(1) [profile 3: reserved(1)]
+/// show_existing_frame(1) frame_type(1)，frame_type 为 0 时是关键帧
+pub fn is_vp9_key_frame(data: &[u8]) -> bool {
+    let Some(&byte) = data.first() else {
+        return false;
+    };
+    if byte >> 6 != 0b10 {
+        return false;
+    }
+    let profile = (byte >> 5 & 1) | (byte >> 4 & 1) << 1;
+    let shift = if profile == 3 { 2 } else { 3 };
+    let show_existing_frame = byte >> shift & 1;
+    let frame_type = byte >> (shift - 1) & 1;
+    show_existing_frame == 0 && frame_type == 0
+}
+
+/// 判断 H.264 Annex-B 数据是否包含 IDR 帧 (NAL 类型 5)
+pub fn is_h264_key_frame(data: &[u8]) -> bool {
+    data.windows(4)
+        .any(|w| w[..3] == [0, 0, 1] && w[3] & 0x1f == 5)
+}
+
+/// 单个分段文件的写入器
+pub trait SegmentWriter: Send {
+    /// 写入一个编码包 (pts 单位为毫秒，从分段开始计时)
+    fn write(&mut self, data: &[u8], is_key_frame: bool, pts_ms: i64) -> Result<()>;
+
+    /// 写入文件尾并关闭
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// 打开分段文件 (最后一个参数为写入元数据的会话 ID)
+type SegmentOpener = Box<dyn Fn(&Path, RecordCodec, u32, u32, &[String]) -> Result<Box<dyn SegmentWriter>> + Send>;
+
+/// 正在写入的分段
+struct Segment {
+    writer: Box<dyn SegmentWriter>,
+    path: PathBuf,
+    started: Instant,
+    bytes: u64,
+    /// 写入元数据的会话 ID
+    sessions: Vec<String>,
+}
+
+/// 会话录制器
+pub struct SessionRecorder {
+    config: RecordingConfig,
+    open: SegmentOpener,
+    /// 当前编码参数 (编码器切换或分辨率变化时更新)
+    stream: Option<(RecordCodec, u32, u32)>,
+    /// 正在观看的会话 ID
+    sessions: Vec<String>,
+    segment: Option<Segment>,
+    /// 已打开的分段数
+    segment_count: u32,
+    /// 已达到分段条件，等待关键帧切换
+    rotate_pending: bool,
+}
+
+impl SessionRecorder {
+    /// 创建录制器 (需要 h264 feature 提供的 FFmpeg 封装器)
+    pub fn new(config: RecordingConfig) -> Result<Self> {
+        #[cfg(feature = "h264")]
+        {
+            Ok(Self::with_opener(config, Box::new(muxer::open_segment)))
+        }
+
+        #[cfg(not(feature = "h264"))]
+        {
+            let _ = config;
+            Err(anyhow!("录制需要启用 h264 feature (FFmpeg)"))
+        }
+    }
+
+    fn with_opener(config: RecordingConfig, open: SegmentOpener) -> Self {
+        Self {
+            config,
+            open,
+            stream: None,
+            sessions: Vec::new(),
+            segment: None,
+            segment_count: 0,
+            rotate_pending: false,
+        }
+    }
+
+    /// 设置编码参数，与当前分段不同时结束当前分段
+    pub fn set_stream(&mut self, codec: RecordCodec, width: u32, height: u32) {
+        if self.stream == Some((codec, width, height)) {
+            return;
+        }
+        if let Err(e) = self.finish() {
+            tracing::warn!("结束录制分段失败: {}", e);
+        }
+        self.stream = Some((codec, width, height));
+    }
+
+    /// 更新正在观看的会话 ID，有当前分段未记录的会话时在下一个关键帧切换分段
+    pub fn set_sessions<'a>(&mut self, sessions: impl IntoIterator<Item = &'a str>) {
+        let sessions: Vec<&str> = sessions.into_iter().collect();
+        if self.sessions.iter().map(String::as_str).eq(sessions.iter().copied()) {
+            return;
+        }
+        if let Some(segment) = &self.segment {
+            if sessions.iter().any(|id| !segment.sessions.iter().any(|known| known == id)) {
+                self.rotate_pending = true;
+            }
+        }
+        self.sessions = sessions.into_iter().map(str::to_string).collect();
+    }
+
+    /// 写入编码包 (分段开头的非关键帧会被丢弃)
+    pub fn write(&mut self, data: &[u8], is_key_frame: bool) -> Result<()> {
+        let Some((codec, width, height)) = self.stream else {
+            return Err(anyhow!("录制器未设置编码参数"));
+        };
+
+        if is_key_frame && self.rotate_pending {
+            self.finish()?;
+        }
+
+        if self.segment.is_none() {
+            if !is_key_frame {
+                return Ok(());
+            }
+            let path = self.segment_path(self.segment_count + 1, codec);
+            let writer = (self.open)(&path, codec, width, height, &self.sessions)?;
+            self.segment_count += 1;
+            tracing::info!("开始录制: {} (会话: {})", path.display(), self.sessions.join(", "));
+            self.segment = Some(Segment {
+                writer,
+                path,
+                started: Instant::now(),
+                bytes: 0,
+                sessions: self.sessions.clone(),
+            });
+        }
+
+        let Some(segment) = self.segment.as_mut() else {
+            return Ok(());
+        };
+        let pts_ms = segment.started.elapsed().as_millis() as i64;
+        segment.writer.write(data, is_key_frame, pts_ms)?;
+        segment.bytes += data.len() as u64;
+
+        let too_long = self
+            .config
+            .segment_duration
+            .is_some_and(|limit| segment.started.elapsed() >= limit);
+        let too_large = self
+            .config
+            .max_segment_bytes
+            .is_some_and(|limit| segment.bytes >= limit);
+        self.rotate_pending |= too_long || too_large;
+        Ok(())
+    }
+
+    /// 是否需要尽快收到关键帧 (等待分段切换或新分段开始)
+    pub fn wants_key_frame(&self) -> bool {
+        self.rotate_pending || (self.stream.is_some() && self.segment.is_none())
+    }
+
+    /// 结束当前分段 (空闲或退出时调用，之后的关键帧会开启新分段)
+    pub fn finish(&mut self) -> Result<()> {
+        self.rotate_pending = false;
+        if let Some(segment) = self.segment.take() {
+            segment.writer.finish()?;
+            tracing::info!(
+                "录制分段已保存: {} ({:.1} MB, {} 秒)",
+                segment.path.display(),
+                segment.bytes as f64 / (1024.0 * 1024.0),
+                segment.started.elapsed().as_secs()
+            );
+        }
+        Ok(())
+    }
+
+    /// 第 index 个分段的路径
+    ///
+    /// 未启用分段时第一个文件直接使用配置的路径，其余文件追加序号 (name-002.mp4)
+    fn segment_path(&self, index: u32, codec: RecordCodec) -> PathBuf {
+        let path = &self.config.path;
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase)
+            .filter(|ext| codec.supports_extension(ext))
+            .unwrap_or_else(|| codec.default_extension().to_string());
+
+        if index == 1 && !self.config.rotates() {
+            return path.with_extension(ext);
+        }
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("recording");
+        path.with_file_name(format!("{}-{:03}.{}", stem, index, ext))
+    }
+}
+
+impl Drop for SessionRecorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            tracing::warn!("结束录制失败: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// 记录每个分段收到的 (路径, 包数)
+    type Log = Arc<Mutex<Vec<(PathBuf, usize)>>>;
+
+    struct FakeSegment {
+        log: Log,
+        index: usize,
+    }
+
+    impl SegmentWriter for FakeSegment {
+        fn write(&mut self, _data: &[u8], _is_key_frame: bool, _pts_ms: i64) -> Result<()> {
+            self.log.lock().unwrap()[self.index].1 += 1;
+            Ok(())
+        }
+
+        fn finish(self: Box<Self>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn fake_recorder(config: RecordingConfig) -> (SessionRecorder, Log) {
+        let log: Log = Arc::default();
+        let opener_log = log.clone();
+        let recorder = SessionRecorder::with_opener(
+            config,
+            Box::new(move |path: &Path, _, _, _, _: &[String]| {
+                let mut log = opener_log.lock().unwrap();
+                log.push((path.to_path_buf(), 0));
+                Ok(Box::new(FakeSegment { log: opener_log.clone(), index: log.len() - 1 }))
+            }),
+        );
+        (recorder, log)
+    }
+
+    #[test]
+    fn test_size_rotation_waits_for_key_frame() {
+        let config = RecordingConfig {
+            max_segment_bytes: Some(10),
+            ..RecordingConfig::new("/tmp/session.mp4")
+        };
+        let (mut recorder, log) = fake_recorder(config);
+        recorder.set_stream(RecordCodec::H264, 1920, 1080);
+
+        // 首个关键帧之前的数据被丢弃
+        recorder.write(&[0; 4], false).unwrap();
+        assert!(recorder.wants_key_frame());
+        recorder.write(&[0; 4], true).unwrap();
+        recorder.write(&[0; 8], false).unwrap();
+        assert!(recorder.wants_key_frame());
+        // 超过大小后仍写入当前分段，直到关键帧到来
+        recorder.write(&[0; 4], false).unwrap();
+        recorder.write(&[0; 4], true).unwrap();
+
+        let log = log.lock().unwrap();
+        assert_eq!(
+            *log,
+            vec![
+                (PathBuf::from("/tmp/session-001.mp4"), 3),
+                (PathBuf::from("/tmp/session-002.mp4"), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_new_session_starts_new_segment() {
+        let opened: Arc<Mutex<Vec<Vec<String>>>> = Arc::default();
+        let opened_log = opened.clone();
+        let mut recorder = SessionRecorder::with_opener(
+            RecordingConfig::new("/tmp/session.webm"),
+            Box::new(move |_: &Path, _, _, _, sessions: &[String]| {
+                opened_log.lock().unwrap().push(sessions.to_vec());
+                Ok(Box::new(FakeSegment { log: Arc::new(Mutex::new(vec![(PathBuf::new(), 0)])), index: 0 }))
+            }),
+        );
+        recorder.set_stream(RecordCodec::Vp8, 1280, 720);
+        recorder.set_sessions(["a"]);
+        recorder.write(&[0], true).unwrap();
+
+        // 会话离开不切换分段
+        recorder.set_sessions(std::iter::empty());
+        assert!(!recorder.wants_key_frame());
+
+        // 新会话加入后在下一个关键帧切换
+        recorder.set_sessions(["a", "b"]);
+        assert!(recorder.wants_key_frame());
+        recorder.write(&[0], false).unwrap();
+        recorder.write(&[0], true).unwrap();
+        assert_eq!(*opened.lock().unwrap(), vec![vec!["a"], vec!["a", "b"]]);
+    }
+
+    #[test]
+    fn test_stream_change_starts_new_segment() {
+        let (mut recorder, log) = fake_recorder(RecordingConfig::new("/tmp/session.mp4"));
+        recorder.set_stream(RecordCodec::H264, 1920, 1080);
+        recorder.write(&[0], true).unwrap();
+        // VP8 不能放进 MP4，改用 WebM
+        recorder.set_stream(RecordCodec::Vp8, 1280, 720);
+        recorder.write(&[0], true).unwrap();
+
+        let paths: Vec<_> = log.lock().unwrap().iter().map(|(p, _)| p.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("/tmp/session.mp4"), PathBuf::from("/tmp/session-002.webm")]
+        );
+        assert!(is_vp8_key_frame(&[0x50]));
+        assert!(!is_vp8_key_frame(&[0x51]));
+        assert!(is_vp9_key_frame(&[0x82, 0x49, 0x83, 0x42]));
+        assert!(!is_vp9_key_frame(&[0x86, 0x00]));
+        // show_existing_frame
+        assert!(!is_vp9_key_frame(&[0x88]));
+        assert!(!is_vp9_key_frame(&[]));
+        // SPS + PPS + IDR
+        assert!(is_h264_key_frame(&[0, 0, 0, 1, 0x67, 0x42, 0, 0, 1, 0x68, 0xce, 0, 0, 1, 0x65, 0x88]));
+        assert!(!is_h264_key_frame(&[0, 0, 0, 1, 0x41, 0x9a]));
+    }
+}
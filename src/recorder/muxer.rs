@@ -0,0 +1,100 @@
+//! 基于 FFmpeg 的分段封装器
+//!
+//! 只做封装 (remux)，编码包原样写入容器；容器格式由文件扩展名决定
+
+use super::{RecordCodec, SegmentWriter};
+use anyhow::{anyhow, Result};
+use ffmpeg_next as ffmpeg;
+use std::path::Path;
+
+/// 录制器使用的时间基 (毫秒)
+const TIME_BASE: ffmpeg::Rational = ffmpeg::Rational(1, 1000);
+
+/// 一个正在写入的容器文件
+struct FfmpegSegment {
+    output: ffmpeg::format::context::Output,
+    /// 容器实际采用的时间基 (write_header 之后可能被修改)
+    stream_time_base: ffmpeg::Rational,
+}
+
+unsafe impl Send for FfmpegSegment {}
+
+/// 创建分段文件并写入文件头
+pub(super) fn open_segment(
+    path: &Path,
+    codec: RecordCodec,
+    width: u32,
+    height: u32,
+    sessions: &[String],
+) -> Result<Box<dyn SegmentWriter>> {
+    ffmpeg::init()?;
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut output = ffmpeg::format::output(&path)
+        .map_err(|e| anyhow!("无法创建录制文件 {}: {}", path.display(), e))?;
+
+    let codec_id = match codec {
+        RecordCodec::H264 => ffmpeg::codec::Id::H264,
+        RecordCodec::Vp8 => ffmpeg::codec::Id::VP8,
+        RecordCodec::Vp9 => ffmpeg::codec::Id::VP9,
+    };
+
+    {
+        let mut stream = output.add_stream(ffmpeg::encoder::find(ffmpeg::codec::Id::None))?;
+        let mut parameters = ffmpeg::codec::Parameters::new();
+        unsafe {
+            let par = parameters.as_mut_ptr();
+            (*par).codec_type = ffmpeg::ffi::AVMediaType::AVMEDIA_TYPE_VIDEO;
+            (*par).codec_id = codec_id.into();
+            (*par).width = width as i32;
+            (*par).height = height as i32;
+        }
+        stream.set_parameters(parameters);
+        stream.set_time_base(TIME_BASE);
+    }
+
+    // 会话 ID 写入通用的 comment 标签 (MP4 和 WebM/MKV 都支持)
+    if !sessions.is_empty() {
+        let mut metadata = ffmpeg::Dictionary::new();
+        metadata.set("comment", &format!("sscontrol sessions: {}", sessions.join(", ")));
+        output.set_metadata(metadata);
+    }
+
+    output
+        .write_header()
+        .map_err(|e| anyhow!("写入录制文件头失败: {}", e))?;
+    let stream_time_base = output
+        .stream(0)
+        .map(|stream| stream.time_base())
+        .unwrap_or(TIME_BASE);
+
+    Ok(Box::new(FfmpegSegment {
+        output,
+        stream_time_base,
+    }))
+}
+
+impl SegmentWriter for FfmpegSegment {
+    fn write(&mut self, data: &[u8], is_key_frame: bool, pts_ms: i64) -> Result<()> {
+        let mut packet = ffmpeg::Packet::copy(data);
+        packet.set_stream(0);
+        packet.set_pts(Some(pts_ms));
+        packet.set_dts(Some(pts_ms));
+        if is_key_frame {
+            packet.set_flags(ffmpeg::packet::Flags::KEY);
+        }
+        packet.rescale_ts(TIME_BASE, self.stream_time_base);
+        packet
+            .write_interleaved(&mut self.output)
+            .map_err(|e| anyhow!("写入录制数据失败: {}", e))
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.output
+            .write_trailer()
+            .map_err(|e| anyhow!("写入录制文件尾失败: {}", e))
+    }
+}
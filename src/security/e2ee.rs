@@ -0,0 +1,299 @@
+//! 端到端加密
+//!
+//! 局域网 ws:// 直连时没有 TLS 证书，视频帧和输入事件都以明文传输。
+//! 本模块在 WebSocket 消息之上提供与传输无关的加密层:
+//!
+//! 1. 双方交换临时 X25519 公钥 (`e2ee_hello` 文本消息)
+//! 2. 共享密钥经 HKDF-SHA256 派生出两个方向各自的 ChaCha20-Poly1305 密钥
+//! 3. 之后所有消息以二进制帧发送: `[版本 1B][计数器 8B][密文 + 16B 标签]`
+//!
+//! 计数器同时作为 nonce，接收端拒绝不递增的计数器以防重放。
+//! 临时密钥交换本身不认证对端，双方可以比对安全码 (`fingerprint`) 排除中间人
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+/// 协议版本
+pub const E2EE_VERSION: u8 = 1;
+
+/// 加密帧头长度 (版本 + 计数器)
+const HEADER_LEN: usize = 9;
+
+/// 握手角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// 发起方 (客户端)
+    Initiator,
+    /// 响应方 (服务端)
+    Responder,
+}
+
+/// 握手消息
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "e2ee_hello")]
+struct HelloMessage {
+    version: u8,
+    /// Base64 编码的 X25519 公钥
+    public_key: String,
+}
+
+/// 判断文本消息是否为握手消息
+pub fn is_hello(text: &str) -> bool {
+    serde_json::from_str::<HelloMessage>(text).is_ok()
+}
+
+/// 密钥交换 (每个连接使用新的临时密钥)
+pub struct E2eeHandshake {
+    role: Role,
+    secret: EphemeralSecret,
+    public: PublicKey,
+}
+
+impl E2eeHandshake {
+    pub fn new(role: Role) -> Self {
+        let secret = EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let public = PublicKey::from(&secret);
+        Self { role, secret, public }
+    }
+
+    /// 本端握手消息 (JSON 文本)
+    pub fn hello(&self) -> String {
+        let message = HelloMessage {
+            version: E2EE_VERSION,
+            public_key: base64::engine::general_purpose::STANDARD.encode(self.public.as_bytes()),
+        };
+        serde_json::to_string(&message).unwrap_or_default()
+    }
+
+    /// 根据对端握手消息完成密钥交换
+    pub fn complete(self, peer_hello: &str) -> Result<E2eeSession> {
+        let message: HelloMessage = serde_json::from_str(peer_hello)
+            .map_err(|e| anyhow!("无效的 E2EE 握手消息: {}", e))?;
+        if message.version != E2EE_VERSION {
+            return Err(anyhow!("不支持的 E2EE 版本: {}", message.version));
+        }
+
+        let peer_bytes: [u8; 32] = base64::engine::general_purpose::STANDARD
+            .decode(&message.public_key)
+            .map_err(|e| anyhow!("E2EE 公钥解码失败: {}", e))?
+            .try_into()
+            .map_err(|_| anyhow!("E2EE 公钥长度错误"))?;
+        let peer_public = PublicKey::from(peer_bytes);
+
+        let shared = self.secret.diffie_hellman(&peer_public);
+        if !shared.was_contributory() {
+            return Err(anyhow!("E2EE 对端公钥无效 (低阶点)"));
+        }
+
+        // 盐按 (发起方, 响应方) 顺序拼接公钥，双方得到相同的派生结果
+        let (initiator, responder) = match self.role {
+            Role::Initiator => (self.public, peer_public),
+            Role::Responder => (peer_public, self.public),
+        };
+        let mut salt = [0u8; 64];
+        salt[..32].copy_from_slice(initiator.as_bytes());
+        salt[32..].copy_from_slice(responder.as_bytes());
+        let hkdf = Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes());
+
+        let derive = |info: &[u8]| -> Result<[u8; 32]> {
+            let mut key = [0u8; 32];
+            hkdf.expand(info, &mut key)
+                .map_err(|_| anyhow!("E2EE 密钥派生失败"))?;
+            Ok(key)
+        };
+        let initiator_key = derive(b"sscontrol-e2ee initiator")?;
+        let responder_key = derive(b"sscontrol-e2ee responder")?;
+        let sas = derive(b"sscontrol-e2ee sas")?;
+
+        let (send_key, recv_key) = match self.role {
+            Role::Initiator => (initiator_key, responder_key),
+            Role::Responder => (responder_key, initiator_key),
+        };
+
+        let code = u32::from_be_bytes([sas[0], sas[1], sas[2], sas[3]]) % 1_000_000;
+
+        Ok(E2eeSession {
+            sealer: E2eeSealer {
+                cipher: ChaCha20Poly1305::new(Key::from_slice(&send_key)),
+                counter: 0,
+            },
+            opener: E2eeOpener {
+                cipher: ChaCha20Poly1305::new(Key::from_slice(&recv_key)),
+                next_counter: 0,
+            },
+            fingerprint: format!("{:03} {:03}", code / 1000, code % 1000),
+        })
+    }
+}
+
+/// 已建立的加密会话
+pub struct E2eeSession {
+    sealer: E2eeSealer,
+    opener: E2eeOpener,
+    fingerprint: String,
+}
+
+impl E2eeSession {
+    /// 安全码 (双方一致则没有中间人)
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    pub fn seal(&mut self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        self.sealer.seal(plaintext)
+    }
+
+    pub fn open(&mut self, frame: &[u8]) -> Result<Vec<u8>> {
+        self.opener.open(frame)
+    }
+
+    /// 拆分为发送/接收两半 (分别交给发送任务和接收任务)
+    pub fn split(self) -> (E2eeSealer, E2eeOpener) {
+        (self.sealer, self.opener)
+    }
+}
+
+/// 加密端
+pub struct E2eeSealer {
+    cipher: ChaCha20Poly1305,
+    counter: u64,
+}
+
+impl E2eeSealer {
+    /// 加密一条消息，返回完整的加密帧
+    pub fn seal(&mut self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let counter = self.counter;
+        self.counter = counter
+            .checked_add(1)
+            .ok_or_else(|| anyhow!("E2EE 计数器耗尽，需要重新握手"))?;
+
+        let header = frame_header(counter);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce(counter), Payload { msg: plaintext, aad: &header })
+            .map_err(|_| anyhow!("E2EE 加密失败"))?;
+
+        let mut frame = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        frame.extend_from_slice(&header);
+        frame.extend_from_slice(&ciphertext);
+        Ok(frame)
+    }
+}
+
+/// 解密端
+pub struct E2eeOpener {
+    cipher: ChaCha20Poly1305,
+    next_counter: u64,
+}
+
+impl E2eeOpener {
+    /// 解密一个加密帧
+    pub fn open(&mut self, frame: &[u8]) -> Result<Vec<u8>> {
+        if frame.len() < HEADER_LEN {
+            return Err(anyhow!("E2EE 帧过短"));
+        }
+        if frame[0] != E2EE_VERSION {
+            return Err(anyhow!("不支持的 E2EE 帧版本: {}", frame[0]));
+        }
+
+        let counter = u64::from_be_bytes(frame[1..HEADER_LEN].try_into()?);
+        if counter < self.next_counter {
+            return Err(anyhow!("E2EE 帧计数器回退 (可能是重放)"));
+        }
+
+        let plaintext = self
+            .cipher
+            .decrypt(
+                &nonce(counter),
+                Payload { msg: &frame[HEADER_LEN..], aad: &frame[..HEADER_LEN] },
+            )
+            .map_err(|_| anyhow!("E2EE 解密失败 (数据被篡改或密钥不匹配)"))?;
+
+        self.next_counter = counter.saturating_add(1);
+        Ok(plaintext)
+    }
+}
+
+fn frame_header(counter: u64) -> [u8; HEADER_LEN] {
+    let mut header = [0u8; HEADER_LEN];
+    header[0] = E2EE_VERSION;
+    header[1..].copy_from_slice(&counter.to_be_bytes());
+    header
+}
+
+fn nonce(counter: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    *Nonce::from_slice(&nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handshake() -> (E2eeSession, E2eeSession) {
+        let client = E2eeHandshake::new(Role::Initiator);
+        let server = E2eeHandshake::new(Role::Responder);
+        let client_hello = client.hello();
+        let server_hello = server.hello();
+        assert!(is_hello(&client_hello));
+
+        (
+            client.complete(&server_hello).unwrap(),
+            server.complete(&client_hello).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_roundtrip_both_directions() {
+        let (mut client, mut server) = handshake();
+        assert_eq!(client.fingerprint(), server.fingerprint());
+
+        let frame = client.seal(b"{\"type\":\"join\"}").unwrap();
+        assert_eq!(server.open(&frame).unwrap(), b"{\"type\":\"join\"}");
+
+        let frame = server.seal(&[0u8; 1024]).unwrap();
+        assert_eq!(frame.len(), HEADER_LEN + 1024 + 16);
+        assert_eq!(client.open(&frame).unwrap(), vec![0u8; 1024]);
+
+        // 两个方向使用不同密钥，不能把自己发出的帧再解开
+        let frame = client.seal(b"x").unwrap();
+        assert!(client.open(&frame).is_err());
+    }
+
+    #[test]
+    fn test_reject_tamper_and_replay() {
+        let (mut client, mut server) = handshake();
+
+        let first = client.seal(b"first").unwrap();
+        let second = client.seal(b"second").unwrap();
+
+        let mut tampered = second.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(server.open(&tampered).is_err());
+
+        assert_eq!(server.open(&second).unwrap(), b"second");
+        // 计数器回退视为重放
+        assert!(server.open(&first).is_err());
+        assert!(server.open(&second).is_err());
+    }
+
+    #[test]
+    fn test_invalid_hello() {
+        let handshake = E2eeHandshake::new(Role::Initiator);
+        assert!(!is_hello("{\"type\":\"join\",\"room_id\":\"x\"}"));
+        assert!(E2eeHandshake::new(Role::Initiator)
+            .complete("{\"type\":\"e2ee_hello\",\"version\":1,\"public_key\":\"AAAA\"}")
+            .is_err());
+        // 全零公钥是低阶点
+        let zero = base64::engine::general_purpose::STANDARD.encode([0u8; 32]);
+        let hello = format!("{{\"type\":\"e2ee_hello\",\"version\":1,\"public_key\":\"{}\"}}", zero);
+        assert!(handshake.complete(&hello).is_err());
+    }
+}
@@ -1,14 +1,24 @@
 //! 安全模块
 //!
-//! 提供认证、TLS 加密和 token 管理功能
+//! 提供认证、TLS 加密、端到端加密和 token 管理功能
 
 #![allow(dead_code)]
 
 pub mod auth;
+#[cfg(feature = "security")]
+pub mod e2ee;
+#[cfg(feature = "security")]
+pub mod mtls;
+pub mod provider;
 pub mod tls;
 pub mod token;
+#[cfg(feature = "security")]
+pub mod totp;
+#[cfg(feature = "security")]
+pub mod unattended;
 
 pub use auth::ApiKeyAuth;
+pub use provider::{AuthProvider, Credentials};
 pub use tls::TlsConfig;
 pub use token::TokenManager;
 
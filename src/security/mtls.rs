@@ -0,0 +1,173 @@
+//! 双向 TLS (mTLS) 设备证书
+//!
+//! 部署信令服务器时先用 `sscontrol auth issue-cert` 生成设备 CA 和各设备的客户端证书:
+//! 服务器配置 `tls_client_ca` 后只接受由该 CA 签发的客户端，被控端通过
+//! `tls_client_cert`/`tls_client_key` 出示证书，未配发证书的设备无法注册会话
+//!
+//! 目录结构:
+//! - `ca.pem` / `ca.key`: 设备 CA (私钥只应保存在签发证书的机器上)
+//! - `<设备名>.pem` / `<设备名>.key`: 设备客户端证书
+
+use anyhow::{anyhow, Context, Result};
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType, ExtendedKeyUsagePurpose, IsCa,
+    KeyPair, KeyUsagePurpose,
+};
+use std::path::{Path, PathBuf};
+
+/// CA 证书的通用名称
+const CA_COMMON_NAME: &str = "sscontrol device CA";
+
+/// 签发出的设备证书文件
+#[derive(Debug, Clone)]
+pub struct IssuedCert {
+    /// 设备证书 (PEM)
+    pub cert_path: PathBuf,
+    /// 设备私钥 (PEM)
+    pub key_path: PathBuf,
+    /// CA 证书 (信令服务器的 `tls_client_ca`)
+    pub ca_path: PathBuf,
+}
+
+/// 设备 CA
+pub struct DeviceCa {
+    cert: Certificate,
+    key: KeyPair,
+}
+
+impl DeviceCa {
+    /// 生成新的 CA
+    pub fn generate() -> Result<Self> {
+        let key = KeyPair::generate().context("生成 CA 私钥失败")?;
+        let cert = ca_params()?.self_signed(&key).context("生成 CA 证书失败")?;
+        Ok(Self { cert, key })
+    }
+
+    /// 从目录加载 CA，不存在时生成并保存
+    ///
+    /// 签发只需要 CA 的名称和私钥，因此加载时用私钥重建签发者即可，
+    /// 之前签发的证书仍由磁盘上的 `ca.pem` 验证
+    pub fn load_or_create(dir: &Path) -> Result<Self> {
+        let key_path = dir.join("ca.key");
+        if key_path.exists() {
+            let pem = std::fs::read_to_string(&key_path)
+                .with_context(|| format!("读取 CA 私钥 {} 失败", key_path.display()))?;
+            let key = KeyPair::from_pem(&pem).map_err(|e| anyhow!("CA 私钥无效: {}", e))?;
+            let cert = ca_params()?.self_signed(&key)?;
+            return Ok(Self { cert, key });
+        }
+
+        std::fs::create_dir_all(dir).with_context(|| format!("创建证书目录 {} 失败", dir.display()))?;
+        let ca = Self::generate()?;
+        std::fs::write(dir.join("ca.pem"), ca.cert.pem())?;
+        write_private(&key_path, &ca.key.serialize_pem())?;
+        tracing::info!("已生成设备 CA: {}", dir.join("ca.pem").display());
+        Ok(ca)
+    }
+
+    /// CA 证书 (PEM)
+    pub fn cert_pem(&self) -> String {
+        self.cert.pem()
+    }
+
+    /// 签发设备客户端证书，返回 (证书 PEM, 私钥 PEM)
+    pub fn issue(&self, device: &str) -> Result<(String, String)> {
+        let mut params = CertificateParams::new(Vec::<String>::new())?;
+        let mut name = DistinguishedName::new();
+        name.push(DnType::CommonName, device);
+        params.distinguished_name = name;
+        params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+
+        let key = KeyPair::generate()?;
+        let cert = params
+            .signed_by(&key, &self.cert, &self.key)
+            .with_context(|| format!("签发设备 {} 的证书失败", device))?;
+        Ok((cert.pem(), key.serialize_pem()))
+    }
+}
+
+/// 在目录中为设备签发证书 (CA 不存在时一并生成)
+pub fn issue_device_cert(dir: &Path, device: &str) -> Result<IssuedCert> {
+    if device.is_empty() || !device.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.') {
+        return Err(anyhow!("设备名只能包含字母、数字、'-'、'_' 和 '.'"));
+    }
+    if device == "ca" {
+        return Err(anyhow!("设备名 ca 与 CA 文件冲突"));
+    }
+
+    let ca = DeviceCa::load_or_create(dir)?;
+    let (cert, key) = ca.issue(device)?;
+    let issued = IssuedCert {
+        cert_path: dir.join(format!("{}.pem", device)),
+        key_path: dir.join(format!("{}.key", device)),
+        ca_path: dir.join("ca.pem"),
+    };
+    std::fs::write(&issued.cert_path, cert)?;
+    write_private(&issued.key_path, &key)?;
+    Ok(issued)
+}
+
+fn ca_params() -> Result<CertificateParams> {
+    let mut params = CertificateParams::new(Vec::<String>::new())?;
+    let mut name = DistinguishedName::new();
+    name.push(DnType::CommonName, CA_COMMON_NAME);
+    params.distinguished_name = name;
+    params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+    params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+    Ok(params)
+}
+
+/// 写入私钥文件 (Unix 上仅当前用户可读)
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    std::fs::write(path, contents).with_context(|| format!("写入 {} 失败", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::TlsConfig;
+    use std::path::PathBuf;
+
+    /// 测试结束时 (包括断言失败) 删除临时目录
+    struct TempDir(PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_issue_and_reload_ca() {
+        // 进程级默认 CryptoProvider 只在 main() 中安装，测试需要自行安装
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let dir = std::env::temp_dir().join(format!("sscontrol-mtls-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let _guard = TempDir(dir.clone());
+
+        let first = issue_device_cert(&dir, "office-pc").unwrap();
+        let ca_pem = std::fs::read_to_string(&first.ca_path).unwrap();
+        assert!(std::fs::read_to_string(&first.cert_path).unwrap().contains("BEGIN CERTIFICATE"));
+
+        // 第二台设备沿用同一个 CA
+        let second = issue_device_cert(&dir, "laptop").unwrap();
+        assert_eq!(std::fs::read_to_string(&second.ca_path).unwrap(), ca_pem);
+        assert!(issue_device_cert(&dir, "../evil").is_err());
+        assert!(issue_device_cert(&dir, "ca").is_err());
+
+        // 签发的证书可以作为客户端身份，CA 可以作为服务器的客户端信任根
+        let identity = TlsConfig::new(
+            second.cert_path.display().to_string(),
+            second.key_path.display().to_string(),
+        );
+        assert!(identity.client_config_with_identity().is_ok());
+        assert!(TlsConfig::server_client_verifier(&second.ca_path.display().to_string()).is_ok());
+    }
+}
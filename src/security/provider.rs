@@ -0,0 +1,633 @@
+//! 可插拔认证提供者
+//!
+//! 信令服务器通过 [`AuthProvider`] 校验连接凭据，支持:
+//! - 共享 API Key
+//! - OIDC Bearer Token (JWKS 验签 + issuer/audience 校验，需要 `sso` feature)
+//! - LDAP 用户名/密码 (简单绑定，需要 `sso` feature)
+//!
+//! 认证成功后根据声明或组成员关系映射为 [`Role`]，角色决定会话权限的上限
+
+use super::auth::ApiKeyAuth;
+use crate::config::{AuthProviderConfig, RoleMappingConfig, SecurityConfig};
+use crate::signaling::SessionPermissions;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// 用户角色 (按权限从低到高排序)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    /// 仅观看
+    Viewer,
+    /// 观看 + 键鼠控制、剪贴板和文件传输 (不含电源操作)
+    Operator,
+    /// 全部权限
+    Admin,
+}
+
+impl FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "viewer" => Ok(Role::Viewer),
+            "operator" => Ok(Role::Operator),
+            "admin" => Ok(Role::Admin),
+            other => Err(anyhow!("未知角色: {}", other)),
+        }
+    }
+}
+
+impl Role {
+    /// 该角色在会话中的权限上限 (Host 放行时分配的权限不会超过它)
+    pub fn permissions(self) -> SessionPermissions {
+        match self {
+            Role::Viewer => SessionPermissions::view_only(),
+            Role::Operator => SessionPermissions {
+                power: false,
+                ..SessionPermissions::full()
+            },
+            Role::Admin => SessionPermissions::full(),
+        }
+    }
+}
+
+/// 认证通过的身份
+#[derive(Debug, Clone)]
+pub struct Identity {
+    /// 用户标识 (OIDC sub / LDAP 用户名)
+    pub subject: String,
+    /// 映射后的角色
+    pub role: Role,
+    /// 认证提供者名称
+    pub provider: &'static str,
+}
+
+/// 连接携带的凭据
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credentials {
+    /// Bearer Token (API Key 或 OIDC ID/Access Token)
+    Bearer(String),
+    /// 用户名/密码 (HTTP Basic)
+    Basic { username: String, password: String },
+}
+
+impl Credentials {
+    /// 解析 HTTP `Authorization` 头
+    pub fn from_authorization(header: &str) -> Option<Self> {
+        let (scheme, value) = header.trim().split_once(' ')?;
+        let value = value.trim();
+
+        if scheme.eq_ignore_ascii_case("bearer") {
+            return Some(Credentials::Bearer(value.to_string()));
+        }
+
+        if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = base64::engine::general_purpose::STANDARD.decode(value).ok()?;
+            let decoded = String::from_utf8(decoded).ok()?;
+            let (username, password) = decoded.split_once(':')?;
+            return Some(Credentials::Basic {
+                username: username.to_string(),
+                password: password.to_string(),
+            });
+        }
+
+        None
+    }
+}
+
+/// 认证提供者
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    /// 提供者名称 (用于日志)
+    fn name(&self) -> &'static str;
+
+    /// 校验凭据并返回身份
+    async fn authenticate(&self, credentials: &Credentials) -> Result<Identity>;
+}
+
+/// 声明值/组 → 角色映射
+#[derive(Debug, Clone, Default)]
+pub struct RoleMapper {
+    mappings: HashMap<String, Role>,
+    default_role: Option<Role>,
+}
+
+impl RoleMapper {
+    /// 从配置创建
+    pub fn from_config(config: &RoleMappingConfig) -> Result<Self> {
+        let mappings = config
+            .mappings
+            .iter()
+            .map(|(key, role)| Ok((key.clone(), role.parse()?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+        let default_role = config.default_role.as_deref().map(str::parse).transpose()?;
+
+        Ok(Self { mappings, default_role })
+    }
+
+    /// 取所有匹配值中权限最高的角色，无匹配时返回默认角色
+    pub fn resolve<'a>(&self, values: impl IntoIterator<Item = &'a str>) -> Option<Role> {
+        values
+            .into_iter()
+            .filter_map(|value| self.mappings.get(value).copied())
+            .max()
+            .or(self.default_role)
+    }
+}
+
+/// 共享 API Key 认证提供者
+///
+/// 持有 API Key 即视为管理员
+pub struct ApiKeyProvider {
+    auth: ApiKeyAuth,
+}
+
+impl ApiKeyProvider {
+    pub fn new(auth: ApiKeyAuth) -> Self {
+        Self { auth }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for ApiKeyProvider {
+    fn name(&self) -> &'static str {
+        "api_key"
+    }
+
+    async fn authenticate(&self, credentials: &Credentials) -> Result<Identity> {
+        match credentials {
+            Credentials::Bearer(key) if self.auth.verify(key) => Ok(Identity {
+                subject: "api_key".to_string(),
+                role: Role::Admin,
+                provider: self.name(),
+            }),
+            _ => Err(anyhow!("API Key 无效")),
+        }
+    }
+}
+
+/// 根据安全配置创建认证提供者
+///
+/// 未配置 `security.auth` 时返回 `None` (不认证)
+pub fn create_provider(config: &SecurityConfig) -> Result<Option<Arc<dyn AuthProvider>>> {
+    let Some(auth) = &config.auth else {
+        return Ok(None);
+    };
+
+    match auth {
+        AuthProviderConfig::ApiKey => {
+            let auth = match &config.api_key {
+                Some(key) => ApiKeyAuth::new(key.clone()),
+                None => ApiKeyAuth::from_env()?,
+            };
+            Ok(Some(Arc::new(ApiKeyProvider::new(auth))))
+        }
+        #[cfg(feature = "sso")]
+        AuthProviderConfig::Oidc(oidc) => Ok(Some(Arc::new(oidc::OidcProvider::new(oidc.clone())?))),
+        #[cfg(feature = "sso")]
+        AuthProviderConfig::Ldap(ldap) => Ok(Some(Arc::new(ldap::LdapProvider::new(ldap.clone())?))),
+        #[cfg(not(feature = "sso"))]
+        AuthProviderConfig::Oidc(_) | AuthProviderConfig::Ldap(_) => {
+            Err(anyhow!("OIDC/LDAP 认证需要启用 sso feature"))
+        }
+    }
+}
+
+/// OIDC Bearer Token 认证
+#[cfg(feature = "sso")]
+pub mod oidc {
+    use super::{AuthProvider, Credentials, Identity, RoleMapper};
+    use crate::config::OidcConfig;
+    use anyhow::{anyhow, Result};
+    use async_trait::async_trait;
+    use jsonwebtoken::jwk::{Jwk, JwkSet};
+    use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+    use std::str::FromStr;
+    use std::time::{Duration, Instant};
+    use tokio::sync::RwLock;
+
+    /// OIDC 认证提供者
+    ///
+    /// JWKS 按 `jwks_cache_secs` 缓存，遇到未知 `kid` 时强制刷新一次以支持密钥轮换
+    pub struct OidcProvider {
+        config: OidcConfig,
+        roles: RoleMapper,
+        /// JWKS 中的密钥未声明 `alg` 时接受的签名算法
+        algorithms: Vec<Algorithm>,
+        http: reqwest::Client,
+        jwks: RwLock<Option<(JwkSet, Instant)>>,
+    }
+
+    impl OidcProvider {
+        pub fn new(config: OidcConfig) -> Result<Self> {
+            let roles = RoleMapper::from_config(&config.roles)?;
+            let algorithms = config
+                .algorithms
+                .iter()
+                .map(|name| parse_algorithm(name))
+                .collect::<Result<Vec<_>>>()?;
+            if algorithms.is_empty() {
+                return Err(anyhow!("OIDC 至少需要配置一种签名算法"));
+            }
+            let http = reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()?;
+
+            Ok(Self {
+                config,
+                roles,
+                algorithms,
+                http,
+                jwks: RwLock::new(None),
+            })
+        }
+
+        /// 获取 JWKS 地址 (未配置时通过 discovery 文档获取)
+        async fn jwks_uri(&self) -> Result<String> {
+            if let Some(uri) = &self.config.jwks_uri {
+                return Ok(uri.clone());
+            }
+
+            let discovery_url = format!(
+                "{}/.well-known/openid-configuration",
+                self.config.issuer.trim_end_matches('/')
+            );
+            let document: serde_json::Value = self.http.get(&discovery_url).send().await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            document["jwks_uri"]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("discovery 文档缺少 jwks_uri: {}", discovery_url))
+        }
+
+        /// 获取 JWKS (优先使用缓存)
+        async fn jwks(&self, force_refresh: bool) -> Result<JwkSet> {
+            let max_age = Duration::from_secs(self.config.jwks_cache_secs);
+            if !force_refresh {
+                if let Some((jwks, fetched_at)) = self.jwks.read().await.as_ref() {
+                    if fetched_at.elapsed() < max_age {
+                        return Ok(jwks.clone());
+                    }
+                }
+            }
+
+            let uri = self.jwks_uri().await?;
+            let jwks: JwkSet = self.http.get(&uri).send().await?
+                .error_for_status()?
+                .json()
+                .await?;
+            tracing::debug!("已获取 JWKS: {} ({} 个密钥)", uri, jwks.keys.len());
+
+            *self.jwks.write().await = Some((jwks.clone(), Instant::now()));
+            Ok(jwks)
+        }
+
+        /// 密钥允许的签名算法: 优先使用 JWKS 中声明的 `alg`，否则使用配置的算法
+        fn algorithms_for(&self, jwk: &Jwk) -> Result<Vec<Algorithm>> {
+            match &jwk.common.key_algorithm {
+                Some(alg) => Ok(vec![parse_algorithm(&alg.to_string())?]),
+                None => Ok(self.algorithms.clone()),
+            }
+        }
+
+        /// 验证 JWT 并返回声明
+        ///
+        /// 签名算法由密钥或配置决定，令牌头部的 `alg` 只用于比对
+        async fn verify(&self, token: &str) -> Result<serde_json::Value> {
+            let header = jsonwebtoken::decode_header(token)?;
+
+            let kid = header.kid.ok_or_else(|| anyhow!("JWT 缺少 kid"))?;
+            let jwks = self.jwks(false).await?;
+            let jwk = match jwks.find(&kid) {
+                Some(jwk) => jwk.clone(),
+                None => self.jwks(true).await?
+                    .find(&kid)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("JWKS 中找不到密钥: {}", kid))?,
+            };
+
+            let algorithms = self.algorithms_for(&jwk)?;
+            if !algorithms.contains(&header.alg) {
+                return Err(anyhow!("密钥 {} 不接受签名算法 {:?}", kid, header.alg));
+            }
+
+            let key = DecodingKey::from_jwk(&jwk)?;
+            let mut validation = Validation::new(header.alg);
+            validation.algorithms = algorithms;
+            validation.set_audience(&[&self.config.audience]);
+            validation.set_issuer(&[&self.config.issuer]);
+
+            let data = jsonwebtoken::decode::<serde_json::Value>(token, &key, &validation)?;
+            Ok(data.claims)
+        }
+    }
+
+    #[async_trait]
+    impl AuthProvider for OidcProvider {
+        fn name(&self) -> &'static str {
+            "oidc"
+        }
+
+        async fn authenticate(&self, credentials: &Credentials) -> Result<Identity> {
+            let Credentials::Bearer(token) = credentials else {
+                return Err(anyhow!("OIDC 认证需要 Bearer Token"));
+            };
+
+            let claims = self.verify(token).await?;
+            let subject = claims["sub"]
+                .as_str()
+                .filter(|sub| !sub.is_empty())
+                .ok_or_else(|| anyhow!("JWT 缺少 sub 声明"))?
+                .to_string();
+
+            // 角色声明可以是字符串或字符串数组
+            let claim = &claims[self.config.role_claim.as_str()];
+            let values: Vec<&str> = match claim {
+                serde_json::Value::String(s) => vec![s.as_str()],
+                serde_json::Value::Array(items) => items.iter().filter_map(|v| v.as_str()).collect(),
+                _ => Vec::new(),
+            };
+
+            let role = self.roles.resolve(values)
+                .ok_or_else(|| anyhow!("用户 {} 没有可映射的角色", subject))?;
+
+            Ok(Identity { subject, role, provider: self.name() })
+        }
+    }
+
+    /// 解析签名算法名称 (只接受非对称签名，防止用公钥作为 HMAC 密钥伪造)
+    fn parse_algorithm(name: &str) -> Result<Algorithm> {
+        let algorithm = Algorithm::from_str(name).map_err(|_| anyhow!("未知的签名算法: {}", name))?;
+        if matches!(algorithm, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            return Err(anyhow!("不支持的签名算法: {}", name));
+        }
+        Ok(algorithm)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::security::provider::Role;
+        use crate::config::RoleMappingConfig;
+        use base64::Engine;
+        use jsonwebtoken::{EncodingKey, Header};
+        use serde_json::json;
+
+        const ISSUER: &str = "https://login.example.com";
+
+        /// 使用本地生成的 P-256 密钥 (预先填充 JWKS 缓存，不访问网络)
+        fn local_provider(key_alg: Option<&str>, algorithms: &[&str]) -> (OidcProvider, EncodingKey) {
+            let key_pair = rcgen::KeyPair::generate().unwrap();
+            let point = key_pair.public_key_raw();
+            let b64 = |bytes: &[u8]| base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes);
+            let mut jwk = json!({
+                "kty": "EC",
+                "crv": "P-256",
+                "kid": "k1",
+                "x": b64(&point[1..33]),
+                "y": b64(&point[33..65]),
+            });
+            if let Some(alg) = key_alg {
+                jwk["alg"] = json!(alg);
+            }
+            let jwks: JwkSet = serde_json::from_value(json!({ "keys": [jwk] })).unwrap();
+
+            let config = OidcConfig {
+                issuer: ISSUER.to_string(),
+                audience: "sscontrol".to_string(),
+                jwks_uri: None,
+                jwks_cache_secs: 3600,
+                role_claim: "groups".to_string(),
+                algorithms: algorithms.iter().map(|alg| alg.to_string()).collect(),
+                roles: RoleMappingConfig {
+                    default_role: Some("operator".to_string()),
+                    ..Default::default()
+                },
+            };
+            let provider = OidcProvider::new(config).unwrap();
+            *provider.jwks.try_write().unwrap() = Some((jwks, Instant::now()));
+            let key = EncodingKey::from_ec_pem(key_pair.serialize_pem().as_bytes()).unwrap();
+            (provider, key)
+        }
+
+        fn token(key: &EncodingKey, alg: Algorithm, claims: serde_json::Value) -> Credentials {
+            let mut header = Header::new(alg);
+            header.kid = Some("k1".to_string());
+            Credentials::Bearer(jsonwebtoken::encode(&header, &claims, key).unwrap())
+        }
+
+        fn claims() -> serde_json::Value {
+            json!({ "sub": "alice", "iss": ISSUER, "aud": "sscontrol", "exp": 4102444800u64 })
+        }
+
+        #[tokio::test]
+        async fn test_authenticate() {
+            let (provider, key) = local_provider(None, &["ES256"]);
+            let identity = provider.authenticate(&token(&key, Algorithm::ES256, claims())).await.unwrap();
+            assert_eq!(identity.subject, "alice");
+            assert_eq!(identity.role, Role::Operator);
+
+            // 缺少 sub 或 sub 为空
+            let mut no_sub = claims();
+            no_sub.as_object_mut().unwrap().remove("sub");
+            assert!(provider.authenticate(&token(&key, Algorithm::ES256, no_sub)).await.is_err());
+            let mut empty_sub = claims();
+            empty_sub["sub"] = json!("");
+            assert!(provider.authenticate(&token(&key, Algorithm::ES256, empty_sub)).await.is_err());
+        }
+
+        #[tokio::test]
+        async fn test_algorithm_is_pinned() {
+            // 默认只接受 RS256: 令牌头部声明的 ES256 不被采信
+            let (provider, key) = local_provider(None, &["RS256"]);
+            assert!(provider.authenticate(&token(&key, Algorithm::ES256, claims())).await.is_err());
+
+            // 密钥声明的 alg 优先于配置
+            let (provider, key) = local_provider(Some("ES256"), &["RS256"]);
+            assert!(provider.authenticate(&token(&key, Algorithm::ES256, claims())).await.is_ok());
+
+            // 头部声明为 HMAC 的令牌
+            let forged = token(&EncodingKey::from_secret(b"public key"), Algorithm::HS256, claims());
+            assert!(provider.authenticate(&forged).await.is_err());
+        }
+
+        #[test]
+        fn test_rejects_symmetric_algorithms() {
+            assert_eq!(parse_algorithm("ES256").unwrap(), Algorithm::ES256);
+            assert!(parse_algorithm("HS256").is_err());
+            assert!(parse_algorithm("none").is_err());
+        }
+    }
+}
+
+/// LDAP 用户名/密码认证
+#[cfg(feature = "sso")]
+pub mod ldap {
+    use super::{AuthProvider, Credentials, Identity, RoleMapper};
+    use crate::config::LdapConfig;
+    use anyhow::{anyhow, Result};
+    use async_trait::async_trait;
+    use ldap3::{LdapConnAsync, Scope, SearchEntry};
+
+    /// LDAP 认证提供者
+    ///
+    /// 以用户自身 DN 简单绑定，成功后读取组成员属性映射角色
+    pub struct LdapProvider {
+        config: LdapConfig,
+        roles: RoleMapper,
+    }
+
+    impl LdapProvider {
+        pub fn new(config: LdapConfig) -> Result<Self> {
+            if !config.user_dn_template.contains("{username}") {
+                return Err(anyhow!("user_dn_template 必须包含 {{username}}"));
+            }
+            let roles = RoleMapper::from_config(&config.roles)?;
+            Ok(Self { config, roles })
+        }
+    }
+
+    /// 组 DN 的首个 RDN 值 (cn=admins,ou=groups,... → admins)
+    fn group_cn(dn: &str) -> Option<&str> {
+        dn.split(',').next()?.split_once('=').map(|(_, value)| value.trim())
+    }
+
+    #[async_trait]
+    impl AuthProvider for LdapProvider {
+        fn name(&self) -> &'static str {
+            "ldap"
+        }
+
+        async fn authenticate(&self, credentials: &Credentials) -> Result<Identity> {
+            let Credentials::Basic { username, password } = credentials else {
+                return Err(anyhow!("LDAP 认证需要用户名和密码"));
+            };
+
+            // 空密码会被服务器当作匿名绑定而"成功"
+            if username.is_empty() || password.is_empty() {
+                return Err(anyhow!("用户名或密码为空"));
+            }
+
+            let dn = self.config.user_dn_template.replace("{username}", &ldap3::dn_escape(username));
+
+            let (conn, mut ldap) = LdapConnAsync::new(&self.config.url).await?;
+            ldap3::drive!(conn);
+
+            ldap.simple_bind(&dn, password).await?
+                .success()
+                .map_err(|_| anyhow!("LDAP 认证失败: {}", username))?;
+
+            let (entries, _) = ldap
+                .search(&dn, Scope::Base, "(objectClass=*)", vec![self.config.group_attribute.as_str()])
+                .await?
+                .success()?;
+            let _ = ldap.unbind().await;
+
+            let groups: Vec<String> = entries
+                .into_iter()
+                .map(SearchEntry::construct)
+                .flat_map(|entry| entry.attrs.get(&self.config.group_attribute).cloned().unwrap_or_default())
+                .collect();
+
+            // 映射同时匹配完整 DN 和 CN
+            let role = self.roles
+                .resolve(groups.iter().flat_map(|dn| [Some(dn.as_str()), group_cn(dn)]).flatten())
+                .ok_or_else(|| anyhow!("用户 {} 没有可映射的角色", username))?;
+
+            Ok(Identity {
+                subject: username.clone(),
+                role,
+                provider: self.name(),
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_group_cn() {
+            assert_eq!(group_cn("cn=admins,ou=groups,dc=example,dc=com"), Some("admins"));
+            assert_eq!(group_cn("admins"), None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_credentials() {
+        assert_eq!(
+            Credentials::from_authorization("Bearer abc.def"),
+            Some(Credentials::Bearer("abc.def".to_string()))
+        );
+
+        // "alice:s3cret"
+        assert_eq!(
+            Credentials::from_authorization("Basic YWxpY2U6czNjcmV0"),
+            Some(Credentials::Basic {
+                username: "alice".to_string(),
+                password: "s3cret".to_string(),
+            })
+        );
+
+        assert_eq!(Credentials::from_authorization("Digest xyz"), None);
+        assert_eq!(Credentials::from_authorization("Basic !!!"), None);
+    }
+
+    #[test]
+    fn test_role_mapper() {
+        let mut config = RoleMappingConfig::default();
+        config.mappings.insert("support".to_string(), "operator".to_string());
+        config.mappings.insert("it-admins".to_string(), "admin".to_string());
+
+        let mapper = RoleMapper::from_config(&config).unwrap();
+        assert_eq!(mapper.resolve(["support", "it-admins"]), Some(Role::Admin));
+        assert_eq!(mapper.resolve(["support"]), Some(Role::Operator));
+        assert_eq!(mapper.resolve(["guests"]), None);
+
+        config.default_role = Some("viewer".to_string());
+        let mapper = RoleMapper::from_config(&config).unwrap();
+        assert_eq!(mapper.resolve(["guests"]), Some(Role::Viewer));
+
+        config.default_role = Some("root".to_string());
+        assert!(RoleMapper::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_role_permissions() {
+        assert!(Role::Viewer.permissions().is_view_only());
+        assert!(Role::Operator.permissions().input);
+        assert!(!Role::Operator.permissions().power);
+        assert_eq!(Role::Admin.permissions(), SessionPermissions::full());
+    }
+
+    #[tokio::test]
+    async fn test_api_key_provider() {
+        let provider = ApiKeyProvider::new(ApiKeyAuth::new("secret".to_string()));
+
+        let identity = provider
+            .authenticate(&Credentials::Bearer("secret".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(identity.role, Role::Admin);
+
+        assert!(provider.authenticate(&Credentials::Bearer("wrong".to_string())).await.is_err());
+    }
+
+    #[test]
+    fn test_create_provider_disabled_by_default() {
+        let config = SecurityConfig::default();
+        assert!(create_provider(&config).unwrap().is_none());
+    }
+}
@@ -1,6 +1,8 @@
 //! TLS 配置
 //!
 //! 提供 TLS 证书配置和验证功能
+//!
+//! 配置客户端 CA 后服务器要求双向 TLS (mTLS)，设备证书由 [`super::mtls`] 签发
 
 use anyhow::{anyhow, Result};
 use std::path::Path;
@@ -10,6 +12,8 @@ use std::path::Path;
 pub struct TlsConfig {
     pub cert_path: String,
     pub key_path: String,
+    /// 客户端 CA 证书路径 (设置后服务器只接受该 CA 签发的客户端证书)
+    pub client_ca_path: Option<String>,
 }
 
 impl TlsConfig {
@@ -18,15 +22,23 @@ impl TlsConfig {
     /// 环境变量:
     /// - `SSCONTROL_TLS_CERT`: 证书文件路径
     /// - `SSCONTROL_TLS_KEY`: 私钥文件路径
+    /// - `SSCONTROL_TLS_CLIENT_CA`: 客户端 CA 证书路径 (可选，启用 mTLS)
     pub fn from_env() -> Option<Self> {
         let cert_path = std::env::var("SSCONTROL_TLS_CERT").ok()?;
         let key_path = std::env::var("SSCONTROL_TLS_KEY").ok()?;
-        Some(Self { cert_path, key_path })
+        let client_ca_path = std::env::var("SSCONTROL_TLS_CLIENT_CA").ok();
+        Some(Self { cert_path, key_path, client_ca_path })
     }
 
     /// 从文件路径创建
     pub fn new(cert_path: String, key_path: String) -> Self {
-        Self { cert_path, key_path }
+        Self { cert_path, key_path, client_ca_path: None }
+    }
+
+    /// 要求客户端出示由指定 CA 签发的证书
+    pub fn with_client_ca(mut self, ca_path: String) -> Self {
+        self.client_ca_path = Some(ca_path);
+        self
     }
 
     /// 验证证书文件存在
@@ -37,6 +49,11 @@ impl TlsConfig {
         if !Path::new(&self.key_path).exists() {
             return Err(anyhow!("私钥文件不存在: {}", self.key_path));
         }
+        if let Some(ca_path) = &self.client_ca_path {
+            if !Path::new(ca_path).exists() {
+                return Err(anyhow!("客户端 CA 证书不存在: {}", ca_path));
+            }
+        }
         Ok(())
     }
 
@@ -45,50 +62,74 @@ impl TlsConfig {
     /// 当 security feature 启用时可用
     #[cfg(feature = "security")]
     pub fn create_client_connector(&self) -> Result<tokio_rustls::TlsConnector> {
+        Self::client_connector()
+    }
+
+    /// 创建仅信任系统根证书的 TLS 连接器 (客户端，不需要本地证书)
+    ///
+    /// 当 security feature 启用时可用
+    #[cfg(feature = "security")]
+    pub fn client_connector() -> Result<tokio_rustls::TlsConnector> {
         use tokio_rustls::rustls::ClientConfig;
-        use tokio_rustls::rustls::RootCertStore;
-
-        // 使用系统根证书
-        let mut root_store = RootCertStore::empty();
-        // 添加 Mozilla 根证书
-        let certs = rustls_native_certs::load_native_certs()
-            .map_err(|e| anyhow!("加载系统根证书失败: {:?}", e))?;
-        for cert in certs {
-            root_store.add(cert).ok();
-        }
 
         let config = ClientConfig::builder()
-            .with_root_certificates(root_store)
+            .with_root_certificates(native_root_store()?)
             .with_no_client_auth();
 
         Ok(tokio_rustls::TlsConnector::from(std::sync::Arc::new(config)))
     }
 
+    /// 以本配置的证书和私钥作为客户端身份 (mTLS)，服务器证书仍用系统根证书验证
+    ///
+    /// 当 security feature 启用时可用
+    #[cfg(feature = "security")]
+    pub fn client_config_with_identity(&self) -> Result<std::sync::Arc<tokio_rustls::rustls::ClientConfig>> {
+        use tokio_rustls::rustls::ClientConfig;
+
+        let config = ClientConfig::builder()
+            .with_root_certificates(native_root_store()?)
+            .with_client_auth_cert(load_certs(&self.cert_path)?, load_private_key(&self.key_path)?)
+            .map_err(|e| anyhow!("客户端证书无效: {:?}", e))?;
+
+        Ok(std::sync::Arc::new(config))
+    }
+
+    /// 只接受指定 CA 签发的客户端证书的验证器
+    ///
+    /// 当 security feature 启用时可用
+    #[cfg(feature = "security")]
+    pub fn server_client_verifier(ca_path: &str) -> Result<std::sync::Arc<dyn rustls::server::danger::ClientCertVerifier>> {
+        use rustls::server::WebPkiClientVerifier;
+        use rustls::RootCertStore;
+
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(ca_path)? {
+            roots
+                .add(cert)
+                .map_err(|e| anyhow!("客户端 CA 证书无效: {:?}", e))?;
+        }
+
+        WebPkiClientVerifier::builder(std::sync::Arc::new(roots))
+            .build()
+            .map_err(|e| anyhow!("创建客户端证书验证器失败: {:?}", e))
+    }
+
     /// 创建 TLS 接受器 (服务器)
     ///
     /// 当 security feature 启用时可用
     #[cfg(feature = "security")]
     pub fn create_server_config(&self) -> Result<std::sync::Arc<rustls::ServerConfig>> {
         use rustls::ServerConfig;
-        use rustls_pemfile::{certs, private_key};
-        use std::io::BufReader;
-
-        // 读取证书
-        let cert_file = std::fs::File::open(&self.cert_path)?;
-        let mut cert_reader = BufReader::new(cert_file);
-        let cert_chain = certs(&mut cert_reader)
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| anyhow!("读取证书失败: {:?}", e))?;
-
-        // 读取私钥
-        let key_file = std::fs::File::open(&self.key_path)?;
-        let mut key_reader = BufReader::new(key_file);
-        let key = private_key(&mut key_reader)
-            .map_err(|e| anyhow!("读取私钥失败: {:?}", e))?
-            .ok_or_else(|| anyhow!("未找到私钥"))?;
-
-        let config = ServerConfig::builder()
-            .with_no_client_auth()
+
+        let cert_chain = load_certs(&self.cert_path)?;
+        let key = load_private_key(&self.key_path)?;
+
+        // 配置了客户端 CA 时要求双向 TLS
+        let builder = match &self.client_ca_path {
+            Some(ca_path) => ServerConfig::builder().with_client_cert_verifier(Self::server_client_verifier(ca_path)?),
+            None => ServerConfig::builder().with_no_client_auth(),
+        };
+        let config = builder
             .with_single_cert(cert_chain, key)
             .map_err(|e| anyhow!("创建服务器配置失败: {:?}", e))?;
 
@@ -96,6 +137,40 @@ impl TlsConfig {
     }
 }
 
+/// 加载系统根证书
+#[cfg(feature = "security")]
+fn native_root_store() -> Result<tokio_rustls::rustls::RootCertStore> {
+    let mut root_store = tokio_rustls::rustls::RootCertStore::empty();
+    let certs = rustls_native_certs::load_native_certs()
+        .map_err(|e| anyhow!("加载系统根证书失败: {:?}", e))?;
+    for cert in certs {
+        root_store.add(cert).ok();
+    }
+    Ok(root_store)
+}
+
+/// 读取 PEM 证书链
+#[cfg(feature = "security")]
+fn load_certs(path: &str) -> Result<Vec<rustls::pki_types::CertificateDer<'static>>> {
+    let file = std::fs::File::open(path).map_err(|e| anyhow!("打开证书 {} 失败: {}", path, e))?;
+    let certs = rustls_pemfile::certs(&mut std::io::BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("读取证书失败: {:?}", e))?;
+    if certs.is_empty() {
+        return Err(anyhow!("{} 中没有证书", path));
+    }
+    Ok(certs)
+}
+
+/// 读取 PEM 私钥
+#[cfg(feature = "security")]
+fn load_private_key(path: &str) -> Result<rustls::pki_types::PrivateKeyDer<'static>> {
+    let file = std::fs::File::open(path).map_err(|e| anyhow!("打开私钥 {} 失败: {}", path, e))?;
+    rustls_pemfile::private_key(&mut std::io::BufReader::new(file))
+        .map_err(|e| anyhow!("读取私钥失败: {:?}", e))?
+        .ok_or_else(|| anyhow!("未找到私钥"))
+}
+
 /// 从环境变量或默认路径加载 TLS 配置
 impl Default for TlsConfig {
     fn default() -> Self {
@@ -122,12 +197,17 @@ mod tests {
         let config = TlsConfig::default();
         assert_eq!(config.cert_path, "cert.pem");
         assert_eq!(config.key_path, "key.pem");
+        assert!(config.client_ca_path.is_none());
     }
 
     #[test]
     fn test_tls_config_validate_nonexistent() {
         let config = TlsConfig::new("/nonexistent/cert.pem".to_string(), "/nonexistent/key.pem".to_string());
         assert!(config.validate().is_err());
+
+        let config = TlsConfig::new("cert.pem".to_string(), "key.pem".to_string())
+            .with_client_ca("/nonexistent/ca.pem".to_string());
+        assert_eq!(config.client_ca_path.as_deref(), Some("/nonexistent/ca.pem"));
     }
 
     #[test]
@@ -135,6 +215,7 @@ mod tests {
         // 环境变量未设置时应返回 None
         std::env::remove_var("SSCONTROL_TLS_CERT");
         std::env::remove_var("SSCONTROL_TLS_KEY");
+        std::env::remove_var("SSCONTROL_TLS_CLIENT_CA");
         assert!(TlsConfig::from_env().is_none());
 
         // 环境变量设置后应返回 Some
@@ -145,6 +226,7 @@ mod tests {
         let config = config.unwrap();
         assert_eq!(config.cert_path, "/path/to/cert.pem");
         assert_eq!(config.key_path, "/path/to/key.pem");
+        assert!(config.client_ca_path.is_none());
 
         // 清理环境变量
         std::env::remove_var("SSCONTROL_TLS_CERT");
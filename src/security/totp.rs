@@ -0,0 +1,224 @@
+//! TOTP 双因素验证 (RFC 6238)
+//!
+//! `sscontrol auth enroll` 生成共享密钥写入配置 (`host.totp_secret`)，并打印
+//! `otpauth://` URI 和二维码供身份验证器 App 扫描。配置了密钥后，Viewer 在信令握手中
+//! 除 PIN 外还必须提交当前的 6 位验证码，通过后才会处理其 Offer
+//!
+//! - HMAC-SHA1，30 秒一个时间步，允许前后各一个时间步的时钟偏差
+//! - 同一时间步的验证码只能使用一次 (防重放)
+//! - 失败计数和锁定规则与 PIN 相同
+
+use crate::signaling::pin::{constant_time_eq, AttemptLimiter, PinConfig, PinVerdict};
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use qrcode::{render::unicode::Dense1x2, EcLevel, QrCode};
+use rand::RngCore;
+use sha1::Sha1;
+use std::collections::HashSet;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// 验证码位数
+pub const TOTP_DIGITS: usize = 6;
+
+/// 时间步长 (秒)
+const STEP_SECS: u64 = 30;
+
+/// 允许的时钟偏差 (时间步)
+const SKEW_STEPS: u64 = 1;
+
+/// 密钥长度 (字节，RFC 4226 推荐 160 位)
+const SECRET_LEN: usize = 20;
+
+/// 身份验证器 App 中显示的发行方
+const ISSUER: &str = "sscontrol";
+
+const BASE32: base32::Alphabet = base32::Alphabet::Rfc4648 { padding: false };
+
+/// 生成新的 TOTP 密钥 (Base32 编码)
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; SECRET_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    base32::encode(BASE32, &bytes)
+}
+
+/// 解码 Base32 密钥 (忽略空格和大小写)
+pub fn decode_secret(secret: &str) -> Result<Vec<u8>> {
+    let normalized: String = secret
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    match base32::decode(BASE32, normalized.trim_end_matches('=')) {
+        Some(bytes) if !bytes.is_empty() => Ok(bytes),
+        _ => Err(anyhow!("TOTP 密钥不是有效的 Base32 字符串")),
+    }
+}
+
+/// 指定时间步的验证码
+fn code_for_step(secret: &[u8], step: u64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC 接受任意长度的密钥");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // RFC 4226 动态截断
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]])
+        & 0x7fff_ffff;
+    format!("{:0width$}", value % 10u32.pow(TOTP_DIGITS as u32), width = TOTP_DIGITS)
+}
+
+/// 指定 Unix 时间的验证码
+pub fn code_at(secret: &[u8], unix_secs: u64) -> String {
+    code_for_step(secret, unix_secs / STEP_SECS)
+}
+
+/// 身份验证器 App 使用的 `otpauth://` URI (account 一般为主机名)
+pub fn provisioning_uri(secret: &str, account: &str) -> String {
+    let encode = |text: &str| url::form_urlencoded::byte_serialize(text.as_bytes()).collect::<String>().replace('+', "%20");
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={digits}&period={period}",
+        issuer = ISSUER,
+        account = encode(account),
+        secret = secret,
+        digits = TOTP_DIGITS,
+        period = STEP_SECS,
+    )
+}
+
+/// 终端中显示的二维码
+pub fn render_qr(text: &str) -> Result<String> {
+    let qr = QrCode::with_error_correction_level(text, EcLevel::M)?;
+    Ok(qr.render::<Dense1x2>().quiet_zone(true).build())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// TOTP 验证器 (按 peer_id 记录验证状态)
+#[derive(Debug)]
+pub struct TotpGuard {
+    secret: Vec<u8>,
+    verified: HashSet<String>,
+    limiter: AttemptLimiter,
+    /// 最近一次通过验证的时间步 (更早或相同的验证码不再接受)
+    last_step: Option<u64>,
+}
+
+impl TotpGuard {
+    /// 从 Base32 密钥创建 (失败计数和锁定使用 PIN 的配置)
+    pub fn new(secret: &str, config: &PinConfig) -> Result<Self> {
+        Ok(Self {
+            secret: decode_secret(secret)?,
+            verified: HashSet::new(),
+            limiter: AttemptLimiter::new(config),
+            last_step: None,
+        })
+    }
+
+    /// Viewer 是否已通过验证
+    pub fn is_verified(&self, peer_id: &str) -> bool {
+        self.verified.contains(peer_id)
+    }
+
+    /// 直接信任 Viewer (恢复已验证过的会话时使用)
+    pub fn trust(&mut self, peer_id: &str) {
+        self.verified.insert(peer_id.to_string());
+    }
+
+    /// Viewer 断开后清除验证状态
+    pub fn forget(&mut self, peer_id: &str) {
+        self.verified.remove(peer_id);
+    }
+
+    /// 校验 Viewer 提交的验证码
+    pub fn verify(&mut self, peer_id: &str, code: &str) -> PinVerdict {
+        self.verify_at(peer_id, code, unix_now(), Instant::now())
+    }
+
+    fn verify_at(&mut self, peer_id: &str, code: &str, unix_secs: u64, now: Instant) -> PinVerdict {
+        if self.is_verified(peer_id) {
+            return PinVerdict::Accepted;
+        }
+        if let Some(locked) = self.limiter.check(now) {
+            return locked;
+        }
+
+        let code = code.trim();
+        let current = unix_secs / STEP_SECS;
+        let matched = (current.saturating_sub(SKEW_STEPS)..=current + SKEW_STEPS)
+            .filter(|step| self.last_step.is_none_or(|last| *step > last))
+            .find(|step| constant_time_eq(code.as_bytes(), code_for_step(&self.secret, *step).as_bytes()));
+
+        match matched {
+            Some(step) => {
+                self.last_step = Some(step);
+                self.verified.insert(peer_id.to_string());
+                self.limiter.succeed();
+                PinVerdict::Accepted
+            }
+            None => self.limiter.fail(now),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6238 附录 B 的 SHA1 测试密钥
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_rfc6238_vectors() {
+        // 附录 B 为 8 位，取末 6 位
+        for (time, expected) in [
+            (59, "94287082"),
+            (1111111109, "07081804"),
+            (1111111111, "14050471"),
+            (1234567890, "89005924"),
+            (2000000000, "69279037"),
+        ] {
+            assert_eq!(code_at(RFC_SECRET, time), expected[2..], "T = {}", time);
+        }
+
+        let secret = generate_secret();
+        assert_eq!(decode_secret(&secret).unwrap().len(), SECRET_LEN);
+        assert_eq!(decode_secret(&secret.to_lowercase()).unwrap(), decode_secret(&secret).unwrap());
+        assert!(decode_secret("not base32!").is_err());
+
+        let uri = provisioning_uri("JBSWY3DPEHPK3PXP", "my host");
+        assert_eq!(
+            uri,
+            "otpauth://totp/sscontrol:my%20host?secret=JBSWY3DPEHPK3PXP&issuer=sscontrol&algorithm=SHA1&digits=6&period=30"
+        );
+        assert!(render_qr(&uri).unwrap().lines().count() > 10);
+    }
+
+    #[test]
+    fn test_guard_skew_replay_and_lockout() {
+        let secret = base32::encode(BASE32, RFC_SECRET);
+        let mut guard = TotpGuard::new(&secret, &PinConfig { max_attempts: 2, ..Default::default() }).unwrap();
+        let now = Instant::now();
+        let t = 1_111_111_111;
+
+        // 上一个时间步的验证码仍然有效 (时钟偏差)
+        let previous = code_at(RFC_SECRET, t - STEP_SECS);
+        assert_eq!(guard.verify_at("a", &previous, t, now), PinVerdict::Accepted);
+        assert!(guard.is_verified("a"));
+
+        // 已用过的时间步不能再用 (包括更早的)
+        assert_eq!(guard.verify_at("b", &previous, t, now), PinVerdict::Rejected { remaining: 1 });
+        assert_eq!(guard.verify_at("b", &code_at(RFC_SECRET, t), t, now), PinVerdict::Accepted);
+
+        // 超出偏差范围的验证码无效，失败达到上限后锁定
+        let stale = code_at(RFC_SECRET, t - 3 * STEP_SECS);
+        assert_eq!(guard.verify_at("c", &stale, t, now), PinVerdict::Rejected { remaining: 1 });
+        assert!(matches!(guard.verify_at("c", "000000", t, now), PinVerdict::Locked { .. }));
+        let next = code_at(RFC_SECRET, t + STEP_SECS);
+        assert!(matches!(guard.verify_at("c", &next, t, now), PinVerdict::Locked { .. }));
+
+        guard.forget("a");
+        assert!(!guard.is_verified("a"));
+    }
+}
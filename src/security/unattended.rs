@@ -0,0 +1,81 @@
+//! 无人值守访问
+//!
+//! `sscontrol host --unattended` 首次启动时生成长期有效的访问密钥，只打印一次；
+//! 配置文件中仅保存其 Argon2id 哈希 (`host.unattended_secret_hash`)。
+//! Viewer 在原本输入 PIN 的位置提交该密钥即可连接，无需有人在被控端读出 PIN。
+//!
+//! 密钥校验与一次性 PIN 共用 `PinGuard` 的失败计数和锁定，暴力破解同样受限
+
+use anyhow::{anyhow, Result};
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rand::Rng;
+
+/// 密钥字符集 (去掉易混淆的 0/o/1/l/i)
+const SECRET_ALPHABET: &[u8] = b"23456789abcdefghjkmnpqrstuvwxyz";
+
+/// 密钥分组数和每组长度 (形如 `xxxxx-xxxxx-xxxxx-xxxxx`，约 99 位熵)
+const SECRET_GROUPS: usize = 4;
+const SECRET_GROUP_LEN: usize = 5;
+
+/// 生成新的访问密钥
+pub fn generate_secret() -> String {
+    let mut rng = rand::rngs::OsRng;
+    (0..SECRET_GROUPS)
+        .map(|_| {
+            (0..SECRET_GROUP_LEN)
+                .map(|_| SECRET_ALPHABET[rng.gen_range(0..SECRET_ALPHABET.len())] as char)
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// 计算密钥的 Argon2id 哈希 (PHC 字符串，含随机盐)
+pub fn hash_secret(secret: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(secret.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| anyhow!("计算访问密钥哈希失败: {}", e))
+}
+
+/// 校验提交的密钥 (哈希无效时视为不匹配)
+pub fn verify_secret(hash: &str, secret: &str) -> bool {
+    let Ok(hash) = PasswordHash::new(hash) else {
+        tracing::warn!("配置中的无人值守访问密钥哈希无效");
+        return false;
+    };
+    Argon2::default().verify_password(secret.trim().as_bytes(), &hash).is_ok()
+}
+
+/// 检查配置中的哈希是否可用 (启动时提前发现手工编辑错误)
+pub fn validate_hash(hash: &str) -> Result<()> {
+    PasswordHash::new(hash)
+        .map(|_| ())
+        .map_err(|e| anyhow!("host.unattended_secret_hash 无效: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_round_trip() {
+        let secret = generate_secret();
+        assert_eq!(secret.len(), SECRET_GROUPS * (SECRET_GROUP_LEN + 1) - 1);
+        assert!(secret.split('-').all(|group| group.len() == SECRET_GROUP_LEN));
+        assert_ne!(secret, generate_secret());
+
+        let hash = hash_secret(&secret).unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(!hash.contains(&secret));
+        validate_hash(&hash).unwrap();
+
+        assert!(verify_secret(&hash, &secret));
+        assert!(verify_secret(&hash, &format!(" {}\n", secret)));
+        assert!(!verify_secret(&hash, "123456"));
+        assert!(!verify_secret("not-a-hash", &secret));
+        assert!(validate_hash("not-a-hash").is_err());
+    }
+}
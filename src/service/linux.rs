@@ -21,6 +21,12 @@ pub struct SystemdService {
     service_file: PathBuf,
 }
 
+impl Default for SystemdService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SystemdService {
     pub fn new() -> Self {
         Self {
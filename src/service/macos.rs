@@ -1,23 +1,42 @@
-//! macOS LaunchAgent 实现
+//! macOS LaunchAgent / LaunchDaemon 实现
 //!
-//! 使用 launchd 管理用户级服务
+//! 使用 launchd 管理服务
 //!
 //! LaunchAgent 是 macOS 的用户级服务机制，支持:
 //! - 开机自启动 (用户登录时)
 //! - 自动重启 (KeepAlive)
 //! - 日志重定向
+//!
+//! LaunchAgent 在用户注销后随之退出。需要无人值守访问时安装 LaunchDaemon
+//! (`sscontrol service install --daemon`)，它在开机后一直运行，并在登录窗口和
+//! 用户会话中启动助手进程 (见 [`super::macos_session`])
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
+use std::time::{Duration, Instant};
 
 const LAUNCH_AGENT_NAME: &str = "com.sscontrol.agent";
 const PLIST_RELATIVE_PATH: &str = "Library/LaunchAgents/com.sscontrol.agent.plist";
 
-/// macOS LaunchAgent 服务控制器
+const LAUNCH_DAEMON_NAME: &str = "com.sscontrol.daemon";
+const LAUNCH_DAEMON_PLIST_PATH: &str = "/Library/LaunchDaemons/com.sscontrol.daemon.plist";
+
+/// LaunchDaemon 启动助手进程的参数
+const HELPER_ARGS: &[&str] = &["run", "--session-helper"];
+
+/// 检查控制台会话和助手进程的间隔
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 等待用户在系统设置中授权的最长时间
+const PERMISSION_WAIT: Duration = Duration::from_secs(180);
+
+/// macOS launchd 服务控制器 (LaunchAgent 或 LaunchDaemon)
 pub struct MacOSLaunchAgent {
     plist_path: PathBuf,
+    label: &'static str,
+    daemon: bool,
 }
 
 impl Default for MacOSLaunchAgent {
@@ -33,11 +52,24 @@ impl MacOSLaunchAgent {
 
         let plist_path = PathBuf::from(home).join(PLIST_RELATIVE_PATH);
 
-        Self { plist_path }
+        Self { plist_path, label: LAUNCH_AGENT_NAME, daemon: false }
+    }
+
+    /// 系统级 LaunchDaemon (开机即运行，注销后不退出，需要 root)
+    pub fn daemon() -> Self {
+        Self {
+            plist_path: PathBuf::from(LAUNCH_DAEMON_PLIST_PATH),
+            label: LAUNCH_DAEMON_NAME,
+            daemon: true,
+        }
     }
 
     /// 生成 plist 文件内容
     fn plist_content(&self) -> String {
+        if self.daemon {
+            return self.daemon_plist_content();
+        }
+
         let exe_path = std::env::current_exe()
             .unwrap_or_else(|_| PathBuf::from("/usr/local/bin/sscontrol"));
 
@@ -80,6 +112,50 @@ impl MacOSLaunchAgent {
         )
     }
 
+    /// 生成 LaunchDaemon plist 文件内容
+    ///
+    /// 以 root 运行在系统域中，通过环境变量告诉 `sscontrol run` 进入监督模式
+    fn daemon_plist_content(&self) -> String {
+        let exe_path = std::env::current_exe()
+            .unwrap_or_else(|_| PathBuf::from("/usr/local/bin/sscontrol"));
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{exe}</string>
+        <string>run</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <true/>
+    <key>StandardOutPath</key>
+    <string>/var/log/sscontrol.log</string>
+    <key>StandardErrorPath</key>
+    <string>/var/log/sscontrol.error.log</string>
+    <key>WorkingDirectory</key>
+    <string>/tmp</string>
+    <key>EnvironmentVariables</key>
+    <dict>
+        <key>PATH</key>
+        <string>/usr/local/bin:/usr/bin:/bin</string>
+        <key>{env}</key>
+        <string>1</string>
+    </dict>
+</dict>
+</plist>"#,
+            label = LAUNCH_DAEMON_NAME,
+            exe = exe_path.display(),
+            env = super::macos_session::LAUNCH_DAEMON_ENV,
+        )
+    }
+
     /// 执行 launchctl 命令
     fn launchctl(&self, args: &[&str]) -> Result<String> {
         let output = Command::new("launchctl")
@@ -97,7 +173,6 @@ impl MacOSLaunchAgent {
     }
 
     /// 获取当前用户 UID
-    #[allow(dead_code)]
     fn get_user_uid(&self) -> Result<String> {
         let output = Command::new("id")
             .arg("-u")
@@ -113,6 +188,10 @@ impl MacOSLaunchAgent {
 
 impl super::ServiceController for MacOSLaunchAgent {
     fn install(&self) -> Result<()> {
+        if self.daemon && self.get_user_uid()? != "0" {
+            bail!("安装 LaunchDaemon 需要 root 权限，请使用 sudo sscontrol service install --daemon");
+        }
+
         // 创建目录
         if let Some(parent) = self.plist_path.parent() {
             fs::create_dir_all(parent)
@@ -128,7 +207,7 @@ impl super::ServiceController for MacOSLaunchAgent {
         // 加载服务
         let path_str = self.plist_path.to_str()
             .ok_or_else(|| anyhow!("plist 路径包含非 UTF-8 字符"))?;
-        self.launchctl(&["load", path_str])?;
+        self.launchctl(&["load", "-w", path_str])?;
         println!("服务已加载");
 
         Ok(())
@@ -157,7 +236,7 @@ impl super::ServiceController for MacOSLaunchAgent {
             return Err(anyhow!("服务未安装，请先运行 install 命令"));
         }
 
-        self.launchctl(&["start", LAUNCH_AGENT_NAME])?;
+        self.launchctl(&["start", self.label])?;
         println!("服务已启动");
         Ok(())
     }
@@ -167,7 +246,7 @@ impl super::ServiceController for MacOSLaunchAgent {
             return Err(anyhow!("服务未安装"));
         }
 
-        self.launchctl(&["stop", LAUNCH_AGENT_NAME])?;
+        self.launchctl(&["stop", self.label])?;
         println!("服务已停止");
         Ok(())
     }
@@ -182,7 +261,7 @@ impl super::ServiceController for MacOSLaunchAgent {
 
         // 查找我们的服务
         for line in output.lines() {
-            if line.contains(LAUNCH_AGENT_NAME) {
+            if line.contains(self.label) {
                 // 格式: PID  状态  名称
                 // 如果 PID 不是 "-" 则说明服务正在运行
                 let parts: Vec<&str> = line.split_whitespace().collect();
@@ -203,6 +282,174 @@ impl super::ServiceController for MacOSLaunchAgent {
     }
 }
 
+/// LaunchDaemon 主循环
+///
+/// 监督控制台会话中的助手进程，控制台会话变化或助手退出时重新启动。
+/// 不会返回: launchd 卸载服务时结束整个进程组，助手进程随之退出
+pub fn run_daemon() -> Result<()> {
+    use super::macos_session::{ConsoleSession, SessionHelper};
+
+    tracing::info!("sscontrol LaunchDaemon 已启动");
+
+    let mut helper: Option<SessionHelper> = None;
+    loop {
+        match ConsoleSession::current() {
+            Ok(session) => {
+                // 会话已切换或助手已退出时结束旧助手
+                if let Some(current) = &mut helper {
+                    if current.session() != session {
+                        tracing::info!("控制台会话已变化: {} -> {}", current.session(), session);
+                        helper = None;
+                    } else if !current.is_running() {
+                        tracing::warn!("{}中的助手进程已退出，重新启动", current.session());
+                        helper = None;
+                    }
+                }
+
+                if helper.is_none() {
+                    match SessionHelper::spawn(session, HELPER_ARGS) {
+                        Ok(spawned) => helper = Some(spawned),
+                        Err(e) => tracing::error!("{}", e),
+                    }
+                }
+            }
+            Err(e) => tracing::warn!("{}", e),
+        }
+
+        std::thread::sleep(SUPERVISE_INTERVAL);
+    }
+}
+
+/// 屏幕录制和辅助功能权限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PermissionStatus {
+    pub screen_recording: bool,
+    pub accessibility: bool,
+}
+
+impl PermissionStatus {
+    /// 当前进程的权限 (屏幕录制的结果在进程内不会刷新)
+    pub fn current() -> Self {
+        Self {
+            screen_recording: crate::capture::macos::MacOSCapturer::check_screen_recording_permission(),
+            accessibility: crate::input::macos::MacOSInputSimulator::check_accessibility_permission(),
+        }
+    }
+
+    /// 新启动一个进程重新检查 (`sscontrol service check-permissions`)
+    pub fn probe() -> Result<Self> {
+        let exe = std::env::current_exe().map_err(|e| anyhow!("获取可执行文件路径失败: {}", e))?;
+        let status = Command::new(exe)
+            .args(["service", "check-permissions"])
+            .status()
+            .map_err(|e| anyhow!("检查权限失败: {}", e))?;
+        let code = status.code().ok_or_else(|| anyhow!("检查权限的进程被信号终止"))?;
+        Self::from_exit_code(code).ok_or_else(|| anyhow!("检查权限失败 (退出码 {})", code))
+    }
+
+    pub fn is_granted(&self) -> bool {
+        self.screen_recording && self.accessibility
+    }
+
+    /// 退出码: 0 = 全部授权，第 1 位 = 缺少屏幕录制，第 2 位 = 缺少辅助功能
+    pub fn exit_code(&self) -> i32 {
+        (!self.screen_recording as i32) | ((!self.accessibility as i32) << 1)
+    }
+
+    fn from_exit_code(code: i32) -> Option<Self> {
+        (0..4).contains(&code).then_some(Self {
+            screen_recording: code & 1 == 0,
+            accessibility: code & 2 == 0,
+        })
+    }
+}
+
+/// 安装服务前的权限向导
+///
+/// 缺少权限时弹出系统授权请求并打开对应的设置页，等待用户授权后再继续。
+/// 从终端运行时系统可能把权限记在终端名下，此时需要在设置页中用 "+" 手动添加 sscontrol
+pub fn permission_assistant() -> Result<()> {
+    let status = PermissionStatus::probe()?;
+    if status.is_granted() {
+        println!("✓ 屏幕录制和辅助功能权限已授权");
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe().map_err(|e| anyhow!("获取可执行文件路径失败: {}", e))?;
+    println!("服务需要以下权限，请在系统设置中允许 {}:", exe.display());
+    if !status.screen_recording {
+        println!("  - 屏幕录制 (系统设置 → 隐私与安全性 → 屏幕录制)");
+        request_screen_recording();
+        open_settings("Privacy_ScreenCapture");
+    }
+    if !status.accessibility {
+        println!("  - 辅助功能 (系统设置 → 隐私与安全性 → 辅助功能)");
+        request_accessibility();
+        open_settings("Privacy_Accessibility");
+    }
+    println!("等待授权 (最多 {} 秒)...", PERMISSION_WAIT.as_secs());
+
+    let deadline = Instant::now() + PERMISSION_WAIT;
+    loop {
+        std::thread::sleep(Duration::from_secs(2));
+        let status = PermissionStatus::probe()?;
+        if status.is_granted() {
+            println!("✓ 权限已授权");
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            let missing: Vec<&str> = [
+                (!status.screen_recording).then_some("屏幕录制"),
+                (!status.accessibility).then_some("辅助功能"),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            bail!(
+                "仍未授权: {}。授权后重新安装，或使用 --skip-permission-check 跳过检查",
+                missing.join("、")
+            );
+        }
+    }
+}
+
+/// 请求屏幕录制权限 (首次调用时弹出系统对话框并把程序加入列表)
+fn request_screen_recording() {
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        /// macOS 10.15+
+        fn CGRequestScreenCaptureAccess() -> bool;
+    }
+    unsafe {
+        CGRequestScreenCaptureAccess();
+    }
+}
+
+/// 请求辅助功能权限 (弹出系统对话框)
+fn request_accessibility() {
+    use core_foundation::base::TCFType;
+    use core_foundation::boolean::CFBoolean;
+    use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+    use core_foundation::string::{CFString, CFStringRef};
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        static kAXTrustedCheckOptionPrompt: CFStringRef;
+        fn AXIsProcessTrustedWithOptions(options: CFDictionaryRef) -> bool;
+    }
+    unsafe {
+        let key = CFString::wrap_under_get_rule(kAXTrustedCheckOptionPrompt);
+        let options = CFDictionary::from_CFType_pairs(&[(key, CFBoolean::true_value())]);
+        AXIsProcessTrustedWithOptions(options.as_concrete_TypeRef());
+    }
+}
+
+/// 打开系统设置的隐私页面
+fn open_settings(pane: &str) {
+    let url = format!("x-apple.systempreferences:com.apple.preference.security?{}", pane);
+    let _ = Command::new("open").arg(url).status();
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -225,4 +472,24 @@ mod tests {
         assert!(path_str.contains("LaunchAgents"));
         assert!(path_str.contains("com.sscontrol.agent.plist"));
     }
+
+    #[test]
+    fn test_daemon_plist() {
+        let daemon = MacOSLaunchAgent::daemon();
+        let content = daemon.plist_content();
+
+        assert_eq!(daemon.plist_path, PathBuf::from(LAUNCH_DAEMON_PLIST_PATH));
+        assert!(content.contains(LAUNCH_DAEMON_NAME));
+        assert!(content.contains(super::super::macos_session::LAUNCH_DAEMON_ENV));
+    }
+
+    #[test]
+    fn test_permission_exit_code() {
+        for (screen_recording, accessibility) in [(true, true), (false, true), (true, false), (false, false)] {
+            let status = PermissionStatus { screen_recording, accessibility };
+            assert_eq!(PermissionStatus::from_exit_code(status.exit_code()), Some(status));
+        }
+        assert_eq!(PermissionStatus { screen_recording: true, accessibility: true }.exit_code(), 0);
+        assert_eq!(PermissionStatus::from_exit_code(101), None);
+    }
 }
@@ -0,0 +1,145 @@
+//! macOS 控制台会话
+//!
+//! LaunchDaemon 以 root 运行在系统域中，连接不到 WindowServer，既不能捕获屏幕也不能注入输入。
+//! 以 LaunchDaemon 运行时 `sscontrol run` 只负责监督:
+//! - 有用户登录时，用 `launchctl asuser` 在该用户的 GUI 会话中启动助手进程
+//! - 停在登录窗口时，用 `launchctl bsexec` 在 loginwindow 的引导命名空间中启动助手进程
+//!   (登录窗口只允许 root 进程捕获)
+//! - 控制台用户变化 (登录、注销、快速切换用户) 或助手退出时重新启动助手
+
+use anyhow::{anyhow, Result};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+/// LaunchDaemon plist 中设置的环境变量，用于区分 LaunchDaemon 和命令行启动
+pub const LAUNCH_DAEMON_ENV: &str = "SSCONTROL_LAUNCH_DAEMON";
+
+/// 当前进程是否由 LaunchDaemon 启动
+pub fn is_launch_daemon() -> bool {
+    std::env::var(LAUNCH_DAEMON_ENV).map(|value| value == "1").unwrap_or(false)
+}
+
+/// 控制台 (当前显示在屏幕上的) 会话
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleSession {
+    /// 登录窗口 (没有用户登录，或快速切换用户的选择界面)
+    LoginWindow,
+    /// 已登录用户的 GUI 会话
+    User(u32),
+}
+
+impl ConsoleSession {
+    /// 当前控制台会话 (/dev/console 的属主，登录窗口时为 root)
+    pub fn current() -> Result<Self> {
+        let output = Command::new("stat")
+            .args(["-f", "%u", "/dev/console"])
+            .output()
+            .map_err(|e| anyhow!("执行 stat 失败: {}", e))?;
+        if !output.status.success() {
+            return Err(anyhow!("查询控制台用户失败: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Self::from_console_uid(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn from_console_uid(output: &str) -> Result<Self> {
+        let uid: u32 = output.trim().parse().map_err(|_| anyhow!("无法解析控制台用户: {:?}", output.trim()))?;
+        Ok(if uid == 0 { Self::LoginWindow } else { Self::User(uid) })
+    }
+}
+
+impl std::fmt::Display for ConsoleSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConsoleSession::LoginWindow => write!(f, "登录窗口"),
+            ConsoleSession::User(uid) => write!(f, "用户 {}", uid),
+        }
+    }
+}
+
+/// 在控制台会话中运行的助手进程
+pub struct SessionHelper {
+    session: ConsoleSession,
+    child: Child,
+}
+
+impl SessionHelper {
+    /// 在控制台会话中启动自身 (需要 root)
+    pub fn spawn(session: ConsoleSession, args: &[&str]) -> Result<Self> {
+        let exe = std::env::current_exe().map_err(|e| anyhow!("获取可执行文件路径失败: {}", e))?;
+        let mut command = Self::command(session, &exe)?;
+        let child = command
+            .args(args)
+            // 助手进程不继承 LaunchDaemon 标记，否则会再次进入监督模式
+            .env_remove(LAUNCH_DAEMON_ENV)
+            .stdin(Stdio::null())
+            .spawn()
+            .map_err(|e| anyhow!("在{}中启动助手进程失败: {}", session, e))?;
+
+        tracing::info!("已在{}中启动助手进程 (PID {})", session, child.id());
+        Ok(Self { session, child })
+    }
+
+    /// 进入控制台会话引导命名空间的 launchctl 命令
+    fn command(session: ConsoleSession, exe: &Path) -> Result<Command> {
+        let mut command = Command::new("launchctl");
+        match session {
+            ConsoleSession::User(uid) => {
+                command.arg("asuser").arg(uid.to_string());
+            }
+            ConsoleSession::LoginWindow => {
+                command.arg("bsexec").arg(login_window_pid()?.to_string());
+            }
+        }
+        command.arg(exe);
+        Ok(command)
+    }
+
+    /// 助手所在的会话
+    pub fn session(&self) -> ConsoleSession {
+        self.session
+    }
+
+    /// 助手是否仍在运行
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// 结束助手进程
+    pub fn terminate(&mut self) {
+        if self.is_running() {
+            tracing::info!("结束{}中的助手进程 (PID {})", self.session, self.child.id());
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+impl Drop for SessionHelper {
+    fn drop(&mut self) {
+        self.terminate();
+    }
+}
+
+/// 登录窗口进程 (以 root 运行的 loginwindow) 的 PID
+fn login_window_pid() -> Result<u32> {
+    let output = Command::new("pgrep")
+        .args(["-x", "-U", "0", "loginwindow"])
+        .output()
+        .map_err(|e| anyhow!("执行 pgrep 失败: {}", e))?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.trim().parse().ok())
+        .ok_or_else(|| anyhow!("找不到登录窗口进程"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_console_session_from_uid() {
+        assert_eq!(ConsoleSession::from_console_uid("0\n").unwrap(), ConsoleSession::LoginWindow);
+        assert_eq!(ConsoleSession::from_console_uid("501\n").unwrap(), ConsoleSession::User(501));
+        assert!(ConsoleSession::from_console_uid("").is_err());
+    }
+}
@@ -4,7 +4,7 @@
 //!
 //! 支持的平台:
 //! - Windows: Windows Service
-//! - macOS: LaunchAgent
+//! - macOS: LaunchAgent / LaunchDaemon
 //! - Linux: systemd
 
 #![allow(dead_code)]
@@ -12,9 +12,15 @@
 #[cfg(target_os = "windows")]
 pub mod windows;
 
+#[cfg(target_os = "windows")]
+pub mod windows_session;
+
 #[cfg(target_os = "macos")]
 pub mod macos;
 
+#[cfg(target_os = "macos")]
+pub mod macos_session;
+
 #[cfg(target_os = "linux")]
 pub mod linux;
 
@@ -95,7 +101,7 @@ pub fn create_controller() -> impl ServiceController {
 
     #[cfg(target_os = "linux")]
     {
-        return linux::SystemdService::new();
+        linux::SystemdService::new()
     }
 
     #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
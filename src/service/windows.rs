@@ -173,10 +173,51 @@ impl super::ServiceController for WindowsServiceController {
     }
 }
 
+windows_service::define_windows_service!(ffi_service_main, service_main);
+
+/// 服务进程中助手进程的启动参数
+const HELPER_ARGS: &[&str] = &["run", "--session-helper"];
+
+/// 检查助手进程和活动会话的间隔
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// ERROR_FAILED_SERVICE_CONTROLLER_CONNECT: 进程不是由服务控制管理器启动的
+const ERROR_FAILED_SERVICE_CONTROLLER_CONNECT: i32 = 1063;
+
+/// 进入服务分发
+///
+/// 由服务控制管理器启动时阻塞到服务停止并返回 true；
+/// 从命令行直接运行 `sscontrol run` 时立即返回 false，由调用方在当前会话中运行
+pub fn dispatch() -> Result<bool> {
+    match windows_service::service_dispatcher::start(SERVICE_NAME, ffi_service_main) {
+        Ok(()) => Ok(true),
+        Err(windows_service::Error::Winapi(e))
+            if e.raw_os_error() == Some(ERROR_FAILED_SERVICE_CONTROLLER_CONNECT) =>
+        {
+            Ok(false)
+        }
+        Err(e) => Err(anyhow!("启动服务分发失败: {}", e)),
+    }
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        tracing::error!("Windows 服务异常退出: {}", e);
+    }
+}
+
+/// 服务控制事件
+enum ServiceEvent {
+    /// 停止或关机
+    Stop,
+    /// 用户登录、注销、切换或远程连接
+    SessionChanged,
+}
+
 /// Windows 服务主循环
 ///
-/// 此函数在服务模式下运行，处理服务控制事件
-#[cfg(target_os = "windows")]
+/// 服务运行在会话 0，无法捕获用户桌面，实际工作由活动控制台会话中的助手进程完成。
+/// 服务负责在会话变化或助手退出时重新启动助手，并代发 Ctrl+Alt+Del
 pub fn run_service() -> Result<()> {
     use windows_service::{
         service::ServiceControl,
@@ -186,20 +227,25 @@ pub fn run_service() -> Result<()> {
 
     use std::sync::mpsc;
 
-    let (tx, _rx) = mpsc::channel();
+    let (tx, rx) = mpsc::channel();
 
     // 定义服务控制事件处理函数
     let event_handler = move |control_event| -> ServiceControlHandlerResult {
         match control_event {
             ServiceControl::Stop => {
-                println!("收到停止信号");
-                let _ = tx.send(());
+                tracing::info!("收到停止信号");
+                let _ = tx.send(ServiceEvent::Stop);
                 ServiceControlHandlerResult::NoError
             }
             ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
             ServiceControl::Shutdown => {
-                println!("收到关机信号");
-                let _ = tx.send(());
+                tracing::info!("收到关机信号");
+                let _ = tx.send(ServiceEvent::Stop);
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::SessionChange(change) => {
+                tracing::debug!("会话变化: {:?}", change.reason);
+                let _ = tx.send(ServiceEvent::SessionChanged);
                 ServiceControlHandlerResult::NoError
             }
             _ => ServiceControlHandlerResult::NotImplemented,
@@ -217,7 +263,9 @@ pub fn run_service() -> Result<()> {
     status_handle.set_service_status(ServiceStatus {
         service_type: ServiceType::OWN_PROCESS,
         current_state: ServiceState::Running,
-        controls_accepted: ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        controls_accepted: ServiceControlAccept::STOP
+            | ServiceControlAccept::SHUTDOWN
+            | ServiceControlAccept::SESSION_CHANGE,
         exit_code: Default::default(),
         checkpoint: Default::default(),
         wait_hint: Duration::default(),
@@ -225,10 +273,10 @@ pub fn run_service() -> Result<()> {
     })
     .map_err(|e| anyhow!("设置服务状态失败: {}", e))?;
 
-    println!("Windows 服务已启动");
+    tracing::info!("Windows 服务已启动");
 
     // 运行主循环
-    run_main_loop()?;
+    let result = run_main_loop(&rx);
 
     // 设置服务状态为已停止
     status_handle.set_service_status(ServiceStatus {
@@ -242,34 +290,53 @@ pub fn run_service() -> Result<()> {
     })
     .map_err(|e| anyhow!("设置服务状态失败: {}", e))?;
 
-    println!("Windows 服务已停止");
+    tracing::info!("Windows 服务已停止");
 
-    Ok(())
+    result
 }
 
-/// Windows 服务主循环
-///
-/// 这里调用实际的业务逻辑
-#[cfg(target_os = "windows")]
-fn run_main_loop() -> Result<()> {
-    // 在这里调用实际的捕获和发送逻辑
-    // 使用 tokio runtime 而不是 #[tokio::main]
-    let rt = tokio::runtime::Runtime::new()
-        .map_err(|e| anyhow!("创建 tokio runtime 失败: {}", e))?;
-
-    rt.block_on(async {
-        // 调用实际的主循环逻辑
-        // 这里应该调用 src/main.rs 中的实际捕获逻辑
-        // 为了避免循环依赖，我们使用一个标志来决定是否以服务模式运行
-
-        println!("服务主循环已启动");
+/// 监督活动会话中的助手进程，直到收到停止信号
+fn run_main_loop(events: &std::sync::mpsc::Receiver<ServiceEvent>) -> Result<()> {
+    use super::windows_session::{active_console_session, SasRelay, SessionHelper};
+    use std::sync::mpsc::RecvTimeoutError;
+
+    // 代发失败不影响捕获和普通输入
+    let _sas_relay = SasRelay::start()
+        .map_err(|e| tracing::warn!("Ctrl+Alt+Del 代发不可用: {}", e))
+        .ok();
+
+    let mut helper: Option<SessionHelper> = None;
+    loop {
+        let session = active_console_session();
+
+        // 会话已切换或助手已退出时结束旧助手
+        if let Some(current) = &helper {
+            if Some(current.session()) != session {
+                tracing::info!("活动会话已变化: {} -> {:?}", current.session(), session);
+                helper = None;
+            } else if !current.is_running() {
+                tracing::warn!("会话 {} 中的助手进程已退出，重新启动", current.session());
+                helper = None;
+            }
+        }
 
-        // 模拟运行直到收到停止信号
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        if helper.is_none() {
+            if let Some(session) = session {
+                match SessionHelper::spawn(session, HELPER_ARGS) {
+                    Ok(spawned) => helper = Some(spawned),
+                    Err(e) => tracing::error!("{}", e),
+                }
+            }
+        }
 
-        Ok::<(), anyhow::Error>(())
-    })?;
+        match events.recv_timeout(SUPERVISE_INTERVAL) {
+            Ok(ServiceEvent::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+            Ok(ServiceEvent::SessionChanged) | Err(RecvTimeoutError::Timeout) => {}
+        }
+    }
 
+    // 结束助手进程
+    drop(helper);
     Ok(())
 }
 
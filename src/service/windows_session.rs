@@ -0,0 +1,453 @@
+//! Windows 会话与桌面
+//!
+//! 服务运行在会话 0，看不到用户桌面，也无法向其注入输入。服务模式下:
+//! - 服务 (LocalSystem) 用自己的令牌在当前活动控制台会话中启动助手进程
+//!   (`sscontrol run --session-helper`)，由助手完成捕获、编码和输入
+//! - 锁屏、登录界面和 UAC 提示运行在安全桌面 (Winlogon) 上，捕获和输入线程在
+//!   每次操作前跟随当前输入桌面切换 ([`follow_input_desktop`])
+//! - Ctrl+Alt+Del 只能由服务调用 SendSAS 发出，助手通过全局事件请求服务代发
+//!   ([`SasRelay`] / [`send_secure_attention`])
+
+use anyhow::{anyhow, Result};
+use std::cell::RefCell;
+use std::ffi::c_void;
+use std::mem;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use windows::core::{HSTRING, PCWSTR, PWSTR};
+use windows::Win32::Foundation::{CloseHandle, BOOL, HANDLE};
+use windows::Win32::System::StationsAndDesktops::HDESK;
+
+/// 服务与助手之间请求 Ctrl+Alt+Del 的全局事件名
+const SAS_EVENT_NAME: &str = "Global\\sscontrol-sas";
+
+/// WTSGetActiveConsoleSessionId 没有活动会话时的返回值
+const NO_SESSION: u32 = 0xFFFF_FFFF;
+
+/// GetExitCodeProcess: 进程仍在运行
+const STILL_ACTIVE: u32 = 259;
+
+/// 当前活动的控制台会话 (没有用户连接到控制台时为 None，例如切换用户过程中)
+pub fn active_console_session() -> Option<u32> {
+    use windows::Win32::System::RemoteDesktop::WTSGetActiveConsoleSessionId;
+
+    let session = unsafe { WTSGetActiveConsoleSessionId() };
+    (session != NO_SESSION).then_some(session)
+}
+
+/// 当前进程所在的会话
+pub fn current_session() -> Result<u32> {
+    use windows::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+    use windows::Win32::System::Threading::GetCurrentProcessId;
+
+    let mut session = 0u32;
+    unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session) }
+        .map_err(|e| anyhow!("查询当前会话失败: {}", e))?;
+    Ok(session)
+}
+
+/// 当前进程是否运行在会话 0 (服务会话)
+pub fn is_session_zero() -> bool {
+    current_session().map(|session| session == 0).unwrap_or(false)
+}
+
+/// 在用户会话中运行的助手进程
+pub struct SessionHelper {
+    session: u32,
+    process: HANDLE,
+    pid: u32,
+}
+
+// 进程句柄可以跨线程使用
+unsafe impl Send for SessionHelper {}
+
+impl SessionHelper {
+    /// 以当前进程 (LocalSystem) 的令牌在指定会话的 winsta0\default 桌面上启动自身
+    ///
+    /// 需要 SE_TCB_NAME 权限，只有服务进程才能调用成功
+    pub fn spawn(session: u32, args: &[&str]) -> Result<Self> {
+        use windows::Win32::Security::{
+            DuplicateTokenEx, SecurityImpersonation, SetTokenInformation, TokenPrimary, TokenSessionId,
+            TOKEN_ALL_ACCESS, TOKEN_DUPLICATE,
+        };
+        use windows::Win32::System::Threading::{
+            CreateProcessAsUserW, GetCurrentProcess, OpenProcessToken, CREATE_NO_WINDOW,
+            CREATE_UNICODE_ENVIRONMENT, PROCESS_INFORMATION, STARTUPINFOW,
+        };
+
+        let exe = std::env::current_exe().map_err(|e| anyhow!("获取可执行文件路径失败: {}", e))?;
+        let mut command_line: Vec<u16> = command_line(&exe, args).encode_utf16().chain([0]).collect();
+        let mut desktop: Vec<u16> = "winsta0\\default".encode_utf16().chain([0]).collect();
+
+        unsafe {
+            let mut own_token = HANDLE::default();
+            OpenProcessToken(GetCurrentProcess(), TOKEN_DUPLICATE, &mut own_token)
+                .map_err(|e| anyhow!("打开进程令牌失败: {}", e))?;
+            let _own_token = OwnedHandle(own_token);
+
+            let mut token = HANDLE::default();
+            DuplicateTokenEx(own_token, TOKEN_ALL_ACCESS, None, SecurityImpersonation, TokenPrimary, &mut token)
+                .map_err(|e| anyhow!("复制进程令牌失败: {}", e))?;
+            let token = OwnedHandle(token);
+
+            SetTokenInformation(
+                token.0,
+                TokenSessionId,
+                &session as *const u32 as *const c_void,
+                mem::size_of::<u32>() as u32,
+            )
+            .map_err(|e| anyhow!("设置令牌会话失败 (需要以服务运行): {}", e))?;
+
+            let startup = STARTUPINFOW {
+                cb: mem::size_of::<STARTUPINFOW>() as u32,
+                lpDesktop: PWSTR(desktop.as_mut_ptr()),
+                ..Default::default()
+            };
+            let mut info = PROCESS_INFORMATION::default();
+            CreateProcessAsUserW(
+                token.0,
+                PCWSTR::null(),
+                PWSTR(command_line.as_mut_ptr()),
+                None,
+                None,
+                false,
+                CREATE_NO_WINDOW | CREATE_UNICODE_ENVIRONMENT,
+                None,
+                PCWSTR::null(),
+                &startup,
+                &mut info,
+            )
+            .map_err(|e| anyhow!("在会话 {} 中启动助手进程失败: {}", session, e))?;
+            let _ = CloseHandle(info.hThread);
+
+            tracing::info!("已在会话 {} 中启动助手进程 (PID {})", session, info.dwProcessId);
+            Ok(Self { session, process: info.hProcess, pid: info.dwProcessId })
+        }
+    }
+
+    /// 助手所在的会话
+    pub fn session(&self) -> u32 {
+        self.session
+    }
+
+    /// 助手进程 ID
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// 助手是否仍在运行
+    pub fn is_running(&self) -> bool {
+        use windows::Win32::System::Threading::GetExitCodeProcess;
+
+        let mut code = 0u32;
+        unsafe { GetExitCodeProcess(self.process, &mut code) }.is_ok() && code == STILL_ACTIVE
+    }
+
+    /// 结束助手进程
+    pub fn terminate(&self) {
+        use windows::Win32::System::Threading::TerminateProcess;
+
+        if self.is_running() {
+            tracing::info!("结束会话 {} 中的助手进程 (PID {})", self.session, self.pid);
+            let _ = unsafe { TerminateProcess(self.process, 0) };
+        }
+    }
+}
+
+impl Drop for SessionHelper {
+    fn drop(&mut self) {
+        self.terminate();
+        let _ = unsafe { CloseHandle(self.process) };
+    }
+}
+
+/// 退出作用域时关闭的句柄
+struct OwnedHandle(HANDLE);
+
+impl Drop for OwnedHandle {
+    fn drop(&mut self) {
+        let _ = unsafe { CloseHandle(self.0) };
+    }
+}
+
+/// 拼接命令行，含空格或引号的参数按 CommandLineToArgvW 的规则加引号
+fn command_line(exe: &Path, args: &[&str]) -> String {
+    std::iter::once(exe.to_string_lossy().as_ref())
+        .chain(args.iter().copied())
+        .map(quote_argument)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn quote_argument(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+thread_local! {
+    /// 本线程通过 SetThreadDesktop 切换到的桌面 (句柄, 名称)
+    static THREAD_DESKTOP: RefCell<Option<(HDESK, String)>> = const { RefCell::new(None) };
+}
+
+/// 让当前线程跟随输入桌面 (Default ↔ Winlogon 安全桌面)
+///
+/// 锁屏、Ctrl+Alt+Del 界面和 UAC 提示时输入桌面会切换，
+/// 不在输入桌面上的线程捕获到的是黑屏，SendInput 也会失败。
+/// 返回 true 表示本次调用切换了线程桌面，之前按桌面创建的资源 (DC、桌面复制) 需要重建。
+/// 线程拥有窗口或钩子时 SetThreadDesktop 会失败，所以只应在捕获和输入线程上调用
+pub fn follow_input_desktop() -> Result<bool> {
+    use windows::Win32::System::StationsAndDesktops::{
+        CloseDesktop, OpenInputDesktop, SetThreadDesktop, DESKTOP_ACCESS_FLAGS, DESKTOP_CONTROL_FLAGS,
+    };
+
+    // GENERIC_ALL
+    const DESKTOP_ALL_ACCESS: DESKTOP_ACCESS_FLAGS = DESKTOP_ACCESS_FLAGS(0x1000_0000);
+
+    unsafe {
+        let input = OpenInputDesktop(DESKTOP_CONTROL_FLAGS(0), false, DESKTOP_ALL_ACCESS)
+            .map_err(|e| anyhow!("打开输入桌面失败: {}", e))?;
+        let name = match desktop_name(input) {
+            Ok(name) => name,
+            Err(e) => {
+                let _ = CloseDesktop(input);
+                return Err(e);
+            }
+        };
+
+        let current = THREAD_DESKTOP.with(|desktop| desktop.borrow().as_ref().map(|(_, name)| name.clone()));
+        if current.as_deref() == Some(name.as_str()) {
+            let _ = CloseDesktop(input);
+            return Ok(false);
+        }
+
+        if let Err(e) = SetThreadDesktop(input) {
+            let _ = CloseDesktop(input);
+            return Err(anyhow!("切换到桌面 {} 失败: {}", name, e));
+        }
+
+        let previous = THREAD_DESKTOP.with(|desktop| desktop.borrow_mut().replace((input, name.clone())));
+        if let Some((handle, _)) = previous {
+            let _ = CloseDesktop(handle);
+        }
+
+        // 第一次调用只是记录线程所在的桌面
+        match current {
+            Some(current) => {
+                tracing::info!("输入桌面已切换: {} -> {}", current, name);
+                Ok(true)
+            }
+            None => {
+                tracing::debug!("线程已绑定到输入桌面 {}", name);
+                Ok(false)
+            }
+        }
+    }
+}
+
+/// 桌面名称 (Default、Winlogon、Screen-saver)
+unsafe fn desktop_name(desktop: HDESK) -> Result<String> {
+    use windows::Win32::System::StationsAndDesktops::{GetUserObjectInformationW, UOI_NAME};
+
+    let mut buffer = [0u16; 256];
+    let mut needed = 0u32;
+    GetUserObjectInformationW(
+        HANDLE(desktop.0),
+        UOI_NAME,
+        Some(buffer.as_mut_ptr() as *mut c_void),
+        mem::size_of_val(&buffer) as u32,
+        Some(&mut needed),
+    )
+    .map_err(|e| anyhow!("查询桌面名称失败: {}", e))?;
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Ok(String::from_utf16_lossy(&buffer[..len]))
+}
+
+/// 发送 Ctrl+Alt+Del
+///
+/// 优先请求服务代发 (助手进程)，没有运行中的服务时直接调用 SendSAS
+/// (只有以服务运行且组策略允许时才会生效)
+pub fn send_secure_attention() -> Result<()> {
+    match SasRelay::request() {
+        Ok(()) => {
+            tracing::info!("已请求服务发送安全注意序列 (Ctrl+Alt+Del)");
+            Ok(())
+        }
+        Err(e) => {
+            tracing::debug!("服务代发不可用 ({})，直接调用 SendSAS", e);
+            send_sas()
+        }
+    }
+}
+
+/// 通过 sas.dll 的 SendSAS 发送 Ctrl+Alt+Del (SendInput 模拟的 Ctrl+Alt+Del 会被系统忽略)
+///
+/// SendSAS 没有返回值，失败时系统不会有任何反应
+fn send_sas() -> Result<()> {
+    use windows::core::{s, w};
+    use windows::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryW};
+
+    unsafe {
+        let library = LoadLibraryW(w!("sas.dll")).map_err(|e| anyhow!("加载 sas.dll 失败: {}", e))?;
+        let proc = GetProcAddress(library, s!("SendSAS")).ok_or_else(|| anyhow!("sas.dll 中没有 SendSAS"))?;
+        let send_sas: unsafe extern "system" fn(BOOL) = mem::transmute(proc);
+        // FALSE: 调用方是服务
+        send_sas(BOOL(0));
+    }
+    tracing::info!("已发送安全注意序列 (Ctrl+Alt+Del)");
+    Ok(())
+}
+
+/// 服务端的 Ctrl+Alt+Del 代发
+///
+/// 创建全局事件并在后台线程中等待，助手进程置位事件后由服务调用 SendSAS
+pub struct SasRelay {
+    event: HANDLE,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+// 事件句柄可以跨线程使用
+unsafe impl Send for SasRelay {}
+
+impl SasRelay {
+    /// 创建事件并启动等待线程 (在服务进程中调用)
+    pub fn start() -> Result<Self> {
+        use windows::Win32::System::Threading::{CreateEventW, WaitForSingleObject};
+
+        let security = EveryoneCanSignal::new()?;
+        let event = unsafe {
+            CreateEventW(Some(&security.attributes as *const _), false, false, &HSTRING::from(SAS_EVENT_NAME))
+                .map_err(|e| anyhow!("创建 Ctrl+Alt+Del 事件失败: {}", e))?
+        };
+
+        let running = Arc::new(AtomicBool::new(true));
+        let thread = {
+            let running = running.clone();
+            let event = event.0;
+            std::thread::Builder::new()
+                .name("sas-relay".to_string())
+                .spawn(move || {
+                    while running.load(Ordering::SeqCst) {
+                        // WAIT_OBJECT_0 = 0，其余为超时或失败
+                        let signaled = unsafe { WaitForSingleObject(HANDLE(event), 500) }.0 == 0;
+                        if signaled && running.load(Ordering::SeqCst) {
+                            if let Err(e) = send_sas() {
+                                tracing::warn!("代发 Ctrl+Alt+Del 失败: {}", e);
+                            }
+                        }
+                    }
+                })
+                .map_err(|e| anyhow!("启动 Ctrl+Alt+Del 代发线程失败: {}", e))?
+        };
+
+        tracing::info!("Ctrl+Alt+Del 代发已就绪");
+        Ok(Self { event, running, thread: Some(thread) })
+    }
+
+    /// 请求服务发送 Ctrl+Alt+Del (在助手进程中调用)
+    pub fn request() -> Result<()> {
+        use windows::Win32::System::Threading::{OpenEventW, SetEvent, EVENT_MODIFY_STATE};
+
+        unsafe {
+            let event = OpenEventW(EVENT_MODIFY_STATE, false, &HSTRING::from(SAS_EVENT_NAME))
+                .map_err(|e| anyhow!("打开 Ctrl+Alt+Del 事件失败: {}", e))?;
+            let event = OwnedHandle(event);
+            SetEvent(event.0).map_err(|e| anyhow!("置位 Ctrl+Alt+Del 事件失败: {}", e))
+        }
+    }
+}
+
+impl Drop for SasRelay {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = unsafe { CloseHandle(self.event) };
+    }
+}
+
+/// 允许用户会话中的助手进程置位事件的安全描述符
+///
+/// 服务 (LocalSystem) 创建的对象默认只有 SYSTEM 和管理员可以访问
+struct EveryoneCanSignal {
+    descriptor: windows::Win32::Security::PSECURITY_DESCRIPTOR,
+    attributes: windows::Win32::Security::SECURITY_ATTRIBUTES,
+}
+
+impl EveryoneCanSignal {
+    fn new() -> Result<Self> {
+        use windows::core::w;
+        use windows::Win32::Security::Authorization::{
+            ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+        };
+        use windows::Win32::Security::{PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES};
+
+        // SYSTEM 完全控制，交互用户只能等待和置位 (SYNCHRONIZE | EVENT_MODIFY_STATE)
+        let mut descriptor = PSECURITY_DESCRIPTOR::default();
+        unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                w!("D:(A;;GA;;;SY)(A;;0x00100002;;;IU)"),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                None,
+            )
+            .map_err(|e| anyhow!("创建事件安全描述符失败: {}", e))?;
+        }
+        let attributes = SECURITY_ATTRIBUTES {
+            nLength: mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: descriptor.0,
+            bInheritHandle: false.into(),
+        };
+        Ok(Self { descriptor, attributes })
+    }
+}
+
+impl Drop for EveryoneCanSignal {
+    fn drop(&mut self) {
+        use windows::Win32::Foundation::{LocalFree, HLOCAL};
+
+        let _ = unsafe { LocalFree(HLOCAL(self.descriptor.0 as isize)) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line_quoting() {
+        let exe = Path::new(r"C:\Program Files\sscontrol\sscontrol.exe");
+        assert_eq!(
+            command_line(exe, &["run", "--session-helper"]),
+            r#""C:\Program Files\sscontrol\sscontrol.exe" run --session-helper"#
+        );
+        assert_eq!(quote_argument(""), r#""""#);
+        assert_eq!(quote_argument(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote_argument(r"C:\dir with space\"), r#""C:\dir with space\\""#);
+    }
+}
@@ -0,0 +1,209 @@
+//! 会话准入控制
+//!
+//! 限制同时连接的 Viewer 数量。超出上限的 Viewer 可进入等待队列，
+//! 有会话结束时按先来先服务的顺序放行
+
+#![allow(dead_code)]
+
+use std::collections::{HashSet, VecDeque};
+
+/// 容量配置
+#[derive(Debug, Clone)]
+pub struct CapacityConfig {
+    /// 最大并发会话数 (None = 不限制；配置为 0 时会被 `Config::check` 拒绝)
+    pub max_sessions: Option<usize>,
+    /// 超出上限时是否排队
+    pub queue_enabled: bool,
+    /// 等待队列最大长度
+    pub max_queue_length: usize,
+    /// 单个房间最多容纳的 Viewer 数 (None = 不限制)
+    pub max_viewers_per_room: Option<usize>,
+    /// 信令服务器同时存在的房间数上限 (None = 不限制)
+    pub max_rooms: Option<usize>,
+}
+
+impl Default for CapacityConfig {
+    fn default() -> Self {
+        Self {
+            max_sessions: None,
+            queue_enabled: false,
+            max_queue_length: 10,
+            max_viewers_per_room: None,
+            max_rooms: None,
+        }
+    }
+}
+
+/// 准入结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Admission {
+    /// 立即放行
+    Admitted,
+    /// 进入等待队列 (位置从 1 开始)
+    Queued { position: usize },
+    /// 已满且不排队
+    Rejected,
+}
+
+/// 准入控制器
+#[derive(Debug, Default)]
+pub struct AdmissionControl {
+    config: CapacityConfig,
+    active: HashSet<String>,
+    /// 等待中的 (peer_id, room_id)
+    queue: VecDeque<(String, String)>,
+}
+
+impl AdmissionControl {
+    pub fn new(config: CapacityConfig) -> Self {
+        Self {
+            config,
+            active: HashSet::new(),
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// 更新容量配置 (不影响已放行的会话)
+    pub fn set_config(&mut self, config: CapacityConfig) {
+        self.config = config;
+    }
+
+    pub fn config(&self) -> &CapacityConfig {
+        &self.config
+    }
+
+    fn has_capacity(&self) -> bool {
+        self.config
+            .max_sessions
+            .is_none_or(|max| self.active.len() < max)
+    }
+
+    /// Viewer 请求加入房间
+    pub fn request(&mut self, peer_id: &str, room_id: &str) -> Admission {
+        if self.active.contains(peer_id) {
+            return Admission::Admitted;
+        }
+        if let Some(position) = self.position(peer_id) {
+            return Admission::Queued { position };
+        }
+
+        // 有人排队时新来的不能插队
+        if self.queue.is_empty() && self.has_capacity() {
+            self.active.insert(peer_id.to_string());
+            return Admission::Admitted;
+        }
+
+        if self.config.queue_enabled && self.queue.len() < self.config.max_queue_length {
+            self.queue.push_back((peer_id.to_string(), room_id.to_string()));
+            return Admission::Queued { position: self.queue.len() };
+        }
+
+        Admission::Rejected
+    }
+
+    /// Viewer 断开 (无论是否已放行)
+    ///
+    /// 返回因此被放行的排队者 (peer_id, room_id)
+    pub fn release(&mut self, peer_id: &str) -> Vec<(String, String)> {
+        if !self.active.remove(peer_id) {
+            self.queue.retain(|(id, _)| id != peer_id);
+        }
+
+        let mut admitted = Vec::new();
+        while self.has_capacity() {
+            let Some((next, room_id)) = self.queue.pop_front() else {
+                break;
+            };
+            self.active.insert(next.clone());
+            admitted.push((next, room_id));
+        }
+        admitted
+    }
+
+    /// 是否已放行
+    pub fn is_active(&self, peer_id: &str) -> bool {
+        self.active.contains(peer_id)
+    }
+
+    /// 排队位置 (从 1 开始)
+    pub fn position(&self, peer_id: &str) -> Option<usize> {
+        self.queue.iter().position(|(id, _)| id == peer_id).map(|i| i + 1)
+    }
+
+    /// 当前所有排队者及其位置
+    pub fn queue_positions(&self) -> Vec<(String, usize)> {
+        self.queue
+            .iter()
+            .enumerate()
+            .map(|(i, (id, _))| (id.clone(), i + 1))
+            .collect()
+    }
+
+    pub fn active_count(&self) -> usize {
+        self.active.len()
+    }
+
+    pub fn queue_len(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limited(max: usize, queue: bool) -> AdmissionControl {
+        AdmissionControl::new(CapacityConfig {
+            max_sessions: Some(max),
+            queue_enabled: queue,
+            max_queue_length: 2,
+            max_viewers_per_room: None,
+            max_rooms: None,
+        })
+    }
+
+    #[test]
+    fn test_unlimited_by_default() {
+        let mut control = AdmissionControl::default();
+        for i in 0..100 {
+            assert_eq!(control.request(&format!("viewer_{}", i), "room"), Admission::Admitted);
+        }
+        assert_eq!(control.active_count(), 100);
+    }
+
+    #[test]
+    fn test_reject_without_queue() {
+        let mut control = limited(1, false);
+        assert_eq!(control.request("a", "room"), Admission::Admitted);
+        assert_eq!(control.request("b", "room"), Admission::Rejected);
+
+        assert!(control.release("a").is_empty());
+        assert_eq!(control.request("b", "room"), Admission::Admitted);
+    }
+
+    #[test]
+    fn test_queue_order_and_limit() {
+        let mut control = limited(1, true);
+        assert_eq!(control.request("a", "room"), Admission::Admitted);
+        assert_eq!(control.request("b", "room"), Admission::Queued { position: 1 });
+        assert_eq!(control.request("c", "room"), Admission::Queued { position: 2 });
+        assert_eq!(control.request("d", "room"), Admission::Rejected);
+
+        // a 断开后 b 被放行，c 前移
+        assert_eq!(control.release("a"), vec![("b".to_string(), "room".to_string())]);
+        assert!(control.is_active("b"));
+        assert_eq!(control.queue_positions(), vec![("c".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_queued_viewer_leaves() {
+        let mut control = limited(1, true);
+        control.request("a", "room");
+        control.request("b", "room");
+        control.request("c", "room");
+
+        assert!(control.release("b").is_empty());
+        assert_eq!(control.position("c"), Some(1));
+        assert_eq!(control.active_count(), 1);
+    }
+}
@@ -0,0 +1,290 @@
+//! 连接审批
+//!
+//! Viewer 发起会话前由 Host 端的人确认:
+//! - prompt: 控制台提示，输入 y 允许 / v 仅查看 / n 拒绝
+//! - desktop: 弹出桌面对话框 (macOS osascript / Linux zenity / Windows WScript)，
+//!   对话框不可用时退回控制台提示
+//! - auto: 自动允许 (无人值守)
+//!
+//! 超时未作答一律拒绝
+//!
+//! 审批提示以外的控制台输入作为被控端命令，由 `take_console_commands` 取走
+
+#![allow(dead_code)]
+
+use super::permissions::SessionPermissions;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use tracing::warn;
+
+/// 审批方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalMode {
+    /// 控制台提示
+    #[default]
+    Prompt,
+    /// 桌面对话框
+    Desktop,
+    /// 自动允许
+    Auto,
+}
+
+/// 审批结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApprovalDecision {
+    /// 允许，并授予给定权限
+    Accept(SessionPermissions),
+    /// 拒绝 (含超时)
+    Reject,
+}
+
+/// 连接审批器
+pub struct ConnectionApprover {
+    mode: ApprovalMode,
+    timeout: Duration,
+}
+
+impl ConnectionApprover {
+    pub fn new(mode: ApprovalMode, timeout: Duration) -> Self {
+        if mode != ApprovalMode::Auto {
+            console();
+        }
+        Self { mode, timeout }
+    }
+
+    pub fn mode(&self) -> ApprovalMode {
+        self.mode
+    }
+
+    /// 请求审批 (permissions 为允许时默认授予的权限)
+    pub async fn request(&self, peer_id: &str, permissions: SessionPermissions) -> ApprovalDecision {
+        match self.mode {
+            ApprovalMode::Auto => ApprovalDecision::Accept(permissions),
+            ApprovalMode::Desktop => match self.ask_desktop(peer_id, permissions).await {
+                Some(decision) => decision,
+                None => self.ask_console(peer_id, permissions).await,
+            },
+            ApprovalMode::Prompt => self.ask_console(peer_id, permissions).await,
+        }
+    }
+
+    async fn ask_console(&self, peer_id: &str, permissions: SessionPermissions) -> ApprovalDecision {
+        let console = console();
+        let mut lines = console.answers.lock().await;
+        // 丢弃上一次提示超时后才输入的内容
+        while lines.try_recv().is_ok() {}
+        let _prompting = Prompting::start(console);
+
+        print!(
+            "  [?] 允许 {} 连接? [y] 允许 ({}) / [v] 仅查看 / [N] 拒绝 ({} 秒后自动拒绝): ",
+            peer_id,
+            permissions,
+            self.timeout.as_secs()
+        );
+        let _ = std::io::stdout().flush();
+
+        match tokio::time::timeout(self.timeout, lines.recv()).await {
+            Ok(Some(line)) => parse_answer(&line, permissions),
+            Ok(None) => {
+                warn!("控制台输入不可用，拒绝连接: {}", peer_id);
+                ApprovalDecision::Reject
+            }
+            Err(_) => {
+                println!();
+                println!("  [!] 审批超时，已拒绝 {}", peer_id);
+                ApprovalDecision::Reject
+            }
+        }
+    }
+
+    /// 桌面对话框 (None = 对话框不可用)
+    async fn ask_desktop(&self, peer_id: &str, permissions: SessionPermissions) -> Option<ApprovalDecision> {
+        let text = format!("Viewer {} 请求远程连接本机 (权限: {})", peer_id, permissions);
+        let mut command = dialog_command(&text, self.timeout)?;
+        command.kill_on_drop(true);
+
+        // 对话框自身的超时之外再留出余量，防止外部程序卡住
+        let output = tokio::time::timeout(self.timeout + Duration::from_secs(5), command.output()).await;
+        match output {
+            Ok(Ok(output)) => Some(parse_dialog_output(
+                output.status.code(),
+                &String::from_utf8_lossy(&output.stdout),
+                permissions,
+            )),
+            Ok(Err(e)) => {
+                warn!("无法弹出桌面对话框 ({})，改用控制台提示", e);
+                None
+            }
+            Err(_) => Some(ApprovalDecision::Reject),
+        }
+    }
+}
+
+/// 解析控制台回答 (未识别的输入按拒绝处理)
+fn parse_answer(line: &str, permissions: SessionPermissions) -> ApprovalDecision {
+    match line.trim().to_lowercase().as_str() {
+        "y" | "yes" => ApprovalDecision::Accept(permissions),
+        "v" | "view" => ApprovalDecision::Accept(SessionPermissions::view_only()),
+        _ => ApprovalDecision::Reject,
+    }
+}
+
+/// 控制台输入
+///
+/// stdin 的阻塞读无法取消，因此整个进程只用一个读取线程，超时的提示不会遗留读取。
+/// 审批提示期间的输入作为回答，其余输入作为命令
+struct Console {
+    answers: Mutex<mpsc::UnboundedReceiver<String>>,
+    commands: std::sync::Mutex<Option<mpsc::UnboundedReceiver<String>>>,
+    prompting: AtomicBool,
+}
+
+/// 首次调用时启动 stdin 读取线程
+fn console() -> &'static Console {
+    static CONSOLE: OnceLock<Console> = OnceLock::new();
+    CONSOLE.get_or_init(|| {
+        let (answer_tx, answers) = mpsc::unbounded_channel();
+        let (command_tx, commands) = mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                let tx = if console().prompting.load(Ordering::Acquire) { &answer_tx } else { &command_tx };
+                // 命令接收器未被取走或已关闭时丢弃
+                let _ = tx.send(line);
+            }
+        });
+        Console {
+            answers: Mutex::new(answers),
+            commands: std::sync::Mutex::new(Some(commands)),
+            prompting: AtomicBool::new(false),
+        }
+    })
+}
+
+/// 审批提示期间把控制台输入当作回答
+struct Prompting(&'static Console);
+
+impl Prompting {
+    fn start(console: &'static Console) -> Self {
+        console.prompting.store(true, Ordering::Release);
+        Self(console)
+    }
+}
+
+impl Drop for Prompting {
+    fn drop(&mut self) {
+        self.0.prompting.store(false, Ordering::Release);
+    }
+}
+
+/// 取走控制台命令 (审批提示以外的输入行，只能取一次)
+pub fn take_console_commands() -> Option<mpsc::UnboundedReceiver<String>> {
+    console().commands.lock().ok()?.take()
+}
+
+/// 构造平台对应的对话框命令
+fn dialog_command(text: &str, timeout: Duration) -> Option<tokio::process::Command> {
+    let secs = timeout.as_secs().max(1);
+
+    #[cfg(target_os = "macos")]
+    {
+        let script = format!(
+            "display dialog \"{}\" with title \"sscontrol\" buttons {{\"拒绝\", \"仅查看\", \"允许\"}} default button \"拒绝\" giving up after {}",
+            text.replace('"', "'"),
+            secs
+        );
+        let mut command = tokio::process::Command::new("osascript");
+        command.args(["-e", &script]);
+        Some(command)
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // Popup 返回值: 6 = 是, 7 = 否, -1 = 超时
+        let script = format!(
+            "exit (New-Object -ComObject WScript.Shell).Popup('{}', {}, 'sscontrol', 0x24)",
+            text.replace('\'', "''"),
+            secs
+        );
+        let mut command = tokio::process::Command::new("powershell");
+        command.args(["-NoProfile", "-Command", &script]);
+        Some(command)
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let mut command = tokio::process::Command::new("zenity");
+        command.args([
+            "--question",
+            "--title=sscontrol",
+            &format!("--text={}", text),
+            "--ok-label=允许",
+            "--cancel-label=拒绝",
+            &format!("--timeout={}", secs),
+        ]);
+        Some(command)
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    {
+        let _ = (text, secs);
+        None
+    }
+}
+
+/// 解析对话框结果
+fn parse_dialog_output(code: Option<i32>, stdout: &str, permissions: SessionPermissions) -> ApprovalDecision {
+    if cfg!(target_os = "macos") {
+        // 输出形如 "button returned:允许, gave up:false"
+        if stdout.contains("gave up:true") {
+            ApprovalDecision::Reject
+        } else if stdout.contains("button returned:允许") {
+            ApprovalDecision::Accept(permissions)
+        } else if stdout.contains("button returned:仅查看") {
+            ApprovalDecision::Accept(SessionPermissions::view_only())
+        } else {
+            ApprovalDecision::Reject
+        }
+    } else if cfg!(target_os = "windows") {
+        match code {
+            Some(6) => ApprovalDecision::Accept(permissions),
+            _ => ApprovalDecision::Reject,
+        }
+    } else {
+        // zenity: 0 = 允许, 1 = 拒绝, 5 = 超时
+        match code {
+            Some(0) => ApprovalDecision::Accept(permissions),
+            _ => ApprovalDecision::Reject,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_answer() {
+        let full = SessionPermissions::full();
+        assert_eq!(parse_answer("y\n", full), ApprovalDecision::Accept(full));
+        assert_eq!(parse_answer(" YES ", full), ApprovalDecision::Accept(full));
+        assert_eq!(
+            parse_answer("v", full),
+            ApprovalDecision::Accept(SessionPermissions::view_only())
+        );
+        assert_eq!(parse_answer("", full), ApprovalDecision::Reject);
+        assert_eq!(parse_answer("n", full), ApprovalDecision::Reject);
+    }
+
+    #[tokio::test]
+    async fn test_auto_approval() {
+        let approver = ConnectionApprover::new(ApprovalMode::Auto, Duration::from_secs(1));
+        let perms = SessionPermissions::view_only();
+        assert_eq!(approver.request("viewer_0", perms).await, ApprovalDecision::Accept(perms));
+    }
+}
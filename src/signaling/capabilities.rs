@@ -0,0 +1,105 @@
+//! Host 能力声明
+//!
+//! 内嵌信令服务器通过 `/capabilities` 公开协议版本和已启用的功能，
+//! 控制端在建立会话前即可判断两端是否兼容 (见 `sscontrol probe`)
+
+use serde::{Deserialize, Serialize};
+
+/// 信令协议版本
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// 仍能互通的最低协议版本
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// 功能开关
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostFeatures {
+    pub webrtc: bool,
+    pub h264: bool,
+    pub audio: bool,
+    pub file_transfer: bool,
+    pub security: bool,
+}
+
+/// Host 能力
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostCapabilities {
+    /// sscontrol 版本
+    pub version: String,
+    /// 信令协议版本
+    pub protocol_version: u32,
+    /// 可兼容的最低协议版本
+    pub min_protocol_version: u32,
+    pub features: HostFeatures,
+    /// 支持的视频 codec
+    pub codecs: Vec<String>,
+    /// 连接是否需要认证
+    pub auth_required: bool,
+}
+
+impl HostCapabilities {
+    /// 本机编译时启用的能力
+    pub fn local() -> Self {
+        // 视频经 WebRTC 发送，没有 WebRTC 时不提供任何 codec
+        let codecs = if cfg!(feature = "webrtc") {
+            crate::encoder::compiled_codecs().into_iter().map(str::to_string).collect()
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            features: HostFeatures {
+                webrtc: cfg!(feature = "webrtc"),
+                h264: cfg!(feature = "h264"),
+                audio: false,
+                file_transfer: true,
+                security: cfg!(feature = "security"),
+            },
+            codecs,
+            auth_required: false,
+        }
+    }
+
+    /// 与本机协议是否兼容 (双方支持的版本区间有交集)
+    pub fn is_compatible(&self) -> bool {
+        self.min_protocol_version <= PROTOCOL_VERSION && self.protocol_version >= MIN_PROTOCOL_VERSION
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_capabilities_compatible() {
+        let caps = HostCapabilities::local();
+        assert!(caps.is_compatible());
+        assert_eq!(caps.features.webrtc, cfg!(feature = "webrtc"));
+        assert_eq!(
+            caps.codecs.contains(&"VP9".to_string()),
+            cfg!(all(feature = "webrtc", feature = "h264"))
+        );
+    }
+
+    #[test]
+    fn test_protocol_compatibility() {
+        let mut caps = HostCapabilities::local();
+
+        // 对端更新但仍兼容旧协议
+        caps.protocol_version = PROTOCOL_VERSION + 1;
+        caps.min_protocol_version = PROTOCOL_VERSION;
+        assert!(caps.is_compatible());
+
+        // 对端要求的最低版本高于本机
+        caps.min_protocol_version = PROTOCOL_VERSION + 1;
+        assert!(!caps.is_compatible());
+
+        // 对端版本过旧
+        caps.protocol_version = MIN_PROTOCOL_VERSION - 1;
+        caps.min_protocol_version = 0;
+        assert!(!caps.is_compatible());
+    }
+}
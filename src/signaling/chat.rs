@@ -0,0 +1,131 @@
+//! 文字聊天
+//!
+//! 被控端操作者与控制端之间互发文字消息 (如远程协助时说明操作):
+//! - WebRTC 控制端通过 `chat` 数据通道收发
+//! - Web 查看器通过信令服务器的 `/chat` 端点轮询和发送
+//! - 被控端在控制台输入 `say <内容>` 发送，收到的消息打印到控制台
+//!
+//! 所有消息记录在信令服务器的 `ChatLog` 中并分配递增 ID，Web 查看器按 ID 增量拉取；
+//! 一方发出的消息会转发给其他所有控制端
+
+// 数据通道只在 webrtc feature 下可用
+#![cfg_attr(not(feature = "webrtc"), allow(dead_code))]
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// 聊天消息使用的数据通道标签 (不需要额外权限)
+pub const CHAT_CHANNEL: &str = "chat";
+
+/// 被控端操作者发出的消息的发送者
+pub const HOST_SENDER: &str = "host";
+
+/// 单条消息的最大长度 (字符)，超出部分截断
+const MAX_TEXT_CHARS: usize = 2000;
+
+/// 保留的历史消息条数
+const HISTORY_LEN: usize = 200;
+
+/// 控制端发送的消息 (数据通道和 `POST /chat` 的 JSON)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatPost {
+    pub text: String,
+}
+
+/// 已记录的聊天消息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    /// 递增 ID (从 1 开始)
+    pub id: u64,
+    /// 发送者: `host` 或控制端的 peer_id
+    pub from: String,
+    pub text: String,
+    /// 发送时间 (Unix 毫秒)
+    pub time_ms: u64,
+}
+
+/// 聊天记录
+#[derive(Debug)]
+pub struct ChatLog {
+    messages: VecDeque<ChatMessage>,
+    next_id: u64,
+}
+
+impl Default for ChatLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChatLog {
+    pub fn new() -> Self {
+        Self {
+            messages: VecDeque::new(),
+            next_id: 1,
+        }
+    }
+
+    /// 记录一条消息 (去掉首尾空白，超长截断)，内容为空时返回 None
+    pub fn post(&mut self, from: &str, text: &str) -> Option<ChatMessage> {
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        let message = ChatMessage {
+            id: self.next_id,
+            from: from.to_string(),
+            text: text.chars().take(MAX_TEXT_CHARS).collect(),
+            time_ms: chrono::Utc::now().timestamp_millis().max(0) as u64,
+        };
+        self.next_id += 1;
+
+        if self.messages.len() == HISTORY_LEN {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message.clone());
+        Some(message)
+    }
+
+    /// ID 大于 after 的消息 (after 为 0 时返回全部历史)
+    pub fn since(&self, after: u64) -> Vec<ChatMessage> {
+        self.messages.iter().filter(|m| m.id > after).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_post_and_since() {
+        let mut log = ChatLog::new();
+        assert_eq!(log.post("viewer_0", "   "), None);
+
+        let first = log.post(HOST_SENDER, "  你好  ").unwrap();
+        assert_eq!((first.id, first.text.as_str()), (1, "你好"));
+        let second = log.post("viewer_0", "请打开设置").unwrap();
+        assert_eq!(second.id, 2);
+
+        assert_eq!(log.since(0), vec![first, second.clone()]);
+        assert_eq!(log.since(1), vec![second]);
+        assert!(log.since(2).is_empty());
+
+        let long = "字".repeat(MAX_TEXT_CHARS + 10);
+        assert_eq!(log.post("viewer_0", &long).unwrap().text.chars().count(), MAX_TEXT_CHARS);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut log = ChatLog::new();
+        for i in 0..HISTORY_LEN + 5 {
+            log.post(HOST_SENDER, &i.to_string());
+        }
+        let history = log.since(0);
+        assert_eq!(history.len(), HISTORY_LEN);
+        assert_eq!(history[0].id, 6);
+        assert_eq!(history.last().unwrap().id as usize, HISTORY_LEN + 5);
+
+        let json = serde_json::to_string(&ChatPost { text: "hi".to_string() }).unwrap();
+        assert_eq!(json, r#"{"text":"hi"}"#);
+    }
+}
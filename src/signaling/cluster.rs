@@ -0,0 +1,344 @@
+//! 多实例部署 (Redis pub/sub)
+//!
+//! 启用 redis feature 并指定 Redis 地址后，多个信令服务器实例可以部署在负载均衡之后，
+//! 共享房间状态:
+//! - 每个实例启动时生成节点 ID，Viewer 的 peer_id 带节点前缀 (`<node>.viewer_<n>`)，全局唯一
+//! - 本实例的房间成员写入 Redis 哈希 `sscontrol:members:<node>` (peer_id -> room_id) 并定期续期，
+//!   实例崩溃后其成员随键过期清除
+//! - 成员变更和房间广播发布到频道 `sscontrol:cluster`，发给其他实例上 Viewer 的消息
+//!   发布到该实例的频道 `sscontrol:node:<node>`
+//! - 各实例在内存中缓存其他实例的成员 ([`RemoteMembers`])，房间人数/房间数上限和成员列表
+//!   按全部实例计算
+//!
+//! WebSocket 连接、PIN/TOTP 验证和待恢复的会话仍保存在各实例内存中，
+//! 断线恢复需要负载均衡把 Viewer 路由回原实例 (粘性会话)。未启用时只使用内存状态
+
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// 成员变更和房间广播频道
+const CLUSTER_CHANNEL: &str = "sscontrol:cluster";
+/// 成员哈希的过期时间 (实例崩溃后成员保留的最长时间)
+const MEMBERS_TTL: Duration = Duration::from_secs(30);
+/// 续期并同步其他实例成员的间隔
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+fn node_channel(node_id: &str) -> String {
+    format!("sscontrol:node:{}", node_id)
+}
+
+fn members_key(node_id: &str) -> String {
+    format!("sscontrol:members:{}", node_id)
+}
+
+/// 实例之间传递的消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Envelope {
+    /// Viewer 加入房间
+    Join { node: String, peer_id: String, room_id: String },
+    /// Viewer 离开房间
+    Leave { node: String, peer_id: String },
+    /// 发给指定 Viewer 的信令
+    Deliver { to: String, msg: String },
+    /// 房间广播
+    Broadcast { node: String, room_id: String, msg: String, exclude: Option<String> },
+}
+
+/// 其他实例发来的事件 (由信令服务器应用到本地状态)
+#[derive(Debug, Clone)]
+pub enum ClusterEvent {
+    Joined { node: String, peer_id: String, room_id: String },
+    Left { peer_id: String },
+    /// 投递给本实例上的 Viewer
+    Deliver { to: String, msg: String },
+    /// 投递给本实例上该房间的成员
+    Broadcast { room_id: String, msg: String, exclude: Option<String> },
+    /// 定期从 Redis 读取的其他实例全部成员 (peer_id -> (节点, 房间))
+    Snapshot(HashMap<String, (String, String)>),
+}
+
+/// 集群句柄: 同步方法只把消息放入队列，由后台任务写入 Redis
+#[derive(Debug, Clone)]
+pub struct ClusterHandle {
+    node_id: String,
+    outbox: mpsc::UnboundedSender<Envelope>,
+}
+
+impl ClusterHandle {
+    /// 连接 Redis，启动发布、订阅和续期任务
+    pub async fn connect(url: &str) -> Result<(Self, mpsc::UnboundedReceiver<ClusterEvent>)> {
+        let client = redis::Client::open(url).context("无效的 Redis 地址")?;
+        let conn = client
+            .get_multiplexed_tokio_connection()
+            .await
+            .context("连接 Redis 失败")?;
+        let mut pubsub = client.get_async_pubsub().await.context("连接 Redis 失败")?;
+
+        let node_id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+        pubsub.subscribe(CLUSTER_CHANNEL).await?;
+        pubsub.subscribe(node_channel(&node_id)).await?;
+
+        let (outbox, outbox_rx) = mpsc::unbounded_channel();
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(publish_loop(node_id.clone(), conn.clone(), outbox_rx));
+        tokio::spawn(heartbeat_loop(node_id.clone(), conn, event_tx.clone()));
+
+        let self_node = node_id.clone();
+        tokio::spawn(async move {
+            let mut messages = pubsub.on_message();
+            while let Some(message) = messages.next().await {
+                let Ok(payload) = message.get_payload::<String>() else {
+                    continue;
+                };
+                let Ok(envelope) = serde_json::from_str::<Envelope>(&payload) else {
+                    tracing::debug!("忽略无法解析的集群消息");
+                    continue;
+                };
+                if let Some(event) = into_event(envelope, &self_node) {
+                    if event_tx.send(event).is_err() {
+                        break;
+                    }
+                }
+            }
+            tracing::warn!("Redis 订阅已断开，不再接收其他实例的消息");
+        });
+
+        tracing::info!("已加入信令集群 (节点 {})", node_id);
+        Ok((Self { node_id, outbox }, event_rx))
+    }
+
+    /// 本实例上新连接的 peer_id
+    pub fn peer_id(&self, counter: u64) -> String {
+        format!("{}.viewer_{}", self.node_id, counter)
+    }
+
+    /// peer_id 所在实例 (不带节点前缀时为 None)
+    pub fn node_of(peer_id: &str) -> Option<&str> {
+        peer_id.split_once('.').map(|(node, _)| node)
+    }
+
+    /// 是否是其他实例上的 Viewer
+    pub fn is_remote(&self, peer_id: &str) -> bool {
+        Self::node_of(peer_id).is_some_and(|node| node != self.node_id)
+    }
+
+    pub fn joined(&self, peer_id: &str, room_id: &str) {
+        let _ = self.outbox.send(Envelope::Join {
+            node: self.node_id.clone(),
+            peer_id: peer_id.to_string(),
+            room_id: room_id.to_string(),
+        });
+    }
+
+    pub fn left(&self, peer_id: &str) {
+        let _ = self.outbox.send(Envelope::Leave {
+            node: self.node_id.clone(),
+            peer_id: peer_id.to_string(),
+        });
+    }
+
+    /// 发给其他实例上的 Viewer
+    pub fn deliver(&self, to: &str, msg: &str) {
+        let _ = self.outbox.send(Envelope::Deliver {
+            to: to.to_string(),
+            msg: msg.to_string(),
+        });
+    }
+
+    /// 广播给其他实例上的房间成员
+    pub fn broadcast(&self, room_id: &str, msg: &str, exclude: Option<&str>) {
+        let _ = self.outbox.send(Envelope::Broadcast {
+            node: self.node_id.clone(),
+            room_id: room_id.to_string(),
+            msg: msg.to_string(),
+            exclude: exclude.map(str::to_string),
+        });
+    }
+}
+
+/// 忽略本实例自己发布的消息
+fn into_event(envelope: Envelope, self_node: &str) -> Option<ClusterEvent> {
+    match envelope {
+        Envelope::Join { node, .. } | Envelope::Leave { node, .. } | Envelope::Broadcast { node, .. }
+            if node == self_node =>
+        {
+            None
+        }
+        Envelope::Join { node, peer_id, room_id } => Some(ClusterEvent::Joined { node, peer_id, room_id }),
+        Envelope::Leave { peer_id, .. } => Some(ClusterEvent::Left { peer_id }),
+        Envelope::Deliver { to, msg } => Some(ClusterEvent::Deliver { to, msg }),
+        Envelope::Broadcast { room_id, msg, exclude, .. } => Some(ClusterEvent::Broadcast { room_id, msg, exclude }),
+    }
+}
+
+/// 把队列中的消息写入 Redis: 成员变更同时更新本实例的成员哈希
+async fn publish_loop(
+    node_id: String,
+    mut conn: redis::aio::MultiplexedConnection,
+    mut outbox: mpsc::UnboundedReceiver<Envelope>,
+) {
+    let key = members_key(&node_id);
+    while let Some(envelope) = outbox.recv().await {
+        let result: redis::RedisResult<()> = async {
+            match &envelope {
+                Envelope::Join { peer_id, room_id, .. } => {
+                    conn.hset::<_, _, _, ()>(&key, peer_id, room_id).await?;
+                    conn.expire::<_, ()>(&key, MEMBERS_TTL.as_secs() as i64).await?;
+                }
+                Envelope::Leave { peer_id, .. } => {
+                    conn.hdel::<_, _, ()>(&key, peer_id).await?;
+                }
+                _ => {}
+            }
+            let channel = match &envelope {
+                Envelope::Deliver { to, .. } => match ClusterHandle::node_of(to) {
+                    Some(node) => node_channel(node),
+                    None => return Ok(()),
+                },
+                _ => CLUSTER_CHANNEL.to_string(),
+            };
+            let payload = serde_json::to_string(&envelope).unwrap_or_default();
+            conn.publish::<_, _, ()>(channel, payload).await
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::warn!("发布集群消息失败: {}", e);
+        }
+    }
+}
+
+/// 定期续期本实例的成员哈希，并读取其他实例的全部成员
+///
+/// 订阅期间错过的变更 (或已崩溃实例留下的成员) 在下一次同步时纠正
+async fn heartbeat_loop(
+    node_id: String,
+    mut conn: redis::aio::MultiplexedConnection,
+    events: mpsc::UnboundedSender<ClusterEvent>,
+) {
+    let own_key = members_key(&node_id);
+    let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        interval.tick().await;
+        let result: redis::RedisResult<HashMap<String, (String, String)>> = async {
+            conn.expire::<_, ()>(&own_key, MEMBERS_TTL.as_secs() as i64).await?;
+
+            let mut keys = Vec::new();
+            {
+                let mut iter = conn.scan_match::<_, String>(members_key("*")).await?;
+                while let Some(key) = iter.next_item().await {
+                    if key != own_key {
+                        keys.push(key);
+                    }
+                }
+            }
+
+            let mut members = HashMap::new();
+            for key in keys {
+                let node = key.trim_start_matches(&members_key("")).to_string();
+                let entries: HashMap<String, String> = conn.hgetall(&key).await?;
+                for (peer_id, room_id) in entries {
+                    members.insert(peer_id, (node.clone(), room_id));
+                }
+            }
+            Ok(members)
+        }
+        .await;
+
+        match result {
+            Ok(members) => {
+                if events.send(ClusterEvent::Snapshot(members)).is_err() {
+                    break;
+                }
+            }
+            Err(e) => tracing::warn!("同步集群成员失败: {}", e),
+        }
+    }
+}
+
+/// 其他实例上的房间成员 (本地缓存)
+#[derive(Debug, Default)]
+pub struct RemoteMembers {
+    /// peer_id -> (节点, 房间)
+    members: HashMap<String, (String, String)>,
+}
+
+impl RemoteMembers {
+    pub fn apply(&mut self, event: &ClusterEvent) {
+        match event {
+            ClusterEvent::Joined { node, peer_id, room_id } => {
+                self.members.insert(peer_id.clone(), (node.clone(), room_id.clone()));
+            }
+            ClusterEvent::Left { peer_id } => {
+                self.members.remove(peer_id);
+            }
+            ClusterEvent::Snapshot(members) => {
+                self.members = members.clone();
+            }
+            ClusterEvent::Deliver { .. } | ClusterEvent::Broadcast { .. } => {}
+        }
+    }
+
+    /// 房间中其他实例上的成员
+    pub fn room_members(&self, room_id: &str) -> Vec<String> {
+        self.members
+            .iter()
+            .filter(|(_, (_, room))| room == room_id)
+            .map(|(peer_id, _)| peer_id.clone())
+            .collect()
+    }
+
+    /// 其他实例上有成员的房间
+    pub fn rooms(&self) -> HashSet<&str> {
+        self.members.values().map(|(_, room)| room.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_members_follow_events() {
+        let mut remote = RemoteMembers::default();
+        remote.apply(&ClusterEvent::Joined {
+            node: "b".to_string(),
+            peer_id: "b.viewer_0".to_string(),
+            room_id: "room".to_string(),
+        });
+        remote.apply(&ClusterEvent::Joined {
+            node: "c".to_string(),
+            peer_id: "c.viewer_0".to_string(),
+            room_id: "other".to_string(),
+        });
+        assert_eq!(remote.room_members("room"), vec!["b.viewer_0".to_string()]);
+        assert_eq!(remote.rooms().len(), 2);
+
+        remote.apply(&ClusterEvent::Left { peer_id: "b.viewer_0".to_string() });
+        assert!(remote.room_members("room").is_empty());
+
+        // 快照整体替换缓存
+        remote.apply(&ClusterEvent::Snapshot(HashMap::new()));
+        assert!(remote.rooms().is_empty());
+    }
+
+    #[test]
+    fn test_own_messages_are_ignored() {
+        let join = Envelope::Join {
+            node: "a".to_string(),
+            peer_id: "a.viewer_0".to_string(),
+            room_id: "room".to_string(),
+        };
+        assert!(into_event(join.clone(), "a").is_none());
+        assert!(matches!(into_event(join, "b"), Some(ClusterEvent::Joined { .. })));
+
+        assert_eq!(ClusterHandle::node_of("a.viewer_3"), Some("a"));
+        assert_eq!(ClusterHandle::node_of("viewer_3"), None);
+    }
+}
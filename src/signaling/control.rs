@@ -0,0 +1,350 @@
+//! 输入控制权仲裁
+//!
+//! 多个 Viewer 同时连接时只有持有控制权的一方可以操作鼠标键盘，其余 Viewer 仅观看:
+//! - 无人持有时，第一个发送输入或申请控制权的 Viewer 自动获得
+//! - 其他 Viewer 申请时转交给当前控制者决定 (允许/拒绝)；控制者超过 `IDLE_HANDOFF`
+//!   没有操作时直接移交
+//! - 控制者放弃或断开后，交给最早申请的 Viewer
+//! - 被控端可随时收回控制权，收回期间所有 Viewer 的输入都被丢弃，直到交还
+//!
+//! 控制权按会话 ID 记录 (断线重连后保持)。申请、移交等消息通过 `control` 数据通道交换，
+//! 每次控制权变化都会向所有 Viewer 推送 `state`，供界面显示当前控制者 (Viewer 打开通道后
+//! 发送 `status` 获取初始状态)
+
+// 控制权消息只通过 WebRTC 数据通道交换 (QUIC 控制端只参与输入仲裁)
+#![cfg_attr(not(feature = "webrtc"), allow(dead_code))]
+
+use super::timeout::ExpiryReason;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// 控制权消息使用的数据通道标签 (申请控制权需要输入权限)
+pub const CONTROL_CHANNEL: &str = "control";
+
+/// 控制者超过该时间没有操作时，其他 Viewer 的申请直接通过
+const IDLE_HANDOFF: Duration = Duration::from_secs(10);
+
+/// `control` 数据通道上的消息 (JSON)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ControlMessage {
+    /// Viewer: 查询当前状态 (通道打开后发送)
+    Status,
+    /// Viewer: 申请控制权
+    Request,
+    /// Viewer: 放弃控制权
+    Release,
+    /// 控制者: 把控制权交给申请者
+    Grant { viewer: String },
+    /// 控制者: 拒绝申请
+    Deny { viewer: String },
+    /// 被控端: 当前控制权状态 (holder 为控制者的会话 ID，host 为 true 表示被控端已收回)
+    State { holder: Option<String>, host: bool, has_control: bool },
+    /// 被控端 → 控制者: 有 Viewer 申请控制权
+    Requested { viewer: String },
+    /// 被控端 → 申请者: 申请被拒绝
+    Denied,
+    /// 被控端 → 所有 Viewer: 屏幕分辨率变化 (编码器已按新尺寸重建，下一帧为关键帧)
+    Resolution { width: u32, height: u32 },
+    /// 控制者: 开启/关闭隐私模式；被控端 → 所有 Viewer: 隐私模式状态
+    Privacy { enabled: bool },
+    /// 被控端 → 所有 Viewer: 捕获或编码异常，画面中断 (`retry_ms` 毫秒后重建，None 表示不再重试)
+    StreamInterrupted { reason: String, retry_ms: Option<u64> },
+    /// 被控端 → 所有 Viewer: 已重建捕获器和编码器，画面恢复 (下一帧为关键帧)
+    StreamResumed,
+    /// 被控端 → Viewer: 会话将在 `remaining_secs` 秒后因空闲或达到最长时长断开
+    SessionExpiring { reason: ExpiryReason, remaining_secs: u64 },
+    /// 被控端 → Viewer: 有了新的活动，取消空闲断开
+    SessionExtended,
+    /// 被控端 → Viewer: 会话已到期，随后关闭连接 (不应自动重连)
+    SessionEnded { reason: ExpiryReason },
+}
+
+/// 当前控制者
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlHolder {
+    /// 无人控制
+    Nobody,
+    /// 被控端收回了控制权
+    Host,
+    /// 持有控制权的 Viewer (会话 ID)
+    Viewer(String),
+}
+
+/// 需要发给各 Viewer 的消息: (会话 ID, 消息)
+pub type Outbox = Vec<(String, ControlMessage)>;
+
+/// 输入控制权仲裁器
+#[derive(Debug)]
+pub struct InputArbiter {
+    holder: ControlHolder,
+    /// 已连接的 Viewer (会话 ID)
+    viewers: Vec<String>,
+    /// 等待控制者答复的申请 (按申请顺序)
+    pending: Vec<String>,
+    /// 控制者最近一次操作的时间
+    last_input: Instant,
+}
+
+impl Default for InputArbiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputArbiter {
+    pub fn new() -> Self {
+        Self {
+            holder: ControlHolder::Nobody,
+            viewers: Vec::new(),
+            pending: Vec::new(),
+            last_input: Instant::now(),
+        }
+    }
+
+    pub fn holder(&self) -> &ControlHolder {
+        &self.holder
+    }
+
+    /// Viewer 加入 (或重连)，向其发送当前状态
+    pub fn join(&mut self, viewer: &str) -> Outbox {
+        if !self.viewers.iter().any(|v| v == viewer) {
+            self.viewers.push(viewer.to_string());
+        }
+        vec![(viewer.to_string(), self.state_for(viewer))]
+    }
+
+    /// Viewer 离开；控制者离开时交给下一个申请者
+    pub fn leave(&mut self, viewer: &str) -> Outbox {
+        self.viewers.retain(|v| v != viewer);
+        self.pending.retain(|v| v != viewer);
+        if self.holds(viewer) {
+            self.hand_to_next(Instant::now())
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Viewer 的输入是否可以注入 (无人控制时自动获得控制权)
+    pub fn allow_input(&mut self, viewer: &str) -> (bool, Outbox) {
+        self.allow_input_at(viewer, Instant::now())
+    }
+
+    fn allow_input_at(&mut self, viewer: &str, now: Instant) -> (bool, Outbox) {
+        match &self.holder {
+            ControlHolder::Viewer(holder) if holder == viewer => {
+                self.last_input = now;
+                (true, Vec::new())
+            }
+            ControlHolder::Nobody => (true, self.grant(viewer, now)),
+            _ => (false, Vec::new()),
+        }
+    }
+
+    /// 处理 Viewer 发来的控制权消息
+    pub fn handle(&mut self, viewer: &str, message: &ControlMessage) -> Outbox {
+        self.handle_at(viewer, message, Instant::now())
+    }
+
+    fn handle_at(&mut self, viewer: &str, message: &ControlMessage, now: Instant) -> Outbox {
+        match message {
+            ControlMessage::Status => vec![(viewer.to_string(), self.state_for(viewer))],
+            ControlMessage::Request => self.request(viewer, now),
+            ControlMessage::Release if self.holds(viewer) => self.hand_to_next(now),
+            ControlMessage::Grant { viewer: to } if self.holds(viewer) && self.pending.contains(to) => {
+                self.grant(to, now)
+            }
+            ControlMessage::Deny { viewer: to } if self.holds(viewer) && self.pending.contains(to) => {
+                self.pending.retain(|v| v != to);
+                vec![(to.clone(), ControlMessage::Denied)]
+            }
+            // 非控制者的放弃/移交，以及只应由被控端发出的消息
+            _ => Vec::new(),
+        }
+    }
+
+    /// 被控端收回控制权
+    pub fn take_host(&mut self) -> Outbox {
+        self.pending.clear();
+        self.holder = ControlHolder::Host;
+        self.broadcast()
+    }
+
+    /// 被控端交还控制权 (交给最早申请的 Viewer，没有则无人控制)
+    pub fn release_host(&mut self) -> Outbox {
+        if self.holder != ControlHolder::Host {
+            return Vec::new();
+        }
+        self.hand_to_next(Instant::now())
+    }
+
+    /// 该 Viewer 是否持有控制权
+    pub fn is_holder(&self, viewer: &str) -> bool {
+        self.holds(viewer)
+    }
+
+    /// 发给所有 Viewer 的消息
+    pub fn send_all(&self, message: ControlMessage) -> Outbox {
+        self.viewers.iter().map(|viewer| (viewer.clone(), message.clone())).collect()
+    }
+
+    fn holds(&self, viewer: &str) -> bool {
+        matches!(&self.holder, ControlHolder::Viewer(holder) if holder == viewer)
+    }
+
+    fn request(&mut self, viewer: &str, now: Instant) -> Outbox {
+        match &self.holder {
+            ControlHolder::Nobody => self.grant(viewer, now),
+            ControlHolder::Host => vec![(viewer.to_string(), ControlMessage::Denied)],
+            ControlHolder::Viewer(holder) if holder == viewer => vec![(viewer.to_string(), self.state_for(viewer))],
+            ControlHolder::Viewer(_) if now.saturating_duration_since(self.last_input) >= IDLE_HANDOFF => {
+                self.grant(viewer, now)
+            }
+            ControlHolder::Viewer(holder) => {
+                if self.pending.iter().any(|v| v == viewer) {
+                    return Vec::new();
+                }
+                self.pending.push(viewer.to_string());
+                vec![(holder.clone(), ControlMessage::Requested { viewer: viewer.to_string() })]
+            }
+        }
+    }
+
+    fn hand_to_next(&mut self, now: Instant) -> Outbox {
+        if self.pending.is_empty() {
+            self.holder = ControlHolder::Nobody;
+            return self.broadcast();
+        }
+        let next = self.pending.remove(0);
+        self.grant(&next, now)
+    }
+
+    fn grant(&mut self, viewer: &str, now: Instant) -> Outbox {
+        self.pending.retain(|v| v != viewer);
+        self.holder = ControlHolder::Viewer(viewer.to_string());
+        self.last_input = now;
+        self.broadcast()
+    }
+
+    fn broadcast(&self) -> Outbox {
+        self.viewers
+            .iter()
+            .map(|viewer| (viewer.clone(), self.state_for(viewer)))
+            .collect()
+    }
+
+    fn state_for(&self, viewer: &str) -> ControlMessage {
+        let holder = match &self.holder {
+            ControlHolder::Viewer(holder) => Some(holder.clone()),
+            _ => None,
+        };
+        ControlMessage::State {
+            has_control: holder.as_deref() == Some(viewer),
+            holder,
+            host: self.holder == ControlHolder::Host,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arbiter_with(viewers: &[&str]) -> InputArbiter {
+        let mut arbiter = InputArbiter::new();
+        for viewer in viewers {
+            arbiter.join(viewer);
+        }
+        arbiter
+    }
+
+    fn has_control(outbox: &Outbox, viewer: &str) -> Option<bool> {
+        outbox.iter().find_map(|(to, message)| match message {
+            ControlMessage::State { has_control, .. } if to == viewer => Some(*has_control),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_first_input_claims_control() {
+        let mut arbiter = arbiter_with(&["a", "b"]);
+        let now = Instant::now();
+
+        let (allowed, outbox) = arbiter.allow_input_at("a", now);
+        assert!(allowed);
+        assert_eq!(has_control(&outbox, "a"), Some(true));
+        assert_eq!(has_control(&outbox, "b"), Some(false));
+
+        let (allowed, outbox) = arbiter.allow_input_at("b", now);
+        assert!(!allowed, "其他 Viewer 的输入被丢弃");
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn test_request_grant_and_deny() {
+        let mut arbiter = arbiter_with(&["a", "b", "c"]);
+        let now = Instant::now();
+        arbiter.allow_input_at("a", now);
+
+        // 控制者在操作中，申请转交给控制者
+        let outbox = arbiter.handle_at("b", &ControlMessage::Request, now);
+        assert_eq!(outbox, vec![("a".to_string(), ControlMessage::Requested { viewer: "b".to_string() })]);
+        arbiter.handle_at("c", &ControlMessage::Request, now);
+
+        // 非控制者不能移交
+        assert!(arbiter.handle_at("c", &ControlMessage::Grant { viewer: "c".to_string() }, now).is_empty());
+
+        let outbox = arbiter.handle_at("a", &ControlMessage::Deny { viewer: "c".to_string() }, now);
+        assert_eq!(outbox, vec![("c".to_string(), ControlMessage::Denied)]);
+
+        let outbox = arbiter.handle_at("a", &ControlMessage::Grant { viewer: "b".to_string() }, now);
+        assert_eq!(has_control(&outbox, "b"), Some(true));
+        assert_eq!(arbiter.holder(), &ControlHolder::Viewer("b".to_string()));
+        assert!(!arbiter.allow_input_at("a", now).0);
+    }
+
+    #[test]
+    fn test_idle_holder_and_release_hand_off() {
+        let mut arbiter = arbiter_with(&["a", "b", "c"]);
+        let start = Instant::now();
+        arbiter.allow_input_at("a", start);
+
+        // 控制者空闲，申请直接通过
+        let later = start + IDLE_HANDOFF;
+        let outbox = arbiter.handle_at("b", &ControlMessage::Request, later);
+        assert_eq!(has_control(&outbox, "b"), Some(true));
+
+        // 放弃后交给排队的申请者；控制者断开时同样
+        arbiter.handle_at("c", &ControlMessage::Request, later);
+        arbiter.handle_at("b", &ControlMessage::Release, later);
+        assert_eq!(arbiter.holder(), &ControlHolder::Viewer("c".to_string()));
+        arbiter.leave("c");
+        assert_eq!(arbiter.holder(), &ControlHolder::Nobody);
+    }
+
+    #[test]
+    fn test_host_takes_control_back() {
+        let mut arbiter = arbiter_with(&["a", "b"]);
+        let now = Instant::now();
+        arbiter.allow_input_at("a", now);
+
+        let outbox = arbiter.take_host();
+        assert_eq!(outbox.len(), 2);
+        assert!(outbox.iter().all(|(_, message)| matches!(
+            message,
+            ControlMessage::State { host: true, has_control: false, holder: None }
+        )));
+        assert!(!arbiter.allow_input_at("a", now).0);
+        assert_eq!(
+            arbiter.handle_at("b", &ControlMessage::Request, now),
+            vec![("b".to_string(), ControlMessage::Denied)]
+        );
+
+        arbiter.release_host();
+        assert_eq!(arbiter.holder(), &ControlHolder::Nobody);
+        assert!(arbiter.allow_input_at("b", now).0);
+
+        let json = serde_json::to_string(&ControlMessage::Grant { viewer: "a".to_string() }).unwrap();
+        assert_eq!(json, r#"{"type":"grant","viewer":"a"}"#);
+    }
+}
@@ -1,6 +1,40 @@
 //! 内嵌信令服务器模块
 //!
 //! 使用 axum 实现，支持 HTTP 反向代理 (如 Cloudflare Tunnel)
+//!
+//! 启用 security feature 并设置认证提供者后，WebSocket 升级前会校验
+//! `Authorization` 头 (Bearer / Basic) 或 `?token=` 查询参数。
+//! 客户端首条消息为 `e2ee_hello` 时，后续消息均使用端到端加密帧传输
+//!
+//! Web 查看器通过 `GET /chat?after=<id>` 拉取聊天消息、`POST /chat` 发送 (见 `signaling::chat`)
+//!
+//! Web 查看器没有 WebRTC 连接时通过 `/video` (WebSocket) 接收 H.264 码流 (见 `signaling::video_stream`)，
+//! 连接时出示在 `/ws` 上通过验证和审批后换取的一次性票据 (`?ticket=`，见 `signaling::ticket`)
+//! 系统信息 (进程、负载、磁盘、电池) 通过 `/sysinfo` 获取 (见 `system`)
+//!
+//! 启用 metrics feature 时提供 `/metrics` 端点 (Prometheus 文本格式，认证方式同 WebSocket)
+//!
+//! 所有请求经过防滥用检查 (见 `signaling::rate_limit`): 封禁中的 IP 直接拒绝，
+//! 返回 401 的请求计入认证失败；WebSocket 连接还受每 IP 连接数和消息速率限制
+//!
+//! 服务器定期向每个 WebSocket 连接发送 Ping，超时未收到任何数据的连接 (半开连接) 会被关闭
+//! (见 `network::keepalive`)
+//!
+//! Host 在进程内，WebSocket 连接的对端一律是 Viewer: Viewer 只能向 Host 发起协商，
+//! 冒充 Host 的消息 (Answer、发给其他 Viewer 的信令) 会被拒绝。断线恢复需要出示随 Answer
+//! 下发的会话令牌 (见 `signaling::session_token`)
+//!
+//! 启用 redis feature 并调用 `set_redis_url` 后，多个实例通过 Redis 共享房间成员并互相转发信令
+//! (见 `signaling::cluster`)；默认只使用内存状态
+//!
+//! 客户端握手时提出压缩 (`X-Sscontrol-Compression: deflate`) 且未关闭 `server.compression` 时，
+//! `/ws` 上较长的文本消息压缩后传输 (见 `network::compression`)；`/video` 的视频帧不压缩
+//!
+//! Host 的事件循环断开后，房间和 Viewer 保留 `HOST_GRACE`，发往 Host 的信令暂存，
+//! Host 凭恢复令牌重新注册后重放 (见 `signaling::host_link`)
+//!
+//! 其他被控端可通过 `/ws` 发送 `register` 和 `heartbeat` 登记在线状态，
+//! `GET /devices` 返回设备清单 (认证方式同 WebSocket，见 `signaling::presence`)
 
 #![allow(dead_code)]
 
@@ -8,21 +42,49 @@ use anyhow::Result;
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        ConnectInfo, DefaultBodyLimit, Extension, Query, Request, State,
     },
-    http::Method,
-    response::{Html, IntoResponse},
-    routing::get,
+    body::Body,
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Json, Response},
+    routing::{get, post},
     Router,
 };
+use super::admission::{Admission, AdmissionControl, CapacityConfig};
+use super::capabilities::HostCapabilities;
+use super::chat::{ChatLog, ChatMessage, ChatPost};
+#[cfg(feature = "redis")]
+use super::cluster::{ClusterEvent, ClusterHandle, RemoteMembers};
+use super::host_info::HostInfo;
+use super::host_link::HostLink;
+use super::permissions::{Permission, SessionPermissions};
+use super::pin::{constant_time_eq, PinAttempt, PinConfig, PinGuard, PinVerdict};
+use super::presence::{DeviceInfo, PresenceRegistry};
+use super::rate_limit::{AbuseConfig, AbuseGuard, Rejection};
+use super::session_token::{PeerRole, SessionTokenIssuer};
+use super::ticket::TicketBook;
+use super::video_stream::VideoStream;
+#[cfg(feature = "security")]
+use crate::security::totp::TotpGuard;
+use crate::nat::predictive_punching::PunchCandidate;
+use crate::network::compression;
+use crate::network::keepalive::{KeepaliveConfig, Liveness};
+use crate::audit::{AuditEvent, AuditLog, AuthMethod};
+use crate::input::{InputCoalescer, InputEvent, TouchTranslator};
+use crate::power::PowerAction;
+use crate::quality::profile::QualityProfile;
+use crate::transfer::{IncomingFiles, TooLarge};
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{broadcast, mpsc, RwLock};
 use tower_http::cors::{Any, CorsLayer};
+use tracing::Instrument;
 
 /// 信令消息类型
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +93,29 @@ pub enum SignalMessage {
     /// 加入房间
     #[serde(rename = "join")]
     Join { room_id: String },
+    /// 断线重连后加入房间并恢复之前的会话 (session_id 和 token 来自之前收到的 Answer)
+    #[serde(rename = "resume")]
+    Resume {
+        room_id: String,
+        session_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+    },
+    /// 会话已恢复 (Viewer 应在原 PeerConnection 上发起 ICE 重启)
+    #[serde(rename = "resumed")]
+    Resumed { session_id: String },
+    /// Viewer 在令牌过期前请求换发
+    #[serde(rename = "refresh_token")]
+    RefreshToken,
+    /// 新的会话令牌 (恢复会话后及响应 `refresh_token` 时下发)
+    #[serde(rename = "session_token")]
+    SessionToken { token: String },
+    /// Web 查看器请求 `/video` 等 HTTP 接口使用的一次性票据 (需已通过验证，首次请求时等待被控端审批)
+    #[serde(rename = "request_ticket")]
+    RequestTicket,
+    /// 一次性票据 (绑定该 Viewer 和当前会话)
+    #[serde(rename = "ticket")]
+    Ticket { ticket: String, peer_id: String },
     /// 房间内现有成员
     #[serde(rename = "peers")]
     Peers { peers: Vec<PeerInfo> },
@@ -44,8 +129,18 @@ pub enum SignalMessage {
     #[serde(rename = "offer")]
     Offer { from: String, to: String, sdp: String },
     /// SDP Answer
+    ///
+    /// Host 发出的 Answer 携带会话 ID (Viewer 可用于关联日志/统计) 和恢复会话用的令牌
     #[serde(rename = "answer")]
-    Answer { from: String, to: String, sdp: String },
+    Answer {
+        from: String,
+        to: String,
+        sdp: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        session_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        session_token: Option<String>,
+    },
     /// ICE 候选
     #[serde(rename = "ice")]
     Ice {
@@ -55,6 +150,72 @@ pub enum SignalMessage {
         sdp_mid: String,
         sdp_mline_index: u16,
     },
+    /// 打洞信息 (Offer 之前交换；candidate 为 None 表示该端不打洞)
+    #[serde(rename = "punch")]
+    Punch {
+        from: String,
+        to: String,
+        candidate: Option<PunchCandidate>,
+    },
+    /// Host 环境信息 (放行后下发)
+    #[serde(rename = "hello")]
+    Hello { host: HostInfo },
+    /// Viewer 切换画面中是否包含鼠标指针
+    #[serde(rename = "cursor")]
+    Cursor { show: bool },
+    /// Viewer 切换画质预设
+    #[serde(rename = "profile")]
+    Profile { profile: QualityProfile },
+    /// Viewer 请求 Host 执行电源操作 (锁屏/注销/重启/关机)
+    #[serde(rename = "power")]
+    Power { action: PowerAction },
+    /// Viewer 的会话权限 (放行时及权限变更时下发)
+    #[serde(rename = "permissions")]
+    Permissions { permissions: SessionPermissions },
+    /// Host 要求 PIN 验证 (放行后下发)
+    #[serde(rename = "pin_required")]
+    PinRequired,
+    /// Viewer 提交 Host 控制台显示的 PIN
+    #[serde(rename = "pin")]
+    Pin { pin: String },
+    /// PIN 验证结果
+    #[serde(rename = "pin_result")]
+    PinResult {
+        accepted: bool,
+        /// 锁定前剩余尝试次数
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        remaining_attempts: Option<u32>,
+        /// 锁定中，多少秒后可重试
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        retry_after_secs: Option<u64>,
+    },
+    /// Host 要求 TOTP 双因素验证 (放行后下发)
+    #[serde(rename = "totp_required")]
+    TotpRequired,
+    /// Viewer 提交身份验证器 App 中的 6 位验证码
+    #[serde(rename = "totp")]
+    Totp { code: String },
+    /// TOTP 验证结果 (字段含义同 `pin_result`)
+    #[serde(rename = "totp_result")]
+    TotpResult {
+        accepted: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        remaining_attempts: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        retry_after_secs: Option<u64>,
+    },
+    /// 被控端登记到设备清单 (之后按 heartbeat_secs 发送 `heartbeat`)
+    #[serde(rename = "register")]
+    Register { device: DeviceInfo, heartbeat_secs: u64 },
+    /// 登记成功
+    #[serde(rename = "registered")]
+    Registered { device_id: String },
+    /// 已登记设备的心跳
+    #[serde(rename = "heartbeat")]
+    Heartbeat,
+    /// 会话已满，正在排队 (位置从 1 开始)
+    #[serde(rename = "queued")]
+    Queued { position: usize, queue_length: usize },
     /// 错误
     #[serde(rename = "error")]
     Error { message: String },
@@ -72,8 +233,10 @@ pub enum HostSignalEvent {
     ViewerJoined { peer_id: String },
     /// Viewer 断开
     ViewerLeft { peer_id: String },
-    /// 收到 Offer
-    Offer { from: String, sdp: String },
+    /// Viewer 断线重连后恢复了之前的会话 (在 ViewerJoined 之前发送)
+    ViewerResumed { peer_id: String, session_id: String },
+    /// 收到 Offer (session_id 为该 Viewer 的逻辑会话 ID，重协商时保持不变)
+    Offer { from: String, sdp: String, session_id: String },
     /// 收到 ICE 候选
     Ice {
         from: String,
@@ -81,6 +244,54 @@ pub enum HostSignalEvent {
         sdp_mid: String,
         sdp_mline_index: u16,
     },
+    /// 收到 Viewer 的打洞信息 (Host 应回复自己的打洞信息)
+    Punch { from: String, candidate: Option<PunchCandidate> },
+    /// Viewer 请求切换画面中的鼠标指针
+    Cursor { from: String, show: bool },
+    /// Viewer 请求切换画质预设
+    Profile { from: String, profile: QualityProfile },
+    /// Viewer 请求电源操作 (已检查权限)
+    Power { from: String, action: PowerAction },
+    /// PIN 已被使用，换发新的 PIN
+    PinChanged { pin: String },
+    /// Web 查看器发送了聊天消息 (已记录到聊天记录)
+    Chat { message: ChatMessage },
+    /// Web 查看器的输入 (已检查权限，触摸手势已转换为鼠标和键盘事件)
+    Input { from: String, event: InputEvent },
+    /// Web 查看器的输入连接已断开 (释放控制权)
+    InputClosed { from: String },
+    /// Web 查看器上传的文件已保存到接收目录 (已检查权限并记录审计日志)
+    FileReceived { from: String, path: std::path::PathBuf },
+    /// 已通过验证的 Web 查看器请求票据 (Host 审批后调用 `grant_ticket`)
+    TicketRequested { from: String, session_id: String },
+}
+
+impl HostSignalEvent {
+    /// 事件相关的 Viewer (PIN、聊天等全局事件为 None)
+    pub fn peer_id(&self) -> Option<&str> {
+        match self {
+            Self::ViewerJoined { peer_id } | Self::ViewerLeft { peer_id } | Self::ViewerResumed { peer_id, .. } => Some(peer_id),
+            Self::Offer { from, .. }
+            | Self::Ice { from, .. }
+            | Self::Punch { from, .. }
+            | Self::Cursor { from, .. }
+            | Self::Profile { from, .. }
+            | Self::Power { from, .. }
+            | Self::Input { from, .. }
+            | Self::InputClosed { from }
+            | Self::FileReceived { from, .. }
+            | Self::TicketRequested { from, .. } => Some(from),
+            Self::PinChanged { .. } | Self::Chat { .. } => None,
+        }
+    }
+}
+
+/// 断线后等待恢复的会话
+struct ResumableSession {
+    permissions: SessionPermissions,
+    pin_verified: bool,
+    totp_verified: bool,
+    expires: Instant,
 }
 
 /// 客户端发送器
@@ -97,8 +308,43 @@ struct Room {
 struct ServerState {
     rooms: HashMap<String, Room>,
     clients: HashMap<String, ClientSender>,
-    host_event_tx: Option<mpsc::UnboundedSender<HostSignalEvent>>,
+    /// Host 事件通道 (Host 断开后在宽限期内保留房间并暂存事件)
+    host: HostLink,
     peer_counter: AtomicU64,
+    admission: AdmissionControl,
+    /// peer_id -> 会话 ID
+    session_ids: HashMap<String, String>,
+    /// PIN 验证 (None = 不要求 PIN)
+    pin: Option<PinGuard>,
+    /// TOTP 双因素验证 (None = 不要求)
+    #[cfg(feature = "security")]
+    totp: Option<TotpGuard>,
+    /// 新 Viewer 的默认权限
+    default_permissions: SessionPermissions,
+    /// peer_id -> 会话权限 (放行时分配)
+    permissions: HashMap<String, SessionPermissions>,
+    /// peer_id -> 认证角色允许的权限上限 (未配置认证提供者时没有上限)
+    limits: HashMap<String, SessionPermissions>,
+    /// 会话 ID -> 断线后等待恢复的会话
+    resumable: HashMap<String, ResumableSession>,
+    /// 聊天记录
+    chat: ChatLog,
+    /// 会话令牌签发
+    tokens: SessionTokenIssuer,
+    /// Web 查看器 HTTP 接口的一次性票据
+    tickets: TicketBook,
+    /// 会话审计日志
+    audit: Arc<AuditLog>,
+    /// 登记到本服务器的设备
+    presence: PresenceRegistry,
+    /// peer_id -> 设备登记使用的身份 (只有已认证的连接可以登记)
+    identities: HashMap<String, String>,
+    /// 多实例部署时的集群句柄 (None = 单实例)
+    #[cfg(feature = "redis")]
+    cluster: Option<ClusterHandle>,
+    /// 其他实例上的房间成员
+    #[cfg(feature = "redis")]
+    remote: RemoteMembers,
 }
 
 impl ServerState {
@@ -106,50 +352,391 @@ impl ServerState {
         Self {
             rooms: HashMap::new(),
             clients: HashMap::new(),
-            host_event_tx: None,
+            host: HostLink::new(EmbeddedSignalingServer::HOST_GRACE),
             peer_counter: AtomicU64::new(0),
+            admission: AdmissionControl::default(),
+            session_ids: HashMap::new(),
+            pin: None,
+            #[cfg(feature = "security")]
+            totp: None,
+            default_permissions: SessionPermissions::default(),
+            permissions: HashMap::new(),
+            limits: HashMap::new(),
+            resumable: HashMap::new(),
+            chat: ChatLog::new(),
+            tokens: SessionTokenIssuer::default(),
+            tickets: TicketBook::default(),
+            audit: Arc::new(AuditLog::disabled()),
+            presence: PresenceRegistry::new(),
+            identities: HashMap::new(),
+            #[cfg(feature = "redis")]
+            cluster: None,
+            #[cfg(feature = "redis")]
+            remote: RemoteMembers::default(),
+        }
+    }
+
+    /// 记录审计事件 (Viewer 已分配会话 ID 时一并记录)
+    fn record_audit(&self, event: AuditEvent) {
+        let session_id = self.session_ids.get(event.peer()).map(String::as_str);
+        self.audit.record_for_session(session_id, event);
+    }
+
+    /// Viewer 的会话权限 (未放行时为默认权限，不超过认证角色的上限)
+    fn permissions_of(&self, peer_id: &str) -> SessionPermissions {
+        self.permissions
+            .get(peer_id)
+            .copied()
+            .unwrap_or(self.default_permissions)
+            .restrict(self.limit_of(peer_id))
+    }
+
+    /// Viewer 认证角色允许的权限上限
+    fn limit_of(&self, peer_id: &str) -> SessionPermissions {
+        self.limits.get(peer_id).copied().unwrap_or_else(SessionPermissions::full)
+    }
+
+    /// 修改 Viewer 权限并通知该 Viewer (超出认证角色上限的部分被去掉)
+    fn update_permissions(&mut self, peer_id: &str, permissions: SessionPermissions) {
+        let permissions = permissions.restrict(self.limit_of(peer_id));
+        self.permissions.insert(peer_id.to_string(), permissions);
+        if let Ok(msg) = serde_json::to_string(&SignalMessage::Permissions { permissions }) {
+            self.send_to(peer_id, &msg);
+        }
+    }
+
+    /// Viewer 是否可以与 Host 协商 (未启用 PIN 或已通过验证)
+    fn pin_verified(&self, peer_id: &str) -> bool {
+        self.pin.as_ref().is_none_or(|pin| pin.is_verified(peer_id))
+    }
+
+    /// Viewer 是否已通过 TOTP 验证 (未启用时为 true)
+    fn totp_verified(&self, peer_id: &str) -> bool {
+        #[cfg(feature = "security")]
+        {
+            self.totp.as_ref().is_none_or(|totp| totp.is_verified(peer_id))
         }
+        #[cfg(not(feature = "security"))]
+        {
+            let _ = peer_id;
+            true
+        }
+    }
+
+    /// Viewer 是否已完成全部验证 (PIN 和 TOTP)，可以与 Host 协商
+    fn authenticated(&self, peer_id: &str) -> bool {
+        self.pin_verified(peer_id) && self.totp_verified(peer_id)
+    }
+
+    /// Viewer 仍在连接、已放行且已完成全部验证
+    fn verified(&self, peer_id: &str) -> bool {
+        self.clients.contains_key(peer_id) && self.admission.is_active(peer_id) && self.authenticated(peer_id)
+    }
+
+    /// 使用票据，返回签发对象 (该 Viewer 须仍处于签发时的会话且已通过验证)
+    fn redeem_ticket(&mut self, ticket: &str) -> Option<String> {
+        let claims = self.tickets.redeem(ticket)?;
+        let current = self.session_ids.get(&claims.peer_id).is_some_and(|session_id| *session_id == claims.session_id);
+        (current && self.verified(&claims.peer_id)).then_some(claims.peer_id)
     }
 
     fn next_peer_id(&self) -> String {
         let id = self.peer_counter.fetch_add(1, Ordering::SeqCst);
+        #[cfg(feature = "redis")]
+        if let Some(cluster) = &self.cluster {
+            return cluster.peer_id(id);
+        }
         format!("viewer_{}", id)
     }
 
+    /// 获取 Viewer 的会话 ID，首次 Offer 时生成
+    fn session_id(&mut self, peer_id: &str) -> String {
+        self.session_ids
+            .entry(peer_id.to_string())
+            .or_insert_with(|| uuid::Uuid::new_v4().to_string())
+            .clone()
+    }
+
+    /// Viewer 所在的房间
+    fn room_of(&self, peer_id: &str) -> Option<&str> {
+        self.rooms
+            .iter()
+            .find(|(_, room)| room.clients.iter().any(|id| id == peer_id))
+            .map(|(room_id, _)| room_id.as_str())
+    }
+
+    /// 为 Viewer 当前的会话签发令牌 (断线恢复时出示)
+    fn session_token(&self, peer_id: &str) -> Option<String> {
+        let session_id = self.session_ids.get(peer_id)?;
+        let room_id = self.room_of(peer_id)?;
+        Some(self.tokens.issue(session_id, room_id, PeerRole::Viewer))
+    }
+
+    /// 下发新的会话令牌 (尚未协商或未加入房间时不下发)
+    fn send_session_token(&self, peer_id: &str) {
+        let Some(token) = self.session_token(peer_id) else {
+            return;
+        };
+        if let Ok(msg) = serde_json::to_string(&SignalMessage::SessionToken { token }) {
+            self.send_to(peer_id, &msg);
+        }
+    }
+
+    /// 校验恢复请求: 令牌必须由本服务器为该会话和房间签发
+    fn verify_resume(&self, room_id: &str, session_id: &str, token: Option<&str>) -> bool {
+        token
+            .and_then(|token| self.tokens.verify(token))
+            .is_some_and(|claims| claims.sid == session_id && claims.room == room_id && claims.role == PeerRole::Viewer)
+    }
+
+    /// 清理断开的 Viewer
+    fn disconnect(&mut self, peer_id: &str) {
+        self.clients.remove(peer_id);
+        self.record_audit(AuditEvent::Disconnect { peer: peer_id.to_string() });
+        if let Some(device_id) = self.presence.disconnect(peer_id) {
+            tracing::info!("设备离线: {}", device_id);
+        }
+        self.identities.remove(peer_id);
+        self.suspend_session(peer_id);
+        self.permissions.remove(peer_id);
+        self.limits.remove(peer_id);
+        self.tickets.forget(peer_id);
+        if let Some(pin) = self.pin.as_mut() {
+            pin.forget(peer_id);
+        }
+        #[cfg(feature = "security")]
+        if let Some(totp) = self.totp.as_mut() {
+            totp.forget(peer_id);
+        }
+
+        if let Some(room_id) = self.leave_room(peer_id) {
+            if let Ok(msg) = serde_json::to_string(&SignalMessage::PeerLeft {
+                peer_id: peer_id.to_string(),
+            }) {
+                self.broadcast_to_room(&room_id, &msg, None);
+            }
+        }
+        self.release(peer_id);
+    }
+
+    /// Viewer 断开: 已协商过的会话保留一段时间等待恢复
+    fn suspend_session(&mut self, peer_id: &str) {
+        let now = Instant::now();
+        self.resumable.retain(|_, session| session.expires > now);
+
+        let Some(session_id) = self.session_ids.remove(peer_id) else {
+            return;
+        };
+        let Some(permissions) = self.permissions.get(peer_id).copied() else {
+            return;
+        };
+        let pin_verified = self.pin_verified(peer_id);
+        let totp_verified = self.totp_verified(peer_id);
+        self.resumable.insert(
+            session_id,
+            ResumableSession {
+                permissions,
+                pin_verified,
+                totp_verified,
+                expires: now + EmbeddedSignalingServer::RESUME_GRACE,
+            },
+        );
+    }
+
+    /// 将未过期的会话交给重连后的 Viewer (沿用会话 ID、权限和 PIN/TOTP 验证状态)
+    fn resume_session(&mut self, peer_id: &str, session_id: &str) -> bool {
+        let Some(session) = self.resumable.remove(session_id) else {
+            return false;
+        };
+        if session.expires <= Instant::now() {
+            return false;
+        }
+
+        self.session_ids.insert(peer_id.to_string(), session_id.to_string());
+        let permissions = session.permissions.restrict(self.limit_of(peer_id));
+        self.permissions.insert(peer_id.to_string(), permissions);
+        if session.pin_verified {
+            if let Some(pin) = self.pin.as_mut() {
+                pin.trust(peer_id);
+            }
+        }
+        #[cfg(feature = "security")]
+        if session.totp_verified {
+            if let Some(totp) = self.totp.as_mut() {
+                totp.trust(peer_id);
+            }
+        }
+        self.record_audit(AuditEvent::Auth {
+            peer: peer_id.to_string(),
+            method: AuthMethod::Resume,
+            success: true,
+        });
+        self.forward_to_host(HostSignalEvent::ViewerResumed {
+            peer_id: peer_id.to_string(),
+            session_id: session_id.to_string(),
+        });
+        if let Ok(msg) = serde_json::to_string(&SignalMessage::Resumed {
+            session_id: session_id.to_string(),
+        }) {
+            self.send_to(peer_id, &msg);
+        }
+        tracing::info!("Viewer {} 恢复会话 {}", peer_id, session_id);
+        #[cfg(feature = "metrics")]
+        super::metrics::metrics().session_resumed();
+        true
+    }
+
+    /// 处理加入请求 (resume 为要恢复的会话 ID，仅在直接放行时恢复)
+    fn request_join(&mut self, peer_id: &str, room_id: String, resume: Option<&str>) {
+        if self.admission.is_active(peer_id) {
+            return;
+        }
+        if !self.room_exists(&room_id)
+            && self.admission.config().max_rooms.is_some_and(|max| self.room_count() >= max)
+        {
+            if let Ok(msg) = serde_json::to_string(&SignalMessage::Error {
+                message: "信令服务器房间数已达上限".to_string(),
+            }) {
+                self.send_to(peer_id, &msg);
+            }
+            tracing::warn!("房间数已达上限，拒绝 Viewer {} 创建房间 {}", peer_id, room_id);
+            return;
+        }
+        if self.room_full(&room_id) {
+            if let Ok(msg) = serde_json::to_string(&SignalMessage::Error {
+                message: "房间 Viewer 数已达上限".to_string(),
+            }) {
+                self.send_to(peer_id, &msg);
+            }
+            tracing::info!("房间 {} 已满，拒绝 Viewer {}", room_id, peer_id);
+            return;
+        }
+
+        match self.admission.request(peer_id, &room_id) {
+            Admission::Admitted => {
+                let resumed = resume.is_some_and(|session_id| {
+                    let resumed = self.resume_session(peer_id, session_id);
+                    if !resumed {
+                        tracing::info!("Viewer {} 的会话 {} 已失效，按新连接处理", peer_id, session_id);
+                    }
+                    resumed
+                });
+                self.admit(peer_id, room_id);
+                // 换发令牌，供下次断线时使用
+                if resumed {
+                    self.send_session_token(peer_id);
+                }
+            }
+            Admission::Queued { position } => {
+                let queue_length = self.admission.queue_len();
+                if let Ok(msg) = serde_json::to_string(&SignalMessage::Queued { position, queue_length }) {
+                    self.send_to(peer_id, &msg);
+                }
+                tracing::info!("会话已满，Viewer {} 排队中 (位置 {})", peer_id, position);
+            }
+            Admission::Rejected => {
+                if let Ok(msg) = serde_json::to_string(&SignalMessage::Error {
+                    message: "被控端会话已满，请稍后重试".to_string(),
+                }) {
+                    self.send_to(peer_id, &msg);
+                }
+                tracing::info!("会话已满，拒绝 Viewer {}", peer_id);
+            }
+        }
+    }
+
+    /// 房间 Viewer 数是否已达上限 (多实例部署时按全部实例计算)
+    fn room_full(&self, room_id: &str) -> bool {
+        let Some(max) = self.admission.config().max_viewers_per_room else {
+            return false;
+        };
+        let local = self.rooms.get(room_id).map_or(0, |room| room.clients.len());
+        local + self.remote_members(room_id).len() >= max
+    }
+
+    /// 房间是否存在 (任一实例上有成员)
+    fn room_exists(&self, room_id: &str) -> bool {
+        self.rooms.contains_key(room_id) || !self.remote_members(room_id).is_empty()
+    }
+
+    /// 全部实例上的房间数
+    fn room_count(&self) -> usize {
+        #[cfg(feature = "redis")]
+        {
+            let mut rooms = self.remote.rooms();
+            rooms.extend(self.rooms.keys().map(String::as_str));
+            rooms.len()
+        }
+        #[cfg(not(feature = "redis"))]
+        {
+            self.rooms.len()
+        }
+    }
+
+    /// 房间中其他实例上的成员 (单实例时为空)
+    fn remote_members(&self, room_id: &str) -> Vec<String> {
+        #[cfg(feature = "redis")]
+        {
+            self.remote.room_members(room_id)
+        }
+        #[cfg(not(feature = "redis"))]
+        {
+            let _ = room_id;
+            Vec::new()
+        }
+    }
+
     fn join_room(&mut self, peer_id: String, room_id: String) -> Vec<String> {
         let room = self.rooms.entry(room_id.clone()).or_insert(Room {
             clients: Vec::new(),
         });
-        let existing = room.clients.clone();
+        let mut existing = room.clients.clone();
         room.clients.push(peer_id.clone());
+        existing.extend(self.remote_members(&room_id));
+        #[cfg(feature = "redis")]
+        if let Some(cluster) = &self.cluster {
+            cluster.joined(&peer_id, &room_id);
+        }
 
         // 通知 Host 有新 Viewer 加入
-        if let Some(tx) = &self.host_event_tx {
-            let _ = tx.send(HostSignalEvent::ViewerJoined { peer_id });
-        }
+        self.forward_to_host(HostSignalEvent::ViewerJoined { peer_id });
 
         existing
     }
 
     fn leave_room(&mut self, peer_id: &str) -> Option<String> {
-        for (room_id, room) in self.rooms.iter_mut() {
-            if let Some(pos) = room.clients.iter().position(|id| id == peer_id) {
-                room.clients.remove(pos);
-
-                // 通知 Host Viewer 离开
-                if let Some(tx) = &self.host_event_tx {
-                    let _ = tx.send(HostSignalEvent::ViewerLeft {
-                        peer_id: peer_id.to_string(),
-                    });
-                }
-
-                return Some(room_id.clone());
+        let room_id = self.room_of(peer_id)?.to_string();
+        if let Some(room) = self.rooms.get_mut(&room_id) {
+            room.clients.retain(|id| id != peer_id);
+            // 空房间不占用房间数上限
+            if room.clients.is_empty() {
+                self.rooms.remove(&room_id);
             }
         }
-        None
+        #[cfg(feature = "redis")]
+        if let Some(cluster) = &self.cluster {
+            cluster.left(peer_id);
+        }
+
+        // 通知 Host Viewer 离开
+        self.forward_to_host(HostSignalEvent::ViewerLeft {
+            peer_id: peer_id.to_string(),
+        });
+
+        Some(room_id)
     }
 
+    /// 发送给 Viewer (多实例部署时其他实例上的 Viewer 经 Redis 转发)
     fn send_to(&self, peer_id: &str, msg: &str) -> bool {
+        #[cfg(feature = "redis")]
+        if let Some(cluster) = self.cluster.as_ref().filter(|cluster| cluster.is_remote(peer_id)) {
+            cluster.deliver(peer_id, msg);
+            return true;
+        }
+        self.send_local(peer_id, msg)
+    }
+
+    fn send_local(&self, peer_id: &str, msg: &str) -> bool {
         if let Some(sender) = self.clients.get(peer_id) {
             sender.sender.send(msg.to_string()).is_ok()
         } else {
@@ -158,6 +745,14 @@ impl ServerState {
     }
 
     fn broadcast_to_room(&self, room_id: &str, msg: &str, exclude: Option<&str>) {
+        #[cfg(feature = "redis")]
+        if let Some(cluster) = &self.cluster {
+            cluster.broadcast(room_id, msg, exclude);
+        }
+        self.broadcast_local(room_id, msg, exclude);
+    }
+
+    fn broadcast_local(&self, room_id: &str, msg: &str, exclude: Option<&str>) {
         if let Some(room) = self.rooms.get(room_id) {
             for peer_id in &room.clients {
                 if let Some(exclude_id) = exclude {
@@ -165,15 +760,111 @@ impl ServerState {
                         continue;
                     }
                 }
-                self.send_to(peer_id, msg);
+                self.send_local(peer_id, msg);
             }
         }
     }
 
-    /// 转发信令给 Host
+    /// 应用其他实例发来的事件
+    #[cfg(feature = "redis")]
+    fn apply_cluster_event(&mut self, event: ClusterEvent) {
+        match &event {
+            ClusterEvent::Deliver { to, msg } => {
+                self.send_local(to, msg);
+            }
+            ClusterEvent::Broadcast { room_id, msg, exclude } => {
+                self.broadcast_local(room_id, msg, exclude.as_deref());
+            }
+            _ => self.remote.apply(&event),
+        }
+    }
+
+    /// 转发信令给 Host (Host 断开时暂存，重新注册后重放)
     fn forward_to_host(&self, event: HostSignalEvent) {
-        if let Some(tx) = &self.host_event_tx {
-            let _ = tx.send(event);
+        self.host.send(event);
+    }
+
+    /// Host 宽限期内未重新注册: 通知并断开本实例的所有 Viewer
+    fn abandon_viewers(&mut self) {
+        tracing::warn!("Host 未在 {} 秒内重新注册，断开 {} 个 Viewer", EmbeddedSignalingServer::HOST_GRACE.as_secs(), self.clients.len());
+        if let Ok(msg) = serde_json::to_string(&SignalMessage::Error { message: "Host 已离线".to_string() }) {
+            for client in self.clients.values() {
+                let _ = client.sender.send(msg.clone());
+            }
+        }
+        // 发送端全部释放后连接的发送任务结束，由连接处理统一清理
+        self.clients.clear();
+    }
+
+    /// 放行 Viewer: 加入房间并下发成员列表
+    fn admit(&mut self, peer_id: &str, room_id: String) {
+        let existing_peers = self.join_room(peer_id.to_string(), room_id.clone());
+
+        // 发送现有成员列表 (包括 host)
+        let mut peers: Vec<PeerInfo> = existing_peers
+            .iter()
+            .map(|id| PeerInfo { id: id.clone() })
+            .collect();
+        // 添加 host 作为成员
+        peers.push(PeerInfo { id: "host".to_string() });
+
+        if let Ok(msg) = serde_json::to_string(&SignalMessage::Peers { peers }) {
+            self.send_to(peer_id, &msg);
+        }
+
+        // 下发 Host 时区/区域信息
+        if let Ok(msg) = serde_json::to_string(&SignalMessage::Hello { host: HostInfo::current() }) {
+            self.send_to(peer_id, &msg);
+        }
+
+        if !self.pin_verified(peer_id) {
+            if let Ok(msg) = serde_json::to_string(&SignalMessage::PinRequired) {
+                self.send_to(peer_id, &msg);
+            }
+        }
+        if !self.totp_verified(peer_id) {
+            if let Ok(msg) = serde_json::to_string(&SignalMessage::TotpRequired) {
+                self.send_to(peer_id, &msg);
+            }
+        }
+
+        // 权限最后下发: Viewer 收到后即可确定是否需要先提交 PIN/验证码
+        // (恢复的会话沿用之前的权限)
+        let permissions = self.permissions_of(peer_id);
+        self.update_permissions(peer_id, permissions);
+
+        // 通知其他成员 (不通知 host，因为已经通过事件通知了)
+        if let Ok(msg) = serde_json::to_string(&SignalMessage::NewPeer {
+            peer_id: peer_id.to_string(),
+        }) {
+            self.broadcast_to_room(&room_id, &msg, Some(peer_id));
+        }
+
+        tracing::info!("Viewer {} 加入房间 {}", peer_id, room_id);
+    }
+
+    /// 通知所有排队者当前位置
+    fn notify_queue_positions(&self) {
+        let queue_length = self.admission.queue_len();
+        for (peer_id, position) in self.admission.queue_positions() {
+            if let Ok(msg) = serde_json::to_string(&SignalMessage::Queued { position, queue_length }) {
+                self.send_to(&peer_id, &msg);
+            }
+        }
+    }
+
+    /// Viewer 断开后释放名额并放行排队者
+    fn release(&mut self, peer_id: &str) {
+        let was_queued = self.admission.position(peer_id).is_some();
+        let admitted = self.admission.release(peer_id);
+
+        for (next, room_id) in &admitted {
+            tracing::info!("排队 Viewer {} 获得会话名额", next);
+            self.admit(next, room_id.clone());
+        }
+
+        if was_queued || !admitted.is_empty() {
+            self.notify_queue_positions();
         }
     }
 }
@@ -182,6 +873,25 @@ impl ServerState {
 #[derive(Clone)]
 struct AppState {
     state: Arc<RwLock<ServerState>>,
+    #[cfg(feature = "security")]
+    auth_provider: Option<Arc<dyn crate::security::AuthProvider>>,
+    /// 是否拒绝未加密的连接
+    #[cfg(feature = "security")]
+    require_e2ee: bool,
+    /// 按 IP 的连接数和认证失败统计
+    abuse: Arc<std::sync::Mutex<AbuseGuard>>,
+    /// WebSocket 心跳
+    keepalive: KeepaliveConfig,
+    /// `/video` 的 H.264 码流
+    video_stream: Arc<VideoStream>,
+    /// Web 查看器输入的限速 (每秒最多注入的鼠标移动/滚动事件数)
+    max_input_rate: u32,
+    /// 上传文件的接收目录
+    incoming: Arc<IncomingFiles>,
+    /// 客户端提出时压缩 `/ws` 的文本消息
+    compression: bool,
+    /// 未配置认证提供者时，设备登记和 `/devices` 接受的令牌
+    fleet_token: Option<String>,
 }
 
 /// 内嵌信令服务器
@@ -190,9 +900,34 @@ pub struct EmbeddedSignalingServer {
     state: Arc<RwLock<ServerState>>,
     shutdown_tx: Option<broadcast::Sender<()>>,
     host_event_rx: Option<mpsc::UnboundedReceiver<HostSignalEvent>>,
+    #[cfg(feature = "security")]
+    auth_provider: Option<Arc<dyn crate::security::AuthProvider>>,
+    #[cfg(feature = "security")]
+    require_e2ee: bool,
+    capacity: CapacityConfig,
+    pin: Option<PinConfig>,
+    #[cfg(feature = "security")]
+    totp_secret: Option<String>,
+    default_permissions: SessionPermissions,
+    abuse: AbuseConfig,
+    keepalive: KeepaliveConfig,
+    audit: Arc<AuditLog>,
+    video_stream: Arc<VideoStream>,
+    max_input_rate: u32,
+    incoming: Arc<IncomingFiles>,
+    compression: bool,
+    fleet_token: Option<String>,
+    #[cfg(feature = "redis")]
+    redis_url: Option<String>,
 }
 
 impl EmbeddedSignalingServer {
+    /// Viewer 断线后会话保留的时长，期间可用 `resume` 消息恢复
+    pub const RESUME_GRACE: Duration = Duration::from_secs(30);
+
+    /// Host 事件通道断开后保留房间和 Viewer 的时长，期间可用 `reattach_host` 重新注册
+    pub const HOST_GRACE: Duration = Duration::from_secs(30);
+
     /// 创建新的内嵌信令服务器
     pub fn new(port: u16) -> Self {
         Self {
@@ -200,7 +935,180 @@ impl EmbeddedSignalingServer {
             state: Arc::new(RwLock::new(ServerState::new())),
             shutdown_tx: None,
             host_event_rx: None,
+            #[cfg(feature = "security")]
+            auth_provider: None,
+            #[cfg(feature = "security")]
+            require_e2ee: false,
+            capacity: CapacityConfig::default(),
+            pin: None,
+            #[cfg(feature = "security")]
+            totp_secret: None,
+            default_permissions: SessionPermissions::default(),
+            abuse: AbuseConfig::default(),
+            keepalive: KeepaliveConfig::default(),
+            audit: Arc::new(AuditLog::disabled()),
+            video_stream: Arc::new(VideoStream::new()),
+            max_input_rate: InputCoalescer::DEFAULT_RATE,
+            incoming: Arc::new(IncomingFiles::default()),
+            compression: true,
+            fleet_token: None,
+            #[cfg(feature = "redis")]
+            redis_url: None,
+        }
+    }
+
+    /// 设置会话容量限制 (需在 start 之前调用)
+    pub fn set_capacity(&mut self, capacity: CapacityConfig) {
+        self.capacity = capacity;
+    }
+
+    /// 设置防滥用限制: 每 IP 连接数、消息速率和认证失败封禁 (需在 start 之前调用)
+    pub fn set_abuse_limits(&mut self, config: AbuseConfig) {
+        self.abuse = config;
+    }
+
+    /// 设置 WebSocket 心跳 (需在 start 之前调用)
+    pub fn set_keepalive(&mut self, keepalive: KeepaliveConfig) {
+        self.keepalive = keepalive;
+    }
+
+    /// 设置是否同意客户端压缩信令消息 (需在 start 之前调用，默认同意)
+    pub fn set_compression(&mut self, enabled: bool) {
+        self.compression = enabled;
+    }
+
+    /// 设置设备登记令牌 (需在 start 之前调用)
+    ///
+    /// 未配置认证提供者时，出示该令牌的连接才能登记设备和读取 `/devices`；都未配置时设备清单不可用
+    pub fn set_fleet_token(&mut self, token: Option<String>) {
+        self.fleet_token = token.filter(|token| !token.is_empty());
+    }
+
+    /// 设置 Web 查看器输入的限速 (需在 start 之前调用，0 = 不限速)
+    pub fn set_max_input_rate(&mut self, max_input_rate: u32) {
+        self.max_input_rate = max_input_rate;
+    }
+
+    /// 设置上传文件的接收目录 (需在 start 之前调用)
+    pub fn set_incoming_files(&mut self, incoming: IncomingFiles) {
+        self.incoming = Arc::new(incoming);
+    }
+
+    /// 记录会话审计日志 (需在 start 之前调用)
+    pub fn set_audit_log(&mut self, audit: Arc<AuditLog>) {
+        self.audit = audit;
+    }
+
+    /// 记录信令之外的通道 (如 QUIC 传输) 和 Host 侧的审计事件
+    pub fn audit(&self, event: AuditEvent) {
+        self.audit.record(event);
+    }
+
+    /// 记录属于某个会话的 Host 侧审计事件
+    pub fn audit_session(&self, session_id: &str, event: AuditEvent) {
+        self.audit.record_for_session(Some(session_id), event);
+    }
+
+    /// 通过 Redis 与其他实例共享房间状态 (需在 start 之前调用)
+    #[cfg(feature = "redis")]
+    pub fn set_redis_url(&mut self, url: Option<String>) {
+        self.redis_url = url;
+    }
+
+    /// 要求 Viewer 提交一次性 PIN 后才能发送 Offer (需在 start 之前调用)
+    pub fn set_pin(&mut self, config: Option<PinConfig>) {
+        self.pin = config;
+    }
+
+    /// 要求 Viewer 额外提交 TOTP 验证码 (Base32 密钥，需在 start 之前调用)
+    ///
+    /// 失败计数和锁定沿用 PIN 配置
+    #[cfg(feature = "security")]
+    pub fn set_totp(&mut self, secret: Option<String>) -> Result<()> {
+        if let Some(secret) = secret.as_deref() {
+            crate::security::totp::decode_secret(secret)?;
+        }
+        self.totp_secret = secret;
+        Ok(())
+    }
+
+    /// 设置新 Viewer 的默认权限 (需在 start 之前调用)
+    pub fn set_default_permissions(&mut self, permissions: SessionPermissions) {
+        self.default_permissions = permissions;
+    }
+
+    /// 获取 Viewer 的会话权限
+    pub async fn permissions(&self, peer_id: &str) -> SessionPermissions {
+        self.state.read().await.permissions_of(peer_id)
+    }
+
+    /// 修改 Viewer 的会话权限，并通知 Viewer 更新界面
+    pub async fn set_permissions(&self, peer_id: &str, permissions: SessionPermissions) {
+        self.state.write().await.update_permissions(peer_id, permissions);
+    }
+
+    /// 当前有效的 PIN (未启用时为 None)
+    pub async fn current_pin(&self) -> Option<String> {
+        self.state.read().await.pin.as_ref().map(|pin| pin.pin().to_string())
+    }
+
+    /// 校验信令之外的通道 (如 QUIC 传输) 提交的 PIN
+    ///
+    /// 与 Viewer 共用失败计数和锁定；通过后同样换发新 PIN 并通知 Host (使用无人值守访问密钥时不换发)。
+    /// 未启用 PIN 时直接通过
+    pub async fn verify_pin(&self, peer_id: &str, pin: Option<&str>) -> PinVerdict {
+        let Some((verdict, new_pin)) = check_pin(&self.state, peer_id, pin.unwrap_or_default()).await else {
+            return PinVerdict::Accepted;
+        };
+        self.audit.record(AuditEvent::Auth {
+            peer: peer_id.to_string(),
+            method: AuthMethod::Pin,
+            success: verdict == PinVerdict::Accepted,
+        });
+        if verdict == PinVerdict::Accepted {
+            let mut state = self.state.write().await;
+            // 这类通道不会重复提交，无需保留验证状态
+            if let Some(guard) = state.pin.as_mut() {
+                guard.forget(peer_id);
+            }
+            if let Some(pin) = new_pin {
+                state.forward_to_host(HostSignalEvent::PinChanged { pin });
+            }
+        }
+        verdict
+    }
+
+    /// 校验信令之外的通道 (如 QUIC 传输) 提交的 TOTP 验证码，未启用时直接通过
+    #[cfg(feature = "security")]
+    pub async fn verify_totp(&self, peer_id: &str, code: Option<&str>) -> PinVerdict {
+        let mut state = self.state.write().await;
+        let Some(guard) = state.totp.as_mut() else {
+            return PinVerdict::Accepted;
+        };
+        let verdict = guard.verify(peer_id, code.unwrap_or_default());
+        self.audit.record(AuditEvent::Auth {
+            peer: peer_id.to_string(),
+            method: AuthMethod::Totp,
+            success: verdict == PinVerdict::Accepted,
+        });
+        if verdict == PinVerdict::Accepted {
+            guard.forget(peer_id);
         }
+        verdict
+    }
+
+    /// 设置认证提供者 (需在 start 之前调用)
+    #[cfg(feature = "security")]
+    pub fn set_auth_provider(&mut self, provider: Arc<dyn crate::security::AuthProvider>) {
+        self.auth_provider = Some(provider);
+    }
+
+    /// 是否要求端到端加密 (需在 start 之前调用)
+    ///
+    /// 开启后未发送 `e2ee_hello` 的客户端 (包括浏览器 Viewer) 会被断开
+    #[cfg(feature = "security")]
+    pub fn set_require_e2ee(&mut self, require: bool) {
+        self.require_e2ee = require;
     }
 
     /// 启动服务器
@@ -209,12 +1117,69 @@ impl EmbeddedSignalingServer {
         self.shutdown_tx = Some(shutdown_tx.clone());
 
         // 创建 Host 事件通道
-        let (host_event_tx, host_event_rx) = mpsc::unbounded_channel();
-        self.host_event_rx = Some(host_event_rx);
-        self.state.write().await.host_event_tx = Some(host_event_tx);
+        {
+            let mut state = self.state.write().await;
+            self.host_event_rx = Some(state.host.attach());
+            state.admission.set_config(self.capacity.clone());
+            state.pin = self.pin.clone().map(PinGuard::new);
+            #[cfg(feature = "security")]
+            {
+                let config = self.pin.clone().unwrap_or_default();
+                state.totp = self.totp_secret.as_deref().map(|secret| TotpGuard::new(secret, &config)).transpose()?;
+            }
+            state.default_permissions = self.default_permissions;
+            state.audit = self.audit.clone();
+        }
+
+        // Host 事件通道断开超过宽限期后断开所有 Viewer
+        {
+            let state = self.state.clone();
+            let mut shutdown_rx = shutdown_tx.subscribe();
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    tokio::select! {
+                        _ = ticker.tick() => {
+                            if state.read().await.host.expire() {
+                                state.write().await.abandon_viewers();
+                            }
+                        }
+                        _ = shutdown_rx.recv() => break,
+                    }
+                }
+            });
+        }
+
+        #[cfg(feature = "redis")]
+        if let Some(url) = self.redis_url.as_deref() {
+            let (cluster, mut events) = ClusterHandle::connect(url).await?;
+            self.state.write().await.cluster = Some(cluster);
+            let state = self.state.clone();
+            let mut shutdown_rx = shutdown_tx.subscribe();
+            tokio::spawn(async move {
+                loop {
+                    tokio::select! {
+                        Some(event) = events.recv() => state.write().await.apply_cluster_event(event),
+                        _ = shutdown_rx.recv() => break,
+                        else => break,
+                    }
+                }
+            });
+        }
 
         let app_state = AppState {
             state: self.state.clone(),
+            #[cfg(feature = "security")]
+            auth_provider: self.auth_provider.clone(),
+            #[cfg(feature = "security")]
+            require_e2ee: self.require_e2ee,
+            abuse: Arc::new(std::sync::Mutex::new(AbuseGuard::new(self.abuse.clone()))),
+            keepalive: self.keepalive,
+            video_stream: self.video_stream.clone(),
+            max_input_rate: self.max_input_rate,
+            incoming: self.incoming.clone(),
+            compression: self.compression,
+            fleet_token: self.fleet_token.clone(),
         };
 
         // 创建 CORS 层
@@ -227,19 +1192,35 @@ impl EmbeddedSignalingServer {
         let app = Router::new()
             .route("/", get(root_handler))
             .route("/health", get(health_check))
-            .route("/ws", get(ws_handler))
+            .route("/host-info", get(host_info_handler))
+            .route("/sysinfo", get(sysinfo_handler))
+            .route("/capabilities", get(capabilities_handler))
+            .route("/cursor", post(cursor_handler))
+            .route("/profile", post(profile_handler))
+            .route("/power", post(power_handler))
+            // 上传大小由接收目录按 host.max_upload_mb 限制，不使用默认的 2MB 请求体上限
+            .route("/upload", post(upload_handler).layer(DefaultBodyLimit::disable()))
+            .route("/permissions", get(permissions_handler))
+            .route("/chat", get(chat_history_handler).post(chat_post_handler))
+            .route("/devices", get(devices_handler))
+            .route("/video", get(video_ws_handler))
+            .route("/ws", get(ws_handler));
+        #[cfg(feature = "metrics")]
+        let app = app.route("/metrics", get(metrics_handler));
+        let app = app
+            .layer(middleware::from_fn_with_state(app_state.clone(), abuse_guard))
             .layer(cors)
             .with_state(app_state);
 
-        let addr: SocketAddr = format!("0.0.0.0:{}", self.port).parse()?;
-        let listener = tokio::net::TcpListener::bind(addr).await?;
-        let actual_port = listener.local_addr()?.port();
+        let listener = bind_dual_stack(self.port)?;
+        let local_addr = listener.local_addr()?;
+        let actual_port = local_addr.port();
 
-        tracing::info!("内嵌信令服务器启动: 0.0.0.0:{}", actual_port);
+        tracing::info!("内嵌信令服务器启动: {}", local_addr);
 
         let mut shutdown_rx = shutdown_tx.subscribe();
         tokio::spawn(async move {
-            axum::serve(listener, app)
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
                 .with_graceful_shutdown(async move {
                     let _ = shutdown_rx.recv().await;
                 })
@@ -255,15 +1236,31 @@ impl EmbeddedSignalingServer {
         self.host_event_rx.take()
     }
 
-    /// 发送 Answer 给 Viewer
-    pub async fn send_answer(&self, to: &str, sdp: &str) {
+    /// Host 重新注册用的恢复令牌 (每次注册后更换)
+    pub async fn host_resume_token(&self) -> Option<String> {
+        self.state.read().await.host.token()
+    }
+
+    /// Host 事件循环重启后凭恢复令牌重新注册，取回新的事件接收器
+    ///
+    /// 断开期间暂存的事件 (Viewer 加入/离开、Offer、ICE 等) 按原顺序重放。
+    /// 超过 `HOST_GRACE` 后 Viewer 已被断开，返回错误
+    pub async fn reattach_host(&self, token: &str) -> Result<mpsc::UnboundedReceiver<HostSignalEvent>> {
+        self.state.read().await.host.reattach(token)
+    }
+
+    /// 发送 Answer 给 Viewer (附带断线恢复用的会话令牌)
+    pub async fn send_answer(&self, to: &str, sdp: &str, session_id: &str) {
+        let state = self.state.read().await;
         let msg = SignalMessage::Answer {
             from: "host".to_string(),
             to: to.to_string(),
             sdp: sdp.to_string(),
+            session_id: Some(session_id.to_string()),
+            session_token: state.session_token(to),
         };
         if let Ok(json) = serde_json::to_string(&msg) {
-            self.state.read().await.send_to(to, &json);
+            state.send_to(to, &json);
         }
     }
 
@@ -281,29 +1278,116 @@ impl EmbeddedSignalingServer {
         }
     }
 
-    /// 停止服务器
-    pub fn stop(&self) {
-        if let Some(ref tx) = self.shutdown_tx {
-            let _ = tx.send(());
+    /// 发送打洞信息给 Viewer
+    pub async fn send_punch(&self, to: &str, candidate: Option<PunchCandidate>) {
+        let msg = SignalMessage::Punch {
+            from: "host".to_string(),
+            to: to.to_string(),
+            candidate,
+        };
+        if let Ok(json) = serde_json::to_string(&msg) {
+            self.state.read().await.send_to(to, &json);
         }
     }
 
-    /// 获取监听端口
-    pub fn port(&self) -> u16 {
-        self.port
+    /// 发送错误消息给 Viewer (如拒绝连接)
+    pub async fn send_error(&self, to: &str, message: &str) {
+        let msg = SignalMessage::Error {
+            message: message.to_string(),
+        };
+        if let Ok(json) = serde_json::to_string(&msg) {
+            self.state.read().await.send_to(to, &json);
+        }
     }
+
+    /// 向已审批的 Web 查看器下发一次性票据 (该 Viewer 已断开或未通过验证时不下发)
+    pub async fn grant_ticket(&self, peer_id: &str) -> bool {
+        let mut state = self.state.write().await;
+        if !state.verified(peer_id) {
+            return false;
+        }
+        let session_id = state.session_id(peer_id);
+        let ticket = state.tickets.issue(peer_id, &session_id);
+        let msg = SignalMessage::Ticket { ticket, peer_id: peer_id.to_string() };
+        match serde_json::to_string(&msg) {
+            Ok(json) => state.send_to(peer_id, &json),
+            Err(_) => false,
+        }
+    }
+
+    /// 记录一条聊天消息 (被控端或 WebRTC 控制端发出)，Web 查看器下次轮询时收到
+    pub async fn post_chat(&self, from: &str, text: &str) -> Option<ChatMessage> {
+        self.state.write().await.chat.post(from, text)
+    }
+
+    /// Web 查看器的 H.264 码流 (编码循环向其发布)
+    pub fn video_stream(&self) -> Arc<VideoStream> {
+        self.video_stream.clone()
+    }
+
+    /// 停止服务器
+    pub fn stop(&self) {
+        if let Some(ref tx) = self.shutdown_tx {
+            let _ = tx.send(());
+        }
+    }
+
+    /// 获取监听端口
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+/// 监听所有 IPv4 和 IPv6 地址 (双栈)，系统不支持 IPv6 时只监听 IPv4
+fn bind_dual_stack(port: u16) -> Result<tokio::net::TcpListener> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let bind = |addr: SocketAddr| -> std::io::Result<Socket> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        if addr.is_ipv6() {
+            // Windows 默认只接受 IPv6，需显式关闭 IPV6_V6ONLY
+            socket.set_only_v6(false)?;
+        }
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        socket.bind(&addr.into())?;
+        socket.listen(1024)?;
+        socket.set_nonblocking(true)?;
+        Ok(socket)
+    };
+
+    let socket = match bind(SocketAddr::from((std::net::Ipv6Addr::UNSPECIFIED, port))) {
+        Ok(socket) => socket,
+        Err(e) => {
+            tracing::debug!("IPv6 不可用 ({})，仅监听 IPv4", e);
+            bind(SocketAddr::from((std::net::Ipv4Addr::UNSPECIFIED, port)))?
+        }
+    };
+    Ok(tokio::net::TcpListener::from_std(socket.into())?)
 }
 
 /// 根路径处理 - 同时支持健康检查和 WebSocket
 async fn root_handler(
     ws: Option<WebSocketUpgrade>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    Extension(ClientIp(ip)): Extension<ClientIp>,
     State(app_state): State<AppState>,
 ) -> impl IntoResponse {
     tracing::debug!("根路径请求, WebSocket升级: {}", ws.is_some());
     if let Some(ws) = ws {
+        let auth = match authorize(&app_state, &headers, &query).await {
+            Ok(auth) => auth,
+            Err(status) => return status.into_response(),
+        };
+        if let Err(rejection) = lock_abuse(&app_state).connect(ip) {
+            tracing::warn!("拒绝来自 {} 的连接: {:?}", ip, rejection);
+            return StatusCode::TOO_MANY_REQUESTS.into_response();
+        }
         tracing::info!("接受 WebSocket 连接");
-        ws.on_upgrade(move |socket| handle_socket(socket, app_state))
-            .into_response()
+        let slot = ConnectionSlot { abuse: app_state.abuse.clone(), ip };
+        let identity = fleet_identity(&app_state, &headers, &query, auth.as_ref());
+        upgrade_signaling(ws, &headers, app_state, slot, auth.map(|auth| auth.limit), identity)
     } else {
         tracing::debug!("HTTP 健康检查");
         Html("sscontrol signaling server - OK").into_response()
@@ -315,18 +1399,686 @@ async fn health_check() -> impl IntoResponse {
     Html("OK")
 }
 
+/// Host 能力声明 (供 `sscontrol probe` 在连接前检查兼容性，无需认证)
+async fn capabilities_handler(State(app_state): State<AppState>) -> impl IntoResponse {
+    #[cfg_attr(not(feature = "security"), allow(unused_mut))]
+    let mut capabilities = HostCapabilities::local();
+    #[cfg(feature = "security")]
+    {
+        capabilities.auth_required = app_state.auth_provider.is_some();
+    }
+    #[cfg(not(feature = "security"))]
+    let _ = app_state;
+    Json(capabilities)
+}
+
+/// Prometheus 指标
+#[cfg(feature = "metrics")]
+async fn metrics_handler(
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    use super::metrics::{metrics, ConnectionGauges};
+
+    if let Err(status) = authorize(&app_state, &headers, &query).await {
+        return status.into_response();
+    }
+    let connections = {
+        let state = app_state.state.read().await;
+        ConnectionGauges {
+            clients: state.clients.len(),
+            active_sessions: state.admission.active_count(),
+            queued: state.admission.queue_len(),
+        }
+    };
+    (
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics().render(&connections),
+    )
+        .into_response()
+}
+
+/// Host 时区/区域信息 (供 Web 查看器状态栏显示)
+async fn host_info_handler(
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    if let Err(status) = authorize(&app_state, &headers, &query).await {
+        return status.into_response();
+    }
+    Json(HostInfo::current()).into_response()
+}
+
+/// 系统信息 (Web 查看器侧边栏和 `sscontrol info` 使用，`GET /sysinfo?top=N&ticket=<票据>`)
+///
+/// 仅查看的会话也可以使用，但需要票据证明请求方已通过验证
+async fn sysinfo_handler(
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    use crate::system::{SysInfoRequest, SystemReport};
+
+    if let Err(status) = authorize(&app_state, &headers, &query).await {
+        return status.into_response();
+    }
+    let peer_id = match redeem_ticket(&app_state, &query).await {
+        Ok(peer_id) => peer_id,
+        Err(status) => return status.into_response(),
+    };
+    app_state.state.read().await.record_audit(AuditEvent::SysInfo { peer: peer_id });
+
+    let request = SysInfoRequest { top: query.get("top").and_then(|v| v.parse().ok()) };
+    let top = request.top();
+    match tokio::task::spawn_blocking(move || SystemReport::collect(top)).await {
+        Ok(report) => Json(report).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// 切换画面中的鼠标指针 (Web 查看器使用，`POST /cursor?show=true|false&ticket=<票据>`)
+async fn cursor_handler(
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    if let Err(status) = authorize(&app_state, &headers, &query).await {
+        return status.into_response();
+    }
+    let peer_id = match redeem_ticket(&app_state, &query).await {
+        Ok(peer_id) => peer_id,
+        Err(status) => return status.into_response(),
+    };
+    let Some(show) = query.get("show").and_then(|v| v.parse::<bool>().ok()) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    let state = app_state.state.read().await;
+    state.record_audit(AuditEvent::Cursor { peer: peer_id.clone(), show });
+    state.forward_to_host(HostSignalEvent::Cursor { from: peer_id, show });
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// 切换画质预设 (Web 查看器使用，`POST /profile?name=low-latency|balanced|...&ticket=<票据>`)
+async fn profile_handler(
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    if let Err(status) = authorize(&app_state, &headers, &query).await {
+        return status.into_response();
+    }
+    let peer_id = match redeem_ticket(&app_state, &query).await {
+        Ok(peer_id) => peer_id,
+        Err(status) => return status.into_response(),
+    };
+    let Some(profile) = query.get("name").and_then(|v| v.parse::<QualityProfile>().ok()) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    let state = app_state.state.read().await;
+    state.record_audit(AuditEvent::Profile { peer: peer_id.clone(), profile });
+    state.forward_to_host(HostSignalEvent::Profile {
+        from: peer_id,
+        profile,
+    });
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// 电源操作 (Web 查看器使用，`POST /power?action=lock|logout|reboot|shutdown&ticket=<票据>`)
+///
+/// 按票据签发对象的会话权限检查，无权限时返回 403
+async fn power_handler(
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    if let Err(status) = authorize(&app_state, &headers, &query).await {
+        return status.into_response();
+    }
+    let peer_id = match redeem_ticket(&app_state, &query).await {
+        Ok(peer_id) => peer_id,
+        Err(status) => return status.into_response(),
+    };
+    let Some(action) = query.get("action").and_then(|v| v.parse::<PowerAction>().ok()) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    let state = app_state.state.read().await;
+    let permission = action.required_permission();
+    if !state.permissions_of(&peer_id).allows(permission) {
+        tracing::warn!("拒绝 Viewer {} 的{}请求: 没有{}权限", peer_id, action.label(), permission.label());
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    state.record_audit(AuditEvent::Power { peer: peer_id.clone(), action });
+    state.forward_to_host(HostSignalEvent::Power {
+        from: peer_id,
+        action,
+    });
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// Web 查看器上传文件 (`POST /upload?name=<文件名>&ticket=<票据>`，请求体为文件内容)
+///
+/// 按票据签发对象的会话权限检查，文件写入 Host 的接收目录，返回保存后的文件名 (重名时会改名)
+async fn upload_handler(
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    State(app_state): State<AppState>,
+    body: Body,
+) -> impl IntoResponse {
+    if let Err(status) = authorize(&app_state, &headers, &query).await {
+        return status.into_response();
+    }
+    let peer_id = match redeem_ticket(&app_state, &query).await {
+        Ok(peer_id) => peer_id,
+        Err(status) => return status.into_response(),
+    };
+    let Some(name) = query.get("name") else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if !app_state.state.read().await.permissions_of(&peer_id).allows(Permission::FileTransfer) {
+        tracing::warn!("拒绝 Viewer {} 上传文件: 没有{}权限", peer_id, Permission::FileTransfer.label());
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let file = match app_state.incoming.receive(name, body.into_data_stream()).await {
+        Ok(file) => file,
+        Err(e) if e.downcast_ref::<TooLarge>().is_some() => {
+            tracing::warn!("拒绝 Viewer {} 上传 {}: {}", peer_id, name, e);
+            return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+        }
+        Err(e) => {
+            tracing::warn!("保存 Viewer {} 上传的 {} 失败: {}", peer_id, name, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let state = app_state.state.read().await;
+    state.record_audit(AuditEvent::FileTransferred {
+        peer: peer_id.clone(),
+        name: file.name(),
+        bytes: file.bytes,
+    });
+    state.forward_to_host(HostSignalEvent::FileReceived {
+        from: peer_id,
+        path: file.path.clone(),
+    });
+    (StatusCode::CREATED, Json(serde_json::json!({ "name": file.name(), "bytes": file.bytes }))).into_response()
+}
+
+/// 聊天记录 (Web 查看器轮询，`GET /chat?after=<id>&ticket=<票据>` 返回 ID 更大的消息)
+async fn chat_history_handler(
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    if let Err(status) = authorize(&app_state, &headers, &query).await {
+        return status.into_response();
+    }
+    if let Err(status) = redeem_ticket(&app_state, &query).await {
+        return status.into_response();
+    }
+    let after = query.get("after").and_then(|v| v.parse::<u64>().ok()).unwrap_or(0);
+    Json(app_state.state.read().await.chat.since(after)).into_response()
+}
+
+/// Web 查看器发送聊天消息 (`POST /chat?ticket=<票据>`，JSON `{"text": ...}`，发送者为票据签发对象)
+async fn chat_post_handler(
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    State(app_state): State<AppState>,
+    Json(post): Json<ChatPost>,
+) -> impl IntoResponse {
+    if let Err(status) = authorize(&app_state, &headers, &query).await {
+        return status.into_response();
+    }
+    let peer_id = match redeem_ticket(&app_state, &query).await {
+        Ok(peer_id) => peer_id,
+        Err(status) => return status.into_response(),
+    };
+    let mut state = app_state.state.write().await;
+    let Some(message) = state.chat.post(&peer_id, &post.text) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    state.record_audit(AuditEvent::Chat { peer: peer_id });
+    state.forward_to_host(HostSignalEvent::Chat { message: message.clone() });
+    Json(message).into_response()
+}
+
+/// 默认会话权限 (Web 查看器据此禁用无权限的控件)
+async fn permissions_handler(
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    if let Err(status) = authorize(&app_state, &headers, &query).await {
+        return status.into_response();
+    }
+    Json(app_state.state.read().await.default_permissions).into_response()
+}
+
+/// 设备清单 (`sscontrol fleet list` 使用，需要与设备登记相同的认证)
+async fn devices_handler(
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    let auth = match authorize(&app_state, &headers, &query).await {
+        Ok(auth) => auth,
+        Err(status) => return status.into_response(),
+    };
+    if fleet_identity(&app_state, &headers, &query, auth.as_ref()).is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    Json(app_state.state.read().await.presence.list(PresenceRegistry::now_ms())).into_response()
+}
+
+/// 请求来源 IP (由 `abuse_guard` 写入请求扩展)
+#[derive(Debug, Clone, Copy)]
+struct ClientIp(IpAddr);
+
+/// 请求来源 IP
+///
+/// 只有来自本机的连接 (Cloudflare Tunnel 等反向代理) 才采信转发头，否则任何人都能伪造来源。
+/// 双栈监听时 IPv4 客户端的地址是 `::ffff:a.b.c.d`，统一还原为 IPv4 地址
+fn client_ip(addr: SocketAddr, headers: &HeaderMap) -> IpAddr {
+    let ip = addr.ip().to_canonical();
+    if !ip.is_loopback() {
+        return ip;
+    }
+    headers
+        .get("cf-connecting-ip")
+        .or_else(|| headers.get("x-forwarded-for"))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .and_then(|forwarded| forwarded.trim().parse::<IpAddr>().ok())
+        .map_or(ip, |forwarded| forwarded.to_canonical())
+}
+
+/// 防滥用中间件: 拒绝封禁中的 IP，并把 401 响应计为认证失败
+async fn abuse_guard(
+    State(app_state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let ip = client_ip(addr, request.headers());
+    if let Err(Rejection::Banned { retry_after }) = lock_abuse(&app_state).check_ban(ip) {
+        return (
+            StatusCode::FORBIDDEN,
+            [(header::RETRY_AFTER, retry_after.as_secs().max(1).to_string())],
+            "认证失败次数过多，已暂时封禁",
+        )
+            .into_response();
+    }
+
+    request.extensions_mut().insert(ClientIp(ip));
+    let response = next.run(request).await;
+    if response.status() == StatusCode::UNAUTHORIZED && lock_abuse(&app_state).auth_failed(ip) {
+        tracing::warn!("{} 认证失败次数过多，已封禁", ip);
+    }
+    response
+}
+
+fn lock_abuse(app_state: &AppState) -> std::sync::MutexGuard<'_, AbuseGuard> {
+    app_state.abuse.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 占用的 WebSocket 连接名额 (释放时归还，升级失败时也会归还)
+struct ConnectionSlot {
+    abuse: Arc<std::sync::Mutex<AbuseGuard>>,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.abuse.lock().unwrap_or_else(|e| e.into_inner()).disconnect(self.ip);
+    }
+}
+
 /// WebSocket 处理 (路径 /ws)
-async fn ws_handler(ws: WebSocketUpgrade, State(app_state): State<AppState>) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, app_state))
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    Extension(ClientIp(ip)): Extension<ClientIp>,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    let auth = match authorize(&app_state, &headers, &query).await {
+        Ok(auth) => auth,
+        Err(status) => return status.into_response(),
+    };
+    if let Err(rejection) = lock_abuse(&app_state).connect(ip) {
+        tracing::warn!("拒绝来自 {} 的连接: {:?}", ip, rejection);
+        return StatusCode::TOO_MANY_REQUESTS.into_response();
+    }
+    let slot = ConnectionSlot { abuse: app_state.abuse.clone(), ip };
+    let identity = fleet_identity(&app_state, &headers, &query, auth.as_ref());
+    upgrade_signaling(ws, &headers, app_state, slot, auth.map(|auth| auth.limit), identity)
+}
+
+/// 升级为信令连接，客户端提出且服务器允许时同意压缩文本消息
+///
+/// `limit`: 认证角色允许的权限上限；`identity`: 设备登记使用的身份 (见 `fleet_identity`)
+fn upgrade_signaling(
+    ws: WebSocketUpgrade,
+    headers: &HeaderMap,
+    app_state: AppState,
+    slot: ConnectionSlot,
+    limit: Option<SessionPermissions>,
+    identity: Option<String>,
+) -> Response {
+    let compressed = app_state.compression
+        && compression::accepts(headers.get(compression::HEADER).and_then(|value| value.to_str().ok()));
+    let mut response = ws
+        .on_upgrade(move |socket| handle_socket(socket, app_state, slot, limit, identity, compressed))
+        .into_response();
+    if compressed {
+        response
+            .headers_mut()
+            .insert(compression::HEADER, header::HeaderValue::from_static(compression::DEFLATE));
+    }
+    response
+}
+
+/// H.264 视频流 (路径 /video?ticket=<票据>，Web 查看器没有 WebRTC 连接时使用)
+///
+/// 票据签发对象的信令连接断开或失去放行后视频流随之关闭
+async fn video_ws_handler(
+    ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    Extension(ClientIp(ip)): Extension<ClientIp>,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    if let Err(status) = authorize(&app_state, &headers, &query).await {
+        return status.into_response();
+    }
+    let peer_id = match redeem_ticket(&app_state, &query).await {
+        Ok(peer_id) => peer_id,
+        Err(status) => return status.into_response(),
+    };
+    // 视频流不经过 E2EE 握手，要求加密时只能使用 WebRTC
+    #[cfg(feature = "security")]
+    if app_state.require_e2ee {
+        tracing::warn!("拒绝来自 {} 的视频流请求: 已要求端到端加密", ip);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if let Err(rejection) = lock_abuse(&app_state).connect(ip) {
+        tracing::warn!("拒绝来自 {} 的连接: {:?}", ip, rejection);
+        return StatusCode::TOO_MANY_REQUESTS.into_response();
+    }
+    let slot = ConnectionSlot { abuse: app_state.abuse.clone(), ip };
+    let stream = app_state.video_stream.clone();
+    let keepalive = app_state.keepalive;
+    app_state.state.read().await.record_audit(AuditEvent::VideoStream { peer: peer_id.clone() });
+    // 输入以票据签发对象的身份参与控制权仲裁，按该 Viewer 当前的会话权限检查
+    let input = WebInput {
+        peer_id: peer_id.clone(),
+        state: app_state.state.clone(),
+        touch: TouchTranslator::default(),
+        coalescer: InputCoalescer::new(app_state.max_input_rate),
+    };
+    let viewer = VideoViewer { peer_id, state: app_state.state.clone() };
+    ws.on_upgrade(move |socket| send_video_stream(socket, stream, keepalive, viewer, input, slot))
+        .into_response()
+}
+
+/// `/video` 连接所属的 Viewer (票据的签发对象)
+struct VideoViewer {
+    peer_id: String,
+    state: Arc<RwLock<ServerState>>,
+}
+
+impl VideoViewer {
+    /// 信令连接是否仍然有效 (未断开、未失去放行)
+    async fn is_verified(&self) -> bool {
+        self.state.read().await.verified(&self.peer_id)
+    }
+}
+
+/// Web 查看器通过 `/video` 连接发送的输入 (`peer_id` 为票据签发对象)
+struct WebInput {
+    peer_id: String,
+    state: Arc<RwLock<ServerState>>,
+    touch: TouchTranslator,
+    coalescer: InputCoalescer,
+}
+
+impl WebInput {
+    /// 解析一条 JSON 输入事件，转换触摸手势、合并鼠标移动后转发给 Host
+    async fn forward(&mut self, text: &str) {
+        let event = match serde_json::from_str::<InputEvent>(text) {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::debug!("忽略无法解析的输入消息: {}", e);
+                return;
+            }
+        };
+        let now = std::time::Instant::now();
+        let events: Vec<_> = self.touch.translate(event).into_iter().flat_map(|event| self.coalescer.push(event, now)).collect();
+        self.send(events).await;
+    }
+
+    /// 转发限速到期的鼠标移动/滚动
+    async fn forward_due(&mut self) {
+        let events = self.coalescer.poll(std::time::Instant::now());
+        self.send(events).await;
+    }
+
+    /// 转发给 Host (信令连接已失效或没有输入权限时丢弃，权限随时可能被 Host 收回)
+    async fn send(&self, events: Vec<InputEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        let state = self.state.read().await;
+        if !state.verified(&self.peer_id) || !state.permissions_of(&self.peer_id).allows(Permission::Input) {
+            tracing::debug!("丢弃 Viewer {} 的输入: 没有{}权限", self.peer_id, Permission::Input.label());
+            return;
+        }
+        for event in events {
+            state.forward_to_host(HostSignalEvent::Input { from: self.peer_id.clone(), event });
+        }
+    }
+
+    /// 连接断开: 松开仍按住的按键并释放控制权
+    async fn close(mut self) {
+        let mut events = self.coalescer.flush();
+        events.extend(self.touch.reset());
+        self.send(events).await;
+        self.state.read().await.forward_to_host(HostSignalEvent::InputClosed { from: self.peer_id });
+    }
+}
+
+/// 向一个 `/video` 连接转发码流，并接收该连接的输入 (slot 在连接结束时释放)
+async fn send_video_stream(
+    socket: WebSocket,
+    stream: Arc<VideoStream>,
+    keepalive: KeepaliveConfig,
+    viewer: VideoViewer,
+    mut input: WebInput,
+    _slot: ConnectionSlot,
+) {
+    let (mut ws_sender, mut ws_receiver) = socket.split();
+    let mut frames = stream.subscribe();
+    let mut notices = stream.notices();
+    let mut session_check = tokio::time::interval(Duration::from_secs(1));
+    tracing::info!("Web 查看器 {} 开始接收视频流 (当前 {} 个)", viewer.peer_id, stream.subscriber_count());
+
+    // 从关键帧开始发送，否则浏览器解码器无法初始化
+    let mut waiting_key_frame = true;
+    loop {
+        tokio::select! {
+            frame = frames.recv() => match frame {
+                Ok(frame) => {
+                    if waiting_key_frame && !frame.key_frame {
+                        continue;
+                    }
+                    waiting_key_frame = false;
+                    if !send_with_timeout(&mut ws_sender, Message::Binary(frame.message.to_vec()), &keepalive).await {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::debug!("视频流观看者落后 {} 帧，等待下一个关键帧", skipped);
+                    waiting_key_frame = true;
+                    stream.request_key_frame();
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            notice = notices.recv() => {
+                if let Ok(text) = notice {
+                    if !send_with_timeout(&mut ws_sender, Message::Text(text.to_string()), &keepalive).await {
+                        break;
+                    }
+                }
+            }
+            _ = session_check.tick() => {
+                if !viewer.is_verified().await {
+                    tracing::info!("Viewer {} 的信令连接已失效，关闭视频流", viewer.peer_id);
+                    break;
+                }
+            }
+            message = ws_receiver.next() => match message {
+                // 浏览器解码出错或积压时请求关键帧重新开始
+                Some(Ok(Message::Text(text))) if text == "key_frame" => {
+                    waiting_key_frame = true;
+                    stream.request_key_frame();
+                }
+                // 其余文本消息是 JSON 输入事件 (鼠标、键盘、触摸、修饰键栏)
+                Some(Ok(Message::Text(text))) => input.forward(&text).await,
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            _ = input.coalescer.due() => input.forward_due().await,
+        }
+    }
+    input.close().await;
+    tracing::info!("Web 查看器 {} 停止接收视频流", viewer.peer_id);
+}
+
+/// 使用请求中的一次性票据 (`?ticket=`)，返回签发对象；缺少或无效时返回 401
+async fn redeem_ticket(app_state: &AppState, query: &HashMap<String, String>) -> Result<String, StatusCode> {
+    let Some(ticket) = query.get("ticket") else {
+        tracing::warn!("拒绝没有票据的 Web 查看器请求");
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    app_state.state.write().await.redeem_ticket(ticket).ok_or_else(|| {
+        tracing::warn!("拒绝无效或已使用的票据");
+        StatusCode::UNAUTHORIZED
+    })
+}
+
+/// 校验 WebSocket 升级请求的凭据，返回认证角色允许的权限上限 (未配置认证提供者时为 None)
+///
+/// 浏览器无法为 WebSocket 设置请求头，因此也接受 `?token=` 查询参数作为 Bearer Token
+#[cfg(feature = "security")]
+async fn authorize(
+    app_state: &AppState,
+    headers: &HeaderMap,
+    query: &HashMap<String, String>,
+) -> Result<Option<Authenticated>, StatusCode> {
+    use crate::security::Credentials;
+
+    let Some(provider) = &app_state.auth_provider else {
+        return Ok(None);
+    };
+
+    let credentials = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Credentials::from_authorization)
+        .or_else(|| query.get("token").map(|token| Credentials::Bearer(token.clone())))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    match provider.authenticate(&credentials).await {
+        Ok(identity) => {
+            tracing::info!(
+                "认证成功: {} ({:?}, {})",
+                identity.subject, identity.role, identity.provider
+            );
+            Ok(Some(Authenticated {
+                identity: format!("{}:{}", identity.provider, identity.subject),
+                limit: identity.role.permissions(),
+            }))
+        }
+        Err(e) => {
+            tracing::warn!("认证失败 ({}): {}", provider.name(), e);
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
+#[cfg(not(feature = "security"))]
+async fn authorize(
+    _app_state: &AppState,
+    _headers: &HeaderMap,
+    _query: &HashMap<String, String>,
+) -> Result<Option<Authenticated>, StatusCode> {
+    Ok(None)
+}
+
+/// 通过认证的请求
+struct Authenticated {
+    /// 身份 (`<认证提供者>:<用户标识>`)
+    identity: String,
+    /// 认证角色允许的权限上限
+    limit: SessionPermissions,
+}
+
+/// 设备清单使用的身份: 认证提供者的身份，或出示了 `fleet.registration_token` 的连接
+///
+/// 同一令牌登记的设备视为同一身份
+fn fleet_identity(
+    app_state: &AppState,
+    headers: &HeaderMap,
+    query: &HashMap<String, String>,
+    auth: Option<&Authenticated>,
+) -> Option<String> {
+    if let Some(auth) = auth {
+        return Some(auth.identity.clone());
+    }
+    let expected = app_state.fleet_token.as_deref()?;
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| query.get("token").map(String::as_str))?;
+    constant_time_eq(token.trim().as_bytes(), expected.as_bytes()).then(|| "fleet-token".to_string())
 }
 
-/// 处理 WebSocket 连接
-async fn handle_socket(socket: WebSocket, app_state: AppState) {
+/// 处理 WebSocket 连接 (slot 在连接结束时释放)
+/// `limit`: 认证角色允许的权限上限 (None = 不限制)
+/// `identity`: 设备登记使用的身份 (None = 不能登记设备)
+/// `compressed`: 握手时已协商压缩文本消息 (见 `network::compression`)
+async fn handle_socket(
+    socket: WebSocket,
+    app_state: AppState,
+    slot: ConnectionSlot,
+    limit: Option<SessionPermissions>,
+    identity: Option<String>,
+    compressed: bool,
+) {
+    #[cfg(feature = "security")]
+    let mut socket = socket;
+    #[cfg(feature = "security")]
+    let Some((first_message, mut sealer, mut opener)) =
+        accept_e2ee(&mut socket, app_state.require_e2ee).await
+    else {
+        return;
+    };
+
     let peer_id = {
         let state = app_state.state.read().await;
         state.next_peer_id()
     };
 
+    // 该连接的日志都带上 peer_id，协商后补上 session_id
+    let span = crate::logging::peer_span(&peer_id);
+
     let (mut ws_sender, mut ws_receiver) = socket.split();
     let (tx, mut rx) = mpsc::unbounded_channel::<String>();
 
@@ -334,26 +2086,144 @@ async fn handle_socket(socket: WebSocket, app_state: AppState) {
     {
         let mut state = app_state.state.write().await;
         state.clients.insert(peer_id.clone(), ClientSender { sender: tx });
+        if let Some(limit) = limit {
+            state.limits.insert(peer_id.clone(), limit);
+        }
+        if let Some(identity) = identity {
+            state.identities.insert(peer_id.clone(), identity);
+        }
+        state.record_audit(AuditEvent::Connect {
+            peer: peer_id.clone(),
+            addr: slot.ip.to_string(),
+            transport: "websocket".to_string(),
+        });
     }
 
-    tracing::info!("Viewer 连接: {}", peer_id);
+    tracing::info!(parent: &span, "Viewer 连接: {}", peer_id);
+    #[cfg(feature = "metrics")]
+    super::metrics::metrics().viewer_connected();
 
-    // 发送任务
+    // 发送任务 (同时负责心跳: 定期 Ping，超时未收到数据时结束连接)
+    let keepalive = app_state.keepalive;
+    let liveness = Liveness::new();
+    let send_liveness = liveness.clone();
     let send_task = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            if ws_sender.send(Message::Text(msg)).await.is_err() {
+        let mut ticker = keepalive.is_enabled().then(|| tokio::time::interval(keepalive.ping_interval));
+        loop {
+            let msg = tokio::select! {
+                msg = rx.recv() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                _ = async {
+                    match ticker.as_mut() {
+                        Some(ticker) => {
+                            ticker.tick().await;
+                        }
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    if send_liveness.is_dead(keepalive.pong_timeout) {
+                        tracing::warn!("{} 秒未收到 Viewer 数据，关闭连接", keepalive.pong_timeout.as_secs());
+                        break;
+                    }
+                    if !send_with_timeout(&mut ws_sender, Message::Ping(Vec::new()), &keepalive).await {
+                        break;
+                    }
+                    continue;
+                }
+            };
+
+            #[cfg(feature = "security")]
+            let msg = match sealer.as_mut().map(|sealer| sealer.seal(msg.as_bytes())) {
+                Some(Ok(frame)) => Message::Binary(frame),
+                Some(Err(e)) => {
+                    tracing::warn!("E2EE 加密失败: {}", e);
+                    break;
+                }
+                None => Message::Text(msg),
+            };
+            #[cfg(not(feature = "security"))]
+            let msg = Message::Text(msg);
+            let msg = match msg {
+                Message::Text(text) if compressed => match compression::compress(&text) {
+                    Some(data) => Message::Binary(data),
+                    None => Message::Text(text),
+                },
+                msg => msg,
+            };
+
+            if !send_with_timeout(&mut ws_sender, msg, &keepalive).await {
                 break;
             }
         }
-    });
+    }.instrument(span.clone()));
 
     // 接收任务
     let state_clone = app_state.state.clone();
     let peer_id_clone = peer_id.clone();
+    let (mut bucket, max_dropped) = {
+        let abuse = lock_abuse(&app_state);
+        (abuse.message_bucket(), abuse.config().message_burst)
+    };
+    let mut dropped = 0u32;
     let recv_task = tokio::spawn(async move {
+        // 握手阶段读到的首条明文消息
+        #[cfg(feature = "security")]
+        if let Some(text) = first_message {
+            if let Ok(signal) = serde_json::from_str::<SignalMessage>(&text) {
+                handle_signal(signal, &peer_id_clone, &state_clone).await;
+            }
+        }
+
         while let Some(msg) = ws_receiver.next().await {
+            if msg.is_ok() {
+                liveness.touch();
+            }
+            // 超出速率的消息直接丢弃，持续超限 (丢弃数达到突发上限) 时断开
+            if !bucket.try_take() {
+                dropped += 1;
+                if dropped >= max_dropped {
+                    tracing::warn!("Viewer {} 消息速率持续超限，断开连接", peer_id_clone);
+                    break;
+                }
+                continue;
+            }
+            dropped = 0;
+
             match msg {
+                #[cfg(feature = "security")]
+                Ok(Message::Binary(frame)) if opener.is_some() => {
+                    let Some(opener) = opener.as_mut() else { continue };
+                    match opener.open(&frame) {
+                        Ok(plaintext) => {
+                            if let Ok(signal) = serde_json::from_slice::<SignalMessage>(&plaintext) {
+                                handle_signal(signal, &peer_id_clone, &state_clone).await;
+                            }
+                        }
+                        Err(e) => {
+                            tracing::warn!("丢弃无法解密的消息: {}", e);
+                        }
+                    }
+                }
+                Ok(Message::Binary(data)) if compressed && compression::is_compressed(&data) => {
+                    match compression::decompress(&data) {
+                        Ok(text) => {
+                            if let Ok(signal) = serde_json::from_str::<SignalMessage>(&text) {
+                                handle_signal(signal, &peer_id_clone, &state_clone).await;
+                            }
+                        }
+                        Err(e) => {
+                            tracing::warn!("丢弃无法解压的消息: {}", e);
+                        }
+                    }
+                }
                 Ok(Message::Text(text)) => {
+                    #[cfg(feature = "security")]
+                    if opener.is_some() {
+                        tracing::warn!("E2EE 会话中收到明文消息，已丢弃");
+                        continue;
+                    }
                     if let Ok(signal) = serde_json::from_str::<SignalMessage>(&text) {
                         handle_signal(signal, &peer_id_clone, &state_clone).await;
                     }
@@ -366,26 +2236,112 @@ async fn handle_socket(socket: WebSocket, app_state: AppState) {
                 _ => {}
             }
         }
-    });
+    }.instrument(span.clone()));
 
     tokio::select! {
         _ = send_task => {}
         _ = recv_task => {}
     }
 
-    // 清理
-    let mut state = app_state.state.write().await;
-    state.clients.remove(&peer_id);
+    app_state.state.write().await.disconnect(&peer_id);
+    drop(slot);
+    tracing::info!(parent: &span, "Viewer 断开: {}", peer_id);
+}
 
-    if let Some(room_id) = state.leave_room(&peer_id) {
-        if let Ok(msg) = serde_json::to_string(&SignalMessage::PeerLeft {
-            peer_id: peer_id.clone(),
-        }) {
-            state.broadcast_to_room(&room_id, &msg, None);
+/// 写入一条消息，启用心跳时写入超过 `pong_timeout` 视为连接已死；失败返回 false
+async fn send_with_timeout(
+    ws_sender: &mut futures_util::stream::SplitSink<WebSocket, Message>,
+    msg: Message,
+    keepalive: &KeepaliveConfig,
+) -> bool {
+    if !keepalive.is_enabled() {
+        return ws_sender.send(msg).await.is_ok();
+    }
+    match tokio::time::timeout(keepalive.pong_timeout, ws_sender.send(msg)).await {
+        Ok(result) => result.is_ok(),
+        Err(_) => {
+            tracing::warn!("写入超时 ({} 秒)，关闭连接", keepalive.pong_timeout.as_secs());
+            false
         }
     }
-
-    tracing::info!("Viewer 断开: {}", peer_id);
+}
+
+/// 服务端 E2EE 握手结果: (首条明文消息, 加密端, 解密端)
+#[cfg(feature = "security")]
+type E2eeAccept = (
+    Option<String>,
+    Option<crate::security::e2ee::E2eeSealer>,
+    Option<crate::security::e2ee::E2eeOpener>,
+);
+
+/// 读取客户端首条消息，是 `e2ee_hello` 时作为响应方完成握手
+///
+/// 返回 None 表示应断开连接
+#[cfg(feature = "security")]
+async fn accept_e2ee(socket: &mut WebSocket, require: bool) -> Option<E2eeAccept> {
+    use crate::security::e2ee::{self, E2eeHandshake, Role};
+
+    let text = match socket.recv().await? {
+        Ok(Message::Text(text)) => text,
+        Ok(Message::Close(_)) | Err(_) => return None,
+        Ok(_) if require => return None,
+        Ok(_) => return Some((None, None, None)),
+    };
+
+    if !e2ee::is_hello(&text) {
+        if require {
+            tracing::warn!("拒绝未加密的连接 (已要求端到端加密)");
+            let _ = socket.send(Message::Close(None)).await;
+            return None;
+        }
+        return Some((Some(text), None, None));
+    }
+
+    let handshake = E2eeHandshake::new(Role::Responder);
+    let hello = handshake.hello();
+    let session = match handshake.complete(&text) {
+        Ok(session) => session,
+        Err(e) => {
+            tracing::warn!("E2EE 握手失败: {}", e);
+            return None;
+        }
+    };
+    socket.send(Message::Text(hello)).await.ok()?;
+
+    tracing::info!("端到端加密已建立，安全码: {}", session.fingerprint());
+    let (sealer, opener) = session.split();
+    Some((None, Some(sealer), Some(opener)))
+}
+
+/// 校验 PIN 或无人值守访问密钥，返回结果和换发的 PIN (未启用 PIN 时返回 None)
+///
+/// Argon2 比对访问密钥耗时数百毫秒，在释放状态锁后放到阻塞线程执行，不影响其他信令
+async fn check_pin(
+    state: &Arc<RwLock<ServerState>>,
+    peer_id: &str,
+    pin: &str,
+) -> Option<(PinVerdict, Option<String>)> {
+    let (hash, otherwise) = {
+        let mut state = state.write().await;
+        let guard = state.pin.as_mut()?;
+        // 已验证的 Viewer 重复提交或使用无人值守访问密钥时 PIN 不变，无需换发
+        let previous_pin = guard.pin().to_string();
+        match guard.begin(peer_id, pin) {
+            PinAttempt::Done(verdict) => {
+                let new_pin = (guard.pin() != previous_pin).then(|| guard.pin().to_string());
+                return Some((verdict, new_pin));
+            }
+            PinAttempt::CheckSecret { hash, otherwise } => (hash, otherwise),
+        }
+    };
+
+    let secret = pin.to_string();
+    let matched = tokio::task::spawn_blocking(move || super::pin::matches_access_secret(&hash, &secret))
+        .await
+        .unwrap_or(false);
+    let mut state = state.write().await;
+    let guard = state.pin.as_mut()?;
+    Some((guard.finish(peer_id, matched, otherwise), None))
 }
 
 /// 处理信令消息
@@ -396,97 +2352,279 @@ async fn handle_signal(
 ) {
     match signal {
         SignalMessage::Join { room_id } => {
+            state.write().await.request_join(peer_id, room_id, None);
+        }
+        SignalMessage::Resume { room_id, session_id, token } => {
             let mut state = state.write().await;
-            let existing_peers = state.join_room(peer_id.to_string(), room_id.clone());
-
-            // 发送现有成员列表 (包括 host)
-            let mut peers: Vec<PeerInfo> = existing_peers
-                .iter()
-                .map(|id| PeerInfo { id: id.clone() })
-                .collect();
-            // 添加 host 作为成员
-            peers.push(PeerInfo { id: "host".to_string() });
-
-            if let Ok(msg) = serde_json::to_string(&SignalMessage::Peers { peers }) {
+            let resume = if state.verify_resume(&room_id, &session_id, token.as_deref()) {
+                Some(session_id.as_str())
+            } else {
+                tracing::warn!("Viewer {} 恢复会话 {} 的令牌无效，按新连接处理", peer_id, session_id);
+                None
+            };
+            state.request_join(peer_id, room_id, resume);
+            if let Some(session_id) = state.session_ids.get(peer_id) {
+                tracing::Span::current().record("session_id", session_id.as_str());
+            }
+        }
+        // Viewer 只能与 Host 协商: Answer 和发给其他对端的信令都是冒充 Host
+        SignalMessage::Answer { .. } => {
+            reject_role_violation(peer_id, "answer", state).await;
+        }
+        SignalMessage::Offer { ref to, .. } | SignalMessage::Ice { ref to, .. } | SignalMessage::Punch { ref to, .. }
+            if to != "host" =>
+        {
+            reject_role_violation(peer_id, "relay", state).await;
+        }
+        // 未放行 (排队中) 的 Viewer 不能与 Host 协商
+        SignalMessage::Offer { ref to, .. } | SignalMessage::Ice { ref to, .. } | SignalMessage::Punch { ref to, .. }
+            if to == "host" && !state.read().await.admission.is_active(peer_id) =>
+        {
+            tracing::debug!("忽略未放行 Viewer {} 的信令", peer_id);
+        }
+        // 未通过 PIN/TOTP 验证的 Viewer 不能发起协商
+        SignalMessage::Offer { ref to, .. } if to == "host" && !state.read().await.authenticated(peer_id) => {
+            let state = state.read().await;
+            let message = if state.pin_verified(peer_id) {
+                "请输入身份验证器 App 中的 6 位验证码"
+            } else {
+                "请先输入被控端显示的 PIN"
+            };
+            if let Ok(msg) = serde_json::to_string(&SignalMessage::Error { message: message.to_string() }) {
                 state.send_to(peer_id, &msg);
             }
-
-            // 通知其他成员 (不通知 host，因为已经通过事件通知了)
-            if let Ok(msg) = serde_json::to_string(&SignalMessage::NewPeer {
-                peer_id: peer_id.to_string(),
-            }) {
-                state.broadcast_to_room(&room_id, &msg, Some(peer_id));
+            tracing::info!("拒绝未完成验证的 Viewer {} 的 Offer", peer_id);
+        }
+        SignalMessage::Ice { ref to, .. } if to == "host" && !state.read().await.authenticated(peer_id) => {
+            tracing::debug!("忽略未完成验证的 Viewer {} 的 ICE", peer_id);
+        }
+        // 打洞信息会暴露 Host 的公网地址，只对通过验证的 Viewer 回复
+        SignalMessage::Punch { ref to, .. } if to == "host" && !state.read().await.authenticated(peer_id) => {
+            tracing::debug!("忽略未完成验证的 Viewer {} 的打洞请求", peer_id);
+        }
+        SignalMessage::Pin { pin } => {
+            let Some((verdict, new_pin)) = check_pin(state, peer_id, &pin).await else {
+                return;
+            };
+            let mut state = state.write().await;
+            // 比对访问密钥期间 Viewer 已断开: 不保留验证状态
+            if !state.clients.contains_key(peer_id) {
+                if let Some(guard) = state.pin.as_mut() {
+                    guard.forget(peer_id);
+                }
+                return;
             }
+            let result = match verdict {
+                PinVerdict::Accepted => {
+                    tracing::info!("Viewer {} 通过 PIN 验证", peer_id);
+                    SignalMessage::PinResult { accepted: true, remaining_attempts: None, retry_after_secs: None }
+                }
+                PinVerdict::Rejected { remaining } => {
+                    tracing::warn!("Viewer {} PIN 错误 (剩余 {} 次)", peer_id, remaining);
+                    SignalMessage::PinResult { accepted: false, remaining_attempts: Some(remaining), retry_after_secs: None }
+                }
+                PinVerdict::Locked { retry_after } => {
+                    tracing::warn!("PIN 验证已锁定，{} 秒后可重试 (来自 {})", retry_after.as_secs(), peer_id);
+                    SignalMessage::PinResult {
+                        accepted: false,
+                        remaining_attempts: Some(0),
+                        retry_after_secs: Some(retry_after.as_secs().max(1)),
+                    }
+                }
+            };
+            state.record_audit(AuditEvent::Auth {
+                peer: peer_id.to_string(),
+                method: AuthMethod::Pin,
+                success: matches!(result, SignalMessage::PinResult { accepted: true, .. }),
+            });
 
-            tracing::info!("Viewer {} 加入房间 {}", peer_id, room_id);
+            if let Ok(msg) = serde_json::to_string(&result) {
+                state.send_to(peer_id, &msg);
+            }
+            if let Some(pin) = new_pin {
+                state.forward_to_host(HostSignalEvent::PinChanged { pin });
+            }
         }
-        SignalMessage::Offer { to, sdp, .. } => {
-            if to == "host" {
-                // 转发给 Host
-                let state = state.read().await;
-                state.forward_to_host(HostSignalEvent::Offer {
-                    from: peer_id.to_string(),
-                    sdp,
-                });
-            } else {
-                // 转发给其他 Viewer
-                let state = state.read().await;
-                if let Ok(msg) = serde_json::to_string(&SignalMessage::Offer {
-                    from: peer_id.to_string(),
-                    to: to.clone(),
-                    sdp,
-                }) {
-                    state.send_to(&to, &msg);
+        #[cfg(feature = "security")]
+        SignalMessage::Totp { code } => {
+            let mut state = state.write().await;
+            let Some(guard) = state.totp.as_mut() else {
+                return;
+            };
+            let result = match guard.verify(peer_id, &code) {
+                PinVerdict::Accepted => {
+                    tracing::info!("Viewer {} 通过 TOTP 验证", peer_id);
+                    SignalMessage::TotpResult { accepted: true, remaining_attempts: None, retry_after_secs: None }
                 }
+                PinVerdict::Rejected { remaining } => {
+                    tracing::warn!("Viewer {} TOTP 验证码错误 (剩余 {} 次)", peer_id, remaining);
+                    SignalMessage::TotpResult { accepted: false, remaining_attempts: Some(remaining), retry_after_secs: None }
+                }
+                PinVerdict::Locked { retry_after } => {
+                    tracing::warn!("TOTP 验证已锁定，{} 秒后可重试 (来自 {})", retry_after.as_secs(), peer_id);
+                    SignalMessage::TotpResult {
+                        accepted: false,
+                        remaining_attempts: Some(0),
+                        retry_after_secs: Some(retry_after.as_secs().max(1)),
+                    }
+                }
+            };
+            state.record_audit(AuditEvent::Auth {
+                peer: peer_id.to_string(),
+                method: AuthMethod::Totp,
+                success: matches!(result, SignalMessage::TotpResult { accepted: true, .. }),
+            });
+            if let Ok(msg) = serde_json::to_string(&result) {
+                state.send_to(peer_id, &msg);
             }
         }
-        SignalMessage::Answer { to, sdp, .. } => {
-            let state = state.read().await;
-            if let Ok(msg) = serde_json::to_string(&SignalMessage::Answer {
+        SignalMessage::Offer { sdp, .. } => {
+            // 转发给 Host
+            let mut state = state.write().await;
+            let session_id = state.session_id(peer_id);
+            tracing::Span::current().record("session_id", session_id.as_str());
+            state.forward_to_host(HostSignalEvent::Offer {
                 from: peer_id.to_string(),
-                to: to.clone(),
                 sdp,
-            }) {
-                state.send_to(&to, &msg);
-            }
+                session_id,
+            });
         }
         SignalMessage::Ice {
-            to,
             candidate,
             sdp_mid,
             sdp_mline_index,
             ..
         } => {
-            if to == "host" {
-                // 转发给 Host
-                let state = state.read().await;
-                state.forward_to_host(HostSignalEvent::Ice {
+            // 转发给 Host
+            state.read().await.forward_to_host(HostSignalEvent::Ice {
+                from: peer_id.to_string(),
+                candidate,
+                sdp_mid,
+                sdp_mline_index,
+            });
+        }
+        SignalMessage::Punch { candidate, .. } => {
+            state.read().await.forward_to_host(HostSignalEvent::Punch {
+                from: peer_id.to_string(),
+                candidate,
+            });
+        }
+        SignalMessage::RefreshToken => {
+            let state = state.read().await;
+            if state.authenticated(peer_id) {
+                state.send_session_token(peer_id);
+            }
+        }
+        SignalMessage::RequestTicket => {
+            let mut state = state.write().await;
+            if !state.verified(peer_id) {
+                if let Ok(msg) = serde_json::to_string(&SignalMessage::Error {
+                    message: "请先完成验证再请求票据".to_string(),
+                }) {
+                    state.send_to(peer_id, &msg);
+                }
+                tracing::info!("拒绝未完成验证的 Viewer {} 的票据请求", peer_id);
+                return;
+            }
+            let session_id = state.session_id(peer_id);
+            tracing::Span::current().record("session_id", session_id.as_str());
+            state.forward_to_host(HostSignalEvent::TicketRequested {
+                from: peer_id.to_string(),
+                session_id,
+            });
+        }
+        SignalMessage::Cursor { show } => {
+            let state = state.read().await;
+            if state.admission.is_active(peer_id) && state.authenticated(peer_id) {
+                state.record_audit(AuditEvent::Cursor { peer: peer_id.to_string(), show });
+                state.forward_to_host(HostSignalEvent::Cursor {
                     from: peer_id.to_string(),
-                    candidate,
-                    sdp_mid,
-                    sdp_mline_index,
+                    show,
                 });
-            } else {
-                // 转发给其他 Viewer
-                let state = state.read().await;
-                if let Ok(msg) = serde_json::to_string(&SignalMessage::Ice {
+            }
+        }
+        SignalMessage::Profile { profile } => {
+            let state = state.read().await;
+            if state.admission.is_active(peer_id) && state.authenticated(peer_id) {
+                state.record_audit(AuditEvent::Profile { peer: peer_id.to_string(), profile });
+                state.forward_to_host(HostSignalEvent::Profile {
                     from: peer_id.to_string(),
-                    to: to.clone(),
-                    candidate,
-                    sdp_mid,
-                    sdp_mline_index,
+                    profile,
+                });
+            }
+        }
+        SignalMessage::Power { action } => {
+            let state = state.read().await;
+            if !state.admission.is_active(peer_id) || !state.authenticated(peer_id) {
+                return;
+            }
+            let permission = action.required_permission();
+            if !state.permissions_of(peer_id).allows(permission) {
+                tracing::warn!("拒绝 Viewer {} 的{}请求: 没有{}权限", peer_id, action.label(), permission.label());
+                if let Ok(msg) = serde_json::to_string(&SignalMessage::Error {
+                    message: format!("没有{}权限，无法{}", permission.label(), action.label()),
                 }) {
-                    state.send_to(&to, &msg);
+                    state.send_to(peer_id, &msg);
                 }
+                return;
+            }
+            state.record_audit(AuditEvent::Power { peer: peer_id.to_string(), action });
+            state.forward_to_host(HostSignalEvent::Power {
+                from: peer_id.to_string(),
+                action,
+            });
+        }
+        SignalMessage::Register { device, heartbeat_secs } => {
+            let mut state = state.write().await;
+            let Some(owner) = state.identities.get(peer_id).cloned() else {
+                tracing::warn!("拒绝未认证连接 {} 的设备登记", peer_id);
+                if let Ok(msg) = serde_json::to_string(&SignalMessage::Error {
+                    message: "设备登记需要认证 (服务器的认证方式或 fleet.registration_token)".to_string(),
+                }) {
+                    state.send_to(peer_id, &msg);
+                }
+                return;
+            };
+            let reply = match state.presence.register(peer_id, &owner, device, heartbeat_secs, PresenceRegistry::now_ms()) {
+                Ok(device_id) => {
+                    tracing::info!("设备上线: {} ({})", device_id, peer_id);
+                    SignalMessage::Registered { device_id }
+                }
+                Err(e) => {
+                    tracing::warn!("拒绝 {} 的设备登记: {}", peer_id, e);
+                    SignalMessage::Error { message: e.to_string() }
+                }
+            };
+            if let Ok(msg) = serde_json::to_string(&reply) {
+                state.send_to(peer_id, &msg);
+            }
+        }
+        SignalMessage::Heartbeat => {
+            let mut state = state.write().await;
+            let registered =
+                state.identities.contains_key(peer_id) && state.presence.heartbeat(peer_id, PresenceRegistry::now_ms());
+            if !registered {
+                tracing::debug!("忽略未登记连接 {} 的心跳", peer_id);
             }
         }
         _ => {}
     }
 }
 
+/// 拒绝 Viewer 发出的 Host 专属信令
+async fn reject_role_violation(peer_id: &str, kind: &str, state: &Arc<RwLock<ServerState>>) {
+    tracing::warn!("拒绝 Viewer {} 冒充被控端的信令 ({})", peer_id, kind);
+    if let Ok(msg) = serde_json::to_string(&SignalMessage::Error {
+        message: "Viewer 只能与被控端协商".to_string(),
+    }) {
+        state.read().await.send_to(peer_id, &msg);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::signaling::Permission;
 
     #[test]
     fn test_signal_message_serialization() {
@@ -496,4 +2634,957 @@ mod tests {
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("\"join\""));
     }
+
+    #[tokio::test]
+    async fn test_capacity_queue_admits_next_viewer() {
+        let state = Arc::new(RwLock::new(ServerState::new()));
+        let mut receivers = HashMap::new();
+        {
+            let mut s = state.write().await;
+            s.admission.set_config(CapacityConfig {
+                max_sessions: Some(1),
+                queue_enabled: true,
+                max_queue_length: 5,
+                max_viewers_per_room: None,
+                max_rooms: None,
+            });
+            for id in ["viewer_0", "viewer_1"] {
+                let (tx, rx) = mpsc::unbounded_channel();
+                s.clients.insert(id.to_string(), ClientSender { sender: tx });
+                receivers.insert(id, rx);
+            }
+        }
+
+        for id in ["viewer_0", "viewer_1"] {
+            handle_signal(SignalMessage::Join { room_id: "room".to_string() }, id, &state).await;
+        }
+
+        let queued = receivers.get_mut("viewer_1").unwrap().try_recv().unwrap();
+        assert!(queued.contains("\"queued\"") && queued.contains("\"position\":1"));
+
+        // viewer_0 断开后 viewer_1 收到成员列表
+        {
+            let mut s = state.write().await;
+            s.leave_room("viewer_0");
+            s.release("viewer_0");
+            assert!(s.admission.is_active("viewer_1"));
+        }
+        let peers = receivers.get_mut("viewer_1").unwrap().try_recv().unwrap();
+        assert!(peers.contains("\"peers\""));
+    }
+
+    #[tokio::test]
+    async fn test_session_id_stable_across_renegotiation() {
+        let state = Arc::new(RwLock::new(ServerState::new()));
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        {
+            let mut s = state.write().await;
+            s.host.connect(host_tx);
+            let (tx, _rx) = mpsc::unbounded_channel();
+            s.clients.insert("viewer_0".to_string(), ClientSender { sender: tx });
+        }
+        handle_signal(SignalMessage::Join { room_id: "room".to_string() }, "viewer_0", &state).await;
+
+        let offer = || SignalMessage::Offer {
+            from: "viewer_0".to_string(),
+            to: "host".to_string(),
+            sdp: "v=0".to_string(),
+        };
+        handle_signal(offer(), "viewer_0", &state).await;
+        handle_signal(offer(), "viewer_0", &state).await;
+
+        let mut session_ids = Vec::new();
+        while let Ok(event) = host_rx.try_recv() {
+            if let HostSignalEvent::Offer { session_id, .. } = event {
+                session_ids.push(session_id);
+            }
+        }
+        assert_eq!(session_ids.len(), 2);
+        assert_eq!(session_ids[0], session_ids[1]);
+        assert!(uuid::Uuid::parse_str(&session_ids[0]).is_ok());
+
+        // Answer 携带会话 ID，Viewer 发出的 Answer 不带该字段
+        let answer = SignalMessage::Answer {
+            from: "host".to_string(),
+            to: "viewer_0".to_string(),
+            sdp: "v=0".to_string(),
+            session_id: Some(session_ids[0].clone()),
+            session_token: None,
+        };
+        assert!(serde_json::to_string(&answer).unwrap().contains(&session_ids[0]));
+        let parsed: SignalMessage =
+            serde_json::from_str(r#"{"type":"answer","from":"a","to":"host","sdp":"v=0"}"#).unwrap();
+        assert!(matches!(parsed, SignalMessage::Answer { session_id: None, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_cursor_toggle_requires_admission() {
+        let state = Arc::new(RwLock::new(ServerState::new()));
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        {
+            let mut s = state.write().await;
+            s.host.connect(host_tx);
+            let (tx, _rx) = mpsc::unbounded_channel();
+            s.clients.insert("viewer_0".to_string(), ClientSender { sender: tx });
+        }
+
+        let cursor_events = |rx: &mut mpsc::UnboundedReceiver<HostSignalEvent>| {
+            let mut events = Vec::new();
+            while let Ok(event) = rx.try_recv() {
+                if let HostSignalEvent::Cursor { from, show } = event {
+                    events.push((from, show));
+                }
+            }
+            events
+        };
+
+        // 未加入房间的 Viewer 不能切换
+        handle_signal(SignalMessage::Cursor { show: false }, "viewer_0", &state).await;
+        assert!(cursor_events(&mut host_rx).is_empty());
+
+        handle_signal(SignalMessage::Join { room_id: "room".to_string() }, "viewer_0", &state).await;
+        handle_signal(SignalMessage::Cursor { show: false }, "viewer_0", &state).await;
+        assert_eq!(cursor_events(&mut host_rx), vec![("viewer_0".to_string(), false)]);
+    }
+
+    #[tokio::test]
+    async fn test_profile_switch_forwarded() {
+        let state = Arc::new(RwLock::new(ServerState::new()));
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        {
+            let mut s = state.write().await;
+            s.host.connect(host_tx);
+            let (tx, _rx) = mpsc::unbounded_channel();
+            s.clients.insert("viewer_0".to_string(), ClientSender { sender: tx });
+        }
+
+        let message: SignalMessage = serde_json::from_str(r#"{"type":"profile","profile":"low-bandwidth"}"#).unwrap();
+        handle_signal(message.clone(), "viewer_0", &state).await;
+        assert!(host_rx.try_recv().is_err());
+
+        handle_signal(SignalMessage::Join { room_id: "room".to_string() }, "viewer_0", &state).await;
+        handle_signal(message, "viewer_0", &state).await;
+        let mut profiles = Vec::new();
+        while let Ok(event) = host_rx.try_recv() {
+            if let HostSignalEvent::Profile { from, profile } = event {
+                profiles.push((from, profile));
+            }
+        }
+        assert_eq!(profiles, vec![("viewer_0".to_string(), QualityProfile::LowBandwidth)]);
+    }
+
+    #[tokio::test]
+    async fn test_power_requires_permission() {
+        let state = Arc::new(RwLock::new(ServerState::new()));
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        let (tx, mut viewer_rx) = mpsc::unbounded_channel();
+        {
+            let mut s = state.write().await;
+            s.host.connect(host_tx);
+            s.default_permissions = SessionPermissions::from_list(&[Permission::Input]);
+            s.clients.insert("viewer_0".to_string(), ClientSender { sender: tx });
+        }
+
+        let power_events = |rx: &mut mpsc::UnboundedReceiver<HostSignalEvent>| {
+            let mut events = Vec::new();
+            while let Ok(event) = rx.try_recv() {
+                if let HostSignalEvent::Power { from, action } = event {
+                    events.push((from, action));
+                }
+            }
+            events
+        };
+
+        // 未加入房间的 Viewer 不能操作
+        handle_signal(SignalMessage::Power { action: PowerAction::Lock }, "viewer_0", &state).await;
+        assert!(power_events(&mut host_rx).is_empty());
+
+        handle_signal(SignalMessage::Join { room_id: "room".to_string() }, "viewer_0", &state).await;
+        while viewer_rx.try_recv().is_ok() {}
+
+        // 锁屏只需要输入权限，重启需要电源权限
+        handle_signal(SignalMessage::Power { action: PowerAction::Lock }, "viewer_0", &state).await;
+        handle_signal(SignalMessage::Power { action: PowerAction::Reboot }, "viewer_0", &state).await;
+        assert_eq!(power_events(&mut host_rx), vec![("viewer_0".to_string(), PowerAction::Lock)]);
+        assert!(viewer_rx.try_recv().unwrap().contains("\"error\""));
+
+        let with_power = SessionPermissions::from_list(&[Permission::Input, Permission::Power]);
+        state.write().await.update_permissions("viewer_0", with_power);
+        handle_signal(SignalMessage::Power { action: PowerAction::Reboot }, "viewer_0", &state).await;
+        assert_eq!(power_events(&mut host_rx), vec![("viewer_0".to_string(), PowerAction::Reboot)]);
+
+        let parsed: SignalMessage = serde_json::from_str(r#"{"type":"power","action":"shutdown"}"#).unwrap();
+        assert!(matches!(parsed, SignalMessage::Power { action: PowerAction::Shutdown }));
+    }
+
+    #[tokio::test]
+    async fn test_offer_requires_pin() {
+        let state = Arc::new(RwLock::new(ServerState::new()));
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        let (tx, mut viewer_rx) = mpsc::unbounded_channel();
+        let pin = {
+            let mut s = state.write().await;
+            s.host.connect(host_tx);
+            s.pin = Some(PinGuard::new(PinConfig::default()));
+            s.clients.insert("viewer_0".to_string(), ClientSender { sender: tx });
+            s.pin.as_ref().unwrap().pin().to_string()
+        };
+        let offer = || SignalMessage::Offer {
+            from: String::new(),
+            to: "host".to_string(),
+            sdp: "v=0".to_string(),
+        };
+        let punch = || SignalMessage::Punch {
+            from: String::new(),
+            to: "host".to_string(),
+            candidate: None,
+        };
+        let drain = |rx: &mut mpsc::UnboundedReceiver<String>| {
+            let mut messages = Vec::new();
+            while let Ok(msg) = rx.try_recv() {
+                messages.push(msg);
+            }
+            messages
+        };
+
+        handle_signal(SignalMessage::Join { room_id: "room".to_string() }, "viewer_0", &state).await;
+        assert!(drain(&mut viewer_rx).iter().any(|m| m.contains("\"pin_required\"")));
+
+        // 未验证 PIN 的 Offer 和打洞请求不转发给 Host
+        handle_signal(punch(), "viewer_0", &state).await;
+        handle_signal(offer(), "viewer_0", &state).await;
+        assert!(drain(&mut viewer_rx).iter().any(|m| m.contains("\"error\"")));
+
+        handle_signal(SignalMessage::Pin { pin: "wrong".to_string() }, "viewer_0", &state).await;
+        let replies = drain(&mut viewer_rx);
+        assert!(replies[0].contains("\"accepted\":false") && replies[0].contains("\"remaining_attempts\":4"));
+
+        handle_signal(SignalMessage::Pin { pin }, "viewer_0", &state).await;
+        assert!(drain(&mut viewer_rx)[0].contains("\"accepted\":true"));
+        handle_signal(punch(), "viewer_0", &state).await;
+        handle_signal(offer(), "viewer_0", &state).await;
+
+        let mut offers = 0;
+        let mut punches = 0;
+        let mut new_pins = 0;
+        while let Ok(event) = host_rx.try_recv() {
+            match event {
+                HostSignalEvent::Offer { from, .. } => {
+                    assert_eq!(from, "viewer_0");
+                    offers += 1;
+                }
+                HostSignalEvent::Punch { .. } => punches += 1,
+                HostSignalEvent::PinChanged { .. } => new_pins += 1,
+                _ => {}
+            }
+        }
+        assert_eq!((offers, punches, new_pins), (1, 1, 1));
+    }
+
+    #[cfg(feature = "security")]
+    #[tokio::test]
+    async fn test_offer_requires_totp() {
+        use crate::security::totp;
+
+        let secret = totp::generate_secret();
+        let state = Arc::new(RwLock::new(ServerState::new()));
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        let (tx, mut viewer_rx) = mpsc::unbounded_channel();
+        {
+            let mut s = state.write().await;
+            s.host.connect(host_tx);
+            s.totp = Some(TotpGuard::new(&secret, &PinConfig::default()).unwrap());
+            s.clients.insert("viewer_0".to_string(), ClientSender { sender: tx });
+        }
+        let offer = || SignalMessage::Offer {
+            from: String::new(),
+            to: "host".to_string(),
+            sdp: "v=0".to_string(),
+        };
+        let drain = |rx: &mut mpsc::UnboundedReceiver<String>| {
+            let mut messages = Vec::new();
+            while let Ok(msg) = rx.try_recv() {
+                messages.push(msg);
+            }
+            messages
+        };
+
+        handle_signal(SignalMessage::Join { room_id: "room".to_string() }, "viewer_0", &state).await;
+        let messages = drain(&mut viewer_rx);
+        assert!(messages.iter().any(|m| m.contains("\"totp_required\"")));
+        assert!(!messages.iter().any(|m| m.contains("\"pin_required\"")));
+
+        handle_signal(offer(), "viewer_0", &state).await;
+        assert!(drain(&mut viewer_rx).iter().any(|m| m.contains("6 位验证码")));
+        handle_signal(SignalMessage::Totp { code: "abcdef".to_string() }, "viewer_0", &state).await;
+        assert!(drain(&mut viewer_rx)[0].contains("\"remaining_attempts\":4"));
+
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        let code = totp::code_at(&totp::decode_secret(&secret).unwrap(), now);
+        handle_signal(SignalMessage::Totp { code }, "viewer_0", &state).await;
+        assert!(drain(&mut viewer_rx)[0].contains("\"accepted\":true"));
+        handle_signal(offer(), "viewer_0", &state).await;
+        let mut offers = 0;
+        while let Ok(event) = host_rx.try_recv() {
+            if matches!(event, HostSignalEvent::Offer { .. }) {
+                offers += 1;
+            }
+        }
+        assert_eq!(offers, 1);
+
+        // 断开后验证状态清除
+        state.write().await.disconnect("viewer_0");
+        assert!(!state.read().await.totp_verified("viewer_0"));
+    }
+
+    #[tokio::test]
+    async fn test_permissions_assigned_on_admit() {
+        let state = Arc::new(RwLock::new(ServerState::new()));
+        let (tx, mut viewer_rx) = mpsc::unbounded_channel();
+        {
+            let mut s = state.write().await;
+            s.default_permissions = SessionPermissions::view_only();
+            s.clients.insert("viewer_0".to_string(), ClientSender { sender: tx });
+        }
+
+        handle_signal(SignalMessage::Join { room_id: "room".to_string() }, "viewer_0", &state).await;
+        let mut messages = Vec::new();
+        while let Ok(msg) = viewer_rx.try_recv() {
+            messages.push(msg);
+        }
+        assert!(messages.iter().any(|m| m.contains("\"permissions\"") && m.contains("\"input\":false")));
+        assert!(state.read().await.permissions_of("viewer_0").is_view_only());
+
+        // 单独授予输入权限
+        let input_only = SessionPermissions::from_list(&[Permission::Input]);
+        state.write().await.update_permissions("viewer_0", input_only);
+        assert!(viewer_rx.try_recv().unwrap().contains("\"input\":true"));
+        assert_eq!(state.read().await.permissions_of("viewer_0"), input_only);
+    }
+
+    #[tokio::test]
+    async fn test_permissions_limited_by_role() {
+        let state = Arc::new(RwLock::new(ServerState::new()));
+        let (tx, mut viewer_rx) = mpsc::unbounded_channel();
+        {
+            let mut s = state.write().await;
+            s.clients.insert("viewer_0".to_string(), ClientSender { sender: tx });
+            s.limits.insert("viewer_0".to_string(), SessionPermissions::from_list(&[Permission::Input]));
+        }
+
+        // 默认权限为全部权限，但认证角色只允许输入
+        handle_signal(SignalMessage::Join { room_id: "room".to_string() }, "viewer_0", &state).await;
+        let limited = SessionPermissions::from_list(&[Permission::Input]);
+        assert_eq!(state.read().await.permissions_of("viewer_0"), limited);
+
+        // Host 放行时授予的权限同样受限
+        while viewer_rx.try_recv().is_ok() {}
+        state.write().await.update_permissions("viewer_0", SessionPermissions::full());
+        assert!(viewer_rx.try_recv().unwrap().contains("\"power\":false"));
+        assert_eq!(state.read().await.permissions_of("viewer_0"), limited);
+
+        state.write().await.disconnect("viewer_0");
+        assert_eq!(state.read().await.limit_of("viewer_0"), SessionPermissions::full());
+    }
+
+    #[tokio::test]
+    async fn test_session_resume_after_reconnect() {
+        let state = Arc::new(RwLock::new(ServerState::new()));
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        let (tx0, _rx0) = mpsc::unbounded_channel();
+        let (tx1, mut viewer_rx) = mpsc::unbounded_channel();
+        let pin = {
+            let mut s = state.write().await;
+            s.host.connect(host_tx);
+            s.pin = Some(PinGuard::new(PinConfig::default()));
+            s.default_permissions = SessionPermissions::view_only();
+            s.clients.insert("viewer_0".to_string(), ClientSender { sender: tx0 });
+            s.clients.insert("viewer_1".to_string(), ClientSender { sender: tx1 });
+            s.pin.as_ref().unwrap().pin().to_string()
+        };
+        let offer = || SignalMessage::Offer {
+            from: String::new(),
+            to: "host".to_string(),
+            sdp: "v=0".to_string(),
+        };
+
+        handle_signal(SignalMessage::Join { room_id: "room".to_string() }, "viewer_0", &state).await;
+        handle_signal(SignalMessage::Pin { pin }, "viewer_0", &state).await;
+        handle_signal(offer(), "viewer_0", &state).await;
+        let token = state.read().await.session_token("viewer_0").unwrap();
+        state.write().await.disconnect("viewer_0");
+
+        let mut session_id = None;
+        while let Ok(event) = host_rx.try_recv() {
+            if let HostSignalEvent::Offer { session_id: id, .. } = event {
+                session_id = Some(id);
+            }
+        }
+        let session_id = session_id.unwrap();
+
+        // 只知道会话 ID 不能恢复 (令牌缺失或属于其他房间)
+        for (room_id, token) in [("room", None), ("other", Some(token.clone()))] {
+            assert!(!state.read().await.verify_resume(room_id, &session_id, token.as_deref()));
+        }
+
+        // 重连后凭会话令牌恢复: 不需要重新输入 PIN，权限和会话 ID 不变
+        handle_signal(
+            SignalMessage::Resume { room_id: "room".to_string(), session_id: session_id.clone(), token: Some(token) },
+            "viewer_1",
+            &state,
+        )
+        .await;
+        let mut messages = Vec::new();
+        while let Ok(msg) = viewer_rx.try_recv() {
+            messages.push(msg);
+        }
+        assert!(messages.iter().any(|m| m.contains("\"resumed\"")));
+        assert!(messages.iter().any(|m| m.contains("\"session_token\"")));
+        assert!(!messages.iter().any(|m| m.contains("\"pin_required\"")));
+        assert!(state.read().await.permissions_of("viewer_1").is_view_only());
+
+        handle_signal(offer(), "viewer_1", &state).await;
+        let events: Vec<_> = std::iter::from_fn(|| host_rx.try_recv().ok()).collect();
+        assert!(events.iter().any(|e| matches!(
+            e,
+            HostSignalEvent::ViewerResumed { peer_id, session_id: id } if peer_id == "viewer_1" && *id == session_id
+        )));
+        assert!(events.iter().any(|e| matches!(
+            e,
+            HostSignalEvent::Offer { from, session_id: id, .. } if from == "viewer_1" && *id == session_id
+        )));
+
+        // 会话只能恢复一次
+        assert!(!state.write().await.resume_session("viewer_2", &session_id));
+    }
+
+    #[tokio::test]
+    async fn test_viewer_cannot_impersonate_host() {
+        let state = Arc::new(RwLock::new(ServerState::new()));
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        let (tx0, mut rx0) = mpsc::unbounded_channel();
+        let (tx1, mut rx1) = mpsc::unbounded_channel();
+        let (tx2, mut rx2) = mpsc::unbounded_channel();
+        {
+            let mut s = state.write().await;
+            s.host.connect(host_tx);
+            s.admission.set_config(CapacityConfig {
+                max_viewers_per_room: Some(2),
+                ..Default::default()
+            });
+            s.clients.insert("viewer_0".to_string(), ClientSender { sender: tx0 });
+            s.clients.insert("viewer_1".to_string(), ClientSender { sender: tx1 });
+            s.clients.insert("viewer_2".to_string(), ClientSender { sender: tx2 });
+        }
+        for id in ["viewer_0", "viewer_1", "viewer_2"] {
+            handle_signal(SignalMessage::Join { room_id: "room".to_string() }, id, &state).await;
+        }
+        // 第三个 Viewer 超出房间上限
+        assert!(std::iter::from_fn(|| rx2.try_recv().ok()).any(|m| m.contains("上限")));
+        assert_eq!(state.read().await.room_of("viewer_2"), None);
+        while rx0.try_recv().is_ok() {}
+        while rx1.try_recv().is_ok() {}
+
+        // viewer_1 冒充 Host 给 viewer_0 发 Answer / Offer / ICE
+        handle_signal(
+            SignalMessage::Answer {
+                from: "host".to_string(),
+                to: "viewer_0".to_string(),
+                sdp: "v=0".to_string(),
+                session_id: None,
+                session_token: None,
+            },
+            "viewer_1",
+            &state,
+        )
+        .await;
+        handle_signal(
+            SignalMessage::Offer { from: "host".to_string(), to: "viewer_0".to_string(), sdp: "v=0".to_string() },
+            "viewer_1",
+            &state,
+        )
+        .await;
+        assert!(rx0.try_recv().is_err());
+        let errors: Vec<_> = std::iter::from_fn(|| rx1.try_recv().ok()).collect();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|m| m.contains("\"error\"")));
+        assert!(!std::iter::from_fn(|| host_rx.try_recv().ok()).any(|e| matches!(e, HostSignalEvent::Offer { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_verify_pin_outside_signaling() {
+        let server = EmbeddedSignalingServer::new(0);
+        assert_eq!(server.verify_pin("quic-a", None).await, PinVerdict::Accepted);
+
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        let pin = {
+            let mut s = server.state.write().await;
+            s.host.connect(host_tx);
+            s.pin = Some(PinGuard::new(PinConfig::default()));
+            s.pin.as_ref().unwrap().pin().to_string()
+        };
+
+        assert_eq!(server.verify_pin("quic-a", None).await, PinVerdict::Rejected { remaining: 4 });
+        assert_eq!(server.verify_pin("quic-a", Some(&pin)).await, PinVerdict::Accepted);
+        // 一次性 PIN 已换发，Host 收到新 PIN；同一地址再次连接需要新 PIN
+        let new_pin = server.current_pin().await.unwrap();
+        assert!(matches!(host_rx.try_recv(), Ok(HostSignalEvent::PinChanged { pin }) if pin == new_pin));
+        if new_pin != pin {
+            assert_ne!(server.verify_pin("quic-a", Some(&pin)).await, PinVerdict::Accepted);
+        }
+    }
+
+    /// HTTP 处理函数使用的应用状态
+    fn app_state(server: &EmbeddedSignalingServer) -> AppState {
+        AppState {
+            state: server.state.clone(),
+            #[cfg(feature = "security")]
+            auth_provider: None,
+            #[cfg(feature = "security")]
+            require_e2ee: false,
+            abuse: Arc::new(std::sync::Mutex::new(AbuseGuard::new(AbuseConfig::default()))),
+            keepalive: server.keepalive,
+            video_stream: server.video_stream.clone(),
+            max_input_rate: server.max_input_rate,
+            incoming: server.incoming.clone(),
+            compression: server.compression,
+            fleet_token: server.fleet_token.clone(),
+        }
+    }
+
+    /// 加入房间的 Viewer (未启用 PIN，加入即通过验证)
+    async fn join_viewer(state: &Arc<RwLock<ServerState>>, peer_id: &str) -> mpsc::UnboundedReceiver<String> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        state.write().await.clients.insert(peer_id.to_string(), ClientSender { sender: tx });
+        handle_signal(SignalMessage::Join { room_id: "default".to_string() }, peer_id, state).await;
+        rx
+    }
+
+    /// 为 Viewer 当前的会话签发票据
+    async fn issue_ticket(state: &Arc<RwLock<ServerState>>, peer_id: &str) -> String {
+        let mut state = state.write().await;
+        let session_id = state.session_id(peer_id);
+        state.tickets.issue(peer_id, &session_id)
+    }
+
+    #[tokio::test]
+    async fn test_power_request_uses_ticket_holder_permissions() {
+        let server = EmbeddedSignalingServer::new(0);
+        let state = server.state.clone();
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        state.read().await.host.connect(host_tx);
+        let _viewer_rx = join_viewer(&state, "viewer_0").await;
+        let app_state = app_state(&server);
+        let power = |ticket: Option<String>| {
+            let mut query = HashMap::from([("action".to_string(), "reboot".to_string())]);
+            if let Some(ticket) = ticket {
+                query.insert("ticket".to_string(), ticket);
+            }
+            power_handler(HeaderMap::new(), Query(query), State(app_state.clone()))
+        };
+        let requests = |rx: &mut mpsc::UnboundedReceiver<HostSignalEvent>| {
+            let mut requests = Vec::new();
+            while let Ok(event) = rx.try_recv() {
+                if let HostSignalEvent::Power { from, action } = event {
+                    requests.push((from, action));
+                }
+            }
+            requests
+        };
+
+        assert_eq!(power(None).await.into_response().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(power(Some("forged".to_string())).await.into_response().status(), StatusCode::UNAUTHORIZED);
+
+        // 按该 Viewer 的权限检查，而不是新 Viewer 的默认权限
+        state.write().await.update_permissions("viewer_0", SessionPermissions::from_list(&[Permission::Input]));
+        let ticket = issue_ticket(&state, "viewer_0").await;
+        assert_eq!(power(Some(ticket)).await.into_response().status(), StatusCode::FORBIDDEN);
+        assert!(requests(&mut host_rx).is_empty());
+
+        state.write().await.update_permissions("viewer_0", SessionPermissions::full());
+        let ticket = issue_ticket(&state, "viewer_0").await;
+        assert_eq!(power(Some(ticket.clone())).await.into_response().status(), StatusCode::ACCEPTED);
+        assert_eq!(requests(&mut host_rx), vec![("viewer_0".to_string(), PowerAction::Reboot)]);
+        assert_eq!(power(Some(ticket)).await.into_response().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_upload_uses_ticket_holder_permissions() {
+        let dir = std::env::temp_dir().join(format!("sscontrol-upload-{}", std::process::id()));
+        let mut server = EmbeddedSignalingServer::new(0);
+        server.set_incoming_files(IncomingFiles::new(dir.to_str(), 1));
+        let state = server.state.clone();
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        state.read().await.host.connect(host_tx);
+        let _viewer_rx = join_viewer(&state, "viewer_0").await;
+        let app_state = app_state(&server);
+        let upload = |ticket: String| {
+            let query = HashMap::from([("name".to_string(), "notes.txt".to_string()), ("ticket".to_string(), ticket)]);
+            upload_handler(HeaderMap::new(), Query(query), State(app_state.clone()), Body::from("hello"))
+        };
+
+        state.write().await.update_permissions("viewer_0", SessionPermissions::from_list(&[Permission::Input]));
+        let ticket = issue_ticket(&state, "viewer_0").await;
+        assert_eq!(upload(ticket).await.into_response().status(), StatusCode::FORBIDDEN);
+
+        state.write().await.update_permissions("viewer_0", SessionPermissions::full());
+        let ticket = issue_ticket(&state, "viewer_0").await;
+        assert_eq!(upload(ticket).await.into_response().status(), StatusCode::CREATED);
+        let mut received = Vec::new();
+        while let Ok(event) = host_rx.try_recv() {
+            if let HostSignalEvent::FileReceived { from, path } = event {
+                received.push((from, path));
+            }
+        }
+        assert_eq!(received, vec![("viewer_0".to_string(), dir.join("notes.txt"))]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_request_audited_as_ticket_holder() {
+        let log_path = std::env::temp_dir().join(format!("sscontrol-cursor-audit-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&log_path);
+        let server = EmbeddedSignalingServer::new(0);
+        let state = server.state.clone();
+        state.write().await.audit = Arc::new(crate::audit::AuditLog::open(&log_path).unwrap());
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        state.read().await.host.connect(host_tx);
+        let _viewer_rx = join_viewer(&state, "viewer_0").await;
+        let app_state = app_state(&server);
+        let cursor = |ticket: Option<String>| {
+            let mut query = HashMap::from([("show".to_string(), "false".to_string())]);
+            if let Some(ticket) = ticket {
+                query.insert("ticket".to_string(), ticket);
+            }
+            cursor_handler(HeaderMap::new(), Query(query), State(app_state.clone()))
+        };
+
+        assert_eq!(cursor(None).await.into_response().status(), StatusCode::UNAUTHORIZED);
+        let ticket = issue_ticket(&state, "viewer_0").await;
+        assert_eq!(cursor(Some(ticket)).await.into_response().status(), StatusCode::NO_CONTENT);
+        let mut toggled = Vec::new();
+        while let Ok(event) = host_rx.try_recv() {
+            if let HostSignalEvent::Cursor { from, show } = event {
+                toggled.push((from, show));
+            }
+        }
+        assert_eq!(toggled, vec![("viewer_0".to_string(), false)]);
+
+        let records = crate::audit::read_records(&log_path).unwrap();
+        assert!(records
+            .iter()
+            .any(|record| record.event == AuditEvent::Cursor { peer: "viewer_0".to_string(), show: false }));
+        let _ = std::fs::remove_file(&log_path);
+    }
+
+    #[tokio::test]
+    async fn test_profile_request_uses_ticket_holder() {
+        let server = EmbeddedSignalingServer::new(0);
+        let state = server.state.clone();
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        state.read().await.host.connect(host_tx);
+        let _viewer_rx = join_viewer(&state, "viewer_0").await;
+        let app_state = app_state(&server);
+        let profile = |ticket: Option<String>| {
+            let mut query = HashMap::from([("name".to_string(), "low-bandwidth".to_string())]);
+            if let Some(ticket) = ticket {
+                query.insert("ticket".to_string(), ticket);
+            }
+            profile_handler(HeaderMap::new(), Query(query), State(app_state.clone()))
+        };
+
+        assert_eq!(profile(None).await.into_response().status(), StatusCode::UNAUTHORIZED);
+        let ticket = issue_ticket(&state, "viewer_0").await;
+        assert_eq!(profile(Some(ticket.clone())).await.into_response().status(), StatusCode::NO_CONTENT);
+        assert_eq!(profile(Some(ticket)).await.into_response().status(), StatusCode::UNAUTHORIZED);
+
+        let mut switched = Vec::new();
+        while let Ok(event) = host_rx.try_recv() {
+            if let HostSignalEvent::Profile { from, profile } = event {
+                switched.push((from, profile));
+            }
+        }
+        assert_eq!(switched, vec![("viewer_0".to_string(), QualityProfile::LowBandwidth)]);
+    }
+
+    #[tokio::test]
+    async fn test_web_chat_reaches_host() {
+        let server = EmbeddedSignalingServer::new(0);
+        let state = server.state.clone();
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        state.read().await.host.connect(host_tx);
+        let _viewer_rx = join_viewer(&state, "viewer_0").await;
+        let app_state = app_state(&server);
+        let post = |text: &str, ticket: Option<String>| {
+            let query = ticket.map(|ticket| HashMap::from([("ticket".to_string(), ticket)])).unwrap_or_default();
+            chat_post_handler(
+                HeaderMap::new(),
+                Query(query),
+                State(app_state.clone()),
+                Json(ChatPost { text: text.to_string() }),
+            )
+        };
+
+        assert_eq!(post("你好", None).await.into_response().status(), StatusCode::UNAUTHORIZED);
+        let ticket = issue_ticket(&state, "viewer_0").await;
+        assert_eq!(post(" ", Some(ticket)).await.into_response().status(), StatusCode::BAD_REQUEST);
+        let ticket = issue_ticket(&state, "viewer_0").await;
+        assert_eq!(post("你好", Some(ticket)).await.into_response().status(), StatusCode::OK);
+        let chats: Vec<_> = std::iter::from_fn(|| host_rx.try_recv().ok())
+            .filter_map(|event| match event {
+                HostSignalEvent::Chat { message } => Some(message),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(chats.len(), 1);
+        assert_eq!((chats[0].from.as_str(), chats[0].text.as_str()), ("viewer_0", "你好"));
+
+        // 被控端的回复不回送给 Host，Web 查看器按 ID 增量拉取
+        server.post_chat("host", "收到").await.unwrap();
+        assert!(host_rx.try_recv().is_err());
+        let history = server.state.read().await.chat.since(1);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].from, "host");
+    }
+
+    #[tokio::test]
+    async fn test_chat_history_requires_ticket() {
+        let server = EmbeddedSignalingServer::new(0);
+        let state = server.state.clone();
+        server.post_chat("host", "请稍等").await.unwrap();
+        let _viewer_rx = join_viewer(&state, "viewer_0").await;
+        let app_state = app_state(&server);
+        let history = |ticket: Option<String>| {
+            let mut query = HashMap::from([("after".to_string(), "0".to_string())]);
+            if let Some(ticket) = ticket {
+                query.insert("ticket".to_string(), ticket);
+            }
+            chat_history_handler(HeaderMap::new(), Query(query), State(app_state.clone()))
+        };
+
+        assert_eq!(history(None).await.into_response().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(history(Some("forged".to_string())).await.into_response().status(), StatusCode::UNAUTHORIZED);
+
+        let ticket = issue_ticket(&state, "viewer_0").await;
+        let response = history(Some(ticket)).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let messages: Vec<ChatMessage> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].text, "请稍等");
+    }
+
+    #[tokio::test]
+    async fn test_ticket_requires_verified_viewer() {
+        let server = EmbeddedSignalingServer::new(0);
+        let state = server.state.clone();
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        let (tx, mut viewer_rx) = mpsc::unbounded_channel();
+        let pin = {
+            let mut s = state.write().await;
+            s.host.connect(host_tx);
+            s.pin = Some(PinGuard::new(PinConfig::default()));
+            s.clients.insert("viewer_0".to_string(), ClientSender { sender: tx });
+            s.pin.as_ref().unwrap().pin().to_string()
+        };
+        let ticket_requests = |rx: &mut mpsc::UnboundedReceiver<HostSignalEvent>| {
+            let mut requests = Vec::new();
+            while let Ok(event) = rx.try_recv() {
+                if let HostSignalEvent::TicketRequested { from, session_id } = event {
+                    requests.push((from, session_id));
+                }
+            }
+            requests
+        };
+        let next_ticket = |rx: &mut mpsc::UnboundedReceiver<String>| {
+            let mut ticket = None;
+            while let Ok(msg) = rx.try_recv() {
+                if let Ok(SignalMessage::Ticket { ticket: issued, peer_id }) = serde_json::from_str(&msg) {
+                    assert_eq!(peer_id, "viewer_0");
+                    ticket = Some(issued);
+                }
+            }
+            ticket
+        };
+
+        // 未放行、未验证 PIN 的 Viewer 请求票据不会转给 Host，Host 也不能为其签发
+        handle_signal(SignalMessage::RequestTicket, "viewer_0", &state).await;
+        handle_signal(SignalMessage::Join { room_id: "default".to_string() }, "viewer_0", &state).await;
+        handle_signal(SignalMessage::RequestTicket, "viewer_0", &state).await;
+        assert!(ticket_requests(&mut host_rx).is_empty());
+        assert!(!server.grant_ticket("viewer_0").await);
+        assert!(next_ticket(&mut viewer_rx).is_none());
+
+        handle_signal(SignalMessage::Pin { pin }, "viewer_0", &state).await;
+        handle_signal(SignalMessage::RequestTicket, "viewer_0", &state).await;
+        let requests = ticket_requests(&mut host_rx);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].0, "viewer_0");
+        assert_eq!(state.write().await.session_id("viewer_0"), requests[0].1);
+
+        // 票据只能使用一次
+        assert!(server.grant_ticket("viewer_0").await);
+        let ticket = next_ticket(&mut viewer_rx).unwrap();
+        assert_eq!(state.write().await.redeem_ticket(&ticket).as_deref(), Some("viewer_0"));
+        assert!(state.write().await.redeem_ticket(&ticket).is_none());
+
+        // 信令连接断开后未使用的票据失效
+        assert!(server.grant_ticket("viewer_0").await);
+        let ticket = next_ticket(&mut viewer_rx).unwrap();
+        state.write().await.disconnect("viewer_0");
+        assert!(state.write().await.redeem_ticket(&ticket).is_none());
+        assert!(!server.grant_ticket("viewer_0").await);
+    }
+
+    #[tokio::test]
+    async fn test_web_input_follows_viewer_permissions() {
+        let state = Arc::new(RwLock::new(ServerState::new()));
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        {
+            let mut s = state.write().await;
+            s.host.connect(host_tx);
+            s.default_permissions = SessionPermissions::view_only();
+            let (tx, _rx) = mpsc::unbounded_channel();
+            s.clients.insert("viewer_0".to_string(), ClientSender { sender: tx });
+        }
+        handle_signal(SignalMessage::Join { room_id: "default".to_string() }, "viewer_0", &state).await;
+        while host_rx.try_recv().is_ok() {}
+
+        let input = WebInput {
+            peer_id: "viewer_0".to_string(),
+            state: state.clone(),
+            touch: TouchTranslator::default(),
+            coalescer: InputCoalescer::new(0),
+        };
+        let pointer = || vec![InputEvent::MouseMove { x: 0.5, y: 0.5 }];
+        let inputs = |rx: &mut mpsc::UnboundedReceiver<HostSignalEvent>| {
+            let mut senders = Vec::new();
+            while let Ok(event) = rx.try_recv() {
+                if let HostSignalEvent::Input { from, .. } = event {
+                    senders.push(from);
+                }
+            }
+            senders
+        };
+
+        // 按该 Viewer 的会话权限检查，Host 授予输入权限后生效
+        input.send(pointer()).await;
+        assert!(inputs(&mut host_rx).is_empty());
+        state.write().await.update_permissions("viewer_0", SessionPermissions::from_list(&[Permission::Input]));
+        input.send(pointer()).await;
+        assert_eq!(inputs(&mut host_rx), vec!["viewer_0".to_string()]);
+
+        // 信令连接断开后丢弃
+        state.write().await.disconnect("viewer_0");
+        input.send(pointer()).await;
+        assert!(inputs(&mut host_rx).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_offer_replayed_after_host_reattach() {
+        let server = EmbeddedSignalingServer::new(0);
+        let state = server.state.clone();
+        let host_rx = state.read().await.host.attach();
+        let token = server.host_resume_token().await.unwrap();
+        let (tx, mut viewer_rx) = mpsc::unbounded_channel();
+        state.write().await.clients.insert("viewer_0".to_string(), ClientSender { sender: tx });
+        handle_signal(SignalMessage::Join { room_id: "room".to_string() }, "viewer_0", &state).await;
+
+        // Host 事件循环退出后 Viewer 发来 Offer
+        drop(host_rx);
+        handle_signal(
+            SignalMessage::Offer { from: "viewer_0".to_string(), to: "host".to_string(), sdp: "v=0".to_string() },
+            "viewer_0",
+            &state,
+        )
+        .await;
+        assert!(!state.read().await.host.expire());
+        assert!(state.read().await.room_of("viewer_0").is_some());
+
+        let mut host_rx = server.reattach_host(&token).await.unwrap();
+        let events: Vec<_> = std::iter::from_fn(|| host_rx.try_recv().ok()).collect();
+        assert!(events.iter().any(|e| matches!(e, HostSignalEvent::Offer { from, .. } if from == "viewer_0")));
+
+        // 宽限期过后通知并断开 Viewer
+        while viewer_rx.try_recv().is_ok() {}
+        state.write().await.abandon_viewers();
+        assert!(viewer_rx.try_recv().unwrap().contains("Host 已离线"));
+        assert!(viewer_rx.try_recv().is_err());
+        assert!(state.read().await.clients.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_device_presence_registration() {
+        let state = Arc::new(RwLock::new(ServerState::new()));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        state.write().await.clients.insert("viewer_0".to_string(), ClientSender { sender: tx });
+
+        let register: SignalMessage = serde_json::from_str(
+            r#"{"type":"register","heartbeat_secs":30,"device":{"device_id":"office-pc","name":"Office","hostname":"office","os":"linux-x86_64","version":"0.1.0"}}"#,
+        )
+        .unwrap();
+        // 未认证的连接不能登记
+        handle_signal(register.clone(), "viewer_0", &state).await;
+        assert!(rx.try_recv().unwrap().contains(r#""type":"error""#));
+        assert!(state.read().await.presence.list(PresenceRegistry::now_ms()).is_empty());
+
+        state.write().await.identities.insert("viewer_0".to_string(), "fleet-token".to_string());
+        handle_signal(register, "viewer_0", &state).await;
+        assert!(rx.try_recv().unwrap().contains(r#""type":"registered""#));
+        handle_signal(SignalMessage::Heartbeat, "viewer_0", &state).await;
+
+        let devices = state.read().await.presence.list(PresenceRegistry::now_ms());
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].info.name, "Office");
+        assert!(devices[0].online);
+
+        // 连接断开后设备保留在清单中，标记为离线
+        state.write().await.disconnect("viewer_0");
+        let devices = state.read().await.presence.list(PresenceRegistry::now_ms());
+        assert_eq!(devices.len(), 1);
+        assert!(!devices[0].online);
+    }
+
+    #[tokio::test]
+    async fn test_device_takeover_rejected() {
+        let mut server = EmbeddedSignalingServer::new(0);
+        server.set_fleet_token(Some("fleet-secret".to_string()));
+        let state = server.state.clone();
+        let register = |url: &str| SignalMessage::Register {
+            device: DeviceInfo {
+                device_id: "office-pc".to_string(),
+                name: "Office".to_string(),
+                hostname: "office".to_string(),
+                os: "linux-x86_64".to_string(),
+                version: "0.1.0".to_string(),
+                url: Some(url.to_string()),
+            },
+            heartbeat_secs: 30,
+        };
+        let mut receivers = Vec::new();
+        for (peer_id, identity) in [("viewer_0", "api-key:office"), ("viewer_1", "api-key:intruder")] {
+            let (tx, rx) = mpsc::unbounded_channel();
+            let mut state = state.write().await;
+            state.clients.insert(peer_id.to_string(), ClientSender { sender: tx });
+            state.identities.insert(peer_id.to_string(), identity.to_string());
+            receivers.push(rx);
+        }
+
+        handle_signal(register("https://office.example.com"), "viewer_0", &state).await;
+        assert!(receivers[0].try_recv().unwrap().contains(r#""type":"registered""#));
+        // 设备在线时其他身份不能用同一设备 ID 改写地址
+        handle_signal(register("https://attacker.example.com"), "viewer_1", &state).await;
+        assert!(receivers[1].try_recv().unwrap().contains(r#""type":"error""#));
+        let devices = state.read().await.presence.list(PresenceRegistry::now_ms());
+        assert_eq!(devices[0].info.url.as_deref(), Some("https://office.example.com"));
+
+        // 设备清单同样需要认证
+        let app_state = app_state(&server);
+        let devices = |query: HashMap<String, String>| devices_handler(HeaderMap::new(), Query(query), State(app_state.clone()));
+        assert_eq!(devices(HashMap::new()).await.into_response().status(), StatusCode::UNAUTHORIZED);
+        let wrong = HashMap::from([("token".to_string(), "guess".to_string())]);
+        assert_eq!(devices(wrong).await.into_response().status(), StatusCode::UNAUTHORIZED);
+        let token = HashMap::from([("token".to_string(), "fleet-secret".to_string())]);
+        assert_eq!(devices(token).await.into_response().status(), StatusCode::OK);
+    }
 }
@@ -0,0 +1,69 @@
+//! Host 环境信息
+//!
+//! Viewer 加入时随 hello 消息下发 Host 的时区、区域设置和当前时间，
+//! 便于跨地区的技术支持人员确认对方的本地时间
+
+use chrono::{Local, Offset};
+use serde::{Deserialize, Serialize};
+
+/// Host 时区/区域信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostInfo {
+    /// IANA 时区名 (如 "Asia/Shanghai")，无法获取时为 None
+    pub timezone: Option<String>,
+    /// 相对 UTC 的偏移 (分钟)
+    pub utc_offset_minutes: i32,
+    /// 区域设置 (BCP 47，如 "zh-CN")
+    pub locale: Option<String>,
+    /// Host 当前时间 (Unix 毫秒)
+    pub time_ms: i64,
+}
+
+impl HostInfo {
+    /// 采集当前 Host 的时区/区域信息
+    pub fn current() -> Self {
+        let now = Local::now();
+        Self {
+            timezone: iana_time_zone::get_timezone().ok(),
+            utc_offset_minutes: now.offset().fix().local_minus_utc() / 60,
+            locale: sys_locale::get_locale(),
+            time_ms: now.timestamp_millis(),
+        }
+    }
+
+    /// UTC 偏移的显示形式 (如 "UTC+08:00")
+    pub fn utc_offset_label(&self) -> String {
+        let sign = if self.utc_offset_minutes < 0 { '-' } else { '+' };
+        let minutes = self.utc_offset_minutes.unsigned_abs();
+        format!("UTC{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utc_offset_label() {
+        let mut info = HostInfo {
+            timezone: Some("Asia/Kolkata".to_string()),
+            utc_offset_minutes: 330,
+            locale: Some("en-IN".to_string()),
+            time_ms: 0,
+        };
+        assert_eq!(info.utc_offset_label(), "UTC+05:30");
+
+        info.utc_offset_minutes = -300;
+        assert_eq!(info.utc_offset_label(), "UTC-05:00");
+    }
+
+    #[test]
+    fn test_current_time_is_recent() {
+        let info = HostInfo::current();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        assert!((now - info.time_ms).abs() < 5_000);
+    }
+}
@@ -0,0 +1,272 @@
+//! Host 事件通道
+//!
+//! Host 通过 mpsc 通道接收信令事件 (见 `HostSignalEvent`)。Host 的事件循环退出或重启时通道断开，
+//! 此前发给 Host 的 Offer 会被丢弃，已连接的 Viewer 一直等不到 Answer。
+//!
+//! 通道断开后进入宽限期: 房间和 Viewer 的注册保留，发往 Host 的事件暂存在这里；
+//! Host 凭恢复令牌重新注册后按原顺序重放暂存的事件 (同一 Viewer 只保留最新的 Offer)。
+//! 宽限期过后仍未重新注册，由信令服务器通知并断开所有 Viewer
+
+use super::embedded::HostSignalEvent;
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// 宽限期内最多暂存的事件数 (超过后丢弃最早的事件)
+const MAX_PENDING: usize = 256;
+
+/// 通道状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostLinkStatus {
+    /// Host 在线
+    Attached,
+    /// Host 已断开，仍在宽限期内
+    Detached,
+    /// 宽限期已过 (或从未注册)
+    Gone,
+}
+
+#[derive(Default)]
+struct LinkState {
+    tx: Option<mpsc::UnboundedSender<HostSignalEvent>>,
+    /// 断开期间暂存的事件
+    pending: VecDeque<HostSignalEvent>,
+    /// 断开时间 (None = 在线或从未注册)
+    detached_at: Option<Instant>,
+    /// 重新注册用的令牌 (每次注册后更换)
+    token: String,
+}
+
+/// Host 事件通道 (断开后暂存事件，宽限期内可重新注册)
+pub struct HostLink {
+    state: Mutex<LinkState>,
+    grace: Duration,
+}
+
+impl HostLink {
+    pub fn new(grace: Duration) -> Self {
+        Self { state: Mutex::new(LinkState::default()), grace }
+    }
+
+    /// 注册新的 Host，返回其事件接收器
+    pub fn attach(&self) -> mpsc::UnboundedReceiver<HostSignalEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.connect(tx);
+        rx
+    }
+
+    /// 使用给定的发送端注册 Host，并重放暂存的事件
+    pub fn connect(&self, tx: mpsc::UnboundedSender<HostSignalEvent>) {
+        let mut state = self.lock();
+        let pending = std::mem::take(&mut state.pending);
+        if !pending.is_empty() {
+            tracing::info!("Host 重新注册，重放 {} 个暂存的信令事件", pending.len());
+        }
+        for event in pending {
+            let _ = tx.send(event);
+        }
+        state.tx = Some(tx);
+        state.detached_at = None;
+        state.token = new_token();
+    }
+
+    /// 宽限期内凭令牌重新注册
+    pub fn reattach(&self, token: &str) -> Result<mpsc::UnboundedReceiver<HostSignalEvent>> {
+        match self.status() {
+            HostLinkStatus::Attached => return Err(anyhow!("Host 仍在线，不能重复注册")),
+            HostLinkStatus::Gone => return Err(anyhow!("Host 宽限期已过，需要重新启动信令服务")),
+            HostLinkStatus::Detached => {}
+        }
+        if self.lock().token != token {
+            return Err(anyhow!("Host 恢复令牌无效"));
+        }
+        Ok(self.attach())
+    }
+
+    /// 当前的恢复令牌 (从未注册时为 None)
+    pub fn token(&self) -> Option<String> {
+        let state = self.lock();
+        (!state.token.is_empty()).then(|| state.token.clone())
+    }
+
+    /// 发送事件给 Host: Host 断开时暂存，宽限期过后丢弃
+    pub fn send(&self, event: HostSignalEvent) {
+        self.send_at(event, Instant::now());
+    }
+
+    fn send_at(&self, event: HostSignalEvent, now: Instant) {
+        let mut state = self.lock();
+        let event = match state.tx.as_ref() {
+            Some(tx) => match tx.send(event) {
+                Ok(()) => return,
+                Err(mpsc::error::SendError(event)) => event,
+            },
+            None => event,
+        };
+        if state.tx.take().is_some() {
+            tracing::warn!("Host 事件通道已断开，{} 秒内等待 Host 重新注册", self.grace.as_secs());
+            state.detached_at = Some(now);
+        }
+        if state.detached_at.is_some_and(|at| now.duration_since(at) < self.grace) {
+            push_pending(&mut state.pending, event);
+        }
+    }
+
+    /// 检查通道状态 (接收端已关闭时开始计算宽限期)
+    pub fn status(&self) -> HostLinkStatus {
+        self.status_at(Instant::now())
+    }
+
+    fn status_at(&self, now: Instant) -> HostLinkStatus {
+        let mut state = self.lock();
+        if state.tx.as_ref().is_some_and(|tx| tx.is_closed()) {
+            tracing::warn!("Host 事件通道已断开，{} 秒内等待 Host 重新注册", self.grace.as_secs());
+            state.tx = None;
+            state.detached_at = Some(now);
+        }
+        match state.detached_at {
+            _ if state.tx.is_some() => HostLinkStatus::Attached,
+            Some(at) if now.duration_since(at) < self.grace => HostLinkStatus::Detached,
+            _ => HostLinkStatus::Gone,
+        }
+    }
+
+    /// 宽限期已过: 丢弃暂存的事件，返回是否是第一次发现 (需要断开 Viewer)
+    pub fn expire(&self) -> bool {
+        self.expire_at(Instant::now())
+    }
+
+    fn expire_at(&self, now: Instant) -> bool {
+        if self.status_at(now) != HostLinkStatus::Gone {
+            return false;
+        }
+        let mut state = self.lock();
+        state.pending.clear();
+        state.detached_at.take().is_some()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LinkState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// 暂存事件: 同一 Viewer 只保留最新的 Offer；Viewer 离开时丢弃它尚未送达的事件
+fn push_pending(pending: &mut VecDeque<HostSignalEvent>, event: HostSignalEvent) {
+    match &event {
+        HostSignalEvent::Offer { from, .. } => {
+            pending.retain(|queued| !matches!(queued, HostSignalEvent::Offer { from: queued_from, .. } if queued_from == from));
+        }
+        HostSignalEvent::ViewerLeft { peer_id } => {
+            let joined_while_detached = pending
+                .iter()
+                .any(|queued| matches!(queued, HostSignalEvent::ViewerJoined { peer_id: joined } if joined == peer_id));
+            pending.retain(|queued| queued.peer_id() != Some(peer_id.as_str()));
+            // 加入和离开都发生在断开期间: Host 无需知道这个 Viewer
+            if joined_while_detached {
+                return;
+            }
+        }
+        _ => {}
+    }
+    if pending.len() >= MAX_PENDING {
+        pending.pop_front();
+    }
+    pending.push_back(event);
+}
+
+fn new_token() -> String {
+    let mut bytes = [0u8; 24];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRACE: Duration = Duration::from_secs(30);
+
+    fn offer(from: &str, sdp: &str) -> HostSignalEvent {
+        HostSignalEvent::Offer { from: from.to_string(), sdp: sdp.to_string(), session_id: format!("s-{}", from) }
+    }
+
+    fn drain(rx: &mut mpsc::UnboundedReceiver<HostSignalEvent>) -> Vec<HostSignalEvent> {
+        std::iter::from_fn(|| rx.try_recv().ok()).collect()
+    }
+
+    #[test]
+    fn test_replays_pending_offers_after_reattach() {
+        let link = HostLink::new(GRACE);
+        let start = Instant::now();
+        let rx = link.attach();
+        let token = link.token().unwrap();
+        assert_eq!(link.status_at(start), HostLinkStatus::Attached);
+
+        // Host 事件循环退出
+        drop(rx);
+        link.send_at(HostSignalEvent::ViewerJoined { peer_id: "viewer_1".to_string() }, start);
+        link.send_at(offer("viewer_1", "v=0 old"), start);
+        link.send_at(offer("viewer_1", "v=0 new"), start);
+        assert_eq!(link.status_at(start + Duration::from_secs(5)), HostLinkStatus::Detached);
+
+        assert!(link.reattach("forged").is_err());
+        let mut rx = link.reattach(&token).unwrap();
+        let events = drain(&mut rx);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(&events[0], HostSignalEvent::ViewerJoined { peer_id } if peer_id == "viewer_1"));
+        assert!(matches!(&events[1], HostSignalEvent::Offer { sdp, .. } if sdp == "v=0 new"));
+
+        // 重新注册后更换令牌，之后的事件直接送达
+        assert_ne!(link.token().unwrap(), token);
+        assert!(link.reattach(&link.token().unwrap()).is_err());
+        link.send(offer("viewer_2", "v=0"));
+        assert_eq!(drain(&mut rx).len(), 1);
+    }
+
+    #[test]
+    fn test_viewer_left_while_detached_is_forgotten() {
+        let link = HostLink::new(GRACE);
+        let start = Instant::now();
+        drop(link.attach());
+
+        link.send_at(HostSignalEvent::ViewerJoined { peer_id: "viewer_1".to_string() }, start);
+        link.send_at(offer("viewer_1", "v=0"), start);
+        link.send_at(offer("viewer_2", "v=0"), start);
+        link.send_at(HostSignalEvent::ViewerLeft { peer_id: "viewer_1".to_string() }, start);
+        link.send_at(HostSignalEvent::ViewerLeft { peer_id: "viewer_2".to_string() }, start);
+
+        let mut rx = link.attach();
+        let events = drain(&mut rx);
+        // viewer_2 在断开前已加入，Host 需要知道它离开了
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], HostSignalEvent::ViewerLeft { peer_id } if peer_id == "viewer_2"));
+    }
+
+    #[test]
+    fn test_grace_period_expires() {
+        let link = HostLink::new(GRACE);
+        let start = Instant::now();
+        // 从未注册的 Host 没有宽限期
+        assert_eq!(link.status_at(start), HostLinkStatus::Gone);
+        assert!(!link.expire_at(start));
+
+        let rx = link.attach();
+        let token = link.token().unwrap();
+        drop(rx);
+        assert_eq!(link.status_at(start), HostLinkStatus::Detached);
+        link.send_at(offer("viewer_1", "v=0"), start);
+        assert!(!link.expire_at(start + GRACE - Duration::from_secs(1)));
+
+        assert!(link.expire_at(start + GRACE));
+        assert!(!link.expire_at(start + GRACE));
+        // 宽限期之后的事件不再暂存
+        link.send_at(offer("viewer_2", "v=0"), start + GRACE);
+        assert!(link.reattach(&token).is_err());
+        assert!(drain(&mut link.attach()).is_empty());
+    }
+}
@@ -0,0 +1,241 @@
+//! Prometheus 指标
+//!
+//! 启用 metrics feature 后信令服务器提供 `/metrics` 端点 (Prometheus 文本格式):
+//! - 连接: 当前 WebSocket 连接数、活跃会话数、排队数，累计连接数和会话恢复 (重连) 次数
+//! - 视频: 累计帧数/字节数 (码率用 `rate()` 计算)、实时帧率、编码器目标码率、
+//!   视频任务崩溃或卡死后的重启次数、编码器连续失败后的切换次数
+//! - 带宽: 最近 2 秒的视频发送码率和当前的合计带宽上限 (来自 `quality::bandwidth`)
+//! - 延迟: 编码耗时、端到端延迟和 RTT 的 p50/p95 (来自 `quality::latency`)
+//! - 输入: 累计注入、合并和丢弃的输入事件数 (来自 `input::coalescer`)
+//!
+//! 计数器是进程级的，由 Host 视频流水线和信令服务器分别写入
+
+// 未启用媒体传输时没有视频帧可统计
+#![cfg_attr(not(any(feature = "webrtc", feature = "quic")), allow(dead_code))]
+
+use crate::quality::bandwidth;
+use crate::quality::latency::{self, Percentiles};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 抓取时由信令服务器提供的连接状态
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectionGauges {
+    /// 当前 WebSocket 连接数 (含排队中的)
+    pub clients: usize,
+    /// 已放行的会话数
+    pub active_sessions: usize,
+    /// 排队等待的 Viewer 数
+    pub queued: usize,
+}
+
+/// 进程级指标
+#[derive(Debug)]
+pub struct Metrics {
+    connections_total: AtomicU64,
+    resumes_total: AtomicU64,
+    frames_total: AtomicU64,
+    bytes_total: AtomicU64,
+    /// f64 的位模式
+    frame_rate: AtomicU64,
+    target_bitrate_kbps: AtomicU64,
+    target_frame_rate: AtomicU64,
+    video_restarts_total: AtomicU64,
+    encoder_switches_total: AtomicU64,
+    input_injected_total: AtomicU64,
+    input_merged_total: AtomicU64,
+    input_dropped_total: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub const fn new() -> Self {
+        Self {
+            connections_total: AtomicU64::new(0),
+            resumes_total: AtomicU64::new(0),
+            frames_total: AtomicU64::new(0),
+            bytes_total: AtomicU64::new(0),
+            frame_rate: AtomicU64::new(0),
+            target_bitrate_kbps: AtomicU64::new(0),
+            target_frame_rate: AtomicU64::new(0),
+            video_restarts_total: AtomicU64::new(0),
+            encoder_switches_total: AtomicU64::new(0),
+            input_injected_total: AtomicU64::new(0),
+            input_merged_total: AtomicU64::new(0),
+            input_dropped_total: AtomicU64::new(0),
+        }
+    }
+
+    /// 新的 WebSocket 连接
+    pub fn viewer_connected(&self) {
+        self.connections_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Viewer 断线重连后恢复了会话
+    pub fn session_resumed(&self) {
+        self.resumes_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 发送了一帧视频
+    pub fn record_frame(&self, bytes: usize) {
+        self.frames_total.fetch_add(1, Ordering::Relaxed);
+        self.bytes_total.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// 最近一秒的实际帧率
+    pub fn set_frame_rate(&self, fps: f64) {
+        self.frame_rate.store(fps.to_bits(), Ordering::Relaxed);
+    }
+
+    /// 编码器当前的目标码率
+    pub fn set_target_bitrate(&self, kbps: u32) {
+        self.target_bitrate_kbps.store(u64::from(kbps), Ordering::Relaxed);
+    }
+
+    /// 帧率调节后的目标帧率
+    pub fn set_target_frame_rate(&self, fps: u32) {
+        self.target_frame_rate.store(u64::from(fps), Ordering::Relaxed);
+    }
+
+    /// 视频任务崩溃或卡死后重建了捕获器和编码器
+    pub fn record_video_restart(&self) {
+        self.video_restarts_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 编码器连续失败，换用了下一个编码器
+    pub fn record_encoder_switch(&self) {
+        self.encoder_switches_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 注入了输入事件
+    pub fn record_input_injected(&self, count: u64) {
+        self.input_injected_total.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// 移动/滚动事件被后续事件合并
+    pub fn record_input_merged(&self, count: u64) {
+        self.input_merged_total.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// 丢弃了输入事件
+    pub fn record_input_dropped(&self, count: u64) {
+        self.input_dropped_total.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// 渲染为 Prometheus 文本格式
+    pub fn render(&self, connections: &ConnectionGauges) -> String {
+        let mut out = String::new();
+        let load = |value: &AtomicU64| value.load(Ordering::Relaxed);
+
+        sample(&mut out, "sscontrol_connected_clients", "gauge", "当前 WebSocket 连接数", connections.clients);
+        sample(&mut out, "sscontrol_active_sessions", "gauge", "已放行的会话数", connections.active_sessions);
+        sample(&mut out, "sscontrol_queued_viewers", "gauge", "排队等待的 Viewer 数", connections.queued);
+        sample(&mut out, "sscontrol_connections_total", "counter", "累计 WebSocket 连接数", load(&self.connections_total));
+        sample(&mut out, "sscontrol_session_resumes_total", "counter", "断线重连后恢复的会话数", load(&self.resumes_total));
+
+        sample(&mut out, "sscontrol_video_frames_total", "counter", "已发送的视频帧数", load(&self.frames_total));
+        sample(&mut out, "sscontrol_video_bytes_total", "counter", "已发送的视频字节数", load(&self.bytes_total));
+        sample(&mut out, "sscontrol_video_frame_rate", "gauge", "最近一秒的实际帧率", f64::from_bits(load(&self.frame_rate)));
+        sample(&mut out, "sscontrol_video_target_bitrate_kbps", "gauge", "编码器目标码率 (kbps)", load(&self.target_bitrate_kbps));
+        sample(&mut out, "sscontrol_video_target_frame_rate", "gauge", "帧率调节后的目标帧率", load(&self.target_frame_rate));
+        sample(&mut out, "sscontrol_video_restarts_total", "counter", "视频任务崩溃或卡死后的重启次数", load(&self.video_restarts_total));
+        sample(&mut out, "sscontrol_encoder_switches_total", "counter", "编码器连续失败后的切换次数", load(&self.encoder_switches_total));
+
+        sample(&mut out, "sscontrol_input_events_injected_total", "counter", "已注入的输入事件数", load(&self.input_injected_total));
+        sample(&mut out, "sscontrol_input_events_merged_total", "counter", "被合并的鼠标移动/滚动事件数", load(&self.input_merged_total));
+        sample(&mut out, "sscontrol_input_events_dropped_total", "counter", "丢弃的输入事件数", load(&self.input_dropped_total));
+
+        let usage = bandwidth::monitor().usage();
+        sample(&mut out, "sscontrol_bandwidth_usage_kbps", "gauge", "最近 2 秒发给所有观看者的视频码率 (kbps)", usage.usage_kbps);
+        sample(&mut out, "sscontrol_bandwidth_cap_kbps", "gauge", "合计带宽上限 (kbps，0 = 不限制)", usage.limit.total_kbps.unwrap_or(0));
+
+        let stats = latency::get_statistics();
+        quantiles(&mut out, "sscontrol_encode_latency_milliseconds", "编码耗时", stats.encode_ms);
+        quantiles(&mut out, "sscontrol_end_to_end_latency_milliseconds", "采集到控制端显示的延迟", stats.end_to_end_ms);
+        quantiles(&mut out, "sscontrol_rtt_milliseconds", "网络往返时延", stats.rtt_ms);
+        out
+    }
+}
+
+/// 进程内共享的指标
+pub fn metrics() -> &'static Metrics {
+    static METRICS: Metrics = Metrics::new();
+    &METRICS
+}
+
+fn sample(out: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// 分位数 (没有样本时只输出说明)
+fn quantiles(out: &mut String, name: &str, help: &str, value: Option<Percentiles>) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    if let Some(p) = value {
+        let _ = writeln!(out, "{}{{quantile=\"0.5\"}} {}", name, p.p50);
+        let _ = writeln!(out, "{}{{quantile=\"0.95\"}} {}", name, p.p95);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus_text() {
+        let metrics = Metrics::new();
+        metrics.viewer_connected();
+        metrics.viewer_connected();
+        metrics.session_resumed();
+        metrics.record_frame(1200);
+        metrics.record_frame(800);
+        metrics.set_frame_rate(29.5);
+        metrics.set_target_bitrate(2500);
+        metrics.set_target_frame_rate(5);
+        metrics.record_video_restart();
+        metrics.record_encoder_switch();
+        metrics.record_input_injected(10);
+        metrics.record_input_merged(7);
+
+        let text = metrics.render(&ConnectionGauges {
+            clients: 3,
+            active_sessions: 2,
+            queued: 1,
+        });
+        for line in [
+            "# TYPE sscontrol_connected_clients gauge",
+            "sscontrol_connected_clients 3",
+            "sscontrol_active_sessions 2",
+            "sscontrol_queued_viewers 1",
+            "# TYPE sscontrol_connections_total counter",
+            "sscontrol_connections_total 2",
+            "sscontrol_session_resumes_total 1",
+            "sscontrol_video_frames_total 2",
+            "sscontrol_video_bytes_total 2000",
+            "sscontrol_video_frame_rate 29.5",
+            "sscontrol_video_target_bitrate_kbps 2500",
+            "sscontrol_video_target_frame_rate 5",
+            "sscontrol_video_restarts_total 1",
+            "sscontrol_encoder_switches_total 1",
+            "sscontrol_input_events_injected_total 10",
+            "sscontrol_input_events_merged_total 7",
+            "sscontrol_input_events_dropped_total 0",
+            "# TYPE sscontrol_bandwidth_usage_kbps gauge",
+            "# TYPE sscontrol_encode_latency_milliseconds gauge",
+        ] {
+            assert!(text.lines().any(|l| l == line), "缺少 `{}`:\n{}", line, text);
+        }
+        // 每个样本行都是 `名称[标签] 数值`
+        for line in text.lines().filter(|l| !l.starts_with('#')) {
+            let (_, value) = line.rsplit_once(' ').unwrap();
+            assert!(value.parse::<f64>().is_ok(), "无效的样本行: {}", line);
+        }
+    }
+}
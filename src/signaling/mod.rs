@@ -2,6 +2,31 @@
 //!
 //! 提供内嵌信令服务器，用于局域网极简模式
 
+pub mod admission;
+pub mod approval;
+pub mod capabilities;
+pub mod chat;
+#[cfg(feature = "redis")]
+pub mod cluster;
+pub mod control;
 mod embedded;
+pub mod host_info;
+pub mod host_link;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod permissions;
+pub mod pin;
+pub mod presence;
+pub mod rate_limit;
+pub mod session_token;
+pub mod ticket;
+pub mod timeout;
+pub mod video_stream;
 
+pub use admission::CapacityConfig;
+pub use approval::ApprovalMode;
+pub use capabilities::HostCapabilities;
 pub use embedded::{EmbeddedSignalingServer, HostSignalEvent};
+pub use host_info::HostInfo;
+pub use permissions::{Permission, SessionPermissions};
+pub use pin::PinConfig;
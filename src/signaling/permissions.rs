@@ -0,0 +1,164 @@
+//! 会话权限
+//!
+//! 每个 Viewer 持有一组权限，Host 按权限过滤 Viewer 发来的数据:
+//! - 输入: 鼠标/键盘事件
+//! - 剪贴板: 剪贴板同步
+//! - 文件传输: 文件收发
+//! - 电源: 注销、重启、关机 (默认不授予)
+//!
+//! 仅查看模式即全部权限关闭，Viewer 只能观看画面
+
+#![allow(dead_code)]
+
+use serde::{Deserialize, Serialize};
+
+/// 单项权限
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Input,
+    Clipboard,
+    FileTransfer,
+    Power,
+}
+
+impl Permission {
+    /// 数据通道标签对应的权限 (未知标签返回 None)
+    pub fn from_channel_label(label: &str) -> Option<Self> {
+        match label {
+            "input" => Some(Self::Input),
+            "clipboard" => Some(Self::Clipboard),
+            "file" | "file_transfer" => Some(Self::FileTransfer),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Input => "输入",
+            Self::Clipboard => "剪贴板",
+            Self::FileTransfer => "文件传输",
+            Self::Power => "电源操作",
+        }
+    }
+}
+
+/// Viewer 的权限集合
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionPermissions {
+    pub input: bool,
+    pub clipboard: bool,
+    pub file_transfer: bool,
+    /// 旧版本下发的权限没有该字段
+    #[serde(default)]
+    pub power: bool,
+}
+
+impl Default for SessionPermissions {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+impl SessionPermissions {
+    /// 全部权限
+    pub fn full() -> Self {
+        Self {
+            input: true,
+            clipboard: true,
+            file_transfer: true,
+            power: true,
+        }
+    }
+
+    /// 仅查看
+    pub fn view_only() -> Self {
+        Self {
+            input: false,
+            clipboard: false,
+            file_transfer: false,
+            power: false,
+        }
+    }
+
+    /// 由权限列表构造 (未列出的权限关闭)
+    pub fn from_list(permissions: &[Permission]) -> Self {
+        Self {
+            input: permissions.contains(&Permission::Input),
+            clipboard: permissions.contains(&Permission::Clipboard),
+            file_transfer: permissions.contains(&Permission::FileTransfer),
+            power: permissions.contains(&Permission::Power),
+        }
+    }
+
+    pub fn allows(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::Input => self.input,
+            Permission::Clipboard => self.clipboard,
+            Permission::FileTransfer => self.file_transfer,
+            Permission::Power => self.power,
+        }
+    }
+
+    pub fn is_view_only(&self) -> bool {
+        *self == Self::view_only()
+    }
+
+    /// 限制在 `limit` 之内 (两组权限取交集)
+    pub fn restrict(self, limit: SessionPermissions) -> Self {
+        Self {
+            input: self.input && limit.input,
+            clipboard: self.clipboard && limit.clipboard,
+            file_transfer: self.file_transfer && limit.file_transfer,
+            power: self.power && limit.power,
+        }
+    }
+}
+
+impl std::fmt::Display for SessionPermissions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_view_only() {
+            return write!(f, "仅查看");
+        }
+        let granted: Vec<_> = [Permission::Input, Permission::Clipboard, Permission::FileTransfer, Permission::Power]
+            .into_iter()
+            .filter(|p| self.allows(*p))
+            .map(|p| p.label())
+            .collect();
+        write!(f, "{}", granted.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_sets() {
+        assert!(SessionPermissions::default().allows(Permission::Input));
+        assert!(SessionPermissions::view_only().is_view_only());
+        assert_eq!(SessionPermissions::view_only().to_string(), "仅查看");
+
+        let input_only = SessionPermissions::from_list(&[Permission::Input]);
+        assert!(input_only.allows(Permission::Input));
+        assert!(!input_only.allows(Permission::Clipboard));
+        assert_eq!(input_only.to_string(), "输入");
+
+        assert_eq!(Permission::from_channel_label("file"), Some(Permission::FileTransfer));
+        assert_eq!(Permission::from_channel_label("video"), None);
+
+        let list: Vec<Permission> = serde_json::from_str(r#"["input","file_transfer"]"#).unwrap();
+        assert_eq!(
+            SessionPermissions::from_list(&list),
+            SessionPermissions { input: true, clipboard: false, file_transfer: true, power: false }
+        );
+
+        // 旧版本的权限没有 power 字段
+        let old: SessionPermissions =
+            serde_json::from_str(r#"{"input":true,"clipboard":true,"file_transfer":true}"#).unwrap();
+        assert!(!old.allows(Permission::Power));
+
+        assert_eq!(SessionPermissions::full().restrict(input_only), input_only);
+        assert!(input_only.restrict(SessionPermissions::view_only()).is_view_only());
+    }
+}
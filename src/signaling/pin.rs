@@ -0,0 +1,378 @@
+//! 一次性 PIN 验证
+//!
+//! Host 启动时生成 6 位数字 PIN 并打印在控制台，Viewer 必须通过信令提交正确的 PIN
+//! 后才能向 Host 发送 Offer。PIN 验证成功一次后立即更换，旧 PIN 作废。
+//!
+//! ## 防暴力破解
+//! 失败次数全局累计 (断线重连换 peer_id 无法绕过)。连续失败达到上限后锁定，
+//! 锁定期间所有提交直接拒绝；锁定结束后再次失败，锁定时间翻倍
+//!
+//! ## 无人值守访问
+//! 配置了访问密钥哈希时 (`sscontrol host --unattended`，见 `security::unattended`)，
+//! Viewer 也可以提交长期访问密钥代替 PIN。密钥验证通过后不换发 PIN，
+//! 失败同样计入上面的失败次数和锁定。Argon2 比对耗时数百毫秒，调用方先用 [`PinGuard::begin`]
+//! 取出哈希，释放状态锁后再比对，最后用 [`PinGuard::finish`] 记录结果
+
+#![allow(dead_code)]
+
+use rand::Rng;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// PIN 位数
+pub const PIN_DIGITS: usize = 6;
+
+/// PIN 验证配置
+#[derive(Debug, Clone)]
+pub struct PinConfig {
+    /// 锁定前允许的连续失败次数
+    pub max_attempts: u32,
+    /// 首次锁定时长
+    pub base_backoff: Duration,
+    /// 锁定时长上限
+    pub max_backoff: Duration,
+    /// 无人值守访问密钥的 Argon2 哈希 (需要 security feature)
+    pub access_secret_hash: Option<String>,
+}
+
+impl Default for PinConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_backoff: Duration::from_secs(30),
+            max_backoff: Duration::from_secs(15 * 60),
+            access_secret_hash: None,
+        }
+    }
+}
+
+/// 验证结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinVerdict {
+    /// 验证通过
+    Accepted,
+    /// PIN 错误 (剩余尝试次数)
+    Rejected { remaining: u32 },
+    /// 失败次数过多，锁定中
+    Locked { retry_after: Duration },
+}
+
+/// [`PinGuard::begin`] 的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinAttempt {
+    /// 已得出结果
+    Done(PinVerdict),
+    /// PIN 不匹配，还需比对无人值守访问密钥 (已预先计入一次失败，不匹配时结果为 `otherwise`)
+    CheckSecret { hash: String, otherwise: PinVerdict },
+}
+
+/// 连续失败计数和指数退避锁定 (PIN 与 TOTP 验证共用同一规则)
+#[derive(Debug)]
+pub struct AttemptLimiter {
+    max_attempts: u32,
+    base_backoff: Duration,
+    max_backoff: Duration,
+    failures: u32,
+    locked_until: Option<Instant>,
+}
+
+impl AttemptLimiter {
+    pub fn new(config: &PinConfig) -> Self {
+        Self {
+            max_attempts: config.max_attempts,
+            base_backoff: config.base_backoff,
+            max_backoff: config.max_backoff,
+            failures: 0,
+            locked_until: None,
+        }
+    }
+
+    /// 锁定中时返回对应的结果
+    pub fn check(&self, now: Instant) -> Option<PinVerdict> {
+        let until = self.locked_until?;
+        (now < until).then(|| PinVerdict::Locked { retry_after: until - now })
+    }
+
+    /// 验证成功，清零失败次数
+    pub fn succeed(&mut self) {
+        self.failures = 0;
+        self.locked_until = None;
+    }
+
+    /// 记录一次失败，达到上限后锁定
+    pub fn fail(&mut self, now: Instant) -> PinVerdict {
+        self.failures += 1;
+        if self.failures < self.max_attempts {
+            return PinVerdict::Rejected {
+                remaining: self.max_attempts - self.failures,
+            };
+        }
+
+        // 每多失败一次锁定时间翻倍
+        let exponent = (self.failures - self.max_attempts).min(16);
+        let backoff = self.base_backoff.saturating_mul(1 << exponent).min(self.max_backoff);
+        self.locked_until = Some(now + backoff);
+        PinVerdict::Locked { retry_after: backoff }
+    }
+}
+
+/// PIN 验证器
+#[derive(Debug)]
+pub struct PinGuard {
+    config: PinConfig,
+    pin: String,
+    verified: HashSet<String>,
+    limiter: AttemptLimiter,
+}
+
+impl PinGuard {
+    pub fn new(config: PinConfig) -> Self {
+        Self {
+            limiter: AttemptLimiter::new(&config),
+            config,
+            pin: generate_pin(),
+            verified: HashSet::new(),
+        }
+    }
+
+    /// 当前有效的 PIN
+    pub fn pin(&self) -> &str {
+        &self.pin
+    }
+
+    /// Viewer 是否已通过验证
+    pub fn is_verified(&self, peer_id: &str) -> bool {
+        self.verified.contains(peer_id)
+    }
+
+    /// 直接信任 Viewer (恢复已验证过的会话时使用，不消耗 PIN)
+    pub fn trust(&mut self, peer_id: &str) {
+        self.verified.insert(peer_id.to_string());
+    }
+
+    /// Viewer 断开后清除验证状态
+    pub fn forget(&mut self, peer_id: &str) {
+        self.verified.remove(peer_id);
+    }
+
+    /// 校验 Viewer 提交的 PIN (在当前线程比对访问密钥，持有状态锁时应改用 `begin`/`finish`)
+    pub fn verify(&mut self, peer_id: &str, pin: &str) -> PinVerdict {
+        self.verify_at(peer_id, pin, Instant::now())
+    }
+
+    fn verify_at(&mut self, peer_id: &str, pin: &str, now: Instant) -> PinVerdict {
+        match self.begin_at(peer_id, pin, now) {
+            PinAttempt::Done(verdict) => verdict,
+            PinAttempt::CheckSecret { hash, otherwise } => {
+                let matched = matches_access_secret(&hash, pin);
+                self.finish(peer_id, matched, otherwise)
+            }
+        }
+    }
+
+    /// 校验的第一步: 比对 PIN，需要比对访问密钥时返回哈希
+    pub fn begin(&mut self, peer_id: &str, pin: &str) -> PinAttempt {
+        self.begin_at(peer_id, pin, Instant::now())
+    }
+
+    fn begin_at(&mut self, peer_id: &str, pin: &str, now: Instant) -> PinAttempt {
+        if self.is_verified(peer_id) {
+            return PinAttempt::Done(PinVerdict::Accepted);
+        }
+        if let Some(locked) = self.limiter.check(now) {
+            return PinAttempt::Done(locked);
+        }
+
+        if constant_time_eq(pin.trim().as_bytes(), self.pin.as_bytes()) {
+            self.verified.insert(peer_id.to_string());
+            self.limiter.succeed();
+            // 一次性 PIN: 用过即换
+            self.pin = generate_pin();
+            return PinAttempt::Done(PinVerdict::Accepted);
+        }
+
+        // 先计入失败: 比对期间的并发尝试同样受次数限制
+        let otherwise = self.limiter.fail(now);
+        match self.access_secret_hash() {
+            Some(hash) => PinAttempt::CheckSecret { hash: hash.to_string(), otherwise },
+            None => PinAttempt::Done(otherwise),
+        }
+    }
+
+    /// 校验的第二步: 记录访问密钥的比对结果
+    pub fn finish(&mut self, peer_id: &str, matched: bool, otherwise: PinVerdict) -> PinVerdict {
+        if !matched {
+            return otherwise;
+        }
+        tracing::info!("{} 使用无人值守访问密钥通过验证", peer_id);
+        self.verified.insert(peer_id.to_string());
+        self.limiter.succeed();
+        PinVerdict::Accepted
+    }
+
+    #[cfg(feature = "security")]
+    fn access_secret_hash(&self) -> Option<&str> {
+        self.config.access_secret_hash.as_deref()
+    }
+
+    #[cfg(not(feature = "security"))]
+    fn access_secret_hash(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// 比对无人值守访问密钥 (Argon2，阻塞数百毫秒)
+#[cfg(feature = "security")]
+pub fn matches_access_secret(hash: &str, secret: &str) -> bool {
+    crate::security::unattended::verify_secret(hash, secret)
+}
+
+#[cfg(not(feature = "security"))]
+pub fn matches_access_secret(_hash: &str, _secret: &str) -> bool {
+    false
+}
+
+/// 生成 6 位数字 PIN
+fn generate_pin() -> String {
+    let value = rand::rngs::OsRng.gen_range(0..10u32.pow(PIN_DIGITS as u32));
+    format!("{:0width$}", value, width = PIN_DIGITS)
+}
+
+/// 与 PIN 长度无关的恒定时间比较
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let diff = a
+        .iter()
+        .zip(b)
+        .fold(a.len() ^ b.len(), |acc, (x, y)| acc | (x ^ y) as usize);
+    diff == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard() -> PinGuard {
+        PinGuard::new(PinConfig {
+            max_attempts: 3,
+            base_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(30),
+            access_secret_hash: None,
+        })
+    }
+
+    #[test]
+    fn test_pin_is_one_time() {
+        let mut guard = guard();
+        let pin = guard.pin().to_string();
+        assert_eq!(pin.len(), PIN_DIGITS);
+        assert!(pin.bytes().all(|b| b.is_ascii_digit()));
+
+        assert_eq!(guard.verify("viewer_0", &pin), PinVerdict::Accepted);
+        assert!(guard.is_verified("viewer_0"));
+
+        // 旧 PIN 立即失效 (新 PIN 恰好相同的概率可忽略)
+        if guard.pin() != pin {
+            assert!(matches!(guard.verify("viewer_1", &pin), PinVerdict::Rejected { .. }));
+        }
+
+        guard.forget("viewer_0");
+        assert!(!guard.is_verified("viewer_0"));
+    }
+
+    #[test]
+    fn test_lockout_with_exponential_backoff() {
+        let mut guard = guard();
+        let start = Instant::now();
+
+        assert_eq!(guard.verify_at("a", "x", start), PinVerdict::Rejected { remaining: 2 });
+        // 换 peer_id 也累计失败次数
+        assert_eq!(guard.verify_at("b", "x", start), PinVerdict::Rejected { remaining: 1 });
+        assert_eq!(
+            guard.verify_at("c", "x", start),
+            PinVerdict::Locked { retry_after: Duration::from_secs(10) }
+        );
+
+        // 锁定期间正确的 PIN 也被拒绝
+        let pin = guard.pin().to_string();
+        assert!(matches!(
+            guard.verify_at("c", &pin, start + Duration::from_secs(5)),
+            PinVerdict::Locked { .. }
+        ));
+
+        // 锁定结束后再次失败，锁定时间翻倍并受上限约束
+        let later = start + Duration::from_secs(10);
+        assert_eq!(
+            guard.verify_at("c", "x", later),
+            PinVerdict::Locked { retry_after: Duration::from_secs(20) }
+        );
+        let later = later + Duration::from_secs(20);
+        assert_eq!(
+            guard.verify_at("c", "x", later),
+            PinVerdict::Locked { retry_after: Duration::from_secs(30) }
+        );
+
+        // 成功后清零
+        let later = later + Duration::from_secs(30);
+        assert_eq!(guard.verify_at("c", &pin, later), PinVerdict::Accepted);
+        assert_eq!(guard.verify_at("d", "x", later), PinVerdict::Rejected { remaining: 2 });
+    }
+
+    #[cfg(feature = "security")]
+    #[test]
+    fn test_access_secret_is_reusable() {
+        use crate::security::unattended::{generate_secret, hash_secret};
+
+        let secret = generate_secret();
+        let mut guard = PinGuard::new(PinConfig {
+            max_attempts: 2,
+            access_secret_hash: Some(hash_secret(&secret).unwrap()),
+            ..Default::default()
+        });
+        let pin = guard.pin().to_string();
+        let start = Instant::now();
+
+        // 密钥可重复使用，且不换发 PIN
+        assert_eq!(guard.verify_at("a", &secret, start), PinVerdict::Accepted);
+        assert_eq!(guard.verify_at("b", &secret, start), PinVerdict::Accepted);
+        assert_eq!(guard.pin(), pin);
+
+        // 错误密钥计入失败次数，锁定期间正确的密钥也被拒绝
+        assert_eq!(guard.verify_at("c", "wrong", start), PinVerdict::Rejected { remaining: 1 });
+        assert!(matches!(guard.verify_at("c", "wrong", start), PinVerdict::Locked { .. }));
+        assert!(matches!(guard.verify_at("c", &secret, start), PinVerdict::Locked { .. }));
+    }
+
+    #[cfg(feature = "security")]
+    #[test]
+    fn test_access_secret_checked_outside_guard() {
+        use crate::security::unattended::{generate_secret, hash_secret};
+
+        let secret = generate_secret();
+        let mut guard = PinGuard::new(PinConfig {
+            max_attempts: 2,
+            access_secret_hash: Some(hash_secret(&secret).unwrap()),
+            ..Default::default()
+        });
+        let start = Instant::now();
+
+        // 比对进行中的尝试已计入失败: 并发的错误尝试不能超出次数限制
+        let PinAttempt::CheckSecret { hash, otherwise } = guard.begin_at("a", &secret, start) else {
+            panic!("应需要比对访问密钥");
+        };
+        assert_eq!(otherwise, PinVerdict::Rejected { remaining: 1 });
+        assert!(matches!(
+            guard.begin_at("b", "wrong", start),
+            PinAttempt::CheckSecret { otherwise: PinVerdict::Locked { .. }, .. }
+        ));
+        assert!(matches!(guard.begin_at("c", "wrong", start), PinAttempt::Done(PinVerdict::Locked { .. })));
+
+        // 正确的密钥通过后解除锁定
+        let matched = matches_access_secret(&hash, &secret);
+        assert_eq!(guard.finish("a", matched, otherwise), PinVerdict::Accepted);
+        assert!(guard.is_verified("a"));
+        assert_eq!(
+            guard.begin_at("d", "wrong", start),
+            PinAttempt::CheckSecret { hash, otherwise: PinVerdict::Rejected { remaining: 1 } }
+        );
+    }
+}
@@ -0,0 +1,419 @@
+//! 设备在线状态 (设备清单)
+//!
+//! 配置了 `fleet.server` 的被控端与中心信令服务器保持一条 WebSocket 连接 (认证方式同 `/ws`)，
+//! 连接后发送 `register` 登记设备信息，之后按 `heartbeat_secs` 发送 `heartbeat`。
+//! 只有已认证的连接 (认证提供者的身份或 `fleet.registration_token`) 可以登记，
+//! 设备在线时只能由同一身份的连接重新登记，其他身份不能接管。
+//! 服务器在 `PresenceRegistry` 中记录每台设备的最近心跳时间:
+//! 连接断开或超过 [`MISSED_HEARTBEATS`] 个心跳间隔没有消息即视为离线，
+//! 离线设备保留在清单中 (显示最后在线时间)，直到服务器重启或清单已满被淘汰
+//!
+//! `GET /devices` 返回设备清单 (`sscontrol fleet list` 使用)
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 超过多少个心跳间隔没有消息视为离线
+pub const MISSED_HEARTBEATS: u64 = 3;
+
+/// 心跳间隔的取值范围 (秒)，设备上报的值会被限制在此范围内
+const HEARTBEAT_RANGE: (u64, u64) = (5, 3600);
+
+/// 清单中最多保留的设备数 (已满时淘汰最久未在线的离线设备)
+const MAX_DEVICES: usize = 1000;
+
+/// 单个字段的最大长度 (字符)，超出部分截断
+const MAX_FIELD_CHARS: usize = 128;
+
+/// 设备登记信息 (`register` 消息携带)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    /// 设备 ID (`server.device_id`)
+    pub device_id: String,
+    /// 显示名称 (`fleet.name`，未配置时为主机名)
+    pub name: String,
+    pub hostname: String,
+    /// 操作系统 (如 "linux-x86_64")
+    pub os: String,
+    /// sscontrol 版本
+    pub version: String,
+    /// 控制端连接该设备使用的地址 (如公网隧道地址)，None = 未知
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl DeviceInfo {
+    /// 本机的登记信息
+    pub fn local(device_id: &str, name: Option<&str>, url: Option<String>) -> Self {
+        let hostname = sysinfo::System::host_name().unwrap_or_else(|| "unknown".to_string());
+        Self {
+            device_id: device_id.to_string(),
+            name: name.map(str::to_string).unwrap_or_else(|| hostname.clone()),
+            hostname,
+            os: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            url,
+        }
+    }
+
+    /// 去掉首尾空白并截断超长字段
+    fn sanitized(self) -> Self {
+        let clean = |value: String| value.trim().chars().take(MAX_FIELD_CHARS).collect::<String>();
+        Self {
+            device_id: clean(self.device_id),
+            name: clean(self.name),
+            hostname: clean(self.hostname),
+            os: clean(self.os),
+            version: clean(self.version),
+            url: self.url.map(clean).filter(|url| !url.is_empty()),
+        }
+    }
+}
+
+/// 设备清单中的一项 (`GET /devices` 返回)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceStatus {
+    #[serde(flatten)]
+    pub info: DeviceInfo,
+    pub online: bool,
+    /// 最近一次收到该设备消息的时间 (Unix 毫秒)
+    pub last_seen_ms: u64,
+    /// 本次上线的时间 (Unix 毫秒)，离线时为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub online_since_ms: Option<u64>,
+}
+
+/// 登记失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterError {
+    /// 设备 ID 为空
+    MissingDeviceId,
+    /// 清单已满且没有可淘汰的离线设备
+    Full,
+    /// 设备 ID 正由其他身份的连接使用
+    Taken,
+}
+
+impl std::fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingDeviceId => write!(f, "设备 ID 不能为空"),
+            Self::Full => write!(f, "设备清单已满 ({} 台)", MAX_DEVICES),
+            Self::Taken => write!(f, "设备 ID 已被其他在线设备使用"),
+        }
+    }
+}
+
+/// 已登记的设备
+#[derive(Debug)]
+struct Entry {
+    info: DeviceInfo,
+    /// 登记该设备的连接身份
+    owner: String,
+    /// 当前连接的 peer_id (None = 已断开)
+    peer: Option<String>,
+    heartbeat_ms: u64,
+    last_seen_ms: u64,
+    online_since_ms: u64,
+}
+
+impl Entry {
+    fn is_online(&self, now_ms: u64) -> bool {
+        self.peer.is_some() && now_ms.saturating_sub(self.last_seen_ms) <= self.heartbeat_ms * MISSED_HEARTBEATS
+    }
+}
+
+/// 设备在线状态登记表
+#[derive(Debug, Default)]
+pub struct PresenceRegistry {
+    /// device_id -> 设备
+    devices: HashMap<String, Entry>,
+    /// peer_id -> device_id
+    peers: HashMap<String, String>,
+}
+
+impl PresenceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 当前毫秒时间戳
+    pub fn now_ms() -> u64 {
+        chrono::Utc::now().timestamp_millis().max(0) as u64
+    }
+
+    /// 登记设备，返回规范化后的设备 ID
+    ///
+    /// 同一身份从新连接重新登记时取代旧连接；设备仍有连接时拒绝其他身份 (`owner`) 的登记
+    pub fn register(
+        &mut self,
+        peer_id: &str,
+        owner: &str,
+        info: DeviceInfo,
+        heartbeat_secs: u64,
+        now_ms: u64,
+    ) -> Result<String, RegisterError> {
+        let info = info.sanitized();
+        if info.device_id.is_empty() {
+            return Err(RegisterError::MissingDeviceId);
+        }
+        if let Some(entry) = self.devices.get(&info.device_id) {
+            let held_by_other = entry.peer.as_deref().is_some_and(|peer| peer != peer_id);
+            if held_by_other && entry.owner != owner {
+                return Err(RegisterError::Taken);
+            }
+        }
+        if !self.devices.contains_key(&info.device_id) && self.devices.len() >= MAX_DEVICES {
+            self.evict_offline(now_ms)?;
+        }
+
+        // 同一连接换用其他设备 ID 登记时，旧设备按断开处理
+        if let Some(previous) = self.peers.get(peer_id).filter(|id| **id != info.device_id).cloned() {
+            if let Some(entry) = self.devices.get_mut(&previous) {
+                entry.peer = None;
+            }
+        }
+        let device_id = info.device_id.clone();
+        let heartbeat_ms = heartbeat_secs.clamp(HEARTBEAT_RANGE.0, HEARTBEAT_RANGE.1) * 1000;
+        let previous_peer = match self.devices.get_mut(&device_id) {
+            Some(entry) => {
+                let was_online = entry.is_online(now_ms);
+                entry.info = info;
+                entry.owner = owner.to_string();
+                entry.heartbeat_ms = heartbeat_ms;
+                entry.last_seen_ms = now_ms;
+                if !was_online {
+                    entry.online_since_ms = now_ms;
+                }
+                entry.peer.replace(peer_id.to_string())
+            }
+            None => {
+                self.devices.insert(
+                    device_id.clone(),
+                    Entry {
+                        info,
+                        owner: owner.to_string(),
+                        peer: Some(peer_id.to_string()),
+                        heartbeat_ms,
+                        last_seen_ms: now_ms,
+                        online_since_ms: now_ms,
+                    },
+                );
+                None
+            }
+        };
+        if let Some(previous_peer) = previous_peer.filter(|peer| peer != peer_id) {
+            self.peers.remove(&previous_peer);
+        }
+        self.peers.insert(peer_id.to_string(), device_id.clone());
+        Ok(device_id)
+    }
+
+    /// 记录心跳，连接未登记时返回 false
+    pub fn heartbeat(&mut self, peer_id: &str, now_ms: u64) -> bool {
+        let Some(entry) = self.peers.get(peer_id).and_then(|device_id| self.devices.get_mut(device_id)) else {
+            return false;
+        };
+        if !entry.is_online(now_ms) {
+            entry.online_since_ms = now_ms;
+        }
+        entry.last_seen_ms = now_ms;
+        true
+    }
+
+    /// 连接断开，返回离线的设备 ID (未登记的连接返回 None)
+    pub fn disconnect(&mut self, peer_id: &str) -> Option<String> {
+        let device_id = self.peers.remove(peer_id)?;
+        let entry = self.devices.get_mut(&device_id)?;
+        entry.peer = None;
+        Some(device_id)
+    }
+
+    /// 设备清单 (在线设备在前，同组按名称排序)
+    pub fn list(&self, now_ms: u64) -> Vec<DeviceStatus> {
+        let mut devices: Vec<DeviceStatus> = self
+            .devices
+            .values()
+            .map(|entry| {
+                let online = entry.is_online(now_ms);
+                DeviceStatus {
+                    info: entry.info.clone(),
+                    online,
+                    last_seen_ms: entry.last_seen_ms,
+                    online_since_ms: online.then_some(entry.online_since_ms),
+                }
+            })
+            .collect();
+        devices.sort_by(|a, b| {
+            b.online
+                .cmp(&a.online)
+                .then_with(|| a.info.name.cmp(&b.info.name))
+                .then_with(|| a.info.device_id.cmp(&b.info.device_id))
+        });
+        devices
+    }
+
+    /// 淘汰最久未在线的离线设备
+    fn evict_offline(&mut self, now_ms: u64) -> Result<(), RegisterError> {
+        let oldest = self
+            .devices
+            .iter()
+            .filter(|(_, entry)| !entry.is_online(now_ms))
+            .min_by_key(|(_, entry)| entry.last_seen_ms)
+            .map(|(device_id, _)| device_id.clone())
+            .ok_or(RegisterError::Full)?;
+        if let Some(entry) = self.devices.remove(&oldest) {
+            if let Some(peer) = entry.peer {
+                self.peers.remove(&peer);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OWNER: &str = "api-key:fleet";
+
+    fn device(id: &str, name: &str) -> DeviceInfo {
+        DeviceInfo {
+            device_id: id.to_string(),
+            name: name.to_string(),
+            hostname: name.to_string(),
+            os: "linux-x86_64".to_string(),
+            version: "0.1.0".to_string(),
+            url: None,
+        }
+    }
+
+    #[test]
+    fn test_register_and_disconnect() {
+        let mut registry = PresenceRegistry::new();
+        assert_eq!(registry.register("peer-1", OWNER, device("a", "office"), 30, 1_000).unwrap(), "a");
+
+        let devices = registry.list(2_000);
+        assert_eq!(devices.len(), 1);
+        assert!(devices[0].online);
+        assert_eq!(devices[0].online_since_ms, Some(1_000));
+
+        assert_eq!(registry.disconnect("peer-1").as_deref(), Some("a"));
+        let devices = registry.list(3_000);
+        assert!(!devices[0].online);
+        assert_eq!(devices[0].last_seen_ms, 1_000);
+        assert_eq!(devices[0].online_since_ms, None);
+        assert!(registry.disconnect("peer-1").is_none());
+    }
+
+    #[test]
+    fn test_missed_heartbeats_mark_offline() {
+        let mut registry = PresenceRegistry::new();
+        registry.register("peer-1", OWNER, device("a", "office"), 10, 0).unwrap();
+        assert!(registry.list(30_000)[0].online);
+        assert!(!registry.list(30_001)[0].online);
+
+        // 心跳恢复后重新上线，上线时间从恢复时算起
+        assert!(registry.heartbeat("peer-1", 40_000));
+        let devices = registry.list(40_000);
+        assert!(devices[0].online);
+        assert_eq!(devices[0].online_since_ms, Some(40_000));
+        assert!(!registry.heartbeat("peer-2", 40_000));
+    }
+
+    #[test]
+    fn test_reconnect_replaces_previous_connection() {
+        let mut registry = PresenceRegistry::new();
+        registry.register("peer-1", OWNER, device("a", "office"), 30, 0).unwrap();
+        registry.register("peer-2", OWNER, device("a", "office-renamed"), 30, 1_000).unwrap();
+
+        // 旧连接随后断开不影响新连接
+        assert!(registry.disconnect("peer-1").is_none());
+        let devices = registry.list(2_000);
+        assert_eq!(devices.len(), 1);
+        assert!(devices[0].online);
+        assert_eq!(devices[0].info.name, "office-renamed");
+        assert_eq!(devices[0].online_since_ms, Some(0));
+    }
+
+    #[test]
+    fn test_other_identity_cannot_take_over_online_device() {
+        let mut registry = PresenceRegistry::new();
+        let mut original = device("a", "office");
+        original.url = Some("https://office.example.com".to_string());
+        registry.register("peer-1", OWNER, original, 30, 0).unwrap();
+
+        let mut forged = device("a", "office");
+        forged.url = Some("https://attacker.example.com".to_string());
+        assert_eq!(
+            registry.register("peer-2", "api-key:other", forged.clone(), 30, 1_000),
+            Err(RegisterError::Taken)
+        );
+        let devices = registry.list(1_000);
+        assert_eq!(devices[0].info.url.as_deref(), Some("https://office.example.com"));
+        // 原连接的心跳不受影响，冒充的连接没有登记
+        assert!(registry.heartbeat("peer-1", 2_000));
+        assert!(!registry.heartbeat("peer-2", 2_000));
+
+        // 同一身份可以从新连接接替 (如重连)
+        registry.register("peer-3", OWNER, forged, 30, 3_000).unwrap();
+        assert_eq!(registry.list(3_000)[0].info.url.as_deref(), Some("https://attacker.example.com"));
+    }
+
+    #[test]
+    fn test_list_orders_online_first() {
+        let mut registry = PresenceRegistry::new();
+        registry.register("peer-1", OWNER, device("a", "zeta"), 30, 0).unwrap();
+        registry.register("peer-2", OWNER, device("b", "beta"), 30, 0).unwrap();
+        registry.register("peer-3", OWNER, device("c", "alpha"), 30, 0).unwrap();
+        registry.disconnect("peer-3");
+
+        let names: Vec<_> = registry.list(0).into_iter().map(|device| device.info.name).collect();
+        assert_eq!(names, ["beta", "zeta", "alpha"]);
+    }
+
+    #[test]
+    fn test_rejects_empty_device_id() {
+        let mut registry = PresenceRegistry::new();
+        assert_eq!(
+            registry.register("peer-1", OWNER, device("  ", "office"), 30, 0),
+            Err(RegisterError::MissingDeviceId)
+        );
+        assert!(registry.list(0).is_empty());
+    }
+
+    #[test]
+    fn test_full_registry_evicts_oldest_offline_device() {
+        let mut registry = PresenceRegistry::new();
+        for i in 0..MAX_DEVICES {
+            registry.register(&format!("peer-{}", i), OWNER, device(&format!("d{}", i), "x"), 30, i as u64).unwrap();
+        }
+        assert_eq!(
+            registry.register("peer-new", OWNER, device("new", "x"), 30, 0),
+            Err(RegisterError::Full)
+        );
+
+        registry.disconnect("peer-5");
+        registry.disconnect("peer-3");
+        registry.register("peer-new", OWNER, device("new", "x"), 30, 10).unwrap();
+        let ids: Vec<_> = registry.list(10).into_iter().map(|device| device.info.device_id).collect();
+        assert!(!ids.contains(&"d3".to_string()));
+        assert!(ids.contains(&"d5".to_string()));
+        assert!(ids.contains(&"new".to_string()));
+    }
+
+    #[test]
+    fn test_status_serializes_flat() {
+        let status = DeviceStatus {
+            info: device("a", "office"),
+            online: true,
+            last_seen_ms: 5,
+            online_since_ms: Some(1),
+        };
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["device_id"], "a");
+        assert_eq!(json["online"], true);
+        assert!(json.get("url").is_none());
+        assert_eq!(serde_json::from_value::<DeviceStatus>(json).unwrap(), status);
+    }
+}
@@ -0,0 +1,234 @@
+//! 信令服务器防滥用
+//!
+//! - 每个 IP 的并发 WebSocket 连接数上限
+//! - 每个连接的消息速率限制 (令牌桶，持续超限时断开)
+//! - 同一 IP 在封禁时长内认证失败达到阈值后被封禁 (计数只随时间清零，
+//!   不会因访问无需认证的端点而重置)
+//!
+//! 房间总数上限见 [`super::CapacityConfig::max_rooms`]
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// 防滥用配置
+#[derive(Debug, Clone)]
+pub struct AbuseConfig {
+    /// 每个 IP 的最大并发连接数
+    pub max_connections_per_ip: usize,
+    /// 每个连接每秒允许的消息数
+    pub messages_per_sec: u32,
+    /// 允许的突发消息数 (令牌桶容量)
+    pub message_burst: u32,
+    /// 认证失败多少次后封禁 (0 = 不封禁)
+    pub auth_failures_before_ban: u32,
+    /// 封禁时长 (也是失败计数的统计窗口)
+    pub ban_duration: Duration,
+}
+
+impl Default for AbuseConfig {
+    fn default() -> Self {
+        Self {
+            max_connections_per_ip: 8,
+            messages_per_sec: 50,
+            message_burst: 100,
+            auth_failures_before_ban: 5,
+            ban_duration: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+/// 令牌桶
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    /// 每秒补充的令牌数
+    rate: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: u32, capacity: u32) -> Self {
+        let capacity = capacity.max(1) as f64;
+        Self {
+            capacity,
+            tokens: capacity,
+            rate: rate as f64,
+            last: Instant::now(),
+        }
+    }
+
+    /// 取一个令牌，没有可用令牌时返回 false
+    pub fn try_take(&mut self) -> bool {
+        self.try_take_at(Instant::now())
+    }
+
+    fn try_take_at(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// 拒绝连接的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    /// IP 被封禁，剩余时长
+    Banned { retry_after: Duration },
+    /// IP 的并发连接数已满
+    TooManyConnections,
+}
+
+/// 按 IP 统计连接数和认证失败
+#[derive(Debug, Default)]
+pub struct AbuseGuard {
+    config: AbuseConfig,
+    connections: HashMap<IpAddr, usize>,
+    /// IP -> (失败次数, 首次失败时间)
+    auth_failures: HashMap<IpAddr, (u32, Instant)>,
+    bans: HashMap<IpAddr, Instant>,
+}
+
+impl AbuseGuard {
+    pub fn new(config: AbuseConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn config(&self) -> &AbuseConfig {
+        &self.config
+    }
+
+    /// IP 是否处于封禁中
+    pub fn check_ban(&mut self, ip: IpAddr) -> Result<(), Rejection> {
+        self.check_ban_at(ip, Instant::now())
+    }
+
+    fn check_ban_at(&mut self, ip: IpAddr, now: Instant) -> Result<(), Rejection> {
+        match self.bans.get(&ip) {
+            Some(until) if *until > now => Err(Rejection::Banned { retry_after: *until - now }),
+            Some(_) => {
+                self.bans.remove(&ip);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// 登记新连接 (成功后断开时必须调用 `disconnect`)
+    pub fn connect(&mut self, ip: IpAddr) -> Result<(), Rejection> {
+        self.check_ban(ip)?;
+        let count = self.connections.entry(ip).or_insert(0);
+        if *count >= self.config.max_connections_per_ip {
+            return Err(Rejection::TooManyConnections);
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    pub fn disconnect(&mut self, ip: IpAddr) {
+        if let Some(count) = self.connections.get_mut(&ip) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                self.connections.remove(&ip);
+            }
+        }
+    }
+
+    /// 记录一次认证失败，达到阈值时封禁并返回 true
+    pub fn auth_failed(&mut self, ip: IpAddr) -> bool {
+        self.auth_failed_at(ip, Instant::now())
+    }
+
+    fn auth_failed_at(&mut self, ip: IpAddr, now: Instant) -> bool {
+        if self.config.auth_failures_before_ban == 0 {
+            return false;
+        }
+        let window = self.config.ban_duration;
+        let (failures, since) = self.auth_failures.entry(ip).or_insert((0, now));
+        if now.saturating_duration_since(*since) > window {
+            *failures = 0;
+            *since = now;
+        }
+        *failures += 1;
+        if *failures < self.config.auth_failures_before_ban {
+            return false;
+        }
+        self.auth_failures.remove(&ip);
+        self.bans.insert(ip, now + self.config.ban_duration);
+        true
+    }
+
+    /// 新连接的消息令牌桶
+    pub fn message_bucket(&self) -> TokenBucket {
+        TokenBucket::new(self.config.messages_per_sec, self.config.message_burst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const IP: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10));
+
+    #[test]
+    fn test_token_bucket_refills() {
+        let mut bucket = TokenBucket::new(10, 3);
+        let start = bucket.last;
+        assert!((0..3).all(|_| bucket.try_take_at(start)));
+        assert!(!bucket.try_take_at(start));
+
+        // 100ms 补充 1 个令牌，补充不超过容量
+        assert!(bucket.try_take_at(start + Duration::from_millis(100)));
+        assert!(!bucket.try_take_at(start + Duration::from_millis(100)));
+        let later = start + Duration::from_secs(10);
+        assert_eq!((0..5).filter(|_| bucket.try_take_at(later)).count(), 3);
+    }
+
+    #[test]
+    fn test_connection_limit_per_ip() {
+        let mut guard = AbuseGuard::new(AbuseConfig { max_connections_per_ip: 2, ..Default::default() });
+        let other = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+        assert!(guard.connect(IP).is_ok());
+        assert!(guard.connect(IP).is_ok());
+        assert_eq!(guard.connect(IP), Err(Rejection::TooManyConnections));
+        assert!(guard.connect(other).is_ok());
+
+        guard.disconnect(IP);
+        assert!(guard.connect(IP).is_ok());
+    }
+
+    #[test]
+    fn test_ban_after_repeated_auth_failures() {
+        let mut guard = AbuseGuard::new(AbuseConfig {
+            auth_failures_before_ban: 3,
+            ban_duration: Duration::from_secs(60),
+            ..Default::default()
+        });
+        let now = Instant::now();
+
+        // 超出统计窗口的失败不计入
+        assert!(!guard.auth_failed_at(IP, now));
+        assert!(!guard.auth_failed_at(IP, now));
+        let now = now + Duration::from_secs(61);
+        assert!(!guard.auth_failed_at(IP, now));
+        assert!(!guard.auth_failed_at(IP, now));
+        assert!(guard.check_ban_at(IP, now).is_ok());
+        assert!(guard.auth_failed_at(IP, now));
+
+        assert!(matches!(guard.check_ban_at(IP, now), Err(Rejection::Banned { .. })));
+        assert!(guard.check_ban_at(IP, now + Duration::from_secs(61)).is_ok());
+    }
+}
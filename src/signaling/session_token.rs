@@ -0,0 +1,147 @@
+//! 信令会话令牌
+//!
+//! Viewer 通过验证并完成协商后，Host 的 Answer 携带一个短期会话令牌，
+//! 内容为 (会话 ID, 房间, 角色, 过期时间)，用本进程随机生成的密钥做 HMAC-SHA256 签名。
+//! 断线重连时 `resume` 必须出示该令牌: 仅知道会话 ID 不能接管别人的会话
+
+#![allow(dead_code)]
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 信令对端角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeerRole {
+    /// 被控端 (内嵌信令服务器中只有进程内的 Host)
+    Host,
+    /// 通过 WebSocket 连接的控制端
+    Viewer,
+}
+
+/// 令牌声明
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionClaims {
+    /// 会话 ID
+    pub sid: String,
+    /// 房间 ID
+    pub room: String,
+    /// 角色
+    pub role: PeerRole,
+    /// 过期时间 (Unix 秒)
+    pub exp: u64,
+}
+
+/// 会话令牌签发/校验
+pub struct SessionTokenIssuer {
+    key: [u8; 32],
+    ttl: Duration,
+}
+
+impl SessionTokenIssuer {
+    /// 默认有效期: 覆盖断线恢复的宽限期即可，不需要长期有效
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(10 * 60);
+
+    pub fn new(ttl: Duration) -> Self {
+        let mut key = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut key);
+        Self { key, ttl }
+    }
+
+    /// 签发令牌
+    pub fn issue(&self, session_id: &str, room_id: &str, role: PeerRole) -> String {
+        self.issue_at(session_id, room_id, role, unix_now())
+    }
+
+    fn issue_at(&self, session_id: &str, room_id: &str, role: PeerRole, now: u64) -> String {
+        let claims = SessionClaims {
+            sid: session_id.to_string(),
+            room: room_id.to_string(),
+            role,
+            exp: now + self.ttl.as_secs(),
+        };
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap_or_default());
+        let signature = URL_SAFE_NO_PAD.encode(self.sign(&payload));
+        format!("{}.{}", payload, signature)
+    }
+
+    /// 校验令牌签名和有效期，返回声明
+    pub fn verify(&self, token: &str) -> Option<SessionClaims> {
+        self.verify_at(token, unix_now())
+    }
+
+    fn verify_at(&self, token: &str, now: u64) -> Option<SessionClaims> {
+        let (payload, signature) = token.split_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC 接受任意长度的密钥");
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature).ok()?;
+
+        let claims: SessionClaims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+        (claims.exp > now).then_some(claims)
+    }
+
+    fn sign(&self, payload: &str) -> Vec<u8> {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC 接受任意长度的密钥");
+        mac.update(payload.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+}
+
+impl Default for SessionTokenIssuer {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_TTL)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issue_and_verify() {
+        let issuer = SessionTokenIssuer::new(Duration::from_secs(60));
+        let token = issuer.issue_at("session-1", "room", PeerRole::Viewer, 1_000);
+
+        let claims = issuer.verify_at(&token, 1_030).unwrap();
+        assert_eq!(claims.sid, "session-1");
+        assert_eq!(claims.room, "room");
+        assert_eq!(claims.role, PeerRole::Viewer);
+
+        // 过期
+        assert!(issuer.verify_at(&token, 1_060).is_none());
+    }
+
+    #[test]
+    fn test_rejects_tampered_and_foreign_tokens() {
+        let issuer = SessionTokenIssuer::default();
+        let token = issuer.issue("session-1", "room", PeerRole::Viewer);
+
+        // 改写声明 (冒充 Host) 后签名不再匹配
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = SessionClaims {
+            sid: "session-1".to_string(),
+            room: "room".to_string(),
+            role: PeerRole::Host,
+            exp: u64::MAX,
+        };
+        let forged = format!("{}.{}", URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged).unwrap()), signature);
+        assert!(issuer.verify(&forged).is_none());
+
+        // 其他进程签发的令牌无效
+        assert!(SessionTokenIssuer::default().verify(&token).is_none());
+        assert!(issuer.verify("garbage").is_none());
+    }
+}
@@ -0,0 +1,152 @@
+//! Web 查看器的一次性票据
+//!
+//! 浏览器的 HTTP 请求和 `/video` 连接无法携带信令连接的身份。Web 查看器在 `/ws` 上通过
+//! PIN/TOTP 验证并经被控端审批后，用 `request_ticket` 换取票据，随请求以 `?ticket=` 出示。
+//! 票据绑定签发时的 peer_id 和会话 ID，只能使用一次，过期或信令连接断开后失效
+
+#![allow(dead_code)]
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// 每个 Viewer 最多同时持有的未使用票据 (超出时丢弃最早签发的)
+const MAX_PER_PEER: usize = 16;
+
+/// 票据绑定的身份
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TicketClaims {
+    /// 签发时的信令连接
+    pub peer_id: String,
+    /// 签发时的会话 ID
+    pub session_id: String,
+}
+
+struct IssuedTicket {
+    claims: TicketClaims,
+    issued: Instant,
+}
+
+/// 未使用的票据
+pub struct TicketBook {
+    tickets: HashMap<String, IssuedTicket>,
+    ttl: Duration,
+}
+
+impl TicketBook {
+    /// 默认有效期: 票据签发后立即使用，不需要长期有效
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(30);
+
+    pub fn new(ttl: Duration) -> Self {
+        Self { tickets: HashMap::new(), ttl }
+    }
+
+    /// 为 Viewer 当前的会话签发票据
+    pub fn issue(&mut self, peer_id: &str, session_id: &str) -> String {
+        self.issue_at(peer_id, session_id, Instant::now())
+    }
+
+    fn issue_at(&mut self, peer_id: &str, session_id: &str, now: Instant) -> String {
+        let ttl = self.ttl;
+        self.tickets.retain(|_, ticket| now.saturating_duration_since(ticket.issued) < ttl);
+
+        let mut held: Vec<_> = self
+            .tickets
+            .iter()
+            .filter(|(_, ticket)| ticket.claims.peer_id == peer_id)
+            .map(|(ticket, issued)| (issued.issued, ticket.clone()))
+            .collect();
+        if held.len() >= MAX_PER_PEER {
+            held.sort();
+            for (_, ticket) in &held[..=held.len() - MAX_PER_PEER] {
+                self.tickets.remove(ticket);
+            }
+        }
+
+        let mut bytes = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        let ticket = URL_SAFE_NO_PAD.encode(bytes);
+        let claims = TicketClaims { peer_id: peer_id.to_string(), session_id: session_id.to_string() };
+        self.tickets.insert(ticket.clone(), IssuedTicket { claims, issued: now });
+        ticket
+    }
+
+    /// 使用票据 (无论是否有效都会作废)，返回绑定的身份
+    pub fn redeem(&mut self, ticket: &str) -> Option<TicketClaims> {
+        self.redeem_at(ticket, Instant::now())
+    }
+
+    fn redeem_at(&mut self, ticket: &str, now: Instant) -> Option<TicketClaims> {
+        let issued = self.tickets.remove(ticket)?;
+        (now.saturating_duration_since(issued.issued) < self.ttl).then_some(issued.claims)
+    }
+
+    /// Viewer 断开: 作废其未使用的票据
+    pub fn forget(&mut self, peer_id: &str) {
+        self.tickets.retain(|_, ticket| ticket.claims.peer_id != peer_id);
+    }
+
+    /// 未使用的票据数
+    pub fn len(&self) -> usize {
+        self.tickets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tickets.is_empty()
+    }
+}
+
+impl Default for TicketBook {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_TTL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ticket_is_single_use() {
+        let mut book = TicketBook::default();
+        let ticket = book.issue("viewer_0", "session-1");
+
+        let claims = book.redeem(&ticket).unwrap();
+        assert_eq!(claims, TicketClaims { peer_id: "viewer_0".to_string(), session_id: "session-1".to_string() });
+        assert!(book.redeem(&ticket).is_none());
+        assert!(book.redeem("garbage").is_none());
+        assert_ne!(book.issue("viewer_0", "session-1"), ticket);
+    }
+
+    #[test]
+    fn test_ticket_expires() {
+        let mut book = TicketBook::new(Duration::from_secs(30));
+        let now = Instant::now();
+        let ticket = book.issue_at("viewer_0", "session-1", now);
+        assert!(book.redeem_at(&ticket, now + Duration::from_secs(30)).is_none());
+
+        // 签发新票据时清理过期的
+        book.issue_at("viewer_0", "session-1", now);
+        book.issue_at("viewer_1", "session-2", now + Duration::from_secs(31));
+        assert_eq!(book.len(), 1);
+    }
+
+    #[test]
+    fn test_forget_and_per_peer_limit() {
+        let mut book = TicketBook::default();
+        let now = Instant::now();
+        let first = book.issue_at("viewer_0", "session-1", now);
+        for i in 1..=MAX_PER_PEER as u64 {
+            book.issue_at("viewer_0", "session-1", now + Duration::from_millis(i));
+        }
+        let other = book.issue_at("viewer_1", "session-2", now);
+        assert_eq!(book.len(), MAX_PER_PEER + 1);
+        assert!(book.redeem_at(&first, now).is_none());
+
+        book.forget("viewer_0");
+        assert_eq!(book.len(), 1);
+        assert_eq!(book.redeem(&other).unwrap().peer_id, "viewer_1");
+    }
+}
@@ -0,0 +1,256 @@
+//! 会话空闲超时和最长时长
+//!
+//! 无人值守的被控端上，控制端忘记断开时会话会一直占用编码和带宽:
+//! - 空闲: 会话没有输入，且画面静止 (静态画面检测) 超过 `idle_timeout_minutes`
+//! - 最长时长: 会话建立后超过 `max_session_minutes` (断线重连不重新计时)
+//!
+//! 到期前 `session_warning_secs` 秒通过 `control` 数据通道发送 `session_expiring`，
+//! 之后有输入或画面变化时发送 `session_extended`；到期时发送 `session_ended` 并关闭 WebRTC 会话，
+//! 没有其他会话时视频任务停止编码
+
+// 会话只通过 WebRTC 建立
+#![cfg_attr(not(feature = "webrtc"), allow(dead_code))]
+
+use crate::config::HostConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 会话到期的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryReason {
+    /// 没有输入且画面静止
+    Idle,
+    /// 达到最长会话时长
+    MaxDuration,
+}
+
+impl fmt::Display for ExpiryReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Idle => "空闲超时",
+            Self::MaxDuration => "达到最长会话时长",
+        })
+    }
+}
+
+/// 超时配置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeoutConfig {
+    pub idle_timeout: Option<Duration>,
+    pub max_duration: Option<Duration>,
+    /// 到期前多久通知控制端
+    pub warning: Duration,
+}
+
+impl TimeoutConfig {
+    pub fn from_host(host: &HostConfig) -> Self {
+        let minutes = |value: Option<u64>| value.filter(|m| *m > 0).map(|m| Duration::from_secs(m * 60));
+        Self {
+            idle_timeout: minutes(host.idle_timeout_minutes),
+            max_duration: minutes(host.max_session_minutes),
+            warning: Duration::from_secs(host.session_warning_secs),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.idle_timeout.is_some() || self.max_duration.is_some()
+    }
+}
+
+/// 需要执行的动作 (会话 ID)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeoutAction {
+    /// 即将到期，通知控制端
+    Warn { session: String, reason: ExpiryReason, remaining: Duration },
+    /// 通知过即将空闲超时后又有了活动，会话继续
+    Extend { session: String },
+    /// 已到期，断开会话 (计时已移除)
+    Expire { session: String, reason: ExpiryReason },
+}
+
+#[derive(Debug)]
+struct SessionClock {
+    started: Instant,
+    last_input: Instant,
+    /// 已通知的到期原因
+    warned: Option<ExpiryReason>,
+}
+
+/// 各会话的超时计时
+#[derive(Debug)]
+pub struct SessionTimeouts {
+    config: TimeoutConfig,
+    sessions: HashMap<String, SessionClock>,
+    last_screen_change: Instant,
+}
+
+/// 在信令事件、输入任务、视频任务之间共享
+pub type SharedTimeouts = Arc<Mutex<SessionTimeouts>>;
+
+impl SessionTimeouts {
+    pub fn new(config: TimeoutConfig) -> Self {
+        Self { config, sessions: HashMap::new(), last_screen_change: Instant::now() }
+    }
+
+    pub fn shared(config: TimeoutConfig) -> SharedTimeouts {
+        Arc::new(Mutex::new(Self::new(config)))
+    }
+
+    pub fn config(&self) -> &TimeoutConfig {
+        &self.config
+    }
+
+    /// 会话建立 (重连恢复的会话保留原来的计时)
+    pub fn join(&mut self, session: &str) {
+        self.join_at(session, Instant::now());
+    }
+
+    fn join_at(&mut self, session: &str, now: Instant) {
+        self.sessions
+            .entry(session.to_string())
+            .or_insert(SessionClock { started: now, last_input: now, warned: None });
+    }
+
+    /// 会话结束
+    pub fn leave(&mut self, session: &str) {
+        self.sessions.remove(session);
+    }
+
+    /// 会话的控制端发送了输入
+    pub fn input(&mut self, session: &str) {
+        if let Some(clock) = self.sessions.get_mut(session) {
+            clock.last_input = Instant::now();
+        }
+    }
+
+    /// 画面有变化 (所有会话都看到同一画面)
+    pub fn screen_changed(&mut self) {
+        self.last_screen_change = Instant::now();
+    }
+
+    /// 检查各会话，返回需要执行的动作
+    pub fn poll(&mut self) -> Vec<TimeoutAction> {
+        self.poll_at(Instant::now())
+    }
+
+    fn poll_at(&mut self, now: Instant) -> Vec<TimeoutAction> {
+        let config = self.config;
+        let last_screen_change = self.last_screen_change;
+        let mut actions = Vec::new();
+        for (session, clock) in &mut self.sessions {
+            let idle_since = clock.last_input.max(last_screen_change).max(clock.started);
+            let deadlines = [
+                config.idle_timeout.map(|timeout| (idle_since + timeout, ExpiryReason::Idle)),
+                config.max_duration.map(|limit| (clock.started + limit, ExpiryReason::MaxDuration)),
+            ];
+            let Some((deadline, reason)) = deadlines.into_iter().flatten().min_by_key(|(deadline, _)| *deadline) else {
+                continue;
+            };
+
+            let remaining = deadline.saturating_duration_since(now);
+            if remaining.is_zero() {
+                actions.push(TimeoutAction::Expire { session: session.clone(), reason });
+            } else if remaining <= config.warning {
+                if clock.warned != Some(reason) {
+                    clock.warned = Some(reason);
+                    actions.push(TimeoutAction::Warn { session: session.clone(), reason, remaining });
+                }
+            } else if clock.warned.take().is_some() {
+                actions.push(TimeoutAction::Extend { session: session.clone() });
+            }
+        }
+        for action in &actions {
+            if let TimeoutAction::Expire { session, .. } = action {
+                self.sessions.remove(session);
+            }
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn timeouts(idle: Option<Duration>, max: Option<Duration>, start: Instant) -> SessionTimeouts {
+        let config = TimeoutConfig { idle_timeout: idle, max_duration: max, warning: MINUTE };
+        let mut timeouts = SessionTimeouts::new(config);
+        timeouts.last_screen_change = start;
+        timeouts
+    }
+
+    #[test]
+    fn test_idle_warning_and_expiry() {
+        let start = Instant::now();
+        let mut timeouts = timeouts(Some(MINUTE * 10), None, start);
+        timeouts.join_at("s1", start);
+
+        assert!(timeouts.poll_at(start + MINUTE * 8).is_empty());
+        assert_eq!(
+            timeouts.poll_at(start + MINUTE * 9),
+            vec![TimeoutAction::Warn { session: "s1".to_string(), reason: ExpiryReason::Idle, remaining: MINUTE }]
+        );
+        // 只通知一次
+        assert!(timeouts.poll_at(start + MINUTE * 9 + Duration::from_secs(30)).is_empty());
+
+        // 画面变化后空闲重新计时
+        timeouts.last_screen_change = start + MINUTE * 9 + Duration::from_secs(40);
+        assert_eq!(
+            timeouts.poll_at(start + MINUTE * 9 + Duration::from_secs(45)),
+            vec![TimeoutAction::Extend { session: "s1".to_string() }]
+        );
+
+        let end = start + MINUTE * 19 + Duration::from_secs(40);
+        timeouts.poll_at(end - Duration::from_secs(1));
+        assert_eq!(
+            timeouts.poll_at(end),
+            vec![TimeoutAction::Expire { session: "s1".to_string(), reason: ExpiryReason::Idle }]
+        );
+        assert!(timeouts.poll_at(end + MINUTE).is_empty());
+    }
+
+    #[test]
+    fn test_max_duration_ignores_activity() {
+        let start = Instant::now();
+        let mut timeouts = timeouts(Some(MINUTE * 10), Some(MINUTE * 30), start);
+        timeouts.join_at("s1", start);
+        // 重连恢复保留原来的计时
+        timeouts.join_at("s1", start + MINUTE * 5);
+
+        for minute in 1..29 {
+            timeouts.last_screen_change = start + MINUTE * minute;
+            assert!(timeouts.poll_at(start + MINUTE * minute).is_empty(), "{} 分钟", minute);
+        }
+        timeouts.last_screen_change = start + MINUTE * 29;
+        assert_eq!(
+            timeouts.poll_at(start + MINUTE * 29),
+            vec![TimeoutAction::Warn {
+                session: "s1".to_string(),
+                reason: ExpiryReason::MaxDuration,
+                remaining: MINUTE,
+            }]
+        );
+        assert_eq!(
+            timeouts.poll_at(start + MINUTE * 30),
+            vec![TimeoutAction::Expire { session: "s1".to_string(), reason: ExpiryReason::MaxDuration }]
+        );
+    }
+
+    #[test]
+    fn test_config_from_host() {
+        let mut host = HostConfig::default();
+        assert!(!TimeoutConfig::from_host(&host).is_enabled());
+        host.idle_timeout_minutes = Some(15);
+        host.max_session_minutes = Some(0);
+        let config = TimeoutConfig::from_host(&host);
+        assert_eq!(config.idle_timeout, Some(MINUTE * 15));
+        assert_eq!(config.max_duration, None);
+        assert_eq!(config.warning, MINUTE);
+    }
+}
@@ -0,0 +1,203 @@
+//! WebSocket 视频流
+//!
+//! Web 查看器不建立 WebRTC 连接时，通过 `GET /video` (WebSocket) 直接接收 H.264 编码帧，
+//! 在浏览器中用 WebCodecs 解码 (不支持时封装为 fMP4 交给 MSE)。局域网 ws:// 模式下
+//! 不需要 ICE/DTLS，浏览器也能使用硬件解码
+//!
+//! ## 消息格式
+//! 每个二进制消息是一帧: 16 字节头 + Annex B 码流 (关键帧带 SPS/PPS)
+//!
+//! | 偏移 | 长度 | 内容 |
+//! |------|------|------|
+//! | 0 | 1 | 标志 (bit 0 = 关键帧) |
+//! | 1 | 3 | 保留 |
+//! | 4 | 2 | 宽度 (大端) |
+//! | 6 | 2 | 高度 (大端) |
+//! | 8 | 8 | 时间戳 (微秒，大端) |
+//!
+//! 新观看者加入或观看者跟不上 (广播通道溢出) 时请求关键帧，收到关键帧之前不发送差分帧
+//!
+//! 浏览器发送的文本消息: `key_frame` 请求关键帧，其余为 JSON 格式的 [`crate::input::InputEvent`]
+//! (需要 input 权限，触摸手势由 [`crate::input::TouchTranslator`] 转换)
+//!
+//! Host 发送的文本消息是 JSON 格式的状态通知 (画面中断/恢复，与 `control` 数据通道的消息相同)
+//! 和连接质量报告 ([`crate::quality::report::QualityReport`])
+
+// 编码帧只在启用 h264 时发布
+#![cfg_attr(not(feature = "h264"), allow(dead_code))]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{broadcast, Notify};
+
+/// 消息头长度
+pub const HEADER_LEN: usize = 16;
+/// 关键帧标志
+pub const FLAG_KEY_FRAME: u8 = 0x01;
+/// 广播通道容量 (帧)，观看者落后超过该帧数后从下一个关键帧恢复
+const CHANNEL_CAPACITY: usize = 64;
+/// 状态通知的广播通道容量
+const NOTICE_CAPACITY: usize = 8;
+
+/// 帧消息头
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub key_frame: bool,
+    pub width: u16,
+    pub height: u16,
+    pub timestamp_us: u64,
+}
+
+impl FrameHeader {
+    /// 封装为一条二进制消息
+    pub fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let mut message = Vec::with_capacity(HEADER_LEN + payload.len());
+        message.push(if self.key_frame { FLAG_KEY_FRAME } else { 0 });
+        message.extend_from_slice(&[0; 3]);
+        message.extend_from_slice(&self.width.to_be_bytes());
+        message.extend_from_slice(&self.height.to_be_bytes());
+        message.extend_from_slice(&self.timestamp_us.to_be_bytes());
+        message.extend_from_slice(payload);
+        message
+    }
+
+    /// 解析消息，返回消息头和码流
+    pub fn decode(message: &[u8]) -> Option<(Self, &[u8])> {
+        if message.len() < HEADER_LEN {
+            return None;
+        }
+        let header = Self {
+            key_frame: message[0] & FLAG_KEY_FRAME != 0,
+            width: u16::from_be_bytes([message[4], message[5]]),
+            height: u16::from_be_bytes([message[6], message[7]]),
+            timestamp_us: u64::from_be_bytes(message[8..16].try_into().ok()?),
+        };
+        Some((header, &message[HEADER_LEN..]))
+    }
+}
+
+/// 已封装的一帧 (所有观看者共享)
+#[derive(Debug, Clone)]
+pub struct StreamFrame {
+    pub key_frame: bool,
+    pub message: Arc<Vec<u8>>,
+}
+
+/// 视频流分发: 编码循环发布，每个 `/video` 连接订阅
+pub struct VideoStream {
+    sender: broadcast::Sender<StreamFrame>,
+    /// 状态通知 (JSON 文本)
+    notices: broadcast::Sender<Arc<str>>,
+    key_frame_requested: AtomicBool,
+    /// 有新观看者时通知 (唤醒空闲的编码循环)
+    subscribed: Notify,
+    started: Instant,
+}
+
+impl VideoStream {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            sender,
+            notices: broadcast::channel(NOTICE_CAPACITY).0,
+            key_frame_requested: AtomicBool::new(false),
+            subscribed: Notify::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// 当前观看者数
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// 订阅视频流 (同时请求关键帧)
+    pub fn subscribe(&self) -> broadcast::Receiver<StreamFrame> {
+        let receiver = self.sender.subscribe();
+        self.request_key_frame();
+        self.subscribed.notify_waiters();
+        receiver
+    }
+
+    /// 订阅状态通知
+    pub fn notices(&self) -> broadcast::Receiver<Arc<str>> {
+        self.notices.subscribe()
+    }
+
+    /// 向所有观看者发送状态通知
+    pub fn notify(&self, text: &str) {
+        let _ = self.notices.send(Arc::from(text));
+    }
+
+    /// 等待新的观看者
+    pub async fn subscribed(&self) {
+        self.subscribed.notified().await
+    }
+
+    pub fn request_key_frame(&self) {
+        self.key_frame_requested.store(true, Ordering::Relaxed);
+    }
+
+    /// 取出关键帧请求 (编码循环每帧调用)
+    pub fn take_key_frame_request(&self) -> bool {
+        self.key_frame_requested.swap(false, Ordering::Relaxed)
+    }
+
+    /// 发布一帧 H.264 (Annex B)；没有观看者时直接丢弃
+    pub fn publish(&self, data: &[u8], key_frame: bool, width: u32, height: u32) {
+        if self.subscriber_count() == 0 {
+            return;
+        }
+        let header = FrameHeader {
+            key_frame,
+            width: width.min(u16::MAX as u32) as u16,
+            height: height.min(u16::MAX as u32) as u16,
+            timestamp_us: self.started.elapsed().as_micros() as u64,
+        };
+        let _ = self.sender.send(StreamFrame { key_frame, message: Arc::new(header.encode(data)) });
+    }
+}
+
+impl Default for VideoStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trip() {
+        let header = FrameHeader { key_frame: true, width: 1920, height: 1080, timestamp_us: 123_456_789 };
+        let message = header.encode(&[0, 0, 0, 1, 0x65]);
+        assert_eq!(message.len(), HEADER_LEN + 5);
+
+        let (decoded, payload) = FrameHeader::decode(&message).unwrap();
+        assert_eq!(decoded, header);
+        assert_eq!(payload, &[0, 0, 0, 1, 0x65]);
+        assert!(FrameHeader::decode(&message[..HEADER_LEN - 1]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_requests_key_frame() {
+        let stream = VideoStream::new();
+        assert!(!stream.take_key_frame_request());
+        // 没有观看者时不封装
+        stream.publish(&[1, 2, 3], false, 640, 360);
+
+        let mut frames = stream.subscribe();
+        assert_eq!(stream.subscriber_count(), 1);
+        assert!(stream.take_key_frame_request());
+        assert!(!stream.take_key_frame_request());
+
+        stream.publish(&[1, 2, 3], true, 640, 360);
+        let frame = frames.recv().await.unwrap();
+        assert!(frame.key_frame);
+        let (header, payload) = FrameHeader::decode(&frame.message).unwrap();
+        assert_eq!((header.width, header.height), (640, 360));
+        assert_eq!(payload, &[1, 2, 3]);
+    }
+}
@@ -0,0 +1,163 @@
+//! 电池状态
+//!
+//! - Linux: `/sys/class/power_supply/BAT*`
+//! - macOS: `pmset -g batt`
+//! - Windows: `GetSystemPowerStatus`
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 充放电状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatteryState {
+    Charging,
+    Discharging,
+    /// 已充满 (接通电源)
+    Full,
+    Unknown,
+}
+
+/// 电池状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatteryStatus {
+    /// 剩余电量 (百分比)
+    pub percent: f32,
+    pub state: BatteryState,
+    /// 预计剩余使用时间 (秒，放电时)
+    pub time_remaining_secs: Option<u64>,
+}
+
+impl fmt::Display for BatteryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.0}%", self.percent)?;
+        f.write_str(match self.state {
+            BatteryState::Charging => ", 充电中",
+            BatteryState::Discharging => ", 使用电池",
+            BatteryState::Full => ", 已充满",
+            BatteryState::Unknown => "",
+        })?;
+        if let Some(secs) = self.time_remaining_secs {
+            write!(f, ", 剩余 {}:{:02}", secs / 3600, secs % 3600 / 60)?;
+        }
+        Ok(())
+    }
+}
+
+/// 当前电池状态 (没有电池或无法获取时为 None)
+#[cfg(target_os = "linux")]
+pub fn status() -> Option<BatteryStatus> {
+    let entries = std::fs::read_dir("/sys/class/power_supply").ok()?;
+    entries.filter_map(|entry| entry.ok()).find_map(|entry| {
+        let path = entry.path();
+        let read = |name: &str| std::fs::read_to_string(path.join(name)).ok().map(|value| value.trim().to_string());
+        if read("type").as_deref() != Some("Battery") {
+            return None;
+        }
+        let percent: f32 = read("capacity")?.parse().ok()?;
+        let state = match read("status").as_deref() {
+            Some("Charging") => BatteryState::Charging,
+            Some("Discharging") => BatteryState::Discharging,
+            Some("Full") => BatteryState::Full,
+            _ => BatteryState::Unknown,
+        };
+        Some(BatteryStatus { percent, state, time_remaining_secs: None })
+    })
+}
+
+/// 当前电池状态 (没有电池或无法获取时为 None)
+#[cfg(target_os = "macos")]
+pub fn status() -> Option<BatteryStatus> {
+    let output = std::process::Command::new("pmset").args(["-g", "batt"]).output().ok()?;
+    parse_pmset(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// 当前电池状态 (没有电池或无法获取时为 None)
+#[cfg(target_os = "windows")]
+pub fn status() -> Option<BatteryStatus> {
+    use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    // BatteryFlag: 8 = 充电中，128 = 没有电池，255 = 未知
+    const FLAG_CHARGING: u8 = 8;
+    const FLAG_NO_BATTERY: u8 = 128;
+    const UNKNOWN: u8 = 255;
+    const UNKNOWN_TIME: u32 = u32::MAX;
+
+    let mut power = SYSTEM_POWER_STATUS::default();
+    unsafe { GetSystemPowerStatus(&mut power) }.ok()?;
+    let no_battery = power.BatteryFlag == UNKNOWN || power.BatteryFlag & FLAG_NO_BATTERY != 0;
+    if no_battery || power.BatteryLifePercent == UNKNOWN {
+        return None;
+    }
+
+    let state = match (power.ACLineStatus, power.BatteryFlag & FLAG_CHARGING != 0) {
+        (_, true) => BatteryState::Charging,
+        (0, _) => BatteryState::Discharging,
+        (1, _) if power.BatteryLifePercent >= 100 => BatteryState::Full,
+        _ => BatteryState::Unknown,
+    };
+    Some(BatteryStatus {
+        percent: f32::from(power.BatteryLifePercent),
+        state,
+        time_remaining_secs: (power.BatteryLifeTime != UNKNOWN_TIME).then_some(u64::from(power.BatteryLifeTime)),
+    })
+}
+
+/// 当前电池状态 (没有电池或无法获取时为 None)
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn status() -> Option<BatteryStatus> {
+    None
+}
+
+/// 解析 `pmset -g batt` 的输出
+///
+/// ```text
+/// Now drawing from 'Battery Power'
+///  -InternalBattery-0 (id=4653155)    84%; discharging; 5:12 remaining present: true
+/// ```
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_pmset(output: &str) -> Option<BatteryStatus> {
+    let line = output.lines().find(|line| line.contains("InternalBattery"))?;
+    let (_, fields) = line.split_once('\t')?;
+    let mut fields = fields.split(';').map(str::trim);
+
+    let percent: f32 = fields.next()?.trim_end_matches('%').parse().ok()?;
+    let state = match fields.next()? {
+        "charging" => BatteryState::Charging,
+        "discharging" => BatteryState::Discharging,
+        "charged" => BatteryState::Full,
+        _ => BatteryState::Unknown,
+    };
+    // "5:12 remaining"，刚拔下电源时为 "(no estimate)"
+    let time_remaining_secs = fields.next().and_then(|remaining| {
+        let (hours, minutes) = remaining.split_whitespace().next()?.split_once(':')?;
+        Some(hours.parse::<u64>().ok()? * 3600 + minutes.parse::<u64>().ok()? * 60)
+    });
+    Some(BatteryStatus {
+        percent,
+        state,
+        time_remaining_secs: time_remaining_secs.filter(|_| state == BatteryState::Discharging),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pmset() {
+        let output = "Now drawing from 'Battery Power'\n -InternalBattery-0 (id=4653155)\t84%; discharging; 5:12 remaining present: true\n";
+        let status = parse_pmset(output).unwrap();
+        assert_eq!(status.percent, 84.0);
+        assert_eq!(status.state, BatteryState::Discharging);
+        assert_eq!(status.time_remaining_secs, Some(5 * 3600 + 12 * 60));
+
+        let output = "Now drawing from 'AC Power'\n -InternalBattery-0 (id=4653155)\t100%; charged; 0:00 remaining present: true\n";
+        let status = parse_pmset(output).unwrap();
+        assert_eq!(status.state, BatteryState::Full);
+        assert_eq!(status.time_remaining_secs, None);
+
+        // 台式机没有电池
+        assert!(parse_pmset("Now drawing from 'AC Power'\n").is_none());
+    }
+}
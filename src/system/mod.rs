@@ -0,0 +1,334 @@
+//! 系统信息报告
+//!
+//! 被控端按需采集操作系统版本、运行时间、CPU/内存负载、占用最高的进程、磁盘用量和电池状态:
+//! - Viewer 通过 `sysinfo` 数据通道发送 [`SysInfoRequest`]，被控端回复 [`SystemReport`] (JSON)
+//! - Web 查看器和 `sscontrol info --ip <host>` 在 `/ws` 上通过验证后，带一次性票据请求信令服务器的 `/sysinfo?top=N`
+//!
+//! CPU 占用需要间隔采样两次，[`SystemReport::collect`] 会阻塞约 200 毫秒，异步代码中应放到
+//! `spawn_blocking` 中执行
+
+#![allow(dead_code)]
+
+pub mod battery;
+
+pub use battery::BatteryStatus;
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+use sysinfo::{Disks, System};
+
+/// 系统信息使用的数据通道标签
+pub const SYSINFO_CHANNEL: &str = "sysinfo";
+
+/// 默认报告的进程数
+pub const DEFAULT_TOP: usize = 10;
+
+/// 最多报告的进程数
+pub const MAX_TOP: usize = 50;
+
+/// 两次 CPU 采样的间隔
+const CPU_SAMPLE_INTERVAL: Duration = Duration::from_millis(200);
+
+/// `sysinfo` 数据通道上的请求
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SysInfoRequest {
+    /// 报告 CPU 占用最高的进程数 (默认 [`DEFAULT_TOP`]，最多 [`MAX_TOP`])
+    #[serde(default)]
+    pub top: Option<usize>,
+}
+
+impl SysInfoRequest {
+    /// 实际报告的进程数
+    pub fn top(&self) -> usize {
+        self.top.unwrap_or(DEFAULT_TOP).min(MAX_TOP)
+    }
+}
+
+/// 系统信息报告
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SystemReport {
+    /// 主机名
+    pub hostname: Option<String>,
+    /// 操作系统名称和版本 (如 "macOS 14.4 Sonoma")
+    pub os: String,
+    /// 内核版本
+    pub kernel: Option<String>,
+    /// CPU 架构
+    pub arch: String,
+    /// 开机以来的时间 (秒)
+    pub uptime_secs: u64,
+    pub cpu: CpuLoad,
+    pub memory: MemoryUsage,
+    /// 进程总数
+    pub process_count: usize,
+    /// CPU 占用最高的进程 (按 CPU 占用降序)
+    pub processes: Vec<ProcessUsage>,
+    pub disks: Vec<DiskUsage>,
+    /// 电池状态 (没有电池时为 None)
+    pub battery: Option<BatteryStatus>,
+    /// 采集时间 (Unix 毫秒)
+    pub time_ms: i64,
+}
+
+/// CPU 负载
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CpuLoad {
+    /// 整体占用 (百分比)
+    pub usage: f32,
+    /// 逻辑核心数
+    pub cores: usize,
+    /// 1/5/15 分钟平均负载 (Windows 不提供)
+    pub load_average: Option<[f64; 3]>,
+}
+
+/// 内存用量 (字节)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryUsage {
+    pub total: u64,
+    pub used: u64,
+    pub swap_total: u64,
+    pub swap_used: u64,
+}
+
+/// 单个进程的资源占用
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessUsage {
+    pub pid: u32,
+    pub name: String,
+    /// CPU 占用 (占全部核心的百分比，与任务管理器一致)
+    pub cpu: f32,
+    /// 常驻内存 (字节)
+    pub memory: u64,
+}
+
+/// 磁盘用量
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    pub mount_point: String,
+    pub file_system: String,
+    /// 总容量 (字节)
+    pub total: u64,
+    /// 可用空间 (字节)
+    pub available: u64,
+}
+
+impl SystemReport {
+    /// 采集系统信息，报告 CPU 占用最高的 `top` 个进程 (阻塞约 200 毫秒)
+    pub fn collect(top: usize) -> Self {
+        let mut system = System::new();
+        system.refresh_cpu();
+        system.refresh_processes();
+        std::thread::sleep(CPU_SAMPLE_INTERVAL.max(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL));
+        system.refresh_cpu();
+        system.refresh_processes();
+        system.refresh_memory();
+
+        let cores = system.cpus().len().max(1);
+        let mut processes: Vec<ProcessUsage> = system
+            .processes()
+            .values()
+            .map(|process| ProcessUsage {
+                pid: process.pid().as_u32(),
+                name: process.name().to_string(),
+                cpu: process.cpu_usage() / cores as f32,
+                memory: process.memory(),
+            })
+            .collect();
+        let process_count = processes.len();
+        top_processes(&mut processes, top);
+
+        let load = System::load_average();
+        let load_average = (load.one > 0.0 || load.five > 0.0 || load.fifteen > 0.0)
+            .then_some([load.one, load.five, load.fifteen]);
+
+        Self {
+            hostname: System::host_name(),
+            os: System::long_os_version()
+                .or_else(System::name)
+                .unwrap_or_else(|| std::env::consts::OS.to_string()),
+            kernel: System::kernel_version(),
+            arch: std::env::consts::ARCH.to_string(),
+            uptime_secs: System::uptime(),
+            cpu: CpuLoad {
+                usage: system.global_cpu_info().cpu_usage(),
+                cores,
+                load_average,
+            },
+            memory: MemoryUsage {
+                total: system.total_memory(),
+                used: system.used_memory(),
+                swap_total: system.total_swap(),
+                swap_used: system.used_swap(),
+            },
+            process_count,
+            processes,
+            disks: disks(),
+            battery: battery::status(),
+            time_ms: chrono::Local::now().timestamp_millis(),
+        }
+    }
+}
+
+/// 按 CPU 占用 (相同时按内存) 降序保留前 `top` 个
+fn top_processes(processes: &mut Vec<ProcessUsage>, top: usize) {
+    processes.sort_by(|a, b| b.cpu.total_cmp(&a.cpu).then(b.memory.cmp(&a.memory)));
+    processes.truncate(top);
+}
+
+/// 已挂载的磁盘 (同一设备挂载多次时只保留第一个挂载点)
+fn disks() -> Vec<DiskUsage> {
+    let disks = Disks::new_with_refreshed_list();
+    let mut seen = Vec::new();
+    let mut usage = Vec::new();
+    for disk in disks.list() {
+        if disk.total_space() == 0 || seen.contains(&disk.name()) {
+            continue;
+        }
+        seen.push(disk.name());
+        usage.push(DiskUsage {
+            mount_point: disk.mount_point().display().to_string(),
+            file_system: disk.file_system().to_string_lossy().into_owned(),
+            total: disk.total_space(),
+            available: disk.available_space(),
+        });
+    }
+    usage
+}
+
+/// 字节数的显示形式 (如 "1.5 GB")
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// 时长的显示形式 (如 "3 天 4 小时 5 分钟")
+pub fn format_uptime(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    match (days, hours) {
+        (0, 0) => format!("{} 分钟", minutes),
+        (0, _) => format!("{} 小时 {} 分钟", hours, minutes),
+        _ => format!("{} 天 {} 小时 {} 分钟", days, hours, minutes),
+    }
+}
+
+fn percent(used: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        used as f64 * 100.0 / total as f64
+    }
+}
+
+impl fmt::Display for SystemReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "系统:")?;
+        if let Some(hostname) = &self.hostname {
+            writeln!(f, "  主机名: {}", hostname)?;
+        }
+        writeln!(f, "  操作系统: {} ({})", self.os, self.arch)?;
+        if let Some(kernel) = &self.kernel {
+            writeln!(f, "  内核: {}", kernel)?;
+        }
+        writeln!(f, "  运行时间: {}", format_uptime(self.uptime_secs))?;
+        writeln!(f)?;
+
+        writeln!(f, "负载:")?;
+        write!(f, "  CPU: {:.1}% ({} 核)", self.cpu.usage, self.cpu.cores)?;
+        if let Some([one, five, fifteen]) = self.cpu.load_average {
+            write!(f, ", 平均负载 {:.2} {:.2} {:.2}", one, five, fifteen)?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "  内存: {} / {} ({:.0}%)",
+            format_bytes(self.memory.used),
+            format_bytes(self.memory.total),
+            percent(self.memory.used, self.memory.total)
+        )?;
+        if self.memory.swap_total > 0 {
+            writeln!(f, "  交换: {} / {}", format_bytes(self.memory.swap_used), format_bytes(self.memory.swap_total))?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "进程 (共 {} 个，按 CPU 排序):", self.process_count)?;
+        writeln!(f, "  {:>7}  {:>6}  {:>9}  名称", "PID", "CPU", "内存")?;
+        for process in &self.processes {
+            writeln!(
+                f,
+                "  {:>7}  {:>5.1}%  {:>9}  {}",
+                process.pid,
+                process.cpu,
+                format_bytes(process.memory),
+                process.name
+            )?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "磁盘:")?;
+        for disk in &self.disks {
+            let used = disk.total.saturating_sub(disk.available);
+            writeln!(
+                f,
+                "  {} ({}): 已用 {} / {} ({:.0}%)",
+                disk.mount_point,
+                disk.file_system,
+                format_bytes(used),
+                format_bytes(disk.total),
+                percent(used, disk.total)
+            )?;
+        }
+
+        if let Some(battery) = &self.battery {
+            writeln!(f)?;
+            writeln!(f, "电池:")?;
+            writeln!(f, "  {}", battery)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, cpu: f32, memory: u64) -> ProcessUsage {
+        ProcessUsage { pid, name: format!("p{}", pid), cpu, memory }
+    }
+
+    #[test]
+    fn test_top_processes() {
+        let mut processes = vec![process(1, 0.5, 100), process(2, 12.0, 10), process(3, 0.5, 300), process(4, 3.0, 0)];
+        top_processes(&mut processes, 3);
+        let pids: Vec<u32> = processes.iter().map(|p| p.pid).collect();
+        assert_eq!(pids, vec![2, 4, 3]);
+    }
+
+    #[test]
+    fn test_request_top_is_capped() {
+        assert_eq!(SysInfoRequest::default().top(), DEFAULT_TOP);
+        assert_eq!(SysInfoRequest { top: Some(500) }.top(), MAX_TOP);
+        let request: SysInfoRequest = serde_json::from_str("{}").unwrap();
+        assert_eq!(request.top, None);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(8 * 1024 * 1024 * 1024), "8.0 GB");
+        assert_eq!(format_uptime(59), "0 分钟");
+        assert_eq!(format_uptime(3 * 3600 + 120), "3 小时 2 分钟");
+        assert_eq!(format_uptime(2 * 86400 + 3600), "2 天 1 小时 0 分钟");
+    }
+}
@@ -0,0 +1,422 @@
+//! 进程内回环测试 (Host ↔ 控制端)
+//!
+//! [`LoopbackHost`] 在本进程内启动内嵌信令服务器 (随机端口)，为每个 Viewer 创建
+//! [`HostSession`]，用合成画面 ([`SyntheticCapturer`]) 编码后的视频驱动；
+//! Viewer 的输入事件不注入系统，而是交给测试检查。控制端使用 [`ControlSession`]。
+//!
+//! 典型用法: `LoopbackHost::start` → `connect` → `expect_frames` 检查收到可解码、
+//! 序号递增的帧 → `round_trip_input` 检查输入事件原样到达 Host → `stop`
+
+use crate::capture::{Capturer, Frame, SyntheticCapturer, SyntheticConfig};
+use crate::encoder::vp9::VP9Encoder;
+use crate::encoder::{Encoder, H264Encoder, VP8Encoder};
+use crate::input::InputEvent;
+use crate::signaling::{EmbeddedSignalingServer, HostSignalEvent, SessionPermissions};
+use crate::webrtc::control_session::{ControlOptions, ControlSession};
+use crate::webrtc::host_session::{HostSession, IceCandidate, IceConfig, VideoCodec};
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+
+/// 编码线程到发送任务之间最多排队的帧
+const SAMPLE_QUEUE: usize = 4;
+
+type Sessions = Arc<Mutex<HashMap<String, Arc<HostSession>>>>;
+
+/// 回环测试选项
+#[derive(Debug, Clone)]
+pub struct LoopbackOptions {
+    /// 视频 codec
+    pub codec: VideoCodec,
+    /// 合成画面 (默认 320x240，编解码足够快)
+    pub capture: SyntheticConfig,
+    /// 帧率
+    pub fps: u32,
+    /// 码率 (kbps)
+    pub bitrate: u32,
+    /// Viewer 的会话权限
+    pub permissions: SessionPermissions,
+    /// 连接、等待帧和输入事件的超时
+    pub timeout: Duration,
+}
+
+impl Default for LoopbackOptions {
+    fn default() -> Self {
+        Self {
+            codec: VideoCodec::VP8,
+            capture: SyntheticConfig { width: 320, height: 240, ..Default::default() },
+            fps: 30,
+            bitrate: 1000,
+            permissions: SessionPermissions::full(),
+            timeout: Duration::from_secs(20),
+        }
+    }
+}
+
+/// 进程内的 Host
+pub struct LoopbackHost {
+    server: Arc<EmbeddedSignalingServer>,
+    port: u16,
+    options: LoopbackOptions,
+    sessions: Sessions,
+    /// Viewer 发来的输入事件 (已通过权限检查)
+    inputs: Mutex<mpsc::UnboundedReceiver<InputEvent>>,
+    stop: Arc<AtomicBool>,
+    tasks: Vec<JoinHandle<()>>,
+    encoder_thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl LoopbackHost {
+    /// 启动信令服务器、编码线程和会话处理任务
+    pub async fn start(options: LoopbackOptions) -> Result<Self> {
+        let mut server = EmbeddedSignalingServer::new(0);
+        server.set_default_permissions(options.permissions);
+        let port = server.start().await?;
+        let events = server.take_host_events().ok_or_else(|| anyhow!("信令事件通道已被取走"))?;
+        let server = Arc::new(server);
+
+        let sessions: Sessions = Arc::default();
+        let (input_tx, inputs) = mpsc::unbounded_channel();
+        // 新会话需要从关键帧开始
+        let key_frame = Arc::new(AtomicBool::new(true));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let (sample_tx, sample_rx) = mpsc::channel(SAMPLE_QUEUE);
+        let encoder_thread = spawn_encoder(&options, sample_tx, key_frame.clone(), stop.clone())?;
+        let frame_duration = Duration::from_secs_f64(1.0 / options.fps.max(1) as f64);
+        let tasks = vec![
+            tokio::spawn(send_samples(sample_rx, sessions.clone(), frame_duration)),
+            tokio::spawn(handle_events(events, server.clone(), sessions.clone(), input_tx, key_frame, options.codec)),
+        ];
+
+        tracing::info!("回环测试 Host 已启动: 端口 {}", port);
+        Ok(Self {
+            server,
+            port,
+            options,
+            sessions,
+            inputs: Mutex::new(inputs),
+            stop,
+            tasks,
+            encoder_thread: Some(encoder_thread),
+        })
+    }
+
+    /// 信令地址
+    pub fn url(&self) -> String {
+        format!("ws://127.0.0.1:{}/ws", self.port)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// 本机回环使用的控制端选项 (不打洞，只使用本机地址候选)
+    pub fn control_options(&self) -> ControlOptions {
+        ControlOptions {
+            timeout: self.options.timeout,
+            stun_servers: Vec::new(),
+            hole_punching: false,
+            ..Default::default()
+        }
+    }
+
+    /// 连接一个无界面控制端
+    pub async fn connect(&self) -> Result<ControlSession> {
+        ControlSession::connect(&self.url(), self.control_options()).await
+    }
+
+    /// 已建立的 WebRTC 会话数
+    pub async fn session_count(&self) -> usize {
+        self.sessions.lock().await.len()
+    }
+
+    /// 等待控制端解码出 `count` 帧，检查分辨率和帧序号递增
+    ///
+    /// 第一帧成功解码之前的解码错误被忽略 (ICE 连通前发出的关键帧会丢失)
+    pub async fn expect_frames(&self, viewer: &ControlSession, count: usize) -> Result<Vec<Frame>> {
+        let deadline = Instant::now() + self.options.timeout;
+        let mut frames: Vec<Frame> = Vec::with_capacity(count);
+        let mut last_index = None;
+        while frames.len() < count {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let frame = match tokio::time::timeout(remaining, viewer.next_frame()).await {
+                Err(_) => bail!("{} 秒内只收到 {}/{} 帧", self.options.timeout.as_secs(), frames.len(), count),
+                Ok(None) => bail!("连接已关闭，只收到 {}/{} 帧", frames.len(), count),
+                Ok(Some(Err(e))) if frames.is_empty() => {
+                    tracing::debug!("等待关键帧: {}", e);
+                    continue;
+                }
+                Ok(Some(Err(e))) => return Err(e.context("解码失败")),
+                Ok(Some(Ok(frame))) => frame,
+            };
+
+            let expected = (self.options.capture.width, self.options.capture.height);
+            if (frame.width, frame.height) != expected {
+                bail!("分辨率不符: {}x{}，应为 {}x{}", frame.width, frame.height, expected.0, expected.1);
+            }
+            let index = SyntheticCapturer::decode_frame_index(&frame).ok_or_else(|| anyhow!("无法读出帧序号"))?;
+            if last_index.is_some_and(|last| index <= last) {
+                bail!("帧序号没有递增: {} 之后收到 {}", last_index.unwrap_or_default(), index);
+            }
+            last_index = Some(index);
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+
+    /// 下一个到达 Host 的输入事件 (超时返回 None)
+    pub async fn next_input(&self) -> Option<InputEvent> {
+        let mut inputs = self.inputs.lock().await;
+        tokio::time::timeout(self.options.timeout, inputs.recv()).await.ok().flatten()
+    }
+
+    /// 控制端发送输入事件，检查 Host 原样收到
+    pub async fn round_trip_input(&self, viewer: &ControlSession, event: &InputEvent) -> Result<()> {
+        viewer.send_event(event).await?;
+        let received = self.next_input().await.ok_or_else(|| anyhow!("Host 未收到输入事件: {:?}", event))?;
+        if serde_json::to_value(&received)? != serde_json::to_value(event)? {
+            bail!("Host 收到的输入事件不符: {:?}，应为 {:?}", received, event);
+        }
+        Ok(())
+    }
+
+    /// 关闭所有会话并停止信令服务器和编码线程
+    pub async fn stop(mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.server.stop();
+        for task in self.tasks.drain(..) {
+            task.abort();
+        }
+        let sessions: Vec<_> = self.sessions.lock().await.drain().map(|(_, session)| session).collect();
+        for session in sessions {
+            let _ = session.close().await;
+        }
+        // 发送任务结束后编码线程的 blocking_send 失败，线程随即退出
+        if let Some(thread) = self.encoder_thread.take() {
+            let _ = tokio::task::spawn_blocking(move || thread.join()).await;
+        }
+    }
+}
+
+impl Drop for LoopbackHost {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.server.stop();
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// 回环测试使用的编码器
+enum LoopbackEncoder {
+    Vp8(VP8Encoder),
+    Vp9(VP9Encoder),
+    H264(H264Encoder),
+}
+
+impl LoopbackEncoder {
+    fn new(codec: VideoCodec, width: u32, height: u32, fps: u32, bitrate: u32) -> Result<Self> {
+        Ok(match codec {
+            VideoCodec::VP8 => Self::Vp8(VP8Encoder::new(width, height, fps, bitrate)?),
+            // 回环只有一个观看者，不分层
+            VideoCodec::VP9 => Self::Vp9(VP9Encoder::new(width, height, fps, bitrate, 1)?),
+            VideoCodec::H264 => Self::H264(H264Encoder::new(width, height, fps, bitrate)?),
+        })
+    }
+
+    fn encode(&mut self, frame: &Frame) -> Result<Option<Vec<u8>>> {
+        match self {
+            Self::Vp8(encoder) => encoder.encode_frame(frame),
+            Self::Vp9(encoder) => Ok(encoder.encode_frame(frame)?.map(|frame| frame.data)),
+            Self::H264(encoder) => Ok(Encoder::encode(encoder, frame)?.map(|packet| packet.data)),
+        }
+    }
+
+    fn request_key_frame(&mut self) {
+        match self {
+            Self::Vp8(encoder) => encoder.request_key_frame(),
+            Self::Vp9(encoder) => encoder.request_key_frame(),
+            Self::H264(encoder) => {
+                let _ = Encoder::request_key_frame(encoder);
+            }
+        }
+    }
+}
+
+/// 按帧率采集合成画面并编码，编码数据交给发送任务
+fn spawn_encoder(
+    options: &LoopbackOptions,
+    samples: mpsc::Sender<Vec<u8>>,
+    key_frame: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+) -> Result<std::thread::JoinHandle<()>> {
+    let mut capturer = SyntheticCapturer::new(options.capture.clone())?;
+    let (width, height) = (capturer.width(), capturer.height());
+    let mut encoder = LoopbackEncoder::new(options.codec, width, height, options.fps, options.bitrate)?;
+    let interval = Duration::from_secs_f64(1.0 / options.fps.max(1) as f64);
+
+    let thread = std::thread::Builder::new().name("loopback-encoder".to_string()).spawn(move || {
+        let mut next = Instant::now();
+        while !stop.load(Ordering::Relaxed) {
+            if key_frame.swap(false, Ordering::Relaxed) {
+                encoder.request_key_frame();
+            }
+            match capturer.capture().and_then(|frame| encoder.encode(&frame)) {
+                Ok(Some(data)) => {
+                    if samples.blocking_send(data).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("回环测试编码失败: {}", e),
+            }
+            next += interval;
+            match next.checked_duration_since(Instant::now()) {
+                Some(wait) => std::thread::sleep(wait),
+                None => next = Instant::now(),
+            }
+        }
+    })?;
+    Ok(thread)
+}
+
+/// 把编码数据发给所有会话
+async fn send_samples(mut samples: mpsc::Receiver<Vec<u8>>, sessions: Sessions, frame_duration: Duration) {
+    while let Some(data) = samples.recv().await {
+        let sessions: Vec<_> = sessions.lock().await.values().cloned().collect();
+        for session in sessions {
+            if let Err(e) = session.send_video_sample(data.clone(), frame_duration).await {
+                tracing::debug!("[{}] 发送视频失败: {}", session.session_id(), e);
+            }
+        }
+    }
+}
+
+/// 处理信令事件: 建立会话、交换 ICE 候选，输入事件转交测试
+async fn handle_events(
+    mut events: mpsc::UnboundedReceiver<HostSignalEvent>,
+    server: Arc<EmbeddedSignalingServer>,
+    sessions: Sessions,
+    input_tx: mpsc::UnboundedSender<InputEvent>,
+    key_frame: Arc<AtomicBool>,
+    codec: VideoCodec,
+) {
+    while let Some(event) = events.recv().await {
+        match event {
+            HostSignalEvent::Offer { from, sdp, session_id } => {
+                let existing = sessions.lock().await.get(&from).cloned();
+                if let Some(session) = existing {
+                    match session.handle_offer(&sdp).await {
+                        Ok(answer) => server.send_answer(&from, &answer, &session_id).await,
+                        Err(e) => tracing::warn!("回环测试重协商失败: {}", e),
+                    }
+                    continue;
+                }
+                let permissions = server.permissions(&from).await;
+                let session =
+                    match HostSession::new(from.clone(), session_id.clone(), codec, permissions, &IceConfig::default(), None, None).await {
+                        Ok(session) => Arc::new(session),
+                        Err(e) => {
+                            tracing::warn!("回环测试创建会话失败: {}", e);
+                            server.send_error(&from, &e.to_string()).await;
+                            continue;
+                        }
+                    };
+                if let Some(mut inputs) = session.take_input_events() {
+                    let input_tx = input_tx.clone();
+                    tokio::spawn(async move {
+                        while let Some(event) = inputs.recv().await {
+                            let _ = input_tx.send(event);
+                        }
+                    });
+                }
+                let answer = match session.handle_offer(&sdp).await {
+                    Ok(answer) => answer,
+                    Err(e) => {
+                        tracing::warn!("回环测试处理 Offer 失败: {}", e);
+                        continue;
+                    }
+                };
+                server.send_answer(&from, &answer, &session_id).await;
+
+                let server_for_ice = server.clone();
+                let session_for_ice = session.clone();
+                tokio::spawn(async move {
+                    while let Some(ice) = session_for_ice.next_ice_candidate().await {
+                        server_for_ice
+                            .send_ice(&session_for_ice.peer_id(), &ice.candidate, &ice.sdp_mid, ice.sdp_mline_index)
+                            .await;
+                    }
+                });
+                sessions.lock().await.insert(from, session);
+                key_frame.store(true, Ordering::Relaxed);
+            }
+            HostSignalEvent::Ice { from, candidate, sdp_mid, sdp_mline_index } => {
+                let session = sessions.lock().await.get(&from).cloned();
+                if let Some(session) = session {
+                    let ice = IceCandidate { candidate, sdp_mid, sdp_mline_index };
+                    if let Err(e) = session.add_ice_candidate(&ice).await {
+                        tracing::debug!("回环测试添加 ICE 候选失败: {}", e);
+                    }
+                }
+            }
+            // 本机回环不打洞
+            HostSignalEvent::Punch { from, .. } => server.send_punch(&from, None).await,
+            HostSignalEvent::ViewerLeft { peer_id } => {
+                let session = sessions.lock().await.remove(&peer_id);
+                if let Some(session) = session {
+                    let _ = session.close().await;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn loopback(codec: VideoCodec) {
+        let host = LoopbackHost::start(LoopbackOptions { codec, ..Default::default() }).await.unwrap();
+        let viewer = host.connect().await.unwrap();
+        assert_eq!(host.session_count().await, 1);
+
+        let frames = host.expect_frames(&viewer, 5).await.unwrap();
+        assert_eq!(frames.len(), 5);
+
+        host.round_trip_input(&viewer, &InputEvent::mouse_move(0.25, 0.75)).await.unwrap();
+        host.round_trip_input(&viewer, &InputEvent::KeyEvent { key: "a".to_string(), pressed: true })
+            .await
+            .unwrap();
+
+        viewer.close().await.unwrap();
+        host.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_loopback_vp8() {
+        loopback(VideoCodec::VP8).await;
+    }
+
+    #[tokio::test]
+    async fn test_loopback_h264() {
+        loopback(VideoCodec::H264).await;
+    }
+
+    #[tokio::test]
+    async fn test_view_only_input_rejected() {
+        let options = LoopbackOptions { permissions: SessionPermissions::view_only(), ..Default::default() };
+        let host = LoopbackHost::start(options).await.unwrap();
+        let viewer = host.connect().await.unwrap();
+        assert!(viewer.send_event(&InputEvent::mouse_move(0.5, 0.5)).await.is_err());
+        viewer.close().await.unwrap();
+        host.stop().await;
+    }
+}
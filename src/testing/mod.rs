@@ -0,0 +1,10 @@
+//! 测试工具
+//!
+//! 不依赖浏览器、显示器和系统输入的端到端测试支持 (需要 webrtc 和 h264 feature):
+//! - `loopback`: 同一进程内的 Host ↔ 控制端回环
+
+#![allow(dead_code)]
+
+pub mod loopback;
+
+pub use loopback::{LoopbackHost, LoopbackOptions};
@@ -0,0 +1,470 @@
+//! 采集和编码性能测试 (`sscontrol bench`)
+//!
+//! 依次运行以下阶段，每个阶段持续相同的时间并尽可能快地处理帧:
+//! - 仅采集
+//! - 采集 + 颜色转换 (RGBA → I420，软件编码路径的主要开销)
+//! - 采集 + 编码 (每个可用的编码器各一次)
+//!
+//! 每个阶段统计帧率、采集和处理耗时的 p50/p95/p99 以及进程 CPU 占用 (单核百分比，
+//! 多线程编码时可能超过 100%)。JSON 输出可用于在不同版本之间比较性能
+
+use crate::capture::{Capturer, DisplayChanged, Frame};
+use crate::encoder::colorspace::{PixelLayout, SourceImage, YuvBuffer, YuvLayout};
+use anyhow::Result;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// 测试选项
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// 每个阶段的时长
+    pub duration: Duration,
+    /// 显示器序号 (None = 主显示器)
+    pub screen_index: Option<u32>,
+    /// 编码器的目标帧率
+    pub fps: u32,
+    /// 编码码率 (kbps)
+    pub bitrate: u32,
+    /// 只测试这些编码器 (为空时测试全部可用的编码器)
+    pub encoders: Vec<String>,
+}
+
+/// 耗时分位数 (毫秒)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl std::fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1}/{:.1}/{:.1}", self.p50, self.p95, self.p99)
+    }
+}
+
+/// 单个阶段的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct StageResult {
+    /// 阶段名称
+    pub name: String,
+    /// 编码器的命令行名称 (编码阶段)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoder: Option<String>,
+    pub frames: u64,
+    /// 采集或处理失败的次数
+    pub errors: u64,
+    pub fps: f64,
+    /// 采集耗时
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capture_ms: Option<LatencySummary>,
+    /// 转换或编码耗时
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process_ms: Option<LatencySummary>,
+    /// 进程 CPU 占用 (单核百分比)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_percent: Option<f64>,
+    /// 编码输出码率
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bitrate_kbps: Option<f64>,
+    /// 阶段无法运行的原因 (如编码器创建失败)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl StageResult {
+    fn failed(name: String, encoder: Option<String>, error: String) -> Self {
+        Self {
+            name,
+            encoder,
+            frames: 0,
+            errors: 0,
+            fps: 0.0,
+            capture_ms: None,
+            process_ms: None,
+            cpu_percent: None,
+            bitrate_kbps: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// 完整的测试结果
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub width: u32,
+    pub height: u32,
+    /// 逻辑 CPU 数
+    pub cpus: usize,
+    pub stage_secs: f64,
+    pub stages: Vec<StageResult>,
+}
+
+impl BenchReport {
+    /// 帧率最高的 H.264 编码器 (帧率相近时取 p95 编码耗时较低的)
+    pub fn recommended_encoder(&self) -> Option<&StageResult> {
+        self.stages
+            .iter()
+            .filter(|stage| stage.error.is_none() && stage.frames > 0)
+            .filter(|stage| stage.encoder.as_deref().is_some_and(|encoder| encoder != "vp8"))
+            .max_by(|a, b| {
+                let p95 = |stage: &StageResult| stage.process_ms.map_or(f64::MAX, |ms| ms.p95);
+                // 帧率差距在 5% 以内视为相同
+                if (a.fps - b.fps).abs() <= a.fps.max(b.fps) * 0.05 {
+                    p95(b).total_cmp(&p95(a))
+                } else {
+                    a.fps.total_cmp(&b.fps)
+                }
+            })
+    }
+}
+
+/// 耗时样本
+#[derive(Debug, Default)]
+struct Samples(Vec<f64>);
+
+impl Samples {
+    fn push(&mut self, value: Duration) {
+        self.0.push(value.as_secs_f64() * 1000.0);
+    }
+
+    fn summary(&self) -> Option<LatencySummary> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let mut sorted = self.0.clone();
+        sorted.sort_by(f64::total_cmp);
+        // 最近秩法
+        let rank = |p: f64| sorted[((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len()) - 1];
+        Some(LatencySummary { p50: rank(0.50), p95: rank(0.95), p99: rank(0.99), max: sorted[sorted.len() - 1] })
+    }
+}
+
+/// 运行全部阶段
+///
+/// 阻塞直到测试结束 (阶段数 × 时长)，应在阻塞线程中调用
+pub fn run(options: &BenchOptions, mut on_stage: impl FnMut(&StageResult)) -> Result<BenchReport> {
+    let mut capturer = crate::capture::create_capturer(options.screen_index)?;
+    capturer.start()?;
+    let (width, height) = (capturer.width(), capturer.height());
+    let mut stages = Vec::new();
+    let mut record = |stage: StageResult| {
+        on_stage(&stage);
+        stages.push(stage);
+    };
+
+    record(measure("仅采集".to_string(), None, capturer.as_mut(), options.duration, None));
+
+    let mut yuv = YuvBuffer::new(YuvLayout::I420);
+    let mut convert = |frame: Frame| -> Result<usize> {
+        let source = SourceImage {
+            data: &frame.data,
+            stride: frame.stride,
+            width: frame.width as usize,
+            height: frame.height as usize,
+            layout: PixelLayout::Rgba,
+        };
+        yuv.convert(&source)?;
+        Ok(0)
+    };
+    record(measure("采集+转换 (I420)".to_string(), None, capturer.as_mut(), options.duration, Some(&mut convert)));
+
+    for (name, label) in candidate_encoders(&options.encoders) {
+        let stage_name = format!("采集+编码 {}", label);
+        let mut encoder = match BenchEncoder::create(name, width, height, options) {
+            Ok(encoder) => encoder,
+            Err(e) => {
+                record(StageResult::failed(stage_name, Some(name.to_string()), e.to_string()));
+                continue;
+            }
+        };
+        let mut encode = |frame: Frame| encoder.encode(frame);
+        record(measure(stage_name, Some(name), capturer.as_mut(), options.duration, Some(&mut encode)));
+    }
+
+    capturer.stop()?;
+    Ok(BenchReport {
+        width,
+        height,
+        cpus: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        stage_secs: options.duration.as_secs_f64(),
+        stages,
+    })
+}
+
+/// 在给定时间内循环采集 (和处理) 帧
+///
+/// `process` 返回输出的字节数 (用于计算码率)
+fn measure(
+    name: String,
+    encoder: Option<&str>,
+    capturer: &mut dyn Capturer,
+    duration: Duration,
+    mut process: Option<&mut dyn FnMut(Frame) -> Result<usize>>,
+) -> StageResult {
+    let mut capture_samples = Samples::default();
+    let mut process_samples = Samples::default();
+    let (mut frames, mut errors, mut bytes) = (0u64, 0u64, 0u64);
+
+    let cpu_start = process_cpu_time();
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        let captured_at = Instant::now();
+        let frame = match capturer.capture() {
+            Ok(frame) => frame,
+            Err(e) => {
+                errors += 1;
+                if DisplayChanged::is(&e) {
+                    return StageResult::failed(name, encoder.map(str::to_string), format!("测试中显示器发生变化: {}", e));
+                }
+                tracing::debug!("采集失败: {}", e);
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+        };
+        capture_samples.push(captured_at.elapsed());
+
+        if let Some(process) = process.as_mut() {
+            let processed_at = Instant::now();
+            match process(frame) {
+                Ok(size) => {
+                    process_samples.push(processed_at.elapsed());
+                    bytes += size as u64;
+                }
+                Err(e) => {
+                    errors += 1;
+                    tracing::debug!("{} 失败: {}", name, e);
+                    continue;
+                }
+            }
+        }
+        frames += 1;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    let cpu_percent = cpu_start
+        .zip(process_cpu_time())
+        .map(|(before, after)| after.saturating_sub(before).as_secs_f64() / elapsed * 100.0);
+
+    StageResult {
+        name,
+        encoder: encoder.map(str::to_string),
+        frames,
+        errors,
+        fps: frames as f64 / elapsed,
+        capture_ms: capture_samples.summary(),
+        process_ms: process_samples.summary(),
+        cpu_percent,
+        bitrate_kbps: encoder.map(|_| bytes as f64 * 8.0 / elapsed / 1000.0),
+        error: None,
+    }
+}
+
+/// 要测试的编码器: (命令行名称, 显示名称)
+fn candidate_encoders(selected: &[String]) -> Vec<(&'static str, &'static str)> {
+    #[allow(unused_mut)]
+    let mut encoders: Vec<(&'static str, &'static str)> = Vec::new();
+
+    #[cfg(feature = "h264")]
+    {
+        #[cfg(target_os = "windows")]
+        {
+            if crate::encoder::nvenc::NvencEncoder::is_available() {
+                encoders.push(("nvenc", "NVIDIA NVENC"));
+            }
+            if crate::encoder::amf::AmfEncoder::is_available() {
+                encoders.push(("amf", "AMD AMF"));
+            }
+            if crate::encoder::qsv::QuickSyncEncoder::is_available() {
+                encoders.push(("qsv", "Intel Quick Sync"));
+            }
+        }
+        #[cfg(target_os = "macos")]
+        encoders.push(("videotoolbox", "Apple VideoToolbox"));
+        encoders.push(("software", "Software (x264)"));
+        encoders.push(("vp8", "VP8 (libvpx)"));
+    }
+
+    if selected.is_empty() {
+        return encoders;
+    }
+    encoders
+        .into_iter()
+        .filter(|(name, _)| selected.iter().any(|selected| selected.eq_ignore_ascii_case(name)))
+        .collect()
+}
+
+/// 被测试的编码器
+enum BenchEncoder {
+    #[cfg(feature = "h264")]
+    H264(crate::encoder::hardware::HardwareEncoderWrapper, crate::encoder::alignment::FrameAligner),
+    #[cfg(feature = "h264")]
+    Vp8(crate::encoder::VP8Encoder, crate::encoder::alignment::FrameAligner),
+}
+
+impl BenchEncoder {
+    #[cfg(feature = "h264")]
+    fn create(name: &str, width: u32, height: u32, options: &BenchOptions) -> Result<Self> {
+        use crate::encoder::alignment::FrameAligner;
+        use crate::encoder::hardware::{EncoderPreset, HardwareEncoderConfig, HardwareEncoderType, HardwareEncoderWrapper};
+
+        // YUV420 编码器要求偶数宽高
+        let aligner = FrameAligner::even(width, height)?;
+        let encoder_type = match name {
+            "vp8" => {
+                let encoder = crate::encoder::VP8Encoder::new(aligner.width(), aligner.height(), options.fps, options.bitrate)?;
+                return Ok(Self::Vp8(encoder, aligner));
+            }
+            "nvenc" => HardwareEncoderType::NVENC,
+            "amf" => HardwareEncoderType::AMF,
+            "qsv" => HardwareEncoderType::QuickSync,
+            "videotoolbox" => HardwareEncoderType::VideoToolbox,
+            _ => HardwareEncoderType::Software,
+        };
+        let config = HardwareEncoderConfig {
+            encoder_type,
+            bitrate: options.bitrate,
+            fps: options.fps,
+            preset: EncoderPreset::LowLatency,
+        };
+        let encoder = HardwareEncoderWrapper::create(encoder_type, aligner.width(), aligner.height(), config)?;
+        Ok(Self::H264(encoder, aligner))
+    }
+
+    #[cfg(not(feature = "h264"))]
+    fn create(name: &str, _width: u32, _height: u32, _options: &BenchOptions) -> Result<Self> {
+        Err(anyhow::anyhow!("编码器 {} 需要 h264 feature", name))
+    }
+
+    /// 编码一帧，返回输出的字节数
+    fn encode(&mut self, frame: Frame) -> Result<usize> {
+        match self {
+            #[cfg(feature = "h264")]
+            Self::H264(encoder, aligner) => {
+                let frame = aligner.align(frame);
+                let packet = crate::encoder::hardware::HardwareEncoder::encode(encoder, &frame)?;
+                Ok(packet.map_or(0, |packet| packet.data.len()))
+            }
+            #[cfg(feature = "h264")]
+            Self::Vp8(encoder, aligner) => {
+                let frame = aligner.align(frame);
+                Ok(encoder.encode_frame(&frame)?.map_or(0, |data| data.len()))
+            }
+            #[cfg(not(feature = "h264"))]
+            _ => {
+                let _ = frame;
+                Err(anyhow::anyhow!("编码需要 h264 feature"))
+            }
+        }
+    }
+}
+
+/// 进程累计 CPU 时间 (用户态 + 内核态)
+#[cfg(unix)]
+fn process_cpu_time() -> Option<Duration> {
+    use std::os::raw::c_long;
+
+    #[repr(C)]
+    struct Timeval {
+        tv_sec: c_long,
+        #[cfg(target_os = "macos")]
+        tv_usec: i32,
+        #[cfg(not(target_os = "macos"))]
+        tv_usec: c_long,
+    }
+
+    #[repr(C)]
+    struct Rusage {
+        utime: Timeval,
+        stime: Timeval,
+        rest: [c_long; 14],
+    }
+
+    extern "C" {
+        fn getrusage(who: i32, usage: *mut Rusage) -> i32;
+    }
+    const RUSAGE_SELF: i32 = 0;
+
+    let mut usage = std::mem::MaybeUninit::<Rusage>::zeroed();
+    if unsafe { getrusage(RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let usage = unsafe { usage.assume_init() };
+    let duration = |tv: &Timeval| Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64);
+    Some(duration(&usage.utime) + duration(&usage.stime))
+}
+
+/// 进程累计 CPU 时间 (用户态 + 内核态)
+#[cfg(windows)]
+fn process_cpu_time() -> Option<Duration> {
+    use windows::Win32::Foundation::FILETIME;
+    use windows::Win32::System::Threading::{GetCurrentProcess, GetProcessTimes};
+
+    let (mut creation, mut exit, mut kernel, mut user) =
+        (FILETIME::default(), FILETIME::default(), FILETIME::default(), FILETIME::default());
+    unsafe { GetProcessTimes(GetCurrentProcess(), &mut creation, &mut exit, &mut kernel, &mut user) }.ok()?;
+    // FILETIME 的单位是 100 纳秒
+    let ticks = |time: &FILETIME| ((time.dwHighDateTime as u64) << 32) | time.dwLowDateTime as u64;
+    Some(Duration::from_nanos((ticks(&kernel) + ticks(&user)) * 100))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn process_cpu_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage(encoder: &str, fps: f64, p95: f64) -> StageResult {
+        StageResult {
+            name: encoder.to_string(),
+            encoder: Some(encoder.to_string()),
+            frames: 100,
+            errors: 0,
+            fps,
+            capture_ms: None,
+            process_ms: Some(LatencySummary { p50: p95 / 2.0, p95, p99: p95, max: p95 }),
+            cpu_percent: None,
+            bitrate_kbps: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_samples_summary() {
+        let mut samples = Samples::default();
+        assert!(samples.summary().is_none());
+        for ms in 1..=100 {
+            samples.push(Duration::from_millis(ms));
+        }
+        let summary = samples.summary().unwrap();
+        assert_eq!((summary.p50, summary.p95, summary.p99, summary.max), (50.0, 95.0, 99.0, 100.0));
+    }
+
+    #[test]
+    fn test_recommended_encoder() {
+        let report = BenchReport {
+            width: 1920,
+            height: 1080,
+            cpus: 8,
+            stage_secs: 5.0,
+            stages: vec![stage("software", 40.0, 20.0), stage("nvenc", 59.0, 6.0), stage("qsv", 60.0, 9.0), stage("vp8", 90.0, 3.0)],
+        };
+        // 帧率相近时取编码耗时较低的，VP8 不参与推荐
+        assert_eq!(report.recommended_encoder().unwrap().encoder.as_deref(), Some("nvenc"));
+    }
+
+    #[test]
+    fn test_process_cpu_time_increases() {
+        let Some(before) = process_cpu_time() else { return };
+        let start = Instant::now();
+        let mut x = 0u64;
+        while start.elapsed() < Duration::from_millis(50) {
+            x = x.wrapping_add(std::hint::black_box(1));
+        }
+        assert!(x > 0);
+        assert!(process_cpu_time().unwrap() > before);
+    }
+}
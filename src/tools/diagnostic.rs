@@ -75,22 +75,18 @@ impl DiagnosticTool {
     pub fn run_full_diagnostic(&self) -> Result<DiagnosticResult> {
         tracing::info!("开始网络诊断...");
 
-        let mut details = Vec::new();
-
-        // 1. 检测本地 IP
-        details.push(self.check_local_ip());
-
-        // 2. 检测 NAT 类型
-        details.push(self.check_nat_type());
-
-        // 3. 测试网络连接
-        details.push(self.check_network_connectivity());
-
-        // 4. 评估网络质量
-        details.push(self.check_network_quality());
-
-        // 5. 检测编码器可用性
-        details.push(self.check_encoders());
+        let details = vec![
+            // 1. 检测本地 IP
+            self.check_local_ip(),
+            // 2. 检测 NAT 类型
+            self.check_nat_type(),
+            // 3. 测试网络连接
+            self.check_network_connectivity(),
+            // 4. 评估网络质量
+            self.check_network_quality(),
+            // 5. 检测编码器可用性
+            self.check_encoders(),
+        ];
 
         // 计算总体结果
         let fail_count = details.iter()
@@ -238,10 +234,9 @@ impl DiagnosticTool {
 
     /// 检测编码器可用性
     fn check_encoders(&self) -> DiagnosticDetail {
-        let mut available_encoders = Vec::new();
-
         // 检测软件编码器 (始终可用)
-        available_encoders.push("Software (x264)".to_string());
+        #[allow(unused_mut, clippy::useless_vec)]
+        let mut available_encoders = vec!["Software (x264)".to_string()];
 
         // 检测硬件编码器
         #[cfg(target_os = "macos")]
@@ -361,7 +356,7 @@ impl Default for DiagnosticTool {
 pub fn format_diagnostic_result(result: &DiagnosticResult) -> String {
     let mut output = String::new();
 
-    output.push_str(&format!("\n=== 诊断结果 ===\n"));
+    output.push_str("\n=== 诊断结果 ===\n");
     output.push_str(&format!("状态: {}\n\n", result.message));
 
     for detail in &result.details {
@@ -419,7 +414,7 @@ mod tests {
         let tool = DiagnosticTool::new();
         let result = tool.check_local_ip();
         // 应该返回有效的 IP 或者错误
-        assert!(result.value.len() > 0);
+        assert!(!result.value.is_empty());
     }
 
     #[test]
@@ -0,0 +1,528 @@
+//! 环境诊断 (`sscontrol doctor`)
+//!
+//! 检查运行被控端所需的环境，每个问题给出可以直接执行的修复方法：
+//! - macOS: 屏幕录制和辅助功能权限
+//! - Windows: DXGI 桌面复制是否可用，是否运行在会话 0 (服务会话)
+//! - FFmpeg、libx264、libvpx 是否可用，硬件编码器
+//! - 信令端口能否监听、系统防火墙是否放行
+//! - STUN 服务器是否可达 (UDP 出站)
+//!
+//! 结果沿用 [`super::diagnostic`] 的 [`DiagnosticDetail`] 格式
+
+use super::diagnostic::{DiagnosticDetail, DiagnosticResult, DiagnosticStatus};
+use std::time::Duration;
+
+/// 未配置 STUN 服务器时用于测试 UDP 出站的服务器
+const FALLBACK_STUN_SERVER: &str = "stun:stun.l.google.com:19302";
+
+/// 单个网络检查的超时
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 诊断选项
+#[derive(Debug, Clone)]
+pub struct DoctorOptions {
+    /// 被控端信令端口
+    pub port: u16,
+    /// 被控端是否同时在该端口的 UDP 上提供 QUIC
+    pub quic: bool,
+    /// 测试的 STUN 服务器 (为空时使用公共服务器)
+    pub stun_servers: Vec<String>,
+}
+
+impl Default for DoctorOptions {
+    fn default() -> Self {
+        Self { port: 9527, quic: true, stun_servers: Vec::new() }
+    }
+}
+
+fn detail(
+    category: &str,
+    name: &str,
+    status: DiagnosticStatus,
+    value: impl Into<String>,
+    recommendation: Option<String>,
+) -> DiagnosticDetail {
+    DiagnosticDetail {
+        category: category.to_string(),
+        name: name.to_string(),
+        status,
+        value: value.into(),
+        recommendation,
+    }
+}
+
+/// 运行全部检查
+pub async fn run(options: &DoctorOptions) -> DiagnosticResult {
+    let mut details = Vec::new();
+    details.extend(check_permissions());
+    details.extend(check_capture());
+    details.extend(check_ffmpeg());
+    details.push(check_hardware_encoders());
+    details.push(check_signaling_port(options.port).await);
+    details.push(check_firewall(options.port, options.quic));
+    details.push(check_stun(&options.stun_servers).await);
+
+    let failures = details.iter().filter(|d| d.status == DiagnosticStatus::Fail).count();
+    let warnings = details.iter().filter(|d| d.status == DiagnosticStatus::Warning).count();
+    let message = match (failures, warnings) {
+        (0, 0) => "环境检查全部通过".to_string(),
+        (0, warnings) => format!("{} 项需要注意", warnings),
+        (failures, warnings) => format!("{} 个问题，{} 项需要注意", failures, warnings),
+    };
+    DiagnosticResult { success: failures == 0, message, details }
+}
+
+/// 系统权限 (macOS)
+fn check_permissions() -> Vec<DiagnosticDetail> {
+    #[cfg(target_os = "macos")]
+    {
+        let screen = if crate::capture::macos::MacOSCapturer::check_screen_recording_permission() {
+            detail("权限", "屏幕录制", DiagnosticStatus::Pass, "已授权", None)
+        } else {
+            detail(
+                "权限",
+                "屏幕录制",
+                DiagnosticStatus::Fail,
+                "未授权 (画面只有桌面背景)",
+                Some(
+                    "系统设置 → 隐私与安全性 → 屏幕录制，允许 sscontrol (从终端运行时允许该终端)，然后重启 sscontrol。\n  \
+                     快速打开: open \"x-apple.systempreferences:com.apple.preference.security?Privacy_ScreenCapture\""
+                        .to_string(),
+                ),
+            )
+        };
+        let accessibility = if crate::input::macos::MacOSInputSimulator::check_accessibility_permission() {
+            detail("权限", "辅助功能", DiagnosticStatus::Pass, "已授权", None)
+        } else {
+            detail(
+                "权限",
+                "辅助功能",
+                DiagnosticStatus::Fail,
+                "未授权 (远程键盘鼠标无效)",
+                Some(
+                    "系统设置 → 隐私与安全性 → 辅助功能，允许 sscontrol (从终端运行时允许该终端)。\n  \
+                     快速打开: open \"x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility\""
+                        .to_string(),
+                ),
+            )
+        };
+        vec![screen, accessibility]
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        Vec::new()
+    }
+}
+
+/// 屏幕捕获接口和会话 (Windows)
+fn check_capture() -> Vec<DiagnosticDetail> {
+    #[cfg(target_os = "windows")]
+    {
+        let mut details = vec![check_windows_session()];
+        details.push(match crate::capture::windows_dxgi::DXGICapturer::new(None) {
+            Ok(_) => detail("屏幕捕获", "DXGI 桌面复制", DiagnosticStatus::Pass, "可用", None),
+            Err(e) => detail(
+                "屏幕捕获",
+                "DXGI 桌面复制",
+                DiagnosticStatus::Warning,
+                format!("不可用 ({})，将回退到较慢的 GDI 捕获", e),
+                Some(
+                    "常见原因: 通过远程桌面 (RDP) 登录、缺少显卡驱动 (使用 Microsoft 基本显示适配器)、运行在会话 0。\
+                     请更新显卡驱动，并在本地控制台会话中运行 sscontrol host"
+                        .to_string(),
+                ),
+            ),
+        });
+        details
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        Vec::new()
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        vec![detail(
+            "屏幕捕获",
+            "平台支持",
+            DiagnosticStatus::Info,
+            format!("{} 上只能作为控制端使用", std::env::consts::OS),
+            Some("被控端 (sscontrol host) 目前只支持 macOS 和 Windows".to_string()),
+        )]
+    }
+}
+
+/// 是否运行在会话 0 或非控制台会话 (Windows)
+#[cfg(target_os = "windows")]
+fn check_windows_session() -> DiagnosticDetail {
+    use windows::Win32::System::RemoteDesktop::{ProcessIdToSessionId, WTSGetActiveConsoleSessionId};
+    use windows::Win32::System::Threading::GetCurrentProcessId;
+
+    let mut session = 0u32;
+    if let Err(e) = unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session) } {
+        return detail("屏幕捕获", "Windows 会话", DiagnosticStatus::Warning, format!("无法获取会话 ID: {}", e), None);
+    }
+    let console = unsafe { WTSGetActiveConsoleSessionId() };
+
+    if session == 0 {
+        detail(
+            "屏幕捕获",
+            "Windows 会话",
+            DiagnosticStatus::Fail,
+            "运行在会话 0 (服务会话)，无法捕获用户桌面，也无法注入输入",
+            Some("使用 sscontrol service install 安装服务 (服务会在用户会话中启动助手进程)，或在已登录用户的桌面会话中运行 sscontrol host".to_string()),
+        )
+    } else if session != console {
+        detail(
+            "屏幕捕获",
+            "Windows 会话",
+            DiagnosticStatus::Warning,
+            format!("会话 {} 不是控制台会话 {} (远程桌面会话)", session, console),
+            Some("远程桌面会话断开后屏幕无法捕获；断开 RDP 前执行 tscon %SESSIONNAME% /dest:console 把会话切回控制台".to_string()),
+        )
+    } else {
+        detail("屏幕捕获", "Windows 会话", DiagnosticStatus::Pass, format!("控制台会话 {}", session), None)
+    }
+}
+
+/// FFmpeg 及软件编码器
+#[cfg(feature = "h264")]
+fn check_ffmpeg() -> Vec<DiagnosticDetail> {
+    use ffmpeg_next as ffmpeg;
+
+    if let Err(e) = ffmpeg::init() {
+        return vec![detail(
+            "编解码",
+            "FFmpeg",
+            DiagnosticStatus::Fail,
+            format!("初始化失败: {}", e),
+            Some("检查 FFmpeg 动态库是否在 PATH (Windows) 或系统库路径中，版本是否与编译时一致".to_string()),
+        )];
+    }
+
+    let version = ffmpeg::util::version();
+    let mut details = vec![detail(
+        "编解码",
+        "FFmpeg",
+        DiagnosticStatus::Pass,
+        format!("libavutil {}.{}.{}", version >> 16, (version >> 8) & 0xff, version & 0xff),
+        None,
+    )];
+    details.push(match ffmpeg::encoder::find_by_name("libx264") {
+        Some(_) => detail("编解码", "H.264 软件编码 (libx264)", DiagnosticStatus::Pass, "可用", None),
+        None => detail(
+            "编解码",
+            "H.264 软件编码 (libx264)",
+            DiagnosticStatus::Warning,
+            "不可用 (没有硬件编码器时无法使用 H.264)",
+            Some("使用以 --enable-libx264 编译的 FFmpeg (如 brew install ffmpeg、BtbN 的 GPL 版本)".to_string()),
+        ),
+    });
+    details.push(match ffmpeg::encoder::find(ffmpeg::codec::Id::VP8) {
+        Some(codec) => detail("编解码", "VP8 编码 (libvpx)", DiagnosticStatus::Pass, format!("可用 ({})", codec.name()), None),
+        None => detail(
+            "编解码",
+            "VP8 编码 (libvpx)",
+            DiagnosticStatus::Warning,
+            "不可用 (浏览器查看器协商到 VP8 时无法推流)",
+            Some("使用以 --enable-libvpx 编译的 FFmpeg (Ubuntu/Debian: sudo apt install libvpx-dev 后重新安装 libavcodec)".to_string()),
+        ),
+    });
+    details
+}
+
+#[cfg(not(feature = "h264"))]
+fn check_ffmpeg() -> Vec<DiagnosticDetail> {
+    vec![detail(
+        "编解码",
+        "FFmpeg",
+        DiagnosticStatus::Warning,
+        "未编译 FFmpeg 支持 (h264 feature)，无法编码 H.264/VP8",
+        Some("按 README 的 \"Installing FFmpeg\" 安装 FFmpeg 开发库，然后用 cargo build --release --features h264 重新编译".to_string()),
+    )]
+}
+
+/// 硬件编码器
+fn check_hardware_encoders() -> DiagnosticDetail {
+    #[allow(unused_mut)]
+    let mut available: Vec<&str> = Vec::new();
+
+    #[cfg(target_os = "windows")]
+    {
+        if crate::encoder::nvenc::NvencEncoder::is_available() {
+            available.push("NVIDIA NVENC");
+        }
+        if crate::encoder::amf::AmfEncoder::is_available() {
+            available.push("AMD AMF");
+        }
+        if crate::encoder::qsv::QuickSyncEncoder::is_available() {
+            available.push("Intel Quick Sync");
+        }
+    }
+
+    // VideoToolbox 在所有 macOS 上都可用
+    #[cfg(target_os = "macos")]
+    available.push("Apple VideoToolbox");
+
+    if !available.is_empty() {
+        return detail("编码器", "硬件编码器", DiagnosticStatus::Pass, available.join(", "), None);
+    }
+
+    let recommendation = if cfg!(not(feature = "h264")) {
+        "硬件编码器需要 h264 feature (cargo build --release --features h264)"
+    } else if cfg!(target_os = "windows") {
+        "安装最新的 NVIDIA / AMD / Intel 显卡驱动，并使用包含 h264_nvenc、h264_amf 或 h264_qsv 的 FFmpeg 构建"
+    } else if cfg!(target_os = "macos") {
+        "确认 FFmpeg 编译时启用了 VideoToolbox (--enable-videotoolbox，Homebrew 版本默认启用)"
+    } else {
+        "当前平台没有硬件编码支持"
+    };
+    detail(
+        "编码器",
+        "硬件编码器",
+        DiagnosticStatus::Warning,
+        "未检测到，将使用软件编码 (CPU 占用较高)",
+        Some(recommendation.to_string()),
+    )
+}
+
+/// 信令端口能否在局域网地址上监听和接受连接
+async fn check_signaling_port(port: u16) -> DiagnosticDetail {
+    const NAME: &str = "信令端口";
+
+    let listener = match tokio::net::TcpListener::bind(("0.0.0.0", port)).await {
+        Ok(listener) => listener,
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+            return detail(
+                "网络",
+                NAME,
+                DiagnosticStatus::Info,
+                format!("TCP {} 已被占用 (被控端是否已在运行?)", port),
+                Some(format!("没有运行被控端时，找出占用端口的程序，或用 sscontrol host --port <端口> 换一个端口 (当前 {})", port)),
+            );
+        }
+        Err(e) => {
+            return detail(
+                "网络",
+                NAME,
+                DiagnosticStatus::Fail,
+                format!("无法监听 TCP {}: {}", port, e),
+                Some("1024 以下的端口需要管理员权限，请换一个端口 (sscontrol host --port <端口>)".to_string()),
+            );
+        }
+    };
+
+    let Some(ip) = crate::network::lan::local_ip() else {
+        return detail(
+            "网络",
+            NAME,
+            DiagnosticStatus::Warning,
+            format!("TCP {} 可以监听，但没有局域网地址", port),
+            Some("检查网络连接".to_string()),
+        );
+    };
+    let address = crate::network::lan::host_port(&ip.to_string(), port);
+    let connect = tokio::time::timeout(NETWORK_TIMEOUT, async {
+        tokio::try_join!(listener.accept(), tokio::net::TcpStream::connect((ip, port)))
+    })
+    .await;
+    match connect {
+        Ok(Ok(_)) => detail("网络", NAME, DiagnosticStatus::Pass, format!("{} 可以监听和连接", address), None),
+        Ok(Err(e)) => detail(
+            "网络",
+            NAME,
+            DiagnosticStatus::Warning,
+            format!("无法连接 {}: {}", address, e),
+            Some("本机的安全软件可能拦截了该端口，请将 sscontrol 加入例外".to_string()),
+        ),
+        Err(_) => detail(
+            "网络",
+            NAME,
+            DiagnosticStatus::Warning,
+            format!("连接 {} 超时", address),
+            Some("本机的安全软件可能拦截了该端口，请将 sscontrol 加入例外".to_string()),
+        ),
+    }
+}
+
+/// 系统防火墙是否放行信令端口 (QUIC 开启时还有同号 UDP 端口)
+fn check_firewall(port: u16, quic: bool) -> DiagnosticDetail {
+    const NAME: &str = "防火墙";
+
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(output) = command_output("ufw", &["status"]) {
+            return match ufw_allows(&output, port) {
+                None => detail("网络", NAME, DiagnosticStatus::Pass, "ufw 未启用", None),
+                Some(true) => detail("网络", NAME, DiagnosticStatus::Pass, format!("ufw 已放行 {}", port), None),
+                Some(false) => {
+                    let mut fix = format!("sudo ufw allow {}/tcp", port);
+                    if quic {
+                        fix.push_str(&format!(" && sudo ufw allow {}/udp", port));
+                    }
+                    detail("网络", NAME, DiagnosticStatus::Warning, format!("ufw 已启用，未放行 {}", port), Some(fix))
+                }
+            };
+        }
+        if command_output("firewall-cmd", &["--state"]).is_some_and(|state| state.trim() == "running") {
+            let ports = command_output("firewall-cmd", &["--list-ports"]).unwrap_or_default();
+            if firewalld_allows(&ports, port, "tcp") && (!quic || firewalld_allows(&ports, port, "udp")) {
+                return detail("网络", NAME, DiagnosticStatus::Pass, format!("firewalld 已放行 {}", port), None);
+            }
+            let mut fix = format!("sudo firewall-cmd --permanent --add-port={}/tcp", port);
+            if quic {
+                fix.push_str(&format!(" --add-port={}/udp", port));
+            }
+            fix.push_str(" && sudo firewall-cmd --reload");
+            return detail("网络", NAME, DiagnosticStatus::Warning, format!("firewalld 未放行 {}", port), Some(fix));
+        }
+        detail("网络", NAME, DiagnosticStatus::Info, "未检测到 ufw 或 firewalld (ufw 需要 root 才能读取状态)", None)
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let _ = (port, quic);
+        const SOCKETFILTERFW: &str = "/usr/libexec/ApplicationFirewall/socketfilterfw";
+        match command_output(SOCKETFILTERFW, &["--getglobalstate"]) {
+            Some(state) if state.contains("enabled") => {
+                let exe = std::env::current_exe()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|_| "sscontrol".to_string());
+                detail(
+                    "网络",
+                    NAME,
+                    DiagnosticStatus::Warning,
+                    "应用防火墙已开启，未放行的程序无法接受传入连接",
+                    Some(format!(
+                        "首次运行被控端时在系统弹窗中选择\"允许\"，或执行: sudo {0} --add \"{1}\" && sudo {0} --unblockapp \"{1}\"",
+                        SOCKETFILTERFW, exe
+                    )),
+                )
+            }
+            Some(_) => detail("网络", NAME, DiagnosticStatus::Pass, "应用防火墙未开启", None),
+            None => detail("网络", NAME, DiagnosticStatus::Info, "无法读取应用防火墙状态", None),
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // 按规则名查询，避免解析本地化的输出
+        let rule_exists = std::process::Command::new("netsh")
+            .args(["advfirewall", "firewall", "show", "rule", "name=sscontrol"])
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if rule_exists {
+            return detail("网络", NAME, DiagnosticStatus::Pass, "已有 sscontrol 入站规则", None);
+        }
+        let mut fix = format!(
+            "以管理员身份运行: netsh advfirewall firewall add rule name=sscontrol dir=in action=allow protocol=TCP localport={}",
+            port
+        );
+        if quic {
+            fix.push_str(&format!(
+                "\n  netsh advfirewall firewall add rule name=sscontrol dir=in action=allow protocol=UDP localport={}",
+                port
+            ));
+        }
+        detail("网络", NAME, DiagnosticStatus::Warning, "没有 sscontrol 入站规则 (Windows 防火墙开启时局域网无法连接)", Some(fix))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        let _ = (port, quic);
+        detail("网络", NAME, DiagnosticStatus::Info, "不支持检测当前平台的防火墙", None)
+    }
+}
+
+/// 运行命令并返回标准输出 (命令不存在或失败时为 None)
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// 解析 `ufw status`: 未启用为 None，否则返回是否有放行该端口的规则
+fn ufw_allows(output: &str, port: u16) -> Option<bool> {
+    if !output.lines().any(|line| line.trim() == "Status: active") {
+        return None;
+    }
+    let port = port.to_string();
+    Some(output.lines().any(|line| {
+        let mut columns = line.split_whitespace();
+        let target = columns.next().unwrap_or_default();
+        let allowed = columns.next() == Some("ALLOW");
+        allowed && (target == port || target.split_once('/').is_some_and(|(p, _)| p == port))
+    }))
+}
+
+/// 解析 `firewall-cmd --list-ports` (如 `9527/tcp 9000-9100/udp`)
+fn firewalld_allows(output: &str, port: u16, protocol: &str) -> bool {
+    output.split_whitespace().any(|entry| {
+        let Some((range, proto)) = entry.split_once('/') else { return false };
+        if proto != protocol {
+            return false;
+        }
+        match range.split_once('-') {
+            Some((start, end)) => match (start.parse::<u16>(), end.parse::<u16>()) {
+                (Ok(start), Ok(end)) => (start..=end).contains(&port),
+                _ => false,
+            },
+            None => range.parse::<u16>() == Ok(port),
+        }
+    })
+}
+
+/// STUN 服务器是否可达 (UDP 出站)
+async fn check_stun(servers: &[String]) -> DiagnosticDetail {
+    const NAME: &str = "STUN";
+
+    let servers: Vec<String> = if servers.is_empty() { vec![FALLBACK_STUN_SERVER.to_string()] } else { servers.to_vec() };
+    let mut errors = Vec::new();
+    for server in &servers {
+        match tokio::time::timeout(NETWORK_TIMEOUT, crate::nat::stun::public_address(server)).await {
+            Ok(Ok(mapped)) => {
+                return detail("网络", NAME, DiagnosticStatus::Pass, format!("{} 可达，公网地址 {}", server, mapped), None);
+            }
+            Ok(Err(e)) => errors.push(format!("{}: {}", server, e)),
+            Err(_) => errors.push(format!("{}: 超时", server)),
+        }
+    }
+    detail(
+        "网络",
+        NAME,
+        DiagnosticStatus::Fail,
+        errors.join("; "),
+        Some(
+            "UDP 出站可能被防火墙拦截，P2P 连接会失败。放行 UDP 出站 (至少 3478 和 19302)，\
+             或在可达的主机上运行 sscontrol stun-server 并配置 webrtc.stun_servers；无法放行时使用 TURN 中继或 --tunnel"
+                .to_string(),
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ufw_allows() {
+        assert_eq!(ufw_allows("Status: inactive\n", 9527), None);
+
+        let active = "Status: active\n\nTo                         Action      From\n--                         ------      ----\n\
+                      22/tcp                     ALLOW       Anywhere\n9527                       ALLOW       Anywhere\n";
+        assert_eq!(ufw_allows(active, 9527), Some(true));
+        assert_eq!(ufw_allows(active, 3389), Some(false));
+        assert_eq!(ufw_allows(active, 22), Some(true));
+    }
+
+    #[test]
+    fn test_firewalld_allows() {
+        let ports = "9527/tcp 9000-9100/udp\n";
+        assert!(firewalld_allows(ports, 9527, "tcp"));
+        assert!(!firewalld_allows(ports, 9527, "udp"));
+        assert!(firewalld_allows(ports, 9050, "udp"));
+        assert!(!firewalld_allows("", 9527, "tcp"));
+    }
+}
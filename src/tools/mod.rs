@@ -5,5 +5,8 @@
 // 命令行工具模块尚未完全激活，标记为允许死代码
 #![allow(dead_code)]
 
+pub mod bench;
 pub mod diagnostic;
+pub mod doctor;
+pub mod probe;
 
@@ -0,0 +1,409 @@
+//! 连接前能力探测
+//!
+//! 不建立完整会话，仅检查被控端信令端口是否可达、测量往返时延、
+//! 校验协议版本并列出对端支持的功能
+//!
+//! ## 探测步骤
+//! 1. 多次 TCP 握手测量 RTT (取最小值，排除调度抖动)
+//! 2. 请求信令服务器的 `/capabilities` 获取版本与功能
+
+use crate::signaling::HostCapabilities;
+use crate::webrtc::signaling::SignalMessage;
+use anyhow::{anyhow, Result};
+use futures_util::{SinkExt, StreamExt};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// TCP 握手测量次数
+const RTT_SAMPLES: usize = 3;
+
+/// 响应体大小上限 (设备清单可达数百 KB)
+const MAX_RESPONSE_SIZE: usize = 1024 * 1024;
+
+/// 探测目标
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeTarget {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+}
+
+impl ProbeTarget {
+    /// 局域网 IP 模式
+    pub fn from_ip(ip: &str, port: u16) -> Self {
+        // IPv6 地址加方括号，与 URL 模式的 host_str 一致
+        let host = if ip.contains(':') && !ip.starts_with('[') {
+            format!("[{}]", ip)
+        } else {
+            ip.to_string()
+        };
+        Self {
+            host,
+            port,
+            tls: false,
+        }
+    }
+
+    /// 公网 URL 模式 (ws/wss/http/https)
+    pub fn from_url(url: &str) -> Result<Self> {
+        let parsed = url::Url::parse(url).map_err(|e| anyhow!("无效的 URL {}: {}", url, e))?;
+        let tls = match parsed.scheme() {
+            "ws" | "http" => false,
+            "wss" | "https" => true,
+            other => return Err(anyhow!("不支持的 URL 协议: {}", other)),
+        };
+        let host = parsed
+            .host_str()
+            .ok_or_else(|| anyhow!("URL 缺少主机名: {}", url))?
+            .to_string();
+        let port = parsed
+            .port_or_known_default()
+            .unwrap_or(if tls { 443 } else { 80 });
+
+        Ok(Self { host, port, tls })
+    }
+
+    fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    fn websocket_url(&self) -> String {
+        let scheme = if self.tls { "wss" } else { "ws" };
+        format!("{}://{}:{}/ws", scheme, self.host, self.port)
+    }
+}
+
+impl std::fmt::Display for ProbeTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scheme = if self.tls { "https" } else { "http" };
+        write!(f, "{}://{}:{}", scheme, self.host, self.port)
+    }
+}
+
+/// 探测结果
+#[derive(Debug, Clone)]
+pub struct ProbeReport {
+    pub target: ProbeTarget,
+    /// 信令端口是否可达
+    pub reachable: bool,
+    /// TCP 握手往返时延 (ms)
+    pub rtt_ms: Option<f64>,
+    /// 对端能力 (老版本 Host 不支持时为 None)
+    pub capabilities: Option<HostCapabilities>,
+    /// 探测过程中的错误
+    pub error: Option<String>,
+}
+
+impl ProbeReport {
+    /// 是否可以建立会话
+    pub fn is_ready(&self) -> bool {
+        self.reachable
+            && self
+                .capabilities
+                .as_ref()
+                .is_some_and(|caps| caps.is_compatible() && caps.features.webrtc)
+    }
+}
+
+/// 探测被控端
+pub async fn probe(target: ProbeTarget, timeout: Duration) -> ProbeReport {
+    let mut report = ProbeReport {
+        target: target.clone(),
+        reachable: false,
+        rtt_ms: None,
+        capabilities: None,
+        error: None,
+    };
+
+    match measure_rtt(&target, timeout).await {
+        Ok(rtt) => {
+            report.reachable = true;
+            report.rtt_ms = Some(rtt.as_secs_f64() * 1000.0);
+        }
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return report;
+        }
+    }
+
+    match tokio::time::timeout(timeout, fetch_capabilities(&target)).await {
+        Ok(Ok(capabilities)) => report.capabilities = Some(capabilities),
+        Ok(Err(e)) => report.error = Some(e.to_string()),
+        Err(_) => report.error = Some(format!("获取能力信息超时 ({:?})", timeout)),
+    }
+
+    report
+}
+
+/// 多次 TCP 握手，返回最小耗时
+async fn measure_rtt(target: &ProbeTarget, timeout: Duration) -> Result<Duration> {
+    let address = target.address();
+    let mut best: Option<Duration> = None;
+
+    for _ in 0..RTT_SAMPLES {
+        let start = Instant::now();
+        tokio::time::timeout(timeout, TcpStream::connect(&address))
+            .await
+            .map_err(|_| anyhow!("连接 {} 超时 ({:?})", address, timeout))?
+            .map_err(|e| anyhow!("无法连接 {}: {}", address, e))?;
+        let elapsed = start.elapsed();
+        best = Some(best.map_or(elapsed, |b| b.min(elapsed)));
+    }
+
+    best.ok_or_else(|| anyhow!("未完成 RTT 测量"))
+}
+
+async fn fetch_capabilities(target: &ProbeTarget) -> Result<HostCapabilities> {
+    let (status, body) = get(target, "/capabilities", None).await?;
+    match status {
+        200 => serde_json::from_slice(&body).map_err(|e| anyhow!("解析能力信息失败: {}", e)),
+        404 => Err(anyhow!("对端版本过旧，不支持能力探测")),
+        code => Err(anyhow!("获取能力信息失败: HTTP {}", code)),
+    }
+}
+
+/// 请求信令服务器的 HTTP 接口 (`token` 以 Bearer 方式认证)，返回 (状态码, 响应体)
+pub async fn get(target: &ProbeTarget, path: &str, token: Option<&str>) -> Result<(u16, Vec<u8>)> {
+    let stream = TcpStream::connect(target.address())
+        .await
+        .map_err(|e| anyhow!("无法连接 {}: {}", target.address(), e))?;
+
+    if target.tls {
+        #[cfg(feature = "security")]
+        {
+            use tokio_rustls::rustls::pki_types::ServerName;
+
+            let connector = crate::security::TlsConfig::client_connector()?;
+            let server_name = ServerName::try_from(target.host.clone())
+                .map_err(|e| anyhow!("无效的 TLS 主机名 {}: {}", target.host, e))?;
+            let stream = connector
+                .connect(server_name, stream)
+                .await
+                .map_err(|e| anyhow!("TLS 握手失败: {}", e))?;
+            http_get(stream, &target.host, path, token).await
+        }
+        #[cfg(not(feature = "security"))]
+        {
+            drop(stream);
+            Err(anyhow!("访问 wss/https 地址需要启用 security feature"))
+        }
+    } else {
+        http_get(stream, &target.host, path, token).await
+    }
+}
+
+/// 先在 `/ws` 上完成验证 (PIN/TOTP，首次连接时等待被控端审批) 换取一次性票据，再带票据请求 HTTP 接口
+///
+/// 票据在信令连接断开后失效，因此连接保持到请求完成
+pub async fn get_with_ticket(
+    target: &ProbeTarget,
+    path: &str,
+    token: Option<&str>,
+    pin: Option<&str>,
+    totp: Option<&str>,
+) -> Result<(u16, Vec<u8>)> {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::http::HeaderValue;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let mut request = target.websocket_url().into_client_request()?;
+    if let Some(token) = token {
+        request
+            .headers_mut()
+            .insert("Authorization", HeaderValue::from_str(&format!("Bearer {}", token))?);
+    }
+    let (mut ws, _) = tokio_tungstenite::connect_async(request)
+        .await
+        .map_err(|e| anyhow!("无法连接信令服务器 {}: {}", target, e))?;
+
+    let encode = |message: &SignalMessage| serde_json::to_string(message).map(Message::Text);
+    ws.send(encode(&SignalMessage::Join { room_id: "default".to_string() })?).await?;
+
+    let mut requested = false;
+    let ticket = loop {
+        let message = ws.next().await.ok_or_else(|| anyhow!("信令连接已断开"))??;
+        let Message::Text(text) = message else { continue };
+        let Ok(signal) = serde_json::from_str::<SignalMessage>(&text) else { continue };
+        let reply = match signal {
+            SignalMessage::PinRequired => {
+                let pin = pin.ok_or_else(|| anyhow!("被控端要求 PIN，请用 --pin 提供"))?;
+                SignalMessage::Pin { pin: pin.to_string() }
+            }
+            SignalMessage::TotpRequired => {
+                let code = totp.ok_or_else(|| anyhow!("被控端要求 TOTP 验证码，请用 --totp 提供"))?;
+                SignalMessage::Totp { code: code.to_string() }
+            }
+            SignalMessage::PinResult { accepted: false, .. } => return Err(anyhow!("PIN 验证失败")),
+            SignalMessage::TotpResult { accepted: false, .. } => return Err(anyhow!("TOTP 验证失败")),
+            // 权限随放行下发，PIN/TOTP 要求在它之前: 此时验证信息已全部提交
+            SignalMessage::Permissions { .. } if !requested => {
+                requested = true;
+                SignalMessage::RequestTicket
+            }
+            SignalMessage::Ticket { ticket, .. } => break ticket,
+            SignalMessage::Error { message } => return Err(anyhow!("被控端拒绝: {}", message)),
+            _ => continue,
+        };
+        ws.send(encode(&reply)?).await?;
+    };
+
+    let separator = if path.contains('?') { '&' } else { '?' };
+    let result = get(target, &format!("{}{}ticket={}", path, separator, ticket), token).await;
+    let _ = ws.close(None).await;
+    result
+}
+
+/// 发送最简 HTTP/1.1 GET 请求，返回 (状态码, 响应体)
+async fn http_get<S>(mut stream: S, host: &str, path: &str, token: Option<&str>) -> Result<(u16, Vec<u8>)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let authorization = token.map(|token| format!("Authorization: Bearer {}\r\n", token)).unwrap_or_default();
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nAccept: application/json\r\n{}Connection: close\r\n\r\n",
+        path, host, authorization
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    (&mut stream)
+        .take(MAX_RESPONSE_SIZE as u64)
+        .read_to_end(&mut response)
+        .await?;
+
+    parse_http_response(&response)
+}
+
+fn parse_http_response(response: &[u8]) -> Result<(u16, Vec<u8>)> {
+    let header_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| anyhow!("HTTP 响应不完整"))?;
+    let head = std::str::from_utf8(&response[..header_end])
+        .map_err(|_| anyhow!("HTTP 响应头不是有效的 UTF-8"))?;
+    let body = &response[header_end + 4..];
+
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("无效的 HTTP 状态行"))?;
+
+    let chunked = lines.any(|line| {
+        let lower = line.to_ascii_lowercase();
+        lower.starts_with("transfer-encoding:") && lower.contains("chunked")
+    });
+
+    let body = if chunked { decode_chunked(body)? } else { body.to_vec() };
+    Ok((status, body))
+}
+
+/// 解码 chunked 传输编码 (经反向代理时常见)
+fn decode_chunked(mut data: &[u8]) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = data
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(|| anyhow!("chunk 长度行不完整"))?;
+        let size_str = std::str::from_utf8(&data[..line_end])?;
+        let size_str = size_str.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_str, 16)
+            .map_err(|_| anyhow!("无效的 chunk 长度: {}", size_str))?;
+        data = &data[line_end + 2..];
+
+        if size == 0 {
+            return Ok(body);
+        }
+        if data.len() < size {
+            return Err(anyhow!("chunk 数据不完整"));
+        }
+        body.extend_from_slice(&data[..size]);
+        data = data.get(size + 2..).unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_from_url() {
+        let target = ProbeTarget::from_url("wss://demo.trycloudflare.com").unwrap();
+        assert_eq!(target, ProbeTarget { host: "demo.trycloudflare.com".to_string(), port: 443, tls: true });
+
+        let target = ProbeTarget::from_url("ws://192.168.1.5:9527/ws").unwrap();
+        assert_eq!(target, ProbeTarget::from_ip("192.168.1.5", 9527));
+
+        assert!(ProbeTarget::from_url("ftp://example.com").is_err());
+    }
+
+    #[test]
+    fn test_parse_http_response() {
+        let (status, body) =
+            parse_http_response(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}").unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(body, b"{}");
+
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\n{\"a\"\r\n3\r\n:1}\r\n0\r\n\r\n";
+        let (_, body) = parse_http_response(chunked).unwrap();
+        assert_eq!(body, b"{\"a\":1}");
+
+        assert!(parse_http_response(b"garbage").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_probe_embedded_server() {
+        let mut server = crate::signaling::EmbeddedSignalingServer::new(0);
+        let port = server.start().await.unwrap();
+
+        let report = probe(ProbeTarget::from_ip("127.0.0.1", port), Duration::from_secs(5)).await;
+        assert!(report.reachable);
+        assert!(report.rtt_ms.is_some());
+        let caps = report.capabilities.expect("应返回能力信息");
+        assert_eq!(caps, HostCapabilities::local());
+        assert!(caps.is_compatible());
+
+        server.stop();
+    }
+
+    #[tokio::test]
+    async fn test_get_with_ticket() {
+        use crate::signaling::HostSignalEvent;
+        use std::sync::Arc;
+
+        let mut server = crate::signaling::EmbeddedSignalingServer::new(0);
+        let port = server.start().await.unwrap();
+        let mut host_events = server.take_host_events().unwrap();
+        let server = Arc::new(server);
+        let host = server.clone();
+        tokio::spawn(async move {
+            while let Some(event) = host_events.recv().await {
+                if let HostSignalEvent::TicketRequested { from, .. } = event {
+                    host.grant_ticket(&from).await;
+                }
+            }
+        });
+        let target = ProbeTarget::from_ip("127.0.0.1", port);
+
+        let (status, _) = get(&target, "/sysinfo?top=1", None).await.unwrap();
+        assert_eq!(status, 401);
+        let (status, body) = get_with_ticket(&target, "/sysinfo?top=1", None, None, None).await.unwrap();
+        assert_eq!(status, 200);
+        assert!(serde_json::from_slice::<crate::system::SystemReport>(&body).is_ok());
+
+        server.stop();
+    }
+
+    #[tokio::test]
+    async fn test_probe_unreachable() {
+        // 绑定后立即释放，得到一个大概率无人监听的端口
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let report = probe(ProbeTarget::from_ip("127.0.0.1", port), Duration::from_secs(2)).await;
+        assert!(!report.reachable);
+        assert!(report.error.is_some());
+        assert!(!report.is_ready());
+    }
+}
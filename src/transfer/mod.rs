@@ -0,0 +1,205 @@
+//! 文件接收
+//!
+//! 控制端上传 (Web 查看器拖放或选择文件) 的文件写入 Host 的接收目录:
+//! 默认是当前用户的桌面，可以用 `host.incoming_dir` 指定。
+//!
+//! 文件名只保留最后一段路径并替换非法字符，同名文件不会被覆盖 (依次改名为 `name (1).ext`)
+
+#![allow(dead_code)]
+
+use anyhow::{anyhow, Context, Result};
+use futures::{Stream, StreamExt};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// 默认的单个文件大小上限 (MB)
+pub const DEFAULT_MAX_UPLOAD_MB: u64 = 1024;
+
+/// 同名文件最多尝试的改名次数
+const MAX_RENAME_ATTEMPTS: u32 = 1000;
+
+/// 已接收的文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceivedFile {
+    /// 保存的路径
+    pub path: PathBuf,
+    /// 字节数
+    pub bytes: u64,
+}
+
+impl ReceivedFile {
+    /// 保存后的文件名 (可能因重名改过)
+    pub fn name(&self) -> String {
+        self.path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+}
+
+/// 文件超过大小上限
+#[derive(Debug)]
+pub struct TooLarge {
+    pub limit: u64,
+}
+
+impl Display for TooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "文件超过 {} MB 上限", self.limit / (1024 * 1024))
+    }
+}
+
+impl std::error::Error for TooLarge {}
+
+/// 接收目录
+#[derive(Debug, Clone)]
+pub struct IncomingFiles {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl Default for IncomingFiles {
+    fn default() -> Self {
+        Self::new(None, DEFAULT_MAX_UPLOAD_MB)
+    }
+}
+
+impl IncomingFiles {
+    /// `dir` 为 None 时使用桌面 (没有桌面目录时使用当前目录)
+    pub fn new(dir: Option<&str>, max_upload_mb: u64) -> Self {
+        let dir = match dir {
+            Some(dir) => PathBuf::from(dir),
+            None => desktop_dir().unwrap_or_else(|| PathBuf::from(".")),
+        };
+        Self { dir, max_bytes: max_upload_mb.saturating_mul(1024 * 1024) }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 把上传的数据流写入接收目录
+    ///
+    /// 写入失败或超过大小上限时删除已写入的部分，超限时错误为 [`TooLarge`]
+    pub async fn receive<S, B, E>(&self, name: &str, mut body: S) -> Result<ReceivedFile>
+    where
+        S: Stream<Item = std::result::Result<B, E>> + Unpin,
+        B: AsRef<[u8]>,
+        E: Display,
+    {
+        let name = sanitize_name(name).ok_or_else(|| anyhow!("无效的文件名: {:?}", name))?;
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("创建接收目录 {} 失败", self.dir.display()))?;
+        let (path, mut file) = self.create_unique(&name).await?;
+
+        let mut bytes = 0u64;
+        let result: Result<()> = async {
+            while let Some(chunk) = body.next().await {
+                let chunk = chunk.map_err(|e| anyhow!("接收文件数据失败: {}", e))?;
+                let chunk = chunk.as_ref();
+                bytes += chunk.len() as u64;
+                if bytes > self.max_bytes {
+                    return Err(TooLarge { limit: self.max_bytes }.into());
+                }
+                file.write_all(chunk).await?;
+            }
+            file.flush().await?;
+            Ok(())
+        }
+        .await;
+
+        if let Err(e) = result {
+            drop(file);
+            let _ = tokio::fs::remove_file(&path).await;
+            return Err(e);
+        }
+        tracing::info!("已接收文件 {} ({} 字节)", path.display(), bytes);
+        Ok(ReceivedFile { path, bytes })
+    }
+
+    /// 以不覆盖的方式创建文件 (重名时依次尝试 `name (1).ext`、`name (2).ext`...)
+    async fn create_unique(&self, name: &str) -> Result<(PathBuf, tokio::fs::File)> {
+        for attempt in 0..MAX_RENAME_ATTEMPTS {
+            let path = self.dir.join(numbered_name(name, attempt));
+            match tokio::fs::OpenOptions::new().write(true).create_new(true).open(&path).await {
+                Ok(file) => return Ok((path, file)),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(anyhow!("创建文件 {} 失败: {}", path.display(), e)),
+            }
+        }
+        Err(anyhow!("接收目录中同名文件过多: {}", name))
+    }
+}
+
+/// 当前用户的桌面目录
+pub fn desktop_dir() -> Option<PathBuf> {
+    let home = if cfg!(target_os = "windows") { std::env::var_os("USERPROFILE") } else { std::env::var_os("HOME") }?;
+    let desktop = PathBuf::from(home).join("Desktop");
+    desktop.is_dir().then_some(desktop)
+}
+
+/// 清理控制端提供的文件名: 只保留最后一段路径，替换控制字符和 Windows 不允许的字符
+pub fn sanitize_name(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = name
+        .chars()
+        .map(|c| if c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*') { '_' } else { c })
+        .collect();
+    // Windows 会去掉结尾的点和空格
+    let cleaned = cleaned.trim().trim_end_matches('.').to_string();
+    (!cleaned.is_empty() && cleaned != "..").then_some(cleaned)
+}
+
+/// 第 `attempt` 次尝试的文件名 (0 = 原名)
+fn numbered_name(name: &str, attempt: u32) -> String {
+    if attempt == 0 {
+        return name.to_string();
+    }
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{} ({}).{}", stem, attempt, ext),
+        _ => format!("{} ({})", name, attempt),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_name() {
+        assert_eq!(sanitize_name("report.pdf").as_deref(), Some("report.pdf"));
+        assert_eq!(sanitize_name("../../etc/passwd").as_deref(), Some("passwd"));
+        assert_eq!(sanitize_name("C:\\Users\\a\\b?.txt").as_deref(), Some("b_.txt"));
+        assert_eq!(sanitize_name("notes. ").as_deref(), Some("notes"));
+        assert_eq!(sanitize_name(".."), None);
+        assert_eq!(sanitize_name("dir/"), None);
+    }
+
+    #[test]
+    fn test_numbered_name() {
+        assert_eq!(numbered_name("photo.jpg", 0), "photo.jpg");
+        assert_eq!(numbered_name("photo.jpg", 2), "photo (2).jpg");
+        assert_eq!(numbered_name("Makefile", 1), "Makefile (1)");
+        assert_eq!(numbered_name(".bashrc", 1), ".bashrc (1)");
+    }
+
+    #[tokio::test]
+    async fn test_receive_does_not_overwrite() {
+        let dir = std::env::temp_dir().join(format!("sscontrol-incoming-{}", std::process::id()));
+        let incoming = IncomingFiles::new(dir.to_str(), 1);
+        let chunks = || futures::stream::iter(vec![Ok::<_, std::io::Error>(b"hello ".to_vec()), Ok(b"world".to_vec())]);
+
+        let first = incoming.receive("a.txt", chunks()).await.unwrap();
+        let second = incoming.receive("a.txt", chunks()).await.unwrap();
+        assert_eq!(first.bytes, 11);
+        assert_eq!(second.name(), "a (1).txt");
+        assert_eq!(std::fs::read_to_string(&first.path).unwrap(), "hello world");
+
+        // 超过上限时不留下不完整的文件
+        let big = futures::stream::iter(vec![Ok::<_, std::io::Error>(vec![0u8; 2 * 1024 * 1024])]);
+        let error = incoming.receive("big.bin", big).await.unwrap_err();
+        assert!(error.downcast_ref::<TooLarge>().is_some());
+        assert!(!dir.join("big.bin").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,81 +1,54 @@
-//! Cloudflare Tunnel 实现
+//! Cloudflare Quick Tunnel
 //!
-//! 使用 cloudflared crate 创建 Quick Tunnel
+//! 运行 `cloudflared tunnel --url http://localhost:<端口>`，无需 Cloudflare 账号。
+//! 公网地址只在 cloudflared 的日志 (stderr) 中打印一次，每次重启都会变化
 
-#![allow(dead_code)]
+use super::TunnelProvider;
+use tokio::process::Command;
 
-use anyhow::{anyhow, Result};
-use cloudflared::Tunnel;
-use tracing::{info, warn, debug};
+/// Quick Tunnel 的域名
+const QUICK_TUNNEL_DOMAIN: &str = ".trycloudflare.com";
 
-/// Cloudflare Tunnel 包装器
-pub struct CloudflareTunnel {
-    tunnel: Option<Tunnel>,
-    public_url: Option<String>,
-}
+/// cloudflared
+pub struct Cloudflared;
 
-impl CloudflareTunnel {
-    /// 创建新的 Cloudflare Tunnel 实例
-    pub fn new() -> Self {
-        Self {
-            tunnel: None,
-            public_url: None,
-        }
+impl TunnelProvider for Cloudflared {
+    fn name(&self) -> &'static str {
+        "cloudflared"
     }
 
-    /// 启动隧道，指向本地端口
-    ///
-    /// 返回公网 WebSocket URL (wss://xxx.trycloudflare.com)
-    pub fn start(&mut self, local_port: u16) -> Result<String> {
-        let local_url = format!("http://localhost:{}", local_port);
-
-        info!("正在创建 Cloudflare Tunnel: {}", local_url);
-
-        let tunnel = Tunnel::builder()
-            .url(&local_url)
-            .build()
-            .map_err(|e| anyhow!("创建 Cloudflare Tunnel 失败: {}", e))?;
-
-        let public_url = tunnel.url().to_string();
-        info!("Cloudflare Tunnel 公网地址: {}", public_url);
-
-        // 等待隧道稳定建立
-        debug!("等待隧道连接稳定...");
-        std::thread::sleep(std::time::Duration::from_secs(2));
-        debug!("隧道应已稳定");
-
-        // 将 https:// 转换为 wss:// (用于 WebSocket)
-        let ws_url = public_url.replace("https://", "wss://");
-
-        self.tunnel = Some(tunnel);
-        self.public_url = Some(ws_url.clone());
-
-        Ok(ws_url)
-    }
-
-    /// 获取公网 WebSocket URL
-    pub fn url(&self) -> Option<&str> {
-        self.public_url.as_deref()
+    fn command(&self, local_port: u16) -> Command {
+        let mut command = Command::new("cloudflared");
+        command
+            .args(["tunnel", "--no-autoupdate", "--url"])
+            .arg(format!("http://localhost:{}", local_port));
+        command
     }
 
-    /// 检查隧道是否正在运行
-    pub fn is_running(&self) -> bool {
-        self.tunnel.is_some()
-    }
-}
-
-impl Default for CloudflareTunnel {
-    fn default() -> Self {
-        Self::new()
+    fn parse_url(&self, line: &str) -> Option<String> {
+        line.split(|c: char| c.is_whitespace() || c == '|')
+            .find(|token| {
+                token.starts_with("https://")
+                    && token.ends_with(QUICK_TUNNEL_DOMAIN)
+                    // 日志中也会出现申请隧道的 API 地址
+                    && !token.starts_with("https://api.")
+            })
+            .map(str::to_string)
     }
 }
 
-impl Drop for CloudflareTunnel {
-    fn drop(&mut self) {
-        if self.tunnel.is_some() {
-            warn!("Cloudflare Tunnel 正在关闭...");
-            // Tunnel 会在 drop 时自动关闭
-            self.tunnel.take();
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quick_tunnel_url() {
+        let lines = [
+            "2026-10-17T08:00:00Z INF Requesting new quick Tunnel on trycloudflare.com...",
+            "2026-10-17T08:00:01Z INF |  https://plain-river-1234.trycloudflare.com                                    |",
+            "2026-10-17T08:00:00Z ERR failed to request https://api.trycloudflare.com",
+        ];
+        let urls: Vec<_> = lines.iter().filter_map(|line| Cloudflared.parse_url(line)).collect();
+        assert_eq!(urls, ["https://plain-river-1234.trycloudflare.com"]);
     }
 }
@@ -1,9 +1,92 @@
 //! 公网隧道模块
 //!
-//! 提供 Cloudflare Tunnel 支持，使被控端可以通过公网地址被访问
+//! 通过外部隧道程序把本机信令端口暴露到公网:
+//! - cloudflared: Cloudflare Quick Tunnel (`https://*.trycloudflare.com`)
+//! - ngrok: ngrok HTTP 隧道
+//! - ssh: `ssh -R` 反向隧道到自己的服务器
+//!
+//! 隧道程序由 [`TunnelSupervisor`] 管理: 退出后自动重启，经公网地址做健康检查，
+//! 地址变化时通知控制台并写入配置文件的 `tunnel.public_url`
 
-#[cfg(feature = "tunnel")]
 mod cloudflare;
+mod ngrok;
+mod ssh;
+mod supervisor;
+
+pub use cloudflare::Cloudflared;
+pub use ngrok::Ngrok;
+pub use ssh::SshReverse;
+pub use supervisor::{TunnelHandle, TunnelSupervisor};
+
+use crate::config::{TunnelConfig, TUNNEL_PROVIDERS};
+use anyhow::{anyhow, Result};
+use tokio::process::Command;
+
+/// 隧道程序
+pub trait TunnelProvider: Send + Sync {
+    /// 名称 (与 `tunnel.provider` 的取值相同)
+    fn name(&self) -> &'static str;
+
+    /// 把本地端口暴露到公网的命令
+    fn command(&self, local_port: u16) -> Command;
+
+    /// 从隧道程序的一行输出中解析公网地址 (https://...)
+    fn parse_url(&self, line: &str) -> Option<String>;
+
+    /// 启动前就能确定的公网地址 (不在输出中打印地址的隧道程序)
+    fn fixed_url(&self, _local_port: u16) -> Option<String> {
+        None
+    }
+}
+
+/// 按配置创建隧道程序 (`name` 为命令行指定的程序，优先于 `tunnel.provider`)
+pub fn provider(config: &TunnelConfig, name: Option<&str>) -> Result<Box<dyn TunnelProvider>> {
+    match name.unwrap_or(&config.provider) {
+        "cloudflared" => Ok(Box::new(Cloudflared)),
+        "ngrok" => Ok(Box::new(Ngrok)),
+        "ssh" => {
+            let target = config
+                .ssh_target
+                .clone()
+                .filter(|target| !target.trim().is_empty())
+                .ok_or_else(|| anyhow!("ssh 隧道需要在配置文件中设置 tunnel.ssh_target"))?;
+            Ok(Box::new(SshReverse::new(target, config.ssh_remote_port, config.ssh_public_url.clone())))
+        }
+        other => Err(anyhow!("未知的隧道程序 \"{}\" (可选 {})", other, TUNNEL_PROVIDERS.join(" / "))),
+    }
+}
+
+/// 公网地址转为 WebSocket 地址 (https → wss，http → ws)
+pub fn websocket_url(url: &str) -> String {
+    if let Some(rest) = url.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = url.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else {
+        url.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_selection() {
+        let config = TunnelConfig::default();
+        assert_eq!(provider(&config, None).unwrap().name(), "cloudflared");
+        assert_eq!(provider(&config, Some("ngrok")).unwrap().name(), "ngrok");
+        assert!(provider(&config, Some("ssh")).is_err());
+        assert!(provider(&config, Some("frp")).is_err());
+
+        let config = TunnelConfig { ssh_target: Some("me@relay.example.com".to_string()), ..Default::default() };
+        let ssh = provider(&config, Some("ssh")).unwrap();
+        assert_eq!(ssh.fixed_url(8443).as_deref(), Some("http://relay.example.com:8443"));
+    }
 
-#[cfg(feature = "tunnel")]
-pub use cloudflare::CloudflareTunnel;
+    #[test]
+    fn test_websocket_url() {
+        assert_eq!(websocket_url("https://a.trycloudflare.com"), "wss://a.trycloudflare.com");
+        assert_eq!(websocket_url("http://relay.example.com:8443"), "ws://relay.example.com:8443");
+    }
+}
@@ -0,0 +1,45 @@
+//! ngrok HTTP 隧道
+//!
+//! 运行 `ngrok http <端口>`，日志以 logfmt 格式输出到 stdout，
+//! 隧道建立后的 `started tunnel` 日志中带有公网地址。需要事先 `ngrok config add-authtoken`
+
+use super::TunnelProvider;
+use tokio::process::Command;
+
+/// ngrok
+pub struct Ngrok;
+
+impl TunnelProvider for Ngrok {
+    fn name(&self) -> &'static str {
+        "ngrok"
+    }
+
+    fn command(&self, local_port: u16) -> Command {
+        let mut command = Command::new("ngrok");
+        command
+            .arg("http")
+            .arg(local_port.to_string())
+            .args(["--log", "stdout", "--log-format", "logfmt"]);
+        command
+    }
+
+    fn parse_url(&self, line: &str) -> Option<String> {
+        line.split_whitespace()
+            .filter_map(|field| field.strip_prefix("url="))
+            .map(|url| url.trim_matches('"'))
+            .find(|url| url.starts_with("https://"))
+            .map(str::to_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_started_tunnel() {
+        let line = r#"t=2026-10-17T08:00:01+0000 lvl=info msg="started tunnel" obj=tunnels name=command_line addr=http://localhost:8443 url=https://3f2a-203-0-113-7.ngrok-free.app"#;
+        assert_eq!(Ngrok.parse_url(line).as_deref(), Some("https://3f2a-203-0-113-7.ngrok-free.app"));
+        assert_eq!(Ngrok.parse_url(r#"t=2026-10-17 lvl=info msg="client session established" url=http://127.0.0.1:4040"#), None);
+    }
+}
@@ -0,0 +1,86 @@
+//! SSH 反向隧道
+//!
+//! 运行 `ssh -N -R <远程端口>:localhost:<本地端口> <目标>`，把本地信令端口转发到自己的服务器。
+//! 公网地址是固定的 (`tunnel.ssh_public_url`，默认 `http://<主机>:<远程端口>`)；
+//! 远程端口要对外可访问，需要服务器的 sshd 设置 `GatewayPorts yes` 或在前面加反向代理。
+//! 认证使用 ssh 自己的配置 (密钥、ssh-agent、~/.ssh/config)，不支持交互式输入密码
+
+use super::TunnelProvider;
+use tokio::process::Command;
+
+/// SSH 反向隧道
+pub struct SshReverse {
+    /// `user@host`、`host` 或 ~/.ssh/config 中的别名
+    target: String,
+    /// 服务器上监听的端口 (None = 与本地端口相同)
+    remote_port: Option<u16>,
+    /// 对外地址 (None = http://<主机>:<远程端口>)
+    public_url: Option<String>,
+}
+
+impl SshReverse {
+    pub fn new(target: String, remote_port: Option<u16>, public_url: Option<String>) -> Self {
+        Self { target, remote_port, public_url }
+    }
+
+    /// 目标中的主机名 (去掉用户名和端口)
+    fn host(&self) -> &str {
+        let host = self.target.rsplit('@').next().unwrap_or(&self.target);
+        host.split(':').next().unwrap_or(host)
+    }
+}
+
+impl TunnelProvider for SshReverse {
+    fn name(&self) -> &'static str {
+        "ssh"
+    }
+
+    fn command(&self, local_port: u16) -> Command {
+        let remote_port = self.remote_port.unwrap_or(local_port);
+        let mut command = Command::new("ssh");
+        command
+            .arg("-N")
+            .arg("-R")
+            .arg(format!("{}:localhost:{}", remote_port, local_port))
+            .args([
+                "-o",
+                "ExitOnForwardFailure=yes",
+                "-o",
+                "ServerAliveInterval=15",
+                "-o",
+                "ServerAliveCountMax=3",
+                "-o",
+                "BatchMode=yes",
+            ])
+            .arg(&self.target);
+        command
+    }
+
+    fn parse_url(&self, _line: &str) -> Option<String> {
+        None
+    }
+
+    fn fixed_url(&self, local_port: u16) -> Option<String> {
+        Some(self.public_url.clone().unwrap_or_else(|| {
+            format!("http://{}:{}", self.host(), self.remote_port.unwrap_or(local_port))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reverse_forward_command() {
+        let ssh = SshReverse::new("deploy@relay.example.com".to_string(), Some(9000), None);
+        let command = ssh.command(8443);
+        let args: Vec<_> = command.as_std().get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        assert_eq!(&args[..3], ["-N", "-R", "9000:localhost:8443"]);
+        assert_eq!(args.last().map(String::as_str), Some("deploy@relay.example.com"));
+        assert_eq!(ssh.fixed_url(8443).as_deref(), Some("http://relay.example.com:9000"));
+
+        let proxied = SshReverse::new("relay".to_string(), None, Some("https://remote.example.com".to_string()));
+        assert_eq!(proxied.fixed_url(8443).as_deref(), Some("https://remote.example.com"));
+    }
+}
@@ -0,0 +1,316 @@
+//! 隧道进程的生命周期
+//!
+//! - 隧道程序退出后按指数退避重启 (1 秒起，最长 30 秒；稳定运行一分钟后退避清零)
+//! - 按 `tunnel.health_check_secs` 经公网地址请求信令服务器的 `/health`，
+//!   连续失败 [`MAX_HEALTH_FAILURES`] 次后重启隧道程序
+//! - 公网地址变化时 (Quick Tunnel 每次重启都会换地址) 在控制台打印新的连接命令，
+//!   并写入配置文件的 `tunnel.public_url`
+//! - 找不到隧道程序时不再重试
+
+use super::{websocket_url, TunnelProvider};
+use crate::config::edit;
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+/// 首次重启前的等待时间
+const BACKOFF: Duration = Duration::from_secs(1);
+
+/// 重启等待时间上限
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// 运行超过该时间后退避清零
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+/// 健康检查连续失败该次数后重启隧道
+const MAX_HEALTH_FAILURES: u32 = 3;
+
+/// 单次健康检查的超时
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 隧道程序的一次运行如何结束
+enum Exit {
+    /// 进程退出或健康检查失败，需要重启
+    Restart(String),
+    /// 无法启动 (隧道程序不存在等)，不再重试
+    Fatal(anyhow::Error),
+}
+
+/// 隧道进程管理
+pub struct TunnelSupervisor {
+    provider: Box<dyn TunnelProvider>,
+    local_port: u16,
+    /// 健康检查间隔 (None = 不检查)
+    health_check: Option<Duration>,
+    /// 记录公网地址的配置文件 (None = 不记录)
+    config_path: Option<PathBuf>,
+}
+
+/// 运行中的隧道
+pub struct TunnelHandle {
+    /// 管理任务 (交给 Supervisor 统一终止，终止时隧道进程随之退出)
+    pub task: JoinHandle<()>,
+    provider: &'static str,
+    urls: watch::Receiver<Option<String>>,
+}
+
+impl TunnelHandle {
+    /// 隧道程序名称
+    pub fn provider(&self) -> &'static str {
+        self.provider
+    }
+
+    /// 当前的公网地址
+    pub fn url(&self) -> Option<String> {
+        self.urls.borrow().clone()
+    }
+
+    /// 等待隧道程序报告公网地址
+    pub async fn wait_for_url(&mut self, timeout: Duration) -> Option<String> {
+        let urls = &mut self.urls;
+        tokio::time::timeout(timeout, urls.wait_for(Option::is_some)).await.ok()?.ok()?.clone()
+    }
+}
+
+impl TunnelSupervisor {
+    pub fn new(provider: Box<dyn TunnelProvider>, local_port: u16) -> Self {
+        Self { provider, local_port, health_check: None, config_path: None }
+    }
+
+    /// 设置健康检查间隔 (0 = 不检查)
+    pub fn with_health_check(mut self, interval: Duration) -> Self {
+        self.health_check = (!interval.is_zero()).then_some(interval);
+        self
+    }
+
+    /// 公网地址变化时写入该配置文件 (文件不存在时不写)
+    pub fn with_config_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_path = Some(path.into());
+        self
+    }
+
+    /// 启动隧道程序并在后台管理
+    pub fn spawn(self) -> TunnelHandle {
+        let (url_tx, urls) = watch::channel(None);
+        let provider = self.provider.name();
+        let task = tokio::spawn(self.run(url_tx));
+        TunnelHandle { task, provider, urls }
+    }
+
+    async fn run(self, url_tx: watch::Sender<Option<String>>) {
+        let mut failures = 0u32;
+        loop {
+            let started = Instant::now();
+            let reason = match self.run_once(&url_tx).await {
+                Exit::Restart(reason) => reason,
+                Exit::Fatal(e) => {
+                    error!("隧道 {} 无法启动: {}", self.provider.name(), e);
+                    return;
+                }
+            };
+            if started.elapsed() >= STABLE_RUN {
+                failures = 0;
+            }
+            let delay = BACKOFF.saturating_mul(1u32 << failures.min(16)).min(MAX_BACKOFF);
+            failures += 1;
+            warn!("隧道 {} 已断开 ({})，{} 秒后重启", self.provider.name(), reason, delay.as_secs());
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// 运行一次隧道程序，直到进程退出或健康检查失败
+    async fn run_once(&self, url_tx: &watch::Sender<Option<String>>) -> Exit {
+        let mut command = self.provider.command(self.local_port);
+        command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).kill_on_drop(true);
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Exit::Fatal(anyhow!("找不到 {}，请先安装并加入 PATH", self.provider.name()));
+            }
+            Err(e) => return Exit::Fatal(e.into()),
+        };
+        info!("隧道 {} 已启动 (pid {:?})", self.provider.name(), child.id());
+
+        // stdout 和 stderr 合并逐行读取 (cloudflared 把日志写到 stderr)
+        let (line_tx, mut lines) = mpsc::unbounded_channel();
+        if let Some(stdout) = child.stdout.take() {
+            forward_lines(stdout, line_tx.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            forward_lines(stderr, line_tx);
+        }
+
+        if let Some(url) = self.provider.fixed_url(self.local_port) {
+            self.publish(url_tx, url);
+        }
+
+        let client = reqwest::Client::builder().timeout(HEALTH_TIMEOUT).build();
+        let mut health_ticker = tokio::time::interval(self.health_check.unwrap_or(Duration::MAX));
+        health_ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // 第一次立即触发，跳过 (刚启动的隧道 DNS 可能还未生效)
+        health_ticker.tick().await;
+        let mut health_failures = 0u32;
+
+        loop {
+            tokio::select! {
+                Some(line) = lines.recv() => {
+                    debug!("[{}] {}", self.provider.name(), line);
+                    if let Some(url) = self.provider.parse_url(&line) {
+                        self.publish(url_tx, url);
+                    }
+                }
+                status = child.wait() => {
+                    return Exit::Restart(match status {
+                        Ok(status) => format!("进程退出: {}", status),
+                        Err(e) => format!("等待进程失败: {}", e),
+                    });
+                }
+                _ = health_ticker.tick(), if self.health_check.is_some() => {
+                    let Some(url) = url_tx.borrow().clone() else { continue };
+                    let Ok(client) = &client else { continue };
+                    match check_health(client, &url).await {
+                        Ok(()) => health_failures = 0,
+                        Err(e) => {
+                            health_failures += 1;
+                            warn!("隧道健康检查失败 ({}/{}): {}", health_failures, MAX_HEALTH_FAILURES, e);
+                            if health_failures >= MAX_HEALTH_FAILURES {
+                                let _ = child.kill().await;
+                                return Exit::Restart("公网地址无法访问".to_string());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// 发布公网地址: 与上次不同时通知控制台并写入配置文件
+    fn publish(&self, url_tx: &watch::Sender<Option<String>>, url: String) {
+        let previous = url_tx.borrow().clone();
+        if previous.as_deref() == Some(url.as_str()) {
+            return;
+        }
+        info!("隧道 {} 公网地址: {}", self.provider.name(), url);
+        if previous.is_some() {
+            println!();
+            println!("[~] 公网地址已变更 ({}):", self.provider.name());
+            println!("  sscontrol connect --url {}", websocket_url(&url));
+            println!();
+        }
+        if let Some(path) = &self.config_path {
+            if let Err(e) = record_public_url(path, &url) {
+                warn!("无法把公网地址写入 {:?}: {}", path, e);
+            }
+        }
+        url_tx.send_replace(Some(url));
+    }
+}
+
+/// 逐行转发进程输出
+fn forward_lines(reader: impl AsyncRead + Unpin + Send + 'static, tx: mpsc::UnboundedSender<String>) {
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// 经公网地址请求信令服务器的 `/health`
+async fn check_health(client: &reqwest::Client, url: &str) -> Result<()> {
+    let response = client.get(format!("{}/health", url.trim_end_matches('/'))).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("HTTP {}", response.status()));
+    }
+    Ok(())
+}
+
+/// 把公网地址写入配置文件的 `tunnel.public_url` (文件不存在时跳过)
+fn record_public_url(path: &std::path::Path, url: &str) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let content = std::fs::read_to_string(path)?;
+    let (content, _) = edit::set_value(&content, "tunnel.public_url", &format!("\"{}\"", url))?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::process::Command;
+
+    /// 每次启动打印不同地址后立即退出
+    struct Flaky {
+        runs: AtomicUsize,
+    }
+
+    impl TunnelProvider for Flaky {
+        fn name(&self) -> &'static str {
+            "flaky"
+        }
+
+        fn command(&self, _local_port: u16) -> Command {
+            let run = self.runs.fetch_add(1, Ordering::SeqCst);
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(format!("echo tunnel ready at https://run-{}.example.com >&2; sleep 0.2", run));
+            command
+        }
+
+        fn parse_url(&self, line: &str) -> Option<String> {
+            line.split_whitespace().find(|token| token.starts_with("https://")).map(str::to_string)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restarts_and_records_new_url() {
+        let dir = std::env::temp_dir().join(format!("sscontrol-tunnel-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("config.toml");
+        crate::config::Config::default().save(&config_path).unwrap();
+
+        let mut handle = TunnelSupervisor::new(Box::new(Flaky { runs: AtomicUsize::new(0) }), 8443)
+            .with_config_path(&config_path)
+            .spawn();
+        assert_eq!(handle.wait_for_url(Duration::from_secs(5)).await.as_deref(), Some("https://run-0.example.com"));
+
+        // 进程退出后 1 秒重启，地址随之变化
+        tokio::time::timeout(Duration::from_secs(5), handle.urls.wait_for(|url| url.as_deref() == Some("https://run-1.example.com")))
+            .await
+            .unwrap()
+            .unwrap();
+        handle.task.abort();
+
+        let config = crate::config::Config::load_unchecked(&config_path).unwrap();
+        assert!(config.tunnel.public_url.is_some_and(|url| url.starts_with("https://run-")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_missing_binary_is_not_retried() {
+        struct Missing;
+        impl TunnelProvider for Missing {
+            fn name(&self) -> &'static str {
+                "missing"
+            }
+            fn command(&self, _local_port: u16) -> Command {
+                Command::new("sscontrol-no-such-tunnel-binary")
+            }
+            fn parse_url(&self, _line: &str) -> Option<String> {
+                None
+            }
+        }
+
+        let handle = TunnelSupervisor::new(Box::new(Missing), 8443).spawn();
+        tokio::time::timeout(Duration::from_secs(2), handle.task).await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,300 @@
+//! 自动更新 (需要 update feature)
+//!
+//! 从发布地址 (默认 GitHub Releases) 检查新版本，下载当前平台的可执行文件和签名文件，
+//! 用配置的 Ed25519 公钥校验后原子替换正在运行的可执行文件，再重启服务:
+//! - `release`: 发布信息、版本比较和附件选择
+//! - `swap`: 原子替换可执行文件
+//!
+//! 签名文件 `<可执行文件名>.sig` 的内容是对可执行文件全部字节的 Ed25519 签名 (十六进制或 Base64)
+
+#![allow(dead_code)]
+
+pub mod release;
+pub mod swap;
+
+pub use release::{Asset, Release};
+
+use crate::config::UpdateConfig;
+use anyhow::{anyhow, bail, Result};
+use base64::Engine;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// 当前版本
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// 自动更新后退出进程的退出码
+///
+/// 非零退出码让 launchd (KeepAlive.SuccessfulExit = false)、systemd 和 Windows 服务重新启动新版本
+pub const RESTART_EXIT_CODE: i32 = 75;
+
+/// 下载大小上限
+const MAX_DOWNLOAD_BYTES: u64 = 256 * 1024 * 1024;
+
+/// 可安装的新版本
+#[derive(Debug, Clone)]
+pub struct UpdateInfo {
+    /// 新版本号
+    pub version: String,
+    pub release: Release,
+    /// 当前平台的可执行文件
+    pub binary: Asset,
+    /// 签名文件
+    pub signature: Asset,
+}
+
+/// 更新器
+pub struct Updater {
+    config: UpdateConfig,
+    http: reqwest::Client,
+}
+
+impl Updater {
+    pub fn new(config: UpdateConfig) -> Result<Self> {
+        // GitHub API 要求设置 User-Agent
+        let http = reqwest::Client::builder()
+            .user_agent(format!("sscontrol/{}", CURRENT_VERSION))
+            .timeout(Duration::from_secs(300))
+            .connect_timeout(Duration::from_secs(10))
+            .build()?;
+        Ok(Self { config, http })
+    }
+
+    /// 检查新版本 (已是最新时返回 None)
+    pub async fn check(&self) -> Result<Option<UpdateInfo>> {
+        let release: Release = self
+            .http
+            .get(&self.config.url)
+            .header("Accept", "application/vnd.github+json")
+            .send()
+            .await
+            .map_err(|e| anyhow!("获取发布信息失败: {}", e))?
+            .error_for_status()
+            .map_err(|e| anyhow!("获取发布信息失败: {}", e))?
+            .json()
+            .await
+            .map_err(|e| anyhow!("解析发布信息失败: {}", e))?;
+
+        if !release::is_newer(release.version(), CURRENT_VERSION) {
+            tracing::debug!("已是最新版本 {} (最新发布 {})", CURRENT_VERSION, release.tag_name);
+            return Ok(None);
+        }
+
+        let binary = release.binary_asset().cloned().ok_or_else(|| {
+            anyhow!(
+                "版本 {} 没有 {}-{} 平台的可执行文件",
+                release.tag_name,
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            )
+        })?;
+        let signature = release
+            .signature_asset(&binary)
+            .cloned()
+            .ok_or_else(|| anyhow!("版本 {} 没有 {} 的签名文件", release.tag_name, binary.name))?;
+
+        Ok(Some(UpdateInfo {
+            version: release.version().to_string(),
+            release,
+            binary,
+            signature,
+        }))
+    }
+
+    /// 下载、校验并替换当前可执行文件，返回被替换的路径
+    pub async fn install(&self, update: &UpdateInfo) -> Result<PathBuf> {
+        let public_key = self
+            .config
+            .public_key
+            .as_deref()
+            .ok_or_else(|| anyhow!("未配置 update.public_key，无法校验签名，不安装更新"))?;
+
+        if update.binary.size > MAX_DOWNLOAD_BYTES {
+            bail!("{} 太大 ({} 字节)", update.binary.name, update.binary.size);
+        }
+        tracing::info!("下载 {} ({})", update.binary.name, update.version);
+        let binary = self.download(&update.binary).await?;
+        let signature = self.download(&update.signature).await?;
+        verify_signature(public_key, &binary, &String::from_utf8_lossy(&signature))?;
+        tracing::info!("签名校验通过");
+
+        let exe = std::env::current_exe().map_err(|e| anyhow!("获取可执行文件路径失败: {}", e))?;
+        let exe = exe.canonicalize().unwrap_or(exe);
+        swap::replace_executable(&exe, &binary)?;
+        tracing::info!("已更新到 {}: {}", update.version, exe.display());
+        Ok(exe)
+    }
+
+    async fn download(&self, asset: &Asset) -> Result<Vec<u8>> {
+        let response = self
+            .http
+            .get(&asset.browser_download_url)
+            .header("Accept", "application/octet-stream")
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| anyhow!("下载 {} 失败: {}", asset.name, e))?;
+        read_limited(response, &asset.name, MAX_DOWNLOAD_BYTES).await
+    }
+}
+
+/// 分块读取响应体，累计超过 `limit` 字节时中止 (服务器不一定发送 Content-Length)
+async fn read_limited(mut response: reqwest::Response, name: &str, limit: u64) -> Result<Vec<u8>> {
+    if response.content_length().is_some_and(|length| length > limit) {
+        bail!("{} 太大", name);
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| anyhow!("下载 {} 失败: {}", name, e))? {
+        if (body.len() + chunk.len()) as u64 > limit {
+            bail!("{} 太大 (超过 {} 字节)", name, limit);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// 校验 Ed25519 签名
+///
+/// `public_key` 为十六进制的 32 字节公钥，`signature` 为十六进制或 Base64 的 64 字节签名
+pub fn verify_signature(public_key: &str, data: &[u8], signature: &str) -> Result<()> {
+    let key: [u8; 32] = hex::decode(public_key.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("update.public_key 不是 32 字节的十六进制 Ed25519 公钥"))?;
+    let key = VerifyingKey::from_bytes(&key).map_err(|e| anyhow!("update.public_key 无效: {}", e))?;
+
+    let signature = signature.trim();
+    let bytes: [u8; 64] = hex::decode(signature)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .or_else(|| {
+            base64::engine::general_purpose::STANDARD
+                .decode(signature)
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+        })
+        .ok_or_else(|| anyhow!("签名格式无效 (应为 64 字节的十六进制或 Base64)"))?;
+
+    key.verify(data, &Signature::from_bytes(&bytes))
+        .map_err(|_| anyhow!("签名校验失败，拒绝安装"))
+}
+
+/// 重启已安装且正在运行的服务 (返回是否重启)
+pub fn restart_service() -> Result<bool> {
+    use crate::service::{ServiceController, ServiceStatus};
+
+    fn restart(controller: &impl ServiceController) -> Result<bool> {
+        if !controller.is_installed() || controller.status()? != ServiceStatus::Running {
+            return Ok(false);
+        }
+        controller.stop()?;
+        // 等待服务完全停止后再启动
+        for _ in 0..20 {
+            if controller.status()? != ServiceStatus::Running {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(500));
+        }
+        controller.start()?;
+        Ok(true)
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let agent = restart(&crate::service::macos::MacOSLaunchAgent::new())?;
+        let daemon = restart(&crate::service::macos::MacOSLaunchAgent::daemon())?;
+        Ok(agent || daemon)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        restart(&crate::service::create_controller())
+    }
+}
+
+/// 服务模式下定期检查并安装更新
+///
+/// 安装成功后以 [`RESTART_EXIT_CODE`] 退出进程，由服务管理器启动新版本
+pub async fn run_auto_update(config: UpdateConfig) {
+    if config.auto_update_hours == 0 {
+        return;
+    }
+    if let Ok(exe) = std::env::current_exe() {
+        swap::cleanup_backup(&exe);
+    }
+
+    let interval = Duration::from_secs(config.auto_update_hours * 3600);
+    tracing::info!("已启用自动更新，每 {} 小时检查一次", config.auto_update_hours);
+
+    let updater = match Updater::new(config) {
+        Ok(updater) => updater,
+        Err(e) => {
+            tracing::warn!("自动更新不可用: {}", e);
+            return;
+        }
+    };
+
+    loop {
+        tokio::time::sleep(interval).await;
+        match updater.check().await {
+            Ok(Some(update)) => match updater.install(&update).await {
+                Ok(_) => {
+                    tracing::info!("已安装 {}，退出以启动新版本", update.version);
+                    std::process::exit(RESTART_EXIT_CODE);
+                }
+                Err(e) => tracing::warn!("安装更新 {} 失败: {}", update.version, e),
+            },
+            Ok(None) => {}
+            Err(e) => tracing::warn!("检查更新失败: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    #[test]
+    fn test_verify_signature() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let public_key = hex::encode(key.verifying_key().to_bytes());
+        let data = b"sscontrol binary";
+        let signature = key.sign(data).to_bytes();
+
+        assert!(verify_signature(&public_key, data, &hex::encode(signature)).is_ok());
+        let base64 = base64::engine::general_purpose::STANDARD.encode(signature);
+        assert!(verify_signature(&public_key, data, &format!("{}\n", base64)).is_ok());
+
+        assert!(verify_signature(&public_key, b"tampered binary", &hex::encode(signature)).is_err());
+        assert!(verify_signature("not-a-key", data, &hex::encode(signature)).is_err());
+        assert!(verify_signature(&public_key, data, "garbage").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_download_limit_without_content_length() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // 分块编码的响应没有 Content-Length，只能边读边计数
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request).await;
+                let response = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+                                a\r\n0123456789\r\na\r\n0123456789\r\n0\r\n\r\n";
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        let url = format!("http://{}/sscontrol", addr);
+
+        let response = reqwest::get(&url).await.unwrap();
+        assert!(response.content_length().is_none());
+        assert!(read_limited(response, "sscontrol", 16).await.is_err());
+
+        let response = reqwest::get(&url).await.unwrap();
+        assert_eq!(read_limited(response, "sscontrol", 20).await.unwrap(), b"01234567890123456789");
+    }
+}
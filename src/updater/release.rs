@@ -0,0 +1,154 @@
+//! 发布信息
+//!
+//! 解析 GitHub Releases API (`/releases/latest`) 格式的发布信息，比较版本号，
+//! 并按当前平台挑选可执行文件和对应的签名文件 (`<可执行文件名>.sig`)
+
+use serde::Deserialize;
+
+/// 一次发布
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    /// 版本标签 (如 "v0.2.0")
+    pub tag_name: String,
+    /// 发布页面
+    #[serde(default)]
+    pub html_url: Option<String>,
+    /// 附件
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+/// 发布附件
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+    #[serde(default)]
+    pub size: u64,
+}
+
+impl Release {
+    /// 去掉 "v" 前缀的版本号
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    /// 当前平台的可执行文件
+    pub fn binary_asset(&self) -> Option<&Asset> {
+        select_asset(&self.assets, std::env::consts::OS, std::env::consts::ARCH)
+    }
+
+    /// 可执行文件的签名文件
+    pub fn signature_asset(&self, binary: &Asset) -> Option<&Asset> {
+        let name = format!("{}.sig", binary.name);
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// `latest` 是否比 `current` 新 (无法解析的版本号视为不更新)
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+/// 解析 "v1.2.3"、"1.2"、"1.2.3-beta.1" 为 (主, 次, 修订, 是否正式版)
+///
+/// 同一版本号的正式版比预发布版新
+fn parse_version(version: &str) -> Option<(u64, u64, u64, bool)> {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split('+').next()?;
+    let (core, release) = match version.split_once('-') {
+        Some((core, _)) => (core, false),
+        None => (version, true),
+    };
+    let mut parts = core.split('.').map(|part| part.parse::<u64>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((major, minor, patch, release))
+}
+
+/// 按名称挑选平台对应的可执行文件 (如 sscontrol-macos-aarch64、sscontrol-windows-x86_64.exe)
+///
+/// 只支持直接发布的可执行文件，压缩包和校验文件会被跳过
+fn select_asset<'a>(assets: &'a [Asset], os: &str, arch: &str) -> Option<&'a Asset> {
+    let os_names: &[&str] = match os {
+        "macos" => &["macos", "darwin", "apple"],
+        "windows" => &["windows", "win64"],
+        "linux" => &["linux"],
+        _ => return None,
+    };
+    let arch_names: &[&str] = match arch {
+        "x86_64" => &["x86_64", "amd64", "x64"],
+        "aarch64" => &["aarch64", "arm64"],
+        _ => return None,
+    };
+    const SKIPPED: &[&str] = &[".sig", ".sha256", ".txt", ".zip", ".tar.gz", ".tgz", ".dmg", ".msi"];
+    let windows = os == "windows";
+
+    assets.iter().find(|asset| {
+        let name = asset.name.to_lowercase();
+        os_names.iter().any(|os| name.contains(os))
+            && arch_names.iter().any(|arch| name.contains(arch))
+            && !SKIPPED.iter().any(|suffix| name.ends_with(suffix))
+            && (!windows || name.ends_with(".exe"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asset(name: &str) -> Asset {
+        Asset {
+            name: name.to_string(),
+            browser_download_url: format!("https://example.com/{}", name),
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("v0.2.0", "0.1.0"));
+        assert!(is_newer("1.0", "0.9.9"));
+        assert!(is_newer("0.2.0", "0.2.0-beta.1"));
+        assert!(!is_newer("v0.1.0", "0.1.0"));
+        assert!(!is_newer("0.2.0-rc.1", "0.2.0"));
+        assert!(!is_newer("nightly", "0.1.0"));
+    }
+
+    #[test]
+    fn test_select_asset() {
+        let assets = vec![
+            asset("sscontrol-linux-x86_64"),
+            asset("sscontrol-linux-x86_64.sig"),
+            asset("sscontrol-macos-arm64.tar.gz"),
+            asset("sscontrol-macos-arm64"),
+            asset("sscontrol-windows-x86_64"),
+            asset("sscontrol-windows-x86_64.exe"),
+        ];
+        let name = |os, arch| select_asset(&assets, os, arch).map(|asset| asset.name.as_str());
+
+        assert_eq!(name("linux", "x86_64"), Some("sscontrol-linux-x86_64"));
+        assert_eq!(name("macos", "aarch64"), Some("sscontrol-macos-arm64"));
+        assert_eq!(name("windows", "x86_64"), Some("sscontrol-windows-x86_64.exe"));
+        assert_eq!(name("linux", "aarch64"), None);
+    }
+
+    #[test]
+    fn test_signature_asset() {
+        let release = Release {
+            tag_name: "v0.2.0".to_string(),
+            html_url: None,
+            assets: vec![asset("sscontrol-linux-x86_64"), asset("sscontrol-linux-x86_64.sig")],
+        };
+        assert_eq!(release.version(), "0.2.0");
+        let signature = release.signature_asset(&release.assets[0]).unwrap();
+        assert_eq!(signature.name, "sscontrol-linux-x86_64.sig");
+    }
+}
@@ -0,0 +1,91 @@
+//! 替换可执行文件
+//!
+//! 新版本先写到可执行文件旁边的 `<名称>.new`，再用 rename 原子替换:
+//! - Unix: 直接 rename 覆盖，正在运行的进程继续使用旧文件的 inode
+//! - Windows: 运行中的可执行文件不能删除或覆盖，但可以改名，
+//!   先把旧文件改名为 `<名称>.old`，下次启动时清理
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 暂存新版本的路径
+fn staged_path(exe: &Path) -> PathBuf {
+    sibling(exe, "new")
+}
+
+/// Windows 上旧版本改名后的路径
+pub fn backup_path(exe: &Path) -> PathBuf {
+    sibling(exe, "old")
+}
+
+fn sibling(exe: &Path, suffix: &str) -> PathBuf {
+    let name = exe.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    exe.with_file_name(format!("{}.{}", name, suffix))
+}
+
+/// 用 `data` 原子替换 `exe` (保留原文件的权限)
+pub fn replace_executable(exe: &Path, data: &[u8]) -> Result<()> {
+    let staged = staged_path(exe);
+    fs::write(&staged, data).map_err(|e| anyhow!("写入 {} 失败: {}", staged.display(), e))?;
+
+    if let Ok(metadata) = fs::metadata(exe) {
+        let _ = fs::set_permissions(&staged, metadata.permissions());
+    }
+
+    let result = swap(exe, &staged);
+    if result.is_err() {
+        let _ = fs::remove_file(&staged);
+    }
+    result
+}
+
+#[cfg(not(target_os = "windows"))]
+fn swap(exe: &Path, staged: &Path) -> Result<()> {
+    fs::rename(staged, exe).map_err(|e| anyhow!("替换 {} 失败: {}", exe.display(), e))
+}
+
+#[cfg(target_os = "windows")]
+fn swap(exe: &Path, staged: &Path) -> Result<()> {
+    let backup = backup_path(exe);
+    let _ = fs::remove_file(&backup);
+    fs::rename(exe, &backup).map_err(|e| anyhow!("移动旧版本 {} 失败: {}", exe.display(), e))?;
+    if let Err(e) = fs::rename(staged, exe) {
+        // 还原旧版本
+        let _ = fs::rename(&backup, exe);
+        return Err(anyhow!("替换 {} 失败: {}", exe.display(), e));
+    }
+    Ok(())
+}
+
+/// 清理上次更新留下的旧版本 (Windows)
+pub fn cleanup_backup(exe: &Path) {
+    let backup = backup_path(exe);
+    if backup.exists() {
+        match fs::remove_file(&backup) {
+            Ok(()) => tracing::debug!("已删除旧版本 {}", backup.display()),
+            Err(e) => tracing::debug!("删除旧版本 {} 失败: {}", backup.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_executable() {
+        let dir = std::env::temp_dir().join(format!("sscontrol-update-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("sscontrol");
+        fs::write(&exe, b"old").unwrap();
+
+        replace_executable(&exe, b"new").unwrap();
+        assert_eq!(fs::read(&exe).unwrap(), b"new");
+        assert!(!staged_path(&exe).exists());
+
+        cleanup_backup(&exe);
+        assert!(!backup_path(&exe).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,156 @@
+//! 查看器品牌定制
+//!
+//! 页面标题、Logo 和配色 (CSS 变量)。默认值即内置页面的样式，
+//! 可以从配置文件的 `[viewer]` 段加载，也可以由嵌入方直接构造
+
+#![allow(dead_code)]
+
+use crate::config::ViewerConfig;
+
+/// 查看器品牌
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Branding {
+    /// 页面标题 (浏览器标签页)
+    pub title: String,
+    /// 页头显示的名称
+    pub heading: String,
+    /// Logo 地址 (None = 不显示)
+    pub logo: Option<String>,
+    /// 页面背景色
+    pub background: String,
+    /// 页头和面板背景色
+    pub panel: String,
+    /// 边框色
+    pub border: String,
+    /// 强调色 (加载动画等)
+    pub accent: String,
+    /// 文字颜色
+    pub text: String,
+}
+
+impl Default for Branding {
+    fn default() -> Self {
+        Self {
+            title: "sscontrol - 远程桌面".to_string(),
+            heading: "sscontrol 远程桌面".to_string(),
+            logo: None,
+            background: "#1a1a2e".to_string(),
+            panel: "#16213e".to_string(),
+            border: "#0f3460".to_string(),
+            accent: "#0984e3".to_string(),
+            text: "#eee".to_string(),
+        }
+    }
+}
+
+impl Branding {
+    /// 从配置加载，未设置的项保持默认值
+    pub fn from_config(config: &ViewerConfig) -> Self {
+        let mut branding = Self::default();
+        if let Some(title) = &config.title {
+            branding.title = title.clone();
+            branding.heading = title.clone();
+        }
+        branding.logo = config.logo.clone();
+        let colors = [
+            (&config.background, &mut branding.background),
+            (&config.panel, &mut branding.panel),
+            (&config.border, &mut branding.border),
+            (&config.accent, &mut branding.accent),
+            (&config.text, &mut branding.text),
+        ];
+        for (value, target) in colors {
+            if let Some(value) = value {
+                *target = value.clone();
+            }
+        }
+        branding
+    }
+
+    /// `:root` 中的 CSS 变量声明 (颜色值不合法时回退到默认值)
+    pub(super) fn css_variables(&self) -> String {
+        let defaults = Self::default();
+        let color = |value: &str, default: String| if is_css_color(value) { value.to_string() } else { default };
+        format!(
+            "--bg: {}; --panel: {}; --border: {}; --accent: {}; --text: {};",
+            color(&self.background, defaults.background),
+            color(&self.panel, defaults.panel),
+            color(&self.border, defaults.border),
+            color(&self.accent, defaults.accent),
+            color(&self.text, defaults.text),
+        )
+    }
+
+    /// 页头 HTML (Logo + 名称)
+    pub(super) fn header_html(&self) -> String {
+        let logo = self
+            .logo
+            .as_deref()
+            .map(|logo| format!(r#"<img class="logo" src="{}" alt="">"#, escape_html(logo)))
+            .unwrap_or_default();
+        format!("{}<h1>{}</h1>", logo, escape_html(&self.heading))
+    }
+
+    pub(super) fn title_html(&self) -> String {
+        escape_html(&self.title)
+    }
+}
+
+/// 是否为可以安全写入样式表的颜色 (`#rgb` / `#rrggbb` / `#rrggbbaa` 或颜色名)
+pub fn is_css_color(value: &str) -> bool {
+    match value.strip_prefix('#') {
+        Some(hex) => matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => !value.is_empty() && value.chars().all(|c| c.is_ascii_alphabetic()),
+    }
+}
+
+/// 转义写入 HTML 文本或属性的字符串
+pub(super) fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_config() {
+        let config = ViewerConfig {
+            title: Some("Acme <Remote>".to_string()),
+            logo: Some("/assets/logo.png".to_string()),
+            accent: Some("#ff8800".to_string()),
+            background: Some("red; }".to_string()),
+            ..Default::default()
+        };
+        let branding = Branding::from_config(&config);
+        assert_eq!(branding.panel, Branding::default().panel);
+        assert_eq!(branding.title_html(), "Acme &lt;Remote&gt;");
+        assert!(branding.header_html().starts_with(r#"<img class="logo" src="/assets/logo.png""#));
+
+        // 非法颜色不会写入样式表
+        let css = branding.css_variables();
+        assert!(css.contains("--accent: #ff8800;"));
+        assert!(css.contains("--bg: #1a1a2e;"));
+    }
+
+    #[test]
+    fn test_is_css_color() {
+        assert!(is_css_color("#fff"));
+        assert!(is_css_color("#1a1a2e"));
+        assert!(is_css_color("white"));
+        assert!(!is_css_color("#12345"));
+        assert!(!is_css_color("url(x)"));
+        assert!(!is_css_color(""));
+    }
+}
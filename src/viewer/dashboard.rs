@@ -0,0 +1,329 @@
+//! 多会话面板
+//!
+//! 在一个浏览器窗口中同时查看多台被控端 (网格或标签页布局)。每台主机放在一个 iframe 中，
+//! 加载 `/session?url=...` 的单机查看页面，因此各自拥有独立的视频流和信令连接，
+//! 键盘和鼠标输入只发往获得焦点的会话
+
+use super::branding::Branding;
+use super::web::ViewerHost;
+
+/// 生成面板 HTML 页面
+pub(super) fn get_dashboard_html(hosts: &[ViewerHost], branding: &Branding) -> String {
+    format!(r#"<!DOCTYPE html>
+<html lang="zh-CN">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>{title}</title>
+    <style>
+        :root {{
+            {css_variables}
+        }}
+        * {{
+            margin: 0;
+            padding: 0;
+            box-sizing: border-box;
+        }}
+        body {{
+            background: var(--bg);
+            color: var(--text);
+            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif;
+            height: 100vh;
+            display: flex;
+            flex-direction: column;
+        }}
+        .header {{
+            background: var(--panel);
+            padding: 10px 20px;
+            display: flex;
+            justify-content: space-between;
+            align-items: center;
+            gap: 12px;
+            border-bottom: 1px solid var(--border);
+        }}
+        .brand {{
+            display: flex;
+            align-items: center;
+            gap: 10px;
+        }}
+        .brand .logo {{
+            height: 28px;
+        }}
+        .header h1 {{
+            font-size: 18px;
+            font-weight: 500;
+        }}
+        .toolbar {{
+            display: flex;
+            align-items: center;
+            gap: 8px;
+        }}
+        .btn {{
+            padding: 6px 12px;
+            background: rgba(255,255,255,0.1);
+            border: 1px solid var(--border);
+            border-radius: 4px;
+            color: var(--text);
+            cursor: pointer;
+            font-size: 13px;
+        }}
+        .btn.active {{
+            border-color: var(--accent);
+        }}
+        #add-input {{
+            width: 280px;
+            padding: 6px 8px;
+            background: transparent;
+            border: 1px solid var(--border);
+            border-radius: 4px;
+            color: var(--text);
+        }}
+        #tabs {{
+            display: none;
+            background: var(--panel);
+            border-bottom: 1px solid var(--border);
+            padding: 0 12px;
+        }}
+        body.tabs #tabs {{
+            display: flex;
+        }}
+        .tab {{
+            padding: 8px 14px;
+            cursor: pointer;
+            font-size: 13px;
+            border-bottom: 2px solid transparent;
+        }}
+        .tab.active {{
+            border-bottom-color: var(--accent);
+        }}
+        #sessions {{
+            flex: 1;
+            display: grid;
+            grid-template-columns: repeat(var(--columns, 1), 1fr);
+            gap: 8px;
+            padding: 8px;
+            min-height: 0;
+        }}
+        body.tabs #sessions {{
+            display: block;
+        }}
+        .session {{
+            display: flex;
+            flex-direction: column;
+            border: 1px solid var(--border);
+            border-radius: 4px;
+            overflow: hidden;
+            min-height: 0;
+        }}
+        .session.focused {{
+            border-color: var(--accent);
+        }}
+        body.tabs .session {{
+            display: none;
+            height: 100%;
+        }}
+        body.tabs .session.current {{
+            display: flex;
+        }}
+        .session-bar {{
+            display: flex;
+            justify-content: space-between;
+            align-items: center;
+            padding: 4px 8px;
+            background: var(--panel);
+            font-size: 13px;
+        }}
+        .session-bar .btn {{
+            padding: 2px 8px;
+            font-size: 12px;
+        }}
+        .session iframe {{
+            flex: 1;
+            width: 100%;
+            border: none;
+            background: #000;
+        }}
+        .empty {{
+            margin: auto;
+            color: #666;
+        }}
+    </style>
+</head>
+<body>
+    <div class="header">
+        <div class="brand">{header}</div>
+        <form class="toolbar" onsubmit="addFromInput(event)">
+            <input id="add-input" placeholder="ws://192.168.1.10:9527 或 wss://..." autocomplete="off">
+            <button class="btn" type="submit">添加主机</button>
+            <button class="btn" type="button" id="grid-btn" onclick="setLayout('grid')">网格</button>
+            <button class="btn" type="button" id="tabs-btn" onclick="setLayout('tabs')">标签页</button>
+        </form>
+    </div>
+    <div id="tabs"></div>
+    <div id="sessions"></div>
+
+    <script>
+        // 命令行指定的主机 (不能从页面移除)
+        const HOSTS = {hosts};
+        // 页面上添加的主机保存在 localStorage
+        const STORAGE_KEY = 'sscontrol.dashboard.hosts';
+        const URL_PATTERN = /^wss?:\/\/[^\s'"\\<>`]+$/;
+
+        const sessions = [];
+        let layout = localStorage.getItem('sscontrol.dashboard.layout') || 'grid';
+        let current = 0;
+
+        function storedHosts() {{
+            try {{
+                return JSON.parse(localStorage.getItem(STORAGE_KEY) || '[]');
+            }} catch (e) {{
+                return [];
+            }}
+        }}
+
+        function saveHosts() {{
+            const added = sessions.filter(session => !session.fixed).map(session => ({{ name: session.name, url: session.url }}));
+            localStorage.setItem(STORAGE_KEY, JSON.stringify(added));
+        }}
+
+        // 每台主机一个 iframe: 独立的视频流和信令连接，键盘输入只发往获得焦点的会话
+        function addSession(host, fixed) {{
+            if (!URL_PATTERN.test(host.url) || sessions.some(session => session.url === host.url)) {{
+                return false;
+            }}
+            const element = document.createElement('div');
+            element.className = 'session';
+
+            const bar = document.createElement('div');
+            bar.className = 'session-bar';
+            const name = document.createElement('span');
+            name.textContent = host.name;
+            const actions = document.createElement('div');
+
+            const frame = document.createElement('iframe');
+            frame.src = '/session?url=' + encodeURIComponent(host.url);
+            frame.allow = 'fullscreen; clipboard-read; clipboard-write';
+
+            const session = {{ name: host.name, url: host.url, fixed, element, frame, tab: null }};
+
+            const popout = document.createElement('button');
+            popout.className = 'btn';
+            popout.textContent = '新窗口';
+            popout.onclick = () => window.open(frame.src, '_blank');
+            actions.appendChild(popout);
+            if (!fixed) {{
+                const close = document.createElement('button');
+                close.className = 'btn';
+                close.textContent = '关闭';
+                close.onclick = () => removeSession(session);
+                actions.appendChild(close);
+            }}
+            bar.appendChild(name);
+            bar.appendChild(actions);
+            element.appendChild(bar);
+            element.appendChild(frame);
+            document.getElementById('sessions').appendChild(element);
+
+            session.tab = document.createElement('div');
+            session.tab.className = 'tab';
+            session.tab.textContent = host.name;
+            session.tab.onclick = () => showSession(sessions.indexOf(session));
+            document.getElementById('tabs').appendChild(session.tab);
+
+            sessions.push(session);
+            render();
+            return true;
+        }}
+
+        function removeSession(session) {{
+            const index = sessions.indexOf(session);
+            if (index < 0) {{
+                return;
+            }}
+            session.element.remove();
+            session.tab.remove();
+            sessions.splice(index, 1);
+            current = Math.min(current, Math.max(sessions.length - 1, 0));
+            saveHosts();
+            render();
+        }}
+
+        function addFromInput(event) {{
+            event.preventDefault();
+            const input = document.getElementById('add-input');
+            const url = input.value.trim();
+            if (!URL_PATTERN.test(url)) {{
+                alert('请输入 ws:// 或 wss:// 开头的地址');
+                return;
+            }}
+            if (addSession({{ name: new URL(url).host, url }}, false)) {{
+                saveHosts();
+                showSession(sessions.length - 1);
+            }}
+            input.value = '';
+        }}
+
+        function showSession(index) {{
+            current = index;
+            render();
+            sessions[index]?.frame.focus();
+        }}
+
+        function setLayout(name) {{
+            layout = name;
+            localStorage.setItem('sscontrol.dashboard.layout', name);
+            render();
+        }}
+
+        function render() {{
+            document.body.classList.toggle('tabs', layout === 'tabs');
+            document.getElementById('grid-btn').classList.toggle('active', layout === 'grid');
+            document.getElementById('tabs-btn').classList.toggle('active', layout === 'tabs');
+            const columns = Math.ceil(Math.sqrt(sessions.length || 1));
+            document.getElementById('sessions').style.setProperty('--columns', columns);
+            sessions.forEach((session, index) => {{
+                session.element.classList.toggle('current', index === current);
+                session.tab.classList.toggle('active', index === current);
+            }});
+            let empty = document.getElementById('empty');
+            if (sessions.length === 0 && !empty) {{
+                empty = document.createElement('p');
+                empty.id = 'empty';
+                empty.className = 'empty';
+                empty.textContent = '还没有主机，在右上角输入被控端地址添加';
+                document.getElementById('sessions').appendChild(empty);
+            }} else if (sessions.length > 0 && empty) {{
+                empty.remove();
+            }}
+        }}
+
+        // 点击 iframe 不会冒泡到本页面，通过焦点变化标记正在接收输入的会话
+        function updateFocus() {{
+            sessions.forEach(session => {{
+                session.element.classList.toggle('focused', document.activeElement === session.frame);
+            }});
+        }}
+        window.addEventListener('blur', () => setTimeout(updateFocus, 0));
+        window.addEventListener('focus', updateFocus);
+
+        HOSTS.forEach(host => addSession(host, true));
+        storedHosts().forEach(host => addSession(host, false));
+        render();
+    </script>
+</body>
+</html>"#,
+        title = branding.title_html(),
+        header = branding.header_html(),
+        css_variables = branding.css_variables(),
+        hosts = hosts_json(hosts),
+    )
+}
+
+/// 命令行指定的主机列表 (写入 `<script>`，转义 `<` 防止提前结束标签)
+fn hosts_json(hosts: &[ViewerHost]) -> String {
+    let hosts: Vec<_> = hosts
+        .iter()
+        .map(|host| serde_json::json!({ "name": host.name, "url": host.signaling_url }))
+        .collect();
+    serde_json::Value::Array(hosts).to_string().replace('<', "\\u003c")
+}
@@ -1,7 +1,10 @@
 //! Web 查看器模块
 //!
-//! 提供基于浏览器的远程桌面查看器
+//! 提供基于浏览器的远程桌面查看器，可以在一个窗口中同时查看多台主机
 
+mod branding;
+mod dashboard;
 mod web;
 
-pub use web::WebViewer;
+pub use branding::is_css_color;
+pub use web::{host_label, ViewerHost, WebViewer};
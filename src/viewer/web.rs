@@ -1,36 +1,153 @@
 //! Web 查看器实现
 //!
-//! 启动本地 HTTP 服务器，提供 WebRTC 远程桌面查看页面
+//! 启动本地 HTTP 服务器，提供远程桌面查看页面。
+//! 页面通过 Host 的 `/video` WebSocket 接收 H.264 码流，优先用 WebCodecs 解码，
+//! 浏览器不支持时封装为 fMP4 交给 MSE 播放 (不需要 WebRTC)
+//!
+//! 嵌入方可以通过 [`WebViewer`] 的构建方法替换品牌、挂载静态资源目录和追加路由
+//!
+//! ## 路由
+//! - `/`: 单台主机时为查看页面，多台主机时为多会话面板
+//! - `/session?url=ws://...`: 指定被控端的查看页面 (面板中每个会话加载一个)
+//! - `/dashboard`: 多会话面板 (可在页面上添加更多主机)
 
+#![allow(dead_code)]
+
+use super::branding::Branding;
+use super::dashboard::get_dashboard_html;
+use crate::config::ViewerConfig;
 use anyhow::Result;
 use axum::{
-    response::Html,
-    routing::get,
+    extract::{Path, Query},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::{get, MethodRouter},
     Router,
 };
+use serde::Deserialize;
 use std::net::SocketAddr;
+use std::path::{Component, PathBuf};
+use std::sync::Arc;
 use tokio::net::TcpListener;
 
+/// 配置文件 `assets_dir` 挂载的路径
+const ASSETS_PREFIX: &str = "/assets";
+
+/// 查看的被控端
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ViewerHost {
+    /// 面板中显示的名称
+    pub name: String,
+    /// 信令地址 (ws:// 或 wss://)
+    pub signaling_url: String,
+}
+
 /// Web 查看器
+///
+/// 嵌入方可以替换品牌 (标题、Logo、配色)，挂载静态资源目录，或追加自定义路由，
+/// 不需要修改内置页面
 pub struct WebViewer {
-    signaling_url: String,
+    hosts: Vec<ViewerHost>,
     port: u16,
+    branding: Branding,
+    /// 附加路由 (与内置页面合并)
+    routes: Router,
+    /// 静态资源目录 (URL 前缀, 本地目录)
+    asset_dirs: Vec<(String, PathBuf)>,
 }
 
 impl WebViewer {
     /// 创建新的 Web 查看器
     pub fn new(signaling_url: String, port: u16) -> Self {
-        Self { signaling_url, port }
+        let host = ViewerHost { name: host_label(&signaling_url), signaling_url };
+        Self::dashboard(vec![host], port)
+    }
+
+    /// 创建同时查看多台主机的面板
+    pub fn dashboard(hosts: Vec<ViewerHost>, port: u16) -> Self {
+        Self {
+            hosts,
+            port,
+            branding: Branding::default(),
+            routes: Router::new(),
+            asset_dirs: Vec::new(),
+        }
+    }
+
+    /// 应用配置文件的 `[viewer]` 段 (品牌 + `assets_dir` 挂载到 `/assets/`)
+    pub fn with_config(mut self, config: &ViewerConfig) -> Self {
+        self.branding = Branding::from_config(config);
+        if let Some(dir) = &config.assets_dir {
+            self = self.serve_dir(ASSETS_PREFIX, dir);
+        }
+        self
+    }
+
+    /// 添加一台主机
+    pub fn add_host(mut self, name: impl Into<String>, signaling_url: impl Into<String>) -> Self {
+        self.hosts.push(ViewerHost { name: name.into(), signaling_url: signaling_url.into() });
+        self
+    }
+
+    /// 已添加的主机
+    pub fn hosts(&self) -> &[ViewerHost] {
+        &self.hosts
+    }
+
+    /// 替换页面品牌
+    pub fn with_branding(mut self, branding: Branding) -> Self {
+        self.branding = branding;
+        self
+    }
+
+    /// 当前品牌
+    pub fn branding(&self) -> &Branding {
+        &self.branding
+    }
+
+    /// 追加路由 (`/` 由内置页面占用)
+    pub fn route(mut self, path: &str, handler: MethodRouter) -> Self {
+        self.routes = self.routes.route(path, handler);
+        self
+    }
+
+    /// 合并一组路由
+    pub fn merge(mut self, router: Router) -> Self {
+        self.routes = self.routes.merge(router);
+        self
+    }
+
+    /// 把本地目录挂载到 `prefix` 下 (如 `/assets`)
+    pub fn serve_dir(mut self, prefix: &str, dir: impl Into<PathBuf>) -> Self {
+        let prefix = format!("/{}", prefix.trim_matches('/'));
+        self.asset_dirs.push((prefix, dir.into()));
+        self
     }
 
     /// 启动 HTTP 服务器
     pub async fn start(&self) -> Result<u16> {
-        let signaling_url = self.signaling_url.clone();
+        if let Some(host) = self.hosts.iter().find(|host| !is_signaling_url(&host.signaling_url)) {
+            anyhow::bail!("无效的被控端地址: {}", host.signaling_url);
+        }
+        let branding = Arc::new(self.branding.clone());
+        let dashboard = Arc::new(get_dashboard_html(&self.hosts, &self.branding));
+        let index = Arc::new(match self.hosts.as_slice() {
+            [host] => get_viewer_html(&host.signaling_url, &self.branding),
+            _ => dashboard.as_str().to_owned(),
+        });
 
-        let app = Router::new()
-            .route("/", get(move || async move {
-                Html(get_viewer_html(&signaling_url))
-            }));
+        let mut app = Router::new()
+            .route("/", get(move || async move { Html(index.as_str().to_owned()) }))
+            .route("/dashboard", get(move || async move { Html(dashboard.as_str().to_owned()) }))
+            .route("/session", get(move |query: Query<SessionQuery>| session_page(branding, query)))
+            .merge(self.routes.clone());
+        for (prefix, dir) in &self.asset_dirs {
+            let dir = Arc::new(dir.clone());
+            app = app.route(
+                &format!("{}/*path", prefix.trim_end_matches('/')),
+                get(move |Path(path): Path<String>| serve_file(dir, path)),
+            );
+        }
 
         let addr: SocketAddr = format!("127.0.0.1:{}", self.port).parse()?;
         let listener = TcpListener::bind(addr).await?;
@@ -44,35 +161,118 @@ impl WebViewer {
     }
 }
 
+#[derive(Deserialize)]
+struct SessionQuery {
+    url: String,
+}
+
+/// 单台主机的查看页面
+async fn session_page(branding: Arc<Branding>, Query(query): Query<SessionQuery>) -> Response {
+    if !is_signaling_url(&query.url) {
+        return (StatusCode::BAD_REQUEST, "无效的被控端地址").into_response();
+    }
+    Html(get_viewer_html(&query.url, &branding)).into_response()
+}
+
+/// 是否为可以写入页面脚本的信令地址 (ws:// 或 wss://，不含引号、尖括号和空白)
+pub fn is_signaling_url(url: &str) -> bool {
+    let rest = url.strip_prefix("ws://").or_else(|| url.strip_prefix("wss://"));
+    rest.is_some_and(|rest| {
+        !rest.is_empty()
+            && url.len() <= 2048
+            && !rest.chars().any(|c| c.is_whitespace() || c.is_control() || "'\"\\<>`".contains(c))
+    })
+}
+
+/// 面板中显示的主机名称 (信令地址去掉协议和路径)
+pub fn host_label(signaling_url: &str) -> String {
+    let rest = signaling_url.split_once("://").map_or(signaling_url, |(_, rest)| rest);
+    rest.split('/').next().unwrap_or(rest).to_string()
+}
+
+/// 读取静态资源目录中的文件 (拒绝 `..` 和绝对路径)
+async fn serve_file(dir: Arc<PathBuf>, path: String) -> Response {
+    let Some(relative) = sanitize_path(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match tokio::fs::read(dir.join(&relative)).await {
+        Ok(body) => ([(header::CONTENT_TYPE, content_type(&relative))], body).into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// 只保留普通路径分量，出现 `..`、根目录或盘符时返回 None
+fn sanitize_path(path: &str) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in std::path::Path::new(path).components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!relative.as_os_str().is_empty()).then_some(relative)
+}
+
+fn content_type(path: &std::path::Path) -> &'static str {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_ascii_lowercase();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff2" => "font/woff2",
+        "woff" => "font/woff",
+        _ => "application/octet-stream",
+    }
+}
+
 /// 生成查看器 HTML 页面
-fn get_viewer_html(signaling_url: &str) -> String {
+fn get_viewer_html(signaling_url: &str, branding: &Branding) -> String {
     format!(r#"<!DOCTYPE html>
 <html lang="zh-CN">
 <head>
     <meta charset="UTF-8">
     <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>sscontrol - 远程桌面</title>
+    <title>{title}</title>
     <style>
+        :root {{
+            {css_variables}
+        }}
         * {{
             margin: 0;
             padding: 0;
             box-sizing: border-box;
         }}
         body {{
-            background: #1a1a2e;
-            color: #eee;
+            background: var(--bg);
+            color: var(--text);
             font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif;
             min-height: 100vh;
             display: flex;
             flex-direction: column;
         }}
         .header {{
-            background: #16213e;
+            background: var(--panel);
             padding: 10px 20px;
             display: flex;
             justify-content: space-between;
             align-items: center;
-            border-bottom: 1px solid #0f3460;
+            border-bottom: 1px solid var(--border);
+        }}
+        .brand {{
+            display: flex;
+            align-items: center;
+            gap: 10px;
+        }}
+        .brand .logo {{
+            height: 28px;
         }}
         .header h1 {{
             font-size: 18px;
@@ -93,6 +293,25 @@ fn get_viewer_html(signaling_url: &str) -> String {
         .status-dot.connected {{
             background: #51cf66;
         }}
+        .host-clock {{
+            margin-left: 12px;
+            color: #aaa;
+            font-size: 13px;
+        }}
+        .perm-badge {{
+            margin-left: 12px;
+            padding: 2px 8px;
+            border-radius: 10px;
+            background: rgba(255,255,255,0.15);
+            font-size: 12px;
+        }}
+        .perm-badge.view-only {{
+            background: #b8860b;
+        }}
+        .btn:disabled {{
+            opacity: 0.4;
+            cursor: not-allowed;
+        }}
         .container {{
             flex: 1;
             display: flex;
@@ -107,7 +326,21 @@ fn get_viewer_html(signaling_url: &str) -> String {
             overflow: hidden;
             box-shadow: 0 4px 20px rgba(0,0,0,0.5);
         }}
-        #remote-video {{
+        /* 拖入文件时的提示 */
+        #video-container.drop-target::after {{
+            content: '松开以上传到远程主机';
+            position: absolute;
+            inset: 0;
+            display: flex;
+            align-items: center;
+            justify-content: center;
+            background: rgba(0, 0, 0, 0.6);
+            border: 3px dashed var(--accent);
+            border-radius: 8px;
+            font-size: 20px;
+            pointer-events: none;
+        }}
+        #video-canvas, #video-element {{
             display: block;
             max-width: 100%;
             max-height: calc(100vh - 100px);
@@ -128,7 +361,7 @@ fn get_viewer_html(signaling_url: &str) -> String {
             width: 40px;
             height: 40px;
             border: 3px solid #333;
-            border-top-color: #0984e3;
+            border-top-color: var(--accent);
             border-radius: 50%;
             animation: spin 1s linear infinite;
             margin-bottom: 20px;
@@ -136,6 +369,33 @@ fn get_viewer_html(signaling_url: &str) -> String {
         @keyframes spin {{
             to {{ transform: rotate(360deg); }}
         }}
+        /* 连接质量 (Host 的质量报告，绿/黄/红) */
+        #quality {{
+            position: absolute;
+            top: 10px;
+            left: 10px;
+            display: none;
+            align-items: center;
+            gap: 6px;
+            padding: 3px 8px;
+            border-radius: 10px;
+            background: rgba(0,0,0,0.55);
+            color: #ddd;
+            font-size: 12px;
+            pointer-events: none;
+        }}
+        #quality .quality-dot {{
+            width: 8px;
+            height: 8px;
+            border-radius: 50%;
+            background: #51cf66;
+        }}
+        #quality.fair .quality-dot {{
+            background: #fcc419;
+        }}
+        #quality.poor .quality-dot {{
+            background: #ff6b6b;
+        }}
         .controls {{
             position: absolute;
             bottom: 10px;
@@ -161,6 +421,34 @@ fn get_viewer_html(signaling_url: &str) -> String {
         .btn:hover {{
             background: rgba(255,255,255,0.3);
         }}
+        #video-canvas, #video-element {{
+            touch-action: none;
+        }}
+        .modifier-bar {{
+            position: absolute;
+            top: 10px;
+            left: 50%;
+            transform: translateX(-50%);
+            display: none;
+            gap: 6px;
+        }}
+        body.touch .modifier-bar {{
+            display: flex;
+        }}
+        .modifier-bar .btn.once {{
+            background: var(--accent);
+        }}
+        .modifier-bar .btn.lock {{
+            background: var(--accent);
+            box-shadow: inset 0 -3px 0 #fff;
+        }}
+        #keyboard-input {{
+            position: absolute;
+            opacity: 0;
+            width: 1px;
+            height: 1px;
+            left: -10px;
+        }}
         #log {{
             position: fixed;
             bottom: 10px;
@@ -178,47 +466,204 @@ fn get_viewer_html(signaling_url: &str) -> String {
         #log.show {{
             display: block;
         }}
+        #chat {{
+            position: fixed;
+            right: 10px;
+            bottom: 10px;
+            width: 300px;
+            height: 360px;
+            background: var(--panel);
+            border: 1px solid var(--border);
+            border-radius: 8px;
+            display: none;
+            flex-direction: column;
+            font-size: 13px;
+        }}
+        #chat.show {{
+            display: flex;
+        }}
+        #chat-messages {{
+            flex: 1;
+            overflow-y: auto;
+            padding: 8px 10px;
+        }}
+        .chat-line {{
+            margin-bottom: 6px;
+            word-break: break-word;
+        }}
+        .chat-from {{
+            color: #74c0fc;
+            margin-right: 4px;
+        }}
+        .chat-line.host .chat-from {{
+            color: #ffa94d;
+        }}
+        .chat-time {{
+            color: #666;
+            font-size: 11px;
+            margin-left: 4px;
+        }}
+        #chat-form {{
+            display: flex;
+            border-top: 1px solid var(--border);
+        }}
+        #chat-input {{
+            flex: 1;
+            padding: 8px;
+            border: none;
+            background: transparent;
+            color: #eee;
+            outline: none;
+        }}
+        #sysinfo {{
+            position: fixed;
+            top: 60px;
+            right: 10px;
+            bottom: 10px;
+            width: 320px;
+            background: var(--panel);
+            border: 1px solid var(--border);
+            border-radius: 8px;
+            padding: 10px 12px;
+            overflow-y: auto;
+            display: none;
+            font-size: 12px;
+        }}
+        #sysinfo.show {{
+            display: block;
+        }}
+        #sysinfo h4 {{
+            margin: 10px 0 4px;
+            color: #74c0fc;
+            font-size: 12px;
+            font-weight: normal;
+        }}
+        #sysinfo table {{
+            width: 100%;
+            border-collapse: collapse;
+        }}
+        #sysinfo td {{
+            padding: 1px 4px 1px 0;
+            white-space: nowrap;
+        }}
+        #sysinfo td.name {{
+            max-width: 140px;
+            overflow: hidden;
+            text-overflow: ellipsis;
+        }}
+        #sysinfo td.num {{
+            text-align: right;
+        }}
     </style>
 </head>
 <body>
     <div class="header">
-        <h1>sscontrol 远程桌面</h1>
+        <div class="brand">{header}</div>
         <div class="status">
             <div class="status-dot" id="status-dot"></div>
             <span id="status-text">正在连接...</span>
+            <span class="host-clock" id="host-clock"></span>
+            <span class="perm-badge" id="perm-badge" style="display: none;"></span>
         </div>
     </div>
 
     <div class="container">
         <div id="video-container">
-            <!-- 使用 canvas 显示视频流 -->
+            <!-- WebCodecs 解码后绘制到 canvas，MSE 回退时使用 video -->
             <canvas id="video-canvas"></canvas>
-            <img id="video-image" style="display: none;">
+            <video id="video-element" muted autoplay playsinline style="display: none;"></video>
             <div class="placeholder" id="placeholder">
                 <div class="spinner"></div>
                 <p>等待视频流...</p>
             </div>
+            <div id="quality"><span class="quality-dot"></span><span id="quality-text"></span></div>
             <div class="controls">
+                <button class="btn" id="cursor-btn" onclick="toggleCursor()">隐藏光标</button>
+                <select class="btn" id="profile-select" onchange="setProfile(this.value)" title="画质预设">
+                    <option value="" selected disabled>画质预设</option>
+                    <option value="low-latency">低延迟</option>
+                    <option value="balanced">均衡</option>
+                    <option value="high-quality">高画质</option>
+                    <option value="low-bandwidth">低带宽</option>
+                    <option value="battery-saver">省电</option>
+                </select>
+                <select class="btn" id="shortcut-select" onchange="sendShortcut(this)" title="发送本机会拦截的组合键">
+                    <option value="" selected disabled>组合键</option>
+                    <option value="Ctrl+Alt+Del">Ctrl+Alt+Del</option>
+                    <option value="Ctrl+Shift+Esc">Ctrl+Shift+Esc</option>
+                    <option value="Alt+Tab">Alt+Tab</option>
+                    <option value="Meta+Tab">Cmd/Win+Tab</option>
+                    <option value="Meta+Space">Cmd/Win+Space</option>
+                    <option value="Alt+F4">Alt+F4</option>
+                </select>
+                <button class="btn" id="clipboard-btn" disabled>剪贴板</button>
+                <button class="btn" id="file-btn" disabled>传输文件</button>
+                <button class="btn" id="lock-btn" onclick="powerAction('lock', '锁屏')" disabled>锁屏</button>
+                <button class="btn power-btn" id="logout-btn" onclick="powerAction('logout', '注销')" disabled>注销</button>
+                <button class="btn power-btn" id="reboot-btn" onclick="powerAction('reboot', '重启')" disabled>重启</button>
+                <button class="btn power-btn" id="shutdown-btn" onclick="powerAction('shutdown', '关机')" disabled>关机</button>
                 <button class="btn" onclick="toggleFullscreen()">全屏</button>
+                <button class="btn" id="chat-btn" onclick="toggleChat()">聊天</button>
+                <button class="btn" onclick="toggleSysInfo()">系统</button>
                 <button class="btn" onclick="toggleLog()">日志</button>
             </div>
+            <!-- 触摸设备上的修饰键栏: 点一次按住到下一次按键或点击，点两次锁定，再点松开 -->
+            <div class="modifier-bar" id="modifier-bar">
+                <button class="btn" data-modifier="Control">Ctrl</button>
+                <button class="btn" data-modifier="Alt">Alt</button>
+                <button class="btn" data-modifier="Shift">Shift</button>
+                <button class="btn" data-modifier="Meta">Win</button>
+                <button class="btn" data-key="Escape">Esc</button>
+                <button class="btn" data-key="Tab">Tab</button>
+                <button class="btn" id="keyboard-btn">键盘</button>
+            </div>
+            <input id="keyboard-input" autocapitalize="off" autocomplete="off" autocorrect="off" spellcheck="false">
+            <input type="file" id="file-input" multiple hidden>
         </div>
     </div>
 
     <div id="log"></div>
 
+    <div id="chat">
+        <div id="chat-messages"></div>
+        <form id="chat-form" onsubmit="sendChat(event)">
+            <input id="chat-input" maxlength="2000" placeholder="输入消息，回车发送" autocomplete="off">
+            <button class="btn" type="submit">发送</button>
+        </form>
+    </div>
+
+    <div id="sysinfo"></div>
+
     <script>
         const SIGNALING_URL = '{signaling_url}';
 
         let ws = null;
         let canvas, ctx;
-        let imageElement;
         const placeholder = document.getElementById('placeholder');
         const statusDot = document.getElementById('status-dot');
         const statusText = document.getElementById('status-text');
         const logDiv = document.getElementById('log');
+        const hostClock = document.getElementById('host-clock');
         let frameCount = 0;
         let lastFpsTime = Date.now();
+        // 最近一秒实际显示的帧率 (评定 /video 连接质量)
+        let shownFps = null;
+        // 最近一次质量报告 (原始 JSON 见 window.sscontrolQuality 和 sscontrol-quality 事件)
+        const qualityBadge = document.getElementById('quality');
+        const qualityText = document.getElementById('quality-text');
+        let hostInfo = null;
+        let hostClockOffset = 0;
+        let showCursor = true;
+        // 网络短暂中断后重新拉流的间隔
+        const RECONNECT_DELAY_MS = 1000;
+        // 聊天消息轮询间隔
+        const CHAT_POLL_MS = 1000;
+        let lastChatId = 0;
+        let unreadChat = 0;
+        // 系统信息侧边栏的刷新间隔和显示的进程数
+        const SYSINFO_POLL_MS = 3000;
+        const SYSINFO_TOP = 10;
+        let sysInfoTimer = null;
 
         function log(msg) {{
             console.log(msg);
@@ -244,89 +689,1210 @@ fn get_viewer_html(signaling_url: &str) -> String {
             logDiv.classList.toggle('show');
         }}
 
-        // 连接视频流
-        function connectVideoStream() {{
-            log('连接视频流: ' + SIGNALING_URL);
+        function toggleChat() {{
+            const chat = document.getElementById('chat');
+            chat.classList.toggle('show');
+            if (chat.classList.contains('show')) {{
+                unreadChat = 0;
+                document.getElementById('chat-btn').textContent = '聊天';
+                document.getElementById('chat-input').focus();
+            }}
+        }}
 
-            // 将信令 URL 转换为视频流 URL
-            const streamUrl = SIGNALING_URL.replace('/ws', '/video-stream');
+        function appendChat(message) {{
+            const list = document.getElementById('chat-messages');
+            const line = document.createElement('div');
+            line.className = 'chat-line' + (message.from === 'host' ? ' host' : '');
+            const from = document.createElement('span');
+            from.className = 'chat-from';
+            from.textContent = message.from === 'host' ? '被控端' : (message.from === selfPeerId ? '我' : message.from);
+            const time = document.createElement('span');
+            time.className = 'chat-time';
+            time.textContent = new Date(message.time_ms).toLocaleTimeString();
+            line.append(from, document.createTextNode(message.text), time);
+            list.appendChild(line);
+            list.scrollTop = list.scrollHeight;
 
-            canvas = document.getElementById('video-canvas');
-            ctx = canvas.getContext('2d');
-            imageElement = document.getElementById('video-image');
+            // 聊天面板未打开时在按钮上提示未读数
+            if (!document.getElementById('chat').classList.contains('show')) {{
+                unreadChat++;
+                document.getElementById('chat-btn').textContent = `聊天 (${{unreadChat}})`;
+            }}
+        }}
 
-            // 发送请求获取视频流
-            fetch(streamUrl).then(response => {{
+        // 拉取新的聊天消息
+        function pollChat() {{
+            fetchWithTicket('/chat', {{ after: String(lastChatId) }}).then(response => {{
                 if (!response.ok) {{
-                    throw new Error('无法连接视频流');
+                    throw new Error('HTTP ' + response.status);
                 }}
-                // 使用 reader 读取流
-                const reader = response.body.getReader();
+                return response.json();
+            }}).then(messages => {{
+                for (const message of messages) {{
+                    lastChatId = Math.max(lastChatId, message.id);
+                    appendChat(message);
+                }}
+            }}).catch(error => {{
+                console.log('拉取聊天消息失败: ' + error.message);
+            }}).finally(() => {{
+                setTimeout(pollChat, CHAT_POLL_MS);
+            }});
+        }}
 
-                function read() {{
-                    reader.read().then(({{ done, value }}) => {{
-                        if (done) {{
-                            log('视频流结束');
-                            return;
-                        }}
+        function sendChat(event) {{
+            event.preventDefault();
+            const input = document.getElementById('chat-input');
+            const text = input.value.trim();
+            if (!text) {{
+                return;
+            }}
 
-                        // value 是 Uint8Array，包含 JPEG 数据
-                        if (value && value.length > 0) {{
-                            const blob = new Blob([value], {{ type: 'image/jpeg' }});
-                            const url = URL.createObjectURL(blob);
-
-                            imageElement.onload = () => {{
-                                // 设置 canvas 大小匹配图片
-                                if (canvas.width !== imageElement.naturalWidth ||
-                                    canvas.height !== imageElement.naturalHeight) {{
-                                    canvas.width = imageElement.naturalWidth;
-                                    canvas.height = imageElement.naturalHeight;
-                                }}
-
-                                // 绘制图片
-                                ctx.drawImage(imageElement, 0, 0);
-
-                                // 释放 URL
-                                URL.revokeObjectURL(url);
-
-                                // 隐藏占位符
-                                placeholder.classList.add('hidden');
-                                setStatus(true, '已连接');
-
-                                // 计算 FPS
-                                frameCount++;
-                                const now = Date.now();
-                                if (now - lastFpsTime >= 1000) {{
-                                    const fps = Math.round(frameCount * 1000 / (now - lastFpsTime));
-                                    statusText.textContent = `已连接 ({{fps}} FPS)`;
-                                    frameCount = 0;
-                                    lastFpsTime = now;
-                                }}
-                            }};
-
-                            imageElement.src = url;
-                        }}
+            fetchWithTicket('/chat', {{}}, {{
+                method: 'POST',
+                headers: {{ 'Content-Type': 'application/json' }},
+                body: JSON.stringify({{ text }}),
+            }}).then(response => {{
+                if (!response.ok) {{
+                    throw new Error('HTTP ' + response.status);
+                }}
+                input.value = '';
+            }}).catch(error => {{
+                log('发送聊天消息失败: ' + error.message);
+            }});
+        }}
 
-                        // 继续读取下一帧
-                        read();
+        // ===== 系统信息侧边栏: 打开时定期请求 /sysinfo =====
+        function toggleSysInfo() {{
+            const panel = document.getElementById('sysinfo');
+            panel.classList.toggle('show');
+            clearTimeout(sysInfoTimer);
+            if (panel.classList.contains('show')) {{
+                panel.textContent = '正在获取系统信息...';
+                pollSysInfo();
+            }}
+        }}
+
+        function pollSysInfo() {{
+            fetchWithTicket('/sysinfo', {{ top: String(SYSINFO_TOP) }}).then(response => {{
+                if (!response.ok) {{
+                    throw new Error('HTTP ' + response.status);
+                }}
+                return response.json();
+            }}).then(renderSysInfo).catch(error => {{
+                document.getElementById('sysinfo').textContent = '获取系统信息失败: ' + error.message;
+            }}).finally(() => {{
+                if (document.getElementById('sysinfo').classList.contains('show')) {{
+                    sysInfoTimer = setTimeout(pollSysInfo, SYSINFO_POLL_MS);
+                }}
+            }});
+        }}
+
+        function formatBytes(bytes) {{
+            const units = ['B', 'KB', 'MB', 'GB', 'TB'];
+            let unit = 0;
+            while (bytes >= 1024 && unit < units.length - 1) {{
+                bytes /= 1024;
+                unit++;
+            }}
+            return (unit === 0 ? bytes : bytes.toFixed(1)) + ' ' + units[unit];
+        }}
+
+        function formatUptime(secs) {{
+            const days = Math.floor(secs / 86400);
+            const hours = Math.floor(secs % 86400 / 3600);
+            const minutes = Math.floor(secs % 3600 / 60);
+            return (days ? days + ' 天 ' : '') + (days || hours ? hours + ' 小时 ' : '') + minutes + ' 分钟';
+        }}
+
+        // 用 DOM 构建 (进程名等来自被控端，不能当作 HTML 插入)
+        function sysInfoTable(rows) {{
+            const table = document.createElement('table');
+            for (const cells of rows) {{
+                const tr = table.insertRow();
+                for (const [text, className] of cells) {{
+                    const td = tr.insertCell();
+                    td.textContent = text;
+                    if (className) {{
+                        td.className = className;
+                    }}
+                }}
+            }}
+            return table;
+        }}
+
+        function renderSysInfo(report) {{
+            const panel = document.getElementById('sysinfo');
+            const section = (title, rows) => {{
+                const heading = document.createElement('h4');
+                heading.textContent = title;
+                panel.append(heading, sysInfoTable(rows));
+            }};
+            panel.replaceChildren();
+
+            const load = report.cpu.load_average ? report.cpu.load_average.map(v => v.toFixed(2)).join(' ') : '-';
+            section('系统', [
+                [['主机名'], [report.hostname || '-', 'name']],
+                [['系统'], [report.os + ' (' + report.arch + ')', 'name']],
+                [['运行时间'], [formatUptime(report.uptime_secs)]],
+            ]);
+            section('负载', [
+                [['CPU'], [report.cpu.usage.toFixed(1) + '% (' + report.cpu.cores + ' 核)']],
+                [['平均负载'], [load]],
+                [['内存'], [formatBytes(report.memory.used) + ' / ' + formatBytes(report.memory.total)]],
+            ]);
+            section('进程 (共 ' + report.process_count + ' 个)', report.processes.map(p => [
+                [p.name, 'name'],
+                [p.cpu.toFixed(1) + '%', 'num'],
+                [formatBytes(p.memory), 'num'],
+            ]));
+            section('磁盘', report.disks.map(d => [
+                [d.mount_point, 'name'],
+                [formatBytes(d.total - d.available) + ' / ' + formatBytes(d.total), 'num'],
+            ]));
+            if (report.battery) {{
+                const states = {{ charging: '充电中', discharging: '使用电池', full: '已充满', unknown: '' }};
+                section('电池', [
+                    [[Math.round(report.battery.percent) + '%'], [states[report.battery.state] || '']],
+                ]);
+            }}
+        }}
+
+        // 切换 Host 画面中是否包含鼠标指针
+        function toggleCursor() {{
+            fetchWithTicket('/cursor', {{ show: String(!showCursor) }}, {{ method: 'POST' }}).then(response => {{
+                if (!response.ok) {{
+                    throw new Error('HTTP ' + response.status);
+                }}
+                showCursor = !showCursor;
+                document.getElementById('cursor-btn').textContent = showCursor ? '隐藏光标' : '显示光标';
+                log(showCursor ? '画面显示鼠标指针' : '画面隐藏鼠标指针');
+            }}).catch(error => {{
+                log('切换光标失败: ' + error.message);
+            }});
+        }}
+
+        // 切换 Host 的画质预设 (帧率、码率、分辨率上限)
+        function setProfile(name) {{
+            fetchWithTicket('/profile', {{ name }}, {{ method: 'POST' }}).then(response => {{
+                if (!response.ok) {{
+                    throw new Error('HTTP ' + response.status);
+                }}
+                log('画质预设已切换: ' + name);
+            }}).catch(error => {{
+                log('切换画质预设失败: ' + error.message);
+            }});
+        }}
+
+        // 请求 Host 执行电源操作 (注销、重启、关机先确认)
+        function powerAction(action, label) {{
+            if (action !== 'lock' && !confirm('确定要让远程主机' + label + '吗?')) {{
+                return;
+            }}
+            fetchWithTicket('/power', {{ action }}, {{ method: 'POST' }}).then(response => {{
+                if (response.status === 403) {{
+                    throw new Error('没有权限');
+                }}
+                if (!response.ok) {{
+                    throw new Error('HTTP ' + response.status);
+                }}
+                log('已请求远程主机' + label);
+            }}).catch(error => {{
+                log(label + '失败: ' + error.message);
+            }});
+        }}
+
+        // 文件上传: 拖放到画面上或点"传输文件"选择，保存到 Host 的接收目录 (默认是桌面)
+        let fileTransferAllowed = false;
+
+        function formatSize(bytes) {{
+            const units = ['B', 'KB', 'MB', 'GB'];
+            let size = bytes;
+            let unit = 0;
+            while (size >= 1024 && unit < units.length - 1) {{
+                size /= 1024;
+                unit++;
+            }}
+            return `${{size.toFixed(unit === 0 ? 0 : 1)}} ${{units[unit]}}`;
+        }}
+
+        function escapeText(text) {{
+            const span = document.createElement('span');
+            span.textContent = text;
+            return span.innerHTML;
+        }}
+
+        function uploadFiles(files) {{
+            if (!fileTransferAllowed) {{
+                log('没有文件传输权限');
+                return;
+            }}
+            for (const file of files) {{
+                const name = escapeText(file.name);
+                log(`正在上传 ${{name}} (${{formatSize(file.size)}})`);
+
+                fetchWithTicket('/upload', {{ name: file.name }}, {{ method: 'POST', body: file }}).then(response => {{
+                    if (response.status === 403) {{
+                        throw new Error('没有权限');
+                    }}
+                    if (response.status === 413) {{
+                        throw new Error('文件超过 Host 的大小上限');
+                    }}
+                    if (!response.ok) {{
+                        throw new Error('HTTP ' + response.status);
+                    }}
+                    return response.json();
+                }}).then(saved => {{
+                    log(`已上传 ${{escapeText(saved.name)}}`);
+                }}).catch(error => {{
+                    log(`上传 ${{name}} 失败: ${{error.message}}`);
+                }});
+            }}
+        }}
+
+        function setupFileDrop() {{
+            const container = document.getElementById('video-container');
+            const fileInput = document.getElementById('file-input');
+            document.getElementById('file-btn').addEventListener('click', () => fileInput.click());
+            fileInput.addEventListener('change', () => {{
+                uploadFiles(fileInput.files);
+                fileInput.value = '';
+            }});
+
+            container.addEventListener('dragover', event => {{
+                if (!fileTransferAllowed || !event.dataTransfer.types.includes('Files')) {{
+                    return;
+                }}
+                event.preventDefault();
+                event.dataTransfer.dropEffect = 'copy';
+                container.classList.add('drop-target');
+            }});
+            container.addEventListener('dragleave', event => {{
+                if (!container.contains(event.relatedTarget)) {{
+                    container.classList.remove('drop-target');
+                }}
+            }});
+            container.addEventListener('drop', event => {{
+                container.classList.remove('drop-target');
+                if (event.dataTransfer.files.length === 0) {{
+                    return;
+                }}
+                event.preventDefault();
+                uploadFiles(event.dataTransfer.files);
+            }});
+        }}
+
+        function formatUtcOffset(minutes) {{
+            const sign = minutes < 0 ? '-' : '+';
+            const abs = Math.abs(minutes);
+            const pad = n => String(n).padStart(2, '0');
+            return `UTC${{sign}}${{pad(Math.floor(abs / 60))}}:${{pad(abs % 60)}}`;
+        }}
+
+        // 按 Host 时区显示 Host 当前时间
+        function updateHostClock() {{
+            const hostNow = new Date(Date.now() + hostClockOffset);
+            const offset = formatUtcOffset(hostInfo.utc_offset_minutes);
+            let text = null;
+            if (hostInfo.timezone) {{
+                try {{
+                    text = hostNow.toLocaleTimeString(hostInfo.locale || undefined, {{ timeZone: hostInfo.timezone }});
+                }} catch (e) {{
+                    // 浏览器不认识该时区，退回按 UTC 偏移计算
+                }}
+            }}
+            if (text === null) {{
+                text = new Date(hostNow.getTime() + hostInfo.utc_offset_minutes * 60000)
+                    .toISOString().substring(11, 19);
+            }}
+            hostClock.textContent = `Host 时间 ${{text}} (${{hostInfo.timezone || offset}})`;
+            hostClock.title = `时区: ${{hostInfo.timezone || '未知'}} ${{offset}}\n区域: ${{hostInfo.locale || '未知'}}`;
+        }}
+
+        // 获取 Host 时区/区域信息
+        function fetchHostInfo() {{
+            const infoUrl = new URL(SIGNALING_URL.replace(/^ws/, 'http'));
+            infoUrl.pathname = '/host-info';
+
+            fetch(infoUrl).then(response => {{
+                if (!response.ok) {{
+                    throw new Error('HTTP ' + response.status);
+                }}
+                return response.json();
+            }}).then(info => {{
+                hostInfo = info;
+                hostClockOffset = info.time_ms - Date.now();
+                log(`Host 时区: ${{info.timezone || '未知'}} (${{formatUtcOffset(info.utc_offset_minutes)}}), 区域: ${{info.locale || '未知'}}`);
+                updateHostClock();
+                setInterval(updateHostClock, 1000);
+            }}).catch(error => {{
+                log('获取 Host 信息失败: ' + error.message);
+            }});
+        }}
+
+        // 按会话权限禁用无权限的控件 (信令连接放行时及权限变更时下发)
+        function applyPermissions(perms) {{
+            const granted = [];
+            if (perms.input) granted.push('输入');
+            if (perms.clipboard) granted.push('剪贴板');
+            if (perms.file_transfer) granted.push('文件传输');
+            if (perms.power) granted.push('电源操作');
+            const viewOnly = granted.length === 0;
+
+            const badge = document.getElementById('perm-badge');
+            badge.textContent = viewOnly ? '仅查看' : granted.join(' / ');
+            badge.classList.toggle('view-only', viewOnly);
+            badge.style.display = '';
+            document.getElementById('clipboard-btn').disabled = !perms.clipboard;
+            document.getElementById('file-btn').disabled = !perms.file_transfer;
+            fileTransferAllowed = perms.file_transfer;
+            document.getElementById('lock-btn').disabled = !perms.input;
+            document.querySelectorAll('.power-btn').forEach(btn => btn.disabled = !perms.power);
+            document.getElementById('video-container').style.cursor = perms.input ? '' : 'not-allowed';
+            log('会话权限: ' + badge.textContent);
+        }}
+
+        // ===== 信令连接: 通过 PIN/TOTP 验证和被控端审批后换取一次性票据 =====
+        // 视频流等 HTTP 接口无法携带信令连接的身份，每次请求出示一张新票据 (?ticket=)
+        // 首次请求票据时被控端可能需要人工确认
+        const TICKET_TIMEOUT_MS = 120000;
+        // 拿不到票据 (被拒绝、超时) 时重试的间隔
+        const TICKET_RETRY_MS = 5000;
+        let selfPeerId = null;
+        let verified = false;
+        let pinRequired = false;
+        let totpRequired = false;
+        // 等待票据的请求 (按发出顺序依次收到票据)
+        let ticketWaiters = [];
+
+        function sendSignal(message) {{
+            if (ws && ws.readyState === WebSocket.OPEN) {{
+                ws.send(JSON.stringify(message));
+            }}
+        }}
+
+        function connectSignaling() {{
+            const socket = new WebSocket(SIGNALING_URL);
+            ws = socket;
+            socket.onopen = () => {{
+                log('信令已连接');
+                sendSignal({{ type: 'join', room_id: 'default' }});
+            }};
+            socket.onmessage = event => {{
+                if (typeof event.data !== 'string') {{
+                    return;
+                }}
+                let message;
+                try {{
+                    message = JSON.parse(event.data);
+                }} catch (e) {{
+                    return;
+                }}
+                handleSignal(message);
+            }};
+            socket.onclose = () => {{
+                if (ws !== socket) {{
+                    return;
+                }}
+                ws = null;
+                verified = false;
+                pinRequired = false;
+                totpRequired = false;
+                failTicketWaiters('信令连接已断开');
+                log('信令连接断开，正在重连...');
+                setTimeout(connectSignaling, RECONNECT_DELAY_MS);
+            }};
+        }}
+
+        function handleSignal(message) {{
+            switch (message.type) {{
+                case 'pin_required':
+                    pinRequired = true;
+                    break;
+                case 'totp_required':
+                    totpRequired = true;
+                    break;
+                case 'permissions':
+                    applyPermissions(message.permissions);
+                    if (!verified) {{
+                        continueVerification();
+                    }}
+                    break;
+                case 'pin_result':
+                    if (message.accepted) {{
+                        pinRequired = false;
+                        log('PIN 验证通过');
+                        continueVerification();
+                    }} else {{
+                        retryVerification('PIN', message, promptPin);
+                    }}
+                    break;
+                case 'totp_result':
+                    if (message.accepted) {{
+                        totpRequired = false;
+                        log('验证码验证通过');
+                        continueVerification();
+                    }} else {{
+                        retryVerification('验证码', message, promptTotp);
+                    }}
+                    break;
+                case 'queued':
+                    log(`会话已满，排队中 (第 ${{message.position}} / ${{message.queue_length}} 位)`);
+                    setStatus(false, '排队中...');
+                    break;
+                case 'ticket':
+                    selfPeerId = message.peer_id;
+                    resolveTicket(message.ticket);
+                    break;
+                case 'error':
+                    log('被控端: ' + message.message);
+                    failTicketWaiters(message.message);
+                    break;
+            }}
+        }}
+
+        // 依次完成 PIN 和 TOTP 验证，全部通过后发出等待中的票据请求
+        function continueVerification() {{
+            if (pinRequired) {{
+                promptPin();
+            }} else if (totpRequired) {{
+                promptTotp();
+            }} else if (!verified) {{
+                verified = true;
+                ticketWaiters.filter(waiter => !waiter.sent).forEach(waiter => {{
+                    waiter.sent = true;
+                    sendSignal({{ type: 'request_ticket' }});
+                }});
+            }}
+        }}
+
+        function promptPin() {{
+            const pin = window.prompt('请输入被控端显示的 PIN');
+            if (pin === null) {{
+                log('已取消 PIN 验证');
+                setStatus(false, '需要 PIN');
+                return;
+            }}
+            sendSignal({{ type: 'pin', pin: pin.trim() }});
+        }}
+
+        function promptTotp() {{
+            const code = window.prompt('请输入身份验证器 App 中的 6 位验证码');
+            if (code === null) {{
+                log('已取消验证码验证');
+                setStatus(false, '需要验证码');
+                return;
+            }}
+            sendSignal({{ type: 'totp', code: code.trim() }});
+        }}
+
+        function retryVerification(label, result, prompt) {{
+            if (result.retry_after_secs) {{
+                log(`${{label}} 错误次数过多，${{result.retry_after_secs}} 秒后重试`);
+                setTimeout(prompt, result.retry_after_secs * 1000);
+            }} else {{
+                log(`${{label}} 错误 (剩余 ${{result.remaining_attempts}} 次)`);
+                prompt();
+            }}
+        }}
+
+        // 请求一张一次性票据 (信令连接通过验证后才发出)
+        function requestTicket() {{
+            return new Promise((resolve, reject) => {{
+                const waiter = {{ resolve, reject, sent: false }};
+                waiter.timer = setTimeout(() => {{
+                    ticketWaiters = ticketWaiters.filter(other => other !== waiter);
+                    reject(new Error('等待票据超时'));
+                }}, TICKET_TIMEOUT_MS);
+                ticketWaiters.push(waiter);
+                if (verified) {{
+                    waiter.sent = true;
+                    sendSignal({{ type: 'request_ticket' }});
+                }}
+            }});
+        }}
+
+        function resolveTicket(ticket) {{
+            const waiter = ticketWaiters.shift();
+            if (waiter) {{
+                clearTimeout(waiter.timer);
+                waiter.resolve(ticket);
+            }}
+        }}
+
+        function failTicketWaiters(reason) {{
+            const waiters = ticketWaiters;
+            ticketWaiters = [];
+            waiters.forEach(waiter => {{
+                clearTimeout(waiter.timer);
+                waiter.reject(new Error(reason));
+            }});
+        }}
+
+        // 带票据的接口地址 (path 为 HTTP 接口或 /video)
+        function ticketUrl(base, path, ticket) {{
+            const url = new URL(base);
+            url.pathname = path;
+            url.searchParams.set('ticket', ticket);
+            return url;
+        }}
+
+        // 带票据请求 HTTP 接口 (params 为其余查询参数)
+        function fetchWithTicket(path, params, options) {{
+            return requestTicket().then(ticket => {{
+                const url = ticketUrl(SIGNALING_URL.replace(/^ws/, 'http'), path, ticket);
+                Object.entries(params).forEach(([key, value]) => url.searchParams.set(key, value));
+                return fetch(url, options);
+            }});
+        }}
+
+        // 视频流 (/video): 每条二进制消息为 16 字节头 + H.264 Annex B 码流
+        // 优先用 WebCodecs 解码 (浏览器硬件解码)，不支持时封装为 fMP4 交给 MSE
+        const HEADER_LEN = 16;
+        // 解码队列积压超过该帧数时丢到下一个关键帧
+        const MAX_DECODE_QUEUE = 8;
+        // MSE 缓冲超过该秒数时跳到最新画面
+        const MAX_MSE_LAG_S = 0.5;
+        let useWebCodecs = 'VideoDecoder' in window && 'EncodedVideoChunk' in window;
+        let decoder = null;
+        let decoderCodec = null;
+        let waitingKeyFrame = true;
+        let mse = null;
+
+        function videoUrl(ticket) {{
+            return ticketUrl(SIGNALING_URL, '/video', ticket);
+        }}
+
+        // 按起始码拆分 NAL 单元
+        function splitNalUnits(data) {{
+            const units = [];
+            let start = -1;
+            for (let i = 0; i + 2 < data.length; i++) {{
+                if (data[i] === 0 && data[i + 1] === 0 && data[i + 2] === 1) {{
+                    if (start >= 0) {{
+                        let end = i;
+                        while (end > start && data[end - 1] === 0) end--;
+                        units.push(data.subarray(start, end));
+                    }}
+                    start = i + 3;
+                    i += 2;
+                }}
+            }}
+            if (start >= 0 && start < data.length) {{
+                units.push(data.subarray(start));
+            }}
+            return units;
+        }}
+
+        function nalType(nal) {{
+            return nal[0] & 0x1f;
+        }}
+
+        // SPS 中的 profile / 约束 / level 组成 codecs 参数 (如 avc1.42e01f)
+        function avcCodecString(sps) {{
+            const hex = value => value.toString(16).padStart(2, '0');
+            return 'avc1.' + hex(sps[1]) + hex(sps[2]) + hex(sps[3]);
+        }}
+
+        function frameShown() {{
+            placeholder.classList.add('hidden');
+            frameCount++;
+            const now = Date.now();
+            if (now - lastFpsTime >= 1000) {{
+                const fps = Math.round(frameCount * 1000 / (now - lastFpsTime));
+                shownFps = fps;
+                setStatus(true, `已连接 (${{fps}} FPS)`);
+                frameCount = 0;
+                lastFpsTime = now;
+            }}
+        }}
+
+        function drawFrame(frame) {{
+            if (canvas.width !== frame.displayWidth || canvas.height !== frame.displayHeight) {{
+                canvas.width = frame.displayWidth;
+                canvas.height = frame.displayHeight;
+            }}
+            ctx.drawImage(frame, 0, 0);
+            frame.close();
+            frameShown();
+        }}
+
+        function resetDecoders() {{
+            if (decoder && decoder.state !== 'closed') {{
+                decoder.close();
+            }}
+            decoder = null;
+            decoderCodec = null;
+            waitingKeyFrame = true;
+            mse = null;
+        }}
+
+        function decodeWithWebCodecs(header, payload, socket) {{
+            if (header.keyFrame) {{
+                const sps = splitNalUnits(payload).find(nal => nalType(nal) === 7);
+                const codec = sps ? avcCodecString(sps) : null;
+                if (codec && (codec !== decoderCodec || !decoder || decoder.state === 'closed')) {{
+                    if (decoder && decoder.state !== 'closed') {{
+                        decoder.close();
+                    }}
+                    // 不提供 description 时按 Annex B 解析，SPS/PPS 随关键帧下发
+                    const config = {{ codec, optimizeForLatency: true, hardwareAcceleration: 'prefer-hardware' }};
+                    decoder = new VideoDecoder({{
+                        output: drawFrame,
+                        error: error => {{
+                            log('WebCodecs 解码错误: ' + error.message);
+                            decoderCodec = null;
+                            waitingKeyFrame = true;
+                            socket.send('key_frame');
+                        }},
                     }});
+                    decoder.configure(config);
+                    decoderCodec = codec;
+                    log('WebCodecs 解码: ' + codec);
+                    VideoDecoder.isConfigSupported(config).then(result => {{
+                        if (!result.supported) {{
+                            log('WebCodecs 不支持 ' + codec + '，改用 MSE');
+                            useWebCodecs = false;
+                            resetDecoders();
+                            socket.send('key_frame');
+                        }}
+                    }}).catch(() => {{}});
+                }}
+                waitingKeyFrame = false;
+            }}
+            if (waitingKeyFrame || !decoder || decoder.state !== 'configured') {{
+                return;
+            }}
+            // 解码跟不上时丢弃差分帧，从下一个关键帧恢复
+            if (decoder.decodeQueueSize > MAX_DECODE_QUEUE) {{
+                waitingKeyFrame = true;
+                socket.send('key_frame');
+                return;
+            }}
+            decoder.decode(new EncodedVideoChunk({{
+                type: header.keyFrame ? 'key' : 'delta',
+                timestamp: header.timestamp,
+                data: payload,
+            }}));
+        }}
+
+        // ---- fMP4 封装 (MSE 回退) ----
+        function bytes(...values) {{
+            return new Uint8Array(values);
+        }}
+
+        function u16(value) {{
+            return bytes((value >>> 8) & 0xff, value & 0xff);
+        }}
+
+        function u32(value) {{
+            return bytes((value >>> 24) & 0xff, (value >>> 16) & 0xff, (value >>> 8) & 0xff, value & 0xff);
+        }}
+
+        function box(type, ...payloads) {{
+            const size = payloads.reduce((total, payload) => total + payload.length, 8);
+            const out = new Uint8Array(size);
+            new DataView(out.buffer).setUint32(0, size);
+            for (let i = 0; i < 4; i++) {{
+                out[4 + i] = type.charCodeAt(i);
+            }}
+            let offset = 8;
+            for (const payload of payloads) {{
+                out.set(payload, offset);
+                offset += payload.length;
+            }}
+            return out;
+        }}
+
+        function fullBox(type, version, flags, ...payloads) {{
+            return box(type, bytes(version, (flags >>> 16) & 0xff, (flags >>> 8) & 0xff, flags & 0xff), ...payloads);
+        }}
+
+        const MATRIX = [0x00010000, 0, 0, 0, 0x00010000, 0, 0, 0, 0x40000000].map(u32);
+        // 时间刻度: 毫秒
+        const TIMESCALE = 1000;
+
+        function initSegment(sps, pps, width, height) {{
+            const avcC = box('avcC',
+                bytes(1, sps[1], sps[2], sps[3], 0xff, 0xe1), u16(sps.length), sps,
+                bytes(1), u16(pps.length), pps);
+            const avc1 = box('avc1',
+                new Uint8Array(6), u16(1), new Uint8Array(16), u16(width), u16(height),
+                u32(0x00480000), u32(0x00480000), u32(0), u16(1), new Uint8Array(32), u16(0x18), u16(0xffff),
+                avcC);
+            const empty = type => fullBox(type, 0, 0, u32(0));
+            const stbl = box('stbl',
+                fullBox('stsd', 0, 0, u32(1), avc1),
+                empty('stts'), empty('stsc'), fullBox('stsz', 0, 0, u32(0), u32(0)), empty('stco'));
+            const dinf = box('dinf', fullBox('dref', 0, 0, u32(1), fullBox('url ', 0, 1)));
+            const minf = box('minf', fullBox('vmhd', 0, 1, new Uint8Array(8)), dinf, stbl);
+            const hdlr = fullBox('hdlr', 0, 0, u32(0), bytes(0x76, 0x69, 0x64, 0x65), new Uint8Array(12),
+                new TextEncoder().encode('VideoHandler\0'));
+            const mdhd = fullBox('mdhd', 0, 0, u32(0), u32(0), u32(TIMESCALE), u32(0), u16(0x55c4), u16(0));
+            const tkhd = fullBox('tkhd', 0, 3, u32(0), u32(0), u32(1), u32(0), u32(0), new Uint8Array(8),
+                u16(0), u16(0), u16(0), u16(0), ...MATRIX, u32(width << 16), u32(height << 16));
+            const trak = box('trak', tkhd, box('mdia', mdhd, hdlr, minf));
+            const mvhd = fullBox('mvhd', 0, 0, u32(0), u32(0), u32(TIMESCALE), u32(0), u32(0x00010000), u16(0x0100),
+                new Uint8Array(10), ...MATRIX, new Uint8Array(24), u32(2));
+            const mvex = box('mvex', fullBox('trex', 0, 0, u32(1), u32(1), u32(0), u32(0), u32(0)));
+            const ftyp = box('ftyp', new TextEncoder().encode('isom'), u32(0x200),
+                new TextEncoder().encode('isomiso6avc1mp41'));
+            const moov = box('moov', mvhd, trak, mvex);
+            const out = new Uint8Array(ftyp.length + moov.length);
+            out.set(ftyp, 0);
+            out.set(moov, ftyp.length);
+            return out;
+        }}
+
+        function mediaSegment(sequence, decodeTime, duration, keyFrame, units) {{
+            // 长度前缀格式，参数集已在初始化段中
+            const samples = units.filter(nal => ![7, 8, 9].includes(nalType(nal)));
+            const size = samples.reduce((total, nal) => total + 4 + nal.length, 0);
+            const mdat = new Uint8Array(8 + size);
+            const view = new DataView(mdat.buffer);
+            view.setUint32(0, mdat.length);
+            mdat.set(bytes(0x6d, 0x64, 0x61, 0x74), 4);
+            let offset = 8;
+            for (const nal of samples) {{
+                view.setUint32(offset, nal.length);
+                mdat.set(nal, offset + 4);
+                offset += 4 + nal.length;
+            }}
+
+            const flags = keyFrame ? 0x02000000 : 0x01010000;
+            const moof = dataOffset => box('moof',
+                fullBox('mfhd', 0, 0, u32(sequence)),
+                box('traf',
+                    fullBox('tfhd', 0, 0x020000, u32(1)),
+                    fullBox('tfdt', 1, 0, u32(Math.floor(decodeTime / 0x100000000)), u32(decodeTime >>> 0)),
+                    fullBox('trun', 0, 0x000701, u32(1), u32(dataOffset), u32(duration), u32(size), u32(flags))));
+            const header = moof(0);
+            const out = new Uint8Array(header.length + mdat.length);
+            out.set(moof(header.length + 8), 0);
+            out.set(mdat, header.length);
+            return out;
+        }}
+
+        function setupMse(sps, pps, width, height) {{
+            const codec = avcCodecString(sps);
+            const mime = `video/mp4; codecs="${{codec}}"`;
+            if (!window.MediaSource || !MediaSource.isTypeSupported(mime)) {{
+                log('浏览器不支持 ' + mime + '，无法播放视频');
+                return null;
+            }}
+            const video = document.getElementById('video-element');
+            const source = new MediaSource();
+            const state = {{
+                codec, width, height, video,
+                queue: [initSegment(sps, pps, width, height)],
+                buffer: null, sequence: 0, baseTime: null, lastTime: null,
+            }};
+            video.src = URL.createObjectURL(source);
+            source.addEventListener('sourceopen', () => {{
+                URL.revokeObjectURL(video.src);
+                state.buffer = source.addSourceBuffer(mime);
+                state.buffer.mode = 'segments';
+                state.buffer.addEventListener('updateend', () => flushMse(state));
+                flushMse(state);
+            }});
+            canvas.style.display = 'none';
+            video.style.display = 'block';
+            video.play().catch(() => {{}});
+            log('MSE 播放: ' + codec);
+            return state;
+        }}
+
+        function flushMse(state) {{
+            const buffer = state.buffer;
+            if (!buffer || buffer.updating) {{
+                return;
+            }}
+            if (state.queue.length > 0) {{
+                buffer.appendBuffer(state.queue.shift());
+                return;
+            }}
+            const video = state.video;
+            if (buffer.buffered.length > 0) {{
+                const end = buffer.buffered.end(buffer.buffered.length - 1);
+                // 直播: 落后太多时跳到最新画面
+                if (end - video.currentTime > MAX_MSE_LAG_S) {{
+                    video.currentTime = Math.max(end - 0.05, 0);
+                }}
+                // 释放已播放的缓冲
+                if (video.currentTime - buffer.buffered.start(0) > 10) {{
+                    buffer.remove(0, video.currentTime - 5);
                 }}
+            }}
+        }}
 
-                read();
+        function appendMse(header, payload) {{
+            const units = splitNalUnits(payload);
+            if (header.keyFrame) {{
+                const sps = units.find(nal => nalType(nal) === 7);
+                const pps = units.find(nal => nalType(nal) === 8);
+                const changed = !mse || mse.codec !== (sps && avcCodecString(sps))
+                    || mse.width !== header.width || mse.height !== header.height;
+                if (sps && pps && changed) {{
+                    mse = setupMse(sps, pps, header.width, header.height);
+                }}
+            }}
+            if (!mse) {{
+                return;
+            }}
+            const time = Math.round(header.timestamp / 1000);
+            if (mse.baseTime === null) {{
+                mse.baseTime = time;
+            }}
+            const duration = mse.lastTime === null ? 33 : Math.max(time - mse.lastTime, 1);
+            mse.lastTime = time;
+            mse.sequence++;
+            mse.queue.push(mediaSegment(mse.sequence, time - mse.baseTime, duration, header.keyFrame, units));
+            flushMse(mse);
+            frameShown();
+        }}
+
+        // Host 状态通知: 捕获或编码异常时画面中断，重建编码器后从关键帧恢复
+        function handleStreamNotice(text) {{
+            let notice;
+            try {{
+                notice = JSON.parse(text);
+            }} catch (e) {{
+                return;
+            }}
+            if (notice.type === 'stream_interrupted') {{
+                log('画面中断: ' + notice.reason);
+                setStatus(false, notice.retry_ms == null ? '画面中断 (Host 已停止重试)' : '画面中断，正在恢复...');
+            }} else if (notice.type === 'stream_resumed') {{
+                log('画面已恢复');
+                setStatus(false, '等待关键帧...');
+                resetDecoders();
+            }} else if (notice.type === 'quality') {{
+                showQuality(notice);
+            }} else if (notice.type === 'encoder_switch') {{
+                // 新编码器的首帧是关键帧，解码器按其中的 SPS 重新配置
+                log(`编码器已切换: ${{notice.from}} → ${{notice.to}}`);
+            }}
+        }}
+
+        // 连接质量: /video 没有 RTT 和丢包统计，显示帧率明显低于编码帧率时说明网络或解码跟不上
+        function showQuality(report) {{
+            let grade = report.grade;
+            if (report.rtt_ms == null && shownFps !== null && report.fps >= 5) {{
+                const ratio = shownFps / report.fps;
+                if (ratio < 0.5) {{
+                    grade = 'poor';
+                }} else if (ratio < 0.8 && grade === 'good') {{
+                    grade = 'fair';
+                }}
+            }}
+            window.sscontrolQuality = report;
+            window.dispatchEvent(new CustomEvent('sscontrol-quality', {{ detail: report }}));
+
+            const parts = [Math.round(report.bitrate_kbps) + ' kbps', Math.round(report.fps) + ' fps'];
+            if (report.rtt_ms != null) {{
+                parts.push(Math.round(report.rtt_ms) + ' ms');
+            }}
+            if (report.packet_loss > 0) {{
+                parts.push('丢包 ' + (report.packet_loss * 100).toFixed(1) + '%');
+            }}
+            qualityText.textContent = parts.join(' · ');
+            qualityBadge.title = report.codec + (report.encoder ? ' / ' + report.encoder : '');
+            qualityBadge.className = grade;
+            qualityBadge.style.display = 'flex';
+        }}
+
+        // 连接视频流 (每次连接使用一张新票据)
+        function connectVideoStream() {{
+            requestTicket().then(openVideoStream).catch(error => {{
+                log('无法连接视频流: ' + error.message);
+                setStatus(false, '等待授权...');
+                setTimeout(connectVideoStream, TICKET_RETRY_MS);
+            }});
+        }}
+
+        function openVideoStream(ticket) {{
+            const url = videoUrl(ticket);
+            log('连接视频流: ' + url.origin + url.pathname + ' (' + (useWebCodecs ? 'WebCodecs' : 'MSE') + ')');
+
+            canvas = document.getElementById('video-canvas');
+            ctx = canvas.getContext('2d');
+
+            const socket = new WebSocket(url);
+            socket.binaryType = 'arraybuffer';
+            socket.onopen = () => {{
+                videoSocket = socket;
                 log('视频流已开始');
                 setStatus(false, '接收视频流...');
-            }}).catch(error => {{
-                log('视频流错误: ' + error.message);
-                setStatus(false, '连接失败');
+            }};
+            socket.onmessage = event => {{
+                if (typeof event.data === 'string') {{
+                    handleStreamNotice(event.data);
+                    return;
+                }}
+                if (!(event.data instanceof ArrayBuffer) || event.data.byteLength < HEADER_LEN) {{
+                    return;
+                }}
+                const view = new DataView(event.data);
+                const header = {{
+                    keyFrame: (view.getUint8(0) & 0x01) !== 0,
+                    width: view.getUint16(4),
+                    height: view.getUint16(6),
+                    timestamp: Number(view.getBigUint64(8)),
+                }};
+                const payload = new Uint8Array(event.data, HEADER_LEN);
+                if (useWebCodecs) {{
+                    decodeWithWebCodecs(header, payload, socket);
+                }} else {{
+                    appendMse(header, payload);
+                }}
+            }};
+            socket.onclose = () => {{
+                // 网络中断或 Host 重启: 重新连接，从关键帧开始解码
+                log('视频流中断，正在重连...');
+                setStatus(false, '重新连接...');
+                resetDecoders();
+                setTimeout(connectVideoStream, RECONNECT_DELAY_MS);
+            }};
+        }}
+
+        // ===== 输入: 通过视频流连接发送 JSON 输入事件 (无输入权限时 Host 忽略) =====
+        // 触摸事件由 Host 转换: 轻点 = 左键，长按 = 右键，拖动 = 左键拖动，双指 = 滚动，捏合 = 缩放
+        let videoSocket = null;
+        // 浏览器滚轮像素换算为一格
+        const WHEEL_STEP_PX = 100;
+        const MOUSE_BUTTONS = ['left', 'middle', 'right'];
+        const MODIFIER_MODES = ['off', 'once', 'lock'];
+        const modifierModes = {{}};
+        let wheelRemainder = {{x: 0, y: 0}};
 
-                // 5秒后重连
-                setTimeout(connectVideoStream, 5000);
+        function sendInput(event) {{
+            if (videoSocket && videoSocket.readyState === WebSocket.OPEN) {{
+                videoSocket.send(JSON.stringify(event));
+            }}
+        }}
+
+        function isVideoSurface(target) {{
+            return target && (target.id === 'video-canvas' || target.id === 'video-element');
+        }}
+
+        function normalizedPosition(target, clientX, clientY) {{
+            const rect = target.getBoundingClientRect();
+            return {{
+                x: Math.min(Math.max((clientX - rect.left) / rect.width, 0), 1),
+                y: Math.min(Math.max((clientY - rect.top) / rect.height, 0), 1),
+            }};
+        }}
+
+        function keyName(key) {{
+            return key === ' ' ? 'space' : key;
+        }}
+
+        function sendKey(key) {{
+            sendInput({{ KeyEvent: {{ key, pressed: true }} }});
+            sendInput({{ KeyEvent: {{ key, pressed: false }} }});
+        }}
+
+        // 组合键菜单: 本机系统会拦截的组合键 (Ctrl+Alt+Del、Cmd+Tab) 由 Host 整体注入
+        function sendShortcut(select) {{
+            sendInput({{ Shortcut: {{ combo: select.value }} }});
+            select.value = '';
+            clearOnceModifiers();
+        }}
+
+        // Host 在下一次按键或点击后松开 once 修饰键，这里同步按钮状态
+        function clearOnceModifiers() {{
+            for (const [key, mode] of Object.entries(modifierModes)) {{
+                if (mode === 'once') {{
+                    setModifierMode(key, 'off', false);
+                }}
+            }}
+        }}
+
+        function setModifierMode(key, mode, send) {{
+            modifierModes[key] = mode;
+            const button = document.querySelector(`[data-modifier="${{key}}"]`);
+            button.classList.toggle('once', mode === 'once');
+            button.classList.toggle('lock', mode === 'lock');
+            if (send) {{
+                sendInput({{ Modifier: {{ key, mode }} }});
+            }}
+        }}
+
+        function setupInput() {{
+            const container = document.getElementById('video-container');
+
+            // 鼠标 (触摸产生的指针事件由下面的 touch 事件处理)
+            container.addEventListener('pointermove', event => {{
+                if (event.pointerType !== 'touch' && isVideoSurface(event.target)) {{
+                    sendInput({{ MouseMove: normalizedPosition(event.target, event.clientX, event.clientY) }});
+                }}
+            }});
+            for (const [type, pressed] of [['pointerdown', true], ['pointerup', false]]) {{
+                container.addEventListener(type, event => {{
+                    if (event.pointerType === 'touch' || !isVideoSurface(event.target) || !MOUSE_BUTTONS[event.button]) {{
+                        return;
+                    }}
+                    event.preventDefault();
+                    sendInput({{ MouseMove: normalizedPosition(event.target, event.clientX, event.clientY) }});
+                    sendInput({{ MouseClick: {{ button: MOUSE_BUTTONS[event.button], pressed }} }});
+                    if (!pressed) {{
+                        clearOnceModifiers();
+                    }}
+                }});
+            }}
+            container.addEventListener('contextmenu', event => {{
+                if (isVideoSurface(event.target)) {{
+                    event.preventDefault();
+                }}
+            }});
+            container.addEventListener('wheel', event => {{
+                if (!isVideoSurface(event.target)) {{
+                    return;
+                }}
+                event.preventDefault();
+                wheelRemainder.x += event.deltaX;
+                wheelRemainder.y += event.deltaY;
+                const x = Math.trunc(wheelRemainder.x / WHEEL_STEP_PX);
+                const y = Math.trunc(wheelRemainder.y / WHEEL_STEP_PX);
+                if (x !== 0 || y !== 0) {{
+                    wheelRemainder.x -= x * WHEEL_STEP_PX;
+                    wheelRemainder.y -= y * WHEEL_STEP_PX;
+                    // 浏览器向下滚动为正，Host 向上滚动为正
+                    sendInput({{ MouseWheel: {{ delta_x: x, delta_y: -y }} }});
+                }}
+            }}, {{ passive: false }});
+
+            // 触摸: 原样发送触点，由 Host 识别手势
+            for (const [type, phase] of [['touchstart', 'down'], ['touchmove', 'move'], ['touchend', 'up'], ['touchcancel', 'cancel']]) {{
+                container.addEventListener(type, event => {{
+                    if (!isVideoSurface(event.target)) {{
+                        return;
+                    }}
+                    event.preventDefault();
+                    document.body.classList.add('touch');
+                    for (const touch of event.changedTouches) {{
+                        const position = normalizedPosition(event.target, touch.clientX, touch.clientY);
+                        sendInput({{ Touch: {{ id: touch.identifier, phase, x: position.x, y: position.y }} }});
+                    }}
+                    if (phase === 'up' && event.touches.length === 0) {{
+                        clearOnceModifiers();
+                    }}
+                }}, {{ passive: false }});
+            }}
+
+            // 物理键盘 (聊天输入框等获得焦点时不转发)
+            for (const [type, pressed] of [['keydown', true], ['keyup', false]]) {{
+                document.addEventListener(type, event => {{
+                    if (event.target.closest('input, select, textarea') || event.key === 'Unidentified') {{
+                        return;
+                    }}
+                    event.preventDefault();
+                    sendInput({{ KeyEvent: {{ key: keyName(event.key), pressed }} }});
+                    if (!pressed) {{
+                        clearOnceModifiers();
+                    }}
+                }});
+            }}
+
+            // 屏幕修饰键栏
+            if (window.matchMedia('(pointer: coarse)').matches) {{
+                document.body.classList.add('touch');
+            }}
+            document.querySelectorAll('[data-modifier]').forEach(button => {{
+                const key = button.dataset.modifier;
+                modifierModes[key] = 'off';
+                button.addEventListener('click', () => {{
+                    const next = MODIFIER_MODES[(MODIFIER_MODES.indexOf(modifierModes[key]) + 1) % MODIFIER_MODES.length];
+                    setModifierMode(key, next, true);
+                }});
+            }});
+            document.querySelectorAll('[data-key]').forEach(button => {{
+                button.addEventListener('click', () => {{
+                    sendKey(button.dataset.key);
+                    clearOnceModifiers();
+                }});
+            }});
+
+            // 屏幕键盘: 聚焦隐藏的输入框弹出系统键盘。普通输入以文本转发 (受控端按 Unicode 注入，
+            // 与键盘布局和输入法无关)；按住修饰键时逐键发送，组合键才能生效
+            const keyboardInput = document.getElementById('keyboard-input');
+            document.getElementById('keyboard-btn').addEventListener('click', () => keyboardInput.focus());
+            const sendText = text => {{
+                const modified = Object.values(modifierModes).some(mode => mode !== 'off');
+                if (!text) {{
+                    return;
+                }}
+                if (!modified) {{
+                    sendInput({{ TextInput: {{ text }} }});
+                    return;
+                }}
+                for (const char of text) {{
+                    sendKey(keyName(char.toLowerCase()));
+                }}
+                clearOnceModifiers();
+            }};
+            keyboardInput.addEventListener('input', event => {{
+                // 输入法组字过程中不转发，等 compositionend 一次性发送
+                if (event.isComposing) {{
+                    return;
+                }}
+                sendText(event.data);
+                keyboardInput.value = '';
+            }});
+            keyboardInput.addEventListener('compositionend', event => {{
+                sendText(event.data);
+                keyboardInput.value = '';
+            }});
+            keyboardInput.addEventListener('keydown', event => {{
+                // 退格、回车、方向键等不产生 input 事件
+                if (event.key.length > 1 && event.key !== 'Unidentified') {{
+                    event.preventDefault();
+                    sendKey(event.key);
+                    clearOnceModifiers();
+                }}
             }});
         }}
 
         // 启动
+        setupInput();
+        setupFileDrop();
+        fetchHostInfo();
+        connectSignaling();
         connectVideoStream();
+        pollChat();
     </script>
 </body>
-</html>"#, signaling_url = signaling_url)
+</html>"#,
+        signaling_url = signaling_url,
+        title = branding.title_html(),
+        header = branding.header_html(),
+        css_variables = branding.css_variables(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signaling_url() {
+        assert!(is_signaling_url("ws://192.168.1.10:9527"));
+        assert!(is_signaling_url("wss://abc.trycloudflare.com/ws"));
+        assert!(!is_signaling_url("http://192.168.1.10"));
+        assert!(!is_signaling_url("ws://"));
+        assert!(!is_signaling_url("ws://host';alert(1)//"));
+        assert!(!is_signaling_url("ws://host</script>"));
+
+        assert_eq!(host_label("ws://192.168.1.10:9527"), "192.168.1.10:9527");
+        assert_eq!(host_label("wss://abc.trycloudflare.com/ws"), "abc.trycloudflare.com");
+    }
+
+    #[test]
+    fn test_sanitize_path() {
+        assert_eq!(sanitize_path("img/logo.png"), Some(PathBuf::from("img/logo.png")));
+        assert_eq!(sanitize_path("./logo.png"), Some(PathBuf::from("logo.png")));
+        assert_eq!(sanitize_path("../secret"), None);
+        assert_eq!(sanitize_path("img/../../secret"), None);
+        assert_eq!(sanitize_path("/etc/passwd"), None);
+        assert_eq!(sanitize_path(""), None);
+    }
 }
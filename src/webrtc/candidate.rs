@@ -0,0 +1,174 @@
+//! ICE 候选过滤
+//!
+//! 两端都用同一组规则决定收集和通告哪些候选:
+//! - 隐藏本机地址: 不发送 host 候选，srflx/relay 候选中的内网地址 (raddr) 改为 0.0.0.0，
+//!   对端只能看到公网映射地址和中继地址
+//! - 只使用中继: ICE 传输策略改为 relay，所有流量经 TURN 服务器
+//! - 限定网段和网卡: 只在指定网段/网卡上收集本机候选 (如只走内网或只走 VPN)
+//!
+//! 每个会话按自己的过滤器创建 PeerConnection
+
+use crate::config::WebRTCConfig;
+use crate::network::lan::{is_usable_candidate_ip, Subnet};
+use anyhow::Result;
+use std::net::IpAddr;
+
+/// ICE 候选过滤器 (默认不过滤)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CandidateFilter {
+    /// 不向对端通告本机地址
+    pub hide_host: bool,
+    /// 只使用 TURN 中继候选
+    pub relay_only: bool,
+    /// 只使用这些网段内的本机地址 (为空时不限制)
+    pub networks: Vec<Subnet>,
+    /// 只使用这些网卡 (为空时不限制)
+    pub interfaces: Vec<String>,
+}
+
+impl CandidateFilter {
+    /// 按配置文件创建 (`webrtc.host_candidates`、`ice_transport_policy`、`candidate_networks`、`candidate_interfaces`)
+    pub fn from_config(webrtc: &WebRTCConfig) -> Result<Self> {
+        Ok(Self {
+            hide_host: !webrtc.host_candidates,
+            relay_only: webrtc.ice_transport_policy == "relay",
+            networks: webrtc
+                .candidate_networks
+                .iter()
+                .map(|network| network.parse())
+                .collect::<Result<_>>()?,
+            interfaces: webrtc.candidate_interfaces.clone(),
+        })
+    }
+
+    /// 是否在该本机地址上收集候选
+    pub fn allows_ip(&self, ip: &IpAddr) -> bool {
+        is_usable_candidate_ip(ip) && (self.networks.is_empty() || self.networks.iter().any(|network| network.contains(ip)))
+    }
+
+    /// 是否在该网卡上收集候选
+    pub fn allows_interface(&self, name: &str) -> bool {
+        self.interfaces.is_empty() || self.interfaces.iter().any(|interface| interface == name)
+    }
+
+    /// 发给对端之前处理本地候选: 返回 None 表示不发送
+    ///
+    /// 空字符串 (候选收集结束) 原样发送
+    pub fn outgoing(&self, candidate: &str) -> Option<String> {
+        if candidate.is_empty() {
+            return Some(String::new());
+        }
+        let mut fields: Vec<&str> = candidate.split_whitespace().collect();
+        let value_of = |fields: &[&str], key: &str| fields.iter().position(|field| *field == key).map(|i| i + 1);
+
+        let typ = value_of(&fields, "typ").and_then(|i| fields.get(i)).copied().unwrap_or("host");
+        if self.relay_only && typ != "relay" {
+            return None;
+        }
+        // 链路本地地址 (169.254.x.x) 对端无法使用
+        if fields.get(4).is_some_and(|address| address.starts_with("169.254.")) {
+            return None;
+        }
+        if !self.hide_host {
+            return Some(candidate.to_string());
+        }
+        if typ == "host" {
+            return None;
+        }
+        if let Some(i) = value_of(&fields, "raddr").filter(|i| *i < fields.len()) {
+            fields[i] = if fields[i].contains(':') { "::" } else { "0.0.0.0" };
+        }
+        if let Some(i) = value_of(&fields, "rport").filter(|i| *i < fields.len()) {
+            fields[i] = "0";
+        }
+        Some(fields.join(" "))
+    }
+
+    /// 按过滤器设置 ICE 网络 (IPv4/IPv6 UDP，地址和网卡限制)
+    #[cfg(feature = "webrtc")]
+    pub fn configure(&self, setting_engine: &mut webrtc::api::setting_engine::SettingEngine) {
+        use webrtc::ice::network_type::NetworkType;
+
+        setting_engine.set_network_types(vec![NetworkType::Udp4, NetworkType::Udp6]);
+        let filter = self.clone();
+        setting_engine.set_ip_filter(Box::new(move |ip| filter.allows_ip(&ip)));
+        let filter = self.clone();
+        setting_engine.set_interface_filter(Box::new(move |name: &str| {
+            let allowed = filter.allows_interface(name);
+            if !allowed {
+                tracing::debug!("跳过网络接口: {}", name);
+            }
+            allowed
+        }));
+    }
+
+    /// ICE 传输策略
+    #[cfg(feature = "webrtc")]
+    pub fn transport_policy(&self) -> webrtc::peer_connection::policy::ice_transport_policy::RTCIceTransportPolicy {
+        use webrtc::peer_connection::policy::ice_transport_policy::RTCIceTransportPolicy;
+
+        if self.relay_only {
+            RTCIceTransportPolicy::Relay
+        } else {
+            RTCIceTransportPolicy::All
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOST: &str = "candidate:1 1 udp 2130706431 192.168.1.5 50000 typ host";
+    const SRFLX: &str = "candidate:2 1 udp 1694498815 203.0.113.7 61000 typ srflx raddr 192.168.1.5 rport 50000";
+    const RELAY: &str = "candidate:3 1 udp 16777215 198.51.100.2 3478 typ relay raddr 203.0.113.7 rport 61000";
+
+    #[test]
+    fn test_default_sends_everything() {
+        let filter = CandidateFilter::default();
+        for candidate in [HOST, SRFLX, RELAY, ""] {
+            assert_eq!(filter.outgoing(candidate).as_deref(), Some(candidate));
+        }
+        assert_eq!(filter.outgoing("candidate:4 1 udp 2130706431 169.254.3.4 50000 typ host"), None);
+    }
+
+    #[test]
+    fn test_hide_host_candidates() {
+        let filter = CandidateFilter { hide_host: true, ..Default::default() };
+        assert_eq!(filter.outgoing(HOST), None);
+        assert_eq!(
+            filter.outgoing(SRFLX).as_deref(),
+            Some("candidate:2 1 udp 1694498815 203.0.113.7 61000 typ srflx raddr 0.0.0.0 rport 0")
+        );
+        assert!(filter.outgoing(RELAY).is_some_and(|c| !c.contains("203.0.113.7 rport")));
+        assert_eq!(filter.outgoing("").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_relay_only() {
+        let filter = CandidateFilter { relay_only: true, ..Default::default() };
+        assert_eq!(filter.outgoing(HOST), None);
+        assert_eq!(filter.outgoing(SRFLX), None);
+        assert_eq!(filter.outgoing(RELAY).as_deref(), Some(RELAY));
+    }
+
+    #[test]
+    fn test_from_config_restricts_networks_and_interfaces() {
+        let config = WebRTCConfig {
+            host_candidates: false,
+            candidate_networks: vec!["10.8.0.0/16".to_string()],
+            candidate_interfaces: vec!["wg0".to_string()],
+            ..Default::default()
+        };
+        let filter = CandidateFilter::from_config(&config).unwrap();
+        assert!(filter.hide_host);
+        assert!(!filter.relay_only);
+        assert!(filter.allows_ip(&"10.8.3.1".parse().unwrap()));
+        assert!(!filter.allows_ip(&"192.168.1.5".parse().unwrap()));
+        assert!(filter.allows_interface("wg0"));
+        assert!(!filter.allows_interface("eth0"));
+
+        let invalid = WebRTCConfig { candidate_networks: vec!["wg0".to_string()], ..Default::default() };
+        assert!(CandidateFilter::from_config(&invalid).is_err());
+    }
+}
@@ -0,0 +1,1168 @@
+//! 控制端 WebRTC 会话
+//!
+//! 不经过浏览器的程序化远程控制: 连接被控端信令服务，完成 PIN 验证和 SDP 协商后
+//! - 接收视频 (编码数据；启用 h264 feature 时可解码为 RGBA 帧)
+//! - 通过 `input` 数据通道发送鼠标/键盘事件
+//! - 通过 `stats` 数据通道回显被控端的延迟探测 (取走视频帧即视为显示)
+//! - 通过 `control` 数据通道申请/移交输入控制权 (多个控制端同时连接时只有控制者的输入生效)
+//! - 通过 `chat` 数据通道与被控端操作者及其他控制端文字聊天
+//!
+//! 供自动化脚本、测试工具等无界面场景使用
+//!
+//! 网络短暂中断时会自动恢复: 媒体链路断开时发起 ICE 重启；信令连接也断开时
+//! 重连并凭会话 ID 和会话令牌恢复会话 (被控端保留 `EmbeddedSignalingServer::RESUME_GRACE`)，
+//! 之后同样通过 ICE 重启接回原 PeerConnection，数据通道和视频轨道保持不变
+
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+#[cfg(feature = "webrtc")]
+use super::host_session::VideoCodec;
+#[cfg(feature = "webrtc")]
+use super::signaling::{SignalingClient, SignalingEvent};
+#[cfg(all(feature = "webrtc", feature = "h264"))]
+use crate::capture::Frame;
+#[cfg(all(feature = "webrtc", feature = "h264"))]
+use crate::encoder::hardware_decoder::DecodedFrame;
+#[cfg(feature = "webrtc")]
+use crate::input::{InputEvent, MouseButton};
+#[cfg(feature = "webrtc")]
+use crate::quality::latency::{self, LatencyMessage};
+#[cfg(feature = "webrtc")]
+use crate::nat::{
+    predictive_punching::{HolePuncher, PunchedPath},
+    NatConfig,
+};
+#[cfg(feature = "webrtc")]
+use crate::recorder::{is_h264_key_frame, is_vp8_key_frame, is_vp9_key_frame};
+#[cfg(feature = "webrtc")]
+use crate::signaling::chat::{ChatMessage, ChatPost, CHAT_CHANNEL};
+#[cfg(feature = "webrtc")]
+use crate::signaling::control::{ControlMessage, CONTROL_CHANNEL};
+#[cfg(feature = "webrtc")]
+use crate::signaling::{EmbeddedSignalingServer, SessionPermissions};
+#[cfg(feature = "webrtc")]
+use crate::signaling::session_token::SessionTokenIssuer;
+#[cfg(feature = "webrtc")]
+use crate::system::{SysInfoRequest, SystemReport, SYSINFO_CHANNEL};
+#[cfg(feature = "webrtc")]
+use anyhow::{anyhow, Result};
+#[cfg(feature = "webrtc")]
+use std::sync::{Arc, RwLock};
+#[cfg(feature = "webrtc")]
+use std::time::SystemTime;
+#[cfg(feature = "webrtc")]
+use tokio::sync::{mpsc, Mutex, Notify, RwLock as AsyncRwLock};
+#[cfg(feature = "webrtc")]
+use tokio::time::Instant;
+#[cfg(feature = "webrtc")]
+use webrtc::{
+    api::{
+        interceptor_registry::register_default_interceptors,
+        media_engine::{MediaEngine, MIME_TYPE_H264, MIME_TYPE_VP9},
+        setting_engine::SettingEngine,
+        APIBuilder,
+    },
+    data_channel::RTCDataChannel,
+    ice_transport::ice_candidate::RTCIceCandidateInit,
+    interceptor::registry::Registry,
+    media::io::sample_builder::SampleBuilder,
+    peer_connection::{
+        configuration::RTCConfiguration,
+        offer_answer_options::RTCOfferOptions,
+        peer_connection_state::RTCPeerConnectionState,
+        sdp::session_description::RTCSessionDescription,
+        RTCPeerConnection,
+    },
+    rtp::{
+        codecs::{h264::H264Packet, vp8::Vp8Packet, vp9::Vp9Packet},
+        packetizer::Depacketizer,
+    },
+    rtp_transceiver::{
+        rtp_codec::RTPCodecType,
+        rtp_transceiver_direction::RTCRtpTransceiverDirection,
+        RTCRtpTransceiverInit,
+    },
+    track::track_remote::TrackRemote,
+};
+
+/// 待取走的视频帧上限 (消费方处理不过来时丢弃，直到下一个关键帧)
+#[cfg(feature = "webrtc")]
+const SAMPLE_QUEUE: usize = 30;
+/// 重组视频帧时允许的最大乱序包数
+#[cfg(feature = "webrtc")]
+const MAX_LATE_PACKETS: u16 = 256;
+/// 信令重连的初始间隔 (之后翻倍，最长 MAX_RECONNECT_DELAY)
+#[cfg(feature = "webrtc")]
+const RECONNECT_DELAY: Duration = Duration::from_millis(200);
+#[cfg(feature = "webrtc")]
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(2);
+/// 等待被控端回复打洞信息的时间 (旧版被控端不回复)
+#[cfg(feature = "webrtc")]
+const PUNCH_REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+/// 等待被控端回复系统信息的时间
+#[cfg(feature = "webrtc")]
+const SYSINFO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 当前使用的信令连接 (重连后替换)
+#[cfg(feature = "webrtc")]
+type SharedSignaling = Arc<AsyncRwLock<Arc<SignalingClient>>>;
+
+/// 连接选项
+#[derive(Debug, Clone)]
+pub struct ControlOptions {
+    /// 房间 ID
+    pub room: String,
+    /// 被控端显示的 PIN 或无人值守访问密钥 (被控端要求 PIN 时必填)
+    pub pin: Option<String>,
+    /// 身份验证器 App 中的 TOTP 验证码 (被控端启用双因素验证时必填)
+    pub totp: Option<String>,
+    /// 连接超时 (包含排队和被控端审批的时间)
+    pub timeout: Duration,
+    /// STUN 服务器 (跨 NAT 直连时使用，为空时只使用本机地址候选)
+    pub stun_servers: Vec<String>,
+    /// TURN 中继服务器 (直连失败时使用)
+    pub turn_servers: Vec<crate::config::TurnServerConfig>,
+    /// 候选过滤 (隐藏本机地址、只用中继、限定网段/网卡)
+    pub candidate_filter: super::candidate::CandidateFilter,
+    /// 任一端为对称 NAT 时在 ICE 之前先做预测性打洞 (需要 STUN 服务器)
+    pub hole_punching: bool,
+    /// 视频解码器 (默认自动选择硬件解码器) 和输出格式
+    pub decoder: crate::encoder::hardware_decoder::HardwareDecoderConfig,
+}
+
+impl Default for ControlOptions {
+    fn default() -> Self {
+        Self {
+            room: "default".to_string(),
+            pin: None,
+            totp: None,
+            timeout: Duration::from_secs(60),
+            stun_servers: Vec::new(),
+            turn_servers: Vec::new(),
+            candidate_filter: Default::default(),
+            hole_punching: true,
+            decoder: Default::default(),
+        }
+    }
+}
+
+impl ControlOptions {
+    pub fn with_pin(pin: impl Into<String>) -> Self {
+        Self {
+            pin: Some(pin.into()),
+            ..Default::default()
+        }
+    }
+
+    /// 使用配置文件中的 STUN/TURN 服务器、候选过滤和打洞设置
+    pub fn with_webrtc_config(mut self, webrtc: &crate::config::WebRTCConfig) -> anyhow::Result<Self> {
+        self.stun_servers = webrtc.stun_servers.clone();
+        self.turn_servers = webrtc.turn_servers.clone();
+        self.candidate_filter = super::candidate::CandidateFilter::from_config(webrtc)?;
+        self.hole_punching = webrtc.hole_punching;
+        Ok(self)
+    }
+}
+
+/// 收到的视频帧 (编码数据)
+#[cfg(feature = "webrtc")]
+#[derive(Debug, Clone)]
+pub struct VideoSample {
+    pub codec: VideoCodec,
+    /// VP8 帧或 H.264 Annex-B 码流
+    pub data: Vec<u8>,
+    pub is_key_frame: bool,
+    pub timestamp: SystemTime,
+}
+
+/// 控制端会话
+#[cfg(feature = "webrtc")]
+pub struct ControlSession {
+    signaling: SharedSignaling,
+    pc: Arc<RTCPeerConnection>,
+    input: Arc<RTCDataChannel>,
+    /// 延迟回显通道
+    stats: Arc<RTCDataChannel>,
+    /// 收到但尚未回显的延迟探测: (帧号, 被控端时间戳, 收到时间)
+    pings: Arc<std::sync::Mutex<Vec<(u64, u64, Instant)>>>,
+    /// 控制权通道
+    control: Arc<RTCDataChannel>,
+    /// 被控端推送的控制权事件
+    control_events: Mutex<mpsc::UnboundedReceiver<ControlMessage>>,
+    /// 最近一次 `state` 中本端是否持有控制权
+    has_control: Arc<std::sync::atomic::AtomicBool>,
+    /// 聊天通道
+    chat: Arc<RTCDataChannel>,
+    /// 收到的聊天消息
+    chat_messages: Mutex<mpsc::UnboundedReceiver<ChatMessage>>,
+    /// 系统信息通道
+    sysinfo: Arc<RTCDataChannel>,
+    /// 被控端回复的系统信息
+    sysinfo_reports: Mutex<mpsc::UnboundedReceiver<SystemReport>>,
+    samples: Mutex<mpsc::Receiver<VideoSample>>,
+    /// 被控端下发的会话权限 (会话期间可能变化)
+    permissions: Arc<RwLock<SessionPermissions>>,
+    session_id: Option<String>,
+    #[cfg(feature = "h264")]
+    decoder: Mutex<Option<(VideoCodec, crate::encoder::hardware_decoder::HardwareVideoDecoder)>>,
+    #[cfg(feature = "h264")]
+    decoder_config: crate::encoder::hardware_decoder::HardwareDecoderConfig,
+}
+
+#[cfg(feature = "webrtc")]
+impl ControlSession {
+    /// 连接被控端 (url 形如 ws://192.168.1.5:9527/ws)
+    ///
+    /// 返回时输入通道已打开，视频帧随后通过 `next_sample` / `next_frame` 获取
+    pub async fn connect(url: &str, options: ControlOptions) -> Result<Self> {
+        let deadline = Instant::now() + options.timeout;
+
+        let signaling = Arc::new(SignalingClient::new(url.to_string()));
+        let (event_tx, mut events) = mpsc::unbounded_channel();
+        forward_events(&signaling, event_tx.clone()).await;
+        signaling.connect().await?;
+        signaling.join_room(options.room.clone()).await?;
+
+        // 等待放行 (权限是放行时最后下发的消息，PIN/TOTP 要求在它之前)
+        let mut pin_required = false;
+        let mut totp_required = false;
+        let permissions = loop {
+            match next_event(&mut events, deadline).await? {
+                SignalingEvent::Queued { position, queue_length } => {
+                    tracing::info!("被控端会话已满，排队中: {}/{}", position, queue_length);
+                }
+                SignalingEvent::PinRequired => pin_required = true,
+                SignalingEvent::TotpRequired => totp_required = true,
+                SignalingEvent::Permissions { permissions } => break permissions,
+                SignalingEvent::Error { message } => return Err(anyhow!("被控端拒绝连接: {}", message)),
+                _ => {}
+            }
+        };
+
+        if pin_required {
+            let pin = options
+                .pin
+                .as_deref()
+                .ok_or_else(|| anyhow!("被控端要求 PIN，请在 ControlOptions 中提供"))?;
+            signaling.send_pin(pin).await?;
+            loop {
+                match next_event(&mut events, deadline).await? {
+                    SignalingEvent::PinResult { accepted: true, .. } => break,
+                    SignalingEvent::PinResult { remaining_attempts, retry_after_secs, .. } => {
+                        return Err(anyhow!(
+                            "PIN 错误 (剩余次数: {:?}, 锁定秒数: {:?})",
+                            remaining_attempts,
+                            retry_after_secs
+                        ));
+                    }
+                    SignalingEvent::Error { message } => return Err(anyhow!("PIN 验证失败: {}", message)),
+                    _ => {}
+                }
+            }
+        }
+
+        if totp_required {
+            let code = options
+                .totp
+                .as_deref()
+                .ok_or_else(|| anyhow!("被控端要求 TOTP 验证码，请在 ControlOptions 中提供"))?;
+            signaling.send_totp(code).await?;
+            loop {
+                match next_event(&mut events, deadline).await? {
+                    SignalingEvent::TotpResult { accepted: true, .. } => break,
+                    SignalingEvent::TotpResult { remaining_attempts, retry_after_secs, .. } => {
+                        return Err(anyhow!(
+                            "TOTP 验证码错误 (剩余次数: {:?}, 锁定秒数: {:?})",
+                            remaining_attempts,
+                            retry_after_secs
+                        ));
+                    }
+                    SignalingEvent::Error { message } => return Err(anyhow!("TOTP 验证失败: {}", message)),
+                    _ => {}
+                }
+            }
+        }
+
+        // 打通的 socket 交给 ICE 使用，被控端的打洞地址在收到 Answer 后作为候选加入
+        let punched = if options.hole_punching && !options.stun_servers.is_empty() {
+            punch_host(&signaling, &mut events, &options.stun_servers, deadline).await
+        } else {
+            None
+        };
+        let punched_remote = punched.as_ref().map(|path| path.remote);
+        let pc = Arc::new(new_peer_connection(&options, punched).await?);
+        pc.add_transceiver_from_kind(
+            RTPCodecType::Video,
+            Some(RTCRtpTransceiverInit {
+                direction: RTCRtpTransceiverDirection::Recvonly,
+                send_encodings: vec![],
+            }),
+        )
+        .await
+        .map_err(|e| anyhow!("添加视频接收器失败: {:?}", e))?;
+
+        // 输入数据通道 (标签对应被控端的输入权限)
+        let input = pc
+            .create_data_channel("input", None)
+            .await
+            .map_err(|e| anyhow!("创建输入通道失败: {:?}", e))?;
+        let opened = Arc::new(Notify::new());
+        let opened_clone = opened.clone();
+        input.on_open(Box::new(move || {
+            opened_clone.notify_one();
+            Box::pin(async {})
+        }));
+
+        // 延迟回显通道: 记下被控端的 Ping，显示下一帧时回复
+        let stats = pc
+            .create_data_channel(latency::STATS_CHANNEL, None)
+            .await
+            .map_err(|e| anyhow!("创建统计通道失败: {:?}", e))?;
+        let pings = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let pings_clone = pings.clone();
+        stats.on_message(Box::new(move |msg| {
+            if let Ok(LatencyMessage::Ping { frame, host_us }) = serde_json::from_slice(&msg.data) {
+                if let Ok(mut pings) = pings_clone.lock() {
+                    pings.push((frame, host_us, Instant::now()));
+                }
+            }
+            Box::pin(async {})
+        }));
+
+        // 控制权通道: 打开后查询当前状态，之后被控端在控制权变化时推送
+        let control = pc
+            .create_data_channel(CONTROL_CHANNEL, None)
+            .await
+            .map_err(|e| anyhow!("创建控制权通道失败: {:?}", e))?;
+        let (control_tx, control_events) = mpsc::unbounded_channel();
+        let has_control = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let has_control_clone = has_control.clone();
+        control.on_message(Box::new(move |msg| {
+            if let Some(message) = parse_control(&msg.data, &has_control_clone) {
+                let _ = control_tx.send(message);
+            }
+            Box::pin(async {})
+        }));
+        let control_for_open = Arc::downgrade(&control);
+        control.on_open(Box::new(move || {
+            let control = control_for_open.upgrade();
+            Box::pin(async move {
+                if let (Some(control), Ok(text)) = (control, serde_json::to_string(&ControlMessage::Status)) {
+                    let _ = control.send_text(text).await;
+                }
+            })
+        }));
+
+        // 聊天通道
+        let chat = pc
+            .create_data_channel(CHAT_CHANNEL, None)
+            .await
+            .map_err(|e| anyhow!("创建聊天通道失败: {:?}", e))?;
+        let (chat_tx, chat_messages) = mpsc::unbounded_channel();
+        chat.on_message(Box::new(move |msg| {
+            if let Ok(message) = serde_json::from_slice::<ChatMessage>(&msg.data) {
+                let _ = chat_tx.send(message);
+            }
+            Box::pin(async {})
+        }));
+
+        // 系统信息通道: 请求一次，被控端回复一份报告
+        let sysinfo = pc
+            .create_data_channel(SYSINFO_CHANNEL, None)
+            .await
+            .map_err(|e| anyhow!("创建系统信息通道失败: {:?}", e))?;
+        let (sysinfo_tx, sysinfo_reports) = mpsc::unbounded_channel();
+        sysinfo.on_message(Box::new(move |msg| {
+            if let Ok(report) = serde_json::from_slice::<SystemReport>(&msg.data) {
+                let _ = sysinfo_tx.send(report);
+            }
+            Box::pin(async {})
+        }));
+
+        let (sample_tx, sample_rx) = mpsc::channel(SAMPLE_QUEUE);
+        pc.on_track(Box::new(move |track, _, _| {
+            tokio::spawn(read_track(track, sample_tx.clone()));
+            Box::pin(async {})
+        }));
+
+        // 媒体链路状态变化交给后台任务处理 (断开时发起 ICE 重启)
+        let (state_tx, state_rx) = mpsc::unbounded_channel();
+        pc.on_peer_connection_state_change(Box::new(move |state| {
+            let _ = state_tx.send(state);
+            Box::pin(async {})
+        }));
+
+        // 本地 ICE 候选收集到后立即转发给被控端 (重连后发往新的信令连接)，收集结束时发送空候选
+        let signaling: SharedSignaling = Arc::new(AsyncRwLock::new(signaling));
+        let signaling_for_ice = signaling.clone();
+        let candidate_filter = options.candidate_filter.clone();
+        pc.on_ice_candidate(Box::new(move |candidate| {
+            let signaling = signaling_for_ice.clone();
+            let init = match candidate {
+                Some(candidate) => candidate.to_json().ok(),
+                None => Some(RTCIceCandidateInit::default()),
+            };
+            let filtered = init.and_then(|init| Some((candidate_filter.outgoing(&init.candidate)?, init)));
+            Box::pin(async move {
+                let Some((candidate, init)) = filtered else {
+                    return;
+                };
+                let signaling = signaling.read().await.clone();
+                if let Err(e) = signaling
+                    .send_ice(
+                        "host".to_string(),
+                        candidate,
+                        init.sdp_mid.unwrap_or_default(),
+                        init.sdp_mline_index.unwrap_or(0),
+                    )
+                    .await
+                {
+                    tracing::debug!("发送 ICE 候选失败: {}", e);
+                }
+            })
+        }));
+
+        let offer = pc
+            .create_offer(None)
+            .await
+            .map_err(|e| anyhow!("创建 Offer 失败: {:?}", e))?;
+        pc.set_local_description(offer.clone())
+            .await
+            .map_err(|e| anyhow!("设置本地描述失败: {:?}", e))?;
+        signaling
+            .read()
+            .await
+            .send_offer("host".to_string(), offer.sdp)
+            .await?;
+
+        // 等待 Answer (被控端可能需要人工审批)；先于 Answer 到达的候选在设置远程描述后加入
+        let mut early_candidates = Vec::new();
+        let (session_id, session_token) = loop {
+            match next_event(&mut events, deadline).await? {
+                SignalingEvent::Answer { sdp, session_id, session_token, .. } => {
+                    let answer = RTCSessionDescription::answer(sdp)
+                        .map_err(|e| anyhow!("解析 Answer 失败: {:?}", e))?;
+                    pc.set_remote_description(answer)
+                        .await
+                        .map_err(|e| anyhow!("设置远程描述失败: {:?}", e))?;
+                    if let Some(remote) = punched_remote {
+                        add_punched_candidate(&pc, remote).await;
+                    }
+                    for init in early_candidates.drain(..) {
+                        if let Err(e) = pc.add_ice_candidate(init).await {
+                            tracing::debug!("添加 ICE 候选失败: {:?}", e);
+                        }
+                    }
+                    break (session_id, session_token);
+                }
+                SignalingEvent::Ice { candidate, sdp_mid, sdp_mline_index, .. } => {
+                    early_candidates.push(RTCIceCandidateInit {
+                        candidate,
+                        sdp_mid: Some(sdp_mid),
+                        sdp_mline_index: Some(sdp_mline_index),
+                        username_fragment: None,
+                    });
+                }
+                SignalingEvent::Error { message } => return Err(anyhow!("被控端拒绝会话: {}", message)),
+                _ => {}
+            }
+        };
+
+        let permissions = Arc::new(RwLock::new(permissions));
+        let supervisor = Supervisor {
+            url: url.to_string(),
+            room: options.room.clone(),
+            session_id: session_id.clone(),
+            session_token,
+            pc: pc.clone(),
+            signaling: signaling.clone(),
+            event_tx,
+            events,
+            states: state_rx,
+            permissions: permissions.clone(),
+        };
+        tokio::spawn(supervisor.run());
+
+        tokio::time::timeout_at(deadline, opened.notified())
+            .await
+            .map_err(|_| anyhow!("等待输入通道打开超时"))?;
+        tracing::info!("已连接被控端 (会话 {})", session_id.as_deref().unwrap_or("未知"));
+
+        Ok(Self {
+            signaling,
+            pc,
+            input,
+            stats,
+            pings,
+            control,
+            control_events: Mutex::new(control_events),
+            has_control,
+            chat,
+            chat_messages: Mutex::new(chat_messages),
+            sysinfo,
+            sysinfo_reports: Mutex::new(sysinfo_reports),
+            samples: Mutex::new(sample_rx),
+            permissions,
+            session_id,
+            #[cfg(feature = "h264")]
+            decoder: Mutex::new(None),
+            #[cfg(feature = "h264")]
+            decoder_config: options.decoder,
+        })
+    }
+
+    /// 下一个视频帧 (编码数据，连接关闭后返回 None)
+    pub async fn next_sample(&self) -> Option<VideoSample> {
+        let sample = self.samples.lock().await.recv().await?;
+        self.echo_pings().await;
+        Some(sample)
+    }
+
+    /// 一帧已显示: 回显此前收到的延迟探测
+    async fn echo_pings(&self) {
+        let pings = self.pings.lock().map(|mut pings| std::mem::take(&mut *pings)).unwrap_or_default();
+        for (frame, host_us, received) in pings {
+            let echo = LatencyMessage::Echo {
+                frame,
+                host_us,
+                hold_us: received.elapsed().as_micros() as u64,
+            };
+            let Ok(text) = serde_json::to_string(&echo) else {
+                continue;
+            };
+            if let Err(e) = self.stats.send_text(text).await {
+                tracing::debug!("回显延迟探测失败: {:?}", e);
+            }
+        }
+    }
+
+    /// 下一个解码后的 RGBA 帧
+    #[cfg(feature = "h264")]
+    pub async fn next_frame(&self) -> Option<Result<Frame>> {
+        Some(self.next_decoded_frame().await?.map(DecodedFrame::into_rgba))
+    }
+
+    /// 下一个解码后的帧 (格式由 `ControlOptions::decoder` 指定)
+    #[cfg(feature = "h264")]
+    pub async fn next_decoded_frame(&self) -> Option<Result<DecodedFrame>> {
+        use crate::encoder::decoder::DecoderCodec;
+        use crate::encoder::hardware_decoder::HardwareVideoDecoder;
+
+        loop {
+            let sample = self.samples.lock().await.recv().await?;
+            let mut decoder = self.decoder.lock().await;
+            if decoder.as_ref().map(|(codec, _)| *codec) != Some(sample.codec) {
+                let codec = match sample.codec {
+                    VideoCodec::VP8 => DecoderCodec::Vp8,
+                    VideoCodec::VP9 => DecoderCodec::Vp9,
+                    VideoCodec::H264 => DecoderCodec::H264,
+                };
+                match HardwareVideoDecoder::new(codec, self.decoder_config.clone()) {
+                    Ok(new_decoder) => *decoder = Some((sample.codec, new_decoder)),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            if let Some((_, decoder)) = decoder.as_mut() {
+                match decoder.decode(&sample.data) {
+                    Ok(Some(frame)) => {
+                        drop(decoder);
+                        self.echo_pings().await;
+                        return Some(Ok(frame));
+                    }
+                    Ok(None) => continue,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+        }
+    }
+
+    /// 发送输入事件
+    pub async fn send_event(&self, event: &InputEvent) -> Result<()> {
+        let json = input_payload(self.permissions(), event)?;
+        self.input
+            .send_text(json)
+            .await
+            .map_err(|e| anyhow!("发送输入事件失败: {:?}", e))?;
+        Ok(())
+    }
+
+    /// 移动鼠标 (归一化坐标 0.0-1.0)
+    pub async fn send_mouse_move(&self, x: f64, y: f64) -> Result<()> {
+        self.send_event(&InputEvent::mouse_move(x, y)).await
+    }
+
+    /// 按下或释放鼠标按键
+    pub async fn send_mouse_click(&self, button: MouseButton, pressed: bool) -> Result<()> {
+        self.send_event(&InputEvent::mouse_click(button, pressed)).await
+    }
+
+    /// 滚动鼠标滚轮
+    pub async fn send_mouse_wheel(&self, delta_x: i32, delta_y: i32) -> Result<()> {
+        self.send_event(&InputEvent::mouse_wheel(delta_x, delta_y)).await
+    }
+
+    /// 按下或释放按键 (按键名与 InputEvent::KeyEvent 一致)
+    pub async fn send_key(&self, key: &str, pressed: bool) -> Result<()> {
+        self.send_event(&InputEvent::KeyEvent { key: key.to_string(), pressed }).await
+    }
+
+    /// 下一个控制权事件 (`state` / `requested` / `denied` / `resolution` / `privacy`，供界面显示)
+    pub async fn next_control_event(&self) -> Option<ControlMessage> {
+        self.control_events.lock().await.recv().await
+    }
+
+    /// 本端当前是否持有输入控制权
+    pub fn has_control(&self) -> bool {
+        self.has_control.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// 申请控制权 (无人控制时立即获得，否则由当前控制者决定)
+    pub async fn request_control(&self) -> Result<()> {
+        self.send_control(&ControlMessage::Request).await
+    }
+
+    /// 放弃控制权
+    pub async fn release_control(&self) -> Result<()> {
+        self.send_control(&ControlMessage::Release).await
+    }
+
+    /// 把控制权交给申请者 (viewer 为 `requested` 事件中的会话 ID)
+    pub async fn grant_control(&self, viewer: &str) -> Result<()> {
+        self.send_control(&ControlMessage::Grant { viewer: viewer.to_string() }).await
+    }
+
+    /// 拒绝申请
+    pub async fn deny_control(&self, viewer: &str) -> Result<()> {
+        self.send_control(&ControlMessage::Deny { viewer: viewer.to_string() }).await
+    }
+
+    /// 开启或关闭被控端的隐私模式 (需要持有控制权，结果以 `privacy` 事件返回)
+    pub async fn set_privacy(&self, enabled: bool) -> Result<()> {
+        self.send_control(&ControlMessage::Privacy { enabled }).await
+    }
+
+    /// 发送聊天消息 (仅查看的会话也可以发送)
+    pub async fn send_chat(&self, text: &str) -> Result<()> {
+        let post = ChatPost { text: text.to_string() };
+        self.chat
+            .send_text(serde_json::to_string(&post)?)
+            .await
+            .map_err(|e| anyhow!("发送聊天消息失败: {:?}", e))?;
+        Ok(())
+    }
+
+    /// 下一条聊天消息 (被控端操作者或其他控制端发出)
+    pub async fn next_chat_message(&self) -> Option<ChatMessage> {
+        self.chat_messages.lock().await.recv().await
+    }
+
+    /// 请求被控端的系统信息 (CPU 占用最高的 `top` 个进程)
+    pub async fn system_report(&self, top: usize) -> Result<SystemReport> {
+        let mut reports = self.sysinfo_reports.lock().await;
+        // 丢弃之前超时请求的迟到回复
+        while reports.try_recv().is_ok() {}
+
+        let request = SysInfoRequest { top: Some(top) };
+        self.sysinfo
+            .send_text(serde_json::to_string(&request)?)
+            .await
+            .map_err(|e| anyhow!("发送系统信息请求失败: {:?}", e))?;
+        tokio::time::timeout(SYSINFO_TIMEOUT, reports.recv())
+            .await
+            .map_err(|_| anyhow!("等待系统信息超时"))?
+            .ok_or_else(|| anyhow!("系统信息通道已关闭"))
+    }
+
+    async fn send_control(&self, message: &ControlMessage) -> Result<()> {
+        require_input(self.permissions())?;
+        self.control
+            .send_text(serde_json::to_string(message)?)
+            .await
+            .map_err(|e| anyhow!("发送控制权消息失败: {:?}", e))?;
+        Ok(())
+    }
+
+    /// 当前会话权限
+    pub fn permissions(&self) -> SessionPermissions {
+        read_permissions(&self.permissions)
+    }
+
+    /// 被控端分配的会话 ID
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    /// 关闭会话
+    pub async fn close(&self) -> Result<()> {
+        self.pc
+            .close()
+            .await
+            .map_err(|e| anyhow!("关闭 PeerConnection 失败: {:?}", e))?;
+        self.signaling.read().await.disconnect().await
+    }
+}
+
+/// 读取当前会话权限 (锁中毒时按只读处理)
+#[cfg(feature = "webrtc")]
+fn read_permissions(permissions: &RwLock<SessionPermissions>) -> SessionPermissions {
+    permissions.read().map(|p| *p).unwrap_or_else(|_| SessionPermissions::view_only())
+}
+
+/// 应用被控端推送的新权限
+#[cfg(feature = "webrtc")]
+fn update_permissions(permissions: &RwLock<SessionPermissions>, updated: SessionPermissions) {
+    if let Ok(mut current) = permissions.write() {
+        *current = updated;
+    }
+}
+
+/// 输入和控制权消息都需要输入权限 (被控端同样会丢弃无权限的输入)
+#[cfg(feature = "webrtc")]
+fn require_input(permissions: SessionPermissions) -> Result<()> {
+    if !permissions.input {
+        return Err(anyhow!("被控端未授予输入权限"));
+    }
+    Ok(())
+}
+
+/// 检查权限后序列化输入事件
+#[cfg(feature = "webrtc")]
+fn input_payload(permissions: SessionPermissions, event: &InputEvent) -> Result<String> {
+    require_input(permissions)?;
+    Ok(serde_json::to_string(event)?)
+}
+
+/// 解析 `control` 通道消息，`State` 消息同时更新本端是否持有控制权
+#[cfg(feature = "webrtc")]
+fn parse_control(data: &[u8], has_control: &std::sync::atomic::AtomicBool) -> Option<ControlMessage> {
+    let message = serde_json::from_slice::<ControlMessage>(data).ok()?;
+    if let ControlMessage::State { has_control: holder, .. } = message {
+        has_control.store(holder, std::sync::atomic::Ordering::Relaxed);
+    }
+    Some(message)
+}
+
+/// 等待下一个信令事件 (超时或断开时返回错误)
+#[cfg(feature = "webrtc")]
+async fn next_event(
+    events: &mut mpsc::UnboundedReceiver<SignalingEvent>,
+    deadline: Instant,
+) -> Result<SignalingEvent> {
+    match tokio::time::timeout_at(deadline, events.recv()).await {
+        Ok(Some(SignalingEvent::Disconnected)) | Ok(None) => Err(anyhow!("信令连接已断开")),
+        Ok(Some(event)) => Ok(event),
+        Err(_) => Err(anyhow!("连接被控端超时")),
+    }
+}
+
+/// 将信令事件转发到通道 (回调是同步的)
+#[cfg(feature = "webrtc")]
+async fn forward_events(signaling: &SignalingClient, tx: mpsc::UnboundedSender<SignalingEvent>) {
+    signaling
+        .on_event(move |event| {
+            let _ = tx.send(event);
+        })
+        .await;
+}
+
+/// 协商完成后的后台任务: 处理 ICE 候选和权限变更，断线时恢复会话
+#[cfg(feature = "webrtc")]
+struct Supervisor {
+    url: String,
+    room: String,
+    session_id: Option<String>,
+    /// 恢复会话时出示的令牌 (定期换发)
+    session_token: Option<String>,
+    pc: Arc<RTCPeerConnection>,
+    signaling: SharedSignaling,
+    event_tx: mpsc::UnboundedSender<SignalingEvent>,
+    events: mpsc::UnboundedReceiver<SignalingEvent>,
+    states: mpsc::UnboundedReceiver<RTCPeerConnectionState>,
+    permissions: Arc<RwLock<SessionPermissions>>,
+}
+
+#[cfg(feature = "webrtc")]
+impl Supervisor {
+    async fn run(mut self) {
+        // 已发起 ICE 重启，等待连接恢复
+        let mut restarting = false;
+
+        let mut token_refresh = tokio::time::interval(SessionTokenIssuer::DEFAULT_TTL / 2);
+        token_refresh.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = token_refresh.tick() => {
+                    let signaling = self.signaling.read().await.clone();
+                    if let Err(e) = signaling.refresh_token().await {
+                        tracing::debug!("请求换发会话令牌失败: {}", e);
+                    }
+                }
+                event = self.events.recv() => match event {
+                    Some(SignalingEvent::Ice { candidate, sdp_mid, sdp_mline_index, .. }) => {
+                        let init = RTCIceCandidateInit {
+                            candidate,
+                            sdp_mid: Some(sdp_mid),
+                            sdp_mline_index: Some(sdp_mline_index),
+                            username_fragment: None,
+                        };
+                        if let Err(e) = self.pc.add_ice_candidate(init).await {
+                            tracing::debug!("添加 ICE 候选失败: {:?}", e);
+                        }
+                    }
+                    Some(SignalingEvent::SessionToken { token }) => self.session_token = Some(token),
+                    // ICE 重启的 Answer
+                    Some(SignalingEvent::Answer { sdp, session_token, .. }) => {
+                        if session_token.is_some() {
+                            self.session_token = session_token;
+                        }
+                        let result = match RTCSessionDescription::answer(sdp) {
+                            Ok(answer) => self.pc.set_remote_description(answer).await,
+                            Err(e) => Err(e),
+                        };
+                        if let Err(e) = result {
+                            tracing::warn!("设置 ICE 重启 Answer 失败: {:?}", e);
+                        }
+                    }
+                    Some(SignalingEvent::Permissions { permissions: updated }) => {
+                        tracing::info!("会话权限已更新: {}", updated);
+                        update_permissions(&self.permissions, updated);
+                    }
+                    // 主动关闭会话
+                    Some(SignalingEvent::Disconnected)
+                        if self.pc.connection_state() == RTCPeerConnectionState::Closed => break,
+                    Some(SignalingEvent::Disconnected) => {
+                        tracing::warn!("信令连接断开，尝试恢复会话");
+                        if !self.reconnect().await {
+                            tracing::error!("无法恢复会话，连接已关闭");
+                            let _ = self.pc.close().await;
+                            break;
+                        }
+                        restarting = true;
+                        if let Err(e) = self.restart_ice().await {
+                            tracing::warn!("ICE 重启失败: {}", e);
+                        }
+                    }
+                    Some(SignalingEvent::Error { message }) => tracing::warn!("信令错误: {}", message),
+                    Some(_) => {}
+                    None => break,
+                },
+                Some(state) = self.states.recv() => match state {
+                    RTCPeerConnectionState::Disconnected | RTCPeerConnectionState::Failed if !restarting => {
+                        tracing::warn!("媒体连接中断 ({:?})，发起 ICE 重启", state);
+                        restarting = true;
+                        // 信令也断开时发送失败，由重连流程再次发起
+                        if let Err(e) = self.restart_ice().await {
+                            tracing::debug!("ICE 重启失败: {}", e);
+                        }
+                    }
+                    RTCPeerConnectionState::Connected => {
+                        if restarting {
+                            tracing::info!("媒体连接已恢复");
+                        }
+                        restarting = false;
+                    }
+                    RTCPeerConnectionState::Closed => break,
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    /// 在原 PeerConnection 上发起 ICE 重启
+    async fn restart_ice(&self) -> Result<()> {
+        let options = RTCOfferOptions {
+            ice_restart: true,
+            ..Default::default()
+        };
+        let offer = self
+            .pc
+            .create_offer(Some(options))
+            .await
+            .map_err(|e| anyhow!("创建 ICE 重启 Offer 失败: {:?}", e))?;
+        self.pc
+            .set_local_description(offer.clone())
+            .await
+            .map_err(|e| anyhow!("设置本地描述失败: {:?}", e))?;
+        let signaling = self.signaling.read().await.clone();
+        signaling.send_offer("host".to_string(), offer.sdp).await
+    }
+
+    /// 重连信令服务器并恢复会话，成功后替换当前信令连接
+    async fn reconnect(&mut self) -> bool {
+        let Some(session_id) = self.session_id.clone() else {
+            return false;
+        };
+        let deadline = Instant::now() + EmbeddedSignalingServer::RESUME_GRACE;
+        let mut delay = RECONNECT_DELAY;
+
+        while Instant::now() < deadline {
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+
+            let client = Arc::new(SignalingClient::new(self.url.clone()));
+            forward_events(&client, self.event_tx.clone()).await;
+            if let Err(e) = client.connect().await {
+                tracing::debug!("重连信令服务器失败: {}", e);
+                continue;
+            }
+            if client
+                .resume_room(self.room.clone(), session_id.clone(), self.session_token.clone())
+                .await
+                .is_err()
+            {
+                continue;
+            }
+            *self.signaling.write().await = client.clone();
+
+            // 恢复成功时 Resumed 先于 Permissions 到达；只收到 Permissions 说明会话已失效
+            loop {
+                match next_event(&mut self.events, deadline).await {
+                    Ok(SignalingEvent::Resumed { .. }) => {
+                        tracing::info!("已重连被控端，会话 {} 已恢复", session_id);
+                        return true;
+                    }
+                    Ok(SignalingEvent::Permissions { .. })
+                    | Ok(SignalingEvent::Queued { .. })
+                    | Ok(SignalingEvent::Error { .. }) => {
+                        tracing::warn!("被控端已不再保留会话 {}", session_id);
+                        let _ = client.disconnect().await;
+                        return false;
+                    }
+                    Ok(_) => {}
+                    // 再次断开或超时，继续重试
+                    Err(_) => break,
+                }
+            }
+        }
+        false
+    }
+}
+
+/// 与被控端交换打洞信息并打洞，不需要或失败时返回 None (退回常规 ICE)
+#[cfg(feature = "webrtc")]
+async fn punch_host(
+    signaling: &SignalingClient,
+    events: &mut mpsc::UnboundedReceiver<SignalingEvent>,
+    stun_servers: &[String],
+    deadline: Instant,
+) -> Option<PunchedPath> {
+    let config = NatConfig {
+        stun_servers: stun_servers.to_vec(),
+        ..Default::default()
+    };
+    let puncher = match HolePuncher::prepare(config).await {
+        Ok(puncher) => puncher,
+        Err(e) => {
+            tracing::debug!("打洞准备失败: {}", e);
+            return None;
+        }
+    };
+    signaling
+        .send_punch("host".to_string(), Some(puncher.candidate().clone()))
+        .await
+        .ok()?;
+
+    let reply_deadline = deadline.min(Instant::now() + PUNCH_REPLY_TIMEOUT);
+    let remote = loop {
+        match next_event(events, reply_deadline).await {
+            Ok(SignalingEvent::Punch { candidate, .. }) => break candidate?,
+            Ok(_) => {}
+            Err(_) => return None,
+        }
+    };
+    if !puncher.needs_punch(&remote) {
+        return None;
+    }
+    match puncher.punch(&remote).await {
+        Ok(path) => {
+            tracing::info!("与被控端打洞成功: {}", path.remote);
+            Some(path)
+        }
+        Err(e) => {
+            tracing::info!("与被控端打洞失败: {}，使用常规 ICE", e);
+            None
+        }
+    }
+}
+
+/// 把被控端的打洞地址作为 ICE 候选加入 (被控端从同一端口回复，对方按 peer-reflexive 候选处理)
+#[cfg(feature = "webrtc")]
+async fn add_punched_candidate(pc: &RTCPeerConnection, remote: std::net::SocketAddr) {
+    let candidate = RTCIceCandidateInit {
+        candidate: format!(
+            "candidate:punch 1 udp 2130706431 {} {} typ host",
+            remote.ip(),
+            remote.port()
+        ),
+        ..Default::default()
+    };
+    if let Err(e) = pc.add_ice_candidate(candidate).await {
+        tracing::warn!("添加打洞候选失败: {:?}", e);
+    }
+}
+
+/// 创建只接收视频的 PeerConnection
+#[cfg(feature = "webrtc")]
+async fn new_peer_connection(options: &ControlOptions, punched: Option<PunchedPath>) -> Result<RTCPeerConnection> {
+    let mut m = MediaEngine::default();
+    m.register_default_codecs()
+        .map_err(|e| anyhow!("注册编解码器失败: {:?}", e))?;
+
+    let mut registry = Registry::new();
+    registry = register_default_interceptors(registry, &mut m)
+        .map_err(|e| anyhow!("注册拦截器失败: {:?}", e))?;
+
+    // 与 Host 一致: IPv4/IPv6 双栈 UDP，按候选过滤器限定网段和网卡，配置了 TURN 时直连失败后经中继
+    let mut setting_engine = SettingEngine::default();
+    options.candidate_filter.configure(&mut setting_engine);
+    if let Some(path) = punched {
+        setting_engine.set_udp_network(super::host_session::punched_udp_network(path.socket));
+    }
+
+    let api = APIBuilder::new()
+        .with_media_engine(m)
+        .with_interceptor_registry(registry)
+        .with_setting_engine(setting_engine)
+        .build();
+
+    let config = RTCConfiguration {
+        ice_servers: super::host_session::ice_servers(&options.stun_servers, &options.turn_servers),
+        ice_transport_policy: options.candidate_filter.transport_policy(),
+        ..Default::default()
+    };
+    api.new_peer_connection(config)
+        .await
+        .map_err(|e| anyhow!("创建 PeerConnection 失败: {:?}", e))
+}
+
+/// 读取远端视频轨道并重组为完整帧
+#[cfg(feature = "webrtc")]
+async fn read_track(track: Arc<TrackRemote>, tx: mpsc::Sender<VideoSample>) {
+    let mime_type = track.codec().capability.mime_type;
+    tracing::info!("收到视频轨道: {}", mime_type);
+    if mime_type.eq_ignore_ascii_case(MIME_TYPE_H264) {
+        depacketize(track, H264Packet::default(), VideoCodec::H264, tx).await;
+    } else if mime_type.eq_ignore_ascii_case(MIME_TYPE_VP9) {
+        depacketize(track, Vp9Packet::default(), VideoCodec::VP9, tx).await;
+    } else {
+        depacketize(track, Vp8Packet::default(), VideoCodec::VP8, tx).await;
+    }
+}
+
+#[cfg(feature = "webrtc")]
+async fn depacketize<T: Depacketizer>(
+    track: Arc<TrackRemote>,
+    depacketizer: T,
+    codec: VideoCodec,
+    tx: mpsc::Sender<VideoSample>,
+) {
+    let mut builder = SampleBuilder::new(MAX_LATE_PACKETS, depacketizer, 90_000);
+    // 丢帧后后续帧依赖缺失，等到下一个关键帧再继续输出
+    let mut waiting_key_frame = false;
+
+    while let Ok((packet, _)) = track.read_rtp().await {
+        builder.push(packet);
+        while let Some(sample) = builder.pop() {
+            let data = sample.data.to_vec();
+            let is_key_frame = match codec {
+                VideoCodec::VP8 => is_vp8_key_frame(&data),
+                VideoCodec::VP9 => is_vp9_key_frame(&data),
+                VideoCodec::H264 => is_h264_key_frame(&data),
+            };
+            if waiting_key_frame && !is_key_frame {
+                continue;
+            }
+            waiting_key_frame = false;
+
+            let sample = VideoSample {
+                codec,
+                data,
+                is_key_frame,
+                timestamp: sample.timestamp,
+            };
+            match tx.try_send(sample) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => waiting_key_frame = true,
+                Err(mpsc::error::TrySendError::Closed(_)) => return,
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "webrtc"))]
+pub struct ControlSession;
+
+#[cfg(not(feature = "webrtc"))]
+impl ControlSession {
+    pub async fn connect(_url: &str, _options: ControlOptions) -> anyhow::Result<Self> {
+        Err(anyhow::anyhow!("WebRTC feature 未启用"))
+    }
+}
+
+#[cfg(all(test, feature = "webrtc"))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn test_input_requires_permission() {
+        let event = InputEvent::mouse_move(0.25, 0.75);
+        assert!(input_payload(SessionPermissions::view_only(), &event).is_err());
+
+        let payload = input_payload(SessionPermissions::full(), &event).unwrap();
+        let decoded: InputEvent = serde_json::from_str(&payload).unwrap();
+        assert_eq!(serde_json::to_value(decoded).unwrap(), serde_json::to_value(&event).unwrap());
+    }
+
+    #[test]
+    fn test_control_requires_input_permission() {
+        assert!(require_input(SessionPermissions::view_only()).is_err());
+        assert!(require_input(SessionPermissions { input: true, ..SessionPermissions::view_only() }).is_ok());
+    }
+
+    #[test]
+    fn test_permission_updates_apply() {
+        let permissions = RwLock::new(SessionPermissions::view_only());
+        let event = InputEvent::KeyEvent { key: "a".to_string(), pressed: true };
+        assert!(input_payload(read_permissions(&permissions), &event).is_err());
+
+        update_permissions(&permissions, SessionPermissions::full());
+        assert!(input_payload(read_permissions(&permissions), &event).is_ok());
+
+        // 被控端收回输入权限后立即生效
+        update_permissions(&permissions, SessionPermissions::view_only());
+        assert!(input_payload(read_permissions(&permissions), &event).is_err());
+    }
+
+    #[test]
+    fn test_poisoned_permissions_are_view_only() {
+        let permissions = Arc::new(RwLock::new(SessionPermissions::full()));
+        let poison = permissions.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poison.write().unwrap();
+            panic!("poison");
+        })
+        .join();
+        assert_eq!(read_permissions(&permissions), SessionPermissions::view_only());
+    }
+
+    #[test]
+    fn test_control_state_tracks_holder() {
+        let has_control = AtomicBool::new(false);
+        let granted = br#"{"type":"state","holder":"s1","host":false,"has_control":true}"#;
+        assert!(matches!(parse_control(granted, &has_control), Some(ControlMessage::State { .. })));
+        assert!(has_control.load(Ordering::Relaxed));
+
+        // 其他消息不影响控制权状态
+        assert_eq!(parse_control(br#"{"type":"denied"}"#, &has_control), Some(ControlMessage::Denied));
+        assert!(has_control.load(Ordering::Relaxed));
+        assert!(parse_control(b"not json", &has_control).is_none());
+
+        let revoked = br#"{"type":"state","holder":null,"host":true,"has_control":false}"#;
+        parse_control(revoked, &has_control);
+        assert!(!has_control.load(Ordering::Relaxed));
+    }
+}
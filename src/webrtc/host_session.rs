@@ -4,41 +4,114 @@
 //!
 //! ## 支持的 Codec
 //! - VP8: 软件编码 (libvpx)
+//! - VP9: 软件编码 (libvpx-vp9)，时域分层，每个会话只转发观看者能承载的层
 //! - H.264: 硬件编码 (NVENC/AMF/QSV/VideoToolbox)
+//!
+//! ## 数据通道
+//! Viewer 创建的数据通道按标签对应会话权限 (`input` / `clipboard` / `file`)，
+//! 没有相应权限时丢弃该通道上的所有消息；`stats` 通道用于延迟回显，不受权限限制；
+//! `control` 通道用于申请/移交输入控制权 (需要输入权限)；`chat` 通道用于文字聊天，
+//! 仅查看的 Viewer 也可以使用。`sscontrol` 通道使用带版本和能力协商的控制协议
+//! (见 [`crate::protocol`])，在一个通道上承载以上所有消息
+//!
+//! ## Simulcast
+//! Viewer 的 Offer 声明了 `a=simulcast:recv` 时，同一个视频发送端按 rid 协商多路编码
+//! (见 [`super::simulcast`])，每帧按样本的档位写入对应的编码；接收端的 REMB/TWCC 反馈
+//! 计入传输统计，用于选择该观看者的档位
 
 #![allow(dead_code)]
 
+use crate::config::TurnServerConfig;
+#[cfg(feature = "webrtc")]
+use crate::encoder::vp9::LayerFilter;
+#[cfg(feature = "webrtc")]
+use crate::engine::{KeyFrameGate, MediaEvent, MediaSink, VideoSample};
+#[cfg(feature = "webrtc")]
+use crate::input::InputEvent;
+#[cfg(feature = "webrtc")]
+use crate::nat::predictive_punching::PunchedPath;
+#[cfg(feature = "webrtc")]
+use crate::network::pacing::{CongestionCallback, CongestionFeedback};
+#[cfg(feature = "webrtc")]
+use crate::protocol::{Capability, Envelope, Inbound, Message, ProtocolState, PROTOCOL_CHANNEL};
+#[cfg(feature = "webrtc")]
+use crate::quality::adaptive_bitrate::TransportSnapshot;
+#[cfg(feature = "webrtc")]
+use crate::quality::latency::{self, LatencyMessage};
+#[cfg(feature = "webrtc")]
+use crate::quality::report::{EncoderSwitchEvent, QualityReport};
+#[cfg(feature = "webrtc")]
+use crate::signaling::chat::{ChatMessage, ChatPost, CHAT_CHANNEL};
+#[cfg(feature = "webrtc")]
+use crate::signaling::control::{ControlMessage, CONTROL_CHANNEL};
+#[cfg(feature = "webrtc")]
+use crate::signaling::permissions::{Permission, SessionPermissions};
+use super::candidate::CandidateFilter;
+#[cfg(feature = "webrtc")]
+use super::rtcp::{nack_sequence_numbers, NackTracker};
+#[cfg(feature = "webrtc")]
+use super::simulcast::{RtcpFeedback, SimulcastOffer};
+#[cfg(feature = "webrtc")]
+use crate::system::{SysInfoRequest, SystemReport, SYSINFO_CHANNEL};
 #[cfg(feature = "webrtc")]
 use anyhow::{anyhow, Result};
 #[cfg(feature = "webrtc")]
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 #[cfg(feature = "webrtc")]
 use tokio::sync::{mpsc, Mutex};
 #[cfg(feature = "webrtc")]
 use webrtc::{
+    data_channel::{data_channel_message::DataChannelMessage, RTCDataChannel},
     api::{
-        interceptor_registry::register_default_interceptors,
-        media_engine::{MediaEngine, MIME_TYPE_VP8, MIME_TYPE_H264},
+        interceptor_registry::{configure_twcc, register_default_interceptors},
+        media_engine::{MediaEngine, MIME_TYPE_VP8, MIME_TYPE_VP9, MIME_TYPE_H264},
         setting_engine::SettingEngine,
         APIBuilder,
     },
-    ice::network_type::NetworkType,
-    ice_transport::ice_server::RTCIceServer,
+    ice::udp_network::{EphemeralUDP, UDPNetwork},
+    ice_transport::{ice_candidate::RTCIceCandidateInit, ice_server::RTCIceServer},
     interceptor::registry::Registry,
     peer_connection::{
         configuration::RTCConfiguration,
         sdp::session_description::RTCSessionDescription,
         RTCPeerConnection,
     },
+    rtcp::{
+        payload_feedbacks::{
+            full_intra_request::FullIntraRequest, picture_loss_indication::PictureLossIndication,
+            receiver_estimated_maximum_bitrate::ReceiverEstimatedMaximumBitrate,
+        },
+        transport_feedbacks::{
+            transport_layer_cc::{PacketStatusChunk, SymbolTypeTcc, TransportLayerCc},
+            transport_layer_nack::TransportLayerNack,
+        },
+    },
     rtp_transceiver::rtp_codec::RTCRtpCodecCapability,
     track::track_local::{track_local_static_sample::TrackLocalStaticSample, TrackLocal},
 };
 
+/// ICE 网络配置 (所有会话共用)
+#[derive(Debug, Clone, Default)]
+pub struct IceConfig {
+    /// STUN 服务器 (为空时只收集主机候选)
+    pub stun_servers: Vec<String>,
+    /// TURN 中继服务器 (直连失败时使用)
+    pub turn_servers: Vec<TurnServerConfig>,
+    /// 候选过滤 (隐藏本机地址、只用中继、限定网段/网卡)
+    pub candidate_filter: CandidateFilter,
+    /// 本地 UDP 端口范围 (None = 系统随机分配)
+    pub udp_port_range: Option<(u16, u16)>,
+    /// 路由器端口映射得到的公网 IP，作为 server-reflexive 候选通告
+    pub public_ip: Option<std::net::Ipv4Addr>,
+}
+
 /// 视频 Codec 类型
 #[cfg(feature = "webrtc")]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum VideoCodec {
     VP8,
+    /// 时域分层编码，多个观看者时只编码一次
+    VP9,
     H264,
 }
 
@@ -47,6 +120,7 @@ impl VideoCodec {
     pub fn mime_type(&self) -> &'static str {
         match self {
             Self::VP8 => MIME_TYPE_VP8,
+            Self::VP9 => MIME_TYPE_VP9,
             Self::H264 => MIME_TYPE_H264,
         }
     }
@@ -54,21 +128,61 @@ impl VideoCodec {
     pub fn name(&self) -> &'static str {
         match self {
             Self::VP8 => "VP8",
+            Self::VP9 => "VP9",
             Self::H264 => "H.264",
         }
     }
+
+    /// 是否按时域层编码 (观看者分档时丢弃较高的层，而不是另外编码一路)
+    pub fn has_temporal_layers(&self) -> bool {
+        matches!(self, Self::VP9)
+    }
 }
 
 
 /// Host WebRTC 会话
 #[cfg(feature = "webrtc")]
 pub struct HostSession {
-    peer_id: String,
+    /// 对端 ID (Viewer 断线重连恢复会话后会变化)
+    peer_id: RwLock<String>,
+    /// 逻辑会话 ID (由信令服务器在 Offer 时分配，用于关联日志/统计)
+    session_id: String,
     pc: Arc<RTCPeerConnection>,
-    video_track: Arc<TrackLocalStaticSample>,
+    /// 视频编码 (没有协商 simulcast 时只有一路；否则按档位从高到低，对应各 rid)
+    video_tracks: Vec<Arc<TrackLocalStaticSample>>,
+    /// 协商的 simulcast 编码
+    simulcast: Option<SimulcastOffer>,
+    /// 当前转发的编码和最近一次写入的时间 (切换编码时按间隔推进 RTP 时间戳)
+    active_encoding: std::sync::Mutex<(usize, Option<std::time::Instant>)>,
+    /// 接收端的 REMB/TWCC 反馈 (由读取 RTCP 的任务写入)
+    rtcp_feedback: Arc<std::sync::Mutex<RtcpFeedback>>,
     ice_tx: mpsc::UnboundedSender<IceCandidate>,
     ice_rx: Arc<Mutex<mpsc::UnboundedReceiver<IceCandidate>>>,
     codec: VideoCodec,
+    /// 时域层过滤 (VP9 分层编码时只转发观看者当前档位的层)
+    layer_filter: std::sync::Mutex<LayerFilter>,
+    /// 关键帧状态 (首个关键帧之前不发差分帧，PLI/FIR 请求关键帧)
+    key_frame_gate: Arc<KeyFrameGate>,
+    /// 关键帧请求回调 (PLI/FIR 或重传无法修复的丢包时调用)
+    feedback_callbacks: Arc<std::sync::Mutex<Vec<CongestionCallback>>>,
+    /// 会话权限 (数据通道回调中读取，可在会话期间修改)
+    permissions: Arc<RwLock<SessionPermissions>>,
+    /// 已通过权限检查的输入事件 (由 take_input_events 取走)
+    input_rx: std::sync::Mutex<Option<mpsc::UnboundedReceiver<InputEvent>>>,
+    /// 延迟回显通道 (控制端打开后才有)
+    stats_channel: Arc<std::sync::Mutex<Option<Arc<RTCDataChannel>>>>,
+    /// 控制权通道 (控制端打开后才有)
+    control_channel: Arc<std::sync::Mutex<Option<Arc<RTCDataChannel>>>>,
+    /// 有输入权限时收到的控制权消息 (由 take_control_messages 取走)
+    control_rx: std::sync::Mutex<Option<mpsc::UnboundedReceiver<ControlMessage>>>,
+    /// 聊天通道 (控制端打开后才有)
+    chat_channel: Arc<std::sync::Mutex<Option<Arc<RTCDataChannel>>>>,
+    /// 收到的聊天消息 (由 take_chat_messages 取走)
+    chat_rx: std::sync::Mutex<Option<mpsc::UnboundedReceiver<ChatPost>>>,
+    /// 控制协议通道 (控制端打开后才有)，没有打开对应的旧通道时消息经由这里发送
+    protocol: Arc<std::sync::Mutex<Option<Arc<ProtocolHandler>>>>,
+    /// 会话日志 span (webrtc-rs 回调中的日志也记录在其中)
+    span: tracing::Span,
 }
 
 /// ICE 候选
@@ -86,25 +200,50 @@ impl HostSession {
     ///
     /// # 参数
     /// * `peer_id` - 对端 ID
+    /// * `session_id` - 逻辑会话 ID
     /// * `codec` - 视频 codec 类型（VP8 或 H.264）
-    pub async fn new(peer_id: String, codec: VideoCodec) -> Result<Self> {
+    /// * `permissions` - 该 Viewer 的会话权限
+    /// * `ice` - STUN 服务器、UDP 端口范围等 ICE 配置
+    /// * `punched` - 与该 Viewer 打洞成功的 UDP 路径 (ICE 改为只使用这个 socket)
+    /// * `simulcast` - Offer 中请求的多路编码 (时域分层的 codec 不使用)
+    pub async fn new(
+        peer_id: String,
+        session_id: String,
+        codec: VideoCodec,
+        permissions: SessionPermissions,
+        ice: &IceConfig,
+        punched: Option<PunchedPath>,
+        simulcast: Option<SimulcastOffer>,
+    ) -> Result<Self> {
+        let stun_servers = &ice.stun_servers;
         // 创建媒体引擎
         let mut m = MediaEngine::default();
         m.register_default_codecs()
             .map_err(|e| anyhow!("注册编解码器失败: {:?}", e))?;
 
-        // 创建拦截器
+        // 创建拦截器 (默认拦截器已注册 simulcast 需要的 mid/rid 头部扩展)；
+        // 发送端 TWCC 让接收端逐包反馈到达情况
         let mut registry = Registry::new();
         registry = register_default_interceptors(registry, &mut m)
             .map_err(|e| anyhow!("注册拦截器失败: {:?}", e))?;
+        registry = configure_twcc(registry, &mut m).map_err(|e| anyhow!("注册 TWCC 失败: {:?}", e))?;
 
-        // 创建设置引擎 - 禁用 IPv6，只使用 IPv4 UDP
+        // 创建设置引擎 - IPv4/IPv6 双栈 UDP，按候选过滤器限定网段和网卡
         let mut setting_engine = SettingEngine::default();
-        setting_engine.set_network_types(vec![NetworkType::Udp4]);
+        ice.candidate_filter.configure(&mut setting_engine);
+        if let Some((min, max)) = ice.udp_port_range {
+            let ports = EphemeralUDP::new(min, max).map_err(|e| anyhow!("无效的 UDP 端口范围: {:?}", e))?;
+            setting_engine.set_udp_network(UDPNetwork::Ephemeral(ports));
+        }
+        if let Some(path) = punched {
+            tracing::info!("使用打洞端口: {} -> {}", path.socket.local_addr()?, path.remote);
+            setting_engine.set_udp_network(punched_udp_network(path.socket));
+        }
 
         // 尝试自动获取本机 IP 并设置为 NAT 1:1 映射
-        // 这将强制 ICE 使用该 IP 而不是自动发现
-        if let Ok(local_ip) = local_ip_address::local_ip() {
+        // 这将强制 ICE 使用该 IP 而不是自动发现 (限定了网段或网卡时按过滤器收集，不做映射)
+        let restricted = !ice.candidate_filter.networks.is_empty() || !ice.candidate_filter.interfaces.is_empty();
+        if let Some(local_ip) = local_ip_address::local_ip().ok().filter(|_| !restricted) {
             tracing::info!("自动检测到的本机 IP: {}", local_ip);
 
             // 验证不是链路本地地址
@@ -119,26 +258,24 @@ impl HostSession {
                         vec![local_ip.to_string()],
                         RTCIceCandidateType::Host,
                     );
-                    setting_engine.set_nat_1to1_ips(
-                        vec![local_ip.to_string()],
-                        RTCIceCandidateType::Srflx,
-                    );
+                    // 路由器已按原端口映射 UDP 端口时直接通告公网 IP；
+                    // 配置了 STUN 时由服务器返回真实的公网映射地址
+                    if let Some(public_ip) = ice.public_ip {
+                        setting_engine.set_nat_1to1_ips(
+                            vec![public_ip.to_string()],
+                            RTCIceCandidateType::Srflx,
+                        );
+                    } else if stun_servers.is_empty() {
+                        setting_engine.set_nat_1to1_ips(
+                            vec![local_ip.to_string()],
+                            RTCIceCandidateType::Srflx,
+                        );
+                    }
                     tracing::info!("设置 NAT 1:1 IP 映射: {}", local_ip);
                 }
             }
         }
 
-        // 设置接口过滤 - 过滤掉链路本地地址接口 (名称包含某些特征)
-        // 注意: webrtc-rs 的 interface_filter 只接受接口名称参数
-        setting_engine.set_interface_filter(Box::new(|interface_name: &str| {
-            tracing::debug!("检查网络接口: {}", interface_name);
-
-            // 过滤掉一些已知的链路本地接口名称模式
-            // Windows 上的链路本地接口通常没有特定名称模式
-            // 所以我们接受所有接口，在候选阶段过滤
-            true
-        }));
-
         // 创建 API
         let api = APIBuilder::new()
             .with_media_engine(m)
@@ -146,21 +283,32 @@ impl HostSession {
             .with_setting_engine(setting_engine)
             .build();
 
-        // ICE 服务器配置 - 零第三方依赖
+        // ICE 服务器配置 - 默认零第三方依赖
         //
-        // sscontrol 2.0 使用纯 P2P 架构，无需 STUN/TURN 服务器
-        // NAT 穿透通过以下技术实现：
-        // 1. 主动 NAT 类型探测 (无需 STUN)
+        // NAT 穿透优先通过以下技术实现：
+        // 1. STUN 获取公网映射地址 (可选，建议使用自建的 `sscontrol stun-server`)
         // 2. 预测性端口攻击 (突破对称 NAT)
         // 3. 本地网络发现 (mDNS)
+        // 配置了 TURN 服务器时，直连失败的会话经中继传输
         //
         // 空的 ice_servers 列表表示仅使用主机候选
         let config = RTCConfiguration {
-            ice_servers: vec![], // 零第三方依赖
+            ice_servers: ice_servers(stun_servers, &ice.turn_servers),
+            ice_transport_policy: ice.candidate_filter.transport_policy(),
             ..Default::default()
         };
 
-        tracing::info!("ICE 配置: 零第三方依赖 (纯 P2P 模式)");
+        if stun_servers.is_empty() && ice.turn_servers.is_empty() {
+            tracing::info!("ICE 配置: 零第三方依赖 (纯 P2P 模式)");
+        } else {
+            if !stun_servers.is_empty() {
+                tracing::info!("ICE 配置: STUN {}", stun_servers.join(", "));
+            }
+            if !ice.turn_servers.is_empty() {
+                let urls: Vec<&str> = ice.turn_servers.iter().map(|server| server.url.as_str()).collect();
+                tracing::info!("ICE 配置: TURN {}{}", urls.join(", "), if ice.candidate_filter.relay_only { " (仅中继)" } else { "" });
+            }
+        }
 
         // 创建 PeerConnection
         let pc = Arc::new(
@@ -169,69 +317,336 @@ impl HostSession {
                 .map_err(|e| anyhow!("创建 PeerConnection 失败: {:?}", e))?,
         );
 
-        tracing::info!("使用视频 codec: {}", codec.name());
+        tracing::info!("[{}] 使用视频 codec: {}", session_id, codec.name());
 
-        // 创建视频轨道
-        let video_track = Arc::new(TrackLocalStaticSample::new(
-            RTCRtpCodecCapability {
-                mime_type: codec.mime_type().to_owned(),
-                ..Default::default()
-            },
-            "video".to_owned(),
-            "screen".to_owned(),
-        ));
+        // 创建视频轨道 (simulcast 时每个 rid 一路编码，共用一个发送端)
+        let capability = RTCRtpCodecCapability {
+            mime_type: codec.mime_type().to_owned(),
+            ..Default::default()
+        };
+        // 时域分层的 codec 只编码一次，由各会话丢弃较高的层，不需要多路编码
+        let simulcast = simulcast.filter(|_| !codec.has_temporal_layers());
+        let video_tracks: Vec<Arc<TrackLocalStaticSample>> = match &simulcast {
+            Some(offer) => {
+                tracing::info!("[{}] simulcast 编码: {}", session_id, offer.rids.join(", "));
+                offer
+                    .rids
+                    .iter()
+                    .map(|rid| {
+                        Arc::new(TrackLocalStaticSample::new_with_rid(
+                            capability.clone(),
+                            "video".to_owned(),
+                            rid.clone(),
+                            "screen".to_owned(),
+                        ))
+                    })
+                    .collect()
+            }
+            None => vec![Arc::new(TrackLocalStaticSample::new(capability, "video".to_owned(), "screen".to_owned()))],
+        };
 
         // 添加视频轨道到 PeerConnection
-        pc.add_track(Arc::clone(&video_track) as Arc<dyn TrackLocal + Send + Sync>)
+        let sender = pc
+            .add_track(Arc::clone(&video_tracks[0]) as Arc<dyn TrackLocal + Send + Sync>)
             .await
             .map_err(|e| anyhow!("添加视频轨道失败: {:?}", e))?;
+        for track in &video_tracks[1..] {
+            sender
+                .add_encoding(Arc::clone(track) as Arc<dyn TrackLocal + Send + Sync>)
+                .await
+                .map_err(|e| anyhow!("添加 simulcast 编码 {} 失败: {:?}", track.rid().unwrap_or_default(), e))?;
+        }
+
+        // 读取接收端的 RTCP (经过拦截器处理后才能响应 NACK)，记录 REMB 和 TWCC 反馈。
+        // NACK 由拦截器重传，重传后仍反复 NACK 的包和 PLI/FIR 一样说明接收端丢失了参考帧，转为关键帧请求
+        let rtcp_feedback = Arc::new(std::sync::Mutex::new(RtcpFeedback::default()));
+        let feedback = Arc::clone(&rtcp_feedback);
+        let key_frame_gate = Arc::new(KeyFrameGate::new());
+        let feedback_callbacks: Arc<std::sync::Mutex<Vec<CongestionCallback>>> = Arc::default();
+        let gate = Arc::clone(&key_frame_gate);
+        let callbacks = Arc::clone(&feedback_callbacks);
+        let rtcp_session_id = session_id.clone();
+        tokio::spawn(async move {
+            let mut nacks = NackTracker::new();
+            while let Ok((packets, _)) = sender.read_rtcp().await {
+                let Ok(mut feedback) = feedback.lock() else {
+                    break;
+                };
+                let mut key_frame = false;
+                for packet in &packets {
+                    let packet = packet.as_any();
+                    if let Some(remb) = packet.downcast_ref::<ReceiverEstimatedMaximumBitrate>() {
+                        feedback.record_remb(f64::from(remb.bitrate));
+                    } else if let Some(twcc) = packet.downcast_ref::<TransportLayerCc>() {
+                        let (received, lost) = twcc_counts(twcc);
+                        feedback.record_twcc(received, lost);
+                    } else if packet.is::<PictureLossIndication>() || packet.is::<FullIntraRequest>() {
+                        tracing::debug!("[{}] 接收端请求关键帧 (PLI/FIR)", rtcp_session_id);
+                        key_frame = true;
+                    } else if let Some(nack) = packet.downcast_ref::<TransportLayerNack>() {
+                        if nacks.record(nack.media_ssrc, nack_sequence_numbers(nack)) {
+                            tracing::debug!(
+                                "[{}] 重传未能修复丢包，请求关键帧 (累计 NACK {} 个包)",
+                                rtcp_session_id,
+                                nacks.nacked_packets()
+                            );
+                            key_frame = true;
+                        }
+                    }
+                }
+                if key_frame {
+                    gate.request();
+                    if let Ok(callbacks) = callbacks.lock() {
+                        for callback in callbacks.iter() {
+                            callback(CongestionFeedback::KeyFrameNeeded);
+                        }
+                    }
+                }
+            }
+        });
 
         // ICE 候选通道
         let (ice_tx, ice_rx) = mpsc::unbounded_channel();
 
-        // 设置 ICE 候选回调
+        let span = crate::logging::session_span(&peer_id, &session_id);
+
+        // 设置 ICE 候选回调: 每个候选收集到后立即发送 (trickle)，收集结束时发送空候选；
+        // 按过滤器丢弃或改写候选 (链路本地地址、隐藏本机地址、只用中继)
         let ice_tx_clone = ice_tx.clone();
+        let span_clone = span.clone();
+        let candidate_filter = ice.candidate_filter.clone();
         pc.on_ice_candidate(Box::new(move |c| {
-            if let Some(c) = c {
-                if let Ok(init) = c.to_json() {
-                    // 过滤无效的链路本地地址 (169.254.x.x)
-                    let candidate_str = &init.candidate;
-                    if candidate_str.contains("169.254.") {
-                        tracing::debug!("过滤链路本地地址候选: {}", candidate_str);
-                        return Box::pin(async {});
-                    }
-
+            let _entered = span_clone.enter();
+            let init = match c {
+                Some(c) => match c.to_json() {
+                    Ok(init) => init,
+                    Err(_) => return Box::pin(async {}),
+                },
+                None => RTCIceCandidateInit::default(),
+            };
+            match candidate_filter.outgoing(&init.candidate) {
+                Some(candidate) => {
                     let _ = ice_tx_clone.send(IceCandidate {
-                        candidate: init.candidate,
+                        candidate,
                         sdp_mid: init.sdp_mid.unwrap_or_default(),
                         sdp_mline_index: init.sdp_mline_index.unwrap_or(0),
                     });
                 }
+                None => tracing::debug!("过滤候选: {}", init.candidate),
             }
             Box::pin(async {})
         }));
 
         // 设置连接状态回调
-        let peer_id_clone = peer_id.clone();
+        // 断线时等待 Viewer 发起 ICE 重启 (Answer 方无法主动重启)
+        let session_id_clone = session_id.clone();
+        let span_clone = span.clone();
         pc.on_peer_connection_state_change(Box::new(move |s| {
-            tracing::info!("PeerConnection 状态 [{}]: {:?}", peer_id_clone, s);
+            let _entered = span_clone.enter();
+            tracing::info!("PeerConnection 状态 ({}): {:?}", session_id_clone, s);
+            Box::pin(async {})
+        }));
+
+        // 数据通道 (由 Viewer 创建)
+        let permissions = Arc::new(RwLock::new(permissions));
+        let (input_tx, input_rx) = mpsc::unbounded_channel();
+        let permissions_clone = permissions.clone();
+        let session_id_clone = session_id.clone();
+        let stats_channel = Arc::new(std::sync::Mutex::new(None));
+        let stats_channel_clone = stats_channel.clone();
+        let control_channel = Arc::new(std::sync::Mutex::new(None));
+        let control_channel_clone = control_channel.clone();
+        let (control_tx, control_rx) = mpsc::unbounded_channel();
+        let chat_channel = Arc::new(std::sync::Mutex::new(None));
+        let chat_channel_clone = chat_channel.clone();
+        let (chat_tx, chat_rx) = mpsc::unbounded_channel();
+        let protocol = Arc::new(std::sync::Mutex::new(None));
+        let protocol_clone = protocol.clone();
+        let span_clone = span.clone();
+        pc.on_data_channel(Box::new(move |channel: Arc<RTCDataChannel>| {
+            let _entered = span_clone.enter();
+            let label = channel.label().to_string();
+            if label == PROTOCOL_CHANNEL {
+                tracing::info!("[{}] 数据通道已打开: {}", session_id_clone, label);
+                // 弱引用避免通道与自身的消息回调形成循环引用
+                let handler = Arc::new(ProtocolHandler {
+                    state: std::sync::Mutex::new(ProtocolState::default()),
+                    channel: Arc::downgrade(&channel),
+                    permissions: permissions_clone.clone(),
+                    input_tx: input_tx.clone(),
+                    control_tx: control_tx.clone(),
+                    chat_tx: chat_tx.clone(),
+                    session_id: session_id_clone.clone(),
+                    span: span_clone.clone(),
+                });
+                let handler_clone = handler.clone();
+                channel.on_message(Box::new(move |msg: DataChannelMessage| {
+                    let handler = handler_clone.clone();
+                    Box::pin(async move { handler.handle(&msg.data).await })
+                }));
+                if let Ok(mut slot) = protocol_clone.lock() {
+                    *slot = Some(handler);
+                }
+                return Box::pin(async {});
+            }
+            if label == CHAT_CHANNEL {
+                tracing::info!("[{}] 数据通道已打开: {}", session_id_clone, label);
+                let chat_tx = chat_tx.clone();
+                let session_id = session_id_clone.clone();
+                let span = span_clone.clone();
+                channel.on_message(Box::new(move |msg: DataChannelMessage| {
+                    let _entered = span.enter();
+                    match serde_json::from_slice::<ChatPost>(&msg.data) {
+                        Ok(post) => {
+                            let _ = chat_tx.send(post);
+                        }
+                        Err(e) => tracing::debug!("[{}] 无效的聊天消息: {}", session_id, e),
+                    }
+                    Box::pin(async {})
+                }));
+                if let Ok(mut slot) = chat_channel_clone.lock() {
+                    *slot = Some(channel);
+                }
+                return Box::pin(async {});
+            }
+            if label == CONTROL_CHANNEL {
+                tracing::info!("[{}] 数据通道已打开: {}", session_id_clone, label);
+                let permissions = permissions_clone.clone();
+                let control_tx = control_tx.clone();
+                let session_id = session_id_clone.clone();
+                let span = span_clone.clone();
+                channel.on_message(Box::new(move |msg: DataChannelMessage| {
+                    let _entered = span.enter();
+                    // 仅查看的 Viewer 不能申请或移交控制权
+                    let allowed = permissions.read().map(|p| p.input).unwrap_or(false);
+                    match serde_json::from_slice::<ControlMessage>(&msg.data) {
+                        Ok(message) if allowed => {
+                            let _ = control_tx.send(message);
+                        }
+                        Ok(_) => tracing::debug!("[{}] 无输入权限，丢弃控制权消息", session_id),
+                        Err(e) => tracing::debug!("[{}] 无效的控制权消息: {}", session_id, e),
+                    }
+                    Box::pin(async {})
+                }));
+                if let Ok(mut slot) = control_channel_clone.lock() {
+                    *slot = Some(channel);
+                }
+                return Box::pin(async {});
+            }
+            if label == latency::STATS_CHANNEL {
+                tracing::info!("[{}] 数据通道已打开: {}", session_id_clone, label);
+                channel.on_message(Box::new(|msg: DataChannelMessage| {
+                    if let Ok(message) = serde_json::from_slice::<LatencyMessage>(&msg.data) {
+                        latency::tracker().on_echo(&message);
+                    }
+                    Box::pin(async {})
+                }));
+                if let Ok(mut slot) = stats_channel_clone.lock() {
+                    *slot = Some(channel);
+                }
+                return Box::pin(async {});
+            }
+            if label == SYSINFO_CHANNEL {
+                tracing::info!("[{}] 数据通道已打开: {}", session_id_clone, label);
+                // 弱引用避免通道与自身的消息回调形成循环引用
+                let reply = Arc::downgrade(&channel);
+                let session_id = session_id_clone.clone();
+                channel.on_message(Box::new(move |msg: DataChannelMessage| {
+                    let request = match serde_json::from_slice::<SysInfoRequest>(&msg.data) {
+                        Ok(request) => request,
+                        Err(e) => {
+                            tracing::debug!("[{}] 无效的系统信息请求: {}", session_id, e);
+                            return Box::pin(async {});
+                        }
+                    };
+                    let reply = reply.clone();
+                    let session_id = session_id.clone();
+                    Box::pin(async move {
+                        let top = request.top();
+                        let Ok(report) = tokio::task::spawn_blocking(move || SystemReport::collect(top)).await else {
+                            return;
+                        };
+                        let (Some(channel), Ok(text)) = (reply.upgrade(), serde_json::to_string(&report)) else {
+                            return;
+                        };
+                        if let Err(e) = channel.send_text(text).await {
+                            tracing::debug!("[{}] 发送系统信息失败: {}", session_id, e);
+                        }
+                    })
+                }));
+                return Box::pin(async {});
+            }
+            let Some(permission) = Permission::from_channel_label(&label) else {
+                tracing::warn!("[{}] 忽略未知数据通道: {}", session_id_clone, label);
+                return Box::pin(async {});
+            };
+            tracing::info!("[{}] 数据通道已打开: {}", session_id_clone, label);
+
+            let permissions = permissions_clone.clone();
+            let input_tx = input_tx.clone();
+            let session_id = session_id_clone.clone();
+            let span = span_clone.clone();
+            channel.on_message(Box::new(move |msg: DataChannelMessage| {
+                let _entered = span.enter();
+                let allowed = permissions
+                    .read()
+                    .map(|p| p.allows(permission))
+                    .unwrap_or(false);
+                if !allowed {
+                    tracing::debug!("[{}] 无{}权限，丢弃消息", session_id, permission.label());
+                    return Box::pin(async {});
+                }
+
+                match permission {
+                    Permission::Input => match serde_json::from_slice::<InputEvent>(&msg.data) {
+                        Ok(event) => {
+                            let _ = input_tx.send(event);
+                        }
+                        Err(e) => tracing::debug!("[{}] 无效的输入事件: {}", session_id, e),
+                    },
+                    _ => tracing::debug!("[{}] 暂不支持的数据通道: {}", session_id, permission.label()),
+                }
+                Box::pin(async {})
+            }));
             Box::pin(async {})
         }));
 
         Ok(Self {
-            peer_id,
+            peer_id: RwLock::new(peer_id),
+            session_id,
             pc,
-            video_track,
+            video_tracks,
+            simulcast,
+            active_encoding: std::sync::Mutex::new((0, None)),
+            rtcp_feedback,
             ice_tx,
             ice_rx: Arc::new(Mutex::new(ice_rx)),
             codec,
+            layer_filter: std::sync::Mutex::new(LayerFilter::default()),
+            key_frame_gate,
+            feedback_callbacks,
+            permissions,
+            input_rx: std::sync::Mutex::new(Some(input_rx)),
+            stats_channel,
+            control_channel,
+            control_rx: std::sync::Mutex::new(Some(control_rx)),
+            chat_channel,
+            chat_rx: std::sync::Mutex::new(Some(chat_rx)),
+            protocol,
+            span,
         })
     }
 
     /// 处理来自 Viewer 的 Offer，返回 Answer
+    ///
+    /// 已协商的会话再次收到 Offer 时为重协商；ICE 凭据变化时 (Viewer 发起 ICE 重启)
+    /// 会在原 PeerConnection 上重新收集候选，数据通道和视频轨道保持不变
     pub async fn handle_offer(&self, offer_sdp: &str) -> Result<String> {
         let offer = RTCSessionDescription::offer(offer_sdp.to_string())
             .map_err(|e| anyhow!("解析 Offer 失败: {:?}", e))?;
+        if self.pc.remote_description().await.is_some() {
+            tracing::info!("[{}] 重新协商 (ICE 重启)", self.session_id);
+        }
 
         self.pc
             .set_remote_description(offer)
@@ -252,10 +667,8 @@ impl HostSession {
         Ok(answer.sdp)
     }
 
-    /// 添加远程 ICE 候选
+    /// 添加远程 ICE 候选 (空候选表示对端收集结束)
     pub async fn add_ice_candidate(&self, candidate: &IceCandidate) -> Result<()> {
-        use webrtc::ice_transport::ice_candidate::RTCIceCandidateInit;
-
         let init = RTCIceCandidateInit {
             candidate: candidate.candidate.clone(),
             sdp_mid: Some(candidate.sdp_mid.clone()),
@@ -276,17 +689,86 @@ impl HostSession {
         self.ice_rx.lock().await.recv().await
     }
 
-    /// 发送视频帧 (VP8 编码后的数据)
+    /// 取走输入事件接收器 (仅包含有输入权限时收到的事件，只能取一次)
+    ///
+    /// PeerConnection 释放后接收器结束
+    pub fn take_input_events(&self) -> Option<mpsc::UnboundedReceiver<InputEvent>> {
+        self.input_rx.lock().ok()?.take()
+    }
+
+    /// 取走控制权消息接收器 (仅包含有输入权限时收到的消息，只能取一次)
+    pub fn take_control_messages(&self) -> Option<mpsc::UnboundedReceiver<ControlMessage>> {
+        self.control_rx.lock().ok()?.take()
+    }
+
+    /// 取走聊天消息接收器 (只能取一次)
+    pub fn take_chat_messages(&self) -> Option<mpsc::UnboundedReceiver<ChatPost>> {
+        self.chat_rx.lock().ok()?.take()
+    }
+
+    /// 当前会话权限
+    pub fn permissions(&self) -> SessionPermissions {
+        self.permissions.read().map(|p| *p).unwrap_or_else(|_| SessionPermissions::view_only())
+    }
+
+    /// 修改会话权限 (立即作用于数据通道)
+    pub fn set_permissions(&self, permissions: SessionPermissions) {
+        if let Ok(mut current) = self.permissions.write() {
+            *current = permissions;
+        }
+        tracing::info!("[{}] 会话权限: {}", self.session_id, permissions);
+    }
+
+    /// 设置转发的最高时域层 (降层立即生效，升层从下一个基础层帧开始)
+    pub fn set_max_temporal_layer(&self, layer: u8) {
+        if let Ok(mut filter) = self.layer_filter.lock() {
+            filter.set_max_layer(layer);
+        }
+    }
+
+    /// 当前转发的最高时域层
+    pub fn max_temporal_layer(&self) -> u8 {
+        self.layer_filter.lock().map_or(u8::MAX, |filter| filter.max_layer())
+    }
+
+    /// 协商的 simulcast 编码 (没有协商时为 None)
+    pub fn simulcast(&self) -> Option<&SimulcastOffer> {
+        self.simulcast.as_ref()
+    }
+
+    /// 发送视频帧 (编码后的数据，写入当前转发的编码)
     pub async fn send_video_sample(&self, data: Vec<u8>, duration: std::time::Duration) -> Result<()> {
+        let encoding = self.active_encoding.lock().map_or(0, |active| active.0);
+        self.send_encoding_sample(encoding, data, duration).await
+    }
+
+    /// 向指定档位的编码发送视频帧 (没有协商 simulcast 时只有一路)
+    ///
+    /// 切换编码后，新编码的 RTP 时间戳按暂停期间的实际间隔推进
+    async fn send_encoding_sample(&self, tier: usize, data: Vec<u8>, duration: std::time::Duration) -> Result<()> {
         use webrtc::media::Sample;
 
+        let index = tier.min(self.video_tracks.len() - 1);
+        let duration = match self.active_encoding.lock() {
+            Ok(mut active) => {
+                let now = std::time::Instant::now();
+                let resumed = active.0 != index;
+                let elapsed = active.1.map(|last| now.duration_since(last));
+                *active = (index, Some(now));
+                match elapsed {
+                    Some(elapsed) if resumed => elapsed.max(duration),
+                    _ => duration,
+                }
+            }
+            Err(_) => duration,
+        };
         let sample = Sample {
             data: data.into(),
             duration,
             ..Default::default()
         };
 
-        self.video_track
+        self.video_tracks[index]
             .write_sample(&sample)
             .await
             .map_err(|e| anyhow!("发送视频帧失败: {:?}", e))?;
@@ -294,9 +776,154 @@ impl HostSession {
         Ok(())
     }
 
+    /// 经由控制协议通道发送 (没有打开通道、没有握手或没有协商相应能力时忽略)
+    async fn send_protocol(&self, message: Message) {
+        let handler = self.protocol.lock().ok().and_then(|slot| slot.clone());
+        if let Some(handler) = handler {
+            handler.send(message, None).await;
+        }
+    }
+
+    /// 发送延迟探测 (控制端没有打开 `stats` 或 `sscontrol` 通道时忽略)
+    pub async fn send_latency_probe(&self, message: &LatencyMessage) {
+        let channel = self.stats_channel.lock().ok().and_then(|slot| slot.clone());
+        let Some(channel) = channel else {
+            self.send_protocol(Message::Latency(*message)).await;
+            return;
+        };
+        let Ok(text) = serde_json::to_string(message) else {
+            return;
+        };
+        if let Err(e) = channel.send_text(text).await {
+            tracing::debug!(parent: &self.span, "[{}] 发送延迟探测失败: {}", self.session_id, e);
+        }
+    }
+
+    /// 发送连接质量报告 (控制端没有打开 `stats` 或 `sscontrol` 通道时忽略)
+    pub async fn send_quality_report(&self, report: &QualityReport) {
+        let channel = self.stats_channel.lock().ok().and_then(|slot| slot.clone());
+        let Some(channel) = channel else {
+            self.send_protocol(Message::Quality(report.clone())).await;
+            return;
+        };
+        if let Err(e) = channel.send_text(report.to_json()).await {
+            tracing::debug!(parent: &self.span, "[{}] 发送质量报告失败: {}", self.session_id, e);
+        }
+    }
+
+    /// 发送编码器切换事件 (控制端没有打开 `stats` 或 `sscontrol` 通道时忽略)
+    pub async fn send_encoder_switch(&self, event: &EncoderSwitchEvent) {
+        let channel = self.stats_channel.lock().ok().and_then(|slot| slot.clone());
+        let Some(channel) = channel else {
+            self.send_protocol(Message::EncoderSwitch(event.clone())).await;
+            return;
+        };
+        if let Err(e) = channel.send_text(event.to_json()).await {
+            tracing::debug!(parent: &self.span, "[{}] 发送编码器切换事件失败: {}", self.session_id, e);
+        }
+    }
+
+    /// 发送控制权消息 (控制端没有打开 `control` 或 `sscontrol` 通道时忽略)
+    pub async fn send_control(&self, message: &ControlMessage) {
+        let channel = self.control_channel.lock().ok().and_then(|slot| slot.clone());
+        let Some(channel) = channel else {
+            self.send_protocol(Message::Control(message.clone())).await;
+            return;
+        };
+        let Ok(text) = serde_json::to_string(message) else {
+            return;
+        };
+        if let Err(e) = channel.send_text(text).await {
+            tracing::debug!(parent: &self.span, "[{}] 发送控制权消息失败: {}", self.session_id, e);
+        }
+    }
+
+    /// 发送聊天消息 (控制端没有打开 `chat` 或 `sscontrol` 通道时忽略)
+    pub async fn send_chat(&self, message: &ChatMessage) {
+        let channel = self.chat_channel.lock().ok().and_then(|slot| slot.clone());
+        let Some(channel) = channel else {
+            self.send_protocol(Message::ChatMessage(message.clone())).await;
+            return;
+        };
+        let Ok(text) = serde_json::to_string(message) else {
+            return;
+        };
+        if let Err(e) = channel.send_text(text).await {
+            tracing::debug!(parent: &self.span, "[{}] 发送聊天消息失败: {}", self.session_id, e);
+        }
+    }
+
+    /// 采集传输统计 (发送端计数 + RTCP 接收报告)
+    pub async fn transport_snapshot(&self) -> TransportSnapshot {
+        use webrtc::stats::StatsReportType;
+
+        let report = self.pc.get_stats().await;
+        let mut snapshot = TransportSnapshot::default();
+
+        for stats in report.reports.values() {
+            match stats {
+                StatsReportType::OutboundRTP(outbound) if outbound.kind == "video" => {
+                    snapshot.bytes_sent += outbound.bytes_sent;
+                    snapshot.packets_sent += outbound.packets_sent;
+                    snapshot.nack_count += outbound.nack_count;
+                }
+                StatsReportType::RemoteInboundRTP(remote) if remote.kind == "video" => {
+                    snapshot.packets_lost += remote.packets_lost;
+                    snapshot.fraction_lost = snapshot.fraction_lost.max(remote.fraction_lost);
+                    if let Some(rtt) = remote.round_trip_time {
+                        snapshot.rtt_ms = Some(rtt * 1000.0);
+                    }
+                }
+                StatsReportType::CandidatePair(pair) if pair.nominated => {
+                    if snapshot.rtt_ms.is_none() && pair.current_round_trip_time > 0.0 {
+                        snapshot.rtt_ms = Some(pair.current_round_trip_time * 1000.0);
+                    }
+                    snapshot.available_bandwidth_mbps = pair.available_outgoing_bitrate / 1_000_000.0;
+                }
+                _ => {}
+            }
+        }
+
+        // 接收端反馈: REMB 是接收端估计的最大码率，TWCC 的逐包到达情况比接收报告更及时
+        if let Ok(mut feedback) = self.rtcp_feedback.lock() {
+            let (remb_mbps, twcc_loss) = feedback.take();
+            if let Some(remb) = remb_mbps {
+                snapshot.available_bandwidth_mbps = if snapshot.available_bandwidth_mbps > 0.0 {
+                    snapshot.available_bandwidth_mbps.min(remb)
+                } else {
+                    remb
+                };
+            }
+            if let Some(loss) = twcc_loss {
+                snapshot.fraction_lost = snapshot.fraction_lost.max(loss);
+            }
+        }
+
+        snapshot
+    }
+
     /// 获取 peer_id
-    pub fn peer_id(&self) -> &str {
-        &self.peer_id
+    pub fn peer_id(&self) -> String {
+        self.peer_id.read().map(|id| id.clone()).unwrap_or_default()
+    }
+
+    /// Viewer 重连恢复会话后更新 peer_id
+    pub fn set_peer_id(&self, peer_id: String) {
+        if let Ok(mut current) = self.peer_id.write() {
+            tracing::info!("[{}] Viewer 已重连: {} -> {}", self.session_id, current, peer_id);
+            self.span.record("peer_id", peer_id.as_str());
+            *current = peer_id;
+        }
+    }
+
+    /// 会话日志 span (会话相关的日志应记录在其中，以便按会话过滤)
+    pub fn span(&self) -> &tracing::Span {
+        &self.span
+    }
+
+    /// 获取会话 ID
+    pub fn session_id(&self) -> &str {
+        &self.session_id
     }
 
     /// 获取视频 codec 类型
@@ -314,12 +941,237 @@ impl HostSession {
     }
 }
 
+#[cfg(feature = "webrtc")]
+#[async_trait::async_trait]
+impl MediaSink for HostSession {
+    fn name(&self) -> String {
+        self.session_id.clone()
+    }
+
+    async fn send_video(&self, sample: VideoSample<'_>) -> Result<()> {
+        // 新会话在第一个关键帧之前无法解码，差分帧直接丢弃
+        if !self.key_frame_gate.admit(sample.is_key_frame) {
+            return Ok(());
+        }
+        // 丢弃的层不发送，其时长计入下一个发送的帧
+        let duration = match self.layer_filter.lock() {
+            Ok(mut filter) => filter.admit(sample.temporal_layer, sample.is_key_frame, sample.duration),
+            Err(_) => Some(sample.duration),
+        };
+        match duration {
+            Some(duration) => self.send_encoding_sample(sample.tier, sample.data.to_vec(), duration).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn send_event(&self, event: &MediaEvent) -> Result<()> {
+        match event {
+            MediaEvent::Control(message) => self.send_control(message).await,
+            MediaEvent::Chat(message) => self.send_chat(message).await,
+        }
+        Ok(())
+    }
+
+    async fn on_feedback(&self, callback: CongestionCallback) {
+        if let Ok(mut callbacks) = self.feedback_callbacks.lock() {
+            callbacks.push(callback);
+        }
+    }
+
+    fn take_key_frame_request(&self) -> bool {
+        self.key_frame_gate.take_request()
+    }
+}
+
+/// 控制协议通道上的消息处理
+///
+/// 握手后按会话权限把消息转交给与旧数据通道相同的队列
+#[cfg(feature = "webrtc")]
+struct ProtocolHandler {
+    state: std::sync::Mutex<ProtocolState>,
+    channel: std::sync::Weak<RTCDataChannel>,
+    permissions: Arc<RwLock<SessionPermissions>>,
+    input_tx: mpsc::UnboundedSender<InputEvent>,
+    control_tx: mpsc::UnboundedSender<ControlMessage>,
+    chat_tx: mpsc::UnboundedSender<ChatPost>,
+    session_id: String,
+    span: tracing::Span,
+}
+
+#[cfg(feature = "webrtc")]
+impl ProtocolHandler {
+    fn has_input(&self) -> bool {
+        self.permissions.read().map(|p| p.input).unwrap_or(false)
+    }
+
+    /// 该会话当前可以提供的能力 (输入和控制权需要输入权限)
+    fn offered(&self) -> Vec<Capability> {
+        let mut offered = vec![Capability::Chat, Capability::Stats, Capability::Sysinfo];
+        if self.has_input() {
+            offered.extend([Capability::Input, Capability::Control]);
+        }
+        offered
+    }
+
+    async fn handle(&self, data: &[u8]) {
+        let offered = self.offered();
+        let Ok(inbound) = self.state.lock().map(|mut state| state.receive(data, &offered)) else {
+            return;
+        };
+        match inbound {
+            Inbound::Reply(envelope) => {
+                if let Message::Welcome(welcome) = &envelope.message {
+                    tracing::info!(
+                        parent: &self.span,
+                        "[{}] 控制协议握手完成: 版本 {}，能力 {:?}",
+                        self.session_id, envelope.version, welcome.capabilities
+                    );
+                }
+                self.send_envelope(&envelope).await;
+            }
+            Inbound::Deliver { id, message } => self.deliver(id, message).await,
+        }
+    }
+
+    async fn deliver(&self, id: Option<u64>, message: Message) {
+        match message {
+            // 握手后输入权限可能被收回
+            Message::Input(_) | Message::Control(_) if !self.has_input() => {
+                tracing::debug!(parent: &self.span, "[{}] 无输入权限，丢弃{}消息", self.session_id, message.kind());
+            }
+            Message::Input(event) => {
+                let _ = self.input_tx.send(event);
+            }
+            Message::Control(control) => {
+                let _ = self.control_tx.send(control);
+            }
+            Message::Chat(post) => {
+                let _ = self.chat_tx.send(post);
+            }
+            Message::Latency(probe) => latency::tracker().on_echo(&probe),
+            Message::SysinfoRequest(request) => {
+                let top = request.top();
+                if let Ok(report) = tokio::task::spawn_blocking(move || SystemReport::collect(top)).await {
+                    self.send(Message::Sysinfo(report), id).await;
+                }
+            }
+            Message::Error(error) => {
+                tracing::debug!(parent: &self.span, "[{}] 控制端报告错误: {}", self.session_id, error);
+            }
+            message => {
+                tracing::debug!(parent: &self.span, "[{}] 忽略控制协议消息: {}", self.session_id, message.kind());
+            }
+        }
+    }
+
+    /// 发送消息 (没有握手或没有协商相应能力时忽略)
+    async fn send(&self, message: Message, id: Option<u64>) {
+        let envelope = match self.state.lock() {
+            Ok(state) => {
+                let allowed = match (Capability::of(&message), state.negotiated()) {
+                    (_, None) => false,
+                    (None, Some(_)) => true,
+                    (Some(capability), Some(negotiated)) => negotiated.allows(capability),
+                };
+                if !allowed {
+                    return;
+                }
+                state.envelope(message, id)
+            }
+            Err(_) => return,
+        };
+        self.send_envelope(&envelope).await;
+    }
+
+    async fn send_envelope(&self, envelope: &Envelope) {
+        let Some(channel) = self.channel.upgrade() else {
+            return;
+        };
+        if let Err(e) = channel.send_text(envelope.encode()).await {
+            tracing::debug!(parent: &self.span, "[{}] 发送控制协议消息失败: {}", self.session_id, e);
+        }
+    }
+}
+
+/// TWCC 报告中到达和未到达的包数
+#[cfg(feature = "webrtc")]
+fn twcc_counts(twcc: &TransportLayerCc) -> (u64, u64) {
+    // 最后一个状态向量可能有填充，只统计 packet_status_count 个包
+    let mut remaining = u64::from(twcc.packet_status_count);
+    let (mut received, mut lost) = (0u64, 0u64);
+    let mut count = |symbol: SymbolTypeTcc, run: u64| {
+        let run = run.min(remaining);
+        remaining -= run;
+        if symbol == SymbolTypeTcc::PacketNotReceived {
+            lost += run;
+        } else {
+            received += run;
+        }
+    };
+    for chunk in &twcc.packet_chunks {
+        match chunk {
+            PacketStatusChunk::RunLengthChunk(run) => count(run.packet_status_symbol, u64::from(run.run_length)),
+            PacketStatusChunk::StatusVectorChunk(vector) => {
+                for &symbol in &vector.symbol_list {
+                    count(symbol, 1);
+                }
+            }
+        }
+    }
+    (received, lost)
+}
+
+/// ICE 使用 IPv4 和 IPv6 UDP 候选 (纯 IPv6 网络也能连通)，
+/// 丢弃 IPv6 链路本地地址: 它们带网卡作用域，对端无法直接使用
+#[cfg(feature = "webrtc")]
+pub fn configure_ice_network(setting_engine: &mut SettingEngine) {
+    CandidateFilter::default().configure(setting_engine);
+}
+
+/// 把打洞成功的 socket 作为 ICE 的唯一 UDP 端口 (多路复用所有候选对)
+#[cfg(feature = "webrtc")]
+pub fn punched_udp_network(socket: tokio::net::UdpSocket) -> UDPNetwork {
+    use webrtc::ice::udp_mux::{UDPMuxDefault, UDPMuxParams};
+
+    UDPNetwork::Muxed(UDPMuxDefault::new(UDPMuxParams::new(socket)))
+}
+
+/// 将 STUN 和 TURN 服务器转换为 ICE 服务器配置 (STUN 地址补全 `stun:` 前缀)
+#[cfg(feature = "webrtc")]
+pub fn ice_servers(stun_servers: &[String], turn_servers: &[TurnServerConfig]) -> Vec<RTCIceServer> {
+    // 每个 TURN 服务器的凭证不同，各占一项
+    let turn = turn_servers.iter().map(|server| RTCIceServer {
+        urls: vec![server.url.clone()],
+        username: server.username.clone(),
+        credential: server.password.clone(),
+    });
+    if stun_servers.is_empty() {
+        return turn.collect();
+    }
+    let urls = stun_servers
+        .iter()
+        .map(|url| {
+            if url.starts_with("stun:") || url.starts_with("stuns:") {
+                url.clone()
+            } else {
+                format!("stun:{}", url)
+            }
+        })
+        .collect();
+    std::iter::once(RTCIceServer {
+        urls,
+        ..Default::default()
+    })
+    .chain(turn)
+    .collect()
+}
+
 #[cfg(not(feature = "webrtc"))]
 pub struct HostSession;
 
 #[cfg(not(feature = "webrtc"))]
 impl HostSession {
-    pub async fn new(_peer_id: String) -> anyhow::Result<Self> {
+    pub async fn new(_peer_id: String, _session_id: String) -> anyhow::Result<Self> {
         Err(anyhow::anyhow!("WebRTC feature 未启用"))
     }
 }
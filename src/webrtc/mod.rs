@@ -7,9 +7,13 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
+pub mod candidate;
+pub mod control_session;
 pub mod host_session;
 pub mod peer_connection;
+pub mod rtcp;
 pub mod signaling;
+pub mod simulcast;
 pub mod video_track;
 
 /// WebRTC 配置
@@ -39,6 +43,30 @@ impl Default for WebRTCConfig {
     }
 }
 
+impl From<&crate::config::WebRTCConfig> for WebRTCConfig {
+    /// 使用配置文件中的 STUN/TURN 服务器和传输策略
+    fn from(config: &crate::config::WebRTCConfig) -> Self {
+        Self {
+            stun_servers: config.stun_servers.clone(),
+            turn_servers: config
+                .turn_servers
+                .iter()
+                .map(|server| TurnServer {
+                    url: server.url.clone(),
+                    username: server.username.clone(),
+                    password: server.password.clone(),
+                })
+                .collect(),
+            ice_transport_policy: if config.ice_transport_policy == "relay" {
+                IceTransportPolicy::Relay
+            } else {
+                IceTransportPolicy::All
+            },
+            use_ipv6: true,
+        }
+    }
+}
+
 /// TURN 服务器配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TurnServer {
@@ -250,6 +278,22 @@ pub async fn create_peer_connection_async(config: WebRTCConfig) -> Result<Box<dy
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_webrtc_config_from_file_config() {
+        let file = crate::config::WebRTCConfig {
+            stun_servers: vec!["stun:stun.example.com:3478".to_string()],
+            turn_servers: vec!["turn:alice:secret@turn.example.com:3478".parse().unwrap()],
+            ice_transport_policy: "relay".to_string(),
+            ..Default::default()
+        };
+
+        let config = WebRTCConfig::from(&file);
+        assert_eq!(config.stun_servers, file.stun_servers);
+        assert_eq!(config.turn_servers[0].url, "turn:turn.example.com:3478");
+        assert_eq!(config.turn_servers[0].password, "secret");
+        assert_eq!(config.ice_transport_policy, IceTransportPolicy::Relay);
+    }
+
     #[test]
     fn test_webrtc_config_default() {
         let config = WebRTCConfig::default();
@@ -22,15 +22,14 @@ use webrtc::{
         APIBuilder,
     },
     data_channel::RTCDataChannel,
-    ice::network_type::NetworkType,
     ice_transport::{
         ice_candidate::RTCIceCandidateInit,
         ice_connection_state::RTCIceConnectionState,
-        ice_server::RTCIceServer,
     },
     peer_connection::{
         configuration::RTCConfiguration,
         peer_connection_state::RTCPeerConnectionState,
+        policy::ice_transport_policy::RTCIceTransportPolicy,
         sdp::session_description::RTCSessionDescription,
         RTCPeerConnection,
     },
@@ -50,22 +49,30 @@ pub struct RealPeerConnection {
 impl RealPeerConnection {
     /// 创建新的 PeerConnection
     pub async fn new(config: WebRTCConfig) -> Result<Self> {
-        // 构建 ICE 服务器配置 - 零第三方依赖
-        //
-        // sscontrol 2.0 使用纯 P2P 架构，无需 STUN/TURN 服务器
-        // 忽略 config 中的 stun_servers 和 turn_servers
-        // NAT 穿透通过主动探测和预测性打洞实现
-        let ice_servers = vec![]; // 零第三方依赖
+        // 构建 ICE 服务器配置 (与 HostSession 相同的转换规则)
+        let turn_servers: Vec<_> = config
+            .turn_servers
+            .iter()
+            .map(|server| crate::config::TurnServerConfig {
+                url: server.url.clone(),
+                username: server.username.clone(),
+                password: server.password.clone(),
+            })
+            .collect();
 
         // 创建 PeerConnection 配置
         let rtc_config = RTCConfiguration {
-            ice_servers,
+            ice_servers: super::host_session::ice_servers(&config.stun_servers, &turn_servers),
+            ice_transport_policy: match config.ice_transport_policy {
+                super::IceTransportPolicy::All => RTCIceTransportPolicy::All,
+                super::IceTransportPolicy::Relay => RTCIceTransportPolicy::Relay,
+            },
             ..Default::default()
         };
 
-        // 创建设置引擎 - 禁用 IPv6，只使用 IPv4 UDP
+        // 创建设置引擎 - IPv4/IPv6 双栈 UDP
         let mut setting_engine = SettingEngine::default();
-        setting_engine.set_network_types(vec![NetworkType::Udp4]);
+        super::host_session::configure_ice_network(&mut setting_engine);
 
         // 创建 API
         let api = APIBuilder::new()
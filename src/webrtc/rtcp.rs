@@ -0,0 +1,100 @@
+//! 接收端丢包反馈 (NACK)
+//!
+//! 丢失的 RTP 包由 webrtc-rs 默认注册的 NACK 应答拦截器从发送缓存中重传 (最近 8192 个包)。
+//! 重传本身也可能丢失: 同一个包被反复 NACK 说明重传无法修复，接收端会一直花屏到下一个 GOP，
+//! 此时 [`NackTracker`] 要求编码器立即输出关键帧
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// 同一个包被 NACK 的次数达到该值后改为请求关键帧
+const MAX_NACK_ATTEMPTS: u8 = 3;
+
+/// 超过该时间的 NACK 记录作废 (此后的 NACK 视为新的丢包)
+const NACK_WINDOW: Duration = Duration::from_secs(2);
+
+/// 按 (SSRC, 序号) 统计重复的 NACK
+#[derive(Debug, Default)]
+pub struct NackTracker {
+    /// (SSRC << 16 | 序号) -> (NACK 次数, 首次 NACK 时间)
+    attempts: HashMap<u64, (u8, Instant)>,
+    /// 累计被 NACK 的包数
+    nacked_packets: u64,
+}
+
+impl NackTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一个 NACK 报告，返回是否需要关键帧
+    pub fn record(&mut self, ssrc: u32, sequence_numbers: impl IntoIterator<Item = u16>) -> bool {
+        self.record_at(ssrc, sequence_numbers, Instant::now())
+    }
+
+    fn record_at(&mut self, ssrc: u32, sequence_numbers: impl IntoIterator<Item = u16>, now: Instant) -> bool {
+        self.attempts.retain(|_, (_, first)| now.duration_since(*first) < NACK_WINDOW);
+
+        let mut key_frame = false;
+        for seq in sequence_numbers {
+            self.nacked_packets += 1;
+            let entry = self.attempts.entry(u64::from(ssrc) << 16 | u64::from(seq)).or_insert((0, now));
+            entry.0 += 1;
+            key_frame |= entry.0 >= MAX_NACK_ATTEMPTS;
+        }
+        // 关键帧之后不再需要之前的包
+        if key_frame {
+            self.attempts.clear();
+        }
+        key_frame
+    }
+
+    /// 累计被 NACK 的包数
+    pub fn nacked_packets(&self) -> u64 {
+        self.nacked_packets
+    }
+}
+
+/// NACK 报告中请求重传的序号
+#[cfg(feature = "webrtc")]
+pub fn nack_sequence_numbers(
+    nack: &webrtc::rtcp::transport_feedbacks::transport_layer_nack::TransportLayerNack,
+) -> impl Iterator<Item = u16> + '_ {
+    nack.nacks.iter().flat_map(|pair| pair.packet_list())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeated_nack_requests_key_frame() {
+        let mut tracker = NackTracker::new();
+        let start = Instant::now();
+
+        // 首次丢包交给重传
+        assert!(!tracker.record_at(1, [100, 101], start));
+        assert!(!tracker.record_at(1, [101], start + Duration::from_millis(100)));
+        // 同一个包第三次 NACK: 重传也丢了
+        assert!(tracker.record_at(1, [101], start + Duration::from_millis(200)));
+        // 关键帧之后重新计数
+        assert!(!tracker.record_at(1, [101], start + Duration::from_millis(300)));
+        assert_eq!(tracker.nacked_packets(), 5);
+    }
+
+    #[test]
+    fn test_nacks_are_per_ssrc_and_expire() {
+        let mut tracker = NackTracker::new();
+        let start = Instant::now();
+
+        // simulcast 的各路编码序号独立
+        assert!(!tracker.record_at(1, [7], start));
+        assert!(!tracker.record_at(2, [7], start));
+        assert!(!tracker.record_at(1, [7], start));
+
+        // 超过窗口后的 NACK 是新的丢包
+        assert!(!tracker.record_at(1, [7], start + NACK_WINDOW));
+        assert!(!tracker.record_at(1, [7], start + NACK_WINDOW));
+        assert!(tracker.record_at(1, [7], start + NACK_WINDOW));
+    }
+}
@@ -2,15 +2,24 @@
 //!
 //! 用于连接信令服务器并与其他对等端交换 SDP 和 ICE 候选
 //!
+//! 服务器同意时较长的信令消息 (主要是 SDP) 压缩后发送 (见 `network::compression`)
+//!
 //! 注意: 此模块目前为客户端连接模式预留，暂未使用
 
 #![allow(dead_code)]
 
+use crate::nat::predictive_punching::PunchCandidate;
+use crate::network::compression;
+use crate::quality::profile::QualityProfile;
+use crate::signaling::{HostInfo, SessionPermissions};
 use anyhow::{anyhow, Result};
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
 use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 
 /// 信令消息类型
@@ -20,6 +29,23 @@ pub enum SignalMessage {
     /// 客户端加入
     #[serde(rename = "join")]
     Join { room_id: String },
+    /// 断线重连后加入房间并恢复之前的会话 (需出示会话令牌)
+    #[serde(rename = "resume")]
+    Resume {
+        room_id: String,
+        session_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+    },
+    /// 会话已恢复 (应在原 PeerConnection 上发起 ICE 重启)
+    #[serde(rename = "resumed")]
+    Resumed { session_id: String },
+    /// 请求换发会话令牌
+    #[serde(rename = "refresh_token")]
+    RefreshToken,
+    /// 新的会话令牌
+    #[serde(rename = "session_token")]
+    SessionToken { token: String },
     /// 房间内现有客户端列表
     #[serde(rename = "peers")]
     Peers { peers: Vec<PeerInfo> },
@@ -32,9 +58,17 @@ pub enum SignalMessage {
     /// SDP Offer
     #[serde(rename = "offer")]
     Offer { from: String, to: String, sdp: String },
-    /// SDP Answer
+    /// SDP Answer (Host 发出时携带会话 ID 和会话令牌)
     #[serde(rename = "answer")]
-    Answer { from: String, to: String, sdp: String },
+    Answer {
+        from: String,
+        to: String,
+        sdp: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        session_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        session_token: Option<String>,
+    },
     /// ICE Candidate
     #[serde(rename = "ice")]
     Ice {
@@ -44,6 +78,64 @@ pub enum SignalMessage {
         sdp_mid: String,
         sdp_mline_index: u16,
     },
+    /// 打洞信息 (candidate 为 None 表示对方不打洞)
+    #[serde(rename = "punch")]
+    Punch {
+        from: String,
+        to: String,
+        candidate: Option<PunchCandidate>,
+    },
+    /// 被控端环境信息 (时区/区域/当前时间)
+    #[serde(rename = "hello")]
+    Hello { host: HostInfo },
+    /// 切换被控端画面中是否包含鼠标指针
+    #[serde(rename = "cursor")]
+    Cursor { show: bool },
+    /// 切换被控端的画质预设
+    #[serde(rename = "profile")]
+    Profile { profile: QualityProfile },
+    /// 被控端要求输入 PIN
+    #[serde(rename = "pin_required")]
+    PinRequired,
+    /// 提交被控端控制台显示的 PIN
+    #[serde(rename = "pin")]
+    Pin { pin: String },
+    /// PIN 验证结果
+    #[serde(rename = "pin_result")]
+    PinResult {
+        accepted: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        remaining_attempts: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        retry_after_secs: Option<u64>,
+    },
+    /// 被控端要求 TOTP 双因素验证
+    #[serde(rename = "totp_required")]
+    TotpRequired,
+    /// 提交身份验证器 App 中的 6 位验证码
+    #[serde(rename = "totp")]
+    Totp { code: String },
+    /// TOTP 验证结果
+    #[serde(rename = "totp_result")]
+    TotpResult {
+        accepted: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        remaining_attempts: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        retry_after_secs: Option<u64>,
+    },
+    /// 本会话的权限 (放行时及被控端调整权限时下发)
+    #[serde(rename = "permissions")]
+    Permissions { permissions: SessionPermissions },
+    /// 请求 HTTP 接口 (`/sysinfo` 等) 使用的一次性票据 (需已通过验证)
+    #[serde(rename = "request_ticket")]
+    RequestTicket,
+    /// 一次性票据 (绑定本连接和当前会话，信令连接断开后失效)
+    #[serde(rename = "ticket")]
+    Ticket { ticket: String, peer_id: String },
+    /// 被控端会话已满，排队中
+    #[serde(rename = "queued")]
+    Queued { position: usize, queue_length: usize },
     /// 错误
     #[serde(rename = "error")]
     Error { message: String },
@@ -68,8 +160,13 @@ pub enum SignalingEvent {
     PeerLeft { peer_id: String },
     /// 收到 Offer
     Offer { from: String, sdp: String },
-    /// 收到 Answer
-    Answer { from: String, sdp: String },
+    /// 收到 Answer (session_token 用于断线后恢复会话)
+    Answer {
+        from: String,
+        sdp: String,
+        session_id: Option<String>,
+        session_token: Option<String>,
+    },
     /// 收到 ICE 候选
     Ice {
         from: String,
@@ -77,6 +174,34 @@ pub enum SignalingEvent {
         sdp_mid: String,
         sdp_mline_index: u16,
     },
+    /// 收到打洞信息
+    Punch { from: String, candidate: Option<PunchCandidate> },
+    /// 收到被控端环境信息
+    Hello { host: HostInfo },
+    /// 排队等待会话名额 (位置从 1 开始)
+    Queued { position: usize, queue_length: usize },
+    /// 被控端要求输入 PIN 后才能发起 Offer
+    PinRequired,
+    /// PIN 验证结果 (锁定时 retry_after_secs 为剩余锁定时间)
+    PinResult {
+        accepted: bool,
+        remaining_attempts: Option<u32>,
+        retry_after_secs: Option<u64>,
+    },
+    /// 被控端要求提交 TOTP 验证码后才能发起 Offer
+    TotpRequired,
+    /// TOTP 验证结果 (字段含义同 PinResult)
+    TotpResult {
+        accepted: bool,
+        remaining_attempts: Option<u32>,
+        retry_after_secs: Option<u64>,
+    },
+    /// 断线重连后会话已恢复
+    Resumed { session_id: String },
+    /// 换发的会话令牌 (替换之前 Answer 中的令牌)
+    SessionToken { token: String },
+    /// 会话权限更新 (仅查看时被控端会丢弃输入等数据)
+    Permissions { permissions: SessionPermissions },
     /// 错误
     Error { message: String },
     /// 断开连接
@@ -86,15 +211,22 @@ pub enum SignalingEvent {
 /// 事件处理器类型
 pub type EventHandler = Arc<Mutex<Option<Box<dyn Fn(SignalingEvent) + Send + 'static>>>>;
 
+/// WebSocket 发送端类型
+type WsSink = futures_util::stream::SplitSink<
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
+    Message,
+>;
+
 /// 信令客户端
 pub struct SignalingClient {
     url: String,
-    sender: Arc<Mutex<Option<futures_util::stream::SplitSink<
-        tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
-        Message,
-    >>>>,
+    sender: Arc<Mutex<Option<WsSink>>>,
     event_handler: EventHandler,
     peer_id: Arc<Mutex<Option<String>>>,
+    /// 握手时提出压缩文本消息
+    compression: bool,
+    /// 服务器已同意压缩
+    compressed: Arc<AtomicBool>,
 }
 
 impl SignalingClient {
@@ -105,9 +237,17 @@ impl SignalingClient {
             sender: Arc::new(Mutex::new(None)),
             event_handler: Arc::new(Mutex::new(None)),
             peer_id: Arc::new(Mutex::new(None)),
+            compression: true,
+            compressed: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// 设置是否提出压缩文本消息 (默认提出，服务器同意后才生效)
+    pub fn with_compression(mut self, enabled: bool) -> Self {
+        self.compression = enabled;
+        self
+    }
+
     /// 设置事件处理器
     pub async fn on_event<F>(&self, handler: F)
     where
@@ -121,9 +261,18 @@ impl SignalingClient {
         let url = self.url.clone();
         tracing::info!("连接到信令服务器: {}", url);
 
-        let (ws_stream, _) = connect_async(&url)
+        let mut request = url.as_str().into_client_request().map_err(|e| anyhow!("无效的信令地址 {}: {}", url, e))?;
+        if self.compression {
+            request
+                .headers_mut()
+                .insert(compression::HEADER, HeaderValue::from_static(compression::DEFLATE));
+        }
+        let (ws_stream, response) = connect_async(request)
             .await
             .map_err(|e| anyhow!("连接失败: {}", e))?;
+        let compressed =
+            compression::accepts(response.headers().get(compression::HEADER).and_then(|value| value.to_str().ok()));
+        self.compressed.store(compressed, Ordering::Relaxed);
 
         let (sender, mut receiver) = ws_stream.split();
         *self.sender.lock().await = Some(sender);
@@ -135,6 +284,18 @@ impl SignalingClient {
         let event_handler = self.event_handler.clone();
         tokio::spawn(async move {
             while let Some(msg) = receiver.next().await {
+                let msg = match msg {
+                    Ok(Message::Binary(data)) if compressed && compression::is_compressed(&data) => {
+                        match compression::decompress(&data) {
+                            Ok(text) => Ok(Message::Text(text)),
+                            Err(e) => {
+                                tracing::warn!("丢弃无法解压的信令消息: {}", e);
+                                continue;
+                            }
+                        }
+                    }
+                    msg => msg,
+                };
                 match msg {
                     Ok(Message::Text(text)) => {
                         if let Ok(signal) = serde_json::from_str::<SignalMessage>(&text) {
@@ -152,8 +313,8 @@ impl SignalingClient {
                                 SignalMessage::Offer { from, sdp, .. } => {
                                     SignalingEvent::Offer { from, sdp }
                                 }
-                                SignalMessage::Answer { from, sdp, .. } => {
-                                    SignalingEvent::Answer { from, sdp }
+                                SignalMessage::Answer { from, sdp, session_id, session_token, .. } => {
+                                    SignalingEvent::Answer { from, sdp, session_id, session_token }
                                 }
                                 SignalMessage::Ice {
                                     from,
@@ -167,6 +328,30 @@ impl SignalingClient {
                                     sdp_mid,
                                     sdp_mline_index,
                                 },
+                                SignalMessage::Punch { from, candidate, .. } => {
+                                    SignalingEvent::Punch { from, candidate }
+                                }
+                                SignalMessage::Hello { host } => SignalingEvent::Hello { host },
+                                SignalMessage::Queued { position, queue_length } => {
+                                    SignalingEvent::Queued { position, queue_length }
+                                }
+                                SignalMessage::PinRequired => SignalingEvent::PinRequired,
+                                SignalMessage::PinResult { accepted, remaining_attempts, retry_after_secs } => {
+                                    SignalingEvent::PinResult { accepted, remaining_attempts, retry_after_secs }
+                                }
+                                SignalMessage::TotpRequired => SignalingEvent::TotpRequired,
+                                SignalMessage::TotpResult { accepted, remaining_attempts, retry_after_secs } => {
+                                    SignalingEvent::TotpResult { accepted, remaining_attempts, retry_after_secs }
+                                }
+                                SignalMessage::Resumed { session_id } => {
+                                    SignalingEvent::Resumed { session_id }
+                                }
+                                SignalMessage::SessionToken { token } => {
+                                    SignalingEvent::SessionToken { token }
+                                }
+                                SignalMessage::Permissions { permissions } => {
+                                    SignalingEvent::Permissions { permissions }
+                                }
                                 SignalMessage::Error { message } => {
                                     SignalingEvent::Error { message }
                                 }
@@ -178,12 +363,7 @@ impl SignalingClient {
                             }
                         }
                     }
-                    Ok(Message::Close(_)) => {
-                        if let Some(handler) = event_handler.lock().await.as_ref() {
-                            handler(SignalingEvent::Disconnected);
-                        }
-                        break;
-                    }
+                    Ok(Message::Close(_)) => break,
                     Err(e) => {
                         tracing::error!("接收错误: {}", e);
                         if let Some(handler) = event_handler.lock().await.as_ref() {
@@ -196,6 +376,11 @@ impl SignalingClient {
                     _ => {}
                 }
             }
+
+            // 正常关闭、出错或连接被重置都视为断开
+            if let Some(handler) = event_handler.lock().await.as_ref() {
+                handler(SignalingEvent::Disconnected);
+            }
         });
 
         Ok(())
@@ -207,6 +392,16 @@ impl SignalingClient {
         self.send(msg).await
     }
 
+    /// 断线重连后加入房间并恢复之前的会话 (成功时收到 Resumed 事件)
+    pub async fn resume_room(&self, room_id: String, session_id: String, token: Option<String>) -> Result<()> {
+        self.send(SignalMessage::Resume { room_id, session_id, token }).await
+    }
+
+    /// 在会话令牌过期前请求换发 (收到 SessionToken 事件)
+    pub async fn refresh_token(&self) -> Result<()> {
+        self.send(SignalMessage::RefreshToken).await
+    }
+
     /// 发送 Offer
     pub async fn send_offer(&self, to: String, sdp: String) -> Result<()> {
         let msg = SignalMessage::Offer { from: self.get_peer_id().await, to, sdp };
@@ -215,7 +410,7 @@ impl SignalingClient {
 
     /// 发送 Answer
     pub async fn send_answer(&self, to: String, sdp: String) -> Result<()> {
-        let msg = SignalMessage::Answer { from: self.get_peer_id().await, to, sdp };
+        let msg = SignalMessage::Answer { from: self.get_peer_id().await, to, sdp, session_id: None, session_token: None };
         self.send(msg).await
     }
 
@@ -237,13 +432,43 @@ impl SignalingClient {
         self.send(msg).await
     }
 
+    /// 发送打洞信息
+    pub async fn send_punch(&self, to: String, candidate: Option<PunchCandidate>) -> Result<()> {
+        let msg = SignalMessage::Punch { from: self.get_peer_id().await, to, candidate };
+        self.send(msg).await
+    }
+
+    /// 切换被控端画面中是否包含鼠标指针
+    pub async fn send_cursor(&self, show: bool) -> Result<()> {
+        self.send(SignalMessage::Cursor { show }).await
+    }
+
+    /// 切换被控端的画质预设
+    pub async fn send_profile(&self, profile: QualityProfile) -> Result<()> {
+        self.send(SignalMessage::Profile { profile }).await
+    }
+
+    /// 提交被控端显示的 PIN
+    pub async fn send_pin(&self, pin: &str) -> Result<()> {
+        self.send(SignalMessage::Pin { pin: pin.to_string() }).await
+    }
+
+    /// 提交身份验证器 App 中的 TOTP 验证码
+    pub async fn send_totp(&self, code: &str) -> Result<()> {
+        self.send(SignalMessage::Totp { code: code.to_string() }).await
+    }
+
     /// 发送消息
     async fn send(&self, msg: SignalMessage) -> Result<()> {
         let json = serde_json::to_string(&msg)?;
         let mut sender = self.sender.lock().await;
+        let message = match self.compressed.load(Ordering::Relaxed).then(|| compression::compress(&json)).flatten() {
+            Some(data) => Message::Binary(data),
+            None => Message::Text(json),
+        };
         if let Some(sender) = sender.as_mut() {
             sender
-                .send(Message::Text(json))
+                .send(message)
                 .await
                 .map_err(|e| anyhow!("发送失败: {}", e))?;
         } else {
@@ -0,0 +1,160 @@
+//! rid 多编码 (simulcast) 协商和接收端反馈
+//!
+//! Viewer 在 Offer 的视频 m 段中声明可以接收的编码 (RFC 8851 / RFC 8853):
+//!
+//! ```text
+//! a=rid:h recv
+//! a=rid:m recv
+//! a=rid:l recv
+//! a=simulcast:recv h;m;l
+//! ```
+//!
+//! `a=simulcast` 中的顺序即档位顺序 (从高到低)。被控端开启 simulcast 时为每个 rid 创建一路编码
+//! (同一个 RTCRtpSender 上的多个 encoding)，Answer 中对应 `a=rid:<id> send` 和 `a=simulcast:send`。
+//! 每个观看者同一时间只转发其中一路 (其余 encoding 暂停)，观看端按 rid/SSRC 区分档位，
+//! 切换档位时不再在同一个 SSRC 上改变分辨率。
+//!
+//! 档位按接收端的 REMB (估计的最大码率) 和 TWCC (逐包到达情况) 反馈切换，见 [`RtcpFeedback`]
+
+/// Offer 中请求接收的多编码
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimulcastOffer {
+    /// 各档对应的 rid (从高到低)
+    pub rids: Vec<String>,
+}
+
+impl SimulcastOffer {
+    /// 档位对应的 rid
+    pub fn rid_of(&self, tier: usize) -> Option<&str> {
+        self.rids.get(tier).map(String::as_str)
+    }
+}
+
+/// 解析 Offer 中视频 m 段的 simulcast 接收声明，最多取 `max_encodings` 路
+///
+/// 没有声明或可用的 rid 少于两个时返回 None (按单路编码协商)
+pub fn parse_offer(sdp: &str, max_encodings: usize) -> Option<SimulcastOffer> {
+    let mut in_video = false;
+    let mut declared = Vec::new();
+    let mut order = Vec::new();
+
+    for line in sdp.lines().map(str::trim) {
+        if let Some(media) = line.strip_prefix("m=") {
+            // 只看第一个视频 m 段
+            if in_video {
+                break;
+            }
+            in_video = media.starts_with("video");
+            continue;
+        }
+        if !in_video {
+            continue;
+        }
+        if let Some(rid) = line.strip_prefix("a=rid:") {
+            let mut fields = rid.split_whitespace();
+            if let (Some(id), Some("recv")) = (fields.next(), fields.next()) {
+                declared.push(id.to_string());
+            }
+        } else if let Some(streams) = line.strip_prefix("a=simulcast:recv ") {
+            // "h;m;l"，每路可以有逗号分隔的备选 rid，"~" 前缀表示初始暂停
+            order = streams
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .split(';')
+                .filter_map(|stream| stream.split(',').next())
+                .map(|rid| rid.trim_start_matches('~').to_string())
+                .collect();
+        }
+    }
+
+    let rids: Vec<String> = order
+        .into_iter()
+        .filter(|rid| declared.contains(rid))
+        .take(max_encodings)
+        .collect();
+    (rids.len() >= 2).then_some(SimulcastOffer { rids })
+}
+
+/// 接收端 RTCP 反馈的累计
+///
+/// 由读取 RTCP 的任务写入，每次采集传输统计时取出上次以来的结果
+#[derive(Debug, Default)]
+pub struct RtcpFeedback {
+    /// 最近一次 REMB 的码率 (bps)
+    remb_bps: Option<f64>,
+    /// TWCC 报告到达的包数
+    twcc_received: u64,
+    /// TWCC 报告未到达的包数
+    twcc_lost: u64,
+}
+
+impl RtcpFeedback {
+    pub fn record_remb(&mut self, bitrate_bps: f64) {
+        if bitrate_bps > 0.0 {
+            self.remb_bps = Some(bitrate_bps);
+        }
+    }
+
+    pub fn record_twcc(&mut self, received: u64, lost: u64) {
+        self.twcc_received += received;
+        self.twcc_lost += lost;
+    }
+
+    /// 取出上次以来的反馈: (REMB 带宽 Mbps, TWCC 丢包比例)
+    ///
+    /// REMB 是接收端的持续估计，保留到下一次报告；TWCC 计数清零
+    pub fn take(&mut self) -> (Option<f64>, Option<f64>) {
+        let remb_mbps = self.remb_bps.map(|bps| bps / 1_000_000.0);
+        let total = self.twcc_received + self.twcc_lost;
+        let loss = (total > 0).then(|| self.twcc_lost as f64 / total as f64);
+        self.twcc_received = 0;
+        self.twcc_lost = 0;
+        (remb_mbps, loss)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFFER: &str = "v=0\r\n\
+        m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+        a=rid:x recv\r\n\
+        a=simulcast:recv x;y\r\n\
+        m=video 9 UDP/TLS/RTP/SAVPF 96\r\n\
+        a=mid:1\r\n\
+        a=rid:h recv\r\n\
+        a=rid:m recv\r\n\
+        a=rid:l recv\r\n\
+        a=simulcast:recv h;~m,m2;l\r\n\
+        m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n";
+
+    #[test]
+    fn test_parse_offer() {
+        let offer = parse_offer(OFFER, 3).unwrap();
+        assert_eq!(offer.rids, vec!["h", "m", "l"]);
+        assert_eq!(offer.rid_of(2), Some("l"));
+        assert_eq!(offer.rid_of(3), None);
+
+        assert_eq!(parse_offer(OFFER, 2).unwrap().rids, vec!["h", "m"]);
+        // 单路编码
+        assert_eq!(parse_offer(OFFER, 1), None);
+        assert_eq!(parse_offer("v=0\r\nm=video 9 UDP/TLS/RTP/SAVPF 96\r\na=mid:0\r\n", 3), None);
+        // 发送方向的 rid 不算
+        assert_eq!(parse_offer("m=video 9 RTP 96\r\na=rid:h send\r\na=rid:l send\r\na=simulcast:recv h;l\r\n", 3), None);
+    }
+
+    #[test]
+    fn test_rtcp_feedback() {
+        let mut feedback = RtcpFeedback::default();
+        assert_eq!(feedback.take(), (None, None));
+
+        feedback.record_remb(2_500_000.0);
+        feedback.record_twcc(90, 5);
+        feedback.record_twcc(0, 5);
+        assert_eq!(feedback.take(), (Some(2.5), Some(0.1)));
+        // REMB 保留，TWCC 清零
+        assert_eq!(feedback.take(), (Some(2.5), None));
+    }
+}